@@ -0,0 +1,31 @@
+"""Split one fuzz input into the two files a comparison target compares."""
+
+from __future__ import annotations
+
+import os
+import tempfile
+
+
+def split_pair(data: bytes) -> tuple[bytes, bytes]:
+    """The two sides of *data*: a 2-byte little-endian length of the first, then both."""
+    if len(data) < 2:
+        return data, data
+    length = min(int.from_bytes(data[:2], "little"), len(data) - 2)
+    return data[2:2 + length], data[2 + length:]
+
+
+def write_pair(directory: str, data: bytes) -> tuple[str, str, str]:
+    """Write both sides of *data*, and a copy of the first, into *directory*."""
+    a, b = split_pair(data)
+    paths = []
+    for name, content in (("a", a), ("b", b), ("a.copy", a)):
+        path = os.path.join(directory, name)
+        with open(path, "wb") as f:
+            f.write(content)
+        paths.append(path)
+    return paths[0], paths[1], paths[2]
+
+
+def scratch() -> tempfile.TemporaryDirectory[str]:
+    """A directory for the files of one input; in memory where /dev/shm exists."""
+    return tempfile.TemporaryDirectory(dir="/dev/shm" if os.path.isdir("/dev/shm") else None)
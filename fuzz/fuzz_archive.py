@@ -0,0 +1,47 @@
+"""Atheris target for compare_archive(), the libarchive entry walk.
+
+The input is split into two files (see _pair.py). Unreadable archives
+must raise ArchiveError, bombs included, and never crash the C reader;
+an archive that opens must equal a copy of itself, and the answer must
+not depend on the order of the two files or on hash_compare.
+
+Build the extension with -DKOMPARU_SANITIZER=address to catch memory
+errors in reader_archive.c.
+
+    python fuzz/fuzz_archive.py fuzz/corpus/archive
+"""
+
+from __future__ import annotations
+
+import sys
+
+import atheris
+
+with atheris.instrument_imports():
+    from komparu import ArchiveError, compare_archive
+    from _pair import scratch, write_pair
+
+# Small limits, so bombs in the corpus trip them instead of filling memory
+LIMITS = {"max_decompressed_size": 1 << 22, "max_archive_entries": 1000}
+
+
+def _equal(path_a: str, path_b: str, hash_compare: bool) -> bool | None:
+    try:
+        return compare_archive(path_a, path_b, hash_compare=hash_compare, **LIMITS).equal
+    except ArchiveError:
+        return None
+
+
+def test_one_input(data: bytes) -> None:
+    with scratch() as directory:
+        a, b, copy = write_pair(directory, data)
+        answers = {_equal(a, b, False), _equal(b, a, False), _equal(a, b, True)}
+        if True in answers and False in answers:
+            raise AssertionError(f"answers differ: {answers}")
+        if _equal(a, copy, True) is False:
+            raise AssertionError("archive differs from its copy")
+
+
+if __name__ == "__main__":
+    atheris.Setup(sys.argv, test_one_input)
+    atheris.Fuzz()
@@ -0,0 +1,65 @@
+"""Atheris target for the content-aware comparators.
+
+The input is split into two files (see _pair.py) that each comparator in
+TARGETS compares both ways round. Malformed files may raise one of
+MALFORMED; anything else escaping, or an answer that changes with the
+order of the files, is a bug. (Reflexivity is not checked: the JSON and
+XML comparators call a file they cannot parse different from itself.)
+
+    python fuzz/fuzz_comparators.py fuzz/corpus/comparators
+"""
+
+from __future__ import annotations
+
+import lzma
+import sqlite3
+import struct
+import sys
+import tarfile
+import zipfile
+import zlib
+from xml.etree import ElementTree
+
+import atheris
+
+with atheris.instrument_imports():
+    from komparu import (
+        DecompressComparator,
+        JsonComparator,
+        KomparuError,
+        SniffingComparator,
+        TarComparator,
+        ZipComparator,
+    )
+    from _pair import scratch, write_pair
+
+# Errors a comparator may raise for a file it cannot parse
+MALFORMED = (
+    ValueError, OSError, EOFError, KomparuError, tarfile.TarError, zipfile.BadZipFile,
+    zlib.error, lzma.LZMAError, struct.error, ElementTree.ParseError, sqlite3.DatabaseError,
+    RecursionError,
+)
+
+TARGETS = (SniffingComparator(chunk_size=512), DecompressComparator(chunk_size=512),
+           TarComparator(chunk_size=512), ZipComparator(chunk_size=512), JsonComparator())
+
+
+def _compare(comparator, path_a: str, path_b: str) -> bool | None:
+    try:
+        return comparator.compare(path_a, path_b)
+    except MALFORMED:
+        return None
+
+
+def test_one_input(data: bytes) -> None:
+    with scratch() as directory:
+        a, b, _ = write_pair(directory, data)
+        for comparator in TARGETS:
+            forward, backward = _compare(comparator, a, b), _compare(comparator, b, a)
+            if forward is not None and backward is not None and forward != backward:
+                raise AssertionError(f"{type(comparator).__name__} is not symmetric")
+
+
+if __name__ == "__main__":
+    atheris.Setup(sys.argv, test_one_input)
+    atheris.Fuzz()
@@ -0,0 +1,118 @@
+/**
+ * fuzz_compare.c — libFuzzer target for the chunked compare loop.
+ *
+ * Input layout:
+ *   byte 0     chunk size selector (1..256 bytes, or 64 KiB when 0)
+ *   byte 1     flags: bit 0 size pre-check, bit 1 sizes unknown,
+ *              bit 2 quick check first
+ *   bytes 2-3  short-read steps of side a and side b (0 = full reads)
+ *   bytes 4-5  length of side a, little-endian; the rest is side b
+ *
+ * Each side is an in-memory reader that hands out at most `step` bytes
+ * per read, cycling through step, step/2 and 1 byte, so refills of short
+ * reads are exercised at every chunk boundary. The result must match
+ * memcmp over the two buffers: EQUAL exactly when the bytes are the same,
+ * and the quick check may only report DIFFERENT for buffers that differ.
+ *
+ * Build: cmake -DKOMPARU_BUILD_FUZZERS=ON -DCMAKE_C_COMPILER=clang
+ */
+
+#include "compare.h"
+#include <stdlib.h>
+#include <string.h>
+
+typedef struct {
+    const uint8_t *data;
+    size_t len;
+    size_t pos;
+    size_t step;
+    unsigned turn;
+    bool unknown_size;
+} mem_ctx_t;
+
+static int64_t mem_read(komparu_reader_t *self, void *buf, size_t size) {
+    mem_ctx_t *ctx = self->ctx;
+    size_t left = ctx->len - ctx->pos;
+    size_t n = size < left ? size : left;
+    if (ctx->step > 0 && n > 0) {
+        size_t cap = ctx->turn % 3 == 0 ? ctx->step
+                   : ctx->turn % 3 == 1 ? (ctx->step + 1) / 2 : 1;
+        ctx->turn++;
+        if (n > cap)
+            n = cap;
+    }
+    memcpy(buf, ctx->data + ctx->pos, n);
+    ctx->pos += n;
+    return (int64_t)n;
+}
+
+static int64_t mem_get_size(komparu_reader_t *self) {
+    mem_ctx_t *ctx = self->ctx;
+    return ctx->unknown_size ? -1 : (int64_t)ctx->len;
+}
+
+static int mem_seek(komparu_reader_t *self, int64_t offset) {
+    mem_ctx_t *ctx = self->ctx;
+    if (offset < 0 || (uint64_t)offset > ctx->len)
+        return -1;
+    ctx->pos = (size_t)offset;
+    return 0;
+}
+
+static void mem_close(komparu_reader_t *self) {
+    (void)self;
+}
+
+static void mem_reader(komparu_reader_t *reader, mem_ctx_t *ctx,
+                       const uint8_t *data, size_t len, uint8_t step, bool unknown) {
+    *ctx = (mem_ctx_t){.data = data, .len = len, .step = step, .unknown_size = unknown};
+    *reader = (komparu_reader_t){
+        .read = mem_read,
+        .get_size = mem_get_size,
+        .seek = mem_seek,
+        .close = mem_close,
+        .ctx = ctx,
+        .source_name = "fuzz",
+    };
+}
+
+int LLVMFuzzerTestOneInput(const uint8_t *data, size_t size) {
+    if (size < 6)
+        return 0;
+    size_t chunk_size = data[0] ? data[0] : 65536;
+    bool precheck = data[1] & 1;
+    bool unknown = data[1] & 2;
+    bool quick = data[1] & 4;
+    size_t len_a = (size_t)data[4] | (size_t)data[5] << 8;
+    const uint8_t *rest = data + 6;
+    size -= 6;
+    if (len_a > size)
+        len_a = size;
+    const uint8_t *a = rest, *b = rest + len_a;
+    size_t len_b = size - len_a;
+    bool same = len_a == len_b && memcmp(a, b, len_a) == 0;
+
+    komparu_reader_t ra, rb;
+    mem_ctx_t ca, cb;
+    const char *err = NULL;
+    if (quick) {
+        mem_reader(&ra, &ca, a, len_a, data[2], unknown);
+        mem_reader(&rb, &cb, b, len_b, data[3], unknown);
+        komparu_result_t q = komparu_quick_check(&ra, &rb, chunk_size, &err);
+        if (q == KOMPARU_DIFFERENT && same)
+            abort();
+    }
+    mem_reader(&ra, &ca, a, len_a, data[2], unknown);
+    mem_reader(&rb, &cb, b, len_b, data[3], unknown);
+    komparu_result_t r = komparu_compare_ex(&ra, &rb, chunk_size, precheck, NULL, &err);
+    if (r == KOMPARU_ERROR || (r == KOMPARU_EQUAL) != same)
+        abort();
+
+    int64_t first = komparu_mem_first_diff(a, len_a, b, len_b);
+    if ((first < 0) != same)
+        abort();
+    if (first >= 0 && (size_t)first < len_a && (size_t)first < len_b
+        && (a[first] == b[first] || memcmp(a, b, (size_t)first) != 0))
+        abort();
+    return 0;
+}
@@ -20,6 +20,7 @@
 #include <string.h>
 #include <errno.h>
 #include <stdatomic.h>
+#include <time.h>
 
 #ifdef KOMPARU_LINUX
 #include <sys/eventfd.h>
@@ -34,6 +35,7 @@ typedef enum {
     KOMPARU_ASYNC_COMPARE_DIR,
     KOMPARU_ASYNC_COMPARE_ARCHIVE,
     KOMPARU_ASYNC_COMPARE_DIR_URLS,
+    KOMPARU_ASYNC_COMPARE_DIR_STREAM,
 } komparu_async_type_t;
 
 /* Task lifecycle states (CAS transitions only):
@@ -46,6 +48,50 @@ typedef enum {
     KOMPARU_TASK_ORPHANED = 2,
 } komparu_task_state_t;
 
+/* Event queue lock (dir stream tasks) */
+#ifdef KOMPARU_WINDOWS
+typedef SRWLOCK komparu_event_lock_t;
+#define EVENT_LOCK_INIT(l)    InitializeSRWLock(l)
+#define EVENT_LOCK(l)         AcquireSRWLockExclusive(l)
+#define EVENT_UNLOCK(l)       ReleaseSRWLockExclusive(l)
+#define EVENT_LOCK_DESTROY(l) ((void)(l))
+#else
+typedef pthread_mutex_t komparu_event_lock_t;
+#define EVENT_LOCK_INIT(l)    pthread_mutex_init(l, NULL)
+#define EVENT_LOCK(l)         pthread_mutex_lock(l)
+#define EVENT_UNLOCK(l)       pthread_mutex_unlock(l)
+#define EVENT_LOCK_DESTROY(l) pthread_mutex_destroy(l)
+#endif
+
+/* Queue-space condition (bounded dir streams). Producers wait in slices
+ * so cancellation and an orphaned task are noticed without a wakeup. */
+#define EVENT_WAIT_SLICE_MS 50
+
+#ifdef KOMPARU_WINDOWS
+typedef CONDITION_VARIABLE komparu_event_cond_t;
+#define EVENT_COND_INIT(c)      InitializeConditionVariable(c)
+#define EVENT_COND_BROADCAST(c) WakeAllConditionVariable(c)
+#define EVENT_COND_DESTROY(c)   ((void)(c))
+static void event_cond_wait_slice(komparu_event_cond_t *c, komparu_event_lock_t *l) {
+    SleepConditionVariableSRW(c, l, EVENT_WAIT_SLICE_MS, 0);
+}
+#else
+typedef pthread_cond_t komparu_event_cond_t;
+#define EVENT_COND_INIT(c)      pthread_cond_init(c, NULL)
+#define EVENT_COND_BROADCAST(c) pthread_cond_broadcast(c)
+#define EVENT_COND_DESTROY(c)   pthread_cond_destroy(c)
+static void event_cond_wait_slice(komparu_event_cond_t *c, komparu_event_lock_t *l) {
+    struct timespec deadline;
+    clock_gettime(CLOCK_REALTIME, &deadline);
+    deadline.tv_nsec += EVENT_WAIT_SLICE_MS * 1000000L;
+    if (deadline.tv_nsec >= 1000000000L) {
+        deadline.tv_sec++;
+        deadline.tv_nsec -= 1000000000L;
+    }
+    (void)pthread_cond_timedwait(c, l, &deadline);
+}
+#endif
+
 /* =========================================================================
  * Task structure
  * ========================================================================= */
@@ -73,9 +119,18 @@ struct komparu_async_task {
     bool allow_private;
     char *proxy;             /* Owned copy, or NULL */
 
+    /* Cancellation token (own reference), or NULL */
+    komparu_cancel_t *cancel;
+
     /* Dir-specific */
     bool follow_symlinks;
     size_t max_workers;
+    komparu_retry_t retry;   /* retries == 0: off */
+    komparu_lock_t lock;     /* used when locking is set */
+    bool locking;
+    komparu_dir_cache_t *cache;  /* own reference, or NULL */
+    int dirfd_a;             /* Owned duplicate, or AT_FDCWD (by path) */
+    int dirfd_b;
 
     /* Archive-specific */
     int64_t max_decompressed_size;
@@ -89,10 +144,23 @@ struct komparu_async_task {
     char **url_urls;        /* owned copies */
     size_t url_count;
 
+    /* Dir_stream-specific: entries produced by the worker, not yet taken */
+    bool progress;
+    size_t max_pending;      /* queue bound; 0 = unbounded */
+    komparu_event_lock_t event_lock;
+    komparu_event_cond_t event_space;  /* signaled when the queue is taken */
+    bool event_lock_init;
+    komparu_dir_event_t *events;
+    size_t event_count;
+    size_t event_capacity;
+    bool event_oom;
+
     /* Output */
     komparu_result_t cmp_result;
     komparu_dir_result_t *dir_result;
     char error_buf[512];
+    int error_code;          /* errno captured at failure, see compat.h */
+    const char *error_path;  /* points at source_a/source_b, or NULL */
     bool has_error;
 
     /* Lifecycle state (CAS-only transitions, see komparu_task_state_t) */
@@ -171,6 +239,11 @@ static void notify_signal(int write_fd) {
     send((SOCKET)(intptr_t)write_fd, &c, 1, 0);
 }
 
+static void notify_drain(int read_fd) {
+    char buf[64];
+    while (recv((SOCKET)(intptr_t)read_fd, buf, sizeof(buf), 0) > 0);
+}
+
 static void notify_close(int read_fd, int write_fd) {
     if (read_fd >= 0) closesocket((SOCKET)(intptr_t)read_fd);
     if (write_fd >= 0 && write_fd != read_fd)
@@ -226,6 +299,16 @@ static void notify_signal(int write_fd) {
 #endif
 }
 
+/* Consume pending notifications so the fd stops polling readable.
+ * Both eventfd and pipe read ends are non-blocking. */
+static void notify_drain(int read_fd) {
+    char buf[64];
+    ssize_t n;
+    do {
+        n = read(read_fd, buf, sizeof(buf));
+    } while (n > 0 || (n < 0 && errno == EINTR));
+}
+
 static void notify_close(int read_fd, int write_fd) {
     if (read_fd >= 0) close(read_fd);
     if (write_fd >= 0 && write_fd != read_fd) close(write_fd);
@@ -326,9 +409,10 @@ static void compare_worker(void *arg) {
         ra = komparu_reader_file_open(task->source_a, &err);
     }
     if (KOMPARU_UNLIKELY(!ra)) {
+        task->error_code = errno;
+        task->error_path = task->source_a;
         snprintf(task->error_buf, sizeof(task->error_buf),
-                 "cannot open '%s': %s", task->source_a,
-                 err ? err : "unknown error");
+                 "cannot open: %s", err ? err : "unknown error");
         task->has_error = true;
         worker_finish(task);
         return;
@@ -347,10 +431,11 @@ static void compare_worker(void *arg) {
         rb = komparu_reader_file_open(task->source_b, &err);
     }
     if (KOMPARU_UNLIKELY(!rb)) {
+        task->error_code = errno;
+        task->error_path = task->source_b;
         ra->close(ra);
         snprintf(task->error_buf, sizeof(task->error_buf),
-                 "cannot open '%s': %s", task->source_b,
-                 err ? err : "unknown error");
+                 "cannot open: %s", err ? err : "unknown error");
         task->has_error = true;
         worker_finish(task);
         return;
@@ -374,10 +459,11 @@ static void compare_worker(void *arg) {
     }
 
     /* Full compare */
-    task->cmp_result = komparu_compare(
-        ra, rb, task->chunk_size, task->size_precheck, &err);
+    task->cmp_result = komparu_compare_ex(
+        ra, rb, task->chunk_size, task->size_precheck, task->cancel, &err);
 
     if (task->cmp_result == KOMPARU_ERROR) {
+        task->error_code = errno;
         snprintf(task->error_buf, sizeof(task->error_buf),
                  "comparison error: %s", err ? err : "unknown");
         task->has_error = true;
@@ -396,13 +482,16 @@ static void compare_dir_worker(void *arg) {
     komparu_async_task_t *task = (komparu_async_task_t *)arg;
     const char *err = NULL;
 
-    task->dir_result = komparu_compare_dirs(
-        task->source_a, task->source_b,
+    task->dir_result = komparu_compare_dirs_at(
+        task->dirfd_a, task->source_a, task->dirfd_b, task->source_b,
         task->chunk_size, task->size_precheck,
         task->quick_check, task->follow_symlinks,
-        task->max_workers, &err);
+        task->max_workers, NULL, task->cancel,
+        task->retry.retries ? &task->retry : NULL,
+        task->locking ? &task->lock : NULL, task->cache, &err);
 
     if (!task->dir_result) {
+        task->error_code = errno;
         snprintf(task->error_buf, sizeof(task->error_buf),
                  "directory comparison failed: %s",
                  err ? err : "unknown error");
@@ -412,6 +501,117 @@ static void compare_dir_worker(void *arg) {
     worker_finish(task);
 }
 
+/* =========================================================================
+ * Worker: streaming directory comparison
+ * ========================================================================= */
+
+/* Nobody will take further events: the handle was discarded or the run
+ * cancelled (the caller then discards the whole stream). */
+static bool dir_stream_abandoned(const komparu_async_task_t *task) {
+    return komparu_cancelled(task->cancel) ||
+           atomic_load_explicit(&task->state, memory_order_acquire)
+               == KOMPARU_TASK_ORPHANED;
+}
+
+/* Runs on the walking thread and on inner pool workers. With a bound,
+ * blocks while the queue is full, so a slow consumer throttles the
+ * workers instead of growing the queue. */
+static void dir_stream_push(komparu_async_task_t *task,
+                            komparu_dir_event_type_t type,
+                            komparu_entry_kind_t kind,
+                            int reason, const char *rel_path) {
+    if (dir_stream_abandoned(task)) return;
+    char *copy = strdup(rel_path);
+
+    EVENT_LOCK(&task->event_lock);
+    if (KOMPARU_UNLIKELY(!copy)) {
+        task->event_oom = true;
+        EVENT_UNLOCK(&task->event_lock);
+        return;
+    }
+    while (task->max_pending && task->event_count >= task->max_pending) {
+        if (dir_stream_abandoned(task)) {
+            EVENT_UNLOCK(&task->event_lock);
+            free(copy);
+            return;
+        }
+        event_cond_wait_slice(&task->event_space, &task->event_lock);
+    }
+    if (task->event_count == task->event_capacity) {
+        size_t new_cap = task->event_capacity ? task->event_capacity * 2 : 64;
+        komparu_dir_event_t *grown = realloc(task->events,
+                                             new_cap * sizeof(*grown));
+        if (KOMPARU_UNLIKELY(!grown)) {
+            task->event_oom = true;
+            EVENT_UNLOCK(&task->event_lock);
+            free(copy);
+            return;
+        }
+        task->events = grown;
+        task->event_capacity = new_cap;
+    }
+    task->events[task->event_count++] = (komparu_dir_event_t){
+        .type = type, .kind = kind, .reason = reason, .rel_path = copy,
+    };
+    EVENT_UNLOCK(&task->event_lock);
+
+    notify_signal(task->write_fd);
+}
+
+static void dir_stream_on_entry(void *user, komparu_entry_kind_t kind,
+                                int reason, const char *rel_path) {
+    dir_stream_push(user, KOMPARU_DIR_EVENT_ENTRY, kind, reason, rel_path);
+}
+
+static void dir_stream_on_start(void *user, const char *rel_path) {
+    dir_stream_push(user, KOMPARU_DIR_EVENT_STARTED,
+                    KOMPARU_ENTRY_EQUAL, -1, rel_path);
+}
+
+static void dir_stream_on_dir(void *user, bool entered, const char *rel_path) {
+    dir_stream_push(user,
+                    entered ? KOMPARU_DIR_EVENT_DIR_ENTERED : KOMPARU_DIR_EVENT_DIR_LEFT,
+                    KOMPARU_ENTRY_EQUAL, -1, rel_path);
+}
+
+static void compare_dir_stream_worker(void *arg) {
+    komparu_async_task_t *task = (komparu_async_task_t *)arg;
+    const char *err = NULL;
+    komparu_dir_hooks_t hooks = {
+        .on_entry = dir_stream_on_entry,
+        .on_start = task->progress ? dir_stream_on_start : NULL,
+        .on_dir = task->progress ? dir_stream_on_dir : NULL,
+        .user = task,
+    };
+
+    /* Entries are delivered through the queue; the aggregate is not needed. */
+    komparu_dir_result_t *result = komparu_compare_dirs_at(
+        task->dirfd_a, task->source_a, task->dirfd_b, task->source_b,
+        task->chunk_size, task->size_precheck,
+        task->quick_check, task->follow_symlinks,
+        task->max_workers, &hooks, task->cancel,
+        task->retry.retries ? &task->retry : NULL,
+        task->locking ? &task->lock : NULL, task->cache, &err);
+
+    if (result) {
+        komparu_dir_result_free(result);
+    } else {
+        task->error_code = errno;
+        snprintf(task->error_buf, sizeof(task->error_buf),
+                 "directory comparison failed: %s",
+                 err ? err : "unknown error");
+        task->has_error = true;
+    }
+    if (task->event_oom) {
+        snprintf(task->error_buf, sizeof(task->error_buf),
+                 "directory comparison failed: out of memory");
+        task->error_code = ENOMEM;
+        task->has_error = true;
+    }
+
+    worker_finish(task);
+}
+
 /* =========================================================================
  * Worker: archive comparison
  * ========================================================================= */
@@ -460,6 +660,7 @@ static void compare_archive_worker(void *arg) {
     }
 
     if (!task->dir_result) {
+        task->error_code = errno;
         snprintf(task->error_buf, sizeof(task->error_buf),
                  "archive comparison failed: %s",
                  err ? err : "unknown error");
@@ -494,6 +695,7 @@ static void compare_dir_urls_worker(void *arg) {
         &err);
 
     if (!task->dir_result) {
+        task->error_code = errno;
         snprintf(task->error_buf, sizeof(task->error_buf),
                  "dir_urls comparison failed: %s",
                  err ? err : "unknown error");
@@ -522,6 +724,8 @@ static komparu_async_task_t *task_alloc(
     task->type = type;
     task->read_fd = -1;
     task->write_fd = -1;
+    task->dirfd_a = AT_FDCWD;
+    task->dirfd_b = AT_FDCWD;
     task->cmp_result = KOMPARU_ERROR;
 
     if (notify_create(&task->read_fd, &task->write_fd) != 0) {
@@ -558,6 +762,7 @@ komparu_async_task_t *komparu_async_compare(
     bool verify_ssl,
     bool allow_private,
     const char *proxy,
+    komparu_cancel_t *cancel,
     const char **err_msg
 ) {
     komparu_pool_t *pool = get_pool();
@@ -569,6 +774,7 @@ komparu_async_task_t *komparu_async_compare(
     komparu_async_task_t *task = task_alloc(
         KOMPARU_ASYNC_COMPARE, source_a, source_b, err_msg);
     if (!task) return NULL;
+    task->cancel = komparu_cancel_ref(cancel);
 
     /* Copy headers */
     if (headers) {
@@ -615,14 +821,50 @@ komparu_async_task_t *komparu_async_compare(
     return task;
 }
 
+/* Duplicate caller descriptors so the task outlives the caller's copies. */
+static int task_set_dirfds(komparu_async_task_t *task, int dirfd_a, int dirfd_b,
+                           const char **err_msg) {
+#ifdef KOMPARU_WINDOWS
+    if (dirfd_a != AT_FDCWD || dirfd_b != AT_FDCWD) {
+        *err_msg = "directory handles are not supported on this platform";
+        errno = ENOTSUP;
+        return -1;
+    }
+    (void)task;
+    return 0;
+#else
+    if (dirfd_a != AT_FDCWD) {
+        task->dirfd_a = fcntl(dirfd_a, F_DUPFD_CLOEXEC, 0);
+        if (task->dirfd_a < 0) goto fail;
+    }
+    if (dirfd_b != AT_FDCWD) {
+        task->dirfd_b = fcntl(dirfd_b, F_DUPFD_CLOEXEC, 0);
+        if (task->dirfd_b < 0) goto fail;
+    }
+    return 0;
+
+fail:
+    if (task->dirfd_a < 0) task->dirfd_a = AT_FDCWD;
+    if (task->dirfd_b < 0) task->dirfd_b = AT_FDCWD;
+    *err_msg = "invalid directory handle";
+    return -1;
+#endif
+}
+
 komparu_async_task_t *komparu_async_compare_dir(
+    int dirfd_a,
     const char *dir_a,
+    int dirfd_b,
     const char *dir_b,
     size_t chunk_size,
     bool size_precheck,
     bool quick_check,
     bool follow_symlinks,
     size_t max_workers,
+    const komparu_retry_t *retry,
+    const komparu_lock_t *lock,
+    komparu_dir_cache_t *cache,
+    komparu_cancel_t *cancel,
     const char **err_msg
 ) {
     komparu_pool_t *pool = get_pool();
@@ -634,12 +876,24 @@ komparu_async_task_t *komparu_async_compare_dir(
     komparu_async_task_t *task = task_alloc(
         KOMPARU_ASYNC_COMPARE_DIR, dir_a, dir_b, err_msg);
     if (!task) return NULL;
+    task->cancel = komparu_cancel_ref(cancel);
+    task->cache = komparu_dir_cache_ref(cache);
+    if (task_set_dirfds(task, dirfd_a, dirfd_b, err_msg) != 0) {
+        task_free_internals(task);
+        free(task);
+        return NULL;
+    }
 
     task->chunk_size = chunk_size ? chunk_size : KOMPARU_DEFAULT_CHUNK_SIZE;
     task->size_precheck = size_precheck;
     task->quick_check = quick_check;
     task->follow_symlinks = follow_symlinks;
     task->max_workers = max_workers;
+    if (retry) task->retry = *retry;
+    if (lock) {
+        task->lock = *lock;
+        task->locking = true;
+    }
 
     if (komparu_pool_submit(pool, compare_dir_worker, task) != 0) {
         *err_msg = "async pool queue full";
@@ -785,6 +1039,75 @@ komparu_async_task_t *komparu_async_compare_dir_urls(
     return task;
 }
 
+komparu_async_task_t *komparu_async_compare_dir_stream(
+    int dirfd_a,
+    const char *dir_a,
+    int dirfd_b,
+    const char *dir_b,
+    size_t chunk_size,
+    bool size_precheck,
+    bool quick_check,
+    bool follow_symlinks,
+    size_t max_workers,
+    bool progress,
+    size_t max_pending,
+    const komparu_retry_t *retry,
+    const komparu_lock_t *lock,
+    komparu_dir_cache_t *cache,
+    komparu_cancel_t *cancel,
+    const char **err_msg
+) {
+    komparu_pool_t *pool = get_pool();
+    if (!pool) {
+        *err_msg = "failed to create async pool";
+        return NULL;
+    }
+
+    komparu_async_task_t *task = task_alloc(
+        KOMPARU_ASYNC_COMPARE_DIR_STREAM, dir_a, dir_b, err_msg);
+    if (!task) return NULL;
+    task->cancel = komparu_cancel_ref(cancel);
+    task->cache = komparu_dir_cache_ref(cache);
+    if (task_set_dirfds(task, dirfd_a, dirfd_b, err_msg) != 0) {
+        task_free_internals(task);
+        free(task);
+        return NULL;
+    }
+
+    EVENT_LOCK_INIT(&task->event_lock);
+    EVENT_COND_INIT(&task->event_space);
+    task->event_lock_init = true;
+
+    task->chunk_size = chunk_size ? chunk_size : KOMPARU_DEFAULT_CHUNK_SIZE;
+    task->size_precheck = size_precheck;
+    task->quick_check = quick_check;
+    task->follow_symlinks = follow_symlinks;
+    task->max_workers = max_workers;
+    if (retry) task->retry = *retry;
+    if (lock) {
+        task->lock = *lock;
+        task->locking = true;
+    }
+    task->progress = progress;
+    task->max_pending = max_pending;
+
+    if (komparu_pool_submit(pool, compare_dir_stream_worker, task) != 0) {
+        *err_msg = "async pool queue full";
+        task_free_internals(task);
+        free(task);
+        return NULL;
+    }
+
+    return task;
+}
+
+int komparu_async_task_error_code(komparu_async_task_t *task, const char **path) {
+    /* Acquire barrier: see all writes from the worker thread. */
+    (void)atomic_load_explicit(&task->state, memory_order_acquire);
+    *path = task->error_path;
+    return task->has_error ? task->error_code : 0;
+}
+
 int komparu_async_task_fd(komparu_async_task_t *task) {
     return task->read_fd;
 }
@@ -819,9 +1142,57 @@ komparu_dir_result_t *komparu_async_task_dir_result(
     return r;
 }
 
+int komparu_async_task_take_events(
+    komparu_async_task_t *task,
+    komparu_dir_event_t **events,
+    size_t *count,
+    bool *finished,
+    const char **err_msg
+) {
+    /* Drain, then load state, then take the queue. A worker that finishes
+     * after the drain leaves its wakeup on the fd for the next poll; one
+     * that finished before the load has all its events in the queue. */
+    notify_drain(task->read_fd);
+
+    bool done = atomic_load_explicit(&task->state, memory_order_acquire)
+                == KOMPARU_TASK_DONE;
+
+    EVENT_LOCK(&task->event_lock);
+    *events = task->events;
+    *count = task->event_count;
+    task->events = NULL;
+    task->event_count = 0;
+    task->event_capacity = 0;
+    if (task->max_pending) EVENT_COND_BROADCAST(&task->event_space);
+    EVENT_UNLOCK(&task->event_lock);
+
+    *finished = done;
+    if (done && task->has_error) {
+        if (*count > 0) {
+            /* Entries resolved before the failure (a cancel, a byte quota)
+             * still count; the error comes with the next take */
+            *finished = false;
+            notify_signal(task->write_fd);
+            return 0;
+        }
+        *err_msg = task->error_buf;
+        return -1;
+    }
+    return 0;
+}
+
+void komparu_dir_events_free(komparu_dir_event_t *events, size_t count) {
+    if (!events) return;
+    for (size_t i = 0; i < count; i++)
+        free(events[i].rel_path);
+    free(events);
+}
+
 /** Free internal resources of a task (does NOT free the task struct). */
 static void task_free_internals(komparu_async_task_t *task) {
     notify_close(task->read_fd, task->write_fd);
+    if (task->dirfd_a != AT_FDCWD) close(task->dirfd_a);
+    if (task->dirfd_b != AT_FDCWD) close(task->dirfd_b);
     free(task->source_a);
     free(task->source_b);
     free(task->proxy);
@@ -842,6 +1213,13 @@ static void task_free_internals(komparu_async_task_t *task) {
     }
     if (task->dir_result)
         komparu_dir_result_free(task->dir_result);
+    komparu_dir_events_free(task->events, task->event_count);
+    komparu_cancel_unref(task->cancel);
+    komparu_dir_cache_unref(task->cache);
+    if (task->event_lock_init) {
+        EVENT_COND_DESTROY(&task->event_space);
+        EVENT_LOCK_DESTROY(&task->event_lock);
+    }
 }
 
 void komparu_async_task_free(komparu_async_task_t *task) {
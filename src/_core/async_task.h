@@ -14,6 +14,9 @@
 
 #include "compat.h"
 #include "compare.h"
+#include "retry.h"
+#include "lock.h"
+#include "dircache.h"
 
 typedef struct komparu_async_task komparu_async_task_t;
 
@@ -25,6 +28,7 @@ typedef struct komparu_async_task komparu_async_task_t;
  * then reads the result with task_cmp_result().
  *
  * headers: NULL-terminated "Key: Value" array (copied), or NULL.
+ * cancel: optional token; the task takes its own reference.
  * Returns NULL on error (pool full, OOM).
  */
 komparu_async_task_t *komparu_async_compare(
@@ -39,26 +43,110 @@ komparu_async_task_t *komparu_async_compare(
     bool verify_ssl,
     bool allow_private,
     const char *proxy,
+    komparu_cancel_t *cancel,
     const char **err_msg
 );
 
 /**
  * Submit an async directory comparison.
  *
- * Internally uses komparu_compare_dirs() which has its own parallelism.
+ * Internally uses komparu_compare_dirs_at() which has its own parallelism.
+ * dirfd_a/dirfd_b: directory descriptors the paths are relative to, or
+ * AT_FDCWD; duplicated, so the caller may close them after submitting.
+ * retry: optional policy (copied), or NULL.
+ * lock: optional shared-lock policy (copied), or NULL.
+ * cache: optional warm cache (referenced for the task's lifetime), or NULL.
  * Returns NULL on error.
  */
 komparu_async_task_t *komparu_async_compare_dir(
+    int dirfd_a,
     const char *dir_a,
+    int dirfd_b,
     const char *dir_b,
     size_t chunk_size,
     bool size_precheck,
     bool quick_check,
     bool follow_symlinks,
     size_t max_workers,
+    const komparu_retry_t *retry,
+    const komparu_lock_t *lock,
+    komparu_dir_cache_t *cache,
+    komparu_cancel_t *cancel,
     const char **err_msg
 );
 
+/**
+ * Submit a streaming directory comparison.
+ *
+ * Same work as komparu_async_compare_dir(), but every resolved entry is
+ * queued as it completes and the fd is signaled per entry. Drain with
+ * komparu_async_task_take_events() each time the fd becomes readable.
+ * progress: also queue STARTED and DIR_ENTERED/DIR_LEFT events.
+ * max_pending: bound on queued events (0 = unbounded). When the queue is
+ * full, workers block until it is taken, cancelled or the task freed.
+ * Returns NULL on error.
+ */
+komparu_async_task_t *komparu_async_compare_dir_stream(
+    int dirfd_a,
+    const char *dir_a,
+    int dirfd_b,
+    const char *dir_b,
+    size_t chunk_size,
+    bool size_precheck,
+    bool quick_check,
+    bool follow_symlinks,
+    size_t max_workers,
+    bool progress,
+    size_t max_pending,
+    const komparu_retry_t *retry,
+    const komparu_lock_t *lock,
+    komparu_dir_cache_t *cache,
+    komparu_cancel_t *cancel,
+    const char **err_msg
+);
+
+typedef enum {
+    KOMPARU_DIR_EVENT_ENTRY       = 0,  /* entry resolved: kind/reason valid */
+    KOMPARU_DIR_EVENT_STARTED     = 1,  /* common file comparison started */
+    KOMPARU_DIR_EVENT_DIR_ENTERED = 2,
+    KOMPARU_DIR_EVENT_DIR_LEFT    = 3,
+} komparu_dir_event_type_t;
+
+/** One event reported by a streaming directory comparison. */
+typedef struct {
+    komparu_dir_event_type_t type;
+    komparu_entry_kind_t kind;
+    int reason;              /* KOMPARU_DIFF_* for KOMPARU_ENTRY_DIFF, else -1 */
+    char *rel_path;          /* owned */
+} komparu_dir_event_t;
+
+/**
+ * Take every event queued so far and reset the fd's readiness.
+ *
+ * events/count receive the batch (caller frees with
+ * komparu_dir_events_free; may be empty). *finished is set once the worker
+ * is done — no further events will follow.
+ * Returns 0 on success, -1 if the comparison failed (*err_msg set). Events
+ * queued before a failure are handed over first: that take returns them
+ * unfinished and signals the fd again, and the next one reports the error.
+ */
+int komparu_async_task_take_events(
+    komparu_async_task_t *task,
+    komparu_dir_event_t **events,
+    size_t *count,
+    bool *finished,
+    const char **err_msg
+);
+
+/** Free an event batch returned by komparu_async_task_take_events(). */
+void komparu_dir_events_free(komparu_dir_event_t *events, size_t count);
+
+/**
+ * Get the errno classifying a failed task (see compat.h), 0 if it did not
+ * fail. *path receives the offending source when known, else NULL.
+ */
+int komparu_async_task_error_code(komparu_async_task_t *task, const char **path);
+
 /** Get the read fd for asyncio.loop.add_reader(). */
 int komparu_async_task_fd(komparu_async_task_t *task);
 
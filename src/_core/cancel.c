@@ -0,0 +1,43 @@
+/**
+ * cancel.c — Reference-counted cancellation token.
+ */
+
+#include "cancel.h"
+#include <stdlib.h>
+#include <stdint.h>
+
+komparu_cancel_t *komparu_cancel_new(void) {
+    return komparu_cancel_new_quota(0);
+}
+
+komparu_cancel_t *komparu_cancel_new_quota(int64_t max_bytes) {
+    komparu_cancel_t *cancel = calloc(1, sizeof(*cancel));
+    if (KOMPARU_UNLIKELY(!cancel)) return NULL;
+    atomic_init(&cancel->cancelled, false);
+    atomic_init(&cancel->refs, 1);
+    atomic_init(&cancel->bytes_read, 0);
+    atomic_init(&cancel->entries_done, 0);
+    atomic_init(&cancel->quota, false);
+    cancel->max_bytes = max_bytes > 0 ? max_bytes : 0;
+    return cancel;
+}
+
+komparu_cancel_t *komparu_cancel_new_child(komparu_cancel_t *parent) {
+    komparu_cancel_t *cancel = komparu_cancel_new_quota(0);
+    if (cancel) cancel->parent = komparu_cancel_ref(parent);
+    return cancel;
+}
+
+komparu_cancel_t *komparu_cancel_ref(komparu_cancel_t *cancel) {
+    if (cancel)
+        atomic_fetch_add_explicit(&cancel->refs, 1, memory_order_relaxed);
+    return cancel;
+}
+
+void komparu_cancel_unref(komparu_cancel_t *cancel) {
+    if (!cancel) return;
+    if (atomic_fetch_sub_explicit(&cancel->refs, 1, memory_order_acq_rel) == 1) {
+        komparu_cancel_unref(cancel->parent);
+        free(cancel);
+    }
+}
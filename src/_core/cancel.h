@@ -0,0 +1,103 @@
+/**
+ * cancel.h — Cancellation token shared between Python and C workers.
+ *
+ * A reference-counted atomic flag. Python holds one reference through the
+ * CancelToken capsule; every async task that uses the token holds another,
+ * so the flag outlives whichever side finishes last.
+ *
+ * Checked between chunks and between directory entries — a cancelled run
+ * stops at the next check and reports KOMPARU_ERROR with a "cancelled"
+ * message. A NULL token is never cancelled.
+ *
+ * A token may also carry a byte quota: the comparison engine counts what
+ * it reads under the token, and the first read past max_bytes cancels it
+ * with the quota flag set, so the caller can tell the two stops apart.
+ * Directory comparisons also count the entries they finish under it, so
+ * progress can be read without per-entry callbacks.
+ *
+ * A child token stops when it or its parent is cancelled, and counts what
+ * it reads and finishes into the parent too, so a run can stop itself
+ * without setting a token the caller shares with other runs.
+ */
+
+#ifndef KOMPARU_CANCEL_H
+#define KOMPARU_CANCEL_H
+
+#include "compat.h"
+#include <stdatomic.h>
+
+typedef struct komparu_cancel {
+    _Atomic bool cancelled;
+    _Atomic int refs;
+    _Atomic int64_t bytes_read;
+    _Atomic int64_t entries_done;  /* directory entries resolved */
+    _Atomic bool quota;     /* cancelled because bytes_read passed max_bytes */
+    int64_t max_bytes;      /* 0 = no quota; fixed at creation */
+    struct komparu_cancel *parent;  /* referenced; NULL for a top-level token */
+} komparu_cancel_t;
+
+/** Create a token with one reference. Returns NULL on OOM. */
+komparu_cancel_t *komparu_cancel_new(void);
+
+/** Create a token that cancels itself once more than max_bytes are read
+ * (0 = never). Returns NULL on OOM. */
+komparu_cancel_t *komparu_cancel_new_quota(int64_t max_bytes);
+
+/** Create a token with no quota whose parent is *parent* (NULL: none).
+ * Returns NULL on OOM. */
+komparu_cancel_t *komparu_cancel_new_child(komparu_cancel_t *parent);
+
+/** Take an extra reference (NULL-safe). Returns the token. */
+komparu_cancel_t *komparu_cancel_ref(komparu_cancel_t *cancel);
+
+/** Drop a reference; frees on last (NULL-safe). */
+void komparu_cancel_unref(komparu_cancel_t *cancel);
+
+/** Request cancellation. Idempotent, safe from any thread. */
+static inline void komparu_cancel_set(komparu_cancel_t *cancel) {
+    atomic_store_explicit(&cancel->cancelled, true, memory_order_release);
+}
+
+static inline bool komparu_cancelled(const komparu_cancel_t *cancel) {
+    for (; cancel; cancel = cancel->parent) {
+        if (KOMPARU_UNLIKELY(atomic_load_explicit(&cancel->cancelled, memory_order_acquire)))
+            return true;
+    }
+    return false;
+}
+
+/**
+ * Count n bytes read under the token (NULL-safe). The token is logically
+ * const to the readers that share it; the counters are atomics meant to be
+ * updated through it.
+ */
+static inline void komparu_cancel_charge(const komparu_cancel_t *cancel, int64_t n) {
+    if (n <= 0) return;
+    for (komparu_cancel_t *c = (komparu_cancel_t *)cancel; c; c = c->parent) {
+        int64_t total = atomic_fetch_add_explicit(&c->bytes_read, n, memory_order_relaxed) + n;
+        if (c->max_bytes > 0 && total > c->max_bytes) {
+            atomic_store_explicit(&c->quota, true, memory_order_relaxed);
+            komparu_cancel_set(c);
+        }
+    }
+}
+
+/** Count one directory entry resolved under the token (NULL-safe). */
+static inline void komparu_cancel_count_entry(const komparu_cancel_t *cancel) {
+    for (komparu_cancel_t *c = (komparu_cancel_t *)cancel; c; c = c->parent)
+        atomic_fetch_add_explicit(&c->entries_done, 1, memory_order_relaxed);
+}
+
+/** True if *cancel* or an ancestor stopped on its byte quota. */
+static inline bool komparu_cancel_quota(const komparu_cancel_t *cancel) {
+    for (; cancel; cancel = cancel->parent) {
+        if (atomic_load_explicit(&cancel->quota, memory_order_acquire))
+            return true;
+    }
+    return false;
+}
+
+/** err_msg set by cancelled operations. */
+#define KOMPARU_CANCELLED_MSG "cancelled"
+
+#endif /* KOMPARU_CANCEL_H */
@@ -11,55 +11,241 @@
  */
 
 #include "compare.h"
+#include "retry.h"
+#include <stdatomic.h>
 #include <stdlib.h>
 #include <string.h>
 
 /* =========================================================================
  * Thread-local comparison buffers — avoid malloc/free per comparison.
  * Each worker thread gets its own pair. Resized only when chunk_size grows.
+ *
+ * Pools are created per directory comparison, so worker threads come and
+ * go. An exiting worker parks its pair in a process-wide free list and the
+ * next thread that needs buffers adopts one, so a daemon comparing trees
+ * in a loop keeps reusing the same allocations across files and runs.
+ *
+ * Buffers come from malloc unless komparu_set_buffer_allocator() installs
+ * another source. Every pair remembers the allocator that made it and is
+ * always released through that one.
  * ========================================================================= */
 
 static _Thread_local void *tl_buf_a = NULL;
 static _Thread_local void *tl_buf_b = NULL;
 static _Thread_local size_t tl_buf_cap = 0;
+static _Thread_local komparu_buffer_allocator_t tl_buf_alloc;  /* owner of the pair */
+static _Thread_local unsigned tl_buf_gen = 0;
+
+#ifdef KOMPARU_WINDOWS
+static SRWLOCK spare_lock = SRWLOCK_INIT;
+#define SPARE_LOCK()   AcquireSRWLockExclusive(&spare_lock)
+#define SPARE_UNLOCK() ReleaseSRWLockExclusive(&spare_lock)
+#else
+#include <pthread.h>
+static pthread_mutex_t spare_lock = PTHREAD_MUTEX_INITIALIZER;
+#define SPARE_LOCK()   pthread_mutex_lock(&spare_lock)
+#define SPARE_UNLOCK() pthread_mutex_unlock(&spare_lock)
+#endif
+
+typedef struct {
+    void *a;
+    void *b;
+    size_t cap;
+    komparu_buffer_allocator_t alloc;
+} buffer_pair_t;
+
+static buffer_pair_t spare[KOMPARU_SPARE_BUFFERS_MAX];
+static size_t spare_count = 0;
+static size_t spare_bytes = 0;   /* both buffers of every parked pair */
+
+/* Installed allocator (zeroed = malloc/free); guarded by spare_lock.
+ * alloc_gen changes on every install so threads drop stale pairs. */
+static komparu_buffer_allocator_t cur_alloc;
+static _Atomic unsigned alloc_gen = 0;
+
+static void *buf_alloc(const komparu_buffer_allocator_t *al, size_t size) {
+    return al->alloc ? al->alloc(al->ctx, size) : malloc(size);
+}
+
+static void buf_release(const komparu_buffer_allocator_t *al, void *ptr, size_t size) {
+    if (!ptr) return;
+    if (al->release) al->release(al->ctx, ptr, size);
+    else free(ptr);
+}
+
+static void pair_release(const buffer_pair_t *pair) {
+    buf_release(&pair->alloc, pair->a, pair->cap);
+    buf_release(&pair->alloc, pair->b, pair->cap);
+}
+
+/* Free this thread's pair through the allocator that produced it. */
+static void tls_release(void) {
+    pair_release(&(buffer_pair_t){ tl_buf_a, tl_buf_b, tl_buf_cap, tl_buf_alloc });
+    tl_buf_a = NULL;
+    tl_buf_b = NULL;
+    tl_buf_cap = 0;
+}
+
+/* Adopt a parked pair, preferring one already large enough. */
+static void adopt_spare(size_t chunk_size) {
+    SPARE_LOCK();
+    if (spare_count > 0) {
+        size_t pick = spare_count - 1;
+        for (size_t k = 0; k < spare_count; k++) {
+            if (spare[k].cap >= chunk_size) {
+                pick = k;
+                break;
+            }
+        }
+        tl_buf_a = spare[pick].a;
+        tl_buf_b = spare[pick].b;
+        tl_buf_cap = spare[pick].cap;
+        tl_buf_alloc = spare[pick].alloc;
+        tl_buf_gen = atomic_load_explicit(&alloc_gen, memory_order_relaxed);
+        spare_bytes -= 2 * spare[pick].cap;
+        spare[pick] = spare[--spare_count];
+    }
+    SPARE_UNLOCK();
+}
 
 static int ensure_buffers(size_t chunk_size, void **a, void **b) {
-    if (KOMPARU_LIKELY(chunk_size <= tl_buf_cap)) {
+    unsigned gen = atomic_load_explicit(&alloc_gen, memory_order_acquire);
+    if (KOMPARU_LIKELY(chunk_size <= tl_buf_cap && tl_buf_gen == gen)) {
         *a = tl_buf_a;
         *b = tl_buf_b;
         return 0;
     }
 
-    /* Use temp pointers so that on OOM we don't lose the old allocation.
-     * realloc(ptr, size) may free the old block on success, so we must
-     * save the new pointer immediately — but only commit to thread-local
-     * state after BOTH allocations succeed. */
-    void *na = realloc(tl_buf_a, chunk_size);
-    if (!na) return -1;
+    /* Allocator was replaced since this pair was made */
+    if (tl_buf_cap > 0 && tl_buf_gen != gen) tls_release();
+
+    if (tl_buf_cap == 0) {
+        adopt_spare(chunk_size);
+        if (chunk_size <= tl_buf_cap) {
+            *a = tl_buf_a;
+            *b = tl_buf_b;
+            return 0;
+        }
+    }
 
-    void *nb = realloc(tl_buf_b, chunk_size);
+    /* Allocate the new pair before dropping the old one, so on OOM the
+     * thread keeps its previous (smaller) buffers and the next call
+     * retries. Contents need not survive, so no realloc: a custom
+     * allocator only has to provide alloc and release. */
+    komparu_buffer_allocator_t al;
+    SPARE_LOCK();
+    al = cur_alloc;
+    gen = atomic_load_explicit(&alloc_gen, memory_order_relaxed);
+    SPARE_UNLOCK();
+
+    void *na = buf_alloc(&al, chunk_size);
+    if (!na) return -1;
+    void *nb = buf_alloc(&al, chunk_size);
     if (!nb) {
-        /* Second realloc failed. na is valid (old tl_buf_a may be freed),
-         * so save it to prevent a leak. tl_buf_b is still valid at old size.
-         * tl_buf_cap stays at old value — next call will retry. */
-        tl_buf_a = na;
+        buf_release(&al, na, chunk_size);
         return -1;
     }
 
+    tls_release();
     tl_buf_a = na;
     tl_buf_b = nb;
     tl_buf_cap = chunk_size;
+    tl_buf_alloc = al;
+    tl_buf_gen = gen;
     *a = na;
     *b = nb;
     return 0;
 }
 
 void komparu_compare_tls_cleanup(void) {
-    free(tl_buf_a);
-    free(tl_buf_b);
-    tl_buf_a = NULL;
-    tl_buf_b = NULL;
-    tl_buf_cap = 0;
+    if (tl_buf_cap > 0) {
+        bool parked = false;
+        SPARE_LOCK();
+        if (tl_buf_gen == atomic_load_explicit(&alloc_gen, memory_order_relaxed) &&
+            spare_count < KOMPARU_SPARE_BUFFERS_MAX &&
+            spare_bytes + 2 * tl_buf_cap <= KOMPARU_SPARE_BUFFERS_MAX_BYTES) {
+            spare[spare_count++] = (buffer_pair_t){ tl_buf_a, tl_buf_b, tl_buf_cap,
+                                                    tl_buf_alloc };
+            spare_bytes += 2 * tl_buf_cap;
+            parked = true;
+        }
+        SPARE_UNLOCK();
+        if (parked) {
+            tl_buf_a = NULL;
+            tl_buf_b = NULL;
+            tl_buf_cap = 0;
+            return;
+        }
+    }
+    tls_release();
+}
+
+void komparu_compare_buffers_drain(void) {
+    SPARE_LOCK();
+    for (size_t k = 0; k < spare_count; k++)
+        pair_release(&spare[k]);
+    spare_count = 0;
+    spare_bytes = 0;
+    SPARE_UNLOCK();
+}
+
+int komparu_set_buffer_allocator(const komparu_buffer_allocator_t *allocator) {
+    if (allocator && (!allocator->alloc || !allocator->release)) {
+        errno = EINVAL;
+        return -1;
+    }
+    SPARE_LOCK();
+    cur_alloc = allocator ? *allocator : (komparu_buffer_allocator_t){0};
+    atomic_fetch_add_explicit(&alloc_gen, 1, memory_order_release);
+    /* Parked pairs belong to the previous allocator */
+    for (size_t k = 0; k < spare_count; k++)
+        pair_release(&spare[k]);
+    spare_count = 0;
+    spare_bytes = 0;
+    SPARE_UNLOCK();
+    return 0;
+}
+
+/* Transient read errors retried in place before they count: EINTR at
+ * once, EAGAIN/EWOULDBLOCK after 1, 2, 4, 8 and 16 ms. Anything longer
+ * (EBUSY, ESTALE, a server that keeps failing) is for a komparu_retry_t. */
+#define READ_TRANSIENT_RETRIES 5
+#define READ_TRANSIENT_DELAY   0.001
+
+/* One read, retried on EINTR and EAGAIN. A failed read consumes nothing,
+ * so the retry reads from where the failed one would have. */
+static int64_t read_once(komparu_reader_t *reader, void *buf, size_t size,
+                         const komparu_cancel_t *cancel) {
+    for (unsigned attempt = 0;; attempt++) {
+        errno = 0;
+        int64_t n = reader->read(reader, buf, size);
+        if (n >= 0) return n;
+        int err = errno;
+        bool again = err == EAGAIN || err == EWOULDBLOCK;
+        if (attempt >= READ_TRANSIENT_RETRIES || (!again && err != EINTR))
+            return -1;
+        if (again && !komparu_sleep(READ_TRANSIENT_DELAY * (double)(1u << attempt), cancel)) {
+            errno = ECANCELED;
+            return -1;
+        }
+    }
+}
+
+/* Read until buf holds size bytes or the reader is at its end. A read may
+ * return less than asked well before EOF (network file systems, pipes, a
+ * signal mid-read); windows of unequal length from two equal files must
+ * not look like a difference. Returns the bytes read, or -1 on error. */
+static int64_t read_full(komparu_reader_t *reader, void *buf, size_t size,
+                         const komparu_cancel_t *cancel) {
+    size_t filled = 0;
+    while (filled < size) {
+        int64_t n = read_once(reader, (char *)buf + filled, size - filled, cancel);
+        if (n < 0) return -1;
+        if (n == 0) break;
+        filled += (size_t)n;
+    }
+    komparu_cancel_charge(cancel, (int64_t)filled);
+    return (int64_t)filled;
 }
 
 komparu_result_t komparu_compare(
@@ -68,6 +254,18 @@ komparu_result_t komparu_compare(
     size_t chunk_size,
     bool size_precheck,
     const char **err_msg
+) {
+    return komparu_compare_ex(reader_a, reader_b, chunk_size,
+                              size_precheck, NULL, err_msg);
+}
+
+komparu_result_t komparu_compare_ex(
+    komparu_reader_t *reader_a,
+    komparu_reader_t *reader_b,
+    size_t chunk_size,
+    bool size_precheck,
+    const komparu_cancel_t *cancel,
+    const char **err_msg
 ) {
     if (chunk_size == 0) {
         chunk_size = KOMPARU_DEFAULT_CHUNK_SIZE;
@@ -98,10 +296,43 @@ komparu_result_t komparu_compare(
 
     komparu_result_t result = KOMPARU_EQUAL;
 
+    /* Holes are skipped where both sides have one; `checked` is how far
+     * the layout is known to need reading */
+    bool sparse = reader_a->extent && reader_b->extent
+                  && reader_a->seek && reader_b->seek;
+    int64_t pos = 0, checked = 0;
+
     /* Step 2: Sequential chunk comparison */
     for (;;) {
-        int64_t n_a = reader_a->read(reader_a, buf_a, chunk_size);
-        int64_t n_b = reader_b->read(reader_b, buf_b, chunk_size);
+        if (komparu_cancelled(cancel)) {
+            *err_msg = KOMPARU_CANCELLED_MSG;
+            errno = ECANCELED;
+            result = KOMPARU_ERROR;
+            break;
+        }
+
+        if (sparse && pos >= checked) {
+            bool hole_a = false, hole_b = false;
+            int64_t end_a = reader_a->extent(reader_a, pos, &hole_a);
+            int64_t end_b = reader_b->extent(reader_b, pos, &hole_b);
+            if (end_a <= pos || end_b <= pos) {
+                sparse = false;
+            } else if (hole_a && hole_b) {
+                pos = end_a < end_b ? end_a : end_b;
+                if (reader_a->seek(reader_a, pos) != 0 ||
+                    reader_b->seek(reader_b, pos) != 0) {
+                    *err_msg = "seek failed while skipping a hole";
+                    result = KOMPARU_ERROR;
+                    break;
+                }
+                continue;
+            } else {
+                checked = end_a < end_b ? end_a : end_b;
+            }
+        }
+
+        int64_t n_a = read_full(reader_a, buf_a, chunk_size, cancel);
+        int64_t n_b = read_full(reader_b, buf_b, chunk_size, cancel);
 
         /* Read errors */
         if (n_a < 0) {
@@ -119,7 +350,7 @@ komparu_result_t komparu_compare(
             break;
         }
 
-        /* Different read lengths → different content */
+        /* Both windows are full up to EOF: a shorter one ended first */
         if (n_a != n_b) {
             result = KOMPARU_DIFFERENT;
             break;
@@ -136,6 +367,7 @@ komparu_result_t komparu_compare(
             result = KOMPARU_DIFFERENT;
             break;
         }
+        pos += n_a;
     }
 
     return result;
@@ -210,8 +442,8 @@ komparu_result_t komparu_quick_check(
             break;
         }
 
-        int64_t n_a = reader_a->read(reader_a, buf_a, chunk_size);
-        int64_t n_b = reader_b->read(reader_b, buf_b, chunk_size);
+        int64_t n_a = read_full(reader_a, buf_a, chunk_size, NULL);
+        int64_t n_b = read_full(reader_b, buf_b, chunk_size, NULL);
 
         if (n_a < 0 || n_b < 0) {
             result = KOMPARU_ERROR;
@@ -237,6 +469,288 @@ komparu_result_t komparu_quick_check(
     return result;
 }
 
+/* =========================================================================
+ * In-memory buffer helpers
+ * ========================================================================= */
+
+/* memcmp() works in word-sized strides; scanning a failing window
+ * byte-by-byte keeps the common (equal) path at memcmp speed. */
+#define MEM_SCAN_WINDOW 4096
+
+int64_t komparu_mem_first_diff(const void *a, size_t len_a,
+                               const void *b, size_t len_b) {
+    const unsigned char *pa = a;
+    const unsigned char *pb = b;
+    size_t common = len_a < len_b ? len_a : len_b;
+
+    for (size_t off = 0; off < common; off += MEM_SCAN_WINDOW) {
+        size_t n = common - off < MEM_SCAN_WINDOW ? common - off : MEM_SCAN_WINDOW;
+        if (memcmp(pa + off, pb + off, n) == 0) continue;
+        for (size_t i = 0; i < n; i++) {
+            if (pa[off + i] != pb[off + i]) return (int64_t)(off + i);
+        }
+    }
+    return len_a == len_b ? -1 : (int64_t)common;
+}
+
+size_t komparu_mem_diff_blocks(const void *a, size_t len_a,
+                               const void *b, size_t len_b,
+                               size_t block_size) {
+    const unsigned char *pa = a;
+    const unsigned char *pb = b;
+    size_t longest = len_a > len_b ? len_a : len_b;
+    size_t count = 0;
+
+    for (size_t off = 0; off < longest; off += block_size) {
+        size_t end = longest - off < block_size ? longest : off + block_size;
+        /* A block that runs past either buffer differs by length alone */
+        if (end > len_a || end > len_b ||
+            memcmp(pa + off, pb + off, end - off) != 0) {
+            count++;
+        }
+    }
+    return count;
+}
+
+/* =========================================================================
+ * Fuzzy hashing — spamsum-style context-triggered piecewise hashes
+ *
+ * A rolling hash over the last FUZZY_WINDOW bytes picks "trigger" points
+ * that depend only on local content, so an edit moves only the digest
+ * characters around it. Each piece between triggers contributes one
+ * base64 character of an FNV-style hash.
+ * ========================================================================= */
+
+#define FUZZY_WINDOW 7
+#define FUZZY_HASH_INIT 0x28021967u
+#define FUZZY_HASH_PRIME 0x01000193u
+
+static const char fuzzy_b64[] =
+    "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+void komparu_mem_fuzzy_digest(const void *buf, size_t len, uint32_t block_size,
+                              char *digest1, char *digest2) {
+    const unsigned char *p = buf;
+    uint32_t window[FUZZY_WINDOW] = {0};
+    uint32_t h1 = 0, h2 = 0, h3 = 0, roll = 0;
+    uint32_t piece1 = FUZZY_HASH_INIT, piece2 = FUZZY_HASH_INIT;
+    uint64_t double_size = (uint64_t)block_size * 2;
+    size_t j = 0, k = 0;
+
+    for (size_t i = 0; i < len; i++) {
+        uint32_t c = p[i];
+        piece1 = (piece1 * FUZZY_HASH_PRIME) ^ c;
+        piece2 = (piece2 * FUZZY_HASH_PRIME) ^ c;
+
+        h2 -= h1;
+        h2 += FUZZY_WINDOW * c;
+        h1 += c;
+        h1 -= window[i % FUZZY_WINDOW];
+        window[i % FUZZY_WINDOW] = c;
+        h3 = (h3 << 5) ^ c;
+        roll = h1 + h2 + h3;
+
+        if (roll % block_size == block_size - 1) {
+            /* A full digest keeps overwriting its last character */
+            digest1[j] = fuzzy_b64[piece1 % 64];
+            if (j < KOMPARU_FUZZY_DIGEST_MAX - 1) {
+                piece1 = FUZZY_HASH_INIT;
+                j++;
+            }
+            if (roll % double_size == double_size - 1) {
+                digest2[k] = fuzzy_b64[piece2 % 64];
+                if (k < KOMPARU_FUZZY_DIGEST_MAX / 2 - 1) {
+                    piece2 = FUZZY_HASH_INIT;
+                    k++;
+                }
+            }
+        }
+    }
+    if (roll != 0) {
+        digest1[j++] = fuzzy_b64[piece1 % 64];
+        digest2[k++] = fuzzy_b64[piece2 % 64];
+    }
+    digest1[j] = '\0';
+    digest2[k] = '\0';
+}
+
+/* =========================================================================
+ * Content-defined chunking — FastCDC with normalized chunking
+ *
+ * A gear hash (one shift and one table add per byte) cuts the buffer
+ * where its top bits are zero, so boundaries follow content and resync
+ * shortly after an insertion or deletion. A stricter mask before the
+ * target size and a looser one after it keep chunk sizes close to avg.
+ * ========================================================================= */
+
+static void cdc_gear_table(uint64_t gear[256]) {
+    /* splitmix64: a fixed seed keeps cut points stable across runs */
+    uint64_t x = 0x6b6f6d70617275ULL;
+    for (int i = 0; i < 256; i++) {
+        uint64_t z = (x += 0x9e3779b97f4a7c15ULL);
+        z = (z ^ (z >> 30)) * 0xbf58476d1ce4e5b9ULL;
+        z = (z ^ (z >> 27)) * 0x94d049bb133111ebULL;
+        gear[i] = z ^ (z >> 31);
+    }
+}
+
+static uint64_t cdc_mask(unsigned bits) {
+    if (bits == 0) return 0;
+    if (bits >= 64) return UINT64_MAX;
+    return ~(UINT64_MAX >> bits);  /* top bits carry the most history */
+}
+
+size_t komparu_mem_cdc(const void *buf, size_t len,
+                       size_t min_size, size_t avg_size, size_t max_size,
+                       size_t *cuts) {
+    const unsigned char *p = buf;
+    uint64_t gear[256];
+    cdc_gear_table(gear);
+
+    unsigned bits = 0;
+    while (((size_t)1 << (bits + 1)) <= avg_size) bits++;
+    uint64_t mask_strict = cdc_mask(bits + 1);
+    uint64_t mask_loose = cdc_mask(bits > 0 ? bits - 1 : 0);
+
+    size_t count = 0;
+    size_t start = 0;
+    while (start < len) {
+        size_t remaining = len - start;
+        size_t n = remaining < max_size ? remaining : max_size;
+        size_t cut = n;
+        if (n > min_size) {
+            size_t normal = n < avg_size ? n : avg_size;
+            uint64_t h = 0;
+            size_t i = min_size;
+            for (; i < normal; i++) {
+                h = (h << 1) + gear[p[start + i]];
+                if (!(h & mask_strict)) { cut = i + 1; goto found; }
+            }
+            for (; i < n; i++) {
+                h = (h << 1) + gear[p[start + i]];
+                if (!(h & mask_loose)) { cut = i + 1; goto found; }
+            }
+        }
+    found:
+        start += cut;
+        cuts[count++] = start;
+    }
+    return count;
+}
+
+/* =========================================================================
+ * CRC-32C (Castagnoli) — slicing-by-8
+ *
+ * Eight tables let the loop fold eight bytes per step. Like the gear
+ * table they are rebuilt per call, which costs a few thousand operations
+ * against buffers of hundreds of kilobytes.
+ * ========================================================================= */
+
+static void crc32c_tables(uint32_t table[8][256]) {
+    for (uint32_t i = 0; i < 256; i++) {
+        uint32_t c = i;
+        for (int k = 0; k < 8; k++)
+            c = (c >> 1) ^ (0x82f63b78u & (0u - (c & 1)));
+        table[0][i] = c;
+    }
+    for (uint32_t i = 0; i < 256; i++)
+        for (int t = 1; t < 8; t++)
+            table[t][i] = (table[t - 1][i] >> 8) ^ table[0][table[t - 1][i] & 0xff];
+}
+
+uint32_t komparu_crc32c(uint32_t crc, const void *buf, size_t len) {
+    uint32_t table[8][256];
+    crc32c_tables(table);
+    const unsigned char *p = buf;
+    crc = ~crc;
+    while (len >= 8) {
+        uint32_t lo = ((uint32_t)p[0] | (uint32_t)p[1] << 8
+                       | (uint32_t)p[2] << 16 | (uint32_t)p[3] << 24) ^ crc;
+        crc = table[7][lo & 0xff] ^ table[6][(lo >> 8) & 0xff]
+              ^ table[5][(lo >> 16) & 0xff] ^ table[4][lo >> 24]
+              ^ table[3][p[4]] ^ table[2][p[5]]
+              ^ table[1][p[6]] ^ table[0][p[7]];
+        p += 8;
+        len -= 8;
+    }
+    while (len--)
+        crc = (crc >> 8) ^ table[0][(crc ^ *p++) & 0xff];
+    return ~crc;
+}
+
+/* =========================================================================
+ * rsync rolling checksum
+ *
+ * s = a + 2^16 b over an n-byte window, a the byte sum and b the sum of
+ * (n - i) * x[i], both mod 2^16 (rsync's get_checksum1 without its char
+ * offset). Sliding the window by a byte takes two updates. A 2^16-bit
+ * filter keyed on the folded checksum keeps the binary search off the
+ * common no-match path.
+ * ========================================================================= */
+
+static uint32_t rsum(const unsigned char *p, size_t n, uint32_t *a_out, uint32_t *b_out) {
+    uint32_t a = 0, b = 0;
+    for (size_t i = 0; i < n; i++) {
+        a += p[i];
+        b += (uint32_t)(n - i) * p[i];
+    }
+    *a_out = a & 0xffff;
+    *b_out = b & 0xffff;
+    return *a_out | *b_out << 16;
+}
+
+void komparu_rsum_blocks(const void *buf, size_t len, size_t block_size, uint32_t *sums) {
+    const unsigned char *p = buf;
+    uint32_t a, b;
+    for (size_t i = 0; i + block_size <= len; i += block_size)
+        sums[i / block_size] = rsum(p + i, block_size, &a, &b);
+}
+
+static bool rsum_known(uint32_t s, const uint32_t *sorted, size_t count) {
+    size_t lo = 0, hi = count;
+    while (lo < hi) {
+        size_t mid = lo + (hi - lo) / 2;
+        if (sorted[mid] < s) lo = mid + 1;
+        else hi = mid;
+    }
+    return lo < count && sorted[lo] == s;
+}
+
+size_t komparu_rsum_scan(const void *buf, size_t len, size_t start, size_t block_size,
+                         const uint32_t *sorted, size_t count, size_t *hits) {
+    const unsigned char *p = buf;
+    uint8_t filter[65536 / 8] = {0};
+    for (size_t i = 0; i < count; i++) {
+        uint32_t key = (sorted[i] ^ sorted[i] >> 16) & 0xffff;
+        filter[key >> 3] |= (uint8_t)(1u << (key & 7));
+    }
+
+    size_t nhits = 0;
+    size_t pos = start;
+    while (count && pos + block_size <= len) {
+        uint32_t a, b;
+        uint32_t s = rsum(p + pos, block_size, &a, &b);
+        for (;;) {
+            uint32_t key = (s ^ s >> 16) & 0xffff;
+            if ((filter[key >> 3] >> (key & 7) & 1) && rsum_known(s, sorted, count)) {
+                hits[nhits++] = pos;
+                pos += block_size;
+                break;
+            }
+            if (pos + block_size >= len) {
+                pos = len;
+                break;
+            }
+            unsigned char out = p[pos], in = p[pos + block_size];
+            a = (a - out + in) & 0xffff;
+            b = (b - (uint32_t)block_size * out + a) & 0xffff;
+            s = a | b << 16;
+            pos++;
+        }
+    }
+    return nhits;
+}
+
 /* =========================================================================
  * Directory / archive comparison result helpers
  * ========================================================================= */
@@ -7,16 +7,11 @@
 #ifndef KOMPARU_COMPARE_H
 #define KOMPARU_COMPARE_H
 
+#include "komparu.h"
 #include "reader.h"
+#include "cancel.h"
 
-/**
- * Comparison result codes.
- */
-typedef enum {
-    KOMPARU_EQUAL     =  1,   /* Sources are byte-identical */
-    KOMPARU_DIFFERENT =  0,   /* Sources differ */
-    KOMPARU_ERROR     = -1,   /* Error during comparison */
-} komparu_result_t;
+/* komparu_result_t, KOMPARU_DIFF_* and komparu_entry_fn live in komparu.h */
 
 /**
  * Compare two readers byte-by-byte in chunks.
@@ -24,6 +19,9 @@ typedef enum {
  * chunk_size: read buffer size (default: KOMPARU_DEFAULT_CHUNK_SIZE).
  * size_precheck: if true, compare get_size() first as fast path.
  *
+ * Both readers must be at offset 0. Ranges that are a hole in both
+ * (reader extent()) are skipped rather than read.
+ *
  * Returns KOMPARU_EQUAL, KOMPARU_DIFFERENT, or KOMPARU_ERROR.
  * On error, sets *err_msg.
  */
@@ -35,6 +33,20 @@ komparu_result_t komparu_compare(
     const char **err_msg
 );
 
+/**
+ * komparu_compare() with cancellation: cancel is checked before every
+ * chunk. Returns KOMPARU_ERROR with *err_msg = KOMPARU_CANCELLED_MSG
+ * once it is set. cancel may be NULL.
+ */
+komparu_result_t komparu_compare_ex(
+    komparu_reader_t *reader_a,
+    komparu_reader_t *reader_b,
+    size_t chunk_size,
+    bool size_precheck,
+    const komparu_cancel_t *cancel,
+    const char **err_msg
+);
+
 /**
  * Quick check: sample up to 5 offsets (start, end, 25%, 50%, 75%) before full scan.
  * Only works if both readers support seek.
@@ -52,21 +64,90 @@ komparu_result_t komparu_quick_check(
 );
 
 /**
- * Free thread-local comparison buffers.
- * Call from worker threads before exit to prevent leaks.
+ * Offset of the first byte at which two buffers differ, or -1 if they are
+ * identical. When one buffer is a prefix of the other, the shorter length
+ * is returned.
+ */
+int64_t komparu_mem_first_diff(const void *a, size_t len_a,
+                               const void *b, size_t len_b);
+
+/**
+ * Number of block_size blocks (over the longer buffer) whose contents
+ * differ. Blocks past the end of the shorter buffer count as differing.
+ * block_size must be > 0.
+ */
+size_t komparu_mem_diff_blocks(const void *a, size_t len_a,
+                               const void *b, size_t len_b,
+                               size_t block_size);
+
+/* Characters in a fuzzy digest at the base block size; the digest at twice
+ * the block size holds half as many */
+#define KOMPARU_FUZZY_DIGEST_MAX 64
+
+/**
+ * spamsum-style fuzzy digests of a buffer at block_size and 2 * block_size.
+ * digest1 needs KOMPARU_FUZZY_DIGEST_MAX + 1 bytes and digest2
+ * KOMPARU_FUZZY_DIGEST_MAX / 2 + 1; both are NUL-terminated.
+ * block_size must be > 0.
+ */
+void komparu_mem_fuzzy_digest(const void *buf, size_t len, uint32_t block_size,
+                              char *digest1, char *digest2);
+
+/**
+ * Content-defined chunk boundaries of a buffer (FastCDC): writes the end
+ * offset of each chunk to cuts, which needs room for len / min_size + 1
+ * entries, and returns their number. Chunks are min_size to max_size
+ * bytes, except a shorter last one, and average about avg_size.
+ * Requires 0 < min_size <= avg_size <= max_size.
+ */
+size_t komparu_mem_cdc(const void *buf, size_t len,
+                       size_t min_size, size_t avg_size, size_t max_size,
+                       size_t *cuts);
+
+/**
+ * CRC-32C (Castagnoli, as in iSCSI, ext4 and GCS) of a buffer, continuing
+ * from crc; start with 0.
+ */
+uint32_t komparu_crc32c(uint32_t crc, const void *buf, size_t len);
+
+/**
+ * rsync's weak checksum of each full block_size block of a buffer, written
+ * to sums (len / block_size entries). block_size must be > 0.
+ */
+void komparu_rsum_blocks(const void *buf, size_t len, size_t block_size, uint32_t *sums);
+
+/**
+ * Scan a buffer from start with a rolling rsync checksum over block_size
+ * windows, and write to hits the offset of each window whose checksum is
+ * in sorted (count ascending entries); after a hit the scan resumes past
+ * that window, as rsync's does after a match. hits needs room for
+ * (len - start) / block_size + 1 entries. Returns the number of hits.
+ */
+size_t komparu_rsum_scan(const void *buf, size_t len, size_t start, size_t block_size,
+                         const uint32_t *sorted, size_t count, size_t *hits);
+
+/* Upper bounds on comparison buffers parked between threads */
+#define KOMPARU_SPARE_BUFFERS_MAX 64
+#define KOMPARU_SPARE_BUFFERS_MAX_BYTES (64u * 1024u * 1024u)
+
+/**
+ * Release this thread's comparison buffers.
+ * Call from worker threads before exit. The pair is parked for reuse by
+ * the next thread that compares (within the bounds above), else freed.
  * Safe to call even if buffers were never allocated.
  */
 void komparu_compare_tls_cleanup(void);
 
+/**
+ * Free every parked buffer pair. Call at process or library shutdown,
+ * after the last worker has exited.
+ */
+void komparu_compare_buffers_drain(void);
+
 /* =========================================================================
  * Directory / archive comparison result
  * ========================================================================= */
 
-/* Diff reasons */
-#define KOMPARU_DIFF_CONTENT    0
-#define KOMPARU_DIFF_SIZE       1
-#define KOMPARU_DIFF_READ_ERROR 2
-
 typedef struct {
     char *path;
     int reason;
@@ -100,4 +181,26 @@ int komparu_dir_result_add_only_left(komparu_dir_result_t *r, const char *path);
 int komparu_dir_result_add_only_right(komparu_dir_result_t *r, const char *path);
 int komparu_dir_result_add_error(komparu_dir_result_t *r, const char *path);
 
+/* =========================================================================
+ * Per-entry hooks — observe directory comparison as it progresses
+ * ========================================================================= */
+
+/** Called when a common file's comparison begins (pool worker thread). */
+typedef void (*komparu_start_fn)(void *user, const char *rel_path);
+
+/**
+ * Called as the sorted merge enters or leaves a subdirectory (walking
+ * thread). Brackets the only-left/only-right entries under it; common
+ * files under it are compared after the merge completes.
+ */
+typedef void (*komparu_dir_fn)(void *user, bool entered, const char *rel_path);
+
+/* Any callback may be NULL. */
+typedef struct {
+    komparu_entry_fn on_entry;
+    komparu_start_fn on_start;
+    komparu_dir_fn on_dir;
+    void *user;
+} komparu_dir_hooks_t;
+
 #endif /* KOMPARU_COMPARE_H */
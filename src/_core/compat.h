@@ -6,17 +6,26 @@
  * - Free-threaded build detection (Py_GIL_DISABLED)
  * - GIL release/acquire macros
  * - Platform-specific includes
+ *
+ * KOMPARU_NO_PYTHON drops everything Python-specific so the engine can be
+ * built as the standalone libkomparu (see komparu.h).
  */
 
 #ifndef KOMPARU_COMPAT_H
 #define KOMPARU_COMPAT_H
 
+#ifndef KOMPARU_NO_PYTHON
 #define PY_SSIZE_T_CLEAN
 #include <Python.h>
+#endif
 
 #include <stdint.h>
 #include <stdbool.h>
 #include <stddef.h>
+#include <stdlib.h>
+#include <limits.h>
+
+#ifndef KOMPARU_NO_PYTHON
 
 /* =========================================================================
  * Python version detection
@@ -53,6 +62,8 @@
     #define KOMPARU_GIL_ACQUIRE()    PyEval_RestoreThread(_komparu_save);
 #endif
 
+#endif /* KOMPARU_NO_PYTHON */
+
 /* =========================================================================
  * Platform detection
  * ========================================================================= */
@@ -74,6 +85,10 @@
     #include <winsock2.h>
     #include <ws2tcpip.h>
     #include <windows.h>
+    /* Directory handles are Unix-only; the sentinel keeps *_at() APIs portable */
+    #ifndef AT_FDCWD
+        #define AT_FDCWD (-100)
+    #endif
 #else
     #include <unistd.h>
     #include <sys/mman.h>
@@ -84,6 +99,23 @@
     #include <pthread.h>
 #endif
 
+/* =========================================================================
+ * Error classification
+ *
+ * Readers and walkers leave errno set when they fail so the binding can
+ * raise a typed exception (see raise_source_error in module.c):
+ *   ENOENT/ENOTDIR  source not found      EACCES/EPERM  permission denied
+ *   ESTALE          source vanished       ENOTSUP       unsupported source
+ *   ETIMEDOUT       HTTP timeout          EFBIG         archive bomb limit
+ *   ECANCELED       cancelled via token   anything else generic I/O error
+ * ========================================================================= */
+
+#include <errno.h>
+
+#ifndef ESTALE
+    #define ESTALE 116
+#endif
+
 /* =========================================================================
  * CPU count
  * ========================================================================= */
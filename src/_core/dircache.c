@@ -0,0 +1,254 @@
+/**
+ * dircache.c — Mutex-protected chained hash map of equal file pairs.
+ */
+
+#include "dircache.h"
+#include <stdatomic.h>
+#include <string.h>
+#include <time.h>
+
+#ifdef KOMPARU_WINDOWS
+typedef SRWLOCK cache_lock_t;
+#define CACHE_LOCK_INIT(l)    InitializeSRWLock(l)
+#define CACHE_LOCK(l)         AcquireSRWLockExclusive(l)
+#define CACHE_UNLOCK(l)       ReleaseSRWLockExclusive(l)
+#define CACHE_LOCK_DESTROY(l) ((void)(l))
+#else
+#include <pthread.h>
+typedef pthread_mutex_t cache_lock_t;
+#define CACHE_LOCK_INIT(l)    pthread_mutex_init(l, NULL)
+#define CACHE_LOCK(l)         pthread_mutex_lock(l)
+#define CACHE_UNLOCK(l)       pthread_mutex_unlock(l)
+#define CACHE_LOCK_DESTROY(l) pthread_mutex_destroy(l)
+#endif
+
+#define CACHE_INITIAL_BUCKETS 256
+
+typedef struct cache_entry {
+    struct cache_entry *next;
+    uint64_t hash;
+    size_t key_len;            /* path_a NUL path_b, without the final NUL */
+    komparu_file_sig_t sig_a;
+    komparu_file_sig_t sig_b;
+    char key[];
+} cache_entry_t;
+
+struct komparu_dir_cache {
+    cache_lock_t lock;
+    cache_entry_t **buckets;   /* NULL until the first store */
+    size_t bucket_count;       /* power of two */
+    size_t count;
+    size_t max_entries;        /* 0 = unbounded */
+    _Atomic uint64_t hits;
+    _Atomic int refs;
+};
+
+/* FNV-1a over path_a, a NUL separator and path_b */
+static uint64_t key_hash(const char *path_a, const char *path_b) {
+    uint64_t h = 14695981039346656037ULL;
+    for (const unsigned char *p = (const unsigned char *)path_a; *p; p++)
+        h = (h ^ *p) * 1099511628211ULL;
+    h *= 1099511628211ULL;  /* separator byte 0 */
+    for (const unsigned char *p = (const unsigned char *)path_b; *p; p++)
+        h = (h ^ *p) * 1099511628211ULL;
+    return h;
+}
+
+static bool key_equal(const cache_entry_t *e, uint64_t hash,
+                      const char *path_a, size_t len_a,
+                      const char *path_b, size_t len_b) {
+    return e->hash == hash && e->key_len == len_a + 1 + len_b &&
+           memcmp(e->key, path_a, len_a + 1) == 0 &&
+           memcmp(e->key + len_a + 1, path_b, len_b) == 0;
+}
+
+static bool sig_equal(const komparu_file_sig_t *x, const komparu_file_sig_t *y) {
+    return x->size == y->size && x->mtime_ns == y->mtime_ns &&
+           x->ctime_ns == y->ctime_ns && x->dev == y->dev && x->ino == y->ino;
+}
+
+static int64_t now_ns(void) {
+    struct timespec ts;
+    if (timespec_get(&ts, TIME_UTC) != TIME_UTC) return 0;
+    return (int64_t)ts.tv_sec * 1000000000LL + ts.tv_nsec;
+}
+
+/* Known timestamps, old enough that a further write would change them */
+static bool sig_settled(const komparu_file_sig_t *sig, int64_t now) {
+    return sig->mtime_ns != 0 && sig->ctime_ns != 0 &&
+           now - sig->mtime_ns >= KOMPARU_DIR_CACHE_RACY_NS;
+}
+
+/* Caller holds the lock. */
+static cache_entry_t **find_slot(komparu_dir_cache_t *cache, uint64_t hash,
+                                 const char *path_a, size_t len_a,
+                                 const char *path_b, size_t len_b) {
+    cache_entry_t **slot = &cache->buckets[hash & (cache->bucket_count - 1)];
+    while (*slot && !key_equal(*slot, hash, path_a, len_a, path_b, len_b))
+        slot = &(*slot)->next;
+    return slot;
+}
+
+/* Caller holds the lock. Returns 0, or -1 on OOM (table unchanged). */
+static int grow(komparu_dir_cache_t *cache) {
+    size_t new_count = cache->bucket_count ? cache->bucket_count * 2
+                                           : CACHE_INITIAL_BUCKETS;
+    cache_entry_t **nb = calloc(new_count, sizeof(*nb));
+    if (KOMPARU_UNLIKELY(!nb)) return -1;
+    for (size_t k = 0; k < cache->bucket_count; k++) {
+        cache_entry_t *e = cache->buckets[k];
+        while (e) {
+            cache_entry_t *next = e->next;
+            size_t idx = e->hash & (new_count - 1);
+            e->next = nb[idx];
+            nb[idx] = e;
+            e = next;
+        }
+    }
+    free(cache->buckets);
+    cache->buckets = nb;
+    cache->bucket_count = new_count;
+    return 0;
+}
+
+/* Caller holds the lock. */
+static void drop_all(komparu_dir_cache_t *cache) {
+    for (size_t k = 0; k < cache->bucket_count; k++) {
+        cache_entry_t *e = cache->buckets[k];
+        while (e) {
+            cache_entry_t *next = e->next;
+            free(e);
+            e = next;
+        }
+        cache->buckets[k] = NULL;
+    }
+    cache->count = 0;
+}
+
+komparu_dir_cache_t *komparu_dir_cache_new(size_t max_entries) {
+    komparu_dir_cache_t *cache = calloc(1, sizeof(*cache));
+    if (KOMPARU_UNLIKELY(!cache)) return NULL;
+    CACHE_LOCK_INIT(&cache->lock);
+    cache->max_entries = max_entries;
+    atomic_init(&cache->hits, 0);
+    atomic_init(&cache->refs, 1);
+    return cache;
+}
+
+komparu_dir_cache_t *komparu_dir_cache_ref(komparu_dir_cache_t *cache) {
+    if (cache)
+        atomic_fetch_add_explicit(&cache->refs, 1, memory_order_relaxed);
+    return cache;
+}
+
+void komparu_dir_cache_unref(komparu_dir_cache_t *cache) {
+    if (!cache) return;
+    if (atomic_fetch_sub_explicit(&cache->refs, 1, memory_order_acq_rel) != 1)
+        return;
+    drop_all(cache);
+    free(cache->buckets);
+    CACHE_LOCK_DESTROY(&cache->lock);
+    free(cache);
+}
+
+bool komparu_dir_cache_hit(komparu_dir_cache_t *cache,
+                           const char *path_a, const char *path_b,
+                           const komparu_file_sig_t *sig_a,
+                           const komparu_file_sig_t *sig_b) {
+    if (!cache || sig_a->mtime_ns == 0 || sig_b->mtime_ns == 0) return false;
+    size_t len_a = strlen(path_a), len_b = strlen(path_b);
+    uint64_t hash = key_hash(path_a, path_b);
+
+    bool hit = false;
+    CACHE_LOCK(&cache->lock);
+    if (cache->count > 0) {
+        cache_entry_t *e = *find_slot(cache, hash, path_a, len_a, path_b, len_b);
+        hit = e && sig_equal(&e->sig_a, sig_a) && sig_equal(&e->sig_b, sig_b);
+    }
+    CACHE_UNLOCK(&cache->lock);
+
+    if (hit) atomic_fetch_add_explicit(&cache->hits, 1, memory_order_relaxed);
+    return hit;
+}
+
+void komparu_dir_cache_store(komparu_dir_cache_t *cache,
+                             const char *path_a, const char *path_b,
+                             const komparu_file_sig_t *sig_a,
+                             const komparu_file_sig_t *sig_b) {
+    if (!cache) return;
+    int64_t now = now_ns();
+    if (!sig_settled(sig_a, now) || !sig_settled(sig_b, now)) {
+        komparu_dir_cache_forget(cache, path_a, path_b);
+        return;
+    }
+    size_t len_a = strlen(path_a), len_b = strlen(path_b);
+    uint64_t hash = key_hash(path_a, path_b);
+
+    CACHE_LOCK(&cache->lock);
+    if (cache->bucket_count == 0 && grow(cache) != 0) goto out;
+
+    cache_entry_t **slot = find_slot(cache, hash, path_a, len_a, path_b, len_b);
+    if (*slot) {
+        (*slot)->sig_a = *sig_a;
+        (*slot)->sig_b = *sig_b;
+        goto out;
+    }
+    if (cache->max_entries && cache->count >= cache->max_entries) goto out;
+
+    cache_entry_t *e = malloc(sizeof(*e) + len_a + 1 + len_b + 1);
+    if (KOMPARU_UNLIKELY(!e)) goto out;
+    e->hash = hash;
+    e->key_len = len_a + 1 + len_b;
+    e->sig_a = *sig_a;
+    e->sig_b = *sig_b;
+    memcpy(e->key, path_a, len_a + 1);
+    memcpy(e->key + len_a + 1, path_b, len_b + 1);
+    e->next = NULL;
+    *slot = e;
+    cache->count++;
+
+    /* Keep chains short; a failed grow only costs lookup speed */
+    if (cache->count > cache->bucket_count)
+        (void)grow(cache);
+out:
+    CACHE_UNLOCK(&cache->lock);
+}
+
+void komparu_dir_cache_forget(komparu_dir_cache_t *cache,
+                              const char *path_a, const char *path_b) {
+    if (!cache) return;
+    size_t len_a = strlen(path_a), len_b = strlen(path_b);
+    uint64_t hash = key_hash(path_a, path_b);
+
+    CACHE_LOCK(&cache->lock);
+    if (cache->count > 0) {
+        cache_entry_t **slot = find_slot(cache, hash, path_a, len_a, path_b, len_b);
+        cache_entry_t *e = *slot;
+        if (e) {
+            *slot = e->next;
+            free(e);
+            cache->count--;
+        }
+    }
+    CACHE_UNLOCK(&cache->lock);
+}
+
+void komparu_dir_cache_clear(komparu_dir_cache_t *cache) {
+    if (!cache) return;
+    CACHE_LOCK(&cache->lock);
+    drop_all(cache);
+    atomic_store_explicit(&cache->hits, 0, memory_order_relaxed);
+    CACHE_UNLOCK(&cache->lock);
+}
+
+size_t komparu_dir_cache_size(komparu_dir_cache_t *cache) {
+    if (!cache) return 0;
+    CACHE_LOCK(&cache->lock);
+    size_t n = cache->count;
+    CACHE_UNLOCK(&cache->lock);
+    return n;
+}
+
+uint64_t komparu_dir_cache_hits(komparu_dir_cache_t *cache) {
+    return cache ? atomic_load_explicit(&cache->hits, memory_order_relaxed) : 0;
+}
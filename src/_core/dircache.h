@@ -0,0 +1,84 @@
+/**
+ * dircache.h — Warm cache of file pairs already found equal.
+ *
+ * Lets repeated directory comparisons of the same trees skip files that
+ * have not changed since the last run. Each equal pair is remembered with
+ * the metadata signature of both files (size, mtime, ctime, dev, ino);
+ * a later comparison whose stat results match the stored signatures
+ * settles the pair as equal without opening it. Any change to either
+ * file — a write, a touch, a replace by rename — changes the signature.
+ *
+ * Reference-counted like komparu_cancel_t: the Python DirCache holds one
+ * reference, every task using the cache holds another. All operations
+ * are thread-safe.
+ *
+ * Files modified within KOMPARU_DIR_CACHE_RACY_NS of the store are not
+ * remembered: a second write in the same timestamp tick would leave the
+ * signature unchanged.
+ */
+
+#ifndef KOMPARU_DIRCACHE_H
+#define KOMPARU_DIRCACHE_H
+
+#include "compat.h"
+
+/** Files whose mtime is newer than this (ns before now) are not cached. */
+#define KOMPARU_DIR_CACHE_RACY_NS (2LL * 1000000000LL)
+
+/** Metadata signature of one side of a cached pair. */
+typedef struct {
+    int64_t size;
+    int64_t mtime_ns;   /* 0 = unknown: never cached */
+    int64_t ctime_ns;
+    uint64_t dev;
+    uint64_t ino;
+} komparu_file_sig_t;
+
+typedef struct komparu_dir_cache komparu_dir_cache_t;
+
+/**
+ * Create an empty cache with one reference. max_entries caps the number
+ * of remembered pairs (0 = unbounded); once full, new pairs are dropped.
+ * Returns NULL on OOM.
+ */
+komparu_dir_cache_t *komparu_dir_cache_new(size_t max_entries);
+
+/** Take an extra reference (NULL-safe). Returns the cache. */
+komparu_dir_cache_t *komparu_dir_cache_ref(komparu_dir_cache_t *cache);
+
+/** Drop a reference; frees on last (NULL-safe). */
+void komparu_dir_cache_unref(komparu_dir_cache_t *cache);
+
+/**
+ * True if (path_a, path_b) was stored with exactly these signatures.
+ * NULL cache never hits.
+ */
+bool komparu_dir_cache_hit(komparu_dir_cache_t *cache,
+                           const char *path_a, const char *path_b,
+                           const komparu_file_sig_t *sig_a,
+                           const komparu_file_sig_t *sig_b);
+
+/**
+ * Remember (path_a, path_b) as equal with these signatures, replacing any
+ * previous entry. Skipped when a timestamp is unknown, a file is too
+ * recent (see KOMPARU_DIR_CACHE_RACY_NS), the cache is full or on OOM.
+ */
+void komparu_dir_cache_store(komparu_dir_cache_t *cache,
+                             const char *path_a, const char *path_b,
+                             const komparu_file_sig_t *sig_a,
+                             const komparu_file_sig_t *sig_b);
+
+/** Drop the entry for (path_a, path_b), if any. */
+void komparu_dir_cache_forget(komparu_dir_cache_t *cache,
+                              const char *path_a, const char *path_b);
+
+/** Drop every entry and reset the hit counter. */
+void komparu_dir_cache_clear(komparu_dir_cache_t *cache);
+
+/** Number of remembered pairs. */
+size_t komparu_dir_cache_size(komparu_dir_cache_t *cache);
+
+/** Comparisons settled by the cache since creation or the last clear. */
+uint64_t komparu_dir_cache_hits(komparu_dir_cache_t *cache);
+
+#endif /* KOMPARU_DIRCACHE_H */
@@ -5,6 +5,11 @@
  * full path resolution on each stat). Produces sorted pathlist
  * for deterministic merge-comparison.
  *
+ * Directory comparison walks and reads through komparu_vfs_t, with the
+ * local filesystem as the default backend. Without follow_symlinks, local
+ * trees are read through a descriptor of each root, resolving every file
+ * beneath it with no symlink followed on the way.
+ *
  * Parallel mode: when max_workers > 1, file comparisons are
  * submitted to a thread pool. Each task is independent (own readers).
  */
@@ -12,8 +17,11 @@
 #include "dirwalk.h"
 #include "compare.h"
 #include "reader_file.h"
+#ifndef KOMPARU_NO_HTTP
 #include "reader_http.h"
+#endif
 #include "pool.h"
+#include "fdlimit.h"
 #include <stdlib.h>
 #include <string.h>
 #include <errno.h>
@@ -70,7 +78,7 @@ static void arena_free(komparu_arena_t *arena) {
  * Pathlist helpers
  * ========================================================================= */
 
-static int pathlist_append(komparu_pathlist_t *list, const char *path, const char **err_msg) {
+int komparu_pathlist_append(komparu_pathlist_t *list, const char *path, const char **err_msg) {
     if (list->count >= list->capacity) {
         size_t new_cap = list->capacity ? list->capacity * 2 : 256;
         char **tmp = realloc(list->paths, new_cap * sizeof(char *));
@@ -203,42 +211,175 @@ static int devino_set_check_and_add(devino_set_t *set, dev_t dev, ino_t ino) {
 }
 
 /* =========================================================================
- * Recursive walker — uses fd-relative operations for performance
+ * Iterative walker — an explicit stack of open directories, fd-relative
  * ========================================================================= */
 
-/* Guard against pathological directory depth (symlink cycles with
- * follow_symlinks=true, or genuinely deep trees). 256 levels of nesting
- * covers any real-world use case while preventing stack overflow. */
-#define KOMPARU_MAX_WALK_DEPTH 256
+/* Directories held open below the root. Deeper in, the walk closes the
+ * oldest and reopens it by path when it gets back there, so nesting is
+ * bounded by PATH_MAX rather than by descriptors. */
+#define KOMPARU_WALK_OPEN_DIRS 32
 
-static int walk_recursive(
-    int parent_fd,          /* consumed — fdopendir takes ownership */
-    const char *rel_prefix, /* "" for root */
-    int stat_flags,
-    int depth,
-    devino_set_t *visited,  /* tracks visited directories for loop detection */
-    komparu_pathlist_t *result,
-    komparu_pathlist_t *errors,  /* NULL = ignore permission errors */
-    const char **err_msg
-) {
-    if (KOMPARU_UNLIKELY(depth > KOMPARU_MAX_WALK_DEPTH)) {
-        close(parent_fd);
-        *err_msg = "directory tree too deep (>256 levels)";
+typedef struct {
+    DIR   *dir;   /* NULL while closed to spare its descriptor */
+    char  *rel;   /* path below the root, "" for the root itself */
+    size_t read;  /* entries read, to find the place again on reopen */
+    dev_t  dev;   /* identity to check a reopened directory against */
+    ino_t  ino;
+} walk_frame_t;
+
+typedef struct {
+    walk_frame_t *frames;     /* frames[count - 1] is being read */
+    size_t        count;
+    size_t        capacity;
+    size_t        open_from;  /* frames[1 .. open_from - 1] are closed */
+    devino_set_t  visited;    /* every directory entered, for loop detection */
+} walk_stack_t;
+
+static void walk_stack_free(walk_stack_t *stack) {
+    size_t open = 0;
+    for (size_t i = 0; i < stack->count; i++) {
+        if (stack->frames[i].dir) {
+            closedir(stack->frames[i].dir);
+            open++;
+        }
+        free(stack->frames[i].rel);
+    }
+    komparu_fd_release(open);
+    free(stack->frames);
+    devino_set_free(&stack->visited);
+}
+
+static void walk_pop(walk_stack_t *stack) {
+    walk_frame_t *top = &stack->frames[--stack->count];
+    closedir(top->dir);
+    free(top->rel);
+    komparu_fd_release(1);
+}
+
+/**
+ * Reopen the closed directory on top of the stack by its path from the
+ * root and skip the entries already read. One that is gone, or no longer
+ * the directory left, is dropped with its path in errors (when given):
+ * the rest of it is unread.
+ *
+ * Returns 0 when reopened or dropped, -1 on error.
+ */
+static int walk_reopen(walk_stack_t *stack, int nofollow, komparu_pathlist_t *errors,
+                       const char **err_msg) {
+    walk_frame_t *top = &stack->frames[stack->count - 1];
+    int fd = komparu_openat_read(dirfd(stack->frames[0].dir), top->rel,
+                                 O_RDONLY | O_DIRECTORY | O_CLOEXEC | nofollow);
+    struct stat st;
+    DIR *dir = NULL;
+    /* Intermediate components may have been swapped for symlinks since;
+     * the identity check rejects whatever they lead to instead */
+    if (fd >= 0 && fstat(fd, &st) == 0 && st.st_dev == top->dev && st.st_ino == top->ino)
+        dir = fdopendir(fd);
+    stack->open_from--;
+    if (KOMPARU_UNLIKELY(!dir)) {
+        if (fd >= 0) close(fd);
+        int rc = 0;
+        if (errors)
+            rc = komparu_pathlist_append(errors, top->rel, err_msg);
+        free(top->rel);
+        stack->count--;
+        return rc;
+    }
+    komparu_fd_take(1);
+    for (size_t i = 0; i < top->read && readdir(dir); i++) {}
+    top->dir = dir;
+    return 0;
+}
+
+/**
+ * Enter directory fd (consumed), identified by st, at rel. The one place
+ * that skips loops: a directory already entered — a symlink cycle, or a
+ * bind mount of an ancestor — is passed over.
+ *
+ * Returns 0 when entered, 1 when skipped, -1 on error.
+ */
+static int walk_push(walk_stack_t *stack, int fd, const struct stat *st,
+                     const char *rel, const char **err_msg) {
+    int vis = devino_set_check_and_add(&stack->visited, st->st_dev, st->st_ino);
+    if (vis == 1) {
+        close(fd);
+        return 1;
+    }
+    if (KOMPARU_UNLIKELY(vis < 0)) {
+        close(fd);
+        *err_msg = "out of memory";
         return -1;
     }
-    DIR *dir = fdopendir(parent_fd);
+    if (stack->count == stack->capacity) {
+        size_t cap = stack->capacity ? stack->capacity * 2 : 16;
+        walk_frame_t *frames = realloc(stack->frames, cap * sizeof(*frames));
+        if (KOMPARU_UNLIKELY(!frames)) {
+            close(fd);
+            *err_msg = "out of memory";
+            return -1;
+        }
+        stack->frames = frames;
+        stack->capacity = cap;
+    }
+    char *dup = strdup(rel);
+    if (KOMPARU_UNLIKELY(!dup)) {
+        close(fd);
+        *err_msg = "out of memory";
+        return -1;
+    }
+    DIR *dir = fdopendir(fd);
     if (KOMPARU_UNLIKELY(!dir)) {
-        close(parent_fd);
+        close(fd);
+        free(dup);
         komparu_strerror(errno, dirwalk_errbuf, sizeof(dirwalk_errbuf));
         *err_msg = dirwalk_errbuf;
         return -1;
     }
-    /* dir now owns parent_fd */
+    /* dir now owns fd */
+    komparu_fd_take(1);
+    if (stack->count >= stack->open_from + KOMPARU_WALK_OPEN_DIRS) {
+        walk_frame_t *oldest = &stack->frames[stack->open_from++];
+        closedir(oldest->dir);
+        oldest->dir = NULL;
+        komparu_fd_release(1);
+    }
+    stack->frames[stack->count++] = (walk_frame_t){
+        .dir = dir, .rel = dup, .dev = st->st_dev, .ino = st->st_ino};
+    return 0;
+}
 
-    int dfd = dirfd(dir);
-    struct dirent *entry;
+/* Join the directory being read and name into out; false if too long. */
+static bool walk_join(char out[PATH_MAX], const char *rel_prefix, const char *name) {
+    int len = rel_prefix[0] ? snprintf(out, PATH_MAX, "%s/%s", rel_prefix, name)
+                            : snprintf(out, PATH_MAX, "%s", name);
+    return len >= 0 && len < PATH_MAX;
+}
+
+/* Read entries off the top of the stack until it is empty, depth first. */
+static int walk_run(
+    walk_stack_t *stack,
+    int stat_flags,
+    komparu_pathlist_t *result,
+    komparu_pathlist_t *errors,  /* NULL = ignore permission errors */
+    const char **err_msg
+) {
+    char rel_path[PATH_MAX];
+    /* Not following symlinks: one swapped in since fstatat is not either */
+    int nofollow = stat_flags & AT_SYMLINK_NOFOLLOW ? O_NOFOLLOW : 0;
+    while (stack->count > 0) {
+        walk_frame_t *top = &stack->frames[stack->count - 1];
+        if (!top->dir) {
+            if (KOMPARU_UNLIKELY(walk_reopen(stack, nofollow, errors, err_msg) != 0))
+                return -1;
+            continue;
+        }
+        struct dirent *entry = readdir(top->dir);
+        if (!entry) {
+            walk_pop(stack);
+            continue;
+        }
+        top->read++;
 
-    while ((entry = readdir(dir)) != NULL) {
         /* Skip . and .. */
         const char *name = entry->d_name;
         if (name[0] == '.') {
@@ -246,82 +387,48 @@ static int walk_recursive(
             if (name[1] == '.' && name[2] == '\0') continue;
         }
 
+        int dfd = dirfd(top->dir);
+        bool fits = walk_join(rel_path, top->rel, name);
         struct stat st;
         if (KOMPARU_UNLIKELY(fstatat(dfd, name, &st, stat_flags) != 0)) {
-            if (errors && (errno == EACCES || errno == EPERM)) {
-                /* Build relative path for the error entry */
-                char err_path[PATH_MAX];
-                int elen;
-                if (rel_prefix[0]) {
-                    elen = snprintf(err_path, sizeof(err_path), "%s/%s", rel_prefix, name);
-                } else {
-                    elen = snprintf(err_path, sizeof(err_path), "%s", name);
-                }
-                if (elen >= 0 && (size_t)elen < sizeof(err_path)) {
-                    if (KOMPARU_UNLIKELY(pathlist_append(errors, err_path, err_msg) != 0)) {
-                        closedir(dir);
-                        return -1;
-                    }
-                }
+            if (errors && (errno == EACCES || errno == EPERM) && fits) {
+                if (KOMPARU_UNLIKELY(komparu_pathlist_append(errors, rel_path, err_msg) != 0))
+                    return -1;
             }
             continue;
         }
-
-        /* Build relative path */
-        char rel_path[PATH_MAX];
-        int plen;
-        if (rel_prefix[0]) {
-            plen = snprintf(rel_path, sizeof(rel_path), "%s/%s", rel_prefix, name);
-        } else {
-            plen = snprintf(rel_path, sizeof(rel_path), "%s", name);
-        }
-        if (KOMPARU_UNLIKELY(plen < 0 || (size_t)plen >= sizeof(rel_path)))
+        if (KOMPARU_UNLIKELY(!fits))
             continue; /* path too long — skip */
 
         if (S_ISREG(st.st_mode)) {
-            if (KOMPARU_UNLIKELY(pathlist_append(result, rel_path, err_msg) != 0)) {
-                closedir(dir);
+            if (KOMPARU_UNLIKELY(komparu_pathlist_append(result, rel_path, err_msg) != 0))
                 return -1;
-            }
         } else if (S_ISDIR(st.st_mode)) {
-            int sub_fd = openat(dfd, name, O_RDONLY | O_DIRECTORY | O_CLOEXEC);
+            int sub_fd = komparu_openat_read(dfd, name,
+                                             O_RDONLY | O_DIRECTORY | O_CLOEXEC | nofollow);
             if (KOMPARU_UNLIKELY(sub_fd < 0)) {
                 if (errors && (errno == EACCES || errno == EPERM)) {
-                    if (KOMPARU_UNLIKELY(pathlist_append(errors, rel_path, err_msg) != 0)) {
-                        closedir(dir);
+                    if (KOMPARU_UNLIKELY(komparu_pathlist_append(errors, rel_path, err_msg) != 0))
                         return -1;
-                    }
                 }
                 continue;
             }
 
-            /* Symlink loop detection: check (dev, ino) of this directory */
             struct stat dir_st;
             if (KOMPARU_UNLIKELY(fstat(sub_fd, &dir_st) != 0)) {
                 close(sub_fd);
                 continue;
             }
-            int vis = devino_set_check_and_add(visited, dir_st.st_dev, dir_st.st_ino);
-            if (vis == 1) {
-                /* Already visited — symlink loop, skip silently */
+            if (KOMPARU_UNLIKELY(dir_st.st_dev != st.st_dev || dir_st.st_ino != st.st_ino)) {
+                /* Replaced between fstatat and openat — not the entry listed */
                 close(sub_fd);
                 continue;
             }
-            if (KOMPARU_UNLIKELY(vis < 0)) {
-                close(sub_fd);
-                closedir(dir);
-                *err_msg = "out of memory";
-                return -1;
-            }
-
-            if (KOMPARU_UNLIKELY(walk_recursive(sub_fd, rel_path, stat_flags, depth + 1, visited, result, errors, err_msg) != 0)) {
-                closedir(dir);
+            /* top is stale after this: the push may move the frames */
+            if (KOMPARU_UNLIKELY(walk_push(stack, sub_fd, &dir_st, rel_path, err_msg) < 0))
                 return -1;
-            }
         }
     }
-
-    closedir(dir);
     return 0;
 }
 
@@ -331,47 +438,57 @@ int komparu_dirwalk(
     komparu_pathlist_t *result,
     komparu_pathlist_t *errors,
     const char **err_msg
+) {
+    return komparu_dirwalk_at(AT_FDCWD, base_dir, follow_symlinks,
+                              result, errors, err_msg);
+}
+
+int komparu_dirwalk_at(
+    int dirfd,
+    const char *base_dir,
+    bool follow_symlinks,
+    komparu_pathlist_t *result,
+    komparu_pathlist_t *errors,
+    const char **err_msg
 ) {
     memset(result, 0, sizeof(*result));
     if (errors) memset(errors, 0, sizeof(*errors));
 
-    int fd = open(base_dir, O_RDONLY | O_DIRECTORY | O_CLOEXEC);
+    int fd = komparu_openat_read(dirfd, base_dir, O_RDONLY | O_DIRECTORY | O_CLOEXEC);
     if (KOMPARU_UNLIKELY(fd < 0)) {
         komparu_strerror(errno, dirwalk_errbuf, sizeof(dirwalk_errbuf));
         *err_msg = dirwalk_errbuf;
         return -1;
     }
 
-    /* Initialize visited set for symlink loop detection */
-    devino_set_t visited;
-    if (KOMPARU_UNLIKELY(devino_set_init(&visited) != 0)) {
+    walk_stack_t stack = {.open_from = 1};
+    if (KOMPARU_UNLIKELY(devino_set_init(&stack.visited) != 0)) {
         close(fd);
         *err_msg = "out of memory";
         return -1;
     }
 
-    /* Register the root directory so we detect cycles back to it */
+    /* The root is entered like any directory, so cycles back to it stop */
     struct stat root_st;
-    if (KOMPARU_UNLIKELY(fstat(fd, &root_st) != 0)) {
+    int rc = fstat(fd, &root_st);
+    if (KOMPARU_UNLIKELY(rc != 0)) {
         komparu_strerror(errno, dirwalk_errbuf, sizeof(dirwalk_errbuf));
         *err_msg = dirwalk_errbuf;
-        devino_set_free(&visited);
         close(fd);
-        return -1;
+    } else {
+        rc = walk_push(&stack, fd, &root_st, "", err_msg);
     }
-    devino_set_check_and_add(&visited, root_st.st_dev, root_st.st_ino);
 
     int stat_flags = follow_symlinks ? 0 : AT_SYMLINK_NOFOLLOW;
-
-    if (KOMPARU_UNLIKELY(walk_recursive(fd, "", stat_flags, 0, &visited, result, errors, err_msg) != 0)) {
-        devino_set_free(&visited);
+    if (rc == 0)
+        rc = walk_run(&stack, stat_flags, result, errors, err_msg);
+    walk_stack_free(&stack);
+    if (KOMPARU_UNLIKELY(rc != 0)) {
         komparu_pathlist_free(result);
         if (errors) komparu_pathlist_free(errors);
         return -1;
     }
 
-    devino_set_free(&visited);
-
     /* Sort for deterministic merge comparison */
     if (result->count > 1) {
         qsort(result->paths, result->count, sizeof(char *), path_cmp);
@@ -380,11 +497,200 @@ int komparu_dirwalk(
     return 0;
 }
 
+/* =========================================================================
+ * Local filesystem backend
+ * ========================================================================= */
+
+#ifdef KOMPARU_MACOS
+#define ST_MTIM(st) ((st)->st_mtimespec)
+#define ST_CTIM(st) ((st)->st_ctimespec)
+#else
+#define ST_MTIM(st) ((st)->st_mtim)
+#define ST_CTIM(st) ((st)->st_ctim)
+#endif
+
+static inline int64_t timespec_ns(struct timespec ts) {
+    return (int64_t)ts.tv_sec * 1000000000LL + ts.tv_nsec;
+}
+
+static void stat_fill(const struct stat *st, komparu_vfs_stat_t *out) {
+    out->size = (int64_t)st->st_size;
+    out->dev = (uint64_t)st->st_dev;
+    out->ino = (uint64_t)st->st_ino;
+    out->mtime_ns = timespec_ns(ST_MTIM(st));
+    out->ctime_ns = timespec_ns(ST_CTIM(st));
+}
+
+static int local_list(const komparu_vfs_t *self, const char *root,
+                      bool follow_symlinks, komparu_pathlist_t *result,
+                      komparu_pathlist_t *errors, const char **err_msg) {
+    (void)self;
+    return komparu_dirwalk(root, follow_symlinks, result, errors, err_msg);
+}
+
+static int local_stat(const komparu_vfs_t *self, const char *path,
+                      komparu_vfs_stat_t *out) {
+    (void)self;
+#ifndef KOMPARU_WINDOWS
+    struct stat st;
+    if (stat(path, &st) != 0) return -1;
+    stat_fill(&st, out);
+    return 0;
+#else
+    /* No (dev, ino) identity; callers fall back to opening the file */
+    (void)path;
+    (void)out;
+    errno = ENOTSUP;
+    return -1;
+#endif
+}
+
+static komparu_reader_t *local_open(const komparu_vfs_t *self, const char *path,
+                                    const char **err_msg) {
+    (void)self;
+    return komparu_reader_file_open(path, err_msg);
+}
+
+static const komparu_vfs_t local_vfs = {
+    .list = local_list,
+    .stat = local_stat,
+    .open = local_open,
+    .ctx = NULL,
+};
+
+const komparu_vfs_t *komparu_vfs_local(void) {
+    return &local_vfs;
+}
+
+/* =========================================================================
+ * Directory-descriptor backend — paths resolved with the *at() calls
+ * ========================================================================= */
+
+static int at_fd(const komparu_vfs_t *self) {
+    return (int)(intptr_t)self->ctx;
+}
+
+static int at_list(const komparu_vfs_t *self, const char *root,
+                   bool follow_symlinks, komparu_pathlist_t *result,
+                   komparu_pathlist_t *errors, const char **err_msg) {
+    return komparu_dirwalk_at(at_fd(self), root, follow_symlinks,
+                              result, errors, err_msg);
+}
+
+static int at_stat(const komparu_vfs_t *self, const char *path,
+                   komparu_vfs_stat_t *out) {
+    struct stat st;
+    if (fstatat(at_fd(self), path, &st, 0) != 0) return -1;
+    stat_fill(&st, out);
+    return 0;
+}
+
+static komparu_reader_t *at_open(const komparu_vfs_t *self, const char *path,
+                                 const char **err_msg) {
+    return komparu_reader_file_openat(at_fd(self), path, err_msg);
+}
+
+komparu_vfs_t komparu_vfs_at(int dirfd) {
+    return (komparu_vfs_t){
+        .list = at_list,
+        .stat = at_stat,
+        .open = at_open,
+        .ctx = (void *)(intptr_t)dirfd,
+    };
+}
+
+/* =========================================================================
+ * Confined backend — files resolved beneath a root descriptor, no symlinks
+ * ========================================================================= */
+
+#ifndef KOMPARU_WINDOWS
+
+#ifdef O_PATH
+#define BENEATH_DIR_FLAGS (O_PATH | O_DIRECTORY)
+#else
+#define BENEATH_DIR_FLAGS (O_RDONLY | O_DIRECTORY)
+#endif
+
+typedef struct {
+    int fd;           /* the root, opened once */
+    size_t root_len;  /* length of the root as given: paths are "root/rel" */
+} beneath_ctx_t;
+
+static const beneath_ctx_t *beneath_ctx(const komparu_vfs_t *self) {
+    return (const beneath_ctx_t *)self->ctx;
+}
+
+static const char *beneath_rel(const komparu_vfs_t *self, const char *path) {
+    return path + beneath_ctx(self)->root_len + 1;
+}
+
+static int beneath_list(const komparu_vfs_t *self, const char *root,
+                        bool follow_symlinks, komparu_pathlist_t *result,
+                        komparu_pathlist_t *errors, const char **err_msg) {
+    (void)root;
+    return komparu_dirwalk_at(beneath_ctx(self)->fd, ".", follow_symlinks,
+                              result, errors, err_msg);
+}
+
+static int beneath_stat(const komparu_vfs_t *self, const char *path,
+                        komparu_vfs_stat_t *out) {
+    int root = beneath_ctx(self)->fd;
+    const char *rel = beneath_rel(self, path);
+    const char *slash = strrchr(rel, '/');
+    int dfd = root;
+    if (slash) {
+        char parent[PATH_MAX];
+        size_t len = (size_t)(slash - rel);
+        if (len >= sizeof(parent)) {
+            errno = ENAMETOOLONG;
+            return -1;
+        }
+        memcpy(parent, rel, len);
+        parent[len] = '\0';
+        dfd = komparu_openat_beneath(root, parent, BENEATH_DIR_FLAGS);
+        if (dfd < 0) return -1;
+    }
+    struct stat st;
+    int rc = fstatat(dfd, slash ? slash + 1 : rel, &st, AT_SYMLINK_NOFOLLOW);
+    if (dfd != root) {
+        int saved = errno;
+        close(dfd);
+        errno = saved;
+    }
+    if (rc != 0) return -1;
+    stat_fill(&st, out);
+    return 0;
+}
+
+static komparu_reader_t *beneath_open(const komparu_vfs_t *self, const char *path,
+                                      const char **err_msg) {
+    return komparu_reader_file_open_beneath(beneath_ctx(self)->fd,
+                                            beneath_rel(self, path), err_msg);
+}
+
+/* The descriptor local paths of vfs are relative to; false if not local. */
+static bool vfs_local_base(const komparu_vfs_t *vfs, int *base) {
+    if (vfs == &local_vfs) {
+        *base = AT_FDCWD;
+        return true;
+    }
+    if (vfs->list == at_list) {
+        *base = at_fd(vfs);
+        return true;
+    }
+    return false;
+}
+
+#endif /* !KOMPARU_WINDOWS */
+
 /* =========================================================================
  * Per-file comparison task (used by both sequential and parallel paths)
  * ========================================================================= */
 
 typedef struct {
+    const komparu_vfs_t *vfs_a;
+    const komparu_vfs_t *vfs_b;
+    bool same_backend;  /* (dev, ino) of both sides comparable */
     char *full_path_a;
     char *full_path_b;
     char *rel_path;
@@ -392,36 +698,49 @@ typedef struct {
     bool size_precheck;
     bool quick_check;
     int result_reason;  /* -1 = equal, else KOMPARU_DIFF_* */
+    const komparu_dir_hooks_t *hooks;  /* NULL = no per-entry reporting */
+    const komparu_cancel_t *cancel;    /* NULL = not cancellable */
+    const komparu_retry_t *retry;      /* NULL = fail on first error */
+    const komparu_lock_t *lock;        /* NULL = read unlocked */
+    komparu_dir_cache_t *cache;        /* NULL = no warm cache */
 } dir_cmp_task_t;
 
-static void dir_cmp_task_exec(void *arg) {
-    dir_cmp_task_t *task = (dir_cmp_task_t *)arg;
-    task->result_reason = -1;  /* assume equal */
+/* Open path, wrapped for per-read retries when a policy is set. */
+static komparu_reader_t *dir_cmp_open(const dir_cmp_task_t *task,
+                                      const komparu_vfs_t *vfs,
+                                      const char *path, const char **err_msg) {
+    komparu_reader_t *r = vfs->open(vfs, path, err_msg);
+    if (r && task->retry)
+        r = komparu_reader_retry_wrap(r, task->retry, task->cancel);
+    return r;
+}
 
-    /* Same-file short-circuit via inode comparison */
-#ifndef KOMPARU_WINDOWS
-    {
-        struct stat sa, sb;
-        if (stat(task->full_path_a, &sa) == 0 &&
-            stat(task->full_path_b, &sb) == 0 &&
-            sa.st_dev == sb.st_dev && sa.st_ino == sb.st_ino) {
-            return;  /* same file — equal */
-        }
-    }
-#endif
+/* A file that cannot be opened: deleted since the walk listed it, or
+ * unreadable. */
+static int dir_cmp_open_failed(int *err) {
+    *err = errno;
+    return *err == ENOENT ? KOMPARU_DIFF_VANISHED : KOMPARU_DIFF_READ_ERROR;
+}
 
+/* Open both sides and compare them; see dir_cmp_task_once(). */
+static int dir_cmp_pair(const dir_cmp_task_t *task, int *err) {
     const char *cmp_err = NULL;
-    komparu_reader_t *ra = komparu_reader_file_open(task->full_path_a, &cmp_err);
-    if (KOMPARU_UNLIKELY(!ra)) {
-        task->result_reason = KOMPARU_DIFF_READ_ERROR;
-        return;
-    }
+    komparu_reader_t *ra = dir_cmp_open(task, task->vfs_a, task->full_path_a, &cmp_err);
+    if (KOMPARU_UNLIKELY(!ra)) return dir_cmp_open_failed(err);
 
-    komparu_reader_t *rb = komparu_reader_file_open(task->full_path_b, &cmp_err);
+    komparu_reader_t *rb = dir_cmp_open(task, task->vfs_b, task->full_path_b, &cmp_err);
     if (KOMPARU_UNLIKELY(!rb)) {
+        int reason = dir_cmp_open_failed(err);
         ra->close(ra);
-        task->result_reason = KOMPARU_DIFF_READ_ERROR;
-        return;
+        return reason;
+    }
+
+    int reason = -1;
+
+    /* Wait for writers holding either file to finish */
+    if (task->lock && komparu_lock_pair(ra, rb, task->lock, task->cancel) != 0) {
+        reason = KOMPARU_DIFF_LOCKED;
+        goto done;
     }
 
     /* Size pre-check */
@@ -429,10 +748,8 @@ static void dir_cmp_task_exec(void *arg) {
         int64_t sa = ra->get_size(ra);
         int64_t sb = rb->get_size(rb);
         if (sa >= 0 && sb >= 0 && sa != sb) {
-            ra->close(ra);
-            rb->close(rb);
-            task->result_reason = KOMPARU_DIFF_SIZE;
-            return;
+            reason = KOMPARU_DIFF_SIZE;
+            goto done;
         }
     }
 
@@ -440,10 +757,8 @@ static void dir_cmp_task_exec(void *arg) {
     if (task->quick_check) {
         komparu_result_t qr = komparu_quick_check(ra, rb, task->chunk_size, &cmp_err);
         if (qr == KOMPARU_DIFFERENT) {
-            ra->close(ra);
-            rb->close(rb);
-            task->result_reason = KOMPARU_DIFF_CONTENT;
-            return;
+            reason = KOMPARU_DIFF_CONTENT;
+            goto done;
         }
         if (qr == KOMPARU_ERROR && ra->seek && rb->seek) {
             ra->seek(ra, 0);
@@ -451,14 +766,207 @@ static void dir_cmp_task_exec(void *arg) {
         }
     }
 
-    komparu_result_t cr = komparu_compare(ra, rb, task->chunk_size, false, &cmp_err);
+    komparu_result_t cr = komparu_compare_ex(ra, rb, task->chunk_size, false,
+                                             task->cancel, &cmp_err);
+    if (cr == KOMPARU_DIFFERENT) {
+        reason = KOMPARU_DIFF_CONTENT;
+    } else if (cr == KOMPARU_ERROR) {
+        *err = errno;
+        reason = KOMPARU_DIFF_READ_ERROR;
+    }
+
+done:
     ra->close(ra);
     rb->close(rb);
+    return reason;
+}
+
+/* One comparison attempt, within the descriptor budget. Returns -1
+ * (equal) or KOMPARU_DIFF_*; on KOMPARU_DIFF_READ_ERROR, *err receives
+ * the errno of the failure. */
+static int dir_cmp_task_once(const dir_cmp_task_t *task, int *err) {
+    if (!komparu_fd_acquire(KOMPARU_FDS_PER_TASK, task->cancel)) {
+        *err = ECANCELED;
+        return KOMPARU_DIFF_READ_ERROR;  /* the caller discards the result */
+    }
+    int reason = dir_cmp_pair(task, err);
+    komparu_fd_release(KOMPARU_FDS_PER_TASK);
+    return reason;
+}
+
+static komparu_file_sig_t file_sig(const komparu_vfs_stat_t *st) {
+    return (komparu_file_sig_t){
+        .size = st->size, .mtime_ns = st->mtime_ns, .ctime_ns = st->ctime_ns,
+        .dev = st->dev, .ino = st->ino,
+    };
+}
+
+/* Stat both sides; false if either fails. */
+static bool dir_cmp_stat(const dir_cmp_task_t *task,
+                         komparu_vfs_stat_t *sa, komparu_vfs_stat_t *sb) {
+    *sa = (komparu_vfs_stat_t){ .size = -1 };
+    *sb = (komparu_vfs_stat_t){ .size = -1 };
+    return task->vfs_a->stat(task->vfs_a, task->full_path_a, sa) == 0 &&
+           task->vfs_b->stat(task->vfs_b, task->full_path_b, sb) == 0;
+}
+
+/* Reopens of a pair that met a full descriptor table, on top of retries */
+#define DIR_CMP_FD_REOPENS 64
+
+static void dir_cmp_task_compare(dir_cmp_task_t *task) {
+    unsigned retries = task->retry ? task->retry->retries : 0;
+    unsigned reopens = 0;
+    for (unsigned attempt = 0;; attempt++) {
+        int err = 0;
+        task->result_reason = dir_cmp_task_once(task, &err);
+        /* EMFILE: descriptors the budget does not see ran out; reopen once
+         * another worker has closed its pair */
+        while (task->result_reason == KOMPARU_DIFF_READ_ERROR &&
+               komparu_errno_fd_exhausted(err) && reopens < DIR_CMP_FD_REOPENS &&
+               komparu_fd_wait_close(0, task->cancel)) {
+            reopens++;
+            task->result_reason = dir_cmp_task_once(task, &err);
+        }
+        if (task->result_reason != KOMPARU_DIFF_READ_ERROR ||
+            !komparu_errno_transient(err))
+            return;
+        if (attempt >= retries) {
+            if (retries > 0) task->result_reason = KOMPARU_DIFF_RETRIES_EXHAUSTED;
+            return;
+        }
+        if (!komparu_retry_sleep(task->retry, attempt, task->cancel))
+            return;  /* cancelled: the caller discards the result */
+    }
+}
+
+/* A file written, truncated or replaced between the two stats. */
+static bool dir_cmp_moved(const komparu_vfs_stat_t *before,
+                          const komparu_vfs_stat_t *after) {
+    return before->size != after->size || before->mtime_ns != after->mtime_ns;
+}
+
+static void dir_cmp_task_run(dir_cmp_task_t *task) {
+    task->result_reason = -1;  /* assume equal */
+
+    komparu_vfs_stat_t sa, sb;
+    bool have_stat = dir_cmp_stat(task, &sa, &sb);
+
+    /* Same-file short-circuit via (dev, ino) identity */
+    if (have_stat && task->same_backend &&
+        (sa.dev != 0 || sa.ino != 0) &&
+        sa.dev == sb.dev && sa.ino == sb.ino) {
+        return;  /* same file — equal */
+    }
+
+    komparu_file_sig_t sig_a = {0}, sig_b = {0};
+    if (task->cache && have_stat) {
+        sig_a = file_sig(&sa);
+        sig_b = file_sig(&sb);
+        if (komparu_dir_cache_hit(task->cache, task->full_path_a,
+                                  task->full_path_b, &sig_a, &sig_b))
+            return;  /* unchanged since found equal */
+    }
 
-    if (cr == KOMPARU_DIFFERENT)
-        task->result_reason = KOMPARU_DIFF_CONTENT;
-    else if (cr == KOMPARU_ERROR)
-        task->result_reason = KOMPARU_DIFF_READ_ERROR;
+    dir_cmp_task_compare(task);
+
+    /* Whatever was found says nothing about a file that moved under the
+     * read: a copy in progress, a log being appended to, a file replaced.
+     * Gone afterwards counts as moved; gone before it was opened is
+     * KOMPARU_DIFF_VANISHED already. */
+    komparu_vfs_stat_t after_a, after_b;
+    bool have_after = have_stat && dir_cmp_stat(task, &after_a, &after_b);
+    if (have_stat && task->result_reason != KOMPARU_DIFF_VANISHED &&
+        (!have_after || dir_cmp_moved(&sa, &after_a) || dir_cmp_moved(&sb, &after_b)))
+        task->result_reason = KOMPARU_DIFF_UNSTABLE;
+
+    if (!task->cache) return;
+
+    /* Remember the pair only if neither file changed while being read */
+    if (task->result_reason < 0 && have_after) {
+        komparu_file_sig_t now_a = file_sig(&after_a), now_b = file_sig(&after_b);
+        if (memcmp(&now_a, &sig_a, sizeof(sig_a)) == 0 &&
+            memcmp(&now_b, &sig_b, sizeof(sig_b)) == 0) {
+            komparu_dir_cache_store(task->cache, task->full_path_a,
+                                    task->full_path_b, &sig_a, &sig_b);
+            return;
+        }
+    }
+    komparu_dir_cache_forget(task->cache, task->full_path_a, task->full_path_b);
+}
+
+static void dir_cmp_task_exec(void *arg) {
+    dir_cmp_task_t *task = (dir_cmp_task_t *)arg;
+    /* Cancelled: drain the queue without touching the files. The caller
+     * discards the whole result, so nothing is reported either. */
+    if (komparu_cancelled(task->cancel)) return;
+    if (task->hooks && task->hooks->on_start)
+        task->hooks->on_start(task->hooks->user, task->rel_path);
+    dir_cmp_task_run(task);
+    if (komparu_cancelled(task->cancel)) return;
+    komparu_cancel_count_entry(task->cancel);
+    if (task->hooks && task->hooks->on_entry) {
+        task->hooks->on_entry(task->hooks->user,
+            task->result_reason < 0 ? KOMPARU_ENTRY_EQUAL : KOMPARU_ENTRY_DIFF,
+            task->result_reason, task->rel_path);
+    }
+}
+
+static inline void emit_entry(const komparu_dir_hooks_t *hooks,
+                              const komparu_cancel_t *cancel,
+                              komparu_entry_kind_t kind, const char *rel_path) {
+    komparu_cancel_count_entry(cancel);
+    if (hooks && hooks->on_entry) hooks->on_entry(hooks->user, kind, -1, rel_path);
+}
+
+/* Current directory of the sorted merge, for on_dir enter/leave events */
+typedef struct {
+    char cur[PATH_MAX];
+    size_t len;
+} dir_track_t;
+
+/**
+ * Move the tracked directory to the parent of path ("" = root), emitting
+ * leave events deepest-first and then enter events outermost-first.
+ * Sorted order keeps every subtree contiguous, so events stay balanced.
+ */
+static void dir_track_to(const komparu_dir_hooks_t *hooks, dir_track_t *tr,
+                         const char *path) {
+    if (!hooks || !hooks->on_dir) return;
+
+    const char *slash = strrchr(path, '/');
+    size_t dlen = slash ? (size_t)(slash - path) : 0;
+    if (KOMPARU_UNLIKELY(dlen >= sizeof(tr->cur))) return;
+
+    /* Longest common prefix that ends on a component boundary */
+    size_t k = 0, common = 0;
+    for (;;) {
+        bool cur_end = (k == tr->len || tr->cur[k] == '/');
+        bool new_end = (k == dlen || path[k] == '/');
+        if (cur_end && new_end) common = k;
+        if (k == tr->len || k == dlen || tr->cur[k] != path[k]) break;
+        k++;
+    }
+
+    while (tr->len > common) {
+        tr->cur[tr->len] = '\0';
+        hooks->on_dir(hooks->user, false, tr->cur);
+        size_t p = tr->len;
+        while (p > common && tr->cur[p - 1] != '/') p--;
+        tr->len = p > common ? p - 1 : common;
+    }
+
+    if (dlen > common) {
+        memcpy(tr->cur, path, dlen);
+        for (k = common ? common + 1 : 0; k <= dlen; k++) {
+            if (k == dlen || path[k] == '/') {
+                char saved = tr->cur[k];
+                tr->cur[k] = '\0';
+                hooks->on_dir(hooks->user, true, tr->cur);
+                tr->cur[k] = saved;
+            }
+        }
+        tr->len = dlen;
+    }
 }
 
 /* =========================================================================
@@ -473,31 +981,84 @@ komparu_dir_result_t *komparu_compare_dirs(
     bool quick_check,
     bool follow_symlinks,
     size_t max_workers,
+    const komparu_dir_hooks_t *hooks,
+    const komparu_cancel_t *cancel,
+    const komparu_retry_t *retry,
+    const komparu_lock_t *lock,
+    komparu_dir_cache_t *cache,
     const char **err_msg
 ) {
-    /* Same-directory short-circuit: realpath both, compare strings.
-     * Catches identical paths, symlinks, and trailing-slash variants. */
-    char real_a[PATH_MAX], real_b[PATH_MAX];
-    if (realpath(dir_a, real_a) && realpath(dir_b, real_b) &&
-        strcmp(real_a, real_b) == 0) {
-        komparu_dir_result_t *r = komparu_dir_result_new();
-        if (KOMPARU_UNLIKELY(!r)) {
-            *err_msg = "out of memory";
-            return NULL;
-        }
-        return r;  /* equal=true, empty diff/only_left/only_right */
-    }
+    return komparu_compare_dirs_vfs(
+        &local_vfs, dir_a, &local_vfs, dir_b,
+        chunk_size, size_precheck, quick_check, follow_symlinks,
+        max_workers, hooks, cancel, retry, lock, cache, err_msg);
+}
+
+komparu_dir_result_t *komparu_compare_dirs_at(
+    int dirfd_a,
+    const char *dir_a,
+    int dirfd_b,
+    const char *dir_b,
+    size_t chunk_size,
+    bool size_precheck,
+    bool quick_check,
+    bool follow_symlinks,
+    size_t max_workers,
+    const komparu_dir_hooks_t *hooks,
+    const komparu_cancel_t *cancel,
+    const komparu_retry_t *retry,
+    const komparu_lock_t *lock,
+    komparu_dir_cache_t *cache,
+    const char **err_msg
+) {
+    /* One backend per distinct descriptor, so equal descriptors keep the
+     * same-file short-circuit (it requires vfs_a == vfs_b). */
+    komparu_vfs_t at_a = komparu_vfs_at(dirfd_a);
+    komparu_vfs_t at_b = komparu_vfs_at(dirfd_b);
+    const komparu_vfs_t *vfs_a = dirfd_a == AT_FDCWD ? &local_vfs : &at_a;
+    const komparu_vfs_t *vfs_b = dirfd_b == AT_FDCWD ? &local_vfs
+                               : dirfd_b == dirfd_a ? vfs_a : &at_b;
+    return komparu_compare_dirs_vfs(
+        vfs_a, dir_a, vfs_b, dir_b,
+        chunk_size, size_precheck, quick_check, follow_symlinks,
+        max_workers, hooks, cancel, retry, lock, cache, err_msg);
+}
 
+static komparu_dir_result_t *compare_dirs(
+    const komparu_vfs_t *vfs_a,
+    const char *dir_a,
+    const komparu_vfs_t *vfs_b,
+    const char *dir_b,
+    bool same_backend,
+    size_t chunk_size,
+    bool size_precheck,
+    bool quick_check,
+    bool follow_symlinks,
+    size_t max_workers,
+    const komparu_dir_hooks_t *hooks,
+    const komparu_cancel_t *cancel,
+    const komparu_retry_t *retry,
+    const komparu_lock_t *lock,
+    komparu_dir_cache_t *cache,
+    const char **err_msg
+) {
     komparu_pathlist_t paths_a = {0};
     komparu_pathlist_t paths_b = {0};
     komparu_pathlist_t errors_a = {0};
     komparu_pathlist_t errors_b = {0};
 
-    if (komparu_dirwalk(dir_a, follow_symlinks, &paths_a, &errors_a, err_msg) != 0) {
+    if (vfs_a->list(vfs_a, dir_a, follow_symlinks, &paths_a, &errors_a, err_msg) != 0) {
+        return NULL;
+    }
+    if (komparu_cancelled(cancel)) {
+        *err_msg = KOMPARU_CANCELLED_MSG;
+        errno = ECANCELED;
+        komparu_pathlist_free(&paths_a);
+        komparu_pathlist_free(&errors_a);
         return NULL;
     }
 
-    if (komparu_dirwalk(dir_b, follow_symlinks, &paths_b, &errors_b, err_msg) != 0) {
+    if (vfs_b->list(vfs_b, dir_b, follow_symlinks, &paths_b, &errors_b, err_msg) != 0) {
         komparu_pathlist_free(&paths_a);
         komparu_pathlist_free(&errors_a);
         return NULL;
@@ -515,6 +1076,7 @@ komparu_dir_result_t *komparu_compare_dirs(
 
     /* Merge permission errors from both walks into the result */
     for (size_t k = 0; k < errors_a.count; k++) {
+        emit_entry(hooks, cancel, KOMPARU_ENTRY_ERROR, errors_a.paths[k]);
         if (KOMPARU_UNLIKELY(komparu_dir_result_add_error(result, errors_a.paths[k]) != 0)) {
             *err_msg = "out of memory";
             komparu_pathlist_free(&paths_a);
@@ -526,6 +1088,7 @@ komparu_dir_result_t *komparu_compare_dirs(
         }
     }
     for (size_t k = 0; k < errors_b.count; k++) {
+        emit_entry(hooks, cancel, KOMPARU_ENTRY_ERROR, errors_b.paths[k]);
         if (KOMPARU_UNLIKELY(komparu_dir_result_add_error(result, errors_b.paths[k]) != 0)) {
             *err_msg = "out of memory";
             komparu_pathlist_free(&paths_a);
@@ -546,17 +1109,22 @@ komparu_dir_result_t *komparu_compare_dirs(
     size_t task_count = 0;
     size_t task_cap = 0;
 
+    dir_track_t track = {0};
+
     size_t i = 0, j = 0;
     while (i < paths_a.count && j < paths_b.count) {
         int cmp = strcmp(paths_a.paths[i], paths_b.paths[j]);
+        dir_track_to(hooks, &track, cmp <= 0 ? paths_a.paths[i] : paths_b.paths[j]);
 
         if (cmp < 0) {
+            emit_entry(hooks, cancel, KOMPARU_ENTRY_ONLY_LEFT, paths_a.paths[i]);
             if (KOMPARU_UNLIKELY(komparu_dir_result_add_only_left(result, paths_a.paths[i]) != 0)) {
                 *err_msg = "out of memory";
                 goto fail;
             }
             i++;
         } else if (cmp > 0) {
+            emit_entry(hooks, cancel, KOMPARU_ENTRY_ONLY_RIGHT, paths_b.paths[j]);
             if (KOMPARU_UNLIKELY(komparu_dir_result_add_only_right(result, paths_b.paths[j]) != 0)) {
                 *err_msg = "out of memory";
                 goto fail;
@@ -582,6 +1150,9 @@ komparu_dir_result_t *komparu_compare_dirs(
             size_t la = strlen(dir_a) + 1 + strlen(paths_a.paths[i]) + 1;
             size_t lb = strlen(dir_b) + 1 + strlen(paths_b.paths[j]) + 1;
 
+            t->vfs_a = vfs_a;
+            t->vfs_b = vfs_b;
+            t->same_backend = same_backend;
             t->full_path_a = malloc(la);
             t->full_path_b = malloc(lb);
             t->rel_path = strdup(paths_a.paths[i]);
@@ -600,6 +1171,11 @@ komparu_dir_result_t *komparu_compare_dirs(
             t->size_precheck = size_precheck;
             t->quick_check = quick_check;
             t->result_reason = -1;
+            t->hooks = hooks;
+            t->cancel = cancel;
+            t->retry = retry && retry->retries > 0 ? retry : NULL;
+            t->lock = lock;
+            t->cache = cache;
 
             task_count++;
             i++; j++;
@@ -607,6 +1183,8 @@ komparu_dir_result_t *komparu_compare_dirs(
     }
 
     while (i < paths_a.count) {
+        dir_track_to(hooks, &track, paths_a.paths[i]);
+        emit_entry(hooks, cancel, KOMPARU_ENTRY_ONLY_LEFT, paths_a.paths[i]);
         if (KOMPARU_UNLIKELY(komparu_dir_result_add_only_left(result, paths_a.paths[i]) != 0)) {
             *err_msg = "out of memory";
             goto fail;
@@ -615,20 +1193,25 @@ komparu_dir_result_t *komparu_compare_dirs(
     }
 
     while (j < paths_b.count) {
+        dir_track_to(hooks, &track, paths_b.paths[j]);
+        emit_entry(hooks, cancel, KOMPARU_ENTRY_ONLY_RIGHT, paths_b.paths[j]);
         if (KOMPARU_UNLIKELY(komparu_dir_result_add_only_right(result, paths_b.paths[j]) != 0)) {
             *err_msg = "out of memory";
             goto fail;
         }
         j++;
     }
+    dir_track_to(hooks, &track, "");  /* leave all open directories */
 
     /* Phase 2: Execute file comparisons */
     if (task_count > 0) {
-        bool use_pool = (max_workers != 1 && task_count > 1);
+        /* No more workers than can hold their pairs at once */
+        size_t workers = max_workers == 1 ? 1 : komparu_fd_workers(max_workers);
+        bool use_pool = (workers > 1 && task_count > 1);
         komparu_pool_t *pool = NULL;
 
         if (use_pool) {
-            pool = komparu_pool_create(max_workers);
+            pool = komparu_pool_create(workers);
             /* Fall back to sequential if pool creation fails */
         }
 
@@ -652,6 +1235,12 @@ komparu_dir_result_t *komparu_compare_dirs(
             }
         }
 
+        if (komparu_cancelled(cancel)) {
+            *err_msg = KOMPARU_CANCELLED_MSG;
+            errno = ECANCELED;
+            goto fail;
+        }
+
         /* Phase 3: Collect results */
         for (size_t k = 0; k < task_count; k++) {
             if (tasks[k].result_reason >= 0) {
@@ -687,6 +1276,212 @@ fail:
     return NULL;
 }
 
+komparu_dir_result_t *komparu_compare_dirs_vfs(
+    const komparu_vfs_t *vfs_a,
+    const char *dir_a,
+    const komparu_vfs_t *vfs_b,
+    const char *dir_b,
+    size_t chunk_size,
+    bool size_precheck,
+    bool quick_check,
+    bool follow_symlinks,
+    size_t max_workers,
+    const komparu_dir_hooks_t *hooks,
+    const komparu_cancel_t *cancel,
+    const komparu_retry_t *retry,
+    const komparu_lock_t *lock,
+    komparu_dir_cache_t *cache,
+    const char **err_msg
+) {
+    /* Same-directory short-circuit: realpath both, compare strings.
+     * Catches identical paths, symlinks, and trailing-slash variants. */
+    char real_a[PATH_MAX], real_b[PATH_MAX];
+    if (vfs_a == &local_vfs && vfs_b == &local_vfs &&
+        realpath(dir_a, real_a) && realpath(dir_b, real_b) &&
+        strcmp(real_a, real_b) == 0) {
+        komparu_dir_result_t *r = komparu_dir_result_new();
+        if (KOMPARU_UNLIKELY(!r)) {
+            *err_msg = "out of memory";
+            return NULL;
+        }
+        return r;  /* equal=true, empty diff/only_left/only_right */
+    }
+
+    bool same_backend = vfs_a == vfs_b;
+#ifndef KOMPARU_WINDOWS
+    /* Not following symlinks: take each root once and resolve every file
+     * beneath it with none on the way, so a directory swapped for a link
+     * after the walk listed it cannot send a read outside the tree. */
+    int base_a, base_b;
+    if (!follow_symlinks && vfs_local_base(vfs_a, &base_a) &&
+        vfs_local_base(vfs_b, &base_b)) {
+        beneath_ctx_t ctx_a = { .root_len = strlen(dir_a) };
+        beneath_ctx_t ctx_b = { .root_len = strlen(dir_b) };
+        ctx_a.fd = openat(base_a, dir_a, O_RDONLY | O_DIRECTORY | O_CLOEXEC);
+        if (KOMPARU_UNLIKELY(ctx_a.fd < 0)) {
+            komparu_strerror(errno, dirwalk_errbuf, sizeof(dirwalk_errbuf));
+            *err_msg = dirwalk_errbuf;
+            return NULL;
+        }
+        ctx_b.fd = openat(base_b, dir_b, O_RDONLY | O_DIRECTORY | O_CLOEXEC);
+        if (KOMPARU_UNLIKELY(ctx_b.fd < 0)) {
+            komparu_strerror(errno, dirwalk_errbuf, sizeof(dirwalk_errbuf));
+            *err_msg = dirwalk_errbuf;
+            int saved = errno;
+            close(ctx_a.fd);
+            errno = saved;
+            return NULL;
+        }
+        komparu_fd_take(2);
+        const komparu_vfs_t confined_a = {
+            .list = beneath_list, .stat = beneath_stat, .open = beneath_open, .ctx = &ctx_a,
+        };
+        const komparu_vfs_t confined_b = {
+            .list = beneath_list, .stat = beneath_stat, .open = beneath_open, .ctx = &ctx_b,
+        };
+        komparu_dir_result_t *result = compare_dirs(
+            &confined_a, dir_a, &confined_b, dir_b, same_backend,
+            chunk_size, size_precheck, quick_check, follow_symlinks,
+            max_workers, hooks, cancel, retry, lock, cache, err_msg);
+        int saved = errno;
+        close(ctx_a.fd);
+        close(ctx_b.fd);
+        komparu_fd_release(2);
+        errno = saved;
+        return result;
+    }
+#endif
+    return compare_dirs(vfs_a, dir_a, vfs_b, dir_b, same_backend,
+                        chunk_size, size_precheck, quick_check, follow_symlinks,
+                        max_workers, hooks, cancel, retry, lock, cache, err_msg);
+}
+
+/* =========================================================================
+ * Dry-run planning
+ * ========================================================================= */
+
+void komparu_dir_plan_free(komparu_dir_plan_t *plan) {
+    if (!plan) return;
+    for (size_t k = 0; k < plan->item_count; k++)
+        free(plan->items[k].path);
+    free(plan->items);
+    komparu_dir_result_free(plan->settled);
+    memset(plan, 0, sizeof(*plan));
+}
+
+static int plan_add_item(komparu_dir_plan_t *plan, const char *path,
+                         int64_t size_a, int64_t size_b, bool same_file) {
+    if (plan->item_count == plan->item_cap) {
+        size_t new_cap = plan->item_cap ? plan->item_cap * 2 : 128;
+        komparu_plan_item_t *tmp = realloc(plan->items, new_cap * sizeof(*tmp));
+        if (KOMPARU_UNLIKELY(!tmp)) return -1;
+        plan->items = tmp;
+        plan->item_cap = new_cap;
+    }
+    char *copy = strdup(path);
+    if (KOMPARU_UNLIKELY(!copy)) return -1;
+    plan->items[plan->item_count++] = (komparu_plan_item_t){
+        .path = copy, .size_a = size_a, .size_b = size_b, .same_file = same_file,
+    };
+    return 0;
+}
+
+/* Stat root/rel through vfs; -1 on failure. */
+static int plan_stat(const komparu_vfs_t *vfs, const char *root, const char *rel,
+                     komparu_vfs_stat_t *st) {
+    char full[PATH_MAX];
+    int n = snprintf(full, sizeof(full), "%s/%s", root, rel);
+    if (KOMPARU_UNLIKELY(n < 0 || (size_t)n >= sizeof(full))) return -1;
+    return vfs->stat(vfs, full, st);
+}
+
+int komparu_plan_dirs(
+    const komparu_vfs_t *vfs_a,
+    const char *dir_a,
+    const komparu_vfs_t *vfs_b,
+    const char *dir_b,
+    bool follow_symlinks,
+    bool size_precheck,
+    const komparu_cancel_t *cancel,
+    komparu_dir_plan_t *plan,
+    const char **err_msg
+) {
+    memset(plan, 0, sizeof(*plan));
+    komparu_pathlist_t paths_a = {0}, paths_b = {0};
+    komparu_pathlist_t errors_a = {0}, errors_b = {0};
+
+    if (vfs_a->list(vfs_a, dir_a, follow_symlinks, &paths_a, &errors_a, err_msg) != 0)
+        return -1;
+    if (vfs_b->list(vfs_b, dir_b, follow_symlinks, &paths_b, &errors_b, err_msg) != 0) {
+        komparu_pathlist_free(&paths_a);
+        komparu_pathlist_free(&errors_a);
+        return -1;
+    }
+
+    plan->settled = komparu_dir_result_new();
+    if (KOMPARU_UNLIKELY(!plan->settled)) goto oom;
+    komparu_dir_result_t *r = plan->settled;
+
+    for (size_t k = 0; k < errors_a.count; k++)
+        if (KOMPARU_UNLIKELY(komparu_dir_result_add_error(r, errors_a.paths[k]) != 0)) goto oom;
+    for (size_t k = 0; k < errors_b.count; k++)
+        if (KOMPARU_UNLIKELY(komparu_dir_result_add_error(r, errors_b.paths[k]) != 0)) goto oom;
+
+    size_t i = 0, j = 0;
+    while (i < paths_a.count || j < paths_b.count) {
+        if (KOMPARU_UNLIKELY(komparu_cancelled(cancel))) {
+            *err_msg = KOMPARU_CANCELLED_MSG;
+            errno = ECANCELED;
+            goto fail;
+        }
+        int cmp = i == paths_a.count ? 1
+                : j == paths_b.count ? -1
+                : strcmp(paths_a.paths[i], paths_b.paths[j]);
+        if (cmp < 0) {
+            if (KOMPARU_UNLIKELY(komparu_dir_result_add_only_left(r, paths_a.paths[i++]) != 0)) goto oom;
+            continue;
+        }
+        if (cmp > 0) {
+            if (KOMPARU_UNLIKELY(komparu_dir_result_add_only_right(r, paths_b.paths[j++]) != 0)) goto oom;
+            continue;
+        }
+
+        const char *rel = paths_a.paths[i];
+        komparu_vfs_stat_t sa = { .size = -1 }, sb = { .size = -1 };
+        bool ok_a = plan_stat(vfs_a, dir_a, rel, &sa) == 0;
+        bool ok_b = plan_stat(vfs_b, dir_b, rel, &sb) == 0;
+        bool same = vfs_a == vfs_b && ok_a && ok_b &&
+                    (sa.dev != 0 || sa.ino != 0) &&
+                    sa.dev == sb.dev && sa.ino == sb.ino;
+
+        if (!same && size_precheck && sa.size >= 0 && sb.size >= 0 && sa.size != sb.size) {
+            if (KOMPARU_UNLIKELY(komparu_dir_result_add_diff(r, rel, KOMPARU_DIFF_SIZE) != 0)) goto oom;
+        } else if (KOMPARU_UNLIKELY(plan_add_item(plan, rel, sa.size, sb.size, same) != 0)) {
+            goto oom;
+        }
+        i++; j++;
+    }
+
+    komparu_pathlist_free(&paths_a);
+    komparu_pathlist_free(&paths_b);
+    komparu_pathlist_free(&errors_a);
+    komparu_pathlist_free(&errors_b);
+    return 0;
+
+oom:
+    *err_msg = "out of memory";
+    errno = ENOMEM;
+fail:
+    komparu_pathlist_free(&paths_a);
+    komparu_pathlist_free(&paths_b);
+    komparu_pathlist_free(&errors_a);
+    komparu_pathlist_free(&errors_b);
+    komparu_dir_plan_free(plan);
+    return -1;
+}
+
+#ifndef KOMPARU_NO_HTTP
+
 /* =========================================================================
  * Directory vs URL map comparison — sorted merge of local tree vs URL set
  * ========================================================================= */
@@ -793,7 +1588,8 @@ komparu_dir_result_t *komparu_compare_dir_urls(
             komparu_reader_t *ra = komparu_reader_file_open(full_path, &open_err);
             if (!ra) {
                 komparu_dir_result_add_diff(result, local_paths.paths[li],
-                                            KOMPARU_DIFF_READ_ERROR);
+                                            errno == ENOENT ? KOMPARU_DIFF_VANISHED
+                                                            : KOMPARU_DIFF_READ_ERROR);
                 li++; ui++;
                 continue;
             }
@@ -889,3 +1685,5 @@ fail:
     komparu_dir_result_free(result);
     return NULL;
 }
+
+#endif /* KOMPARU_NO_HTTP */
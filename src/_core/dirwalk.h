@@ -1,5 +1,5 @@
 /**
- * dirwalk.h — Recursive directory traversal and comparison.
+ * dirwalk.h — Directory traversal and comparison.
  *
  * Produces a sorted list of relative paths for all regular files.
  */
@@ -8,10 +8,10 @@
 #define KOMPARU_DIRWALK_H
 
 #include "compat.h"
-
-/* Forward declaration */
-struct komparu_dir_result;
-typedef struct komparu_dir_result komparu_dir_result_t;
+#include "compare.h"
+#include "retry.h"
+#include "lock.h"
+#include "dircache.h"
 
 /**
  * Arena block for contiguous string storage.
@@ -48,8 +48,10 @@ typedef struct {
  * follow_symlinks: if true, follow symbolic links.
  * result: output path list (caller must free with komparu_pathlist_free).
  * errors: if non-NULL, paths that could not be stat'd due to permission
- *         denied (EACCES/EPERM) are appended here instead of silently skipped.
- *         Caller must free with komparu_pathlist_free.
+ *         denied (EACCES/EPERM) are appended here instead of silently skipped,
+ *         as are directories deep in the tree that were replaced or removed
+ *         while the walk was below them. Caller must free with
+ *         komparu_pathlist_free.
  *
  * Returns 0 on success, -1 on error.
  */
@@ -61,17 +63,111 @@ int komparu_dirwalk(
     const char **err_msg
 );
 
+/**
+ * komparu_dirwalk() with base_dir resolved relative to the directory
+ * descriptor dirfd (openat semantics: AT_FDCWD = current directory,
+ * "." = dirfd itself). dirfd stays owned by the caller.
+ */
+int komparu_dirwalk_at(
+    int dirfd,
+    const char *base_dir,
+    bool follow_symlinks,
+    komparu_pathlist_t *result,
+    komparu_pathlist_t *errors,
+    const char **err_msg
+);
+
+/**
+ * Append a copy of path to list. Returns 0, or -1 on OOM (*err_msg set).
+ */
+int komparu_pathlist_append(komparu_pathlist_t *list, const char *path,
+                            const char **err_msg);
+
 /**
  * Free a path list and all its strings.
  */
 void komparu_pathlist_free(komparu_pathlist_t *list);
 
+/* =========================================================================
+ * Virtual filesystem — what the directory engine walks and reads
+ * ========================================================================= */
+
+/** Metadata returned by komparu_vfs_t.stat. */
+typedef struct {
+    int64_t size;   /* bytes, or -1 if unknown */
+    uint64_t dev;   /* identity: equal (dev, ino) means the same file; */
+    uint64_t ino;   /* both 0 = identity unknown */
+    int64_t mtime_ns;  /* modification and status-change times, */
+    int64_t ctime_ns;  /* 0 = unknown (disables the warm cache) */
+} komparu_vfs_stat_t;
+
+/**
+ * Filesystem backend for komparu_compare_dirs_vfs().
+ *
+ * Paths passed to stat and open are "root/rel_path" as built by the
+ * engine. All callbacks must be thread-safe: stat and open run on pool
+ * workers when max_workers > 1. ctx belongs to the implementation.
+ */
+typedef struct komparu_vfs {
+    /**
+     * Collect every regular file under root as sorted, '/'-separated
+     * relative paths (same contract as komparu_dirwalk). errors may be NULL.
+     * Returns 0 on success, -1 on error (*err_msg and errno set).
+     */
+    int (*list)(const struct komparu_vfs *self, const char *root,
+                bool follow_symlinks, komparu_pathlist_t *result,
+                komparu_pathlist_t *errors, const char **err_msg);
+
+    /** Fill *st for path. Returns 0 on success, -1 on error. */
+    int (*stat)(const struct komparu_vfs *self, const char *path,
+                komparu_vfs_stat_t *st);
+
+    /** Open path for reading. Returns NULL on error (*err_msg and errno set). */
+    komparu_reader_t *(*open)(const struct komparu_vfs *self, const char *path,
+                              const char **err_msg);
+
+    void *ctx;
+} komparu_vfs_t;
+
+/** The local filesystem (komparu_dirwalk + file reader). Static, never freed. */
+const komparu_vfs_t *komparu_vfs_local(void);
+
+/**
+ * The local filesystem seen through directory descriptor dirfd: every
+ * path is resolved with openat/fstatat relative to it, so a process that
+ * may not resolve absolute paths (capability sandboxes) can still compare.
+ * Returned by value, nothing to free; dirfd must stay open while in use.
+ */
+komparu_vfs_t komparu_vfs_at(int dirfd);
+
 /**
  * Compare two directories recursively.
  *
  * Walks both directories, merge-compares sorted path lists,
  * opens file readers and uses komparu_compare for each common entry.
- * If max_workers > 1, file comparisons run in parallel.
+ * If max_workers > 1, file comparisons run in parallel. A common file
+ * deleted after the walk listed it (ENOENT on open) is reported as
+ * KOMPARU_DIFF_VANISHED rather than READ_ERROR.
+ *
+ * hooks: optional per-entry callbacks (NULL = none). Every entry is
+ * reported exactly once, in completion order, before this returns
+ * (the same-directory short-circuit reports nothing).
+ *
+ * cancel: optional token (NULL = none). Once set, pending comparisons are
+ * skipped and NULL is returned with *err_msg = KOMPARU_CANCELLED_MSG.
+ *
+ * retry: optional policy (NULL = none). Reads are retried in place, then
+ * the file comparison is re-run; a file that still fails transiently is
+ * reported as KOMPARU_DIFF_RETRIES_EXHAUSTED instead of READ_ERROR.
+ *
+ * lock: optional policy (NULL = none). Both files of a pair are read under
+ * a shared advisory lock; a pair a writer keeps locked past the policy's
+ * timeout is reported as KOMPARU_DIFF_LOCKED without being read.
+ *
+ * cache: optional warm cache (NULL = none). Common files whose stat
+ * signatures match a pair stored by an earlier run are settled equal
+ * without reading; pairs found equal are stored, pairs found different
+ * are dropped.
  *
  * Returns allocated dir_result_t on success, NULL on error.
  * Caller must free with komparu_dir_result_free().
@@ -84,9 +180,112 @@ komparu_dir_result_t *komparu_compare_dirs(
     bool quick_check,
     bool follow_symlinks,
     size_t max_workers,
+    const komparu_dir_hooks_t *hooks,
+    const komparu_cancel_t *cancel,
+    const komparu_retry_t *retry,
+    const komparu_lock_t *lock,
+    komparu_dir_cache_t *cache,
+    const char **err_msg
+);
+
+/**
+ * komparu_compare_dirs() with each directory resolved relative to a
+ * directory descriptor (AT_FDCWD = by path, as komparu_compare_dirs()).
+ * Pass "." as the path to compare the descriptor's own directory.
+ */
+komparu_dir_result_t *komparu_compare_dirs_at(
+    int dirfd_a,
+    const char *dir_a,
+    int dirfd_b,
+    const char *dir_b,
+    size_t chunk_size,
+    bool size_precheck,
+    bool quick_check,
+    bool follow_symlinks,
+    size_t max_workers,
+    const komparu_dir_hooks_t *hooks,
+    const komparu_cancel_t *cancel,
+    const komparu_retry_t *retry,
+    const komparu_lock_t *lock,
+    komparu_dir_cache_t *cache,
+    const char **err_msg
+);
+
+/**
+ * komparu_compare_dirs() over arbitrary backends: dir_a is walked and
+ * read through vfs_a, dir_b through vfs_b. komparu_compare_dirs() is this
+ * with komparu_vfs_local() on both sides.
+ *
+ * The same-file short-circuit uses stat identity and applies only when
+ * vfs_a == vfs_b; the same-directory short-circuit only to local trees.
+ * Without follow_symlinks, local and directory-descriptor trees are read
+ * through a descriptor of each root taken once, every file resolved
+ * beneath it by komparu_openat_beneath(): a component swapped for a
+ * symlink after the walk fails the read (ELOOP) instead of leaving the tree.
+ */
+komparu_dir_result_t *komparu_compare_dirs_vfs(
+    const komparu_vfs_t *vfs_a,
+    const char *dir_a,
+    const komparu_vfs_t *vfs_b,
+    const char *dir_b,
+    size_t chunk_size,
+    bool size_precheck,
+    bool quick_check,
+    bool follow_symlinks,
+    size_t max_workers,
+    const komparu_dir_hooks_t *hooks,
+    const komparu_cancel_t *cancel,
+    const komparu_retry_t *retry,
+    const komparu_lock_t *lock,
+    komparu_dir_cache_t *cache,
     const char **err_msg
 );
 
+/* =========================================================================
+ * Dry-run planning — traversal and metadata only, no content I/O
+ * ========================================================================= */
+
+/** A common file that a real comparison would have to read. */
+typedef struct {
+    char *path;       /* relative, owned */
+    int64_t size_a;   /* -1 if stat failed */
+    int64_t size_b;
+    bool same_file;   /* same (dev, ino): settled without reading */
+} komparu_plan_item_t;
+
+typedef struct {
+    /* Entries settled by metadata alone: only_left, only_right, errors,
+     * and KOMPARU_DIFF_SIZE diffs when size_precheck is set. */
+    komparu_dir_result_t *settled;
+
+    komparu_plan_item_t *items;
+    size_t item_count;
+    size_t item_cap;
+} komparu_dir_plan_t;
+
+/**
+ * Walk both trees and stat every common file, as komparu_compare_dirs_vfs()
+ * would before reading. Nothing is opened.
+ *
+ * Returns 0 on success (caller frees with komparu_dir_plan_free), -1 on
+ * error (*err_msg and errno set). cancel may be NULL.
+ */
+int komparu_plan_dirs(
+    const komparu_vfs_t *vfs_a,
+    const char *dir_a,
+    const komparu_vfs_t *vfs_b,
+    const char *dir_b,
+    bool follow_symlinks,
+    bool size_precheck,
+    const komparu_cancel_t *cancel,
+    komparu_dir_plan_t *plan,
+    const char **err_msg
+);
+
+void komparu_dir_plan_free(komparu_dir_plan_t *plan);
+
+#ifndef KOMPARU_NO_HTTP
+
 /**
  * Compare local directory files against a URL mapping.
  *
@@ -116,4 +315,6 @@ komparu_dir_result_t *komparu_compare_dir_urls(
     const char **err_msg
 );
 
+#endif /* KOMPARU_NO_HTTP */
+
 #endif /* KOMPARU_DIRWALK_H */
@@ -0,0 +1,101 @@
+/**
+ * fdlimit.c — Process-wide descriptor budget.
+ */
+
+#include "fdlimit.h"
+#include "retry.h"
+#include <stdatomic.h>
+
+#ifndef KOMPARU_WINDOWS
+#include <sys/resource.h>
+#endif
+
+/* Kept out of the budget for descriptors the engine does not count: a
+ * quarter of the limit, within these bounds */
+#define FD_RESERVE_MIN 16
+#define FD_RESERVE_MAX 256
+
+/* How long a worker waits for room before opening its pair regardless */
+#define FD_WAIT_MAX_SEC 5.0
+#define FD_WAIT_STEP_SEC 0.002
+
+static _Atomic size_t fd_in_use;
+static _Atomic size_t fd_budget_cache;  /* 0 = not read yet */
+
+static size_t fd_budget_read(void) {
+#ifdef KOMPARU_WINDOWS
+    return SIZE_MAX;
+#else
+    struct rlimit rl;
+    if (getrlimit(RLIMIT_NOFILE, &rl) != 0 || rl.rlim_cur == RLIM_INFINITY ||
+        rl.rlim_cur > (rlim_t)(SIZE_MAX / 2))
+        return SIZE_MAX;
+    size_t limit = (size_t)rl.rlim_cur;
+    size_t reserve = limit / 4;
+    if (reserve < FD_RESERVE_MIN) reserve = FD_RESERVE_MIN;
+    if (reserve > FD_RESERVE_MAX) reserve = FD_RESERVE_MAX;
+    return limit > reserve + KOMPARU_FDS_PER_TASK ? limit - reserve : KOMPARU_FDS_PER_TASK;
+#endif
+}
+
+size_t komparu_fd_budget(void) {
+    /* Read once: the limit rarely changes after startup, and a racing
+     * first read computes the same value */
+    size_t budget = atomic_load_explicit(&fd_budget_cache, memory_order_relaxed);
+    if (KOMPARU_UNLIKELY(budget == 0)) {
+        budget = fd_budget_read();
+        atomic_store_explicit(&fd_budget_cache, budget, memory_order_relaxed);
+    }
+    return budget;
+}
+
+size_t komparu_fd_workers(size_t num_workers) {
+    if (num_workers == 0) {
+        num_workers = komparu_cpu_count();
+        if (num_workers > KOMPARU_MAX_DEFAULT_WORKERS)
+            num_workers = KOMPARU_MAX_DEFAULT_WORKERS;
+    }
+    size_t room = komparu_fd_budget() / KOMPARU_FDS_PER_TASK;
+    if (num_workers > room) num_workers = room;
+    return num_workers ? num_workers : 1;
+}
+
+void komparu_fd_take(size_t n) {
+    atomic_fetch_add_explicit(&fd_in_use, n, memory_order_relaxed);
+}
+
+void komparu_fd_release(size_t n) {
+    atomic_fetch_sub_explicit(&fd_in_use, n, memory_order_relaxed);
+}
+
+bool komparu_fd_acquire(size_t n, const komparu_cancel_t *cancel) {
+    size_t budget = komparu_fd_budget();
+    double waited = 0.0;
+    size_t cur = atomic_load_explicit(&fd_in_use, memory_order_relaxed);
+    for (;;) {
+        if (cur + n <= budget || waited >= FD_WAIT_MAX_SEC) {
+            if (atomic_compare_exchange_weak_explicit(&fd_in_use, &cur, cur + n,
+                                                      memory_order_relaxed,
+                                                      memory_order_relaxed))
+                return true;
+            continue;  /* cur reloaded */
+        }
+        if (!komparu_sleep(FD_WAIT_STEP_SEC, cancel))
+            return false;
+        waited += FD_WAIT_STEP_SEC;
+        cur = atomic_load_explicit(&fd_in_use, memory_order_relaxed);
+    }
+}
+
+bool komparu_fd_wait_close(size_t own, const komparu_cancel_t *cancel) {
+    size_t seen = atomic_load_explicit(&fd_in_use, memory_order_relaxed);
+    if (seen <= own)
+        return false;
+    for (double waited = 0.0; waited < FD_WAIT_MAX_SEC; waited += FD_WAIT_STEP_SEC) {
+        if (!komparu_sleep(FD_WAIT_STEP_SEC, cancel))
+            return false;
+        if (atomic_load_explicit(&fd_in_use, memory_order_relaxed) < seen)
+            return true;
+    }
+    return false;
+}
@@ -0,0 +1,64 @@
+/**
+ * fdlimit.h — Descriptor budget under RLIMIT_NOFILE.
+ *
+ * Descriptors the engine holds across more than one system call — the
+ * two files a comparison has open, the directories a walk has entered,
+ * the roots a confined comparison keeps — are counted against one
+ * process-wide budget: the soft RLIMIT_NOFILE less a reserve for
+ * descriptors opened elsewhere (the interpreter, sockets, the caller's
+ * own files). A directory comparison starts no more workers than the
+ * budget has room for, a worker whose pair would overrun it waits for
+ * another to close one, and a worker that meets EMFILE anyway waits and
+ * reopens — so parallel runs under default ulimits (256 on macOS, 1024
+ * on most Linux distributions) slow down instead of failing partway.
+ *
+ * Windows sets no per-process handle limit; the budget is unbounded there.
+ */
+
+#ifndef KOMPARU_FDLIMIT_H
+#define KOMPARU_FDLIMIT_H
+
+#include "compat.h"
+#include "cancel.h"
+
+/* Descriptors one file comparison holds: one per side */
+#define KOMPARU_FDS_PER_TASK 2
+
+/** Descriptors the engine may hold at once; SIZE_MAX when unbounded. */
+size_t komparu_fd_budget(void);
+
+/**
+ * Workers a pool may run with the budget: num_workers (0 = the pool
+ * default) capped so every worker can hold its pair. At least 1.
+ */
+size_t komparu_fd_workers(size_t num_workers);
+
+/** Count n descriptors just opened, without waiting. */
+void komparu_fd_take(size_t n);
+
+/**
+ * Reserve n descriptors before opening them, waiting while that would
+ * overrun the budget. Gives up waiting after a few seconds and takes them
+ * anyway, so descriptors held by a walk or a caller can never deadlock a
+ * worker. Returns false (nothing taken) if cancel is set. cancel may be
+ * NULL.
+ */
+bool komparu_fd_acquire(size_t n, const komparu_cancel_t *cancel);
+
+/** Return n descriptors counted by komparu_fd_take() or komparu_fd_acquire(). */
+void komparu_fd_release(size_t n);
+
+/**
+ * After EMFILE or ENFILE: wait until another engine descriptor is closed.
+ * Returns false without waiting if the engine holds none besides the
+ * caller's `own` — waiting cannot help then — and false if cancelled or
+ * none closed within a few seconds.
+ */
+bool komparu_fd_wait_close(size_t own, const komparu_cancel_t *cancel);
+
+/** True for the errno of a full descriptor table (EMFILE, ENFILE). */
+static inline bool komparu_errno_fd_exhausted(int err) {
+    return err == EMFILE || err == ENFILE;
+}
+
+#endif /* KOMPARU_FDLIMIT_H */
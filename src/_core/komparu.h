@@ -0,0 +1,247 @@
+/**
+ * komparu.h — Public C API of libkomparu.
+ *
+ * The comparison engine behind komparu._core, usable from C and any
+ * language with a C FFI. Build with -DKOMPARU_BUILD_C_LIBRARY=ON.
+ *
+ * Self-contained: depends only on the C standard library headers.
+ *
+ *     komparu_init();
+ *
+ *     komparu_options_t opts;
+ *     komparu_options_init(&opts, sizeof opts);
+ *     opts.quick_check = false;
+ *
+ *     char err[256];
+ *     komparu_result_t r = komparu_compare_files("a.bin", "b.bin",
+ *                                                &opts, err, sizeof err);
+ *     if (r == KOMPARU_ERROR) fprintf(stderr, "%s\n", err);
+ *
+ *     komparu_cleanup();
+ */
+
+#ifndef KOMPARU_H
+#define KOMPARU_H
+
+#include <stdbool.h>
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#if defined(_WIN32) && defined(KOMPARU_BUILDING_LIB)
+#  define KOMPARU_API __declspec(dllexport)
+#elif defined(_WIN32) && defined(KOMPARU_SHARED)
+#  define KOMPARU_API __declspec(dllimport)
+#elif defined(KOMPARU_BUILDING_LIB) && defined(__GNUC__)
+#  define KOMPARU_API __attribute__((visibility("default")))
+#else
+#  define KOMPARU_API
+#endif
+
+/**
+ * Comparison result codes.
+ */
+typedef enum {
+    KOMPARU_EQUAL     =  1,   /* Sources are byte-identical */
+    KOMPARU_DIFFERENT =  0,   /* Sources differ */
+    KOMPARU_ERROR     = -1,   /* Error during comparison */
+} komparu_result_t;
+
+/* Diff reasons */
+#define KOMPARU_DIFF_CONTENT    0
+#define KOMPARU_DIFF_SIZE       1
+#define KOMPARU_DIFF_READ_ERROR 2
+#define KOMPARU_DIFF_RETRIES_EXHAUSTED 3  /* read error that persisted through retries */
+#define KOMPARU_DIFF_UNSTABLE   4  /* a file's size or mtime changed while it was read */
+#define KOMPARU_DIFF_LOCKED     5  /* a writer held a lock on a file past lock_timeout */
+#define KOMPARU_DIFF_VANISHED   6  /* a listed file was gone by the time it was opened */
+
+typedef enum {
+    KOMPARU_ENTRY_EQUAL      = 0,  /* common file, identical */
+    KOMPARU_ENTRY_DIFF       = 1,  /* common file, differs (reason = KOMPARU_DIFF_*) */
+    KOMPARU_ENTRY_ONLY_LEFT  = 2,
+    KOMPARU_ENTRY_ONLY_RIGHT = 3,
+    KOMPARU_ENTRY_ERROR      = 4,  /* skipped: permission denied during walk */
+} komparu_entry_kind_t;
+
+/**
+ * Called once per resolved entry. May be invoked concurrently from pool
+ * worker threads — implementations must synchronize themselves.
+ * rel_path is only valid for the duration of the call.
+ */
+typedef void (*komparu_entry_fn)(
+    void *user,
+    komparu_entry_kind_t kind,
+    int reason,
+    const char *rel_path
+);
+
+/* =========================================================================
+ * Library API
+ * ========================================================================= */
+
+/**
+ * Comparison options. Always fill with komparu_options_init() first so
+ * fields added in later versions get their defaults.
+ *
+ * Fields are only ever appended. struct_size records how much of the
+ * struct the caller was compiled with: the library reads that prefix and
+ * uses its defaults for any field past it, so a program built against an
+ * older komparu.h keeps working with a newer library. Fields a newer
+ * header added past the end of this library's struct are ignored. A
+ * comparison given a struct_size below KOMPARU_OPTIONS_MIN_SIZE fails
+ * with EINVAL.
+ */
+typedef struct {
+    size_t struct_size;         /* sizeof(komparu_options_t) as the caller sees it */
+    size_t chunk_size;          /* read buffer size (default 64 KB) */
+    bool size_precheck;         /* compare sizes first (default true) */
+    bool quick_check;           /* sample offsets before full scan (default true) */
+    bool follow_symlinks;       /* directories only (default true) */
+    size_t max_workers;         /* directories only; 0 = auto, 1 = sequential */
+
+    /* HTTP sources (http:// and https://), if KOMPARU_FEATURE_HTTP */
+    double timeout;             /* seconds (default 30) */
+    bool follow_redirects;      /* default true */
+    bool verify_ssl;            /* default true */
+    bool allow_private;         /* allow private/loopback addresses (default false) */
+    const char *proxy;          /* NULL = none */
+    const char *const *headers; /* NULL-terminated "Key: Value" array, or NULL */
+
+    /* Transient I/O errors (EAGAIN, ESTALE, ...); directories only */
+    unsigned retries;           /* extra attempts per read and per file (default 0) */
+    double retry_delay;         /* first backoff in seconds, doubled each time (default 0.1) */
+    double retry_max_delay;     /* backoff cap in seconds (default 2) */
+
+    /* Shared advisory locks (flock, LockFileEx) while reading; directories only */
+    bool lock;                  /* default false */
+    double lock_timeout;        /* seconds to wait for a writer; < 0 = no limit (default -1) */
+} komparu_options_t;
+
+/** Smallest struct_size the library accepts: struct_size and chunk_size. */
+#define KOMPARU_OPTIONS_MIN_SIZE (2 * sizeof(size_t))
+
+/**
+ * Fill the first struct_size bytes of opts with the defaults used by the
+ * Python API and record struct_size; pass sizeof of your komparu_options_t.
+ * Returns 0, or -1 with errno EINVAL if struct_size is below
+ * KOMPARU_OPTIONS_MIN_SIZE.
+ */
+KOMPARU_API int komparu_options_init(komparu_options_t *opts, size_t struct_size);
+
+/**
+ * Initialize process-wide state (SIGBUS handler, libcurl).
+ * Call once before any comparison. Returns 0 on success, -1 on error.
+ */
+KOMPARU_API int komparu_init(void);
+
+/**
+ * Release process-wide state. No comparison may be running.
+ * Threads that called into the library should exit first; the calling
+ * thread's buffers and those parked by exited workers are freed here.
+ */
+KOMPARU_API void komparu_cleanup(void);
+
+/**
+ * Source of the engine's comparison buffers (two per worker thread, each
+ * chunk_size bytes). Lets embedders place them in pinned, NUMA-local or
+ * DMA-capable memory. alloc returns NULL on failure; release receives the
+ * size that was passed to alloc. Both may be called concurrently from any
+ * thread that compares.
+ */
+typedef struct {
+    void *(*alloc)(void *ctx, size_t size);
+    void (*release)(void *ctx, void *ptr, size_t size);
+    void *ctx;
+} komparu_buffer_allocator_t;
+
+/**
+ * Install allocator for comparison buffers (copied), or restore
+ * malloc/free with NULL. Best called before the first comparison. The
+ * previous allocator must stay callable until every buffer it made is
+ * released: threads drop theirs at their next comparison or on exit, and
+ * komparu_cleanup() frees the rest.
+ * Returns 0, or -1 with errno EINVAL if alloc or release is NULL.
+ */
+KOMPARU_API int komparu_set_buffer_allocator(const komparu_buffer_allocator_t *allocator);
+
+/**
+ * Open local files and directories with O_NOATIME from now on, so that
+ * reading them leaves their access times as they were, or stop with
+ * false. The kernel refuses the flag on files the process neither owns
+ * nor has CAP_FOWNER over; those are opened without it and counted by
+ * komparu_noatime_denied(). Enabling resets the count.
+ * Returns false if the platform has no O_NOATIME (only Linux has).
+ */
+KOMPARU_API bool komparu_set_noatime(bool enable);
+
+/** Opens since komparu_set_noatime(true) that had to update the access time. */
+KOMPARU_API unsigned long long komparu_noatime_denied(void);
+
+/**
+ * Largest span of a file mapped at once, in bytes, rounded up to whole
+ * pages (to the 64 KiB allocation granularity on Windows). Files up to
+ * that size are mapped whole, larger ones through a window that slides
+ * along as they are read, so 32-bit processes and files of hundreds of
+ * GB do not run out of address space. 0 restores the default: 1 GiB, or
+ * 64 MiB where pointers are 32 bits wide. Readers already open keep
+ * their window.
+ * Returns the window now in effect.
+ */
+KOMPARU_API unsigned long long komparu_set_mmap_window(unsigned long long window);
+
+/** Library version string, e.g. "0.1.0". */
+KOMPARU_API const char *komparu_version(void);
+
+/* Optional capabilities, selected at build time (komparu_features) */
+#define KOMPARU_FEATURE_HTTP    (1u << 0)   /* http(s):// sources; -DKOMPARU_WITH_HTTP */
+
+/**
+ * Bitmask of KOMPARU_FEATURE_* compiled into this build. Without
+ * KOMPARU_FEATURE_HTTP, URL sources fail with errno ENOTSUP.
+ */
+KOMPARU_API unsigned komparu_features(void);
+
+/**
+ * Compare two sources byte-by-byte. a and b are local paths or
+ * http(s):// URLs. opts may be NULL for defaults.
+ *
+ * Returns KOMPARU_EQUAL, KOMPARU_DIFFERENT or KOMPARU_ERROR. On error,
+ * errno classifies the failure (ENOENT, EACCES, ETIMEDOUT, ...) and a
+ * message is written to errbuf when errbuf_size > 0.
+ */
+KOMPARU_API komparu_result_t komparu_compare_files(
+    const char *a,
+    const char *b,
+    const komparu_options_t *opts,
+    char *errbuf,
+    size_t errbuf_size
+);
+
+/**
+ * Compare two directories recursively. opts may be NULL for defaults.
+ *
+ * on_entry (may be NULL) receives every entry exactly once before this
+ * returns; with max_workers > 1 it is called from worker threads.
+ *
+ * Returns KOMPARU_EQUAL if both trees hold the same files with the same
+ * content, KOMPARU_DIFFERENT otherwise, or KOMPARU_ERROR (errno and
+ * errbuf as for komparu_compare_files).
+ */
+KOMPARU_API komparu_result_t komparu_compare_directories(
+    const char *dir_a,
+    const char *dir_b,
+    const komparu_options_t *opts,
+    komparu_entry_fn on_entry,
+    void *user,
+    char *errbuf,
+    size_t errbuf_size
+);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* KOMPARU_H */
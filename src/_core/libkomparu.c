@@ -0,0 +1,277 @@
+/**
+ * libkomparu.c — Public C API (komparu.h) over the comparison engine.
+ *
+ * Built only into the standalone shared library (KOMPARU_BUILD_C_LIBRARY).
+ * Mirrors what module.c does for Python, minus the GIL and object
+ * conversion.
+ */
+
+#include "compat.h"
+#include "komparu.h"
+#include "compare.h"
+#include "dirwalk.h"
+#include "reader_file.h"
+#ifndef KOMPARU_NO_HTTP
+#include "reader_http.h"
+#include "curl_share.h"
+#endif
+
+#ifndef KOMPARU_LIB_VERSION
+#define KOMPARU_LIB_VERSION "0.1.0"
+#endif
+
+/* =========================================================================
+ * Helpers
+ * ========================================================================= */
+
+static bool is_url(const char *source) {
+    return (strncmp(source, "http://", 7) == 0 ||
+            strncmp(source, "https://", 8) == 0);
+}
+
+/* Write "prefix: msg" to errbuf, preserving errno. */
+static void set_error(char *errbuf, size_t errbuf_size,
+                      const char *prefix, const char *msg) {
+    if (!errbuf || errbuf_size == 0) return;
+    int saved = errno;
+    snprintf(errbuf, errbuf_size, "%s: %s", prefix, msg ? msg : "unknown error");
+    errno = saved;
+}
+
+static komparu_reader_t *open_reader(const char *source,
+                                     const komparu_options_t *opts,
+                                     const char **err_msg) {
+    if (is_url(source)) {
+#ifdef KOMPARU_NO_HTTP
+        (void)opts;
+        *err_msg = "HTTP support not compiled in";
+        errno = ENOTSUP;
+        return NULL;
+#else
+        return komparu_reader_http_open_ex(
+            source, (const char **)opts->headers,
+            opts->timeout, opts->follow_redirects, opts->verify_ssl,
+            opts->allow_private, opts->proxy,
+            err_msg
+        );
+#endif
+    }
+    return komparu_reader_file_open(source, err_msg);
+}
+
+/* =========================================================================
+ * Library lifecycle
+ * ========================================================================= */
+
+int komparu_options_init(komparu_options_t *opts, size_t struct_size) {
+    if (struct_size < KOMPARU_OPTIONS_MIN_SIZE) {
+        errno = EINVAL;
+        return -1;
+    }
+    komparu_options_t defaults;
+    memset(&defaults, 0, sizeof defaults);
+    defaults.chunk_size = KOMPARU_DEFAULT_CHUNK_SIZE;
+    defaults.size_precheck = true;
+    defaults.quick_check = true;
+    defaults.follow_symlinks = true;
+    defaults.max_workers = 0;
+    defaults.timeout = 30.0;
+    defaults.follow_redirects = true;
+    defaults.verify_ssl = true;
+    defaults.allow_private = false;
+    defaults.retries = 0;
+    defaults.retry_delay = 0.1;
+    defaults.retry_max_delay = 2.0;
+    defaults.lock = false;
+    defaults.lock_timeout = -1.0;
+    /* A caller built against a newer header: its extra fields stay zero */
+    if (struct_size > sizeof defaults) {
+        memset((char *)opts + sizeof defaults, 0, struct_size - sizeof defaults);
+    }
+    memcpy(opts, &defaults, struct_size < sizeof defaults ? struct_size : sizeof defaults);
+    opts->struct_size = struct_size;
+    return 0;
+}
+
+/*
+ * The caller's options as this library's struct: the prefix it was
+ * compiled with, defaults past it. NULL with errno EINVAL if struct_size
+ * is too small to hold even chunk_size.
+ */
+static const komparu_options_t *resolve_options(const komparu_options_t *opts,
+                                                komparu_options_t *full) {
+    komparu_options_init(full, sizeof *full);
+    if (!opts) {
+        return full;
+    }
+    if (opts->struct_size < KOMPARU_OPTIONS_MIN_SIZE) {
+        errno = EINVAL;
+        return NULL;
+    }
+    if (opts->struct_size >= sizeof *full) {
+        return opts;
+    }
+    memcpy(full, opts, opts->struct_size);
+    full->struct_size = sizeof *full;
+    return full;
+}
+
+int komparu_init(void) {
+    if (komparu_sigbus_init() != 0) return -1;
+#ifndef KOMPARU_NO_HTTP
+    if (komparu_curl_global_init() != 0) return -1;
+    if (komparu_curl_share_init() != 0) {
+        komparu_curl_global_cleanup();
+        return -1;
+    }
+#endif
+    return 0;
+}
+
+void komparu_cleanup(void) {
+    /* Same order as the Python module's atexit handlers */
+    komparu_compare_tls_cleanup();
+    komparu_compare_buffers_drain();
+#ifndef KOMPARU_NO_HTTP
+    komparu_curl_share_cleanup();
+    komparu_curl_global_cleanup();
+#endif
+}
+
+const char *komparu_version(void) {
+    return KOMPARU_LIB_VERSION;
+}
+
+unsigned komparu_features(void) {
+    unsigned features = 0;
+#ifndef KOMPARU_NO_HTTP
+    features |= KOMPARU_FEATURE_HTTP;
+#endif
+    return features;
+}
+
+/* =========================================================================
+ * Comparisons
+ * ========================================================================= */
+
+komparu_result_t komparu_compare_files(
+    const char *a,
+    const char *b,
+    const komparu_options_t *opts,
+    char *errbuf,
+    size_t errbuf_size
+) {
+    komparu_options_t full;
+    opts = resolve_options(opts, &full);
+    if (!opts) {
+        set_error(errbuf, errbuf_size, "invalid argument", "struct_size is too small");
+        return KOMPARU_ERROR;
+    }
+    if (!a || !b || opts->chunk_size == 0) {
+        errno = EINVAL;
+        set_error(errbuf, errbuf_size, "invalid argument",
+                  !a || !b ? "source is NULL" : "chunk_size must be positive");
+        return KOMPARU_ERROR;
+    }
+
+    /* Same-file short-circuit, as in module.c */
+#ifndef KOMPARU_WINDOWS
+    if (!is_url(a) && !is_url(b)) {
+        struct stat st_a, st_b;
+        if (stat(a, &st_a) == 0 && stat(b, &st_b) == 0 &&
+            st_a.st_dev == st_b.st_dev && st_a.st_ino == st_b.st_ino) {
+            return KOMPARU_EQUAL;
+        }
+    }
+#endif
+
+    const char *err_msg = NULL;
+    komparu_result_t result;
+    komparu_reader_t *reader_b = NULL;
+    komparu_reader_t *reader_a = open_reader(a, opts, &err_msg);
+    if (!reader_a) {
+        set_error(errbuf, errbuf_size, "cannot open", err_msg);
+        return KOMPARU_ERROR;
+    }
+    reader_b = open_reader(b, opts, &err_msg);
+    if (!reader_b) {
+        set_error(errbuf, errbuf_size, "cannot open", err_msg);
+        int saved = errno;
+        reader_a->close(reader_a);
+        errno = saved;
+        return KOMPARU_ERROR;
+    }
+
+    if (opts->quick_check) {
+        result = komparu_quick_check(reader_a, reader_b,
+                                     opts->chunk_size, &err_msg);
+        if (result == KOMPARU_DIFFERENT) goto done;
+        if (result == KOMPARU_ERROR && reader_a->seek && reader_b->seek) {
+            reader_a->seek(reader_a, 0);
+            reader_b->seek(reader_b, 0);
+        }
+    }
+
+    result = komparu_compare_ex(reader_a, reader_b, opts->chunk_size,
+                                opts->size_precheck, NULL, &err_msg);
+    if (result == KOMPARU_ERROR)
+        set_error(errbuf, errbuf_size, "comparison error", err_msg);
+
+done:;
+    int saved = errno;
+    reader_a->close(reader_a);
+    reader_b->close(reader_b);
+    errno = saved;
+    return result;
+}
+
+komparu_result_t komparu_compare_directories(
+    const char *dir_a,
+    const char *dir_b,
+    const komparu_options_t *opts,
+    komparu_entry_fn on_entry,
+    void *user,
+    char *errbuf,
+    size_t errbuf_size
+) {
+    komparu_options_t full;
+    opts = resolve_options(opts, &full);
+    if (!opts) {
+        set_error(errbuf, errbuf_size, "invalid argument", "struct_size is too small");
+        return KOMPARU_ERROR;
+    }
+    if (!dir_a || !dir_b || opts->chunk_size == 0) {
+        errno = EINVAL;
+        set_error(errbuf, errbuf_size, "invalid argument",
+                  !dir_a || !dir_b ? "directory is NULL"
+                                   : "chunk_size must be positive");
+        return KOMPARU_ERROR;
+    }
+
+    komparu_dir_hooks_t hooks = {
+        .on_entry = on_entry,
+        .user = user,
+    };
+    komparu_retry_t retry = {
+        .retries = opts->retries,
+        .delay = opts->retry_delay,
+        .max_delay = opts->retry_max_delay,
+    };
+    komparu_lock_t lock = { .timeout = opts->lock_timeout };
+    const char *err_msg = NULL;
+    komparu_dir_result_t *result = komparu_compare_dirs(
+        dir_a, dir_b,
+        opts->chunk_size, opts->size_precheck, opts->quick_check,
+        opts->follow_symlinks, opts->max_workers,
+        on_entry ? &hooks : NULL, NULL, &retry, opts->lock ? &lock : NULL,
+        NULL, &err_msg
+    );
+    if (!result) {
+        set_error(errbuf, errbuf_size, "directory comparison error", err_msg);
+        return KOMPARU_ERROR;
+    }
+
+    bool equal = result->equal;
+    komparu_dir_result_free(result);
+    return equal ? KOMPARU_EQUAL : KOMPARU_DIFFERENT;
+}
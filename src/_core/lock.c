@@ -0,0 +1,42 @@
+/**
+ * lock.c — Waiting for shared locks on both sides of a comparison.
+ */
+
+#include "lock.h"
+#include "retry.h"
+
+#define LOCK_POLL_SEC 0.05
+
+/* 1 if taken (or r cannot be locked), 0 if a writer holds it. */
+static int lock_try(komparu_reader_t *r) {
+    if (!r->lock_shared || r->lock_shared(r, true) == 0) return 1;
+    return errno == EWOULDBLOCK || errno == EAGAIN ? 0 : 1;
+}
+
+static void lock_release(komparu_reader_t *r) {
+    if (r->lock_shared) r->lock_shared(r, false);
+}
+
+int komparu_lock_pair(komparu_reader_t *a, komparu_reader_t *b,
+                      const komparu_lock_t *policy,
+                      const komparu_cancel_t *cancel) {
+    double waited = 0.0;
+    for (;;) {
+        if (lock_try(a)) {
+            if (lock_try(b)) return 0;
+            lock_release(a);
+        }
+        if (policy->timeout >= 0.0 && waited >= policy->timeout) {
+            errno = EWOULDBLOCK;
+            return -1;
+        }
+        double step = LOCK_POLL_SEC;
+        if (policy->timeout >= 0.0 && policy->timeout - waited < step)
+            step = policy->timeout - waited;
+        if (!komparu_sleep(step, cancel)) {
+            errno = ECANCELED;
+            return -1;
+        }
+        waited += step;
+    }
+}
@@ -0,0 +1,35 @@
+/**
+ * lock.h — Shared advisory locks on the files being compared.
+ *
+ * Writers that cooperate take an exclusive lock while they modify a file
+ * (flock(LOCK_EX) on Unix, LockFileEx on Windows). A comparison holding
+ * a shared lock on both sides then never reads a file mid-write. On Unix
+ * the locks are advisory: a writer that does not lock is not held back.
+ */
+
+#ifndef KOMPARU_LOCK_H
+#define KOMPARU_LOCK_H
+
+#include "compat.h"
+#include "reader.h"
+#include "cancel.h"
+
+typedef struct {
+    double timeout;   /* seconds to wait for a writer to let go; < 0 = no limit */
+} komparu_lock_t;
+
+/**
+ * Take a shared lock on both readers, polling every 50 ms until both are
+ * free, the policy's timeout passes or cancel is set. Never holds one side
+ * while waiting for the other, so a writer locking both in turn cannot
+ * deadlock with it. A reader without lock_shared, or whose file system
+ * cannot lock (ENOLCK, EOPNOTSUPP, ...), is compared unlocked.
+ *
+ * Returns 0 with the locks held (close() releases them), or -1 with errno
+ * EWOULDBLOCK when the timeout passed and ECANCELED when cancelled.
+ */
+int komparu_lock_pair(komparu_reader_t *a, komparu_reader_t *b,
+                      const komparu_lock_t *policy,
+                      const komparu_cancel_t *cancel);
+
+#endif /* KOMPARU_LOCK_H */
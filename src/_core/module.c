@@ -13,9 +13,23 @@
 #include "curl_share.h"
 #include "dirwalk.h"
 #include "reader_archive.h"
+#include "reader_stream.h"
 #include "async_task.h"
 #include <string.h>
 #include <stdlib.h>
+#include <stdarg.h>
+#include <errno.h>
+#ifdef KOMPARU_LINUX
+#include <sys/inotify.h>
+#include <sys/fanotify.h>
+#include <sys/ioctl.h>
+#include <sys/stat.h>
+#include <sys/vfs.h>
+#include <fcntl.h>
+#include <limits.h>
+#include <unistd.h>
+#include <linux/fs.h>
+#endif
 
 /* =========================================================================
  * URL detection — check if source is an HTTP(S) URL
@@ -117,6 +131,272 @@ static komparu_reader_t *open_reader(
     return komparu_reader_file_open(source, err_msg);
 }
 
+/* =========================================================================
+ * Raise a typed OSError for a failed comparison.
+ *
+ * code is the errno captured where the failure happened (see compat.h).
+ * OSError(code, message, path) picks the builtin subclass itself
+ * (FileNotFoundError, PermissionError, TimeoutError, ...); komparu._errors
+ * narrows it to the komparu exception hierarchy.
+ * ========================================================================= */
+
+static void raise_source_error(int code, const char *path, const char *fmt, ...) {
+    va_list ap;
+    va_start(ap, fmt);
+    PyObject *msg = PyUnicode_FromFormatV(fmt, ap);
+    va_end(ap);
+    if (!msg) return;
+
+    PyObject *filename = path ? PyUnicode_DecodeFSDefault(path) : NULL;
+    if (path && !filename) {
+        Py_DECREF(msg);
+        return;
+    }
+    PyObject *exc = path
+        ? PyObject_CallFunction(PyExc_OSError, "iOO", code ? code : EIO, msg, filename)
+        : PyObject_CallFunction(PyExc_OSError, "iO", code ? code : EIO, msg);
+    Py_DECREF(msg);
+    Py_XDECREF(filename);
+    if (!exc) return;
+    PyErr_SetObject((PyObject *)Py_TYPE(exc), exc);
+    Py_DECREF(exc);
+}
+
+/**
+ * Owned copy of a path argument (str, bytes or os.PathLike) in the file
+ * system encoding: the bytes on disk on POSIX, undecodable ones arriving
+ * as surrogate escapes, and WTF-8 on Windows, whose names may hold
+ * unpaired UTF-16 surrogates. NULL with an exception set.
+ */
+static char *path_dup(PyObject *obj) {
+    PyObject *bytes;
+    if (!PyUnicode_FSConverter(obj, &bytes)) return NULL;
+    char *copy = strdup(PyBytes_AS_STRING(bytes));
+    Py_DECREF(bytes);
+    if (!copy) PyErr_NoMemory();
+    return copy;
+}
+
+/* =========================================================================
+ * Cancellation tokens — capsule "komparu.cancel_token"
+ * ========================================================================= */
+
+static void cancel_capsule_destructor(PyObject *capsule) {
+    komparu_cancel_unref(PyCapsule_GetPointer(capsule, "komparu.cancel_token"));
+}
+
+/* None -> NULL. Borrowed: valid while the argument object is alive. */
+static int get_cancel(PyObject *obj, komparu_cancel_t **out) {
+    *out = NULL;
+    if (obj == NULL || obj == Py_None) return 0;
+    komparu_cancel_t *cancel = PyCapsule_GetPointer(obj, "komparu.cancel_token");
+    if (!cancel) {
+        PyErr_Clear();
+        PyErr_SetString(PyExc_TypeError, "cancel must be a cancel token handle or None");
+        return -1;
+    }
+    *out = cancel;
+    return 0;
+}
+
+/* retries/retry_delay/retry_max_delay kwargs -> policy (retries 0 = off). */
+static int get_retry(int retries, double delay, double max_delay,
+                     komparu_retry_t *out) {
+    if (retries < 0 || delay < 0.0 || max_delay < 0.0) {
+        PyErr_SetString(PyExc_ValueError, "retry settings must be non-negative");
+        return -1;
+    }
+    *out = (komparu_retry_t){
+        .retries = (unsigned)retries,
+        .delay = delay,
+        .max_delay = max_delay,
+    };
+    return 0;
+}
+
+/* dirfd_a/dirfd_b kwargs: -1 = resolve the path as given (AT_FDCWD). */
+static int get_dirfd(int fd, int *out) {
+    if (fd < -1) {
+        PyErr_SetString(PyExc_ValueError, "directory handle must be non-negative");
+        return -1;
+    }
+#ifdef KOMPARU_WINDOWS
+    if (fd >= 0) {
+        PyErr_SetString(PyExc_NotImplementedError,
+                        "directory handles are not supported on this platform");
+        return -1;
+    }
+#endif
+    *out = fd < 0 ? AT_FDCWD : fd;
+    return 0;
+}
+
+static PyObject *py_cancel_token_new(PyObject *self, PyObject *args) {
+    (void)self;
+    long long max_bytes = 0;
+    PyObject *py_parent = Py_None;
+    if (!PyArg_ParseTuple(args, "|LO", &max_bytes, &py_parent)) return NULL;
+    if (max_bytes < 0) {
+        PyErr_SetString(PyExc_ValueError, "max_bytes must be non-negative");
+        return NULL;
+    }
+    komparu_cancel_t *parent;
+    if (get_cancel(py_parent, &parent) < 0) return NULL;
+    if (parent && max_bytes) {
+        PyErr_SetString(PyExc_ValueError, "a child token has no quota of its own");
+        return NULL;
+    }
+    komparu_cancel_t *cancel = parent ? komparu_cancel_new_child(parent)
+                                      : komparu_cancel_new_quota((int64_t)max_bytes);
+    if (!cancel) return PyErr_NoMemory();
+    PyObject *capsule = PyCapsule_New(cancel, "komparu.cancel_token",
+                                      cancel_capsule_destructor);
+    if (!capsule) komparu_cancel_unref(cancel);
+    return capsule;
+}
+
+static PyObject *py_cancel_token_set(PyObject *self, PyObject *arg) {
+    (void)self;
+    komparu_cancel_t *cancel;
+    if (get_cancel(arg, &cancel) < 0) return NULL;
+    if (cancel) komparu_cancel_set(cancel);
+    Py_RETURN_NONE;
+}
+
+static PyObject *py_cancel_token_is_set(PyObject *self, PyObject *arg) {
+    (void)self;
+    komparu_cancel_t *cancel;
+    if (get_cancel(arg, &cancel) < 0) return NULL;
+    return PyBool_FromLong(komparu_cancelled(cancel));
+}
+
+static PyObject *py_cancel_token_stats(PyObject *self, PyObject *arg) {
+    (void)self;
+    komparu_cancel_t *cancel;
+    if (get_cancel(arg, &cancel) < 0) return NULL;
+    if (!cancel) return Py_BuildValue("(LNL)", 0LL, PyBool_FromLong(0), 0LL);
+    return Py_BuildValue("(LNL)",
+                         (long long)atomic_load_explicit(&cancel->bytes_read,
+                                                         memory_order_relaxed),
+                         PyBool_FromLong(komparu_cancel_quota(cancel)),
+                         (long long)atomic_load_explicit(&cancel->entries_done,
+                                                         memory_order_relaxed));
+}
+
+/* =========================================================================
+ * Warm directory caches — capsule "komparu.dir_cache"
+ * ========================================================================= */
+
+static void dir_cache_capsule_destructor(PyObject *capsule) {
+    komparu_dir_cache_unref(PyCapsule_GetPointer(capsule, "komparu.dir_cache"));
+}
+
+/* None -> NULL. Borrowed: valid while the argument object is alive. */
+static int get_dir_cache(PyObject *obj, komparu_dir_cache_t **out) {
+    *out = NULL;
+    if (obj == NULL || obj == Py_None) return 0;
+    komparu_dir_cache_t *cache = PyCapsule_GetPointer(obj, "komparu.dir_cache");
+    if (!cache) {
+        PyErr_Clear();
+        PyErr_SetString(PyExc_TypeError, "cache must be a dir cache handle or None");
+        return -1;
+    }
+    *out = cache;
+    return 0;
+}
+
+static PyObject *py_dir_cache_new(PyObject *self, PyObject *arg) {
+    (void)self;
+    Py_ssize_t max_entries = PyLong_AsSsize_t(arg);
+    if (max_entries == -1 && PyErr_Occurred()) return NULL;
+    if (max_entries < 0) {
+        PyErr_SetString(PyExc_ValueError, "max_entries must be non-negative");
+        return NULL;
+    }
+    komparu_dir_cache_t *cache = komparu_dir_cache_new((size_t)max_entries);
+    if (!cache) return PyErr_NoMemory();
+    PyObject *capsule = PyCapsule_New(cache, "komparu.dir_cache",
+                                      dir_cache_capsule_destructor);
+    if (!capsule) komparu_dir_cache_unref(cache);
+    return capsule;
+}
+
+static PyObject *py_dir_cache_clear(PyObject *self, PyObject *arg) {
+    (void)self;
+    komparu_dir_cache_t *cache;
+    if (get_dir_cache(arg, &cache) < 0) return NULL;
+    komparu_dir_cache_clear(cache);
+    Py_RETURN_NONE;
+}
+
+static PyObject *py_dir_cache_stats(PyObject *self, PyObject *arg) {
+    (void)self;
+    komparu_dir_cache_t *cache;
+    if (get_dir_cache(arg, &cache) < 0) return NULL;
+    return Py_BuildValue("(nK)", (Py_ssize_t)komparu_dir_cache_size(cache),
+                         (unsigned long long)komparu_dir_cache_hits(cache));
+}
+
+/* =========================================================================
+ * Buffer allocator hook — capsule "komparu.buffer_allocator" from another
+ * extension, pointing at a komparu_buffer_allocator_t. The struct is
+ * copied; its functions must stay valid for the life of the process.
+ * ========================================================================= */
+
+static PyObject *py_set_buffer_allocator(PyObject *self, PyObject *arg) {
+    (void)self;
+    const komparu_buffer_allocator_t *allocator = NULL;
+    if (arg != Py_None) {
+        allocator = PyCapsule_GetPointer(arg, "komparu.buffer_allocator");
+        if (!allocator) {
+            PyErr_Clear();
+            PyErr_SetString(PyExc_TypeError,
+                            "allocator must be a komparu.buffer_allocator capsule or None");
+            return NULL;
+        }
+    }
+    if (komparu_set_buffer_allocator(allocator) != 0) {
+        PyErr_SetString(PyExc_ValueError, "allocator must set both alloc and release");
+        return NULL;
+    }
+    Py_RETURN_NONE;
+}
+
+/* =========================================================================
+ * Access times — forensic reads leave them alone where the kernel lets us
+ * ========================================================================= */
+
+static PyObject *py_set_noatime(PyObject *self, PyObject *arg) {
+    (void)self;
+    int enable = PyObject_IsTrue(arg);
+    if (enable < 0) return NULL;
+    return PyBool_FromLong(komparu_set_noatime(enable));
+}
+
+static PyObject *py_noatime_denied(PyObject *self, PyObject *unused) {
+    (void)self;
+    (void)unused;
+    return PyLong_FromUnsignedLongLong(komparu_noatime_denied());
+}
+
+/* =========================================================================
+ * Mapping window — how much of a file the mmap reader maps at once
+ * ========================================================================= */
+
+static PyObject *py_set_mmap_window(PyObject *self, PyObject *arg) {
+    (void)self;
+    int overflow = 0;
+    long long window = PyLong_AsLongLongAndOverflow(arg, &overflow);
+    if (window == -1 && PyErr_Occurred()) return NULL;
+    if (window < 0 || overflow < 0) {
+        PyErr_SetString(PyExc_ValueError, "mmap_window must be non-negative");
+        return NULL;
+    }
+    unsigned long long effective = komparu_set_mmap_window(
+        overflow ? ULLONG_MAX : (unsigned long long)window);
+    return PyLong_FromUnsignedLongLong(effective);
+}
+
 /* =========================================================================
  * Python wrapper: compare(source_a, source_b, ...) -> bool
  * ========================================================================= */
@@ -124,8 +404,8 @@ static komparu_reader_t *open_reader(
 static PyObject *py_compare(PyObject *self, PyObject *args, PyObject *kwargs) {
     (void)self;
 
-    const char *source_a = NULL;
-    const char *source_b = NULL;
+    PyObject *source_a = NULL;
+    PyObject *source_b = NULL;
     Py_ssize_t chunk_size = KOMPARU_DEFAULT_CHUNK_SIZE;
     int size_precheck = 1;
     int quick_check = 1;
@@ -135,17 +415,18 @@ static PyObject *py_compare(PyObject *self, PyObject *args, PyObject *kwargs) {
     int verify_ssl = 1;
     int allow_private = 0;
     const char *proxy = NULL;
+    PyObject *py_cancel = Py_None;
 
     static char *kwlist[] = {
         "source_a", "source_b", "chunk_size", "size_precheck", "quick_check",
         "headers", "timeout", "follow_redirects", "verify_ssl", "allow_private",
-        "proxy", NULL
+        "proxy", "cancel", NULL
     };
 
-    if (!PyArg_ParseTupleAndKeywords(args, kwargs, "ss|nppOdpppz", kwlist,
+    if (!PyArg_ParseTupleAndKeywords(args, kwargs, "OO|nppOdpppzO", kwlist,
             &source_a, &source_b, &chunk_size, &size_precheck, &quick_check,
             &py_headers, &timeout, &follow_redirects, &verify_ssl,
-            &allow_private, &proxy)) {
+            &allow_private, &proxy, &py_cancel)) {
         return NULL;
     }
 
@@ -154,6 +435,9 @@ static PyObject *py_compare(PyObject *self, PyObject *args, PyObject *kwargs) {
         return NULL;
     }
 
+    komparu_cancel_t *cancel;
+    if (get_cancel(py_cancel, &cancel) < 0) return NULL;
+
     /* Validate headers type */
     if (py_headers != Py_None && !PyDict_Check(py_headers)) {
         PyErr_SetString(PyExc_TypeError, "headers must be a dict or None");
@@ -171,21 +455,18 @@ static PyObject *py_compare(PyObject *self, PyObject *args, PyObject *kwargs) {
     }
 
     /* Copy source strings — PyArg strings are only valid while GIL is held */
-    char *src_a = strdup(source_a);
-    char *src_b = strdup(source_b);
+    char *src_a = path_dup(source_a);
+    char *src_b = src_a ? path_dup(source_b) : NULL;
     char *proxy_copy = proxy ? strdup(proxy) : NULL;
     if (!src_a || !src_b || (proxy && !proxy_copy)) {
+        if (src_b) PyErr_NoMemory();
         free(src_a);
         free(src_b);
         free(proxy_copy);
         free_header_array(header_array, header_count);
-        PyErr_NoMemory();
         return NULL;
     }
 
-    bool src_a_is_url = is_url(src_a);
-    bool src_b_is_url = is_url(src_b);
-
     /*
      * Release GIL for ALL I/O work: open_reader does curl_easy_perform
      * (HEAD request) which blocks. If GIL is held, Python-threaded test
@@ -194,6 +475,7 @@ static PyObject *py_compare(PyObject *self, PyObject *args, PyObject *kwargs) {
     komparu_result_t result;
     komparu_reader_t *reader_a = NULL;
     komparu_reader_t *reader_b = NULL;
+    int err_code = 0;
 
     KOMPARU_GIL_STATE_DECL
     KOMPARU_GIL_RELEASE()
@@ -202,7 +484,7 @@ static PyObject *py_compare(PyObject *self, PyObject *args, PyObject *kwargs) {
      * they are identical — no I/O needed. Covers same path, hard links,
      * symlinks to same target. */
 #ifndef KOMPARU_WINDOWS
-    if (!src_a_is_url && !src_b_is_url) {
+    if (!is_url(src_a) && !is_url(src_b)) {
         struct stat st_a, st_b;
         if (stat(src_a, &st_a) == 0 && stat(src_b, &st_b) == 0 &&
             st_a.st_dev == st_b.st_dev && st_a.st_ino == st_b.st_ino) {
@@ -241,12 +523,14 @@ static PyObject *py_compare(PyObject *self, PyObject *args, PyObject *kwargs) {
         }
     }
 
-    result = komparu_compare(reader_a, reader_b,
-                             (size_t)chunk_size, (bool)size_precheck,
-                             &err_msg);
+    result = komparu_compare_ex(reader_a, reader_b,
+                                (size_t)chunk_size, (bool)size_precheck,
+                                cancel, &err_msg);
+    if (result == KOMPARU_ERROR) err_code = errno;
     goto done;
 
 open_failed:
+    err_code = errno;
     result = KOMPARU_ERROR;
 
 done:
@@ -278,24 +562,14 @@ done:
             Py_RETURN_FALSE;
         case KOMPARU_ERROR:
             if (!reader_a) {
-                if (src_a_is_url) {
-                    PyErr_Format(PyExc_IOError, "cannot open '%s': %s",
-                                 src_a, err_msg ? err_msg : "unknown error");
-                } else {
-                    PyErr_Format(PyExc_FileNotFoundError, "cannot open '%s': %s",
-                                 src_a, err_msg ? err_msg : "unknown error");
-                }
+                raise_source_error(err_code, src_a, "cannot open: %s",
+                                   err_msg ? err_msg : "unknown error");
             } else if (!reader_b) {
-                if (src_b_is_url) {
-                    PyErr_Format(PyExc_IOError, "cannot open '%s': %s",
-                                 src_b, err_msg ? err_msg : "unknown error");
-                } else {
-                    PyErr_Format(PyExc_FileNotFoundError, "cannot open '%s': %s",
-                                 src_b, err_msg ? err_msg : "unknown error");
-                }
+                raise_source_error(err_code, src_b, "cannot open: %s",
+                                   err_msg ? err_msg : "unknown error");
             } else {
-                PyErr_Format(PyExc_IOError, "comparison error: %s",
-                             err_msg ? err_msg : "unknown");
+                raise_source_error(err_code, NULL, "comparison error: %s",
+                                   err_msg ? err_msg : "unknown");
             }
             free(src_a);
             free(src_b);
@@ -318,10 +592,16 @@ static const char *diff_reason_str(int reason) {
         case KOMPARU_DIFF_CONTENT: return "content_mismatch";
         case KOMPARU_DIFF_SIZE:    return "size_mismatch";
         case KOMPARU_DIFF_READ_ERROR: return "read_error";
+        case KOMPARU_DIFF_RETRIES_EXHAUSTED: return "retries_exhausted";
+        case KOMPARU_DIFF_UNSTABLE: return "unstable";
+        case KOMPARU_DIFF_LOCKED:   return "locked";
+        case KOMPARU_DIFF_VANISHED: return "vanished";
         default: return "unknown";
     }
 }
 
+/* Paths are decoded as os.fsdecode() does: archive entry names and file
+ * names need not be UTF-8, and undecodable bytes become surrogates. */
 static PyObject *dir_result_to_python(komparu_dir_result_t *r) {
     PyObject *dict = PyDict_New();
     if (!dict) return NULL;
@@ -335,7 +615,7 @@ static PyObject *dir_result_to_python(komparu_dir_result_t *r) {
         PyObject *diff = PyDict_New();
         if (!diff) goto fail;
         for (size_t i = 0; i < r->diff_count; i++) {
-            PyObject *key = PyUnicode_FromString(r->diffs[i].path);
+            PyObject *key = PyUnicode_DecodeFSDefault(r->diffs[i].path);
             PyObject *val = PyUnicode_FromString(diff_reason_str(r->diffs[i].reason));
             if (!key || !val || PyDict_SetItem(diff, key, val) < 0) {
                 Py_XDECREF(key);
@@ -358,7 +638,7 @@ static PyObject *dir_result_to_python(komparu_dir_result_t *r) {
         PyObject *ol = PySet_New(NULL);
         if (!ol) goto fail;
         for (size_t i = 0; i < r->only_left_count; i++) {
-            PyObject *s = PyUnicode_FromString(r->only_left[i]);
+            PyObject *s = PyUnicode_DecodeFSDefault(r->only_left[i]);
             if (!s || PySet_Add(ol, s) < 0) {
                 Py_XDECREF(s);
                 Py_DECREF(ol);
@@ -378,7 +658,7 @@ static PyObject *dir_result_to_python(komparu_dir_result_t *r) {
         PyObject *or_set = PySet_New(NULL);
         if (!or_set) goto fail;
         for (size_t i = 0; i < r->only_right_count; i++) {
-            PyObject *s = PyUnicode_FromString(r->only_right[i]);
+            PyObject *s = PyUnicode_DecodeFSDefault(r->only_right[i]);
             if (!s || PySet_Add(or_set, s) < 0) {
                 Py_XDECREF(s);
                 Py_DECREF(or_set);
@@ -398,7 +678,7 @@ static PyObject *dir_result_to_python(komparu_dir_result_t *r) {
         PyObject *err_set = PySet_New(NULL);
         if (!err_set) goto fail;
         for (size_t i = 0; i < r->error_count; i++) {
-            PyObject *s = PyUnicode_FromString(r->errors[i]);
+            PyObject *s = PyUnicode_DecodeFSDefault(r->errors[i]);
             if (!s || PySet_Add(err_set, s) < 0) {
                 Py_XDECREF(s);
                 Py_DECREF(err_set);
@@ -427,22 +707,35 @@ fail:
 static PyObject *py_compare_dir(PyObject *self, PyObject *args, PyObject *kwargs) {
     (void)self;
 
-    const char *dir_a = NULL;
-    const char *dir_b = NULL;
+    PyObject *dir_a = NULL;
+    PyObject *dir_b = NULL;
     Py_ssize_t chunk_size = KOMPARU_DEFAULT_CHUNK_SIZE;
     int size_precheck = 1;
     int quick_check = 1;
     int follow_symlinks = 1;
     Py_ssize_t max_workers = 0;  /* 0 = auto */
+    PyObject *py_cancel = Py_None;
+    int retries = 0;
+    double retry_delay = 0.1;
+    double retry_max_delay = 2.0;
+    int dirfd_a = -1;
+    int dirfd_b = -1;
+    PyObject *py_cache = Py_None;
+    int lock = 0;
+    double lock_timeout = -1.0;
 
     static char *kwlist[] = {
         "dir_a", "dir_b", "chunk_size", "size_precheck",
-        "quick_check", "follow_symlinks", "max_workers", NULL
+        "quick_check", "follow_symlinks", "max_workers", "cancel",
+        "retries", "retry_delay", "retry_max_delay", "dirfd_a", "dirfd_b",
+        "cache", "lock", "lock_timeout", NULL
     };
 
-    if (!PyArg_ParseTupleAndKeywords(args, kwargs, "ss|npppn", kwlist,
+    if (!PyArg_ParseTupleAndKeywords(args, kwargs, "OO|npppnOiddiiOpd", kwlist,
             &dir_a, &dir_b, &chunk_size, &size_precheck,
-            &quick_check, &follow_symlinks, &max_workers)) {
+            &quick_check, &follow_symlinks, &max_workers, &py_cancel,
+            &retries, &retry_delay, &retry_max_delay, &dirfd_a, &dirfd_b,
+            &py_cache, &lock, &lock_timeout)) {
         return NULL;
     }
 
@@ -451,12 +744,19 @@ static PyObject *py_compare_dir(PyObject *self, PyObject *args, PyObject *kwargs
         return NULL;
     }
 
-    char *da = strdup(dir_a);
-    char *db = strdup(dir_b);
-    if (!da || !db) {
+    komparu_cancel_t *cancel;
+    if (get_cancel(py_cancel, &cancel) < 0) return NULL;
+    komparu_retry_t retry;
+    if (get_retry(retries, retry_delay, retry_max_delay, &retry) < 0) return NULL;
+    komparu_lock_t lock_policy = { .timeout = lock_timeout };
+    if (get_dirfd(dirfd_a, &dirfd_a) < 0 || get_dirfd(dirfd_b, &dirfd_b) < 0) return NULL;
+    komparu_dir_cache_t *cache;
+    if (get_dir_cache(py_cache, &cache) < 0) return NULL;
+
+    char *da = path_dup(dir_a);
+    char *db = da ? path_dup(dir_b) : NULL;
+    if (!db) {
         free(da);
-        free(db);
-        PyErr_NoMemory();
         return NULL;
     }
 
@@ -466,11 +766,12 @@ static PyObject *py_compare_dir(PyObject *self, PyObject *args, PyObject *kwargs
     KOMPARU_GIL_STATE_DECL
     KOMPARU_GIL_RELEASE()
 
-    result = komparu_compare_dirs(da, db,
+    result = komparu_compare_dirs_at(dirfd_a, da, dirfd_b, db,
         (size_t)chunk_size, (bool)size_precheck,
         (bool)quick_check, (bool)follow_symlinks,
         (size_t)(max_workers >= 0 ? max_workers : 0),
-        &err_msg);
+        NULL, cancel, &retry, lock ? &lock_policy : NULL, cache, &err_msg);
+    int err_code = result ? 0 : errno;
 
     KOMPARU_GIL_ACQUIRE()
 
@@ -484,8 +785,8 @@ static PyObject *py_compare_dir(PyObject *self, PyObject *args, PyObject *kwargs
     }
 
     if (!result) {
-        PyErr_Format(PyExc_IOError, "directory comparison failed: %s",
-                     err_msg ? err_msg : "unknown error");
+        raise_source_error(err_code, NULL, "directory comparison failed: %s",
+                           err_msg ? err_msg : "unknown error");
         return NULL;
     }
 
@@ -494,6 +795,110 @@ static PyObject *py_compare_dir(PyObject *self, PyObject *args, PyObject *kwargs
     return py_result;
 }
 
+/* =========================================================================
+ * Python wrapper: plan_dir(dir_a, dir_b, ...) -> dict
+ * ========================================================================= */
+
+static PyObject *plan_to_python(komparu_dir_plan_t *plan) {
+    PyObject *dict = dir_result_to_python(plan->settled);
+    if (!dict) return NULL;
+
+    PyObject *pairs = PyList_New((Py_ssize_t)plan->item_count);
+    if (!pairs) goto fail;
+    for (size_t k = 0; k < plan->item_count; k++) {
+        const komparu_plan_item_t *it = &plan->items[k];
+        PyObject *item = Py_BuildValue("(NLLO)", PyUnicode_DecodeFSDefault(it->path),
+                                       (long long)it->size_a, (long long)it->size_b,
+                                       it->same_file ? Py_True : Py_False);
+        if (!item) {
+            Py_DECREF(pairs);
+            goto fail;
+        }
+        PyList_SET_ITEM(pairs, (Py_ssize_t)k, item);
+    }
+    if (PyDict_SetItemString(dict, "pairs", pairs) < 0) {
+        Py_DECREF(pairs);
+        goto fail;
+    }
+    Py_DECREF(pairs);
+    return dict;
+
+fail:
+    Py_DECREF(dict);
+    return NULL;
+}
+
+static PyObject *py_plan_dir(PyObject *self, PyObject *args, PyObject *kwargs) {
+    (void)self;
+
+    PyObject *dir_a = NULL;
+    PyObject *dir_b = NULL;
+    int size_precheck = 1;
+    int follow_symlinks = 1;
+    PyObject *py_cancel = Py_None;
+    int dirfd_a = -1;
+    int dirfd_b = -1;
+
+    static char *kwlist[] = {
+        "dir_a", "dir_b", "size_precheck", "follow_symlinks", "cancel",
+        "dirfd_a", "dirfd_b", NULL
+    };
+
+    if (!PyArg_ParseTupleAndKeywords(args, kwargs, "OO|ppOii", kwlist,
+            &dir_a, &dir_b, &size_precheck, &follow_symlinks, &py_cancel,
+            &dirfd_a, &dirfd_b)) {
+        return NULL;
+    }
+
+    komparu_cancel_t *cancel;
+    if (get_cancel(py_cancel, &cancel) < 0) return NULL;
+    if (get_dirfd(dirfd_a, &dirfd_a) < 0 || get_dirfd(dirfd_b, &dirfd_b) < 0) return NULL;
+
+    char *da = path_dup(dir_a);
+    char *db = da ? path_dup(dir_b) : NULL;
+    if (!db) {
+        free(da);
+        return NULL;
+    }
+
+    const char *err_msg = NULL;
+    komparu_dir_plan_t plan;
+    const komparu_vfs_t *local = komparu_vfs_local();
+    komparu_vfs_t at_a = komparu_vfs_at(dirfd_a);
+    komparu_vfs_t at_b = komparu_vfs_at(dirfd_b);
+    const komparu_vfs_t *vfs_a = dirfd_a == AT_FDCWD ? local : &at_a;
+    const komparu_vfs_t *vfs_b = dirfd_b == AT_FDCWD ? local
+                               : dirfd_b == dirfd_a ? vfs_a : &at_b;
+
+    KOMPARU_GIL_STATE_DECL
+    KOMPARU_GIL_RELEASE()
+
+    int rc = komparu_plan_dirs(vfs_a, da, vfs_b, db,
+                               (bool)follow_symlinks, (bool)size_precheck,
+                               cancel, &plan, &err_msg);
+    int err_code = rc == 0 ? 0 : errno;
+
+    KOMPARU_GIL_ACQUIRE()
+
+    free(da);
+    free(db);
+
+    if (PyErr_CheckSignals() < 0) {
+        if (rc == 0) komparu_dir_plan_free(&plan);
+        return NULL;
+    }
+
+    if (rc != 0) {
+        raise_source_error(err_code, NULL, "directory planning failed: %s",
+                           err_msg ? err_msg : "unknown error");
+        return NULL;
+    }
+
+    PyObject *py_result = plan_to_python(&plan);
+    komparu_dir_plan_free(&plan);
+    return py_result;
+}
+
 /* =========================================================================
  * Python wrapper: compare_archive(path_a, path_b, ...) -> dict
  * ========================================================================= */
@@ -501,8 +906,8 @@ static PyObject *py_compare_dir(PyObject *self, PyObject *args, PyObject *kwargs
 static PyObject *py_compare_archive(PyObject *self, PyObject *args, PyObject *kwargs) {
     (void)self;
 
-    const char *path_a = NULL;
-    const char *path_b = NULL;
+    PyObject *path_a = NULL;
+    PyObject *path_b = NULL;
     Py_ssize_t chunk_size = KOMPARU_DEFAULT_CHUNK_SIZE;
     long long max_decompressed_size = -1;  /* -1 = use default */
     int max_compression_ratio = -1;
@@ -516,19 +921,17 @@ static PyObject *py_compare_archive(PyObject *self, PyObject *args, PyObject *kw
         "max_entries", "max_entry_name_length", "hash_compare", NULL
     };
 
-    if (!PyArg_ParseTupleAndKeywords(args, kwargs, "ss|nLiLLp", kwlist,
+    if (!PyArg_ParseTupleAndKeywords(args, kwargs, "OO|nLiLLp", kwlist,
             &path_a, &path_b, &chunk_size,
             &max_decompressed_size, &max_compression_ratio,
             &max_entries, &max_entry_name_length, &hash_compare)) {
         return NULL;
     }
 
-    char *pa = strdup(path_a);
-    char *pb = strdup(path_b);
-    if (!pa || !pb) {
+    char *pa = path_dup(path_a);
+    char *pb = pa ? path_dup(path_b) : NULL;
+    if (!pb) {
         free(pa);
-        free(pb);
-        PyErr_NoMemory();
         return NULL;
     }
 
@@ -572,6 +975,7 @@ static PyObject *py_compare_archive(PyObject *self, PyObject *args, PyObject *kw
         result = komparu_compare_archives(pa, pb,
             (size_t)chunk_size, mds, mcr, me, menl, &err_msg);
     }
+    int err_code = result ? 0 : errno;
 
     KOMPARU_GIL_ACQUIRE()
 
@@ -585,8 +989,106 @@ static PyObject *py_compare_archive(PyObject *self, PyObject *args, PyObject *kw
     }
 
     if (!result) {
-        PyErr_Format(PyExc_IOError, "archive comparison failed: %s",
-                     err_msg ? err_msg : "unknown error");
+        raise_source_error(err_code, NULL, "archive comparison failed: %s",
+                           err_msg ? err_msg : "unknown error");
+        return NULL;
+    }
+
+    PyObject *py_result = dir_result_to_python(result);
+    komparu_dir_result_free(result);
+    return py_result;
+}
+
+/* =========================================================================
+ * Python wrapper: compare_dir_archive(path_a, path_b, archive_a, ...) -> dict
+ * ========================================================================= */
+
+static PyObject *py_compare_dir_archive(PyObject *self, PyObject *args, PyObject *kwargs) {
+    (void)self;
+
+    PyObject *path_a = NULL;
+    PyObject *path_b = NULL;
+    int archive_a = 0;
+    Py_ssize_t chunk_size = KOMPARU_DEFAULT_CHUNK_SIZE;
+    int size_precheck = 1;
+    int quick_check = 1;
+    int follow_symlinks = 1;
+    Py_ssize_t max_workers = 0;  /* 0 = auto */
+    PyObject *py_cancel = Py_None;
+    long long max_decompressed_size = -1;  /* -1 = use default */
+    int max_compression_ratio = -1;
+    long long max_entries = -1;
+    long long max_entry_name_length = -1;
+
+    static char *kwlist[] = {
+        "path_a", "path_b", "archive_a", "chunk_size", "size_precheck",
+        "quick_check", "follow_symlinks", "max_workers", "cancel",
+        "max_decompressed_size", "max_compression_ratio",
+        "max_entries", "max_entry_name_length", NULL
+    };
+
+    if (!PyArg_ParseTupleAndKeywords(args, kwargs, "OOp|npppnOLiLL", kwlist,
+            &path_a, &path_b, &archive_a, &chunk_size, &size_precheck,
+            &quick_check, &follow_symlinks, &max_workers, &py_cancel,
+            &max_decompressed_size, &max_compression_ratio,
+            &max_entries, &max_entry_name_length)) {
+        return NULL;
+    }
+
+    if (chunk_size <= 0) {
+        PyErr_SetString(PyExc_ValueError, "chunk_size must be positive");
+        return NULL;
+    }
+
+    komparu_cancel_t *cancel;
+    if (get_cancel(py_cancel, &cancel) < 0) return NULL;
+
+    char *pa = path_dup(path_a);
+    char *pb = pa ? path_dup(path_b) : NULL;
+    if (!pb) {
+        free(pa);
+        return NULL;
+    }
+
+    int64_t mds = max_decompressed_size >= 0 ? (int64_t)max_decompressed_size : 0;
+    int mcr = max_compression_ratio >= 0 ? max_compression_ratio : 0;
+    int64_t me = max_entries >= 0 ? (int64_t)max_entries : 0;
+    int64_t menl = max_entry_name_length >= 0 ? (int64_t)max_entry_name_length : 0;
+
+    const char *err_msg = NULL;
+    komparu_dir_result_t *result = NULL;
+    komparu_vfs_t archive = {0};
+    const komparu_vfs_t *local = komparu_vfs_local();
+
+    KOMPARU_GIL_STATE_DECL
+    KOMPARU_GIL_RELEASE()
+
+    if (komparu_vfs_archive_open(archive_a ? pa : pb, mds, mcr, me, menl,
+                                 &archive, &err_msg) == 0) {
+        result = komparu_compare_dirs_vfs(
+            archive_a ? &archive : local, pa,
+            archive_a ? local : &archive, pb,
+            (size_t)chunk_size, (bool)size_precheck,
+            (bool)quick_check, (bool)follow_symlinks,
+            (size_t)(max_workers >= 0 ? max_workers : 0),
+            NULL, cancel, NULL, NULL, NULL, &err_msg);
+    }
+    int err_code = result ? 0 : errno;
+    komparu_vfs_archive_close(&archive);
+
+    KOMPARU_GIL_ACQUIRE()
+
+    free(pa);
+    free(pb);
+
+    if (PyErr_CheckSignals() < 0) {
+        if (result) komparu_dir_result_free(result);
+        return NULL;
+    }
+
+    if (!result) {
+        raise_source_error(err_code, NULL, "directory-archive comparison failed: %s",
+                           err_msg ? err_msg : "unknown error");
         return NULL;
     }
 
@@ -602,7 +1104,7 @@ static PyObject *py_compare_archive(PyObject *self, PyObject *args, PyObject *kw
 static PyObject *py_compare_dir_urls(PyObject *self, PyObject *args, PyObject *kwargs) {
     (void)self;
 
-    const char *dir_path = NULL;
+    PyObject *dir_path = NULL;
     PyObject *py_url_map = NULL;
     Py_ssize_t chunk_size = KOMPARU_DEFAULT_CHUNK_SIZE;
     int size_precheck = 1;
@@ -620,7 +1122,7 @@ static PyObject *py_compare_dir_urls(PyObject *self, PyObject *args, PyObject *k
         "proxy", NULL
     };
 
-    if (!PyArg_ParseTupleAndKeywords(args, kwargs, "sO|nppOdpppz", kwlist,
+    if (!PyArg_ParseTupleAndKeywords(args, kwargs, "OO|nppOdpppz", kwlist,
             &dir_path, &py_url_map, &chunk_size, &size_precheck, &quick_check,
             &py_headers, &timeout, &follow_redirects, &verify_ssl,
             &allow_private, &proxy)) {
@@ -709,9 +1211,10 @@ static PyObject *py_compare_dir_urls(PyObject *self, PyObject *args, PyObject *k
     }
 
     /* Copy dir_path and proxy — PyArg strings are only valid with GIL */
-    char *dir_copy = strdup(dir_path);
-    char *proxy_copy = proxy ? strdup(proxy) : NULL;
+    char *dir_copy = path_dup(dir_path);
+    char *proxy_copy = dir_copy && proxy ? strdup(proxy) : NULL;
     if (!dir_copy || (proxy && !proxy_copy)) {
+        if (dir_copy) PyErr_NoMemory();
         free(dir_copy);
         free(proxy_copy);
         for (Py_ssize_t j = 0; j < map_size; j++) {
@@ -721,7 +1224,6 @@ static PyObject *py_compare_dir_urls(PyObject *self, PyObject *args, PyObject *k
         free(rel_paths);
         free(url_strs);
         free_header_array(header_array, header_count);
-        PyErr_NoMemory();
         return NULL;
     }
 
@@ -745,6 +1247,7 @@ static PyObject *py_compare_dir_urls(PyObject *self, PyObject *args, PyObject *k
         (bool)allow_private,
         proxy_copy,
         &err_msg);
+    int err_code = result ? 0 : errno;
 
     KOMPARU_GIL_ACQUIRE()
 
@@ -766,8 +1269,8 @@ static PyObject *py_compare_dir_urls(PyObject *self, PyObject *args, PyObject *k
     }
 
     if (!result) {
-        PyErr_Format(PyExc_IOError, "dir_urls comparison failed: %s",
-                     err_msg ? err_msg : "unknown error");
+        raise_source_error(err_code, NULL, "dir_urls comparison failed: %s",
+                           err_msg ? err_msg : "unknown error");
         return NULL;
     }
 
@@ -777,47 +1280,1048 @@ static PyObject *py_compare_dir_urls(PyObject *self, PyObject *args, PyObject *k
 }
 
 /* =========================================================================
- * Python wrapper: compare_buffers(buf_a, buf_b) -> bool
+ * Python wrapper: compare_streams(stream_a, stream_b, ...) -> bool
+ *
+ * Same engine as compare(): quick check (when both streams are seekable
+ * and positioned at 0) followed by the sequential chunk loop. The GIL is
+ * released around the engine; stream callbacks re-acquire it per call.
  * ========================================================================= */
 
-static PyObject *py_compare_buffers(PyObject *self, PyObject *args) {
+static PyObject *py_compare_streams(PyObject *self, PyObject *args, PyObject *kwargs) {
     (void)self;
 
-    Py_buffer buf_a, buf_b;
-    if (!PyArg_ParseTuple(args, "y*y*", &buf_a, &buf_b)) {
+    PyObject *stream_a = NULL;
+    PyObject *stream_b = NULL;
+    Py_ssize_t chunk_size = KOMPARU_DEFAULT_CHUNK_SIZE;
+    int size_precheck = 1;
+    int quick_check = 1;
+    PyObject *py_cancel = Py_None;
+
+    static char *kwlist[] = {
+        "stream_a", "stream_b", "chunk_size", "size_precheck", "quick_check", "cancel", NULL
+    };
+
+    if (!PyArg_ParseTupleAndKeywords(args, kwargs, "OO|nppO", kwlist,
+            &stream_a, &stream_b, &chunk_size, &size_precheck, &quick_check,
+            &py_cancel)) {
         return NULL;
     }
 
-    bool equal;
-    if (buf_a.len != buf_b.len) {
-        equal = false;
-    } else if (buf_a.len == 0) {
-        equal = true;
-    } else {
-        equal = (memcmp(buf_a.buf, buf_b.buf, (size_t)buf_a.len) == 0);
+    if (chunk_size <= 0) {
+        PyErr_SetString(PyExc_ValueError, "chunk_size must be positive");
+        return NULL;
     }
 
-    PyBuffer_Release(&buf_a);
-    PyBuffer_Release(&buf_b);
+    komparu_cancel_t *cancel;
+    if (get_cancel(py_cancel, &cancel) < 0) return NULL;
 
-    if (equal) Py_RETURN_TRUE;
-    Py_RETURN_FALSE;
-}
+    komparu_reader_t *reader_a = komparu_reader_stream_open(stream_a, "stream_a");
+    if (!reader_a) return NULL;
+    komparu_reader_t *reader_b = komparu_reader_stream_open(stream_b, "stream_b");
+    if (!reader_b) {
+        reader_a->close(reader_a);
+        return NULL;
+    }
 
-/* =========================================================================
- * Async task wrappers — C pool + eventfd/pipe for asyncio integration
- * ========================================================================= */
+    const char *err_msg = NULL;
+    komparu_result_t result = KOMPARU_EQUAL;
+    bool decided = false;
+    int err_code = 0;
 
-static void async_task_capsule_destructor(PyObject *capsule) {
-    komparu_async_task_t *task = PyCapsule_GetPointer(capsule, "komparu.async_task");
+    KOMPARU_GIL_STATE_DECL
+    KOMPARU_GIL_RELEASE()
+
+    if (quick_check && reader_a->seek && reader_b->seek) {
+        result = komparu_quick_check(reader_a, reader_b,
+                                     (size_t)chunk_size, &err_msg);
+        if (result == KOMPARU_DIFFERENT) {
+            decided = true;
+        } else if (result == KOMPARU_ERROR) {
+            reader_a->seek(reader_a, 0);
+            reader_b->seek(reader_b, 0);
+        }
+    }
+
+    if (!decided) {
+        result = komparu_compare_ex(reader_a, reader_b,
+                                    (size_t)chunk_size, (bool)size_precheck,
+                                    cancel, &err_msg);
+        if (result == KOMPARU_ERROR) err_code = errno;
+    }
+
+    KOMPARU_GIL_ACQUIRE()
+
+    /* An exception raised by either stream wins over the generic error */
+    int restored = komparu_reader_stream_restore_error(reader_a);
+    if (!restored) restored = komparu_reader_stream_restore_error(reader_b);
+    reader_a->close(reader_a);
+    reader_b->close(reader_b);
+    if (restored) return NULL;
+
+    if (PyErr_CheckSignals() < 0) return NULL;
+
+    switch (result) {
+        case KOMPARU_EQUAL:
+            Py_RETURN_TRUE;
+        case KOMPARU_DIFFERENT:
+            Py_RETURN_FALSE;
+        case KOMPARU_ERROR:
+            raise_source_error(err_code, NULL, "comparison error: %s",
+                               err_msg ? err_msg : "unknown");
+            return NULL;
+        default:
+            PyErr_SetString(PyExc_RuntimeError, "unexpected comparison result");
+            return NULL;
+    }
+}
+
+/* =========================================================================
+ * Python wrapper: compare_fds(fd_a, fd_b, ...) -> bool
+ *
+ * compare() over already-open descriptors: no path is resolved. Readers
+ * work on duplicates and use their own offsets, so the caller's
+ * descriptors stay open and keep their file positions.
+ * ========================================================================= */
+
+static PyObject *py_compare_fds(PyObject *self, PyObject *args, PyObject *kwargs) {
+    (void)self;
+
+    int fd_a = -1;
+    int fd_b = -1;
+    Py_ssize_t chunk_size = KOMPARU_DEFAULT_CHUNK_SIZE;
+    int size_precheck = 1;
+    int quick_check = 1;
+    PyObject *py_cancel = Py_None;
+
+    static char *kwlist[] = {
+        "fd_a", "fd_b", "chunk_size", "size_precheck", "quick_check", "cancel", NULL
+    };
+
+    if (!PyArg_ParseTupleAndKeywords(args, kwargs, "ii|nppO", kwlist,
+            &fd_a, &fd_b, &chunk_size, &size_precheck, &quick_check, &py_cancel)) {
+        return NULL;
+    }
+
+    if (chunk_size <= 0) {
+        PyErr_SetString(PyExc_ValueError, "chunk_size must be positive");
+        return NULL;
+    }
+    if (fd_a < 0 || fd_b < 0) {
+        PyErr_SetString(PyExc_ValueError, "file handle must be non-negative");
+        return NULL;
+    }
+
+    komparu_cancel_t *cancel;
+    if (get_cancel(py_cancel, &cancel) < 0) return NULL;
+
+    const char *err_msg = NULL;
+    komparu_result_t result;
+    komparu_reader_t *reader_a = NULL;
+    komparu_reader_t *reader_b = NULL;
+    int err_code = 0;
+
+    KOMPARU_GIL_STATE_DECL
+    KOMPARU_GIL_RELEASE()
+
+#ifndef KOMPARU_WINDOWS
+    /* Same-file short-circuit, as in compare() */
+    struct stat st_a, st_b;
+    if (fstat(fd_a, &st_a) == 0 && fstat(fd_b, &st_b) == 0 &&
+        S_ISREG(st_a.st_mode) &&
+        st_a.st_dev == st_b.st_dev && st_a.st_ino == st_b.st_ino) {
+        KOMPARU_GIL_ACQUIRE()
+        Py_RETURN_TRUE;
+    }
+#endif
+
+    reader_a = komparu_reader_file_fdopen(fd_a, &err_msg);
+    if (!reader_a) goto open_failed;
+    reader_b = komparu_reader_file_fdopen(fd_b, &err_msg);
+    if (!reader_b) goto open_failed;
+
+    if (quick_check) {
+        result = komparu_quick_check(reader_a, reader_b,
+                                     (size_t)chunk_size, &err_msg);
+        if (result == KOMPARU_DIFFERENT) goto done;
+        if (result == KOMPARU_ERROR) {
+            reader_a->seek(reader_a, 0);
+            reader_b->seek(reader_b, 0);
+        }
+    }
+
+    result = komparu_compare_ex(reader_a, reader_b,
+                                (size_t)chunk_size, (bool)size_precheck,
+                                cancel, &err_msg);
+    if (result == KOMPARU_ERROR) err_code = errno;
+    goto done;
+
+open_failed:
+    err_code = errno;
+    result = KOMPARU_ERROR;
+
+done:
+    if (reader_a) reader_a->close(reader_a);
+    if (reader_b) reader_b->close(reader_b);
+
+    KOMPARU_GIL_ACQUIRE()
+
+    if (PyErr_CheckSignals() < 0) return NULL;
+
+    switch (result) {
+        case KOMPARU_EQUAL:
+            Py_RETURN_TRUE;
+        case KOMPARU_DIFFERENT:
+            Py_RETURN_FALSE;
+        case KOMPARU_ERROR:
+            if (!reader_a || !reader_b) {
+                raise_source_error(err_code, NULL, "cannot open fd %d: %s",
+                                   reader_a ? fd_b : fd_a,
+                                   err_msg ? err_msg : "unknown error");
+            } else {
+                raise_source_error(err_code, NULL, "comparison error: %s",
+                                   err_msg ? err_msg : "unknown");
+            }
+            return NULL;
+        default:
+            PyErr_SetString(PyExc_RuntimeError, "unexpected comparison result");
+            return NULL;
+    }
+}
+
+/* =========================================================================
+ * Python wrapper: verify_zero(path, ...) -> bool
+ *
+ * compare() of a file or block device against a zero reader of its size:
+ * holes are skipped on both sides, so only allocated data is read.
+ * ========================================================================= */
+
+static PyObject *py_verify_zero(PyObject *self, PyObject *args, PyObject *kwargs) {
+    (void)self;
+
+    PyObject *py_path = NULL;
+    Py_ssize_t chunk_size = KOMPARU_DEFAULT_CHUNK_SIZE;
+    int quick_check = 1;
+    PyObject *py_cancel = Py_None;
+
+    static char *kwlist[] = {"path", "chunk_size", "quick_check", "cancel", NULL};
+
+    if (!PyArg_ParseTupleAndKeywords(args, kwargs, "O|npO", kwlist,
+            &py_path, &chunk_size, &quick_check, &py_cancel)) {
+        return NULL;
+    }
+
+    if (chunk_size <= 0) {
+        PyErr_SetString(PyExc_ValueError, "chunk_size must be positive");
+        return NULL;
+    }
+
+    komparu_cancel_t *cancel;
+    if (get_cancel(py_cancel, &cancel) < 0) return NULL;
+
+    char *path = path_dup(py_path);
+    if (!path) return NULL;
+
+    const char *err_msg = NULL;
+    komparu_result_t result;
+    komparu_reader_t *reader = NULL;
+    komparu_reader_t *zeros = NULL;
+    int err_code = 0;
+
+    KOMPARU_GIL_STATE_DECL
+    KOMPARU_GIL_RELEASE()
+
+    reader = komparu_reader_device_open(path, &err_msg);
+    if (!reader) goto open_failed;
+    if (reader->get_size(reader) < 0) {
+        /* A /proc or /sys file: zeros of which length? */
+        err_msg = "size unknown to the file system";
+        errno = ENOTSUP;
+        goto open_failed;
+    }
+    zeros = komparu_reader_zero_open(reader->get_size(reader), &err_msg);
+    if (!zeros) goto open_failed;
+
+    if (quick_check) {
+        result = komparu_quick_check(reader, zeros, (size_t)chunk_size, &err_msg);
+        if (result == KOMPARU_DIFFERENT) goto done;
+        if (result == KOMPARU_ERROR) {
+            reader->seek(reader, 0);
+            zeros->seek(zeros, 0);
+        }
+    }
+
+    result = komparu_compare_ex(reader, zeros, (size_t)chunk_size, false,
+                                cancel, &err_msg);
+    if (result == KOMPARU_ERROR) err_code = errno;
+    goto done;
+
+open_failed:
+    err_code = errno;
+    result = KOMPARU_ERROR;
+
+done:
+    if (reader) reader->close(reader);
+    if (zeros) zeros->close(zeros);
+
+    KOMPARU_GIL_ACQUIRE()
+
+    if (PyErr_CheckSignals() < 0) {
+        free(path);
+        return NULL;
+    }
+
+    PyObject *ret = NULL;
+    switch (result) {
+        case KOMPARU_EQUAL:
+            ret = Py_True;
+            Py_INCREF(ret);
+            break;
+        case KOMPARU_DIFFERENT:
+            ret = Py_False;
+            Py_INCREF(ret);
+            break;
+        case KOMPARU_ERROR:
+            if (!reader || !zeros) {
+                raise_source_error(err_code, path, "cannot open: %s",
+                                   err_msg ? err_msg : "unknown error");
+            } else {
+                raise_source_error(err_code, NULL, "comparison error: %s",
+                                   err_msg ? err_msg : "unknown");
+            }
+            break;
+        default:
+            PyErr_SetString(PyExc_RuntimeError, "unexpected comparison result");
+            break;
+    }
+    free(path);
+    return ret;
+}
+
+/* =========================================================================
+ * Python wrapper: compare_parts(parts_a, parts_b, ...) -> bool
+ *
+ * compare() of two sequences of local files, each read back to back as
+ * one file: split volumes against the original, or two splits of it.
+ * ========================================================================= */
+
+static void paths_free(char **paths, size_t count) {
+    for (size_t i = 0; i < count; i++) free(paths[i]);
+    free(paths);
+}
+
+/* Owned copies of a sequence of paths, as path_dup(); NULL with an
+ * exception set. */
+static char **paths_dup(PyObject *obj, size_t *count) {
+    PyObject *seq = PySequence_Fast(obj, "parts must be a sequence of paths");
+    if (!seq) return NULL;
+    size_t n = (size_t)PySequence_Fast_GET_SIZE(seq);
+    char **paths = calloc(n ? n : 1, sizeof(char *));
+    if (!paths) {
+        Py_DECREF(seq);
+        PyErr_NoMemory();
+        return NULL;
+    }
+    for (size_t i = 0; i < n; i++) {
+        paths[i] = path_dup(PySequence_Fast_GET_ITEM(seq, (Py_ssize_t)i));
+        if (!paths[i]) {
+            Py_DECREF(seq);
+            paths_free(paths, i);
+            return NULL;
+        }
+    }
+    Py_DECREF(seq);
+    *count = n;
+    return paths;
+}
+
+/* One reader over the files in paths; on error *failed is the path that
+ * could not be opened (NULL if none was at fault). */
+static komparu_reader_t *open_parts(char **paths, size_t count, const char **failed,
+                                    const char **err_msg) {
+    komparu_reader_t **readers = calloc(count ? count : 1, sizeof(*readers));
+    if (!readers) {
+        *err_msg = "out of memory";
+        return NULL;
+    }
+    for (size_t i = 0; i < count; i++) {
+        readers[i] = komparu_reader_file_open(paths[i], err_msg);
+        if (!readers[i]) {
+            int saved = errno;
+            for (size_t j = 0; j < i; j++) readers[j]->close(readers[j]);
+            free(readers);
+            *failed = paths[i];
+            errno = saved;
+            return NULL;
+        }
+    }
+    komparu_reader_t *reader = komparu_reader_concat_open(readers, count, err_msg);
+    free(readers);
+    return reader;
+}
+
+static PyObject *py_compare_parts(PyObject *self, PyObject *args, PyObject *kwargs) {
+    (void)self;
+
+    PyObject *py_parts_a = NULL;
+    PyObject *py_parts_b = NULL;
+    Py_ssize_t chunk_size = KOMPARU_DEFAULT_CHUNK_SIZE;
+    int size_precheck = 1;
+    int quick_check = 1;
+    PyObject *py_cancel = Py_None;
+
+    static char *kwlist[] = {
+        "parts_a", "parts_b", "chunk_size", "size_precheck", "quick_check", "cancel", NULL
+    };
+
+    if (!PyArg_ParseTupleAndKeywords(args, kwargs, "OO|nppO", kwlist,
+            &py_parts_a, &py_parts_b, &chunk_size, &size_precheck, &quick_check,
+            &py_cancel)) {
+        return NULL;
+    }
+
+    if (chunk_size <= 0) {
+        PyErr_SetString(PyExc_ValueError, "chunk_size must be positive");
+        return NULL;
+    }
+
+    komparu_cancel_t *cancel;
+    if (get_cancel(py_cancel, &cancel) < 0) return NULL;
+
+    size_t count_a = 0, count_b = 0;
+    char **paths_a = paths_dup(py_parts_a, &count_a);
+    if (!paths_a) return NULL;
+    char **paths_b = paths_dup(py_parts_b, &count_b);
+    if (!paths_b) {
+        paths_free(paths_a, count_a);
+        return NULL;
+    }
+
+    const char *err_msg = NULL;
+    const char *failed = NULL;
+    komparu_result_t result;
+    komparu_reader_t *reader_a = NULL;
+    komparu_reader_t *reader_b = NULL;
+    int err_code = 0;
+
+    KOMPARU_GIL_STATE_DECL
+    KOMPARU_GIL_RELEASE()
+
+    reader_a = open_parts(paths_a, count_a, &failed, &err_msg);
+    if (!reader_a) goto open_failed;
+    reader_b = open_parts(paths_b, count_b, &failed, &err_msg);
+    if (!reader_b) goto open_failed;
+
+    if (quick_check) {
+        result = komparu_quick_check(reader_a, reader_b, (size_t)chunk_size, &err_msg);
+        if (result == KOMPARU_DIFFERENT) goto done;
+        if (result == KOMPARU_ERROR) {
+            reader_a->seek(reader_a, 0);
+            reader_b->seek(reader_b, 0);
+        }
+    }
+
+    result = komparu_compare_ex(reader_a, reader_b, (size_t)chunk_size,
+                                (bool)size_precheck, cancel, &err_msg);
+    if (result == KOMPARU_ERROR) err_code = errno;
+    goto done;
+
+open_failed:
+    err_code = errno;
+    result = KOMPARU_ERROR;
+
+done:
+    if (reader_a) reader_a->close(reader_a);
+    if (reader_b) reader_b->close(reader_b);
+
+    KOMPARU_GIL_ACQUIRE()
+
+    PyObject *ret = NULL;
+    if (PyErr_CheckSignals() < 0) goto out;
+
+    switch (result) {
+        case KOMPARU_EQUAL:
+            ret = Py_True;
+            Py_INCREF(ret);
+            break;
+        case KOMPARU_DIFFERENT:
+            ret = Py_False;
+            Py_INCREF(ret);
+            break;
+        case KOMPARU_ERROR:
+            if (!reader_a || !reader_b) {
+                raise_source_error(err_code, failed, "cannot open: %s",
+                                   err_msg ? err_msg : "unknown error");
+            } else {
+                raise_source_error(err_code, NULL, "comparison error: %s",
+                                   err_msg ? err_msg : "unknown");
+            }
+            break;
+        default:
+            PyErr_SetString(PyExc_RuntimeError, "unexpected comparison result");
+            break;
+    }
+out:
+    paths_free(paths_a, count_a);
+    paths_free(paths_b, count_b);
+    return ret;
+}
+
+/* =========================================================================
+ * Python wrapper: compare_buffers(buf_a, buf_b) -> bool
+ * ========================================================================= */
+
+static PyObject *py_compare_buffers(PyObject *self, PyObject *args) {
+    (void)self;
+
+    Py_buffer buf_a, buf_b;
+    if (!PyArg_ParseTuple(args, "y*y*", &buf_a, &buf_b)) {
+        return NULL;
+    }
+
+    bool equal;
+    if (buf_a.len != buf_b.len) {
+        equal = false;
+    } else if (buf_a.len == 0) {
+        equal = true;
+    } else {
+        equal = (memcmp(buf_a.buf, buf_b.buf, (size_t)buf_a.len) == 0);
+    }
+
+    PyBuffer_Release(&buf_a);
+    PyBuffer_Release(&buf_b);
+
+    if (equal) Py_RETURN_TRUE;
+    Py_RETURN_FALSE;
+}
+
+/* =========================================================================
+ * Python wrappers: first_diff / diff_blocks / fuzzy_digest / cdc_chunks /
+ * crc32c / rsum_blocks / rsum_scan over bytes-like objects
+ * ========================================================================= */
+
+static PyObject *py_first_diff(PyObject *self, PyObject *args) {
+    (void)self;
+
+    Py_buffer buf_a, buf_b;
+    if (!PyArg_ParseTuple(args, "y*y*", &buf_a, &buf_b)) {
+        return NULL;
+    }
+
+    int64_t offset;
+    KOMPARU_GIL_STATE_DECL
+    KOMPARU_GIL_RELEASE()
+    offset = komparu_mem_first_diff(buf_a.buf, (size_t)buf_a.len,
+                                    buf_b.buf, (size_t)buf_b.len);
+    KOMPARU_GIL_ACQUIRE()
+
+    PyBuffer_Release(&buf_a);
+    PyBuffer_Release(&buf_b);
+    return PyLong_FromLongLong((long long)offset);
+}
+
+static PyObject *py_diff_blocks(PyObject *self, PyObject *args) {
+    (void)self;
+
+    Py_buffer buf_a, buf_b;
+    Py_ssize_t block_size;
+    if (!PyArg_ParseTuple(args, "y*y*n", &buf_a, &buf_b, &block_size)) {
+        return NULL;
+    }
+    if (block_size <= 0) {
+        PyBuffer_Release(&buf_a);
+        PyBuffer_Release(&buf_b);
+        PyErr_SetString(PyExc_ValueError, "block_size must be positive");
+        return NULL;
+    }
+
+    size_t count;
+    KOMPARU_GIL_STATE_DECL
+    KOMPARU_GIL_RELEASE()
+    count = komparu_mem_diff_blocks(buf_a.buf, (size_t)buf_a.len,
+                                    buf_b.buf, (size_t)buf_b.len,
+                                    (size_t)block_size);
+    KOMPARU_GIL_ACQUIRE()
+
+    PyBuffer_Release(&buf_a);
+    PyBuffer_Release(&buf_b);
+    return PyLong_FromSize_t(count);
+}
+
+static PyObject *py_fuzzy_digest(PyObject *self, PyObject *args) {
+    (void)self;
+
+    Py_buffer buf;
+    unsigned long block_size;
+    if (!PyArg_ParseTuple(args, "y*k", &buf, &block_size)) {
+        return NULL;
+    }
+    if (block_size == 0 || block_size > UINT32_MAX) {
+        PyBuffer_Release(&buf);
+        PyErr_SetString(PyExc_ValueError, "block_size must be positive and fit in 32 bits");
+        return NULL;
+    }
+
+    char digest1[KOMPARU_FUZZY_DIGEST_MAX + 1];
+    char digest2[KOMPARU_FUZZY_DIGEST_MAX / 2 + 1];
+    KOMPARU_GIL_STATE_DECL
+    KOMPARU_GIL_RELEASE()
+    komparu_mem_fuzzy_digest(buf.buf, (size_t)buf.len, (uint32_t)block_size,
+                             digest1, digest2);
+    KOMPARU_GIL_ACQUIRE()
+
+    PyBuffer_Release(&buf);
+    return Py_BuildValue("(ss)", digest1, digest2);
+}
+
+static PyObject *py_cdc_chunks(PyObject *self, PyObject *args) {
+    (void)self;
+
+    Py_buffer buf;
+    Py_ssize_t min_size, avg_size, max_size;
+    if (!PyArg_ParseTuple(args, "y*nnn", &buf, &min_size, &avg_size, &max_size)) {
+        return NULL;
+    }
+    if (min_size <= 0 || avg_size < min_size || max_size < avg_size) {
+        PyBuffer_Release(&buf);
+        PyErr_SetString(PyExc_ValueError,
+                        "chunk sizes must satisfy 0 < min_size <= avg_size <= max_size");
+        return NULL;
+    }
+
+    size_t cap = (size_t)buf.len / (size_t)min_size + 1;
+    size_t *cuts = malloc(cap * sizeof(size_t));
+    if (!cuts) {
+        PyBuffer_Release(&buf);
+        return PyErr_NoMemory();
+    }
+    size_t count;
+    KOMPARU_GIL_STATE_DECL
+    KOMPARU_GIL_RELEASE()
+    count = komparu_mem_cdc(buf.buf, (size_t)buf.len, (size_t)min_size,
+                            (size_t)avg_size, (size_t)max_size, cuts);
+    KOMPARU_GIL_ACQUIRE()
+    PyBuffer_Release(&buf);
+
+    PyObject *list = PyList_New((Py_ssize_t)count);
+    for (size_t i = 0; list && i < count; i++) {
+        PyObject *end = PyLong_FromSize_t(cuts[i]);
+        if (!end) {
+            Py_CLEAR(list);
+            break;
+        }
+        PyList_SET_ITEM(list, (Py_ssize_t)i, end);
+    }
+    free(cuts);
+    return list;
+}
+
+static PyObject *py_crc32c(PyObject *self, PyObject *args) {
+    (void)self;
+
+    Py_buffer buf;
+    unsigned long crc = 0;
+    if (!PyArg_ParseTuple(args, "y*|k", &buf, &crc)) {
+        return NULL;
+    }
+    if (crc > UINT32_MAX) {
+        PyBuffer_Release(&buf);
+        PyErr_SetString(PyExc_ValueError, "crc must fit in 32 bits");
+        return NULL;
+    }
+
+    uint32_t result;
+    KOMPARU_GIL_STATE_DECL
+    KOMPARU_GIL_RELEASE()
+    result = komparu_crc32c((uint32_t)crc, buf.buf, (size_t)buf.len);
+    KOMPARU_GIL_ACQUIRE()
+
+    PyBuffer_Release(&buf);
+    return PyLong_FromUnsignedLong(result);
+}
+
+static PyObject *py_rsum_blocks(PyObject *self, PyObject *args) {
+    (void)self;
+
+    Py_buffer buf;
+    Py_ssize_t block_size;
+    if (!PyArg_ParseTuple(args, "y*n", &buf, &block_size)) {
+        return NULL;
+    }
+    if (block_size <= 0) {
+        PyBuffer_Release(&buf);
+        PyErr_SetString(PyExc_ValueError, "block_size must be positive");
+        return NULL;
+    }
+
+    Py_ssize_t count = buf.len / block_size;
+    PyObject *sums = PyBytes_FromStringAndSize(NULL, count * (Py_ssize_t)sizeof(uint32_t));
+    if (!sums) {
+        PyBuffer_Release(&buf);
+        return NULL;
+    }
+    uint32_t *out = (uint32_t *)PyBytes_AS_STRING(sums);
+    KOMPARU_GIL_STATE_DECL
+    KOMPARU_GIL_RELEASE()
+    komparu_rsum_blocks(buf.buf, (size_t)buf.len, (size_t)block_size, out);
+    KOMPARU_GIL_ACQUIRE()
+
+    PyBuffer_Release(&buf);
+    return sums;
+}
+
+static PyObject *py_rsum_scan(PyObject *self, PyObject *args) {
+    (void)self;
+
+    Py_buffer buf, table;
+    Py_ssize_t start, block_size;
+    if (!PyArg_ParseTuple(args, "y*nny*", &buf, &start, &block_size, &table)) {
+        return NULL;
+    }
+    if (block_size <= 0 || start < 0 || table.len % (Py_ssize_t)sizeof(uint32_t)) {
+        PyBuffer_Release(&buf);
+        PyBuffer_Release(&table);
+        PyErr_SetString(PyExc_ValueError,
+                        "block_size must be positive, start non-negative and the table "
+                        "a whole number of 32-bit sums");
+        return NULL;
+    }
+
+    size_t from = (size_t)start < (size_t)buf.len ? (size_t)start : (size_t)buf.len;
+    size_t cap = ((size_t)buf.len - from) / (size_t)block_size + 1;
+    size_t *hits = malloc(cap * sizeof(size_t));
+    if (!hits) {
+        PyBuffer_Release(&buf);
+        PyBuffer_Release(&table);
+        return PyErr_NoMemory();
+    }
+    size_t count;
+    KOMPARU_GIL_STATE_DECL
+    KOMPARU_GIL_RELEASE()
+    count = komparu_rsum_scan(buf.buf, (size_t)buf.len, from, (size_t)block_size,
+                              table.buf, (size_t)table.len / sizeof(uint32_t), hits);
+    KOMPARU_GIL_ACQUIRE()
+    PyBuffer_Release(&buf);
+    PyBuffer_Release(&table);
+
+    PyObject *list = PyList_New((Py_ssize_t)count);
+    for (size_t i = 0; list && i < count; i++) {
+        PyObject *hit = PyLong_FromSize_t(hits[i]);
+        if (!hit) {
+            Py_CLEAR(list);
+            break;
+        }
+        PyList_SET_ITEM(list, (Py_ssize_t)i, hit);
+    }
+    free(hits);
+    return list;
+}
+
+#ifdef KOMPARU_LINUX
+/* =========================================================================
+ * inotify — change notifications for komparu.watch_dir
+ * ========================================================================= */
+
+#define KOMPARU_WATCH_MASK (IN_CREATE | IN_DELETE | IN_MODIFY | IN_CLOSE_WRITE | IN_ATTRIB \
+                            | IN_MOVED_FROM | IN_MOVED_TO | IN_DELETE_SELF | IN_MOVE_SELF)
+
+static PyObject *py_inotify_init(PyObject *self, PyObject *Py_UNUSED(ignored)) {
+    (void)self;
+    int fd = inotify_init1(IN_NONBLOCK | IN_CLOEXEC);
+    if (fd < 0) return PyErr_SetFromErrno(PyExc_OSError);
+    return PyLong_FromLong(fd);
+}
+
+static PyObject *py_inotify_add_watch(PyObject *self, PyObject *args) {
+    (void)self;
+    int fd, follow_symlinks;
+    PyObject *path;
+    if (!PyArg_ParseTuple(args, "iO&p", &fd, PyUnicode_FSConverter, &path, &follow_symlinks)) {
+        return NULL;
+    }
+    uint32_t mask = KOMPARU_WATCH_MASK | IN_ONLYDIR | (follow_symlinks ? 0 : IN_DONT_FOLLOW);
+    int wd = inotify_add_watch(fd, PyBytes_AS_STRING(path), mask);
+    if (wd < 0) {
+        PyErr_SetFromErrnoWithFilenameObject(PyExc_OSError, path);
+        Py_DECREF(path);
+        return NULL;
+    }
+    Py_DECREF(path);
+    return PyLong_FromLong(wd);
+}
+
+/* Every queued event as (wd, created_or_moved_in_dir, overflow, name); [] once drained. */
+static PyObject *py_inotify_read(PyObject *self, PyObject *arg) {
+    (void)self;
+    long fd = PyLong_AsLong(arg);
+    if (fd == -1 && PyErr_Occurred()) return NULL;
+    PyObject *events = PyList_New(0);
+    if (!events) return NULL;
+    _Alignas(struct inotify_event) char buf[16384];
+    for (;;) {
+        ssize_t n;
+        KOMPARU_GIL_STATE_DECL
+        KOMPARU_GIL_RELEASE()
+        n = read((int)fd, buf, sizeof(buf));
+        KOMPARU_GIL_ACQUIRE()
+        if (n < 0 && errno == EINTR) {
+            if (PyErr_CheckSignals() < 0) goto fail;
+            continue;
+        }
+        if (n < 0 && errno == EAGAIN) return events;
+        if (n < 0) {
+            PyErr_SetFromErrno(PyExc_OSError);
+            goto fail;
+        }
+        for (char *p = buf; p < buf + n;) {
+            const struct inotify_event *ev = (const struct inotify_event *)p;
+            p += sizeof(*ev) + ev->len;
+            if (ev->mask & IN_IGNORED) continue;
+            bool new_dir = (ev->mask & IN_ISDIR) && (ev->mask & (IN_CREATE | IN_MOVED_TO));
+            PyObject *item = Py_BuildValue(
+                "(iNNN)", ev->wd, PyBool_FromLong(new_dir),
+                PyBool_FromLong(ev->mask & IN_Q_OVERFLOW),
+                PyUnicode_DecodeFSDefault(ev->len ? ev->name : ""));
+            if (!item || PyList_Append(events, item) < 0) {
+                Py_XDECREF(item);
+                goto fail;
+            }
+            Py_DECREF(item);
+        }
+    }
+fail:
+    Py_DECREF(events);
+    return NULL;
+}
+
+/* =========================================================================
+ * fanotify — whole file system change notifications (CAP_SYS_ADMIN, 5.9+)
+ * ========================================================================= */
+
+#define KOMPARU_FANOTIFY_MASK (FAN_CREATE | FAN_DELETE | FAN_MODIFY | FAN_CLOSE_WRITE \
+                               | FAN_ATTRIB | FAN_MOVED_FROM | FAN_MOVED_TO | FAN_DELETE_SELF \
+                               | FAN_MOVE_SELF | FAN_ONDIR)
+#define KOMPARU_FANOTIFY_MAX_MOUNTS 8
+
+static PyObject *py_fanotify_init(PyObject *self, PyObject *Py_UNUSED(ignored)) {
+    (void)self;
+    int fd = fanotify_init(FAN_CLASS_NOTIF | FAN_REPORT_DFID_NAME | FAN_NONBLOCK | FAN_CLOEXEC,
+                           O_RDONLY | O_LARGEFILE);
+    if (fd < 0) return PyErr_SetFromErrno(PyExc_OSError);
+    return PyLong_FromLong(fd);
+}
+
+static PyObject *py_fanotify_mark(PyObject *self, PyObject *args) {
+    (void)self;
+    int fd;
+    PyObject *path;
+    if (!PyArg_ParseTuple(args, "iO&", &fd, PyUnicode_FSConverter, &path)) return NULL;
+    int rc = fanotify_mark(fd, FAN_MARK_ADD | FAN_MARK_FILESYSTEM, KOMPARU_FANOTIFY_MASK,
+                           AT_FDCWD, PyBytes_AS_STRING(path));
+    if (rc < 0) {
+        PyErr_SetFromErrnoWithFilenameObject(PyExc_OSError, path);
+        Py_DECREF(path);
+        return NULL;
+    }
+    Py_DECREF(path);
+    Py_RETURN_NONE;
+}
+
+/* Path of the entry an event names, through the mount of the descriptor on its file
+ * system; None if it is gone (its removal has an event of its own). */
+static PyObject *fanotify_path(const struct fanotify_event_info_fid *fid, const int *mount_fds,
+                               const fsid_t *fsids, Py_ssize_t mounts) {
+    struct file_handle *handle = (struct file_handle *)fid->handle;
+    const char *name = NULL;
+    if (fid->hdr.info_type == FAN_EVENT_INFO_TYPE_DFID_NAME) {
+        name = (const char *)handle->f_handle + handle->handle_bytes;
+    }
+    int dir = -1;
+    for (Py_ssize_t i = 0; i < mounts && dir < 0; i++) {
+        if (memcmp(&fsids[i], &fid->fsid, sizeof(fsids[i])) == 0) {
+            dir = open_by_handle_at(mount_fds[i], handle, O_PATH | O_CLOEXEC);
+        }
+    }
+    if (dir < 0) Py_RETURN_NONE;
+    char link[64], path[PATH_MAX];
+    snprintf(link, sizeof(link), "/proc/self/fd/%d", dir);
+    ssize_t len = readlink(link, path, sizeof(path) - 1);
+    close(dir);
+    static const char deleted[] = " (deleted)";
+    if (len < 0 || (len >= (ssize_t)sizeof(deleted) - 1
+                    && memcmp(path + len - (sizeof(deleted) - 1), deleted,
+                              sizeof(deleted) - 1) == 0)) {
+        Py_RETURN_NONE;
+    }
+    path[len] = '\0';
+    if (name == NULL || strcmp(name, ".") == 0) return PyUnicode_DecodeFSDefault(path);
+    char full[PATH_MAX + NAME_MAX + 2];
+    snprintf(full, sizeof(full), "%s%s%s", path, strcmp(path, "/") ? "/" : "", name);
+    return PyUnicode_DecodeFSDefault(full);
+}
+
+/* (paths, overflow): every queued event as the path it names; [] once drained. */
+static PyObject *py_fanotify_read(PyObject *self, PyObject *args) {
+    (void)self;
+    int fd;
+    PyObject *fds;
+    if (!PyArg_ParseTuple(args, "iO", &fd, &fds)) return NULL;
+    PyObject *seq = PySequence_Fast(fds, "mount_fds must be a sequence");
+    if (!seq) return NULL;
+    Py_ssize_t mounts = PySequence_Fast_GET_SIZE(seq);
+    if (mounts > KOMPARU_FANOTIFY_MAX_MOUNTS) {
+        Py_DECREF(seq);
+        PyErr_SetString(PyExc_ValueError, "too many mount descriptors");
+        return NULL;
+    }
+    int mount_fds[KOMPARU_FANOTIFY_MAX_MOUNTS];
+    fsid_t fsids[KOMPARU_FANOTIFY_MAX_MOUNTS];
+    for (Py_ssize_t i = 0; i < mounts; i++) {
+        mount_fds[i] = (int)PyLong_AsLong(PySequence_Fast_GET_ITEM(seq, i));
+        struct statfs st;
+        if (PyErr_Occurred() || fstatfs(mount_fds[i], &st) < 0) {
+            Py_DECREF(seq);
+            return PyErr_Occurred() ? NULL : PyErr_SetFromErrno(PyExc_OSError);
+        }
+        fsids[i] = st.f_fsid;
+    }
+    Py_DECREF(seq);
+    PyObject *paths = PyList_New(0);
+    if (!paths) return NULL;
+    bool overflow = false;
+    _Alignas(struct fanotify_event_metadata) char buf[16384];
+    for (;;) {
+        ssize_t n;
+        KOMPARU_GIL_STATE_DECL
+        KOMPARU_GIL_RELEASE()
+        n = read(fd, buf, sizeof(buf));
+        KOMPARU_GIL_ACQUIRE()
+        if (n < 0 && errno == EINTR) {
+            if (PyErr_CheckSignals() < 0) goto fail;
+            continue;
+        }
+        if (n < 0 && errno == EAGAIN) break;
+        if (n < 0) {
+            PyErr_SetFromErrno(PyExc_OSError);
+            goto fail;
+        }
+        for (struct fanotify_event_metadata *ev = (struct fanotify_event_metadata *)buf;
+             FAN_EVENT_OK(ev, n); ev = FAN_EVENT_NEXT(ev, n)) {
+            if (ev->vers != FANOTIFY_METADATA_VERSION) {
+                PyErr_SetString(PyExc_OSError, "unknown fanotify event version");
+                goto fail;
+            }
+            if (ev->mask & FAN_Q_OVERFLOW) {
+                overflow = true;
+                continue;
+            }
+            const char *info = (const char *)ev + ev->metadata_len;
+            const char *end = (const char *)ev + ev->event_len;
+            while (info + sizeof(struct fanotify_event_info_header) <= end) {
+                const struct fanotify_event_info_fid *fid =
+                    (const struct fanotify_event_info_fid *)info;
+                if (fid->hdr.len == 0) break;
+                info += fid->hdr.len;
+                if (fid->hdr.info_type != FAN_EVENT_INFO_TYPE_DFID_NAME
+                    && fid->hdr.info_type != FAN_EVENT_INFO_TYPE_DFID) {
+                    continue;
+                }
+                PyObject *path = fanotify_path(fid, mount_fds, fsids, mounts);
+                if (!path) goto fail;
+                int rc = path == Py_None ? 0 : PyList_Append(paths, path);
+                Py_DECREF(path);
+                if (rc < 0) goto fail;
+            }
+        }
+    }
+    return Py_BuildValue("(NN)", paths, PyBool_FromLong(overflow));
+fail:
+    Py_DECREF(paths);
+    return NULL;
+}
+
+/* =========================================================================
+ * Birth time and inode flags — metadata os.stat does not give on Linux
+ * ========================================================================= */
+
+/* Nanoseconds since the epoch, or None where the file system keeps no birth time. */
+static PyObject *py_birth_time(PyObject *self, PyObject *args) {
+    (void)self;
+    int follow_symlinks;
+    PyObject *path;
+    if (!PyArg_ParseTuple(args, "O&p", PyUnicode_FSConverter, &path, &follow_symlinks)) {
+        return NULL;
+    }
+    struct statx stx;
+    int rc;
+    Py_BEGIN_ALLOW_THREADS
+    rc = statx(AT_FDCWD, PyBytes_AS_STRING(path),
+               AT_STATX_SYNC_AS_STAT | (follow_symlinks ? 0 : AT_SYMLINK_NOFOLLOW),
+               STATX_BTIME, &stx);
+    Py_END_ALLOW_THREADS
+    if (rc < 0) {
+        PyErr_SetFromErrnoWithFilenameObject(PyExc_OSError, path);
+        Py_DECREF(path);
+        return NULL;
+    }
+    Py_DECREF(path);
+    if (!(stx.stx_mask & STATX_BTIME)) Py_RETURN_NONE;
+    return PyLong_FromLongLong((long long)stx.stx_btime.tv_sec * 1000000000LL
+                               + stx.stx_btime.tv_nsec);
+}
+
+/* chattr flags of a file or directory; 0 for other entries and file systems without them. */
+static PyObject *py_inode_flags(PyObject *self, PyObject *args) {
+    (void)self;
+    int follow_symlinks;
+    PyObject *path;
+    if (!PyArg_ParseTuple(args, "O&p", PyUnicode_FSConverter, &path, &follow_symlinks)) {
+        return NULL;
+    }
+    /* The kernel reads and writes an int, whatever the ioctl number says */
+    int flags = 0, err = 0;
+    Py_BEGIN_ALLOW_THREADS
+    int fd = open(PyBytes_AS_STRING(path), O_RDONLY | O_NONBLOCK | O_NOCTTY | O_CLOEXEC
+                  | (follow_symlinks ? 0 : O_NOFOLLOW));
+    if (fd < 0) {
+        err = errno;
+    } else {
+        struct stat st;
+        if (fstat(fd, &st) < 0) {
+            err = errno;
+        } else if ((S_ISREG(st.st_mode) || S_ISDIR(st.st_mode))
+                   && ioctl(fd, FS_IOC_GETFLAGS, &flags) < 0) {
+            err = errno;
+        }
+        close(fd);
+    }
+    Py_END_ALLOW_THREADS
+    if (err == ELOOP && !follow_symlinks) err = 0;  /* a symlink: no flags */
+    if (err == ENOTTY || err == ENOTSUP || err == EOPNOTSUPP || err == EINVAL) err = 0;
+    if (err) {
+        errno = err;
+        PyErr_SetFromErrnoWithFilenameObject(PyExc_OSError, path);
+        Py_DECREF(path);
+        return NULL;
+    }
+    Py_DECREF(path);
+    return PyLong_FromLong(flags);
+}
+#endif /* KOMPARU_LINUX */
+
+/* =========================================================================
+ * Async task wrappers — C pool + eventfd/pipe for asyncio integration
+ * ========================================================================= */
+
+static void async_task_capsule_destructor(PyObject *capsule) {
+    komparu_async_task_t *task = PyCapsule_GetPointer(capsule, "komparu.async_task");
     if (task) komparu_async_task_free(task);
 }
 
 static PyObject *py_async_compare_start(PyObject *self, PyObject *args, PyObject *kwargs) {
     (void)self;
 
-    const char *source_a = NULL;
-    const char *source_b = NULL;
+    PyObject *source_a = NULL;
+    PyObject *source_b = NULL;
     Py_ssize_t chunk_size = KOMPARU_DEFAULT_CHUNK_SIZE;
     int size_precheck = 1;
     int quick_check = 1;
@@ -827,17 +2331,18 @@ static PyObject *py_async_compare_start(PyObject *self, PyObject *args, PyObject
     int verify_ssl = 1;
     int allow_private = 0;
     const char *proxy = NULL;
+    PyObject *py_cancel = Py_None;
 
     static char *kwlist[] = {
         "source_a", "source_b", "chunk_size", "size_precheck", "quick_check",
         "headers", "timeout", "follow_redirects", "verify_ssl", "allow_private",
-        "proxy", NULL
+        "proxy", "cancel", NULL
     };
 
-    if (!PyArg_ParseTupleAndKeywords(args, kwargs, "ss|nppOdpppz", kwlist,
+    if (!PyArg_ParseTupleAndKeywords(args, kwargs, "OO|nppOdpppzO", kwlist,
             &source_a, &source_b, &chunk_size, &size_precheck, &quick_check,
             &py_headers, &timeout, &follow_redirects, &verify_ssl,
-            &allow_private, &proxy)) {
+            &allow_private, &proxy, &py_cancel)) {
         return NULL;
     }
 
@@ -846,6 +2351,9 @@ static PyObject *py_async_compare_start(PyObject *self, PyObject *args, PyObject
         return NULL;
     }
 
+    komparu_cancel_t *cancel;
+    if (get_cancel(py_cancel, &cancel) < 0) return NULL;
+
     if (py_headers != Py_None && !PyDict_Check(py_headers)) {
         PyErr_SetString(PyExc_TypeError, "headers must be a dict or None");
         return NULL;
@@ -859,16 +2367,25 @@ static PyObject *py_async_compare_start(PyObject *self, PyObject *args, PyObject
         PyErr_SetString(PyExc_ValueError, err_msg);
         return NULL;
     }
+    char *src_a = path_dup(source_a);
+    char *src_b = src_a ? path_dup(source_b) : NULL;
+    if (!src_b) {
+        free(src_a);
+        free_header_array(header_array, header_count);
+        return NULL;
+    }
 
     /* Submit async task — runs in C pool, no GIL */
     komparu_async_task_t *task = komparu_async_compare(
-        source_a, source_b, header_array,
+        src_a, src_b, header_array,
         (size_t)chunk_size, (bool)size_precheck, (bool)quick_check,
         timeout, (bool)follow_redirects, (bool)verify_ssl, (bool)allow_private,
-        proxy, &err_msg
+        proxy, cancel, &err_msg
     );
 
     free_header_array(header_array, header_count);
+    free(src_a);
+    free(src_b);
 
     if (!task) {
         PyErr_Format(PyExc_RuntimeError, "async compare failed: %s",
@@ -899,7 +2416,9 @@ static PyObject *py_async_compare_result(PyObject *self, PyObject *arg) {
     const char *err_msg = NULL;
     bool result;
     if (komparu_async_task_cmp_result(task, &result, &err_msg) != 0) {
-        PyErr_Format(PyExc_IOError, "%s", err_msg ? err_msg : "unknown error");
+        const char *path = NULL;
+        int code = komparu_async_task_error_code(task, &path);
+        raise_source_error(code, path, "%s", err_msg ? err_msg : "unknown error");
         return NULL;
     }
 
@@ -910,22 +2429,35 @@ static PyObject *py_async_compare_result(PyObject *self, PyObject *arg) {
 static PyObject *py_async_compare_dir_start(PyObject *self, PyObject *args, PyObject *kwargs) {
     (void)self;
 
-    const char *dir_a = NULL;
-    const char *dir_b = NULL;
+    PyObject *dir_a = NULL;
+    PyObject *dir_b = NULL;
     Py_ssize_t chunk_size = KOMPARU_DEFAULT_CHUNK_SIZE;
     int size_precheck = 1;
     int quick_check = 1;
     int follow_symlinks = 1;
     Py_ssize_t max_workers = 0;
+    PyObject *py_cancel = Py_None;
+    int retries = 0;
+    double retry_delay = 0.1;
+    double retry_max_delay = 2.0;
+    int dirfd_a = -1;
+    int dirfd_b = -1;
+    PyObject *py_cache = Py_None;
+    int lock = 0;
+    double lock_timeout = -1.0;
 
     static char *kwlist[] = {
         "dir_a", "dir_b", "chunk_size", "size_precheck",
-        "quick_check", "follow_symlinks", "max_workers", NULL
+        "quick_check", "follow_symlinks", "max_workers", "cancel",
+        "retries", "retry_delay", "retry_max_delay", "dirfd_a", "dirfd_b",
+        "cache", "lock", "lock_timeout", NULL
     };
 
-    if (!PyArg_ParseTupleAndKeywords(args, kwargs, "ss|npppn", kwlist,
+    if (!PyArg_ParseTupleAndKeywords(args, kwargs, "OO|npppnOiddiiOpd", kwlist,
             &dir_a, &dir_b, &chunk_size, &size_precheck,
-            &quick_check, &follow_symlinks, &max_workers)) {
+            &quick_check, &follow_symlinks, &max_workers, &py_cancel,
+            &retries, &retry_delay, &retry_max_delay, &dirfd_a, &dirfd_b,
+            &py_cache, &lock, &lock_timeout)) {
         return NULL;
     }
 
@@ -934,16 +2466,39 @@ static PyObject *py_async_compare_dir_start(PyObject *self, PyObject *args, PyOb
         return NULL;
     }
 
+    komparu_cancel_t *cancel;
+    if (get_cancel(py_cancel, &cancel) < 0) return NULL;
+    komparu_retry_t retry;
+    if (get_retry(retries, retry_delay, retry_max_delay, &retry) < 0) return NULL;
+    komparu_lock_t lock_policy = { .timeout = lock_timeout };
+    if (get_dirfd(dirfd_a, &dirfd_a) < 0 || get_dirfd(dirfd_b, &dirfd_b) < 0) return NULL;
+    komparu_dir_cache_t *cache;
+    if (get_dir_cache(py_cache, &cache) < 0) return NULL;
+    char *da = path_dup(dir_a);
+    char *db = da ? path_dup(dir_b) : NULL;
+    if (!db) {
+        free(da);
+        return NULL;
+    }
+
     const char *err_msg = NULL;
     komparu_async_task_t *task = komparu_async_compare_dir(
-        dir_a, dir_b,
+        dirfd_a, da, dirfd_b, db,
         (size_t)chunk_size, (bool)size_precheck, (bool)quick_check,
         (bool)follow_symlinks,
         (size_t)(max_workers >= 0 ? max_workers : 0),
-        &err_msg
+        &retry, lock ? &lock_policy : NULL, cache, cancel, &err_msg
     );
+    int saved = errno;
+    free(da);
+    free(db);
+    errno = saved;
 
     if (!task) {
+        if (errno == EBADF) {
+            raise_source_error(EBADF, NULL, "async compare_dir failed: %s", err_msg);
+            return NULL;
+        }
         PyErr_Format(PyExc_RuntimeError, "async compare_dir failed: %s",
                      err_msg ? err_msg : "unknown error");
         return NULL;
@@ -972,8 +2527,10 @@ static PyObject *py_async_compare_dir_result(PyObject *self, PyObject *arg) {
     const char *err_msg = NULL;
     komparu_dir_result_t *result = komparu_async_task_dir_result(task, &err_msg);
     if (!result) {
-        PyErr_Format(PyExc_IOError, "directory comparison failed: %s",
-                     err_msg ? err_msg : "unknown error");
+        const char *path = NULL;
+        int code = komparu_async_task_error_code(task, &path);
+        raise_source_error(code, path, "directory comparison failed: %s",
+                           err_msg ? err_msg : "unknown error");
         return NULL;
     }
 
@@ -982,6 +2539,173 @@ static PyObject *py_async_compare_dir_result(PyObject *self, PyObject *arg) {
     return py_result;
 }
 
+/* =========================================================================
+ * Async streaming directory comparison
+ * ========================================================================= */
+
+static const char *entry_kind_str(komparu_entry_kind_t kind) {
+    switch (kind) {
+        case KOMPARU_ENTRY_EQUAL:      return "equal";
+        case KOMPARU_ENTRY_DIFF:       return "diff";
+        case KOMPARU_ENTRY_ONLY_LEFT:  return "only_left";
+        case KOMPARU_ENTRY_ONLY_RIGHT: return "only_right";
+        case KOMPARU_ENTRY_ERROR:      return "error";
+        default:                       return "error";
+    }
+}
+
+static const char *dir_event_str(const komparu_dir_event_t *ev) {
+    switch (ev->type) {
+        case KOMPARU_DIR_EVENT_STARTED:     return "started";
+        case KOMPARU_DIR_EVENT_DIR_ENTERED: return "dir_entered";
+        case KOMPARU_DIR_EVENT_DIR_LEFT:    return "dir_left";
+        default:                            return entry_kind_str(ev->kind);
+    }
+}
+
+static PyObject *py_async_dir_stream_start(PyObject *self, PyObject *args, PyObject *kwargs) {
+    (void)self;
+
+    PyObject *dir_a = NULL;
+    PyObject *dir_b = NULL;
+    Py_ssize_t chunk_size = KOMPARU_DEFAULT_CHUNK_SIZE;
+    int size_precheck = 1;
+    int quick_check = 1;
+    int follow_symlinks = 1;
+    Py_ssize_t max_workers = 0;
+    int progress = 0;
+    PyObject *py_cancel = Py_None;
+    int retries = 0;
+    double retry_delay = 0.1;
+    double retry_max_delay = 2.0;
+    int dirfd_a = -1;
+    int dirfd_b = -1;
+    PyObject *py_cache = Py_None;
+    int lock = 0;
+    double lock_timeout = -1.0;
+    Py_ssize_t max_pending = 0;  /* 0 = unbounded */
+
+    static char *kwlist[] = {
+        "dir_a", "dir_b", "chunk_size", "size_precheck",
+        "quick_check", "follow_symlinks", "max_workers", "progress",
+        "cancel", "retries", "retry_delay", "retry_max_delay",
+        "dirfd_a", "dirfd_b", "cache", "max_pending", "lock", "lock_timeout", NULL
+    };
+
+    if (!PyArg_ParseTupleAndKeywords(args, kwargs, "OO|npppnpOiddiiOnpd", kwlist,
+            &dir_a, &dir_b, &chunk_size, &size_precheck,
+            &quick_check, &follow_symlinks, &max_workers, &progress,
+            &py_cancel, &retries, &retry_delay, &retry_max_delay,
+            &dirfd_a, &dirfd_b, &py_cache, &max_pending, &lock, &lock_timeout)) {
+        return NULL;
+    }
+
+    if (chunk_size <= 0) {
+        PyErr_SetString(PyExc_ValueError, "chunk_size must be positive");
+        return NULL;
+    }
+    if (max_pending < 0) {
+        PyErr_SetString(PyExc_ValueError, "max_pending must be non-negative");
+        return NULL;
+    }
+
+    komparu_cancel_t *cancel;
+    if (get_cancel(py_cancel, &cancel) < 0) return NULL;
+    komparu_retry_t retry;
+    if (get_retry(retries, retry_delay, retry_max_delay, &retry) < 0) return NULL;
+    komparu_lock_t lock_policy = { .timeout = lock_timeout };
+    if (get_dirfd(dirfd_a, &dirfd_a) < 0 || get_dirfd(dirfd_b, &dirfd_b) < 0) return NULL;
+    komparu_dir_cache_t *cache;
+    if (get_dir_cache(py_cache, &cache) < 0) return NULL;
+    char *da = path_dup(dir_a);
+    char *db = da ? path_dup(dir_b) : NULL;
+    if (!db) {
+        free(da);
+        return NULL;
+    }
+
+    const char *err_msg = NULL;
+    komparu_async_task_t *task = komparu_async_compare_dir_stream(
+        dirfd_a, da, dirfd_b, db,
+        (size_t)chunk_size, (bool)size_precheck, (bool)quick_check,
+        (bool)follow_symlinks,
+        (size_t)(max_workers >= 0 ? max_workers : 0),
+        (bool)progress, (size_t)max_pending, &retry, lock ? &lock_policy : NULL,
+        cache, cancel, &err_msg
+    );
+    int saved = errno;
+    free(da);
+    free(db);
+    errno = saved;
+
+    if (!task) {
+        if (errno == EBADF) {
+            raise_source_error(EBADF, NULL, "async dir stream failed: %s", err_msg);
+            return NULL;
+        }
+        PyErr_Format(PyExc_RuntimeError, "async dir stream failed: %s",
+                     err_msg ? err_msg : "unknown error");
+        return NULL;
+    }
+
+    int fd = komparu_async_task_fd(task);
+    PyObject *capsule = PyCapsule_New(task, "komparu.async_task",
+                                      async_task_capsule_destructor);
+    if (!capsule) {
+        komparu_async_task_free(task);
+        return NULL;
+    }
+
+    return Py_BuildValue("(iN)", fd, capsule);
+}
+
+static PyObject *py_async_dir_stream_poll(PyObject *self, PyObject *arg) {
+    (void)self;
+
+    komparu_async_task_t *task = PyCapsule_GetPointer(arg, "komparu.async_task");
+    if (!task) {
+        PyErr_SetString(PyExc_ValueError, "invalid async task handle");
+        return NULL;
+    }
+
+    komparu_dir_event_t *events = NULL;
+    size_t count = 0;
+    bool finished = false;
+    const char *err_msg = NULL;
+    if (komparu_async_task_take_events(task, &events, &count,
+                                       &finished, &err_msg) != 0) {
+        const char *path = NULL;
+        int code = komparu_async_task_error_code(task, &path);
+        raise_source_error(code, path, "%s",
+                           err_msg ? err_msg : "directory comparison failed");
+        return NULL;
+    }
+
+    PyObject *list = PyList_New((Py_ssize_t)count);
+    if (!list) {
+        komparu_dir_events_free(events, count);
+        return NULL;
+    }
+
+    for (size_t i = 0; i < count; i++) {
+        bool is_diff = events[i].type == KOMPARU_DIR_EVENT_ENTRY &&
+                       events[i].kind == KOMPARU_ENTRY_DIFF;
+        PyObject *item = Py_BuildValue("(szN)",
+            dir_event_str(&events[i]),
+            is_diff ? diff_reason_str(events[i].reason) : NULL,
+            PyUnicode_DecodeFSDefault(events[i].rel_path));
+        if (!item) {
+            Py_DECREF(list);
+            komparu_dir_events_free(events, count);
+            return NULL;
+        }
+        PyList_SET_ITEM(list, (Py_ssize_t)i, item);
+    }
+    komparu_dir_events_free(events, count);
+
+    return Py_BuildValue("(NO)", list, finished ? Py_True : Py_False);
+}
+
 /* =========================================================================
  * Async archive comparison
  * ========================================================================= */
@@ -989,8 +2713,8 @@ static PyObject *py_async_compare_dir_result(PyObject *self, PyObject *arg) {
 static PyObject *py_async_compare_archive_start(PyObject *self, PyObject *args, PyObject *kwargs) {
     (void)self;
 
-    const char *path_a = NULL;
-    const char *path_b = NULL;
+    PyObject *path_a = NULL;
+    PyObject *path_b = NULL;
     Py_ssize_t chunk_size = KOMPARU_DEFAULT_CHUNK_SIZE;
     long long max_decompressed_size = -1;
     int max_compression_ratio = -1;
@@ -1004,7 +2728,7 @@ static PyObject *py_async_compare_archive_start(PyObject *self, PyObject *args,
         "max_entries", "max_entry_name_length", "hash_compare", NULL
     };
 
-    if (!PyArg_ParseTupleAndKeywords(args, kwargs, "ss|nLiLLp", kwlist,
+    if (!PyArg_ParseTupleAndKeywords(args, kwargs, "OO|nLiLLp", kwlist,
             &path_a, &path_b, &chunk_size,
             &max_decompressed_size, &max_compression_ratio,
             &max_entries, &max_entry_name_length, &hash_compare)) {
@@ -1016,10 +2740,19 @@ static PyObject *py_async_compare_archive_start(PyObject *self, PyObject *args,
     int64_t me = max_entries >= 0 ? (int64_t)max_entries : 0;
     int64_t menl = max_entry_name_length >= 0 ? (int64_t)max_entry_name_length : 0;
 
+    char *pa = path_dup(path_a);
+    char *pb = pa ? path_dup(path_b) : NULL;
+    if (!pb) {
+        free(pa);
+        return NULL;
+    }
+
     const char *err_msg = NULL;
     komparu_async_task_t *task = komparu_async_compare_archive(
-        path_a, path_b, (size_t)chunk_size,
+        pa, pb, (size_t)chunk_size,
         mds, mcr, me, menl, hash_compare, &err_msg);
+    free(pa);
+    free(pb);
 
     if (!task) {
         PyErr_Format(PyExc_RuntimeError, "async compare_archive failed: %s",
@@ -1050,8 +2783,10 @@ static PyObject *py_async_compare_archive_result(PyObject *self, PyObject *arg)
     const char *err_msg = NULL;
     komparu_dir_result_t *result = komparu_async_task_dir_result(task, &err_msg);
     if (!result) {
-        PyErr_Format(PyExc_IOError, "archive comparison failed: %s",
-                     err_msg ? err_msg : "unknown error");
+        const char *path = NULL;
+        int code = komparu_async_task_error_code(task, &path);
+        raise_source_error(code, path, "archive comparison failed: %s",
+                           err_msg ? err_msg : "unknown error");
         return NULL;
     }
 
@@ -1067,7 +2802,7 @@ static PyObject *py_async_compare_archive_result(PyObject *self, PyObject *arg)
 static PyObject *py_async_compare_dir_urls_start(PyObject *self, PyObject *args, PyObject *kwargs) {
     (void)self;
 
-    const char *dir_path = NULL;
+    PyObject *dir_path = NULL;
     PyObject *py_url_map = NULL;
     Py_ssize_t chunk_size = KOMPARU_DEFAULT_CHUNK_SIZE;
     int size_precheck = 1;
@@ -1085,7 +2820,7 @@ static PyObject *py_async_compare_dir_urls_start(PyObject *self, PyObject *args,
         "proxy", NULL
     };
 
-    if (!PyArg_ParseTupleAndKeywords(args, kwargs, "sO|nppOdpppz", kwlist,
+    if (!PyArg_ParseTupleAndKeywords(args, kwargs, "OO|nppOdpppz", kwlist,
             &dir_path, &py_url_map, &chunk_size, &size_precheck, &quick_check,
             &py_headers, &timeout, &follow_redirects, &verify_ssl,
             &allow_private, &proxy)) {
@@ -1143,14 +2878,22 @@ static PyObject *py_async_compare_dir_urls_start(PyObject *self, PyObject *args,
         PyErr_SetString(PyExc_ValueError, err_msg);
         return NULL;
     }
+    char *dir_copy = path_dup(dir_path);
+    if (!dir_copy) {
+        free(rel_paths);
+        free(url_strs);
+        free_header_array(header_array, header_count);
+        return NULL;
+    }
 
     komparu_async_task_t *task = komparu_async_compare_dir_urls(
-        dir_path, rel_paths, url_strs, (size_t)map_size,
+        dir_copy, rel_paths, url_strs, (size_t)map_size,
         header_array,
         (size_t)chunk_size, (bool)size_precheck, (bool)quick_check,
         timeout, (bool)follow_redirects, (bool)verify_ssl, (bool)allow_private,
         proxy, &err_msg);
 
+    free(dir_copy);
     free(rel_paths);
     free(url_strs);
     free_header_array(header_array, header_count);
@@ -1184,8 +2927,10 @@ static PyObject *py_async_compare_dir_urls_result(PyObject *self, PyObject *arg)
     const char *err_msg = NULL;
     komparu_dir_result_t *result = komparu_async_task_dir_result(task, &err_msg);
     if (!result) {
-        PyErr_Format(PyExc_IOError, "dir_urls comparison failed: %s",
-                     err_msg ? err_msg : "unknown error");
+        const char *path = NULL;
+        int code = komparu_async_task_error_code(task, &path);
+        raise_source_error(code, path, "dir_urls comparison failed: %s",
+                           err_msg ? err_msg : "unknown error");
         return NULL;
     }
 
@@ -1216,8 +2961,21 @@ static PyMethodDef module_methods[] = {
         "compare_dir(dir_a, dir_b, *, chunk_size=65536, size_precheck=True, "
         "quick_check=True, follow_symlinks=True) -> dict\n\n"
         "Compare two directories recursively.\n"
+        "dirfd_a/dirfd_b: resolve each path relative to an open directory\n"
+        "descriptor (openat-style; -1 = as given).\n"
+        "cache: dir cache handle; unchanged pairs found equal before are skipped.\n"
         "Returns dict with equal, diff, only_left, only_right."
     },
+    {
+        "plan_dir",
+        (PyCFunction)(void(*)(void))py_plan_dir,
+        METH_VARARGS | METH_KEYWORDS,
+        "plan_dir(dir_a, dir_b, *, size_precheck=True, follow_symlinks=True, "
+        "cancel=None, dirfd_a=-1, dirfd_b=-1) -> dict\n\n"
+        "Walk both directories and stat common files without reading content.\n"
+        "Returns dict with equal, diff, only_left, only_right, errors and\n"
+        "pairs: list of (path, size_a, size_b, same_file)."
+    },
     {
         "compare_archive",
         (PyCFunction)(void(*)(void))py_compare_archive,
@@ -1227,6 +2985,15 @@ static PyMethodDef module_methods[] = {
         "hash_compare: use streaming hash (O(entries) memory).\n"
         "Returns dict with equal, diff, only_left, only_right."
     },
+    {
+        "compare_dir_archive",
+        (PyCFunction)(void(*)(void))py_compare_dir_archive,
+        METH_VARARGS | METH_KEYWORDS,
+        "compare_dir_archive(path_a, path_b, archive_a, *, chunk_size=65536, ...) -> dict\n\n"
+        "Compare a directory against an archive without extracting it.\n"
+        "archive_a: path_a is the archive (else path_b).\n"
+        "Returns dict with equal, diff, only_left, only_right."
+    },
     {
         "compare_dir_urls",
         (PyCFunction)(void(*)(void))py_compare_dir_urls,
@@ -1235,6 +3002,39 @@ static PyMethodDef module_methods[] = {
         "Compare local directory against URL mapping.\n"
         "Returns dict with equal, diff, only_left, only_right."
     },
+    {
+        "compare_streams",
+        (PyCFunction)(void(*)(void))py_compare_streams,
+        METH_VARARGS | METH_KEYWORDS,
+        "compare_streams(stream_a, stream_b, *, chunk_size=65536, "
+        "size_precheck=True, quick_check=True) -> bool\n\n"
+        "Compare two binary file-like objects byte-by-byte.\n"
+        "Returns True if streams are identical, False otherwise."
+    },
+    {
+        "compare_fds",
+        (PyCFunction)(void(*)(void))py_compare_fds,
+        METH_VARARGS | METH_KEYWORDS,
+        "compare_fds(fd_a, fd_b, *, chunk_size=65536, size_precheck=True, "
+        "quick_check=True, cancel=None) -> bool\n\n"
+        "Compare two open file descriptors byte-by-byte.\n"
+        "The descriptors stay open and keep their file positions."
+    },
+    {
+        "verify_zero",
+        (PyCFunction)(void(*)(void))py_verify_zero,
+        METH_VARARGS | METH_KEYWORDS,
+        "verify_zero(path, *, chunk_size=65536, quick_check=True, cancel=None) -> bool\n\n"
+        "Whether a file or block device holds only zero bytes."
+    },
+    {
+        "compare_parts",
+        (PyCFunction)(void(*)(void))py_compare_parts,
+        METH_VARARGS | METH_KEYWORDS,
+        "compare_parts(parts_a, parts_b, *, chunk_size=65536, size_precheck=True, "
+        "quick_check=True, cancel=None) -> bool\n\n"
+        "Compare two sequences of files, each read back to back as one file."
+    },
     {
         "compare_buffers",
         (PyCFunction)py_compare_buffers,
@@ -1243,7 +3043,200 @@ static PyMethodDef module_methods[] = {
         "Compare two bytes-like objects via memcmp.\n"
         "Returns True if identical, False otherwise."
     },
+    {
+        "first_diff",
+        (PyCFunction)py_first_diff,
+        METH_VARARGS,
+        "first_diff(buf_a, buf_b) -> int\n\n"
+        "Offset of the first differing byte of two bytes-like objects,\n"
+        "or -1 if they are identical."
+    },
+    {
+        "diff_blocks",
+        (PyCFunction)py_diff_blocks,
+        METH_VARARGS,
+        "diff_blocks(buf_a, buf_b, block_size) -> int\n\n"
+        "Number of block_size blocks that differ between two bytes-like objects."
+    },
+    {
+        "fuzzy_digest",
+        (PyCFunction)py_fuzzy_digest,
+        METH_VARARGS,
+        "fuzzy_digest(buf, block_size) -> (str, str)\n\n"
+        "spamsum-style digests of a bytes-like object at block_size and\n"
+        "twice block_size."
+    },
+    {
+        "cdc_chunks",
+        (PyCFunction)py_cdc_chunks,
+        METH_VARARGS,
+        "cdc_chunks(buf, min_size, avg_size, max_size) -> list[int]\n\n"
+        "End offsets of the content-defined chunks of a bytes-like object."
+    },
+    {
+        "crc32c",
+        (PyCFunction)py_crc32c,
+        METH_VARARGS,
+        "crc32c(buf, crc=0) -> int\n\n"
+        "CRC-32C of a bytes-like object, continuing from crc."
+    },
+    {
+        "rsum_blocks",
+        (PyCFunction)py_rsum_blocks,
+        METH_VARARGS,
+        "rsum_blocks(buf, block_size) -> bytes\n\n"
+        "rsync weak checksum of each full block, as native uint32 values."
+    },
+    {
+        "rsum_scan",
+        (PyCFunction)py_rsum_scan,
+        METH_VARARGS,
+        "rsum_scan(buf, start, block_size, table) -> list[int]\n\n"
+        "Offsets from start of block_size windows whose rolling rsync checksum\n"
+        "is in table (sorted native uint32 values), skipping past each hit."
+    },
+#ifdef KOMPARU_LINUX
+    {
+        "inotify_init",
+        (PyCFunction)py_inotify_init,
+        METH_NOARGS,
+        "inotify_init() -> fd\n\n"
+        "Open a non-blocking, close-on-exec inotify descriptor."
+    },
+    {
+        "inotify_add_watch",
+        (PyCFunction)py_inotify_add_watch,
+        METH_VARARGS,
+        "inotify_add_watch(fd, dir, follow_symlinks) -> wd\n\n"
+        "Watch dir for entries created, deleted, modified, moved or changing metadata."
+    },
+    {
+        "inotify_read",
+        (PyCFunction)py_inotify_read,
+        METH_O,
+        "inotify_read(fd) -> [(wd, new_dir, overflow, name), ...]\n\n"
+        "Take every queued event; new_dir is set for a directory created or moved in."
+    },
+    {
+        "fanotify_init",
+        (PyCFunction)py_fanotify_init,
+        METH_NOARGS,
+        "fanotify_init() -> fd\n\n"
+        "Open a non-blocking fanotify descriptor reporting directory handles and names."
+    },
+    {
+        "fanotify_mark",
+        (PyCFunction)py_fanotify_mark,
+        METH_VARARGS,
+        "fanotify_mark(fd, path)\n\n"
+        "Watch the whole file system path is on for entries created, deleted, modified,\n"
+        "moved or changing metadata."
+    },
+    {
+        "fanotify_read",
+        (PyCFunction)py_fanotify_read,
+        METH_VARARGS,
+        "fanotify_read(fd, mount_fds) -> (paths, overflow)\n\n"
+        "Take every queued event as the path it names, resolved through the descriptor\n"
+        "in mount_fds on the same file system; entries already gone are left out."
+    },
+    {
+        "birth_time",
+        (PyCFunction)py_birth_time,
+        METH_VARARGS,
+        "birth_time(path, follow_symlinks) -> int | None\n\n"
+        "Creation time in nanoseconds since the epoch, from statx; None where the file\n"
+        "system does not record it."
+    },
+    {
+        "inode_flags",
+        (PyCFunction)py_inode_flags,
+        METH_VARARGS,
+        "inode_flags(path, follow_symlinks) -> int\n\n"
+        "Inode flags (FS_IOC_GETFLAGS, as lsattr shows them) of a file or directory;\n"
+        "0 for other entries and on file systems without them."
+    },
+#endif
     /* Async — C pool + eventfd/pipe for asyncio integration */
+    {
+        "cancel_token_new",
+        (PyCFunction)py_cancel_token_new,
+        METH_VARARGS,
+        "cancel_token_new(max_bytes=0, parent=None) -> handle\n\n"
+        "Create a cancellation token handle for the cancel= argument.\n"
+        "With max_bytes, it cancels itself once the engine reads more than that.\n"
+        "With parent, it is also cancelled by that token and counts into it."
+    },
+    {
+        "cancel_token_set",
+        (PyCFunction)py_cancel_token_set,
+        METH_O,
+        "cancel_token_set(handle) -> None\n\n"
+        "Request cancellation. Thread-safe; in-flight work stops at the next check."
+    },
+    {
+        "cancel_token_is_set",
+        (PyCFunction)py_cancel_token_is_set,
+        METH_O,
+        "cancel_token_is_set(handle) -> bool"
+    },
+    {
+        "cancel_token_stats",
+        (PyCFunction)py_cancel_token_stats,
+        METH_O,
+        "cancel_token_stats(handle) -> (bytes_read, quota_reached, entries_done)\n\n"
+        "Bytes the engine read under the token, whether its quota stopped it,\n"
+        "and the directory entries it resolved."
+    },
+    {
+        "dir_cache_new",
+        (PyCFunction)py_dir_cache_new,
+        METH_O,
+        "dir_cache_new(max_entries) -> handle\n\n"
+        "Create a warm directory cache handle for the cache= argument\n"
+        "(max_entries 0 = unbounded)."
+    },
+    {
+        "dir_cache_clear",
+        (PyCFunction)py_dir_cache_clear,
+        METH_O,
+        "dir_cache_clear(handle) -> None\n\n"
+        "Forget every remembered pair and reset the hit counter."
+    },
+    {
+        "dir_cache_stats",
+        (PyCFunction)py_dir_cache_stats,
+        METH_O,
+        "dir_cache_stats(handle) -> (entries, hits)"
+    },
+    {
+        "set_buffer_allocator",
+        (PyCFunction)py_set_buffer_allocator,
+        METH_O,
+        "set_buffer_allocator(capsule_or_None) -> None"
+    },
+    {
+        "set_noatime",
+        (PyCFunction)py_set_noatime,
+        METH_O,
+        "set_noatime(enable) -> bool\n\n"
+        "Open files with O_NOATIME from now on. False if the platform lacks it."
+    },
+    {
+        "noatime_denied",
+        (PyCFunction)py_noatime_denied,
+        METH_NOARGS,
+        "noatime_denied() -> int\n\n"
+        "Opens since set_noatime(True) that updated the access time."
+    },
+    {
+        "set_mmap_window",
+        (PyCFunction)py_set_mmap_window,
+        METH_O,
+        "set_mmap_window(size) -> int\n\n"
+        "Map at most size bytes of a file at once (0 = default). Returns the\n"
+        "window in effect, rounded up to whole pages."
+    },
     {
         "async_compare_start",
         (PyCFunction)(void(*)(void))py_async_compare_start,
@@ -1272,6 +3265,23 @@ static PyMethodDef module_methods[] = {
         "async_compare_dir_result(task) -> dict\n\n"
         "Get result of async directory comparison. Call after fd is readable."
     },
+    {
+        "async_dir_stream_start",
+        (PyCFunction)(void(*)(void))py_async_dir_stream_start,
+        METH_VARARGS | METH_KEYWORDS,
+        "async_dir_stream_start(dir_a, dir_b, ...) -> (fd, task)\n\n"
+        "Submit streaming directory comparison. fd is signaled per resolved entry.\n"
+        "max_pending: bound on queued events (0 = unbounded); workers pause\n"
+        "while the queue is full."
+    },
+    {
+        "async_dir_stream_poll",
+        (PyCFunction)py_async_dir_stream_poll,
+        METH_O,
+        "async_dir_stream_poll(task) -> (events, finished)\n\n"
+        "Take queued (kind, reason, path) events. finished=True after the last batch.\n"
+        "With progress=True, kind may also be 'started', 'dir_entered' or 'dir_left'."
+    },
     {
         "async_compare_archive_start",
         (PyCFunction)(void(*)(void))py_async_compare_archive_start,
@@ -1341,10 +3351,12 @@ PyMODINIT_FUNC PyInit__core(void) {
                         "failed to initialize curl share handle");
         return NULL;
     }
-    /* Py_AtExit runs in LIFO order: TLS cleanup first, then share,
-     * then curl_global_cleanup last (registered first). */
+    /* Py_AtExit runs in LIFO order: TLS cleanup first (parks the main
+     * thread's buffers), then the parked buffers, then share, then
+     * curl_global_cleanup last (registered first). */
     Py_AtExit(komparu_curl_global_cleanup);
     Py_AtExit(komparu_curl_share_cleanup);
+    Py_AtExit(komparu_compare_buffers_drain);
     Py_AtExit(komparu_compare_tls_cleanup);
 
     return PyModuleDef_Init(&module_def);
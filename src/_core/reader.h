@@ -45,6 +45,32 @@ typedef struct komparu_reader {
      */
     int (*seek)(struct komparu_reader *self, int64_t offset);
 
+    /**
+     * End of the run of data or of hole that `offset` is in, as
+     * lseek(SEEK_DATA/SEEK_HOLE) tell it; *hole says which. Optional
+     * (NULL): only sparse-aware sources set it. Holes read as zeros, so
+     * a comparison can skip a range that is a hole on both sides — on
+     * NFSv4.2 the server answers from its own allocation map (SEEK), so
+     * those bytes never cross the network.
+     *
+     * Returns:
+     *   >= offset — end of the run (the size at the last one)
+     *   -1        — layout unknown; read everything
+     */
+    int64_t (*extent)(struct komparu_reader *self, int64_t offset, bool *hole);
+
+    /**
+     * Take (lock = true) or release a shared advisory lock on the whole
+     * source — flock() on Unix, LockFileEx() on Windows — without
+     * waiting. Optional (NULL): sources that cannot be locked. close()
+     * releases a lock still held.
+     *
+     * Returns:
+     *    0  — success
+     *   -1  — error; errno EWOULDBLOCK while a writer holds the source
+     */
+    int (*lock_shared)(struct komparu_reader *self, bool lock);
+
     /**
      * Close reader and free all resources.
      * After close(), the reader must not be used.
@@ -71,6 +97,67 @@ typedef struct komparu_reader {
  */
 komparu_reader_t *komparu_reader_file_open(const char *path, const char **err_msg);
 
+/**
+ * komparu_reader_file_open() that also reads block devices (Unix), whose
+ * size is where lseek(SEEK_END) lands; their reader has no extent().
+ */
+komparu_reader_t *komparu_reader_device_open(const char *path, const char **err_msg);
+
+/**
+ * A source of `size` zero bytes: one hole from start to end, so a
+ * comparison against it reads only the data extents of the other side.
+ * Returns NULL (out of memory) on error.
+ */
+komparu_reader_t *komparu_reader_zero_open(int64_t size, const char **err_msg);
+
+/**
+ * The count readers in parts, read one after the other as one source —
+ * split volumes as the file they were cut from. Takes ownership of the
+ * readers (closed with it, or right away on error) but not of the array.
+ * Every part must know its size. Returns NULL on error.
+ */
+komparu_reader_t *komparu_reader_concat_open(komparu_reader_t **parts, size_t count,
+                                             const char **err_msg);
+
+/**
+ * komparu_reader_file_open() for path relative to the directory
+ * descriptor dirfd (openat semantics; AT_FDCWD = current directory).
+ * dirfd stays owned by the caller. Unix only (ENOTSUP elsewhere).
+ */
+komparu_reader_t *komparu_reader_file_openat(int dirfd, const char *path,
+                                             const char **err_msg);
+
+/**
+ * openat() of the relative path under dirfd that follows no symlink in
+ * any component and never leaves dirfd, so a directory swapped for a
+ * symlink after it was listed cannot lead outside it. flags gain
+ * O_NOFOLLOW and O_CLOEXEC. Uses openat2(RESOLVE_BENEATH) where the
+ * kernel has it. Returns the descriptor, or -1 with errno ELOOP (or
+ * ENOTDIR) for a symlink on the way and EXDEV for an absolute path or
+ * "..". Unix only.
+ */
+int komparu_openat_beneath(int dirfd, const char *path, int flags);
+
+/**
+ * openat() for reading, with O_NOATIME while komparu_set_noatime() has
+ * it on; retried without it on EPERM. Unix only.
+ */
+int komparu_openat_read(int dirfd, const char *path, int flags);
+
+/**
+ * komparu_reader_file_openat() through komparu_openat_beneath().
+ * Unix only (ENOTSUP elsewhere).
+ */
+komparu_reader_t *komparu_reader_file_open_beneath(int dirfd, const char *path,
+                                                   const char **err_msg);
+
+/**
+ * Create a file reader over an already-open descriptor. The reader works
+ * on a duplicate: fd stays open and its file position is untouched.
+ * Unix only (ENOTSUP elsewhere).
+ */
+komparu_reader_t *komparu_reader_file_fdopen(int fd, const char **err_msg);
+
 /**
  * Create an HTTP reader using libcurl.
  *
@@ -14,13 +14,32 @@
 
 #include "reader_archive.h"
 #include "compare.h"
+#include "dirwalk.h"
+#include "reader_file.h"
 #include <archive.h>
 #include <archive_entry.h>
 #include <stdlib.h>
 #include <string.h>
+#include <errno.h>
 
 static _Thread_local char archive_errbuf[512];
 
+/* The ANSI API would garble a Windows name outside the code page */
+static int open_archive_file(struct archive *a, const char *path) {
+#ifdef KOMPARU_WINDOWS
+    wchar_t *wpath = komparu_long_path_win(path);
+    if (!wpath) {
+        archive_set_error(a, errno, "invalid file name");
+        return ARCHIVE_FATAL;
+    }
+    int rc = archive_read_open_filename_w(a, wpath, 65536);
+    free(wpath);
+    return rc;
+#else
+    return archive_read_open_filename(a, path, 65536);
+#endif
+}
+
 /* =========================================================================
  * Archive bomb limits (defaults, overridable via config)
  * ========================================================================= */
@@ -165,18 +184,21 @@ static int read_archive_entries(
     archive_read_support_filter_all(a);
     archive_read_support_format_all(a);
 
-    int rc = archive_read_open_filename(a, path, 65536);
+    int rc = open_archive_file(a, path);
     if (rc != ARCHIVE_OK) {
         snprintf(archive_errbuf, sizeof(archive_errbuf),
                  "cannot open archive: %s", archive_error_string(a));
         *err_msg = archive_errbuf;
+        int open_errno = archive_errno(a);
         archive_read_free(a);
+        errno = open_errno ? open_errno : EIO;
         return -1;
     }
 
     int64_t total_decompressed = 0;
     int64_t total_compressed = 0;
     int64_t entry_count = 0;
+    int fail_errno = EIO;
 
     struct archive_entry *entry;
     while (archive_read_next_header(a, &entry) == ARCHIVE_OK) {
@@ -348,10 +370,12 @@ static int read_archive_entries(
     return 0;
 
 bomb:
+    fail_errno = EFBIG;
 fail:
     entry_list_free(out);
     archive_read_close(a);
     archive_read_free(a);
+    errno = fail_errno;
     return -1;
 }
 
@@ -552,18 +576,21 @@ int read_archive_entries_hashed(
     archive_read_support_filter_all(a);
     archive_read_support_format_all(a);
 
-    int rc = archive_read_open_filename(a, path, 65536);
+    int rc = open_archive_file(a, path);
     if (rc != ARCHIVE_OK) {
         snprintf(archive_errbuf, sizeof(archive_errbuf),
                  "cannot open archive: %s", archive_error_string(a));
         *err_msg = archive_errbuf;
+        int open_errno = archive_errno(a);
         archive_read_free(a);
+        errno = open_errno ? open_errno : EIO;
         return -1;
     }
 
     int64_t total_decompressed = 0;
     int64_t total_compressed = 0;
     int64_t entry_count = 0;
+    int fail_errno = EIO;
 
     struct archive_entry *entry;
     while (archive_read_next_header(a, &entry) == ARCHIVE_OK) {
@@ -696,10 +723,12 @@ int read_archive_entries_hashed(
     return 0;
 
 bomb:
+    fail_errno = EFBIG;
 fail:
     entry_hash_list_free(out);
     archive_read_close(a);
     archive_read_free(a);
+    errno = fail_errno;
     return -1;
 }
 
@@ -810,6 +839,160 @@ merge_fail:
     return NULL;
 }
 
+/* =========================================================================
+ * Archive backend — entries in memory, served through komparu_vfs_t
+ * ========================================================================= */
+
+typedef struct {
+    entry_list_t list;   /* sorted by name, names unique */
+    size_t root_len;     /* strlen(path) + 1: engine paths are "path/rel" */
+} archive_vfs_t;
+
+typedef struct {
+    const entry_data_t *entry;
+    size_t pos;
+} mem_reader_t;
+
+static const entry_data_t *archive_vfs_find(const komparu_vfs_t *self, const char *path) {
+    const archive_vfs_t *av = self->ctx;
+    if (strlen(path) < av->root_len) return NULL;
+    entry_data_t key = { .name = (char *)path + av->root_len };
+    return bsearch(&key, av->list.entries, av->list.count, sizeof(entry_data_t), entry_cmp);
+}
+
+static int archive_vfs_list(const komparu_vfs_t *self, const char *root,
+                            bool follow_symlinks, komparu_pathlist_t *result,
+                            komparu_pathlist_t *errors, const char **err_msg) {
+    (void)root;
+    (void)follow_symlinks;
+    (void)errors;
+    const archive_vfs_t *av = self->ctx;
+    for (size_t k = 0; k < av->list.count; k++) {
+        if (komparu_pathlist_append(result, av->list.entries[k].name, err_msg) != 0) {
+            komparu_pathlist_free(result);
+            errno = ENOMEM;
+            return -1;
+        }
+    }
+    return 0;
+}
+
+static int archive_vfs_stat(const komparu_vfs_t *self, const char *path,
+                            komparu_vfs_stat_t *st) {
+    const entry_data_t *e = archive_vfs_find(self, path);
+    if (!e) {
+        errno = ENOENT;
+        return -1;
+    }
+    *st = (komparu_vfs_stat_t){ .size = (int64_t)e->size };
+    return 0;
+}
+
+static int64_t mem_read(komparu_reader_t *self, void *buf, size_t size) {
+    mem_reader_t *m = self->ctx;
+    size_t left = m->entry->size - m->pos;
+    if (size > left) size = left;
+    if (size > 0) memcpy(buf, m->entry->data + m->pos, size);
+    m->pos += size;
+    return (int64_t)size;
+}
+
+static int64_t mem_get_size(komparu_reader_t *self) {
+    return (int64_t)((mem_reader_t *)self->ctx)->entry->size;
+}
+
+static int mem_seek(komparu_reader_t *self, int64_t offset) {
+    mem_reader_t *m = self->ctx;
+    if (offset < 0 || (uint64_t)offset > m->entry->size) return -1;
+    m->pos = (size_t)offset;
+    return 0;
+}
+
+static void mem_close(komparu_reader_t *self) {
+    free(self->ctx);
+    free(self);
+}
+
+static komparu_reader_t *archive_vfs_open(const komparu_vfs_t *self, const char *path,
+                                          const char **err_msg) {
+    const entry_data_t *e = archive_vfs_find(self, path);
+    if (!e) {
+        *err_msg = "no such archive entry";
+        errno = ENOENT;
+        return NULL;
+    }
+    komparu_reader_t *reader = calloc(1, sizeof(komparu_reader_t));
+    mem_reader_t *m = calloc(1, sizeof(mem_reader_t));
+    if (!reader || !m) {
+        free(reader);
+        free(m);
+        *err_msg = "out of memory";
+        errno = ENOMEM;
+        return NULL;
+    }
+    m->entry = e;
+    reader->read = mem_read;
+    reader->get_size = mem_get_size;
+    reader->seek = mem_seek;
+    reader->close = mem_close;
+    reader->ctx = m;
+    reader->source_name = e->name;
+    return reader;
+}
+
+int komparu_vfs_archive_open(
+    const char *path,
+    int64_t max_decompressed_size,
+    int max_compression_ratio,
+    int64_t max_entries,
+    int64_t max_entry_name_length,
+    komparu_vfs_t *out,
+    const char **err_msg
+) {
+    archive_vfs_t *av = calloc(1, sizeof(*av));
+    if (!av) {
+        *err_msg = "out of memory";
+        errno = ENOMEM;
+        return -1;
+    }
+    if (read_archive_entries(path, &av->list,
+            max_decompressed_size, max_compression_ratio,
+            max_entries, max_entry_name_length, err_msg) != 0) {
+        free(av);
+        return -1;
+    }
+
+    /* Drop repeated names so the path list stays unique */
+    size_t kept = 0;
+    for (size_t k = 0; k < av->list.count; k++) {
+        entry_data_t *e = &av->list.entries[k];
+        if (kept > 0 && strcmp(av->list.entries[kept - 1].name, e->name) == 0) {
+            free(e->name);
+            free(e->data);
+            continue;
+        }
+        av->list.entries[kept++] = *e;
+    }
+    av->list.count = kept;
+    av->root_len = strlen(path) + 1;
+
+    *out = (komparu_vfs_t){
+        .list = archive_vfs_list,
+        .stat = archive_vfs_stat,
+        .open = archive_vfs_open,
+        .ctx = av,
+    };
+    return 0;
+}
+
+void komparu_vfs_archive_close(komparu_vfs_t *vfs) {
+    if (!vfs || !vfs->ctx) return;
+    archive_vfs_t *av = vfs->ctx;
+    entry_list_free(&av->list);
+    free(av);
+    vfs->ctx = NULL;
+}
+
 /* =========================================================================
  * Iterator API (for future use / streaming access)
  * ========================================================================= */
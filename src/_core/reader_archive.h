@@ -98,6 +98,38 @@ komparu_dir_result_t *komparu_compare_archives_hashed(
 
 void entry_hash_list_free(entry_hash_list_t *list);
 
+/* =========================================================================
+ * Archive as a directory-engine backend
+ * ========================================================================= */
+
+struct komparu_vfs;
+
+/**
+ * Open the archive at path as a filesystem backend for
+ * komparu_compare_dirs_vfs(), so a directory can be compared against it
+ * without extraction. Its regular-file entries are read into memory once,
+ * under the same bomb limits as komparu_compare_archives(), and appear as
+ * files at their sanitized relative paths; pass path itself as the
+ * directory to compare. A name stored twice keeps one of its entries.
+ * Entries have no identity or timestamps, so the same-file short-circuit
+ * and the warm cache never apply.
+ *
+ * Fills *out and returns 0, or -1 on error (*err_msg and errno set,
+ * EFBIG when a limit is exceeded). Release with komparu_vfs_archive_close().
+ */
+int komparu_vfs_archive_open(
+    const char *path,
+    int64_t max_decompressed_size,
+    int max_compression_ratio,
+    int64_t max_entries,
+    int64_t max_entry_name_length,
+    struct komparu_vfs *out,
+    const char **err_msg
+);
+
+/** Free the entries behind a backend from komparu_vfs_archive_open(). */
+void komparu_vfs_archive_close(struct komparu_vfs *vfs);
+
 /**
  * Open an archive for iteration over entries.
  *
@@ -0,0 +1,144 @@
+/**
+ * reader_concat.c — Several readers read back to back as one source.
+ *
+ * Split volumes (file.001, file.002, ...) are one logical file; reading
+ * them as such lets them be compared with the unsplit original, or with
+ * another split of it, without concatenating them on disk first.
+ */
+
+#include "reader.h"
+#include <string.h>
+#include <stdlib.h>
+#include <errno.h>
+
+typedef struct {
+    komparu_reader_t **parts;
+    int64_t *starts;    /* Logical offset of each part; starts[count] = total */
+    size_t count;
+    size_t current;     /* Part the next read comes from */
+    char source[1024];
+} concat_ctx_t;
+
+static int64_t concat_read(komparu_reader_t *self, void *buf, size_t size) {
+    concat_ctx_t *ctx = (concat_ctx_t *)self->ctx;
+    while (ctx->current < ctx->count) {
+        komparu_reader_t *part = ctx->parts[ctx->current];
+        int64_t n = part->read(part, buf, size);
+        if (n != 0) {
+            return n;
+        }
+        /* This part is done: on to the next, from its start */
+        if (++ctx->current < ctx->count) {
+            komparu_reader_t *next = ctx->parts[ctx->current];
+            if (next->seek && next->seek(next, 0) != 0) {
+                return -1;
+            }
+        }
+    }
+    return 0;
+}
+
+static int64_t concat_get_size(komparu_reader_t *self) {
+    concat_ctx_t *ctx = (concat_ctx_t *)self->ctx;
+    return ctx->starts[ctx->count];
+}
+
+/* Part that holds offset: the last one starting at or before it, skipping
+ * empty parts */
+static size_t concat_part_at(const concat_ctx_t *ctx, int64_t offset) {
+    size_t i = 0;
+    while (i + 1 < ctx->count && ctx->starts[i + 1] <= offset) {
+        i++;
+    }
+    return i;
+}
+
+static int concat_seek(komparu_reader_t *self, int64_t offset) {
+    concat_ctx_t *ctx = (concat_ctx_t *)self->ctx;
+    if (offset < 0 || offset > ctx->starts[ctx->count]) {
+        errno = EINVAL;
+        return -1;
+    }
+    size_t i = concat_part_at(ctx, offset);
+    komparu_reader_t *part = ctx->parts[i];
+    if (!part->seek || part->seek(part, offset - ctx->starts[i]) != 0) {
+        return -1;
+    }
+    ctx->current = i;
+    return 0;
+}
+
+static int64_t concat_extent(komparu_reader_t *self, int64_t offset, bool *hole) {
+    concat_ctx_t *ctx = (concat_ctx_t *)self->ctx;
+    if (offset >= ctx->starts[ctx->count]) {
+        return -1;
+    }
+    size_t i = concat_part_at(ctx, offset);
+    komparu_reader_t *part = ctx->parts[i];
+    if (!part->extent) {
+        return -1;
+    }
+    /* Runs end at the part boundary at the latest */
+    int64_t end = part->extent(part, offset - ctx->starts[i], hole);
+    return end < 0 ? -1 : ctx->starts[i] + end;
+}
+
+static void concat_close(komparu_reader_t *self) {
+    concat_ctx_t *ctx = (concat_ctx_t *)self->ctx;
+    for (size_t i = 0; i < ctx->count; i++) {
+        ctx->parts[i]->close(ctx->parts[i]);
+    }
+    free(ctx->parts);
+    free(ctx->starts);
+    free(ctx);
+    free(self);
+}
+
+komparu_reader_t *komparu_reader_concat_open(komparu_reader_t **parts, size_t count,
+                                             const char **err_msg) {
+    komparu_reader_t *reader = calloc(1, sizeof(komparu_reader_t));
+    concat_ctx_t *ctx = calloc(1, sizeof(concat_ctx_t));
+    komparu_reader_t **own = count ? malloc(count * sizeof(*own)) : NULL;
+    int64_t *starts = malloc((count + 1) * sizeof(*starts));
+    if (!reader || !ctx || (count && !own) || !starts) {
+        *err_msg = "out of memory";
+        goto fail;
+    }
+    starts[0] = 0;
+    bool extents = count > 0;
+    for (size_t i = 0; i < count; i++) {
+        int64_t size = parts[i]->get_size(parts[i]);
+        if (size < 0) {
+            *err_msg = "split part of unknown size";
+            errno = ENOTSUP;
+            goto fail;
+        }
+        starts[i + 1] = starts[i] + size;
+        extents = extents && parts[i]->extent && parts[i]->seek;
+    }
+    memcpy(own, parts, count * sizeof(*own));
+    ctx->parts = own;
+    ctx->starts = starts;
+    ctx->count = count;
+    snprintf(ctx->source, sizeof(ctx->source), "%s",
+             count ? parts[0]->source_name : "<no parts>");
+
+    reader->ctx = ctx;
+    reader->source_name = ctx->source;
+    reader->read = concat_read;
+    reader->get_size = concat_get_size;
+    reader->seek = concat_seek;
+    reader->close = concat_close;
+    if (extents) reader->extent = concat_extent;
+    return reader;
+
+fail:
+    for (size_t i = 0; i < count; i++) {
+        parts[i]->close(parts[i]);
+    }
+    free(own);
+    free(starts);
+    free(ctx);
+    free(reader);
+    return NULL;
+}
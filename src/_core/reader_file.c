@@ -7,10 +7,24 @@
  */
 
 #include "reader_file.h"
+#include "komparu.h"
+#include <stdatomic.h>
 #include <string.h>
 #include <stdlib.h>
 #include <errno.h>
 
+#ifndef KOMPARU_WINDOWS
+#include <sys/file.h>
+#else
+#include <wchar.h>
+#endif
+#ifdef __linux__
+#include <sys/syscall.h>
+#include <sys/vfs.h>
+#include <linux/magic.h>
+#include <linux/openat2.h>
+#endif
+
 /* Thread-safe error message buffer */
 static _Thread_local char komparu_errbuf[256];
 
@@ -78,6 +92,40 @@ int komparu_sigbus_init(void) {
 
 #endif /* KOMPARU_WINDOWS */
 
+/* =========================================================================
+ * Mapping window
+ * ========================================================================= */
+
+#if SIZE_MAX <= UINT32_MAX
+#define MMAP_WINDOW_DEFAULT ((int64_t)64 << 20)
+#else
+#define MMAP_WINDOW_DEFAULT ((int64_t)1 << 30)
+#endif
+
+static _Atomic int64_t mmap_window = MMAP_WINDOW_DEFAULT;
+
+/* Window offsets must fall on this boundary */
+static int64_t map_granularity(void) {
+#ifndef KOMPARU_WINDOWS
+    long page = sysconf(_SC_PAGESIZE);
+    return page > 0 ? (int64_t)page : 4096;
+#else
+    SYSTEM_INFO info;
+    GetSystemInfo(&info);
+    return (int64_t)info.dwAllocationGranularity;
+#endif
+}
+
+unsigned long long komparu_set_mmap_window(unsigned long long window) {
+    if (window == 0) window = MMAP_WINDOW_DEFAULT;
+    int64_t unit = map_granularity();
+    int64_t limit = INT64_MAX / unit * unit;
+    int64_t rounded = window >= (unsigned long long)limit
+        ? limit : ((int64_t)window + unit - 1) / unit * unit;
+    mmap_window = rounded;
+    return (unsigned long long)rounded;
+}
+
 /* =========================================================================
  * File reader context
  * ========================================================================= */
@@ -86,7 +134,10 @@ int komparu_sigbus_init(void) {
 
 typedef struct {
     int fd;
-    void *mapped;       /* mmap base address, or NULL if using read() */
+    void *mapped;       /* Base of the mapped window, or NULL if using read() */
+    int64_t map_start;  /* File offset the window starts at */
+    size_t map_len;     /* Bytes mapped from there */
+    int64_t window;     /* Largest window, from komparu_set_mmap_window() */
     int64_t file_size;
     int64_t offset;     /* Current read position */
     char source[1024];  /* Source path for error messages */
@@ -94,6 +145,30 @@ typedef struct {
 
 /* ---- read via mmap ---- */
 
+/* Map the window holding ctx->offset in place of the current one. A file
+ * no larger than the window is mapped whole, once. */
+static int map_window(file_ctx_t *ctx) {
+    int64_t start = ctx->offset - ctx->offset % ctx->window;
+    int64_t len = ctx->file_size - start;
+    if (len > ctx->window) len = ctx->window;
+    if (ctx->mapped != NULL) {
+        munmap(ctx->mapped, ctx->map_len);
+        ctx->mapped = NULL;
+    }
+    void *mapped = mmap(NULL, (size_t)len, PROT_READ, MAP_PRIVATE, ctx->fd, (off_t)start);
+    if (mapped == MAP_FAILED) {
+        return -1;
+    }
+    /* Advise sequential access */
+    madvise(mapped, (size_t)len, MADV_SEQUENTIAL);
+    ctx->mapped = mapped;
+    ctx->map_start = start;
+    ctx->map_len = (size_t)len;
+    return 0;
+}
+
+static int64_t file_read_fallback(komparu_reader_t *self, void *buf, size_t size);
+
 static int64_t file_read_mmap(komparu_reader_t *self, void *buf, size_t size) {
     file_ctx_t *ctx = (file_ctx_t *)self->ctx;
 
@@ -101,22 +176,43 @@ static int64_t file_read_mmap(komparu_reader_t *self, void *buf, size_t size) {
         return 0; /* EOF */
     }
 
-    size_t remaining = (size_t)(ctx->file_size - ctx->offset);
-    size_t to_read = (size < remaining) ? size : remaining;
+    /* 64-bit: the rest of a large file may not fit a 32-bit size_t */
+    uint64_t remaining = (uint64_t)(ctx->file_size - ctx->offset);
+    size_t to_read = ((uint64_t)size < remaining) ? size : (size_t)remaining;
+    /* volatile: changed between sigsetjmp and a possible siglongjmp */
+    volatile size_t done = 0;
+    volatile bool unmapped = false;
 
     /* Arm SIGBUS protection before accessing mmap'd memory */
     sigbus_armed = 1;
     if (sigsetjmp(sigbus_jmpbuf, 1) != 0) {
         /* SIGBUS caught — file was truncated under us */
         sigbus_armed = 0;
+        errno = ESTALE;
         return -1;
     }
 
-    memcpy(buf, (const char *)ctx->mapped + ctx->offset, to_read);
+    while (done < to_read) {
+        if (ctx->mapped == NULL || ctx->offset < ctx->map_start
+                || ctx->offset >= ctx->map_start + (int64_t)ctx->map_len) {
+            if (map_window(ctx) != 0) {
+                unmapped = true;
+                break;
+            }
+        }
+        size_t in_window = (size_t)(ctx->map_start + (int64_t)ctx->map_len - ctx->offset);
+        size_t n = (to_read - done < in_window) ? to_read - done : in_window;
+        memcpy((char *)buf + done, (const char *)ctx->mapped + (ctx->offset - ctx->map_start), n);
+        done += n;
+        ctx->offset += (int64_t)n;
+    }
     sigbus_armed = 0;
 
-    ctx->offset += (int64_t)to_read;
-    return (int64_t)to_read;
+    /* Out of address space for the next window: read() this part instead */
+    if (unmapped && done == 0) {
+        return file_read_fallback(self, buf, to_read);
+    }
+    return (int64_t)done;
 }
 
 static int64_t file_get_size(komparu_reader_t *self) {
@@ -133,10 +229,48 @@ static int file_seek(komparu_reader_t *self, int64_t offset) {
     return 0;
 }
 
+static int file_lock_shared(komparu_reader_t *self, bool lock) {
+    file_ctx_t *ctx = (file_ctx_t *)self->ctx;
+    int r;
+    do {
+        r = flock(ctx->fd, lock ? LOCK_SH | LOCK_NB : LOCK_UN);
+    } while (r != 0 && errno == EINTR);
+    return r;
+}
+
+#if defined(SEEK_DATA) && defined(SEEK_HOLE)
+/* Moves the descriptor's file position, which neither read path uses. */
+static int64_t file_extent(komparu_reader_t *self, int64_t offset, bool *hole) {
+    file_ctx_t *ctx = (file_ctx_t *)self->ctx;
+    if (offset >= ctx->file_size) {
+        return -1;
+    }
+    off_t data = lseek(ctx->fd, (off_t)offset, SEEK_DATA);
+    if (data < 0) {
+        if (errno != ENXIO) {
+            return -1;
+        }
+        /* No data past offset: a hole to the end */
+        *hole = true;
+        return ctx->file_size;
+    }
+    if (data > offset) {
+        *hole = true;
+        return (int64_t)data;
+    }
+    off_t end = lseek(ctx->fd, (off_t)offset, SEEK_HOLE);
+    if (end < 0) {
+        return -1;
+    }
+    *hole = false;
+    return (int64_t)end;
+}
+#endif
+
 static void file_close_mmap(komparu_reader_t *self) {
     file_ctx_t *ctx = (file_ctx_t *)self->ctx;
-    if (ctx->mapped != MAP_FAILED && ctx->mapped != NULL) {
-        munmap(ctx->mapped, (size_t)ctx->file_size);
+    if (ctx->mapped != NULL) {
+        munmap(ctx->mapped, ctx->map_len);
     }
     if (ctx->fd >= 0) {
         close(ctx->fd);
@@ -147,11 +281,13 @@ static void file_close_mmap(komparu_reader_t *self) {
 
 /* ---- read via read() fallback ---- */
 
+/* pread at our own offset: a caller-supplied descriptor shares its file
+ * position with the caller, which must not move under them. */
 static int64_t file_read_fallback(komparu_reader_t *self, void *buf, size_t size) {
     file_ctx_t *ctx = (file_ctx_t *)self->ctx;
     ssize_t n;
     do {
-        n = read(ctx->fd, buf, size);
+        n = pread(ctx->fd, buf, size, (off_t)ctx->offset);
     } while (n < 0 && errno == EINTR);
     if (n < 0) {
         return -1;
@@ -162,8 +298,8 @@ static int64_t file_read_fallback(komparu_reader_t *self, void *buf, size_t size
 
 static int file_seek_fallback(komparu_reader_t *self, int64_t offset) {
     file_ctx_t *ctx = (file_ctx_t *)self->ctx;
-    off_t result = lseek(ctx->fd, (off_t)offset, SEEK_SET);
-    if (result == (off_t)-1) {
+    if (offset < 0) {
+        errno = EINVAL;
         return -1;
     }
     ctx->offset = offset;
@@ -181,14 +317,44 @@ static void file_close_fallback(komparu_reader_t *self) {
 
 /* ---- constructor ---- */
 
-komparu_reader_t *komparu_reader_file_open(const char *path, const char **err_msg) {
-    int fd = open(path, O_RDONLY);
-    if (fd < 0) {
-        komparu_strerror(errno, komparu_errbuf, sizeof(komparu_errbuf));
-        *err_msg = komparu_errbuf;
-        return NULL;
+/* Whether st_size says nothing about the content: /proc and /sys make
+ * their files up on read and report 0 or a page, and some FUSE file
+ * systems report 0 for files they cannot size up front. Only those two
+ * sizes are checked, so ordinary files cost no extra syscall. */
+static bool size_unreliable(int fd, const struct stat *st) {
+#ifdef __linux__
+    if (st->st_size != 0 && st->st_size != 4096) {
+        return false;
+    }
+    struct statfs fs;
+    if (fstatfs(fd, &fs) != 0) {
+        return false;
     }
+    switch ((unsigned long)fs.f_type) {
+    case PROC_SUPER_MAGIC:
+    case SYSFS_MAGIC:
+    case DEBUGFS_MAGIC:
+    case TRACEFS_MAGIC:
+    case SECURITYFS_MAGIC:
+    case CGROUP_SUPER_MAGIC:
+    case CGROUP2_SUPER_MAGIC:
+        return true;
+    case FUSE_SUPER_MAGIC:
+        return st->st_size == 0;
+    default:
+        return false;
+    }
+#else
+    (void)fd;
+    (void)st;
+    return false;
+#endif
+}
 
+/* Build a reader over fd, which it takes ownership of (closed on error).
+ * Block devices are read, with pread and no extents, only with devices. */
+static komparu_reader_t *file_reader_from_fd(int fd, const char *source, bool devices,
+                                             const char **err_msg) {
     struct stat st;
     if (fstat(fd, &st) != 0) {
         komparu_strerror(errno, komparu_errbuf, sizeof(komparu_errbuf));
@@ -198,11 +364,24 @@ komparu_reader_t *komparu_reader_file_open(const char *path, const char **err_ms
     }
 
     /* Reject non-regular files (directories, devices, pipes, sockets) */
-    if (!S_ISREG(st.st_mode)) {
-        *err_msg = "not a regular file";
+    bool device = devices && S_ISBLK(st.st_mode);
+    if (!S_ISREG(st.st_mode) && !device) {
+        *err_msg = devices ? "not a regular file or block device" : "not a regular file";
         close(fd);
+        errno = ENOTSUP;
         return NULL;
     }
+    if (device) {
+        /* st_size is 0 for a device; its end is its size */
+        off_t end = lseek(fd, 0, SEEK_END);
+        if (end < 0) {
+            komparu_strerror(errno, komparu_errbuf, sizeof(komparu_errbuf));
+            *err_msg = komparu_errbuf;
+            close(fd);
+            return NULL;
+        }
+        st.st_size = end;
+    }
 
     /* Allocate reader + context */
     komparu_reader_t *reader = calloc(1, sizeof(komparu_reader_t));
@@ -215,22 +394,25 @@ komparu_reader_t *komparu_reader_file_open(const char *path, const char **err_ms
         return NULL;
     }
 
+    /* Unknown size (-1): no size pre-check, read to EOF with pread */
+    bool sized = device || !size_unreliable(fd, &st);
     ctx->fd = fd;
-    ctx->file_size = (int64_t)st.st_size;
+    ctx->file_size = sized ? (int64_t)st.st_size : -1;
     ctx->offset = 0;
-    snprintf(ctx->source, sizeof(ctx->source), "%s", path);
+    snprintf(ctx->source, sizeof(ctx->source), "%s", source);
 
     reader->ctx = ctx;
     reader->source_name = ctx->source;
     reader->get_size = file_get_size;
-
-    /* Try mmap for non-empty files */
-    if (st.st_size > 0) {
-        void *mapped = mmap(NULL, (size_t)st.st_size, PROT_READ, MAP_PRIVATE, fd, 0);
-        if (mapped != MAP_FAILED) {
-            /* Advise sequential access */
-            madvise(mapped, (size_t)st.st_size, MADV_SEQUENTIAL);
-            ctx->mapped = mapped;
+    reader->lock_shared = file_lock_shared;
+#if defined(SEEK_DATA) && defined(SEEK_HOLE)
+    if (!device && sized) reader->extent = file_extent;
+#endif
+
+    /* Try mmap for non-empty files, mapping the first window */
+    ctx->window = mmap_window;
+    if (ctx->file_size > 0 && !device) {
+        if (map_window(ctx) == 0) {
             reader->read = file_read_mmap;
             reader->seek = file_seek;
             reader->close = file_close_mmap;
@@ -247,6 +429,157 @@ komparu_reader_t *komparu_reader_file_open(const char *path, const char **err_ms
     return reader;
 }
 
+static komparu_reader_t *file_open_failed(const char **err_msg) {
+    komparu_strerror(errno, komparu_errbuf, sizeof(komparu_errbuf));
+    *err_msg = komparu_errbuf;
+    return NULL;
+}
+
+/* Forensic reads: O_NOATIME on every open, and how many opens it was refused on */
+static _Atomic bool noatime = false;
+static _Atomic unsigned long long noatime_denied = 0;
+
+bool komparu_set_noatime(bool enable) {
+#ifdef O_NOATIME
+    if (enable) noatime_denied = 0;
+    noatime = enable;
+    return true;
+#else
+    (void)enable;
+    return false;
+#endif
+}
+
+unsigned long long komparu_noatime_denied(void) {
+    return noatime_denied;
+}
+
+/* Opens with O_NOATIME when on; EPERM means the file is not ours to keep */
+static int open_noatime(int (*open_fn)(int, const char *, int), int dirfd,
+                        const char *path, int flags) {
+#ifdef O_NOATIME
+    if (noatime) {
+        int fd = open_fn(dirfd, path, flags | O_NOATIME);
+        if (fd >= 0 || errno != EPERM) return fd;
+        noatime_denied++;
+    }
+#endif
+    return open_fn(dirfd, path, flags);
+}
+
+static int plain_openat(int dirfd, const char *path, int flags) {
+    return openat(dirfd, path, flags);
+}
+
+int komparu_openat_read(int dirfd, const char *path, int flags) {
+    return open_noatime(plain_openat, dirfd, path, flags);
+}
+
+komparu_reader_t *komparu_reader_file_open(const char *path, const char **err_msg) {
+    int fd = komparu_openat_read(AT_FDCWD, path, O_RDONLY | O_CLOEXEC);
+    if (fd < 0) return file_open_failed(err_msg);
+    return file_reader_from_fd(fd, path, false, err_msg);
+}
+
+komparu_reader_t *komparu_reader_device_open(const char *path, const char **err_msg) {
+    int fd = komparu_openat_read(AT_FDCWD, path, O_RDONLY | O_CLOEXEC);
+    if (fd < 0) return file_open_failed(err_msg);
+    return file_reader_from_fd(fd, path, true, err_msg);
+}
+
+komparu_reader_t *komparu_reader_file_openat(int dirfd, const char *path,
+                                             const char **err_msg) {
+    int fd = komparu_openat_read(dirfd, path, O_RDONLY | O_CLOEXEC);
+    if (fd < 0) return file_open_failed(err_msg);
+    return file_reader_from_fd(fd, path, false, err_msg);
+}
+
+#if defined(__linux__) && defined(SYS_openat2)
+/* One syscall that resolves the whole path; -1 with ENOSYS before Linux 5.6 */
+static int openat2_beneath(int dirfd, const char *path, int flags) {
+    struct open_how how = {
+        .flags = (uint64_t)(flags | O_NOFOLLOW | O_CLOEXEC),
+        .resolve = RESOLVE_BENEATH | RESOLVE_NO_SYMLINKS,
+    };
+    int fd;
+    do {
+        fd = (int)syscall(SYS_openat2, dirfd, path, &how, sizeof(how));
+    } while (fd < 0 && errno == EAGAIN);  /* a rename raced the lookup */
+    return fd;
+}
+#endif
+
+int komparu_openat_beneath(int dirfd, const char *path, int flags) {
+#if defined(__linux__) && defined(SYS_openat2)
+    static _Atomic bool no_openat2 = false;
+    if (!no_openat2) {
+        int fd = openat2_beneath(dirfd, path, flags);
+        if (fd >= 0 || errno != ENOSYS) return fd;
+        no_openat2 = true;
+    }
+#endif
+    /* One component at a time, none of them allowed to be a symlink */
+    if (path[0] == '/') {
+        errno = EXDEV;
+        return -1;
+    }
+    int cur = dirfd;
+    const char *p = path;
+    for (;;) {
+        const char *slash = strchr(p, '/');
+        size_t len = slash ? (size_t)(slash - p) : strlen(p);
+        char name[NAME_MAX + 1];
+        if (len > NAME_MAX) {
+            if (cur != dirfd) close(cur);
+            errno = ENAMETOOLONG;
+            return -1;
+        }
+        memcpy(name, p, len);
+        name[len] = '\0';
+        if (strcmp(name, "..") == 0) {
+            if (cur != dirfd) close(cur);
+            errno = EXDEV;
+            return -1;
+        }
+        int next;
+        if (slash) {
+            next = len == 0 || strcmp(name, ".") == 0 ? cur
+                 : openat(cur, name, O_RDONLY | O_DIRECTORY | O_NOFOLLOW | O_CLOEXEC);
+        } else {
+            next = openat(cur, len ? name : ".", flags | O_NOFOLLOW | O_CLOEXEC);
+        }
+        int saved = errno;
+        if (cur != dirfd && next != cur) close(cur);
+        errno = saved;
+        if (next < 0 || !slash) return next;
+        cur = next;
+        p = slash + 1;
+    }
+}
+
+komparu_reader_t *komparu_reader_file_open_beneath(int dirfd, const char *path,
+                                                   const char **err_msg) {
+    int fd = open_noatime(komparu_openat_beneath, dirfd, path, O_RDONLY);
+    if (fd < 0) return file_open_failed(err_msg);
+    return file_reader_from_fd(fd, path, false, err_msg);
+}
+
+komparu_reader_t *komparu_reader_file_fdopen(int fd, const char **err_msg) {
+    /* Own a duplicate so close() leaves the caller's descriptor open */
+    int own = fcntl(fd, F_DUPFD_CLOEXEC, 0);
+    if (own < 0) return file_open_failed(err_msg);
+    char source[32];
+    snprintf(source, sizeof(source), "<fd %d>", fd);
+    komparu_reader_t *reader = file_reader_from_fd(own, source, false, err_msg);
+    /* The duplicate shares the caller's file position, which lseek moves,
+     * and the caller's flock(), which closing it would not release */
+    if (reader) {
+        reader->extent = NULL;
+        reader->lock_shared = NULL;
+    }
+    return reader;
+}
+
 #else /* KOMPARU_WINDOWS */
 
 /* =========================================================================
@@ -256,41 +589,85 @@ komparu_reader_t *komparu_reader_file_open(const char *path, const char **err_ms
 typedef struct {
     HANDLE hFile;
     HANDLE hMapping;
-    void *mapped;
+    void *mapped;       /* Base of the mapped view, or NULL */
+    int64_t map_start;  /* File offset the view starts at */
+    size_t map_len;     /* Bytes viewed from there */
+    int64_t window;     /* Largest view, from komparu_set_mmap_window() */
     int64_t file_size;
     int64_t offset;
     char source[1024];
 } file_ctx_win_t;
 
+/* Map the view holding ctx->offset in place of the current one */
+static bool map_window_win(file_ctx_win_t *ctx) {
+    int64_t start = ctx->offset - ctx->offset % ctx->window;
+    int64_t len = ctx->file_size - start;
+    if (len > ctx->window) len = ctx->window;
+    if (ctx->mapped) {
+        UnmapViewOfFile(ctx->mapped);
+        ctx->mapped = NULL;
+    }
+    void *mapped = MapViewOfFile(ctx->hMapping, FILE_MAP_READ, (DWORD)((uint64_t)start >> 32),
+                                 (DWORD)((uint64_t)start & 0xFFFFFFFFu), (SIZE_T)len);
+    if (!mapped) return false;
+    ctx->mapped = mapped;
+    ctx->map_start = start;
+    ctx->map_len = (size_t)len;
+    return true;
+}
+
+static int64_t read_handle_win(file_ctx_win_t *ctx, void *buf, size_t size) {
+    DWORD to_read = (size > MAXDWORD) ? MAXDWORD : (DWORD)size;
+    DWORD bytes_read = 0;
+    if (!ReadFile(ctx->hFile, buf, to_read, &bytes_read, NULL)) {
+        return -1;
+    }
+    ctx->offset += bytes_read;
+    return (int64_t)bytes_read;
+}
+
 static int64_t file_read_win(komparu_reader_t *self, void *buf, size_t size) {
     file_ctx_win_t *ctx = (file_ctx_win_t *)self->ctx;
 
-    if (ctx->mapped) {
+    if (ctx->hMapping) {
         /* Memory-mapped read */
         if (ctx->offset >= ctx->file_size) return 0;
 
-        size_t remaining = (size_t)(ctx->file_size - ctx->offset);
-        size_t to_read = (size < remaining) ? size : remaining;
-
-        __try {
-            memcpy(buf, (const char *)ctx->mapped + ctx->offset, to_read);
-        } __except (GetExceptionCode() == EXCEPTION_IN_PAGE_ERROR
-                        ? EXCEPTION_EXECUTE_HANDLER
-                        : EXCEPTION_CONTINUE_SEARCH) {
-            return -1;  /* File truncated — equivalent to SIGBUS */
+        /* 64-bit: the rest of a large file may not fit a 32-bit size_t */
+        uint64_t remaining = (uint64_t)(ctx->file_size - ctx->offset);
+        size_t to_read = ((uint64_t)size < remaining) ? size : (size_t)remaining;
+        size_t done = 0;
+
+        while (done < to_read) {
+            if (!ctx->mapped || ctx->offset < ctx->map_start
+                    || ctx->offset >= ctx->map_start + (int64_t)ctx->map_len) {
+                if (!map_window_win(ctx)) {
+                    if (done) break;
+                    /* Out of address space for the next view: ReadFile this part */
+                    LARGE_INTEGER li;
+                    li.QuadPart = ctx->offset;
+                    if (!SetFilePointerEx(ctx->hFile, li, NULL, FILE_BEGIN)) return -1;
+                    return read_handle_win(ctx, buf, to_read);
+                }
+            }
+            size_t in_window = (size_t)(ctx->map_start + (int64_t)ctx->map_len - ctx->offset);
+            size_t n = (to_read - done < in_window) ? to_read - done : in_window;
+            __try {
+                memcpy((char *)buf + done,
+                       (const char *)ctx->mapped + (ctx->offset - ctx->map_start), n);
+            } __except (GetExceptionCode() == EXCEPTION_IN_PAGE_ERROR
+                            ? EXCEPTION_EXECUTE_HANDLER
+                            : EXCEPTION_CONTINUE_SEARCH) {
+                errno = ESTALE;
+                return -1;  /* File truncated — equivalent to SIGBUS */
+            }
+            done += n;
+            ctx->offset += (int64_t)n;
         }
-
-        ctx->offset += (int64_t)to_read;
-        return (int64_t)to_read;
+        return (int64_t)done;
     } else {
         /* ReadFile fallback */
-        DWORD to_read = (size > MAXDWORD) ? MAXDWORD : (DWORD)size;
-        DWORD bytes_read = 0;
-        if (!ReadFile(ctx->hFile, buf, to_read, &bytes_read, NULL)) {
-            return -1;
-        }
-        ctx->offset += bytes_read;
-        return (int64_t)bytes_read;
+        return read_handle_win(ctx, buf, size);
     }
 }
 
@@ -302,7 +679,7 @@ static int64_t file_get_size_win(komparu_reader_t *self) {
 static int file_seek_win(komparu_reader_t *self, int64_t offset) {
     file_ctx_win_t *ctx = (file_ctx_win_t *)self->ctx;
 
-    if (ctx->mapped) {
+    if (ctx->hMapping) {
         if (offset < 0 || offset > ctx->file_size) return -1;
         ctx->offset = offset;
         return 0;
@@ -317,6 +694,17 @@ static int file_seek_win(komparu_reader_t *self, int64_t offset) {
     return 0;
 }
 
+static int file_lock_shared_win(komparu_reader_t *self, bool lock) {
+    file_ctx_win_t *ctx = (file_ctx_win_t *)self->ctx;
+    OVERLAPPED ov = {0};
+    BOOL ok = lock
+        ? LockFileEx(ctx->hFile, LOCKFILE_FAIL_IMMEDIATELY, 0, MAXDWORD, MAXDWORD, &ov)
+        : UnlockFileEx(ctx->hFile, 0, MAXDWORD, MAXDWORD, &ov);
+    if (ok) return 0;
+    errno = GetLastError() == ERROR_LOCK_VIOLATION ? EWOULDBLOCK : EIO;
+    return -1;
+}
+
 static void file_close_win(komparu_reader_t *self) {
     file_ctx_win_t *ctx = (file_ctx_win_t *)self->ctx;
     if (ctx->mapped) UnmapViewOfFile(ctx->mapped);
@@ -326,13 +714,121 @@ static void file_close_win(komparu_reader_t *self) {
     free(self);
 }
 
+/**
+ * Decode WTF-8 — UTF-8 that may also encode lone surrogates, the form
+ * Python gives a Windows name holding unpaired UTF-16 — into out, which
+ * has room for strlen(path) + 1 units. MultiByteToWideChar rejects those
+ * names or replaces the surrogates, opening some other file or none.
+ * Returns false on bytes that are not WTF-8.
+ */
+static bool wtf8_to_wide(const char *path, wchar_t *out) {
+    const unsigned char *p = (const unsigned char *)path;
+    while (*p) {
+        uint32_t cp;
+        int extra;
+        if (*p < 0x80) {
+            cp = *p;
+            extra = 0;
+        } else if (*p >= 0xC2 && *p < 0xE0) {
+            cp = *p & 0x1F;
+            extra = 1;
+        } else if (*p >= 0xE0 && *p < 0xF0) {
+            cp = *p & 0x0F;
+            extra = 2;
+        } else if (*p >= 0xF0 && *p < 0xF5) {
+            cp = *p & 0x07;
+            extra = 3;
+        } else {
+            return false;
+        }
+        p++;
+        for (int i = 0; i < extra; i++, p++) {
+            if ((*p & 0xC0) != 0x80) return false;
+            cp = cp << 6 | (*p & 0x3F);
+        }
+        static const uint32_t min_cp[] = { 0, 0x80, 0x800, 0x10000 };
+        if (cp < min_cp[extra] || cp > 0x10FFFF) return false;  /* overlong */
+        if (cp >= 0x10000) {
+            *out++ = (wchar_t)(0xD800 | ((cp - 0x10000) >> 10));
+            *out++ = (wchar_t)(0xDC00 | (cp & 0x3FF));
+        } else {
+            *out++ = (wchar_t)cp;  /* surrogates included: they are the name */
+        }
+    }
+    *out = L'\0';
+    return true;
+}
+
+/* Absolute, backslashes only and prefixed with \\?\ (\\?\UNC\ for a share) */
+wchar_t *komparu_long_path_win(const char *path) {
+    /* A unit per byte at most: no sequence decodes to more units than bytes */
+    wchar_t *wide = malloc((strlen(path) + 1) * sizeof(wchar_t));
+    if (!wide) {
+        errno = ENOMEM;
+        return NULL;
+    }
+    if (!wtf8_to_wide(path, wide)) {
+        free(wide);
+        errno = EINVAL;
+        return NULL;
+    }
+    if (wcsncmp(wide, L"\\\\?\\", 4) == 0) return wide;  /* already extended */
+    for (wchar_t *c = wide; *c; c++) {
+        if (*c == L'/') *c = L'\\';
+    }
+
+    /* GetFullPathNameW resolves . and .., which the prefix turns off */
+    DWORD full_len = GetFullPathNameW(wide, 0, NULL, NULL);
+    wchar_t *full = full_len ? malloc(((size_t)full_len + 8) * sizeof(wchar_t)) : NULL;
+    if (!full) {
+        errno = full_len ? ENOMEM : ENOENT;
+        free(wide);
+        return NULL;
+    }
+    wchar_t *body = full + 4;
+    if (GetFullPathNameW(wide, full_len, body, NULL) == 0) {
+        errno = ENOENT;
+        free(full);
+        free(wide);
+        return NULL;
+    }
+    free(wide);
+    const wchar_t *prefix = L"\\\\?\\";
+    if (wcsncmp(body, L"\\\\.\\", 4) == 0) {
+        prefix = L"";  /* a device path, not subject to MAX_PATH */
+    } else if (wcsncmp(body, L"\\\\", 2) == 0) {
+        prefix = L"\\\\?\\UNC\\";
+        body += 2;  /* \\server\share\... becomes \\?\UNC\server\share\... */
+    }
+    size_t prefix_len = wcslen(prefix);
+    memmove(full + prefix_len, body, (wcslen(body) + 1) * sizeof(wchar_t));
+    memcpy(full, prefix, prefix_len * sizeof(wchar_t));
+    return full;
+}
+
 komparu_reader_t *komparu_reader_file_open(const char *path, const char **err_msg) {
-    HANDLE hFile = CreateFileA(
-        path, GENERIC_READ, FILE_SHARE_READ, NULL,
+    wchar_t *wpath = komparu_long_path_win(path);
+    if (!wpath) {
+        *err_msg = errno == ENOMEM ? "out of memory" : "invalid file name";
+        return NULL;
+    }
+    HANDLE hFile = CreateFileW(
+        wpath, GENERIC_READ, FILE_SHARE_READ, NULL,
         OPEN_EXISTING, FILE_ATTRIBUTE_NORMAL, NULL
     );
+    free(wpath);
     if (hFile == INVALID_HANDLE_VALUE) {
+        DWORD code = GetLastError();
         *err_msg = "cannot open file";
+        if (code == ERROR_FILE_NOT_FOUND || code == ERROR_PATH_NOT_FOUND) {
+            errno = ENOENT;
+        } else if (code == ERROR_ACCESS_DENIED) {
+            errno = EACCES;
+        } else if (code == ERROR_FILENAME_EXCED_RANGE) {
+            errno = ENAMETOOLONG;
+        } else {
+            errno = EIO;
+        }
         return NULL;
     }
 
@@ -362,18 +858,16 @@ komparu_reader_t *komparu_reader_file_open(const char *path, const char **err_ms
     reader->get_size = file_get_size_win;
     reader->read = file_read_win;
     reader->seek = file_seek_win;
+    reader->lock_shared = file_lock_shared_win;
     reader->close = file_close_win;
 
-    /* Try memory mapping for non-empty files */
+    /* Try memory mapping for non-empty files, viewing the first window */
+    ctx->window = mmap_window;
     if (size.QuadPart > 0) {
-        HANDLE hMapping = CreateFileMappingA(hFile, NULL, PAGE_READONLY, 0, 0, NULL);
+        HANDLE hMapping = CreateFileMappingW(hFile, NULL, PAGE_READONLY, 0, 0, NULL);
         if (hMapping) {
-            void *mapped = MapViewOfFile(hMapping, FILE_MAP_READ, 0, 0, 0);
-            if (mapped) {
-                ctx->hMapping = hMapping;
-                ctx->mapped = mapped;
-                return reader;
-            }
+            ctx->hMapping = hMapping;
+            if (map_window_win(ctx)) return reader;
             CloseHandle(hMapping);
         }
     }
@@ -384,4 +878,63 @@ komparu_reader_t *komparu_reader_file_open(const char *path, const char **err_ms
     return reader;
 }
 
+/* Raw disks (\\.\PhysicalDriveN) need IOCTL_DISK_GET_LENGTH_INFO for
+ * their size; only files for now. */
+komparu_reader_t *komparu_reader_device_open(const char *path, const char **err_msg) {
+    return komparu_reader_file_open(path, err_msg);
+}
+
+/* No openat or POSIX descriptors to duplicate: handles are Unix-only. */
+komparu_reader_t *komparu_reader_file_openat(int dirfd, const char *path,
+                                             const char **err_msg) {
+    (void)dirfd;
+    (void)path;
+    *err_msg = "directory handles are not supported on this platform";
+    errno = ENOTSUP;
+    return NULL;
+}
+
+int komparu_openat_beneath(int dirfd, const char *path, int flags) {
+    (void)dirfd;
+    (void)path;
+    (void)flags;
+    errno = ENOTSUP;
+    return -1;
+}
+
+int komparu_openat_read(int dirfd, const char *path, int flags) {
+    (void)dirfd;
+    (void)path;
+    (void)flags;
+    errno = ENOTSUP;
+    return -1;
+}
+
+/* Reading a file through a handle does not change its access time on NTFS
+ * by default (NtfsDisableLastAccessUpdate), and there is no per-open flag. */
+bool komparu_set_noatime(bool enable) {
+    (void)enable;
+    return false;
+}
+
+unsigned long long komparu_noatime_denied(void) {
+    return 0;
+}
+
+komparu_reader_t *komparu_reader_file_open_beneath(int dirfd, const char *path,
+                                                   const char **err_msg) {
+    (void)dirfd;
+    (void)path;
+    *err_msg = "directory handles are not supported on this platform";
+    errno = ENOTSUP;
+    return NULL;
+}
+
+komparu_reader_t *komparu_reader_file_fdopen(int fd, const char **err_msg) {
+    (void)fd;
+    *err_msg = "file handles are not supported on this platform";
+    errno = ENOTSUP;
+    return NULL;
+}
+
 #endif /* KOMPARU_WINDOWS */
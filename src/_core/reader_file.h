@@ -18,4 +18,16 @@
  */
 int komparu_sigbus_init(void);
 
+#ifdef KOMPARU_WINDOWS
+#include <wchar.h>
+
+/**
+ * UTF-16 form of a WTF-8 path for the wide API, in extended-length
+ * \\?\ syntax so it is not cut at MAX_PATH. Unpaired surrogates in the
+ * name are kept. Returns a malloc'd string, or NULL with errno set
+ * (EINVAL for bytes that are not WTF-8, ENOMEM, ENOENT).
+ */
+wchar_t *komparu_long_path_win(const char *path);
+#endif
+
 #endif /* KOMPARU_READER_FILE_H */
@@ -205,6 +205,14 @@ static int http_seek(komparu_reader_t *self, int64_t offset) {
     return 0;
 }
 
+/* Map a transfer failure to the errno reported to the binding (see compat.h). */
+static int http_errno(CURLcode res, long status) {
+    if (res == CURLE_OPERATION_TIMEDOUT) return ETIMEDOUT;
+    if (status == 404 || status == 410) return ENOENT;
+    if (status == 401 || status == 403) return EACCES;
+    return EIO;
+}
+
 static int64_t http_read(komparu_reader_t *self, void *buf, size_t size) {
     if (size == 0) return 0;
 
@@ -260,6 +268,7 @@ static int64_t http_read(komparu_reader_t *self, void *buf, size_t size) {
     if (res != CURLE_OK) {
         snprintf(http_errbuf, sizeof(http_errbuf),
                  "HTTP read error: %s", ctx->curl_errbuf);
+        errno = http_errno(res, 0);
         return -1;
     }
 
@@ -297,6 +306,7 @@ static int64_t http_read(komparu_reader_t *self, void *buf, size_t size) {
     /* 4xx / 5xx error */
     snprintf(http_errbuf, sizeof(http_errbuf),
              "HTTP error: status %ld", response_code);
+    errno = http_errno(CURLE_OK, response_code);
     return -1;
 }
 
@@ -457,6 +467,7 @@ komparu_reader_t *komparu_reader_http_open_ex(
     curl_easy_setopt(ctx->easy, CURLOPT_HEADERDATA, &head_range_supported);
 
     CURLcode res = curl_easy_perform(ctx->easy);
+    long response_code = 0;
 
     /* Remove header callback for subsequent requests */
     curl_easy_setopt(ctx->easy, CURLOPT_HEADERFUNCTION, NULL);
@@ -475,7 +486,6 @@ komparu_reader_t *komparu_reader_http_open_ex(
     }
 
     /* Check HTTP status */
-    long response_code = 0;
     curl_easy_getinfo(ctx->easy, CURLINFO_RESPONSE_CODE, &response_code);
 
     if (response_code == 404 || response_code == 410) {
@@ -517,5 +527,6 @@ fail:
     free(ctx->url);
     free(ctx);
     free(reader);
+    errno = http_errno(res, response_code);
     return NULL;
 }
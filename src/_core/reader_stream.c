@@ -0,0 +1,232 @@
+/**
+ * reader_stream.c — Reader over a Python binary file-like object.
+ *
+ * The engine runs with the GIL released; every callback attaches via
+ * PyGILState_Ensure() for the duration of one Python call. Reads loop
+ * until the requested size is filled or EOF, so short reads from
+ * sockets/pipes never look like a content difference.
+ *
+ * Exceptions raised by the stream are stashed in the context and
+ * restored by the caller once it holds the GIL again. A non-blocking
+ * stream with no data yet (readinto() returning None, BlockingIOError)
+ * fails the read with EAGAIN, which the engine retries; the stashed
+ * BlockingIOError is dropped by the next read and raised only if the
+ * retries run out.
+ */
+
+#include "reader_stream.h"
+#include <stdlib.h>
+
+typedef struct {
+    PyObject *stream;       /* strong reference */
+    PyObject *exc;          /* pending exception from a callback, or NULL */
+    bool transient;         /* exc is a BlockingIOError: the next read retries */
+    int64_t size;           /* -1 if unknown (not seekable) */
+    bool seekable;
+    bool has_readinto;
+} stream_ctx_t;
+
+/* Called with GIL held: stash the current exception (keep the first one). */
+static void stash_error(stream_ctx_t *ctx) {
+    PyObject *exc = PyErr_GetRaisedException();
+    if (!ctx->exc) {
+        ctx->exc = exc;
+    } else {
+        Py_XDECREF(exc);
+    }
+}
+
+/* One read call. Returns bytes read, 0 on EOF, -1 on error. GIL held. */
+static int64_t read_once(stream_ctx_t *ctx, char *buf, size_t size) {
+    if (ctx->has_readinto) {
+        PyObject *view = PyMemoryView_FromMemory(buf, (Py_ssize_t)size, PyBUF_WRITE);
+        if (!view) return -1;
+        PyObject *n = PyObject_CallMethod(ctx->stream, "readinto", "O", view);
+        Py_DECREF(view);
+        if (!n) return -1;
+        if (n == Py_None) {
+            /* Non-blocking stream with no data yet: EAGAIN, retried */
+            Py_DECREF(n);
+            PyErr_SetString(PyExc_BlockingIOError, "stream returned no data");
+            return -1;
+        }
+        Py_ssize_t got = PyLong_AsSsize_t(n);
+        Py_DECREF(n);
+        if (got < 0 || (size_t)got > size) {
+            if (!PyErr_Occurred())
+                PyErr_SetString(PyExc_ValueError, "readinto() returned invalid length");
+            return -1;
+        }
+        return (int64_t)got;
+    }
+
+    PyObject *data = PyObject_CallMethod(ctx->stream, "read", "n", (Py_ssize_t)size);
+    if (!data) return -1;
+    if (!PyBytes_Check(data)) {
+        Py_DECREF(data);
+        PyErr_SetString(PyExc_TypeError, "stream read() must return bytes");
+        return -1;
+    }
+    Py_ssize_t got = PyBytes_GET_SIZE(data);
+    if ((size_t)got > size) {
+        Py_DECREF(data);
+        PyErr_SetString(PyExc_ValueError, "read() returned more data than requested");
+        return -1;
+    }
+    memcpy(buf, PyBytes_AS_STRING(data), (size_t)got);
+    Py_DECREF(data);
+    return (int64_t)got;
+}
+
+static int64_t stream_read(komparu_reader_t *self, void *buf, size_t size) {
+    stream_ctx_t *ctx = self->ctx;
+    if (ctx->exc && !ctx->transient) return -1;
+
+    PyGILState_STATE gs = PyGILState_Ensure();
+    Py_CLEAR(ctx->exc);  /* a BlockingIOError the engine is retrying */
+    ctx->transient = false;
+    size_t total = 0;
+    while (total < size) {
+        int64_t n = read_once(ctx, (char *)buf + total, size - total);
+        if (n < 0) {
+            if (PyErr_ExceptionMatches(PyExc_BlockingIOError)) {
+                if (total > 0) {  /* hand over what came, wait for the rest later */
+                    PyErr_Clear();
+                    break;
+                }
+                ctx->transient = true;
+            }
+            stash_error(ctx);
+            PyGILState_Release(gs);
+            if (ctx->transient) errno = EAGAIN;
+            return -1;
+        }
+        if (n == 0) break;
+        total += (size_t)n;
+    }
+    PyGILState_Release(gs);
+    return (int64_t)total;
+}
+
+static int64_t stream_get_size(komparu_reader_t *self) {
+    stream_ctx_t *ctx = self->ctx;
+    return ctx->size;
+}
+
+static int stream_seek(komparu_reader_t *self, int64_t offset) {
+    stream_ctx_t *ctx = self->ctx;
+    if (ctx->exc) return -1;
+
+    PyGILState_STATE gs = PyGILState_Ensure();
+    PyObject *r = PyObject_CallMethod(ctx->stream, "seek", "Li", (long long)offset, 0);
+    if (!r) {
+        stash_error(ctx);
+        PyGILState_Release(gs);
+        return -1;
+    }
+    Py_DECREF(r);
+    PyGILState_Release(gs);
+    return 0;
+}
+
+static void stream_close(komparu_reader_t *self) {
+    if (!self) return;
+    stream_ctx_t *ctx = self->ctx;
+    if (ctx) {
+        PyGILState_STATE gs = PyGILState_Ensure();
+        Py_XDECREF(ctx->exc);
+        Py_XDECREF(ctx->stream);
+        PyGILState_Release(gs);
+        free(ctx);
+    }
+    free(self);
+}
+
+/* Probe size via tell()/seek(0, 2)/seek(pos). Returns -1 if unavailable. */
+static int64_t probe_size(PyObject *stream) {
+    PyObject *r = PyObject_CallMethod(stream, "seekable", NULL);
+    if (!r) {
+        PyErr_Clear();
+        return -1;
+    }
+    int ok = PyObject_IsTrue(r);
+    Py_DECREF(r);
+    if (ok != 1) {
+        PyErr_Clear();
+        return -1;
+    }
+
+    PyObject *pos = PyObject_CallMethod(stream, "tell", NULL);
+    if (!pos) goto fail;
+    r = PyObject_CallMethod(stream, "seek", "ii", 0, 2);
+    if (!r) {
+        Py_DECREF(pos);
+        goto fail;
+    }
+    long long end = PyLong_AsLongLong(r);
+    Py_DECREF(r);
+    r = PyObject_CallMethod(stream, "seek", "O", pos);
+    long long start = PyLong_AsLongLong(pos);
+    Py_DECREF(pos);
+    if (!r) goto fail;
+    Py_DECREF(r);
+    if (PyErr_Occurred()) goto fail;
+
+    /* Size is what remains from the current position */
+    return end >= start ? (int64_t)(end - start) : -1;
+
+fail:
+    PyErr_Clear();
+    return -1;
+}
+
+komparu_reader_t *komparu_reader_stream_open(PyObject *stream, const char *name) {
+    if (!PyObject_HasAttrString(stream, "read") &&
+        !PyObject_HasAttrString(stream, "readinto")) {
+        PyErr_Format(PyExc_TypeError,
+                     "%s must be a binary file-like object with read() or readinto()",
+                     name);
+        return NULL;
+    }
+
+    komparu_reader_t *reader = calloc(1, sizeof(komparu_reader_t));
+    stream_ctx_t *ctx = calloc(1, sizeof(stream_ctx_t));
+    if (!reader || !ctx) {
+        free(reader);
+        free(ctx);
+        PyErr_NoMemory();
+        return NULL;
+    }
+
+    Py_INCREF(stream);
+    ctx->stream = stream;
+    ctx->has_readinto = PyObject_HasAttrString(stream, "readinto");
+    ctx->size = probe_size(stream);
+    ctx->seekable = ctx->size >= 0;
+
+    reader->read = stream_read;
+    reader->get_size = stream_get_size;
+    /* Quick check seeks to absolute sample offsets — only safe for
+     * streams positioned at 0, where "remaining size" == total size. */
+    reader->seek = NULL;
+    if (ctx->seekable) {
+        PyObject *pos = PyObject_CallMethod(stream, "tell", NULL);
+        if (pos) {
+            if (PyLong_AsLongLong(pos) == 0) reader->seek = stream_seek;
+            Py_DECREF(pos);
+        }
+        PyErr_Clear();
+    }
+    reader->close = stream_close;
+    reader->ctx = ctx;
+    reader->source_name = name;
+    return reader;
+}
+
+int komparu_reader_stream_restore_error(komparu_reader_t *reader) {
+    stream_ctx_t *ctx = reader->ctx;
+    if (!ctx->exc) return 0;
+    PyErr_SetRaisedException(ctx->exc);
+    ctx->exc = NULL;
+    return 1;
+}
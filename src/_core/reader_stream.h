@@ -0,0 +1,30 @@
+/**
+ * reader_stream.h — Reader over a Python binary file-like object.
+ *
+ * Lets the comparison engine consume anything with read()/readinto()
+ * (and optionally seek()/tell()): io.BytesIO, decrypting wrappers,
+ * custom storage layers. Each callback briefly re-acquires the GIL.
+ */
+
+#ifndef KOMPARU_READER_STREAM_H
+#define KOMPARU_READER_STREAM_H
+
+#include "reader.h"
+
+/**
+ * Wrap a Python stream object. Must be called with the GIL held.
+ *
+ * The reader holds a strong reference to `stream` until close().
+ * Size and seek support are probed once here via seekable()/tell().
+ * Returns NULL on error with a Python exception set.
+ */
+komparu_reader_t *komparu_reader_stream_open(PyObject *stream, const char *name);
+
+/**
+ * Move the Python exception raised inside a reader callback (if any)
+ * back onto the current thread. Must be called with the GIL held,
+ * before close(). Returns 1 if an exception was restored, 0 otherwise.
+ */
+int komparu_reader_stream_restore_error(komparu_reader_t *reader);
+
+#endif /* KOMPARU_READER_STREAM_H */
@@ -0,0 +1,73 @@
+/**
+ * reader_zero.c — A source of zero bytes.
+ *
+ * The constant other side of verify_zero(): compared against a file or
+ * block device of the same size, it makes the ordinary engine answer
+ * whether that side is all zeros. It reports itself as one hole, so
+ * ranges that are holes in the other side are skipped, not read.
+ */
+
+#include "reader.h"
+#include <string.h>
+#include <stdlib.h>
+
+typedef struct {
+    int64_t size;
+    int64_t offset;
+} zero_ctx_t;
+
+static int64_t zero_read(komparu_reader_t *self, void *buf, size_t size) {
+    zero_ctx_t *ctx = (zero_ctx_t *)self->ctx;
+    size_t remaining = (size_t)(ctx->size - ctx->offset);
+    size_t n = size < remaining ? size : remaining;
+    memset(buf, 0, n);
+    ctx->offset += (int64_t)n;
+    return (int64_t)n;
+}
+
+static int64_t zero_get_size(komparu_reader_t *self) {
+    return ((zero_ctx_t *)self->ctx)->size;
+}
+
+static int zero_seek(komparu_reader_t *self, int64_t offset) {
+    zero_ctx_t *ctx = (zero_ctx_t *)self->ctx;
+    if (offset < 0 || offset > ctx->size) {
+        return -1;
+    }
+    ctx->offset = offset;
+    return 0;
+}
+
+static int64_t zero_extent(komparu_reader_t *self, int64_t offset, bool *hole) {
+    zero_ctx_t *ctx = (zero_ctx_t *)self->ctx;
+    if (offset >= ctx->size) {
+        return -1;
+    }
+    *hole = true;
+    return ctx->size;
+}
+
+static void zero_close(komparu_reader_t *self) {
+    free(self->ctx);
+    free(self);
+}
+
+komparu_reader_t *komparu_reader_zero_open(int64_t size, const char **err_msg) {
+    komparu_reader_t *reader = calloc(1, sizeof(komparu_reader_t));
+    zero_ctx_t *ctx = calloc(1, sizeof(zero_ctx_t));
+    if (!reader || !ctx) {
+        *err_msg = "out of memory";
+        free(reader);
+        free(ctx);
+        return NULL;
+    }
+    ctx->size = size;
+    reader->ctx = ctx;
+    reader->source_name = "<zeros>";
+    reader->read = zero_read;
+    reader->get_size = zero_get_size;
+    reader->seek = zero_seek;
+    reader->extent = zero_extent;
+    reader->close = zero_close;
+    return reader;
+}
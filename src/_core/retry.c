@@ -0,0 +1,152 @@
+/**
+ * retry.c — Transient error classification, backoff, retrying reader.
+ */
+
+#include "retry.h"
+
+#ifndef KOMPARU_WINDOWS
+#include <time.h>
+#endif
+
+bool komparu_errno_transient(int err) {
+    switch (err) {
+        case EAGAIN:
+#if defined(EWOULDBLOCK) && EWOULDBLOCK != EAGAIN
+        case EWOULDBLOCK:
+#endif
+        case EINTR:
+        case EBUSY:
+        case ESTALE:
+            return true;
+        default:
+            return false;
+    }
+}
+
+/* Sleep in slices so a cancelled run does not wait out the backoff */
+#define RETRY_SLICE_SEC 0.05
+
+static void sleep_seconds(double sec) {
+#ifdef KOMPARU_WINDOWS
+    Sleep((DWORD)(sec * 1000.0));
+#else
+    struct timespec ts = {
+        .tv_sec = (time_t)sec,
+        .tv_nsec = (long)((sec - (double)(time_t)sec) * 1e9),
+    };
+    while (nanosleep(&ts, &ts) != 0 && errno == EINTR);
+#endif
+}
+
+bool komparu_sleep(double wait, const komparu_cancel_t *cancel) {
+    while (wait > 0.0) {
+        if (komparu_cancelled(cancel)) return false;
+        double step = wait < RETRY_SLICE_SEC ? wait : RETRY_SLICE_SEC;
+        sleep_seconds(step);
+        wait -= step;
+    }
+    return !komparu_cancelled(cancel);
+}
+
+bool komparu_retry_sleep(const komparu_retry_t *policy, unsigned attempt,
+                         const komparu_cancel_t *cancel) {
+    double wait = policy->delay;
+    for (unsigned k = 0; k < attempt && wait < policy->max_delay; k++)
+        wait *= 2.0;
+    if (wait > policy->max_delay) wait = policy->max_delay;
+    return komparu_sleep(wait, cancel);
+}
+
+/* =========================================================================
+ * Retrying reader
+ * ========================================================================= */
+
+typedef struct {
+    komparu_reader_t *inner;
+    komparu_retry_t policy;
+    const komparu_cancel_t *cancel;
+    int64_t offset;     /* position after the last successful read */
+} retry_ctx_t;
+
+static int64_t retry_read(komparu_reader_t *self, void *buf, size_t size) {
+    retry_ctx_t *ctx = (retry_ctx_t *)self->ctx;
+    komparu_reader_t *in = ctx->inner;
+
+    for (unsigned attempt = 0;; attempt++) {
+        int64_t n = in->read(in, buf, size);
+        if (n >= 0) {
+            ctx->offset += n;
+            return n;
+        }
+        int err = errno;
+        if (attempt >= ctx->policy.retries || !komparu_errno_transient(err))
+            return -1;
+        if (!komparu_retry_sleep(&ctx->policy, attempt, ctx->cancel)) {
+            errno = ECANCELED;
+            return -1;
+        }
+        /* A failed read may have moved the position; restore it */
+        if (in->seek && in->seek(in, ctx->offset) != 0) {
+            errno = err;
+            return -1;
+        }
+    }
+}
+
+static int64_t retry_get_size(komparu_reader_t *self) {
+    komparu_reader_t *in = ((retry_ctx_t *)self->ctx)->inner;
+    return in->get_size(in);
+}
+
+static int retry_seek(komparu_reader_t *self, int64_t offset) {
+    retry_ctx_t *ctx = (retry_ctx_t *)self->ctx;
+    if (ctx->inner->seek(ctx->inner, offset) != 0) return -1;
+    ctx->offset = offset;
+    return 0;
+}
+
+static int64_t retry_extent(komparu_reader_t *self, int64_t offset, bool *hole) {
+    komparu_reader_t *in = ((retry_ctx_t *)self->ctx)->inner;
+    return in->extent(in, offset, hole);
+}
+
+static int retry_lock_shared(komparu_reader_t *self, bool lock) {
+    komparu_reader_t *in = ((retry_ctx_t *)self->ctx)->inner;
+    return in->lock_shared(in, lock);
+}
+
+static void retry_close(komparu_reader_t *self) {
+    retry_ctx_t *ctx = (retry_ctx_t *)self->ctx;
+    ctx->inner->close(ctx->inner);
+    free(ctx);
+    free(self);
+}
+
+komparu_reader_t *komparu_reader_retry_wrap(komparu_reader_t *inner,
+                                            const komparu_retry_t *policy,
+                                            const komparu_cancel_t *cancel) {
+    komparu_reader_t *reader = calloc(1, sizeof(*reader));
+    retry_ctx_t *ctx = calloc(1, sizeof(*ctx));
+    if (KOMPARU_UNLIKELY(!reader || !ctx)) {
+        free(reader);
+        free(ctx);
+        inner->close(inner);
+        errno = ENOMEM;
+        return NULL;
+    }
+
+    ctx->inner = inner;
+    ctx->policy = *policy;
+    ctx->cancel = cancel;
+    ctx->offset = 0;
+
+    reader->read = retry_read;
+    reader->get_size = retry_get_size;
+    reader->seek = inner->seek ? retry_seek : NULL;
+    reader->extent = inner->extent ? retry_extent : NULL;
+    reader->lock_shared = inner->lock_shared ? retry_lock_shared : NULL;
+    reader->close = retry_close;
+    reader->ctx = ctx;
+    reader->source_name = inner->source_name;
+    return reader;
+}
@@ -0,0 +1,50 @@
+/**
+ * retry.h — Retry with backoff for transient I/O errors.
+ *
+ * Two levels, both driven by one policy: a wrapped reader retries each
+ * failed read in place, and the directory engine re-runs a whole file
+ * comparison (reopening both sides) when it still fails transiently.
+ *
+ * Transient: EAGAIN/EWOULDBLOCK, EINTR, EBUSY and ESTALE (NFS handles
+ * invalidated by the server). Everything else fails immediately.
+ */
+
+#ifndef KOMPARU_RETRY_H
+#define KOMPARU_RETRY_H
+
+#include "compat.h"
+#include "reader.h"
+#include "cancel.h"
+
+typedef struct {
+    unsigned retries;   /* extra attempts after the first failure; 0 = off */
+    double delay;       /* first backoff in seconds, doubled per attempt */
+    double max_delay;   /* backoff cap in seconds */
+} komparu_retry_t;
+
+/** True if err is worth retrying. */
+bool komparu_errno_transient(int err);
+
+/**
+ * Sleep for seconds in 50 ms slices, waking early if cancel is set.
+ * Returns false if cancelled. cancel may be NULL.
+ */
+bool komparu_sleep(double seconds, const komparu_cancel_t *cancel);
+
+/**
+ * Sleep for the backoff of the given attempt (0-based), waking early if
+ * cancel is set. Returns false if cancelled. cancel may be NULL.
+ */
+bool komparu_retry_sleep(const komparu_retry_t *policy, unsigned attempt,
+                         const komparu_cancel_t *cancel);
+
+/**
+ * Wrap a reader so each read is retried per policy on transient errors.
+ * Takes ownership of inner (closed with the wrapper, or on failure).
+ * The policy is copied. Returns NULL on OOM.
+ */
+komparu_reader_t *komparu_reader_retry_wrap(komparu_reader_t *inner,
+                                            const komparu_retry_t *policy,
+                                            const komparu_cancel_t *cancel);
+
+#endif /* KOMPARU_RETRY_H */
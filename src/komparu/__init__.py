@@ -8,45 +8,280 @@ from komparu._types import (
     Source,
     DirResult,
     CompareResult,
+    ChunkDiff,
+    Manifest,
+    DuplicateGroup,
+    DedupResult,
+    DirSummary,
+    Snapshot,
+    SnapshotEntry,
+    DiffRegion,
+    RegionKind,
+    SalvageResult,
+    UnreadableRange,
     DiffReason,
+    DiffEntry,
+    DriftEvent,
+    DriftKind,
+    EntryKind,
+    Severity,
+    Progress,
+    PlannedPair,
+    DirPlan,
+    SyncAction,
+    StorageKind,
+    SyncPlan,
+    SyncStep,
     KomparuError,
     SourceNotFoundError,
     SourceReadError,
+    SourcePermissionError,
+    SourceVanishedError,
+    UnsupportedSourceError,
     ArchiveError,
     ArchiveBombError,
     ConfigError,
     ComparisonTimeoutError,
+    ComparisonCancelledError,
+    QuotaReachedError,
+    SignatureError,
+    CopyVerifyError,
+    PatchError,
+    path_key,
+    tree_key,
 )
-from komparu._config import configure, get_config, reset_config
+from komparu._options import CompareOptions, LockPolicy, RetryPolicy, SeverityPolicy
+from komparu._visitor import CompareVisitor
+from komparu._rollup import SummaryVisitor, summarize_dirs
+from komparu._comparator import (
+    AudioComparator,
+    ContentComparator,
+    DebComparator,
+    DecompressComparator,
+    ExecutableComparator,
+    FilterComparator,
+    GzipComparator,
+    ImageComparator,
+    JsonComparator,
+    ParquetComparator,
+    PdfComparator,
+    SniffingComparator,
+    SqliteComparator,
+    TarComparator,
+    TextComparator,
+    XmlComparator,
+    YamlComparator,
+    ZipComparator,
+)
+from komparu._filter import PathFilter, RsyncFilter
+from komparu._fuzzy import fuzzy_hash, fuzzy_score
+from komparu._delta import chunk_diff, delta_size
+from komparu._patch import apply_patch, make_patch
+from komparu._storage import detect_storage, tune_for_storage
+from komparu._metadata import compare_dir_metadata, metadata_diff
+from komparu._dupes import deduplicate, find_duplicates
+from komparu._snapshot import compare_snapshot, read_snapshot, snapshot_tree, write_snapshot
+from komparu._copy import copy_tree_verified, copy_verified
+from komparu._watch import watch_dir
+from komparu._journal import ChangeJournal
+from komparu._sync import apply_sync_plan, sync_dir, sync_plan
+from komparu._oci import compare_image_dir, compare_images
+from komparu._diskimage import compare_disk_image_dir, compare_disk_images
+from komparu._fssnap import compare_fs_snapshots
+from komparu._agent import compare_dir_agent
+from komparu._server import ComparisonServer
+from komparu._remote import chunk_diff_ssh, compare_dir_ssh
+from komparu._s3 import compare_dir_s3
+from komparu._store import compare_dir_store
+from komparu._git import compare_dir_git, compare_git_revs
+from komparu._signing import sign_manifest, verify_signature
+from komparu._manifest import (
+    compare_manifests,
+    format_manifest_line,
+    hash_tree,
+    parse_manifest,
+    parse_sfv,
+    read_manifest,
+    tree_digest,
+    update_manifest,
+    verify_manifest,
+)
+from komparu._metrics import MetricsSink, PrometheusMetrics
+from komparu._logsink import LogSink
+from komparu._audit import AuditLog, check_audit_log
+from komparu._split import split_parts
+from komparu._salvage import compare_salvage
+from komparu._vss import shadow_copy
+from komparu._cancel import CancelToken
+from komparu._dircache import DirCache
+from komparu._config import configure, get_config, reset_config, set_buffer_allocator
 from komparu._api import (
     compare,
+    compare_streams,
+    verify_zero,
+    compare_bytes,
+    first_diff,
+    count_diff_blocks,
+    similarity,
     compare_dir,
+    iter_dir,
+    plan_dir,
     compare_archive,
+    compare_dir_archive,
     compare_all,
     compare_many,
     compare_dir_urls,
 )
+from komparu._service import Comparator
 
 __all__ = [
     "__version__",
     "compare",
+    "compare_streams",
+    "verify_zero",
+    "split_parts",
+    "compare_salvage",
+    "shadow_copy",
+    "compare_bytes",
+    "first_diff",
+    "count_diff_blocks",
+    "similarity",
+    "fuzzy_hash",
+    "fuzzy_score",
+    "chunk_diff",
+    "chunk_diff_ssh",
+    "delta_size",
+    "make_patch",
+    "apply_patch",
+    "detect_storage",
+    "tune_for_storage",
+    "path_key",
+    "tree_key",
     "compare_dir",
+    "iter_dir",
+    "plan_dir",
     "compare_archive",
+    "compare_dir_archive",
+    "compare_dir_metadata",
+    "metadata_diff",
+    "hash_tree",
+    "compare_manifests",
+    "update_manifest",
+    "sign_manifest",
+    "verify_signature",
+    "tree_digest",
+    "format_manifest_line",
+    "parse_manifest",
+    "parse_sfv",
+    "read_manifest",
+    "verify_manifest",
+    "check_audit_log",
     "compare_all",
     "compare_many",
+    "find_duplicates",
+    "deduplicate",
+    "summarize_dirs",
+    "copy_verified",
+    "copy_tree_verified",
+    "snapshot_tree",
+    "write_snapshot",
+    "read_snapshot",
+    "compare_snapshot",
+    "watch_dir",
+    "ChangeJournal",
+    "sync_plan",
+    "apply_sync_plan",
+    "sync_dir",
     "compare_dir_urls",
+    "compare_dir_ssh",
+    "compare_dir_agent",
+    "compare_images",
+    "compare_image_dir",
+    "compare_disk_images",
+    "compare_disk_image_dir",
+    "compare_fs_snapshots",
+    "compare_dir_s3",
+    "compare_dir_store",
+    "compare_dir_git",
+    "compare_git_revs",
     "configure",
     "get_config",
     "reset_config",
+    "set_buffer_allocator",
     "Source",
+    "CompareOptions",
+    "Comparator",
+    "ComparisonServer",
+    "RetryPolicy",
+    "LockPolicy",
+    "SeverityPolicy",
+    "CompareVisitor",
+    "SummaryVisitor",
+    "ContentComparator",
+    "TextComparator",
+    "JsonComparator",
+    "YamlComparator",
+    "XmlComparator",
+    "GzipComparator",
+    "DecompressComparator",
+    "TarComparator",
+    "ZipComparator",
+    "DebComparator",
+    "ImageComparator",
+    "AudioComparator",
+    "PdfComparator",
+    "SqliteComparator",
+    "ParquetComparator",
+    "ExecutableComparator",
+    "SniffingComparator",
+    "FilterComparator",
+    "PathFilter",
+    "RsyncFilter",
+    "MetricsSink",
+    "PrometheusMetrics",
+    "LogSink",
+    "AuditLog",
+    "CancelToken",
+    "DirCache",
     "DirResult",
     "CompareResult",
+    "ChunkDiff",
+    "Manifest",
+    "DuplicateGroup",
+    "DedupResult",
+    "DirSummary",
+    "Snapshot",
+    "SnapshotEntry",
+    "DiffRegion",
+    "RegionKind",
+    "SalvageResult",
+    "UnreadableRange",
     "DiffReason",
+    "DiffEntry",
+    "DriftEvent",
+    "DriftKind",
+    "EntryKind",
+    "Severity",
+    "Progress",
+    "PlannedPair",
+    "DirPlan",
+    "SyncAction",
+    "StorageKind",
+    "SyncPlan",
+    "SyncStep",
     "KomparuError",
     "SourceNotFoundError",
     "SourceReadError",
+    "SourcePermissionError",
+    "SourceVanishedError",
+    "UnsupportedSourceError",
     "ArchiveError",
     "ArchiveBombError",
     "ConfigError",
     "ComparisonTimeoutError",
+    "ComparisonCancelledError",
+    "QuotaReachedError",
+    "SignatureError",
+    "CopyVerifyError",
+    "PatchError",
 ]
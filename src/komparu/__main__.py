@@ -0,0 +1,9 @@
+"""Entry point for ``python -m komparu``."""
+
+from __future__ import annotations
+
+import sys
+
+from komparu._cli import main
+
+sys.exit(main())
@@ -0,0 +1,287 @@
+"""A komparu agent: trees on another machine hashed where they are, over a socket.
+
+``komparu agent`` serves one session on standard input and output (for
+inetd, systemd socket activation or ``ssh host komparu agent``), or many
+over TCP with ``--listen``. Requests and answers are lines of JSON::
+
+    {"op": "hello", "token": "..."}
+        -> {"ok": true, "protocol": 1, "version": "..."}
+    {"op": "hash", "path": "/srv/www", "algorithm": "sha256",
+     "follow_symlinks": true, "ignore": [], "max_workers": 0}
+        -> {"file": "index.html", "digest": "..."} per file,
+           then {"ok": true, "files": 2}
+
+and a failed request is answered ``{"ok": false, "error": "...", "errno":
+2}``. An agent given a token closes any session whose hello does not
+carry it; one given roots hashes nothing outside them. Paths are sent as
+they are on disk, undecodable bytes as lone surrogates.
+"""
+
+from __future__ import annotations
+
+import contextlib
+import errno
+import hmac
+import json
+import os
+import socket
+import socketserver
+import urllib.parse
+from collections.abc import Iterator
+from typing import Any, BinaryIO
+
+from komparu._filter import PathFilter
+from komparu._hashing import validate_algorithm
+from komparu._manifest import hash_tree, verify_manifest
+from komparu._types import (
+    PATH_ERRORS,
+    DirResult,
+    SourceNotFoundError,
+    SourcePermissionError,
+    SourceReadError,
+)
+from komparu._validate import validate_max_workers, validate_path
+
+PROTOCOL = 1
+DEFAULT_PORT = 7457
+# Longest request line an agent reads
+_LINE_LIMIT = 1024 * 1024
+
+
+def _send(wfile: BinaryIO, message: dict[str, Any]) -> None:
+    wfile.write(json.dumps(message).encode("ascii") + b"\n")
+
+
+def _error(code: int, message: str) -> dict[str, Any]:
+    return {"ok": False, "error": message, "errno": code}
+
+
+def resolve_under(path: str, roots: list[str] | None) -> str:
+    """*path* on this host; relative to the first root, and under one, when there are roots.
+
+    *roots* must be real paths already.
+    """
+    if not roots:
+        return path or "."
+    real = os.path.realpath(os.path.join(roots[0], path))
+    if not any(os.path.commonpath([real, root]) == root for root in roots):
+        raise PermissionError(errno.EACCES, "outside the served roots", path)
+    return real
+
+
+def _hash(request: dict[str, Any], roots: list[str] | None, wfile: BinaryIO) -> None:
+    path = request.get("path", "")
+    ignore = request.get("ignore") or []
+    if not isinstance(path, str) or not (isinstance(ignore, list)
+                                         and all(isinstance(p, str) for p in ignore)):
+        raise ValueError("malformed hash request")
+    files = 0
+    for name, digest in hash_tree(resolve_under(path, roots),
+                                  algorithm=str(request.get("algorithm", "sha256")),
+                                  follow_symlinks=bool(request.get("follow_symlinks", True)),
+                                  ignore=ignore,
+                                  max_workers=int(request.get("max_workers", 0))):
+        _send(wfile, {"file": name, "digest": digest})
+        files += 1
+    _send(wfile, {"ok": True, "files": files})
+
+
+def serve_session(rfile: BinaryIO, wfile: BinaryIO, *, roots: list[str] | None = None,
+                  token: str | None = None) -> None:
+    """Answer requests from *rfile* on *wfile* until the client hangs up.
+
+    :param rfile: Binary stream requests are read from.
+    :param wfile: Binary stream answers are written to.
+    :param roots: Directories the agent may hash under; relative request
+        paths are taken from the first. None allows any path.
+    :param token: Secret the session's hello must carry.
+    """
+    from komparu import __version__
+
+    roots = [os.path.realpath(root) for root in roots] if roots else None
+    greeted = token is None
+    while line := rfile.readline(_LINE_LIMIT):
+        try:
+            request = json.loads(line)
+            if not isinstance(request, dict):
+                raise ValueError("a request is a JSON object")
+        except ValueError:
+            _send(wfile, _error(errno.EINVAL, "malformed request"))
+            break
+        op = request.get("op")
+        if op == "hello":
+            given = request.get("token")
+            if token is not None and not (isinstance(given, str) and hmac.compare_digest(
+                    given.encode("utf-8", "surrogateescape"),
+                    token.encode("utf-8", "surrogateescape"))):
+                _send(wfile, _error(errno.EACCES, "wrong agent token"))
+                break
+            greeted = True
+            _send(wfile, {"ok": True, "protocol": PROTOCOL, "version": __version__})
+        elif not greeted:
+            _send(wfile, _error(errno.EACCES, "the agent needs a token"))
+            break
+        elif op == "hash":
+            try:
+                _hash(request, roots, wfile)
+            except OSError as exc:
+                _send(wfile, _error(exc.errno or errno.EIO, exc.strerror or str(exc)))
+            except (ValueError, ImportError) as exc:
+                _send(wfile, _error(errno.EINVAL, str(exc)))
+        else:
+            _send(wfile, _error(errno.EINVAL, f"unknown request {op!r}"))
+        wfile.flush()
+    wfile.flush()
+
+
+class _Handler(socketserver.StreamRequestHandler):
+    server: AgentServer
+
+    def handle(self) -> None:
+        with contextlib.suppress(BrokenPipeError, ConnectionResetError):
+            serve_session(self.rfile, self.wfile, roots=self.server.roots,
+                          token=self.server.token)
+
+
+class AgentServer(socketserver.ThreadingTCPServer):
+    """A TCP server running :func:`serve_session` for each connection, on its own thread.
+
+    :param address: ``(host, port)`` to listen on; port 0 picks a free one.
+    :param roots: Directories the agent may hash under, at least one.
+    :param token: Secret every session's hello must carry.
+    """
+
+    daemon_threads = True
+    allow_reuse_address = True
+
+    def __init__(self, address: tuple[str, int], *, roots: list[str],
+                 token: str | None = None) -> None:
+        if not roots:
+            raise ValueError("an agent listening on a socket needs at least one root")
+        self.roots = roots
+        self.token = token
+        if ":" in address[0]:
+            self.address_family = socket.AF_INET6
+        super().__init__(address, _Handler)
+
+
+def parse_agent_url(url: str) -> tuple[str, int, str]:
+    """``(host, port, path)`` of ``komparu://host[:port]/path``.
+
+    An empty path is the agent's first root.
+
+    :raises ValueError: If *url* is not one.
+    """
+    parts = urllib.parse.urlsplit(url)
+    if parts.scheme != "komparu" or not parts.hostname:
+        raise ValueError(f"expected komparu://host[:port]/path, got {url!r}")
+    return (parts.hostname, parts.port or DEFAULT_PORT,
+            urllib.parse.unquote(parts.path, errors=PATH_ERRORS))
+
+
+def _answers(rfile: BinaryIO, url: str) -> Iterator[dict[str, Any]]:
+    while True:
+        line = rfile.readline()
+        if not line:
+            raise SourceReadError(errno.EIO, "agent closed the connection", url)
+        try:
+            answer = json.loads(line)
+        except ValueError:
+            raise SourceReadError(errno.EIO, "malformed answer from the agent", url) from None
+        if answer.get("ok") is False:
+            code, message = answer.get("errno") or errno.EIO, str(answer.get("error"))
+            if code == errno.ENOENT:
+                raise SourceNotFoundError(code, message, url)
+            if code == errno.EACCES:
+                raise SourcePermissionError(code, message, url)
+            raise SourceReadError(code, message, url)
+        yield answer
+
+
+def agent_digests(
+    url: str,
+    *,
+    algorithm: str = "sha256",
+    follow_symlinks: bool = True,
+    ignore: list[str] | None = None,
+    max_workers: int = 0,
+    token: str | None = None,
+    timeout: float | None = 60.0,
+) -> dict[str, str]:
+    """Digest per file of the tree a ``komparu://`` URL names, from its agent."""
+    host, port, path = parse_agent_url(url)
+    if token is None:
+        token = os.environ.get("KOMPARU_AGENT_TOKEN")
+    try:
+        with socket.create_connection((host, port), timeout=timeout) as sock, \
+                sock.makefile("rb") as rfile, sock.makefile("wb") as wfile:
+            _send(wfile, {"op": "hello", **({"token": token} if token is not None else {})})
+            _send(wfile, {"op": "hash", "path": path, "algorithm": algorithm,
+                          "follow_symlinks": follow_symlinks, "ignore": ignore or [],
+                          "max_workers": max_workers})
+            wfile.flush()
+            answers = _answers(rfile, url)
+            next(answers)  # hello
+            digests = {}
+            for answer in answers:
+                if "file" not in answer:
+                    break
+                digests[answer["file"]] = answer["digest"]
+    except SourceReadError:
+        raise
+    except OSError as exc:
+        raise SourceReadError(exc.errno or errno.EIO,
+                              f"cannot reach the agent: {exc.strerror or exc}", url) from None
+    return digests
+
+
+def compare_dir_agent(
+    directory: str,
+    url: str,
+    *,
+    algorithm: str = "sha256",
+    follow_symlinks: bool = True,
+    ignore: list[str] | None = None,
+    path_filter: PathFilter | None = None,
+    max_workers: int = 0,
+    token: str | None = None,
+) -> DirResult:
+    """Compare a local directory with one a komparu agent serves.
+
+    The agent (``komparu agent --listen``) walks and hashes its tree
+    where it is and sends back only the digests, which the local tree is
+    then checked against as by :func:`komparu.verify_manifest`. Unlike
+    :func:`komparu.compare_dir_ssh` nothing is started per comparison, so
+    the remote host needs no SSH access for the caller.
+
+    The local tree is the left side and the agent's the right.
+
+    :param directory: Local directory path.
+    :param url: ``komparu://host[:port]/path``; the port defaults to
+        7457 and an empty path is the agent's first root.
+    :param algorithm: Hash algorithm both sides use; the agent must
+        support it.
+    :param follow_symlinks: Follow symbolic links during traversal, on
+        both hosts.
+    :param ignore: Glob patterns to exclude (matched per path component),
+        on both hosts.
+    :param path_filter: PathFilter deciding per entry whether it is
+        compared; applied locally, to both trees.
+    :param max_workers: Thread pool size (0=auto, 1=sequential), on both
+        hosts.
+    :param token: Secret the agent was started with; by default the
+        ``KOMPARU_AGENT_TOKEN`` environment variable.
+    :raises ValueError: If *url* is not a ``komparu://`` URL.
+    :raises SourceNotFoundError: If the agent has no such directory.
+    :raises SourcePermissionError: If the agent refuses the token or the
+        path, or a directory cannot be listed.
+    :raises SourceReadError: If the agent cannot be reached or fails.
+    """
+    validate_path(directory, "directory")
+    validate_algorithm(algorithm)
+    validate_max_workers(max_workers)
+    digests = agent_digests(url, algorithm=algorithm, follow_symlinks=follow_symlinks,
+                            ignore=ignore, max_workers=max_workers, token=token)
+    return verify_manifest(directory, digests, algorithm=algorithm,
+                           follow_symlinks=follow_symlinks, ignore=ignore,
+                           path_filter=path_filter, max_workers=max_workers)
@@ -2,21 +2,92 @@
 
 from __future__ import annotations
 
-from komparu._types import Source, CompareResult
+import errno
+import os
+import select
+from collections.abc import Buffer, Callable, Iterator, Mapping, Sequence
+from typing import Any, BinaryIO
+
+from komparu._types import (
+    Source,
+    CompareResult,
+    DiffEntry,
+    DirPlan,
+    PlannedPair,
+    Progress,
+    QuotaReachedError,
+)
+from komparu._options import (
+    DIR_OPTION_DEFAULTS,
+    FILE_OPTION_DEFAULTS,
+    CompareOptions,
+    LockPolicy,
+    RetryPolicy,
+    lock_kwargs,
+    reject_overrides,
+    retry_kwargs,
+)
 from komparu._config import get_config
 from komparu._core import compare as _compare_c
 from komparu._core import compare_dir as _compare_dir_c
+from komparu._core import plan_dir as _plan_dir_c
 from komparu._core import compare_archive as _compare_archive_c
+from komparu._core import compare_dir_archive as _compare_dir_archive_c
 from komparu._core import compare_dir_urls as _compare_dir_urls_c
-from komparu._validate import validate_path, validate_chunk_size, validate_timeout, validate_max_workers
-from komparu._helpers import resolve_headers, build_dir_result, filter_dir_result
+from komparu._core import compare_streams as _compare_streams_c
+from komparu._core import compare_fds as _compare_fds_c
+from komparu._core import compare_parts as _compare_parts_c
+from komparu._core import verify_zero as _verify_zero_c
+from komparu._core import compare_buffers as _compare_buffers_c
+from komparu._core import first_diff as _first_diff_c
+from komparu._core import diff_blocks as _diff_blocks_c
+from komparu._core import async_dir_stream_start as _dir_stream_start_c
+from komparu._core import async_dir_stream_poll as _dir_stream_poll_c
+from komparu._validate import (
+    validate_path,
+    validate_dir,
+    validate_chunk_size,
+    validate_timeout,
+    validate_max_workers,
+    validate_max_pending,
+)
+from komparu._helpers import (
+    DEFAULT_MAX_PENDING,
+    resolve_headers,
+    dir_handles,
+    build_dir_result,
+    filter_dir_result,
+    is_url,
+    iter_diff_entries,
+    make_annotate,
+    stat_entry,
+)
+from komparu._metrics import MetricsSink, MetricsVisitor
+from komparu._visitor import CompareVisitor, ProgressVisitor, VisitorDriver
+from komparu._cancel import CancelToken, cancel_handle, child_token
+from komparu._dircache import DirCache, cache_handle
+from komparu._comparator import ContentComparator, make_dispatch
+from komparu._filter import PathFilter, make_exclude
+from komparu._prefilter import group_identical
+from komparu._errors import error_scope
 
 from komparu._types import DirResult  # noqa: F401 — re-export for type annotations
 
 
+def _parts(source: str | Source | Sequence[str], name: str) -> list[str]:
+    if isinstance(source, Source) or (isinstance(source, str) and is_url(source)):
+        raise ValueError("split parts are compared with local files only")
+    parts = [source] if isinstance(source, (str, os.PathLike)) else list(source)
+    if not parts:
+        raise ValueError(f"{name} has no parts")
+    for part in parts:
+        validate_path(part, name)
+    return parts
+
+
 def compare(
-    source_a: str | Source,
-    source_b: str | Source,
+    source_a: str | Source | int | Sequence[str],
+    source_b: str | Source | int | Sequence[str],
     *,
     chunk_size: int = 65536,
     size_precheck: bool = True,
@@ -26,11 +97,16 @@ def compare(
     follow_redirects: bool = True,
     verify_ssl: bool = True,
     proxy: str | None = None,
+    options: CompareOptions | None = None,
+    cancel: CancelToken | None = None,
 ) -> bool:
     """Compare two sources byte-by-byte.
 
-    :param source_a: File path, URL, or Source object.
-    :param source_b: File path, URL, or Source object.
+    :param source_a: File path, URL, Source object, open file descriptor,
+        or a list of local files read back to back as one (split volumes,
+        see :func:`komparu.split_parts`).
+    :param source_b: The same (descriptors are compared only with
+        descriptors, lists only with local files or lists).
     :param chunk_size: Chunk size in bytes.
     :param size_precheck: Compare sizes before content.
     :param quick_check: Sample key offsets before full scan.
@@ -39,8 +115,44 @@ def compare(
     :param follow_redirects: Follow HTTP redirects.
     :param verify_ssl: Verify SSL certificates.
     :param proxy: Proxy URL (e.g. http://host:port, socks5://host:port).
+    :param options: CompareOptions bundle; replaces the keyword arguments above,
+        which raise TypeError if also passed.
+    :param cancel: CancelToken that aborts the comparison when set.
     :returns: True if sources are byte-identical.
+    :raises SourceReadError: If a source cannot be opened or read; the
+        subclass (SourceNotFoundError, SourcePermissionError, ...) names the cause.
+    :raises ComparisonCancelledError: If *cancel* was set while running.
     """
+    if options is not None:
+        reject_overrides("compare", locals(), FILE_OPTION_DEFAULTS)
+        return compare(source_a, source_b, cancel=cancel, **options.file_kwargs())
+
+    if isinstance(source_a, int) or isinstance(source_b, int):
+        if not (isinstance(source_a, int) and isinstance(source_b, int)):
+            raise TypeError("compare() takes two file descriptors or two sources, not a mix")
+        validate_chunk_size(chunk_size)
+        # No path is resolved; both descriptors stay open and keep their position
+        with error_scope(cancel):
+            return _compare_fds_c(
+                source_a, source_b,
+                chunk_size=chunk_size,
+                size_precheck=size_precheck,
+                quick_check=quick_check,
+                cancel=cancel_handle(cancel),
+            )
+
+    if isinstance(source_a, (list, tuple)) or isinstance(source_b, (list, tuple)):
+        parts_a, parts_b = _parts(source_a, "source_a"), _parts(source_b, "source_b")
+        validate_chunk_size(chunk_size)
+        with error_scope(cancel):
+            return _compare_parts_c(
+                parts_a, parts_b,
+                chunk_size=chunk_size,
+                size_precheck=size_precheck,
+                quick_check=quick_check,
+                cancel=cancel_handle(cancel),
+            )
+
     validate_path(source_a, "source_a")
     validate_path(source_b, "source_b")
     validate_chunk_size(chunk_size)
@@ -58,60 +170,499 @@ def compare(
 
     p = proxy if proxy is not None else cfg.proxy
 
-    return _compare_c(
-        path_a, path_b,
-        chunk_size=chunk_size,
-        size_precheck=size_precheck,
-        quick_check=quick_check,
-        headers=h if h else None,
-        timeout=timeout,
-        follow_redirects=follow_redirects,
-        verify_ssl=verify_ssl,
-        allow_private=cfg.allow_private_redirects,
-        proxy=p,
-    )
+    with error_scope(cancel):
+        return _compare_c(
+            path_a, path_b,
+            chunk_size=chunk_size,
+            size_precheck=size_precheck,
+            quick_check=quick_check,
+            headers=h if h else None,
+            timeout=timeout,
+            follow_redirects=follow_redirects,
+            verify_ssl=verify_ssl,
+            allow_private=cfg.allow_private_redirects,
+            proxy=p,
+            cancel=cancel_handle(cancel),
+        )
+
+
+def compare_streams(
+    stream_a: BinaryIO,
+    stream_b: BinaryIO,
+    *,
+    chunk_size: int = 65536,
+    size_precheck: bool = True,
+    quick_check: bool = True,
+    options: CompareOptions | None = None,
+    cancel: CancelToken | None = None,
+) -> bool:
+    """Compare two binary file-like objects byte-by-byte.
+
+    Runs the same C engine as :func:`compare`. Streams need ``read()`` or
+    ``readinto()``; if they are also seekable, size precheck applies and
+    (when positioned at 0) quick check samples key offsets first.
+    Comparison starts at each stream's current position.
+
+    :param stream_a: First binary stream (e.g. ``io.BytesIO``, open file).
+    :param stream_b: Second binary stream.
+    :param chunk_size: Chunk size in bytes.
+    :param size_precheck: Compare remaining sizes before content.
+    :param quick_check: Sample key offsets before full scan (seekable only).
+    :param options: CompareOptions bundle; replaces the keyword arguments above,
+        which raise TypeError if also passed. Its HTTP settings do not apply.
+    :param cancel: CancelToken that aborts the comparison when set.
+    :returns: True if streams are byte-identical.
+    :raises ComparisonCancelledError: If *cancel* was set while running.
+    """
+    if options is not None:
+        reject_overrides("compare_streams", locals(), FILE_OPTION_DEFAULTS)
+        chunk_size = options.chunk_size
+        size_precheck = options.size_precheck
+        quick_check = options.quick_check
+    validate_chunk_size(chunk_size)
+
+    try:
+        return _compare_streams_c(
+            stream_a, stream_b,
+            chunk_size=chunk_size,
+            size_precheck=size_precheck,
+            quick_check=quick_check,
+            cancel=cancel_handle(cancel),
+        )
+    except OSError as e:
+        # Only the engine stops with ECANCELED; other errors from a stream
+        # are the caller's and propagate unchanged
+        if cancel is None or e.errno != errno.ECANCELED:
+            raise
+        with error_scope(cancel):
+            raise
+
+
+def verify_zero(
+    path: str,
+    *,
+    chunk_size: int = 65536,
+    quick_check: bool = True,
+    cancel: CancelToken | None = None,
+) -> bool:
+    """Check that a file or block device holds nothing but zero bytes.
+
+    Runs the engine of :func:`compare` against a source of zeros of the
+    same size: holes (``SEEK_DATA``/``SEEK_HOLE``) are skipped without
+    reading, and the first non-zero byte ends the scan. For confirming
+    that a disk was wiped or that a pre-allocated image is still blank.
+
+    :param path: Regular file, or block device (Unix) such as ``/dev/sdb``.
+    :param chunk_size: Chunk size in bytes.
+    :param quick_check: Sample key offsets before the full scan.
+    :param cancel: CancelToken that aborts the scan when set.
+    :returns: True if every byte is zero (also for an empty file).
+    :raises SourceReadError: If *path* cannot be opened or read, or is
+        neither a regular file nor a block device.
+    :raises ComparisonCancelledError: If *cancel* was set while running.
+    """
+    validate_path(path, "path")
+    validate_chunk_size(chunk_size)
+    with error_scope(cancel):
+        return _verify_zero_c(path, chunk_size=chunk_size, quick_check=quick_check,
+                              cancel=cancel_handle(cancel))
+
+
+def compare_bytes(data_a: Buffer, data_b: Buffer) -> bool:
+    """Check whether two in-memory buffers are byte-identical.
+
+    :param data_a: First bytes-like object (bytes, bytearray, memoryview, mmap, ...).
+    :param data_b: Second bytes-like object.
+    :returns: True if both buffers have the same length and content.
+    """
+    return _compare_buffers_c(data_a, data_b)
+
+
+def first_diff(data_a: Buffer, data_b: Buffer) -> int | None:
+    """Find the offset of the first byte at which two buffers differ.
+
+    The GIL is released while scanning.
+
+    :param data_a: First bytes-like object.
+    :param data_b: Second bytes-like object.
+    :returns: Offset of the first differing byte, the shorter length if one
+        buffer is a prefix of the other, or None if they are identical.
+    """
+    offset = _first_diff_c(data_a, data_b)
+    return None if offset < 0 else offset
+
+
+def count_diff_blocks(data_a: Buffer, data_b: Buffer, *, block_size: int = 4096) -> int:
+    """Count fixed-size blocks whose contents differ between two buffers.
+
+    Blocks are aligned at multiples of *block_size* over the longer buffer;
+    blocks past the end of the shorter one count as differing. The GIL is
+    released while scanning.
+
+    :param data_a: First bytes-like object.
+    :param data_b: Second bytes-like object.
+    :param block_size: Block size in bytes.
+    :returns: Number of differing blocks (0 if identical).
+    """
+    if block_size <= 0:
+        raise ValueError("block_size must be positive")
+    return _diff_blocks_c(data_a, data_b, block_size)
+
+
+def similarity(path_a: str, path_b: str, *, block_size: int = 4096) -> float:
+    """Fraction of fixed-size blocks that match between two files.
+
+    Blocks are aligned as in :func:`count_diff_blocks`, over the longer
+    file; the files are streamed, not loaded. Useful for ranking differing
+    files by how far they diverged.
+
+    :param path_a: First file path.
+    :param path_b: Second file path.
+    :param block_size: Block size in bytes.
+    :returns: Matching blocks over total blocks, from 0.0 to 1.0 (1.0 for
+        two empty files).
+    """
+    if block_size <= 0:
+        raise ValueError("block_size must be positive")
+    validate_path(path_a, "path_a")
+    validate_path(path_b, "path_b")
+    read_size = block_size * max(1, 65536 // block_size)
+    total = differing = 0
+    with open(path_a, "rb") as fa, open(path_b, "rb") as fb:
+        while True:
+            chunk_a, chunk_b = fa.read(read_size), fb.read(read_size)
+            if not chunk_a and not chunk_b:
+                break
+            total += -(-max(len(chunk_a), len(chunk_b)) // block_size)
+            differing += _diff_blocks_c(chunk_a, chunk_b, block_size)
+    return 1.0 if total == 0 else (total - differing) / total
 
 
 def compare_dir(
-    dir_a: str,
-    dir_b: str,
+    dir_a: str | int,
+    dir_b: str | int,
     *,
     chunk_size: int = 65536,
     size_precheck: bool = True,
     quick_check: bool = True,
     follow_symlinks: bool = True,
     max_workers: int = 0,
+    retry: RetryPolicy | None = None,
+    lock: LockPolicy | None = None,
     ignore: list[str] | None = None,
+    path_filter: PathFilter | None = None,
+    fail_fast: bool = False,
+    options: CompareOptions | None = None,
+    visitor: CompareVisitor | None = None,
+    progress: Callable[[Progress], None] | None = None,
+    metrics: MetricsSink | None = None,
+    comparators: Mapping[str, ContentComparator] | None = None,
+    cancel: CancelToken | None = None,
+    cache: DirCache | None = None,
+    prescan: bool = False,
 ) -> DirResult:
     """Compare two directories recursively.
 
-    :param dir_a: Path to first directory.
-    :param dir_b: Path to second directory.
+    :param dir_a: Path to first directory, or an open directory descriptor.
+    :param dir_b: Path to second directory, or an open directory descriptor.
     :param chunk_size: Chunk size for file comparison.
     :param size_precheck: Compare file sizes before content.
     :param quick_check: Sample key offsets before full scan.
     :param follow_symlinks: Follow symbolic links during traversal.
     :param max_workers: Thread pool size (0=auto, 1=sequential).
+    :param retry: RetryPolicy for transient I/O errors (EAGAIN, ESTALE, ...).
+    :param lock: LockPolicy reading files under shared advisory locks.
     :param ignore: Glob patterns to exclude (matched per path component).
+    :param path_filter: PathFilter deciding per entry, with metadata, whether
+        it is reported.
+    :param fail_fast: Stop at the first difference and return what was found
+        by then; ``equal`` is still exact, the other fields may be partial.
+    :param options: CompareOptions bundle; replaces the keyword arguments above,
+        which raise TypeError if also passed.
+    :param visitor: CompareVisitor notified in this thread as the comparison runs.
+    :param progress: Called in this thread with a Progress after each started or
+        resolved entry.
+    :param metrics: MetricsSink receiving counters and phase timings.
+    :param comparators: ContentComparator per glob pattern (matched against
+        the relative path), consulted for files whose bytes differ.
+    :param cancel: CancelToken that aborts the comparison when set.
+    :param cache: DirCache shared across calls; pairs found equal before and
+        unchanged since are not re-read.
+    :param prescan: Walk both trees and sum file sizes first, so every
+        Progress carries totals and an ETA; costs one extra traversal.
     :returns: DirResult with equal, diff, only_left, only_right.
+    :raises ValueError: If *prescan* is set without *progress*.
+    :raises SourceReadError: If a directory cannot be walked.
+    :raises ComparisonCancelledError: If *cancel* was set while running.
     """
-    validate_path(dir_a, "dir_a")
-    validate_path(dir_b, "dir_b")
+    if options is not None:
+        reject_overrides("compare_dir", locals(), {**DIR_OPTION_DEFAULTS, "fail_fast": False})
+        return compare_dir(dir_a, dir_b, visitor=visitor, progress=progress,
+                           metrics=metrics, fail_fast=options.fail_fast,
+                           comparators=comparators,
+                           cancel=cancel, cache=cache, prescan=prescan,
+                           **options.dir_kwargs())
+
+    validate_dir(dir_a, "dir_a")
+    validate_dir(dir_b, "dir_b")
     validate_chunk_size(chunk_size)
     validate_max_workers(max_workers)
 
-    raw = _compare_dir_c(
-        dir_a, dir_b,
+    exclude = make_exclude(ignore, path_filter, dir_a, dir_b, follow_symlinks)
+    dispatch = make_dispatch(dir_a, dir_b, comparators)
+    if prescan and progress is None:
+        raise ValueError("prescan needs a progress callback")
+    if progress is not None:
+        totals = None
+        if prescan:
+            totals = prescan_totals(dir_a, dir_b, size_precheck=size_precheck,
+                                    follow_symlinks=follow_symlinks, ignore=ignore,
+                                    path_filter=path_filter, cancel=cancel)
+        visitor = ProgressVisitor(progress, dir_a, visitor, totals)
+    meter = None
+    if metrics is not None:
+        meter = visitor = MetricsVisitor(metrics, dir_a, visitor)
+
+    if visitor is not None or fail_fast:
+        # Work runs in the C async pool; this thread only waits on the
+        # notification fd and dispatches callbacks. Fail-fast stops it
+        # through its own token so the caller's stays unset.
+        stop = child_token(cancel) if fail_fast else cancel
+        path_a, path_b, handles = dir_handles(dir_a, dir_b)
+        fd, task = _dir_stream_start_c(
+            path_a, path_b,
+            chunk_size=chunk_size,
+            size_precheck=size_precheck,
+            quick_check=quick_check,
+            follow_symlinks=follow_symlinks,
+            max_workers=max_workers,
+            progress=True,
+            max_pending=DEFAULT_MAX_PENDING,
+            cancel=cancel_handle(stop),
+            cache=cache_handle(cache),
+            **retry_kwargs(retry),
+            **lock_kwargs(lock),
+            **handles,
+        )
+        annotate = make_annotate(dir_a, dir_b, follow_symlinks,
+                                 dispatch.recheck if dispatch else None)
+        driver = VisitorDriver(visitor or CompareVisitor(), exclude, annotate)
+        try:
+            finished = False
+            while not finished:
+                select.select([fd], [], [])
+                with error_scope(cancel):
+                    events, finished = _dir_stream_poll_c(task)
+                driver.feed(events)
+                if fail_fast and not driver.result().equal:
+                    stop.cancel()
+                    break
+        except QuotaReachedError as e:
+            e.partial = driver.result()
+            raise
+        finally:
+            if meter is not None:
+                meter.close()
+        return driver.result()
+
+    path_a, path_b, handles = dir_handles(dir_a, dir_b)
+    with error_scope(cancel):
+        raw = _compare_dir_c(
+            path_a, path_b,
+            chunk_size=chunk_size,
+            size_precheck=size_precheck,
+            quick_check=quick_check,
+            follow_symlinks=follow_symlinks,
+            max_workers=max_workers,
+            cancel=cancel_handle(cancel),
+            cache=cache_handle(cache),
+            **retry_kwargs(retry),
+            **lock_kwargs(lock),
+            **handles,
+        )
+    result = build_dir_result(raw)
+    result = filter_dir_result(result, exclude)
+    if dispatch is not None:
+        result = dispatch.apply(result)
+    return result
+
+
+def iter_dir(
+    dir_a: str | int,
+    dir_b: str | int,
+    *,
+    chunk_size: int = 65536,
+    size_precheck: bool = True,
+    quick_check: bool = True,
+    follow_symlinks: bool = True,
+    max_workers: int = 0,
+    retry: RetryPolicy | None = None,
+    lock: LockPolicy | None = None,
+    ignore: list[str] | None = None,
+    path_filter: PathFilter | None = None,
+    include_equal: bool = False,
+    max_pending: int = DEFAULT_MAX_PENDING,
+    options: CompareOptions | None = None,
+    comparators: Mapping[str, ContentComparator] | None = None,
+    cancel: CancelToken | None = None,
+    cache: DirCache | None = None,
+) -> Iterator[DiffEntry]:
+    """Compare two directories, yielding entries as they are resolved.
+
+    Lazy counterpart of :func:`compare_dir`: the comparison runs in C
+    threads and each entry is yielded as soon as it is settled, in
+    completion order. No DirResult is accumulated, so callers can act on
+    the first difference immediately and stop early.
+
+    :param dir_a: Path to first directory, or an open directory descriptor.
+    :param dir_b: Path to second directory, or an open directory descriptor.
+    :param retry: RetryPolicy for transient I/O errors (EAGAIN, ESTALE, ...).
+    :param lock: LockPolicy reading files under shared advisory locks.
+    :param ignore: Glob patterns to exclude (matched per path component).
+    :param path_filter: PathFilter deciding per entry, with metadata, whether
+        it is reported.
+    :param include_equal: Also yield identical files (``EntryKind.EQUAL``).
+    :param max_pending: Entries queued ahead of the consumer before the C
+        workers pause (0 = unbounded), so a slow loop body throttles the
+        comparison instead of buffering results in memory.
+    :param options: CompareOptions bundle; replaces the keyword arguments above,
+        which raise TypeError if also passed.
+    :param comparators: ContentComparator per glob pattern; entries they
+        declare equal are yielded as ``EntryKind.EQUAL``.
+    :param cancel: CancelToken that aborts the comparison when set.
+    :param cache: DirCache shared across calls; pairs found equal before and
+        unchanged since are not re-read.
+    :returns: Iterator of DiffEntry.
+    :raises ComparisonCancelledError: If *cancel* was set while running.
+    """
+    if options is not None:
+        reject_overrides("iter_dir", locals(), DIR_OPTION_DEFAULTS)
+        yield from iter_dir(dir_a, dir_b, include_equal=include_equal,
+                            max_pending=max_pending, comparators=comparators,
+                            cancel=cancel, cache=cache, **options.dir_kwargs())
+        return
+
+    validate_dir(dir_a, "dir_a")
+    validate_dir(dir_b, "dir_b")
+    validate_chunk_size(chunk_size)
+    validate_max_workers(max_workers)
+    validate_max_pending(max_pending)
+    exclude = make_exclude(ignore, path_filter, dir_a, dir_b, follow_symlinks)
+    dispatch = make_dispatch(dir_a, dir_b, comparators)
+    annotate = make_annotate(dir_a, dir_b, follow_symlinks,
+                             dispatch.recheck if dispatch else None)
+
+    path_a, path_b, handles = dir_handles(dir_a, dir_b)
+    fd, task = _dir_stream_start_c(
+        path_a, path_b,
         chunk_size=chunk_size,
         size_precheck=size_precheck,
         quick_check=quick_check,
         follow_symlinks=follow_symlinks,
         max_workers=max_workers,
+        max_pending=max_pending,
+        cancel=cancel_handle(cancel),
+        cache=cache_handle(cache),
+        **retry_kwargs(retry),
+        **lock_kwargs(lock),
+        **handles,
+    )
+    finished = False
+    while not finished:
+        select.select([fd], [], [])
+        with error_scope(cancel):
+            events, finished = _dir_stream_poll_c(task)
+        yield from iter_diff_entries(events, exclude, include_equal, annotate)
+
+
+def plan_dir(
+    dir_a: str | int,
+    dir_b: str | int,
+    *,
+    size_precheck: bool = True,
+    follow_symlinks: bool = True,
+    ignore: list[str] | None = None,
+    path_filter: PathFilter | None = None,
+    options: CompareOptions | None = None,
+    cancel: CancelToken | None = None,
+) -> DirPlan:
+    """Plan a directory comparison without reading file content.
+
+    Walks both trees and stats every common file — the cheap part of
+    :func:`compare_dir` — and reports what the content phase would have
+    to read, so callers can estimate the cost before running it.
+
+    :param dir_a: Path to first directory, or an open directory descriptor.
+    :param dir_b: Path to second directory, or an open directory descriptor.
+    :param size_precheck: Settle common files of different size as different.
+    :param follow_symlinks: Follow symbolic links during traversal.
+    :param ignore: Glob patterns to exclude (matched per path component).
+    :param path_filter: PathFilter deciding per entry, with metadata, whether
+        it is reported.
+    :param options: CompareOptions bundle; replaces the keyword arguments above,
+        which raise TypeError if also passed.
+    :param cancel: CancelToken that aborts the walk when set.
+    :returns: DirPlan with the pairs to read and the entries already settled.
+    :raises SourceReadError: If a directory cannot be walked.
+    """
+    if options is not None:
+        reject_overrides("plan_dir", locals(), DIR_OPTION_DEFAULTS)
+        return plan_dir(dir_a, dir_b,
+                        size_precheck=options.size_precheck,
+                        follow_symlinks=options.follow_symlinks,
+                        ignore=list(options.ignore) or None,
+                        path_filter=options.path_filter, cancel=cancel)
+
+    validate_dir(dir_a, "dir_a")
+    validate_dir(dir_b, "dir_b")
+    exclude = make_exclude(ignore, path_filter, dir_a, dir_b, follow_symlinks)
+
+    path_a, path_b, handles = dir_handles(dir_a, dir_b)
+    with error_scope(cancel):
+        raw = _plan_dir_c(
+            path_a, path_b,
+            size_precheck=size_precheck,
+            follow_symlinks=follow_symlinks,
+            cancel=cancel_handle(cancel),
+            **handles,
+        )
+    settled = filter_dir_result(build_dir_result(raw), exclude)
+    pairs: list[PlannedPair] = []
+    same_file: set[str] = set()
+    for path, size_a, size_b, same in raw["pairs"]:
+        if exclude is not None and exclude(path):
+            continue
+        if same:
+            same_file.add(path)
+        else:
+            pairs.append(PlannedPair(path, size_a, size_b))
+    return DirPlan(
+        pairs=pairs,
+        same_file=same_file,
+        size_mismatch=set(settled.diff),
+        only_left=settled.only_left,
+        only_right=settled.only_right,
+        errors=settled.errors,
     )
-    result = build_dir_result(raw)
-    if ignore:
-        result = filter_dir_result(result, ignore)
-    return result
+
+
+def prescan_totals(dir_a: str | int, dir_b: str | int, **kwargs: Any) -> tuple[int, int]:
+    """Entries a directory comparison will resolve and bytes it will compare.
+
+    Plans the comparison with :func:`plan_dir` (keyword arguments are
+    passed on) and counts what :class:`ProgressVisitor` will count, so
+    the totals are the final ``files_done`` and ``bytes_done``.
+    """
+    plan = plan_dir(dir_a, dir_b, **kwargs)
+    files = (len(plan.pairs) + len(plan.same_file) + len(plan.size_mismatch)
+             + len(plan.only_left) + len(plan.only_right) + len(plan.errors))
+    size = sum(max(p.size_a, 0) for p in plan.pairs)
+    for path in plan.same_file:
+        try:
+            size += stat_entry(dir_a, path).st_size
+        except OSError:
+            pass
+    return files, size
 
 
 def compare_archive(
@@ -138,23 +689,96 @@ def compare_archive(
         of O(total_decompressed)). Computes streaming FNV-1a 128-bit fingerprint
         of each entry instead of storing full content.
     :returns: DirResult with equal, diff, only_left, only_right.
+    :raises ArchiveError: If an archive cannot be read (ArchiveBombError
+        when a safety limit is exceeded).
     """
     validate_path(path_a, "path_a")
     validate_path(path_b, "path_b")
     validate_chunk_size(chunk_size)
 
-    raw = _compare_archive_c(
-        path_a, path_b,
-        chunk_size=chunk_size,
-        max_decompressed_size=max_decompressed_size,
-        max_compression_ratio=max_compression_ratio,
-        max_entries=max_archive_entries,
-        max_entry_name_length=max_entry_name_length,
-        hash_compare=hash_compare,
-    )
+    with error_scope(archive=True):
+        raw = _compare_archive_c(
+            path_a, path_b,
+            chunk_size=chunk_size,
+            max_decompressed_size=max_decompressed_size,
+            max_compression_ratio=max_compression_ratio,
+            max_entries=max_archive_entries,
+            max_entry_name_length=max_entry_name_length,
+            hash_compare=hash_compare,
+        )
     return build_dir_result(raw)
 
 
+def compare_dir_archive(
+    path_a: str,
+    path_b: str,
+    *,
+    chunk_size: int = 65536,
+    size_precheck: bool = True,
+    quick_check: bool = True,
+    follow_symlinks: bool = True,
+    max_workers: int = 0,
+    ignore: list[str] | None = None,
+    max_decompressed_size: int = 1073741824,
+    max_compression_ratio: int = 200,
+    max_archive_entries: int = 100_000,
+    max_entry_name_length: int = 4096,
+    cancel: CancelToken | None = None,
+) -> DirResult:
+    """Compare a directory against an archive, without extracting it.
+
+    One side is a directory and the other an archive in any format
+    :func:`compare_archive` reads (tar, zip, ...); the side that is not a
+    directory is the archive, so results keep their left/right meaning.
+    Regular-file entries map to relative paths with a leading ``./``
+    stripped. The archive is decompressed into memory once, within the
+    bomb limits; the directory is walked and read as by
+    :func:`compare_dir`.
+
+    :param path_a: Directory or archive (left side).
+    :param path_b: Directory or archive (right side).
+    :param chunk_size: Chunk size for file comparison.
+    :param size_precheck: Compare file sizes before content.
+    :param quick_check: Sample key offsets before full scan.
+    :param follow_symlinks: Follow symbolic links in the directory.
+    :param max_workers: Thread pool size (0=auto, 1=sequential).
+    :param ignore: Glob patterns to exclude (matched per path component).
+    :param max_decompressed_size: Max total decompressed bytes (bomb limit).
+    :param max_compression_ratio: Max compression ratio (bomb limit).
+    :param max_archive_entries: Max number of archive entries (bomb limit).
+    :param max_entry_name_length: Max entry path length (bomb limit).
+    :param cancel: CancelToken that aborts the comparison when set.
+    :returns: DirResult with equal, diff, only_left, only_right.
+    :raises ValueError: If both sides are directories.
+    :raises ArchiveError: If the archive cannot be read (ArchiveBombError
+        when a safety limit is exceeded).
+    :raises SourceReadError: If the directory cannot be walked.
+    """
+    validate_path(path_a, "path_a")
+    validate_path(path_b, "path_b")
+    validate_chunk_size(chunk_size)
+    validate_max_workers(max_workers)
+    archive_a = not os.path.isdir(path_a)
+    if not archive_a and os.path.isdir(path_b):
+        raise ValueError("one of path_a, path_b must be an archive, got two directories")
+
+    with error_scope(cancel, archive=True):
+        raw = _compare_dir_archive_c(
+            path_a, path_b, archive_a,
+            chunk_size=chunk_size,
+            size_precheck=size_precheck,
+            quick_check=quick_check,
+            follow_symlinks=follow_symlinks,
+            max_workers=max_workers,
+            cancel=cancel_handle(cancel),
+            max_decompressed_size=max_decompressed_size,
+            max_compression_ratio=max_compression_ratio,
+            max_entries=max_archive_entries,
+            max_entry_name_length=max_entry_name_length,
+        )
+    return filter_dir_result(build_dir_result(raw), make_exclude(ignore, None, path_a, path_b))
+
+
 def compare_all(
     sources: list[str | Source],
     *,
@@ -216,6 +840,11 @@ def compare_all(
         return True
 
 
+def _is_local_file(source: str | Source) -> bool:
+    return (isinstance(source, str) and not source.startswith(("http://", "https://"))
+            and os.path.isfile(source))
+
+
 def compare_many(
     sources: list[str | Source],
     *,
@@ -231,6 +860,10 @@ def compare_many(
 ) -> CompareResult:
     """Detailed pairwise comparison of multiple sources.
 
+    When every source is a local file, the pairs are not compared one by
+    one: files are grouped by size, then by a hash of their first and last
+    4 KiB, and only the candidates left are read in full.
+
     :param sources: List of file paths, URLs, or Source objects.
     :param max_workers: Thread pool size (0=auto, 1=sequential).
     :param proxy: Proxy URL (e.g. http://host:port, socks5://host:port).
@@ -256,6 +889,18 @@ def compare_many(
         names = [s.url if isinstance(s, Source) else s for s in sources]
         return CompareResult(all_equal=True, groups=[set(names)], diff={})
 
+    if size_precheck and all(_is_local_file(s) for s in sources):
+        paths = [s for s in sources if isinstance(s, str)]
+        groups = group_identical(paths, chunk_size=chunk_size, quick_check=quick_check,
+                                 max_workers=max_workers)
+        group_of = {i: g for g, group in enumerate(groups) for i in group}
+        return CompareResult(
+            all_equal=len(groups) == 1,
+            groups=[set(group) for group in groups],
+            diff={(a, b): group_of[a] == group_of[b]
+                  for i, a in enumerate(paths) for b in paths[i + 1:]},
+        )
+
     pairs: list[tuple[int, int]] = []
     for i in range(n):
         for j in range(i + 1, n):
@@ -345,16 +990,17 @@ def compare_dir_urls(
     h = headers if headers is not None else (cfg.headers or None)
     p = proxy if proxy is not None else cfg.proxy
 
-    raw = _compare_dir_urls_c(
-        dir_path, url_map,
-        chunk_size=chunk_size,
-        size_precheck=size_precheck,
-        quick_check=quick_check,
-        headers=h if h else None,
-        timeout=timeout,
-        follow_redirects=follow_redirects,
-        verify_ssl=verify_ssl,
-        allow_private=cfg.allow_private_redirects,
-        proxy=p,
-    )
+    with error_scope():
+        raw = _compare_dir_urls_c(
+            dir_path, url_map,
+            chunk_size=chunk_size,
+            size_precheck=size_precheck,
+            quick_check=quick_check,
+            headers=h if h else None,
+            timeout=timeout,
+            follow_redirects=follow_redirects,
+            verify_ssl=verify_ssl,
+            allow_private=cfg.allow_private_redirects,
+            proxy=p,
+        )
     return build_dir_result(raw)
@@ -0,0 +1,171 @@
+"""Per-path attributes: ``.komparuattributes`` files, in the manner of ``.gitattributes``.
+
+Each line is a pattern followed by the attributes it assigns::
+
+    # comments and blank lines are skipped
+    *.csv           text
+    *.json          compare=json
+    *.bin           binary
+    build/          ignore
+    etc/**          metadata=mode,owner
+    etc/motd        -metadata
+
+``NAME`` sets an attribute, ``-NAME`` unsets it, ``!NAME`` leaves it as
+if no earlier line had mentioned it and ``NAME=VALUE`` gives it a value;
+``binary`` stands for ``-text``. Patterns follow ``.gitattributes``: one
+without a ``/`` matches the last path component at any depth, one with a
+``/`` (a trailing one aside) the whole path from the compared root; ``*``
+and ``?`` stay within a component and ``**`` spans them. When several
+lines match a path, the last one to mention an attribute decides it.
+"""
+
+from __future__ import annotations
+
+import os
+import re
+from collections.abc import Iterable
+
+from komparu._filter import PathFilter, _glob_regex
+from komparu._metadata import EXTRA_ATTRIBUTES, METADATA_ATTRIBUTES, validate_attributes
+from komparu._types import PATH_ERRORS
+
+ATTRIBUTES_FILE = ".komparuattributes"
+# Attributes a line may assign; "compare" and "metadata" take values
+KNOWN = ("text", "compare", "ignore", "metadata")
+_MACROS = {"binary": (("text", False),)}
+
+Value = bool | str
+
+
+def _pattern_regex(pattern: str) -> re.Pattern[str]:
+    segments = pattern.split("/")
+    out = []
+    for i, segment in enumerate(segments):
+        last = i == len(segments) - 1
+        if segment == "**":
+            out.append(".*" if last else "(?:.*/)?")
+        else:
+            out.append(_glob_regex(segment) + ("" if last else "/"))
+    return re.compile("".join(out), re.DOTALL)
+
+
+class _Line:
+    __slots__ = ("regex", "basename", "values", "where")
+
+    def __init__(self, pattern: str, values: list[tuple[str, Value | None]], where: str) -> None:
+        pattern = pattern.rstrip("/")
+        self.basename = "/" not in pattern
+        self.regex = _pattern_regex(pattern.lstrip("/"))
+        self.values = values
+        self.where = where
+
+    def matches(self, path: str) -> bool:
+        return self.regex.fullmatch(path.rsplit("/", 1)[-1] if self.basename else path) is not None
+
+
+def _parse_value(token: str, where: str) -> list[tuple[str, Value | None]]:
+    if token.startswith(("-", "!")):
+        name, value = token[1:], (False if token[0] == "-" else None)
+    elif "=" in token:
+        name, value = token.split("=", 1)
+    else:
+        name, value = token, True
+    if name in _MACROS:
+        if value is not True:
+            raise ValueError(f"{where}: {name} can only be set")
+        return list(_MACROS[name])
+    if name not in KNOWN:
+        raise ValueError(f"{where}: unknown attribute {name!r} "
+                         f"(expected {', '.join(KNOWN)} or binary)")
+    if isinstance(value, str):
+        if name in ("text", "ignore"):
+            raise ValueError(f"{where}: {name} takes no value")
+        if not value:
+            raise ValueError(f"{where}: {name}= needs a value")
+        if name == "metadata":
+            try:
+                validate_attributes(value.split(","), METADATA_ATTRIBUTES + EXTRA_ATTRIBUTES)
+            except ValueError as exc:
+                raise ValueError(f"{where}: {exc}") from None
+    elif value is True and name == "compare":
+        raise ValueError(f"{where}: compare needs a value, as in compare=json")
+    return [(name, value)]
+
+
+class PathAttributes:
+    """Attributes assigned to relative paths by ``.komparuattributes`` lines.
+
+    :param lines: Lines of a rules file.
+    :param where: Name of their source, for error messages.
+    :raises ValueError: If a line is malformed or names an unknown attribute.
+    """
+
+    __slots__ = ("_lines",)
+
+    def __init__(self, lines: Iterable[str] = (), *, where: str = ATTRIBUTES_FILE) -> None:
+        self._lines: list[_Line] = []
+        self.add(lines, where=where)
+
+    def add(self, lines: Iterable[str], *, where: str = ATTRIBUTES_FILE) -> None:
+        """Append the rules of *lines*, which then override the earlier ones."""
+        for number, line in enumerate(lines, 1):
+            at = f"{where}:{number}"
+            fields = line.split()
+            if not fields or fields[0].startswith("#"):
+                continue
+            pattern, tokens = fields[0], fields[1:]
+            if pattern.startswith("!"):
+                raise ValueError(f"{at}: negative patterns are not supported")
+            if pattern.strip("/") == "":
+                raise ValueError(f"{at}: empty pattern")
+            values = [value for token in tokens for value in _parse_value(token, at)]
+            if values:
+                self._lines.append(_Line(pattern, values, at))
+
+    @classmethod
+    def from_files(cls, paths: Iterable[str]) -> PathAttributes:
+        """Rules read from *paths*, each overriding the ones before it."""
+        attributes = cls()
+        for path in paths:
+            with open(path, encoding="utf-8", errors=PATH_ERRORS) as f:
+                attributes.add(f, where=path)
+        return attributes
+
+    def __bool__(self) -> bool:
+        return bool(self._lines)
+
+    def values(self, name: str) -> list[tuple[Value, str]]:
+        """Every value lines assign to *name*, with the line that does."""
+        return [(value, line.where) for line in self._lines for key, value in line.values
+                if key == name and value is not None]
+
+    def lookup(self, path: str) -> dict[str, Value]:
+        """The attributes of relative *path*: True if set, False if unset, or the value."""
+        found: dict[str, Value] = {}
+        for line in self._lines:
+            if line.matches(path):
+                for name, value in line.values:
+                    if value is None:
+                        found.pop(name, None)
+                    else:
+                        found[name] = value
+        return found
+
+    def ignored(self, path: str) -> bool:
+        """Whether *path*, or a directory it is in, has ``ignore`` set."""
+        parts = path.split("/")
+        return any(self.lookup("/".join(parts[:n])).get("ignore") is True
+                   for n in range(1, len(parts) + 1))
+
+    def path_filter(self) -> PathFilter | None:
+        """PathFilter dropping what :meth:`ignored` names (None if no line sets ``ignore``)."""
+        return _Ignored(self) if any(value is True for value, _ in self.values("ignore")) else None
+
+
+class _Ignored(PathFilter):
+    def __init__(self, attributes: PathAttributes) -> None:
+        self._attributes = attributes
+
+    def include(self, path: str, stat_a: os.stat_result | None,
+                stat_b: os.stat_result | None) -> bool:
+        return not self._attributes.ignored(path)
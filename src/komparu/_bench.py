@@ -0,0 +1,187 @@
+"""``komparu bench``: time komparu against cmp, diff and rsync on the same inputs."""
+
+from __future__ import annotations
+
+import os
+import shlex
+import shutil
+import statistics
+import subprocess
+import time
+from collections.abc import Sequence
+from dataclasses import dataclass, field
+from typing import Any
+
+from komparu._api import compare, compare_dir
+from komparu._cancel import CancelToken
+
+TOOLS = ("komparu", "cmp", "diff", "rsync")
+
+try:
+    import resource
+except ImportError:  # Windows
+    resource = None  # type: ignore[assignment]
+
+
+@dataclass(frozen=True, slots=True)
+class BenchResult:
+    """How one tool did on the benchmarked pair.
+
+    :param tool: Name from :data:`TOOLS`.
+    :param command: What was run, as a shell would show it.
+    :param result: ``"equal"``, ``"different"`` or ``"error"`` of the last
+        run; ``"not installed"`` or ``"not applicable"`` if it did not run.
+    :param seconds: Wall time of each timed run.
+    :param bytes_read: Bytes read by the last run, None where unknown.
+    :param disk_bytes: Of those, bytes read from the disk rather than the
+        page cache, None where unknown.
+    """
+
+    tool: str
+    command: str
+    result: str
+    seconds: tuple[float, ...] = field(default=())
+    bytes_read: int | None = None
+    disk_bytes: int | None = None
+
+    @property
+    def median(self) -> float | None:
+        return statistics.median(self.seconds) if self.seconds else None
+
+    def to_dict(self) -> dict[str, Any]:
+        return {"tool": self.tool, "command": self.command, "result": self.result,
+                "seconds": list(self.seconds), "median": self.median,
+                "bytes_read": self.bytes_read, "disk_bytes": self.disk_bytes}
+
+
+def _argv(tool: str, path_a: str, path_b: str, is_dir: bool) -> list[str] | None:
+    if tool == "cmp":
+        return None if is_dir else ["cmp", "-s", path_a, path_b]
+    if tool == "diff":
+        return ["diff", "-rq", path_a, path_b]
+    if is_dir:
+        return ["rsync", "-rnc", "--delete", "--out-format=%n",
+                os.path.join(path_a, ""), os.path.join(path_b, "")]
+    return ["rsync", "-nc", "--out-format=%n", path_a, path_b]
+
+
+def _proc_io(pid: int) -> dict[str, int]:
+    """The counters of ``/proc/PID/io``, empty where there are none."""
+    try:
+        with open(f"/proc/{pid}/io", encoding="ascii") as f:
+            return {key: int(value) for key, value in (line.split(": ") for line in f)}
+    except (OSError, ValueError):
+        return {}
+
+
+def _run_tool(argv: list[str], listing: bool) -> tuple[float, str, int | None, int | None]:
+    """Run *argv* once; its result, wall time and bytes read.
+
+    The child is waited for without being reaped first, so its
+    ``/proc/PID/io`` can still be read, then reaped with its rusage.
+    """
+    start = time.perf_counter()
+    child = subprocess.Popen(argv, stdin=subprocess.DEVNULL, stderr=subprocess.DEVNULL,
+                             stdout=subprocess.PIPE if listing else subprocess.DEVNULL)
+    output = child.stdout.read() if child.stdout is not None else b""
+    counters: dict[str, int] = {}
+    if hasattr(os, "waitid") and hasattr(os, "wait4"):
+        os.waitid(os.P_PID, child.pid, os.WEXITED | os.WNOWAIT)
+        elapsed = time.perf_counter() - start
+        counters = _proc_io(child.pid)
+        _, status, usage = os.wait4(child.pid, 0)
+        child.returncode = os.waitstatus_to_exitcode(status)
+    else:
+        child.wait()
+        elapsed = time.perf_counter() - start
+        usage = None
+    if child.stdout is not None:
+        child.stdout.close()
+    code = child.returncode
+    if listing:
+        result = "error" if code else "different" if output.strip() else "equal"
+    else:
+        result = {0: "equal", 1: "different"}.get(code, "error")
+    disk = counters.get("read_bytes")
+    if disk is None and usage is not None:
+        disk = usage.ru_inblock * 512
+    return elapsed, result, counters.get("rchar"), disk
+
+
+def _inblock() -> int | None:
+    return resource.getrusage(resource.RUSAGE_SELF).ru_inblock if resource else None
+
+
+def _run_komparu(path_a: str, path_b: str,
+                 is_dir: bool) -> tuple[float, str, int | None, int | None]:
+    """Compare in this process, counting what the engine reads."""
+    cancel = CancelToken()
+    blocks = _inblock()
+    start = time.perf_counter()
+    try:
+        if is_dir:
+            equal = compare_dir(path_a, path_b, cancel=cancel).equal
+        else:
+            equal = compare(path_a, path_b, cancel=cancel)
+    except (OSError, ValueError):
+        result = "error"
+    else:
+        result = "equal" if equal else "different"
+    elapsed = time.perf_counter() - start
+    after = _inblock()
+    disk = None if blocks is None or after is None else (after - blocks) * 512
+    return elapsed, result, cancel.bytes_read, disk
+
+
+def bench(path_a: str, path_b: str, *, tools: Sequence[str] = TOOLS, repeat: int = 3,
+          warmup: int = 1) -> list[BenchResult]:
+    """Time each of *tools* comparing *path_a* with *path_b*.
+
+    komparu runs in this process, as the library would; the others run as
+    child processes, so their times include starting them. Each tool gets
+    *warmup* untimed runs first, which also fills the page cache.
+
+    :param path_a: First file or directory.
+    :param path_b: Second, of the same kind.
+    :param tools: Names from :data:`TOOLS`, in the order to run them.
+    :param repeat: Timed runs per tool.
+    :param warmup: Untimed runs per tool before those.
+    :returns: BenchResult per tool, in *tools* order.
+    :raises ValueError: If the paths are not two files or two directories,
+        or *repeat* or *warmup* are out of range.
+    """
+    if repeat < 1:
+        raise ValueError("repeat must be at least 1")
+    if warmup < 0:
+        raise ValueError("warmup must be non-negative")
+    is_dir = os.path.isdir(path_a)
+    if is_dir != os.path.isdir(path_b) or not is_dir and not (
+            os.path.isfile(path_a) and os.path.isfile(path_b)):
+        raise ValueError("bench needs two files or two directories")
+    results = []
+    for tool in tools:
+        if tool not in TOOLS:
+            raise ValueError(f"unknown tool: {tool}")
+        if tool == "komparu":
+            name = "compare_dir" if is_dir else "compare"
+            command = f"komparu.{name}({path_a!r}, {path_b!r})"
+            argv = None
+        else:
+            argv = _argv(tool, path_a, path_b, is_dir)
+            if argv is None:
+                results.append(BenchResult(tool, "", "not applicable"))
+                continue
+            command = shlex.join(argv)
+            if shutil.which(argv[0]) is None:
+                results.append(BenchResult(tool, command, "not installed"))
+                continue
+        seconds = []
+        for i in range(warmup + repeat):
+            if argv is None:
+                elapsed, result, read, disk = _run_komparu(path_a, path_b, is_dir)
+            else:
+                elapsed, result, read, disk = _run_tool(argv, tool == "rsync")
+            if i >= warmup:
+                seconds.append(elapsed)
+        results.append(BenchResult(tool, command, result, tuple(seconds), read, disk))
+    return results
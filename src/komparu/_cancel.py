@@ -0,0 +1,82 @@
+"""Cancellation tokens for in-flight comparisons."""
+
+from __future__ import annotations
+
+from typing import Any
+
+from komparu._core import (
+    cancel_token_new,
+    cancel_token_set,
+    cancel_token_is_set,
+    cancel_token_stats,
+)
+
+
+class CancelToken:
+    """Thread-safe flag that stops in-flight comparisons.
+
+    Pass as ``cancel=`` to :func:`komparu.compare`, :func:`komparu.compare_dir`,
+    :func:`komparu.iter_dir` or their :mod:`komparu.aio` counterparts, then
+    call :meth:`cancel` from any thread (a GUI handler, a signal, another
+    task). C workers check the flag between chunks and between directory
+    entries; the call then raises :class:`ComparisonCancelledError`.
+
+    A token stays cancelled once set and may be shared by several calls.
+
+    :param max_bytes: Byte quota: the token cancels itself at the first
+        chunk the engine reads past this many bytes, counted over both
+        sides of every call that shares it, and the call raises
+        :class:`QuotaReachedError`. None for no quota.
+    """
+
+    __slots__ = ("_handle", "max_bytes")
+
+    def __init__(self, *, max_bytes: int | None = None) -> None:
+        if max_bytes is not None and max_bytes <= 0:
+            raise ValueError("max_bytes must be positive")
+        self._handle = cancel_token_new(max_bytes or 0)
+        self.max_bytes = max_bytes
+
+    def cancel(self) -> None:
+        """Request cancellation. Idempotent."""
+        cancel_token_set(self._handle)
+
+    @property
+    def cancelled(self) -> bool:
+        """True once :meth:`cancel` has been called."""
+        return cancel_token_is_set(self._handle)
+
+    @property
+    def bytes_read(self) -> int:
+        """Bytes the comparison engine has read under this token so far."""
+        return cancel_token_stats(self._handle)[0]
+
+    @property
+    def entries_done(self) -> int:
+        """Directory entries the engine has resolved under this token so far."""
+        return cancel_token_stats(self._handle)[2]
+
+    @property
+    def quota_reached(self) -> bool:
+        """True if this token or its parent stopped because *max_bytes* were read."""
+        return cancel_token_stats(self._handle)[1]
+
+    def __repr__(self) -> str:
+        return f"CancelToken(cancelled={self.cancelled})"
+
+
+def child_token(parent: CancelToken | None) -> CancelToken:
+    """A token cancelled with *parent* too, whose reads and entries also count into it.
+
+    Lets a run stop itself without setting a token the caller may share.
+    """
+    token = CancelToken.__new__(CancelToken)
+    token._handle = cancel_token_new(0, cancel_handle(parent))
+    token.max_bytes = None
+    return token
+
+
+def cancel_handle(token: CancelToken | None) -> Any:
+    """C extension handle for *token* (None passes through)."""
+    return token._handle if token is not None else None
+
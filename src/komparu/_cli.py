@@ -0,0 +1,2660 @@
+"""komparu command-line interface.
+
+Thin wrapper over the public library API. All comparison work is done
+by the same functions embedders call directly (``komparu.compare``,
+``komparu.compare_dir``, ``komparu.compare_archive``,
+``komparu.compare_dir_archive``).
+
+Exit codes follow ``cmp``/``diff``: 0 = equal, 1 = different, 2 = error.
+With ``--severity`` rules the highest severity decides instead:
+``error`` = 1, ``warn`` = 3, ``info`` = 0.
+"""
+
+from __future__ import annotations
+
+import argparse
+import contextlib
+import errno
+import getpass
+import io
+import json
+import os
+import posixpath
+import shlex
+import signal
+import stat
+import sys
+import threading
+import time
+from collections.abc import Callable, Collection, Iterable, Iterator, Sequence
+from typing import Any
+from fnmatch import fnmatch
+
+from komparu import __version__
+from komparu._api import (
+    compare,
+    compare_dir,
+    compare_archive,
+    compare_dir_archive,
+    first_diff,
+    plan_dir,
+    similarity,
+    verify_zero,
+)
+from komparu._salvage import compare_salvage
+from komparu._vss import shadow_copy
+from komparu._split import split_parts
+from komparu._snapshot import (
+    compare_snapshot,
+    read_snapshot,
+    snapshot_changes,
+    snapshot_tree,
+    write_snapshot,
+)
+from komparu._agent import AgentServer, compare_dir_agent, serve_session
+from komparu._attributes import ATTRIBUTES_FILE, PathAttributes
+from komparu._bench import TOOLS as BENCH_TOOLS
+from komparu._bench import BenchResult, bench
+from komparu._completion import SHELLS, completion_script
+from komparu._config import configure, get_config
+from komparu._configfile import env_flag, load_defaults
+from komparu._copy import copy_tree_verified, copy_verified
+from komparu._watch import watch_dir
+from komparu._sync import sync_dir, sync_plan
+from komparu._tui import DiffBrowser, run_tui
+from komparu._server import DEFAULT_PORT as SERVE_PORT, ComparisonServer
+from komparu._dupes import DEDUP_METHODS, deduplicate, find_duplicates
+from komparu._delta import chunk_diff, delta_size
+from komparu._cancel import CancelToken
+from komparu._dircache import DirCache
+from komparu._storage import tune_for_storage
+from komparu._patch import PATCH_SUFFIX, apply_patch, make_patch
+from komparu._fuzzy import fuzzy_hash, fuzzy_score
+from komparu._filter import PathFilter, RsyncFilter, all_filters, make_exclude
+from komparu._helpers import atomic_open, fetch_url, is_url, walk_tree
+from komparu._manifest import (
+    compare_manifests,
+    format_manifest_line,
+    hash_tree,
+    parse_manifest,
+    parse_sfv,
+    read_manifest,
+    tree_digest,
+    update_manifest,
+    verify_manifest,
+)
+from komparu._git import compare_dir_git, compare_git_revs, git_range
+from komparu._oci import compare_image_dir, compare_images
+from komparu._diskimage import compare_disk_image_dir, compare_disk_images
+from komparu._fssnap import compare_fs_snapshots
+from komparu._remote import chunk_diff_ssh, compare_dir_ssh, parse_ssh_target
+from komparu._rsync import mapped, read_signature, write_delta
+from komparu._s3 import compare_dir_s3
+from komparu._store import STORE_SCHEMES, compare_dir_store
+from komparu._signing import key_is_encrypted, sign_manifest, verify_signature
+from komparu._logsink import LogSink
+from komparu._audit import AuditLog
+from komparu._metrics import PrometheusMetrics
+from komparu._metadata import (
+    EXTRA_ATTRIBUTES,
+    METADATA_ATTRIBUTES,
+    compare_dir_metadata,
+    metadata_diff,
+    validate_attributes,
+)
+from komparu._comparator import (
+    AudioComparator,
+    ContentComparator,
+    DebComparator,
+    DecompressComparator,
+    ExecutableComparator,
+    FilterComparator,
+    ImageComparator,
+    JsonComparator,
+    ParquetComparator,
+    PdfComparator,
+    SniffingComparator,
+    SqliteComparator,
+    TarComparator,
+    TextComparator,
+    XmlComparator,
+    YamlComparator,
+    ZipComparator,
+)
+from komparu._rules import load_rules
+from komparu._options import SEVERITY_CATEGORIES, CompareOptions, LockPolicy, SeverityPolicy
+from komparu._visitor import CompareVisitor
+from komparu._rollup import SummaryVisitor
+from komparu._core import noatime_denied as _noatime_denied
+from komparu._core import set_noatime as _set_noatime
+from komparu._types import (
+    PATH_ERRORS,
+    UNREADABLE,
+    ChunkDiff,
+    DiffEntry,
+    DiffReason,
+    DiffRegion,
+    DirResult,
+    DirSummary,
+    DriftKind,
+    DuplicateGroup,
+    EntryKind,
+    SyncAction,
+    SyncPlan,
+    SyncStep,
+    Manifest,
+    QuotaReachedError,
+    RegionKind,
+    Severity,
+    SourcePermissionError,
+    SourceReadError,
+    path_key,
+    tree_key,
+)
+
+EXIT_EQUAL = 0
+EXIT_DIFFERENT = 1
+EXIT_ERROR = 2
+EXIT_WARN = 3
+EXIT_QUOTA = 4
+
+# --on-error: what a file that cannot be read does to the run
+ON_ERROR = ("fail", "skip", "report")
+
+# Signals asking a running comparison how far it got (SIGINFO is Ctrl+T on BSD and macOS)
+_STATUS_SIGNALS = tuple(getattr(signal, name) for name in ("SIGUSR1", "SIGINFO")
+                        if hasattr(signal, name))
+
+_TARBALLS = ("*.tar", "*.tar.gz", "*.tgz", "*.tar.bz2", "*.tbz2", "*.tar.xz", "*.txz")
+_ZIPS = ("*.zip", "*.jar", "*.whl")
+_DEBS = ("*.deb", "*.udeb")
+_IMAGES = ("*.png", "*.jpg", "*.jpeg", "*.tif", "*.tiff")
+_AUDIO = ("*.mp3", "*.flac", "*.ogg", "*.oga", "*.opus")
+_SQLITE = ("*.sqlite", "*.sqlite3", "*.db")
+_PARQUET = ("*.parquet", "*.arrow", "*.feather")
+_COMPRESSED = ("*.gz", "*.bz2", "*.xz", "*.zst")
+# --compare-as strategies, beyond the bytes the engine compares anyway
+COMPARE_AS = ("bytes", "text", "json", "yaml", "xml", "decompress", "tar", "zip", "deb",
+              "image", "audio", "pdf", "sqlite", "parquet", "executable", "content-aware")
+
+_SEVERITY_EXIT = {
+    Severity.INFO: EXIT_EQUAL,
+    Severity.WARN: EXIT_WARN,
+    Severity.ERROR: EXIT_DIFFERENT,
+}
+
+
+def _build_parser() -> tuple[argparse.ArgumentParser, dict[str, argparse.ArgumentParser]]:
+    parser = argparse.ArgumentParser(
+        prog="komparu",
+        description="Ultra-fast file, directory and archive comparison.",
+    )
+    parser.add_argument("--version", action="version", version=f"%(prog)s {__version__}")
+    parser.add_argument("--config", metavar="FILE",
+                        help="read defaults from this TOML file instead of the nearest "
+                             "komparu.toml (the user's ~/.config/komparu/config.toml still "
+                             "applies beneath it; also $KOMPARU_CONFIG)")
+    parser.add_argument("--profile", metavar="NAME",
+                        help="apply the [profile.NAME] table of the configuration files "
+                             "over their other options (also $KOMPARU_PROFILE)")
+    parser.add_argument("--no-config", action="store_true",
+                        help="ignore komparu.toml, ~/.config/komparu/config.toml and "
+                             "KOMPARU_<OPTION> variables (also $KOMPARU_NO_CONFIG=1)")
+    parser.add_argument("--fips", action="store_true",
+                        help="hash only with FIPS-approved algorithms (SHA-256/384/512) and "
+                             "refuse everything else (also $KOMPARU_FIPS=1)")
+    sub = parser.add_subparsers(dest="command", required=True)
+
+    p = sub.add_parser("compare", help="compare two files, URLs, directories or archives, or a directory "
+                        "against an archive, a [user@]host:path over SSH or an s3://bucket/prefix")
+    p.add_argument("left", nargs="?",
+                   help="first path, URL, [user@]host:path, komparu:// agent URL or s3://, "
+                        "gs:// or az:// prefix")
+    p.add_argument("right", nargs="?",
+                   help="second path, URL, [user@]host:path, komparu:// agent URL or s3://, "
+                        "gs:// or az:// prefix")
+    p.add_argument("--pair", action="append", default=[], nargs=2, metavar=("LEFT", "RIGHT"),
+                   help="compare this pair too, instead of the two paths (repeatable)")
+    p.add_argument("--pairs-from", metavar="FILE",
+                   help="compare every LEFT<TAB>RIGHT line of FILE ('-' for stdin) as a pair, "
+                        "in one run, with one combined report")
+    p.add_argument("--archive", action="store_true",
+                   help="treat both paths as archives and compare entries")
+    p.add_argument("--split", action="store_true",
+                   help="read LEFT as split volumes: NAME.001 and the numbered parts after "
+                        "it, or a file listing the parts, compared as one file with RIGHT")
+    p.add_argument("--salvage", action="store_true",
+                   help="for flaky media: read failing chunks again in smaller pieces and "
+                        "report the byte ranges that could not be read instead of stopping")
+    p.add_argument("--min-read", type=int, metavar="BYTES",
+                   help="smallest read of --salvage, the sector size (default: 512; 2048 "
+                        "for optical discs)")
+    p.add_argument("--read-retries", type=int, metavar="N",
+                   help="extra attempts of --salvage at each failing sector (default: 2)")
+    p.add_argument("--chunk-size", type=int, default=65536, metavar="BYTES",
+                   help="chunk size in bytes (default: 65536)")
+    p.add_argument("--no-size-precheck", dest="size_precheck", action="store_false",
+                   help="do not compare sizes before content")
+    p.add_argument("--no-quick-check", dest="quick_check", action="store_false",
+                   help="do not sample key offsets before full scan")
+    p.add_argument("--no-follow-symlinks", dest="follow_symlinks", action="store_false",
+                   help="do not follow symbolic links during traversal")
+    p.add_argument("--names-only", action="store_true",
+                   help="compare only the tree structure (paths and entry types) of two "
+                        "directories, reading no file content")
+    p.add_argument("--metadata-only", action="store_true",
+                   help="compare only the permissions, ownership and extended attributes "
+                        "of every entry in two directories, reading no file content")
+    p.add_argument("--metadata", metavar="ATTRS",
+                   help="comma-separated attributes to compare: "
+                        f"{', '.join(METADATA_ATTRIBUTES)} (the default), "
+                        f"{', '.join(EXTRA_ATTRIBUTES)} (implies --metadata-only)")
+    p.add_argument("-j", "--max-workers", type=int, default=0, metavar="N",
+                   help="thread pool size (0 = auto: one per CPU, or picked for spinning "
+                        "disks and network file systems; 1 = sequential)")
+    p.add_argument("--lock", action="store_true",
+                   help="read files of two directories under shared advisory locks, so "
+                        "writers holding an exclusive lock are waited for")
+    p.add_argument("--lock-timeout", type=float, metavar="SECONDS",
+                   help="report files still locked after SECONDS as locked instead of "
+                        "waiting (implies --lock)")
+    p.add_argument("--on-error", choices=ON_ERROR, default="report",
+                   help="files that cannot be read: fail stops the run at the first one, "
+                        "skip leaves them out, report lists them as unverifiable with a "
+                        "count (default: report)")
+    p.add_argument("--max-bytes", type=int, metavar="BYTES",
+                   help="stop once the comparison has read this many bytes (both sides, "
+                        "all pairs), report what was compared so far and exit with 4")
+    p.add_argument("--text", action="store_true",
+                   help="treat CRLF and LF line endings as equal")
+    p.add_argument("-Z", "--ignore-trailing-space", action="store_true",
+                   help="text mode, also ignoring whitespace at line end (implies --text)")
+    p.add_argument("-b", "--ignore-space-change", action="store_true",
+                   help="text mode, also treating runs of whitespace as one space "
+                        "(implies --text)")
+    p.add_argument("--ignore-bom", action="store_true",
+                   help="text mode, also ignoring a leading byte-order mark (implies --text)")
+    p.add_argument("--ignore-final-newline", action="store_true",
+                   help="text mode, also ignoring whether the last line ends with a newline "
+                        "(implies --text)")
+    p.add_argument("--decode", action="store_true",
+                   help="text mode, decoding UTF-8, UTF-16 and Latin-1 before comparing "
+                        "(implies --text)")
+    p.add_argument("--json", action="store_true",
+                   help="compare *.json files structurally (key order, whitespace and "
+                        "number formatting ignored)")
+    p.add_argument("--yaml", action="store_true",
+                   help="compare *.yaml and *.yml files semantically (needs PyYAML)")
+    p.add_argument("--xml", action="store_true",
+                   help="compare *.xml files in canonical form (C14N)")
+    p.add_argument("--tar", action="store_true",
+                   help="compare tarballs by member list and contents")
+    p.add_argument("--tar-ignore-mtime", action="store_true",
+                   help="tar mode, also ignoring member mtimes (implies --tar)")
+    p.add_argument("--tar-ignore-order", action="store_true",
+                   help="tar mode, also ignoring member order (implies --tar)")
+    p.add_argument("--zip", action="store_true",
+                   help="compare *.zip, *.jar and *.whl files by entry paths and contents")
+    p.add_argument("--zip-ignore-timestamps", action="store_true",
+                   help="zip mode, also ignoring entry timestamps (implies --zip)")
+    p.add_argument("--zip-ignore-metadata", action="store_true",
+                   help="zip mode, also ignoring permissions, extra fields and comments "
+                        "(implies --zip)")
+    p.add_argument("--reproducible", action="store_true",
+                   help="reproducible-build check: compare zips, jars, wheels, tarballs and "
+                        ".deb packages by member contents, ignoring timestamps and member order")
+    p.add_argument("--image", action="store_true",
+                   help="compare PNG, JPEG and TIFF files by pixel data, ignoring EXIF, "
+                        "XMP and ICC metadata")
+    p.add_argument("--audio", action="store_true",
+                   help="compare MP3, FLAC and Ogg files by audio data, ignoring ID3, "
+                        "APE and Vorbis tags")
+    p.add_argument("--pdf", action="store_true",
+                   help="compare *.pdf files ignoring document IDs, dates and XMP timestamps")
+    p.add_argument("--sqlite", action="store_true",
+                   help="compare *.sqlite, *.sqlite3 and *.db files by schema and rows")
+    p.add_argument("--parquet", action="store_true",
+                   help="compare *.parquet, *.arrow and *.feather files by schema and rows "
+                        "(needs komparu[parquet])")
+    p.add_argument("--decompress", action="store_true",
+                   help="compare *.gz, *.bz2, *.xz and *.zst files by decompressed content")
+    p.add_argument("--content-aware", action="store_true",
+                   help="detect each differing file's type from its content and compare it "
+                        "with the matching comparator, reporting which was used")
+    p.add_argument("--filter", action="append", default=[], metavar="PATTERN=COMMAND",
+                   help="pipe differing files matching PATTERN through COMMAND and compare "
+                        "the outputs; {} in COMMAND stands for the file path (repeatable)")
+    p.add_argument("--compare-as", action="append", default=[], metavar="PATTERN=STRATEGY",
+                   help="compare differing files matching PATTERN with STRATEGY, one of "
+                        f"{', '.join(COMPARE_AS)}; bytes keeps them out of every other rule "
+                        "(repeatable, the first matching PATTERN wins)")
+    p.add_argument("--attributes", action="append", default=[], metavar="FILE",
+                   help=f"per-path rules in the {ATTRIBUTES_FILE} format: text, compare=STRATEGY, "
+                        "ignore and metadata=ATTRS; read after LEFT's own, later rules win "
+                        "(repeatable, two local directories only)")
+    p.add_argument("--no-tree-attributes", dest="tree_attributes", action="store_false",
+                   help=f"do not read the {ATTRIBUTES_FILE} at the top of LEFT")
+    p.add_argument("--similarity", action="store_true",
+                   help="report the fraction of matching blocks of each differing file, "
+                        "most diverged first")
+    p.add_argument("--chunks", action="store_true",
+                   help="split differing files into content-defined chunks and report how "
+                        "many are shared and the insertions, deletions and changes")
+    p.add_argument("--regions", action="store_true",
+                   help="list the byte ranges where differing files differ, telling "
+                        "insertions and deletions apart from changed bytes")
+    p.add_argument("--delta-size", action="store_true",
+                   help="estimate the size of a delta that turns each differing left file "
+                        "into the right one")
+    p.add_argument("--patch", metavar="PATH",
+                   help="write a binary patch that turns the left file into the right one to "
+                        "PATH; for directories, one PATH/<file>.kpatch per differing file "
+                        "(apply with komparu patch)")
+    p.add_argument("--fuzzy", action="store_true",
+                   help="report the fuzzy-hash score of differing files and pair files only "
+                        "in one tree with near-identical files in the other")
+    p.add_argument("--fuzzy-threshold", type=int, metavar="SCORE",
+                   help="lowest fuzzy score (0-100) that pairs two files (default: 50; "
+                        "implies --fuzzy)")
+    p.add_argument("--include-from", action="append", default=[], metavar="FILE",
+                   help="read rsync include patterns from FILE (repeatable; '+ '/'- ' "
+                        "prefixes, ** and dir/*** as in rsync)")
+    p.add_argument("--exclude-from", action="append", default=[], metavar="FILE",
+                   help="read rsync exclude patterns from FILE (repeatable; applied after "
+                        "--include-from)")
+    p.add_argument("--rules", metavar="SCRIPT",
+                   help="Python script defining include(path, stat_a, stat_b) and/or "
+                        "compare(path_a, path_b) hooks")
+    p.add_argument("--savings", action="store_true",
+                   help="for two directories, add the space files stored more than once "
+                        "across both waste, and the largest duplicate groups")
+    p.add_argument("--suggest-sync", action="store_true",
+                   help="for two directories, print the deletions, copies and chmods that "
+                        "would make the right one match the left instead of the differences")
+    p.add_argument("--suggest-sync-json", action="store_true",
+                   help="print that plan as JSON (implies --suggest-sync)")
+    p.add_argument("--ignore", action="append", default=[], metavar="PATTERN",
+                   help="glob pattern to exclude (repeatable)")
+    p.add_argument("--git", metavar="REV",
+                   help="compare the directory given alone with the tree of this commit "
+                        "(or REV:subdir), symlinks as links; A..B or A...B, given no "
+                        "directory, compares two commits")
+    p.add_argument("--git-repo", metavar="DIR",
+                   help="repository to read --git REV from (default: the one the "
+                        "directory is in)")
+    p.add_argument("--gitignore", action="store_true",
+                   help="with --git, leave out files .gitignore ignores that the commit lacks")
+    p.add_argument("--oci", action="store_true",
+                   help="compare container images (OCI layouts, OCI archives or docker save "
+                        "tarballs, each optionally PATH:TAG) by their flattened file "
+                        "systems; the right side may be a directory")
+    p.add_argument("--platform", metavar="OS/ARCH",
+                   help="with --oci, the platform of a multi-platform image to compare")
+    p.add_argument("--disk-image", action="store_true",
+                   help="compare directories inside ext2/3/4, FAT, exFAT, ISO 9660 or UDF "
+                        "images without mounting them, each PATH[@PARTITION][:DIR]; the right side "
+                        "may be a directory")
+    p.add_argument("--fs-snapshots", action="store_true",
+                   help="compare a directory in two btrfs or two ZFS snapshots of one file "
+                        "system, reading only the files btrfs send or zfs diff lists as changed")
+    p.add_argument("--ssh", default="ssh", metavar="COMMAND",
+                   help="command reaching the host of a [user@]host:path (default: ssh)")
+    p.add_argument("--remote-komparu", default="komparu", metavar="COMMAND",
+                   help="komparu command on that host (default: komparu)")
+    p.add_argument("--severity", action="append", default=[], metavar="CATEGORY=LEVEL",
+                   help="severity (error, warn, info) of a difference category: "
+                        f"{', '.join(SEVERITY_CATEGORIES)} (repeatable)")
+    p.add_argument("--default-severity", type=Severity, metavar="LEVEL",
+                   help="severity of categories without a --severity rule (default: error)")
+    p.add_argument("--log", metavar="TARGET",
+                   help="also write the result as structured records to journald, syslog "
+                        "(/dev/log), syslog://HOST[:PORT] or auto")
+    p.add_argument("--audit-log", metavar="FILE",
+                   help="append a record of every file pair compared (both paths, sizes, "
+                        "SHA-256 digests, result and time) to FILE, each line chained to "
+                        "the one before")
+    p.add_argument("--forensic", action="store_true",
+                   help="for evidence: write nothing to either side, read files without "
+                        "updating their access times where permitted (O_NOATIME), and "
+                        "record that in the report; local files and directories only")
+    p.add_argument("--vss", action="store_true",
+                   help="on Windows, compare Volume Shadow Copies of LEFT and RIGHT taken "
+                        "first, not the live files, so writers cannot race the comparison or "
+                        "lock files (needs an elevated prompt)")
+    p.add_argument("--cmp-compat", action="store_true",
+                   help="for two files, print and exit exactly as GNU cmp does "
+                        "(A B differ: byte N, line M; cmp: EOF on B after byte N)")
+    p.add_argument("--diff-compat", action="store_true",
+                   help="print and exit exactly as diff -rq does (Files A/x and B/x differ; "
+                        "Only in A: y)")
+    p.add_argument("--rollup", action="store_true",
+                   help="after the differences, sum them up per directory: files equal, "
+                        "differing and on one side only, and the bytes not equal (two local "
+                        "directories only)")
+    p.add_argument("--rollup-depth", type=int, metavar="N",
+                   help="list directories down to N levels below the top in --rollup "
+                        "(default: 2)")
+    p.add_argument("--tui", action="store_true",
+                   help="browse the differences of two directories in an interactive "
+                        "terminal view instead of printing them")
+    p.add_argument("-q", "--quiet", action="store_true",
+                   help="print nothing, report result via exit code only")
+    p.set_defaults(func=_cmd_compare, cache=None)
+
+    p = sub.add_parser("hash", help="print a checksum manifest (sha256sum format) of every file "
+                       "in a directory")
+    p.add_argument("directory", help="directory to hash")
+    p.add_argument("-a", "--hash", "--algorithm", dest="algorithm", metavar="NAME",
+                   help="hash algorithm: any hashlib name such as sha256, sha512 or blake2b, "
+                        "or blake3, xxh3, xxh128, crc32, crc32c (default: sha256, or the --update "
+                        "manifest's)")
+    p.add_argument("--no-follow-symlinks", dest="follow_symlinks", action="store_false",
+                   help="do not follow symbolic links during traversal")
+    p.add_argument("-j", "--max-workers", type=int, default=0, metavar="N",
+                   help="thread pool size (0 = auto, 1 = sequential)")
+    p.add_argument("--ignore", action="append", default=[], metavar="PATTERN",
+                   help="glob pattern to exclude (repeatable)")
+    p.add_argument("--tag", action="store_true",
+                   help="write BSD-style lines, SHA256 (path) = digest")
+    p.add_argument("--tree", action="store_true",
+                   help="print one Merkle-root digest of the whole tree instead")
+    p.add_argument("-o", "--output", metavar="FILE",
+                   help="write the manifest to FILE instead of standard output")
+    p.add_argument("--update", metavar="MANIFEST",
+                   help="update MANIFEST in place, re-hashing only files whose size or mtime "
+                        "changed since its last update")
+    p.add_argument("--sign", metavar="SECRET_KEY",
+                   help="sign the --output or --update file with this minisign secret key, into "
+                        "FILE.minisig (password from KOMPARU_SIGN_PASSWORD or a prompt)")
+    p.add_argument("--metadata", metavar="ATTRS",
+                   help="with --tree, also cover these comma-separated attributes: "
+                        f"{', '.join(METADATA_ATTRIBUTES)}")
+    p.set_defaults(func=_cmd_hash)
+
+    p = sub.add_parser("verify", help="re-hash a directory and check it against a manifest "
+                       "written by `komparu hash`, sha256sum, md5sum or shasum, or an SFV file")
+    p.add_argument("directory", help="directory to check")
+    p.add_argument("manifest", help="manifest file, http(s):// URL, or - for standard input")
+    p.add_argument("-a", "--hash", "--algorithm", dest="algorithm", metavar="NAME",
+                   help="hash algorithm of the manifest, as for hash (default: the one BSD-style "
+                        "lines name, else guessed from the digest length)")
+    p.add_argument("--sfv", action="store_true",
+                   help="read the manifest as SFV (path and CRC-32 per line); implied by a "
+                        ".sfv file name")
+    p.add_argument("--public-key", metavar="FILE",
+                   help="only trust the manifest if its minisign signature verifies "
+                        "with this public key")
+    p.add_argument("--signature", metavar="FILE",
+                   help="signature file or URL (default: MANIFEST.minisig)")
+    p.add_argument("--no-follow-symlinks", dest="follow_symlinks", action="store_false",
+                   help="do not follow symbolic links during traversal")
+    p.add_argument("-j", "--max-workers", type=int, default=0, metavar="N",
+                   help="thread pool size (0 = auto, 1 = sequential)")
+    p.add_argument("--ignore", action="append", default=[], metavar="PATTERN",
+                   help="glob pattern to exclude (repeatable)")
+    p.add_argument("--log", metavar="TARGET",
+                   help="also write the result as structured records to journald, syslog "
+                        "(/dev/log), syslog://HOST[:PORT] or auto")
+    p.add_argument("-q", "--quiet", action="store_true",
+                   help="print nothing, report result via exit code only")
+    p.set_defaults(func=_cmd_verify)
+
+    p = sub.add_parser("verify-zero", help="check that files or block devices hold only zero "
+                       "bytes, e.g. wiped disks or blank pre-allocated images")
+    p.add_argument("paths", nargs="+", metavar="PATH", help="file or block device to check")
+    p.add_argument("--chunk-size", type=int, default=65536, metavar="BYTES",
+                   help="chunk size in bytes (default: 65536)")
+    p.add_argument("--no-quick-check", dest="quick_check", action="store_false",
+                   help="do not sample key offsets before full scan")
+    p.add_argument("-q", "--quiet", action="store_true",
+                   help="print nothing, report result via exit code only")
+    p.set_defaults(func=_cmd_verify_zero)
+
+    p = sub.add_parser("diff-manifests", help="compare two manifests without reading the trees "
+                       "they describe")
+    p.add_argument("left", help="first manifest file, or - for standard input")
+    p.add_argument("right", help="second manifest file, or - for standard input")
+    p.add_argument("--ignore", action="append", default=[], metavar="PATTERN",
+                   help="glob pattern to exclude (repeatable)")
+    p.add_argument("-q", "--quiet", action="store_true",
+                   help="print nothing, report result via exit code only")
+    p.set_defaults(func=_cmd_diff_manifests)
+
+    p = sub.add_parser("snapshot", help="record paths, metadata and content digests of a "
+                       "directory in a compact binary file")
+    p.add_argument("directory", help="directory to snapshot")
+    p.add_argument("-a", "--hash", "--algorithm", dest="algorithm", default="sha256",
+                   metavar="NAME", help="hash algorithm (default: sha256)")
+    p.add_argument("-o", "--output", metavar="FILE",
+                   help="write the snapshot to FILE instead of standard output")
+    p.add_argument("--no-follow-symlinks", dest="follow_symlinks", action="store_false",
+                   help="record symbolic links instead of what they point to")
+    p.add_argument("-j", "--max-workers", type=int, default=0, metavar="N",
+                   help="thread pool size (0 = auto, 1 = sequential)")
+    p.add_argument("--ignore", action="append", default=[], metavar="PATTERN",
+                   help="glob pattern to exclude (repeatable)")
+    p.set_defaults(func=_cmd_snapshot)
+
+    p = sub.add_parser("diff-snapshot", help="compare a snapshot with a directory as it is "
+                       "now, or with another snapshot")
+    p.add_argument("snapshot", help="snapshot file, or - for standard input")
+    p.add_argument("current", help="directory, or a later snapshot file")
+    p.add_argument("--metadata", metavar="ATTRS",
+                   help="comma-separated metadata to compare: mode, owner, xattrs "
+                        "(default: all)")
+    p.add_argument("--no-follow-symlinks", dest="follow_symlinks", action="store_false",
+                   help="compare symbolic links instead of what they point to")
+    p.add_argument("-j", "--max-workers", type=int, default=0, metavar="N",
+                   help="thread pool size (0 = auto, 1 = sequential)")
+    p.add_argument("--ignore", action="append", default=[], metavar="PATTERN",
+                   help="glob pattern to exclude (repeatable)")
+    p.add_argument("-q", "--quiet", action="store_true",
+                   help="print nothing, report result via exit code only")
+    p.set_defaults(func=_cmd_diff_snapshot)
+
+    p = sub.add_parser("watch", help="compare two directories, then keep re-comparing where "
+                       "either changes, printing differences as they appear and are resolved")
+    p.add_argument("left", help="first directory")
+    p.add_argument("right", help="second directory")
+    p.add_argument("--settle", type=float, default=0.2, metavar="SECONDS",
+                   help="quiet time after a change before re-comparing (default: 0.2)")
+    p.add_argument("--poll", type=float, metavar="SECONDS",
+                   help="re-scan both trees this often instead of using inotify "
+                        "(for network file systems)")
+    p.add_argument("--chunk-size", type=int, default=65536, metavar="BYTES",
+                   help="chunk size in bytes (default: 65536)")
+    p.add_argument("--no-follow-symlinks", dest="follow_symlinks", action="store_false",
+                   help="do not follow symbolic links")
+    p.add_argument("-j", "--max-workers", type=int, default=0, metavar="N",
+                   help="thread pool size (0 = auto, 1 = sequential)")
+    p.add_argument("--ignore", action="append", default=[], metavar="PATTERN",
+                   help="glob pattern to exclude (repeatable)")
+    p.set_defaults(func=_cmd_watch)
+
+    p = sub.add_parser("sync", help="make the right directory match the left: copy what is "
+                       "missing or differs, fix permissions (prints the plan unless --apply)")
+    p.add_argument("left", help="directory to copy from")
+    p.add_argument("right", help="directory to change")
+    p.add_argument("--apply", action="store_true",
+                   help="make the changes; without it nothing is changed")
+    p.add_argument("--delete", action="store_true",
+                   help="also delete what the left directory does not have")
+    p.add_argument("-u", "--update", action="store_true",
+                   help="leave files alone that were modified later on the right")
+    p.add_argument("--chunk-size", type=int, default=65536, metavar="BYTES",
+                   help="chunk size in bytes (default: 65536)")
+    p.add_argument("--no-follow-symlinks", dest="follow_symlinks", action="store_false",
+                   help="copy symbolic links as links instead of what they point to")
+    p.add_argument("-j", "--max-workers", type=int, default=0, metavar="N",
+                   help="thread pool size (0 = auto: one per CPU, or picked for spinning "
+                        "disks and network file systems; 1 = sequential)")
+    p.add_argument("--ignore", action="append", default=[], metavar="PATTERN",
+                   help="glob pattern to exclude (repeatable)")
+    p.add_argument("--include-from", action="append", default=[], metavar="FILE",
+                   help="read rsync include patterns from FILE (repeatable)")
+    p.add_argument("--exclude-from", action="append", default=[], metavar="FILE",
+                   help="read rsync exclude patterns from FILE (repeatable; applied after "
+                        "--include-from)")
+    p.add_argument("-q", "--quiet", action="store_true",
+                   help="print nothing, report result via exit code only")
+    p.set_defaults(func=_cmd_sync)
+
+    p = sub.add_parser("patch", help="apply a patch from compare --patch, or a directory of "
+                       "them to a directory")
+    p.add_argument("target", help="file or directory the patch was made for")
+    p.add_argument("patch", help="patch file, or directory of .kpatch files")
+    p.add_argument("-o", "--output", metavar="PATH",
+                   help="write the patched file here instead of replacing TARGET "
+                        "(a single file only)")
+    p.add_argument("-q", "--quiet", action="store_true",
+                   help="print nothing, report result via exit code only")
+    p.set_defaults(func=_cmd_patch)
+
+    p = sub.add_parser("dupes", help="find byte-identical files in one or more directories "
+                       "(exit 1 if there are any)")
+    p.add_argument("directories", nargs="+", metavar="DIR", help="directory to search")
+    p.add_argument("--min-size", type=int, default=1, metavar="BYTES",
+                   help="skip files smaller than this (default: 1, leaving out empty files)")
+    p.add_argument("--hard-links", action="store_true",
+                   help="report hard links to the same file as duplicates")
+    p.add_argument("--no-follow-symlinks", dest="follow_symlinks", action="store_false",
+                   help="do not follow symbolic links during traversal")
+    p.add_argument("-j", "--max-workers", type=int, default=0, metavar="N",
+                   help="thread pool size (0 = auto, 1 = sequential)")
+    p.add_argument("--ignore", action="append", default=[], metavar="PATTERN",
+                   help="glob pattern to exclude (repeatable)")
+    p.add_argument("--savings", action="store_true",
+                   help="print the space duplicates waste and the largest groups instead of "
+                        "every group")
+    p.add_argument("--top", type=int, default=10, metavar="N",
+                   help="number of groups --savings lists (default: 10)")
+    p.add_argument("--apply", choices=DEDUP_METHODS, metavar="METHOD",
+                   help="replace duplicates by hard links to (hardlink) or clones of "
+                        "(reflink) the first path of each group; exit 1 if any are skipped")
+    p.add_argument("--dry-run", action="store_true",
+                   help="with --apply, list what would be replaced without changing anything")
+    p.add_argument("-q", "--quiet", action="store_true",
+                   help="print nothing, report result via exit code only")
+    p.set_defaults(func=_cmd_dupes)
+
+    p = sub.add_parser("copy-verify", help="copy a file or directory tree and check the copy "
+                       "against hashes taken while reading the source")
+    p.add_argument("source", help="file or directory to copy")
+    p.add_argument("destination", help="path of the copy; an existing directory receives a "
+                   "copied file under its name")
+    p.add_argument("-a", "--hash", "--algorithm", dest="algorithm", default="sha256",
+                   metavar="NAME", help="hash algorithm (default: sha256)")
+    p.add_argument("--manifest", metavar="FILE",
+                   help="also write a manifest (sha256sum format) of the copied files")
+    p.add_argument("--no-follow-symlinks", dest="follow_symlinks", action="store_false",
+                   help="copy symbolic links as links instead of what they point to")
+    p.add_argument("--ignore", action="append", default=[], metavar="PATTERN",
+                   help="glob pattern to exclude (repeatable)")
+    p.set_defaults(func=_cmd_copy_verify)
+
+    p = sub.add_parser("rsync-delta", help="answer a block signature on standard input with "
+                       "an rsync delta of FILE (what compare runs over --ssh)")
+    p.add_argument("file", help="file the delta rebuilds")
+    p.set_defaults(func=_cmd_rsync_delta)
+
+    p = sub.add_parser("agent", help="hash trees on request for komparu:// comparisons, on "
+                       "standard input and output or a TCP socket")
+    p.add_argument("--listen", metavar="HOST:PORT",
+                   help="serve connections on this address instead (HOST defaults to 127.0.0.1)")
+    p.add_argument("--root", action="append", default=[], metavar="DIR",
+                   help="only hash under this directory; relative paths start from the first "
+                        "(repeatable, required with --listen)")
+    p.add_argument("--token-file", metavar="FILE",
+                   help="only serve clients sending the secret in FILE (from "
+                        "KOMPARU_AGENT_TOKEN on the client)")
+    p.set_defaults(func=_cmd_agent)
+
+    p = sub.add_parser("serve", help="run comparisons on request over an HTTP/JSON API, with "
+                       "progress streams and results to fetch")
+    p.add_argument("--listen", default=f"127.0.0.1:{SERVE_PORT}", metavar="HOST:PORT",
+                   help=f"address to serve on (default: 127.0.0.1:{SERVE_PORT})")
+    p.add_argument("--root", action="append", default=[], metavar="DIR",
+                   help="only compare under this directory, refusing URLs; relative paths "
+                        "start from the first (repeatable)")
+    p.add_argument("--token-file", metavar="FILE",
+                   help="only serve requests sending the secret in FILE as a bearer token")
+    p.add_argument("--chunk-size", type=int, default=65536, metavar="BYTES",
+                   help="default chunk size in bytes (default: 65536)")
+    p.add_argument("-j", "--max-workers", type=int, default=0, metavar="N",
+                   help="thread pool size per comparison, and the most a request may ask for "
+                        "(0 = auto, 1 = sequential)")
+    p.add_argument("--max-jobs", type=int, default=100, metavar="N",
+                   help="finished comparisons kept for clients to read back (default: 100)")
+    p.add_argument("--max-running", type=int, default=4, metavar="N",
+                   help="comparisons run at once, more answered 503 (default: 4)")
+    p.add_argument("--webhook", metavar="URL",
+                   help="POST each comparison that finds differences or fails to this URL "
+                        "as JSON")
+    p.add_argument("--webhook-secret-file", metavar="FILE",
+                   help="sign webhook bodies with the key in FILE (X-Komparu-Signature header)")
+    p.add_argument("--metrics", action="store_true",
+                   help="serve Prometheus metrics of the comparisons at /metrics")
+    p.add_argument("--log", metavar="TARGET",
+                   help="also write each finished comparison as structured records to "
+                        "journald, syslog (/dev/log), syslog://HOST[:PORT] or auto")
+    p.add_argument("-q", "--quiet", action="store_true", help="do not log requests")
+    p.set_defaults(func=_cmd_serve)
+
+    p = sub.add_parser("bench", help="time komparu against cmp, diff -rq and rsync -nc on the "
+                       "same two files or directories")
+    p.add_argument("left", help="first file or directory")
+    p.add_argument("right", help="second file or directory")
+    p.add_argument("--tool", action="append", choices=BENCH_TOOLS, dest="tools",
+                   help="only run this tool (repeatable; default: all of them)")
+    p.add_argument("--repeat", type=int, default=3, metavar="N",
+                   help="timed runs per tool; the table shows the median (default: 3)")
+    p.add_argument("--warmup", type=int, default=1, metavar="N",
+                   help="untimed runs per tool before those, warming the page cache "
+                        "(default: 1)")
+    p.add_argument("--json", action="store_true",
+                   help="print the results as a JSON array instead of a table")
+    p.set_defaults(func=_cmd_bench)
+
+    p = sub.add_parser("completions", help="print a completion script for bash, zsh, fish or "
+                       "PowerShell covering every command and option")
+    p.add_argument("shell", choices=SHELLS, help="shell to complete in")
+    p.set_defaults(func=_cmd_completions)
+
+    return parser, sub.choices
+
+
+def _print_dir_result(result: DirResult, policy: SeverityPolicy | None,
+                      similarities: dict[str, float] | None = None,
+                      routes: dict[str, str] | None = None,
+                      details: dict[str, list[str]] | None = None,
+                      regions: dict[str, tuple[DiffRegion, ...]] | None = None,
+                      sides: tuple[str, str] = ("left", "right")) -> None:
+    """Print a directory result; *details* are extra notes per differing path."""
+    severities = policy.classify(result) if policy is not None else {}
+    routes = routes or {}
+    details = details or {}
+    regions = regions or {}
+
+    def line(text: str, path: str) -> None:
+        severity = severities.get(path)
+        print(f"{text} [{severity.value}]" if severity is not None else text)
+
+    diff = sorted(result.diff, key=path_key)
+    if similarities is not None:
+        # Most diverged first; entries without a score (e.g. type mismatches) last
+        diff.sort(key=lambda p: similarities.get(p, 2.0))
+    unverifiable = result.unverifiable
+    for path in diff:
+        if path in unverifiable:
+            continue
+        if result.diff[path] is DiffReason.UNSTABLE:
+            line(f"unstable: {path} (changed while it was compared)", path)
+            continue
+        if result.diff[path] is DiffReason.LOCKED:
+            line(f"locked: {path} (held by a writer, not compared)", path)
+            continue
+        if result.diff[path] is DiffReason.VANISHED:
+            line(f"vanished: {path} (deleted before it was compared)", path)
+            continue
+        detail = result.diff[path].value
+        if similarities is not None and path in similarities:
+            detail += f", {similarities[path]:.1%} similar"
+        for note in details.get(path, ()):
+            detail += f", {note}"
+        if path in routes:
+            detail += f", compared as {routes[path]}"
+        line(f"differ: {path} ({detail})", path)
+        for region in regions.get(path, ()):
+            print(f"  {_describe_region(region)}")
+    for path in sorted(routes.keys() - result.diff.keys(), key=path_key):
+        print(f"equal: {path} (compared as {routes[path]})")
+    for path in sorted(result.only_left, key=path_key):
+        line(f"only in {sides[0]}: {path}", path)
+    for path in sorted(result.only_right, key=path_key):
+        line(f"only in {sides[1]}: {path}", path)
+    for path in sorted(unverifiable, key=path_key):
+        reason = result.diff.get(path)
+        line(f"unverifiable: {path} ({reason.value if reason else 'permission denied'})", path)
+    if unverifiable:
+        print(f"{len(unverifiable)} unverifiable (could not be read)")
+
+
+def _describe_summary(summary: DirSummary, sides: tuple[str, str]) -> str:
+    counts = ((summary.diff, "differ"), (summary.only_left, f"only in {sides[0]}"),
+              (summary.only_right, f"only in {sides[1]}"), (summary.errors, "unreadable"),
+              (summary.equal, "equal"))
+    text = ", ".join(f"{count} {label}" for count, label in counts if count)
+    if summary.differing:
+        text += f"; {_describe_bytes(summary.bytes)} not equal"
+    return text
+
+
+def _print_rollup(summaries: dict[str, DirSummary], depth: int,
+                  sides: tuple[str, str]) -> None:
+    """Print the top and the directories down to *depth* that hold something not equal."""
+    print("by directory:")
+    for path, summary in summaries.items():
+        level = path.count("/") + 1 if path else 0
+        if level > depth or (path and not summary.differing):
+            continue
+        name = path.rsplit("/", 1)[-1] if path else "."
+        print(f"  {'  ' * level}{name}/ {_describe_summary(summary, sides)}")
+
+
+def _describe_chunks(diff: ChunkDiff) -> str:
+    parts = [f"{diff.shared_fraction:.1%} of chunks shared"]
+    for count, noun in ((diff.insertions, "insertion"), (diff.deletions, "deletion"),
+                        (diff.changes, "change")):
+        if count:
+            parts.append(f"{count} {noun}" + ("" if count == 1 else "s"))
+    return ", ".join(parts)
+
+
+def _print_savings(groups: list[DuplicateGroup], top: int) -> None:
+    copies = sum(len(group.paths) - 1 for group in groups)
+    print(f"{sum(group.wasted for group in groups)} bytes wasted by {copies} duplicate "
+          f"file{'' if copies == 1 else 's'} in {len(groups)} "
+          f"group{'' if len(groups) == 1 else 's'}")
+    for group in sorted(groups, key=lambda g: g.wasted, reverse=True)[:top]:
+        print(f"  {group.wasted} bytes: {len(group.paths)} copies of {group.paths[0]} "
+              f"({group.size} bytes each)")
+
+
+def _print_sync_plan(plan: SyncPlan, as_json: bool) -> None:
+    if as_json:
+        print(json.dumps(plan.to_dict(), indent=2))
+        return
+    for step in plan.steps:
+        print(_describe_step(step))
+    for path in sorted(plan.errors, key=path_key):
+        print(f"error: {path}")
+
+
+def _describe_step(step: SyncStep) -> str:
+    line = f"{step.action.value} {step.path}"
+    if step.mode is not None:
+        line += f" {step.mode:04o}"
+    if step.action is SyncAction.COPY and step.size is not None:
+        line += f" ({step.size} byte{'' if step.size == 1 else 's'})"
+    return line
+
+
+def _describe_delta(delta: int, size: int) -> str:
+    return f"delta about {delta} of {size} bytes"
+
+
+def _describe_metadata(changes: dict[str, tuple[Any, Any]]) -> list[str]:
+    notes = []
+    if "mode" in changes:
+        mode_a, mode_b = changes["mode"]
+        notes.append(f"mode {mode_a:04o} -> {mode_b:04o}")
+    if "owner" in changes:
+        (uid_a, gid_a), (uid_b, gid_b) = changes["owner"]
+        notes.append(f"owner {uid_a}:{gid_a} -> {uid_b}:{gid_b}")
+    if "xattrs" in changes:
+        xattrs_a, xattrs_b = changes["xattrs"]
+        names = sorted(name for name in xattrs_a.keys() | xattrs_b.keys()
+                       if xattrs_a.get(name) != xattrs_b.get(name))
+        notes.append(f"xattrs {', '.join(names)}")
+    for name in ("birthtime", "ctime"):
+        if name in changes:
+            time_a, time_b = changes[name]
+            notes.append(f"{name} {_describe_time(time_a)} -> {_describe_time(time_b)}")
+    if "flags" in changes:
+        flags_a, flags_b = changes["flags"]
+        notes.append(f"flags {flags_a:#x} -> {flags_b:#x}")
+    return notes
+
+
+def _describe_time(ns: int | None) -> str:
+    if ns is None:
+        return "unknown"
+    seconds, fraction = divmod(ns, 1_000_000_000)
+    return f"{time.strftime('%Y-%m-%dT%H:%M:%S', time.gmtime(seconds))}.{fraction:09d}Z"
+
+
+def _describe_region(region: DiffRegion) -> str:
+    if region.kind is RegionKind.INSERTED:
+        return f"inserted: {region.size_b} bytes at {region.offset_b}"
+    if region.kind is RegionKind.DELETED:
+        return f"deleted: {region.size_a} bytes at {region.offset_a}"
+    return (f"changed: {region.size_a} bytes at {region.offset_a} -> "
+            f"{region.size_b} bytes at {region.offset_b}")
+
+
+def _print_fuzzy_pairs(left: str, right: str, result: DirResult, threshold: int) -> None:
+    """Pair each file only in *left* with its closest file only in *right*."""
+
+    def hashes(root: str, paths: set[str]) -> dict[str, str]:
+        return {path: fuzzy_hash(os.path.join(root, path)) for path in sorted(paths, key=path_key)
+                if os.path.isfile(os.path.join(root, path))}
+
+    candidates = hashes(right, result.only_right)
+    for path, digest in hashes(left, result.only_left).items():
+        scores = [(fuzzy_score(digest, other), match) for match, other in candidates.items()]
+        score, match = max(scores, key=lambda s: s[0], default=(0, ""))
+        if match and score >= threshold:
+            print(f"similar: {path} ~ {match} (fuzzy score {score})")
+
+
+def _policy_from_args(args: argparse.Namespace) -> SeverityPolicy | None:
+    if not args.severity and args.default_severity is None:
+        return None
+    rules = {}
+    for rule in args.severity:
+        category, sep, level = rule.partition("=")
+        if not sep:
+            raise ValueError(f"--severity expects CATEGORY=LEVEL, got {rule!r}")
+        rules[category.strip()] = level.strip()
+    return SeverityPolicy(rules, args.default_severity or Severity.ERROR)
+
+
+def _unreadable(path: str, permission: bool) -> SourceReadError:
+    if permission:
+        return SourcePermissionError(errno.EACCES, "cannot read", path)
+    return SourceReadError(errno.EIO, "cannot read", path)
+
+
+class _StopOnError(CompareVisitor):
+    """Cancels a directory comparison at the first file it cannot read (--on-error fail)."""
+
+    def __init__(self, root: str, cancel: CancelToken | None = None) -> None:
+        self.root = root
+        self.cancel = cancel if cancel is not None else CancelToken()
+
+    def _stop(self, entry: DiffEntry, permission: bool) -> None:
+        self.cancel.cancel()
+        raise _unreadable(os.path.join(self.root, entry.path), permission)
+
+    def on_entry_finished(self, entry: DiffEntry) -> None:
+        if entry.reason in UNREADABLE:
+            self._stop(entry, False)
+
+    def on_error(self, entry: DiffEntry) -> None:
+        self._stop(entry, True)
+
+
+class _RunStatus(CompareVisitor):
+    """How far a comparison got, for the line a status signal prints.
+
+    The counts come from the engine through *cancel*; the files being
+    compared are known only when it also runs as the visitor.
+    """
+
+    def __init__(self, inner: CompareVisitor | None, cancel: CancelToken,
+                 active: Iterable[str] = ()) -> None:
+        self.inner = inner
+        self.cancel = cancel
+        self.active = dict.fromkeys(active)  # started and not finished, oldest first
+        self.start = time.monotonic()
+
+    def on_dir_entered(self, path: str) -> None:
+        if self.inner is not None:
+            self.inner.on_dir_entered(path)
+
+    def on_dir_left(self, path: str) -> None:
+        if self.inner is not None:
+            self.inner.on_dir_left(path)
+
+    def on_entry_started(self, path: str) -> None:
+        self.active[path] = None
+        if self.inner is not None:
+            self.inner.on_entry_started(path)
+
+    def on_entry_finished(self, entry: DiffEntry) -> None:
+        self.active.pop(entry.path, None)
+        if self.inner is not None:
+            self.inner.on_entry_finished(entry)
+
+    def on_error(self, entry: DiffEntry) -> None:
+        if self.inner is not None:
+            self.inner.on_error(entry)
+
+    def line(self) -> str:
+        elapsed = time.monotonic() - self.start
+        read, files = self.cancel.bytes_read, self.cancel.entries_done
+        minutes, seconds = divmod(int(elapsed), 60)
+        line = (f"komparu: {files} entr{'y' if files == 1 else 'ies'} done, "
+                f"{_describe_bytes(read)} read "
+                f"({_describe_bytes(read / elapsed if elapsed > 0 else 0)}/s), "
+                f"{minutes // 60}:{minutes % 60:02d}:{seconds:02d} elapsed")
+        active = list(self.active)
+        if active:
+            more = f" and {len(active) - 3} more" if len(active) > 3 else ""
+            line += f"; comparing {', '.join(active[:3])}{more}"
+        return line
+
+
+def _describe_bytes(count: float) -> str:
+    for unit in ("bytes", "KiB", "MiB", "GiB", "TiB"):
+        if count < 1024 or unit == "TiB":
+            break
+        count /= 1024
+    return f"{count:.0f} bytes" if unit == "bytes" else f"{count:.1f} {unit}"
+
+
+@contextlib.contextmanager
+def _status_on_signal(status: _RunStatus) -> Iterator[None]:
+    """Print *status* to stderr at each SIGUSR1 (or SIGINFO) while the block runs.
+
+    The signals are blocked in this thread and taken by a waiting one, so
+    the line comes out at once even while the C engine holds this thread
+    for hours; a signal delivered to some other thread runs the handler
+    here instead, once the engine returns.
+    """
+    if not _STATUS_SIGNALS or threading.current_thread() is not threading.main_thread():
+        yield
+        return
+
+    def report(*_: Any) -> None:
+        with contextlib.suppress(OSError, ValueError):
+            print(status.line(), file=sys.stderr, flush=True)
+
+    previous = {signum: signal.signal(signum, report) for signum in _STATUS_SIGNALS}
+    mask = signal.pthread_sigmask(signal.SIG_BLOCK, _STATUS_SIGNALS)
+    done = threading.Event()
+
+    def wait() -> None:
+        while True:
+            signal.sigwait(_STATUS_SIGNALS)
+            if done.is_set():
+                return
+            report()
+
+    waiter = threading.Thread(target=wait, name="komparu-status", daemon=True)
+    waiter.start()
+    try:
+        yield
+    finally:
+        done.set()
+        signal.pthread_kill(waiter.ident, _STATUS_SIGNALS[0])  # type: ignore[arg-type]
+        waiter.join()
+        signal.pthread_sigmask(signal.SIG_SETMASK, mask)
+        for signum, handler in previous.items():
+            signal.signal(signum, signal.SIG_DFL if handler is None else handler)
+
+
+def _apply_on_error(result: DirResult, on_error: str) -> DirResult:
+    """*result* under --on-error: fail raises for an unreadable file, skip drops them."""
+    unverifiable = result.unverifiable
+    if not unverifiable or on_error == "report":
+        return result
+    if on_error == "fail":
+        path = min(unverifiable, key=path_key)
+        raise _unreadable(path, path in result.errors)
+    diff = {path: reason for path, reason in result.diff.items() if path not in unverifiable}
+    return DirResult(equal=not (diff or result.only_left or result.only_right), diff=diff,
+                     only_left=result.only_left, only_right=result.only_right)
+
+
+def _options_from_args(args: argparse.Namespace) -> CompareOptions:
+    return CompareOptions(
+        chunk_size=args.chunk_size,
+        size_precheck=args.size_precheck,
+        quick_check=args.quick_check,
+        follow_symlinks=args.follow_symlinks,
+        max_workers=args.max_workers,
+        ignore=tuple(args.ignore),
+        lock=(LockPolicy(args.lock_timeout)
+              if args.lock or args.lock_timeout is not None else None),
+    )
+
+
+class _Bytes(ContentComparator):
+    """--compare-as PATTERN=bytes: what the engine found different stays different."""
+
+    def compare(self, path_a: str, path_b: str) -> bool:
+        return False
+
+
+def _text_comparator(args: argparse.Namespace) -> TextComparator:
+    return TextComparator(args.chunk_size,
+                          ignore_trailing_space=args.ignore_trailing_space,
+                          collapse_whitespace=args.ignore_space_change,
+                          decode=args.decode,
+                          ignore_bom=args.ignore_bom,
+                          ignore_final_newline=args.ignore_final_newline)
+
+
+def _compare_as(args: argparse.Namespace, strategy: str,
+                on_route: Callable[[str, str, str], None] | None) -> ContentComparator:
+    chunk_size = args.chunk_size
+    factories: dict[str, Callable[[], ContentComparator]] = {
+        "bytes": _Bytes,
+        "text": lambda: _text_comparator(args),
+        "json": JsonComparator,
+        "yaml": YamlComparator,
+        "xml": XmlComparator,
+        "decompress": lambda: DecompressComparator(chunk_size),
+        "tar": lambda: TarComparator(chunk_size, ignore_mtime=args.tar_ignore_mtime,
+                                     ignore_order=args.tar_ignore_order),
+        "zip": lambda: ZipComparator(chunk_size, ignore_timestamps=args.zip_ignore_timestamps,
+                                     ignore_metadata=args.zip_ignore_metadata),
+        "deb": lambda: DebComparator(chunk_size),
+        "image": lambda: ImageComparator(chunk_size),
+        "audio": lambda: AudioComparator(chunk_size),
+        "pdf": PdfComparator,
+        "sqlite": SqliteComparator,
+        "parquet": ParquetComparator,
+        "executable": ExecutableComparator,
+        "content-aware": lambda: SniffingComparator(chunk_size, on_route=on_route),
+    }
+    return factories[strategy]()
+
+
+class _AttributeComparator(ContentComparator):
+    """The comparator a path's text and compare attributes pick, else the one its pattern does."""
+
+    def __init__(self, attributes: PathAttributes, root: str,
+                 strategies: dict[str, ContentComparator],
+                 comparators: dict[str, ContentComparator]) -> None:
+        self._attributes = attributes
+        self._root = root
+        self._strategies = strategies
+        self._comparators = comparators
+
+    def compare(self, path_a: str, path_b: str) -> bool:
+        path = os.path.relpath(path_a, self._root).replace(os.sep, "/")
+        found = self._attributes.lookup(path)
+        strategy = found.get("compare")
+        if strategy is None and "text" in found:
+            strategy = "text" if found["text"] else "bytes"
+        if strategy is not None:
+            return self._strategies[strategy or "bytes"].compare(path_a, path_b)
+        comparator = next((comparator for pattern, comparator in self._comparators.items()
+                           if fnmatch(path, pattern)), None)
+        return comparator is not None and comparator.compare(path_a, path_b)
+
+
+def _attributes_from_args(args: argparse.Namespace, local: bool) -> PathAttributes | None:
+    """LEFT's rules file, then every --attributes one; None if there are no rules."""
+    if args.attributes and not local:
+        raise ValueError("--attributes needs two local directories")
+    if not local:
+        return None
+    files = list(args.attributes)
+    own = os.path.join(args.left, ATTRIBUTES_FILE)
+    if args.tree_attributes and os.path.isfile(own):
+        files.insert(0, own)
+    attributes = PathAttributes.from_files(files)
+    for strategy, where in attributes.values("compare"):
+        if strategy is not False and strategy not in COMPARE_AS:
+            raise ValueError(f"{where}: unknown strategy {strategy!r} "
+                             f"(expected {', '.join(COMPARE_AS)})")
+    # Like the content comparators, these read files outside the engine
+    reads = [value for name in ("text", "compare") for value, _ in attributes.values(name)
+             if value not in (False, "bytes")]
+    if args.max_bytes is not None and reads:
+        raise ValueError("--max-bytes cannot be combined with the text and compare attributes")
+    return attributes or None
+
+
+def _attributes_of(path_attributes: PathAttributes,
+                   default: Collection[str]) -> Callable[[str], Collection[str]]:
+    """Metadata attributes of each path: its metadata= list, none if unset, else *default*."""
+    validate_attributes(default, METADATA_ATTRIBUTES + EXTRA_ATTRIBUTES)
+
+    def attributes_of(path: str) -> Collection[str]:
+        value = path_attributes.lookup(path).get("metadata")
+        if value is None or value is True:
+            return default
+        return () if value is False else tuple(value.split(","))
+
+    return attributes_of
+
+
+def _comparators_from_args(
+    args: argparse.Namespace,
+    on_route: Callable[[str, str, str], None] | None = None,
+) -> dict[str, ContentComparator]:
+    comparators: dict[str, ContentComparator] = {}
+    for rule in args.filter:
+        pattern, sep, command = rule.partition("=")
+        if not sep or not pattern.strip() or not command.strip():
+            raise ValueError(f"--filter expects PATTERN=COMMAND, got {rule!r}")
+        comparators[pattern.strip()] = FilterComparator(shlex.split(command), args.chunk_size)
+    for rule in args.compare_as:
+        pattern, sep, strategy = (part.strip() for part in rule.rpartition("="))
+        if not sep or not pattern:
+            raise ValueError(f"--compare-as expects PATTERN=STRATEGY, got {rule!r}")
+        if strategy not in COMPARE_AS:
+            raise ValueError(f"--compare-as: unknown strategy {strategy!r} "
+                             f"(expected {', '.join(COMPARE_AS)})")
+        comparators.setdefault(pattern, _compare_as(args, strategy, on_route))
+    # The flags below only cover patterns no --filter or --compare-as rule named
+    rules, comparators = comparators, {}
+    if args.json:
+        comparators["*.json"] = JsonComparator()
+    if args.yaml:
+        comparators["*.yaml"] = comparators["*.yml"] = YamlComparator()
+    if args.xml:
+        comparators["*.xml"] = XmlComparator()
+    reproducible = args.reproducible
+    if args.tar or args.tar_ignore_mtime or args.tar_ignore_order or reproducible:
+        tar = TarComparator(args.chunk_size,
+                            ignore_mtime=args.tar_ignore_mtime or reproducible,
+                            ignore_order=args.tar_ignore_order or reproducible)
+        comparators.update(dict.fromkeys(_TARBALLS, tar))
+    if args.zip or args.zip_ignore_timestamps or args.zip_ignore_metadata or reproducible:
+        zip_ = ZipComparator(args.chunk_size,
+                             ignore_timestamps=args.zip_ignore_timestamps or reproducible,
+                             ignore_metadata=args.zip_ignore_metadata)
+        comparators.update(dict.fromkeys(_ZIPS, zip_))
+    if reproducible:
+        deb = DebComparator(args.chunk_size, ignore_mtime=True, ignore_order=True)
+        comparators.update(dict.fromkeys(_DEBS, deb))
+    if args.image:
+        comparators.update(dict.fromkeys(_IMAGES, ImageComparator(args.chunk_size)))
+    if args.audio:
+        comparators.update(dict.fromkeys(_AUDIO, AudioComparator(args.chunk_size)))
+    if args.pdf:
+        comparators["*.pdf"] = PdfComparator()
+    if args.sqlite:
+        comparators.update(dict.fromkeys(_SQLITE, SqliteComparator()))
+    if args.parquet:
+        comparators.update(dict.fromkeys(_PARQUET, ParquetComparator()))
+    if args.decompress:
+        comparators.update(dict.fromkeys(_COMPRESSED, DecompressComparator(args.chunk_size)))
+    if (args.text or args.ignore_trailing_space or args.ignore_space_change
+            or args.ignore_bom or args.ignore_final_newline or args.decode):
+        comparators["*"] = _text_comparator(args)
+    if args.content_aware:
+        # Explicit patterns above still win; --text rules cover what is not sniffed
+        comparators["*"] = SniffingComparator(args.chunk_size, fallback=comparators.get("*"),
+                                              on_route=on_route)
+    return {**rules, **{pattern: comparator for pattern, comparator in comparators.items()
+                        if pattern not in rules}}
+
+
+def _remote_kind(arg: str) -> str | None:
+    if arg.startswith("s3://"):
+        return "s3"
+    if arg.startswith(STORE_SCHEMES):
+        return "store"
+    if arg.startswith("komparu://"):
+        return "agent"
+    # A local path wins over a host:path spec of the same name
+    if not os.path.exists(arg) and parse_ssh_target(arg) is not None:
+        return "ssh"
+    return None
+
+
+def _ssh_join(remote: str, path: str) -> str:
+    host, directory = parse_ssh_target(remote) or (remote, ".")
+    return f"{host}:{posixpath.join(directory, path)}"
+
+
+def _note_chunks(args: argparse.Namespace, notes: list[str],
+                 regions: dict[str, tuple[DiffRegion, ...]], path: str,
+                 chunks: ChunkDiff) -> None:
+    if args.chunks:
+        notes.append(_describe_chunks(chunks))
+    if args.regions:
+        regions[path] = chunks.regions
+
+
+def _compare_file_ssh(args: argparse.Namespace, policy: SeverityPolicy | None, local: str,
+                      remote: str) -> int:
+    # Only regions of the mismatch cross the network, so they come for free
+    chunks = chunk_diff_ssh(local, remote, ssh_command=args.ssh,
+                            remote_command=args.remote_komparu)
+    equal = chunks.size_a == chunks.size_b and not chunks.regions
+    if args.log_sink is not None:
+        args.log_sink.file_result(equal, args.left, args.right)
+    if equal:
+        return EXIT_EQUAL
+    severity = policy.of(DiffReason.CONTENT_MISMATCH) if policy is not None else None
+    if not args.quiet:
+        message = f"{args.left} {args.right} differ"
+        if args.chunks:
+            message += f" ({_describe_chunks(chunks)})"
+        print(f"{message} [{severity.value}]" if severity is not None else message)
+        for region in chunks.regions if args.regions else ():
+            print(f"  {_describe_region(region)}")
+    return EXIT_DIFFERENT if severity is None else _SEVERITY_EXIT[severity]
+
+
+def _compare_cmp(args: argparse.Namespace, content_rules: bool) -> int:
+    """Two files byte by byte, reported in GNU cmp's words and exit codes."""
+    if args.left is None or args.right is None or args.git is not None:
+        raise ValueError("--cmp-compat compares two files")
+    if (content_rules or args.oci or args.disk_image or args.fs_snapshots or args.archive
+            or args.names_only or args.metadata_only or args.metadata is not None or args.tui
+            or args.diff_compat):
+        raise ValueError("--cmp-compat compares raw bytes; the archive, metadata, content "
+                         "comparator, --diff-compat and --tui options do not apply")
+    names = (args.left, args.right)
+    try:
+        with contextlib.ExitStack() as stack:
+            files = [sys.stdin.buffer if name == "-" else stack.enter_context(open(name, "rb"))
+                     for name in names]
+            offset = lines = 0
+            newline = False  # whether the bytes so far end with one
+            while True:
+                a, b = (f.read(args.chunk_size) for f in files)
+                at = first_diff(a, b)
+                if at is not None or not a:
+                    break
+                offset, lines, newline = offset + len(a), lines + a.count(b"\n"), a.endswith(b"\n")
+    except OSError as exc:
+        if not args.quiet:
+            print(f"cmp: {exc.filename}: {exc.strerror}", file=sys.stderr)
+        return EXIT_ERROR
+    if args.log_sink is not None:
+        args.log_sink.file_result(at is None, *names)
+    if at is None:
+        return EXIT_EQUAL
+    if args.quiet:
+        return EXIT_DIFFERENT
+    if at < min(len(a), len(b)):
+        line = lines + a.count(b"\n", 0, at) + 1
+        print(f"{names[0]} {names[1]} differ: byte {offset + at + 1}, line {line}")
+        return EXIT_DIFFERENT
+    # One file is a prefix of the other
+    shorter = names[len(b) < len(a)]
+    if at:
+        offset, lines, newline = offset + at, lines + a.count(b"\n", 0, at), a[at - 1:at] == b"\n"
+    if offset == 0:
+        print(f"cmp: EOF on {shorter} which is empty", file=sys.stderr)
+    elif newline:
+        print(f"cmp: EOF on {shorter} after byte {offset}, line {lines}", file=sys.stderr)
+    else:
+        print(f"cmp: EOF on {shorter} after byte {offset}, in line {lines + 1}",
+              file=sys.stderr)
+    return EXIT_DIFFERENT
+
+
+_DIFF_FILE_TYPES = ((stat.S_ISDIR, "directory"), (stat.S_ISLNK, "symbolic link"),
+                    (stat.S_ISFIFO, "fifo"), (stat.S_ISSOCK, "socket"),
+                    (stat.S_ISCHR, "character special file"),
+                    (stat.S_ISBLK, "block special file"))
+
+
+def _diff_file_type(st: os.stat_result) -> str:
+    if stat.S_ISREG(st.st_mode):
+        return "regular file" if st.st_size else "regular empty file"
+    return next((name for test, name in _DIFF_FILE_TYPES if test(st.st_mode)), "weird file")
+
+
+def _compare_diff(args: argparse.Namespace, options: CompareOptions,
+                  comparators: dict[str, ContentComparator], path_filter: PathFilter | None) -> int:
+    """Two directories (or files) reported in ``diff -rq``'s words and exit codes."""
+    if args.left is None or args.right is None or args.git is not None:
+        raise ValueError("--diff-compat compares two directories or files")
+    if (args.oci or args.disk_image or args.fs_snapshots or args.archive or args.names_only
+            or args.metadata_only or args.metadata is not None or args.tui
+            or _remote_kind(args.left)
+            or _remote_kind(args.right)):
+        raise ValueError("--diff-compat compares local directories and files; the archive, "
+                         "metadata, remote and --tui options do not apply")
+    left, right = args.left, args.right
+    try:
+        st_a, st_b = os.stat(left), os.stat(right)
+    except OSError as exc:
+        if not args.quiet:
+            print(f"diff: {exc.filename}: {exc.strerror}", file=sys.stderr)
+        return EXIT_ERROR
+    if stat.S_ISDIR(st_a.st_mode) != stat.S_ISDIR(st_b.st_mode):
+        # Like diff, a file against a directory is compared with the file of its name there
+        if stat.S_ISDIR(st_a.st_mode):
+            left = os.path.join(left, os.path.basename(right))
+        else:
+            right = os.path.join(right, os.path.basename(left))
+    if not (stat.S_ISDIR(st_a.st_mode) and stat.S_ISDIR(st_b.st_mode)):
+        try:
+            equal = compare(left, right, options=options)
+        except OSError as exc:
+            if not args.quiet:
+                print(f"diff: {exc.filename or right}: {exc.strerror}", file=sys.stderr)
+            return EXIT_ERROR
+        if not equal:
+            comparator = next((c for pattern, c in comparators.items()
+                               if fnmatch(os.path.basename(left), pattern)), None)
+            equal = comparator is not None and comparator.compare(left, right)
+        if args.log_sink is not None:
+            args.log_sink.file_result(equal, left, right)
+        if not equal and not args.quiet:
+            print(f"Files {left} and {right} differ")
+        return EXIT_EQUAL if equal else EXIT_DIFFERENT
+
+    result = compare_dir(left, right, options=options.replace(path_filter=path_filter),
+                         comparators=comparators or None)
+    if args.log_sink is not None:
+        args.log_sink.dir_result(result, left, right)
+    # diff also reports what the DirResult leaves out: empty directories, and
+    # a directory only on one side as itself rather than its files
+    exclude = make_exclude(list(options.ignore), path_filter, left, right,
+                           options.follow_symlinks)
+    (entries_a, errors_a), (entries_b, errors_b) = (
+        walk_tree(root, options.follow_symlinks, exclude) for root in (left, right))
+    different = False
+    skip = None  # directory whose contents diff does not descend into
+    for path in sorted(entries_a.keys() | entries_b.keys(), key=tree_key):
+        if skip is not None and path.startswith(skip + "/"):
+            continue
+        a, b = entries_a.get(path), entries_b.get(path)
+        if a is None or b is None:
+            parent, _, name = path.rpartition("/")
+            root = left if b is None else right
+            line = f"Only in {os.path.join(root, parent) if parent else root}: {name}"
+            skip = path
+        elif stat.S_IFMT(a.st_mode) != stat.S_IFMT(b.st_mode):
+            line = (f"File {os.path.join(left, path)} is a {_diff_file_type(a)} while file "
+                    f"{os.path.join(right, path)} is a {_diff_file_type(b)}")
+            skip = path
+        elif path in result.diff:
+            line = f"Files {os.path.join(left, path)} and {os.path.join(right, path)} differ"
+        else:
+            continue
+        different = True
+        if not args.quiet:
+            print(line)
+    unreadable = ({os.path.join(left, path) for path in errors_a}
+                  | {os.path.join(right, path) for path in errors_b})
+    for path in result.errors:
+        name = os.path.join(left, path)
+        unreadable.add(name if not os.access(name, os.R_OK) else os.path.join(right, path))
+    if not args.quiet:
+        for name in sorted(unreadable):
+            print(f"diff: {name}: Permission denied", file=sys.stderr)
+    if unreadable:
+        return EXIT_ERROR
+    return EXIT_DIFFERENT if different else EXIT_EQUAL
+
+
+def _compare_oci(args: argparse.Namespace, options: CompareOptions,
+                 policy: SeverityPolicy | None, content_rules: bool) -> int:
+    if args.left is None or args.right is None or args.git is not None:
+        raise ValueError("--oci compares two images, or an image and a directory")
+    if (content_rules or args.archive or args.names_only or args.metadata_only
+            or args.metadata is not None or args.savings or args.similarity or args.chunks
+            or args.regions or args.delta_size or args.fuzzy or args.fuzzy_threshold is not None):
+        raise ValueError("--oci compares image file systems; the archive, metadata, content "
+                         "comparator and similarity options do not apply")
+    if os.path.isdir(args.right) and not os.path.isfile(os.path.join(args.right, "oci-layout")):
+        result = compare_image_dir(args.left, args.right, platform=args.platform,
+                                   follow_symlinks=False, ignore=list(options.ignore),
+                                   max_workers=options.max_workers)
+        sides = ("image", "tree")
+    else:
+        result = compare_images(args.left, args.right, platform=args.platform,
+                                ignore=list(options.ignore))
+        sides = ("left", "right")
+    if args.log_sink is not None:
+        args.log_sink.dir_result(result, args.left, args.right, sides=sides)
+    if not args.quiet:
+        _print_dir_result(result, policy, sides=sides)
+    if policy is None:
+        return EXIT_EQUAL if result.equal else EXIT_DIFFERENT
+    highest = policy.highest(result)
+    return EXIT_EQUAL if highest is None else _SEVERITY_EXIT[highest]
+
+
+def _compare_disk_image(args: argparse.Namespace, options: CompareOptions,
+                        policy: SeverityPolicy | None, content_rules: bool) -> int:
+    if args.left is None or args.right is None or args.git is not None:
+        raise ValueError("--disk-image compares two disk images, or an image and a directory")
+    if (content_rules or args.oci or args.archive or args.names_only or args.metadata_only
+            or args.metadata is not None or args.savings or args.similarity or args.chunks
+            or args.regions or args.delta_size or args.fuzzy or args.fuzzy_threshold is not None):
+        raise ValueError("--disk-image compares file systems in images; the --oci, archive, "
+                         "metadata, content comparator and similarity options do not apply")
+    if os.path.isdir(args.right):
+        result = compare_disk_image_dir(args.left, args.right, follow_symlinks=False,
+                                        ignore=list(options.ignore))
+        sides = ("image", "tree")
+    else:
+        result = compare_disk_images(args.left, args.right, ignore=list(options.ignore))
+        sides = ("left", "right")
+    if args.log_sink is not None:
+        args.log_sink.dir_result(result, args.left, args.right, sides=sides)
+    if not args.quiet:
+        _print_dir_result(result, policy, sides=sides)
+    if policy is None:
+        return EXIT_EQUAL if result.equal else EXIT_DIFFERENT
+    highest = policy.highest(result)
+    return EXIT_EQUAL if highest is None else _SEVERITY_EXIT[highest]
+
+
+def _compare_fs_snapshots(args: argparse.Namespace, options: CompareOptions,
+                          policy: SeverityPolicy | None, content_rules: bool) -> int:
+    if args.left is None or args.right is None or args.git is not None:
+        raise ValueError("--fs-snapshots compares a directory in two snapshots")
+    if (content_rules or args.oci or args.disk_image or args.archive or args.names_only
+            or args.metadata_only or args.metadata is not None or args.savings
+            or args.similarity or args.chunks or args.regions or args.delta_size or args.fuzzy
+            or args.fuzzy_threshold is not None):
+        raise ValueError("--fs-snapshots compares files in snapshots by content; the --oci, "
+                         "--disk-image, archive, metadata, content comparator and similarity "
+                         "options do not apply")
+    result = compare_fs_snapshots(args.left, args.right, ignore=list(options.ignore),
+                                  max_workers=options.max_workers)
+    if args.log_sink is not None:
+        args.log_sink.dir_result(result, args.left, args.right)
+    if not args.quiet:
+        _print_dir_result(result, policy)
+    if policy is None:
+        return EXIT_EQUAL if result.equal else EXIT_DIFFERENT
+    highest = policy.highest(result)
+    return EXIT_EQUAL if highest is None else _SEVERITY_EXIT[highest]
+
+
+def _read_pairs(path: str) -> list[tuple[str, str]]:
+    pairs = []
+    with (contextlib.nullcontext(sys.stdin) if path == "-"
+          else open(path, encoding="utf-8", errors=PATH_ERRORS)) as f:
+        for number, line in enumerate(f, 1):
+            line = line.rstrip("\r\n")
+            if not line.strip() or line.startswith("#"):
+                continue
+            left, sep, right = line.partition("\t")
+            if not (sep and left and right) or "\t" in right:
+                raise ValueError(f"{path}:{number}: expected LEFT<TAB>RIGHT")
+            pairs.append((left, right))
+    return pairs
+
+
+# Worst first: an error, a run cut short, then differences, then warn-level ones only
+_PAIR_RANK = {EXIT_ERROR: 0, EXIT_QUOTA: 1, EXIT_DIFFERENT: 2, EXIT_WARN: 3, EXIT_EQUAL: 4}
+
+
+def _compare_pairs(args: argparse.Namespace) -> int:
+    if (args.left is not None or args.git is not None or args.tui or args.patch is not None
+            or args.suggest_sync or args.suggest_sync_json):
+        raise ValueError("--pair and --pairs-from take no paths and no --git, --tui, --patch "
+                         "or --suggest-sync")
+    pairs = [(left, right) for left, right in args.pair]
+    if args.pairs_from is not None:
+        pairs += _read_pairs(args.pairs_from)
+    if not pairs:
+        raise ValueError(f"{args.pairs_from} lists no pairs")
+    # One cache for the run: a tree given in several pairs is read once
+    cache = DirCache()
+    codes = []
+    for left, right in pairs:
+        if not args.quiet:
+            print(f"== {left} {right}", flush=True)
+        pair_args = argparse.Namespace(**{**vars(args), "left": left, "right": right,
+                                          "pair": [], "pairs_from": None, "cache": cache})
+        try:
+            codes.append(_cmd_compare(pair_args))
+        except QuotaReachedError as e:
+            print(f"komparu: {left} {right}: {e}", file=sys.stderr)
+            codes.append(EXIT_QUOTA)
+        except (OSError, ValueError, ImportError) as e:
+            print(f"komparu: {left} {right}: {e}", file=sys.stderr)
+            codes.append(EXIT_ERROR)
+        if codes[-1] == EXIT_QUOTA:
+            break
+    if not args.quiet:
+        different = codes.count(EXIT_DIFFERENT) + codes.count(EXIT_WARN)
+        summary = (f"{len(codes)} pairs: {codes.count(EXIT_EQUAL)} equal, {different} "
+                   f"different, {codes.count(EXIT_ERROR)} failed")
+        if EXIT_QUOTA in codes:
+            summary += f", quota reached with {len(pairs) - len(codes) + 1} left"
+        print(summary)
+    return min(codes, key=_PAIR_RANK.__getitem__)
+
+
+# Options that pick how file contents are compared, outside the engine's byte comparison
+_CONTENT_OPTIONS = (
+    ("--filter", "filter"), ("--compare-as", "compare_as"), ("--text", "text"),
+    ("--ignore-trailing-space", "ignore_trailing_space"),
+    ("--ignore-space-change", "ignore_space_change"), ("--ignore-bom", "ignore_bom"),
+    ("--ignore-final-newline", "ignore_final_newline"), ("--decode", "decode"),
+    ("--json", "json"), ("--yaml", "yaml"), ("--xml", "xml"), ("--tar", "tar"),
+    ("--tar-ignore-mtime", "tar_ignore_mtime"), ("--tar-ignore-order", "tar_ignore_order"),
+    ("--zip", "zip"), ("--zip-ignore-timestamps", "zip_ignore_timestamps"),
+    ("--zip-ignore-metadata", "zip_ignore_metadata"), ("--reproducible", "reproducible"),
+    ("--image", "image"), ("--audio", "audio"), ("--pdf", "pdf"), ("--sqlite", "sqlite"),
+    ("--parquet", "parquet"), ("--decompress", "decompress"),
+    ("--content-aware", "content_aware"),
+)
+
+
+# Options --forensic refuses: they write, run outside code, or read files
+# or list directories in Python, where O_NOATIME does not reach
+_NOT_FORENSIC = (
+    ("--archive", "archive"), ("--git", "git"), ("--oci", "oci"),
+    ("--disk-image", "disk_image"), ("--fs-snapshots", "fs_snapshots"), ("--patch", "patch"),
+    ("--tui", "tui"), ("--rules", "rules"), *_CONTENT_OPTIONS,
+    ("--suggest-sync", "suggest_sync"), ("--suggest-sync-json", "suggest_sync_json"),
+    ("--metadata-only", "metadata_only"), ("--metadata", "metadata"),
+    ("--similarity", "similarity"), ("--chunks", "chunks"), ("--regions", "regions"),
+    ("--delta-size", "delta_size"), ("--fuzzy", "fuzzy"),
+    ("--fuzzy-threshold", "fuzzy_threshold"), ("--savings", "savings"),
+    ("--cmp-compat", "cmp_compat"), ("--diff-compat", "diff_compat"),
+    ("--audit-log", "audit_log"), ("--attributes", "attributes"),
+)
+
+
+# Options that read LEFT as a file or a directory
+_NOT_SPLIT = (
+    *_NOT_FORENSIC, ("--names-only", "names_only"), ("--pair", "pair"),
+    ("--pairs-from", "pairs_from"),
+)
+
+
+# Options that read LEFT and RIGHT as anything but two local files
+_NOT_SALVAGE = (
+    *_NOT_SPLIT, ("--split", "split"), ("--forensic", "forensic"), ("--max-bytes", "max_bytes"),
+)
+
+# Options that read content outside the engine, where --max-bytes does not count it
+_NOT_QUOTA = (
+    ("--archive", "archive"), ("--git", "git"), ("--oci", "oci"),
+    ("--disk-image", "disk_image"), ("--fs-snapshots", "fs_snapshots"), ("--patch", "patch"),
+    ("--similarity", "similarity"), ("--chunks", "chunks"), ("--regions", "regions"),
+    ("--delta-size", "delta_size"), ("--fuzzy", "fuzzy"),
+    ("--fuzzy-threshold", "fuzzy_threshold"), ("--savings", "savings"),
+    ("--cmp-compat", "cmp_compat"), ("--diff-compat", "diff_compat"),
+    ("--audit-log", "audit_log"), ("--tui", "tui"), ("--suggest-sync", "suggest_sync"),
+    ("--suggest-sync-json", "suggest_sync_json"), *_CONTENT_OPTIONS,
+)
+
+
+def _flags_given(args: argparse.Namespace, options: tuple[tuple[str, str], ...]) -> list[str]:
+    given = vars(args)
+    return [flag for flag, dest in options
+            if given[dest] is not None and given[dest] is not False and given[dest] != []]
+
+
+def _forensic_note(noatime: bool, denied: int) -> str:
+    note = "forensic: read-only, nothing written to either side; "
+    if not noatime:
+        return note + "access times may have changed (no O_NOATIME on this platform)"
+    if denied:
+        return note + (f"access times of {denied} files not owned by this user may have "
+                       "changed")
+    return note + "access times kept"
+
+
+def _compare_forensic(args: argparse.Namespace) -> int:
+    """Compare with nothing written to either side, then record that it was so."""
+    refused = _flags_given(args, _NOT_FORENSIC)
+    if refused:
+        raise ValueError(f"--forensic cannot be combined with {', '.join(refused)}")
+    pairs = [(left, right) for left, right in args.pair]
+    if args.pairs_from is not None:
+        pairs += _read_pairs(args.pairs_from)
+    for path in (path for pair in pairs or [(args.left, args.right)] for path in pair):
+        if path is not None and (_remote_kind(path) or is_url(path)):
+            raise ValueError("--forensic compares local files and directories")
+    if pairs:
+        args = argparse.Namespace(**{**vars(args), "pair": pairs, "pairs_from": None})
+    for left, right in pairs or [(args.left, args.right)]:
+        if left is not None and right is not None and os.path.isdir(left) != os.path.isdir(right):
+            raise ValueError("--forensic compares two files or two directories")
+    noatime = _set_noatime(True)
+    try:
+        # LEFT's rules file would be read in Python, where O_NOATIME does not reach
+        code = _cmd_compare(argparse.Namespace(**{**vars(args), "forensic": False,
+                                                  "tree_attributes": False}))
+        denied = _noatime_denied()
+    finally:
+        _set_noatime(False)
+    if args.log_sink is not None:
+        args.log_sink.forensic(noatime, denied)
+    if not args.quiet:
+        print(_forensic_note(noatime, denied))
+    return code
+
+
+# Options that read LEFT and RIGHT as anything but one or two local paths, and
+# --forensic: a shadow copy writes its differences to the volume it copies
+_NOT_VSS = (
+    ("--pair", "pair"), ("--pairs-from", "pairs_from"), ("--git", "git"), ("--split", "split"),
+    ("--forensic", "forensic"),
+)
+
+
+def _compare_vss(args: argparse.Namespace) -> int:
+    """Compare shadow copies of LEFT and RIGHT instead of the live paths."""
+    refused = _flags_given(args, _NOT_VSS)
+    if refused:
+        raise ValueError(f"--vss cannot be combined with {', '.join(refused)}")
+    paths = [path for path in (args.left, args.right) if path is not None]
+    if not paths or any(_remote_kind(path) or is_url(path) for path in paths):
+        raise ValueError("--vss snapshots local files and directories")
+    with shadow_copy(*paths) as snapshots:
+        frozen = iter(snapshots)
+        sides = {side: next(frozen) for side in ("left", "right") if vars(args)[side] is not None}
+        return _cmd_compare(argparse.Namespace(**{**vars(args), **sides, "vss": False}))
+
+
+def _compare_split(args: argparse.Namespace) -> int:
+    """Compare the split volumes LEFT names with the file RIGHT."""
+    refused = _flags_given(args, _NOT_SPLIT)
+    if refused:
+        raise ValueError(f"--split cannot be combined with {', '.join(refused)}")
+    if args.left is None or args.right is None:
+        raise ValueError("--split needs the first part (or a list of parts) and a file")
+    if any(_remote_kind(path) or is_url(path) for path in (args.left, args.right)):
+        raise ValueError("--split compares local files")
+    policy = _policy_from_args(args)
+    equal = compare(split_parts(args.left), args.right, options=_options_from_args(args),
+                    cancel=_quota_token(args))
+    if args.log_sink is not None:
+        args.log_sink.file_result(equal, args.left, args.right)
+    if equal:
+        return EXIT_EQUAL
+    severity = policy.of(DiffReason.CONTENT_MISMATCH) if policy is not None else None
+    if not args.quiet:
+        message = f"{args.left} {args.right} differ"
+        print(f"{message} [{severity.value}]" if severity is not None else message)
+    return EXIT_DIFFERENT if severity is None else _SEVERITY_EXIT[severity]
+
+
+def _compare_salvage(args: argparse.Namespace) -> int:
+    """Compare two files on flaky media, reporting the ranges that could not be read."""
+    refused = _flags_given(args, _NOT_SALVAGE)
+    if refused:
+        raise ValueError(f"--salvage cannot be combined with {', '.join(refused)}")
+    if args.left is None or args.right is None:
+        raise ValueError("--salvage needs two files")
+    if any(_remote_kind(path) or is_url(path) or os.path.isdir(path)
+           for path in (args.left, args.right)):
+        raise ValueError("--salvage compares two local files or block devices")
+    result = compare_salvage(
+        args.left, args.right, chunk_size=args.chunk_size,
+        min_read=512 if args.min_read is None else args.min_read,
+        retries=2 if args.read_retries is None else args.read_retries)
+    if not args.quiet:
+        for lost in result.unreadable:
+            print(f"unreadable in {lost.side}: bytes {lost.start}-{lost.end - 1} "
+                  f"({lost.size} bytes)")
+    if not result.equal:
+        if args.log_sink is not None:
+            args.log_sink.file_result(False, args.left, args.right)
+        if not args.quiet:
+            print(f"{args.left} {args.right} differ")
+        return EXIT_DIFFERENT
+    if result.unreadable:
+        message = (f"{args.left} {args.right}: {result.unreadable_bytes} bytes in "
+                   f"{len(result.unreadable)} ranges could not be verified")
+        if args.log_sink is not None:
+            args.log_sink.error(message, left=args.left, right=args.right)
+        print(message, file=sys.stderr)
+        return EXIT_ERROR
+    if args.log_sink is not None:
+        args.log_sink.file_result(True, args.left, args.right)
+    return EXIT_EQUAL
+
+
+def _quota_token(args: argparse.Namespace) -> CancelToken | None:
+    """The one token --max-bytes counts the whole run under (pairs and all)."""
+    if args.max_bytes is None:
+        return None
+    if getattr(args, "quota", None) is None:
+        if args.max_bytes <= 0:
+            raise ValueError("--max-bytes must be positive")
+        refused = _flags_given(args, _NOT_QUOTA)
+        if refused:
+            raise ValueError(f"--max-bytes cannot be combined with {', '.join(refused)}")
+        args.quota = CancelToken(max_bytes=args.max_bytes)
+    return args.quota
+
+
+# Options of a plain compare: the kinds of _CompareRun they apply to, what they
+# need otherwise, and the options they cannot be combined with
+_LOCAL = frozenset(("files", "urls", "dirs", "names", "metadata"))
+_TREES = frozenset(("dirs", "names", "metadata", "mixed", "archives", "git", "revs", "ssh",
+                    "remote"))
+_NEEDS = (
+    (_CONTENT_OPTIONS, _LOCAL, "two files or two local directories", ()),
+    ((("--rules", "rules"), ("--include-from", "include_from"),
+      ("--exclude-from", "exclude_from")),
+     _LOCAL | {"git", "ssh", "remote"}, "files or directories, not archives or two commits", ()),
+    ((("--similarity", "similarity"), ("--delta-size", "delta_size"), ("--fuzzy", "fuzzy"),
+      ("--fuzzy-threshold", "fuzzy_threshold")),
+     _LOCAL, "two files or two local directories", ()),
+    ((("--chunks", "chunks"), ("--regions", "regions")),
+     _LOCAL | {"ssh"}, "two files, two local directories or a [user@]host:path on the right", ()),
+    ((("--names-only", "names_only"),), frozenset(("names",)), "two directories",
+     (("--metadata-only", "metadata_only"), ("--metadata", "metadata"))),
+    ((("--metadata-only", "metadata_only"), ("--metadata", "metadata")),
+     frozenset(("metadata",)), "two directories", ()),
+    ((("--savings", "savings"),), frozenset(("dirs", "names", "metadata")),
+     "two directories", ()),
+    ((("--lock", "lock"), ("--lock-timeout", "lock_timeout")),
+     frozenset(("dirs",)), "two local directories", ()),
+    ((("--rollup", "rollup"),), frozenset(("dirs",)), "two local directories", ()),
+    ((("--suggest-sync", "suggest_sync"), ("--suggest-sync-json", "suggest_sync_json")),
+     frozenset(("dirs",)), "two local directories",
+     (("--tui", "tui"), ("--audit-log", "audit_log"), ("--rollup", "rollup"))),
+    ((("--patch", "patch"),), frozenset(("files", "urls", "dirs")),
+     "two local files or two local directories", (("--tui", "tui"),)),
+    ((("--audit-log", "audit_log"),), frozenset(("files", "dirs")),
+     "two local files or two local directories", ()),
+    ((("--max-bytes", "max_bytes"),), _LOCAL | {"mixed", "archives"},
+     "local files or directories", ()),
+    ((("--tui", "tui"),), _TREES, "directories or archives", ()),
+    ((("--archive", "archive"),), frozenset(("archives",)), "two local archives", ()),
+    ((("--git-repo", "git_repo"),), frozenset(("git", "revs")), "--git", ()),
+    ((("--gitignore", "gitignore"),), frozenset(("git",)), "--git and a directory", ()),
+)
+
+
+class _CompareRun:
+    """A plain ``compare`` of LEFT and RIGHT: its options, and what the two are.
+
+    *kind* is one of ``files``, ``urls`` (two files, one a URL), ``dirs``,
+    ``names`` and ``metadata`` (two local directories, with --names-only or
+    --metadata-only), ``mixed`` (a directory and an archive), ``archives``,
+    ``git`` (a directory and a commit), ``revs`` (two commits), ``ssh`` (a
+    local directory and a [user@]host:path) or ``remote`` (any other remote
+    directory); _NEEDS says which options apply to which.
+    """
+
+    def __init__(self, args: argparse.Namespace, quota: CancelToken | None) -> None:
+        self.args = args
+        self.quota = quota
+        self.options = tune_for_storage(_options_from_args(args), *(
+            path for path in (args.left, args.right) if path is not None))
+        self.policy = _policy_from_args(args)
+        self.routes: dict[str, str] = {}
+        self.comparators = _comparators_from_args(args, self.on_route)
+        self.path_filter: PathFilter | None = None
+        if args.rules:
+            self.path_filter, script = load_rules(args.rules, self.comparators.get("*"))
+            if script is not None:
+                self.comparators["*"] = script
+        if args.include_from or args.exclude_from:
+            patterns = RsyncFilter.from_files(args.include_from, args.exclude_from)
+            self.path_filter = all_filters(patterns, self.path_filter)
+        self.kind = ""
+        self.left_dir = self.right_dir = False
+        self.remote_left: str | None = None
+        self.remote_right: str | None = None
+        self.revs: tuple[str, str] | None = None
+        self.attributes: Collection[str] | Callable[[str], Collection[str]] = (
+            METADATA_ATTRIBUTES if args.metadata is None
+            else tuple(name.strip() for name in args.metadata.split(",")))
+
+    @property
+    def local_dirs(self) -> bool:
+        return self.kind in ("dirs", "names", "metadata")
+
+    @property
+    def remote(self) -> bool:
+        return self.kind in ("git", "revs", "ssh", "remote")
+
+    @property
+    def chunked(self) -> bool:
+        return self.args.chunks or self.args.regions
+
+    @property
+    def fuzzy(self) -> bool:
+        return self.args.fuzzy or self.args.fuzzy_threshold is not None
+
+    def on_route(self, path_a: str, path_b: str, kind: str) -> None:
+        self.routes[path_a] = kind
+
+
+def _compare_inputs(run: _CompareRun) -> None:
+    """Tell what LEFT and RIGHT (or --git) are, and refuse what does not apply to them."""
+    args = run.args
+    run.revs = git_range(args.git, args.git_repo or ".") if args.git is not None else None
+    if run.revs is not None:
+        if args.left is not None:
+            raise ValueError("--git A..B compares two commits and takes no paths")
+        run.left_dir = run.right_dir = True
+    elif args.git is not None:
+        if args.left is None or args.right is not None:
+            raise ValueError("--git compares a single directory")
+        run.left_dir = run.right_dir = os.path.isdir(args.left)
+    elif args.left is None or args.right is None:
+        raise ValueError("compare needs two paths, or --git REV and a directory")
+    else:
+        run.left_dir, run.right_dir = os.path.isdir(args.left), os.path.isdir(args.right)
+    run.remote_left = _remote_kind(args.left) if args.left is not None else None
+    run.remote_right = "git" if args.git is not None else _remote_kind(args.right)
+    if run.revs is not None:
+        run.kind = "revs"
+    elif args.git is not None:
+        run.kind = "git"
+    elif run.remote_left or run.remote_right:
+        run.kind = "ssh" if run.remote_right == "ssh" and not run.remote_left else "remote"
+    elif args.archive:
+        run.kind = "archives"
+    elif run.left_dir != run.right_dir:
+        run.kind = "mixed"
+    elif run.left_dir:
+        metadata_only = args.metadata_only or args.metadata is not None
+        run.kind = "metadata" if metadata_only else "names" if args.names_only else "dirs"
+    else:
+        run.kind = "urls" if is_url(args.left) or is_url(args.right) else "files"
+    for options, kinds, needs, conflicts in _NEEDS:
+        given = _flags_given(args, options)
+        if not given:
+            continue
+        clash = _flags_given(args, conflicts)
+        if clash:
+            raise ValueError(f"{given[0]} cannot be combined with {', '.join(clash)}")
+        if run.kind not in kinds:
+            raise ValueError(f"{', '.join(given)} need{'s' if len(given) == 1 else ''} {needs}")
+    if args.fuzzy_threshold is not None and not 0 <= args.fuzzy_threshold <= 100:
+        raise ValueError("--fuzzy-threshold must be between 0 and 100")
+    if args.rollup_depth is not None and not args.rollup:
+        raise ValueError("--rollup-depth needs --rollup")
+    if args.rollup_depth is not None and args.rollup_depth < 0:
+        raise ValueError("--rollup-depth must be non-negative")
+
+
+def _apply_attributes(run: _CompareRun) -> None:
+    """Fold LEFT's rules file and --attributes into the comparators, filter and metadata."""
+    args = run.args
+    path_attributes = _attributes_from_args(args, run.local_dirs)
+    if path_attributes is None:
+        return
+    strategies = {"text" if value is True else value or "bytes"
+                  for name in ("text", "compare")
+                  for value, _ in path_attributes.values(name)}
+    if strategies:
+        run.comparators = {"*": _AttributeComparator(
+            path_attributes, args.left,
+            {strategy: _compare_as(args, strategy, run.on_route) for strategy in strategies},
+            run.comparators)}
+    run.path_filter = all_filters(path_attributes.path_filter(), run.path_filter)
+    if path_attributes.values("metadata"):
+        run.attributes = _attributes_of(path_attributes, run.attributes)
+
+
+def _compare_sync_plan(run: _CompareRun) -> int:
+    """--suggest-sync: print the steps that would make RIGHT match LEFT."""
+    args = run.args
+    plan = sync_plan(args.left, args.right,
+                     options=run.options.replace(path_filter=run.path_filter),
+                     comparators=run.comparators or None)
+    if not args.quiet:
+        _print_sync_plan(plan, args.suggest_sync_json)
+    return EXIT_EQUAL if not (plan.steps or plan.errors) else EXIT_DIFFERENT
+
+
+def _compare_remote(run: _CompareRun) -> DirResult:
+    """A local directory against a remote one, a commit, or two commits."""
+    args, options = run.args, run.options
+    remote_left, remote_right = run.remote_left, run.remote_right
+    local, remote = (args.right, args.left) if remote_left else (args.left, args.right)
+    if (remote_left and remote_right) or not (run.left_dir or run.right_dir):
+        raise ValueError("a remote directory is compared with a local one")
+    if run.revs is not None:
+        return compare_git_revs(*run.revs, repo=args.git_repo or ".",
+                                ignore=list(options.ignore))
+    if remote_right == "git":
+        return compare_dir_git(args.left, args.git, repo=args.git_repo,
+                               gitignore=args.gitignore, ignore=list(options.ignore),
+                               path_filter=run.path_filter, max_workers=options.max_workers)
+    kind = remote_left or remote_right
+    if kind == "s3":
+        result = compare_dir_s3(local, remote, follow_symlinks=options.follow_symlinks,
+                                ignore=list(options.ignore), path_filter=run.path_filter,
+                                max_workers=options.max_workers)
+    elif kind == "store":
+        result = compare_dir_store(local, remote, follow_symlinks=options.follow_symlinks,
+                                   ignore=list(options.ignore), path_filter=run.path_filter,
+                                   max_workers=options.max_workers)
+    elif kind == "agent":
+        result = compare_dir_agent(local, remote, follow_symlinks=options.follow_symlinks,
+                                   ignore=list(options.ignore), path_filter=run.path_filter,
+                                   max_workers=options.max_workers)
+    else:
+        result = compare_dir_ssh(local, remote, ssh_command=args.ssh,
+                                 remote_command=args.remote_komparu,
+                                 follow_symlinks=options.follow_symlinks,
+                                 ignore=list(options.ignore), path_filter=run.path_filter,
+                                 max_workers=options.max_workers)
+    if remote_left:
+        result = DirResult(equal=result.equal, diff=result.diff,
+                           only_left=result.only_right, only_right=result.only_left,
+                           errors=result.errors)
+    return result
+
+
+def _compare_local_dirs(run: _CompareRun
+                        ) -> tuple[DirResult, SummaryVisitor | None, QuotaReachedError | None]:
+    """Two local directories through the engine: the result, its rollup and any quota stop."""
+    args, options, quota = run.args, run.options, run.quota
+    summary: SummaryVisitor | None = None
+    stop = _StopOnError(args.left, quota) if args.on_error == "fail" else None
+    with (AuditLog(args.audit_log) if args.audit_log is not None
+          else contextlib.nullcontext()) as audit:
+        visitor = audit.visitor(args.left, args.right, stop) if audit is not None else stop
+        if quota is not None and visitor is None:
+            visitor = CompareVisitor()  # a visitor run keeps the partial result
+        if args.rollup or args.tui:
+            visitor = summary = SummaryVisitor(args.left, args.right, visitor,
+                                               follow_symlinks=options.follow_symlinks)
+        cancel = (stop.cancel if stop else quota) or CancelToken()
+        status = _RunStatus(visitor, cancel)
+        try:
+            with _status_on_signal(status):
+                # Watching a run that streams anyway also names its files; a
+                # plain one stays on the faster path and reports counts only
+                result = compare_dir(args.left, args.right,
+                                     options=options.replace(path_filter=run.path_filter),
+                                     cache=args.cache, comparators=run.comparators or None,
+                                     visitor=status if visitor is not None else None,
+                                     cancel=cancel)
+        except QuotaReachedError as e:
+            if e.partial is None:
+                raise
+            return e.partial, summary, e
+    return result, summary, None
+
+
+def _compare_trees(run: _CompareRun
+                   ) -> tuple[DirResult, SummaryVisitor | None, QuotaReachedError | None]:
+    """Whatever LEFT and RIGHT hold that is compared as a tree of entries."""
+    args, options = run.args, run.options
+    if run.remote:
+        return _compare_remote(run), None, None
+    if run.kind == "archives":
+        return compare_archive(args.left, args.right, chunk_size=options.chunk_size), None, None
+    if run.kind == "metadata":
+        result = compare_dir_metadata(args.left, args.right, attributes=run.attributes,
+                                      options=options.replace(path_filter=run.path_filter))
+        return result, None, None
+    if run.kind == "names":
+        # A file replaced by a directory shows up as the file on one side
+        # and the directory's entries on the other
+        plan = plan_dir(args.left, args.right,
+                        options=options.replace(size_precheck=False, path_filter=run.path_filter))
+        return DirResult(equal=not (plan.only_left or plan.only_right or plan.errors),
+                         diff={}, only_left=plan.only_left, only_right=plan.only_right,
+                         errors=plan.errors), None, None
+    if run.kind == "dirs":
+        return _compare_local_dirs(run)
+    # A directory against an archive of it, e.g. a backup
+    return compare_dir_archive(args.left, args.right, chunk_size=options.chunk_size,
+                               size_precheck=options.size_precheck,
+                               quick_check=options.quick_check,
+                               follow_symlinks=options.follow_symlinks,
+                               max_workers=options.max_workers,
+                               ignore=list(options.ignore)), None, None
+
+
+def _compare_files(run: _CompareRun) -> int:
+    """Two files (or URLs), with what the flags ask to know about how they differ."""
+    args, policy = run.args, run.policy
+    cancel = run.quota or CancelToken()
+    with _status_on_signal(_RunStatus(None, cancel, [args.left])):
+        equal = compare(args.left, args.right, options=run.options, cancel=cancel)
+    if not equal:
+        name = os.path.basename(args.left)
+        comparator = next((c for pattern, c in run.comparators.items()
+                           if fnmatch(name, pattern)), None)
+        if comparator is not None:
+            equal = comparator.compare(args.left, args.right)
+    kind = run.routes.get(args.left)
+    if args.log_sink is not None:
+        args.log_sink.file_result(equal, args.left, args.right)
+    if args.audit_log is not None:
+        reason = DiffReason.CONTENT_MISMATCH
+        if not equal and os.path.getsize(args.left) != os.path.getsize(args.right):
+            reason = DiffReason.SIZE_MISMATCH
+        with AuditLog(args.audit_log) as audit:
+            audit.record(args.left, args.right, "equal" if equal else reason.value)
+    if equal:
+        if kind is not None and not args.quiet:
+            print(f"{args.left} {args.right} equal (compared as {kind})")
+        return EXIT_EQUAL
+    message = f"{args.left} {args.right} differ"
+    if kind is not None:
+        message += f" (compared as {kind})"
+    if args.patch is not None and os.path.isfile(args.left) and os.path.isfile(args.right):
+        make_patch(args.left, args.right, args.patch)
+    regions: tuple[DiffRegion, ...] = ()
+    if not args.quiet and os.path.isfile(args.left) and os.path.isfile(args.right):
+        if args.similarity:
+            message += f" ({similarity(args.left, args.right):.1%} similar)"
+        if run.chunked:
+            chunks = chunk_diff(args.left, args.right)
+            if args.chunks:
+                message += f" ({_describe_chunks(chunks)})"
+            if args.regions:
+                regions = chunks.regions
+        if args.delta_size:
+            size = os.path.getsize(args.right)
+            message += f" ({_describe_delta(delta_size(args.left, args.right), size)})"
+        if run.fuzzy:
+            score = fuzzy_score(fuzzy_hash(args.left), fuzzy_hash(args.right))
+            message += f" (fuzzy score {score})"
+    severity = policy.of(DiffReason.CONTENT_MISMATCH) if policy is not None else None
+    if not args.quiet:
+        print(f"{message} [{severity.value}]" if severity is not None else message)
+        for region in regions:
+            print(f"  {_describe_region(region)}")
+    return EXIT_DIFFERENT if severity is None else _SEVERITY_EXIT[severity]
+
+
+def _print_dir_details(run: _CompareRun, result: DirResult,
+                       summary: SummaryVisitor | None, sides: tuple[str, str]) -> None:
+    """The directory report, with the notes --metadata, --similarity and the like add."""
+    args, options = run.args, run.options
+    similarities = {} if args.similarity else None
+    details: dict[str, list[str]] = {}
+    regions_by_path: dict[str, tuple[DiffRegion, ...]] = {}
+    attributes = run.attributes
+    for path, reason in result.diff.items() if run.kind == "metadata" else ():
+        if reason is DiffReason.METADATA_MISMATCH:
+            changes = metadata_diff(os.path.join(args.left, path),
+                                    os.path.join(args.right, path),
+                                    attributes=(attributes(path) if callable(attributes)
+                                                else attributes),
+                                    follow_symlinks=options.follow_symlinks)
+            details[path] = _describe_metadata(changes)
+    for path in result.diff if args.similarity or run.chunked or args.delta_size else ():
+        if result.diff[path] in UNREADABLE:
+            continue
+        left, right = os.path.join(args.left, path), os.path.join(args.right, path)
+        if run.remote_right == "ssh":
+            if result.diff[path] in (DiffReason.SIZE_MISMATCH, DiffReason.CONTENT_MISMATCH):
+                _note_chunks(args, details.setdefault(path, []), regions_by_path, path,
+                             chunk_diff_ssh(left, _ssh_join(args.right, path),
+                                            ssh_command=args.ssh,
+                                            remote_command=args.remote_komparu))
+            continue
+        if not (os.path.isfile(left) and os.path.isfile(right)):
+            continue
+        notes = details.setdefault(path, [])
+        if similarities is not None:
+            similarities[path] = similarity(left, right)
+        if run.chunked:
+            _note_chunks(args, notes, regions_by_path, path, chunk_diff(left, right))
+        if args.delta_size:
+            notes.append(_describe_delta(delta_size(left, right), os.path.getsize(right)))
+    routed = {os.path.relpath(path, args.left): kind for path, kind in run.routes.items()}
+    _print_dir_result(result, run.policy, similarities, routed, details, regions_by_path,
+                      sides=sides)
+    if summary is not None:
+        _print_rollup(summary.summaries(),
+                      2 if args.rollup_depth is None else args.rollup_depth, sides)
+    if run.fuzzy:
+        threshold = 50 if args.fuzzy_threshold is None else args.fuzzy_threshold
+        _print_fuzzy_pairs(args.left, args.right, result, threshold)
+    if args.savings:
+        _print_savings(find_duplicates([args.left, args.right],
+                                       follow_symlinks=options.follow_symlinks,
+                                       ignore=list(options.ignore),
+                                       path_filter=run.path_filter,
+                                       max_workers=options.max_workers), 10)
+
+
+def _report_trees(run: _CompareRun, result: DirResult, summary: SummaryVisitor | None,
+                  quota_reached: QuotaReachedError | None) -> int:
+    """Write, log, browse or print a tree comparison; its exit code."""
+    args, policy, revs = run.args, run.policy, run.revs
+    result = _apply_on_error(result, args.on_error)
+    sides = (revs or ("tree", "commit")) if args.git is not None else ("left", "right")
+    if args.patch is not None:
+        _write_patches(args.left, args.right, result, args.patch)
+    if args.log_sink is not None:
+        args.log_sink.dir_result(result, *(revs or (args.left, args.right or args.git)),
+                                 sides=sides)
+    if args.tui:
+        browser = DiffBrowser(result, (args.left, args.right) if run.local_dirs else None,
+                              sides=sides,
+                              severities=policy.classify(result) if policy is not None else None,
+                              summaries=summary.summaries() if summary is not None else None)
+        run_tui(browser, f"komparu: {' vs '.join(revs or (args.left, args.right or args.git))}")
+    elif not args.quiet:
+        _print_dir_details(run, result, summary, sides)
+    if quota_reached is not None:
+        message = f"{quota_reached}; the report covers what was compared before it"
+        if args.log_sink is not None:
+            args.log_sink.error(message, left=args.left, right=args.right)
+        print(f"komparu: {message}", file=sys.stderr)
+        return EXIT_QUOTA
+    if policy is None:
+        return EXIT_EQUAL if result.equal else EXIT_DIFFERENT
+    highest = policy.highest(result)
+    return EXIT_EQUAL if highest is None else _SEVERITY_EXIT[highest]
+
+
+def _cmd_compare(args: argparse.Namespace) -> int:
+    if args.vss:
+        return _compare_vss(args)
+    quota = _quota_token(args)
+    if args.salvage:
+        return _compare_salvage(args)
+    if args.forensic:
+        return _compare_forensic(args)
+    if args.min_read is not None or args.read_retries is not None:
+        raise ValueError("--min-read and --read-retries need --salvage")
+    if args.split:
+        return _compare_split(args)
+    if args.pair or args.pairs_from is not None:
+        return _compare_pairs(args)
+    run = _CompareRun(args, quota)
+    options, policy = run.options, run.policy
+    content_rules = bool(run.comparators or run.path_filter)
+    if args.platform is not None and not args.oci:
+        raise ValueError("--platform needs --oci")
+    if args.audit_log is not None and (args.cmp_compat or args.diff_compat or args.oci
+                                       or args.disk_image or args.fs_snapshots):
+        raise ValueError("--audit-log needs two local files or two local directories")
+    if args.cmp_compat:
+        return _compare_cmp(args, content_rules)
+    if args.diff_compat:
+        return _compare_diff(args, options, run.comparators, run.path_filter)
+    if args.disk_image:
+        return _compare_disk_image(args, options, policy, content_rules)
+    if args.fs_snapshots:
+        return _compare_fs_snapshots(args, options, policy, content_rules)
+    if args.oci:
+        return _compare_oci(args, options, policy, content_rules)
+    _compare_inputs(run)
+    _apply_attributes(run)
+    if args.suggest_sync or args.suggest_sync_json:
+        return _compare_sync_plan(run)
+    if run.kind in ("files", "urls"):
+        return _compare_files(run)
+    if "ssh" in (run.remote_left, run.remote_right) and not (run.remote_left
+                                                             and run.remote_right):
+        local, remote = (args.right, args.left) if run.remote_left else (args.left, args.right)
+        if os.path.isfile(local):
+            return _compare_file_ssh(args, policy, local, remote)
+    return _report_trees(run, *_compare_trees(run))
+
+
+def _write_patches(dir_a: str, dir_b: str, result: DirResult, out_dir: str) -> None:
+    for path in sorted(result.diff, key=path_key):
+        left, right = os.path.join(dir_a, path), os.path.join(dir_b, path)
+        if result.diff[path] not in UNREADABLE and os.path.isfile(left) and os.path.isfile(right):
+            patch = os.path.join(out_dir, path + PATCH_SUFFIX)
+            os.makedirs(os.path.dirname(patch), exist_ok=True)
+            make_patch(left, right, patch)
+
+
+def _sign_password(secret_key: str) -> str | None:
+    if not key_is_encrypted(secret_key):
+        return None
+    password = os.environ.get("KOMPARU_SIGN_PASSWORD")
+    return password if password is not None else getpass.getpass(f"Password for {secret_key}: ")
+
+
+def _cmd_hash(args: argparse.Namespace) -> int:
+    if args.metadata is not None and not args.tree:
+        raise ValueError("--metadata needs --tree")
+    if args.tree and args.tag:
+        raise ValueError("--tag and --tree cannot be combined")
+    if args.update is not None and (args.output is not None or args.tree):
+        raise ValueError("--update cannot be combined with --output or --tree")
+    if args.sign is not None and args.output is None and args.update is None:
+        raise ValueError("--sign needs --output or --update")
+    # Ask for the password before hashing a large tree, not after
+    password = _sign_password(args.sign) if args.sign is not None else None
+    if args.update is not None:
+        update_manifest(args.directory, args.update, algorithm=args.algorithm,
+                        tag=args.tag or None, follow_symlinks=args.follow_symlinks,
+                        ignore=args.ignore, max_workers=args.max_workers)
+        if args.sign is not None:
+            sign_manifest(args.update, args.sign, password=password)
+        return EXIT_EQUAL
+    algorithm = args.algorithm or "sha256"
+    with (atomic_open(args.output, "w", encoding="utf-8", errors=PATH_ERRORS)
+          if args.output else contextlib.nullcontext(sys.stdout)) as out:
+        if args.tree:
+            metadata = () if args.metadata is None else tuple(
+                name.strip() for name in args.metadata.split(","))
+            print(tree_digest(args.directory, algorithm=algorithm, metadata=metadata,
+                              follow_symlinks=args.follow_symlinks, ignore=args.ignore,
+                              max_workers=args.max_workers), file=out)
+        else:
+            for path, digest in hash_tree(args.directory, algorithm=algorithm,
+                                          follow_symlinks=args.follow_symlinks,
+                                          ignore=args.ignore, max_workers=args.max_workers):
+                print(format_manifest_line(digest, path,
+                                           algorithm=algorithm if args.tag else None),
+                      file=out)
+    if args.sign is not None:
+        sign_manifest(args.output, args.sign, password=password)
+    return EXIT_EQUAL
+
+
+def _read_manifest_arg(path: str, sfv: bool = False) -> Manifest:
+    if path == "-":
+        return (parse_sfv if sfv else parse_manifest)(sys.stdin, "<stdin>")
+    if sfv and is_url(path):
+        return parse_sfv(fetch_url(path).decode("utf-8", PATH_ERRORS).splitlines(), path)
+    if sfv:
+        with open(path, encoding="utf-8", errors=PATH_ERRORS) as f:
+            return parse_sfv(f, path)
+    return read_manifest(path)
+
+
+def _read_signed_manifest(path: str, public_key: str, signature: str | None,
+                          sfv: bool = False) -> Manifest:
+    if path != "-" and not sfv:
+        return read_manifest(path, public_key=public_key, signature=signature)
+    if path == "-":
+        if signature is None:
+            raise ValueError("--public-key with a manifest on standard input needs --signature")
+        data = sys.stdin.buffer.read()
+    elif is_url(path):
+        data = fetch_url(path)
+    else:
+        with open(path, "rb") as f:
+            data = f.read()
+    verify_signature(data, signature or path + ".minisig", public_key)
+    parse = parse_sfv if sfv else parse_manifest
+    return parse(data.decode("utf-8", PATH_ERRORS).splitlines(),
+                 "<stdin>" if path == "-" else path)
+
+
+def _cmd_verify(args: argparse.Namespace) -> int:
+    if args.public_key is not None:
+        manifest = _read_signed_manifest(args.manifest, args.public_key, args.signature,
+                                         args.sfv)
+    elif args.signature is not None:
+        raise ValueError("--signature needs --public-key")
+    else:
+        manifest = _read_manifest_arg(args.manifest, args.sfv)
+    result = verify_manifest(args.directory, manifest, algorithm=args.algorithm,
+                             follow_symlinks=args.follow_symlinks, ignore=args.ignore,
+                             max_workers=args.max_workers)
+    if args.log_sink is not None:
+        args.log_sink.dir_result(result, args.directory, args.manifest,
+                                 sides=("tree", "manifest"))
+    if not args.quiet:
+        _print_dir_result(result, None, sides=("tree", "manifest"))
+    return EXIT_EQUAL if result.equal else EXIT_DIFFERENT
+
+
+def _cmd_verify_zero(args: argparse.Namespace) -> int:
+    zero = True
+    for path in args.paths:
+        blank = verify_zero(path, chunk_size=args.chunk_size, quick_check=args.quick_check)
+        zero = zero and blank
+        if not args.quiet:
+            print(f"{path}: {'all zeros' if blank else 'not zeroed'}")
+    return EXIT_EQUAL if zero else EXIT_DIFFERENT
+
+
+def _cmd_diff_manifests(args: argparse.Namespace) -> int:
+    if args.left == "-" and args.right == "-":
+        raise ValueError("only one manifest can be read from standard input")
+    result = compare_manifests(_read_manifest_arg(args.left), _read_manifest_arg(args.right),
+                               ignore=args.ignore)
+    if not args.quiet:
+        _print_dir_result(result, None)
+    return EXIT_EQUAL if result.equal else EXIT_DIFFERENT
+
+
+def _cmd_snapshot(args: argparse.Namespace) -> int:
+    if args.output is None and sys.stdout.isatty():
+        raise ValueError("refusing to write a binary snapshot to a terminal; "
+                         "redirect the output or use -o")
+    snapshot = snapshot_tree(args.directory, algorithm=args.algorithm,
+                             follow_symlinks=args.follow_symlinks, ignore=args.ignore,
+                             max_workers=args.max_workers)
+    if args.output is not None:
+        write_snapshot(snapshot, args.output)
+    else:
+        write_snapshot(snapshot, sys.stdout.buffer)
+        sys.stdout.buffer.flush()
+    return EXIT_EQUAL
+
+
+def _cmd_diff_snapshot(args: argparse.Namespace) -> int:
+    attributes = (METADATA_ATTRIBUTES if args.metadata is None
+                  else tuple(name.strip() for name in args.metadata.split(",")))
+    old = read_snapshot(sys.stdin.buffer if args.snapshot == "-" else args.snapshot)
+    live = os.path.isdir(args.current)
+    new = (snapshot_tree(args.current, algorithm=old.algorithm,
+                         follow_symlinks=args.follow_symlinks, ignore=args.ignore,
+                         max_workers=args.max_workers)
+           if live else read_snapshot(args.current))
+    result = compare_snapshot(old, new, attributes=attributes, ignore=args.ignore)
+    if not args.quiet:
+        details = {path: _describe_metadata(snapshot_changes(old.entries[path],
+                                                             new.entries[path], attributes))
+                   for path, reason in result.diff.items()
+                   if reason is DiffReason.METADATA_MISMATCH}
+        _print_dir_result(result, None, details=details,
+                          sides=("snapshot", "tree") if live else ("left", "right"))
+    return EXIT_EQUAL if result.equal else EXIT_DIFFERENT
+
+
+def _describe_entry(entry: DiffEntry) -> str:
+    if entry.kind is EntryKind.ONLY_LEFT:
+        return f"only in left: {entry.path}"
+    if entry.kind is EntryKind.ONLY_RIGHT:
+        return f"only in right: {entry.path}"
+    if entry.kind is EntryKind.ERROR:
+        return f"error: {entry.path}"
+    return f"differ: {entry.path} ({entry.reason.value if entry.reason else entry.kind.value})"
+
+
+def _cmd_watch(args: argparse.Namespace) -> int:
+    events = watch_dir(args.left, args.right, chunk_size=args.chunk_size,
+                       follow_symlinks=args.follow_symlinks, max_workers=args.max_workers,
+                       ignore=args.ignore or None, settle=args.settle, poll=args.poll)
+    differing: set[str] = set()
+    try:
+        for event in events:
+            appeared = event.kind is DriftKind.APPEARED
+            if appeared:
+                differing.add(event.entry.path)
+            else:
+                differing.discard(event.entry.path)
+            print(f"{time.strftime('%H:%M:%S')} {'+' if appeared else '-'} "
+                  f"{_describe_entry(event.entry)}", flush=True)
+    except KeyboardInterrupt:
+        pass
+    finally:
+        events.close()
+    # Like compare: whether the trees still differ when the watch ends
+    return EXIT_DIFFERENT if differing else EXIT_EQUAL
+
+
+def _cmd_dupes(args: argparse.Namespace) -> int:
+    if args.top < 0:
+        raise ValueError("--top must be non-negative")
+    if args.apply is None and args.dry_run:
+        raise ValueError("--dry-run needs --apply")
+    if args.apply is not None and args.savings:
+        raise ValueError("--apply and --savings cannot be combined")
+    groups = find_duplicates(args.directories, min_size=args.min_size,
+                             hard_links=args.hard_links, follow_symlinks=args.follow_symlinks,
+                             ignore=args.ignore, max_workers=args.max_workers)
+    if args.apply is not None:
+        return _apply_dedup(groups, args.apply, args.dry_run, args.quiet)
+    if args.savings and not args.quiet:
+        _print_savings(groups, args.top)
+    elif not args.quiet:
+        for i, group in enumerate(groups):
+            if i:
+                print()
+            print(f"{len(group.paths)} files, {group.size} bytes each:")
+            for path in group.paths:
+                print(f"  {path}")
+    return EXIT_DIFFERENT if groups else EXIT_EQUAL
+
+
+def _cmd_sync(args: argparse.Namespace) -> int:
+    for path in (args.left, args.right):
+        if not os.path.isdir(path):
+            raise ValueError(f"sync needs two directories, {path!r} is not one")
+    options = CompareOptions(chunk_size=args.chunk_size, follow_symlinks=args.follow_symlinks,
+                             max_workers=args.max_workers, ignore=tuple(args.ignore))
+    options = tune_for_storage(options, args.left, args.right)
+    if args.include_from or args.exclude_from:
+        options = options.replace(
+            path_filter=RsyncFilter.from_files(args.include_from, args.exclude_from))
+    plan = sync_dir(args.left, args.right, delete=args.delete, update=args.update,
+                    dry_run=not args.apply, options=options)
+    if not args.quiet:
+        for step in plan.steps:
+            print(_describe_step(step) if args.apply else f"would {_describe_step(step)}")
+        for path in sorted(plan.errors, key=path_key):
+            print(f"skipped: {path} (cannot read)")
+        if plan.steps and not args.apply:
+            print(f"{len(plan.steps)} step(s), {plan.bytes_to_copy} bytes to copy; "
+                  "nothing changed without --apply", file=sys.stderr)
+    if plan.errors or (plan.steps and not args.apply):
+        return EXIT_DIFFERENT
+    return EXIT_EQUAL
+
+
+def _cmd_patch(args: argparse.Namespace) -> int:
+    if not os.path.isdir(args.patch):
+        if os.path.isdir(args.target):
+            raise ValueError("a single patch applies to a file, not a directory")
+        patched = apply_patch(args.target, args.patch, output=args.output)
+        if not args.quiet:
+            print(f"patched: {args.target}" if patched else f"up to date: {args.target}")
+        return EXIT_EQUAL
+    if args.output is not None:
+        raise ValueError("--output applies to a single patch")
+    if not os.path.isdir(args.target):
+        raise ValueError(f"a directory of patches applies to a directory, {args.target!r} "
+                         "is not one")
+    entries, _ = walk_tree(args.patch, True, None)
+    for patch in sorted((path for path, st in entries.items()
+                         if stat.S_ISREG(st.st_mode) and path.endswith(PATCH_SUFFIX)),
+                        key=path_key):
+        path = patch[:-len(PATCH_SUFFIX)]
+        patched = apply_patch(os.path.join(args.target, path), os.path.join(args.patch, patch))
+        if not args.quiet:
+            print(f"patched: {path}" if patched else f"up to date: {path}")
+    return EXIT_EQUAL
+
+
+def _apply_dedup(groups: list[DuplicateGroup], method: str, dry_run: bool,
+                 quiet: bool) -> int:
+    result = deduplicate(groups, method=method, dry_run=dry_run)
+    if not quiet:
+        verb = f"would {method}" if dry_run else method
+        for path, kept in result.linked:
+            print(f"{verb}: {path} -> {kept}")
+        for path, reason in result.skipped.items():
+            print(f"skipped: {path} ({reason})")
+        freed = "would be freed" if dry_run else "freed"
+        print(f"{len(result.linked)} file(s), {result.bytes_saved} bytes {freed}")
+    return EXIT_DIFFERENT if result.skipped else EXIT_EQUAL
+
+
+def _cmd_copy_verify(args: argparse.Namespace) -> int:
+    if os.path.isdir(args.source):
+        copied: Iterable[tuple[str, str]] = copy_tree_verified(
+            args.source, args.destination, algorithm=args.algorithm,
+            follow_symlinks=args.follow_symlinks, ignore=args.ignore)
+    else:
+        destination = args.destination
+        if os.path.isdir(destination):
+            destination = os.path.join(destination, os.path.basename(args.source))
+        copied = [(os.path.basename(destination),
+                   copy_verified(args.source, destination, algorithm=args.algorithm))]
+    with (atomic_open(args.manifest, "w", encoding="utf-8", errors=PATH_ERRORS)
+          if args.manifest else contextlib.nullcontext()) as out:
+        for path, digest in copied:
+            if out is not None:
+                print(format_manifest_line(digest, path), file=out)
+    return EXIT_EQUAL
+
+
+def _cmd_rsync_delta(args: argparse.Namespace) -> int:
+    with mapped(args.file) as data:
+        write_delta(data, read_signature(sys.stdin.buffer), sys.stdout.buffer)
+    sys.stdout.buffer.flush()
+    return EXIT_EQUAL
+
+
+def _read_token(path: str | None) -> str | None:
+    if path is None:
+        return None
+    with open(path, encoding="utf-8") as f:
+        token = f.read().strip()
+    if not token:
+        raise ValueError(f"{path} holds no token")
+    return token
+
+
+def _listen_address(spec: str) -> tuple[str, int]:
+    host, sep, port = spec.rpartition(":")
+    if not sep or not port.isdigit():
+        raise ValueError("--listen takes HOST:PORT")
+    return host.strip("[]") or "127.0.0.1", int(port)
+
+
+def _cmd_agent(args: argparse.Namespace) -> int:
+    token = _read_token(args.token_file)
+    if args.listen is None:
+        serve_session(sys.stdin.buffer, sys.stdout.buffer, roots=args.root or None, token=token)
+        return EXIT_EQUAL
+    address = _listen_address(args.listen)
+    if not args.root:
+        raise ValueError("--listen needs at least one --root")
+    with AgentServer(address, roots=args.root, token=token) as server:
+        print(f"komparu agent listening on port {server.server_address[1]}", file=sys.stderr)
+        with contextlib.suppress(KeyboardInterrupt):
+            server.serve_forever()
+    return EXIT_EQUAL
+
+
+def _cmd_serve(args: argparse.Namespace) -> int:
+    options = CompareOptions(chunk_size=args.chunk_size, max_workers=args.max_workers)
+    with ComparisonServer(_listen_address(args.listen), options=options,
+                          roots=args.root or None, token=_read_token(args.token_file),
+                          max_jobs=args.max_jobs, max_running=args.max_running,
+                          log=not args.quiet, webhook=args.webhook,
+                          webhook_secret=_read_token(args.webhook_secret_file),
+                          metrics=PrometheusMetrics() if args.metrics else None,
+                          log_sink=args.log_sink) as server:
+        print(f"komparu serving on port {server.server_address[1]}", file=sys.stderr)
+        with contextlib.suppress(KeyboardInterrupt):
+            server.serve_forever()
+    return EXIT_EQUAL
+
+
+def _describe_seconds(seconds: float | None) -> str:
+    if seconds is None:
+        return "-"
+    return f"{seconds * 1000:.1f} ms" if seconds < 1 else f"{seconds:.2f} s"
+
+
+def _print_bench(results: Sequence[BenchResult]) -> None:
+    rows = [("tool", "result", "median", "min", "read", "from disk")]
+    for r in results:
+        rows.append((r.tool, r.result, _describe_seconds(r.median),
+                     _describe_seconds(min(r.seconds, default=None)),
+                     "-" if r.bytes_read is None else _describe_bytes(r.bytes_read),
+                     "-" if r.disk_bytes is None else _describe_bytes(r.disk_bytes)))
+    widths = [max(len(row[i]) for row in rows) for i in range(len(rows[0]))]
+    for row in rows:
+        print("  ".join(cell.ljust(width) for cell, width in zip(row, widths)).rstrip())
+
+
+def _cmd_bench(args: argparse.Namespace) -> int:
+    results = bench(args.left, args.right, tools=args.tools or BENCH_TOOLS,
+                    repeat=args.repeat, warmup=args.warmup)
+    if args.json:
+        print(json.dumps([r.to_dict() for r in results], indent=2))
+    else:
+        _print_bench(results)
+    verdicts = {r.result for r in results if r.seconds}
+    if len(verdicts) > 1:
+        print("komparu: the tools disagree: " + ", ".join(
+            f"{r.tool} {r.result}" for r in results if r.seconds), file=sys.stderr)
+        return EXIT_DIFFERENT
+    return EXIT_EQUAL
+
+
+def _cmd_completions(args: argparse.Namespace) -> int:
+    parser, commands = _build_parser()
+    sys.stdout.write(completion_script(args.shell, parser, commands))
+    return EXIT_EQUAL
+
+
+def _keep_surrogates(stream: Any) -> None:
+    """Let *stream* print names a strict encoder fails on, as it does on a Windows console."""
+    if isinstance(stream, io.TextIOWrapper) and stream.errors == "strict":
+        stream.reconfigure(errors=PATH_ERRORS)
+
+
+def main(argv: Sequence[str] | None = None) -> int:
+    """Run the komparu CLI.
+
+    :param argv: Argument list (defaults to ``sys.argv[1:]``).
+    :returns: Process exit code (0 = equal, 1 = different, 2 = error,
+        3 = only warn-level differences under ``--severity``, 4 = stopped by
+        ``--max-bytes``).
+    """
+    _keep_surrogates(sys.stdout)
+    _keep_surrogates(sys.stderr)
+    parser, commands = _build_parser()
+    args = parser.parse_args(argv)
+    log_sink = None
+    fips = get_config().fips
+    try:
+        if args.fips or env_flag("KOMPARU_FIPS", os.environ):
+            configure(fips=True)
+        if args.no_config or env_flag("KOMPARU_NO_CONFIG", os.environ):
+            if args.profile is not None:
+                raise ValueError("--profile needs the configuration files --no-config ignores")
+        else:
+            defaults = load_defaults(commands, args.config,
+                                     profile=args.profile).get(args.command)
+            if defaults:
+                # Parse again so flags on the command line override the files
+                commands[args.command].set_defaults(**defaults)
+                args = parser.parse_args(argv)
+        if getattr(args, "log", None) is not None:
+            log_sink = LogSink(args.log)
+        args.log_sink = log_sink
+        return args.func(args)
+    except QuotaReachedError as e:
+        print(f"komparu: {e}", file=sys.stderr)
+        return EXIT_QUOTA
+    except (OSError, ValueError, ImportError) as e:
+        print(f"komparu: {e}", file=sys.stderr)
+        if log_sink is not None:
+            with contextlib.suppress(OSError):
+                log_sink.error(f"komparu {args.command}: {e}", command=args.command)
+        return EXIT_ERROR
+    finally:
+        configure(fips=fips)
+        if log_sink is not None:
+            log_sink.close()
@@ -0,0 +1,1992 @@
+"""Pluggable content comparators for directory comparison."""
+
+from __future__ import annotations
+
+import codecs
+import errno
+import gzip
+import io
+import json
+import os
+import posixpath
+import re
+import struct
+import subprocess
+import tarfile
+import zipfile
+import zlib
+from collections.abc import Callable, Iterator, Mapping, Sequence
+from contextlib import ExitStack, closing
+from decimal import Decimal
+from fnmatch import fnmatch
+from itertools import zip_longest
+from pathlib import Path
+from typing import Any, BinaryIO
+from xml.etree import ElementTree
+
+from komparu._types import (
+    DiffEntry,
+    DiffReason,
+    DirResult,
+    EntryKind,
+    SourceReadError,
+    UnsupportedSourceError,
+)
+from komparu._validate import validate_chunk_size
+
+
+class ContentComparator:
+    """Domain-specific equality for files the byte engine reports as different.
+
+    Subclass and implement :meth:`compare`, then pass instances keyed by
+    glob pattern as ``comparators=`` to :func:`komparu.compare_dir`,
+    :func:`komparu.iter_dir` or their async counterparts. The C engine
+    still compares every common file byte-by-byte; a comparator is only
+    consulted for a file whose content or size differs, and can declare
+    it equal (e.g. a container format with a timestamp in its header).
+
+    Comparators run in the calling thread (sync) or on the event loop
+    (async), never in C worker threads. An exception raised by
+    :meth:`compare` aborts the call.
+    """
+
+    def compare(self, path_a: str, path_b: str) -> bool:
+        """Return True if the files at *path_a* and *path_b* are equivalent."""
+        raise NotImplementedError
+
+
+# Bytes sniffed for a BOM or the NUL pattern of BOM-less UTF-16
+_SNIFF_SIZE = 4096
+
+
+def _detect_encoding(stream: BinaryIO, chunk_size: int) -> str:
+    """Guess the encoding of *stream*: UTF-8, UTF-16LE/BE or Latin-1.
+
+    A BOM decides; otherwise NULs on mostly odd (even) offsets mean
+    UTF-16LE (BE), and anything that is not valid UTF-8 is Latin-1.
+    Leaves the stream at offset 0.
+    """
+    head = stream.read(_SNIFF_SIZE)
+    stream.seek(0)
+    if head.startswith(codecs.BOM_UTF8):
+        return "utf-8-sig"
+    if head.startswith((codecs.BOM_UTF16_LE, codecs.BOM_UTF16_BE)):
+        return "utf-16"
+    pairs = len(head) // 2
+    if pairs:
+        even_nuls = head[0:pairs * 2:2].count(0)
+        odd_nuls = head[1:pairs * 2:2].count(0)
+        if odd_nuls > pairs // 2 and even_nuls == 0:
+            return "utf-16-le"
+        if even_nuls > pairs // 2 and odd_nuls == 0:
+            return "utf-16-be"
+    decoder = codecs.getincrementaldecoder("utf-8")()
+    try:
+        while chunk := stream.read(chunk_size):
+            decoder.decode(chunk)
+        decoder.decode(b"", final=True)
+    except UnicodeDecodeError:
+        return "latin-1"
+    finally:
+        stream.seek(0)
+    return "utf-8"
+
+
+_BOMS = (codecs.BOM_UTF8, codecs.BOM_UTF16_LE, codecs.BOM_UTF16_BE)
+
+
+def _skip_bom(stream: BinaryIO) -> None:
+    """Position *stream* after a leading UTF-8 or UTF-16 BOM, if any."""
+    head = stream.read(len(codecs.BOM_UTF8))
+    skip = next((len(bom) for bom in _BOMS if head.startswith(bom)), 0)
+    stream.seek(skip)
+
+
+class _Utf8Reader:
+    """Re-encode a decoded text stream as UTF-8, like a binary file."""
+
+    __slots__ = ("_text",)
+
+    def __init__(self, stream: BinaryIO, encoding: str) -> None:
+        # newline="\n": split lines on LF only and leave CRs untouched
+        self._text = io.TextIOWrapper(stream, encoding=encoding, newline="\n")
+
+    def read(self, size: int) -> bytes:
+        return self._text.read(size).encode("utf-8")
+
+    def __iter__(self) -> Iterator[bytes]:
+        for line in self._text:
+            yield line.encode("utf-8")
+
+
+_Stream = BinaryIO | _Utf8Reader
+
+
+def _normalized(stream: _Stream, chunk_size: int) -> Iterator[bytes]:
+    """Yield *stream* in chunks with every CRLF turned into LF."""
+    carry = b""
+    while chunk := stream.read(chunk_size):
+        chunk = carry + chunk
+        # A CR at the end may be the first half of a CRLF split by the read
+        carry = b"\r" if chunk.endswith(b"\r") else b""
+        if carry:
+            chunk = chunk[:-1]
+        if chunk:
+            yield chunk.replace(b"\r\n", b"\n")
+    if carry:
+        yield carry
+
+
+def _chunks_equal(chunks_a: Iterator[bytes], chunks_b: Iterator[bytes]) -> bool:
+    """Compare two byte streams split at arbitrary, non-empty chunk boundaries."""
+    buf_a = buf_b = b""
+    while True:
+        if not buf_a:
+            buf_a = next(chunks_a, b"")
+        if not buf_b:
+            buf_b = next(chunks_b, b"")
+        if not buf_a or not buf_b:
+            # Chunks are never empty, so one side ended
+            return not buf_a and not buf_b
+        n = min(len(buf_a), len(buf_b))
+        if buf_a[:n] != buf_b[:n]:
+            return False
+        buf_a, buf_b = buf_a[n:], buf_b[n:]
+
+
+def _read_chunks(stream: BinaryIO, chunk_size: int) -> Iterator[bytes]:
+    while chunk := stream.read(chunk_size):
+        yield chunk
+
+
+# Whitespace as diff -b sees it; a CR left before LF counts too
+_SPACE = b" \t\r\f\v"
+_SPACE_RUN = re.compile(rb"[ \t\r\f\v]+")
+
+
+def _normalized_lines(stream: _Stream, collapse: bool) -> Iterator[bytes]:
+    """Yield lines of *stream* without trailing whitespace, keeping the LF."""
+    for line in stream:
+        eol = b"\n" if line.endswith(b"\n") else b""
+        body = line[:-1] if eol else line
+        if collapse:
+            body = _SPACE_RUN.sub(b" ", body)
+        yield body.rstrip(_SPACE) + eol
+
+
+def _without_final_newline(chunks: Iterator[bytes]) -> Iterator[bytes]:
+    """Yield *chunks* minus one LF at the very end, if there is one."""
+    held = b""
+    for chunk in chunks:
+        if held:
+            yield held
+        held = chunk
+    if held.endswith(b"\n"):
+        held = held[:-1]
+    if held:
+        yield held
+
+
+class TextComparator(ContentComparator):
+    """Equal when the files differ only in line endings (CRLF vs LF).
+
+    Lets source trees checked out on Windows and on Linux compare equal.
+    A lone CR (classic Mac line ending) is content, not a line ending.
+    Used by the CLI's ``--text`` mode; register it for text globs, e.g.
+    ``comparators={"*.py": TextComparator()}``.
+
+    The whitespace options compare line by line, for generated config
+    files that differ only in formatting. Under either, a CR is
+    whitespace like any other.
+
+    :param chunk_size: Read size in bytes while re-checking a file.
+    :param ignore_trailing_space: Ignore whitespace at the end of lines
+        (``diff -Z``).
+    :param collapse_whitespace: Treat every run of whitespace as a single
+        space and ignore trailing whitespace (``diff -b``).
+    :param decode: Detect each file's encoding (UTF-8, UTF-16LE/BE,
+        Latin-1) and compare the decoded text, so a UTF-16 export equals
+        its UTF-8 re-encoding. A file that fails to decode is different.
+        Decoding consumes the BOM, so it also implies *ignore_bom*.
+    :param ignore_bom: Skip a leading UTF-8 or UTF-16 byte-order mark,
+        which editors on Windows add and remove at will.
+    :param ignore_final_newline: Ignore whether the last line ends with a
+        line ending; only one is ignored, so an extra blank line still
+        differs.
+    """
+
+    __slots__ = ("_chunk_size", "_trailing", "_collapse", "_decode", "_ignore_bom",
+                 "_final_newline")
+
+    def __init__(
+        self,
+        chunk_size: int = 65536,
+        *,
+        ignore_trailing_space: bool = False,
+        collapse_whitespace: bool = False,
+        decode: bool = False,
+        ignore_bom: bool = False,
+        ignore_final_newline: bool = False,
+    ) -> None:
+        validate_chunk_size(chunk_size)
+        self._chunk_size = chunk_size
+        self._trailing = ignore_trailing_space or collapse_whitespace
+        self._collapse = collapse_whitespace
+        self._decode = decode
+        self._ignore_bom = ignore_bom
+        self._final_newline = ignore_final_newline
+
+    def compare(self, path_a: str, path_b: str) -> bool:
+        with open(path_a, "rb") as fa, open(path_b, "rb") as fb:
+            if not self._decode:
+                if self._ignore_bom:
+                    _skip_bom(fa)
+                    _skip_bom(fb)
+                return self._compare(fa, fb)
+            ra = _Utf8Reader(fa, _detect_encoding(fa, self._chunk_size))
+            rb = _Utf8Reader(fb, _detect_encoding(fb, self._chunk_size))
+            try:
+                return self._compare(ra, rb)
+            except UnicodeDecodeError:
+                return False
+
+    def _compare(self, fa: _Stream, fb: _Stream) -> bool:
+        if self._trailing:
+            return self._compare_lines(fa, fb)
+        return self._compare_chunks(fa, fb)
+
+    def _compare_lines(self, fa: _Stream, fb: _Stream) -> bool:
+        lines_a = _normalized_lines(fa, self._collapse)
+        lines_b = _normalized_lines(fb, self._collapse)
+        if self._final_newline:
+            lines_a, lines_b = _without_final_newline(lines_a), _without_final_newline(lines_b)
+        return all(a == b for a, b in zip_longest(lines_a, lines_b))
+
+    def _compare_chunks(self, fa: _Stream, fb: _Stream) -> bool:
+        chunks_a = _normalized(fa, self._chunk_size)
+        chunks_b = _normalized(fb, self._chunk_size)
+        if self._final_newline:
+            chunks_a, chunks_b = _without_final_newline(chunks_a), _without_final_newline(chunks_b)
+        return _chunks_equal(chunks_a, chunks_b)
+
+
+def _pointer_token(key: Any) -> str:
+    """Escape one reference token of a JSON pointer (RFC 6901)."""
+    return str(key).replace("~", "~0").replace("/", "~1")
+
+
+def _is_number(value: Any) -> bool:
+    return isinstance(value, (int, float, Decimal)) and not isinstance(value, bool)
+
+
+def _value_mismatch(a: Any, b: Any, pointer: str) -> str | None:
+    """JSON pointer of the first difference between two parsed documents.
+
+    Numbers compare by value whatever their type (YAML has int and
+    float, JSON here only Decimal); bool is never a number, since
+    ``1 == True`` in Python.
+    """
+    if _is_number(a) and _is_number(b):
+        return None if a == b else pointer
+    if type(a) is not type(b):
+        return pointer
+    if isinstance(a, dict):
+        # YAML keys need not be strings, so sort on their text
+        for key in sorted(a.keys() | b.keys(), key=str):
+            at = f"{pointer}/{_pointer_token(key)}"
+            if key not in a or key not in b:
+                return at
+            found = _value_mismatch(a[key], b[key], at)
+            if found is not None:
+                return found
+        return None
+    if isinstance(a, list):
+        for index, (item_a, item_b) in enumerate(zip(a, b)):
+            found = _value_mismatch(item_a, item_b, f"{pointer}/{index}")
+            if found is not None:
+                return found
+        if len(a) != len(b):
+            return f"{pointer}/{min(len(a), len(b))}"
+        return None
+    return None if a == b else pointer
+
+
+def _load_json(path: str) -> Any:
+    with open(path, "rb") as f:
+        # Decimal makes 1, 1.0 and 1e0 equal without float rounding
+        return json.load(f, parse_float=Decimal, parse_int=Decimal)
+
+
+class JsonComparator(ContentComparator):
+    """Equal when both files hold the same JSON value.
+
+    Parses both sides and compares structurally: object key order,
+    whitespace and number formatting (``1``, ``1.0``, ``1e0``) do not
+    matter; array order and string content do. Numbers are compared
+    exactly, without float rounding. A file that is not valid JSON is
+    different. Register it for JSON globs, e.g.
+    ``comparators={"*.json": JsonComparator()}``.
+
+    :param on_mismatch: Called as ``on_mismatch(path_a, path_b, pointer)``
+        for every pair found different, with the JSON pointer (RFC 6901)
+        of the first mismatch; ``""`` is the document root.
+    """
+
+    __slots__ = ("_on_mismatch",)
+
+    def __init__(self, on_mismatch: Callable[[str, str, str], None] | None = None) -> None:
+        self._on_mismatch = on_mismatch
+
+    def first_mismatch(self, path_a: str, path_b: str) -> str | None:
+        """JSON pointer of the first difference, or None if equal.
+
+        :raises ValueError: If either file is not valid JSON.
+        """
+        return _value_mismatch(_load_json(path_a), _load_json(path_b), "")
+
+    def compare(self, path_a: str, path_b: str) -> bool:
+        try:
+            pointer = self.first_mismatch(path_a, path_b)
+        except ValueError:
+            return False
+        if pointer is None:
+            return True
+        if self._on_mismatch is not None:
+            self._on_mismatch(path_a, path_b, pointer)
+        return False
+
+
+def _load_yaml(path: str) -> list[Any]:
+    import yaml
+
+    with open(path, "rb") as f:
+        return list(yaml.safe_load_all(f))
+
+
+class YamlComparator(ContentComparator):
+    """Equal when both files hold the same YAML documents.
+
+    Parses both sides with PyYAML's safe loader (``pip install
+    komparu[yaml]``) and compares the resulting values, so anchors and
+    aliases are resolved, merge keys applied, and mapping key order,
+    quoting, flow vs block style and comments do not matter. Sequence
+    order and the order of documents in a multi-document stream do.
+    Kubernetes manifests rendered by different tools compare equal when
+    they mean the same thing. A file that is not valid YAML is different.
+    Register it for YAML globs, e.g.
+    ``comparators={"*.yaml": YamlComparator(), "*.yml": YamlComparator()}``.
+
+    :param on_mismatch: Called as ``on_mismatch(path_a, path_b, pointer)``
+        for every pair found different, with the JSON pointer (RFC 6901)
+        of the first mismatch; its first token is the document index,
+        e.g. ``"/1/spec/replicas"``.
+    :raises ImportError: If PyYAML is not installed.
+    """
+
+    __slots__ = ("_on_mismatch",)
+
+    def __init__(self, on_mismatch: Callable[[str, str, str], None] | None = None) -> None:
+        try:
+            import yaml
+        except ImportError:
+            raise ImportError(
+                "YamlComparator needs PyYAML: pip install komparu[yaml]"
+            ) from None
+        self._on_mismatch = on_mismatch
+
+    def first_mismatch(self, path_a: str, path_b: str) -> str | None:
+        """JSON pointer of the first difference, or None if equal.
+
+        :raises yaml.YAMLError: If either file is not valid YAML.
+        """
+        return _value_mismatch(_load_yaml(path_a), _load_yaml(path_b), "")
+
+    def compare(self, path_a: str, path_b: str) -> bool:
+        import yaml
+
+        try:
+            pointer = self.first_mismatch(path_a, path_b)
+        except yaml.YAMLError:
+            return False
+        if pointer is None:
+            return True
+        if self._on_mismatch is not None:
+            self._on_mismatch(path_a, path_b, pointer)
+        return False
+
+
+class GzipComparator(ContentComparator):
+    """Equal when two gzip files decompress to the same bytes.
+
+    Two gzips of identical data routinely differ byte-wise: the header
+    stores a timestamp and the original file name, and the compression
+    level changes the deflate stream. Both sides are decompressed as
+    they are read, so memory stays at ``chunk_size`` per side.
+    Multi-member files are read through to the end. A file that is not
+    valid gzip is different. Register it with
+    ``comparators={"*.gz": GzipComparator()}``; :class:`DecompressComparator`
+    also handles bzip2, xz and zstd.
+
+    :param chunk_size: Decompressed bytes read per step.
+    """
+
+    __slots__ = ("_chunk_size",)
+
+    def __init__(self, chunk_size: int = 65536) -> None:
+        validate_chunk_size(chunk_size)
+        self._chunk_size = chunk_size
+
+    def compare(self, path_a: str, path_b: str) -> bool:
+        try:
+            with gzip.open(path_a, "rb") as fa, gzip.open(path_b, "rb") as fb:
+                return _chunks_equal(_read_chunks(fa, self._chunk_size),
+                                     _read_chunks(fb, self._chunk_size))
+        except (gzip.BadGzipFile, EOFError, zlib.error):
+            return False
+
+
+# Leading bytes of each format; unrecognized files are read as they are
+_MAGIC = (
+    (b"\x1f\x8b", "gzip"),
+    (b"BZh", "bzip2"),
+    (b"\xfd7zXZ\x00", "xz"),
+    (b"\x28\xb5\x2f\xfd", "zstd"),
+)
+_MAGIC_SIZE = max(len(magic) for magic, _ in _MAGIC)
+
+# Opener and the exceptions it raises on corrupt data
+_Decompressor = tuple[Callable[[str], BinaryIO], tuple[type[Exception], ...]]
+
+
+def _decompressors() -> dict[str, _Decompressor]:
+    """Decompressors this interpreter provides, by format name."""
+    found: dict[str, _Decompressor] = {
+        "gzip": (lambda p: gzip.open(p, "rb"), (gzip.BadGzipFile, EOFError, zlib.error)),
+    }
+    try:
+        import bz2
+        found["bzip2"] = (lambda p: bz2.open(p, "rb"), (OSError, EOFError))
+    except ImportError:
+        pass
+    try:
+        import lzma
+        found["xz"] = (lambda p: lzma.open(p, "rb"), (lzma.LZMAError, EOFError))
+    except ImportError:
+        pass
+    try:
+        from compression import zstd  # Python 3.14+
+        found["zstd"] = (lambda p: zstd.open(p, "rb"), (zstd.ZstdError, EOFError))
+    except ImportError:
+        try:
+            import zstandard
+            found["zstd"] = (lambda p: zstandard.open(p, "rb"), (zstandard.ZstdError,))
+        except ImportError:
+            pass
+    return found
+
+
+class DecompressComparator(ContentComparator):
+    """Equal when two files decompress to the same bytes.
+
+    The format of each side is chosen by its magic bytes — gzip, bzip2,
+    xz or zstd — not by its name, so compressed log archives are verified
+    for content equality whatever compressor or settings produced them,
+    and a gzip matches an xz of the same data. A file in no known format
+    is read as it is. Both sides are decompressed as they are read, so
+    memory stays at ``chunk_size`` per side. A corrupt or truncated
+    stream is different.
+
+    bzip2 and xz need the interpreter's ``bz2`` and ``lzma`` modules;
+    zstd needs Python 3.14 or ``pip install komparu[zstd]``. See
+    :meth:`available_formats`. Used by the CLI's ``--decompress``.
+
+    :param chunk_size: Decompressed bytes read per step.
+    :raises UnsupportedSourceError: From :meth:`compare`, for a file in
+        a format this interpreter cannot decompress.
+    """
+
+    __slots__ = ("_chunk_size", "_decompressors")
+
+    def __init__(self, chunk_size: int = 65536) -> None:
+        validate_chunk_size(chunk_size)
+        self._chunk_size = chunk_size
+        self._decompressors = _decompressors()
+
+    @staticmethod
+    def available_formats() -> frozenset[str]:
+        """Formats this interpreter can decompress (``"gzip"`` always)."""
+        return frozenset(_decompressors())
+
+    def _detect(self, path: str) -> _Decompressor:
+        with open(path, "rb") as f:
+            head = f.read(_MAGIC_SIZE)
+        fmt = next((name for magic, name in _MAGIC if head.startswith(magic)), None)
+        if fmt is None:
+            return (lambda p: open(p, "rb")), ()
+        if fmt not in self._decompressors:
+            raise UnsupportedSourceError(
+                errno.ENOTSUP, f"{fmt} decompression is not available", path)
+        return self._decompressors[fmt]
+
+    def compare(self, path_a: str, path_b: str) -> bool:
+        (open_a, errors_a), (open_b, errors_b) = self._detect(path_a), self._detect(path_b)
+        try:
+            with open_a(path_a) as fa, open_b(path_b) as fb:
+                return _chunks_equal(_read_chunks(fa, self._chunk_size),
+                                     _read_chunks(fb, self._chunk_size))
+        except errors_a + errors_b:
+            return False
+
+
+try:
+    from lzma import LZMAError
+except ImportError:  # interpreter built without lzma: tarfile cannot read xz
+    LZMAError = OSError
+
+_TAR_ERRORS = (tarfile.TarError, EOFError, OSError, zlib.error, LZMAError)
+
+
+class TarComparator(ContentComparator):
+    """Equal when two tarballs hold the same members with the same contents.
+
+    Compares the member list — normalized name, type, permission bits,
+    size and link target — and the contents of regular files, instead
+    of the raw bytes, so a tarball repackaged with another ``tar``,
+    format (ustar, GNU, pax) or compressor still matches. Compressed
+    tarballs (gzip, bzip2, xz) are opened transparently. Ownership is not
+    compared. A file that is not a readable tarball is different.
+
+    :param chunk_size: Member bytes read per step.
+    :param ignore_mtime: Do not compare member modification times.
+    :param ignore_order: Match members by name rather than by position.
+    """
+
+    __slots__ = ("_chunk_size", "_ignore_mtime", "_ignore_order")
+
+    def __init__(
+        self,
+        chunk_size: int = 65536,
+        *,
+        ignore_mtime: bool = False,
+        ignore_order: bool = False,
+    ) -> None:
+        validate_chunk_size(chunk_size)
+        self._chunk_size = chunk_size
+        self._ignore_mtime = ignore_mtime
+        self._ignore_order = ignore_order
+
+    def _header(self, member: tarfile.TarInfo) -> tuple[Any, ...]:
+        kind = tarfile.REGTYPE if member.isreg() else member.type
+        header = (posixpath.normpath(member.name), kind, member.mode & 0o7777,
+                  member.size, member.linkname)
+        return header if self._ignore_mtime else header + (member.mtime,)
+
+    def _members(self, archive: tarfile.TarFile) -> list[tarfile.TarInfo]:
+        members = archive.getmembers()
+        if self._ignore_order:
+            members.sort(key=lambda m: posixpath.normpath(m.name))
+        return members
+
+    def _contents(self, archive: tarfile.TarFile, member: tarfile.TarInfo) -> Iterator[bytes]:
+        f = archive.extractfile(member)
+        if f is not None:
+            with f:
+                yield from _read_chunks(f, self._chunk_size)
+
+    def _equal(self, ta: tarfile.TarFile, tb: tarfile.TarFile) -> bool:
+        members_a, members_b = self._members(ta), self._members(tb)
+        if len(members_a) != len(members_b):
+            return False
+        pairs = list(zip(members_a, members_b))
+        if any(self._header(a) != self._header(b) for a, b in pairs):
+            return False
+        return all(_chunks_equal(self._contents(ta, a), self._contents(tb, b))
+                   for a, b in pairs if a.isreg())
+
+    def compare(self, path_a: str, path_b: str) -> bool:
+        try:
+            with tarfile.open(path_a, "r:*") as ta, tarfile.open(path_b, "r:*") as tb:
+                return self._equal(ta, tb)
+        except _TAR_ERRORS:
+            return False
+
+
+class _FileSlice(io.RawIOBase):
+    """Read-only view of *length* bytes of *stream* starting at *offset*."""
+
+    def __init__(self, stream: BinaryIO, offset: int, length: int) -> None:
+        super().__init__()
+        self._stream = stream
+        self._offset = offset
+        self._length = length
+        self._pos = 0
+
+    def readable(self) -> bool:
+        return True
+
+    def seekable(self) -> bool:
+        return True
+
+    def tell(self) -> int:
+        return self._pos
+
+    def seek(self, pos: int, whence: int = os.SEEK_SET) -> int:
+        base = (0, self._pos, self._length)[whence]
+        self._pos = max(0, base + pos)
+        return self._pos
+
+    def readinto(self, buffer: Any) -> int:
+        n = min(len(buffer), self._length - self._pos)
+        if n <= 0:
+            return 0
+        self._stream.seek(self._offset + self._pos)
+        data = self._stream.read(n)
+        buffer[:len(data)] = data
+        self._pos += len(data)
+        return len(data)
+
+
+# name, mtime, mode, offset and size of one ar member
+_ArMember = tuple[str, int, int, int, int]
+
+
+def _ar_members(stream: BinaryIO) -> list[_ArMember]:
+    """Members of a Unix ar archive, the container of Debian packages."""
+    end = stream.seek(0, os.SEEK_END)
+    stream.seek(0)
+    if stream.read(8) != b"!<arch>\n":
+        raise ValueError("not an ar archive")
+    members = []
+    while header := stream.read(60):
+        if len(header) < 60 or header[58:60] != b"`\n":
+            raise ValueError("ar member header invalid")
+        name = header[:16].decode("ascii").rstrip().removesuffix("/")  # GNU ends names in /
+        offset, size = stream.tell(), int(header[48:58])
+        if offset + size > end:
+            raise ValueError(f"ar member {name} truncated")
+        members.append((name, int(header[16:28]), int(header[40:48], 8), offset, size))
+        stream.seek(offset + size + size % 2)  # members are 2-byte aligned
+    return members
+
+
+class DebComparator(ContentComparator):
+    """Equal when two Debian packages hold the same members and contents.
+
+    A ``.deb`` is an ar archive of ``debian-binary``, ``control.tar.*``
+    and ``data.tar.*``. Member names and permission bits are compared;
+    the embedded tarballs are compared like :class:`TarComparator` does,
+    whatever their compressor, and other members byte-by-byte. Owners
+    are not compared. For reproducible-build verification set both
+    flags, as the CLI's ``--reproducible`` does. A file that is not a
+    readable ar archive is different.
+
+    A ``data.tar.zst`` (Ubuntu's default) needs Python 3.14, where
+    :mod:`tarfile` reads zstd.
+
+    :param chunk_size: Member bytes read per step.
+    :param ignore_mtime: Do not compare modification times, of the ar
+        members and of the files inside the tarballs.
+    :param ignore_order: Match members, at both levels, by name rather
+        than by position.
+    :raises UnsupportedSourceError: From :meth:`compare`, for a zstd
+        tarball member before Python 3.14.
+    """
+
+    __slots__ = ("_chunk_size", "_ignore_mtime", "_ignore_order", "_tar")
+
+    def __init__(
+        self,
+        chunk_size: int = 65536,
+        *,
+        ignore_mtime: bool = False,
+        ignore_order: bool = False,
+    ) -> None:
+        validate_chunk_size(chunk_size)
+        self._chunk_size = chunk_size
+        self._ignore_mtime = ignore_mtime
+        self._ignore_order = ignore_order
+        self._tar = TarComparator(chunk_size, ignore_mtime=ignore_mtime,
+                                  ignore_order=ignore_order)
+
+    def _header(self, member: _ArMember) -> tuple[Any, ...]:
+        name, mtime, mode, _, _ = member
+        return (name, mode & 0o7777) if self._ignore_mtime else (name, mode & 0o7777, mtime)
+
+    def _equal(self, fa: BinaryIO, a: _ArMember, fb: BinaryIO, b: _ArMember) -> bool:
+        slice_a = io.BufferedReader(_FileSlice(fa, a[3], a[4]))
+        slice_b = io.BufferedReader(_FileSlice(fb, b[3], b[4]))
+        if ".tar" in a[0]:
+            with tarfile.open(fileobj=slice_a) as ta, tarfile.open(fileobj=slice_b) as tb:
+                return self._tar._equal(ta, tb)
+        return _chunks_equal(_read_chunks(slice_a, self._chunk_size),
+                             _read_chunks(slice_b, self._chunk_size))
+
+    def compare(self, path_a: str, path_b: str) -> bool:
+        with open(path_a, "rb") as fa, open(path_b, "rb") as fb:
+            try:
+                members_a, members_b = _ar_members(fa), _ar_members(fb)
+            except ValueError:
+                return False
+            if "zst" not in tarfile.TarFile.OPEN_METH:  # Python 3.14+
+                for path, members in ((path_a, members_a), (path_b, members_b)):
+                    if any(name.endswith(".zst") for name, *_ in members):
+                        raise UnsupportedSourceError(
+                            errno.ENOTSUP, "zstd-compressed tarballs need Python 3.14", path)
+            if self._ignore_order:
+                members_a.sort()
+                members_b.sort()
+            if len(members_a) != len(members_b):
+                return False
+            pairs = list(zip(members_a, members_b))
+            if any(self._header(a) != self._header(b) for a, b in pairs):
+                return False
+            try:
+                return all(self._equal(fa, a, fb, b) for a, b in pairs)
+            except _TAR_ERRORS:
+                return False
+
+
+_ZIP_ERRORS = (zipfile.BadZipFile, EOFError, OSError, zlib.error, LZMAError,
+               NotImplementedError, RuntimeError)
+# Extra fields holding timestamps: extended (UT), NTFS and Info-ZIP Unix (UX)
+_ZIP_TIME_FIELDS = frozenset({0x5455, 0x000A, 0x5855})
+
+
+def _zip_extra_without(extra: bytes, drop: frozenset[int]) -> bytes:
+    """*extra* without the fields whose header ID is in *drop*."""
+    kept = bytearray()
+    pos = 0
+    while pos + 4 <= len(extra):
+        field_id, size = struct.unpack_from("<HH", extra, pos)
+        if field_id not in drop:
+            kept += extra[pos:pos + 4 + size]
+        pos += 4 + size
+    return bytes(kept + extra[pos:])
+
+
+class ZipComparator(ContentComparator):
+    """Equal when two zip files hold the same entries with the same contents.
+
+    Entries are matched by normalized path, whatever their order, and
+    compared by decompressed content, so an artifact rebuilt with another
+    compression level or tool still matches; each entry's CRC is checked
+    as it is read. Entry timestamps and the remaining header metadata —
+    permission bits, creator system, extra fields (where extended
+    timestamps live), entry and archive comments — are compared unless
+    ignored. A file that is not a readable zip, or holds an encrypted or
+    unsupported entry, is different.
+
+    :param chunk_size: Entry bytes read per step.
+    :param ignore_timestamps: Do not compare entry modification times,
+        including the extended, NTFS and Unix timestamp extra fields.
+    :param ignore_metadata: Do not compare header metadata other than
+        the path and timestamp.
+    """
+
+    __slots__ = ("_chunk_size", "_ignore_timestamps", "_ignore_metadata")
+
+    def __init__(
+        self,
+        chunk_size: int = 65536,
+        *,
+        ignore_timestamps: bool = False,
+        ignore_metadata: bool = False,
+    ) -> None:
+        validate_chunk_size(chunk_size)
+        self._chunk_size = chunk_size
+        self._ignore_timestamps = ignore_timestamps
+        self._ignore_metadata = ignore_metadata
+
+    def _header(self, entry: zipfile.ZipInfo) -> tuple[Any, ...]:
+        header: tuple[Any, ...] = (posixpath.normpath(entry.filename), entry.is_dir(),
+                                   entry.file_size)
+        if not self._ignore_timestamps:
+            header += (entry.date_time,)
+        if not self._ignore_metadata:
+            extra = entry.extra
+            if self._ignore_timestamps:
+                extra = _zip_extra_without(extra, _ZIP_TIME_FIELDS)
+            header += (entry.external_attr, entry.create_system, extra, entry.comment)
+        return header
+
+    def _entries(self, archive: zipfile.ZipFile) -> list[zipfile.ZipInfo]:
+        return sorted(archive.infolist(), key=lambda e: posixpath.normpath(e.filename))
+
+    def _contents(self, archive: zipfile.ZipFile, entry: zipfile.ZipInfo) -> Iterator[bytes]:
+        with archive.open(entry) as f:
+            yield from _read_chunks(f, self._chunk_size)
+
+    def compare(self, path_a: str, path_b: str) -> bool:
+        try:
+            with zipfile.ZipFile(path_a) as za, zipfile.ZipFile(path_b) as zb:
+                if not self._ignore_metadata and za.comment != zb.comment:
+                    return False
+                entries_a, entries_b = self._entries(za), self._entries(zb)
+                if len(entries_a) != len(entries_b):
+                    return False
+                pairs = list(zip(entries_a, entries_b))
+                if any(self._header(a) != self._header(b) for a, b in pairs):
+                    return False
+                return all(_chunks_equal(self._contents(za, a), self._contents(zb, b))
+                           for a, b in pairs if not a.is_dir())
+        except _ZIP_ERRORS:
+            return False
+
+
+def _read_span(stream: BinaryIO, length: int, chunk_size: int) -> Iterator[bytes]:
+    """Exactly *length* bytes of *stream*; a truncated file raises EOFError."""
+    if length < 0:
+        raise ValueError("negative span length")
+    while length:
+        chunk = stream.read(min(length, chunk_size))
+        if not chunk:
+            raise EOFError("image data truncated")
+        length -= len(chunk)
+        yield chunk
+
+
+_PNG_SIGNATURE = b"\x89PNG\r\n\x1a\n"
+# Text (XMP lives in iTXt), EXIF, ICC profile and modification time
+_PNG_METADATA = frozenset({b"tEXt", b"zTXt", b"iTXt", b"eXIf", b"iCCP", b"tIME"})
+
+
+def _png_pixels(stream: BinaryIO, chunk_size: int) -> Iterator[bytes]:
+    """Non-metadata chunks of a PNG, IDAT as one decompressed stream.
+
+    Splitting the deflate stream into IDAT chunks differently, or
+    recompressing it, does not change the result.
+    """
+    stream.seek(len(_PNG_SIGNATURE))
+    inflate = None
+    while True:
+        header = stream.read(8)
+        if len(header) < 8:
+            raise EOFError("PNG has no IEND chunk")
+        length, kind = struct.unpack(">I4s", header)
+        if kind == b"IDAT":
+            if inflate is None:
+                inflate = zlib.decompressobj()
+                yield kind
+            for data in _read_span(stream, length, chunk_size):
+                while data:
+                    if out := inflate.decompress(data, chunk_size):
+                        yield out
+                    data = inflate.unconsumed_tail
+        elif kind in _PNG_METADATA:
+            stream.seek(length, os.SEEK_CUR)
+        else:
+            if inflate is not None and not inflate.eof:
+                raise ValueError("PNG image data truncated")
+            yield header
+            yield from _read_span(stream, length, chunk_size)
+            if kind == b"IEND":
+                return
+        stream.seek(4, os.SEEK_CUR)  # CRC; corrupt image data fails to inflate
+
+
+# APP0-APP13 and APP15 (JFIF, EXIF, XMP, ICC, IPTC, ...) and comments;
+# APP14 stays, since Adobe's color transform changes the decoded pixels
+_JPEG_METADATA = frozenset(range(0xE0, 0xF0)) - {0xEE} | {0xFE}
+# Markers without a length field
+_JPEG_STANDALONE = frozenset({0x01, *range(0xD0, 0xD8)})
+
+
+def _jpeg_pixels(stream: BinaryIO, chunk_size: int) -> Iterator[bytes]:
+    """A JPEG's segments without metadata, then its scans as they are.
+
+    Compares the coded image — tables, frame header and entropy-coded
+    data — so equal output means equal pixels without decoding.
+    """
+    stream.seek(2)
+    while True:
+        if stream.read(1) != b"\xff":
+            raise ValueError("JPEG marker expected")
+        marker = stream.read(1)
+        while marker == b"\xff":  # fill bytes
+            marker = stream.read(1)
+        if not marker:
+            raise EOFError("JPEG has no EOI marker")
+        code = marker[0]
+        if code == 0xD9:  # EOI
+            yield marker
+            return
+        if code in _JPEG_STANDALONE:
+            yield marker
+            continue
+        size = stream.read(2)
+        if len(size) < 2 or (length := int.from_bytes(size, "big")) < 2:
+            raise ValueError("JPEG segment length invalid")
+        if code in _JPEG_METADATA:
+            stream.seek(length - 2, os.SEEK_CUR)
+            continue
+        yield marker + size
+        yield from _read_span(stream, length - 2, chunk_size)
+        if code == 0xDA:  # SOS: scans and the markers between them are all data
+            yield from _read_chunks(stream, chunk_size)
+            return
+
+
+# struct code and values per item of each TIFF field type (RATIONALs are pairs)
+_TIFF_TYPES = {1: ("B", 1), 2: ("B", 1), 3: ("H", 1), 4: ("I", 1), 5: ("I", 2),
+               6: ("b", 1), 7: ("B", 1), 8: ("h", 1), 9: ("i", 1), 10: ("i", 2),
+               11: ("f", 1), 12: ("d", 1), 13: ("I", 1)}
+# Tags that describe how the strips or tiles decode into pixels
+_TIFF_LAYOUT = frozenset({254, 256, 257, 258, 259, 262, 266, 277, 278, 284, 317, 320,
+                          322, 323, 338, 339, 347, 529, 530, 531, 532})
+# (offsets, byte counts) tag pairs locating the image data
+_TIFF_SEGMENTS = ((273, 279), (324, 325))
+
+
+def _tiff_pixels(stream: BinaryIO, chunk_size: int) -> Iterator[bytes]:
+    """Layout tags and image data of every page of a TIFF, in order.
+
+    EXIF, XMP, ICC, IPTC and every other tag are skipped, as is where
+    the data sits in the file, so rewriting tags does not matter.
+    """
+    end = stream.seek(0, os.SEEK_END)
+    stream.seek(0)
+    order = "<" if stream.read(2) == b"II" else ">"
+    stream.seek(4)
+    offset, = struct.unpack(order + "I", stream.read(4))
+    seen = set()
+    while offset:
+        if offset in seen:
+            raise ValueError("TIFF page chain loops")
+        seen.add(offset)
+        stream.seek(offset)
+        count, = struct.unpack(order + "H", stream.read(2))
+        entries = stream.read(count * 12)
+        next_offset = stream.read(4)
+        if len(next_offset) < 4:
+            raise EOFError("TIFF page truncated")
+        tags = {}
+        for i in range(0, count * 12, 12):
+            tag, kind, n = struct.unpack_from(order + "HHI", entries, i)
+            if tag not in _TIFF_LAYOUT and all(tag not in pair for pair in _TIFF_SEGMENTS):
+                continue
+            if kind not in _TIFF_TYPES:
+                raise ValueError(f"TIFF tag {tag} has unknown type {kind}")
+            code, per = _TIFF_TYPES[kind]
+            fmt = f"{order}{n * per}{code}"
+            size = struct.calcsize(fmt)
+            if size <= 4:
+                raw = entries[i + 8:i + 8 + size]
+            else:
+                value_offset, = struct.unpack_from(order + "I", entries, i + 8)
+                if value_offset + size > end:
+                    raise EOFError(f"TIFF tag {tag} truncated")
+                here = stream.tell()
+                stream.seek(value_offset)
+                raw = stream.read(size)
+                stream.seek(here)
+            if len(raw) < size:
+                raise EOFError(f"TIFF tag {tag} truncated")
+            tags[tag] = struct.unpack(fmt, raw)
+        yield repr(sorted((tag, value) for tag, value in tags.items()
+                          if tag in _TIFF_LAYOUT)).encode()
+        for offsets_tag, counts_tag in _TIFF_SEGMENTS:
+            offsets, counts = tags.get(offsets_tag, ()), tags.get(counts_tag, ())
+            if len(offsets) != len(counts):
+                raise ValueError("TIFF strip or tile counts do not match")
+            for start, length in zip(offsets, counts):
+                stream.seek(start)
+                yield from _read_span(stream, length, chunk_size)
+        offset, = struct.unpack(order + "I", next_offset)
+
+
+_IMAGE_FORMATS: tuple[tuple[bytes, Callable[[BinaryIO, int], Iterator[bytes]]], ...] = (
+    (_PNG_SIGNATURE, _png_pixels),
+    (b"\xff\xd8", _jpeg_pixels),
+    (b"II*\x00", _tiff_pixels),
+    (b"MM\x00*", _tiff_pixels),
+)
+
+
+class ImageComparator(ContentComparator):
+    """Equal when two images hold the same pixel data, whatever their metadata.
+
+    Made for photo libraries where a tool rewrote tags: EXIF, XMP, ICC
+    profiles, IPTC, comments and text chunks are skipped, and the image
+    data itself is compared. PNG is compared by its decompressed image
+    stream, JPEG by its coded tables and scans, TIFF by the tags that
+    define the pixel layout and the strip or tile data of each page.
+    The format is detected from magic bytes. Nothing is decoded to
+    pixels, so the same picture re-encoded (saved again as JPEG,
+    re-filtered PNG) is different, as are two files in different formats
+    or a file that is not a valid PNG, JPEG or baseline TIFF. Used by
+    the CLI's ``--image``.
+
+    :param chunk_size: Image bytes read per step.
+    """
+
+    __slots__ = ("_chunk_size",)
+
+    def __init__(self, chunk_size: int = 65536) -> None:
+        validate_chunk_size(chunk_size)
+        self._chunk_size = chunk_size
+
+    @staticmethod
+    def _parser(stream: BinaryIO) -> Callable[[BinaryIO, int], Iterator[bytes]] | None:
+        head = stream.read(len(_PNG_SIGNATURE))
+        return next((parse for magic, parse in _IMAGE_FORMATS if head.startswith(magic)),
+                    None)
+
+    def compare(self, path_a: str, path_b: str) -> bool:
+        with open(path_a, "rb") as fa, open(path_b, "rb") as fb:
+            parse = self._parser(fa)
+            if parse is None or self._parser(fb) is not parse:
+                return False
+            try:
+                return _chunks_equal(parse(fa, self._chunk_size),
+                                     parse(fb, self._chunk_size))
+            except (ValueError, EOFError, struct.error, zlib.error):
+                return False
+
+
+def _id3v2_size(header: bytes) -> int:
+    """Total size of the ID3v2 tag whose 10-byte header or footer is given."""
+    size = header[6] << 21 | header[7] << 14 | header[8] << 7 | header[9]  # syncsafe
+    return 10 + size + (10 if header[5] & 0x10 else 0)
+
+
+def _tagged_span(stream: BinaryIO) -> tuple[int, int]:
+    """Offsets of the data between leading and trailing tags.
+
+    Leading ID3v2 tags; trailing ID3v1 (and its TAG+ extension), APEv2,
+    Lyrics3v2 and footer-flagged ID3v2 tags, in any order.
+    """
+    end = stream.seek(0, os.SEEK_END)
+    start = 0
+    while True:
+        stream.seek(start)
+        header = stream.read(10)
+        if len(header) < 10 or not header.startswith(b"ID3"):
+            break
+        start += _id3v2_size(header)
+    while end > start:
+        stream.seek(max(start, end - 355))
+        tail = stream.read(end - stream.tell())
+        if tail[-128:-125] == b"TAG":
+            end -= 227 + 128 if tail[-355:-351] == b"TAG+" else 128
+        elif tail[-32:-24] == b"APETAGEX":
+            size, _, flags = struct.unpack_from("<III", tail, len(tail) - 20)
+            if size < 32:
+                raise ValueError("APE tag size invalid")
+            end -= size + (32 if flags & 0x80000000 else 0)
+        elif tail[-9:] == b"LYRICS200" and tail[-15:-9].isdigit():
+            end -= int(tail[-15:-9]) + 15
+        elif tail[-10:-7] == b"3DI":
+            end -= _id3v2_size(tail[-10:])
+        else:
+            break
+    if end < start:
+        raise ValueError("tags overlap")
+    return start, end
+
+
+# FLAC metadata blocks that only hold tags: padding, Vorbis comment, picture
+_FLAC_TAG_BLOCKS = frozenset({1, 4, 6})
+# Ogg packets carrying the tags of a Vorbis or Opus stream
+_OGG_TAG_PACKETS = (b"\x03vorbis", b"OpusTags")
+
+
+def _flac_frames(stream: BinaryIO, start: int, end: int, chunk_size: int) -> Iterator[bytes]:
+    """FLAC metadata blocks other than tags, then the audio frames."""
+    stream.seek(start + 4)
+    last = False
+    while not last:
+        header = stream.read(4)
+        if len(header) < 4:
+            raise EOFError("FLAC metadata truncated")
+        last, kind = bool(header[0] & 0x80), header[0] & 0x7F
+        length = int.from_bytes(header[1:], "big")
+        if kind in _FLAC_TAG_BLOCKS:
+            stream.seek(length, os.SEEK_CUR)
+        else:
+            yield bytes((kind,)) + header[1:]  # the last-block flag moves with the tags
+            yield from _read_span(stream, length, chunk_size)
+    yield from _read_span(stream, end - stream.tell(), chunk_size)
+
+
+def _ogg_packets(stream: BinaryIO) -> Iterator[bytes]:
+    """Packets of an Ogg file other than tags, tagged with their stream.
+
+    Pages are reassembled, so page boundaries, sequence numbers and CRCs,
+    which all change when the tag packet grows, do not matter.
+    """
+    partial: dict[int, bytearray] = {}
+    streams: dict[int, int] = {}  # serial number -> order of appearance
+    while header := stream.read(27):
+        if len(header) < 27 or not header.startswith(b"OggS"):
+            raise ValueError("Ogg page header invalid")
+        serial, = struct.unpack_from("<I", header, 14)
+        lacing = stream.read(header[26])
+        body = stream.read(sum(lacing))
+        if len(lacing) < header[26] or len(body) < sum(lacing):
+            raise EOFError("Ogg page truncated")
+        index = streams.setdefault(serial, len(streams))
+        packet = partial.setdefault(serial, bytearray())
+        pos = 0
+        for lace in lacing:
+            packet += body[pos:pos + lace]
+            pos += lace
+            if lace < 255:
+                if not packet.startswith(_OGG_TAG_PACKETS):
+                    yield struct.pack("<II", index, len(packet)) + packet
+                packet.clear()
+
+
+class AudioComparator(ContentComparator):
+    """Equal when two audio files hold the same audio, whatever their tags.
+
+    Made for verifying retagged music libraries against the originals:
+
+    * MP3: leading ID3v2 and trailing ID3v1, APEv2, Lyrics3v2 tags are
+      skipped and the frames between them compared;
+    * FLAC: padding, Vorbis comment and picture blocks are skipped
+      (stray ID3 tags too); STREAMINFO, which holds the MD5 of the
+      decoded audio, and the frames are compared;
+    * Ogg Vorbis and Opus: pages are reassembled into packets and all
+      but the tag packet compared, so a tag that changed size and moved
+      every page boundary does not matter.
+
+    The format is detected from magic bytes. Audio is not decoded, so a
+    re-encoded file is different, as are files in different formats and
+    anything not recognized as one of the above. Used by the CLI's
+    ``--audio``.
+
+    :param chunk_size: Audio bytes read per step.
+    """
+
+    __slots__ = ("_chunk_size",)
+
+    def __init__(self, chunk_size: int = 65536) -> None:
+        validate_chunk_size(chunk_size)
+        self._chunk_size = chunk_size
+
+    def _frames(self, stream: BinaryIO) -> tuple[str, Iterator[bytes]] | None:
+        head = stream.read(4)
+        if head == b"OggS":
+            stream.seek(0)
+            return "ogg", _ogg_packets(stream)
+        start, end = _tagged_span(stream)
+        stream.seek(start)
+        head = stream.read(4)
+        if head == b"fLaC":
+            return "flac", _flac_frames(stream, start, end, self._chunk_size)
+        if start or (len(head) > 1 and head[0] == 0xFF and head[1] & 0xE0 == 0xE0):
+            stream.seek(start)
+            return "mp3", _read_span(stream, end - start, self._chunk_size)
+        return None
+
+    def compare(self, path_a: str, path_b: str) -> bool:
+        with open(path_a, "rb") as fa, open(path_b, "rb") as fb:
+            try:
+                a, b = self._frames(fa), self._frames(fb)
+                if a is None or b is None or a[0] != b[0]:
+                    return False
+                return _chunks_equal(a[1], b[1])
+            except (ValueError, EOFError, struct.error):
+                return False
+
+
+class _Masked:
+    """A file's bytes with ranges zeroed or cut out, to compare the rest."""
+
+    __slots__ = ("data", "_cuts")
+
+    def __init__(self, data: bytes) -> None:
+        self.data = bytearray(data)
+        self._cuts: list[tuple[int, int]] = []
+
+    def _check(self, offset: int, length: int) -> None:
+        if offset < 0 or length < 0 or offset + length > len(self.data):
+            raise ValueError("range outside the file")
+
+    def zero(self, offset: int, length: int) -> None:
+        self._check(offset, length)
+        self.data[offset:offset + length] = bytes(length)
+
+    def cut(self, offset: int, length: int) -> None:
+        """Drop the range; unlike zeroing, its length may differ between files."""
+        self._check(offset, length)
+        self._cuts.append((offset, length))
+
+    def result(self) -> bytearray:
+        for offset, length in sorted(self._cuts, reverse=True):
+            del self.data[offset:offset + length]
+        return self.data
+
+
+_ELF_BUILD_ID_SECTIONS = frozenset({b".note.gnu.build-id", b".note.go.buildid"})
+_SHT_NOBITS = 8
+# Trailer of a signed Linux kernel module, after a 12-byte module_signature
+_MODULE_SIGNATURE = b"~Module signature appended~\n"
+
+_PE_CODEVIEW = 2
+_PE_REPRO = 16
+
+_MACHO_MAGIC = {
+    b"\xfe\xed\xfa\xce": (">", False), b"\xce\xfa\xed\xfe": ("<", False),
+    b"\xfe\xed\xfa\xcf": (">", True), b"\xcf\xfa\xed\xfe": ("<", True),
+}
+_LC_SEGMENT, _LC_SEGMENT_64, _LC_UUID, _LC_CODE_SIGNATURE = 0x1, 0x19, 0x1B, 0x1D
+# Load commands holding a dylib struct, with its timestamp at offset 12
+_LC_DYLIBS = frozenset({0x0C, 0x0D, 0x18, 0x1F, 0x20, 0x23})
+# Java class files share the fat magic; their major version is at least 45
+_FAT_MAX_ARCHS = 45
+
+
+class ExecutableComparator(ContentComparator):
+    """Equal when two executables differ only in build IDs, signatures or timestamps.
+
+    Meant for verifying that two compiler runs produced functionally
+    identical binaries. ELF, PE and Mach-O (thin or universal) files are
+    recognized by their magic bytes; the ignored parts are zeroed, or cut
+    out where their size can change, and the rest is compared:
+
+    * build IDs: ELF ``.note.gnu.build-id`` and ``.note.go.buildid``,
+      the PE CodeView GUID and age and the ``/Brepro`` hash, the Mach-O
+      ``LC_UUID``;
+    * signatures: an appended Linux kernel module signature, the PE
+      certificate table (Authenticode), the Mach-O ``LC_CODE_SIGNATURE``
+      blob and the load command sizes that change with it;
+    * timestamps: the PE file header, export and debug directory
+      timestamps, Mach-O dylib timestamps.
+
+    The PE checksum is always ignored, since it covers all of the above.
+    Both files are read into memory. Files in different or unknown
+    formats, or malformed ones, are different. Executables seldom share
+    a file extension, so register it for the paths that hold them, e.g.
+    ``{"bin/*": ExecutableComparator(), "*.so": ExecutableComparator()}``.
+
+    :param ignore_build_id: Ignore build IDs.
+    :param ignore_signature: Ignore code signatures.
+    :param ignore_timestamps: Ignore embedded link timestamps.
+    """
+
+    __slots__ = ("_build_id", "_signature", "_timestamps")
+
+    def __init__(
+        self,
+        *,
+        ignore_build_id: bool = True,
+        ignore_signature: bool = True,
+        ignore_timestamps: bool = True,
+    ) -> None:
+        self._build_id = ignore_build_id
+        self._signature = ignore_signature
+        self._timestamps = ignore_timestamps
+
+    def _elf(self, m: _Masked) -> None:
+        d = m.data
+        order = "<" if d[5] == 1 else ">"
+        if d[4] == 2:  # ELFCLASS64
+            shoff, = struct.unpack_from(order + "Q", d, 0x28)
+            shentsize, shnum, shstrndx = struct.unpack_from(order + "HHH", d, 0x3A)
+            fmt = order + "IIQQQQ"
+        else:
+            shoff, = struct.unpack_from(order + "I", d, 0x20)
+            shentsize, shnum, shstrndx = struct.unpack_from(order + "HHH", d, 0x2E)
+            fmt = order + "IIIIII"
+        if self._build_id and shnum:
+            sections = [struct.unpack_from(fmt, d, shoff + i * shentsize) for i in range(shnum)]
+            names = sections[shstrndx][4]
+            for name, kind, _, _, offset, size in sections:
+                start = names + name
+                section = bytes(d[start:d.index(0, start)])
+                if kind != _SHT_NOBITS and section in _ELF_BUILD_ID_SECTIONS:
+                    m.zero(offset, size)
+        if self._signature and d.endswith(_MODULE_SIGNATURE):
+            end = len(d) - len(_MODULE_SIGNATURE)
+            sig_len, = struct.unpack_from(">I", d, end - 4)
+            m.cut(end - 12 - sig_len, 12 + sig_len + len(_MODULE_SIGNATURE))
+
+    def _pe(self, m: _Masked) -> None:
+        d = m.data
+        pe, = struct.unpack_from("<I", d, 0x3C)
+        if d[pe:pe + 4] != b"PE\0\0":
+            raise ValueError("PE signature missing")
+        coff = pe + 4
+        nsections, = struct.unpack_from("<H", d, coff + 2)
+        optional_size, = struct.unpack_from("<H", d, coff + 16)
+        optional = coff + 20
+        dirs = optional + (112 if struct.unpack_from("<H", d, optional)[0] == 0x20B else 96)
+        ndirs, = struct.unpack_from("<I", d, dirs - 4)
+        table = optional + optional_size
+        sections = [struct.unpack_from("<IIII", d, table + 40 * i + 8) for i in range(nsections)]
+
+        def directory(index: int) -> tuple[int, int]:
+            return struct.unpack_from("<II", d, dirs + 8 * index) if index < ndirs else (0, 0)
+
+        def file_offset(rva: int) -> int:
+            for vsize, va, raw_size, raw in sections:
+                if va <= rva < va + max(vsize, raw_size):
+                    return raw + rva - va
+            raise ValueError(f"RVA {rva:#x} is in no section")
+
+        m.zero(optional + 64, 4)  # CheckSum
+        if self._timestamps:
+            m.zero(coff + 4, 4)
+            rva, size = directory(0)
+            if size:
+                m.zero(file_offset(rva) + 4, 4)
+        if self._signature:
+            offset, size = directory(4)  # a file offset, not an RVA
+            if size:
+                m.cut(offset, size)
+                m.zero(dirs + 32, 8)
+        rva, size = directory(6)
+        base = file_offset(rva) if size else 0
+        for entry in range(base, base + size - size % 28, 28):
+            kind, data_size, _, raw = struct.unpack_from("<IIII", d, entry + 12)
+            if self._timestamps:
+                m.zero(entry + 4, 4)
+            if self._build_id and kind == _PE_CODEVIEW and d[raw:raw + 4] == b"RSDS":
+                m.zero(raw + 4, 20)  # GUID and age
+            elif self._build_id and kind == _PE_REPRO:
+                m.zero(raw, data_size)
+
+    def _macho(self, m: _Masked, start: int) -> None:
+        d = m.data
+        order, wide = _MACHO_MAGIC[bytes(d[start:start + 4])]
+        ncmds, = struct.unpack_from(order + "I", d, start + 16)
+        pos = start + (32 if wide else 28)
+        for _ in range(ncmds):
+            cmd, size = struct.unpack_from(order + "II", d, pos)
+            cmd &= ~0x80000000  # LC_REQ_DYLD
+            if cmd == _LC_UUID and self._build_id:
+                m.zero(pos + 8, 16)
+            elif cmd == _LC_CODE_SIGNATURE and self._signature:
+                offset, length = struct.unpack_from(order + "II", d, pos + 8)
+                m.cut(start + offset, length)
+                m.zero(pos + 8, 8)
+            elif cmd in _LC_DYLIBS and self._timestamps:
+                m.zero(pos + 12, 4)
+            elif (cmd in (_LC_SEGMENT, _LC_SEGMENT_64) and self._signature
+                  and d[pos + 8:pos + 24].rstrip(b"\0") == b"__LINKEDIT"):
+                # vmsize and filesize grow with the signature
+                if wide:
+                    m.zero(pos + 32, 8)
+                    m.zero(pos + 48, 8)
+                else:
+                    m.zero(pos + 28, 4)
+                    m.zero(pos + 36, 4)
+            if size < 8:
+                raise ValueError("Mach-O load command size invalid")
+            pos += size
+
+    def _fat(self, m: _Masked, count: int) -> None:
+        d = m.data
+        wide = d[3] == 0xBF
+        entry_size, fmt, field_size = (32, ">QQ", 16) if wide else (20, ">II", 8)
+        slices = []
+        for pos in range(8, 8 + count * entry_size, entry_size):
+            offset, size = struct.unpack_from(fmt, d, pos + 8)
+            self._macho(m, offset)
+            slices.append((offset, size))
+            if self._signature:
+                m.zero(pos + 8, field_size)  # offset and size
+        if self._signature:
+            # Slices move and their alignment padding changes with the signatures
+            cursor = 8 + count * entry_size
+            for offset, size in sorted(slices):
+                m.cut(cursor, offset - cursor)
+                cursor = offset + size
+            m.cut(cursor, len(d) - cursor)
+
+    def _normalized(self, path: str) -> tuple[str, bytearray] | None:
+        with open(path, "rb") as f:
+            m = _Masked(f.read())
+        head = bytes(m.data[:8])
+        if head.startswith(b"\x7fELF"):
+            kind = "elf"
+            self._elf(m)
+        elif head.startswith(b"MZ"):
+            kind = "pe"
+            self._pe(m)
+        elif head[:4] in _MACHO_MAGIC:
+            kind = "macho"
+            self._macho(m, 0)
+        elif head[:4] in (b"\xca\xfe\xba\xbe", b"\xca\xfe\xba\xbf") and (
+                count := int.from_bytes(head[4:8], "big")) < _FAT_MAX_ARCHS:
+            kind = "fat"
+            self._fat(m, count)
+        else:
+            return None
+        return kind, m.result()
+
+    def compare(self, path_a: str, path_b: str) -> bool:
+        try:
+            a, b = self._normalized(path_a), self._normalized(path_b)
+        except (ValueError, KeyError, struct.error):
+            return False
+        return a is not None and a == b
+
+
+# Values PDF generators change on every export — the file identifier and
+# the Info dates — and the byte offsets that move when they change length
+_PDF_VOLATILE = re.compile(
+    rb"(/ID)\s*\[[^\]]*\]"
+    rb"|(/(?:CreationDate|ModDate))\s*(?:\([^)]*\)|<[^>]*>)"
+    rb"|(/Length)\s+\d+(?:\s+\d+\s+R)?"
+    rb"|(/Prev)\s+\d+"
+    rb"|(startxref)\s+\d+"
+    rb"|\b(xref)\r?\n.*?(?=trailer)",
+    re.DOTALL,
+)
+_XMP_FIELDS = rb"(?:xmp:(?:Create|Modify|Metadata)Date|xmpMM:(?:Document|Instance)ID)"
+_XMP_ELEMENT = re.compile(rb"(<" + _XMP_FIELDS + rb">)[^<]*")
+_XMP_ATTRIBUTE = re.compile(rb"(" + _XMP_FIELDS + rb"=)(?:\"[^\"]*\"|'[^']*')")
+_PDF_STREAM = re.compile(rb"\bstream\r?\n")
+_PDF_ENDSTREAM = re.compile(rb"\r?\n?endstream\b")
+_PDF_XREF_STREAM = re.compile(rb"/Type\s*/XRef\b")
+
+
+def _pdf_masked(data: bytes) -> bytes:
+    data = _PDF_VOLATILE.sub(lambda m: m.group(m.lastindex), data)
+    data = _XMP_ELEMENT.sub(rb"\1", data)
+    return _XMP_ATTRIBUTE.sub(rb'\1""', data)
+
+
+def _pdf_stream(head: bytes, body: bytes) -> bytes:
+    """A stream's content, inflated if it is Flate-encoded, then masked."""
+    if b"/FlateDecode" in head:
+        try:
+            body = zlib.decompressobj().decompress(body)
+        except zlib.error:
+            pass  # compared as stored
+    return _pdf_masked(body)
+
+
+def _pdf_normalized(data: bytes) -> list[bytes]:
+    """Objects of a PDF with volatile values blanked and streams inflated.
+
+    Cross-reference streams are dropped: they only hold byte offsets.
+    """
+    parts = []
+    pos = 0
+    while (start := _PDF_STREAM.search(data, pos)) is not None:
+        end = _PDF_ENDSTREAM.search(data, start.end())
+        if end is None:
+            break
+        head = data[pos:start.start()]
+        parts.append(_pdf_masked(head))
+        head = head[head.rfind(b"obj"):]  # this stream's dictionary
+        if _PDF_XREF_STREAM.search(head) is None:
+            parts.append(_pdf_stream(head, data[start.end():end.start()]))
+        pos = end.end()
+    parts.append(_pdf_masked(data[pos:]))
+    return parts
+
+
+class PdfComparator(ContentComparator):
+    """Equal when two PDFs differ only in the fields generators randomize.
+
+    Regenerated reports with identical content routinely differ byte-wise:
+    every export writes a new document ID, new Info creation and
+    modification dates and new XMP timestamps and IDs, and the
+    cross-reference offsets move with them. These are blanked before
+    comparing, and Flate-encoded streams (page content, XMP, object
+    streams) are compared inflated, so a different compression level
+    does not matter either. Both files are read into memory. A file that
+    does not start with a PDF header is different. Used by the CLI's
+    ``--pdf``.
+    """
+
+    __slots__ = ()
+
+    def compare(self, path_a: str, path_b: str) -> bool:
+        with open(path_a, "rb") as fa, open(path_b, "rb") as fb:
+            data_a, data_b = fa.read(), fb.read()
+        if not (data_a.startswith(b"%PDF-") and data_b.startswith(b"%PDF-")):
+            return False
+        return _pdf_normalized(data_a) == _pdf_normalized(data_b)
+
+
+def _sql_name(name: str) -> str:
+    return '"' + name.replace('"', '""') + '"'
+
+
+class SqliteComparator(ContentComparator):
+    """Equal when two SQLite databases hold the same schema and rows.
+
+    Page layout, page size, freelists, the journal mode and whether the
+    latest changes still sit in the WAL make raw bytes useless for
+    comparing databases. Both are opened read-only and compared
+    logically instead: ``user_version``, the schema (the ``CREATE``
+    statements of tables, indexes, views and triggers, excluding
+    SQLite's internal tables) and the rows of every table, ordered by
+    primary key, or by all columns for a table without one. Values
+    compare with their storage class, so ``1`` is not ``1.0``. Rows are
+    streamed, so memory does not grow with the table size. A file that
+    is not a readable SQLite database is different. Used by the CLI's
+    ``--sqlite``.
+    """
+
+    __slots__ = ()
+
+    @staticmethod
+    def _connect(path: str) -> Any:
+        import sqlite3
+
+        return sqlite3.connect(Path(path).absolute().as_uri() + "?mode=ro", uri=True)
+
+    @staticmethod
+    def _schema(db: Any) -> list[tuple[Any, ...]]:
+        return db.execute(
+            "SELECT type, name, tbl_name, sql FROM sqlite_master"
+            " WHERE name NOT LIKE 'sqlite\\_%' ESCAPE '\\' ORDER BY type, name").fetchall()
+
+    @staticmethod
+    def _rows(db: Any, table: str) -> Iterator[tuple[Any, ...]]:
+        columns = db.execute(f"PRAGMA table_info({_sql_name(table)})").fetchall()
+        # (position in the key, column number) for primary key columns
+        key = sorted((column[5], column[0] + 1) for column in columns if column[5])
+        order = [number for _, number in key] or [column[0] + 1 for column in columns]
+        cursor = db.execute(f"SELECT * FROM {_sql_name(table)} "
+                            f"ORDER BY {', '.join(map(str, order))}")
+        for row in cursor:
+            yield tuple((type(value), value) for value in row)
+
+    def compare(self, path_a: str, path_b: str) -> bool:
+        import sqlite3
+
+        try:
+            with closing(self._connect(path_a)) as da, closing(self._connect(path_b)) as db:
+                version = "PRAGMA user_version"
+                if da.execute(version).fetchone() != db.execute(version).fetchone():
+                    return False
+                schema = self._schema(da)
+                if schema != self._schema(db):
+                    return False
+                tables = [name for kind, name, _, sql in schema if kind == "table"
+                          and not (sql or "").upper().startswith("CREATE VIRTUAL")]
+                return all(all(a == b for a, b in zip_longest(self._rows(da, t),
+                                                              self._rows(db, t)))
+                           for t in tables)
+        except sqlite3.DatabaseError:
+            return False
+
+
+def _batches_equal(batches_a: Iterator[Any], batches_b: Iterator[Any]) -> bool:
+    """Compare two streams of record batches split at arbitrary row counts."""
+    batches_a = (batch for batch in batches_a if batch.num_rows)
+    batches_b = (batch for batch in batches_b if batch.num_rows)
+    buf_a = buf_b = None
+    while True:
+        if buf_a is None or not buf_a.num_rows:
+            buf_a = next(batches_a, None)
+        if buf_b is None or not buf_b.num_rows:
+            buf_b = next(batches_b, None)
+        if buf_a is None or buf_b is None:
+            return buf_a is None and buf_b is None
+        n = min(buf_a.num_rows, buf_b.num_rows)
+        if not buf_a.slice(0, n).equals(buf_b.slice(0, n)):
+            return False
+        buf_a, buf_b = buf_a.slice(n), buf_b.slice(n)
+
+
+class ParquetComparator(ContentComparator):
+    """Equal when two Parquet files hold the same schema and rows.
+
+    For validating pipeline re-runs: Parquet files with identical data
+    routinely differ byte-wise, since the writer version, statistics and
+    key-value metadata are embedded. The schemas (field names, types and
+    nullability, not their metadata) and then the rows, in file order,
+    are compared through pyarrow (``pip install komparu[parquet]``),
+    ``batch_size`` rows at a time. Row-group sizes and column codecs are
+    compared too unless ignored. Arrow IPC files (``.arrow``,
+    ``.feather``), whose record batches stand for row groups, are read
+    as well. A file in neither format is different. Used by the CLI's
+    ``--parquet``.
+
+    :param batch_size: Rows read per step.
+    :param ignore_row_groups: Do not compare how rows are split into row
+        groups (record batches).
+    :param ignore_compression: Do not compare column compression codecs.
+    :raises ImportError: If pyarrow is not installed.
+    """
+
+    __slots__ = ("_batch_size", "_ignore_row_groups", "_ignore_compression")
+
+    def __init__(
+        self,
+        batch_size: int = 65536,
+        *,
+        ignore_row_groups: bool = False,
+        ignore_compression: bool = False,
+    ) -> None:
+        try:
+            import pyarrow
+        except ImportError:
+            raise ImportError(
+                "ParquetComparator needs pyarrow: pip install komparu[parquet]"
+            ) from None
+        if batch_size <= 0:
+            raise ValueError("batch_size must be positive")
+        self._batch_size = batch_size
+        self._ignore_row_groups = ignore_row_groups
+        self._ignore_compression = ignore_compression
+
+    def _open(
+        self, stack: ExitStack, path: str
+    ) -> tuple[Any, list[Any], Iterator[Any]] | None:
+        """Schema, layout and record batches of a Parquet or Arrow IPC file."""
+        import pyarrow.ipc
+        import pyarrow.parquet
+
+        f = stack.enter_context(open(path, "rb"))
+        magic = f.read(6)
+        f.seek(0)
+        if magic.startswith(b"PAR1"):
+            parquet = pyarrow.parquet.ParquetFile(f)
+            meta = parquet.metadata
+            groups = [meta.row_group(i) for i in range(meta.num_row_groups)]
+            layout: list[Any] = []
+            if not self._ignore_row_groups:
+                layout.append([group.num_rows for group in groups])
+            if not self._ignore_compression:
+                columns = (group.column(i) for group in groups for i in range(group.num_columns))
+                layout.append(sorted({(c.path_in_schema, c.compression) for c in columns}))
+            return (parquet.schema_arrow, layout,
+                    parquet.iter_batches(batch_size=self._batch_size))
+        if magic == b"ARROW1":
+            reader = pyarrow.ipc.open_file(f)
+            batches = [reader.get_batch(i) for i in range(reader.num_record_batches)]
+            layout = [] if self._ignore_row_groups else [[b.num_rows for b in batches]]
+            return reader.schema, layout, iter(batches)
+        return None
+
+    def compare(self, path_a: str, path_b: str) -> bool:
+        import pyarrow
+
+        with ExitStack() as stack:
+            try:
+                a, b = self._open(stack, path_a), self._open(stack, path_b)
+                if a is None or b is None:
+                    return False
+                (schema_a, layout_a, batches_a), (schema_b, layout_b, batches_b) = a, b
+                if layout_a != layout_b or not schema_a.equals(schema_b, check_metadata=False):
+                    return False
+                return _batches_equal(batches_a, batches_b)
+            except pyarrow.ArrowException:
+                return False
+
+
+class XmlComparator(ContentComparator):
+    """Equal when both files have the same canonical XML form.
+
+    Both sides are canonicalized with :func:`xml.etree.ElementTree.canonicalize`
+    (C14N 2.0) before comparison, so attribute order, quoting, empty-element
+    syntax, the XML declaration and character references do not matter.
+    Meant for verifying XML datasets exported by different systems.
+    Element order and text content do. A file that is not well-formed XML
+    is different. Register it for XML globs, e.g.
+    ``comparators={"*.xml": XmlComparator()}``.
+
+    :param strip_whitespace: Ignore whitespace around text, including the
+        indentation between elements.
+    :param rewrite_prefixes: Replace namespace prefixes with ``n0``, ``n1``,
+        ...; documents using different prefixes for the same namespace URIs
+        compare equal.
+    :param with_comments: Compare comments too.
+    """
+
+    __slots__ = ("_strip", "_rewrite", "_comments")
+
+    def __init__(
+        self,
+        *,
+        strip_whitespace: bool = True,
+        rewrite_prefixes: bool = True,
+        with_comments: bool = False,
+    ) -> None:
+        self._strip = strip_whitespace
+        self._rewrite = rewrite_prefixes
+        self._comments = with_comments
+
+    def _canonical(self, path: str) -> str:
+        return ElementTree.canonicalize(
+            from_file=path,
+            strip_text=self._strip,
+            rewrite_prefixes=self._rewrite,
+            with_comments=self._comments,
+        )
+
+    def compare(self, path_a: str, path_b: str) -> bool:
+        try:
+            return self._canonical(path_a) == self._canonical(path_b)
+        except ElementTree.ParseError:
+            return False
+
+
+class FilterComparator(ContentComparator):
+    """Equal when an external command normalizes both files to the same output.
+
+    Like a git clean filter, but for comparison: each side is piped
+    through *command* — ``sort``, ``jq -S .``, an in-house canonicalizer
+    — and the two outputs are compared as they are produced, so memory
+    stays at ``chunk_size`` per side and both commands are stopped at
+    the first difference. The file is the command's standard input, or,
+    if an argument is ``"{}"``, its path is put there instead. The
+    command is run directly, not through a shell. Used by the CLI's
+    ``--filter``.
+
+    :param command: Program and arguments, e.g. ``["sort", "-u"]``.
+    :param chunk_size: Output bytes read per step.
+    :raises SourceReadError: From :meth:`compare`, if the command exits
+        with a non-zero status.
+    """
+
+    __slots__ = ("_command", "_chunk_size")
+
+    def __init__(self, command: Sequence[str], chunk_size: int = 65536) -> None:
+        if isinstance(command, str) or not command:
+            raise ValueError("command must be a non-empty sequence of arguments")
+        validate_chunk_size(chunk_size)
+        self._command = tuple(command)
+        self._chunk_size = chunk_size
+
+    def _start(self, stack: ExitStack, path: str) -> tuple[subprocess.Popen[bytes], BinaryIO]:
+        if "{}" in self._command:
+            argv = [path if arg == "{}" else arg for arg in self._command]
+            stdin: Any = subprocess.DEVNULL
+        else:
+            argv = list(self._command)
+            stdin = stack.enter_context(open(path, "rb"))
+        proc = subprocess.Popen(argv, stdin=stdin, stdout=subprocess.PIPE)
+        # Unwound in reverse: close the pipe, stop the command, reap it
+        stack.callback(proc.wait)
+        stack.callback(proc.kill)
+        stdout: BinaryIO = stack.enter_context(proc.stdout)
+        return proc, stdout
+
+    def _check(self, proc: subprocess.Popen[bytes], path: str) -> None:
+        status = proc.wait()
+        if status != 0:
+            raise SourceReadError(
+                errno.EIO, f"filter {self._command[0]} exited with status {status}", path)
+
+    def compare(self, path_a: str, path_b: str) -> bool:
+        with ExitStack() as stack:
+            proc_a, out_a = self._start(stack, path_a)
+            proc_b, out_b = self._start(stack, path_b)
+            equal = _chunks_equal(_read_chunks(out_a, self._chunk_size),
+                                  _read_chunks(out_b, self._chunk_size))
+            if equal:
+                # A filter that failed halfway must not make two files equal
+                self._check(proc_a, path_a)
+                self._check(proc_b, path_b)
+            return equal
+
+
+_SNIFF_HEAD = 512
+_TAR_MAGIC_OFFSET = 257
+
+
+def _is_tar(head: bytes) -> bool:
+    return head[_TAR_MAGIC_OFFSET:_TAR_MAGIC_OFFSET + 5] == b"ustar"
+
+
+def _is_mp3_frame(head: bytes) -> bool:
+    return len(head) > 1 and head[0] == 0xFF and head[1] & 0xE0 == 0xE0
+
+
+def _is_fat_binary(head: bytes) -> bool:
+    return (head[:4] in (b"\xca\xfe\xba\xbe", b"\xca\xfe\xba\xbf")
+            and len(head) >= 8 and struct.unpack(">I", head[4:8])[0] < _FAT_MAX_ARCHS)
+
+
+# File kind by leading bytes, tested in order. Compressed files are
+# looked into separately, for tarballs.
+_SNIFFERS: tuple[tuple[str, Callable[[bytes], bool]], ...] = (
+    ("deb", lambda h: h.startswith(b"!<arch>\ndebian-binary")),
+    ("tar", _is_tar),
+    ("zip", lambda h: h.startswith((b"PK\x03\x04", b"PK\x05\x06"))),
+    ("image", lambda h: h.startswith(tuple(magic for magic, _ in _IMAGE_FORMATS))),
+    ("audio", lambda h: h.startswith((b"ID3", b"fLaC", b"OggS")) or _is_mp3_frame(h)),
+    ("pdf", lambda h: h.startswith(b"%PDF-")),
+    ("sqlite", lambda h: h.startswith(b"SQLite format 3\0")),
+    ("parquet", lambda h: h.startswith((b"PAR1", b"ARROW1"))),
+    ("executable", lambda h: (h.startswith((b"\x7fELF", b"MZ")) or h[:4] in _MACHO_MAGIC
+                              or _is_fat_binary(h))),
+    ("xml", lambda h: h.lstrip(codecs.BOM_UTF8).lstrip().startswith(b"<?xml")),
+    ("json", lambda h: h.lstrip(codecs.BOM_UTF8).lstrip()[:1] in (b"{", b"[")),
+)
+
+
+class SniffingComparator(ContentComparator):
+    """Route each pair of files to a content-aware comparator by file type.
+
+    The type of each side is detected from its leading bytes, not its
+    name, and when both sides agree the matching comparator decides:
+
+    ========== ==========================================================
+    Kind       Comparator
+    ========== ==========================================================
+    compressed :class:`DecompressComparator` (gzip, bzip2, xz, zstd)
+    tar        :class:`TarComparator`, compressed tarballs included
+    zip        :class:`ZipComparator` (also jar, wheel, OOXML)
+    deb        :class:`DebComparator`
+    image      :class:`ImageComparator` (PNG, JPEG, TIFF)
+    audio      :class:`AudioComparator` (MP3, FLAC, Ogg)
+    pdf        :class:`PdfComparator`
+    sqlite     :class:`SqliteComparator`
+    parquet    :class:`ParquetComparator`, if pyarrow is installed
+    executable :class:`ExecutableComparator` (ELF, PE, Mach-O)
+    xml        :class:`XmlComparator`, for files starting ``<?xml``
+    json       :class:`JsonComparator`, for files starting ``{`` or ``[``
+    ========== ==========================================================
+
+    Files of different or unrecognized kinds go to ``fallback``, and
+    are different without one. Register it for every path with
+    ``comparators={"*": SniffingComparator()}``; more specific patterns
+    listed before it still win. Used by the CLI's ``--content-aware``.
+
+    :param chunk_size: Chunk size passed to the comparators that take one.
+    :param fallback: Comparator for files no kind applies to, e.g. a
+        :class:`TextComparator`.
+    :param on_route: Called as ``on_route(path_a, path_b, kind)`` before
+        a pair is handed to the comparator for *kind*, to report which
+        one was used.
+    """
+
+    __slots__ = ("_comparators", "_fallback", "_on_route", "_decompressors")
+
+    def __init__(
+        self,
+        chunk_size: int = 65536,
+        *,
+        fallback: ContentComparator | None = None,
+        on_route: Callable[[str, str, str], None] | None = None,
+    ) -> None:
+        validate_chunk_size(chunk_size)
+        self._comparators: dict[str, ContentComparator] = {
+            "compressed": DecompressComparator(chunk_size),
+            "tar": TarComparator(chunk_size),
+            "zip": ZipComparator(chunk_size),
+            "deb": DebComparator(chunk_size),
+            "image": ImageComparator(chunk_size),
+            "audio": AudioComparator(chunk_size),
+            "pdf": PdfComparator(),
+            "sqlite": SqliteComparator(),
+            "executable": ExecutableComparator(),
+            "xml": XmlComparator(),
+            "json": JsonComparator(),
+        }
+        try:
+            self._comparators["parquet"] = ParquetComparator()
+        except ImportError:
+            pass
+        self._fallback = fallback
+        self._on_route = on_route
+        self._decompressors = _decompressors()
+
+    def detect(self, path: str) -> str | None:
+        """Kind of file at *path* (a key of the table above), or None."""
+        with open(path, "rb") as f:
+            head = f.read(_SNIFF_HEAD)
+        fmt = next((name for magic, name in _MAGIC if head.startswith(magic)), None)
+        if fmt is not None:
+            if fmt in self._decompressors:
+                open_, errors = self._decompressors[fmt]
+                try:
+                    with open_(path) as f:
+                        inner = f.read(_SNIFF_HEAD)
+                except errors:
+                    inner = b""
+                if _is_tar(inner):
+                    return "tar"
+            return "compressed"
+        kind = next((kind for kind, sniff in _SNIFFERS if sniff(head)), None)
+        return kind if kind in self._comparators else None
+
+    def compare(self, path_a: str, path_b: str) -> bool:
+        kind = self.detect(path_a)
+        if kind is None or self.detect(path_b) != kind:
+            return self._fallback is not None and self._fallback.compare(path_a, path_b)
+        if self._on_route is not None:
+            self._on_route(path_a, path_b, kind)
+        return self._comparators[kind].compare(path_a, path_b)
+
+
+# Diff reasons a comparator may override; read errors stay errors
+_RECHECKED = frozenset({DiffReason.CONTENT_MISMATCH, DiffReason.SIZE_MISMATCH})
+
+
+class ComparatorDispatch:
+    """Route differing entries to the comparator registered for their path.
+
+    Patterns are matched against the whole relative path with
+    :func:`fnmatch.fnmatch` (``*`` also matches ``/``); the first matching
+    pattern, in mapping order, wins.
+    """
+
+    __slots__ = ("_dir_a", "_dir_b", "_items")
+
+    def __init__(
+        self,
+        dir_a: str,
+        dir_b: str,
+        comparators: Mapping[str, ContentComparator],
+    ) -> None:
+        for pattern, comparator in comparators.items():
+            if not isinstance(pattern, str):
+                raise TypeError("comparator patterns must be strings")
+            if not isinstance(comparator, ContentComparator):
+                raise TypeError(
+                    f"comparator for {pattern!r} must be a ContentComparator, "
+                    f"got {type(comparator).__name__}"
+                )
+        self._dir_a = dir_a
+        self._dir_b = dir_b
+        self._items = tuple(comparators.items())
+
+    def _lookup(self, path: str) -> ContentComparator | None:
+        for pattern, comparator in self._items:
+            if fnmatch(path, pattern):
+                return comparator
+        return None
+
+    def _equivalent(self, path: str, reason: DiffReason | None) -> bool:
+        if reason not in _RECHECKED:
+            return False
+        comparator = self._lookup(path)
+        if comparator is None:
+            return False
+        return bool(comparator.compare(os.path.join(self._dir_a, path),
+                                       os.path.join(self._dir_b, path)))
+
+    def recheck(self, entry: DiffEntry) -> DiffEntry:
+        """Turn a DIFF entry into EQUAL if its comparator says so."""
+        if entry.kind is EntryKind.DIFF and self._equivalent(entry.path, entry.reason):
+            return DiffEntry(entry.path, EntryKind.EQUAL)
+        return entry
+
+    def apply(self, result: DirResult) -> DirResult:
+        """Drop diff entries their comparator declares equal."""
+        diff = {path: reason for path, reason in result.diff.items()
+                if not self._equivalent(path, reason)}
+        if len(diff) == len(result.diff):
+            return result
+        return DirResult(
+            equal=not diff and not result.only_left and not result.only_right,
+            diff=diff,
+            only_left=result.only_left,
+            only_right=result.only_right,
+            errors=result.errors,
+        )
+
+
+def make_dispatch(
+    dir_a: str | int,
+    dir_b: str | int,
+    comparators: Mapping[str, ContentComparator] | None,
+) -> ComparatorDispatch | None:
+    """Build a dispatcher, or None when no comparators are registered."""
+    if not comparators:
+        return None
+    if isinstance(dir_a, int) or isinstance(dir_b, int):
+        raise TypeError("comparators need directory paths, not directory handles")
+    return ComparatorDispatch(dir_a, dir_b, comparators)
@@ -0,0 +1,338 @@
+"""Shell completion scripts generated from the argparse definition of the CLI."""
+
+from __future__ import annotations
+
+import argparse
+import enum
+from collections.abc import Callable, Mapping
+from dataclasses import dataclass
+
+SHELLS = ("bash", "zsh", "fish", "powershell")
+# Metavars whose values are paths, completed as files or directories
+_FILE_METAVARS = frozenset({"FILE", "MANIFEST", "SCRIPT", "SECRET_KEY"})
+_DIR_METAVARS = frozenset({"DIR"})
+
+
+@dataclass(frozen=True, slots=True)
+class _Option:
+    flags: tuple[str, ...]
+    help: str
+    takes_value: bool
+    repeatable: bool
+    metavar: str
+    choices: tuple[str, ...] | None
+    # "file", "dir" or None for a free value
+    path: str | None
+
+
+@dataclass(frozen=True, slots=True)
+class _Command:
+    name: str
+    help: str
+    options: tuple[_Option, ...]
+    # Values the positional arguments take, offered instead of file names
+    values: tuple[str, ...] | None
+
+
+def _option(action: argparse.Action) -> _Option:
+    takes_value = action.nargs != 0
+    metavar = action.metavar if isinstance(action.metavar, str) else action.dest.upper()
+    choices: tuple[str, ...] | None = None
+    if action.choices is not None:
+        choices = tuple(str(choice) for choice in action.choices)
+    elif isinstance(action.type, type) and issubclass(action.type, enum.Enum):
+        choices = tuple(str(member.value) for member in action.type)
+    path = None
+    if takes_value and choices is None:
+        path = "dir" if metavar in _DIR_METAVARS else "file" if metavar in _FILE_METAVARS else None
+    return _Option(tuple(action.option_strings), " ".join((action.help or "").split()),
+                   takes_value, isinstance(action.default, list), metavar, choices, path)
+
+
+def _options(parser: argparse.ArgumentParser) -> tuple[_Option, ...]:
+    return tuple(_option(action) for action in parser._actions
+                 if action.option_strings and action.help != argparse.SUPPRESS)
+
+
+def _commands(parser: argparse.ArgumentParser,
+              commands: Mapping[str, argparse.ArgumentParser]) -> list[_Command]:
+    helps = {}
+    for action in parser._actions:
+        if isinstance(action, argparse._SubParsersAction):
+            helps = {choice.dest: choice.help or "" for choice in action._choices_actions}
+    result = []
+    for name, sub in commands.items():
+        values = None
+        for action in sub._actions:
+            if not action.option_strings and action.choices is not None:
+                values = tuple(str(choice) for choice in action.choices)
+        result.append(_Command(name, " ".join(helps.get(name, "").split()), _options(sub), values))
+    return result
+
+
+def _summary(text: str) -> str:
+    """The first clause of a help text, short enough for a completion menu."""
+    for stop in ("; ", " ("):
+        text = text.split(stop, 1)[0]
+    return text
+
+
+def _bash(globals_: tuple[_Option, ...], commands: list[_Command]) -> str:
+    def value_cases(options: tuple[_Option, ...], indent: str) -> list[str]:
+        lines = []
+        for opt in options:
+            if not opt.takes_value:
+                continue
+            if opt.choices is not None:
+                reply = f'COMPREPLY=($(compgen -W "{" ".join(opt.choices)}" -- "$cur"))'
+            elif opt.path == "dir":
+                reply = 'COMPREPLY=($(compgen -d -- "$cur"))'
+            elif opt.path == "file":
+                reply = 'COMPREPLY=($(compgen -f -- "$cur"))'
+            else:
+                reply = ""
+            lines.append(f"{indent}{'|'.join(opt.flags)}) {reply + '; ' if reply else ''}return ;;")
+        return lines
+
+    global_values = [flag for opt in globals_ if opt.takes_value for flag in opt.flags]
+    out = [
+        "# bash completion for komparu; generated by `komparu completions bash`",
+        "_komparu() {",
+        '    local cur="${COMP_WORDS[COMP_CWORD]}" prev="${COMP_WORDS[COMP_CWORD-1]}"',
+        '    local command="" opts i',
+        "    for ((i = 1; i < COMP_CWORD; i++)); do",
+        '        case "${COMP_WORDS[i]}" in',
+        f"            {'|'.join(global_values)}) ((i++)) ;;",
+        "            -*) ;;",
+        '            *) command="${COMP_WORDS[i]}"; break ;;',
+        "        esac",
+        "    done",
+        '    case "$command" in',
+        '        "")',
+        '            case "$prev" in',
+        *value_cases(globals_, "                "),
+        "            esac",
+        '            if [[ $cur == -* ]]; then',
+        f'                opts="{" ".join(f for opt in globals_ for f in opt.flags)}"',
+        '                COMPREPLY=($(compgen -W "$opts" -- "$cur"))',
+        "            else",
+        f'                COMPREPLY=($(compgen -W "{" ".join(c.name for c in commands)}" '
+        '-- "$cur"))',
+        "            fi",
+        "            return ;;",
+    ]
+    for command in commands:
+        out += [
+            f"        {command.name})",
+            '            case "$prev" in',
+            *value_cases(command.options, "                "),
+            "            esac",
+            f'            opts="{" ".join(f for opt in command.options for f in opt.flags)}"',
+        ]
+        if command.values is not None:
+            out += [
+                '            if [[ $cur != -* ]]; then',
+                f'                COMPREPLY=($(compgen -W "{" ".join(command.values)}" -- "$cur"))',
+                "                return",
+                "            fi",
+            ]
+        out.append("            ;;")
+    out += [
+        "        *) return ;;",
+        "    esac",
+        '    if [[ $cur == -* ]]; then',
+        '        COMPREPLY=($(compgen -W "$opts" -- "$cur"))',
+        "    else",
+        '        COMPREPLY=($(compgen -f -- "$cur"))',
+        "    fi",
+        "}",
+        "complete -o filenames -o bashdefault -F _komparu komparu",
+        "",
+    ]
+    return "\n".join(out)
+
+
+def _zsh_quote(text: str) -> str:
+    return "'" + text.replace("'", "'\\''") + "'"
+
+
+def _zsh_describe(text: str) -> str:
+    return _summary(text).replace(":", "\\:")
+
+
+def _zsh_specs(options: tuple[_Option, ...]) -> list[str]:
+    specs = []
+    for opt in options:
+        description = _summary(opt.help).replace("[", "\\[").replace("]", "\\]")
+        action = ""
+        if opt.takes_value:
+            if opt.choices is not None:
+                action = f"({' '.join(opt.choices)})"
+            elif opt.path == "dir":
+                action = "_files -/"
+            elif opt.path == "file":
+                action = "_files"
+            action = f":{opt.metavar.replace(':', ' ').lower()}:{action}"
+        exclusive = f"({' '.join(opt.flags)})" if len(opt.flags) > 1 and not opt.repeatable else ""
+        for flag in opt.flags:
+            specs.append(_zsh_quote(f"{exclusive}{'*' if opt.repeatable else ''}"
+                                    f"{flag}[{description}]{action}"))
+    return specs
+
+
+def _zsh(globals_: tuple[_Option, ...], commands: list[_Command]) -> str:
+    out = [
+        "#compdef komparu",
+        "# zsh completion for komparu; generated by `komparu completions zsh`",
+        "",
+        "_komparu() {",
+        "    local curcontext=$curcontext state line",
+        "    typeset -A opt_args",
+        "    _arguments -C \\",
+        *(f"        {spec} \\" for spec in _zsh_specs(globals_)),
+        "        '1:command:->command' \\",
+        "        '*::argument:->argument'",
+        "    case $state in",
+        "        command)",
+        "            local -a commands",
+        "            commands=(",
+        *(f"                {_zsh_quote(f'{c.name}:{_zsh_describe(c.help)}')}" for c in commands),
+        "            )",
+        "            _describe -t commands 'komparu command' commands ;;",
+        "        argument)",
+        "            case $line[1] in",
+    ]
+    for command in commands:
+        out += [
+            f"                {command.name})",
+            "                    _arguments \\",
+            *(f"                        {spec} \\" for spec in _zsh_specs(command.options)),
+            "                        " + (f"'*:value:({' '.join(command.values)})' ;;"
+                                         if command.values is not None else "'*:file:_files' ;;"),
+        ]
+    out += [
+        "            esac ;;",
+        "    esac",
+        "}",
+        "",
+        '_komparu "$@"',
+        "",
+    ]
+    return "\n".join(out)
+
+
+def _fish_quote(text: str) -> str:
+    return "'" + text.replace("\\", "\\\\").replace("'", "\\'") + "'"
+
+
+def _fish_option(condition: str, opt: _Option) -> str:
+    parts = ["complete -c komparu", f"-n {_fish_quote(condition)}"]
+    for flag in opt.flags:
+        parts.append(f"-l {flag[2:]}" if flag.startswith("--") else f"-s {flag[1:]}")
+    if opt.choices is not None:
+        parts.append(f"-x -a {_fish_quote(' '.join(opt.choices))}")
+    elif opt.path is not None:
+        parts.append("-r -F")
+    elif opt.takes_value:
+        parts.append("-x")
+    parts.append(f"-d {_fish_quote(_summary(opt.help))}")
+    return " ".join(parts)
+
+
+def _fish(globals_: tuple[_Option, ...], commands: list[_Command]) -> str:
+    out = [
+        "# fish completion for komparu; generated by `komparu completions fish`",
+        "complete -c komparu -f",
+        *(_fish_option("__fish_use_subcommand", opt) for opt in globals_),
+    ]
+    for command in commands:
+        out.append(f"complete -c komparu -n __fish_use_subcommand -a {command.name} "
+                   f"-d {_fish_quote(_summary(command.help))}")
+    for command in commands:
+        condition = f"__fish_seen_subcommand_from {command.name}"
+        if command.values is not None:
+            out.append(f"complete -c komparu -n {_fish_quote(condition)} "
+                       f"-a {_fish_quote(' '.join(command.values))}")
+        else:
+            out.append(f"complete -c komparu -n {_fish_quote(condition)} -F")
+        out += [_fish_option(condition, opt) for opt in command.options]
+    out.append("")
+    return "\n".join(out)
+
+
+def _ps_quote(text: str) -> str:
+    return "'" + text.replace("'", "''") + "'"
+
+
+def _ps_list(items: list[str]) -> str:
+    return "@(" + ", ".join(_ps_quote(item) for item in items) + ")"
+
+
+def _powershell(globals_: tuple[_Option, ...], commands: list[_Command]) -> str:
+    options = [opt for command in commands for opt in command.options] + list(globals_)
+    choices = {flag: list(opt.choices) for opt in options if opt.choices for flag in opt.flags}
+    valued = sorted({flag for opt in globals_ if opt.takes_value for flag in opt.flags})
+    values = {c.name: list(c.values) for c in commands if c.values is not None}
+    out = [
+        "# PowerShell completion for komparu; generated by `komparu completions powershell`",
+        "Register-ArgumentCompleter -Native -CommandName komparu -ScriptBlock {",
+        "    param($wordToComplete, $commandAst, $cursorPosition)",
+        "    $commands = @{",
+        *(f"        {_ps_quote(c.name)} = {_ps_list([f for o in c.options for f in o.flags])}"
+          for c in commands),
+        "    }",
+        f"    $globals = {_ps_list([f for opt in globals_ for f in opt.flags])}",
+        f"    $valued = {_ps_list(valued)}",
+        "    $choices = @{",
+        *(f"        {_ps_quote(flag)} = {_ps_list(values)}" for flag, values in choices.items()),
+        "    }",
+        "    $values = @{",
+        *(f"        {_ps_quote(name)} = {_ps_list(items)}" for name, items in values.items()),
+        "    }",
+        "    $words = @($commandAst.CommandElements | Select-Object -Skip 1 |",
+        "        Where-Object { $_.Extent.EndOffset -lt $cursorPosition } |",
+        "        ForEach-Object { $_.ToString() })",
+        "    $command = $null",
+        "    for ($i = 0; $i -lt $words.Count; $i++) {",
+        "        if ($valued -contains $words[$i]) { $i++ }",
+        "        elseif ($words[$i] -notlike '-*') { $command = $words[$i]; break }",
+        "    }",
+        "    $previous = if ($words.Count) { $words[-1] } else { $null }",
+        "    if ($previous -and $choices.ContainsKey($previous)) {",
+        "        $candidates = $choices[$previous]",
+        "    } elseif ($null -eq $command) {",
+        "        $candidates = if ($wordToComplete -like '-*') { $globals }"
+        " else { @($commands.Keys | Sort-Object) }",
+        "    } elseif ($wordToComplete -like '-*' -and $commands.ContainsKey($command)) {",
+        "        $candidates = $commands[$command]",
+        "    } elseif ($values.ContainsKey($command)) {",
+        "        $candidates = $values[$command]",
+        "    } else {",
+        "        return",
+        "    }",
+        '    $candidates | Where-Object { $_ -like "$wordToComplete*" } | ForEach-Object {',
+        "        [System.Management.Automation.CompletionResult]::new("
+        "$_, $_, 'ParameterValue', $_)",
+        "    }",
+        "}",
+        "",
+    ]
+    return "\n".join(out)
+
+
+_GENERATORS: dict[str, Callable[[tuple[_Option, ...], list[_Command]], str]] = {
+    "bash": _bash, "zsh": _zsh, "fish": _fish, "powershell": _powershell,
+}
+
+
+def completion_script(shell: str, parser: argparse.ArgumentParser,
+                      commands: Mapping[str, argparse.ArgumentParser]) -> str:
+    """Completion script for *shell* covering every command and option of *parser*.
+
+    :param shell: ``bash``, ``zsh``, ``fish`` or ``powershell``.
+    :param commands: Parser of every subcommand, by name.
+    :raises ValueError: If *shell* is not one of those.
+    """
+    if shell not in _GENERATORS:
+        raise ValueError(f"shell must be one of {', '.join(SHELLS)}, got {shell!r}")
+    return _GENERATORS[shell](_options(parser), _commands(parser, commands))
@@ -3,6 +3,10 @@
 from __future__ import annotations
 
 from dataclasses import dataclass, field
+from typing import Any
+
+from komparu._core import set_buffer_allocator as _set_buffer_allocator
+from komparu._core import set_mmap_window as _set_mmap_window
 
 
 @dataclass
@@ -21,6 +25,7 @@ class KomparuConfig:
     verify_ssl: bool = True
     size_precheck: bool = True
     quick_check: bool = True
+    mmap_window: int = 0  # 0 = 1 GiB (64 MiB on 32-bit)
 
     # HTTP
     headers: dict[str, str] = field(default_factory=dict)
@@ -40,6 +45,9 @@ class KomparuConfig:
     # SSRF protection
     allow_private_redirects: bool = False
 
+    # Only FIPS-approved digests (SHA-256/384/512)
+    fips: bool = False
+
 
 # Global singleton
 _config = KomparuConfig()
@@ -55,6 +63,9 @@ def configure(**kwargs) -> None:
     :param verify_ssl: Verify SSL certificates.
     :param size_precheck: Compare sizes before content.
     :param quick_check: Sample key offsets before full scan.
+    :param mmap_window: Largest span of a file mapped at once, in bytes
+        (0 = 1 GiB, 64 MiB on 32-bit builds). Larger files are mapped in
+        windows that slide along as they are read.
     :param headers: Global HTTP headers.
     :param max_decompressed_size: Max decompressed bytes per archive (None = no limit).
     :param max_compression_ratio: Max compression ratio (None = no limit).
@@ -63,6 +74,8 @@ def configure(**kwargs) -> None:
     :param comparison_timeout: Wall-clock timeout per comparison (None = no limit).
     :param proxy: Proxy URL (e.g. http://host:port, socks5://host:port).
     :param allow_private_redirects: Allow redirects to private networks.
+    :param fips: Allow only FIPS-approved hash algorithms (SHA-256, SHA-384,
+        SHA-512); anything needing another one raises ValueError.
     """
     global _config
     # Validate all keys first to avoid partial updates
@@ -70,6 +83,8 @@ def configure(**kwargs) -> None:
         if not hasattr(_config, key):
             from komparu._types import ConfigError
             raise ConfigError(f"unknown config option: {key!r}")
+    if "mmap_window" in kwargs:
+        _set_mmap_window(kwargs["mmap_window"])
     for key, value in kwargs.items():
         setattr(_config, key, value)
 
@@ -83,3 +98,25 @@ def reset_config() -> None:
     """Reset configuration to defaults."""
     global _config
     _config = KomparuConfig()
+    _set_mmap_window(0)
+
+
+def set_buffer_allocator(allocator: Any) -> None:
+    """Choose where the engine allocates its comparison buffers.
+
+    Each worker thread holds two buffers of ``chunk_size`` bytes. By
+    default they come from ``malloc``; specialized environments can supply
+    pinned, NUMA-local or DMA-capable memory instead. *allocator* is a
+    ``PyCapsule`` named ``"komparu.buffer_allocator"`` wrapping a C
+    ``komparu_buffer_allocator_t`` (see ``komparu.h``), typically exported
+    by another extension module. The struct is copied; its functions must
+    stay valid for the life of the process.
+
+    Best called at startup. Buffers made by the previous allocator are
+    released through it, as each thread next compares or exits.
+
+    :param allocator: Allocator capsule, or None to restore ``malloc``.
+    :raises TypeError: If *allocator* is not such a capsule.
+    :raises ValueError: If the struct lacks ``alloc`` or ``release``.
+    """
+    _set_buffer_allocator(allocator)
@@ -0,0 +1,260 @@
+"""komparu.toml: defaults for the command line, shared per project or per user.
+
+A file holds option values by their long names, with dashes or
+underscores, at the top level for every command that has the option and
+in a table per command::
+
+    ignore = [".git", "*.pyc"]
+    max_workers = 8
+
+    [compare]
+    metadata = "mode,owner"
+    severity = ["only_right=warn"]
+
+    [verify]
+    algorithm = "sha512"
+
+Negated flags are set by the name they negate: ``follow_symlinks =
+false`` for ``--no-follow-symlinks``. Options taking ``PATTERN=VALUE``
+(``compare_as``, ``filter``, ``severity``) may be tables instead::
+
+    [compare.compare_as]
+    "*.json" = "json"
+    "vendor/*.gz" = "decompress"
+    "*.bin" = "bytes"
+
+``[profile.NAME]`` tables (and ``[profile.NAME.command]`` under them)
+bundle options for a recurring job, applied over the rest of the file
+only when ``--profile NAME`` selects them::
+
+    [profile.backup-verify]
+    metadata = "mode,owner,mtime"
+    log = "journald"
+    quiet = true
+
+``KOMPARU_<OPTION>`` environment variables (``KOMPARU_MAX_WORKERS``,
+``KOMPARU_CHUNK_SIZE``, ``KOMPARU_QUIET``, ...) override the files, for
+CI systems and containers where the environment is all there is; lists
+are comma-separated. Flags given on the command line override both;
+repeatable ones add to the list the files and the environment set.
+"""
+
+from __future__ import annotations
+
+import argparse
+import os
+import tomllib
+from collections.abc import Mapping
+from typing import Any
+
+PROJECT_FILE = "komparu.toml"
+ENV_PREFIX = "KOMPARU_"
+PROFILE_TABLE = "profile"
+# Variables named for what the option does rather than its long name
+_ENV_ALIASES = {"JOBS": "max_workers"}
+_TRUE = frozenset({"1", "true", "yes", "on"})
+_FALSE = frozenset({"", "0", "false", "no", "off"})
+# Options that load code or change files, which a file merely found in a
+# checkout must not set
+_TRUSTED_ONLY = frozenset({"rules", "apply"})
+# Repeatable PATTERN=VALUE options, which may also be written as a table
+_TABLES = frozenset({"compare_as", "filter", "severity"})
+
+
+def user_config_path() -> str:
+    """``$XDG_CONFIG_HOME/komparu/config.toml``, by default under ``~/.config``."""
+    base = os.environ.get("XDG_CONFIG_HOME") or os.path.join(os.path.expanduser("~"), ".config")
+    return os.path.join(base, "komparu", "config.toml")
+
+
+def find_project_config(start: str = ".") -> str | None:
+    """The ``komparu.toml`` in *start* or the nearest directory above it."""
+    directory = os.path.abspath(start)
+    while True:
+        candidate = os.path.join(directory, PROJECT_FILE)
+        if os.path.isfile(candidate):
+            return candidate
+        parent = os.path.dirname(directory)
+        if parent == directory:
+            return None
+        directory = parent
+
+
+def _options(parser: argparse.ArgumentParser) -> dict[str, argparse.Action]:
+    return {action.dest: action for action in parser._actions
+            if action.option_strings and action.dest not in ("help", "version")}
+
+
+def _convert(action: argparse.Action, value: Any, where: str, *, text: bool = False) -> Any:
+    if action.nargs == 0:  # store_true / store_false: the dest names the positive sense
+        if text and value.strip().lower() in _TRUE | _FALSE:
+            return value.strip().lower() in _TRUE
+        if not isinstance(value, bool):
+            raise ValueError(f"{where}: expected true or false")
+        return value
+    if isinstance(action.default, list):  # append
+        if isinstance(value, dict) and action.dest in _TABLES:
+            # In file order, which decides between overlapping patterns
+            value = [f"{key}={item}" for key, item in value.items()]
+        if text:
+            value = [item.strip() for item in value.split(",") if item.strip()]
+        items = value if isinstance(value, list) else [value]
+        return [_convert_one(action, item, where) for item in items]
+    return _convert_one(action, value, where)
+
+
+def _convert_one(action: argparse.Action, value: Any, where: str) -> Any:
+    if isinstance(value, (dict, list)) or (isinstance(value, bool) and action.type is not bool):
+        raise ValueError(f"{where}: expected a single value")
+    if action.type is not None:
+        try:
+            value = action.type(value if isinstance(value, str) else str(value))
+        except (TypeError, ValueError, argparse.ArgumentTypeError) as exc:
+            raise ValueError(f"{where}: {exc}") from None
+    elif not isinstance(value, str):
+        value = str(value)
+    if action.choices is not None and value not in action.choices:
+        raise ValueError(f"{where}: expected one of {', '.join(map(str, action.choices))}")
+    return value
+
+
+def _load(path: str) -> dict[str, Any]:
+    with open(path, "rb") as f:
+        try:
+            return tomllib.load(f)
+        except tomllib.TOMLDecodeError as exc:
+            raise ValueError(f"{path}: {exc}") from None
+
+
+def _profiles(data: dict[str, Any], path: str) -> dict[str, dict[str, Any]]:
+    profiles = data.get(PROFILE_TABLE, {})
+    if not isinstance(profiles, dict) or not all(isinstance(p, dict) for p in profiles.values()):
+        raise ValueError(f"{path}: [{PROFILE_TABLE}.NAME] must be tables")
+    return profiles
+
+
+def _values(data: dict[str, Any], where: str, commands: Mapping[str, argparse.ArgumentParser],
+            trusted: bool) -> dict[str, dict[str, Any]]:
+    options = {name: _options(parser) for name, parser in commands.items()}
+    shared = {key: value for key, value in data.items() if key not in commands}
+    for key in shared:
+        if not any(key.replace("-", "_") in known for known in options.values()):
+            raise ValueError(f"{where}: unknown option {key!r}")
+    defaults: dict[str, dict[str, Any]] = {}
+    for command, known in options.items():
+        table = data.get(command, {})
+        if not isinstance(table, dict):
+            raise ValueError(f"{where}: [{command}] must be a table")
+        values = {}
+        for key, value in (*shared.items(), *table.items()):
+            dest = key.replace("-", "_")
+            at = f"{where}: {key}" if key in shared else f"{where}: [{command}] {key}"
+            if dest not in known:
+                if key in shared:
+                    continue
+                raise ValueError(f"{where}: unknown option {key!r} for komparu {command}")
+            if not trusted and dest in _TRUSTED_ONLY:
+                raise ValueError(f"{at}: only the user configuration or --config may set it")
+            values[dest] = _convert(known[dest], value, at)
+        if values:
+            defaults[command] = values
+    return defaults
+
+
+def _merge(layers: list[dict[str, dict[str, Any]]]) -> dict[str, dict[str, Any]]:
+    defaults: dict[str, dict[str, Any]] = {}
+    for layer in layers:
+        for command, values in layer.items():
+            defaults.setdefault(command, {}).update(values)
+    return defaults
+
+
+def read_config(path: str, commands: Mapping[str, argparse.ArgumentParser], *,
+                trusted: bool = True, profile: str | None = None) -> dict[str, dict[str, Any]]:
+    """Defaults per command that the file at *path* sets, checked and converted.
+
+    :param commands: Parser of every subcommand, by name.
+    :param trusted: Whether the file may set options that run code
+        (``rules``) or change files (``apply``); a project file found by
+        searching is not.
+    :param profile: Name of a ``[profile.NAME]`` table whose options
+        override the file's own, if the file has one.
+    :raises ValueError: If the file is not TOML, or names an unknown
+        option or gives one a value it does not take.
+    """
+    data = _load(path)
+    profiles = _profiles(data, path)
+    base = {key: value for key, value in data.items() if key != PROFILE_TABLE}
+    layers = [_values(base, path, commands, trusted)]
+    if profile is not None and profile in profiles:
+        layers.append(_values(profiles[profile], f"{path}: [{PROFILE_TABLE}.{profile}]",
+                              commands, trusted))
+    return _merge(layers)
+
+
+def profile_names(path: str) -> list[str]:
+    """Names of the ``[profile.NAME]`` tables in the file at *path*."""
+    return sorted(_profiles(_load(path), path))
+
+
+def env_defaults(commands: Mapping[str, argparse.ArgumentParser],
+                 environ: Mapping[str, str]) -> dict[str, dict[str, Any]]:
+    """Defaults per command that ``KOMPARU_<OPTION>`` variables in *environ* set.
+
+    Variables that name no option of any command are left alone: other
+    ``KOMPARU_`` variables (``KOMPARU_AGENT_TOKEN``, ...) mean other things.
+
+    :raises ValueError: If a variable gives an option a value it does not
+        take.
+    """
+    wanted = {}
+    for name, value in environ.items():
+        if name.startswith(ENV_PREFIX):
+            key = name[len(ENV_PREFIX):]
+            wanted[_ENV_ALIASES.get(key, key.lower())] = (name, value)
+    defaults: dict[str, dict[str, Any]] = {}
+    for command, parser in commands.items():
+        known = _options(parser)
+        values = {dest: _convert(known[dest], value, name, text=True)
+                  for dest, (name, value) in wanted.items() if dest in known}
+        if values:
+            defaults[command] = values
+    return defaults
+
+
+def env_flag(name: str, environ: Mapping[str, str]) -> bool:
+    """Whether the variable *name* is set to a true value (``1``, ``yes``, ...)."""
+    return environ.get(name, "").strip().lower() in _TRUE
+
+
+def load_defaults(commands: Mapping[str, argparse.ArgumentParser],
+                  explicit: str | None = None,
+                  environ: Mapping[str, str] | None = None,
+                  profile: str | None = None) -> dict[str, dict[str, Any]]:
+    """Defaults per command from the user configuration, the project's and the environment.
+
+    The project file (*explicit*, else ``$KOMPARU_CONFIG``, else the
+    nearest ``komparu.toml``) overrides the user's ``config.toml`` option
+    by option, and ``KOMPARU_<OPTION>`` variables override both. A
+    *profile* (else ``$KOMPARU_PROFILE``) overrides each file's options
+    with its ``[profile.NAME]`` table there.
+
+    :raises ValueError: If no file defines *profile*.
+    """
+    environ = os.environ if environ is None else environ
+    explicit = explicit or environ.get("KOMPARU_CONFIG") or None
+    profile = profile or environ.get("KOMPARU_PROFILE") or None
+    files = []
+    if os.path.isfile(user := user_config_path()):
+        files.append((user, True))
+    if explicit is not None:
+        files.append((explicit, True))
+    elif (project := find_project_config()) is not None:
+        files.append((project, False))
+    if profile is not None and not any(profile in profile_names(path) for path, _ in files):
+        where = ", ".join(path for path, _ in files) or "no configuration file found"
+        raise ValueError(f"unknown profile {profile!r} ({where})")
+    layers = [read_config(path, commands, trusted=trusted, profile=profile)
+              for path, trusted in files]
+    layers.append(env_defaults(commands, environ))
+    return _merge(layers)
@@ -0,0 +1,121 @@
+"""Copies verified against a hash taken while the source was read."""
+
+from __future__ import annotations
+
+import errno
+import os
+import shutil
+import stat
+from collections.abc import Iterator
+
+from komparu._filter import PathFilter, make_exclude
+from komparu._hashing import file_digest, new_hash, validate_algorithm
+from komparu._helpers import walk_tree
+from komparu._types import CopyVerifyError, SourcePermissionError
+from komparu._validate import validate_chunk_size, validate_path
+
+
+def _evict(fd: int) -> None:
+    # Written pages would otherwise be read back from the page cache,
+    # and the verification would never see what reached the disk
+    os.fsync(fd)
+    if hasattr(os, "posix_fadvise"):
+        os.posix_fadvise(fd, 0, 0, os.POSIX_FADV_DONTNEED)
+
+
+def _copy_file(source: str, destination: str, algorithm: str, chunk_size: int) -> str:
+    h = new_hash(algorithm)
+    buf = bytearray(chunk_size)
+    view = memoryview(buf)
+    with open(source, "rb") as src, open(destination, "wb") as dst:
+        while n := src.readinto(buf):
+            h.update(view[:n])
+            dst.write(view[:n])
+        dst.flush()
+        _evict(dst.fileno())
+    shutil.copymode(source, destination)
+    digest = h.hexdigest()
+    copied = file_digest(destination, algorithm)
+    if copied != digest:
+        raise CopyVerifyError(errno.EIO, f"copy does not match its source ({algorithm} "
+                                         f"{copied}, source {digest})", destination)
+    return digest
+
+
+def copy_verified(
+    source: str,
+    destination: str,
+    *,
+    algorithm: str = "sha256",
+    chunk_size: int = 1024 * 1024,
+) -> str:
+    """Copy a file and check the copy, reading the source only once.
+
+    The source is hashed as it is copied; the copy is then flushed to
+    disk, dropped from the page cache where the platform allows, and
+    hashed again from disk.
+
+    :param source: File to copy.
+    :param destination: Path of the copy (overwritten if it exists).
+    :param algorithm: Any fixed-size :mod:`hashlib` algorithm, or
+        ``blake3``, ``xxh3``, ``xxh128``, ``crc32`` or ``crc32c``.
+    :param chunk_size: Read and write size in bytes.
+    :returns: Hex digest of the source, which the copy matches.
+    :raises CopyVerifyError: If the copy reads back differently.
+    """
+    validate_path(source, "source")
+    validate_path(destination, "destination")
+    validate_algorithm(algorithm)
+    validate_chunk_size(chunk_size)
+    return _copy_file(source, destination, algorithm, chunk_size)
+
+
+def copy_tree_verified(
+    source: str,
+    destination: str,
+    *,
+    algorithm: str = "sha256",
+    chunk_size: int = 1024 * 1024,
+    follow_symlinks: bool = True,
+    ignore: list[str] | None = None,
+    path_filter: PathFilter | None = None,
+) -> Iterator[tuple[str, str]]:
+    """Copy a directory tree, checking every file as :func:`copy_verified` does.
+
+    Directories are created as needed; symlinks that are not followed (or
+    dangle) are recreated as links, and other special files are skipped.
+    Yields ``(relative_path, hex_digest)`` per copied file in path order,
+    which :func:`komparu.format_manifest_line` turns into a manifest of
+    the copy.
+
+    :param source: Directory to copy.
+    :param destination: Root of the copy; created if missing.
+    :param algorithm: Hash algorithm, as for :func:`copy_verified`.
+    :param chunk_size: Read and write size in bytes.
+    :param follow_symlinks: Follow symbolic links during traversal.
+    :param ignore: Glob patterns to exclude (matched per path component).
+    :param path_filter: PathFilter deciding per entry whether it is copied.
+    :raises CopyVerifyError: If a copy reads back differently.
+    :raises SourcePermissionError: If a directory cannot be listed.
+    """
+    validate_path(source, "source")
+    validate_path(destination, "destination")
+    validate_algorithm(algorithm)
+    validate_chunk_size(chunk_size)
+    exclude = make_exclude(ignore, path_filter, source, source, follow_symlinks)
+    entries, errors = walk_tree(source, follow_symlinks, exclude)
+    if errors:
+        raise SourcePermissionError(errno.EACCES, "cannot read",
+                                    os.path.join(source, min(errors)))
+    os.makedirs(destination, exist_ok=True)
+    for path in sorted(entries):
+        st = entries[path]
+        target = os.path.join(destination, path)
+        if stat.S_ISDIR(st.st_mode):
+            os.makedirs(target, exist_ok=True)
+        elif stat.S_ISLNK(st.st_mode):
+            if os.path.lexists(target):
+                os.unlink(target)
+            os.symlink(os.readlink(os.path.join(source, path)), target)
+        elif stat.S_ISREG(st.st_mode):
+            yield path, _copy_file(os.path.join(source, path), target, algorithm, chunk_size)
@@ -0,0 +1,205 @@
+"""Content-defined chunking: where and how two differing files diverge."""
+
+from __future__ import annotations
+
+import difflib
+import mmap
+from types import TracebackType
+
+from komparu._core import cdc_chunks as _cdc_chunks_c
+from komparu._core import first_diff as _first_diff_c
+from komparu._hashing import fingerprint_hash
+from komparu._types import ChunkDiff, DiffRegion, RegionKind
+from komparu._validate import validate_path
+
+_MIN_AVG_CHUNK_SIZE = 64
+
+# Delta instructions as in VCDIFF: an opcode byte plus varint operands
+_OPCODE_SIZE = 1
+
+
+def _validate_avg_chunk_size(avg_chunk_size: int) -> None:
+    if avg_chunk_size < _MIN_AVG_CHUNK_SIZE:
+        raise ValueError(f"avg_chunk_size must be at least {_MIN_AVG_CHUNK_SIZE}")
+
+
+class _Chunked:
+    """A file mapped into memory and cut into content-defined chunks."""
+
+    __slots__ = ("data", "ends", "digests", "_file", "_map")
+
+    def __init__(self, path: str, avg_chunk_size: int) -> None:
+        validate_path(path, "path")
+        self._file = open(path, "rb")
+        try:
+            self._map = mmap.mmap(self._file.fileno(), 0, access=mmap.ACCESS_READ)
+        except ValueError:  # empty file
+            self._map = None
+        self.data: mmap.mmap | bytes = self._map if self._map is not None else b""
+        self.ends: list[int] = _cdc_chunks_c(self.data, avg_chunk_size // 4, avg_chunk_size,
+                                             avg_chunk_size * 8)
+        with memoryview(self.data) as view:
+            starts = [0, *self.ends[:-1]]
+            self.digests = [fingerprint_hash(view[start:end]).digest()
+                            for start, end in zip(starts, self.ends)]
+
+    def offset(self, index: int) -> int:
+        """Byte offset where chunk *index* starts (the file size past the end)."""
+        return self.ends[index - 1] if index else 0
+
+    def __enter__(self) -> _Chunked:
+        return self
+
+    def __exit__(
+        self,
+        exc_type: type[BaseException] | None,
+        exc: BaseException | None,
+        tb: TracebackType | None,
+    ) -> None:
+        if self._map is not None:
+            self._map.close()
+        self._file.close()
+
+
+def common_length(x: bytes | memoryview, y: bytes | memoryview) -> int:
+    offset = _first_diff_c(x, y)
+    return min(len(x), len(y)) if offset < 0 else offset
+
+
+def _narrowed(a: _Chunked, b: _Chunked, start_a: int, end_a: int, start_b: int, end_b: int,
+              ) -> tuple[int, int, int, int]:
+    """Two unmatched byte ranges without their common prefix and suffix."""
+    with memoryview(a.data) as view_a, memoryview(b.data) as view_b:
+        prefix = common_length(view_a[start_a:end_a], view_b[start_b:end_b])
+        start_a, start_b = start_a + prefix, start_b + prefix
+        suffix = common_length(bytes(view_a[start_a:end_a])[::-1],
+                                bytes(view_b[start_b:end_b])[::-1])
+    return start_a, end_a - suffix, start_b, end_b - suffix
+
+
+def chunk_diff(path_a: str, path_b: str, *, avg_chunk_size: int = 8192) -> ChunkDiff:
+    """Cut two files into content-defined chunks and align them.
+
+    Chunk boundaries depend on content only (FastCDC), so an insertion
+    shifts the rest of the file without changing its chunks: a file with
+    a few bytes inserted shares all but a chunk or two with the original,
+    where a block-aligned comparison would see everything after the
+    insertion differ. Both files are memory-mapped; the GIL is released
+    while chunking.
+
+    :param path_a: First file path.
+    :param path_b: Second file path.
+    :param avg_chunk_size: Target chunk size in bytes (chunks are a
+        quarter of it to eight times it); smaller finds smaller edits.
+    :returns: Chunk counts and the insertions, deletions and changes
+        that turn the first file into the second, with their byte ranges.
+    """
+    _validate_avg_chunk_size(avg_chunk_size)
+    with _Chunked(path_a, avg_chunk_size) as a, _Chunked(path_b, avg_chunk_size) as b:
+        matcher = difflib.SequenceMatcher(None, a.digests, b.digests, autojunk=False)
+        regions: list[DiffRegion] = []
+        shared_chunks = shared_bytes = 0
+        for tag, i1, i2, j1, j2 in matcher.get_opcodes():
+            if tag == "equal":
+                shared_chunks += i2 - i1
+                shared_bytes += a.offset(i2) - a.offset(i1)
+                continue
+            start_a, end_a, start_b, end_b = a.offset(i1), a.offset(i2), b.offset(j1), b.offset(j2)
+            if tag == "replace":
+                # An edit inside a chunk replaces it; look at the bytes for what it was
+                start_a, end_a, start_b, end_b = _narrowed(a, b, start_a, end_a,
+                                                           start_b, end_b)
+            if start_a == end_a:
+                kind = RegionKind.INSERTED
+            elif start_b == end_b:
+                kind = RegionKind.DELETED
+            else:
+                kind = RegionKind.CHANGED
+            regions.append(DiffRegion(kind, start_a, end_a - start_a, start_b, end_b - start_b))
+        return ChunkDiff(
+            size_a=len(a.data),
+            size_b=len(b.data),
+            chunks_a=len(a.ends),
+            chunks_b=len(b.ends),
+            shared_chunks=shared_chunks,
+            shared_bytes=shared_bytes,
+            insertions=sum(r.kind is RegionKind.INSERTED for r in regions),
+            deletions=sum(r.kind is RegionKind.DELETED for r in regions),
+            changes=sum(r.kind is RegionKind.CHANGED for r in regions),
+            regions=tuple(regions),
+        )
+
+
+def _varint_size(value: int) -> int:
+    return max(1, (value.bit_length() + 6) // 7)
+
+
+def delta_size(path_a: str, path_b: str, *, avg_chunk_size: int = 8192) -> int:
+    """Estimate the size of a delta that rebuilds the second file from the first.
+
+    The second file's content-defined chunks are looked up among the
+    first's wherever they are, so moved content is copied rather than
+    resent; adjacent copies merge into one instruction, and each run of
+    new chunks is trimmed to the bytes that differ from its neighbours
+    in the first file. The estimate counts these literal bytes plus a
+    few bytes per copy or literal instruction, as a VCDIFF- or
+    rsync-style delta would encode them — compare it with the file size
+    to decide whether delta transfer is worth it.
+
+    :param path_a: Old file path (the delta's source).
+    :param path_b: New file path (what the delta rebuilds).
+    :param avg_chunk_size: Target chunk size in bytes; smaller finds
+        smaller matches at the cost of more instructions.
+    :returns: Estimated delta size in bytes; 0 for identical files.
+    """
+    _validate_avg_chunk_size(avg_chunk_size)
+    with _Chunked(path_a, avg_chunk_size) as a, _Chunked(path_b, avg_chunk_size) as b:
+        where: dict[bytes, list[int]] = {}
+        for index, digest in enumerate(a.digests):
+            where.setdefault(digest, []).append(index)
+
+        # (is_copy, start, end): copies index the first file, literals the second
+        ops: list[tuple[bool, int, int]] = []
+        last = -2
+        for j, digest in enumerate(b.digests):
+            matches = where.get(digest)
+            if matches is None:
+                if ops and not ops[-1][0]:
+                    ops[-1] = (False, ops[-1][1], b.ends[j])
+                else:
+                    ops.append((False, b.offset(j), b.ends[j]))
+                last = -2
+            elif last + 1 in matches:
+                ops[-1] = (True, ops[-1][1], a.ends[last + 1])
+                last += 1
+            else:
+                last = matches[0]
+                ops.append((True, a.offset(last), a.ends[last]))
+
+        if ops == [(True, 0, len(a.data))]:
+            return 0
+        size = 0
+        with memoryview(a.data) as view_a, memoryview(b.data) as view_b:
+            for position, (is_copy, start, end) in enumerate(ops):
+                if is_copy:
+                    size += _OPCODE_SIZE + _varint_size(start) + _varint_size(end - start)
+                    continue
+                # Bytes that carry on from the previous copy or lead into the
+                # next one extend those copies instead of being sent; at either
+                # end of the file they are copied from that end of the first
+                after = ops[position - 1][2] if position > 0 else 0
+                prefix = common_length(view_a[after:after + end - start], view_b[start:end])
+                if prefix and position == 0:
+                    size += _OPCODE_SIZE + _varint_size(0) + _varint_size(prefix)
+                start += prefix
+                before = ops[position + 1][1] if position + 1 < len(ops) else len(a.data)
+                length = min(before, end - start)
+                suffix = common_length(bytes(view_a[before - length:before])[::-1],
+                                        bytes(view_b[start:end])[::-1])
+                if suffix and position + 1 == len(ops):
+                    size += (_OPCODE_SIZE + _varint_size(before - suffix)
+                             + _varint_size(suffix))
+                end -= suffix
+                if end > start:
+                    size += _OPCODE_SIZE + _varint_size(end - start) + end - start
+        return size
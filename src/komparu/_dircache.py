@@ -0,0 +1,64 @@
+"""Warm cache of equal file pairs shared across directory comparisons."""
+
+from __future__ import annotations
+
+from typing import Any
+
+from komparu._core import dir_cache_new, dir_cache_clear, dir_cache_stats
+
+
+class DirCache:
+    """Remembers which file pairs were equal, so unchanged ones are not re-read.
+
+    Pass as ``cache=`` to :func:`komparu.compare_dir`, :func:`komparu.iter_dir`,
+    their :mod:`komparu.aio` counterparts or :class:`komparu.Comparator`.
+    Every pair found equal is stored with the size, mtime, ctime, device
+    and inode of both files; a later run that stats the same values
+    settles the pair as equal without opening it. A write, touch or
+    replace of either file changes that signature and forces a real
+    comparison. Files modified less than two seconds before the store
+    are not remembered, since a second write within the same timestamp
+    tick would go unnoticed.
+
+    Thread-safe: several comparisons may share one cache concurrently.
+
+    :param max_entries: Upper bound on remembered pairs (None = unbounded);
+        once reached, new pairs are not added until :meth:`clear`.
+    """
+
+    __slots__ = ("_handle",)
+
+    def __init__(self, max_entries: int | None = None) -> None:
+        if max_entries is not None:
+            if isinstance(max_entries, bool) or not isinstance(max_entries, int):
+                raise TypeError(
+                    f"max_entries must be an int or None, got {type(max_entries).__name__}"
+                )
+            if max_entries <= 0:
+                raise ValueError(f"max_entries must be positive, got {max_entries}")
+        self._handle = dir_cache_new(max_entries or 0)
+
+    def clear(self) -> None:
+        """Forget every pair and reset :attr:`hits`."""
+        dir_cache_clear(self._handle)
+
+    @property
+    def hits(self) -> int:
+        """Pairs settled from the cache since creation or the last :meth:`clear`."""
+        return dir_cache_stats(self._handle)[1]
+
+    def __len__(self) -> int:
+        return dir_cache_stats(self._handle)[0]
+
+    def __repr__(self) -> str:
+        entries, hits = dir_cache_stats(self._handle)
+        return f"DirCache(entries={entries}, hits={hits})"
+
+
+def cache_handle(cache: DirCache | None) -> Any:
+    """C extension handle for *cache* (None passes through)."""
+    if cache is None:
+        return None
+    if not isinstance(cache, DirCache):
+        raise TypeError(f"cache must be a DirCache, got {type(cache).__name__}")
+    return cache._handle
@@ -0,0 +1,1217 @@
+"""File systems inside disk images — ext2/3/4, FAT, exFAT, ISO 9660, UDF — unmounted.
+
+Firmware, forensic and disc images are usually compared by
+loop-mounting them, which needs root. The readers here parse the on-disk structures
+directly, read-only: a directory of the image becomes a tree of
+entries whose content is streamed from the image file on demand.
+"""
+
+from __future__ import annotations
+
+import errno
+import os
+import stat
+import struct
+from collections.abc import Iterator
+from typing import Any, NamedTuple
+
+from komparu._filter import make_exclude
+from komparu._helpers import walk_tree
+from komparu._types import (
+    ArchiveError,
+    DiffReason,
+    DirResult,
+    KomparuError,
+    SourceNotFoundError,
+    SourcePermissionError,
+    UnsupportedSourceError,
+)
+from komparu._validate import validate_path
+
+_CHUNK = 1 << 20
+
+
+class _Node(NamedTuple):
+    kind: str  # file, dir, symlink or other
+    size: int
+    ref: Any  # what the file system reads the entry by
+    target: str | None  # of a symlink
+
+
+def _zeros(size: int) -> Iterator[bytes]:
+    while size > 0:
+        n = min(size, _CHUNK)
+        yield bytes(n)
+        size -= n
+
+
+class _Volume:
+    """Bytes of one file system: a region of an image file or block device."""
+
+    def __init__(self, fd: int, offset: int, size: int, source: str) -> None:
+        self.fd = fd
+        self.offset = offset
+        self.size = size
+        self.source = source
+
+    def read(self, offset: int, size: int) -> bytes:
+        if offset < 0 or offset + size > self.size:
+            raise ArchiveError(errno.EINVAL, "structure points past the end of the file "
+                               "system", self.source)
+        data = os.pread(self.fd, size, self.offset + offset)
+        if len(data) != size:
+            raise ArchiveError(errno.EINVAL, "image is truncated", self.source)
+        return data
+
+    def corrupt(self, what: str) -> ArchiveError:
+        return ArchiveError(errno.EINVAL, f"corrupt file system: {what}", self.source)
+
+
+# ---- ext2/3/4 ----
+
+_EXT_MAGIC = 0xEF53
+_EXT_INCOMPAT_UNSUPPORTED = {0x1: "compression", 0x10: "meta_bg", 0x10000: "encryption"}
+_EXT_INCOMPAT_64BIT = 0x80
+_EXT_EXTENTS_FL = 0x80000
+_EXT_INLINE_DATA_FL = 0x10000000
+_EXT_ENCRYPT_FL = 0x800
+_EXT_EXTENT_MAGIC = 0xF30A
+_EXT_ROOT = 2
+
+
+class _Ext:
+    """ext2, ext3 and ext4: block maps, extent trees and small inline files."""
+
+    def __init__(self, vol: _Volume) -> None:
+        self._vol = vol
+        sb = vol.read(1024, 1024)
+        if struct.unpack_from("<H", sb, 56)[0] != _EXT_MAGIC:
+            raise vol.corrupt("no ext superblock")
+        inodes, = struct.unpack_from("<I", sb, 0)
+        first_data, log_block = struct.unpack_from("<II", sb, 20)
+        self._ipg, = struct.unpack_from("<I", sb, 40)
+        rev, = struct.unpack_from("<I", sb, 76)
+        incompat, = struct.unpack_from("<I", sb, 96)
+        if log_block > 6 or not self._ipg:
+            raise vol.corrupt("bad superblock")
+        for flag, name in _EXT_INCOMPAT_UNSUPPORTED.items():
+            if incompat & flag:
+                raise UnsupportedSourceError(
+                    errno.ENOTSUP, f"ext file system uses {name}, which is not supported",
+                    vol.source)
+        self.block = 1024 << log_block
+        self._inode_size = struct.unpack_from("<H", sb, 88)[0] if rev else 128
+        wide = bool(incompat & _EXT_INCOMPAT_64BIT)
+        desc_size = (struct.unpack_from("<H", sb, 254)[0] or 32) if wide else 32
+        groups = -(-inodes // self._ipg)
+        table = vol.read((first_data + 1) * self.block, groups * desc_size)
+        self._tables = []
+        for group in range(groups):
+            base = group * desc_size
+            lo, = struct.unpack_from("<I", table, base + 8)
+            hi = struct.unpack_from("<I", table, base + 0x28)[0] if desc_size >= 64 else 0
+            self._tables.append(lo | hi << 32)
+        self.root = _EXT_ROOT
+
+    def _inode(self, ino: int) -> bytes:
+        group, index = divmod(ino - 1, self._ipg)
+        if ino < 1 or group >= len(self._tables):
+            raise self._vol.corrupt(f"inode {ino} out of range")
+        return self._vol.read(self._tables[group] * self.block + index * self._inode_size, 128)
+
+    def _block(self, number: int) -> bytes:
+        return self._vol.read(number * self.block, self.block)
+
+    def _node(self, ino: int) -> _Node:
+        raw = self._inode(ino)
+        mode, size_lo = struct.unpack_from("<HxxI", raw, 0)
+        size = size_lo | struct.unpack_from("<I", raw, 108)[0] << 32
+        fmt = stat.S_IFMT(mode)
+        if fmt == stat.S_IFREG:
+            return _Node("file", size, ino, None)
+        if fmt == stat.S_IFDIR:
+            return _Node("dir", 0, ino, None)
+        if fmt != stat.S_IFLNK:
+            return _Node("other", 0, ino, None)
+        flags, = struct.unpack_from("<I", raw, 32)
+        blocks, = struct.unpack_from("<I", raw, 28)
+        acl, = struct.unpack_from("<I", raw, 104)
+        fast = (size < 60 and not flags & _EXT_EXTENTS_FL
+                and blocks == (self.block // 512 if acl else 0))
+        if fast or flags & _EXT_INLINE_DATA_FL:
+            data = raw[40:40 + size]
+        else:
+            data = b"".join(self._content(raw, size))
+        return _Node("symlink", 0, ino, os.fsdecode(data))
+
+    def _extent_runs(self, node: bytes, depth_left: int,
+                     runs: list[tuple[int, int, int, bool]]) -> None:
+        magic, count, _, depth = struct.unpack_from("<HHHH", node, 0)
+        if magic != _EXT_EXTENT_MAGIC or depth > depth_left or 12 + count * 12 > len(node):
+            raise self._vol.corrupt("bad extent tree")
+        for i in range(count):
+            base = 12 + i * 12
+            if depth:
+                leaf_lo, leaf_hi = struct.unpack_from("<IH", node, base + 4)
+                self._extent_runs(self._block(leaf_lo | leaf_hi << 32), depth - 1, runs)
+            else:
+                logical, length, start_hi, start_lo = struct.unpack_from("<IHHI", node, base)
+                zero = length > 32768  # allocated but never written: reads as zeros
+                runs.append((logical, start_lo | start_hi << 32,
+                             length - 32768 if zero else length, zero))
+
+    def _map_runs(self, pointers: tuple[int, ...], level: int, logical: int, limit: int,
+                  runs: list[tuple[int, int, int, bool]]) -> int:
+        per = self.block // 4
+        span = per ** level
+        for pointer in pointers:
+            if logical >= limit:
+                break
+            if pointer and level:
+                block = self._block(pointer)
+                self._map_runs(struct.unpack(f"<{per}I", block), level - 1, logical, limit, runs)
+            elif pointer:
+                start, physical, count, _ = runs[-1] if runs else (-1, -1, 0, False)
+                if start + count == logical and physical + count == pointer:
+                    runs[-1] = (start, physical, count + 1, False)
+                else:
+                    runs.append((logical, pointer, 1, False))
+            logical += span
+        return logical
+
+    def _content(self, raw: bytes, size: int) -> Iterator[bytes]:
+        flags, = struct.unpack_from("<I", raw, 32)
+        if flags & _EXT_ENCRYPT_FL:
+            raise UnsupportedSourceError(errno.ENOTSUP, "file is encrypted", self._vol.source)
+        if flags & _EXT_INLINE_DATA_FL:
+            if size > 60:
+                raise UnsupportedSourceError(
+                    errno.ENOTSUP, "inline data past the inode is not supported",
+                    self._vol.source)
+            yield raw[40:40 + size]
+            return
+        runs: list[tuple[int, int, int, bool]] = []
+        if flags & _EXT_EXTENTS_FL:
+            self._extent_runs(raw[40:100], 5, runs)
+        else:
+            pointers = struct.unpack_from("<15I", raw, 40)
+            limit = -(-size // self.block)
+            logical = self._map_runs(pointers[:12], 0, 0, limit, runs)
+            for level, pointer in enumerate(pointers[12:], 1):
+                logical = self._map_runs((pointer,), level, logical, limit, runs)
+        pos = 0
+        for logical, physical, count, zero in runs:
+            start = logical * self.block
+            if start >= size:
+                break
+            if start < pos:
+                raise self._vol.corrupt("overlapping extents")
+            yield from _zeros(start - pos)
+            end = min(size, start + count * self.block)
+            if zero:
+                yield from _zeros(end - start)
+            else:
+                offset = physical * self.block
+                for at in range(start, end, _CHUNK):
+                    yield self._vol.read(offset + at - start, min(_CHUNK, end - at))
+            pos = end
+        yield from _zeros(size - pos)
+
+    def read(self, node: _Node) -> Iterator[bytes]:
+        return self._content(self._inode(node.ref), node.size)
+
+    def list(self, ref: int) -> dict[str, _Node]:
+        raw = self._inode(ref)
+        size, = struct.unpack_from("<I", raw, 4)
+        flags, = struct.unpack_from("<I", raw, 32)
+        if flags & _EXT_ENCRYPT_FL:
+            raise UnsupportedSourceError(errno.ENOTSUP, "directory is encrypted",
+                                         self._vol.source)
+        if flags & _EXT_INLINE_DATA_FL:
+            data = raw[44:40 + min(size, 60)]  # after the parent's inode number
+        else:
+            data = b"".join(self._content(raw, size))
+        entries = {}
+        pos = 0
+        while pos + 8 <= len(data):
+            ino, rec_len, name_len = struct.unpack_from("<IHB", data, pos)
+            if rec_len < 8 or pos + rec_len > len(data):
+                raise self._vol.corrupt("bad directory entry")
+            name = data[pos + 8:pos + 8 + name_len]
+            if ino and name not in (b".", b".."):
+                entries[os.fsdecode(name)] = self._node(ino)
+            pos += rec_len
+        return entries
+
+
+# ---- FAT and exFAT ----
+
+class _Clustered:
+    """Cluster chains of a FAT12/16/32 or exFAT volume."""
+
+    _vol: _Volume
+    _cluster: int
+    _heap: int
+    _count: int
+
+    def _next(self, cluster: int) -> int | None:
+        raise NotImplementedError
+
+    def _chain(self, first: int) -> list[int]:
+        chain = []
+        cluster: int | None = first
+        while cluster is not None:
+            if not 2 <= cluster < self._count + 2 or len(chain) > self._count:
+                raise self._vol.corrupt("bad cluster chain")
+            chain.append(cluster)
+            cluster = self._next(cluster)
+        return chain
+
+    def _clusters(self, clusters: list[int], size: int) -> Iterator[bytes]:
+        """*size* bytes stored in *clusters*, contiguous ones read together."""
+        i = 0
+        while size > 0 and i < len(clusters):
+            j = i + 1
+            while j < len(clusters) and clusters[j] == clusters[j - 1] + 1:
+                j += 1
+            offset = self._heap + (clusters[i] - 2) * self._cluster
+            length = min(size, (j - i) * self._cluster)
+            for at in range(0, length, _CHUNK):
+                yield self._vol.read(offset + at, min(_CHUNK, length - at))
+            size -= length
+            i = j
+        if size > 0:
+            raise self._vol.corrupt("cluster chain shorter than the file")
+
+
+_FAT_ATTR_LFN = 0x0F
+_FAT_ATTR_VOLUME = 0x08
+_FAT_ATTR_DIR = 0x10
+
+
+def _fat_checksum(short: bytes) -> int:
+    total = 0
+    for byte in short:
+        total = ((total & 1) << 7) + (total >> 1) + byte & 0xFF
+    return total
+
+
+def _fat_bpb(boot: bytes) -> tuple[int, ...] | None:
+    """The BIOS parameter block fields of a FAT boot sector, or None if it is not one."""
+    if boot[510:512] != b"\x55\xaa" or boot[0] not in (0xEB, 0xE9):
+        return None
+    bps, spc, reserved, fats, root_entries, total16 = struct.unpack_from("<HBHBHH", boot, 11)
+    fat16, = struct.unpack_from("<H", boot, 22)
+    total32, fat32 = struct.unpack_from("<II", boot, 32)
+    fat_sectors, total = fat16 or fat32, total16 or total32
+    if (bps not in (512, 1024, 2048, 4096) or not spc or spc & (spc - 1) or not reserved
+            or fats not in (1, 2) or not fat_sectors or not total):
+        return None
+    return bps, spc, reserved, fats, root_entries, total, fat_sectors
+
+
+class _Fat(_Clustered):
+    """FAT12, FAT16 and FAT32 with long (VFAT) names."""
+
+    def __init__(self, vol: _Volume) -> None:
+        self._vol = vol
+        boot = vol.read(0, 512)
+        bpb = _fat_bpb(boot)
+        if bpb is None:
+            raise vol.corrupt("no FAT boot sector")
+        bps, spc, reserved, fats, root_entries, total, fat_sectors = bpb
+        root_sectors = -(-root_entries * 32 // bps)
+        first_data = reserved + fats * fat_sectors + root_sectors
+        self._count = (total - first_data) // spc
+        self._bits = 12 if self._count < 4085 else 16 if self._count < 65525 else 32
+        self._cluster = bps * spc
+        self._heap = first_data * bps
+        self._fat = vol.read(reserved * bps, fat_sectors * bps)
+        if self._bits == 32:
+            self.root: tuple[int, int] = (struct.unpack_from("<I", boot, 44)[0], 0)
+        else:
+            self.root = (0, root_entries * 32)
+        self._root_offset = (reserved + fats * fat_sectors) * bps
+
+    def _next(self, cluster: int) -> int | None:
+        try:
+            if self._bits == 12:
+                value, = struct.unpack_from("<H", self._fat, cluster + cluster // 2)
+                value = value >> 4 if cluster & 1 else value & 0xFFF
+                end, bad = 0xFF8, 0xFF7
+            elif self._bits == 16:
+                value, = struct.unpack_from("<H", self._fat, cluster * 2)
+                end, bad = 0xFFF8, 0xFFF7
+            else:
+                value = struct.unpack_from("<I", self._fat, cluster * 4)[0] & 0x0FFFFFFF
+                end, bad = 0x0FFFFFF8, 0x0FFFFFF7
+        except struct.error:
+            raise self._vol.corrupt("cluster past the FAT") from None
+        if value == bad:
+            raise self._vol.corrupt("bad cluster in a chain")
+        return None if value >= end else value
+
+    def read(self, node: _Node) -> Iterator[bytes]:
+        if not node.size:
+            return iter(())
+        return self._clusters(self._chain(node.ref), node.size)
+
+    def list(self, ref: tuple[int, int]) -> dict[str, _Node]:
+        first, fixed = ref
+        if fixed:
+            data = self._vol.read(self._root_offset, fixed)
+        else:
+            chain = self._chain(first)
+            data = b"".join(self._clusters(chain, len(chain) * self._cluster))
+        entries: dict[str, _Node] = {}
+        long_parts: list[tuple[int, str]] = []
+        for pos in range(0, len(data) - 31, 32):
+            entry = data[pos:pos + 32]
+            if entry[0] == 0:
+                break
+            attr = entry[11]
+            if entry[0] == 0xE5:
+                long_parts = []
+            elif attr == _FAT_ATTR_LFN:
+                chars = entry[1:11] + entry[14:26] + entry[28:32]
+                long_parts.append((entry[13], chars.decode("utf-16-le", "replace")))
+            elif attr & _FAT_ATTR_VOLUME:
+                long_parts = []
+            else:
+                short = entry[:11]
+                if short[0] == 0x05:
+                    short = b"\xe5" + short[1:]
+                name = None
+                if long_parts and all(s == _fat_checksum(entry[:11]) for s, _ in long_parts):
+                    name = "".join(part for _, part in reversed(long_parts))
+                    name = name.split("\x00", 1)[0]
+                long_parts = []
+                if not name:
+                    base = short[:8].rstrip(b" ").decode("cp437")
+                    ext = short[8:].rstrip(b" ").decode("cp437")
+                    base = base.lower() if entry[12] & 0x08 else base
+                    ext = ext.lower() if entry[12] & 0x10 else ext
+                    name = f"{base}.{ext}" if ext else base
+                if name in (".", "..") or "/" in name:
+                    continue
+                hi, = struct.unpack_from("<H", entry, 20)
+                lo, = struct.unpack_from("<H", entry, 26)
+                cluster = (hi << 16 if self._bits == 32 else 0) | lo
+                if attr & _FAT_ATTR_DIR:
+                    entries[name] = _Node("dir", 0, (cluster, 0), None)
+                else:
+                    size, = struct.unpack_from("<I", entry, 28)
+                    entries[name] = _Node("file", size, cluster, None)
+        return entries
+
+
+class _ExFat(_Clustered):
+    """exFAT, including files stored without a FAT chain."""
+
+    def __init__(self, vol: _Volume) -> None:
+        self._vol = vol
+        boot = vol.read(0, 512)
+        if boot[3:11] != b"EXFAT   ":
+            raise vol.corrupt("no exFAT boot sector")
+        fat_offset, fat_length, heap, self._count, root = struct.unpack_from("<IIIII", boot, 80)
+        bps_shift, spc_shift = boot[108], boot[109]
+        if not 9 <= bps_shift <= 12 or bps_shift + spc_shift > 25:
+            raise vol.corrupt("bad exFAT boot sector")
+        bps = 1 << bps_shift
+        self._cluster = bps << spc_shift
+        self._heap = heap * bps
+        self._fat = vol.read(fat_offset * bps, min(fat_length * bps, (self._count + 2) * 4))
+        # (first cluster, data length, valid data length, contiguous)
+        self.root: tuple[int, int, int, bool] = (root, -1, -1, False)
+
+    def _next(self, cluster: int) -> int | None:
+        try:
+            value, = struct.unpack_from("<I", self._fat, cluster * 4)
+        except struct.error:
+            raise self._vol.corrupt("cluster past the FAT") from None
+        if value == 0xFFFFFFF7:
+            raise self._vol.corrupt("bad cluster in a chain")
+        return None if value == 0xFFFFFFFF else value
+
+    def _data(self, ref: tuple[int, int, int, bool]) -> Iterator[bytes]:
+        first, length, valid, contiguous = ref
+        if first == 0 or length == 0:
+            return iter(())
+        if length < 0:  # the root directory: its chain says how long it is
+            chain = self._chain(first)
+            return self._clusters(chain, len(chain) * self._cluster)
+        if contiguous:
+            chain = list(range(first, first + -(-length // self._cluster)))
+        else:
+            chain = self._chain(first)
+
+        def content() -> Iterator[bytes]:
+            yield from self._clusters(chain, valid)
+            yield from _zeros(length - valid)
+
+        return content()
+
+    def read(self, node: _Node) -> Iterator[bytes]:
+        return self._data(node.ref)
+
+    def list(self, ref: tuple[int, int, int, bool]) -> dict[str, _Node]:
+        data = b"".join(self._data(ref))
+        entries: dict[str, _Node] = {}
+        pos = 0
+        while pos + 32 <= len(data):
+            kind, secondary = data[pos], data[pos + 1]
+            if kind == 0:
+                break
+            if kind != 0x85:
+                pos += 32
+                continue
+            group = data[pos:pos + 32 * (1 + secondary)]
+            pos += 32 * (1 + secondary)
+            if secondary < 2 or len(group) < 32 * (1 + secondary) or group[32] != 0xC0:
+                raise self._vol.corrupt("bad exFAT directory entry set")
+            attrs, = struct.unpack_from("<H", group, 4)
+            flags, name_length = group[33], group[35]
+            valid, = struct.unpack_from("<Q", group, 40)
+            first, length = struct.unpack_from("<IQ", group, 52)
+            name = "".join(group[i + 2:i + 32].decode("utf-16-le", "replace")
+                           for i in range(64, len(group), 32) if group[i] == 0xC1)
+            name = name[:name_length]
+            if not name or "/" in name:
+                continue
+            node_ref = (first, length, min(valid, length), bool(flags & 0x02))
+            if attrs & 0x10:
+                entries[name] = _Node("dir", 0, node_ref, None)
+            else:
+                entries[name] = _Node("file", length, node_ref, None)
+        return entries
+
+
+# ---- ISO 9660 and UDF ----
+
+_ISO_DESCRIPTORS = 32768
+_ISO_SECTOR = 2048
+_JOLIET_ESCAPES = (b"%/@", b"%/C", b"%/E")
+_ISO_DIR, _ISO_ASSOCIATED, _ISO_MULTI_EXTENT = 0x02, 0x04, 0x80
+
+
+def _recognition(vol: _Volume) -> tuple[bool, bool]:
+    """Whether the volume descriptors at 32 KiB announce ISO 9660 and UDF."""
+    iso = udf = False
+    for step in (2048, 4096):  # one descriptor per sector
+        for index in range(64):
+            at = _ISO_DESCRIPTORS + index * step
+            if at + step > vol.size:
+                break
+            desc = vol.read(at, 6)
+            if desc[1:6] == b"CD001":
+                iso = iso or desc[0] == 1
+            elif desc[1:6] in (b"NSR02", b"NSR03"):
+                udf = True
+            elif desc[1:6] not in (b"BEA01", b"TEA01", b"BOOT2", b"CDW02"):
+                break
+        if iso or udf:
+            break
+    return iso, udf
+
+
+class _Iso:
+    """ISO 9660 with Rock Ridge or Joliet names, and multi-extent files."""
+
+    def __init__(self, vol: _Volume) -> None:
+        self._vol = vol
+        primary = joliet = None
+        for index in range(16, 80):
+            if (index + 1) * _ISO_SECTOR > vol.size:
+                break
+            desc = vol.read(index * _ISO_SECTOR, _ISO_SECTOR)
+            if desc[1:6] != b"CD001" or desc[0] == 255:
+                break
+            if desc[0] == 1 and primary is None:
+                primary = desc
+            elif desc[0] == 2 and desc[88:91] in _JOLIET_ESCAPES and joliet is None:
+                joliet = desc
+        if primary is None:
+            raise vol.corrupt("no primary volume descriptor")
+        self._block, = struct.unpack_from("<H", primary, 128)
+        if self._block not in (512, 1024, 2048):
+            raise vol.corrupt("bad logical block size")
+        self._rock = False
+        self._skip = 0
+        self.root = self._root(primary)
+        # Rock Ridge starts with an SP entry in the root's "." record
+        dot = next(self._records(self.root), b"")
+        use = dot[34:] if len(dot) > 34 else b""
+        if use[:2] == b"SP" and use[4:6] == b"\xbe\xef":
+            self._rock, self._skip = True, use[6]
+        elif joliet is not None:
+            self.root = self._root(joliet)
+        self._joliet = not self._rock and joliet is not None
+
+    @staticmethod
+    def _root(desc: bytes) -> tuple[int, int]:
+        lba, = struct.unpack_from("<I", desc, 156 + 2)
+        size, = struct.unpack_from("<I", desc, 156 + 10)
+        return lba + desc[156 + 1], size
+
+    def _records(self, ref: tuple[int, int]) -> Iterator[bytes]:
+        lba, size = ref
+        data = self._vol.read(lba * self._block, size)
+        pos = 0
+        while pos < len(data):
+            length = data[pos]
+            if length == 0:  # records do not cross sectors: the rest of this one is padding
+                pos = (pos // _ISO_SECTOR + 1) * _ISO_SECTOR
+                continue
+            if length < 34 or pos + length > len(data) or 33 + data[pos + 32] > length:
+                raise self._vol.corrupt("bad directory record")
+            yield data[pos:pos + length]
+            pos += length
+
+    def _susp(self, record: bytes) -> Iterator[tuple[bytes, bytes]]:
+        """``(signature, entry)`` of a record's System Use entries, across continuations."""
+        name_len = record[32]
+        area = record[33 + name_len + (1 - name_len % 2) + self._skip:]
+        for _ in range(64):
+            pos, following = 0, b""
+            while pos + 4 <= len(area):
+                signature, length = area[pos:pos + 2], area[pos + 2]
+                if length < 4 or pos + length > len(area) or signature == b"ST":
+                    break
+                entry = area[pos:pos + length]
+                if signature == b"CE" and length >= 28:
+                    block, = struct.unpack_from("<I", entry, 4)
+                    offset, = struct.unpack_from("<I", entry, 12)
+                    size, = struct.unpack_from("<I", entry, 20)
+                    following = self._vol.read(block * self._block + offset, size)
+                else:
+                    yield signature, entry
+                pos += length
+            if not following:
+                return
+            area = following
+        raise self._vol.corrupt("continuation area loop")
+
+    def _name(self, ident: bytes, directory: bool) -> str:
+        if self._joliet:
+            name = ident.decode("utf-16-be", "replace")
+        else:
+            name = ident.decode("ascii", "replace").lower()  # as Linux shows plain names
+        if not directory:
+            name = name.rsplit(";", 1)[0]
+            if not self._joliet:
+                name = name.rstrip(".")
+        return name
+
+    @staticmethod
+    def _symlink(entries: list[bytes]) -> str:
+        parts: list[str] = []
+        continued = False
+        for entry in entries:
+            pos = 5
+            while pos + 2 <= len(entry):
+                flags, length = entry[pos], entry[pos + 1]
+                if flags & 0x02:
+                    piece = "."
+                elif flags & 0x04:
+                    piece = ".."
+                elif flags & 0x08:
+                    piece = ""  # the root
+                else:
+                    piece = os.fsdecode(entry[pos + 2:pos + 2 + length])
+                if continued:
+                    parts[-1] += piece
+                else:
+                    parts.append(piece)
+                continued = bool(flags & 0x01)
+                pos += 2 + length
+        return "/" if parts == [""] else "/".join(parts)
+
+    def _entry(self, record: bytes, extents: list[tuple[int, int]]) -> tuple[str, _Node] | None:
+        flags, ident = record[25], record[33:33 + record[32]]
+        if ident in (b"\x00", b"\x01") or flags & _ISO_ASSOCIATED:
+            return None
+        if record[26] or record[27]:
+            raise UnsupportedSourceError(errno.ENOTSUP, "interleaved files are not supported",
+                                         self._vol.source)
+        kind = "dir" if flags & _ISO_DIR else "file"
+        name = self._name(ident, kind == "dir")
+        ref: Any = extents[0] if kind == "dir" else tuple(extents)
+        target = None
+        if self._rock:
+            susp = list(self._susp(record))
+            names = [entry[5:] for signature, entry in susp if signature == b"NM"]
+            if names:
+                name = os.fsdecode(b"".join(names))
+            for signature, entry in susp:
+                if signature == b"RE":
+                    return None  # a relocated directory, listed where its CL entry is
+                if signature == b"CL" and len(entry) >= 12:
+                    lba, = struct.unpack_from("<I", entry, 4)
+                    dot = self._vol.read(lba * self._block, 34)
+                    return name, _Node("dir", 0, (lba, struct.unpack_from("<I", dot, 10)[0]),
+                                       None)
+                if signature == b"PX" and len(entry) >= 8:
+                    fmt = stat.S_IFMT(struct.unpack_from("<I", entry, 4)[0])
+                    kind = ("dir" if fmt == stat.S_IFDIR else "file" if fmt == stat.S_IFREG
+                            else "symlink" if fmt == stat.S_IFLNK else "other")
+            if kind == "symlink":
+                target = self._symlink([entry for signature, entry in susp
+                                        if signature == b"SL"])
+        if kind == "file":
+            return name, _Node("file", sum(size for _, size in extents), ref, None)
+        return name, _Node(kind, 0, ref if kind == "dir" else None, target)
+
+    def read(self, node: _Node) -> Iterator[bytes]:
+        for lba, size in node.ref:
+            offset = lba * self._block
+            for at in range(0, size, _CHUNK):
+                yield self._vol.read(offset + at, min(_CHUNK, size - at))
+
+    def list(self, ref: tuple[int, int]) -> dict[str, _Node]:
+        entries = {}
+        extents: list[tuple[int, int]] = []
+        for record in self._records(ref):
+            lba, = struct.unpack_from("<I", record, 2)
+            size, = struct.unpack_from("<I", record, 10)
+            extents.append((lba + record[1], size))
+            if record[25] & _ISO_MULTI_EXTENT:  # more of this file in the next record
+                continue
+            entry = self._entry(record, extents)
+            extents = []
+            if entry is not None and entry[0] and "/" not in entry[0]:
+                entries[entry[0]] = entry[1]
+        return entries
+
+
+_UDF_AVDP, _UDF_VDP, _UDF_PD, _UDF_LVD, _UDF_TD = 2, 3, 5, 6, 8
+_UDF_FSD, _UDF_FID, _UDF_AED, _UDF_FE, _UDF_EFE = 256, 257, 258, 261, 266
+
+
+def _udf_tag(data: bytes, ident: int, location: int | None = None) -> bool:
+    """Whether *data* starts with a valid descriptor tag of type *ident*."""
+    if len(data) < 16:
+        return False
+    checksum = (sum(data[0:4]) + sum(data[5:16])) & 0xFF
+    return (struct.unpack_from("<H", data, 0)[0] == ident and data[4] == checksum
+            and (location is None or struct.unpack_from("<I", data, 12)[0] == location))
+
+
+class _Udf:
+    """UDF: physical and metadata partitions, short, long and embedded data."""
+
+    def __init__(self, vol: _Volume) -> None:
+        self._vol = vol
+        for sector in (2048, 512, 4096):
+            if vol.size >= 257 * sector and _udf_tag(vol.read(256 * sector, 32), _UDF_AVDP, 256):
+                break
+        else:
+            raise vol.corrupt("no UDF anchor volume descriptor")
+        self._sector = sector
+        length, location = struct.unpack_from("<II", vol.read(256 * sector, 32), 16)
+        starts: dict[int, int] = {}  # partition number: first sector
+        lvd = None
+        for _ in range(64):  # volume descriptor pointers chain sequences
+            following = None
+            for index in range(min(length // sector, 1024)):
+                desc = vol.read((location + index) * sector, min(sector, 512))
+                if _udf_tag(desc, _UDF_TD):
+                    break
+                if _udf_tag(desc, _UDF_PD):
+                    number, = struct.unpack_from("<H", desc, 22)
+                    starts.setdefault(number, struct.unpack_from("<I", desc, 188)[0])
+                elif _udf_tag(desc, _UDF_LVD) and lvd is None:
+                    map_length, = struct.unpack_from("<I", desc, 264)
+                    lvd = vol.read((location + index) * sector, 440 + map_length)
+                elif _udf_tag(desc, _UDF_VDP):
+                    following = struct.unpack_from("<II", desc, 20)
+                    break
+            if following is None:
+                break
+            length, location = following
+        if lvd is None:
+            raise vol.corrupt("no UDF logical volume descriptor")
+        if struct.unpack_from("<I", lvd, 212)[0] != sector:
+            raise UnsupportedSourceError(errno.ENOTSUP, "UDF block size differs from the "
+                                         "sector size", vol.source)
+        # each map: (first sector of the physical partition, metadata runs or None)
+        self._maps: list[tuple[int, list[tuple[int, int, int]] | None]] = []
+        metadata: list[tuple[int, int]] = []  # (map, location of the metadata file)
+        pos = 440
+        for _ in range(struct.unpack_from("<I", lvd, 268)[0]):
+            kind, size = lvd[pos], lvd[pos + 1]
+            if kind == 1 and size == 6:
+                number, = struct.unpack_from("<H", lvd, pos + 4)
+            elif kind == 2 and size == 64:
+                ident = lvd[pos + 5:pos + 28].rstrip(b"\x00").decode("ascii", "replace")
+                if ident != "*UDF Metadata Partition":
+                    what = ident.removeprefix("*UDF ").lower()
+                    raise UnsupportedSourceError(errno.ENOTSUP, f"UDF {what}s are not supported",
+                                                 vol.source)
+                number, = struct.unpack_from("<H", lvd, pos + 38)
+                metadata.append((len(self._maps), struct.unpack_from("<I", lvd, pos + 40)[0]))
+            else:
+                raise vol.corrupt("bad UDF partition map")
+            if number not in starts:
+                raise vol.corrupt(f"no UDF partition {number}")
+            self._maps.append((starts[number], None))
+            pos += size
+        for index, file_location in metadata:
+            # the metadata file lays the metadata partition out over the physical one
+            start = self._maps[index][0]
+            physical = self._maps.index((start, None))
+            runs: list[tuple[int, int, int]] = []
+            logical = 0
+            _, _, ad_type, ads = self._file(physical, file_location)
+            for _, block, size, recorded in self._runs(physical, ad_type, ads):
+                count = size // sector
+                if recorded:
+                    runs.append((logical, block, count))
+                logical += count
+            self._maps[index] = (start, runs)
+        fsd_block, fsd_part = struct.unpack_from("<IH", lvd, 252)
+        fsd = b"".join(self._extent(fsd_part, fsd_block, sector))
+        if not _udf_tag(fsd, _UDF_FSD, fsd_block):
+            raise vol.corrupt("bad UDF file set descriptor")
+        root_block, root_part = struct.unpack_from("<IH", fsd, 404)
+        self.root = (root_part, root_block)
+
+    def _locate(self, part: int, block: int) -> tuple[int, int]:
+        """Image offset of a logical block, and how many blocks follow it contiguously."""
+        if not 0 <= part < len(self._maps):
+            raise self._vol.corrupt("bad UDF partition reference")
+        start, runs = self._maps[part]
+        if runs is None:
+            return (start + block) * self._sector, 1 << 40
+        for logical, physical, count in runs:
+            if logical <= block < logical + count:
+                return (start + physical + block - logical) * self._sector, logical + count - block
+        raise self._vol.corrupt("block past the UDF metadata file")
+
+    def _extent(self, part: int, block: int, length: int) -> Iterator[bytes]:
+        while length > 0:
+            offset, blocks = self._locate(part, block)
+            n = min(length, blocks * self._sector, _CHUNK)
+            yield self._vol.read(offset, n)
+            length -= n
+            block += n // self._sector
+
+    def _file(self, part: int, block: int) -> tuple[int, int, int, bytes]:
+        """``(file type, size, allocation type, allocation descriptors)`` of a file entry."""
+        raw = b"".join(self._extent(part, block, self._sector))
+        if _udf_tag(raw, _UDF_FE, block):
+            l_ea, l_ad = struct.unpack_from("<II", raw, 168)
+            base = 176
+        elif _udf_tag(raw, _UDF_EFE, block):
+            l_ea, l_ad = struct.unpack_from("<II", raw, 208)
+            base = 216
+        else:
+            raise self._vol.corrupt("bad UDF file entry")
+        if struct.unpack_from("<H", raw, 20)[0] != 4:
+            raise UnsupportedSourceError(errno.ENOTSUP, "UDF ICB strategy is not supported",
+                                         self._vol.source)
+        if base + l_ea + l_ad > len(raw):
+            raise self._vol.corrupt("bad UDF file entry")
+        size, = struct.unpack_from("<Q", raw, 56)
+        ad_type = struct.unpack_from("<H", raw, 34)[0] & 7
+        return raw[27], size, ad_type, raw[base + l_ea:base + l_ea + l_ad]
+
+    def _runs(self, part: int, ad_type: int,
+              ads: bytes) -> Iterator[tuple[int, int, int, bool]]:
+        """``(partition, block, length, recorded)`` of each extent, across continuations."""
+        if ad_type == 2:
+            raise UnsupportedSourceError(errno.ENOTSUP, "UDF extended allocation descriptors "
+                                         "are not supported", self._vol.source)
+        if ad_type not in (0, 1):
+            raise self._vol.corrupt("bad UDF allocation descriptor type")
+        step = 8 if ad_type == 0 else 16
+        for _ in range(4096):
+            following = None
+            for pos in range(0, len(ads) - step + 1, step):
+                length, block = struct.unpack_from("<II", ads, pos)
+                ref = part if ad_type == 0 else struct.unpack_from("<H", ads, pos + 8)[0]
+                kind, length = length >> 30, length & 0x3FFFFFFF
+                if length == 0:
+                    break
+                if kind == 3:  # the list goes on in an allocation extent descriptor
+                    aed = b"".join(self._extent(ref, block, self._sector))
+                    if not _udf_tag(aed, _UDF_AED, block):
+                        raise self._vol.corrupt("bad UDF allocation extent")
+                    following = aed[24:24 + struct.unpack_from("<I", aed, 20)[0]]
+                    break
+                yield ref, block, length, kind == 0
+            if following is None:
+                return
+            ads = following
+        raise self._vol.corrupt("UDF allocation extent loop")
+
+    def _content(self, part: int, block: int) -> Iterator[bytes]:
+        _, size, ad_type, ads = self._file(part, block)
+        if ad_type == 3:  # embedded in the file entry
+            yield ads[:size]
+            return
+        pos = 0
+        for ref, start, length, recorded in self._runs(part, ad_type, ads):
+            n = min(length, size - pos)
+            if n <= 0:
+                break
+            yield from self._extent(ref, start, n) if recorded else _zeros(n)
+            pos += n
+        yield from _zeros(size - pos)
+
+    def _name(self, raw: bytes) -> str:
+        if not raw:
+            return ""
+        if raw[0] == 8:
+            return raw[1:].decode("latin-1")
+        if raw[0] == 16:
+            return raw[1:].decode("utf-16-be", "replace")
+        raise self._vol.corrupt("bad UDF name encoding")
+
+    def _symlink(self, data: bytes) -> str:
+        parts: list[str] = []
+        pos = 0
+        while pos + 4 <= len(data):
+            kind, length = data[pos], data[pos + 1]
+            ident = data[pos + 4:pos + 4 + length]
+            pos += 4 + length
+            if kind == 2 or kind == 1 and not length:
+                parts = [""]  # the root
+            elif kind == 3:
+                parts.append("..")
+            elif kind == 4:
+                parts.append(".")
+            elif kind == 5:
+                parts.append(self._name(ident))
+        return "/" if parts == [""] else "/".join(parts)
+
+    def _node(self, part: int, block: int) -> _Node:
+        file_type, size, _, _ = self._file(part, block)
+        if file_type == 4:
+            return _Node("dir", 0, (part, block), None)
+        if file_type == 5:
+            return _Node("file", size, (part, block), None)
+        if file_type == 12:
+            return _Node("symlink", 0, None, self._symlink(b"".join(self._content(part, block))))
+        return _Node("other", 0, None, None)
+
+    def read(self, node: _Node) -> Iterator[bytes]:
+        return self._content(*node.ref)
+
+    def list(self, ref: tuple[int, int]) -> dict[str, _Node]:
+        data = b"".join(self._content(*ref))
+        entries = {}
+        pos = 0
+        while pos + 38 <= len(data):
+            if not _udf_tag(data[pos:pos + 16], _UDF_FID):
+                raise self._vol.corrupt("bad UDF file identifier")
+            characteristics, name_len = data[pos + 18], data[pos + 19]
+            block, part = struct.unpack_from("<IH", data, pos + 24)
+            start = pos + 38 + struct.unpack_from("<H", data, pos + 36)[0]
+            name = self._name(data[start:start + name_len])
+            pos = start + name_len
+            pos += -pos % 4
+            if characteristics & 0x0C or not name or "/" in name:  # deleted or the parent
+                continue
+            entries[name] = self._node(part, block)
+        return entries
+
+
+# ---- images, partitions and trees ----
+
+def split_disk_ref(spec: str) -> tuple[str, int | None, str]:
+    """``(path, partition, directory)`` of ``PATH[@N][:DIR]``; *path* exists."""
+    def is_image(path: str) -> bool:
+        return os.path.exists(path) and not os.path.isdir(path)
+
+    if is_image(spec):
+        return spec, None, ""
+    for index, char in enumerate(spec):
+        if char in "@:" and is_image(spec[:index]):
+            path, rest = spec[:index], spec[index:]
+            break
+    else:
+        raise SourceNotFoundError(errno.ENOENT, "no such disk image", spec)
+    partition = None
+    if rest.startswith("@"):
+        number, colon, directory = rest[1:].partition(":")
+        if not number.isdigit() or int(number) < 1:
+            raise ValueError(f"{spec}: partition must be a number from 1, as in PATH@1")
+        partition, rest = int(number), colon + directory
+    return path, partition, rest[1:].strip("/")
+
+
+def _partitions(fd: int, source: str) -> dict[int, tuple[int, int]]:
+    """``{number: (offset, size)}`` from an MBR (primary partitions) or GPT."""
+    mbr = os.pread(fd, 512, 0)
+    if len(mbr) < 512 or mbr[510:512] != b"\x55\xaa":
+        return {}
+    primary = {i + 1: struct.unpack_from("<BxxxII", mbr, 446 + 16 * i) for i in range(4)}
+    if all(kind != 0xEE for kind, _, _ in primary.values()):
+        return {n: (start * 512, count * 512)
+                for n, (kind, start, count) in primary.items() if kind and count}
+    for sector in (512, 4096):
+        header = os.pread(fd, 92, sector)
+        if header[:8] != b"EFI PART":
+            continue
+        table_lba, count, entry_size = struct.unpack_from("<QII", header, 72)
+        if entry_size < 56 or count > 4096:
+            break
+        table = os.pread(fd, count * entry_size, table_lba * sector)
+        parts = {}
+        for i in range(len(table) // entry_size):
+            entry = table[i * entry_size:(i + 1) * entry_size]
+            first, last = struct.unpack_from("<QQ", entry, 32)
+            if any(entry[:16]) and last >= first:
+                parts[i + 1] = (first * sector, (last - first + 1) * sector)
+        return parts
+    raise ArchiveError(errno.EINVAL, "protective MBR without a GPT", source)
+
+
+def _detect(vol: _Volume) -> _Ext | _Fat | _ExFat | _Iso | _Udf | None:
+    iso, udf = _recognition(vol) if vol.size > _ISO_DESCRIPTORS else (False, False)
+    if udf:
+        try:
+            return _Udf(vol)
+        except UnsupportedSourceError:
+            if not iso:
+                raise  # else read the ISO 9660 tree of a bridge disc instead
+    if iso:
+        return _Iso(vol)
+    if vol.size >= 2048 and vol.read(1080, 2) == struct.pack("<H", _EXT_MAGIC):
+        return _Ext(vol)
+    if vol.size >= 512:
+        boot = vol.read(0, 512)
+        if boot[3:11] == b"EXFAT   ":
+            return _ExFat(vol)
+        if _fat_bpb(boot) is not None:
+            return _Fat(vol)
+    return None
+
+
+class _Image:
+    """An open disk image and the file system a ``PATH[@N][:DIR]`` names in it."""
+
+    def __init__(self, spec: str) -> None:
+        path, partition, self.directory = split_disk_ref(spec)
+        try:
+            self._fd = os.open(path, os.O_RDONLY | getattr(os, "O_BINARY", 0))
+        except PermissionError as exc:
+            raise SourcePermissionError(errno.EACCES, "cannot read", path) from exc
+        try:
+            size = os.lseek(self._fd, 0, os.SEEK_END)
+            if partition is None:
+                self.fs = _detect(_Volume(self._fd, 0, size, path))
+                if self.fs is None and _partitions(self._fd, path):
+                    raise ValueError(f"{path} is a partitioned disk image; pick a partition "
+                                     f"as {path}@N")
+            else:
+                parts = _partitions(self._fd, path)
+                if partition not in parts:
+                    raise SourceNotFoundError(errno.ENOENT, f"no partition {partition}", path)
+                offset, length = parts[partition]
+                self.fs = _detect(_Volume(self._fd, offset, min(length, size - offset),
+                                          f"{path}@{partition}"))
+            if self.fs is None:
+                raise UnsupportedSourceError(errno.ENOTSUP, "no ext2/3/4, FAT, exFAT, ISO 9660 "
+                                             "or UDF file system found", spec)
+        except BaseException:
+            os.close(self._fd)
+            raise
+        self.spec = spec
+
+    def close(self) -> None:
+        os.close(self._fd)
+
+    def __enter__(self) -> _Image:
+        return self
+
+    def __exit__(self, *exc: object) -> None:
+        self.close()
+
+    def tree(self, ignore: list[str] | None) -> dict[str, _Node]:
+        """Every entry under the named directory, by relative path."""
+        fs = self.fs
+        ref = fs.root
+        for part in self.directory.split("/") if self.directory else ():
+            node = fs.list(ref).get(part)
+            if node is None or node.kind != "dir":
+                raise SourceNotFoundError(errno.ENOENT, "no such directory in the image",
+                                          self.spec)
+            ref = node.ref
+        exclude = make_exclude(ignore, None, self.spec, self.spec)
+        tree: dict[str, _Node] = {}
+        seen = {ref}
+        pending = [("", ref)]
+        while pending:
+            prefix, ref = pending.pop()
+            for name, node in fs.list(ref).items():
+                path = prefix + name
+                if exclude is not None and exclude(path):
+                    continue
+                tree[path] = node
+                if node.kind == "dir":
+                    if node.ref in seen:
+                        raise ArchiveError(errno.ELOOP, "directory loop", self.spec)
+                    seen.add(node.ref)
+                    pending.append((path + "/", node.ref))
+        return tree
+
+    def read(self, node: _Node) -> Iterator[bytes]:
+        return self.fs.read(node)
+
+
+def _same_content(a: Iterator[bytes], b: Iterator[bytes]) -> bool:
+    """Whether two chunk streams hold the same bytes, whatever their chunking."""
+    view_a = view_b = memoryview(b"")
+    while True:
+        while not view_a:
+            chunk = next(a, None)
+            if chunk is None:
+                break
+            view_a = memoryview(chunk)
+        while not view_b:
+            chunk = next(b, None)
+            if chunk is None:
+                break
+            view_b = memoryview(chunk)
+        if not view_a or not view_b:
+            return not view_a and not view_b
+        n = min(len(view_a), len(view_b))
+        if view_a[:n] != view_b[:n]:
+            return False
+        view_a, view_b = view_a[n:], view_b[n:]
+
+
+def _diff_nodes(a: _Node, b: _Node) -> DiffReason | None:
+    """Difference short of content: type, symlink target or size."""
+    if a.kind != b.kind:
+        return DiffReason.TYPE_MISMATCH
+    if a.kind == "symlink" and a.target != b.target:
+        return DiffReason.CONTENT_MISMATCH
+    if a.size != b.size:
+        return DiffReason.SIZE_MISMATCH
+    return None
+
+
+def _result(tree_a: dict[str, _Node], tree_b: dict[str, _Node],
+            diff: dict[str, DiffReason]) -> DirResult:
+    # Directories count through what is in them, as for container images
+    only_left = {p for p in tree_a.keys() - tree_b.keys() if tree_a[p].kind != "dir"}
+    only_right = {p for p in tree_b.keys() - tree_a.keys() if tree_b[p].kind != "dir"}
+    return DirResult(equal=not (diff or only_left or only_right), diff=diff,
+                     only_left=only_left, only_right=only_right)
+
+
+def compare_disk_images(
+    image_a: str,
+    image_b: str,
+    *,
+    ignore: list[str] | None = None,
+) -> DirResult:
+    """Compare directories inside two disk images without mounting them.
+
+    Each side is ``PATH[@N][:DIR]``: an image file or block device, an
+    optional partition number of its MBR or GPT, and an optional
+    directory in the file system on it (the root if left out), e.g.
+    ``sdcard.img@2:/etc``. ext2, ext3, ext4 (block maps, extents,
+    uninitialized extents and small inline files), FAT12/16/32 with long
+    names, exFAT, ISO 9660 (Rock Ridge names and symlinks, else Joliet
+    names) and UDF (physical and metadata partitions) are read directly
+    from the image, read-only, so no root or loop device is needed. A
+    UDF bridge disc is read through its UDF tree. Files are compared by size and then
+    content, symlinks by target; permissions, owners and times are not
+    compared.
+
+    :param image_a: Left side, ``PATH[@N][:DIR]``.
+    :param image_b: Right side.
+    :param ignore: Glob patterns to exclude (matched per path component).
+    :raises SourceNotFoundError: If an image, partition or directory does not exist.
+    :raises UnsupportedSourceError: If there is no supported file system, or
+        it uses a feature the readers lack (encryption, compression, meta_bg,
+        interleaved ISO files, UDF virtual or sparable partitions).
+    :raises ArchiveError: If the file system structures are corrupt.
+    :raises ValueError: If a partitioned image is given without a partition.
+    """
+    with _Image(image_a) as left, _Image(image_b) as right:
+        tree_a, tree_b = left.tree(ignore), right.tree(ignore)
+        diff: dict[str, DiffReason] = {}
+        for path in sorted(tree_a.keys() & tree_b.keys()):
+            a, b = tree_a[path], tree_b[path]
+            reason = _diff_nodes(a, b)
+            if reason is None and a.kind == "file" and not _same_content(left.read(a),
+                                                                         right.read(b)):
+                reason = DiffReason.CONTENT_MISMATCH
+            if reason is not None:
+                diff[path] = reason
+    return _result(tree_a, tree_b, diff)
+
+
+def _kind(st: os.stat_result) -> str:
+    if stat.S_ISREG(st.st_mode):
+        return "file"
+    if stat.S_ISDIR(st.st_mode):
+        return "dir"
+    return "symlink" if stat.S_ISLNK(st.st_mode) else "other"
+
+
+def _file_chunks(path: str) -> Iterator[bytes]:
+    with open(path, "rb") as f:
+        while chunk := f.read(_CHUNK):
+            yield chunk
+
+
+def compare_disk_image_dir(
+    image: str,
+    directory: str,
+    *,
+    follow_symlinks: bool = False,
+    ignore: list[str] | None = None,
+) -> DirResult:
+    """Compare a directory inside a disk image with a directory on disk.
+
+    The image side is read as by :func:`compare_disk_images`, e.g. a
+    firmware's root file system against the tree it was built from. The
+    image is the left side and the directory the right. A local file
+    that cannot be read is reported as ``READ_ERROR``.
+
+    :param image: Image side, ``PATH[@N][:DIR]``.
+    :param directory: Directory path.
+    :param follow_symlinks: Follow symbolic links in the directory (the
+        image stores links as links, so off by default).
+    :param ignore: Glob patterns to exclude (matched per path component),
+        on both sides.
+    :raises SourceNotFoundError: If the image, partition or directory does not exist.
+    :raises UnsupportedSourceError: If there is no supported file system.
+    :raises ArchiveError: If the file system structures are corrupt.
+    :raises SourcePermissionError: If a local directory cannot be listed.
+    """
+    validate_path(directory, "directory")
+    exclude = make_exclude(ignore, None, directory, directory, follow_symlinks)
+    entries, errors = walk_tree(directory, follow_symlinks, exclude)
+    if errors:
+        raise SourcePermissionError(errno.EACCES, "cannot read",
+                                    os.path.join(directory, min(errors)))
+    with _Image(image) as source:
+        tree = source.tree(ignore)
+        local: dict[str, _Node] = {}
+        for path, st in entries.items():
+            kind = _kind(st)
+            target = (os.readlink(os.path.join(directory, path))
+                      if kind == "symlink" and path in tree else None)
+            local[path] = _Node(kind, st.st_size if kind == "file" else 0, None, target)
+        diff: dict[str, DiffReason] = {}
+        for path in sorted(tree.keys() & local.keys()):
+            reason = _diff_nodes(tree[path], local[path])
+            if reason is None and local[path].kind == "file":
+                try:
+                    same = _same_content(source.read(tree[path]),
+                                         _file_chunks(os.path.join(directory, path)))
+                except KomparuError:
+                    raise  # the image, not the local file
+                except OSError:
+                    reason = DiffReason.READ_ERROR
+                else:
+                    reason = None if same else DiffReason.CONTENT_MISMATCH
+            if reason is not None:
+                diff[path] = reason
+    return _result(tree, local, diff)
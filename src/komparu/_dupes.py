@@ -0,0 +1,170 @@
+"""Duplicate files across one or more directory trees."""
+
+from __future__ import annotations
+
+import contextlib
+import errno
+import os
+import shutil
+import stat
+import sys
+
+from komparu._api import compare
+from komparu._filter import PathFilter, make_exclude
+from komparu._helpers import walk_tree
+from komparu._prefilter import group_identical
+from komparu._types import DedupResult, DuplicateGroup, SourcePermissionError
+from komparu._validate import validate_max_workers, validate_path
+
+
+def find_duplicates(
+    directories: list[str],
+    *,
+    min_size: int = 1,
+    hard_links: bool = False,
+    follow_symlinks: bool = True,
+    ignore: list[str] | None = None,
+    path_filter: PathFilter | None = None,
+    max_workers: int = 0,
+) -> list[DuplicateGroup]:
+    """Find groups of byte-identical regular files.
+
+    Candidates go through the same stages as :func:`komparu.compare_many`:
+    size, a hash of the first and last 4 KiB, then the full content, so
+    files that differ are mostly told apart without being read.
+
+    :param directories: Trees to search; duplicates across trees are found too.
+    :param min_size: Skip files smaller than this many bytes (the default
+        leaves out empty files).
+    :param hard_links: Report paths that are the same inode (hard links,
+        or symlinks when following them) as duplicates of each other;
+        by default only the first path of an inode is kept.
+    :param follow_symlinks: Follow symbolic links during traversal.
+    :param ignore: Glob patterns to exclude (matched per path component).
+    :param path_filter: PathFilter deciding per entry whether it is considered.
+    :param max_workers: Thread pool size (0=auto, 1=sequential).
+    :returns: Groups of two or more paths (each *directory* joined with the
+        relative path), in traversal order.
+    :raises SourcePermissionError: If a directory cannot be listed.
+    """
+    if not directories:
+        raise ValueError("directories cannot be empty")
+    for directory in directories:
+        validate_path(directory, "directory")
+    if min_size < 0:
+        raise ValueError("min_size must be non-negative")
+    validate_max_workers(max_workers)
+
+    paths: list[str] = []
+    sizes: dict[str, int] = {}
+    seen: set[tuple[int, int]] = set()
+    for directory in directories:
+        exclude = make_exclude(ignore, path_filter, directory, directory, follow_symlinks)
+        entries, errors = walk_tree(directory, follow_symlinks, exclude)
+        if errors:
+            raise SourcePermissionError(errno.EACCES, "cannot read",
+                                        os.path.join(directory, min(errors)))
+        for rel in sorted(entries):
+            st = entries[rel]
+            if not stat.S_ISREG(st.st_mode) or st.st_size < min_size:
+                continue
+            if not hard_links:
+                if (st.st_dev, st.st_ino) in seen:
+                    continue
+                seen.add((st.st_dev, st.st_ino))
+            path = os.path.join(directory, rel)
+            if path in sizes:  # the same directory given twice
+                continue
+            paths.append(path)
+            sizes[path] = st.st_size
+
+    return [DuplicateGroup(size=sizes[group[0]], paths=group)
+            for group in group_identical(paths, max_workers=max_workers) if len(group) > 1]
+
+
+DEDUP_METHODS = ("hardlink", "reflink")
+
+# Linux ioctl _IOW(0x94, 9, int): share the source's extents with the target
+_FICLONE = 0x40049409
+_NO_REFLINK = {errno.EOPNOTSUPP, errno.ENOTSUP, errno.EXDEV, errno.EINVAL, errno.ENOTTY}
+
+
+def _reflink(kept: str, path: str, temp: str) -> None:
+    import fcntl
+
+    with open(kept, "rb") as src, open(temp, "wb") as dst:
+        fcntl.ioctl(dst.fileno(), _FICLONE, src.fileno())
+    # A clone is a separate file: keep the replaced one's metadata
+    st = os.stat(path)
+    shutil.copystat(path, temp)
+    with contextlib.suppress(PermissionError):
+        os.chown(temp, st.st_uid, st.st_gid)
+
+
+def deduplicate(
+    groups: list[DuplicateGroup],
+    *,
+    method: str = "hardlink",
+    dry_run: bool = False,
+) -> DedupResult:
+    """Replace duplicate files by hard links to, or reflinks of, one copy.
+
+    The first path of each group is kept and every other path replaced,
+    atomically, through a temporary name in its directory. Each file is
+    compared with the kept copy again right before it is replaced, so a
+    file changed since :func:`find_duplicates` ran is skipped rather than
+    lost.
+
+    Hard links make the paths one file, sharing permissions, owner and
+    timestamps from then on, and only work within a file system.
+    Reflinks (Linux, on Btrfs, XFS and other file systems with shared
+    extents) keep separate files with their own metadata that only share
+    storage until one is written to.
+
+    :param groups: Groups from :func:`find_duplicates`.
+    :param method: ``"hardlink"`` or ``"reflink"``.
+    :param dry_run: Only report what would be replaced.
+    :returns: The replaced files, the skipped ones and the bytes saved.
+    :raises ValueError: If *method* is unknown, or reflinks are asked for
+        outside Linux.
+    """
+    if method not in DEDUP_METHODS:
+        raise ValueError(f"unknown dedup method: {method} (expected {', '.join(DEDUP_METHODS)})")
+    if method == "reflink" and not sys.platform.startswith("linux"):
+        raise ValueError("reflinks are only supported on Linux")
+
+    linked: list[tuple[str, str]] = []
+    skipped: dict[str, str] = {}
+    saved = 0
+    for group in groups:
+        kept = group.paths[0]
+        kept_stat = os.stat(kept)
+        for path in group.paths[1:]:
+            st = os.stat(path)
+            if (st.st_dev, st.st_ino) == (kept_stat.st_dev, kept_stat.st_ino):
+                continue
+            if method == "hardlink" and st.st_dev != kept_stat.st_dev:
+                skipped[path] = "on another file system"
+                continue
+            if not dry_run:
+                if not compare(kept, path, quick_check=False):
+                    skipped[path] = "changed since it was found"
+                    continue
+                directory, name = os.path.split(path)
+                temp = os.path.join(directory, f".{name}.komparu-{os.getpid()}")
+                try:
+                    if method == "hardlink":
+                        os.link(kept, temp)
+                    else:
+                        _reflink(kept, path, temp)
+                    os.replace(temp, path)
+                except OSError as exc:
+                    with contextlib.suppress(FileNotFoundError):
+                        os.unlink(temp)
+                    if method == "reflink" and exc.errno in _NO_REFLINK:
+                        skipped[path] = "file system does not support reflinks"
+                        continue
+                    raise
+            linked.append((path, kept))
+            saved += st.st_size
+    return DedupResult(linked=linked, skipped=skipped, bytes_saved=saved)
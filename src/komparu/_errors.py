@@ -0,0 +1,80 @@
+"""Translate C core failures into the komparu exception hierarchy."""
+
+from __future__ import annotations
+
+import errno
+from collections.abc import Iterator
+from contextlib import contextmanager
+from typing import TYPE_CHECKING
+
+from komparu._types import (
+    KomparuError,
+    SourceNotFoundError,
+    SourceReadError,
+    SourcePermissionError,
+    SourceVanishedError,
+    UnsupportedSourceError,
+    ArchiveError,
+    ArchiveBombError,
+    ComparisonTimeoutError,
+    ComparisonCancelledError,
+    QuotaReachedError,
+)
+
+if TYPE_CHECKING:
+    from komparu._cancel import CancelToken
+
+# The C core reports a truncated source as ESTALE (defined as 116 where
+# the platform lacks it, see compat.h).
+_ESTALE = getattr(errno, "ESTALE", 116)
+
+_BY_ERRNO: dict[int, type[OSError]] = {
+    errno.ENOENT: SourceNotFoundError,
+    errno.ENOTDIR: SourceNotFoundError,
+    errno.EACCES: SourcePermissionError,
+    errno.EPERM: SourcePermissionError,
+    errno.ETIMEDOUT: ComparisonTimeoutError,
+    errno.ENOTSUP: UnsupportedSourceError,
+    errno.EOPNOTSUPP: UnsupportedSourceError,
+    _ESTALE: SourceVanishedError,
+}
+
+
+def translate_error(exc: OSError, *, archive: bool = False) -> Exception:
+    """Map an OSError raised by the C core to its komparu exception.
+
+    The C core sets ``errno`` to classify the failure; ``strerror`` and
+    ``filename`` are carried over unchanged.
+
+    :param exc: Exception raised by a ``komparu._core`` call.
+    :param archive: The call compared archives (unclassified errors become
+        ArchiveError instead of SourceReadError).
+    """
+    if exc.errno == errno.ECANCELED:
+        return ComparisonCancelledError("comparison cancelled")
+    if archive and exc.errno == errno.EFBIG:
+        cls: type[OSError] = ArchiveBombError
+    else:
+        cls = _BY_ERRNO.get(exc.errno, ArchiveError if archive else SourceReadError)
+    if exc.errno is None:
+        return cls(*exc.args)
+    return cls(exc.errno, exc.strerror, exc.filename)
+
+
+@contextmanager
+def error_scope(cancel: CancelToken | None = None, *, archive: bool = False) -> Iterator[None]:
+    """Re-raise C core OSErrors as komparu exceptions.
+
+    Wrap only the C calls, never user callbacks: an OSError raised by a
+    visitor must reach the caller untouched.
+    """
+    try:
+        yield
+    except KomparuError:
+        raise
+    except OSError as e:
+        if cancel is not None and cancel.quota_reached:
+            raise QuotaReachedError(f"quota reached: {cancel.bytes_read} bytes read") from e
+        if cancel is not None and cancel.cancelled:
+            raise ComparisonCancelledError("comparison cancelled") from e
+        raise translate_error(e, archive=archive) from e
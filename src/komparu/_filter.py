@@ -0,0 +1,236 @@
+"""Pluggable path filters for directory comparison."""
+
+from __future__ import annotations
+
+import os
+import re
+import stat
+import sys
+from collections.abc import Callable, Iterable
+
+from komparu._helpers import _path_matches_ignore, stat_entry
+from komparu._types import PATH_ERRORS
+
+
+class PathFilter:
+    """Decide which entries a directory comparison reports.
+
+    Subclass and override :meth:`include`, then pass an instance as
+    ``path_filter=`` to :func:`komparu.compare_dir`, :func:`komparu.iter_dir`
+    or their async counterparts. It is asked once per entry, together with
+    ``ignore=`` patterns (an entry must pass both), and can base the
+    decision on either side's metadata — extension, size, age, owner.
+
+    Filters run in the calling thread (sync) or on the event loop (async).
+    Like ``ignore=``, they select what is reported: the C engine has
+    already walked and compared the entry.
+    """
+
+    def include(
+        self,
+        path: str,
+        stat_a: os.stat_result | None,
+        stat_b: os.stat_result | None,
+    ) -> bool:
+        """Return False to drop *path* from the result.
+
+        :param path: Relative path of the entry.
+        :param stat_a: Metadata in the first directory, None if absent there.
+        :param stat_b: Metadata in the second directory, None if absent there.
+        """
+        return True
+
+
+def _glob_regex(pattern: str) -> str:
+    """Regex of an rsync wildcard pattern: ``*`` and ``?`` stop at ``/``, ``**`` does not."""
+    out = []
+    i = 0
+    while i < len(pattern):
+        c = pattern[i]
+        if c == "\\" and i + 1 < len(pattern):
+            out.append(re.escape(pattern[i + 1]))
+            i += 2
+            continue
+        if pattern.startswith("**", i):
+            out.append(".*")
+            i += 2
+            continue
+        if c == "*":
+            out.append("[^/]*")
+        elif c == "?":
+            out.append("[^/]")
+        elif c == "[" and "]" in pattern[i + 2:]:
+            end = pattern.index("]", i + 2)
+            body = pattern[i + 1:end]
+            if body[0] in "!^":
+                body = "^" + body[1:]
+            body = body.replace("\\", "\\\\").replace("[", "\\[")
+            out.append(f"[{body}]")
+            i = end + 1
+            continue
+        else:
+            out.append(re.escape(c))
+        i += 1
+    return "".join(out)
+
+
+class _Rule:
+    __slots__ = ("include", "dir_only", "regex", "basename")
+
+    def __init__(self, include: bool, pattern: str) -> None:
+        self.include = include
+        self.dir_only = pattern.endswith("/")
+        pattern = pattern.rstrip("/") if self.dir_only else pattern
+        anchored = pattern.startswith("/")
+        pattern = pattern.lstrip("/")
+        tree = pattern.endswith("/***")  # the directory and everything in it
+        if tree:
+            pattern = pattern[:-4]
+        body = _glob_regex(pattern) + ("(?:/.*)?" if tree else "")
+        # Without a slash or ** a pattern names the last component; with them, the
+        # whole path, anywhere below the root unless anchored
+        self.basename = not anchored and "/" not in pattern and "**" not in pattern and not tree
+        self.regex = re.compile(body if anchored or self.basename else f"(?:.*/)?{body}",
+                                re.DOTALL)
+
+    def matches(self, path: str, is_dir: bool) -> bool:
+        if self.dir_only and not is_dir:
+            return False
+        return self.regex.fullmatch(path.rsplit("/", 1)[-1] if self.basename else path) is not None
+
+
+class RsyncFilter(PathFilter):
+    """Include and exclude entries by rsync filter rules.
+
+    Each rule is ``+ PATTERN`` (include) or ``- PATTERN`` (exclude); a bare
+    pattern takes *default*, and ``!`` clears the rules before it. The first
+    rule matching an entry decides, and an entry no rule matches is
+    included. Patterns follow rsync: ``*`` and ``?`` match within a path
+    component and ``**`` across them, a trailing ``/`` matches only
+    directories, a leading ``/`` anchors at the compared roots, a pattern
+    without a ``/`` matches the last component at any depth, and
+    ``dir/***`` matches ``dir`` and everything in it. As in rsync, an
+    entry is excluded when a directory it is in is.
+
+    :param rules: Rule lines; blank lines and ``#`` or ``;`` comments are
+        skipped.
+    :param default: ``"+"`` or ``"-"``, the kind of a rule without a prefix.
+    """
+
+    def __init__(self, rules: Iterable[str] = (), *, default: str = "-") -> None:
+        self.rules: list[_Rule] = []
+        self._add(rules, default)
+
+    def _add(self, lines: Iterable[str], default: str) -> None:
+        if default not in ("+", "-"):
+            raise ValueError(f"default must be '+' or '-', got {default!r}")
+        for line in lines:
+            line = line.rstrip("\r\n")
+            if not line or line.startswith(("#", ";")):
+                continue
+            if line.strip() == "!":
+                self.rules.clear()
+                continue
+            kind, pattern = (line[0], line[2:]) if line[:2] in ("+ ", "- ") else (default, line)
+            if not pattern:
+                raise ValueError(f"empty pattern in rule {line!r}")
+            self.rules.append(_Rule(kind == "+", pattern))
+
+    @classmethod
+    def from_files(cls, include_from: Iterable[str] = (),
+                   exclude_from: Iterable[str] = ()) -> RsyncFilter:
+        """Rules read from rsync ``--include-from`` and ``--exclude-from`` files.
+
+        The include files come first, as with rsync's ``--include-from``
+        given before ``--exclude-from``; ``-`` reads standard input.
+        """
+        rules = cls()
+        for paths, default in ((include_from, "+"), (exclude_from, "-")):
+            for path in paths:
+                if path == "-":
+                    rules._add(sys.stdin, default)
+                    continue
+                with open(path, encoding="utf-8", errors=PATH_ERRORS) as f:
+                    rules._add(f, default)
+        return rules
+
+    def _allows(self, path: str, is_dir: bool) -> bool:
+        for rule in self.rules:
+            if rule.matches(path, is_dir):
+                return rule.include
+        return True
+
+    def include(
+        self,
+        path: str,
+        stat_a: os.stat_result | None,
+        stat_b: os.stat_result | None,
+    ) -> bool:
+        parts = path.split("/")
+        for n in range(1, len(parts)):
+            if not self._allows("/".join(parts[:n]), True):
+                return False
+        is_dir = any(st is not None and stat.S_ISDIR(st.st_mode) for st in (stat_a, stat_b))
+        return self._allows(path, is_dir)
+
+
+class _AllFilters(PathFilter):
+    """Includes what every one of its filters includes."""
+
+    def __init__(self, filters: Iterable[PathFilter]) -> None:
+        self.filters = tuple(filters)
+
+    def include(
+        self,
+        path: str,
+        stat_a: os.stat_result | None,
+        stat_b: os.stat_result | None,
+    ) -> bool:
+        return all(f.include(path, stat_a, stat_b) for f in self.filters)
+
+
+def all_filters(*filters: PathFilter | None) -> PathFilter | None:
+    """One PathFilter including what all of *filters* include (None if there are none)."""
+    present = [f for f in filters if f is not None]
+    if len(present) < 2:
+        return present[0] if present else None
+    return _AllFilters(present)
+
+
+def _stat(root: str | int, rel: str, follow_symlinks: bool) -> os.stat_result | None:
+    try:
+        return stat_entry(root, rel, follow_symlinks)
+    except OSError:
+        return None
+
+
+def make_exclude(
+    ignore: list[str] | None,
+    path_filter: PathFilter | None,
+    dir_a: str | int,
+    dir_b: str | int,
+    follow_symlinks: bool = True,
+) -> Callable[[str], bool] | None:
+    """Combine ignore patterns and a PathFilter into one exclusion predicate.
+
+    Returns None when nothing is excluded.
+    """
+    if path_filter is not None and not isinstance(path_filter, PathFilter):
+        raise TypeError(
+            f"path_filter must be a PathFilter, got {type(path_filter).__name__}"
+        )
+    if not ignore and path_filter is None:
+        return None
+
+    def exclude(path: str) -> bool:
+        if ignore and _path_matches_ignore(path, ignore):
+            return True
+        if path_filter is None:
+            return False
+        return not path_filter.include(
+            path,
+            _stat(dir_a, path, follow_symlinks),
+            _stat(dir_b, path, follow_symlinks),
+        )
+
+    return exclude
@@ -0,0 +1,108 @@
+"""Fuzzy hashing: similarity scores for files that are not identical."""
+
+from __future__ import annotations
+
+import mmap
+import os
+import re
+
+from komparu._core import fuzzy_digest as _fuzzy_digest_c
+from komparu._validate import validate_path
+
+# spamsum constants: digest length at the base block size, smallest block
+# size and rolling-hash window (also the shortest substring two digests
+# must share to score above zero)
+_DIGEST_LENGTH = 64
+_MIN_BLOCK_SIZE = 3
+_WINDOW = 7
+
+_RUNS = re.compile(r"(.)\1{3,}")
+
+
+def fuzzy_hash(path: str) -> str:
+    """Context-triggered piecewise hash of a file, in ssdeep's format.
+
+    Returns ``"blocksize:digest:digest2"``. Files that share most of
+    their content get digests that share most of their characters; compare
+    two hashes with :func:`fuzzy_score`. The file is memory-mapped, not
+    read into memory, and the GIL is released while hashing.
+
+    :param path: File path.
+    :returns: The fuzzy hash, e.g. ``"96:s4Ud7pEmqq...:s4Ud7pEmT..."``.
+    """
+    validate_path(path, "path")
+    with open(path, "rb") as f:
+        size = os.fstat(f.fileno()).st_size
+        block_size = _MIN_BLOCK_SIZE
+        while block_size * _DIGEST_LENGTH < size:
+            block_size *= 2
+        data = mmap.mmap(f.fileno(), 0, access=mmap.ACCESS_READ) if size else b""
+        try:
+            while True:
+                digest1, digest2 = _fuzzy_digest_c(data, block_size)
+                # Too few trigger points to tell files apart: retry finer
+                if block_size > _MIN_BLOCK_SIZE and len(digest1) < _DIGEST_LENGTH // 2:
+                    block_size //= 2
+                    continue
+                return f"{block_size}:{digest1}:{digest2}"
+        finally:
+            if size:
+                data.close()
+
+
+def _parse(fuzzy: str) -> tuple[int, str, str]:
+    block_size, sep1, rest = fuzzy.partition(":")
+    digest1, sep2, digest2 = rest.partition(":")
+    if not (sep1 and sep2 and block_size.isdigit() and int(block_size) > 0):
+        raise ValueError(f"not a fuzzy hash: {fuzzy!r}")
+    # Long runs of one character carry little information and inflate scores
+    return int(block_size), _RUNS.sub(r"\1\1\1", digest1), _RUNS.sub(r"\1\1\1", digest2)
+
+
+def _edit_distance(s1: str, s2: str) -> int:
+    """Insertions and deletions (a substitution costs two) turning s1 into s2."""
+    prev = list(range(len(s2) + 1))
+    for i, c1 in enumerate(s1, 1):
+        cur = [i]
+        for j, c2 in enumerate(s2, 1):
+            cur.append(min(prev[j] + 1, cur[j - 1] + 1, prev[j - 1] + (0 if c1 == c2 else 2)))
+        prev = cur
+    return prev[-1]
+
+
+def _score_digests(s1: str, s2: str, block_size: int) -> int:
+    if not any(s1[i:i + _WINDOW] in s2 for i in range(len(s1) - _WINDOW + 1)):
+        return 0
+    distance = _edit_distance(s1, s2) * _DIGEST_LENGTH // (len(s1) + len(s2))
+    score = 100 - 100 * distance // _DIGEST_LENGTH
+    if score <= 0:
+        return 0
+    # Small files have short digests, where a few matching characters would
+    # otherwise claim a high score
+    if block_size < (99 + _WINDOW) // _WINDOW * _MIN_BLOCK_SIZE:
+        score = min(score, block_size // _MIN_BLOCK_SIZE * min(len(s1), len(s2)))
+    return score
+
+
+def fuzzy_score(hash_a: str, hash_b: str) -> int:
+    """Similarity of two :func:`fuzzy_hash` values, from 0 to 100.
+
+    100 means the digests match; 0 means no meaningful similarity. Hashes
+    whose block sizes are more than a factor of two apart (files of very
+    different sizes) always score 0.
+
+    :param hash_a: First fuzzy hash.
+    :param hash_b: Second fuzzy hash.
+    :raises ValueError: If either argument is not a fuzzy hash.
+    """
+    size_a, a1, a2 = _parse(hash_a)
+    size_b, b1, b2 = _parse(hash_b)
+    if size_a == size_b:
+        if a1 == b1:
+            return 100
+        return max(_score_digests(a1, b1, size_a), _score_digests(a2, b2, size_a * 2))
+    if size_a == size_b * 2:
+        return _score_digests(a1, b2, size_a)
+    if size_b == size_a * 2:
+        return _score_digests(a2, b1, size_b)
+    return 0
@@ -0,0 +1,242 @@
+"""Directories compared with the tree of a git commit, through the git command."""
+
+from __future__ import annotations
+
+import errno
+import hashlib
+import os
+import stat
+import subprocess
+from concurrent.futures import ThreadPoolExecutor
+
+from komparu._filter import PathFilter, make_exclude
+from komparu._hashing import require_fips_approved
+from komparu._helpers import walk_tree
+from komparu._metadata import is_under
+from komparu._types import (
+    DiffReason,
+    DirResult,
+    SourceNotFoundError,
+    SourcePermissionError,
+    SourceReadError,
+)
+from komparu._validate import validate_max_workers, validate_path
+
+
+def _git(args: list[str], where: str, input: bytes = b"", ok: tuple[int, ...] = (0,)
+         ) -> subprocess.CompletedProcess[bytes]:
+    proc = subprocess.run(["git", *args], input=input, capture_output=True)
+    if proc.returncode not in ok:
+        lines = proc.stderr.decode("utf-8", "replace").strip().splitlines()
+        raise SourceReadError(errno.EIO, lines[-1] if lines else "git failed", where)
+    return proc
+
+
+def _open_repo(repo: str, *revs: str) -> tuple[list[str], str]:
+    """git options reaching *repo*'s object store, and its hash algorithm."""
+    git_dir = _git(["-C", repo, "rev-parse", "--absolute-git-dir"], repo).stdout.decode().strip()
+    git = ["--git-dir", git_dir]
+    for rev in revs:
+        if _git([*git, "rev-parse", "--quiet", "--verify", rev], repo, ok=(0, 1)).returncode:
+            raise SourceNotFoundError(errno.ENOENT, "no such revision", rev)
+    algorithm = (_git([*git, "rev-parse", "--show-object-format"], repo).stdout.decode().strip()
+                 or "sha1")
+    return git, algorithm
+
+
+def _ls_tree(git: list[str], rev: str, repo: str) -> dict[str, tuple[str, str, str, int]]:
+    """``(mode, type, object id, size)`` per path of *rev*'s tree; size -1 for submodules."""
+    tree = {}
+    listing = _git([*git, "ls-tree", "-r", "-l", "-z", "--full-tree", rev], repo).stdout
+    for record in listing.split(b"\0"):
+        if record:
+            info, _, name = record.partition(b"\t")
+            mode, kind, oid, size = info.decode().split()
+            tree[os.fsdecode(name)] = (mode, kind, oid, -1 if size == "-" else int(size))
+    return tree
+
+
+def _blob_id(data: bytes, algorithm: str) -> str:
+    h = hashlib.new(algorithm)
+    h.update(b"blob %d\0" % len(data))
+    h.update(data)
+    return h.hexdigest()
+
+
+def _file_id(path: str, size: int, algorithm: str) -> str:
+    h = hashlib.new(algorithm)
+    h.update(b"blob %d\0" % size)
+    with open(path, "rb") as f:
+        while chunk := f.read(1024 * 1024):
+            h.update(chunk)
+    return h.hexdigest()
+
+
+def compare_dir_git(
+    directory: str,
+    rev: str,
+    *,
+    repo: str | None = None,
+    gitignore: bool = False,
+    follow_symlinks: bool = False,
+    ignore: list[str] | None = None,
+    path_filter: PathFilter | None = None,
+    max_workers: int = 0,
+) -> DirResult:
+    """Compare a directory with the tree of a git commit.
+
+    The commit's tree is listed with ``git ls-tree`` and each local file
+    hashed the way git hashes a blob, so nothing is checked out and no
+    blob is read from the repository; sizes are compared first. Symlinks
+    are compared by target, submodules skipped along with everything
+    under them, and a file whose executable bit differs from the commit's
+    is ``METADATA_MISMATCH``. ``.git`` directories are never compared.
+
+    The directory is the left side and the commit the right.
+
+    :param directory: Directory to check, e.g. a deployment.
+    :param rev: Commit, tag or branch, or ``rev:subdir`` for a subtree.
+    :param repo: Repository to read *rev* from; by default the one
+        *directory* is in.
+    :param gitignore: Leave out local files the commit does not have
+        that ``.gitignore`` files (in *directory*) or the repository's
+        ``info/exclude`` ignore.
+    :param follow_symlinks: Follow symbolic links during traversal (git
+        stores links as links, so off by default).
+    :param ignore: Glob patterns to exclude (matched per path component).
+    :param path_filter: PathFilter deciding per entry whether it is compared.
+    :param max_workers: Thread pool size (0=auto, 1=sequential).
+    :raises SourceNotFoundError: If *rev* names nothing.
+    :raises SourceReadError: If git fails, e.g. *repo* is no repository
+        or *rev* names a file.
+    :raises SourcePermissionError: If a directory cannot be listed.
+    :raises ValueError: In FIPS mode, for a repository with SHA-1 object ids.
+    """
+    validate_path(directory, "directory")
+    validate_max_workers(max_workers)
+    repo = directory if repo is None else repo
+    git, algorithm = _open_repo(repo, rev)
+    require_fips_approved(algorithm, "git object ids")
+
+    exclude = make_exclude([*(ignore or ()), ".git"], path_filter, directory, directory,
+                           follow_symlinks)
+    blobs: dict[str, tuple[str, str, int]] = {}
+    submodules: set[str] = set()
+    for path, (mode, kind, oid, size) in _ls_tree(git, rev, repo).items():
+        if kind == "commit":
+            submodules.add(path)
+        elif kind == "blob" and not (exclude and exclude(path)):
+            blobs[path] = (mode, oid, size)
+
+    entries, errors = walk_tree(directory, follow_symlinks, exclude)
+    if errors:
+        raise SourcePermissionError(errno.EACCES, "cannot read",
+                                    os.path.join(directory, min(errors)))
+    local = {path: st for path, st in entries.items()
+             if path not in submodules and not is_under(path, submodules)}
+
+    diff: dict[str, DiffReason] = {}
+    to_hash: list[str] = []
+    for path in sorted(local.keys() & blobs.keys()):
+        st = local[path]
+        mode, oid, size = blobs[path]
+        if stat.S_ISLNK(st.st_mode) != (mode == "120000") or not (
+                stat.S_ISLNK(st.st_mode) or stat.S_ISREG(st.st_mode)):
+            diff[path] = DiffReason.TYPE_MISMATCH
+        elif stat.S_ISLNK(st.st_mode):
+            target = os.fsencode(os.readlink(os.path.join(directory, path)))
+            if _blob_id(target, algorithm) != oid:
+                diff[path] = DiffReason.CONTENT_MISMATCH
+        elif st.st_size != size:
+            diff[path] = DiffReason.SIZE_MISMATCH
+        else:
+            to_hash.append(path)
+
+    def file_id(path: str) -> str:
+        return _file_id(os.path.join(directory, path), local[path].st_size, algorithm)
+
+    workers = max_workers or min(len(to_hash), 8) or 1
+    with ThreadPoolExecutor(max_workers=workers) as pool:
+        for path, oid in zip(to_hash, pool.map(file_id, to_hash)):
+            mode = blobs[path][0]
+            if oid != blobs[path][1]:
+                diff[path] = DiffReason.CONTENT_MISMATCH
+            elif bool(local[path].st_mode & stat.S_IXUSR) != (mode == "100755"):
+                diff[path] = DiffReason.METADATA_MISMATCH
+
+    only_left = {path for path, st in local.items()
+                 if path not in blobs and not stat.S_ISDIR(st.st_mode)}
+    only_right = blobs.keys() - local.keys()
+    if gitignore and only_left:
+        # Exit status 1: nothing is ignored
+        ignored = _git([*git, "--work-tree", os.path.abspath(directory), "-C", directory,
+                        "check-ignore", "--no-index", "--stdin", "-z"], directory,
+                       input=b"".join(os.fsencode(path) + b"\0" for path in sorted(only_left)),
+                       ok=(0, 1)).stdout
+        only_left -= {os.fsdecode(path) for path in ignored.split(b"\0") if path}
+    return DirResult(equal=not (diff or only_left or only_right), diff=diff,
+                     only_left=only_left, only_right=set(only_right))
+
+
+def compare_git_revs(
+    rev_a: str,
+    rev_b: str,
+    *,
+    repo: str = ".",
+    ignore: list[str] | None = None,
+) -> DirResult:
+    """Compare the trees of two git commits without checking either out.
+
+    Both trees are listed from the object store with ``git ls-tree`` and
+    compared by object id, so no file content is read at all. A path
+    whose mode changes between a file and a symlink or submodule is
+    ``TYPE_MISMATCH``, one whose executable bit alone changes
+    ``METADATA_MISMATCH``, and changed content ``SIZE_MISMATCH`` or
+    ``CONTENT_MISMATCH``; submodules compare by the commit they point at.
+
+    :param rev_a: Left commit, tag or branch, or ``rev:subdir``.
+    :param rev_b: Right one.
+    :param repo: Repository (or any directory in its working tree).
+    :param ignore: Glob patterns to exclude (matched per path component).
+    :raises SourceNotFoundError: If either revision names nothing.
+    :raises SourceReadError: If git fails.
+    """
+    validate_path(repo, "repo")
+    git, _ = _open_repo(repo, rev_a, rev_b)
+    exclude = make_exclude(ignore, None, repo, repo)
+    tree_a, tree_b = _ls_tree(git, rev_a, repo), _ls_tree(git, rev_b, repo)
+    if exclude is not None:
+        tree_a = {path: entry for path, entry in tree_a.items() if not exclude(path)}
+        tree_b = {path: entry for path, entry in tree_b.items() if not exclude(path)}
+
+    diff: dict[str, DiffReason] = {}
+    for path in tree_a.keys() & tree_b.keys():
+        mode_a, kind_a, oid_a, size_a = tree_a[path]
+        mode_b, kind_b, oid_b, size_b = tree_b[path]
+        if kind_a != kind_b or (mode_a == "120000") != (mode_b == "120000"):
+            diff[path] = DiffReason.TYPE_MISMATCH
+        elif oid_a != oid_b:
+            diff[path] = (DiffReason.SIZE_MISMATCH if size_a != size_b
+                          else DiffReason.CONTENT_MISMATCH)
+        elif mode_a != mode_b:
+            diff[path] = DiffReason.METADATA_MISMATCH
+    only_left = set(tree_a.keys() - tree_b.keys())
+    only_right = set(tree_b.keys() - tree_a.keys())
+    return DirResult(equal=not (diff or only_left or only_right), diff=diff,
+                     only_left=only_left, only_right=only_right)
+
+
+def git_range(spec: str, repo: str) -> tuple[str, str] | None:
+    """The two revisions of ``A..B`` (or ``A...B``, from their merge base), else None.
+
+    An empty side is ``HEAD``, as for git.
+    """
+    if ".." not in spec:
+        return None
+    symmetric = "..." in spec
+    rev_a, _, rev_b = spec.partition("..." if symmetric else "..")
+    rev_a, rev_b = rev_a or "HEAD", rev_b or "HEAD"
+    if symmetric:
+        git, _ = _open_repo(repo, rev_a, rev_b)
+        rev_a = _git([*git, "merge-base", rev_a, rev_b], repo).stdout.decode().strip()
+    return rev_a, rev_b
@@ -0,0 +1,129 @@
+"""Hash algorithms for manifests and tree digests: hashlib, plus blake3, xxh3 and CRCs."""
+
+from __future__ import annotations
+
+import hashlib
+import zlib
+from collections.abc import Callable
+from typing import Any
+
+from komparu._config import get_config
+from komparu._core import crc32c as _crc32c_c
+from komparu._helpers import long_path
+
+# Beyond hashlib; blake3 and xxh3/xxh128 come from optional packages
+EXTRA_ALGORITHMS = ("blake3", "xxh3", "xxh128", "crc32", "crc32c")
+# What configure(fips=True) leaves: the FIPS 180-4 hashes regulated users certify
+FIPS_ALGORITHMS = ("sha256", "sha384", "sha512")
+
+
+class _Crc:
+    """hashlib-style wrapper over a 32-bit CRC, digest in big-endian order."""
+
+    __slots__ = ("_crc",)
+
+    name: str
+    digest_size = 4
+    block_size = 1
+    _update: Callable[[bytes, int], int]
+
+    def __init__(self) -> None:
+        self._crc = 0
+
+    def update(self, data: bytes) -> None:
+        self._crc = self._update(data, self._crc)
+
+    def digest(self) -> bytes:
+        return self._crc.to_bytes(4, "big")
+
+    def hexdigest(self) -> str:
+        return self.digest().hex()
+
+
+class _Crc32(_Crc):
+    """CRC-32 of zip, gzip and SFV files."""
+
+    __slots__ = ()
+
+    name = "crc32"
+    _update = staticmethod(zlib.crc32)
+
+
+class _Crc32c(_Crc):
+    """CRC-32C (Castagnoli), from the C core."""
+
+    __slots__ = ()
+
+    name = "crc32c"
+    _update = staticmethod(_crc32c_c)
+
+
+def require_fips_approved(algorithm: str, what: str | None = None) -> None:
+    """Refuse *algorithm* in FIPS mode unless it is one of :data:`FIPS_ALGORITHMS`.
+
+    :param what: What needs the algorithm, for the message (e.g. a file format).
+    """
+    if get_config().fips and algorithm.lower() not in FIPS_ALGORITHMS:
+        needs = f"{what} needs {algorithm}, which" if what else algorithm
+        raise ValueError(f"{needs} is not FIPS-approved (fips mode allows "
+                         f"{', '.join(FIPS_ALGORITHMS)})")
+
+
+def fingerprint_hash(data: bytes = b"") -> Any:
+    """Hash for in-memory fingerprints of *data*: BLAKE2b-128, or SHA-256 in FIPS mode."""
+    if get_config().fips:
+        return hashlib.sha256(data)
+    return hashlib.blake2b(data, digest_size=16)
+
+
+def new_hash(algorithm: str) -> Any:
+    """A fresh hash object with the hashlib interface.
+
+    :param algorithm: A :mod:`hashlib` name, or one of
+        :data:`EXTRA_ALGORITHMS`.
+    :raises ValueError: If *algorithm* is unknown, or not FIPS-approved in
+        FIPS mode.
+    :raises ImportError: If the package an extra algorithm needs is missing.
+    """
+    require_fips_approved(algorithm)
+    if algorithm == "crc32":
+        return _Crc32()
+    if algorithm == "crc32c":
+        return _Crc32c()
+    if algorithm == "blake3":
+        try:
+            import blake3
+        except ImportError:
+            raise ImportError(
+                "blake3 hashes need blake3: pip install komparu[blake3]"
+            ) from None
+        return blake3.blake3()
+    if algorithm in ("xxh3", "xxh128"):
+        try:
+            import xxhash
+        except ImportError:
+            raise ImportError(
+                f"{algorithm} hashes need xxhash: pip install komparu[xxhash]"
+            ) from None
+        return xxhash.xxh3_64() if algorithm == "xxh3" else xxhash.xxh3_128()
+    try:
+        return hashlib.new(algorithm)
+    except ValueError:
+        raise ValueError(f"unsupported hash algorithm: {algorithm}") from None
+
+
+def is_algorithm(name: str) -> bool:
+    """Whether *name* is a hashlib or extra algorithm, installed or not."""
+    return name in hashlib.algorithms_available or name in EXTRA_ALGORITHMS
+
+
+def validate_algorithm(algorithm: str) -> None:
+    """Check that *algorithm* can be used and has a fixed digest size."""
+    if not new_hash(algorithm).digest_size:  # shake_* have no fixed length
+        raise ValueError(f"hash algorithm {algorithm} has no fixed digest size")
+
+
+def file_digest(path: str, algorithm: str) -> str:
+    """Hex digest of a file's content."""
+    with open(long_path(path), "rb") as f:
+        return hashlib.file_digest(f, lambda: new_hash(algorithm)).hexdigest()
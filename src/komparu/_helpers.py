@@ -2,10 +2,160 @@
 
 from __future__ import annotations
 
+import contextlib
+import dataclasses
+import errno
+import ntpath
+import os
+import secrets
+import ssl
+import stat
+import sys
+import urllib.error
+import urllib.request
+from collections.abc import Callable, Iterator
 from fnmatch import fnmatch
 from pathlib import PurePosixPath
+from typing import IO, Any
 
-from komparu._types import DiffReason, DirResult, Source
+from komparu._types import (
+    DiffEntry,
+    DiffReason,
+    DirResult,
+    EntryKind,
+    Source,
+    SourceNotFoundError,
+    SourcePermissionError,
+    SourceReadError,
+)
+
+# Events a streaming comparison queues in C before its workers pause
+DEFAULT_MAX_PENDING = 1024
+
+# Windows refuses longer paths (248 for directories) unless they are in \\?\ form
+_WINDOWS_MAX_DIR_PATH = 248
+
+
+def dir_handles(dir_a: str | int, dir_b: str | int) -> tuple[str, str, dict[str, int]]:
+    """Split directory arguments into C core paths plus ``dirfd_*`` kwargs.
+
+    An int is an open directory descriptor: the core walks it as ``"."``
+    with every path resolved relative to it (openat-style).
+    """
+    handles: dict[str, int] = {}
+    if isinstance(dir_a, int):
+        handles["dirfd_a"], dir_a = dir_a, "."
+    if isinstance(dir_b, int):
+        handles["dirfd_b"], dir_b = dir_b, "."
+    return dir_a, dir_b, handles
+
+
+def long_path(path: str) -> str:
+    """*path* in extended-length ``\\\\?\\`` form on Windows if it is too long for MAX_PATH.
+
+    Short paths, paths already in that form and other platforms come back
+    as they are.
+    """
+    if (sys.platform != "win32" or len(path) < _WINDOWS_MAX_DIR_PATH
+            or path.startswith(("\\\\?\\", "\\\\.\\"))):
+        return path
+    path = ntpath.abspath(path)  # the prefix turns off resolving "." and ".."
+    if path.startswith("\\\\"):
+        return "\\\\?\\UNC\\" + path[2:]
+    return "\\\\?\\" + path
+
+
+def stat_entry(root: str | int, rel: str, follow_symlinks: bool = True) -> os.stat_result:
+    """os.stat() of *rel* under a directory path or descriptor."""
+    if isinstance(root, int):
+        return os.stat(rel, dir_fd=root, follow_symlinks=follow_symlinks)
+    return os.stat(long_path(os.path.join(root, rel)), follow_symlinks=follow_symlinks)
+
+
+def walk_tree(
+    root: str,
+    follow_symlinks: bool,
+    exclude: Callable[[str], bool] | None,
+) -> tuple[dict[str, os.stat_result], set[str]]:
+    """Every entry under *root* by relative path, and the paths that could not be read."""
+    entries: dict[str, os.stat_result] = {}
+    errors: set[str] = set()
+    root_stat = os.stat(root)
+    seen = {(root_stat.st_dev, root_stat.st_ino)}
+    pending = [""]
+    while pending:
+        rel = pending.pop()
+        try:
+            with os.scandir(long_path(os.path.join(root, rel))) as it:
+                children = list(it)
+        except PermissionError:
+            errors.add(rel)
+            continue
+        for entry in children:
+            path = f"{rel}/{entry.name}" if rel else entry.name
+            if exclude is not None and exclude(path):
+                continue
+            try:
+                st = entry.stat(follow_symlinks=follow_symlinks)
+            except FileNotFoundError:  # dangling symlink
+                st = entry.stat(follow_symlinks=False)
+            except PermissionError:
+                errors.add(path)
+                continue
+            entries[path] = st
+            # Directory symlinks may loop; visit each directory once
+            if stat.S_ISDIR(st.st_mode) and (st.st_dev, st.st_ino) not in seen:
+                seen.add((st.st_dev, st.st_ino))
+                pending.append(path)
+    return entries, errors
+
+
+def _sync_directory(directory: str) -> None:
+    if sys.platform == "win32":
+        return  # NTFS journals the rename itself; directories cannot be opened to sync
+    try:
+        fd = os.open(directory, os.O_RDONLY)
+    except OSError:
+        return
+    try:
+        os.fsync(fd)
+    except OSError:
+        pass  # file systems that cannot sync a directory
+    finally:
+        os.close(fd)
+
+
+@contextlib.contextmanager
+def atomic_open(path: str, mode: str = "w", **kwargs: Any) -> Iterator[IO[Any]]:
+    """Open *path* for writing so that it holds the old contents or all of the new.
+
+    Writes go to a temporary file beside *path*, which is flushed to
+    disk and renamed over it when the block ends, and the directory is
+    synced so the rename survives a power loss. An exception, or a crash
+    before the rename, leaves *path* as it was. The file keeps the mode
+    of the one it replaces.
+
+    :param path: File to write.
+    :param mode: ``"w"`` or ``"wb"``.
+    :param kwargs: Passed to :func:`open`, e.g. ``encoding``.
+    """
+    directory, name = os.path.split(os.fspath(path))
+    partial = os.path.join(directory, f".{name}.{secrets.token_hex(4)}.komparu-tmp")
+    flags = os.O_WRONLY | os.O_CREAT | os.O_EXCL | getattr(os, "O_BINARY", 0)
+    f = os.fdopen(os.open(partial, flags, 0o666), mode, **kwargs)
+    try:
+        with f:
+            yield f
+            f.flush()
+            os.fsync(f.fileno())
+        with contextlib.suppress(FileNotFoundError):
+            os.chmod(partial, stat.S_IMODE(os.stat(path).st_mode))
+        os.replace(partial, path)
+    except BaseException:
+        with contextlib.suppress(FileNotFoundError):
+            os.unlink(partial)
+        raise
+    _sync_directory(directory or ".")
 
 
 def resolve_headers(source: str | Source, global_headers: dict[str, str] | None) -> dict[str, str] | None:
@@ -19,28 +169,58 @@ def resolve_headers(source: str | Source, global_headers: dict[str, str] | None)
     return global_headers
 
 
+def is_url(path: str) -> bool:
+    """Whether *path* is an HTTP(S) URL rather than a local path."""
+    return path.startswith(("http://", "https://"))
+
+
+def fetch_url(url: str) -> bytes:
+    """The body at *url*, with the configured timeout, headers and TLS checks.
+
+    :raises SourceNotFoundError: On HTTP 404 or 410.
+    :raises SourcePermissionError: On HTTP 401 or 403.
+    :raises SourceReadError: If *url* cannot be reached or answers with
+        another error status.
+    """
+    from komparu._config import get_config
+
+    cfg = get_config()
+    context = None if cfg.verify_ssl else ssl._create_unverified_context()
+    request = urllib.request.Request(url, headers={"User-Agent": "komparu", **cfg.headers})
+    try:
+        with urllib.request.urlopen(request, timeout=cfg.timeout, context=context) as response:
+            return bytes(response.read())
+    except urllib.error.HTTPError as exc:
+        message = f"HTTP {exc.code} {exc.reason}"
+        if exc.code in (404, 410):
+            raise SourceNotFoundError(errno.ENOENT, message, url) from None
+        if exc.code in (401, 403):
+            raise SourcePermissionError(errno.EACCES, message, url) from None
+        raise SourceReadError(errno.EIO, message, url) from None
+    except (urllib.error.URLError, OSError) as exc:
+        reason = getattr(exc, "reason", None) or exc
+        raise SourceReadError(errno.EIO, f"cannot fetch: {reason}", url) from None
+
+
 def _path_matches_ignore(path: str, patterns: list[str]) -> bool:
     """Check if any component of *path* matches any ignore pattern."""
     parts = PurePosixPath(path).parts
     return any(fnmatch(part, pat) for part in parts for pat in patterns)
 
 
-def filter_dir_result(result: DirResult, ignore: list[str]) -> DirResult:
-    """Remove entries whose path matches any ignore glob pattern.
+def filter_dir_result(result: DirResult, exclude: Callable[[str], bool] | None) -> DirResult:
+    """Remove entries whose relative path *exclude* rejects.
 
-    Each component of the relative path is tested against every pattern
-    using :func:`fnmatch.fnmatch`.  If any component matches, the entry
-    is excluded.  The ``equal`` flag is recomputed after filtering.
+    *exclude* is built by :func:`komparu._filter.make_exclude` from ignore
+    patterns and a PathFilter.  The ``equal`` flag is recomputed after
+    filtering.
     """
-    if not ignore:
+    if exclude is None:
         return result
 
-    diff = {k: v for k, v in result.diff.items()
-            if not _path_matches_ignore(k, ignore)}
-    only_left = {p for p in result.only_left
-                 if not _path_matches_ignore(p, ignore)}
-    only_right = {p for p in result.only_right
-                  if not _path_matches_ignore(p, ignore)}
+    diff = {k: v for k, v in result.diff.items() if not exclude(k)}
+    only_left = {p for p in result.only_left if not exclude(p)}
+    only_right = {p for p in result.only_right if not exclude(p)}
 
     # If the original result was equal and nothing was filtered away,
     # keep it.  Otherwise recompute: equal iff no remaining diffs.
@@ -48,8 +228,7 @@ def filter_dir_result(result: DirResult, ignore: list[str]) -> DirResult:
         not diff and not only_left and not only_right
     )
 
-    errors = {p for p in result.errors
-              if not _path_matches_ignore(p, ignore)}
+    errors = {p for p in result.errors if not exclude(p)}
 
     return DirResult(equal=equal, diff=diff,
                      only_left=only_left, only_right=only_right,
@@ -66,3 +245,71 @@ def build_dir_result(raw: dict) -> DirResult:
         only_right=raw["only_right"],
         errors=raw.get("errors", set()),
     )
+
+
+_ENTRY_REASON = {
+    EntryKind.ONLY_LEFT: DiffReason.MISSING,
+    EntryKind.ONLY_RIGHT: DiffReason.MISSING,
+    EntryKind.ERROR: DiffReason.READ_ERROR,
+}
+
+
+def build_diff_entry(kind: str, reason: str | None, path: str) -> DiffEntry:
+    """Convert a C extension ``(kind, reason, path)`` event to DiffEntry."""
+    k = EntryKind(kind)
+    r = DiffReason(reason) if reason is not None else _ENTRY_REASON.get(k)
+    return DiffEntry(path=path, kind=k, reason=r)
+
+
+def _size(root: str | int, rel: str, follow_symlinks: bool) -> int | None:
+    try:
+        return stat_entry(root, rel, follow_symlinks).st_size
+    except OSError:
+        return None
+
+
+def make_annotate(
+    dir_a: str | int,
+    dir_b: str | int,
+    follow_symlinks: bool,
+    recheck: Callable[[DiffEntry], DiffEntry] | None = None,
+) -> Callable[[DiffEntry], DiffEntry]:
+    """Entry transform for streamed comparisons.
+
+    Applies *recheck* (see :class:`komparu._comparator.ComparatorDispatch`),
+    then attaches both sides' sizes to entries that are still DIFF.
+    """
+    def annotate(entry: DiffEntry) -> DiffEntry:
+        if recheck is not None:
+            entry = recheck(entry)
+        if entry.kind is not EntryKind.DIFF:
+            return entry
+        return dataclasses.replace(
+            entry,
+            size_a=_size(dir_a, entry.path, follow_symlinks),
+            size_b=_size(dir_b, entry.path, follow_symlinks),
+        )
+
+    return annotate
+
+
+def iter_diff_entries(
+    events: list[tuple[str, str | None, str]],
+    exclude: Callable[[str], bool] | None,
+    include_equal: bool,
+    recheck: Callable[[DiffEntry], DiffEntry] | None = None,
+) -> Iterator[DiffEntry]:
+    """Convert a batch of stream events, dropping equal and excluded entries.
+
+    *recheck*, if given, may replace each entry before filtering (see
+    :class:`komparu._comparator.ComparatorDispatch`).
+    """
+    for kind, reason, path in events:
+        if exclude is not None and exclude(path):
+            continue
+        entry = build_diff_entry(kind, reason, path)
+        if recheck is not None:
+            entry = recheck(entry)
+        if entry.kind is EntryKind.EQUAL and not include_equal:
+            continue
+        yield entry
@@ -0,0 +1,192 @@
+"""Change journal: verify two trees again and again, re-reading only what changed.
+
+The trees are put under change notification (fanotify, inotify or
+metadata polling, as for :func:`komparu.watch_dir`) before the first
+verification, and a background thread gathers the paths the events name
+from then on. Each verification takes the paths gathered since the
+previous one, re-compares just those, and carries the rest of the last
+result over.
+"""
+
+from __future__ import annotations
+
+import os
+import select
+import threading
+from types import TracebackType
+
+from komparu._cancel import CancelToken
+from komparu._filter import PathFilter, all_filters, make_exclude
+from komparu._options import CompareOptions
+from komparu._types import DiffEntry, DirResult, EntryKind
+from komparu._validate import validate_path
+from komparu._watch import (
+    _WAKE,
+    _compare_dirs,
+    _entries,
+    _Fanotify,
+    _Poller,
+    _topmost,
+    _under,
+    _watcher,
+)
+
+
+class ChangeJournal:
+    """Two directories under change notification, re-verified where they changed.
+
+    Made for checking the same trees repeatedly from one long-running
+    process — a replica before each deploy, a backup every hour. The
+    first :meth:`verify` compares everything; each later one re-compares
+    only the paths created, written, removed, moved or changing metadata
+    since the previous one began, and keeps the rest of the previous
+    result. Subscription starts when the journal is made, so a change
+    made while a verification runs is picked up by the next.
+
+    Change notifications come from fanotify when the process has
+    ``CAP_SYS_ADMIN``, symlinks are not followed and no other file
+    system is mounted inside the trees; otherwise from inotify (one
+    watch per directory, up to ``fs.inotify.max_user_watches``). If
+    events are lost — the kernel queue overflowed — the next verification
+    compares everything again. With *poll*, or where neither is
+    available, each verification re-scans both trees' metadata instead,
+    which still spares reading files that did not change.
+
+    Changes made while no journal is open are not seen: the journal
+    lives as long as the process, not across runs. Not thread-safe:
+    call :meth:`verify` from one thread at a time.
+
+    :param dir_a: Path to first directory.
+    :param dir_b: Path to second directory.
+    :param options: Options of every verification (defaults if None).
+    :param path_filter: PathFilter deciding per entry whether it is reported.
+    :param poll: Re-scan metadata at each verification instead of
+        subscribing to change notifications (for network file systems,
+        which do not deliver them).
+    :raises OSError: If the trees cannot be watched (e.g. too many
+        directories for ``fs.inotify.max_user_watches``).
+    """
+
+    def __init__(
+        self,
+        dir_a: str,
+        dir_b: str,
+        *,
+        options: CompareOptions | None = None,
+        path_filter: PathFilter | None = None,
+        poll: bool = False,
+    ) -> None:
+        validate_path(dir_a, "dir_a")
+        validate_path(dir_b, "dir_b")
+        if options is None:
+            options = CompareOptions()
+        elif not isinstance(options, CompareOptions):
+            raise TypeError(f"options must be CompareOptions, got {type(options).__name__}")
+        self._roots = (os.fspath(dir_a), os.fspath(dir_b))
+        self._kwargs = {key: value for key, value in options.dir_kwargs().items()
+                        if key not in ("ignore", "path_filter")}
+        self._exclude = make_exclude(list(options.ignore),
+                                     all_filters(options.path_filter, path_filter),
+                                     dir_a, dir_b, options.follow_symlinks)
+        self._watcher = _watcher(self._roots, options.follow_symlinks, self._exclude, poll)
+        self._lock = threading.Lock()  # guards _touched
+        self._watching = threading.Lock()  # one reader of the watcher at a time
+        self._touched: set[str] | None = None  # None: compare everything
+        self._state: dict[str, DiffEntry] = {}
+        self._closed = threading.Event()
+        self._thread: threading.Thread | None = None
+        if not isinstance(self._watcher, _Poller):
+            self._thread = threading.Thread(target=self._drain, name="komparu-journal",
+                                            daemon=True)
+            self._thread.start()
+
+    def _add(self, changed: set[str] | None) -> None:
+        with self._lock:
+            if changed is None or self._touched is None:
+                self._touched = None
+            else:
+                self._touched |= changed
+
+    def _collect(self) -> None:
+        """Take the events queued so far (re-scan, when polling)."""
+        with self._watching:
+            changed = self._watcher.wait(0)
+        if changed is None or changed:
+            self._add(changed)
+
+    def _drain(self) -> None:
+        fd = self._watcher.fileno()  # type: ignore[union-attr]
+        while not self._closed.is_set():
+            # Kept short of the kernel queue limit, where events would be lost
+            if select.select([fd], [], [], _WAKE)[0]:
+                self._collect()
+
+    @property
+    def backend(self) -> str:
+        """``"fanotify"``, ``"inotify"`` or ``"poll"``: where changes are learned from."""
+        if isinstance(self._watcher, _Fanotify):
+            return "fanotify"
+        return "poll" if isinstance(self._watcher, _Poller) else "inotify"
+
+    @property
+    def pending(self) -> set[str] | None:
+        """Paths the next :meth:`verify` re-compares; None if it compares everything."""
+        self._collect()
+        with self._lock:
+            return None if self._touched is None else set(self._touched)
+
+    def verify(self, *, cancel: CancelToken | None = None) -> DirResult:
+        """Compare the trees where they changed since the previous verification.
+
+        :param cancel: CancelToken that aborts the verification; the paths
+            it would have re-compared are kept for the next one.
+        :returns: DirResult of the whole trees.
+        :raises SourceReadError: If a directory cannot be walked.
+        :raises ComparisonCancelledError: If *cancel* was set while running.
+        """
+        if self._closed.is_set():
+            raise ValueError("verify() on a closed ChangeJournal")
+        self._collect()
+        with self._lock:
+            touched, self._touched = self._touched, set()
+        try:
+            if touched is None:
+                state = _compare_dirs(list(self._roots), "", self._kwargs, self._exclude, cancel)
+            else:
+                tops = _topmost(touched)
+                state = {path: entry for path, entry in self._state.items()
+                         if not any(_under(path, top) for top in tops)}
+                for top in tops:
+                    state.update(_entries(self._roots, top, self._kwargs, self._exclude, cancel))
+        except BaseException:
+            self._add(touched)
+            raise
+        self._state = state
+        entries = state.values()
+        diff = {entry.path: entry.reason for entry in entries
+                if entry.kind is EntryKind.DIFF and entry.reason is not None}
+        only_left = {entry.path for entry in entries if entry.kind is EntryKind.ONLY_LEFT}
+        only_right = {entry.path for entry in entries if entry.kind is EntryKind.ONLY_RIGHT}
+        errors = {entry.path for entry in entries if entry.kind is EntryKind.ERROR}
+        return DirResult(equal=not (diff or only_left or only_right), diff=diff,
+                         only_left=only_left, only_right=only_right, errors=errors)
+
+    def close(self) -> None:
+        """Stop watching; the journal cannot verify any more."""
+        if self._closed.is_set():
+            return
+        self._closed.set()
+        if self._thread is not None:
+            self._thread.join()
+        with self._watching:
+            self._watcher.close()
+
+    def __enter__(self) -> ChangeJournal:
+        return self
+
+    def __exit__(self, exc_type: type[BaseException] | None, exc: BaseException | None,
+                 tb: TracebackType | None) -> None:
+        self.close()
+
+    def __repr__(self) -> str:
+        return f"ChangeJournal({self._roots[0]!r}, {self._roots[1]!r}, backend={self.backend!r})"
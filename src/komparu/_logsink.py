@@ -0,0 +1,170 @@
+"""Comparison results and errors sent as structured records to journald or syslog."""
+
+from __future__ import annotations
+
+import datetime
+import os
+import socket
+from collections.abc import Iterable
+from types import TracebackType
+
+from komparu._types import PATH_ERRORS, DirResult, path_key
+
+JOURNAL_SOCKET = "/run/systemd/journal/socket"
+SYSLOG_SOCKET = "/dev/log"
+LOG_TARGETS = ("auto", "journald", "syslog", "syslog://HOST[:PORT]")
+
+_ERR, _WARNING, _NOTICE, _INFO = 3, 4, 5, 6
+_FACILITIES = {"user": 1, "daemon": 3, "auth": 4, "syslog": 5, "cron": 9,
+               **{f"local{n}": 16 + n for n in range(8)}}
+# SD-ID of RFC 5424 structured data; 32473 is the example enterprise number of RFC 5612
+_SD_ID = "komparu@32473"
+
+
+def _journal_field(name: str, value: str) -> bytes:
+    data = value.encode("utf-8", PATH_ERRORS)
+    if b"\n" not in data:
+        return f"{name}=".encode() + data + b"\n"
+    # Values with newlines go in the binary form: name, length, value
+    return name.encode() + b"\n" + len(data).to_bytes(8, "little") + data + b"\n"
+
+
+def _sd_value(value: str) -> str:
+    return value.replace("\\", "\\\\").replace('"', '\\"').replace("]", "\\]")
+
+
+class LogSink:
+    """Write comparison results and errors as structured records to journald or syslog.
+
+    Each result becomes a summary record (``info`` if equal, ``warning``
+    otherwise) and, with *entries*, one ``notice`` record per differing
+    or one-sided path, so scheduled verifications land in the same log
+    aggregation as everything else. journald receives the fields as its
+    own (``KOMPARU_EVENT``, ``KOMPARU_LEFT``, ``KOMPARU_PATH``,
+    ``KOMPARU_REASON``, ...), syslog as RFC 5424 structured data under
+    ``komparu@32473`` with the same names in lower case.
+
+    :param target: ``journald``, ``syslog`` (the local ``/dev/log``),
+        ``syslog://host[:port]`` (UDP, port 514 by default), or ``auto``
+        for journald where it runs, else the local syslog.
+    :param address: Socket path to use instead of the target's usual one.
+    :param ident: Program name the records carry.
+    :param facility: Syslog facility: ``user``, ``daemon``, ``cron``,
+        ``local0`` to ``local7``, ...
+    :param entries: Also write one record per differing path.
+    :raises ValueError: If *target* or *facility* is unknown.
+    :raises OSError: If the log socket cannot be reached.
+    """
+
+    __slots__ = ("_journal", "_socket", "_ident", "_facility", "_entries", "_hostname")
+
+    def __init__(self, target: str = "auto", *, address: str | None = None,
+                 ident: str = "komparu", facility: str = "user", entries: bool = True) -> None:
+        if facility not in _FACILITIES:
+            raise ValueError(f"unknown syslog facility {facility!r}")
+        if target == "auto":
+            target = "journald" if os.path.exists(address or JOURNAL_SOCKET) else "syslog"
+        self._ident = ident
+        self._facility = _FACILITIES[facility]
+        self._entries = entries
+        self._hostname = socket.gethostname()
+        self._journal = target == "journald"
+        if target.startswith("syslog://"):
+            host, _, port = target[len("syslog://"):].rstrip("/").rpartition(":")
+            if not host or not port.isdigit():
+                host, port = target[len("syslog://"):].rstrip("/"), "514"
+            if not host:
+                raise ValueError(f"expected syslog://HOST[:PORT], got {target!r}")
+            host = host.strip("[]")
+            family = socket.AF_INET6 if ":" in host else socket.AF_INET
+            self._socket = socket.socket(family, socket.SOCK_DGRAM)
+            self._socket.connect((host, int(port)))
+        elif target in ("journald", "syslog"):
+            path = address or (JOURNAL_SOCKET if self._journal else SYSLOG_SOCKET)
+            self._socket = socket.socket(socket.AF_UNIX, socket.SOCK_DGRAM)
+            try:
+                self._socket.connect(path)
+            except OSError:
+                self._socket.close()
+                raise
+        else:
+            raise ValueError(f"log target must be one of {', '.join(LOG_TARGETS)}, "
+                             f"got {target!r}")
+
+    def __enter__(self) -> LogSink:
+        return self
+
+    def __exit__(self, exc_type: type[BaseException] | None, exc: BaseException | None,
+                 tb: TracebackType | None) -> None:
+        self.close()
+
+    def close(self) -> None:
+        self._socket.close()
+
+    def _write(self, priority: int, message: str, fields: dict[str, str]) -> None:
+        if self._journal:
+            record = b"".join(_journal_field(name, value) for name, value in [
+                ("MESSAGE", message), ("PRIORITY", str(priority)),
+                ("SYSLOG_IDENTIFIER", self._ident), ("SYSLOG_FACILITY", str(self._facility)),
+                *((f"KOMPARU_{name.upper()}", value) for name, value in fields.items())])
+        else:
+            stamp = datetime.datetime.now(datetime.timezone.utc).isoformat(
+                timespec="milliseconds")
+            params = " ".join(f'{name}="{_sd_value(value)}"' for name, value in fields.items())
+            record = (f"<{self._facility * 8 + priority}>1 {stamp} {self._hostname} "
+                      f"{self._ident} {os.getpid()} {fields.get('event', '-')} "
+                      f"[{_SD_ID} {params}] ").encode("utf-8", PATH_ERRORS)
+            record += b"\xef\xbb\xbf" + message.encode("utf-8", PATH_ERRORS)  # BOM: UTF-8
+        self._socket.send(record)
+
+    def dir_result(self, result: DirResult, left: str, right: str, *,
+                   sides: tuple[str, str] = ("left", "right")) -> None:
+        """Record the result of comparing two trees (or a tree and a manifest, ...).
+
+        :param sides: Names of the two sides, as the CLI prints them.
+        """
+        counts = {"diff": len(result.diff), "only_left": len(result.only_left),
+                  "only_right": len(result.only_right), "errors": len(result.errors)}
+        summary = ", ".join(f"{n} {name.replace('_', ' ')}" for name, n in counts.items() if n)
+        message = f"{left} {right} " + (f"differ: {summary}" if not result.equal else "equal")
+        self._write(_INFO if result.equal else _WARNING, message,
+                    {"event": "result", "left": left, "right": right,
+                     "equal": str(result.equal).lower(),
+                     **{name: str(n) for name, n in counts.items()}})
+        if not self._entries:
+            return
+        entries: Iterable[tuple[str, str, str]] = [
+            *((path, reason.value, f"differ: {path} ({reason.value})")
+              for path, reason in sorted(result.diff.items(), key=lambda item: path_key(item[0]))),
+            *((path, "only_left", f"only in {sides[0]}: {path}")
+              for path in sorted(result.only_left, key=path_key)),
+            *((path, "only_right", f"only in {sides[1]}: {path}")
+              for path in sorted(result.only_right, key=path_key)),
+            *((path, "error", f"error: {path}") for path in sorted(result.errors, key=path_key)),
+        ]
+        for path, reason, text in entries:
+            self._write(_NOTICE, text, {"event": "difference", "left": left, "right": right,
+                                        "path": path, "reason": reason})
+
+    def file_result(self, equal: bool, left: str, right: str) -> None:
+        """Record the result of comparing two files."""
+        self._write(_INFO if equal else _WARNING,
+                    f"{left} {right} {'equal' if equal else 'differ'}",
+                    {"event": "result", "left": left, "right": right,
+                     "equal": str(equal).lower()})
+
+    def forensic(self, noatime: bool, denied: int) -> None:
+        """Record that a comparison wrote nothing to what it compared.
+
+        :param noatime: Whether files were opened with O_NOATIME.
+        :param denied: How many of them the kernel refused it on.
+        """
+        kept = noatime and not denied
+        self._write(_INFO, "forensic: read-only, nothing written to either side; access "
+                    f"times {'kept' if kept else 'may have changed'}",
+                    {"event": "forensic", "noatime": str(noatime).lower(),
+                     "atime_denied": str(denied)})
+
+    def error(self, message: str, **fields: str) -> None:
+        """Record that a comparison failed."""
+        self._write(_ERR, message, {"event": "error", **fields})
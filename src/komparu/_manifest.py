@@ -0,0 +1,632 @@
+"""Checksum manifests and Merkle digests of trees.
+
+Manifests list per-file digests in ``sha256sum`` or BSD format; a tree
+digest folds a whole tree into one.
+"""
+
+from __future__ import annotations
+
+import errno
+import hashlib
+import os
+import re
+import stat
+import urllib.parse
+from collections import deque
+from collections.abc import Collection, Iterable, Iterator, Mapping
+from concurrent.futures import Future, ThreadPoolExecutor
+
+from komparu._filter import PathFilter, make_exclude
+from komparu._hashing import file_digest, is_algorithm, new_hash, validate_algorithm
+from komparu._helpers import atomic_open, fetch_url, is_url, walk_tree
+from komparu._signing import verify_signature
+from komparu._metadata import read_xattrs, validate_attributes
+from komparu._types import (
+    PATH_ERRORS,
+    DiffReason,
+    DirResult,
+    Manifest,
+    SourcePermissionError,
+    path_key,
+)
+from komparu._validate import validate_max_workers, validate_path
+
+
+# Digest lengths of the algorithms sha*sum and md5sum write, for manifests
+# that do not say which they used
+_ALGORITHM_BY_LENGTH = {32: "md5", 40: "sha1", 56: "sha224", 64: "sha256",
+                        96: "sha384", 128: "sha512"}
+_HEX = frozenset("0123456789abcdefABCDEF")
+
+# Names BSD md5/shasum and GNU --tag write; others are upper-cased
+_BSD_TAGS = {"md5": "MD5", "sha1": "SHA1", "sha224": "SHA224", "sha256": "SHA256",
+             "sha384": "SHA384", "sha512": "SHA512", "blake2b": "BLAKE2b",
+             "blake2s": "BLAKE2s"}
+_BSD_LINE = re.compile(r"([A-Za-z0-9_-]+) \((.*)\) = ([0-9a-fA-F]+)")
+
+
+def _escape(path: str) -> tuple[bool, str]:
+    if "\\" in path or "\n" in path:
+        return True, path.replace("\\", "\\\\").replace("\n", "\\n")
+    return False, path
+
+
+def format_manifest_line(digest: str, path: str, *, algorithm: str | None = None) -> str:
+    """One manifest line, escaped as the GNU tools do for odd names.
+
+    :param digest: Hex digest.
+    :param path: Relative path.
+    :param algorithm: Write a BSD-style line naming this algorithm,
+        ``SHA256 (path) = digest``, instead of ``digest  path``.
+    """
+    escaped, path = _escape(path)
+    if algorithm is not None:
+        tag = _BSD_TAGS.get(algorithm, algorithm.upper().replace("_", "-"))
+        line = f"{tag} ({path}) = {digest}"
+    else:
+        line = f"{digest}  {path}"
+    return "\\" + line if escaped else line
+
+
+def _algorithm_of_tag(tag: str, where: str) -> str:
+    algorithm = tag.lower().replace("-", "_")
+    if not is_algorithm(algorithm):
+        raise ValueError(f"{where}: unknown hash algorithm {tag}")
+    return algorithm
+
+
+def hash_tree(
+    directory: str,
+    *,
+    algorithm: str = "sha256",
+    follow_symlinks: bool = True,
+    ignore: list[str] | None = None,
+    path_filter: PathFilter | None = None,
+    max_workers: int = 0,
+) -> Iterator[tuple[str, str]]:
+    """Digest every regular file under a directory.
+
+    Yields ``(relative_path, hex_digest)`` in path order while later files
+    are still being hashed: files are read on a thread pool (hashlib
+    releases the GIL), a bounded number ahead of the consumer, so memory
+    stays flat however large the tree is.
+
+    :param directory: Directory path.
+    :param algorithm: Any fixed-size :mod:`hashlib` algorithm, or
+        ``blake3``, ``xxh3``, ``xxh128``, ``crc32`` or ``crc32c``.
+    :param follow_symlinks: Follow symbolic links during traversal.
+    :param ignore: Glob patterns to exclude (matched per path component).
+    :param path_filter: PathFilter deciding per entry whether it is hashed.
+    :param max_workers: Thread pool size (0=auto, 1=sequential).
+    :raises ValueError: If *algorithm* is unknown or of variable length.
+    :raises SourcePermissionError: If a directory cannot be listed.
+    """
+    validate_path(directory, "directory")
+    validate_algorithm(algorithm)
+    validate_max_workers(max_workers)
+    exclude = make_exclude(ignore, path_filter, directory, directory, follow_symlinks)
+    entries, errors = walk_tree(directory, follow_symlinks, exclude)
+    if errors:
+        raise SourcePermissionError(errno.EACCES, "cannot read",
+                                    os.path.join(directory, min(errors)))
+    paths = sorted(path for path, st in entries.items() if stat.S_ISREG(st.st_mode))
+    yield from hash_files(directory, paths, algorithm, max_workers)
+
+
+def hash_files(
+    directory: str, paths: list[str], algorithm: str, max_workers: int,
+) -> Iterator[tuple[str, str]]:
+    if max_workers == 1:
+        for path in paths:
+            yield path, file_digest(os.path.join(directory, path), algorithm)
+        return
+    workers = max_workers or min(32, os.cpu_count() or 1)
+    pending: deque[tuple[str, Future[str]]] = deque()
+    with ThreadPoolExecutor(max_workers=workers) as pool:
+        try:
+            for path in paths:
+                future = pool.submit(file_digest, os.path.join(directory, path), algorithm)
+                pending.append((path, future))
+                if len(pending) >= workers * 4:
+                    done, future = pending.popleft()
+                    yield done, future.result()
+            while pending:
+                done, future = pending.popleft()
+                yield done, future.result()
+        finally:
+            for _, future in pending:
+                future.cancel()
+
+
+def _unescape(name: str) -> str:
+    out = []
+    chars = iter(name)
+    for c in chars:
+        if c == "\\":
+            escaped = next(chars, "")
+            if escaped not in ("\\", "n"):
+                raise ValueError(f"bad escape in {name!r}")
+            out.append("\n" if escaped == "n" else "\\")
+        else:
+            out.append(c)
+    return "".join(out)
+
+
+def parse_manifest(lines: Iterable[str], source: str = "manifest") -> Manifest:
+    """Digest per relative path from checksum manifest lines.
+
+    Accepts what :func:`format_manifest_line` and the GNU and BSD tools
+    write: ``digest  path`` and ``digest *path`` (binary mode) as from
+    ``sha256sum``, ``SHA256 (path) = digest`` as from BSD ``md5``,
+    ``shasum --tag`` and ``sha256sum --tag``, and the escaped form of
+    either for names with backslashes or newlines. Blank lines, comments
+    (``#`` or ``;``) and a leading byte-order mark are skipped, and the
+    backslashes of Windows tools, which never escape names, separate
+    directories.
+
+    :param lines: Manifest lines, with or without line endings.
+    :param source: Name used in error messages.
+    :returns: The digests, and the algorithm if the lines name one.
+    :raises ValueError: On a line that is not a manifest entry, or lines
+        naming different algorithms.
+    """
+    digests: dict[str, str] = {}
+    algorithm = None
+    for number, line in enumerate(lines, 1):
+        line = line.rstrip("\r\n")
+        if number == 1:
+            line = line.removeprefix("\ufeff")
+        if not line.strip() or line.startswith(("#", ";")):
+            continue
+        where = f"{source}:{number}"
+        escaped = line.startswith("\\")
+        if escaped:
+            line = line[1:]
+        if bsd := _BSD_LINE.fullmatch(line):
+            tag, name, digest = bsd.groups()
+            named = _algorithm_of_tag(tag, where)
+            if algorithm is not None and named != algorithm:
+                raise ValueError(f"{where}: {tag} line in a {algorithm} manifest")
+            algorithm = named
+        else:
+            digest, sep, name = line.partition(" ")
+            if not (sep and digest and _HEX.issuperset(digest) and name[:1] in (" ", "*")
+                    and len(name) > 1):
+                raise ValueError(f"{where}: not a checksum line")
+            name = name[1:]
+            if not escaped:
+                name = name.replace("\\", "/")
+        if escaped:
+            name = _unescape(name)
+        # find . -type f | xargs sha256sum writes ./-prefixed paths
+        digests[name.removeprefix("./")] = digest.lower()
+    return Manifest(digests, algorithm)
+
+
+def parse_sfv(lines: Iterable[str], source: str = "sfv") -> Manifest:
+    """CRC-32 per relative path from Simple File Verification lines.
+
+    Lines are ``path CRC`` with an 8-digit hex CRC-32 last; comments
+    start with ``;``. Backslashes in paths separate directories, as the
+    Windows tools that write SFV files mean them.
+
+    :param lines: SFV lines, with or without line endings.
+    :param source: Name used in error messages.
+    :returns: The digests, with algorithm ``crc32``.
+    :raises ValueError: On a line that is not an SFV entry.
+    """
+    digests: dict[str, str] = {}
+    for number, line in enumerate(lines, 1):
+        line = line.rstrip("\r\n")
+        if number == 1:
+            line = line.removeprefix("\ufeff")
+        if not line.strip() or line.startswith(";"):
+            continue
+        name, _, crc = line.rstrip().rpartition(" ")
+        name = name.rstrip()
+        if not name or len(crc) != 8 or not _HEX.issuperset(crc):
+            raise ValueError(f"{source}:{number}: not an SFV line")
+        digests[name.replace("\\", "/").removeprefix("./")] = crc.lower()
+    return Manifest(digests, "crc32")
+
+
+def read_manifest(
+    path: str, *, public_key: str | None = None, signature: str | None = None,
+) -> Manifest:
+    """Read a manifest file; see :func:`parse_manifest`.
+
+    A file named ``*.sfv`` is read with :func:`parse_sfv` instead. An
+    ``http://`` or ``https://`` URL is fetched with the configured
+    timeout, headers and certificate checks (see :func:`komparu.configure`),
+    as is a signature next to it.
+
+    :param path: Manifest file or URL.
+    :param public_key: minisign public key file; when given, the manifest
+        is only parsed once its signature verifies.
+    :param signature: Signature file or URL; defaults to
+        ``path + ".minisig"``.
+    :raises SignatureError: If the signature does not verify.
+    :raises SourceReadError: If a URL cannot be fetched.
+    """
+    validate_path(path, "path")
+    url = is_url(path)
+    name = urllib.parse.urlsplit(path).path if url else path
+    parse = parse_sfv if name.lower().endswith(".sfv") else parse_manifest
+    if url:
+        data = fetch_url(path)
+        if public_key is not None:
+            verify_signature(data, signature or path + ".minisig", public_key)
+        return parse(data.decode("utf-8", PATH_ERRORS).splitlines(), path)
+    if public_key is None:
+        with open(path, encoding="utf-8", errors=PATH_ERRORS) as f:
+            return parse(f, path)
+    # Parse the very bytes that were verified, not a second read
+    with open(path, "rb") as f:
+        data = f.read()
+    verify_signature(data, signature or path + ".minisig", public_key)
+    return parse(data.decode("utf-8", PATH_ERRORS).splitlines(), path)
+
+
+def _guess_algorithm(digests: Mapping[str, str]) -> str:
+    lengths = {len(digest) for digest in digests.values()}
+    if len(lengths) > 1 or not lengths <= _ALGORITHM_BY_LENGTH.keys():
+        raise ValueError("cannot tell the manifest's hash algorithm; pass it explicitly")
+    return _ALGORITHM_BY_LENGTH[lengths.pop()] if lengths else "sha256"
+
+
+def _load(manifest: str | Manifest | Mapping[str, str]) -> Manifest:
+    if isinstance(manifest, str):
+        return read_manifest(manifest)
+    if isinstance(manifest, Manifest):
+        return manifest
+    return Manifest(dict(manifest))
+
+
+def compare_manifests(
+    manifest_a: str | Manifest | Mapping[str, str],
+    manifest_b: str | Manifest | Mapping[str, str],
+    *,
+    ignore: list[str] | None = None,
+) -> DirResult:
+    """Compare two manifests without touching the trees they describe.
+
+    For checking a dataset copied to an air-gapped machine: hash it on
+    both ends, carry the manifests across, and compare them anywhere.
+
+    :param manifest_a: First manifest: a file path, a :class:`Manifest`
+        or a digest per relative path.
+    :param manifest_b: Second manifest.
+    :param ignore: Glob patterns to exclude (matched per path component).
+    :returns: DirResult with ``CONTENT_MISMATCH`` for paths whose digests
+        differ and the paths only one manifest lists.
+    :raises ValueError: If a manifest is malformed, or the two were made
+        with different hash algorithms.
+    """
+    a, b = _load(manifest_a), _load(manifest_b)
+    if a.algorithm and b.algorithm and a.algorithm != b.algorithm:
+        raise ValueError(f"manifests use different hash algorithms: "
+                         f"{a.algorithm} and {b.algorithm}")
+    lengths = {len(digest) for digest in a.digests.values()}
+    lengths |= {len(digest) for digest in b.digests.values()}
+    if len(lengths) > 1:
+        raise ValueError("manifests mix digest lengths, so they use different hash algorithms")
+    exclude = make_exclude(ignore, None, "", "")
+    digests_a = {path: digest for path, digest in a.digests.items()
+                 if exclude is None or not exclude(path)}
+    digests_b = {path: digest for path, digest in b.digests.items()
+                 if exclude is None or not exclude(path)}
+    diff = {path: DiffReason.CONTENT_MISMATCH
+            for path in sorted(digests_a.keys() & digests_b.keys(), key=path_key)
+            if digests_a[path] != digests_b[path]}
+    only_left = set(digests_a.keys() - digests_b.keys())
+    only_right = set(digests_b.keys() - digests_a.keys())
+    return DirResult(equal=not (diff or only_left or only_right), diff=diff,
+                     only_left=only_left, only_right=only_right)
+
+
+def verify_manifest(
+    directory: str,
+    manifest: str | Manifest | Mapping[str, str],
+    *,
+    algorithm: str | None = None,
+    public_key: str | None = None,
+    signature: str | None = None,
+    follow_symlinks: bool = True,
+    ignore: list[str] | None = None,
+    path_filter: PathFilter | None = None,
+    max_workers: int = 0,
+) -> DirResult:
+    """Re-hash a tree and check it against a manifest.
+
+    The tree is the left side and the manifest the right, as in
+    :func:`komparu.compare_dir`: files whose digest changed are in
+    ``diff`` (``CONTENT_MISMATCH``), files the manifest does not list in
+    ``only_left`` and listed files that are gone in ``only_right``.
+
+    :param directory: Directory path.
+    :param manifest: Manifest file path or URL, a :class:`Manifest` as
+        returned by :func:`read_manifest`, or a digest per relative path.
+    :param algorithm: Hash algorithm the manifest was made with; when
+        None, the one BSD-style lines name, else guessed from the digest
+        length (md5, sha1, sha224, sha256, sha384, sha512).
+    :param public_key: minisign public key file the manifest file must be
+        signed with; see :func:`read_manifest`.
+    :param signature: Signature file; defaults to ``manifest + ".minisig"``.
+    :param follow_symlinks: Follow symbolic links during traversal.
+    :param ignore: Glob patterns to exclude (matched per path component),
+        in the tree and in the manifest.
+    :param path_filter: PathFilter deciding per entry whether it is checked.
+    :param max_workers: Thread pool size (0=auto, 1=sequential).
+    :raises ValueError: If the manifest is malformed or its algorithm
+        cannot be guessed.
+    :raises SignatureError: If the manifest's signature does not verify.
+    :raises SourcePermissionError: If a directory cannot be listed.
+    """
+    if public_key is not None:
+        if not isinstance(manifest, str):
+            raise ValueError("public_key needs the manifest as a file path")
+        manifest = read_manifest(manifest, public_key=public_key, signature=signature)
+    manifest = _load(manifest)
+    expected = dict(manifest.digests)
+    algorithm = algorithm or manifest.algorithm or _guess_algorithm(expected)
+    exclude = make_exclude(ignore, path_filter, directory, directory, follow_symlinks)
+    if exclude is not None:
+        expected = {path: digest for path, digest in expected.items() if not exclude(path)}
+
+    diff: dict[str, DiffReason] = {}
+    only_left: set[str] = set()
+    for path, digest in hash_tree(directory, algorithm=algorithm,
+                                  follow_symlinks=follow_symlinks, ignore=ignore,
+                                  path_filter=path_filter, max_workers=max_workers):
+        listed = expected.pop(path, None)
+        if listed is None:
+            only_left.add(path)
+        elif listed != digest:
+            diff[path] = DiffReason.CONTENT_MISMATCH
+    only_right = set(expected)
+    return DirResult(equal=not (diff or only_left or only_right), diff=diff,
+                     only_left=only_left, only_right=only_right)
+
+
+def _metadata_record(path: str, st: os.stat_result, attributes: Collection[str],
+                     follow_symlinks: bool) -> bytes:
+    record = b""
+    if "mode" in attributes:
+        record += b"mode=%o\n" % stat.S_IMODE(st.st_mode)
+    if "owner" in attributes:
+        record += b"owner=%d:%d\n" % (st.st_uid, st.st_gid)
+    if "xattrs" in attributes:
+        for name, value in sorted(read_xattrs(path, follow_symlinks).items()):
+            record += b"xattr=%s:%s\n" % (os.fsencode(name), value.hex().encode())
+    return record
+
+
+def tree_digest(
+    directory: str,
+    *,
+    algorithm: str = "sha256",
+    metadata: Collection[str] = (),
+    follow_symlinks: bool = True,
+    ignore: list[str] | None = None,
+    path_filter: PathFilter | None = None,
+    max_workers: int = 0,
+) -> str:
+    """One Merkle-root digest of a whole tree.
+
+    Two trees have the same digest exactly when they hold the same names,
+    entry types and file content (and, with *metadata*, the same
+    attributes), so trees on different machines compare by exchanging
+    one hash. Every entry gets a node digest ``H(kind || record || body)``,
+    with ``H`` the chosen algorithm:
+
+    * kind is one byte: ``F`` for a regular file, ``L`` for a symlink
+      (only without *follow_symlinks*, or a dangling one), ``D`` for a
+      directory;
+    * record is empty, or for each of ``mode``, ``owner``, ``xattrs``
+      picked, in that order, the lines ``mode=<octal>\\n``,
+      ``owner=<uid>:<gid>\\n`` and ``xattr=<name>:<hex value>\\n`` (one
+      per attribute, by name);
+    * body is the file's content digest, the link target, or for a
+      directory every child as ``name || 0x00 || node digest``, ordered
+      by the bytes of the name.
+
+    Names are the file-system bytes of each entry (UTF-8 on most
+    systems), so the digest does not depend on where the tree lives,
+    on traversal order or on *max_workers*. The result is the root
+    directory's node digest with an empty record: the root's own
+    metadata is not part of it. Other special files (fifos, sockets,
+    devices) are left out.
+
+    :param directory: Directory path.
+    :param algorithm: Any fixed-size :mod:`hashlib` algorithm, or
+        ``blake3``, ``xxh3``, ``xxh128``, ``crc32`` or ``crc32c``.
+    :param metadata: Which of ``"mode"``, ``"owner"`` and ``"xattrs"`` to
+        cover, as in :func:`komparu.metadata_diff`.
+    :param follow_symlinks: Follow symbolic links during traversal.
+    :param ignore: Glob patterns to exclude (matched per path component).
+    :param path_filter: PathFilter deciding per entry whether it is covered.
+    :param max_workers: Thread pool size (0=auto, 1=sequential).
+    :returns: The hex root digest.
+    :raises ValueError: If *algorithm* or an attribute is unknown.
+    :raises SourcePermissionError: If a directory cannot be listed.
+    """
+    validate_path(directory, "directory")
+    validate_algorithm(algorithm)
+    validate_attributes(metadata)
+    validate_max_workers(max_workers)
+    exclude = make_exclude(ignore, path_filter, directory, directory, follow_symlinks)
+    entries, errors = walk_tree(directory, follow_symlinks, exclude)
+    if errors:
+        raise SourcePermissionError(errno.EACCES, "cannot read",
+                                    os.path.join(directory, min(errors)))
+    entries = {path: st for path, st in entries.items()
+               if stat.S_ISREG(st.st_mode) or stat.S_ISDIR(st.st_mode)
+               or stat.S_ISLNK(st.st_mode)}
+    files = sorted(path for path, st in entries.items() if stat.S_ISREG(st.st_mode))
+    content = dict(hash_files(directory, files, algorithm, max_workers))
+
+    children: dict[str, list[tuple[bytes, str]]] = {"": []}
+    for path in entries:
+        parent, _, name = path.rpartition("/")
+        children.setdefault(parent, []).append((os.fsencode(name), path))
+    nodes: dict[str, bytes] = {}
+    # Deepest first, so every child is done before its directory
+    for path in sorted(entries, key=lambda p: p.count("/"), reverse=True) + [""]:
+        full = os.path.join(directory, path) if path else directory
+        st = entries.get(path)
+        if st is None or stat.S_ISDIR(st.st_mode):
+            kind = b"D"
+            body = b"".join(name + b"\0" + nodes.pop(child)
+                            for name, child in sorted(children.get(path, ())))
+        elif stat.S_ISREG(st.st_mode):
+            kind, body = b"F", bytes.fromhex(content[path])
+        else:
+            kind, body = b"L", os.fsencode(os.readlink(full))
+        record = b"" if st is None else _metadata_record(full, st, metadata, follow_symlinks)
+        h = new_hash(algorithm)
+        h.update(kind + record + body)
+        nodes[path] = h.digest()
+    return nodes[""].hex()
+
+
+# Sizes and mtimes of the files a manifest was last updated from, kept
+# next to it so unchanged files need not be read again. The second line
+# names the manifest the cache was written with, the last one the cache's
+# own checksum: a cache that is torn, or belongs to a manifest replaced
+# since, would vouch for digests nobody computed.
+_STAT_CACHE_HEADER = "# komparu stat cache 2"
+
+
+def _sha256(data: bytes) -> str:
+    return hashlib.sha256(data).hexdigest()
+
+
+def _read_stat_cache(path: str, manifest: bytes) -> dict[str, tuple[int, int]]:
+    try:
+        with open(path, "rb") as f:
+            data = f.read()
+    except FileNotFoundError:
+        return {}
+    end = data.rfind(b"\n", 0, -1) + 1
+    body, trailer = data[:end], data[end:]
+    lines = body.decode("utf-8", PATH_ERRORS).split("\n")[:-1]
+    # Unknown, damaged or stale: re-hash everything
+    if (lines[:2] != [_STAT_CACHE_HEADER, f"# manifest sha256 {_sha256(manifest)}"]
+            or trailer != f"# sha256 {_sha256(body)}\n".encode()):
+        return {}
+    stats: dict[str, tuple[int, int]] = {}
+    for line in lines[2:]:
+        escaped = line.startswith("\\")
+        size, _, rest = line.removeprefix("\\").partition("\t")
+        mtime, _, name = rest.partition("\t")
+        try:
+            stats[_unescape(name) if escaped else name] = (int(size), int(mtime))
+        except ValueError:
+            return {}
+    return stats
+
+
+def _stat_line(path: str, size: int, mtime_ns: int) -> str:
+    escaped, path = _escape(path)
+    line = f"{size}\t{mtime_ns}\t{path}"
+    return "\\" + line if escaped else line
+
+
+def _write_stat_cache(path: str, manifest: bytes, lines: Iterable[str]) -> None:
+    body = "".join(line + "\n" for line in [
+        _STAT_CACHE_HEADER, f"# manifest sha256 {_sha256(manifest)}", *lines])
+    data = body.encode("utf-8", PATH_ERRORS)
+    with atomic_open(path, "wb") as f:
+        f.write(data + f"# sha256 {_sha256(data)}\n".encode())
+
+
+def update_manifest(
+    directory: str,
+    manifest: str,
+    *,
+    algorithm: str | None = None,
+    tag: bool | None = None,
+    follow_symlinks: bool = True,
+    ignore: list[str] | None = None,
+    path_filter: PathFilter | None = None,
+    max_workers: int = 0,
+) -> DirResult:
+    """Bring a manifest file up to date with a tree, re-hashing only what changed.
+
+    Files whose size and mtime match what they were at the last update
+    keep their digest from the manifest; others are hashed again, new
+    files are added and vanished ones dropped. The sizes and mtimes are
+    kept in ``manifest + ".stat"``; without it (the first update) every
+    file is hashed. Both files are replaced atomically.
+
+    :param directory: Directory path.
+    :param manifest: Manifest file to update; created if missing.
+    :param algorithm: Hash algorithm; defaults to the manifest's (named
+        by BSD-style lines or guessed from the digest length), or sha256
+        for a new manifest. Digests in another algorithm are redone.
+    :param tag: Write BSD-style lines; defaults to what the manifest has.
+    :param follow_symlinks: Follow symbolic links during traversal.
+    :param ignore: Glob patterns to exclude (matched per path component).
+    :param path_filter: PathFilter deciding per entry whether it is listed.
+    :param max_workers: Thread pool size (0=auto, 1=sequential).
+    :returns: How the tree differs from the old manifest, as from
+        :func:`verify_manifest`: changed files in ``diff`` (unless the
+        algorithm changed), new ones in ``only_left``, dropped ones in
+        ``only_right``.
+    :raises ValueError: If the manifest is malformed or its algorithm
+        cannot be guessed.
+    :raises SourcePermissionError: If a directory cannot be listed.
+    """
+    validate_path(directory, "directory")
+    validate_path(manifest, "manifest")
+    validate_max_workers(max_workers)
+    try:
+        old = read_manifest(manifest)
+        with open(manifest, "rb") as f:
+            old_bytes = f.read()
+    except FileNotFoundError:
+        old, old_bytes = Manifest({}), b""
+    old_algorithm = old.algorithm
+    if old_algorithm is None and old.digests:
+        if algorithm is None:
+            old_algorithm = _guess_algorithm(old.digests)
+        elif {len(digest) for digest in old.digests.values()} == {
+                new_hash(algorithm).digest_size * 2}:
+            old_algorithm = algorithm  # GNU lines: take the caller's word for it
+    algorithm = algorithm or old_algorithm or "sha256"
+    validate_algorithm(algorithm)
+    reusable = old_algorithm == algorithm
+    if tag is None:
+        tag = old.algorithm is not None
+
+    exclude = make_exclude(ignore, path_filter, directory, directory, follow_symlinks)
+    entries, errors = walk_tree(directory, follow_symlinks, exclude)
+    if errors:
+        raise SourcePermissionError(errno.EACCES, "cannot read",
+                                    os.path.join(directory, min(errors)))
+    stats = {path: (st.st_size, st.st_mtime_ns) for path, st in entries.items()
+             if stat.S_ISREG(st.st_mode)}
+    cached = _read_stat_cache(manifest + ".stat", old_bytes) if reusable else {}
+    digests = {path: old.digests[path] for path, st in stats.items()
+               if path in old.digests and cached.get(path) == st}
+    stale = sorted(stats.keys() - digests.keys())
+    digests.update(hash_files(directory, stale, algorithm, max_workers))
+
+    paths = sorted(digests, key=path_key)
+    new_bytes = "".join(format_manifest_line(digests[path], path,
+                                             algorithm=algorithm if tag else None) + "\n"
+                        for path in paths).encode("utf-8", PATH_ERRORS)
+    # Manifest first: a crash before the cache is replaced leaves a cache
+    # naming the old manifest, which the next update ignores
+    with atomic_open(manifest, "wb") as f:
+        f.write(new_bytes)
+    _write_stat_cache(manifest + ".stat", new_bytes,
+                      (_stat_line(path, *stats[path]) for path in paths))
+
+    # Digests in another algorithm say nothing about what changed
+    diff = {path: DiffReason.CONTENT_MISMATCH for path in paths
+            if reusable and path in old.digests and old.digests[path] != digests[path]}
+    only_left = set(digests.keys() - old.digests.keys())
+    only_right = set(old.digests.keys() - digests.keys())
+    return DirResult(equal=not (diff or only_left or only_right), diff=diff,
+                     only_left=only_left, only_right=only_right)
@@ -0,0 +1,239 @@
+"""Metadata-only tree comparison: permissions, ownership, extended attributes,
+birth and change times and file flags."""
+
+from __future__ import annotations
+
+import errno
+import os
+import stat
+from collections.abc import Callable, Collection
+from typing import Any
+
+from komparu._filter import PathFilter, make_exclude
+from komparu._helpers import walk_tree
+from komparu._options import DIR_OPTION_DEFAULTS, CompareOptions, reject_overrides
+from komparu._types import DiffReason, DirResult
+from komparu._validate import validate_path
+
+try:
+    from komparu._core import birth_time as _birth_time_c
+    from komparu._core import inode_flags as _inode_flags_c
+except ImportError:  # not Linux: os.stat has them where the platform does
+    _birth_time_c = _inode_flags_c = None
+
+# Compared unless the caller picks attributes
+METADATA_ATTRIBUTES = ("mode", "owner", "xattrs")
+# Also comparable on request: copies rarely keep them unless told to
+EXTRA_ATTRIBUTES = ("birthtime", "ctime", "flags")
+# Reported but never a mismatch by itself: no copy can set it
+INFORMATIONAL_ATTRIBUTES = frozenset({"ctime"})
+
+# Filesystems without extended attributes, or entries (symlinks) that cannot have them
+_NO_XATTRS = {errno.ENOTSUP, errno.EOPNOTSUPP, errno.EPERM}
+
+
+def validate_attributes(attributes: Collection[str],
+                        known: Collection[str] = METADATA_ATTRIBUTES) -> None:
+    unknown = sorted(set(attributes) - set(known))
+    if unknown:
+        raise ValueError(f"unknown metadata attribute: {', '.join(unknown)} "
+                         f"(expected {', '.join(known)})")
+
+
+def read_xattrs(path: str, follow_symlinks: bool) -> dict[str, bytes]:
+    if not hasattr(os, "listxattr"):
+        return {}
+    try:
+        return {name: os.getxattr(path, name, follow_symlinks=follow_symlinks)
+                for name in os.listxattr(path, follow_symlinks=follow_symlinks)}
+    except OSError as exc:
+        if exc.errno in _NO_XATTRS:
+            return {}
+        raise
+
+
+def read_birthtime(path: str, st: os.stat_result, follow_symlinks: bool) -> int | None:
+    """Creation time in nanoseconds, or None where the file system does not keep it."""
+    birth = getattr(st, "st_birthtime_ns", None)
+    if birth is None and hasattr(st, "st_birthtime"):  # macOS and the BSDs
+        birth = int(st.st_birthtime * 1_000_000_000)
+    if birth is None and _birth_time_c is not None:
+        birth = _birth_time_c(path, follow_symlinks)
+    return birth
+
+
+def read_flags(path: str, st: os.stat_result, follow_symlinks: bool) -> int:
+    """chflags flags (BSD, macOS) or chattr inode flags (Linux); 0 where there are none."""
+    if hasattr(st, "st_flags"):
+        return st.st_flags
+    if _inode_flags_c is not None and (stat.S_ISREG(st.st_mode) or stat.S_ISDIR(st.st_mode)):
+        return _inode_flags_c(path, follow_symlinks)
+    return 0
+
+
+def _differences(
+    path_a: str,
+    path_b: str,
+    st_a: os.stat_result,
+    st_b: os.stat_result,
+    attributes: Collection[str],
+    follow_symlinks: bool,
+) -> dict[str, tuple[Any, Any]]:
+    changes: dict[str, tuple[Any, Any]] = {}
+    if "mode" in attributes and stat.S_IMODE(st_a.st_mode) != stat.S_IMODE(st_b.st_mode):
+        changes["mode"] = (stat.S_IMODE(st_a.st_mode), stat.S_IMODE(st_b.st_mode))
+    if "owner" in attributes and (st_a.st_uid, st_a.st_gid) != (st_b.st_uid, st_b.st_gid):
+        changes["owner"] = ((st_a.st_uid, st_a.st_gid), (st_b.st_uid, st_b.st_gid))
+    if "xattrs" in attributes:
+        xattrs_a = read_xattrs(path_a, follow_symlinks)
+        xattrs_b = read_xattrs(path_b, follow_symlinks)
+        if xattrs_a != xattrs_b:
+            changes["xattrs"] = (xattrs_a, xattrs_b)
+    if "birthtime" in attributes:
+        birth_a = read_birthtime(path_a, st_a, follow_symlinks)
+        birth_b = read_birthtime(path_b, st_b, follow_symlinks)
+        if birth_a != birth_b:
+            changes["birthtime"] = (birth_a, birth_b)
+    if "ctime" in attributes and st_a.st_ctime_ns != st_b.st_ctime_ns:
+        changes["ctime"] = (st_a.st_ctime_ns, st_b.st_ctime_ns)
+    if "flags" in attributes:
+        flags_a = read_flags(path_a, st_a, follow_symlinks)
+        flags_b = read_flags(path_b, st_b, follow_symlinks)
+        if flags_a != flags_b:
+            changes["flags"] = (flags_a, flags_b)
+    return changes
+
+
+def metadata_diff(
+    path_a: str,
+    path_b: str,
+    *,
+    attributes: Collection[str] = METADATA_ATTRIBUTES,
+    follow_symlinks: bool = True,
+) -> dict[str, tuple[Any, Any]]:
+    """Permissions, ownership and other metadata that differ between two entries.
+
+    :param path_a: First path (file, directory or symlink).
+    :param path_b: Second path.
+    :param attributes: Which of ``"mode"`` (permission bits, including
+        setuid, setgid and sticky), ``"owner"`` (uid and gid) and
+        ``"xattrs"`` (extended attributes, where the platform has them)
+        to compare — all three by default — and, only when named,
+        ``"birthtime"`` (creation time), ``"ctime"`` (inode change time)
+        and ``"flags"`` (``chflags`` flags on BSD and macOS, ``chattr``
+        flags such as immutable and append-only on Linux).
+    :param follow_symlinks: Compare what symlinks point to rather than
+        the links themselves.
+    :returns: The differing attributes, each as a ``(value_a, value_b)``
+        pair: mode and flags as ints, owner as ``(uid, gid)``, xattrs as
+        a dict of name to value, times in nanoseconds since the epoch
+        (birthtime None where the file system does not record it). Empty
+        when the metadata matches.
+    :raises ValueError: If *attributes* names an unknown attribute.
+    """
+    validate_path(path_a, "path_a")
+    validate_path(path_b, "path_b")
+    validate_attributes(attributes, METADATA_ATTRIBUTES + EXTRA_ATTRIBUTES)
+    st_a = os.stat(path_a, follow_symlinks=follow_symlinks)
+    st_b = os.stat(path_b, follow_symlinks=follow_symlinks)
+    return _differences(path_a, path_b, st_a, st_b, attributes, follow_symlinks)
+
+
+def is_under(path: str, roots: set[str]) -> bool:
+    parent = path.rpartition("/")[0]
+    while parent:
+        if parent in roots:
+            return True
+        parent = parent.rpartition("/")[0]
+    return False
+
+
+def compare_dir_metadata(
+    dir_a: str,
+    dir_b: str,
+    *,
+    attributes: Collection[str] | Callable[[str], Collection[str]] = METADATA_ATTRIBUTES,
+    follow_symlinks: bool = True,
+    ignore: list[str] | None = None,
+    path_filter: PathFilter | None = None,
+    options: CompareOptions | None = None,
+) -> DirResult:
+    """Compare the metadata of two trees without reading file content.
+
+    Every entry — files, directories and symlinks — is compared by the
+    :func:`metadata_diff` attributes; content is never read, so auditing
+    a deployed system against a golden image takes as long as walking
+    and stat-ing both trees. A directory only on one side is reported
+    once, not entry by entry.
+
+    :param dir_a: Path to first directory.
+    :param dir_b: Path to second directory.
+    :param attributes: Attributes to compare, as in :func:`metadata_diff`.
+        A ``"ctime"`` difference alone is not a mismatch: copying a file
+        always gives it a new change time, so it only adds to what
+        :func:`metadata_diff` reports for entries that differ otherwise. A
+        callable is given each relative path and returns its attributes.
+    :param follow_symlinks: Follow symbolic links during traversal.
+    :param ignore: Glob patterns to exclude (matched per path component).
+    :param path_filter: PathFilter deciding per entry, with metadata, whether
+        it is reported.
+    :param options: CompareOptions bundle; replaces ``follow_symlinks``,
+        ``ignore`` and ``path_filter``, and *attributes* if it sets ``metadata``.
+    :returns: DirResult whose ``diff`` holds ``METADATA_MISMATCH`` entries,
+        and ``TYPE_MISMATCH`` for paths that are a file on one side and a
+        directory on the other.
+    :raises ValueError: If *attributes* names an unknown attribute.
+    """
+    if options is not None:
+        replaced = dict(DIR_OPTION_DEFAULTS)
+        if options.metadata is not None:
+            replaced["attributes"] = METADATA_ATTRIBUTES
+        reject_overrides("compare_dir_metadata", locals(), replaced)
+        if options.metadata is not None:
+            attributes = options.metadata
+        follow_symlinks = options.follow_symlinks
+        ignore = list(options.ignore) or None
+        path_filter = options.path_filter
+    validate_path(dir_a, "dir_a")
+    validate_path(dir_b, "dir_b")
+    known = METADATA_ATTRIBUTES + EXTRA_ATTRIBUTES
+    if not callable(attributes):
+        validate_attributes(attributes, known)
+    exclude = make_exclude(ignore, path_filter, dir_a, dir_b, follow_symlinks)
+
+    entries_a, errors_a = walk_tree(dir_a, follow_symlinks, exclude)
+    entries_b, errors_b = walk_tree(dir_b, follow_symlinks, exclude)
+    errors = errors_a | errors_b
+    diff: dict[str, DiffReason] = {}
+    for path in sorted(entries_a.keys() & entries_b.keys()):
+        st_a, st_b = entries_a[path], entries_b[path]
+        if stat.S_IFMT(st_a.st_mode) != stat.S_IFMT(st_b.st_mode):
+            diff[path] = DiffReason.TYPE_MISMATCH
+            continue
+        names = attributes
+        if callable(attributes):
+            names = attributes(path)
+            validate_attributes(names, known)
+        decisive = [name for name in names if name not in INFORMATIONAL_ATTRIBUTES]
+        try:
+            if _differences(os.path.join(dir_a, path), os.path.join(dir_b, path),
+                            st_a, st_b, decisive, follow_symlinks):
+                diff[path] = DiffReason.METADATA_MISMATCH
+        except PermissionError:
+            errors.add(path)
+
+    # Whatever is under a directory missing from, or a file on, the other
+    # side is reported through that directory
+    only_left = set(entries_a.keys() - entries_b.keys())
+    only_right = set(entries_b.keys() - entries_a.keys())
+    reported = only_left | only_right
+    reported |= {path for path, reason in diff.items() if reason is DiffReason.TYPE_MISMATCH}
+    only_left = {path for path in only_left if not is_under(path, reported)}
+    only_right = {path for path in only_right if not is_under(path, reported)}
+    return DirResult(
+        equal=not (diff or only_left or only_right or errors),
+        diff=diff,
+        only_left=only_left,
+        only_right=only_right,
+        errors=errors,
+    )
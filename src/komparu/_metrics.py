@@ -0,0 +1,194 @@
+"""Metrics hook for directory comparison."""
+
+from __future__ import annotations
+
+import threading
+import time
+
+from komparu._types import DiffEntry, DiffReason, EntryKind
+from komparu._helpers import stat_entry
+from komparu._visitor import CompareVisitor
+
+
+class MetricsSink:
+    """Receive counters and phase timings from a directory comparison.
+
+    Subclass and forward to your metrics system (StatsD, Prometheus,
+    OpenTelemetry, ...), then pass an instance as ``metrics=`` to
+    :func:`komparu.compare_dir` or :func:`komparu.aio.compare_dir`.
+    Both methods default to no-ops and are called in the calling thread
+    (on the event loop for async).
+
+    Counters: ``files_compared``, ``files_different``, ``only_left``,
+    ``only_right``, ``errors``, ``retries_exhausted``, ``bytes_compared``.
+    Timings (seconds): ``walk`` (traversal and merge, until the first
+    common file starts), ``compare`` (content phase) and ``total``.
+    """
+
+    def count(self, name: str, value: int = 1) -> None:
+        """Add *value* to counter *name*."""
+
+    def timing(self, name: str, seconds: float) -> None:
+        """Record that phase *name* took *seconds*."""
+
+
+# MetricsSink counters with their Prometheus help text
+_COUNTERS = {
+    "files_compared": "Common files whose content was compared.",
+    "files_different": "Common files that differed.",
+    "only_left": "Entries found only on the left side.",
+    "only_right": "Entries found only on the right side.",
+    "errors": "Entries skipped because they could not be read.",
+    "retries_exhausted": "Files that failed through every retry.",
+    "bytes_compared": "Bytes of common files whose content was compared.",
+}
+OUTCOMES = ("equal", "different", "failed", "cancelled")
+
+
+class PrometheusMetrics(MetricsSink):
+    """MetricsSink keeping totals across comparisons, for Prometheus to scrape.
+
+    Pass one instance as ``metrics=`` to every comparison of a
+    long-running process and serve :meth:`render` at ``/metrics``.
+    Counters become ``komparu_<name>_total``; the latest phase timings
+    ``komparu_last_phase_seconds{phase=...}``. Comparisons reported
+    through :meth:`started` and :meth:`finished` also give
+    ``komparu_comparisons_total{outcome=...}``, ``komparu_comparisons_running``
+    and the duration, end time and age of the latest run. Safe to share
+    between threads.
+    """
+
+    __slots__ = ("_lock", "_counters", "_phases", "_outcomes", "_running", "_last")
+
+    def __init__(self) -> None:
+        self._lock = threading.Lock()
+        self._counters = dict.fromkeys(_COUNTERS, 0)
+        self._phases: dict[str, float] = {}
+        self._outcomes = dict.fromkeys(OUTCOMES, 0)
+        self._running = 0
+        self._last: tuple[float, float] | None = None  # duration, end time
+
+    def count(self, name: str, value: int = 1) -> None:
+        with self._lock:
+            self._counters[name] = self._counters.get(name, 0) + value
+
+    def timing(self, name: str, seconds: float) -> None:
+        with self._lock:
+            self._phases[name] = seconds
+
+    def started(self) -> None:
+        """Note that a comparison began."""
+        with self._lock:
+            self._running += 1
+
+    def finished(self, outcome: str, seconds: float) -> None:
+        """Note that a comparison begun with :meth:`started` ended after *seconds*.
+
+        :param outcome: One of ``equal``, ``different``, ``failed`` or
+            ``cancelled``.
+        """
+        if outcome not in OUTCOMES:
+            raise ValueError(f"outcome must be one of {', '.join(OUTCOMES)}, got {outcome!r}")
+        with self._lock:
+            self._running -= 1
+            self._outcomes[outcome] += 1
+            self._last = (seconds, time.time())
+
+    def render(self) -> str:
+        """The metrics in the Prometheus text exposition format."""
+        with self._lock:
+            counters, phases = dict(self._counters), dict(self._phases)
+            outcomes, running, last = dict(self._outcomes), self._running, self._last
+        lines = []
+
+        def metric(name: str, kind: str, help: str, samples: list[tuple[str, float]]) -> None:
+            lines.extend([f"# HELP komparu_{name} {help}", f"# TYPE komparu_{name} {kind}"])
+            lines.extend(f"komparu_{name}{labels} {value}" for labels, value in samples)
+
+        for name, value in counters.items():
+            metric(f"{name}_total", "counter", _COUNTERS.get(name, f"{name} counted."),
+                   [("", value)])
+        metric("comparisons_total", "counter", "Comparisons finished, by outcome.",
+               [(f'{{outcome="{outcome}"}}', n) for outcome, n in outcomes.items()])
+        metric("comparisons_running", "gauge", "Comparisons in progress.", [("", running)])
+        if phases:
+            metric("last_phase_seconds", "gauge", "Phase timings of the latest directory "
+                   "comparison.", [(f'{{phase="{phase}"}}', s) for phase, s in phases.items()])
+        if last is not None:
+            metric("last_run_duration_seconds", "gauge", "Duration of the latest comparison.",
+                   [("", last[0])])
+            metric("last_run_timestamp_seconds", "gauge", "Unix time the latest comparison "
+                   "ended.", [("", last[1])])
+            metric("last_run_age_seconds", "gauge", "Seconds since the latest comparison ended.",
+                   [("", time.time() - last[1])])
+        return "\n".join(lines) + "\n"
+
+
+class MetricsVisitor(CompareVisitor):
+    """Derive MetricsSink counters and timings from visitor callbacks.
+
+    Forwards every callback to *inner* (if any) first. Call :meth:`close`
+    once the comparison has ended, successfully or not.
+    """
+
+    __slots__ = ("_sink", "_base", "_inner", "_start", "_compare_start")
+
+    def __init__(self, sink: MetricsSink, base_dir: str | int,
+                 inner: CompareVisitor | None = None) -> None:
+        if not isinstance(sink, MetricsSink):
+            raise TypeError(f"metrics must be a MetricsSink, got {type(sink).__name__}")
+        self._sink = sink
+        self._base = base_dir
+        self._inner = inner
+        self._start = time.perf_counter()
+        self._compare_start: float | None = None
+
+    def on_dir_entered(self, path: str) -> None:
+        if self._inner is not None:
+            self._inner.on_dir_entered(path)
+
+    def on_dir_left(self, path: str) -> None:
+        if self._inner is not None:
+            self._inner.on_dir_left(path)
+
+    def on_entry_started(self, path: str) -> None:
+        if self._inner is not None:
+            self._inner.on_entry_started(path)
+        if self._compare_start is None:
+            self._compare_start = time.perf_counter()
+            self._sink.timing("walk", self._compare_start - self._start)
+
+    def on_entry_finished(self, entry: DiffEntry) -> None:
+        if self._inner is not None:
+            self._inner.on_entry_finished(entry)
+        sink = self._sink
+        if entry.kind is EntryKind.ONLY_LEFT:
+            sink.count("only_left")
+            return
+        if entry.kind is EntryKind.ONLY_RIGHT:
+            sink.count("only_right")
+            return
+        sink.count("files_compared")
+        if entry.kind is EntryKind.DIFF:
+            sink.count("files_different")
+        if entry.reason is DiffReason.RETRIES_EXHAUSTED:
+            sink.count("retries_exhausted")
+        if entry.kind is EntryKind.EQUAL or entry.reason is DiffReason.CONTENT_MISMATCH:
+            try:
+                sink.count("bytes_compared", stat_entry(self._base, entry.path).st_size)
+            except OSError:
+                pass  # vanished since it was compared
+
+    def on_error(self, entry: DiffEntry) -> None:
+        if self._inner is not None:
+            self._inner.on_error(entry)
+        self._sink.count("errors")
+
+    def close(self) -> None:
+        """Record the remaining phase timings."""
+        end = time.perf_counter()
+        if self._compare_start is None:
+            self._sink.timing("walk", end - self._start)
+        else:
+            self._sink.timing("compare", end - self._compare_start)
+        self._sink.timing("total", end - self._start)
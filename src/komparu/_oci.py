@@ -0,0 +1,408 @@
+"""Container images, OCI layouts and ``docker save`` archives, flattened into trees."""
+
+from __future__ import annotations
+
+import errno
+import hashlib
+import json
+import os
+import posixpath
+import stat
+import tarfile
+from collections.abc import Iterator
+from concurrent.futures import ThreadPoolExecutor
+from typing import IO, Any, NamedTuple
+
+from komparu._filter import make_exclude
+from komparu._hashing import file_digest
+from komparu._helpers import walk_tree
+from komparu._metadata import is_under
+from komparu._types import (
+    ArchiveError,
+    DiffReason,
+    DirResult,
+    SourceNotFoundError,
+    SourcePermissionError,
+    UnsupportedSourceError,
+)
+from komparu._validate import validate_max_workers, validate_path
+
+_WHITEOUT = ".wh."
+_OPAQUE = ".wh..wh..opq"
+_INDEX_TYPES = {
+    "application/vnd.oci.image.index.v1+json",
+    "application/vnd.docker.distribution.manifest.list.v2+json",
+}
+_REF_NAME = "org.opencontainers.image.ref.name"
+_IMAGE_NAME = "io.containerd.image.name"
+_ZSTD_MAGIC = b"\x28\xb5\x2f\xfd"
+
+
+class _Entry(NamedTuple):
+    kind: str  # file, dir, symlink or other
+    mode: int
+    uid: int
+    gid: int
+    size: int
+    digest: str | None  # SHA-256 of a file
+    target: str | None  # of a symlink
+
+
+def split_image_ref(spec: str) -> tuple[str, str | None]:
+    """``(path, tag)`` of ``path`` or ``path:tag``; *path* exists, the tag may hold ``:``."""
+    if os.path.exists(spec):
+        return spec, None
+    for index, char in enumerate(spec):
+        if char == ":" and os.path.exists(spec[:index]):
+            return spec[:index], spec[index + 1:]
+    raise SourceNotFoundError(errno.ENOENT, "no such image", spec)
+
+
+class _Blobs:
+    """Files of an image layout, in a directory or a tar archive of one."""
+
+    def __init__(self, path: str) -> None:
+        self.path = path
+        self._tar = None if os.path.isdir(path) else _open_tar(path)
+
+    def exists(self, name: str) -> bool:
+        if self._tar is None:
+            return os.path.isfile(os.path.join(self.path, name))
+        try:
+            return self._tar.getmember(name).isfile()
+        except KeyError:
+            return False
+
+    def open(self, name: str) -> IO[bytes]:
+        if self._tar is None:
+            return open(os.path.join(self.path, name), "rb")
+        try:
+            f = self._tar.extractfile(name)
+        except KeyError:
+            f = None
+        if f is None:
+            raise ArchiveError(errno.ENOENT, f"image has no {name}", self.path)
+        return f
+
+    def json(self, name: str) -> Any:
+        with self.open(name) as f:
+            try:
+                return json.load(f)
+            except (ValueError, UnicodeDecodeError) as exc:
+                raise ArchiveError(errno.EINVAL, f"{name} is not JSON: {exc}",
+                                   self.path) from None
+
+    def close(self) -> None:
+        if self._tar is not None:
+            self._tar.close()
+
+
+def _open_tar(path: str) -> tarfile.TarFile:
+    try:
+        return tarfile.open(path, "r:*")
+    except tarfile.TarError:
+        raise ArchiveError(errno.EINVAL, "not an OCI layout or docker save archive",
+                           path) from None
+
+
+def _blob_name(digest: str) -> str:
+    algorithm, _, hexdigest = digest.partition(":")
+    return f"blobs/{algorithm}/{hexdigest}"
+
+
+def _platform_name(descriptor: dict[str, Any]) -> str:
+    platform = descriptor.get("platform", {})
+    parts = [platform.get("os", "unknown"), platform.get("architecture", "unknown")]
+    if platform.get("variant"):
+        parts.append(platform["variant"])
+    return "/".join(parts)
+
+
+def _pick(candidates: list[Any], names: list[set[str]], ref: str | None, source: str) -> Any:
+    if ref is not None:
+        candidates = [c for c, known in zip(candidates, names)
+                      if ref in known or any(name.endswith(("/" + ref, ":" + ref))
+                                             for name in known)]
+        if not candidates:
+            raise SourceNotFoundError(errno.ENOENT, f"image has no tag {ref}", source)
+    if len(candidates) != 1:
+        raise ValueError(f"{source} holds {len(candidates)} images; name one as PATH:TAG")
+    return candidates[0]
+
+
+def _layers(blobs: _Blobs, ref: str | None, platform: str | None) -> list[str]:
+    """Names of the image's layer blobs, lowest first."""
+    source = blobs.path
+    if blobs.exists("index.json"):  # OCI layout; docker save writes one too since 25.0
+        descriptors = blobs.json("index.json").get("manifests", [])
+        descriptor = _pick(descriptors, [
+            {value for key, value in d.get("annotations", {}).items()
+             if key in (_REF_NAME, _IMAGE_NAME)} for d in descriptors], ref, source)
+        while descriptor.get("mediaType") in _INDEX_TYPES:
+            descriptors = [d for d in blobs.json(_blob_name(descriptor["digest"]))["manifests"]
+                           if _platform_name(d) != "unknown/unknown"]  # attestations
+            if platform is not None:
+                # linux/arm64 also picks linux/arm64/v8
+                descriptors = [d for d in descriptors if _platform_name(d) == platform
+                               or _platform_name(d).startswith(platform + "/")]
+            if len(descriptors) != 1:
+                platforms = ", ".join(sorted(_platform_name(d) for d in descriptors)) or "none"
+                raise ValueError(f"{source} has no single image for "
+                                 f"{platform or 'any platform'} ({platforms}); "
+                                 "pick one with platform=")
+            descriptor = descriptors[0]
+        manifest = blobs.json(_blob_name(descriptor["digest"]))
+        return [_blob_name(layer["digest"]) for layer in manifest.get("layers", [])]
+    if blobs.exists("manifest.json"):  # docker save before 25.0
+        images = blobs.json("manifest.json")
+        image = _pick(images, [set(i.get("RepoTags") or ()) for i in images], ref, source)
+        return list(image["Layers"])
+    raise ArchiveError(errno.EINVAL, "not an OCI layout or docker save archive", source)
+
+
+def _zstd_reader(f: IO[bytes], source: str) -> IO[bytes]:
+    try:
+        from compression import zstd  # Python 3.14+
+        return zstd.ZstdFile(f)
+    except ImportError:
+        pass
+    try:
+        import zstandard
+    except ImportError:
+        raise UnsupportedSourceError(
+            errno.ENOTSUP, "zstd layers need Python 3.14 or pip install komparu[zstd]",
+            source) from None
+    return zstandard.ZstdDecompressor().stream_reader(f)  # type: ignore[no-any-return]
+
+
+def _members(f: IO[bytes], source: str) -> Iterator[tuple[tarfile.TarInfo, tarfile.TarFile]]:
+    """Members of a layer tarball, whatever it is compressed with, read as a stream."""
+    peek = getattr(f, "peek", None)
+    if peek is not None and peek(4)[:4] == _ZSTD_MAGIC:
+        layer = tarfile.open(fileobj=_zstd_reader(f, source), mode="r|")
+    else:
+        layer = tarfile.open(fileobj=f, mode="r|*")
+    with layer:
+        for member in layer:
+            yield member, layer
+
+
+def _entry(member: tarfile.TarInfo, layer: tarfile.TarFile) -> _Entry:
+    digest = target = None
+    if member.isreg():
+        kind = "file"
+        h = hashlib.sha256()
+        f = layer.extractfile(member)
+        assert f is not None
+        while chunk := f.read(1024 * 1024):
+            h.update(chunk)
+        digest = h.hexdigest()
+    elif member.isdir():
+        kind = "dir"
+    elif member.issym():
+        kind, target = "symlink", member.linkname
+    else:
+        kind = "other"
+    return _Entry(kind, stat.S_IMODE(member.mode), member.uid, member.gid,
+                  member.size if kind == "file" else 0, digest, target)
+
+
+def _clean(name: str) -> str:
+    return posixpath.normpath("/" + name).lstrip("/")
+
+
+def flatten_image(path: str, ref: str | None = None, platform: str | None = None
+                  ) -> dict[str, _Entry]:
+    """The file system an image's layers stack up to, by relative path.
+
+    Whiteouts (``.wh.name``) delete what lower layers put at that path,
+    and an opaque whiteout (``.wh..wh..opq``) what they put in its
+    directory, as overlay file systems apply them.
+    """
+    blobs = _Blobs(path)
+    try:
+        tree: dict[str, _Entry] = {}
+        for name in _layers(blobs, ref, platform):
+            added: dict[str, _Entry] = {}
+            removed: set[str] = set()
+            opaque: set[str] = set()
+            with blobs.open(name) as f:
+                try:
+                    for member, layer in _members(f, path):
+                        rel = _clean(member.name)
+                        parent, base = posixpath.split(rel)
+                        if base == _OPAQUE:
+                            opaque.add(parent)
+                        elif base.startswith(_WHITEOUT):
+                            removed.add(posixpath.join(parent, base[len(_WHITEOUT):]))
+                        elif member.islnk():  # a hard link to an earlier member
+                            linked = _clean(member.linkname)
+                            entry = added.get(linked) or tree.get(linked)
+                            if entry is not None:
+                                added[rel] = entry
+                        elif rel:
+                            added[rel] = _entry(member, layer)
+                except (tarfile.TarError, EOFError) as exc:
+                    raise ArchiveError(errno.EINVAL, f"layer {name} is unreadable: {exc}",
+                                       path) from None
+            # A file or link replacing a directory hides what was in it too
+            hidden = removed | {rel for rel, entry in added.items() if entry.kind != "dir"}
+            tree = {rel: entry for rel, entry in tree.items()
+                    if rel not in removed and not is_under(rel, hidden)
+                    and "" not in opaque and not is_under(rel, opaque)}
+            tree.update(added)
+        return tree
+    finally:
+        blobs.close()
+
+
+def _diff_entries(a: _Entry, b: _Entry, metadata: bool) -> DiffReason | None:
+    if a.kind != b.kind:
+        return DiffReason.TYPE_MISMATCH
+    if a.kind == "symlink" and a.target != b.target:
+        return DiffReason.CONTENT_MISMATCH
+    if a.size != b.size:
+        return DiffReason.SIZE_MISMATCH
+    if a.digest != b.digest:
+        return DiffReason.CONTENT_MISMATCH
+    if metadata and (a.mode, a.uid, a.gid) != (b.mode, b.uid, b.gid):
+        return DiffReason.METADATA_MISMATCH
+    return None
+
+
+def _result(tree_a: dict[str, _Entry], tree_b: dict[str, _Entry],
+            diff: dict[str, DiffReason]) -> DirResult:
+    # Directories count through what is in them, as in a git tree
+    only_left = {p for p in tree_a.keys() - tree_b.keys() if tree_a[p].kind != "dir"}
+    only_right = {p for p in tree_b.keys() - tree_a.keys() if tree_b[p].kind != "dir"}
+    return DirResult(equal=not (diff or only_left or only_right), diff=diff,
+                     only_left=only_left, only_right=only_right)
+
+
+def _flattened(image: str, platform: str | None, ignore: list[str] | None
+               ) -> dict[str, _Entry]:
+    path, ref = split_image_ref(image)
+    tree = flatten_image(path, ref, platform)
+    exclude = make_exclude(ignore, None, path, path)
+    if exclude is None:
+        return tree
+    return {rel: entry for rel, entry in tree.items() if not exclude(rel)}
+
+
+def compare_images(
+    image_a: str,
+    image_b: str,
+    *,
+    platform: str | None = None,
+    metadata: bool = True,
+    ignore: list[str] | None = None,
+) -> DirResult:
+    """Compare the file systems of two container images.
+
+    Each image's layers are stacked as a container runtime would, with
+    whiteouts deleting lower files and opaque directories hiding lower
+    contents, and the resulting trees are compared: files by size and
+    SHA-256, symlinks by target. Layers are read as streams, once, and
+    nothing is extracted to disk; gzip, xz and zstd layers are
+    supported (zstd needs Python 3.14 or ``pip install komparu[zstd]``).
+
+    An image is an OCI image layout directory, an OCI archive, or a
+    ``docker save`` tarball, optionally followed by ``:TAG`` to pick an
+    image of several in it (``layout:v2``, ``images.tar:app:1.4``), so
+    two tags of one layout can be compared.
+
+    :param image_a: Left image, ``PATH`` or ``PATH:TAG``.
+    :param image_b: Right image.
+    :param platform: ``os/arch[/variant]`` of a multi-platform image's
+        manifest to use, e.g. ``"linux/arm64"``; needed when an index
+        has several.
+    :param metadata: Report files whose permissions or owner changed as
+        ``METADATA_MISMATCH``.
+    :param ignore: Glob patterns to exclude (matched per path component).
+    :raises SourceNotFoundError: If an image or tag does not exist.
+    :raises ArchiveError: If an image or one of its layers is malformed.
+    :raises UnsupportedSourceError: For zstd layers zstd is missing for.
+    :raises ValueError: If a tag or platform is needed to pick an image.
+    """
+    tree_a = _flattened(image_a, platform, ignore)
+    tree_b = _flattened(image_b, platform, ignore)
+    diff: dict[str, DiffReason] = {}
+    for path in tree_a.keys() & tree_b.keys():
+        reason = _diff_entries(tree_a[path], tree_b[path], metadata)
+        if reason is not None:
+            diff[path] = reason
+    return _result(tree_a, tree_b, diff)
+
+
+def _kind(st: os.stat_result) -> str:
+    if stat.S_ISREG(st.st_mode):
+        return "file"
+    if stat.S_ISDIR(st.st_mode):
+        return "dir"
+    return "symlink" if stat.S_ISLNK(st.st_mode) else "other"
+
+
+def compare_image_dir(
+    image: str,
+    directory: str,
+    *,
+    platform: str | None = None,
+    follow_symlinks: bool = False,
+    ignore: list[str] | None = None,
+    max_workers: int = 0,
+) -> DirResult:
+    """Compare a container image's file system with a directory.
+
+    The image is flattened as by :func:`compare_images` and checked
+    against the directory, e.g. a root file system the image was built
+    from or extracted to: types, symlink targets, sizes, then SHA-256 of
+    local files of the image's size. Permissions and owners are not
+    compared, as they rarely survive extraction by an unprivileged user.
+
+    The image is the left side and the directory the right.
+
+    :param image: Image, ``PATH`` or ``PATH:TAG``.
+    :param directory: Directory path.
+    :param platform: ``os/arch[/variant]`` of a multi-platform image.
+    :param follow_symlinks: Follow symbolic links in the directory (the
+        image stores links as links, so off by default).
+    :param ignore: Glob patterns to exclude (matched per path component),
+        on both sides.
+    :param max_workers: Thread pool size for hashing (0=auto, 1=sequential).
+    :raises SourceNotFoundError: If the image or tag does not exist.
+    :raises ArchiveError: If the image or one of its layers is malformed.
+    :raises SourcePermissionError: If a directory cannot be listed.
+    """
+    validate_path(directory, "directory")
+    validate_max_workers(max_workers)
+    tree = _flattened(image, platform, ignore)
+    exclude = make_exclude(ignore, None, directory, directory, follow_symlinks)
+    entries, errors = walk_tree(directory, follow_symlinks, exclude)
+    if errors:
+        raise SourcePermissionError(errno.EACCES, "cannot read",
+                                    os.path.join(directory, min(errors)))
+
+    local: dict[str, _Entry] = {}
+    diff: dict[str, DiffReason] = {}
+    to_hash: list[str] = []
+    for path, st in entries.items():
+        kind = _kind(st)
+        target = (os.readlink(os.path.join(directory, path))
+                  if kind == "symlink" and path in tree else None)
+        local[path] = _Entry(kind, 0, 0, 0, st.st_size if kind == "file" else 0, None, target)
+    for path in sorted(tree.keys() & local.keys()):
+        image_entry, entry = tree[path], local[path]
+        if image_entry.kind == entry.kind == "file" and image_entry.size == entry.size:
+            to_hash.append(path)
+        elif (reason := _diff_entries(image_entry, entry, False)) is not None:
+            diff[path] = reason
+
+    def digest(path: str) -> str:
+        return file_digest(os.path.join(directory, path), "sha256")
+
+    workers = max_workers or min(len(to_hash), 8) or 1
+    with ThreadPoolExecutor(max_workers=workers) as pool:
+        for path, hexdigest in zip(to_hash, pool.map(digest, to_hash)):
+            if hexdigest != tree[path].digest:
+                diff[path] = DiffReason.CONTENT_MISMATCH
+    return _result(tree, local, diff)
@@ -0,0 +1,255 @@
+"""Reusable comparison options."""
+
+from __future__ import annotations
+
+import dataclasses
+from collections.abc import Mapping
+from dataclasses import dataclass, field
+from typing import Any
+
+from komparu._filter import PathFilter
+from komparu._types import DiffReason, DirResult, Severity
+from komparu._validate import validate_chunk_size, validate_timeout, validate_max_workers
+
+
+@dataclass(frozen=True, slots=True)
+class RetryPolicy:
+    """Retry transient I/O errors during directory comparison.
+
+    Transient means EAGAIN, EINTR, EBUSY or ESTALE (e.g. a flaky NFS
+    mount). Each failed read is retried in place; if the file still fails,
+    its comparison is re-run from the start. Backoff starts at *delay* and
+    doubles up to *max_delay*. A file that fails through every retry is
+    reported with ``DiffReason.RETRIES_EXHAUSTED``.
+
+    :param retries: Extra attempts per read and per file.
+    :param delay: First backoff in seconds.
+    :param max_delay: Backoff cap in seconds.
+    """
+
+    retries: int = 3
+    delay: float = 0.1
+    max_delay: float = 2.0
+
+    def __post_init__(self) -> None:
+        if not isinstance(self.retries, int) or isinstance(self.retries, bool):
+            raise TypeError("retries must be an integer")
+        if self.retries < 0:
+            raise ValueError("retries must be non-negative")
+        if self.delay < 0 or self.max_delay < 0:
+            raise ValueError("retry delays must be non-negative")
+
+
+def retry_kwargs(retry: RetryPolicy | None) -> dict[str, Any]:
+    """Keyword arguments passing *retry* to the C core (empty if None)."""
+    if retry is None:
+        return {}
+    if not isinstance(retry, RetryPolicy):
+        raise TypeError(f"retry must be a RetryPolicy, got {type(retry).__name__}")
+    return {
+        "retries": retry.retries,
+        "retry_delay": retry.delay,
+        "retry_max_delay": retry.max_delay,
+    }
+
+
+@dataclass(frozen=True, slots=True)
+class LockPolicy:
+    """Read files under shared advisory locks during directory comparison.
+
+    Each pair of common files is locked shared (``flock`` on Unix,
+    ``LockFileEx`` on Windows) before it is read, so a writer that takes
+    an exclusive lock while it modifies a file never has it compared
+    mid-write. A pair still locked by a writer after *timeout* is skipped
+    and reported with ``DiffReason.LOCKED``. Files on file systems that
+    cannot lock are compared unlocked.
+
+    :param timeout: Seconds to wait for a writer to let go of a file;
+        None waits as long as it takes, 0 skips locked files at once.
+    """
+
+    timeout: float | None = None
+
+    def __post_init__(self) -> None:
+        if self.timeout is not None and self.timeout < 0:
+            raise ValueError("lock timeout must be non-negative")
+
+
+def lock_kwargs(lock: LockPolicy | None) -> dict[str, Any]:
+    """Keyword arguments passing *lock* to the C core (empty if None)."""
+    if lock is None:
+        return {}
+    if not isinstance(lock, LockPolicy):
+        raise TypeError(f"lock must be a LockPolicy, got {type(lock).__name__}")
+    return {"lock": True, "lock_timeout": -1.0 if lock.timeout is None else lock.timeout}
+
+
+SEVERITY_CATEGORIES = (*(r.value for r in DiffReason), "only_left", "only_right", "error")
+
+
+@dataclass(frozen=True, slots=True)
+class SeverityPolicy:
+    """Severity per category of difference in a :class:`DirResult`.
+
+    Categories are the :class:`DiffReason` values for ``diff`` entries,
+    plus ``only_left``, ``only_right`` and ``error`` for the other result
+    sets. Categories without a rule get *default*.
+
+    :param rules: Severity (or its value) per category.
+    :param default: Severity of categories not in *rules*.
+    """
+
+    rules: Mapping[str, Severity | str] = field(default_factory=dict)
+    default: Severity = Severity.ERROR
+
+    def __post_init__(self) -> None:
+        rules: dict[str, Severity] = {}
+        for category, severity in self.rules.items():
+            category = category.value if isinstance(category, DiffReason) else category
+            if category not in SEVERITY_CATEGORIES:
+                raise ValueError(f"unknown difference category: {category!r}")
+            rules[category] = Severity(severity)
+        object.__setattr__(self, "rules", rules)
+        object.__setattr__(self, "default", Severity(self.default))
+
+    def of(self, category: str | DiffReason) -> Severity:
+        """Severity of one category."""
+        if isinstance(category, DiffReason):
+            category = category.value
+        return self.rules.get(category, self.default)
+
+    def classify(self, result: DirResult) -> dict[str, Severity]:
+        """Severity of every differing path in *result*."""
+        severities = {path: self.of(reason) for path, reason in result.diff.items()}
+        for category, paths in (("only_left", result.only_left),
+                                ("only_right", result.only_right),
+                                ("error", result.errors)):
+            severity = self.of(category)
+            severities.update(dict.fromkeys(paths, severity))
+        return severities
+
+    def highest(self, result: DirResult) -> Severity | None:
+        """Highest severity in *result*, or None if nothing differs."""
+        return max(self.classify(result).values(), key=lambda s: s.rank, default=None)
+
+
+@dataclass(frozen=True, slots=True)
+class CompareOptions:
+    """Validated bundle of comparison options.
+
+    Build once, derive variants with :meth:`replace`, and pass as
+    ``options=`` to :func:`komparu.compare` / :func:`komparu.compare_dir`
+    instead of repeating keyword arguments. Values are validated on
+    construction, so an invalid bundle never reaches the C core.
+
+    :param chunk_size: Chunk size in bytes.
+    :param size_precheck: Compare sizes before content.
+    :param quick_check: Sample key offsets before full scan.
+    :param follow_symlinks: Follow symbolic links during traversal.
+    :param max_workers: Thread pool size (0=auto, 1=sequential).
+    :param ignore: Glob patterns to exclude (matched per path component).
+    :param path_filter: PathFilter deciding per entry, with metadata, whether
+        it is reported (directories only).
+    :param fail_fast: :func:`komparu.compare_dir` stops at the first
+        difference and returns what it found by then.
+    :param retry: RetryPolicy for transient errors (directories only).
+    :param lock: LockPolicy reading files under shared locks (directories only).
+    :param headers: Global HTTP headers for URL sources.
+    :param timeout: HTTP timeout in seconds.
+    :param follow_redirects: Follow HTTP redirects.
+    :param verify_ssl: Verify SSL certificates.
+    :param proxy: Proxy URL (e.g. http://host:port, socks5://host:port).
+    :param metadata: Attributes :func:`komparu.compare_dir_metadata`
+        compares, as in :func:`komparu.metadata_diff`; None for its default.
+    """
+
+    chunk_size: int = 65536
+    size_precheck: bool = True
+    quick_check: bool = True
+    follow_symlinks: bool = True
+    max_workers: int = 0
+    ignore: tuple[str, ...] = ()
+    path_filter: PathFilter | None = None
+    fail_fast: bool = False
+    retry: RetryPolicy | None = None
+    lock: LockPolicy | None = None
+    headers: dict[str, str] | None = None
+    timeout: float = 30.0
+    follow_redirects: bool = True
+    verify_ssl: bool = True
+    proxy: str | None = None
+    metadata: tuple[str, ...] | None = None
+
+    def __post_init__(self) -> None:
+        validate_chunk_size(self.chunk_size)
+        validate_timeout(self.timeout)
+        validate_max_workers(self.max_workers)
+        if isinstance(self.ignore, str):
+            raise TypeError("ignore must be a sequence of patterns, not a string")
+        object.__setattr__(self, "ignore", tuple(self.ignore))
+        if self.path_filter is not None and not isinstance(self.path_filter, PathFilter):
+            raise TypeError(
+                f"path_filter must be a PathFilter, got {type(self.path_filter).__name__}")
+        retry_kwargs(self.retry)  # type check
+        lock_kwargs(self.lock)
+        if self.metadata is not None:
+            from komparu._metadata import EXTRA_ATTRIBUTES, METADATA_ATTRIBUTES, validate_attributes
+
+            if isinstance(self.metadata, str):
+                raise TypeError("metadata must be a sequence of attributes, not a string")
+            object.__setattr__(self, "metadata", tuple(self.metadata))
+            validate_attributes(self.metadata, METADATA_ATTRIBUTES + EXTRA_ATTRIBUTES)
+
+    def replace(self, **changes: Any) -> CompareOptions:
+        """Return a copy with the given fields changed (re-validated)."""
+        return dataclasses.replace(self, **changes)
+
+    def file_kwargs(self) -> dict[str, Any]:
+        """Keyword arguments for :func:`komparu.compare`."""
+        return {
+            "chunk_size": self.chunk_size,
+            "size_precheck": self.size_precheck,
+            "quick_check": self.quick_check,
+            "headers": self.headers,
+            "timeout": self.timeout,
+            "follow_redirects": self.follow_redirects,
+            "verify_ssl": self.verify_ssl,
+            "proxy": self.proxy,
+        }
+
+    def dir_kwargs(self) -> dict[str, Any]:
+        """Keyword arguments for :func:`komparu.compare_dir`."""
+        return {
+            "chunk_size": self.chunk_size,
+            "size_precheck": self.size_precheck,
+            "quick_check": self.quick_check,
+            "follow_symlinks": self.follow_symlinks,
+            "max_workers": self.max_workers,
+            "ignore": list(self.ignore) or None,
+            "path_filter": self.path_filter,
+            "retry": self.retry,
+            "lock": self.lock,
+        }
+
+
+FILE_OPTION_DEFAULTS = CompareOptions().file_kwargs()
+DIR_OPTION_DEFAULTS = CompareOptions().dir_kwargs()
+
+
+def reject_overrides(func: str, arguments: Mapping[str, Any],
+                     defaults: Mapping[str, Any]) -> None:
+    """Refuse keyword arguments passed along with ``options=``, which replaces them.
+
+    :param func: Name of the called function, for the message.
+    :param arguments: The call's parameters (its ``locals()``).
+    :param defaults: Default of each parameter *options* replaces; a value
+        that differs from it was passed explicitly. An empty value stands
+        for a None default.
+    :raises TypeError: If one was.
+    """
+    given = [name for name, default in defaults.items()
+             if name in arguments and arguments[name] != default
+             and not (default is None and not arguments[name])]
+    if given:
+        names = ", ".join(f"{name}=" for name in given)
+        raise TypeError(f"{func}() got options= and {names}; set them on the CompareOptions")
@@ -0,0 +1,167 @@
+"""Binary patches: rebuild one file from another and the bytes where they differ.
+
+A patch is the magic ``KPP1`` followed by::
+
+    block size (varint)
+    source size (varint), BLAKE2b-256 of the source (32 bytes)
+    BLAKE2b-256 of the target (32 bytes)
+
+and a ``KPD1`` delta of the target against the source's block signature,
+as :mod:`komparu._rsync` writes it: blocks of the source found anywhere
+in the target cost a few bytes each, and only the bytes matching none
+are stored. The hashes make sure a patch is applied to the file it was
+made for and rebuilds exactly the file it was made from.
+"""
+
+from __future__ import annotations
+
+import hashlib
+import os
+import shutil
+from typing import BinaryIO
+
+from komparu._hashing import require_fips_approved
+from komparu._helpers import atomic_open
+from komparu._rsync import (
+    OP_COPY,
+    _read_exact,
+    _read_varint,
+    _varint,
+    block_size_for,
+    make_signature,
+    mapped,
+    read_delta,
+    write_delta,
+)
+from komparu._types import PatchError
+from komparu._validate import validate_path
+
+PATCH_MAGIC = b"KPP1"
+PATCH_SUFFIX = ".kpatch"
+_DIGEST_SIZE = 32
+
+
+def _digest(data: bytes | memoryview) -> bytes:
+    return hashlib.blake2b(data, digest_size=_DIGEST_SIZE).digest()
+
+
+def make_patch(path_a: str, path_b: str, patch: str, *, block_size: int = 0) -> int:
+    """Write a binary patch that turns the file *path_a* into *path_b*.
+
+    :param path_a: File the patch is applied to.
+    :param path_b: File the patch rebuilds.
+    :param patch: Path of the patch file (overwritten if it exists).
+    :param block_size: Block size in bytes (0=about the square root of
+        *path_a*'s size); smaller stores less of a changed block and
+        costs more per unchanged one.
+    :returns: Size of the patch in bytes.
+    :raises ValueError: If *block_size* is negative, or in FIPS mode (patches
+        are hashed with BLAKE2b).
+    """
+    require_fips_approved("blake2b", "komparu patches")
+    validate_path(path_a, "path_a")
+    validate_path(path_b, "path_b")
+    validate_path(patch, "patch")
+    if block_size < 0:
+        raise ValueError("block_size must be non-negative")
+    with mapped(path_a) as source, mapped(path_b) as target:
+        block_size = block_size or block_size_for(len(source))
+        signature = make_signature(source, block_size)
+        with atomic_open(patch, "wb") as out:
+            out.write(PATCH_MAGIC + _varint(block_size) + _varint(len(source)))
+            out.write(_digest(source) + _digest(target))
+            write_delta(target, signature, out)
+            return out.tell()
+
+
+def _read_header(stream: BinaryIO) -> tuple[int, int, bytes, bytes]:
+    try:
+        if stream.read(len(PATCH_MAGIC)) != PATCH_MAGIC:
+            raise PatchError("not a komparu patch")
+        block_size, size = _read_varint(stream), _read_varint(stream)
+        source, target = _read_exact(stream, _DIGEST_SIZE), _read_exact(stream, _DIGEST_SIZE)
+    except PatchError:
+        raise
+    except ValueError as exc:
+        raise PatchError(f"corrupt patch: {exc}") from None
+    if block_size <= 0:
+        raise PatchError("corrupt patch: no block size")
+    return block_size, size, source, target
+
+
+def _rebuild(source: bytes | memoryview, block_size: int, stream: BinaryIO,
+             out: BinaryIO) -> tuple[int, bytes]:
+    """Write the target; returns its size and digest."""
+    h = hashlib.blake2b(digest_size=_DIGEST_SIZE)
+    blocks = len(source) // block_size
+    try:
+        expected, ops = read_delta(stream)
+        written = 0
+        for op, value in ops:
+            if op == OP_COPY:
+                assert isinstance(value, int)
+                if value >= blocks:
+                    raise PatchError(f"patch copies block {value} of {blocks}")
+                # Released at once: a view left to a traceback keeps the source mapped
+                with memoryview(source)[value * block_size:(value + 1) * block_size] as block:
+                    h.update(block)
+                    out.write(block)
+                    written += len(block)
+                continue
+            assert not isinstance(value, int)
+            h.update(value)
+            out.write(value)
+            written += len(value)
+    except PatchError:
+        raise
+    except ValueError as exc:
+        raise PatchError(f"corrupt patch: {exc}") from None
+    if written != expected:
+        raise PatchError("patch does not add up to the file size")
+    return written, h.digest()
+
+
+def apply_patch(path: str, patch: str, *, output: str | None = None) -> bool:
+    """Rebuild the file a patch from :func:`make_patch` was made from.
+
+    The result is written next to its destination and renamed over it,
+    keeping *path*'s permission bits, once its hash matches; a file that
+    already is the patch's target is left alone.
+
+    :param path: File the patch was made for.
+    :param patch: Path of the patch file.
+    :param output: Where to write the result (default: replace *path*).
+    :returns: True if a file was written, False if *path* already matched
+        (and was copied to *output*, if given).
+    :raises PatchError: If the patch is corrupt, *path* is not the file it
+        was made for, or the result does not match it.
+    :raises ValueError: In FIPS mode.
+    """
+    require_fips_approved("blake2b", "komparu patches")
+    validate_path(path, "path")
+    validate_path(patch, "patch")
+    destination = os.fspath(output if output is not None else path)
+    partial = os.path.join(os.path.dirname(destination),
+                           f".{os.path.basename(destination)}.komparu-patch")
+    try:
+        with open(patch, "rb") as stream, mapped(path) as source, memoryview(source) as view:
+            block_size, size, source_digest, target_digest = _read_header(stream)
+            digest = _digest(view)
+            if digest == target_digest:
+                if output is not None:
+                    shutil.copy2(path, destination)
+                return False
+            if len(view) != size or digest != source_digest:
+                raise PatchError(f"patch was made for another version of {path}")
+            with open(partial, "wb") as out:
+                _, written = _rebuild(view, block_size, stream, out)
+        if written != target_digest:
+            raise PatchError(f"patched {path} does not match the patch's target")
+        shutil.copymode(path, partial)
+        # After the source is unmapped: Windows cannot replace a mapped file
+        os.replace(partial, destination)
+    except BaseException:
+        if os.path.lexists(partial):
+            os.unlink(partial)
+        raise
+    return True
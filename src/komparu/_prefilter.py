@@ -0,0 +1,146 @@
+"""Staged grouping of identical files: size, then the file edges, then the content.
+
+Each stage only reads what the previous one could not rule out, so most
+non-duplicates are told apart by ``stat`` or by 8 KiB of I/O.
+"""
+
+from __future__ import annotations
+
+import hashlib
+import os
+from collections.abc import Callable, Sequence
+from typing import TypeVar
+
+from komparu._core import compare as _compare_c
+from komparu._errors import error_scope
+from komparu._hashing import fingerprint_hash
+from komparu._validate import validate_chunk_size, validate_max_workers
+
+EDGE_SIZE = 4096
+
+_T = TypeVar("_T")
+_R = TypeVar("_R")
+
+
+def _map(fn: Callable[[_T], _R], items: list[_T], max_workers: int) -> list[_R]:
+    if max_workers == 1 or len(items) < 2:
+        return [fn(x) for x in items]
+    from concurrent.futures import ThreadPoolExecutor
+
+    pool_size = max_workers if max_workers > 0 else min(len(items), 8)
+    with ThreadPoolExecutor(max_workers=pool_size) as pool:
+        return list(pool.map(fn, items))
+
+
+def _edge_digest(path: str, size: int, edge_size: int) -> bytes:
+    h = fingerprint_hash()
+    with open(path, "rb") as f:
+        if size <= 2 * edge_size:
+            h.update(f.read())
+        else:
+            h.update(f.read(edge_size))
+            f.seek(size - edge_size)
+            h.update(f.read(edge_size))
+    return h.digest()
+
+
+def _content_digest(path: str) -> bytes:
+    with open(path, "rb") as f:
+        return hashlib.file_digest(f, fingerprint_hash).digest()
+
+
+def _split(bucket: list[int], keys: dict[int, object]) -> list[list[int]]:
+    parts: dict[object, list[int]] = {}
+    for i in bucket:
+        parts.setdefault(keys[i], []).append(i)
+    return list(parts.values())
+
+
+def group_identical(
+    paths: Sequence[str],
+    *,
+    edge_size: int = EDGE_SIZE,
+    chunk_size: int = 65536,
+    quick_check: bool = True,
+    max_workers: int = 0,
+) -> list[list[str]]:
+    """Group local files by identical content.
+
+    Files are bucketed by size first; same-size candidates are then split
+    by a hash of their first and last *edge_size* bytes, and only what
+    is left is read in full. A pair is compared byte by byte (stopping at
+    the first difference); larger buckets are split by a BLAKE2b-512
+    digest so each file is read once. Hard links to the same inode are
+    grouped without reading.
+
+    :param paths: Regular files to group.
+    :param edge_size: Bytes hashed at each end of a file in the second
+        stage; files up to twice this size are decided there.
+    :param chunk_size: Chunk size of the byte-by-byte pair comparison.
+    :param quick_check: Sample key offsets before a pair's full scan.
+    :param max_workers: Thread pool size for the hashing stages
+        (0=auto, 1=sequential).
+    :returns: Every path in exactly one group, singletons included; groups
+        in order of their first path, paths in input order.
+    :raises SourceReadError: If a file cannot be read.
+    """
+    if edge_size < 1:
+        raise ValueError("edge_size must be positive")
+    validate_chunk_size(chunk_size)
+    validate_max_workers(max_workers)
+
+    with error_scope():
+        stats = [os.stat(p) for p in paths]
+
+    # Stage 0: one representative per inode
+    members: dict[int, list[int]] = {}
+    by_inode: dict[tuple[int, int], int] = {}
+    for i, st in enumerate(stats):
+        rep = by_inode.setdefault((st.st_dev, st.st_ino), i)
+        members.setdefault(rep, []).append(i)
+
+    # Stage 1: size
+    by_size: dict[int, list[int]] = {}
+    for rep in members:
+        by_size.setdefault(stats[rep].st_size, []).append(rep)
+
+    done: list[list[int]] = []
+    pending: list[list[int]] = []
+    for bucket in by_size.values():
+        if len(bucket) == 1 or stats[bucket[0]].st_size == 0:
+            done.append(bucket)
+        else:
+            pending.append(bucket)
+
+    # Stage 2: first and last edge_size bytes
+    candidates = [i for bucket in pending for i in bucket]
+    with error_scope():
+        edges = _map(lambda i: _edge_digest(paths[i], stats[i].st_size, edge_size),
+                     candidates, max_workers)
+    keys: dict[int, object] = dict(zip(candidates, edges))
+    remaining: list[list[int]] = []
+    for bucket in pending:
+        for part in _split(bucket, keys):
+            if len(part) == 1 or stats[part[0]].st_size <= 2 * edge_size:
+                done.append(part)
+            else:
+                remaining.append(part)
+
+    # Stage 3: full content
+    pairs = [part for part in remaining if len(part) == 2]
+    hashed = [i for part in remaining if len(part) > 2 for i in part]
+    with error_scope():
+        same = _map(lambda p: _compare_c(paths[p[0]], paths[p[1]], chunk_size=chunk_size,
+                                         quick_check=quick_check),
+                    pairs, max_workers)
+        digests = _map(lambda i: _content_digest(paths[i]), hashed, max_workers)
+    for pair, eq in zip(pairs, same):
+        done.extend([pair] if eq else [[pair[0]], [pair[1]]])
+    keys = dict(zip(hashed, digests))
+    for part in remaining:
+        if len(part) > 2:
+            done.extend(_split(part, keys))
+
+    groups = [sorted(i for rep in part for i in members[rep]) for part in done]
+    groups.sort(key=lambda g: g[0])
+    return [[paths[i] for i in g] for g in groups]
@@ -0,0 +1,259 @@
+"""Files and directories on other machines, compared by what a remote komparu sends back."""
+
+from __future__ import annotations
+
+import contextlib
+import errno
+import re
+import shlex
+import subprocess
+from typing import BinaryIO
+
+from komparu._delta import common_length
+from komparu._filter import PathFilter
+from komparu._hashing import validate_algorithm
+from komparu._manifest import parse_manifest, verify_manifest
+from komparu._rsync import (
+    Signature,
+    block_size_for,
+    encode_signature,
+    make_signature,
+    mapped,
+    read_delta,
+)
+from komparu._types import ChunkDiff, DiffRegion, DirResult, RegionKind, SourceReadError
+from komparu._validate import validate_max_workers, validate_path
+
+# [user@]host:path, as ssh and scp take it; a bracketed host may be IPv6
+_TARGET = re.compile(r"(?P<host>(?:[^@/:]+@)?(?:\[[^\]/]+\]|[^@/:\[\]]{2,})):(?P<path>.*)",
+                     re.DOTALL)
+_URL = re.compile(r"[A-Za-z][A-Za-z0-9+.-]*://")
+
+
+def parse_ssh_target(spec: str) -> tuple[str, str] | None:
+    """``(host, path)`` of a ``[user@]host:path`` spec, or None if *spec* is not one.
+
+    An empty path is the remote home directory, as for scp. URLs and
+    single-letter hosts (Windows drive letters) are not targets.
+    """
+    if _URL.match(spec):
+        return None
+    match = _TARGET.fullmatch(spec)
+    if match is None:
+        return None
+    return match["host"], match["path"] or "."
+
+
+def _target(remote: str) -> tuple[str, str]:
+    target = parse_ssh_target(remote)
+    if target is None:
+        raise ValueError(f"remote must be [user@]host:path, got {remote!r}")
+    return target
+
+
+def _command(ssh_command: str, host: str, args: list[str]) -> list[str]:
+    # ssh hands the remote shell one command line
+    return [*shlex.split(ssh_command), host, " ".join(shlex.quote(arg) for arg in args)]
+
+
+def _failure(returncode: int, stderr: bytes, ssh_command: str, remote: str) -> SourceReadError:
+    lines = stderr.decode("utf-8", "replace").strip().splitlines()
+    reason = lines[-1] if lines else f"{ssh_command} exited with {returncode}"
+    return SourceReadError(errno.EIO, reason, remote)
+
+
+def compare_dir_ssh(
+    directory: str,
+    remote: str,
+    *,
+    algorithm: str = "sha256",
+    ssh_command: str = "ssh",
+    remote_command: str = "komparu",
+    follow_symlinks: bool = True,
+    ignore: list[str] | None = None,
+    path_filter: PathFilter | None = None,
+    max_workers: int = 0,
+) -> DirResult:
+    """Compare a local directory with one on another machine, over SSH.
+
+    ``komparu hash`` runs on the remote host and sends back a manifest of
+    the remote tree, which the local tree is then checked against as by
+    :func:`komparu.verify_manifest`: only digests cross the network, never
+    file contents. komparu must be installed on the remote host.
+
+    The local tree is the left side and the remote one the right.
+
+    :param directory: Local directory path.
+    :param remote: Remote directory as ``[user@]host:path``.
+    :param algorithm: Hash algorithm both sides use; the remote komparu
+        must support it.
+    :param ssh_command: Command that runs a command on the host, split as
+        by a shell, e.g. ``"ssh -p 2222 -o BatchMode=yes"``.
+    :param remote_command: komparu command on the remote host, e.g.
+        ``"python3 -m komparu"``.
+    :param follow_symlinks: Follow symbolic links during traversal, on
+        both hosts.
+    :param ignore: Glob patterns to exclude (matched per path component),
+        on both hosts.
+    :param path_filter: PathFilter deciding per entry whether it is
+        compared; applied locally, to both trees.
+    :param max_workers: Thread pool size (0=auto, 1=sequential), on both
+        hosts.
+    :raises ValueError: If *remote* is not ``[user@]host:path``.
+    :raises SourceReadError: If ssh or the remote komparu fails.
+    :raises SourcePermissionError: If a local directory cannot be listed.
+    """
+    validate_path(directory, "directory")
+    validate_algorithm(algorithm)
+    validate_max_workers(max_workers)
+    host, path = _target(remote)
+
+    args = [*shlex.split(remote_command), "hash", "-a", algorithm, "-j", str(max_workers)]
+    if not follow_symlinks:
+        args.append("--no-follow-symlinks")
+    for pattern in ignore or ():
+        args += ["--ignore", pattern]
+    proc = subprocess.run(_command(ssh_command, host, [*args, "--", path]),
+                          stdin=subprocess.DEVNULL, capture_output=True)
+    if proc.returncode != 0:
+        raise _failure(proc.returncode, proc.stderr, ssh_command, remote)
+
+    lines = proc.stdout.decode("utf-8", "surrogateescape").splitlines()
+    manifest = parse_manifest(lines, source=remote)
+    return verify_manifest(directory, manifest, algorithm=algorithm,
+                           follow_symlinks=follow_symlinks, ignore=ignore,
+                           path_filter=path_filter, max_workers=max_workers)
+
+
+def _region(local: memoryview, start_a: int, end_a: int, remote: bytes, start_b: int
+            ) -> DiffRegion | None:
+    """The unmatched local range and remote bytes without their common prefix and suffix."""
+    prefix = common_length(local[start_a:end_a], remote)
+    start_a, start_b, remote = start_a + prefix, start_b + prefix, remote[prefix:]
+    suffix = common_length(bytes(local[start_a:end_a])[::-1], remote[::-1])
+    end_a, remote = end_a - suffix, remote[:len(remote) - suffix]
+    if start_a == end_a and not remote:
+        return None
+    if start_a == end_a:
+        kind = RegionKind.INSERTED
+    elif not remote:
+        kind = RegionKind.DELETED
+    else:
+        kind = RegionKind.CHANGED
+    return DiffRegion(kind, start_a, end_a - start_a, start_b, len(remote))
+
+
+def _read_regions(view: memoryview, signature: Signature, stream: BinaryIO
+                  ) -> tuple[int, int, int, list[DiffRegion]]:
+    """Remote size, instruction count, in-order copies and regions of a delta."""
+    size_b, ops = read_delta(stream)
+    bs = signature.block_size
+    regions: list[DiffRegion] = []
+    shared = count = offset_b = 0
+    # Unmatched remote bytes since the last in-order copy, and where they
+    # and the local bytes they stand for start
+    pending = bytearray()
+    start_a = start_b = 0
+    for op, value in ops:
+        count += 1
+        if isinstance(value, bytes):
+            pending += value
+            offset_b += len(value)
+            continue
+        if value >= len(signature.strong):
+            raise ValueError(f"rsync delta copies block {value} of {len(signature.strong)}")
+        block = value * bs
+        if block >= start_a:
+            region = _region(view, start_a, block, bytes(pending), start_b)
+            if region is not None:
+                regions.append(region)
+            shared += 1
+            pending.clear()
+            start_a, start_b = block + bs, offset_b + bs
+        else:  # a block the remote file has again, or moved back
+            pending += view[block:block + bs]
+        offset_b += bs
+    region = _region(view, start_a, len(view), bytes(pending), start_b)
+    if region is not None:
+        regions.append(region)
+    if offset_b != size_b:
+        raise ValueError("rsync delta does not add up to the file size")
+    return size_b, count, shared, regions
+
+
+def chunk_diff_ssh(
+    path: str,
+    remote: str,
+    *,
+    block_size: int = 0,
+    ssh_command: str = "ssh",
+    remote_command: str = "komparu",
+) -> ChunkDiff:
+    """Find where a file on another machine differs from a local one, over SSH.
+
+    The rsync algorithm: the local file's block signature (a rolling
+    checksum and a strong hash per block) goes to ``komparu rsync-delta``
+    on the remote host, which finds those blocks at any offset in its
+    file and sends back copy instructions for them and only the bytes
+    that match none. Unchanged data costs about 20 bytes per block
+    instead of the block itself. komparu must be installed on the remote
+    host.
+
+    The local file is the first side and the remote one the second;
+    chunks are the local file's blocks, and the remote file's copied
+    blocks and literal runs.
+
+    :param path: Local file path.
+    :param remote: Remote file as ``[user@]host:path``.
+    :param block_size: Block size in bytes (0=about the square root of
+        the local file's size); smaller sends less of a changed block
+        and a longer signature.
+    :param ssh_command: Command that runs a command on the host, split as
+        by a shell.
+    :param remote_command: komparu command on the remote host.
+    :returns: Block counts and the insertions, deletions and changes that
+        turn the local file into the remote one, with their byte ranges.
+    :raises ValueError: If *remote* is not ``[user@]host:path`` or
+        *block_size* is negative.
+    :raises SourceReadError: If ssh or the remote komparu fails.
+    """
+    validate_path(path, "path")
+    if block_size < 0:
+        raise ValueError("block_size must be non-negative")
+    host, remote_path = _target(remote)
+    with mapped(path) as data:
+        signature = make_signature(data, block_size or block_size_for(len(data)))
+        command = _command(ssh_command, host,
+                           [*shlex.split(remote_command), "rsync-delta", "--", remote_path])
+        with subprocess.Popen(command, stdin=subprocess.PIPE, stdout=subprocess.PIPE,
+                              stderr=subprocess.PIPE) as proc:
+            assert proc.stdin is not None and proc.stdout is not None
+            assert proc.stderr is not None
+            with contextlib.suppress(BrokenPipeError):  # it failed before reading it all
+                proc.stdin.write(encode_signature(signature))
+                proc.stdin.close()
+            problem = None
+            try:
+                with memoryview(data) as view:
+                    size_b, count, shared, regions = _read_regions(view, signature,
+                                                                   proc.stdout)
+            except ValueError as exc:
+                problem = exc
+            proc.stdout.close()
+            stderr = proc.stderr.read()
+        if proc.returncode != 0:
+            raise _failure(proc.returncode, stderr, ssh_command, remote)
+        if problem is not None:
+            raise SourceReadError(errno.EIO, str(problem), remote)
+        return ChunkDiff(
+            size_a=len(data),
+            size_b=size_b,
+            chunks_a=-(-len(data) // signature.block_size),
+            chunks_b=count,
+            shared_chunks=shared,
+            shared_bytes=shared * signature.block_size,
+            insertions=sum(r.kind is RegionKind.INSERTED for r in regions),
+            deletions=sum(r.kind is RegionKind.DELETED for r in regions),
+            changes=sum(r.kind is RegionKind.CHANGED for r in regions),
+            regions=tuple(regions),
+        )
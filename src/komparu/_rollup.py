@@ -0,0 +1,118 @@
+"""Per-directory rollups of a directory comparison, for reading it top-down."""
+
+from __future__ import annotations
+
+from collections.abc import Iterable
+
+from komparu._helpers import stat_entry
+from komparu._types import DiffEntry, DirSummary, EntryKind, tree_key
+from komparu._visitor import CompareVisitor
+
+_FIELDS = {EntryKind.EQUAL: 0, EntryKind.DIFF: 1, EntryKind.ONLY_LEFT: 2,
+           EntryKind.ONLY_RIGHT: 3, EntryKind.ERROR: 4}
+
+
+def _size(root: str | int, path: str, follow_symlinks: bool) -> int:
+    try:
+        return stat_entry(root, path, follow_symlinks).st_size
+    except OSError:
+        return 0
+
+
+def _entry_bytes(entry: DiffEntry, roots: tuple[str | int, str | int] | None,
+                 follow_symlinks: bool) -> int:
+    if entry.kind is EntryKind.DIFF and entry.size_a is not None and entry.size_b is not None:
+        return max(entry.size_a, entry.size_b)
+    if roots is None or entry.kind in (EntryKind.EQUAL, EntryKind.ERROR):
+        return 0
+    sides: tuple[str | int, ...] = roots
+    if entry.kind is EntryKind.ONLY_LEFT:
+        sides = roots[:1]
+    elif entry.kind is EntryKind.ONLY_RIGHT:
+        sides = roots[1:]
+    return max(_size(root, entry.path, follow_symlinks) for root in sides)
+
+
+def summarize_dirs(
+    entries: Iterable[DiffEntry],
+    roots: tuple[str | int, str | int] | None = None,
+    *,
+    follow_symlinks: bool = True,
+) -> dict[str, DirSummary]:
+    """Sum up the entries of a comparison per directory.
+
+    Each entry counts toward every directory above it and toward the
+    roots, so a comparison of a million paths can be read from the top:
+    which subtrees differ, by how many files and how many bytes. Feed it
+    every resolved entry — :func:`komparu.iter_dir` with
+    ``include_equal=True``, or a :class:`SummaryVisitor` run of
+    :func:`komparu.compare_dir`; a :class:`DirResult` alone does not
+    list the equal files.
+
+    :param entries: Resolved entries, in any order.
+    :param roots: The two compared directories; sizes the entries do not
+        carry (files on one side only, differing files compared without a
+        size) are read from them. Without, such files count no bytes.
+    :param follow_symlinks: Follow symlinks when reading those sizes.
+    :returns: DirSummary per directory with at least one entry under it,
+        keyed by relative path (``""`` for the roots), in tree order.
+    """
+    counts: dict[str, list[int]] = {}
+    for entry in entries:
+        field = _FIELDS.get(entry.kind)
+        if field is None:
+            continue
+        size = _entry_bytes(entry, roots, follow_symlinks)
+        parts = entry.path.split("/")
+        for depth in range(len(parts)):
+            row = counts.setdefault("/".join(parts[:depth]), [0] * 6)
+            row[field] += 1
+            row[5] += size
+    return {path: DirSummary(path, *counts[path]) for path in sorted(counts, key=tree_key)}
+
+
+class SummaryVisitor(CompareVisitor):
+    """Keep the entries a comparison resolves, for :meth:`summaries`.
+
+    Forwards every callback to *inner* (if any) first.
+
+    :param dir_a: The first compared directory.
+    :param dir_b: The second compared directory.
+    :param inner: CompareVisitor to pass the callbacks on to.
+    :param follow_symlinks: As given to the comparison.
+    """
+
+    __slots__ = ("_roots", "_inner", "_follow_symlinks", "entries")
+
+    def __init__(self, dir_a: str | int, dir_b: str | int, inner: CompareVisitor | None = None,
+                 *, follow_symlinks: bool = True) -> None:
+        self._roots = (dir_a, dir_b)
+        self._inner = inner
+        self._follow_symlinks = follow_symlinks
+        self.entries: list[DiffEntry] = []
+
+    def on_dir_entered(self, path: str) -> None:
+        if self._inner is not None:
+            self._inner.on_dir_entered(path)
+
+    def on_dir_left(self, path: str) -> None:
+        if self._inner is not None:
+            self._inner.on_dir_left(path)
+
+    def on_entry_started(self, path: str) -> None:
+        if self._inner is not None:
+            self._inner.on_entry_started(path)
+
+    def on_entry_finished(self, entry: DiffEntry) -> None:
+        if self._inner is not None:
+            self._inner.on_entry_finished(entry)
+        self.entries.append(entry)
+
+    def on_error(self, entry: DiffEntry) -> None:
+        if self._inner is not None:
+            self._inner.on_error(entry)
+        self.entries.append(entry)
+
+    def summaries(self) -> dict[str, DirSummary]:
+        """:func:`summarize_dirs` of the entries so far."""
+        return summarize_dirs(self.entries, self._roots, follow_symlinks=self._follow_symlinks)
@@ -0,0 +1,227 @@
+"""rsync-style block signatures and deltas: where a remote file differs from a local one.
+
+The side holding the old file sends a signature, the magic ``KPR1``
+followed by::
+
+    block size (varint), block count (varint)
+    per full block: rolling checksum (4 bytes, little-endian),
+        BLAKE2b-128 of the block (16 bytes)
+
+and the side holding the new file answers with a delta, the magic
+``KPD1`` followed by the new file's size (varint) and instructions::
+
+    0x01, block index (varint): the next bytes are that block
+    0x02, length (varint), bytes: the next bytes are these
+    0x00: end
+
+The new side finds blocks at any offset with the rolling checksum and
+confirms each with the strong hash, so unchanged data costs a few bytes
+per block and only what differs is sent.
+"""
+
+from __future__ import annotations
+
+import contextlib
+import hashlib
+import math
+import mmap
+import sys
+from array import array
+from collections.abc import Iterator
+from typing import BinaryIO
+
+from komparu._core import rsum_blocks as _rsum_blocks_c
+from komparu._core import rsum_scan as _rsum_scan_c
+from komparu._hashing import require_fips_approved
+
+SIGNATURE_MAGIC = b"KPR1"
+DELTA_MAGIC = b"KPD1"
+STRONG_SIZE = 16
+
+OP_END, OP_COPY, OP_LITERAL = 0, 1, 2
+
+_MIN_BLOCK_SIZE = 512
+_MAX_BLOCK_SIZE = 128 * 1024
+# Literals go out in pieces no larger than this
+_LITERAL_LIMIT = 1024 * 1024
+
+
+class Signature:
+    """Block size, rolling checksums and strong hashes of a file's full blocks."""
+
+    __slots__ = ("block_size", "weak", "strong")
+
+    def __init__(self, block_size: int, weak: array, strong: list[bytes]) -> None:
+        self.block_size = block_size
+        self.weak = weak
+        self.strong = strong
+
+
+def block_size_for(size: int) -> int:
+    """A block size near the square root of *size*, as rsync picks it."""
+    return max(_MIN_BLOCK_SIZE, min(_MAX_BLOCK_SIZE, math.isqrt(size) // 8 * 8))
+
+
+def _strong(block: bytes | memoryview) -> bytes:
+    return hashlib.blake2b(block, digest_size=STRONG_SIZE).digest()
+
+
+def _le32(values: array) -> array:
+    if sys.byteorder == "big":
+        values = array("I", values)
+        values.byteswap()
+    return values
+
+
+def _varint(value: int) -> bytes:
+    out = bytearray()
+    while value > 0x7F:
+        out.append(value & 0x7F | 0x80)
+        value >>= 7
+    out.append(value)
+    return bytes(out)
+
+
+def _read_exact(stream: BinaryIO, size: int) -> bytes:
+    data = stream.read(size)
+    if len(data) != size:
+        raise ValueError("truncated rsync stream")
+    return data
+
+
+def _read_varint(stream: BinaryIO) -> int:
+    value = shift = 0
+    while True:
+        byte = _read_exact(stream, 1)[0]
+        value |= (byte & 0x7F) << shift
+        if byte < 0x80:
+            return value
+        shift += 7
+
+
+@contextlib.contextmanager
+def mapped(path: str) -> Iterator[mmap.mmap | bytes]:
+    """A file mapped into memory (``b""`` if it is empty)."""
+    with open(path, "rb") as f:
+        try:
+            data = mmap.mmap(f.fileno(), 0, access=mmap.ACCESS_READ)
+        except ValueError:
+            yield b""
+            return
+        with data:
+            yield data
+
+
+def make_signature(data: bytes | mmap.mmap, block_size: int) -> Signature:
+    """Signature of *data* cut into *block_size* blocks; a partial last block is left out."""
+    require_fips_approved("blake2b", "rsync signatures")
+    weak = array("I")
+    weak.frombytes(_rsum_blocks_c(data, block_size))
+    with memoryview(data) as view:
+        strong = [_strong(view[i:i + block_size])
+                  for i in range(0, len(weak) * block_size, block_size)]
+    return Signature(block_size, weak, strong)
+
+
+def encode_signature(signature: Signature) -> bytes:
+    weak = _le32(signature.weak).tobytes()
+    body = [SIGNATURE_MAGIC, _varint(signature.block_size), _varint(len(signature.strong))]
+    for index, strong in enumerate(signature.strong):
+        body += [weak[index * 4:index * 4 + 4], strong]
+    return b"".join(body)
+
+
+def read_signature(stream: BinaryIO) -> Signature:
+    """Parse a signature from a binary stream.
+
+    :raises ValueError: If the stream is not a signature, or in FIPS mode.
+    """
+    require_fips_approved("blake2b", "rsync signatures")
+    if stream.read(len(SIGNATURE_MAGIC)) != SIGNATURE_MAGIC:
+        raise ValueError("not a komparu rsync signature")
+    block_size = _read_varint(stream)
+    if block_size <= 0:
+        raise ValueError("rsync signature has no block size")
+    count = _read_varint(stream)
+    records = _read_exact(stream, count * (4 + STRONG_SIZE))
+    weak = array("I")
+    weak.frombytes(b"".join(records[i:i + 4] for i in range(0, len(records), 4 + STRONG_SIZE)))
+    strong = [records[i + 4:i + 4 + STRONG_SIZE]
+              for i in range(0, len(records), 4 + STRONG_SIZE)]
+    return Signature(block_size, _le32(weak), strong)
+
+
+def delta_ops(data: bytes | mmap.mmap, signature: Signature
+              ) -> Iterator[tuple[int, int, int]]:
+    """``(op, a, b)`` instructions rebuilding *data* from the signed file.
+
+    Copies are ``(OP_COPY, block index, 0)`` and literals ``(OP_LITERAL,
+    start, end)`` offsets into *data*. Windows whose rolling checksum is
+    known are confirmed by strong hash; one that is not resumes the scan
+    a byte later. Of equal blocks, the one after the last copied is
+    preferred, as rsync does, so repeated content stays in order.
+    """
+    block_size = signature.block_size
+    blocks: dict[bytes, int] = {}
+    for index, strong in enumerate(signature.strong):
+        blocks.setdefault(strong, index)
+    table = array("I", sorted(set(signature.weak))).tobytes()
+
+    with memoryview(data) as view:
+        literal = pos = following = 0
+        while True:
+            resume = None
+            for hit in _rsum_scan_c(data, pos, block_size, table):
+                strong = _strong(view[hit:hit + block_size])
+                index = blocks.get(strong)
+                if index is None:
+                    resume = hit + 1
+                    break
+                if following < len(signature.strong) and signature.strong[following] == strong:
+                    index = following
+                following = index + 1
+                if hit > literal:
+                    yield OP_LITERAL, literal, hit
+                yield OP_COPY, index, 0
+                literal = hit + block_size
+            if resume is None:
+                break
+            pos = resume
+    if len(data) > literal:
+        yield OP_LITERAL, literal, len(data)
+
+
+def write_delta(data: bytes | mmap.mmap, signature: Signature, out: BinaryIO) -> None:
+    """Write the delta rebuilding *data* from the signed file."""
+    out.write(DELTA_MAGIC + _varint(len(data)))
+    with memoryview(data) as view:
+        for op, a, b in delta_ops(data, signature):
+            if op == OP_COPY:
+                out.write(b"\x01" + _varint(a))
+                continue
+            for start in range(a, b, _LITERAL_LIMIT):
+                end = min(b, start + _LITERAL_LIMIT)
+                out.write(b"\x02" + _varint(end - start))
+                out.write(view[start:end])
+    out.write(b"\x00")
+
+
+def read_delta(stream: BinaryIO) -> tuple[int, Iterator[tuple[int, int | bytes]]]:
+    """The new file's size and its ``(OP_COPY, index)`` / ``(OP_LITERAL, bytes)`` instructions.
+
+    :raises ValueError: If the stream is not a delta.
+    """
+    if stream.read(len(DELTA_MAGIC)) != DELTA_MAGIC:
+        raise ValueError("not a komparu rsync delta")
+    size = _read_varint(stream)
+
+    def ops() -> Iterator[tuple[int, int | bytes]]:
+        while (op := _read_exact(stream, 1)[0]) != OP_END:
+            if op == OP_COPY:
+                yield op, _read_varint(stream)
+            elif op == OP_LITERAL:
+                yield op, _read_exact(stream, _read_varint(stream))
+            else:
+                raise ValueError(f"unknown rsync delta instruction {op}")
+
+    return size, ops()
@@ -0,0 +1,77 @@
+"""Comparison rules written as Python scripts, for the CLI's ``--rules``."""
+
+from __future__ import annotations
+
+import os
+import runpy
+from collections.abc import Callable
+
+from komparu._comparator import ContentComparator
+from komparu._filter import PathFilter
+
+_HOOKS = ("include", "compare")
+
+
+class _ScriptFilter(PathFilter):
+    __slots__ = ("_include",)
+
+    def __init__(self, include: Callable[..., object]) -> None:
+        self._include = include
+
+    def include(
+        self,
+        path: str,
+        stat_a: os.stat_result | None,
+        stat_b: os.stat_result | None,
+    ) -> bool:
+        return bool(self._include(path, stat_a, stat_b))
+
+
+class _ScriptComparator(ContentComparator):
+    __slots__ = ("_compare", "_fallback")
+
+    def __init__(
+        self,
+        compare: Callable[[str, str], bool | None],
+        fallback: ContentComparator | None,
+    ) -> None:
+        self._compare = compare
+        self._fallback = fallback
+
+    def compare(self, path_a: str, path_b: str) -> bool:
+        verdict = self._compare(path_a, path_b)
+        if verdict is None:
+            return self._fallback is not None and self._fallback.compare(path_a, path_b)
+        return bool(verdict)
+
+
+def load_rules(
+    path: str,
+    fallback: ContentComparator | None = None,
+) -> tuple[PathFilter | None, ContentComparator | None]:
+    """Run a rules script and wrap the hooks it defines.
+
+    The script is plain Python and may define either or both of:
+
+    * ``include(path, stat_a, stat_b) -> bool`` — as
+      :meth:`PathFilter.include`, False drops the entry;
+    * ``compare(path_a, path_b) -> bool | None`` — asked for every pair
+      of files whose bytes differ; True or False decides, None leaves
+      the decision to *fallback* (different without one).
+
+    :param path: Script path.
+    :param fallback: Comparator consulted when ``compare`` returns None.
+    :returns: The path filter and comparator, None for an undefined hook.
+    :raises ValueError: If the script defines neither hook, or a hook
+        is not callable.
+    """
+    namespace = runpy.run_path(path, run_name="komparu_rules")
+    hooks = {name: namespace[name] for name in _HOOKS if name in namespace}
+    if not hooks:
+        raise ValueError(f"{path} defines neither include() nor compare()")
+    for name, hook in hooks.items():
+        if not callable(hook):
+            raise ValueError(f"{path}: {name} must be a function")
+    path_filter = _ScriptFilter(hooks["include"]) if "include" in hooks else None
+    comparator = _ScriptComparator(hooks["compare"], fallback) if "compare" in hooks else None
+    return path_filter, comparator
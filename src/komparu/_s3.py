@@ -0,0 +1,230 @@
+"""Local trees compared with an S3 bucket prefix (needs boto3: ``pip install komparu[s3]``)."""
+
+from __future__ import annotations
+
+import base64
+import contextlib
+import errno
+import hashlib
+import os
+import stat
+from collections.abc import Iterator
+from concurrent.futures import ThreadPoolExecutor
+from typing import Any
+
+from komparu._filter import PathFilter, make_exclude
+from komparu._config import get_config
+from komparu._hashing import FIPS_ALGORITHMS, file_digest
+from komparu._helpers import walk_tree
+from komparu._types import (
+    DiffReason,
+    DirResult,
+    SourceNotFoundError,
+    SourcePermissionError,
+    SourceReadError,
+)
+from komparu._validate import validate_chunk_size, validate_max_workers, validate_path
+
+try:
+    from botocore.exceptions import ClientError as _ClientError
+except ImportError:  # only a caller's own client can be in use then
+    class _ClientError(Exception):  # type: ignore[no-redef]
+        def __init__(self, error_response: dict[str, Any], operation_name: str) -> None:
+            super().__init__(f"{operation_name}: {error_response}")
+            self.response = error_response
+
+# S3 checksum algorithm -> komparu algorithm; CRC64NVME has no local counterpart
+_CHECKSUMS = {"SHA256": "sha256", "SHA1": "sha1", "CRC32": "crc32", "CRC32C": "crc32c"}
+_NOT_FOUND = {"NoSuchBucket", "NoSuchKey", "404"}
+_DENIED = {"AccessDenied", "AllAccessDisabled", "403"}
+
+
+def parse_s3_url(url: str) -> tuple[str, str]:
+    """``(bucket, prefix)`` of ``s3://bucket/prefix``; a non-empty prefix ends in ``/``."""
+    if not url.startswith("s3://"):
+        raise ValueError(f"not an s3:// URL: {url!r}")
+    bucket, _, prefix = url[len("s3://"):].partition("/")
+    if not bucket:
+        raise ValueError(f"s3 URL has no bucket: {url!r}")
+    if prefix and not prefix.endswith("/"):
+        prefix += "/"
+    return bucket, prefix
+
+
+def _client() -> Any:
+    try:
+        import boto3
+    except ImportError:
+        raise ImportError("S3 comparison needs boto3: pip install komparu[s3]") from None
+    return boto3.client("s3")
+
+
+@contextlib.contextmanager
+def _client_errors(url: str) -> Iterator[None]:
+    try:
+        yield
+    except _ClientError as exc:
+        error = exc.response.get("Error", {})
+        code = str(error.get("Code", ""))
+        message = error.get("Message") or code or "S3 request failed"
+        if code in _NOT_FOUND:
+            raise SourceNotFoundError(errno.ENOENT, message, url) from None
+        if code in _DENIED:
+            raise SourcePermissionError(errno.EACCES, message, url) from None
+        raise SourceReadError(errno.EIO, message, url) from None
+
+
+def _opaque_etag(head: dict[str, Any]) -> bool:
+    # With SSE-KMS and SSE-C the ETag is not an MD5 of the content
+    return (head.get("ServerSideEncryption", "").startswith("aws:kms")
+            or "SSECustomerAlgorithm" in head)
+
+
+def _multipart_etag(path: str, part_size: int, chunk_size: int) -> str | None:
+    if part_size <= 0:
+        return None
+    digests = []
+    with open(path, "rb") as f:
+        while True:
+            part = hashlib.md5(usedforsecurity=False)
+            left = part_size
+            while left and (data := f.read(min(left, chunk_size))):
+                part.update(data)
+                left -= len(data)
+            if left == part_size:
+                break
+            digests.append(part.digest())
+    return f"{hashlib.md5(b''.join(digests), usedforsecurity=False).hexdigest()}-{len(digests)}"
+
+
+def _ranged_equal(client: Any, bucket: str, key: str, path: str, chunk_size: int) -> bool:
+    offset = 0
+    with open(path, "rb") as f:
+        while data := f.read(chunk_size):
+            byte_range = f"bytes={offset}-{offset + len(data) - 1}"
+            if client.get_object(Bucket=bucket, Key=key, Range=byte_range)["Body"].read() != data:
+                return False
+            offset += len(data)
+    return True
+
+
+def _equal(client: Any, bucket: str, key: str, obj: dict[str, Any], path: str,
+           chunk_size: int) -> bool:
+    fips = get_config().fips
+    for name in obj.get("ChecksumAlgorithm", ()):
+        if name in _CHECKSUMS and (not fips or _CHECKSUMS[name] in FIPS_ALGORITHMS):
+            head = client.head_object(Bucket=bucket, Key=key, ChecksumMode="ENABLED")
+            value = head.get(f"Checksum{name}")
+            # Multipart uploads may store a checksum of the part checksums
+            if value and "-" not in value and head.get("ChecksumType") != "COMPOSITE":
+                digest = bytes.fromhex(file_digest(path, _CHECKSUMS[name]))
+                return base64.b64encode(digest).decode() == value
+    etag = obj["ETag"].strip('"')
+    if fips:  # the ETag is an MD5: read the object back instead
+        return _ranged_equal(client, bucket, key, path, chunk_size)
+    if "-" in etag:
+        head = client.head_object(Bucket=bucket, Key=key, PartNumber=1)
+        if not _opaque_etag(head):
+            return _multipart_etag(path, head["ContentLength"], chunk_size) == etag
+    else:
+        if file_digest(path, "md5") == etag:
+            return True
+        if not _opaque_etag(client.head_object(Bucket=bucket, Key=key)):
+            return False
+    return _ranged_equal(client, bucket, key, path, chunk_size)
+
+
+def compare_dir_s3(
+    directory: str,
+    url: str,
+    *,
+    client: Any = None,
+    chunk_size: int = 8 * 1024 * 1024,
+    follow_symlinks: bool = True,
+    ignore: list[str] | None = None,
+    path_filter: PathFilter | None = None,
+    max_workers: int = 0,
+) -> DirResult:
+    """Compare a local directory with the objects under an S3 prefix.
+
+    Objects are listed once; a size that differs from the local file's
+    settles that file. Otherwise, cheapest first: a full-object checksum
+    stored with the object (SHA-256, SHA-1, CRC32 or CRC32C) is checked
+    against a hash of the local file, then the ETag, which is the MD5 of
+    single-part uploads and, for multipart ones, the MD5 of the part MD5s
+    (part size taken from the first part). With SSE-KMS or SSE-C, where
+    the ETag is no MD5, the object is read back in ranged GETs of
+    *chunk_size* bytes and compared as it arrives, up to the first
+    difference. In FIPS mode only SHA-256 checksums are used, and objects
+    without one are read back.
+
+    Multipart ETags assume parts of equal size, as the AWS SDKs and CLI
+    upload them.
+
+    The local tree is the left side and the bucket the right; keys ending
+    in ``/`` (folder markers) are skipped.
+
+    :param directory: Local directory path.
+    :param url: ``s3://bucket/prefix`` (the prefix may be empty).
+    :param client: boto3 S3 client; by default ``boto3.client("s3")``,
+        configured from the environment.
+    :param chunk_size: Size of each ranged GET and of local reads.
+    :param follow_symlinks: Follow symbolic links during traversal.
+    :param ignore: Glob patterns to exclude (matched per path component),
+        locally and under the prefix.
+    :param path_filter: PathFilter deciding per entry whether it is compared.
+    :param max_workers: Thread pool size (0=auto, 1=sequential).
+    :raises ValueError: If *url* is not an ``s3://`` URL.
+    :raises ImportError: If no client is given and boto3 is missing.
+    :raises SourceNotFoundError: If the bucket does not exist.
+    :raises SourcePermissionError: If S3 denies access, or a local
+        directory cannot be listed.
+    :raises SourceReadError: On other S3 errors.
+    """
+    validate_path(directory, "directory")
+    validate_chunk_size(chunk_size)
+    validate_max_workers(max_workers)
+    bucket, prefix = parse_s3_url(url)
+    if client is None:
+        client = _client()
+
+    exclude = make_exclude(ignore, path_filter, directory, directory, follow_symlinks)
+    entries, errors = walk_tree(directory, follow_symlinks, exclude)
+    if errors:
+        raise SourcePermissionError(errno.EACCES, "cannot read",
+                                    os.path.join(directory, min(errors)))
+    local = {path: st for path, st in entries.items() if stat.S_ISREG(st.st_mode)}
+
+    objects: dict[str, dict[str, Any]] = {}
+    with _client_errors(url):
+        for page in client.get_paginator("list_objects_v2").paginate(Bucket=bucket,
+                                                                     Prefix=prefix):
+            for obj in page.get("Contents", ()):
+                path = obj["Key"][len(prefix):]
+                if path and not path.endswith("/") and not (exclude and exclude(path)):
+                    objects[path] = obj
+
+    diff: dict[str, DiffReason] = {}
+    same_size = []
+    for path in sorted(local.keys() & objects.keys()):
+        if local[path].st_size != objects[path]["Size"]:
+            diff[path] = DiffReason.SIZE_MISMATCH
+        else:
+            same_size.append(path)
+
+    def check(path: str) -> bool:
+        with _client_errors(url):
+            return _equal(client, bucket, prefix + path, objects[path],
+                          os.path.join(directory, path), chunk_size)
+
+    if same_size:
+        workers = max_workers or min(len(same_size), 8)
+        with ThreadPoolExecutor(max_workers=workers) as pool:
+            for path, equal in zip(same_size, pool.map(check, same_size)):
+                if not equal:
+                    diff[path] = DiffReason.CONTENT_MISMATCH
+
+    only_left = local.keys() - objects.keys()
+    only_right = objects.keys() - local.keys()
+    return DirResult(equal=not (diff or only_left or only_right), diff=diff,
+                     only_left=set(only_left), only_right=set(only_right))
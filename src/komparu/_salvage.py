@@ -0,0 +1,123 @@
+"""Comparison of files on flaky media: read past the sectors that fail.
+
+Optical discs and failing drives lose a sector here and there; a plain
+comparison stops at the first read error. :func:`compare_salvage` reads
+a chunk that fails again in halves, then quarters, down to *min_read*
+bytes, retries those, and records what still cannot be read as an
+:class:`UnreadableRange` — the comparison goes on past it.
+"""
+
+from __future__ import annotations
+
+import errno
+import os
+from typing import BinaryIO
+
+from komparu._cancel import CancelToken
+from komparu._types import ComparisonCancelledError, SalvageResult, UnreadableRange
+from komparu._validate import validate_chunk_size, validate_path
+
+
+def _read_at(f: BinaryIO, offset: int, size: int) -> bytes:
+    """*size* bytes of *f* from *offset*, or OSError if they cannot all be read."""
+    f.seek(offset)
+    parts = []
+    while size:
+        data = f.read(size)
+        if not data:
+            raise OSError(errno.EIO, "file ended before its size")
+        parts.append(data)
+        size -= len(data)
+    return b"".join(parts)
+
+
+def _half(size: int, min_read: int) -> int:
+    """Half of *size*, rounded up to whole *min_read* blocks."""
+    return max(min_read, -(-size // 2 // min_read) * min_read)
+
+
+class _Salvage:
+    __slots__ = ("_files", "_min_read", "_retries", "_cancel", "unreadable")
+
+    def __init__(self, files: tuple[BinaryIO, BinaryIO], min_read: int, retries: int,
+                 cancel: CancelToken | None) -> None:
+        self._files = files
+        self._min_read = min_read
+        self._retries = retries
+        self._cancel = cancel
+        self.unreadable: list[UnreadableRange] = []
+
+    def _read(self, f: BinaryIO, offset: int, size: int, tries: int) -> bytes | None:
+        for _ in range(tries):
+            try:
+                return _read_at(f, offset, size)
+            except OSError:
+                pass
+        return None
+
+    def _lost(self, start: int, end: int, side: str) -> None:
+        last = self.unreadable[-1] if self.unreadable else None
+        if last is not None and last.end == start and last.side == side:
+            self.unreadable[-1] = UnreadableRange(last.start, end, side)
+        else:
+            self.unreadable.append(UnreadableRange(start, end, side))
+
+    def compare(self, start: int, size: int, step: int) -> bool:
+        """Compare *size* bytes from *start* in *step* reads; False on a difference."""
+        end = start + size
+        for offset in range(start, end, step):
+            if self._cancel is not None and self._cancel.cancelled:
+                raise ComparisonCancelledError("comparison cancelled")
+            n = min(step, end - offset)
+            smallest = n <= self._min_read
+            tries = 1 + self._retries if smallest else 1
+            a, b = (self._read(f, offset, n, tries) for f in self._files)
+            if a is not None and b is not None:
+                if a != b:
+                    return False
+            elif not smallest:
+                if not self.compare(offset, n, _half(n, self._min_read)):
+                    return False
+            else:
+                self._lost(offset, offset + n,
+                           "both" if a is None and b is None else "left" if a is None else "right")
+        return True
+
+
+def compare_salvage(path_a: str, path_b: str, *, chunk_size: int = 65536, min_read: int = 512,
+                    retries: int = 2, cancel: CancelToken | None = None) -> SalvageResult:
+    """Compare two files, reading around the sectors either one cannot read.
+
+    A chunk that fails to read on either side is read again in halves,
+    recursively, down to *min_read* bytes; a block of that size that
+    still fails after *retries* further attempts is reported as
+    unreadable and skipped. Everything else is compared as usual, so the
+    result says exactly which bytes went unverified. Block devices
+    (``/dev/sr0``) are read like files.
+
+    :param path_a: First file or block device.
+    :param path_b: Second file or block device.
+    :param chunk_size: Bytes read at a time while reads succeed.
+    :param min_read: Smallest read, the sector size of the medium: 512,
+        or 2048 for optical discs.
+    :param retries: Extra attempts at each failing *min_read* block.
+    :param cancel: Token checked before every read.
+    :returns: Whether the readable bytes match, and the ranges that are not.
+    :raises ValueError: If *min_read* is not positive or exceeds *chunk_size*.
+    :raises OSError: If either file cannot be opened.
+    :raises ComparisonCancelledError: If *cancel* is set.
+    """
+    validate_path(path_a, "path_a")
+    validate_path(path_b, "path_b")
+    validate_chunk_size(chunk_size)
+    if min_read <= 0 or min_read > chunk_size:
+        raise ValueError("min_read must be between 1 and chunk_size")
+    if retries < 0:
+        raise ValueError("retries must be non-negative")
+    with open(path_a, "rb", buffering=0) as fa, open(path_b, "rb", buffering=0) as fb:
+        size = fa.seek(0, os.SEEK_END)
+        if fb.seek(0, os.SEEK_END) != size:
+            return SalvageResult(equal=False)
+        salvage = _Salvage((fa, fb), min_read, retries, cancel)
+        equal = salvage.compare(0, size, chunk_size)
+    return SalvageResult(equal=equal, unreadable=tuple(salvage.unreadable))
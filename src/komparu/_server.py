@@ -0,0 +1,415 @@
+"""``komparu serve``: comparisons started, watched and read back over HTTP with JSON.
+
+Endpoints::
+
+    GET    /v1/health                 {"ok": true, "version": "..."}
+    POST   /v1/comparisons            start a comparison, answered 202 with its job
+    GET    /v1/comparisons            every job still kept
+    GET    /v1/comparisons/ID         one job: status, progress and result or error
+    GET    /v1/comparisons/ID/events  the job as JSON lines, on each change until it ends
+    GET    /v1/comparisons/ID/report  the result alone, once the job is done
+    DELETE /v1/comparisons/ID         cancel a running job, or forget a finished one
+
+A comparison is requested as ``{"left": ..., "right": ..., "archive":
+false, "options": {...}}``, the options being fields of
+:class:`CompareOptions` (``chunk_size``, ``size_precheck``,
+``quick_check``, ``follow_symlinks``, ``max_workers``, ``ignore``). Two
+directories are compared with :func:`compare_dir`, a directory and an
+archive with :func:`compare_dir_archive`, anything else with
+:func:`compare`, or with :func:`compare_archive` when ``archive`` is set.
+Errors are answered ``{"error": "..."}`` with a 4xx status, or 503 when
+the server already runs as many comparisons as it allows; a request's
+``max_workers`` is capped at the server's own.
+
+A server given a :class:`PrometheusMetrics` also serves it at ``GET
+/metrics``, fed by every comparison it runs.
+
+A server given a webhook POSTs each job that finds differences or fails
+there before the job ends, as ``{"event": "differences" | "failed",
+"job": {...}}``, and records how that went in the job's ``webhook``.
+"""
+
+from __future__ import annotations
+
+import contextlib
+import errno
+import hmac
+import http.server
+import json
+import os
+import secrets
+import socket
+import threading
+import time
+from typing import Any
+
+from komparu._agent import resolve_under
+from komparu._api import compare, compare_archive, compare_dir, compare_dir_archive
+from komparu._cancel import CancelToken
+from komparu._logsink import LogSink
+from komparu._metrics import PrometheusMetrics
+from komparu._options import CompareOptions
+from komparu._types import ComparisonCancelledError, DirResult, KomparuError, Progress
+from komparu._webhook import post_webhook, validate_webhook_url
+
+DEFAULT_PORT = 7458
+# Request options a client may set
+_OPTIONS = ("chunk_size", "size_precheck", "quick_check", "follow_symlinks", "max_workers",
+            "ignore")
+# Longest request body read
+_BODY_LIMIT = 1024 * 1024
+# Least time between two progress lines of an event stream
+_EVENT_INTERVAL = 0.2
+
+
+class _Job:
+    """One comparison and what is known of it so far."""
+
+    __slots__ = ("id", "left", "right", "archive", "options", "status", "files_done",
+                 "bytes_done", "result", "error", "webhook", "cancel", "changed", "version",
+                 "started")
+
+    def __init__(self, left: str, right: str, archive: bool, options: CompareOptions) -> None:
+        self.id = secrets.token_hex(8)
+        self.left = left
+        self.right = right
+        self.archive = archive
+        self.options = options
+        self.status = "running"
+        self.files_done = self.bytes_done = 0
+        self.result: dict[str, Any] | None = None
+        self.error: str | None = None
+        self.webhook: dict[str, Any] | None = None
+        self.cancel = CancelToken()
+        self.changed = threading.Condition()
+        self.version = 0  # bumped on every change, for event streams
+        self.started = time.perf_counter()
+
+    def to_dict(self) -> dict[str, Any]:
+        job = {"id": self.id, "left": self.left, "right": self.right, "status": self.status,
+               "progress": {"files_done": self.files_done, "bytes_done": self.bytes_done}}
+        if self.result is not None:
+            job["result"] = self.result
+        if self.error is not None:
+            job["error"] = self.error
+        if self.webhook is not None:
+            job["webhook"] = self.webhook
+        return job
+
+    def update(self, **changes: Any) -> None:
+        with self.changed:
+            for name, value in changes.items():
+                setattr(self, name, value)
+            self.version += 1
+            self.changed.notify_all()
+
+
+class _HttpError(Exception):
+    def __init__(self, status: int, message: str) -> None:
+        super().__init__(message)
+        self.status = status
+
+
+def _notify(job: _Job, changes: dict[str, Any], url: str, secret: str | None) -> None:
+    """Report *job*, about to take *changes*, to the webhook at *url*."""
+    event = "failed" if changes["status"] == "failed" else "differences"
+    payload = {"event": event, "job": {**job.to_dict(), **changes}}
+    try:
+        changes["webhook"] = {"status": post_webhook(url, payload, secret=secret)}
+    except OSError as exc:
+        changes["webhook"] = {"error": str(exc)}
+
+
+def _log(sink: LogSink, job: _Job, changes: dict[str, Any]) -> None:
+    if changes["status"] == "failed":
+        sink.error(f"comparison {job.id} of {job.left} {job.right} failed: {changes['error']}",
+                   id=job.id, left=job.left, right=job.right)
+    elif changes["status"] == "done" and "diff" in changes["result"]:
+        sink.dir_result(DirResult.from_dict(changes["result"]), job.left, job.right)
+    elif changes["status"] == "done":
+        sink.file_result(changes["result"]["equal"], job.left, job.right)
+
+
+def _run(job: _Job, server: ComparisonServer) -> None:
+    def progress(snapshot: Progress) -> None:
+        job.update(files_done=snapshot.files_done, bytes_done=snapshot.bytes_done)
+
+    left, right, options = job.left, job.right, job.options
+    try:
+        if job.archive:
+            result = compare_archive(left, right, chunk_size=options.chunk_size).to_dict()
+        elif os.path.isdir(left) and os.path.isdir(right):
+            result = compare_dir(left, right, options=options, progress=progress,
+                                 metrics=server.metrics, cancel=job.cancel).to_dict()
+        elif os.path.isdir(left) or os.path.isdir(right):
+            result = compare_dir_archive(left, right, chunk_size=options.chunk_size,
+                                         size_precheck=options.size_precheck,
+                                         quick_check=options.quick_check,
+                                         follow_symlinks=options.follow_symlinks,
+                                         max_workers=options.max_workers,
+                                         ignore=list(options.ignore) or None,
+                                         cancel=job.cancel).to_dict()
+        else:
+            result = {"equal": compare(left, right, options=options, cancel=job.cancel)}
+    except ComparisonCancelledError:
+        changes: dict[str, Any] = {"status": "cancelled"}
+    except (OSError, ValueError, ImportError, KomparuError) as exc:
+        changes = {"status": "failed", "error": str(exc)}
+    else:
+        changes = {"status": "done", "result": result}
+    if server.webhook is not None and (changes["status"] == "failed" or (
+            changes["status"] == "done" and not changes["result"].get("equal", True))):
+        _notify(job, changes, server.webhook, server.webhook_secret)
+    if server.metrics is not None:
+        outcome = changes["status"]
+        if outcome == "done":
+            outcome = "equal" if changes["result"].get("equal", True) else "different"
+        server.metrics.finished(outcome, time.perf_counter() - job.started)
+    if server.log_sink is not None:
+        with contextlib.suppress(OSError):  # a lost record must not lose the job
+            _log(server.log_sink, job, changes)
+    job.update(**changes)
+
+
+class _Handler(http.server.BaseHTTPRequestHandler):
+    server: ComparisonServer
+    server_version = "komparu"
+
+    def log_message(self, format: str, *args: Any) -> None:
+        if self.server.log:
+            super().log_message(format, *args)
+
+    def _send(self, status: int, body: dict[str, Any] | list[Any],
+              headers: dict[str, str] | None = None) -> None:
+        data = json.dumps(body).encode() + b"\n"
+        self.send_response(status)
+        self.send_header("Content-Type", "application/json")
+        self.send_header("Content-Length", str(len(data)))
+        for name, value in (headers or {}).items():
+            self.send_header(name, value)
+        self.end_headers()
+        self.wfile.write(data)
+
+    def _dispatch(self, method: str) -> None:
+        try:
+            self._authorize()
+            parts = self.path.split("?", 1)[0].strip("/").split("/")
+            if parts == ["metrics"] and method == "GET" and self.server.metrics is not None:
+                self._metrics(self.server.metrics)
+                return
+            if parts[0] != "v1":
+                raise _HttpError(404, "not found")
+            self._route(method, parts[1:])
+        except _HttpError as exc:
+            self._send(exc.status, {"error": str(exc)})
+        except (BrokenPipeError, ConnectionResetError):
+            pass
+
+    def do_GET(self) -> None:
+        self._dispatch("GET")
+
+    def do_POST(self) -> None:
+        self._dispatch("POST")
+
+    def do_DELETE(self) -> None:
+        self._dispatch("DELETE")
+
+    def _authorize(self) -> None:
+        token = self.server.token
+        if token is None:
+            return
+        scheme, _, given = (self.headers.get("Authorization") or "").partition(" ")
+        if scheme.lower() != "bearer" or not hmac.compare_digest(given.strip().encode(),
+                                                                 token.encode()):
+            raise _HttpError(401, "missing or wrong bearer token")
+
+    def _route(self, method: str, parts: list[str]) -> None:
+        if parts == ["health"] and method == "GET":
+            from komparu import __version__
+            self._send(200, {"ok": True, "version": __version__})
+        elif parts == ["comparisons"] and method == "GET":
+            self._send(200, [job.to_dict() for job in self.server.jobs()])
+        elif parts == ["comparisons"] and method == "POST":
+            job = self.server.submit(**self._comparison())
+            self._send(202, job.to_dict(), {"Location": f"/v1/comparisons/{job.id}"})
+        elif len(parts) in (2, 3) and parts[0] == "comparisons":
+            job = self.server.job(parts[1])
+            if job is None:
+                raise _HttpError(404, f"no comparison {parts[1]}")
+            tail = parts[2] if len(parts) == 3 else None
+            if (method, tail) == ("GET", None):
+                self._send(200, job.to_dict())
+            elif (method, tail) == ("GET", "events"):
+                self._events(job)
+            elif (method, tail) == ("GET", "report"):
+                if job.status != "done":
+                    raise _HttpError(409, f"comparison is {job.status}")
+                self._send(200, job.result or {})
+            elif (method, tail) == ("DELETE", None):
+                self.server.remove(job)
+                self._send(200, job.to_dict())
+            else:
+                raise _HttpError(405, f"{method} not allowed here")
+        else:
+            raise _HttpError(404, "not found")
+
+    def _metrics(self, metrics: PrometheusMetrics) -> None:
+        data = metrics.render().encode()
+        self.send_response(200)
+        self.send_header("Content-Type", "text/plain; version=0.0.4")
+        self.send_header("Content-Length", str(len(data)))
+        self.end_headers()
+        self.wfile.write(data)
+
+    def _comparison(self) -> dict[str, Any]:
+        try:
+            length = int(self.headers.get("Content-Length") or 0)
+        except ValueError:
+            raise _HttpError(400, "bad Content-Length") from None
+        if length > _BODY_LIMIT:
+            raise _HttpError(413, "request too large")
+        try:
+            body = json.loads(self.rfile.read(length) or b"null")
+        except ValueError:
+            raise _HttpError(400, "request body is not JSON") from None
+        if not isinstance(body, dict):
+            raise _HttpError(400, "request body must be a JSON object")
+        left, right, options = body.get("left"), body.get("right"), body.get("options") or {}
+        if not (isinstance(left, str) and isinstance(right, str)):
+            raise _HttpError(400, "left and right must be paths or URLs")
+        if not isinstance(options, dict) or not set(options) <= set(_OPTIONS):
+            raise _HttpError(400, f"options may only set {', '.join(_OPTIONS)}")
+        if isinstance(options.get("ignore"), str):
+            raise _HttpError(400, "ignore must be a list of patterns")
+        try:
+            return {"left": self.server.resolve(left), "right": self.server.resolve(right),
+                    "archive": bool(body.get("archive", False)),
+                    "options": self.server.options.replace(**options)}
+        except PermissionError as exc:
+            raise _HttpError(403, f"{exc.filename}: {exc.strerror}") from None
+        except (TypeError, ValueError) as exc:
+            raise _HttpError(400, str(exc)) from None
+
+    def _events(self, job: _Job) -> None:
+        self.send_response(200)
+        self.send_header("Content-Type", "application/x-ndjson")
+        self.send_header("Cache-Control", "no-cache")
+        self.end_headers()
+        seen = -1
+        while True:
+            with job.changed:
+                job.changed.wait_for(lambda: job.version != seen, timeout=15)
+                seen, line, running = job.version, job.to_dict(), job.status == "running"
+            self.wfile.write(json.dumps(line).encode() + b"\n")
+            self.wfile.flush()
+            if not running:
+                return
+            with job.changed:
+                job.changed.wait_for(lambda: job.status != "running", timeout=_EVENT_INTERVAL)
+
+
+class ComparisonServer(http.server.ThreadingHTTPServer):
+    """An HTTP server running comparisons on request, each on its own thread.
+
+    Clients start comparisons, follow their progress and fetch their
+    results with JSON over HTTP, so orchestration can verify trees
+    without wrapping the CLI; see the module for the endpoints. Finished
+    jobs are kept, oldest dropped first, up to *max_jobs*. At most
+    *max_running* comparisons run at once, each on at most the workers of
+    *options*.
+
+    :param address: ``(host, port)`` to listen on; port 0 picks a free one.
+    :param options: Defaults of every comparison; requests may override
+        the chunk, precheck, symlink, worker and ignore options, asking
+        for no more workers than its ``max_workers`` (the CPU count if 0).
+    :param roots: Directories comparisons may read under; relative
+        request paths start from the first, and URLs are refused. None
+        allows any path or URL.
+    :param token: Secret every request must send as ``Authorization:
+        Bearer``.
+    :param max_jobs: Finished jobs kept for clients to read back.
+    :param max_running: Comparisons run at once; more are refused.
+    :param log: Log each request to standard error.
+    :param webhook: URL every job that finds differences or fails is
+        POSTed to.
+    :param webhook_secret: Key the webhook body is signed with, as
+        ``X-Komparu-Signature: sha256=<hex HMAC-SHA256>``.
+    :param metrics: PrometheusMetrics every comparison reports to,
+        served at ``/metrics``.
+    :param log_sink: LogSink every finished comparison is written to.
+    """
+
+    daemon_threads = True
+    allow_reuse_address = True
+
+    def __init__(self, address: tuple[str, int], *, options: CompareOptions | None = None,
+                 roots: list[str] | None = None, token: str | None = None,
+                 max_jobs: int = 100, max_running: int = 4, log: bool = False,
+                 webhook: str | None = None,
+                 webhook_secret: str | None = None,
+                 metrics: PrometheusMetrics | None = None,
+                 log_sink: LogSink | None = None) -> None:
+        if max_jobs < 1:
+            raise ValueError("max_jobs must be at least 1")
+        if max_running < 1:
+            raise ValueError("max_running must be at least 1")
+        if webhook is not None:
+            validate_webhook_url(webhook)
+        elif webhook_secret is not None:
+            raise ValueError("webhook_secret needs a webhook")
+        self.options = options or CompareOptions()
+        self.roots = [os.path.realpath(root) for root in roots] if roots else None
+        self.token = token
+        self.max_jobs = max_jobs
+        self.max_running = max_running
+        self.log = log
+        self.webhook = webhook
+        self.webhook_secret = webhook_secret
+        self.metrics = metrics
+        self.log_sink = log_sink
+        self._jobs: dict[str, _Job] = {}
+        self._lock = threading.Lock()
+        if ":" in address[0]:
+            self.address_family = socket.AF_INET6
+        super().__init__(address, _Handler)
+
+    def resolve(self, path: str) -> str:
+        """*path* as a comparison reads it, checked against the roots."""
+        if self.roots is not None and "://" in path:
+            raise PermissionError(errno.EACCES, "URLs are not served", path)
+        return resolve_under(path, self.roots) if self.roots else path
+
+    def submit(self, left: str, right: str, archive: bool, options: CompareOptions) -> _Job:
+        workers = self.options.max_workers or os.cpu_count() or 1
+        if options.max_workers > workers:
+            options = options.replace(max_workers=workers)
+        job = _Job(left, right, archive, options)
+        with self._lock:
+            running = sum(old.status == "running" for old in self._jobs.values())
+            if running >= self.max_running:
+                raise _HttpError(503, f"already running {running} comparisons")
+            self._jobs[job.id] = job
+            finished = [old for old in self._jobs.values() if old.status != "running"]
+            for old in finished[:max(0, len(finished) - self.max_jobs)]:
+                del self._jobs[old.id]
+        if self.metrics is not None:
+            self.metrics.started()
+        threading.Thread(target=_run, args=(job, self), name=f"komparu-{job.id}",
+                         daemon=True).start()
+        return job
+
+    def jobs(self) -> list[_Job]:
+        with self._lock:
+            return list(self._jobs.values())
+
+    def job(self, id: str) -> _Job | None:
+        with self._lock:
+            return self._jobs.get(id)
+
+    def remove(self, job: _Job) -> None:
+        """Cancel *job* if it is running, else forget it."""
+        if job.status == "running":
+            job.cancel.cancel()
+            return
+        with self._lock:
+            self._jobs.pop(job.id, None)
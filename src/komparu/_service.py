@@ -0,0 +1,153 @@
+"""Reusable, thread-safe comparator bound to a set of options."""
+
+from __future__ import annotations
+
+from collections.abc import Callable, Iterator, Mapping
+from types import MappingProxyType
+
+from komparu._api import compare, compare_dir, iter_dir, plan_dir
+from komparu._cancel import CancelToken
+from komparu._comparator import ComparatorDispatch, ContentComparator
+from komparu._dircache import DirCache
+from komparu._filter import PathFilter, all_filters, make_exclude
+from komparu._helpers import DEFAULT_MAX_PENDING
+from komparu._metrics import MetricsSink
+from komparu._options import CompareOptions
+from komparu._types import DiffEntry, DirPlan, DirResult, Progress, Source
+from komparu._visitor import CompareVisitor
+
+
+class Comparator:
+    """Comparison settings validated once and shared across threads.
+
+    Bundles a :class:`CompareOptions` with the directory plug-ins
+    (``path_filter``, ``comparators``, ``metrics``) so a long-running
+    service builds it at startup and calls it from any number of threads.
+    The instance is immutable; every call gets its own C task, while the
+    worker pool and HTTP connection cache behind the engine are
+    process-wide and stay warm between calls. Plug-ins are shared too, so
+    they must be safe to call from several threads at once.
+
+    With a *cache*, directory calls also remember which file pairs were
+    equal, so re-verifying the same trees only reads files that changed
+    since the previous call.
+
+    :param options: Options for every call (defaults if None).
+    :param path_filter: PathFilter for directory calls.
+    :param comparators: ContentComparator per glob pattern for directory
+        calls (copied).
+    :param metrics: MetricsSink for :meth:`compare_dir`.
+    :param cache: DirCache shared by every directory call.
+    """
+
+    __slots__ = ("_options", "_dir_options", "_comparators", "_metrics", "_cache")
+
+    def __init__(
+        self,
+        options: CompareOptions | None = None,
+        *,
+        path_filter: PathFilter | None = None,
+        comparators: Mapping[str, ContentComparator] | None = None,
+        metrics: MetricsSink | None = None,
+        cache: DirCache | None = None,
+    ) -> None:
+        if options is None:
+            options = CompareOptions()
+        elif not isinstance(options, CompareOptions):
+            raise TypeError(f"options must be CompareOptions, got {type(options).__name__}")
+        if metrics is not None and not isinstance(metrics, MetricsSink):
+            raise TypeError(f"metrics must be a MetricsSink, got {type(metrics).__name__}")
+        if cache is not None and not isinstance(cache, DirCache):
+            raise TypeError(f"cache must be a DirCache, got {type(cache).__name__}")
+        # Validate the plug-ins now rather than on the first call
+        make_exclude(None, path_filter, "", "")
+        frozen = MappingProxyType(dict(comparators or {}))
+        ComparatorDispatch("", "", frozen)
+
+        self._options = options
+        # The directory calls take the filter through the options
+        self._dir_options = options.replace(
+            path_filter=all_filters(options.path_filter, path_filter))
+        self._comparators = frozen
+        self._metrics = metrics
+        self._cache = cache
+
+    @property
+    def options(self) -> CompareOptions:
+        """The bound options."""
+        return self._options
+
+    @property
+    def cache(self) -> DirCache | None:
+        """The bound warm cache, if any."""
+        return self._cache
+
+    def __repr__(self) -> str:
+        return f"Comparator({self._options!r})"
+
+    def compare(
+        self,
+        source_a: str | Source | int,
+        source_b: str | Source | int,
+        *,
+        cancel: CancelToken | None = None,
+    ) -> bool:
+        """:func:`komparu.compare` with the bound options."""
+        return compare(source_a, source_b, options=self._options, cancel=cancel)
+
+    def compare_dir(
+        self,
+        dir_a: str | int,
+        dir_b: str | int,
+        *,
+        visitor: CompareVisitor | None = None,
+        progress: Callable[[Progress], None] | None = None,
+        cancel: CancelToken | None = None,
+        prescan: bool = False,
+    ) -> DirResult:
+        """:func:`komparu.compare_dir` with the bound options and plug-ins.
+
+        *visitor*, *progress* and *prescan* are per call, since they
+        usually track one request.
+        """
+        return compare_dir(
+            dir_a, dir_b,
+            options=self._dir_options,
+            comparators=self._comparators or None,
+            metrics=self._metrics,
+            visitor=visitor,
+            progress=progress,
+            cancel=cancel,
+            cache=self._cache,
+            prescan=prescan,
+        )
+
+    def iter_dir(
+        self,
+        dir_a: str | int,
+        dir_b: str | int,
+        *,
+        include_equal: bool = False,
+        max_pending: int = DEFAULT_MAX_PENDING,
+        cancel: CancelToken | None = None,
+    ) -> Iterator[DiffEntry]:
+        """:func:`komparu.iter_dir` with the bound options and plug-ins."""
+        return iter_dir(
+            dir_a, dir_b,
+            options=self._dir_options,
+            comparators=self._comparators or None,
+            include_equal=include_equal,
+            max_pending=max_pending,
+            cancel=cancel,
+            cache=self._cache,
+        )
+
+    def plan_dir(
+        self,
+        dir_a: str | int,
+        dir_b: str | int,
+        *,
+        cancel: CancelToken | None = None,
+    ) -> DirPlan:
+        """:func:`komparu.plan_dir` with the bound options and path filter."""
+        return plan_dir(dir_a, dir_b, options=self._dir_options, cancel=cancel)
@@ -0,0 +1,191 @@
+"""Minisign signatures for manifests: Ed25519 over a BLAKE2b-512 prehash."""
+
+from __future__ import annotations
+
+import base64
+import binascii
+import hashlib
+import os
+import time
+from typing import Any
+
+from komparu._hashing import require_fips_approved
+from komparu._helpers import atomic_open, fetch_url, is_url
+from komparu._types import SignatureError
+
+# Secret key layout (after base64): signature algorithm, KDF, checksum
+# algorithm, KDF salt and limits, then the key number block, encrypted
+# with scrypt output when the KDF is "Sc"
+_SK_LENGTH = 2 + 2 + 2 + 32 + 8 + 8 + 104
+_PK_LENGTH = 2 + 8 + 32
+_SIG_LENGTH = 2 + 8 + 64
+
+
+def _ed25519() -> Any:
+    try:
+        from cryptography.hazmat.primitives.asymmetric import ed25519
+    except ImportError:
+        raise ImportError(
+            "manifest signatures need cryptography: pip install komparu[sign]"
+        ) from None
+    return ed25519
+
+
+def _key_lines(path: str, what: str, *, remote: bool = False) -> list[str]:
+    if remote and is_url(path):
+        lines = fetch_url(path).decode("utf-8", "replace").splitlines()
+    else:
+        with open(path, encoding="utf-8") as f:
+            lines = [line.rstrip("\r\n") for line in f]
+    if not lines or not lines[0].startswith("untrusted comment:"):
+        raise ValueError(f"{path}: not a minisign {what}")
+    return lines
+
+
+def _decode(line: str, length: int, path: str, what: str) -> bytes:
+    try:
+        raw = base64.b64decode(line, validate=True)
+    except binascii.Error:
+        raw = b""
+    if len(raw) != length:
+        raise ValueError(f"{path}: not a minisign {what}")
+    return raw
+
+
+def _key_id(raw: bytes) -> str:
+    # minisign prints the little-endian key id as hex
+    return f"{int.from_bytes(raw, 'little'):016X}"
+
+
+def _scrypt_params(opslimit: int, memlimit: int) -> tuple[int, int, int]:
+    # libsodium's crypto_pwhash_scryptsalsa208sha256 parameter choice
+    opslimit = max(opslimit, 32768)
+    r = 8
+    cpu_bound = opslimit < memlimit // 32
+    max_n = opslimit // (r * 4) if cpu_bound else memlimit // (r * 128)
+    n_log2 = 1
+    while n_log2 < 63 and 1 << n_log2 <= max_n // 2:
+        n_log2 += 1
+    p = 1 if cpu_bound else min((opslimit // 4) >> n_log2, 0x3FFFFFFF) // r
+    return 1 << n_log2, r, p
+
+
+def _secret_key_raw(path: str) -> bytes:
+    lines = _key_lines(path, "secret key")
+    raw = _decode(lines[1] if len(lines) > 1 else "", _SK_LENGTH, path, "secret key")
+    if raw[:2] != b"Ed" or raw[4:6] != b"B2" or raw[2:4] not in (b"Sc", b"\0\0"):
+        raise ValueError(f"{path}: unsupported minisign secret key")
+    return raw
+
+
+def key_is_encrypted(path: str) -> bool:
+    """Whether a minisign secret key needs a password."""
+    return _secret_key_raw(path)[2:4] == b"Sc"
+
+
+def _load_secret_key(path: str, password: str | None) -> tuple[bytes, bytes]:
+    raw = _secret_key_raw(path)
+    keynum = raw[54:]
+    if raw[2:4] == b"Sc":
+        if password is None:
+            raise ValueError(f"{path}: secret key is encrypted; pass its password")
+        n, r, p = _scrypt_params(int.from_bytes(raw[38:46], "little"),
+                                 int.from_bytes(raw[46:54], "little"))
+        stream = hashlib.scrypt(password.encode(), salt=raw[6:38], n=n, r=r, p=p,
+                                maxmem=128 * r * (n + p + 2) + (1 << 20), dklen=len(keynum))
+        keynum = bytes(x ^ y for x, y in zip(keynum, stream))
+    key_id, secret, checksum = keynum[:8], keynum[8:72], keynum[72:]
+    if hashlib.blake2b(raw[:2] + key_id + secret, digest_size=32).digest() != checksum:
+        raise ValueError(f"{path}: wrong password or damaged secret key")
+    return key_id, secret[:32]
+
+
+def sign_manifest(
+    path: str,
+    secret_key: str,
+    *,
+    password: str | None = None,
+    trusted_comment: str | None = None,
+) -> str:
+    """Sign a manifest file with a minisign key, as ``minisign -S`` does.
+
+    The signature goes next to the manifest, in ``path + ".minisig"``,
+    and can be checked with :func:`verify_signature`, ``minisign -V`` or
+    :func:`komparu.verify_manifest`.
+
+    :param path: Manifest file to sign.
+    :param secret_key: minisign secret key file (``minisign -G``).
+    :param password: Password of an encrypted secret key.
+    :param trusted_comment: Signed comment; defaults to the time and
+        file name.
+    :returns: Path of the signature file.
+    :raises ValueError: If the key is malformed, or encrypted and the
+        password is missing or wrong, or in FIPS mode (the prehash is
+        BLAKE2b).
+    :raises ImportError: If the ``cryptography`` package is missing.
+    """
+    require_fips_approved("blake2b", "minisign signatures")
+    ed25519 = _ed25519()
+    key_id, seed = _load_secret_key(secret_key, password)
+    if trusted_comment is None:
+        trusted_comment = (f"timestamp:{int(time.time())}\t"
+                           f"file:{os.path.basename(path)}\thashed")
+    if "\n" in trusted_comment or "\r" in trusted_comment:
+        raise ValueError("trusted_comment must be a single line")
+    with open(path, "rb") as f:
+        prehash = hashlib.file_digest(f, "blake2b").digest()
+    signer = ed25519.Ed25519PrivateKey.from_private_bytes(seed)
+    signature = signer.sign(prehash)
+    global_signature = signer.sign(signature + trusted_comment.encode())
+    signature_path = path + ".minisig"
+    with atomic_open(signature_path, "w", encoding="utf-8") as f:
+        f.write("untrusted comment: signature from komparu secret key\n"
+                f"{base64.b64encode(b'ED' + key_id + signature).decode()}\n"
+                f"trusted comment: {trusted_comment}\n"
+                f"{base64.b64encode(global_signature).decode()}\n")
+    return signature_path
+
+
+def verify_signature(data: bytes, signature: str, public_key: str) -> str:
+    """Check a minisign signature, as ``minisign -V`` does.
+
+    :param data: The signed content.
+    :param signature: Signature file (``.minisig``), or its HTTP(S) URL.
+    :param public_key: minisign public key file; always local, since it
+        is what the signature is trusted by.
+    :returns: The signed trusted comment.
+    :raises SignatureError: If the signature is from another key or does
+        not verify.
+    :raises ValueError: If either file is malformed, or in FIPS mode for
+        a signature over a BLAKE2b prehash.
+    :raises ImportError: If the ``cryptography`` package is missing.
+    """
+    ed25519 = _ed25519()
+    from cryptography.exceptions import InvalidSignature
+
+    lines = _key_lines(public_key, "public key")
+    pk = _decode(lines[1] if len(lines) > 1 else "", _PK_LENGTH, public_key, "public key")
+    if pk[:2] != b"Ed":
+        raise ValueError(f"{public_key}: unsupported minisign public key")
+    lines = _key_lines(signature, "signature", remote=True)
+    if len(lines) < 4 or not lines[2].startswith("trusted comment: "):
+        raise ValueError(f"{signature}: not a minisign signature")
+    sig = _decode(lines[1], _SIG_LENGTH, signature, "signature")
+    global_signature = _decode(lines[3], 64, signature, "signature")
+    trusted_comment = lines[2].removeprefix("trusted comment: ")
+    if sig[:2] not in (b"ED", b"Ed"):
+        raise ValueError(f"{signature}: unsupported signature algorithm")
+    if sig[:2] == b"ED":
+        require_fips_approved("blake2b", f"{signature}: the prehashed signature")
+    if sig[2:10] != pk[2:10]:
+        raise SignatureError(f"{signature}: made with key {_key_id(sig[2:10])}, "
+                             f"not {_key_id(pk[2:10])}")
+    # "ED" signs the BLAKE2b-512 of the content, legacy "Ed" the content itself
+    message = hashlib.blake2b(data).digest() if sig[:2] == b"ED" else data
+    verifier = ed25519.Ed25519PublicKey.from_public_bytes(pk[10:])
+    try:
+        verifier.verify(sig[10:], message)
+        verifier.verify(global_signature, sig[10:] + trusted_comment.encode())
+    except InvalidSignature:
+        raise SignatureError(f"{signature}: signature does not verify") from None
+    return trusted_comment
@@ -0,0 +1,317 @@
+"""Point-in-time snapshots of a tree: paths, metadata and content digests.
+
+A snapshot file is the magic ``KPZ1`` followed by a zlib stream of::
+
+    algorithm (string), digest size (varint), entry count (varint)
+    per entry, in path order:
+        shared prefix length with the previous path (varint),
+        rest of the path (bytes), kind (one byte: F, D or L),
+        mode, uid, gid, size (varints), mtime_ns (zigzag varint),
+        digest (digest size bytes, files) or target (bytes, symlinks),
+        xattr count (varint), then name and value (bytes) per xattr
+
+where a string or bytes field is a varint length and that many bytes,
+and paths and link targets are file-system bytes.
+"""
+
+from __future__ import annotations
+
+import errno
+import os
+import stat
+import zlib
+from collections.abc import Collection
+from typing import Any, BinaryIO
+
+from komparu._filter import PathFilter, make_exclude
+from komparu._hashing import new_hash, validate_algorithm
+from komparu._helpers import atomic_open, walk_tree
+from komparu._manifest import hash_files
+from komparu._metadata import METADATA_ATTRIBUTES, is_under, read_xattrs, validate_attributes
+from komparu._types import (
+    DiffReason,
+    DirResult,
+    Snapshot,
+    SnapshotEntry,
+    SourcePermissionError,
+    path_key,
+)
+from komparu._validate import validate_max_workers, validate_path
+
+_MAGIC = b"KPZ1"
+_KINDS = {"file": b"F", "dir": b"D", "symlink": b"L"}
+_KIND_NAMES = {code[0]: kind for kind, code in _KINDS.items()}
+
+
+def snapshot_tree(
+    directory: str,
+    *,
+    algorithm: str = "sha256",
+    follow_symlinks: bool = True,
+    ignore: list[str] | None = None,
+    path_filter: PathFilter | None = None,
+    max_workers: int = 0,
+) -> Snapshot:
+    """Record every entry of a tree with its metadata and content digest.
+
+    Files, directories and symlinks (only without *follow_symlinks*, or
+    dangling ones) are recorded; other special files are left out.
+
+    :param directory: Directory path.
+    :param algorithm: Any fixed-size :mod:`hashlib` algorithm, or
+        ``blake3``, ``xxh3``, ``xxh128``, ``crc32`` or ``crc32c``.
+    :param follow_symlinks: Follow symbolic links during traversal.
+    :param ignore: Glob patterns to exclude (matched per path component).
+    :param path_filter: PathFilter deciding per entry whether it is recorded.
+    :param max_workers: Thread pool size for hashing (0=auto, 1=sequential).
+    :raises SourcePermissionError: If a directory cannot be listed.
+    """
+    validate_path(directory, "directory")
+    validate_algorithm(algorithm)
+    validate_max_workers(max_workers)
+    exclude = make_exclude(ignore, path_filter, directory, directory, follow_symlinks)
+    walked, errors = walk_tree(directory, follow_symlinks, exclude)
+    if errors:
+        raise SourcePermissionError(errno.EACCES, "cannot read",
+                                    os.path.join(directory, min(errors)))
+    files = sorted(path for path, st in walked.items() if stat.S_ISREG(st.st_mode))
+    digests = dict(hash_files(directory, files, algorithm, max_workers))
+    entries: dict[str, SnapshotEntry] = {}
+    for path in sorted(walked):
+        st = walked[path]
+        full = os.path.join(directory, path)
+        if stat.S_ISREG(st.st_mode):
+            kind = "file"
+        elif stat.S_ISDIR(st.st_mode):
+            kind = "dir"
+        elif stat.S_ISLNK(st.st_mode):
+            kind = "symlink"
+        else:
+            continue
+        entries[path] = SnapshotEntry(
+            kind=kind, mode=stat.S_IMODE(st.st_mode), uid=st.st_uid, gid=st.st_gid,
+            size=st.st_size, mtime_ns=st.st_mtime_ns, digest=digests.get(path),
+            target=os.readlink(full) if kind == "symlink" else None,
+            xattrs=read_xattrs(full, follow_symlinks and kind != "symlink"),
+        )
+    return Snapshot(algorithm=algorithm, entries=entries)
+
+
+def _varint(value: int) -> bytes:
+    out = bytearray()
+    while value > 0x7F:
+        out.append(value & 0x7F | 0x80)
+        value >>= 7
+    out.append(value)
+    return bytes(out)
+
+
+def _zigzag(value: int) -> int:
+    # mtimes before 1970 are negative
+    return value << 1 if value >= 0 else (-value << 1) - 1
+
+
+def _field(data: bytes) -> bytes:
+    return _varint(len(data)) + data
+
+
+def write_snapshot(snapshot: Snapshot, file: str | BinaryIO) -> None:
+    """Store a snapshot in the compact binary format.
+
+    :param snapshot: Snapshot from :func:`snapshot_tree`.
+    :param file: File path, or a binary stream open for writing.
+    """
+    digest_size = new_hash(snapshot.algorithm).digest_size
+    body = [_field(snapshot.algorithm.encode()), _varint(digest_size),
+            _varint(len(snapshot.entries))]
+    previous = b""
+    for path in sorted(snapshot.entries, key=path_key):
+        entry = snapshot.entries[path]
+        name = os.fsencode(path)
+        shared = len(os.path.commonprefix([previous, name]))
+        body += [_varint(shared), _field(name[shared:]), _KINDS[entry.kind],
+                 _varint(entry.mode), _varint(entry.uid), _varint(entry.gid),
+                 _varint(entry.size), _varint(_zigzag(entry.mtime_ns))]
+        if entry.kind == "file":
+            body.append(bytes.fromhex(entry.digest or ""))
+        elif entry.kind == "symlink":
+            body.append(_field(os.fsencode(entry.target or "")))
+        body.append(_varint(len(entry.xattrs)))
+        for xattr, value in sorted(entry.xattrs.items()):
+            body += [_field(os.fsencode(xattr)), _field(value)]
+        previous = name
+    data = _MAGIC + zlib.compress(b"".join(body), 9)
+    if isinstance(file, str):
+        with atomic_open(file, "wb") as f:
+            f.write(data)
+    else:
+        file.write(data)
+
+
+class _Reader:
+    __slots__ = ("_data", "_pos", "_where")
+
+    def __init__(self, data: bytes, where: str) -> None:
+        self._data, self._pos, self._where = data, 0, where
+
+    def bytes(self, n: int) -> bytes:
+        if self._pos + n > len(self._data):
+            raise ValueError(f"{self._where}: truncated snapshot")
+        chunk = self._data[self._pos:self._pos + n]
+        self._pos += n
+        return chunk
+
+    def varint(self) -> int:
+        value = shift = 0
+        while True:
+            byte = self.bytes(1)[0]
+            value |= (byte & 0x7F) << shift
+            if byte < 0x80:
+                return value
+            shift += 7
+
+    def field(self) -> bytes:
+        return self.bytes(self.varint())
+
+    def at_end(self) -> bool:
+        return self._pos == len(self._data)
+
+
+def read_snapshot(file: str | BinaryIO) -> Snapshot:
+    """Read a snapshot stored by :func:`write_snapshot`.
+
+    :param file: File path, or a binary stream open for reading.
+    :raises ValueError: If the data is not a komparu snapshot or is damaged.
+    """
+    if isinstance(file, str):
+        where = file
+        with open(file, "rb") as f:
+            data = f.read()
+    else:
+        where = getattr(file, "name", "snapshot")
+        data = file.read()
+    if not data.startswith(_MAGIC):
+        raise ValueError(f"{where}: not a komparu snapshot")
+    try:
+        body = zlib.decompress(data[len(_MAGIC):])
+    except zlib.error:
+        raise ValueError(f"{where}: damaged snapshot") from None
+    reader = _Reader(body, where)
+    algorithm = reader.field().decode("ascii", "replace")
+    digest_size = reader.varint()
+    entries: dict[str, SnapshotEntry] = {}
+    previous = b""
+    for _ in range(reader.varint()):
+        shared = reader.varint()
+        name = previous[:shared] + reader.field()
+        kind = _KIND_NAMES.get(reader.bytes(1)[0])
+        if kind is None:
+            raise ValueError(f"{where}: damaged snapshot")
+        fields: dict[str, Any] = {"mode": reader.varint(), "uid": reader.varint(),
+                                  "gid": reader.varint(), "size": reader.varint()}
+        mtime = reader.varint()
+        fields["mtime_ns"] = mtime >> 1 if mtime & 1 == 0 else -((mtime + 1) >> 1)
+        if kind == "file":
+            fields["digest"] = reader.bytes(digest_size).hex()
+        elif kind == "symlink":
+            fields["target"] = os.fsdecode(reader.field())
+        fields["xattrs"] = {os.fsdecode(reader.field()): reader.field()
+                            for _ in range(reader.varint())}
+        entries[os.fsdecode(name)] = SnapshotEntry(kind=kind, **fields)
+        previous = name
+    if not reader.at_end():
+        raise ValueError(f"{where}: damaged snapshot")
+    return Snapshot(algorithm=algorithm, entries=entries)
+
+
+def snapshot_changes(
+    entry_a: SnapshotEntry,
+    entry_b: SnapshotEntry,
+    attributes: Collection[str] = METADATA_ATTRIBUTES,
+) -> dict[str, tuple[Any, Any]]:
+    """Metadata that differs between two entries, as :func:`komparu.metadata_diff` reports it."""
+    changes: dict[str, tuple[Any, Any]] = {}
+    if "mode" in attributes and entry_a.mode != entry_b.mode:
+        changes["mode"] = (entry_a.mode, entry_b.mode)
+    if "owner" in attributes and (entry_a.uid, entry_a.gid) != (entry_b.uid, entry_b.gid):
+        changes["owner"] = ((entry_a.uid, entry_a.gid), (entry_b.uid, entry_b.gid))
+    if "xattrs" in attributes and entry_a.xattrs != entry_b.xattrs:
+        changes["xattrs"] = (entry_a.xattrs, entry_b.xattrs)
+    return changes
+
+
+def _load(snapshot: str | Snapshot | BinaryIO) -> Snapshot:
+    return snapshot if isinstance(snapshot, Snapshot) else read_snapshot(snapshot)
+
+
+def compare_snapshot(
+    snapshot: str | Snapshot | BinaryIO,
+    current: str | Snapshot,
+    *,
+    attributes: Collection[str] = METADATA_ATTRIBUTES,
+    follow_symlinks: bool = True,
+    ignore: list[str] | None = None,
+    path_filter: PathFilter | None = None,
+    max_workers: int = 0,
+) -> DirResult:
+    """Compare a snapshot with a live tree or with another snapshot.
+
+    The snapshot is the left side: paths added since are in
+    ``only_right``, removed ones in ``only_left``. A file whose size or
+    digest changed is ``SIZE_MISMATCH`` or ``CONTENT_MISMATCH``, a changed
+    symlink target ``CONTENT_MISMATCH``, an entry that changed kind
+    ``TYPE_MISMATCH`` and one whose *attributes* changed
+    ``METADATA_MISMATCH``. Modification times are recorded but not
+    compared. A directory only on one side is reported once, not entry
+    by entry.
+
+    :param snapshot: Snapshot file path, binary stream or :class:`Snapshot`.
+    :param current: Directory to snapshot now (with the same algorithm),
+        or another snapshot (a :class:`Snapshot`; a path that is not a
+        directory is read as a snapshot file).
+    :param attributes: Which of ``"mode"``, ``"owner"`` and ``"xattrs"`` to
+        compare, as in :func:`komparu.metadata_diff`.
+    :param follow_symlinks: Follow symbolic links while walking *current*.
+    :param ignore: Glob patterns to exclude (matched per path component),
+        on both sides.
+    :param path_filter: PathFilter deciding per entry of a live tree
+        whether it is compared.
+    :param max_workers: Thread pool size for hashing (0=auto, 1=sequential).
+    :raises ValueError: If a snapshot is malformed, the two snapshots use
+        different hash algorithms, or *attributes* names an unknown one.
+    """
+    validate_attributes(attributes)
+    old = _load(snapshot)
+    if isinstance(current, str) and os.path.isdir(current):
+        new = snapshot_tree(current, algorithm=old.algorithm, follow_symlinks=follow_symlinks,
+                            ignore=ignore, path_filter=path_filter, max_workers=max_workers)
+    else:
+        new = _load(current)
+        if new.algorithm != old.algorithm:
+            raise ValueError(f"snapshots use different hash algorithms: "
+                             f"{old.algorithm} and {new.algorithm}")
+    exclude = make_exclude(ignore, None, "", "")
+    left = {path: entry for path, entry in old.entries.items()
+            if exclude is None or not exclude(path)}
+    right = {path: entry for path, entry in new.entries.items()
+             if exclude is None or not exclude(path)}
+    diff: dict[str, DiffReason] = {}
+    for path in sorted(left.keys() & right.keys(), key=path_key):
+        a, b = left[path], right[path]
+        if a.kind != b.kind:
+            diff[path] = DiffReason.TYPE_MISMATCH
+        elif a.kind == "file" and a.size != b.size:
+            diff[path] = DiffReason.SIZE_MISMATCH
+        elif a.digest != b.digest or a.target != b.target:
+            diff[path] = DiffReason.CONTENT_MISMATCH
+        elif snapshot_changes(a, b, attributes):
+            diff[path] = DiffReason.METADATA_MISMATCH
+    only_left = set(left.keys() - right.keys())
+    only_right = set(right.keys() - left.keys())
+    reported = only_left | only_right
+    reported |= {path for path, reason in diff.items() if reason is DiffReason.TYPE_MISMATCH}
+    only_left = {path for path in only_left if not is_under(path, reported)}
+    only_right = {path for path in only_right if not is_under(path, reported)}
+    return DirResult(equal=not (diff or only_left or only_right), diff=diff,
+                     only_left=only_left, only_right=only_right)
+
@@ -0,0 +1,49 @@
+"""Split volumes (file.001, file.002, ...) found and read as one file."""
+
+from __future__ import annotations
+
+import os
+import re
+
+from komparu._types import PATH_ERRORS
+from komparu._validate import validate_path
+
+_NUMBERED = re.compile(r"\.(\d+)$")
+
+
+def split_parts(path: str) -> list[str]:
+    """The parts of a split file, in order.
+
+    *path* is either the first of numbered volumes — ``backup.tar.001``,
+    as ``split -d``, 7-Zip and HJSplit name them, or ``.000``, ``.1`` —
+    which the later numbers of the same width follow up to the first one
+    missing, or a list of parts: a text file with one path per line,
+    relative to its own directory, blank lines and ``#`` comments skipped.
+
+    Pass the result to :func:`komparu.compare` to compare the parts as the
+    file they were cut from.
+
+    :param path: First part, or a file listing the parts.
+    :returns: Paths of the parts.
+    :raises FileNotFoundError: If *path* does not exist.
+    :raises ValueError: If a list of parts names none.
+    """
+    validate_path(path, "path")
+    path = os.fspath(path)
+    numbered = _NUMBERED.search(path)
+    if numbered is not None:
+        if not os.path.exists(path):
+            raise FileNotFoundError(2, "no such split part", path)
+        digits = numbered.group(1)
+        stem, first = path[:numbered.start(1)], int(digits)
+        parts = []
+        while os.path.exists(part := f"{stem}{first + len(parts):0{len(digits)}d}"):
+            parts.append(part)
+        return parts
+    base = os.path.dirname(path)
+    with open(path, encoding="utf-8", errors=PATH_ERRORS) as f:
+        parts = [os.path.join(base, line.strip()) for line in f
+                 if line.strip() and not line.lstrip().startswith("#")]
+    if not parts:
+        raise ValueError(f"{path}: lists no split parts")
+    return parts
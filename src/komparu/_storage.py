@@ -0,0 +1,204 @@
+"""Storage detection: pick parallelism and read size for what the files are on.
+
+A solid-state disk answers many reads at once, so the default of one
+worker per CPU suits it. A spinning disk does not: parallel reads only
+make the head seek between files, and one worker reading large chunks
+is faster. A network file system is bound by round trips rather than
+the disk, and gains from more requests in flight than there are CPUs.
+
+On Linux the kind comes from the mount table and the block device's
+``queue/rotational`` flag in sysfs (for device-mapper and md devices,
+from the disks under them). macOS asks ``diskutil``; Windows only tells
+network drives apart. Anything else is ``UNKNOWN`` and left alone.
+"""
+
+from __future__ import annotations
+
+import os
+import plistlib
+import re
+import subprocess
+import sys
+from functools import lru_cache
+
+from komparu._options import CompareOptions
+from komparu._types import StorageKind
+from komparu._validate import validate_path
+
+_SYSFS_BLOCK = "/sys/dev/block"
+_MOUNTINFO = "/proc/self/mountinfo"
+
+_NETWORK_FS = frozenset({
+    "nfs", "nfs4", "cifs", "smb3", "smbfs", "9p", "afs", "ceph", "glusterfs", "lustre",
+    "fuse.sshfs", "fuse.s3fs", "fuse.rclone", "fuse.gcsfuse",
+})
+# Random access as fast as sequential
+_MEMORY_FS = frozenset({"tmpfs", "ramfs"})
+
+# Settings per kind; SSD and UNKNOWN keep the options as they are
+_LARGE_CHUNK = 1 << 20
+_NETWORK_WORKERS = 16
+_DEFAULT_CHUNK = CompareOptions().chunk_size
+
+
+def _mount(dev: int) -> tuple[str, str] | None:
+    """File system type and source of the mount of device *dev*."""
+    key = f"{os.major(dev)}:{os.minor(dev)}"
+    found = None
+    try:
+        with open(_MOUNTINFO, encoding="utf-8", errors="surrogateescape") as f:
+            for line in f:
+                fields = line.split()
+                if len(fields) > 2 and fields[2] == key and " - " in line:
+                    fstype, source = (line.split(" - ", 1)[1].split() + ["", ""])[:2]
+                    found = fstype, source  # the last mount on a device hides the others
+    except OSError:
+        return None
+    return found
+
+
+def _unescape(field: str) -> str:
+    """A mountinfo field with its ``\\040``-style escapes decoded."""
+    return re.sub(r"\\([0-7]{3})", lambda m: chr(int(m.group(1), 8)), field)
+
+
+def mount_table() -> list[tuple[str, str, str]]:
+    """``(mount point, file system type, source)`` of every mount, in mount order.
+
+    Empty where there is no ``/proc/self/mountinfo``.
+    """
+    mounts = []
+    try:
+        with open(_MOUNTINFO, encoding="utf-8", errors="surrogateescape") as f:
+            for line in f:
+                fields = line.split()
+                if len(fields) > 4 and " - " in line:
+                    fstype, source = (line.split(" - ", 1)[1].split() + ["", ""])[:2]
+                    mounts.append((_unescape(fields[4]), fstype, _unescape(source)))
+    except OSError:
+        return []
+    return mounts
+
+
+def _rotational(device: str) -> bool | None:
+    """Whether the sysfs block device *device* spins; None if it cannot be told."""
+    device = os.path.realpath(device)
+    if not os.path.isdir(os.path.join(device, "queue")):  # a partition: ask its disk
+        device = os.path.dirname(device)
+    try:
+        slaves = os.listdir(os.path.join(device, "slaves"))
+    except OSError:
+        slaves = []
+    if slaves:
+        # A stacked device spins if any disk under it does
+        under = [_rotational(os.path.join(device, "slaves", name)) for name in slaves]
+        if True in under:
+            return True
+        return False if None not in under else None
+    try:
+        with open(os.path.join(device, "queue", "rotational"), encoding="ascii") as f:
+            return f.read().strip() == "1"
+    except OSError:
+        return None
+
+
+@lru_cache(maxsize=64)
+def _linux_kind(dev: int) -> StorageKind:
+    mount = _mount(dev)
+    if mount is not None and mount[0] in _NETWORK_FS:
+        return StorageKind.NETWORK
+    if mount is not None and mount[0] in _MEMORY_FS:
+        return StorageKind.SSD
+    if os.major(dev) == 0:
+        # Btrfs and friends report an anonymous device; the mount names the real one
+        if mount is None or not mount[1].startswith("/dev/"):
+            return StorageKind.UNKNOWN
+        try:
+            dev = os.stat(mount[1]).st_rdev
+        except OSError:
+            return StorageKind.UNKNOWN
+    rotational = _rotational(f"{_SYSFS_BLOCK}/{os.major(dev)}:{os.minor(dev)}")
+    if rotational is None:
+        return StorageKind.UNKNOWN
+    return StorageKind.HDD if rotational else StorageKind.SSD
+
+
+def _mount_point(path: str) -> str:
+    path = os.path.realpath(path)
+    dev = os.stat(path).st_dev
+    while path != os.path.dirname(path) and os.stat(os.path.dirname(path)).st_dev == dev:
+        path = os.path.dirname(path)
+    return path
+
+
+@lru_cache(maxsize=64)
+def _macos_kind(mount_point: str) -> StorageKind:
+    try:
+        out = subprocess.run(["diskutil", "info", "-plist", mount_point], capture_output=True,
+                             timeout=5, check=True).stdout
+        info = plistlib.loads(out)
+    except (OSError, subprocess.SubprocessError, plistlib.InvalidFileException):
+        return StorageKind.UNKNOWN
+    solid = info.get("SolidState")
+    if solid is None:
+        return StorageKind.UNKNOWN
+    return StorageKind.SSD if solid else StorageKind.HDD
+
+
+def _windows_kind(path: str) -> StorageKind:
+    import ctypes
+
+    drive = os.path.splitdrive(os.path.abspath(path))[0]
+    if drive.startswith("\\\\"):
+        return StorageKind.NETWORK
+    kernel32 = ctypes.windll.kernel32  # type: ignore[attr-defined]
+    if kernel32.GetDriveTypeW(drive + "\\") == 4:  # DRIVE_REMOTE: a mapped network drive
+        return StorageKind.NETWORK
+    return StorageKind.UNKNOWN
+
+
+def detect_storage(path: str) -> StorageKind:
+    """What the file or directory *path* is stored on.
+
+    :param path: Local path; URLs and paths that cannot be read are
+        ``UNKNOWN``.
+    :returns: StorageKind.
+    """
+    validate_path(path, "path")
+    try:
+        if sys.platform.startswith("linux"):
+            return _linux_kind(os.stat(path).st_dev)
+        if sys.platform == "darwin":
+            return _macos_kind(_mount_point(path))
+        if sys.platform == "win32":
+            return _windows_kind(os.fspath(path))
+    except (OSError, ValueError):
+        pass
+    return StorageKind.UNKNOWN
+
+
+def tune_for_storage(options: CompareOptions, *paths: str) -> CompareOptions:
+    """*options* with parallelism and chunk size picked for what *paths* are on.
+
+    Only automatic settings change: ``max_workers=0`` becomes 1 if any
+    path is on a spinning disk and 16 if any is on a network file system
+    (and none spins), and the default chunk size becomes 1 MiB for
+    either. Options for solid-state or undetected storage come back as
+    they are.
+
+    :param options: CompareOptions to start from.
+    :param paths: The paths the options will compare.
+    :returns: CompareOptions.
+    """
+    if options.max_workers != 0:
+        return options
+    kinds = {detect_storage(path) for path in paths}
+    if StorageKind.HDD in kinds:
+        workers = 1
+    elif StorageKind.NETWORK in kinds:
+        workers = _NETWORK_WORKERS
+    else:
+        return options
+    if options.chunk_size != _DEFAULT_CHUNK:
+        return options.replace(max_workers=workers)
+    return options.replace(max_workers=workers, chunk_size=_LARGE_CHUNK)
@@ -0,0 +1,168 @@
+"""Local trees compared with object stores through fsspec (``pip install komparu[cloud]``)."""
+
+from __future__ import annotations
+
+import base64
+import contextlib
+import errno
+import os
+import stat
+from collections.abc import Iterator
+from concurrent.futures import ThreadPoolExecutor
+from typing import Any
+
+from komparu._filter import PathFilter, make_exclude
+from komparu._config import get_config
+from komparu._hashing import file_digest
+from komparu._helpers import walk_tree
+from komparu._types import (
+    DiffReason,
+    DirResult,
+    SourceNotFoundError,
+    SourcePermissionError,
+    SourceReadError,
+)
+from komparu._validate import validate_chunk_size, validate_max_workers, validate_path
+
+# Schemes the CLI sends here; s3:// has its own boto3 backend
+STORE_SCHEMES = ("gs://", "gcs://", "az://", "abfs://", "abfss://")
+
+
+def _filesystem(url: str, storage_options: dict[str, Any]) -> tuple[Any, str]:
+    try:
+        import fsspec
+    except ImportError:
+        raise ImportError("object store comparison needs fsspec: pip install komparu[cloud] "
+                          "and the store's package (gcsfs, adlfs, s3fs, ...)") from None
+    return fsspec.core.url_to_fs(url, **storage_options)  # type: ignore[no-any-return]
+
+
+@contextlib.contextmanager
+def _store_errors(url: str) -> Iterator[None]:
+    # fsspec implementations raise the built-in OSError subclasses
+    try:
+        yield
+    except FileNotFoundError as exc:
+        raise SourceNotFoundError(errno.ENOENT, str(exc) or "not found", url) from None
+    except PermissionError as exc:
+        raise SourcePermissionError(errno.EACCES, str(exc) or "access denied", url) from None
+    except OSError as exc:
+        raise SourceReadError(exc.errno or errno.EIO, str(exc) or "read failed", url) from None
+
+
+def _stored_md5(info: dict[str, Any]) -> str | None:
+    """Hex MD5 of an object as listed, if the store keeps one (GCS, Azure Blob)."""
+    if info.get("md5Hash"):  # gcsfs, base64
+        return base64.b64decode(info["md5Hash"]).hex()
+    settings = info.get("content_settings")
+    md5 = settings.get("content_md5") if isinstance(settings, dict) else None
+    return bytes(md5).hex() if md5 else None  # adlfs, raw bytes
+
+
+def _ranged_equal(fs: Any, key: str, path: str, chunk_size: int) -> bool:
+    offset = 0
+    with open(path, "rb") as f:
+        while data := f.read(chunk_size):
+            if fs.cat_file(key, start=offset, end=offset + len(data)) != data:
+                return False
+            offset += len(data)
+    return True
+
+
+def compare_dir_store(
+    directory: str,
+    url: str,
+    *,
+    fs: Any = None,
+    storage_options: dict[str, Any] | None = None,
+    chunk_size: int = 8 * 1024 * 1024,
+    follow_symlinks: bool = True,
+    ignore: list[str] | None = None,
+    path_filter: PathFilter | None = None,
+    max_workers: int = 0,
+) -> DirResult:
+    """Compare a local directory with the objects under a prefix of any object store.
+
+    Google Cloud Storage, Azure Blob Storage, S3-compatible stores and
+    the rest of what fsspec reaches go through the one backend: the
+    prefix is listed once, a size that differs from the local file's
+    settles that file, an MD5 the listing carries (GCS and Azure keep
+    one for most objects) is checked against the local file, and
+    anything else is read back in ranged reads of *chunk_size* bytes
+    and compared as it arrives, up to the first difference.
+
+    The local tree is the left side and the store the right.
+
+    :param directory: Local directory path.
+    :param url: ``scheme://bucket/prefix``, e.g. ``gs://backups/site`` or
+        ``az://container/site``.
+    :param fs: fsspec file system to use; by default the one *url*'s
+        scheme names, made with *storage_options*. With *fs* given, the
+        scheme is only stripped from *url*.
+    :param storage_options: Options for the file system, e.g. credentials
+        or an ``endpoint_url``.
+    :param chunk_size: Size of each ranged read and of local reads.
+    :param follow_symlinks: Follow symbolic links during traversal.
+    :param ignore: Glob patterns to exclude (matched per path component),
+        locally and under the prefix.
+    :param path_filter: PathFilter deciding per entry whether it is compared.
+    :param max_workers: Thread pool size (0=auto, 1=sequential).
+    :raises ImportError: If no *fs* is given and fsspec, or the store's
+        implementation, is missing.
+    :raises SourceNotFoundError: If the store reports the bucket missing.
+    :raises SourcePermissionError: If the store denies access, or a local
+        directory cannot be listed.
+    :raises SourceReadError: On other store errors.
+    """
+    validate_path(directory, "directory")
+    validate_chunk_size(chunk_size)
+    validate_max_workers(max_workers)
+    if fs is None:
+        fs, root = _filesystem(url, storage_options or {})
+    else:
+        root = url.partition("://")[2] if "://" in url else url
+    root = root.rstrip("/")
+
+    exclude = make_exclude(ignore, path_filter, directory, directory, follow_symlinks)
+    entries, errors = walk_tree(directory, follow_symlinks, exclude)
+    if errors:
+        raise SourcePermissionError(errno.EACCES, "cannot read",
+                                    os.path.join(directory, min(errors)))
+    local = {path: st for path, st in entries.items() if stat.S_ISREG(st.st_mode)}
+
+    objects: dict[str, dict[str, Any]] = {}
+    keys: dict[str, str] = {}
+    with _store_errors(url):
+        for key, info in fs.find(root, withdirs=False, detail=True).items():
+            path = key.lstrip("/")[len(root.lstrip("/")):].lstrip("/")
+            if (path and info.get("type", "file") == "file" and not path.endswith("/")
+                    and not (exclude and exclude(path))):
+                objects[path], keys[path] = info, key
+
+    diff: dict[str, DiffReason] = {}
+    same_size = []
+    for path in sorted(local.keys() & objects.keys()):
+        if local[path].st_size != objects[path].get("size"):
+            diff[path] = DiffReason.SIZE_MISMATCH
+        else:
+            same_size.append(path)
+
+    def check(path: str) -> bool:
+        local_path = os.path.join(directory, path)
+        md5 = None if get_config().fips else _stored_md5(objects[path])
+        if md5 is not None:
+            return file_digest(local_path, "md5") == md5
+        with _store_errors(url):
+            return _ranged_equal(fs, keys[path], local_path, chunk_size)
+
+    if same_size:
+        workers = max_workers or min(len(same_size), 8)
+        with ThreadPoolExecutor(max_workers=workers) as pool:
+            for path, equal in zip(same_size, pool.map(check, same_size)):
+                if not equal:
+                    diff[path] = DiffReason.CONTENT_MISMATCH
+
+    only_left = local.keys() - objects.keys()
+    only_right = objects.keys() - local.keys()
+    return DirResult(equal=not (diff or only_left or only_right), diff=diff,
+                     only_left=set(only_left), only_right=set(only_right))
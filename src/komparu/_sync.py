@@ -0,0 +1,241 @@
+"""Sync plans: the operations that would make one tree match another, and running them.
+
+An rsync dry run driven by komparu's own comparison: content is compared
+as :func:`compare_dir` does, the structure and permissions of both trees
+are walked, and the differences become ordered :class:`SyncStep` records.
+:func:`apply_sync_plan` carries them out; :func:`sync_dir` does both.
+"""
+
+from __future__ import annotations
+
+import os
+import shutil
+import stat
+from collections.abc import Mapping
+
+from komparu._api import compare_dir
+from komparu._cancel import CancelToken
+from komparu._comparator import ContentComparator
+from komparu._filter import PathFilter, make_exclude
+from komparu._helpers import walk_tree
+from komparu._options import (
+    DIR_OPTION_DEFAULTS,
+    CompareOptions,
+    LockPolicy,
+    RetryPolicy,
+    reject_overrides,
+)
+from komparu._types import DiffReason, SyncAction, SyncPlan, SyncStep, tree_key
+from komparu._validate import validate_path
+
+# Name a copy is written under before it replaces the target
+_PARTIAL = ".{}.komparu-sync"
+
+
+def _create(path: str, st: os.stat_result) -> SyncStep:
+    if stat.S_ISDIR(st.st_mode):
+        return SyncStep(SyncAction.MKDIR, path, stat.S_IMODE(st.st_mode))
+    if stat.S_ISLNK(st.st_mode):
+        return SyncStep(SyncAction.COPY, path)
+    return SyncStep(SyncAction.COPY, path, stat.S_IMODE(st.st_mode), st.st_size)
+
+
+def sync_plan(
+    dir_a: str,
+    dir_b: str,
+    *,
+    chunk_size: int = 65536,
+    size_precheck: bool = True,
+    quick_check: bool = True,
+    follow_symlinks: bool = True,
+    max_workers: int = 0,
+    retry: RetryPolicy | None = None,
+    lock: LockPolicy | None = None,
+    ignore: list[str] | None = None,
+    path_filter: PathFilter | None = None,
+    options: CompareOptions | None = None,
+    comparators: Mapping[str, ContentComparator] | None = None,
+    cancel: CancelToken | None = None,
+) -> SyncPlan:
+    """Plan the deletions, copies and permission changes that make *dir_b* match *dir_a*.
+
+    Entries only in *dir_b*, or of another type there, are deleted; entries
+    only in *dir_a*, of another type in *dir_b*, or with other content are
+    created (a copy carries the file's permission bits); entries that are
+    the same but for their permission bits get a ``CHMOD``. Symlinks are
+    copied as links when *follow_symlinks* is False and followed otherwise.
+    Files skipped under *lock* because a writer holds them get no step.
+
+    :param dir_a: Path to the directory to match.
+    :param dir_b: Path to the directory the plan changes.
+    :param ignore: Glob patterns to exclude (matched per path component).
+    :param path_filter: PathFilter deciding per entry whether it is planned for.
+    :param options: CompareOptions bundle; replaces the keyword arguments above,
+        which raise TypeError if also passed.
+    :param comparators: ContentComparator per glob pattern; files they find
+        equal are not copied.
+    :param cancel: CancelToken that aborts the comparison when set.
+    :returns: SyncPlan; paths that could not be read are in its ``errors``
+        and get no step.
+    :raises SourceReadError: If a directory cannot be walked.
+    """
+    if options is not None:
+        reject_overrides("sync_plan", locals(), DIR_OPTION_DEFAULTS)
+        return sync_plan(dir_a, dir_b, comparators=comparators, cancel=cancel,
+                         **options.dir_kwargs())
+    validate_path(dir_a, "dir_a")
+    validate_path(dir_b, "dir_b")
+    dir_a, dir_b = os.fspath(dir_a), os.fspath(dir_b)
+    result = compare_dir(dir_a, dir_b, chunk_size=chunk_size, size_precheck=size_precheck,
+                         quick_check=quick_check, follow_symlinks=follow_symlinks,
+                         max_workers=max_workers, retry=retry, lock=lock, ignore=ignore,
+                         path_filter=path_filter, comparators=comparators, cancel=cancel)
+    exclude = make_exclude(ignore, path_filter, dir_a, dir_b, follow_symlinks)
+    entries_a, errors_a = walk_tree(dir_a, follow_symlinks, exclude)
+    entries_b, errors_b = walk_tree(dir_b, follow_symlinks, exclude)
+    errors = errors_a | errors_b | result.errors
+    unread = tuple(path + "/" for path in errors_a | errors_b)  # directories not listed
+
+    deletes: list[SyncStep] = []
+    creates: list[SyncStep] = []
+    chmods: list[SyncStep] = []
+    deleted = None  # the last directory deleted whole; nothing under it needs a step
+    for path in sorted(entries_a.keys() | entries_b.keys(), key=tree_key):
+        if path in errors or path.startswith(unread):
+            continue
+        if result.diff.get(path) is DiffReason.LOCKED:
+            continue  # a writer holds it; the next sync copies it once it is done
+        if result.diff.get(path) is DiffReason.VANISHED:
+            continue  # deleted mid-run; the next sync sees what became of it
+        a, b = entries_a.get(path), entries_b.get(path)
+        if deleted is not None and path.startswith(deleted + "/"):
+            b = None
+        if b is not None and (a is None or stat.S_IFMT(a.st_mode) != stat.S_IFMT(b.st_mode)):
+            deletes.append(SyncStep(SyncAction.DELETE, path))
+            deleted = path
+            b = None
+        if a is None:
+            continue
+        if b is None or path in result.diff:
+            creates.append(_create(path, a))
+        elif stat.S_IMODE(a.st_mode) != stat.S_IMODE(b.st_mode) and not stat.S_ISLNK(a.st_mode):
+            chmods.append(SyncStep(SyncAction.CHMOD, path, stat.S_IMODE(a.st_mode)))
+    return SyncPlan(deletes + creates + chmods, errors)
+
+
+def _remove(path: str) -> None:
+    if os.path.isdir(path) and not os.path.islink(path):
+        shutil.rmtree(path)
+    else:
+        os.unlink(path)
+
+
+def _copy(source: str, target: str, follow_symlinks: bool) -> None:
+    if os.path.islink(source) and not follow_symlinks:
+        if os.path.lexists(target):
+            os.unlink(target)
+        os.symlink(os.readlink(source), target)
+        return
+    # Readers of the target see the old file or the new one, never half of it
+    partial = os.path.join(os.path.dirname(target), _PARTIAL.format(os.path.basename(target)))
+    try:
+        shutil.copy2(source, partial)
+        os.replace(partial, target)
+    except BaseException:
+        if os.path.lexists(partial):
+            os.unlink(partial)
+        raise
+
+
+def apply_sync_plan(plan: SyncPlan, dir_a: str, dir_b: str, *,
+                    follow_symlinks: bool = True) -> None:
+    """Carry out *plan* on *dir_b*, copying from *dir_a*.
+
+    Copies keep the source's permission bits and modification time and
+    replace their target in one rename. Directories get their mode once
+    everything under them is in place, so a read-only one can be filled.
+
+    :param plan: Plan from :func:`sync_plan`, possibly edited or loaded
+        with :meth:`SyncPlan.from_dict`.
+    :param follow_symlinks: Copy what symlinks point to rather than the
+        links; as given to :func:`sync_plan`.
+    :raises OSError: If a step fails; the steps before it stay done.
+    """
+    validate_path(dir_a, "dir_a")
+    validate_path(dir_b, "dir_b")
+    created: list[SyncStep] = []
+    for step in plan.steps:
+        target = os.path.join(dir_b, step.path)
+        if step.action is SyncAction.DELETE:
+            _remove(target)
+        elif step.action is SyncAction.MKDIR:
+            os.mkdir(target)
+            created.append(step)
+        elif step.action is SyncAction.COPY:
+            _copy(os.path.join(dir_a, step.path), target, follow_symlinks)
+        elif step.mode is not None:
+            os.chmod(target, step.mode)
+    for step in reversed(created):
+        if step.mode is not None:
+            os.chmod(os.path.join(dir_b, step.path), step.mode)
+
+
+def sync_dir(
+    dir_a: str,
+    dir_b: str,
+    *,
+    delete: bool = False,
+    update: bool = False,
+    dry_run: bool = False,
+    chunk_size: int = 65536,
+    size_precheck: bool = True,
+    quick_check: bool = True,
+    follow_symlinks: bool = True,
+    max_workers: int = 0,
+    retry: RetryPolicy | None = None,
+    lock: LockPolicy | None = None,
+    ignore: list[str] | None = None,
+    path_filter: PathFilter | None = None,
+    options: CompareOptions | None = None,
+    comparators: Mapping[str, ContentComparator] | None = None,
+    cancel: CancelToken | None = None,
+) -> SyncPlan:
+    """Make *dir_b* match *dir_a*: copy what is missing or differs, fix permissions.
+
+    Runs :func:`sync_plan` and :func:`apply_sync_plan`. Entries only in
+    *dir_b* stay unless *delete* is set; one of another type than in
+    *dir_a* is always replaced.
+
+    :param dir_a: Path to the directory to copy from.
+    :param dir_b: Path to the directory to change.
+    :param delete: Also delete entries *dir_a* does not have.
+    :param update: Leave files alone that were modified later in *dir_b*
+        than in *dir_a*.
+    :param dry_run: Plan only; change nothing.
+    :param options: CompareOptions bundle; replaces the comparison keyword
+        arguments.
+    :returns: SyncPlan of the steps taken (or, with *dry_run*, to take).
+    :raises OSError: If a step fails; the steps before it stay done.
+    """
+    if options is not None:
+        reject_overrides("sync_dir", locals(), DIR_OPTION_DEFAULTS)
+        return sync_dir(dir_a, dir_b, delete=delete, update=update, dry_run=dry_run,
+                        comparators=comparators, cancel=cancel, **options.dir_kwargs())
+    plan = sync_plan(dir_a, dir_b, chunk_size=chunk_size, size_precheck=size_precheck,
+                     quick_check=quick_check, follow_symlinks=follow_symlinks,
+                     max_workers=max_workers, retry=retry, lock=lock, ignore=ignore,
+                     path_filter=path_filter, comparators=comparators, cancel=cancel)
+    kept = set()  # files newer in dir_b, with update
+    for step in plan.steps if update else ():
+        target = os.path.join(dir_b, step.path)
+        if step.action is SyncAction.COPY and os.path.isfile(target):
+            source = os.path.join(dir_a, step.path)
+            if os.stat(target).st_mtime_ns > os.stat(source).st_mtime_ns:
+                kept.add(step.path)
+    created = {step.path for step in plan.steps if step.action is not SyncAction.DELETE}
+    steps = [step for step in plan.steps if step.path not in kept
+             and (step.action is not SyncAction.DELETE or delete or step.path in created)]
+    plan = SyncPlan(steps, plan.errors)
+    if not dry_run:
+        apply_sync_plan(plan, dir_a, dir_b, follow_symlinks=follow_symlinks)
+    return plan
@@ -0,0 +1,407 @@
+"""Interactive terminal browser for directory comparison results (``compare --tui``).
+
+:class:`DiffBrowser` holds the state — the tree of differing entries,
+which statuses and paths are shown, folded directories, marked entries —
+and renders details; :func:`run_tui` draws it with :mod:`curses`.
+"""
+
+from __future__ import annotations
+
+import json
+import os
+import stat
+import time
+from collections import Counter
+from collections.abc import Iterable
+from dataclasses import dataclass
+from typing import Any
+
+from komparu._api import first_diff
+from komparu._helpers import atomic_open
+from komparu._types import DiffReason, DirResult, DirSummary, Severity, tree_key
+
+STATUSES = ("diff", "only_left", "only_right", "error")
+_TAGS = {"diff": "~", "only_left": "<", "only_right": ">", "error": "!"}
+_HEX_WIDTH = 16
+_READ_SIZE = 1 << 20
+
+
+@dataclass(frozen=True, slots=True)
+class Row:
+    """One line of the tree: a differing entry, or a directory above some.
+
+    :param count: For a directory, the shown entries under it.
+    """
+
+    path: str
+    depth: int
+    is_dir: bool
+    status: str | None = None
+    reason: DiffReason | None = None
+    count: int = 0
+
+    @property
+    def name(self) -> str:
+        return self.path.rsplit("/", 1)[-1]
+
+
+def _parents(path: str) -> Iterable[str]:
+    parts = path.split("/")
+    for n in range(1, len(parts)):
+        yield "/".join(parts[:n])
+
+
+def _hex_line(offset: int, data: bytes, other: bytes) -> str:
+    cells = " ".join(f"{b:02x}" if i < len(other) and other[i] == b else f"{b:02X}"
+                     for i, b in enumerate(data))
+    text = "".join(chr(b) if 32 <= b < 127 else "." for b in data)
+    return f"{offset:08x}  {cells:<{_HEX_WIDTH * 3}} {text}"
+
+
+class DiffBrowser:
+    """Differences of a DirResult as a tree to navigate, filter, inspect and export.
+
+    :param result: The comparison to browse.
+    :param roots: The two compared directories, when they are local, for
+        file details and hex context.
+    :param sides: Names of the two sides, as the CLI prints them.
+    :param severities: Severity per path, from a SeverityPolicy.
+    :param summaries: DirSummary per directory, from :func:`komparu.summarize_dirs`,
+        shown on the directory rows.
+    """
+
+    def __init__(self, result: DirResult, roots: tuple[str, str] | None = None, *,
+                 sides: tuple[str, str] = ("left", "right"),
+                 severities: dict[str, Severity] | None = None,
+                 summaries: dict[str, DirSummary] | None = None) -> None:
+        self.roots = roots
+        self.sides = sides
+        self.severities = severities or {}
+        self.summaries = summaries or {}
+        self.entries: dict[str, tuple[str, DiffReason | None]] = {
+            **{path: ("only_left", None) for path in result.only_left},
+            **{path: ("only_right", None) for path in result.only_right},
+            **{path: ("error", None) for path in result.errors},
+            **{path: ("diff", reason) for path, reason in result.diff.items()},
+        }
+        self.statuses = set(STATUSES)
+        self.pattern = ""
+        self.folded: set[str] = set()
+        self.marked: set[str] = set()
+
+    def shown(self) -> list[str]:
+        """Paths of the entries the status and path filters let through."""
+        pattern = self.pattern.lower()
+        return sorted((path for path, (status, _) in self.entries.items()
+                       if status in self.statuses and pattern in path.lower()),
+                      key=tree_key)
+
+    def rows(self) -> list[Row]:
+        """The tree of shown entries, without what is under folded directories."""
+        paths = self.shown()
+        counts = Counter(parent for path in paths for parent in _parents(path))
+        rows: list[Row] = []
+        listed: set[str] = set()
+        for path in paths:
+            hidden = False
+            for depth, parent in enumerate(_parents(path)):
+                if parent not in listed:
+                    listed.add(parent)
+                    rows.append(Row(parent, depth, True, count=counts[parent]))
+                if parent in self.folded:
+                    hidden = True
+                    break
+            if not hidden:
+                status, reason = self.entries[path]
+                rows.append(Row(path, path.count("/"), False, status, reason))
+        return rows
+
+    def toggle_status(self, status: str) -> None:
+        """Show or hide the entries of *status*; hiding the last one shows all again."""
+        self.statuses ^= {status}
+        if not self.statuses:
+            self.statuses = set(STATUSES)
+
+    def fold(self, path: str) -> None:
+        """Fold the directory *path*, or unfold it if it is folded."""
+        self.folded ^= {path}
+
+    def under(self, row: Row) -> list[str]:
+        """Shown entries of *row*: itself, or all under a directory."""
+        if not row.is_dir:
+            return [row.path]
+        return [path for path in self.shown() if path.startswith(row.path + "/")]
+
+    def mark(self, row: Row) -> None:
+        """Mark the entries of *row* for export, or unmark them if all are marked."""
+        paths = set(self.under(row))
+        if paths <= self.marked:
+            self.marked -= paths
+        else:
+            self.marked |= paths
+
+    def rollup(self, path: str) -> str:
+        """What the directory *path* holds (``""`` for the top), if its summary is known."""
+        summary = self.summaries.get(path)
+        if summary is None:
+            return ""
+        counts = ((summary.diff, "differ"), (summary.only_left, f"only in {self.sides[0]}"),
+                  (summary.only_right, f"only in {self.sides[1]}"),
+                  (summary.errors, "unreadable"), (summary.equal, "equal"))
+        text = ", ".join(f"{count} {label}" for count, label in counts if count)
+        return f"{text}; {summary.bytes} bytes not equal" if summary.differing else text
+
+    def describe(self, path: str) -> str:
+        """The entry as the CLI prints it, e.g. ``differ: a.txt (content_mismatch)``."""
+        status, reason = self.entries[path]
+        if status == "diff":
+            text = f"differ: {path} ({reason.value if reason is not None else 'diff'})"
+        elif status == "error":
+            text = f"error: {path}"
+        else:
+            text = f"only in {self.sides[status == 'only_right']}: {path}"
+        severity = self.severities.get(path)
+        return f"{text} [{severity.value}]" if severity is not None else text
+
+    def details(self, path: str) -> list[str]:
+        """What is known about the entry at *path*: status, both sides' metadata, hex context."""
+        lines = [self.describe(path), ""]
+        if self.roots is None:
+            return lines
+        stats: list[os.stat_result | None] = []
+        for side, root in zip(self.sides, self.roots):
+            try:
+                st: os.stat_result | None = os.lstat(os.path.join(root, path))
+            except OSError as exc:
+                st = None
+                lines.append(f"{side}: {exc.strerror or 'absent'}")
+            else:
+                modified = time.strftime("%Y-%m-%d %H:%M:%S", time.localtime(st.st_mtime))
+                lines.append(f"{side}: {st.st_size} bytes, mode {stat.filemode(st.st_mode)}, "
+                             f"owner {st.st_uid}:{st.st_gid}, modified {modified}")
+            stats.append(st)
+        if all(st is not None and stat.S_ISREG(st.st_mode) for st in stats):
+            lines += ["", *self.hex_context(path)]
+        return lines
+
+    def hex_context(self, path: str, rows: int = 4) -> list[str]:
+        """Hex dump of both files around their first differing byte."""
+        assert self.roots is not None
+        names = [os.path.join(root, path) for root in self.roots]
+        try:
+            with open(names[0], "rb") as fa, open(names[1], "rb") as fb:
+                offset = 0
+                while True:
+                    a, b = fa.read(_READ_SIZE), fb.read(_READ_SIZE)
+                    at = first_diff(a, b)
+                    if at is not None:
+                        offset += at
+                        break
+                    if not a:
+                        return ["contents are identical"]
+                    offset += len(a)
+                start = max(0, (offset // _HEX_WIDTH - rows // 2) * _HEX_WIDTH)
+                windows = []
+                for f in (fa, fb):
+                    f.seek(start)
+                    windows.append(f.read(rows * _HEX_WIDTH))
+        except OSError as exc:
+            return [f"cannot read: {exc.strerror}"]
+        lines = [f"first difference at byte {offset} (differing bytes in upper case)"]
+        for side, data, other in ((self.sides[0], *windows), (self.sides[1], *windows[::-1])):
+            lines.append(f"{side}:")
+            for row in range(0, max(len(data), 1), _HEX_WIDTH):
+                chunk = data[row:row + _HEX_WIDTH]
+                lines.append(_hex_line(start + row, chunk, other[row:row + _HEX_WIDTH]))
+        return lines
+
+    def export(self, path: str, paths: Iterable[str]) -> int:
+        """Write the entries at *paths* to the file *path*; returns how many.
+
+        A ``.json`` file gets a list of objects with ``path``, ``status``
+        and ``reason``; any other gets the lines the CLI prints.
+        """
+        chosen = sorted(paths, key=tree_key)
+        with atomic_open(path, "w", encoding="utf-8") as f:
+            if path.endswith(".json"):
+                records: list[dict[str, Any]] = []
+                for entry in chosen:
+                    status, reason = self.entries[entry]
+                    records.append({"path": entry, "status": status,
+                                    "reason": reason.value if reason is not None else None})
+                json.dump(records, f, indent=2)
+                f.write("\n")
+            else:
+                f.writelines(self.describe(entry) + "\n" for entry in chosen)
+        return len(chosen)
+
+
+_HELP = ("up/down move  left/right fold  enter details  1-4 status  / search  "
+         "space mark  e export  q quit")
+
+
+def run_tui(browser: DiffBrowser, title: str) -> None:
+    """Browse *browser* in the terminal until the user quits.
+
+    :raises ValueError: If standard input or output is not a terminal.
+    """
+    import sys
+
+    if not (sys.stdin.isatty() and sys.stdout.isatty()):
+        raise ValueError("--tui needs a terminal")
+    import curses
+
+    curses.wrapper(_Screen(browser, title).run)
+
+
+class _Screen:
+    def __init__(self, browser: DiffBrowser, title: str) -> None:
+        self.browser = browser
+        self.title = title
+        self.cursor = 0
+        self.top = 0
+        self.message = ""
+
+    def run(self, screen: Any) -> None:
+        import curses
+
+        curses.curs_set(0)
+        colors = {}
+        if curses.has_colors():
+            curses.use_default_colors()
+            for n, (status, color) in enumerate(zip(STATUSES, (
+                    curses.COLOR_YELLOW, curses.COLOR_RED, curses.COLOR_GREEN,
+                    curses.COLOR_MAGENTA)), 1):
+                curses.init_pair(n, color, -1)
+                colors[status] = curses.color_pair(n)
+        while True:
+            rows = self.browser.rows()
+            self.cursor = max(0, min(self.cursor, len(rows) - 1))
+            self.draw(screen, rows, colors)
+            key = screen.getch()
+            row = rows[self.cursor] if rows else None
+            self.message = ""
+            if key in (ord("q"), 27):
+                return
+            if key in (curses.KEY_UP, ord("k")):
+                self.cursor -= 1
+            elif key in (curses.KEY_DOWN, ord("j")):
+                self.cursor += 1
+            elif key == curses.KEY_PPAGE:
+                self.cursor -= screen.getmaxyx()[0] - 3
+            elif key == curses.KEY_NPAGE:
+                self.cursor += screen.getmaxyx()[0] - 3
+            elif key in (curses.KEY_HOME, ord("g")):
+                self.cursor = 0
+            elif key in (curses.KEY_END, ord("G")):
+                self.cursor = len(rows) - 1
+            elif key in (ord("1"), ord("2"), ord("3"), ord("4")):
+                self.browser.toggle_status(STATUSES[key - ord("1")])
+            elif key == ord("/"):
+                self.browser.pattern = self.prompt(screen, "search: ", self.browser.pattern)
+                self.cursor = 0
+            elif row is None:
+                continue
+            elif key in (curses.KEY_LEFT, curses.KEY_RIGHT, ord("h"), ord("l")) and row.is_dir:
+                self.browser.fold(row.path)
+            elif key in (curses.KEY_ENTER, 10, 13):
+                if row.is_dir:
+                    self.browser.fold(row.path)
+                else:
+                    self.page(screen, self.browser.details(row.path))
+            elif key == ord(" "):
+                self.browser.mark(row)
+                self.cursor += 1
+            elif key == ord("e"):
+                self.export(screen)
+
+    def export(self, screen: Any) -> None:
+        paths = self.browser.marked or set(self.browser.shown())
+        target = self.prompt(screen, f"export {len(paths)} entries to ", "komparu-selection.txt")
+        if not target:
+            return
+        try:
+            count = self.browser.export(target, paths)
+        except OSError as exc:
+            self.message = f"cannot write {target}: {exc.strerror}"
+        else:
+            self.message = f"wrote {count} entries to {target}"
+
+    def draw(self, screen: Any, rows: list[Row], colors: dict[str, int]) -> None:
+        import curses
+
+        height, width = screen.getmaxyx()
+        body = max(1, height - 2)
+        if self.cursor < self.top:
+            self.top = self.cursor
+        elif self.cursor >= self.top + body:
+            self.top = self.cursor - body + 1
+        screen.erase()
+        shown = " ".join(status for status in STATUSES if status in self.browser.statuses)
+        header = f"{self.title}  [{shown}]"
+        if self.browser.pattern:
+            header += f"  /{self.browser.pattern}"
+        if self.browser.rollup(""):
+            header += f"  {self.browser.rollup('')}"
+        screen.addnstr(0, 0, header.ljust(width), width - 1, curses.A_REVERSE)
+        for y, row in enumerate(rows[self.top:self.top + body], 1):
+            mark = "*" if not row.is_dir and row.path in self.browser.marked else " "
+            if row.is_dir:
+                fold = "+" if row.path in self.browser.folded else "-"
+                rollup = self.browser.rollup(row.path)
+                text = (f"{mark} {'  ' * row.depth}{fold} {row.name}/ ({row.count}"
+                        f"{'; ' + rollup if rollup else ''})")
+                attr = curses.A_BOLD
+            else:
+                assert row.status is not None
+                detail = f" ({row.reason.value})" if row.reason is not None else ""
+                text = f"{mark} {'  ' * row.depth}{_TAGS[row.status]} {row.name}{detail}"
+                attr = colors.get(row.status, 0)
+            if self.top + y - 1 == self.cursor:
+                attr |= curses.A_REVERSE
+            screen.addnstr(y, 0, text, width - 1, attr)
+        if not rows:
+            screen.addnstr(1, 0, "no differences to show", width - 1)
+        footer = self.message or f"{len(self.browser.marked)} marked  {_HELP}"
+        screen.addnstr(height - 1, 0, footer, width - 1)
+        screen.refresh()
+
+    def page(self, screen: Any, lines: list[str]) -> None:
+        import curses
+
+        top = 0
+        while True:
+            height, width = screen.getmaxyx()
+            screen.erase()
+            for y, line in enumerate(lines[top:top + height - 1]):
+                screen.addnstr(y, 0, line, width - 1)
+            screen.addnstr(height - 1, 0, "up/down scroll  any other key returns", width - 1,
+                           curses.A_REVERSE)
+            screen.refresh()
+            key = screen.getch()
+            if key in (curses.KEY_UP, ord("k")):
+                top = max(0, top - 1)
+            elif key in (curses.KEY_DOWN, ord("j")):
+                top = min(max(0, len(lines) - 1), top + 1)
+            else:
+                return
+
+    def prompt(self, screen: Any, label: str, default: str) -> str:
+        """A line typed at the bottom; just Enter keeps *default*."""
+        import curses
+
+        height, width = screen.getmaxyx()
+        if default:
+            label = f"{label}[{default}] "
+        screen.move(height - 1, 0)
+        screen.clrtoeol()
+        screen.addnstr(height - 1, 0, label, width - 1)
+        curses.echo()
+        curses.curs_set(1)
+        try:
+            raw = screen.getstr(height - 1, min(len(label), width - 2),
+                                max(1, width - len(label) - 1))
+        finally:
+            curses.noecho()
+            curses.curs_set(0)
+        return raw.decode(errors="replace").strip() or default
@@ -2,8 +2,51 @@
 
 from __future__ import annotations
 
-from dataclasses import dataclass, field
+import codecs
+import sys
+from dataclasses import KW_ONLY, dataclass, field
 from enum import Enum
+from typing import Any
+
+
+def _wtf8_error(exc: UnicodeError) -> tuple[str | bytes, int]:
+    """Keep lone surrogates as their three bytes, and other bytes as surrogateescape does."""
+    if isinstance(exc, UnicodeEncodeError):
+        return exc.object[exc.start:exc.end].encode("utf-8", "surrogatepass"), exc.end
+    if isinstance(exc, UnicodeDecodeError):
+        data = exc.object[exc.start:exc.start + 3]
+        if len(data) == 3 and data[0] == 0xED and data[1] >= 0xA0 and 0x80 <= data[2] < 0xC0:
+            return data.decode("utf-8", "surrogatepass"), exc.start + 3
+        return chr(0xDC00 + exc.object[exc.start]), exc.start + 1
+    raise exc
+
+
+codecs.register_error("komparu.wtf8", _wtf8_error)
+
+# Error handler for paths in text komparu writes and reads back. POSIX
+# names are bytes: undecodable ones travel as lone surrogates, as in
+# os.fsdecode. Windows names are UTF-16 that may hold unpaired
+# surrogates, which surrogateescape cannot encode; they are written in
+# WTF-8 and read back as the same surrogates.
+PATH_ERRORS = "komparu.wtf8" if sys.platform == "win32" else "surrogateescape"
+
+
+def path_key(path: str) -> bytes:
+    """Sort key ordering relative paths byte-wise, the same on every platform.
+
+    The key is the path's UTF-8 bytes, with undecodable bytes kept as
+    they were on disk: the order of ``LC_ALL=C sort`` and of the C core's
+    traversal, not of the locale or the file system's listing.
+    """
+    try:
+        return path.encode("utf-8", "surrogateescape")
+    except UnicodeEncodeError:  # a lone surrogate from a Windows name
+        return path.encode("utf-8", "surrogatepass")
+
+
+def tree_key(path: str) -> list[bytes]:
+    """Sort key like :func:`path_key` that puts a directory's contents right after it."""
+    return [path_key(part) for part in path.split("/")]
 
 
 class DiffReason(str, Enum):
@@ -14,6 +57,74 @@ class DiffReason(str, Enum):
     MISSING = "missing"
     TYPE_MISMATCH = "type_mismatch"
     READ_ERROR = "read_error"
+    RETRIES_EXHAUSTED = "retries_exhausted"
+    METADATA_MISMATCH = "metadata_mismatch"
+    UNSTABLE = "unstable"
+    LOCKED = "locked"
+    VANISHED = "vanished"
+
+
+# Reasons meaning a file was not read, so it is neither equal nor different
+UNREADABLE = frozenset({DiffReason.READ_ERROR, DiffReason.RETRIES_EXHAUSTED})
+
+
+class EntryKind(str, Enum):
+    """Outcome of a single entry in a streamed directory comparison."""
+
+    EQUAL = "equal"
+    DIFF = "diff"
+    ONLY_LEFT = "only_left"
+    ONLY_RIGHT = "only_right"
+    ERROR = "error"
+
+
+class DriftKind(str, Enum):
+    """Whether a difference reported while watching two trees is new or gone."""
+
+    APPEARED = "appeared"
+    RESOLVED = "resolved"
+
+
+class SyncAction(str, Enum):
+    """What a step of a sync plan does to the second tree."""
+
+    DELETE = "delete"
+    MKDIR = "mkdir"
+    COPY = "copy"
+    CHMOD = "chmod"
+
+
+class StorageKind(str, Enum):
+    """What a path is stored on, as far as reading it in parallel goes."""
+
+    SSD = "ssd"
+    HDD = "hdd"
+    NETWORK = "network"
+    UNKNOWN = "unknown"
+
+
+class Severity(str, Enum):
+    """How much a category of difference matters, lowest first."""
+
+    INFO = "info"
+    WARN = "warn"
+    ERROR = "error"
+
+    @property
+    def rank(self) -> int:
+        """Position in the INFO < WARN < ERROR order."""
+        return _SEVERITY_RANK[self]
+
+
+_SEVERITY_RANK = {Severity.INFO: 0, Severity.WARN: 1, Severity.ERROR: 2}
+
+
+class RegionKind(str, Enum):
+    """How a range of bytes differs between two files."""
+
+    INSERTED = "inserted"
+    DELETED = "deleted"
+    CHANGED = "changed"
 
 
 @dataclass(frozen=True, slots=True)
@@ -57,6 +168,474 @@ class DirResult:
     only_right: set[str]
     errors: set[str] = field(default_factory=set)
 
+    @property
+    def unverifiable(self) -> set[str]:
+        """Paths that could not be read: ``errors`` and read-error diffs."""
+        return self.errors | {path for path, reason in self.diff.items() if reason in UNREADABLE}
+
+    def to_dict(self) -> dict[str, Any]:
+        """JSON-compatible form: enums as values, sets as sorted lists."""
+        return {
+            "equal": self.equal,
+            "diff": {path: self.diff[path].value for path in sorted(self.diff, key=path_key)},
+            "only_left": sorted(self.only_left, key=path_key),
+            "only_right": sorted(self.only_right, key=path_key),
+            "errors": sorted(self.errors, key=path_key),
+        }
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> DirResult:
+        """Rebuild a DirResult from :meth:`to_dict` output."""
+        return cls(
+            equal=data["equal"],
+            diff={path: DiffReason(reason) for path, reason in data["diff"].items()},
+            only_left=set(data["only_left"]),
+            only_right=set(data["only_right"]),
+            errors=set(data.get("errors", ())),
+        )
+
+
+@dataclass(frozen=True, slots=True)
+class DiffEntry:
+    """Single entry yielded while a directory comparison is in progress.
+
+    Stability: ``path``, ``kind`` and ``reason`` are fixed and positional.
+    Everything after them is payload — keyword-only, defaulting to None
+    when unknown, excluded from equality and hashing — so new payload
+    fields can be added without breaking construction, comparison or
+    :meth:`from_dict` of older data. New :class:`EntryKind` and
+    :class:`DiffReason` members may also appear in minor releases; match
+    on them with a fallback branch.
+
+    :param path: Relative path of the entry.
+    :param kind: What happened to the entry.
+    :param reason: Why it differs; ``MISSING`` for ``ONLY_LEFT``/``ONLY_RIGHT``,
+        ``READ_ERROR`` for ``ERROR``, ``None`` for ``EQUAL``.
+    :param size_a: Size in the first directory, for ``DIFF`` entries.
+    :param size_b: Size in the second directory, for ``DIFF`` entries.
+    """
+
+    path: str
+    kind: EntryKind
+    reason: DiffReason | None = None
+    _: KW_ONLY
+    size_a: int | None = field(default=None, compare=False)
+    size_b: int | None = field(default=None, compare=False)
+
+    def to_dict(self) -> dict[str, Any]:
+        """JSON-compatible form: enums as values, unset payload omitted."""
+        data: dict[str, Any] = {
+            "path": self.path,
+            "kind": self.kind.value,
+            "reason": self.reason.value if self.reason is not None else None,
+        }
+        for name in _DIFF_ENTRY_PAYLOAD:
+            value = getattr(self, name)
+            if value is not None:
+                data[name] = value
+        return data
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> DiffEntry:
+        """Rebuild a DiffEntry from :meth:`to_dict` output.
+
+        Unknown keys are ignored, so data written by a newer version loads.
+        """
+        reason = data.get("reason")
+        return cls(
+            path=data["path"],
+            kind=EntryKind(data["kind"]),
+            reason=DiffReason(reason) if reason is not None else None,
+            **{name: data[name] for name in _DIFF_ENTRY_PAYLOAD if name in data},
+        )
+
+
+_DIFF_ENTRY_PAYLOAD = ("size_a", "size_b")
+
+
+@dataclass(frozen=True, slots=True)
+class DriftEvent:
+    """Difference that appeared or was resolved while watching two trees.
+
+    :param kind: ``APPEARED`` for a difference that was not there before
+        (or is there for another reason now), ``RESOLVED`` for one that no
+        longer is.
+    :param entry: The difference: the new one for ``APPEARED``, the one
+        that went away for ``RESOLVED``.
+    """
+
+    kind: DriftKind
+    entry: DiffEntry
+
+
+@dataclass(frozen=True, slots=True)
+class Progress:
+    """Snapshot passed to a ``progress=`` callback of a directory comparison.
+
+    :param files_done: Entries resolved so far (equal, different, one-sided
+        or skipped).
+    :param bytes_done: Combined size of the common files whose content was
+        compared so far (left side; size mismatches read nothing).
+    :param path: Relative path of the entry that triggered this update.
+    :param files_total: Entries the comparison will resolve, when a
+        pre-scan counted them (``prescan=True``); otherwise None.
+    :param bytes_total: Final value of *bytes_done*, when a pre-scan
+        summed it; otherwise None.
+    :param elapsed: Seconds since the content comparison started.
+    """
+
+    files_done: int
+    bytes_done: int
+    path: str
+    files_total: int | None = None
+    bytes_total: int | None = None
+    elapsed: float = field(default=0.0, compare=False)
+
+    @property
+    def eta(self) -> float | None:
+        """Estimated seconds left at the rate so far, or None without a pre-scan."""
+        if self.bytes_total is None or self.files_total is None:
+            return None
+        if self.bytes_total > 0 and self.bytes_done > 0:
+            done = self.bytes_done / self.bytes_total
+        elif self.files_total > 0 and self.files_done > 0:
+            done = self.files_done / self.files_total
+        else:
+            return None
+        return self.elapsed * (1 - min(done, 1.0)) / done
+
+
+@dataclass(frozen=True, slots=True)
+class PlannedPair:
+    """Common file whose content a comparison would read.
+
+    :param path: Relative path.
+    :param size_a: Size in the first directory (-1 if it could not be stat'ed).
+    :param size_b: Size in the second directory (-1 if it could not be stat'ed).
+    """
+
+    path: str
+    size_a: int
+    size_b: int
+
+
+@dataclass(frozen=True, slots=True)
+class DirPlan:
+    """Work a directory comparison would do, found without reading content.
+
+    :param pairs: Common files that need a content comparison.
+    :param same_file: Common paths that are the same file on both sides
+        (settled equal without reading).
+    :param size_mismatch: Common files settled as different by size.
+    :param only_left: Paths only in the first directory.
+    :param only_right: Paths only in the second directory.
+    :param errors: Paths skipped (permission denied during the walk).
+    """
+
+    pairs: list[PlannedPair]
+    same_file: set[str] = field(default_factory=set)
+    size_mismatch: set[str] = field(default_factory=set)
+    only_left: set[str] = field(default_factory=set)
+    only_right: set[str] = field(default_factory=set)
+    errors: set[str] = field(default_factory=set)
+
+    @property
+    def bytes_to_read(self) -> int:
+        """Upper bound on bytes read: both sides of every pair in full."""
+        return sum(max(p.size_a, 0) + max(p.size_b, 0) for p in self.pairs)
+
+
+@dataclass(frozen=True, slots=True)
+class SyncStep:
+    """One operation on the second tree of a :class:`SyncPlan`.
+
+    :param action: What to do at *path*.
+    :param path: Relative path, the same in both trees.
+    :param mode: Permission bits the entry gets (for ``MKDIR``, ``CHMOD``,
+        and ``COPY`` of anything but a symlink).
+    :param size: Bytes to copy, for ``COPY``.
+    """
+
+    action: SyncAction
+    path: str
+    mode: int | None = None
+    size: int | None = None
+
+    def to_dict(self) -> dict[str, Any]:
+        """JSON-compatible form: the action as its value, unset fields omitted."""
+        data: dict[str, Any] = {"action": self.action.value, "path": self.path}
+        if self.mode is not None:
+            data["mode"] = self.mode
+        if self.size is not None:
+            data["size"] = self.size
+        return data
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> SyncStep:
+        """Rebuild a SyncStep from :meth:`to_dict` output."""
+        return cls(SyncAction(data["action"]), data["path"], data.get("mode"), data.get("size"))
+
+
+@dataclass(frozen=True, slots=True)
+class SyncPlan:
+    """Operations that make a second tree match the first, in the order to run them.
+
+    Deletions come first (a directory once, with everything in it), then
+    directories and copies parents before children, then permission
+    changes of entries that are otherwise the same.
+
+    :param steps: The operations.
+    :param errors: Paths left out because they could not be read.
+    """
+
+    steps: list[SyncStep]
+    errors: set[str] = field(default_factory=set)
+
+    @property
+    def bytes_to_copy(self) -> int:
+        """Combined size of the files the plan copies."""
+        return sum(step.size or 0 for step in self.steps if step.action is SyncAction.COPY)
+
+    def to_dict(self) -> dict[str, Any]:
+        """JSON-compatible form: steps as records, errors as a sorted list."""
+        return {"steps": [step.to_dict() for step in self.steps],
+                "errors": sorted(self.errors, key=path_key)}
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> SyncPlan:
+        """Rebuild a SyncPlan from :meth:`to_dict` output."""
+        return cls([SyncStep.from_dict(step) for step in data["steps"]],
+                   set(data.get("errors", ())))
+
+
+@dataclass(frozen=True, slots=True)
+class DiffRegion:
+    """A range of bytes where two files differ, as offsets into each.
+
+    An inserted region is empty in the first file (``size_a`` is 0 and
+    ``offset_a`` is where the bytes go), a deleted one in the second.
+
+    :param kind: Inserted, deleted or changed.
+    :param offset_a: Start of the region in the first file.
+    :param size_a: Length of the region in the first file.
+    :param offset_b: Start of the region in the second file.
+    :param size_b: Length of the region in the second file.
+    """
+
+    kind: RegionKind
+    offset_a: int
+    size_a: int
+    offset_b: int
+    size_b: int
+
+
+@dataclass(frozen=True, slots=True)
+class ChunkDiff:
+    """Two files cut into content-defined chunks and aligned.
+
+    Returned by :func:`komparu.chunk_diff`. Chunks are matched in order;
+    each run of unmatched chunks is narrowed to the bytes that differ,
+    which are an insertion when only the second file has them, a
+    deletion when only the first does and a change otherwise.
+
+    :param size_a: Size of the first file.
+    :param size_b: Size of the second file.
+    :param chunks_a: Chunks in the first file.
+    :param chunks_b: Chunks in the second file.
+    :param shared_chunks: Chunks common to both, in order.
+    :param shared_bytes: Bytes in the shared chunks.
+    :param insertions: Runs of bytes only in the second file.
+    :param deletions: Runs of bytes only in the first file.
+    :param changes: Runs of bytes replaced by different ones.
+    :param regions: Where each insertion, deletion and change is, in
+        file order.
+    """
+
+    size_a: int
+    size_b: int
+    chunks_a: int
+    chunks_b: int
+    shared_chunks: int
+    shared_bytes: int
+    insertions: int
+    deletions: int
+    changes: int
+    regions: tuple[DiffRegion, ...] = ()
+
+    @property
+    def shared_fraction(self) -> float:
+        """Shared chunks over the chunks of the larger side (1.0 if both are empty)."""
+        total = max(self.chunks_a, self.chunks_b)
+        return self.shared_chunks / total if total else 1.0
+
+
+@dataclass(frozen=True, slots=True)
+class UnreadableRange:
+    """Bytes that could not be read from one or both files.
+
+    :param start: Offset of the first byte.
+    :param end: Offset just past the last byte.
+    :param side: ``left``, ``right`` or ``both``.
+    """
+
+    start: int
+    end: int
+    side: str
+
+    @property
+    def size(self) -> int:
+        return self.end - self.start
+
+
+@dataclass(frozen=True, slots=True)
+class SalvageResult:
+    """Two files compared past the sectors either of them could not read.
+
+    Returned by :func:`komparu.compare_salvage`.
+
+    :param equal: The sizes match and every byte both files gave back
+        matches; False stops at the first difference.
+    :param unreadable: Ranges left unverified, in file order.
+    """
+
+    equal: bool
+    unreadable: tuple[UnreadableRange, ...] = ()
+
+    @property
+    def verified(self) -> bool:
+        """True if the files are equal and every byte of both was read."""
+        return self.equal and not self.unreadable
+
+    @property
+    def unreadable_bytes(self) -> int:
+        return sum(r.size for r in self.unreadable)
+
+
+@dataclass(frozen=True, slots=True)
+class Manifest:
+    """Digests read from a checksum manifest.
+
+    Returned by :func:`komparu.read_manifest` and
+    :func:`komparu.parse_manifest`.
+
+    :param digests: Lower-case hex digest per relative path.
+    :param algorithm: The hash algorithm BSD-style lines name, None for
+        lines that do not say.
+    """
+
+    digests: dict[str, str]
+    algorithm: str | None = None
+
+
+@dataclass(frozen=True, slots=True)
+class DuplicateGroup:
+    """Byte-identical files found by :func:`komparu.find_duplicates`.
+
+    :param size: Size of each file in bytes.
+    :param paths: The files, in traversal order.
+    """
+
+    size: int
+    paths: list[str]
+
+    @property
+    def wasted(self) -> int:
+        """Bytes taken by the copies beyond the first."""
+        return self.size * (len(self.paths) - 1)
+
+
+@dataclass(frozen=True, slots=True)
+class DedupResult:
+    """Outcome of :func:`komparu.deduplicate`.
+
+    :param linked: ``(path, kept)`` per file replaced by a link to (or a
+        clone of) the kept copy of its group.
+    :param skipped: Reason per duplicate left alone.
+    :param bytes_saved: Combined size of the replaced files.
+    """
+
+    linked: list[tuple[str, str]]
+    skipped: dict[str, str]
+    bytes_saved: int
+
+
+@dataclass(frozen=True, slots=True)
+class DirSummary:
+    """Entries under one directory of a comparison, from :func:`komparu.summarize_dirs`.
+
+    Counts cover the whole subtree, not just the directory's own entries.
+
+    :param path: Relative path of the directory, ``""`` for the roots.
+    :param equal: Files found equal.
+    :param diff: Files that differ.
+    :param only_left: Files only in the first directory.
+    :param only_right: Files only in the second directory.
+    :param errors: Entries that could not be read.
+    :param bytes: Combined size of the files that are not equal, counting
+        the larger side of a file that differs.
+    """
+
+    path: str
+    equal: int = 0
+    diff: int = 0
+    only_left: int = 0
+    only_right: int = 0
+    errors: int = 0
+    bytes: int = 0
+
+    @property
+    def differing(self) -> int:
+        """Entries that are not equal."""
+        return self.diff + self.only_left + self.only_right + self.errors
+
+    def to_dict(self) -> dict[str, Any]:
+        """JSON-compatible form."""
+        return {"path": self.path, "equal": self.equal, "diff": self.diff,
+                "only_left": self.only_left, "only_right": self.only_right,
+                "errors": self.errors, "bytes": self.bytes}
+
+
+@dataclass(frozen=True, slots=True)
+class SnapshotEntry:
+    """One entry of a :class:`Snapshot`.
+
+    :param kind: ``"file"``, ``"dir"`` or ``"symlink"``.
+    :param mode: Permission bits (``stat.S_IMODE``).
+    :param uid: Owner user id.
+    :param gid: Owner group id.
+    :param size: Size in bytes (``st_size``).
+    :param mtime_ns: Modification time in nanoseconds.
+    :param digest: Hex content digest of a file, None otherwise.
+    :param target: Target of a symlink, None otherwise.
+    :param xattrs: Extended attributes by name.
+    """
+
+    kind: str
+    mode: int
+    uid: int
+    gid: int
+    size: int
+    mtime_ns: int
+    digest: str | None = None
+    target: str | None = None
+    xattrs: dict[str, bytes] = field(default_factory=dict)
+
+
+@dataclass(frozen=True, slots=True)
+class Snapshot:
+    """Paths, metadata and content digests of a tree at one point in time.
+
+    Made by :func:`komparu.snapshot_tree`, stored by
+    :func:`komparu.write_snapshot` and read back by
+    :func:`komparu.read_snapshot`.
+
+    :param algorithm: Hash algorithm of the file digests.
+    :param entries: Entry per relative path.
+    """
+
+    algorithm: str
+    entries: dict[str, SnapshotEntry]
+
 
 @dataclass(frozen=True, slots=True)
 class CompareResult:
@@ -71,6 +650,28 @@ class CompareResult:
     groups: list[set[str]]
     diff: dict[tuple[str, str], bool]
 
+    def to_dict(self) -> dict[str, Any]:
+        """JSON-compatible form.
+
+        Groups become sorted lists; the pairwise ``diff`` mapping becomes a
+        list of ``{"a", "b", "equal"}`` records, since JSON keys cannot be
+        tuples.
+        """
+        return {
+            "all_equal": self.all_equal,
+            "groups": [sorted(group, key=path_key) for group in self.groups],
+            "diff": [{"a": a, "b": b, "equal": eq} for (a, b), eq in self.diff.items()],
+        }
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> CompareResult:
+        """Rebuild a CompareResult from :meth:`to_dict` output."""
+        return cls(
+            all_equal=data["all_equal"],
+            groups=[set(group) for group in data["groups"]],
+            diff={(rec["a"], rec["b"]): rec["equal"] for rec in data["diff"]},
+        )
+
 
 # ---- Errors ----
 
@@ -78,15 +679,32 @@ class KomparuError(Exception):
     """Base exception for all komparu errors."""
 
 
-class SourceNotFoundError(KomparuError):
-    """File or URL not found."""
+class SourceReadError(KomparuError, OSError):
+    """I/O or HTTP read error.
+
+    Base of the source failures below. ``errno``, ``strerror`` and
+    ``filename`` (the offending path or URL, when known) are set as on
+    any OSError, so existing ``except OSError`` handlers keep working.
+    """
+
+
+class SourceNotFoundError(SourceReadError, FileNotFoundError):
+    """File, directory or URL not found (HTTP 404/410)."""
+
+
+class SourcePermissionError(SourceReadError, PermissionError):
+    """Access denied (file permissions, HTTP 401/403)."""
 
 
-class SourceReadError(KomparuError):
-    """I/O or HTTP read error."""
+class SourceVanishedError(SourceReadError):
+    """Source was truncated or removed while being compared."""
 
 
-class ArchiveError(KomparuError):
+class UnsupportedSourceError(SourceReadError):
+    """Source cannot be compared (not a regular file)."""
+
+
+class ArchiveError(KomparuError, OSError):
     """Cannot read or parse archive."""
 
 
@@ -98,5 +716,31 @@ class ConfigError(KomparuError):
     """Invalid configuration."""
 
 
-class ComparisonTimeoutError(KomparuError):
-    """Comparison exceeded wall-clock timeout."""
+class ComparisonTimeoutError(KomparuError, TimeoutError):
+    """Comparison or HTTP transfer exceeded its timeout."""
+
+
+class ComparisonCancelledError(KomparuError):
+    """Comparison stopped via a CancelToken."""
+
+
+class QuotaReachedError(ComparisonCancelledError):
+    """Comparison stopped because its CancelToken's byte quota was read.
+
+    ``partial`` is the :class:`DirResult` of the entries resolved before
+    the stop, for a directory comparison run with a visitor; else None.
+    """
+
+    partial: DirResult | None = None
+
+
+class SignatureError(KomparuError, ValueError):
+    """Manifest signature is from another key or does not verify."""
+
+
+class CopyVerifyError(KomparuError, OSError):
+    """Copied file reads back differently from its source."""
+
+
+class PatchError(KomparuError, ValueError):
+    """Binary patch is malformed, for another file, or rebuilds the wrong content."""
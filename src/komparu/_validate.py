@@ -11,6 +11,15 @@ def validate_path(val: str | Source, name: str) -> None:
         raise ValueError(f"{name} cannot be empty")
 
 
+def validate_dir(val: str | int, name: str) -> None:
+    """Accept a directory path or an open directory descriptor."""
+    if isinstance(val, int) and not isinstance(val, bool):
+        if val < 0:
+            raise ValueError(f"{name} handle must be non-negative")
+        return
+    validate_path(val, name)
+
+
 def validate_chunk_size(chunk_size: int) -> None:
     if chunk_size <= 0:
         raise ValueError("chunk_size must be positive")
@@ -28,3 +37,8 @@ def validate_max_workers(max_workers: int) -> None:
         raise ValueError("max_workers must be non-negative")
     if max_workers > 256:
         raise ValueError("max_workers must be <= 256")
+
+
+def validate_max_pending(max_pending: int) -> None:
+    if max_pending < 0:
+        raise ValueError("max_pending must be non-negative")
@@ -0,0 +1,157 @@
+"""Directory comparison visitor."""
+
+from __future__ import annotations
+
+import time
+from collections.abc import Callable
+
+from komparu._types import DiffEntry, DiffReason, DirResult, EntryKind, Progress
+from komparu._helpers import build_diff_entry, stat_entry
+
+
+class CompareVisitor:
+    """Callbacks driven by the directory engine while it runs.
+
+    Subclass and override what you need; every method defaults to a
+    no-op. Pass an instance as ``visitor=`` to :func:`komparu.compare_dir`
+    or :func:`komparu.aio.compare_dir` to build progress UIs and reports
+    without waiting for the full :class:`DirResult`.
+
+    Callbacks run in the calling thread (sync) or on the event loop
+    (async), never in C worker threads. Directory events follow the
+    sorted merge of both trees and bracket the ``ONLY_LEFT``/``ONLY_RIGHT``
+    entries under them; common files are compared after the merge, so
+    their ``on_entry_started``/``on_entry_finished`` come later, in
+    completion order. Ignored paths produce no callbacks.
+    """
+
+    def on_dir_entered(self, path: str) -> None:
+        """The merge entered subdirectory *path* (relative)."""
+
+    def on_dir_left(self, path: str) -> None:
+        """The merge left subdirectory *path* (relative)."""
+
+    def on_entry_started(self, path: str) -> None:
+        """Content comparison of common file *path* started."""
+
+    def on_entry_finished(self, entry: DiffEntry) -> None:
+        """*entry* is resolved: equal, different, or present on one side only."""
+
+    def on_error(self, entry: DiffEntry) -> None:
+        """*entry* was skipped (permission denied during the walk)."""
+
+
+class ProgressVisitor(CompareVisitor):
+    """Turn visitor callbacks into :class:`Progress` updates.
+
+    Forwards every callback to *inner* (if any) first, then reports
+    progress for started and resolved entries. *totals* — entries and
+    bytes counted by a pre-scan — are passed on in every update.
+    """
+
+    __slots__ = ("_progress", "_base", "_inner", "_files", "_bytes", "_totals", "_start")
+
+    def __init__(
+        self,
+        progress: Callable[[Progress], None],
+        base_dir: str | int,
+        inner: CompareVisitor | None = None,
+        totals: tuple[int, int] | None = None,
+    ) -> None:
+        self._progress = progress
+        self._base = base_dir
+        self._inner = inner
+        self._files = 0
+        self._bytes = 0
+        self._totals = totals or (None, None)
+        self._start = time.monotonic()
+
+    def _report(self, path: str) -> None:
+        self._progress(Progress(self._files, self._bytes, path, *self._totals,
+                                elapsed=time.monotonic() - self._start))
+
+    def on_dir_entered(self, path: str) -> None:
+        if self._inner is not None:
+            self._inner.on_dir_entered(path)
+
+    def on_dir_left(self, path: str) -> None:
+        if self._inner is not None:
+            self._inner.on_dir_left(path)
+
+    def on_entry_started(self, path: str) -> None:
+        if self._inner is not None:
+            self._inner.on_entry_started(path)
+        self._report(path)
+
+    def on_entry_finished(self, entry: DiffEntry) -> None:
+        if self._inner is not None:
+            self._inner.on_entry_finished(entry)
+        if entry.kind is EntryKind.EQUAL or entry.reason is DiffReason.CONTENT_MISMATCH:
+            try:
+                self._bytes += stat_entry(self._base, entry.path).st_size
+            except OSError:
+                pass  # vanished since it was compared
+        self._files += 1
+        self._report(entry.path)
+
+    def on_error(self, entry: DiffEntry) -> None:
+        if self._inner is not None:
+            self._inner.on_error(entry)
+        self._files += 1
+        self._report(entry.path)
+
+
+class VisitorDriver:
+    """Dispatch C stream events to a visitor and accumulate the DirResult."""
+
+    __slots__ = ("_visitor", "_exclude", "_recheck", "_diff", "_only_left", "_only_right", "_errors")
+
+    def __init__(
+        self,
+        visitor: CompareVisitor,
+        exclude: Callable[[str], bool] | None,
+        recheck: Callable[[DiffEntry], DiffEntry] | None = None,
+    ) -> None:
+        self._visitor = visitor
+        self._exclude = exclude
+        self._recheck = recheck
+        self._diff: dict[str, DiffReason] = {}
+        self._only_left: set[str] = set()
+        self._only_right: set[str] = set()
+        self._errors: set[str] = set()
+
+    def feed(self, events: list[tuple[str, str | None, str]]) -> None:
+        v = self._visitor
+        for kind, reason, path in events:
+            if self._exclude is not None and self._exclude(path):
+                continue
+            if kind == "dir_entered":
+                v.on_dir_entered(path)
+            elif kind == "dir_left":
+                v.on_dir_left(path)
+            elif kind == "started":
+                v.on_entry_started(path)
+            else:
+                entry = build_diff_entry(kind, reason, path)
+                if self._recheck is not None:
+                    entry = self._recheck(entry)
+                if entry.kind is EntryKind.ERROR:
+                    self._errors.add(path)
+                    v.on_error(entry)
+                    continue
+                if entry.kind is EntryKind.DIFF:
+                    self._diff[path] = entry.reason
+                elif entry.kind is EntryKind.ONLY_LEFT:
+                    self._only_left.add(path)
+                elif entry.kind is EntryKind.ONLY_RIGHT:
+                    self._only_right.add(path)
+                v.on_entry_finished(entry)
+
+    def result(self) -> DirResult:
+        return DirResult(
+            equal=not self._diff and not self._only_left and not self._only_right,
+            diff=self._diff,
+            only_left=self._only_left,
+            only_right=self._only_right,
+            errors=self._errors,
+        )
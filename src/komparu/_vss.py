@@ -0,0 +1,133 @@
+"""Volume Shadow Copies: compare a frozen view of in-use Windows volumes.
+
+A directory that is being written while it is compared gives results
+that are true of no single moment, and files open for writing in
+another process fail with sharing violations. A shadow copy of the
+volume is a point-in-time, read-only view that neither races nor locks.
+"""
+
+from __future__ import annotations
+
+import contextlib
+import errno
+import os
+import subprocess
+import sys
+from collections.abc import Iterator
+
+from komparu._types import SourcePermissionError, SourceReadError, UnsupportedSourceError
+from komparu._validate import validate_path
+
+_PROVIDER_TIMEOUT = 120  # seconds; VSS may flush writers for a while first
+
+# Win32_ShadowCopy.Create return codes
+_CREATE_ERRORS = {
+    2: "invalid argument",
+    3: "volume not found",
+    4: "volume does not support shadow copies",
+    5: "unsupported shadow copy context",
+    6: "not enough shadow storage",
+    7: "volume is in use",
+    8: "maximum number of shadow copies reached",
+    9: "another shadow copy operation is in progress",
+    10: "shadow copy provider vetoed the operation",
+    11: "shadow copy provider not registered",
+    12: "shadow copy provider failure",
+}
+
+_CREATE = (
+    "$r = Invoke-CimMethod -ClassName Win32_ShadowCopy -MethodName Create "
+    "-Arguments @{{Volume='{volume}'; Context='ClientAccessible'}}; "
+    "if ($r.ReturnValue -ne 0) {{ exit $r.ReturnValue }}; "
+    "$s = Get-CimInstance Win32_ShadowCopy -Filter \"ID='$($r.ShadowID)'\"; "
+    "$s.ID; $s.DeviceObject"
+)
+_DELETE = "Get-CimInstance Win32_ShadowCopy -Filter \"ID='{id}'\" | Remove-CimInstance"
+
+
+def _powershell(script: str) -> tuple[int, str]:
+    """Exit status and output of a PowerShell *script*."""
+    try:
+        done = subprocess.run(["powershell", "-NoProfile", "-NonInteractive", "-Command", script],
+                              capture_output=True, text=True, timeout=_PROVIDER_TIMEOUT)
+    except subprocess.TimeoutExpired:
+        return -1, "timed out"
+    return done.returncode, done.stdout or done.stderr
+
+
+def _volume_root(path: str) -> str:
+    """The root of the volume *path* is on, e.g. ``C:\\`` or a mount folder."""
+    import ctypes
+
+    buffer = ctypes.create_unicode_buffer(32768)
+    kernel32 = ctypes.windll.kernel32  # type: ignore[attr-defined]
+    if not kernel32.GetVolumePathNameW(path, buffer, len(buffer)):
+        raise ctypes.WinError()  # type: ignore[attr-defined]
+    return str(buffer.value)
+
+
+def _create(volume: str) -> tuple[str, str]:
+    """``(shadow id, device path)`` of a new shadow copy of *volume*."""
+    code, out = _powershell(_CREATE.format(volume=volume.replace("'", "''")))
+    if code == 1:
+        raise SourcePermissionError(errno.EACCES, "creating a shadow copy needs an elevated "
+                                    "(administrator) prompt", volume)
+    if code == 4:
+        raise UnsupportedSourceError(errno.ENOTSUP, _CREATE_ERRORS[code], volume)
+    lines = out.split()
+    if code or len(lines) != 2:
+        reason = _CREATE_ERRORS.get(code) or out.strip() or f"PowerShell exited with {code}"
+        raise SourceReadError(errno.EIO, f"cannot create a shadow copy: {reason}", volume)
+    return lines[0], lines[1]
+
+
+@contextlib.contextmanager
+def shadow_copy(*paths: str) -> Iterator[tuple[str, ...]]:
+    """Snapshot the volumes *paths* are on, and give the paths inside the snapshots.
+
+    One Volume Shadow Copy is created per volume, however many paths are
+    on it, so a left and right side on one volume see the same moment.
+    Inside the ``with`` block the yielded paths name the same files and
+    directories as frozen when the copies were made: compare them
+    instead of the live ones to verify in-use directories without races
+    against writers or sharing violations. The copies are deleted on
+    leaving the block.
+
+    Windows only; creating a shadow copy needs an elevated prompt. Local
+    drives only: a network share or a volume without shadow copy
+    support (FAT, for one) cannot be snapshotted.
+
+    :param paths: Local files or directories.
+    :returns: Context manager yielding the snapshot path of each of *paths*, in order.
+    :raises UnsupportedSourceError: Off Windows, or if a path is on a network
+        share or a volume without shadow copies.
+    :raises SourcePermissionError: If the process is not elevated.
+    :raises SourceReadError: If the shadow copy service fails, or a copy
+        cannot be deleted afterwards.
+    """
+    if not paths:
+        raise ValueError("no paths to snapshot")
+    for path in paths:
+        validate_path(path, "path")
+    if sys.platform != "win32":
+        raise UnsupportedSourceError(errno.ENOTSUP, "shadow copies need Windows", paths[0])
+    located = []
+    for path in paths:
+        path = os.path.abspath(path)
+        if path.startswith("\\\\") and not path.startswith("\\\\?\\"):
+            raise UnsupportedSourceError(errno.ENOTSUP, "network shares cannot be "
+                                         "snapshotted", path)
+        root = _volume_root(path)
+        located.append((root, path[len(root):]))
+    shadows: dict[str, tuple[str, str]] = {}
+    try:
+        for root, _ in located:
+            if root not in shadows:
+                shadows[root] = _create(root)
+        yield tuple(os.path.join(shadows[root][1], rel) for root, rel in located)
+    finally:
+        left = [shadow_id for shadow_id, _ in shadows.values()
+                if _powershell(_DELETE.format(id=shadow_id))[0]]
+    if left:
+        raise SourceReadError(errno.EIO, "cannot delete shadow copies; remove them with "
+                              "vssadmin delete shadows /shadow=ID", ", ".join(left))
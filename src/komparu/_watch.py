@@ -0,0 +1,397 @@
+"""Live drift detection: re-compare two trees where they change.
+
+On Linux, inotify watches every directory of both trees; each batch of
+events re-compares only the paths it names (a whole subtree for a
+directory created, moved or removed). With ``CAP_SYS_ADMIN``, symlinks
+not followed and no other file system mounted inside the trees, one
+fanotify mark per file system replaces the watches, so nothing is added
+per directory and no watch limit applies. Elsewhere, or with ``poll=``,
+the trees are re-scanned by metadata at that interval instead.
+"""
+
+from __future__ import annotations
+
+import dataclasses
+import errno
+import os
+import select
+import stat
+import time
+from collections.abc import Callable, Iterator
+from typing import Any
+
+from komparu._api import compare, iter_dir
+from komparu._cancel import CancelToken
+from komparu._filter import PathFilter, make_exclude
+from komparu._helpers import walk_tree
+from komparu._options import (
+    DIR_OPTION_DEFAULTS,
+    CompareOptions,
+    LockPolicy,
+    RetryPolicy,
+    reject_overrides,
+)
+from komparu._storage import mount_table
+from komparu._types import (
+    DiffEntry,
+    DiffReason,
+    DriftEvent,
+    DriftKind,
+    EntryKind,
+    SourceReadError,
+    path_key,
+)
+from komparu._validate import validate_chunk_size, validate_max_workers, validate_path
+
+try:
+    from komparu._core import inotify_add_watch as _inotify_add_watch_c
+    from komparu._core import inotify_init as _inotify_init_c
+    from komparu._core import inotify_read as _inotify_read_c
+except ImportError:  # not Linux: poll
+    _inotify_init_c = None
+try:
+    from komparu._core import fanotify_init as _fanotify_init_c
+    from komparu._core import fanotify_mark as _fanotify_mark_c
+    from komparu._core import fanotify_read as _fanotify_read_c
+except ImportError:
+    _fanotify_init_c = None
+
+# Longest wait for events before checking the cancel token
+_WAKE = 0.5
+
+_Exclude = Callable[[str], bool] | None
+
+
+def _join(rel: str, name: str) -> str:
+    return f"{rel}/{name}" if rel else name
+
+
+def _under(path: str, root: str) -> bool:
+    return not root or path == root or path.startswith(root + "/")
+
+
+class _Inotify:
+    """Events of both trees as the relative paths they name."""
+
+    def __init__(self, roots: tuple[str, str], follow_symlinks: bool, exclude: _Exclude) -> None:
+        self._roots = roots
+        self._follow = follow_symlinks
+        self._exclude = exclude
+        self._dirs: dict[int, tuple[int, str]] = {}  # watch descriptor -> (side, directory)
+        self._fd = _inotify_init_c()
+        try:
+            for side in (0, 1):
+                self._add(side, "")
+        except BaseException:
+            os.close(self._fd)
+            raise
+
+    def _add(self, side: int, rel: str) -> None:
+        pending = [rel]
+        while pending:
+            rel = pending.pop()
+            path = os.path.join(self._roots[side], rel)
+            try:
+                wd = _inotify_add_watch_c(self._fd, path, self._follow)
+                with os.scandir(path) as it:
+                    children = [entry.name for entry in it
+                                if entry.is_dir(follow_symlinks=self._follow)]
+            except (FileNotFoundError, NotADirectoryError):
+                continue  # gone again already
+            except OSError as exc:
+                if exc.errno == errno.ENOSPC:
+                    raise OSError(errno.ENOSPC, "inotify watch limit reached; raise "
+                                  "fs.inotify.max_user_watches", path) from None
+                raise
+            # Re-adding a directory moved within the tree updates where it is
+            self._dirs[wd] = (side, rel)
+            pending += [_join(rel, name) for name in children
+                        if self._exclude is None or not self._exclude(_join(rel, name))]
+
+    def wait(self, timeout: float) -> set[str] | None:
+        """Paths changed within *timeout*; None if events were lost."""
+        if not select.select([self._fd], [], [], timeout)[0]:
+            return set()
+        changed = set()
+        for wd, new_dir, overflow, name in _inotify_read_c(self._fd):
+            if overflow:
+                for side in (0, 1):
+                    self._add(side, "")
+                return None
+            if wd not in self._dirs:
+                continue
+            side, rel = self._dirs[wd]
+            path = _join(rel, name)
+            if new_dir and (self._exclude is None or not self._exclude(path)):
+                self._add(side, path)
+            changed.add(path)
+        return changed
+
+    def fileno(self) -> int:
+        return self._fd
+
+    def close(self) -> None:
+        os.close(self._fd)
+
+
+class _Fanotify:
+    """Events of both trees from marks on the file systems they are on."""
+
+    def __init__(self, roots: tuple[str, str], exclude: _Exclude) -> None:
+        self._roots = [os.path.realpath(root) for root in roots]
+        self._exclude = exclude
+        self._fd = _fanotify_init_c()
+        self._mounts: list[int] = []  # a directory on each file system, to resolve handles
+        try:
+            for root in self._roots:
+                _fanotify_mark_c(self._fd, root)
+                self._mounts.append(os.open(root, os.O_RDONLY | os.O_DIRECTORY))
+        except BaseException:
+            self.close()
+            raise
+
+    def _excluded(self, path: str) -> bool:
+        while path and self._exclude is not None:
+            if self._exclude(path):
+                return True
+            path = path.rpartition("/")[0]
+        return False
+
+    def wait(self, timeout: float) -> set[str] | None:
+        """Paths changed within *timeout*; None if events were lost."""
+        if not select.select([self._fd], [], [], timeout)[0]:
+            return set()
+        paths, overflow = _fanotify_read_c(self._fd, self._mounts)
+        if overflow:
+            return None
+        changed = set()
+        for path in paths:  # the mark covers the whole file system: keep the trees'
+            for root in self._roots:
+                if path == root or path.startswith(root.rstrip("/") + "/"):
+                    rel = path[len(root):].lstrip("/")
+                    if not self._excluded(rel):
+                        changed.add(rel)
+        return changed
+
+    def fileno(self) -> int:
+        return self._fd
+
+    def close(self) -> None:
+        for fd in self._mounts:
+            os.close(fd)
+        os.close(self._fd)
+
+
+class _Poller:
+    """Changes found by re-scanning both trees' metadata."""
+
+    def __init__(self, roots: tuple[str, str], follow_symlinks: bool, exclude: _Exclude) -> None:
+        self._roots = roots
+        self._follow = follow_symlinks
+        self._exclude = exclude
+        self._seen = [self._scan(root) for root in roots]
+
+    def _scan(self, root: str) -> dict[str, tuple[int, int, int, int]]:
+        try:
+            entries, _ = walk_tree(root, self._follow, self._exclude)
+        except FileNotFoundError:
+            return {}
+        return {path: (st.st_mode, st.st_size, st.st_mtime_ns, st.st_ino)
+                for path, st in entries.items()}
+
+    def wait(self, timeout: float) -> set[str] | None:
+        time.sleep(timeout)
+        changed = set()
+        for side, root in enumerate(self._roots):
+            seen = self._scan(root)
+            old = self._seen[side]
+            changed |= {path for path in old.keys() | seen.keys()
+                        if old.get(path) != seen.get(path)}
+            self._seen[side] = seen
+        return changed
+
+    def close(self) -> None:
+        pass
+
+
+def _watcher(roots: tuple[str, str], follow_symlinks: bool, exclude: _Exclude,
+             poll: bool) -> _Fanotify | _Inotify | _Poller:
+    """The cheapest way to learn of changes to *roots* this process has."""
+    if poll or _inotify_init_c is None:
+        return _Poller(roots, follow_symlinks, exclude)
+    if _fanotify_init_c is not None and not follow_symlinks:
+        real = [os.path.realpath(root).rstrip("/") + "/" for root in roots]
+        # A mark covers one file system: one mounted inside a tree would go unseen
+        if not any(point.startswith(root) for point, _, _ in mount_table() for root in real):
+            try:
+                return _Fanotify(roots, exclude)
+            except OSError:
+                pass  # no CAP_SYS_ADMIN, or a kernel before 5.9
+    return _Inotify(roots, follow_symlinks, exclude)
+
+
+def _kind(path: str, follow_symlinks: bool) -> str | None:
+    try:
+        st = os.stat(path, follow_symlinks=follow_symlinks)
+    except FileNotFoundError:
+        return None
+    return "dir" if stat.S_ISDIR(st.st_mode) else "file"
+
+
+def _compare_dirs(paths: list[str], rel: str, kwargs: dict[str, Any], exclude: _Exclude,
+                  cancel: CancelToken | None) -> dict[str, DiffEntry]:
+    found = {}
+    for entry in iter_dir(*paths, cancel=cancel, **kwargs):
+        path = _join(rel, entry.path)
+        if exclude is None or not exclude(path):
+            found[path] = dataclasses.replace(entry, path=path)
+    return found
+
+
+def _entries(roots: tuple[str, str], rel: str, kwargs: dict[str, Any], exclude: _Exclude,
+             cancel: CancelToken | None) -> dict[str, DiffEntry]:
+    """Differences at and under *rel* (the whole comparison for ``""``)."""
+    if rel and exclude is not None and exclude(rel):
+        return {}
+    paths = [os.path.join(root, rel) if rel else root for root in roots]
+    follow = kwargs["follow_symlinks"]
+    kinds = [_kind(path, follow) for path in paths]
+    if kinds == ["dir", "dir"]:
+        return _compare_dirs(paths, rel, kwargs, exclude, cancel)
+    found: dict[str, DiffEntry] = {}
+    if kinds == ["file", "file"]:
+        try:
+            if compare(*paths, chunk_size=kwargs["chunk_size"], cancel=cancel,
+                       size_precheck=kwargs["size_precheck"], quick_check=kwargs["quick_check"]):
+                return found
+            size_a, size_b = (os.stat(path).st_size for path in paths)
+        except (SourceReadError, OSError):
+            return {rel: DiffEntry(rel, EntryKind.ERROR, DiffReason.READ_ERROR)}
+        reason = DiffReason.SIZE_MISMATCH if size_a != size_b else DiffReason.CONTENT_MISMATCH
+        return {rel: DiffEntry(rel, EntryKind.DIFF, reason, size_a=size_a, size_b=size_b)}
+    # Present on one side only, or a file on one and a directory on the other
+    for path, kind, entry_kind in zip(paths, kinds, (EntryKind.ONLY_LEFT, EntryKind.ONLY_RIGHT)):
+        if kind == "file":
+            found[rel] = DiffEntry(rel, entry_kind, DiffReason.MISSING)
+        elif kind == "dir":
+            sub_exclude = None if exclude is None else (lambda p: exclude(_join(rel, p)))
+            try:
+                entries, _ = walk_tree(path, follow, sub_exclude)
+            except FileNotFoundError:
+                continue
+            for sub, st in entries.items():
+                if not stat.S_ISDIR(st.st_mode):
+                    found[_join(rel, sub)] = DiffEntry(_join(rel, sub), entry_kind,
+                                                       DiffReason.MISSING)
+    return found
+
+
+def _drift(old: dict[str, DiffEntry], new: dict[str, DiffEntry]) -> Iterator[DriftEvent]:
+    for path in sorted(old.keys() | new.keys(), key=path_key):
+        before, after = old.get(path), new.get(path)
+        if after is not None and after != before:
+            yield DriftEvent(DriftKind.APPEARED, after)
+        elif after is None and before is not None:
+            yield DriftEvent(DriftKind.RESOLVED, before)
+
+
+def _topmost(paths: set[str]) -> list[str]:
+    """*paths* without those under another of them."""
+    if "" in paths:
+        return [""]
+    return sorted(path for path in paths
+                  if not any("/".join(path.split("/")[:n]) in paths
+                             for n in range(1, path.count("/") + 1)))
+
+
+def watch_dir(
+    dir_a: str,
+    dir_b: str,
+    *,
+    chunk_size: int = 65536,
+    size_precheck: bool = True,
+    quick_check: bool = True,
+    follow_symlinks: bool = True,
+    max_workers: int = 0,
+    retry: RetryPolicy | None = None,
+    lock: LockPolicy | None = None,
+    ignore: list[str] | None = None,
+    path_filter: PathFilter | None = None,
+    options: CompareOptions | None = None,
+    settle: float = 0.2,
+    poll: float | None = None,
+    cancel: CancelToken | None = None,
+) -> Iterator[DriftEvent]:
+    """Compare two directories, then keep re-comparing where either one changes.
+
+    The differences of the first full comparison are yielded as
+    ``APPEARED``. After that, each batch of changes re-compares only the
+    paths it touched and yields the differences that appeared there and
+    the ones that were resolved, until *cancel* is set or the iterator
+    is closed; it never ends on its own.
+
+    :param dir_a: Path to first directory.
+    :param dir_b: Path to second directory.
+    :param ignore: Glob patterns to exclude (matched per path component);
+        excluded directories are not watched.
+    :param path_filter: PathFilter deciding per entry whether it is reported.
+    :param options: CompareOptions bundle; replaces the keyword arguments above,
+        which raise TypeError if also passed.
+    :param settle: Seconds without further changes to wait before
+        re-comparing, so a file being written is compared once it is done.
+    :param poll: Re-scan both trees every this many seconds instead of
+        using change notifications (the only way on platforms without
+        inotify, and on network file systems that do not deliver them).
+    :param cancel: CancelToken that ends the watch; checked at least
+        every half second.
+    :returns: Iterator of DriftEvent.
+    :raises SourceReadError: If a directory cannot be walked.
+    :raises OSError: If the trees cannot be watched (e.g. too many
+        directories for ``fs.inotify.max_user_watches``).
+    """
+    if options is not None:
+        reject_overrides("watch_dir", locals(), DIR_OPTION_DEFAULTS)
+        yield from watch_dir(dir_a, dir_b, settle=settle, poll=poll,
+                             cancel=cancel, **options.dir_kwargs())
+        return
+
+    validate_path(dir_a, "dir_a")
+    validate_path(dir_b, "dir_b")
+    validate_chunk_size(chunk_size)
+    validate_max_workers(max_workers)
+    if settle < 0:
+        raise ValueError("settle must be non-negative")
+    if poll is not None and poll <= 0:
+        raise ValueError("poll must be positive")
+
+    roots = (os.fspath(dir_a), os.fspath(dir_b))
+    kwargs = {"chunk_size": chunk_size, "size_precheck": size_precheck,
+              "quick_check": quick_check, "follow_symlinks": follow_symlinks,
+              "max_workers": max_workers, "retry": retry, "lock": lock}
+    exclude = make_exclude(ignore, path_filter, dir_a, dir_b, follow_symlinks)
+    state = _compare_dirs(list(roots), "", kwargs, exclude, cancel)
+    watcher = _watcher(roots, follow_symlinks, exclude, poll is not None)
+    wake = _WAKE if not isinstance(watcher, _Poller) else poll or 1.0
+    try:
+        yield from _drift({}, state)
+        while cancel is None or not cancel.cancelled:
+            changed = watcher.wait(wake)
+            while changed:
+                more = watcher.wait(settle)
+                if more is None:
+                    changed = None
+                elif not more:
+                    break
+                else:
+                    changed |= more
+            if changed is not None and not changed:
+                continue
+            touched = [""] if changed is None else _topmost(changed)
+            new = {path: entry for path, entry in state.items()
+                   if not any(_under(path, root) for root in touched)}
+            for root in touched:
+                new.update(_entries(roots, root, kwargs, exclude, cancel))
+            yield from _drift(state, new)
+            state = new
+    finally:
+        watcher.close()
@@ -0,0 +1,45 @@
+"""Webhook notifications: JSON payloads POSTed where drift should be reported."""
+
+from __future__ import annotations
+
+import hashlib
+import hmac
+import json
+import urllib.error
+import urllib.request
+from typing import Any
+
+# Header carrying the HMAC-SHA256 of the body when a secret is set
+SIGNATURE_HEADER = "X-Komparu-Signature"
+
+
+def validate_webhook_url(url: str) -> None:
+    """Check that *url* is one :func:`post_webhook` can POST to."""
+    if not url.startswith(("http://", "https://")):
+        raise ValueError(f"webhook URL must be http:// or https://, got {url!r}")
+
+
+def post_webhook(url: str, payload: dict[str, Any], *, secret: str | None = None,
+                 timeout: float = 10.0) -> int:
+    """POST *payload* as JSON to *url*; the HTTP status of the answer.
+
+    With a *secret*, the body is signed as ``X-Komparu-Signature:
+    sha256=<hex HMAC-SHA256>`` so the receiver can check where it came
+    from, as GitHub signs its webhooks.
+
+    :raises OSError: If *url* cannot be reached or answers with an error
+        status.
+    """
+    body = json.dumps(payload).encode()
+    headers = {"Content-Type": "application/json", "User-Agent": "komparu"}
+    if secret is not None:
+        digest = hmac.new(secret.encode(), body, hashlib.sha256).hexdigest()
+        headers[SIGNATURE_HEADER] = f"sha256={digest}"
+    request = urllib.request.Request(url, data=body, headers=headers, method="POST")
+    try:
+        with urllib.request.urlopen(request, timeout=timeout) as response:
+            return int(response.status)
+    except urllib.error.HTTPError as exc:
+        raise OSError(f"webhook answered {exc.code} {exc.reason}") from None
+    except urllib.error.URLError as exc:
+        raise OSError(f"webhook unreachable: {exc.reason}") from None
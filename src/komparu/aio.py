@@ -11,12 +11,15 @@ Usage::
     result = await komparu.aio.compare("/path/a", "/path/b")
     result = await komparu.aio.compare("https://a.com/f", "https://b.com/f")
     dir_result = await komparu.aio.compare_dir("/dir_a", "/dir_b")
+    async for entry in komparu.aio.iter_dir("/dir_a", "/dir_b"):
+        print(entry.path, entry.kind)
     all_same = await komparu.aio.compare_all(["/a", "/b", "/c"])
 """
 
 from __future__ import annotations
 
 import asyncio
+from collections.abc import AsyncIterator, Callable, Mapping
 from typing import Any
 
 from komparu._config import get_config
@@ -25,14 +28,48 @@ from komparu._core import (
     async_compare_result,
     async_compare_dir_start,
     async_compare_dir_result,
+    async_dir_stream_start,
+    async_dir_stream_poll,
     async_compare_archive_start,
     async_compare_archive_result,
     async_compare_dir_urls_start,
     async_compare_dir_urls_result,
 )
-from komparu._types import CompareResult, DirResult, Source
-from komparu._validate import validate_path, validate_chunk_size, validate_timeout, validate_max_workers
-from komparu._helpers import build_dir_result, filter_dir_result
+from komparu._types import CompareResult, DiffEntry, DirResult, Progress, Source
+from komparu._options import (
+    DIR_OPTION_DEFAULTS,
+    FILE_OPTION_DEFAULTS,
+    CompareOptions,
+    LockPolicy,
+    RetryPolicy,
+    lock_kwargs,
+    reject_overrides,
+    retry_kwargs,
+)
+from komparu._metrics import MetricsSink, MetricsVisitor
+from komparu._visitor import CompareVisitor, ProgressVisitor, VisitorDriver
+from komparu._cancel import CancelToken, cancel_handle, child_token
+from komparu._dircache import DirCache, cache_handle
+from komparu._comparator import ContentComparator, make_dispatch
+from komparu._filter import PathFilter, make_exclude
+from komparu._api import prescan_totals
+from komparu._errors import error_scope
+from komparu._validate import (
+    validate_path,
+    validate_dir,
+    validate_chunk_size,
+    validate_timeout,
+    validate_max_workers,
+    validate_max_pending,
+)
+from komparu._helpers import (
+    DEFAULT_MAX_PENDING,
+    build_dir_result,
+    dir_handles,
+    filter_dir_result,
+    iter_diff_entries,
+    make_annotate,
+)
 
 
 def _source_path(source: str | Source) -> str:
@@ -79,6 +116,8 @@ async def compare(
     follow_redirects: bool = True,
     verify_ssl: bool = True,
     proxy: str | None = None,
+    options: CompareOptions | None = None,
+    cancel: CancelToken | None = None,
 ) -> bool:
     """Compare two sources byte-by-byte (async).
 
@@ -87,8 +126,16 @@ async def compare(
     :param source_a: File path, URL, or Source object.
     :param source_b: File path, URL, or Source object.
     :param proxy: Proxy URL (e.g. http://host:port, socks5://host:port).
+    :param options: CompareOptions bundle; replaces the keyword arguments above,
+        which raise TypeError if also passed.
+    :param cancel: CancelToken that aborts the comparison when set.
     :returns: True if sources are byte-identical.
+    :raises ComparisonCancelledError: If *cancel* was set while running.
     """
+    if options is not None:
+        reject_overrides("compare", locals(), FILE_OPTION_DEFAULTS)
+        return await compare(source_a, source_b, cancel=cancel, **options.file_kwargs())
+
     validate_path(source_a, "source_a")
     validate_path(source_b, "source_b")
     validate_chunk_size(chunk_size)
@@ -113,52 +160,260 @@ async def compare(
         verify_ssl=verify_ssl,
         allow_private=cfg.allow_private_redirects,
         proxy=p,
+        cancel=cancel_handle(cancel),
     )
 
-    return await _await_task(fd, lambda: async_compare_result(task))
+    with error_scope(cancel):
+        return await _await_task(fd, lambda: async_compare_result(task))
 
 
 async def compare_dir(
-    dir_a: str,
-    dir_b: str,
+    dir_a: str | int,
+    dir_b: str | int,
     *,
     chunk_size: int = 65536,
     size_precheck: bool = True,
     quick_check: bool = True,
     follow_symlinks: bool = True,
     max_workers: int = 0,
+    retry: RetryPolicy | None = None,
+    lock: LockPolicy | None = None,
     ignore: list[str] | None = None,
+    path_filter: PathFilter | None = None,
+    fail_fast: bool = False,
+    options: CompareOptions | None = None,
+    visitor: CompareVisitor | None = None,
+    progress: Callable[[Progress], None] | None = None,
+    metrics: MetricsSink | None = None,
+    comparators: Mapping[str, ContentComparator] | None = None,
+    cancel: CancelToken | None = None,
+    cache: DirCache | None = None,
+    prescan: bool = False,
 ) -> DirResult:
     """Compare two directories recursively (async).
 
     Directory walk and file comparisons run in C threads.
 
-    :param dir_a: Path to first directory.
-    :param dir_b: Path to second directory.
+    :param dir_a: Path to first directory, or an open directory descriptor.
+    :param dir_b: Path to second directory, or an open directory descriptor.
+    :param retry: RetryPolicy for transient I/O errors (EAGAIN, ESTALE, ...).
+    :param lock: LockPolicy reading files under shared advisory locks.
     :param ignore: Glob patterns to exclude (matched per path component).
+    :param path_filter: PathFilter deciding per entry, with metadata, whether
+        it is reported.
+    :param fail_fast: Stop at the first difference and return what was found
+        by then; ``equal`` is still exact, the other fields may be partial.
+    :param options: CompareOptions bundle; replaces the keyword arguments above,
+        which raise TypeError if also passed.
+    :param visitor: CompareVisitor notified on the event loop as the comparison runs.
+    :param progress: Called on the event loop with a Progress after each started or
+        resolved entry.
+    :param metrics: MetricsSink receiving counters and phase timings.
+    :param comparators: ContentComparator per glob pattern (matched against
+        the relative path), consulted for files whose bytes differ.
+    :param cancel: CancelToken that aborts the comparison when set.
+    :param cache: DirCache shared across calls; pairs found equal before and
+        unchanged since are not re-read.
+    :param prescan: Walk both trees and sum file sizes first (in a worker
+        thread), so every Progress carries totals and an ETA.
     :returns: DirResult with equal, diff, only_left, only_right.
+    :raises ValueError: If *prescan* is set without *progress*.
+    :raises ComparisonCancelledError: If *cancel* was set while running.
     """
-    validate_path(dir_a, "dir_a")
-    validate_path(dir_b, "dir_b")
+    if options is not None:
+        reject_overrides("compare_dir", locals(), {**DIR_OPTION_DEFAULTS, "fail_fast": False})
+        return await compare_dir(dir_a, dir_b, visitor=visitor, progress=progress,
+                                 metrics=metrics, fail_fast=options.fail_fast,
+                                 comparators=comparators,
+                                 cancel=cancel, cache=cache, prescan=prescan,
+                                 **options.dir_kwargs())
+
+    validate_dir(dir_a, "dir_a")
+    validate_dir(dir_b, "dir_b")
     validate_chunk_size(chunk_size)
     validate_max_workers(max_workers)
 
+    exclude = make_exclude(ignore, path_filter, dir_a, dir_b, follow_symlinks)
+    dispatch = make_dispatch(dir_a, dir_b, comparators)
+    if prescan and progress is None:
+        raise ValueError("prescan needs a progress callback")
+    if progress is not None:
+        totals = None
+        if prescan:
+            totals = await asyncio.to_thread(
+                prescan_totals, dir_a, dir_b, size_precheck=size_precheck,
+                follow_symlinks=follow_symlinks, ignore=ignore, path_filter=path_filter,
+                cancel=cancel)
+        visitor = ProgressVisitor(progress, dir_a, visitor, totals)
+    meter = None
+    if metrics is not None:
+        meter = visitor = MetricsVisitor(metrics, dir_a, visitor)
+
+    if visitor is not None or fail_fast:
+        stop = child_token(cancel) if fail_fast else cancel
+        path_a, path_b, handles = dir_handles(dir_a, dir_b)
+        fd, task = async_dir_stream_start(
+            path_a, path_b,
+            chunk_size=chunk_size,
+            size_precheck=size_precheck,
+            quick_check=quick_check,
+            follow_symlinks=follow_symlinks,
+            max_workers=max_workers,
+            progress=True,
+            max_pending=DEFAULT_MAX_PENDING,
+            cancel=cancel_handle(stop),
+            cache=cache_handle(cache),
+            **retry_kwargs(retry),
+            **lock_kwargs(lock),
+            **handles,
+        )
+        annotate = make_annotate(dir_a, dir_b, follow_symlinks,
+                                 dispatch.recheck if dispatch else None)
+        driver = VisitorDriver(visitor or CompareVisitor(), exclude, annotate)
+        loop = asyncio.get_running_loop()
+        ready = asyncio.Event()
+        loop.add_reader(fd, ready.set)
+        try:
+            finished = False
+            while not finished:
+                await ready.wait()
+                ready.clear()
+                with error_scope(cancel):
+                    events, finished = async_dir_stream_poll(task)
+                driver.feed(events)
+                if fail_fast and not driver.result().equal:
+                    stop.cancel()
+                    break
+        finally:
+            loop.remove_reader(fd)
+            if meter is not None:
+                meter.close()
+        return driver.result()
+
+    path_a, path_b, handles = dir_handles(dir_a, dir_b)
     fd, task = async_compare_dir_start(
-        dir_a, dir_b,
+        path_a, path_b,
         chunk_size=chunk_size,
         size_precheck=size_precheck,
         quick_check=quick_check,
         follow_symlinks=follow_symlinks,
         max_workers=max_workers,
+        cancel=cancel_handle(cancel),
+        cache=cache_handle(cache),
+        **retry_kwargs(retry),
+        **lock_kwargs(lock),
+        **handles,
     )
 
-    raw = await _await_task(fd, lambda: async_compare_dir_result(task))
+    with error_scope(cancel):
+        raw = await _await_task(fd, lambda: async_compare_dir_result(task))
     result = build_dir_result(raw)
-    if ignore:
-        result = filter_dir_result(result, ignore)
+    result = filter_dir_result(result, exclude)
+    if dispatch is not None:
+        result = dispatch.apply(result)
     return result
 
 
+async def iter_dir(
+    dir_a: str | int,
+    dir_b: str | int,
+    *,
+    chunk_size: int = 65536,
+    size_precheck: bool = True,
+    quick_check: bool = True,
+    follow_symlinks: bool = True,
+    max_workers: int = 0,
+    retry: RetryPolicy | None = None,
+    lock: LockPolicy | None = None,
+    ignore: list[str] | None = None,
+    path_filter: PathFilter | None = None,
+    include_equal: bool = False,
+    max_pending: int = DEFAULT_MAX_PENDING,
+    options: CompareOptions | None = None,
+    comparators: Mapping[str, ContentComparator] | None = None,
+    cancel: CancelToken | None = None,
+    cache: DirCache | None = None,
+) -> AsyncIterator[DiffEntry]:
+    """Compare two directories, yielding entries as they are resolved (async).
+
+    Same work as :func:`compare_dir`, but each entry is delivered as soon
+    as the C worker settles it, so callers can react before the whole
+    tree is done. Order follows completion, not path order.
+
+    :param dir_a: Path to first directory, or an open directory descriptor.
+    :param dir_b: Path to second directory, or an open directory descriptor.
+    :param retry: RetryPolicy for transient I/O errors (EAGAIN, ESTALE, ...).
+    :param lock: LockPolicy reading files under shared advisory locks.
+    :param ignore: Glob patterns to exclude (matched per path component).
+    :param path_filter: PathFilter deciding per entry, with metadata, whether
+        it is reported.
+    :param include_equal: Also yield identical files (``EntryKind.EQUAL``).
+    :param max_pending: Entries queued ahead of the consumer before the C
+        workers pause (0 = unbounded), so a slow loop body throttles the
+        comparison instead of buffering results in memory.
+    :param options: CompareOptions bundle; replaces the keyword arguments above,
+        which raise TypeError if also passed.
+    :param comparators: ContentComparator per glob pattern; entries they
+        declare equal are yielded as ``EntryKind.EQUAL``.
+    :param cancel: CancelToken that aborts the comparison when set.
+    :param cache: DirCache shared across calls; pairs found equal before and
+        unchanged since are not re-read.
+    :returns: Async iterator of DiffEntry.
+    :raises ComparisonCancelledError: If *cancel* was set while running.
+    """
+    if options is not None:
+        reject_overrides("iter_dir", locals(), DIR_OPTION_DEFAULTS)
+        async for entry in iter_dir(dir_a, dir_b, include_equal=include_equal,
+                                    max_pending=max_pending, comparators=comparators,
+                                    cancel=cancel, cache=cache, **options.dir_kwargs()):
+            yield entry
+        return
+
+    validate_dir(dir_a, "dir_a")
+    validate_dir(dir_b, "dir_b")
+    validate_chunk_size(chunk_size)
+    validate_max_workers(max_workers)
+    validate_max_pending(max_pending)
+    exclude = make_exclude(ignore, path_filter, dir_a, dir_b, follow_symlinks)
+    dispatch = make_dispatch(dir_a, dir_b, comparators)
+    annotate = make_annotate(dir_a, dir_b, follow_symlinks,
+                             dispatch.recheck if dispatch else None)
+
+    path_a, path_b, handles = dir_handles(dir_a, dir_b)
+    fd, task = async_dir_stream_start(
+        path_a, path_b,
+        chunk_size=chunk_size,
+        size_precheck=size_precheck,
+        quick_check=quick_check,
+        follow_symlinks=follow_symlinks,
+        max_workers=max_workers,
+        max_pending=max_pending,
+        cancel=cancel_handle(cancel),
+        cache=cache_handle(cache),
+        **retry_kwargs(retry),
+        **lock_kwargs(lock),
+        **handles,
+    )
+
+    loop = asyncio.get_running_loop()
+    ready = asyncio.Event()
+    loop.add_reader(fd, ready.set)
+    try:
+        while True:
+            await ready.wait()
+            ready.clear()
+            with error_scope(cancel):
+                events, finished = async_dir_stream_poll(task)
+            for entry in iter_diff_entries(events, exclude, include_equal, annotate):
+                yield entry
+            if finished:
+                return
+    finally:
+        # Closing the iterator early detaches the C task; the worker
+        # finishes in the background and frees itself.
+        loop.remove_reader(fd)
+
+
 async def compare_archive(
     path_a: str,
     path_b: str,
@@ -192,7 +447,8 @@ async def compare_archive(
         hash_compare=hash_compare,
     )
 
-    raw = await _await_task(fd, lambda: async_compare_archive_result(task))
+    with error_scope(archive=True):
+        raw = await _await_task(fd, lambda: async_compare_archive_result(task))
     return build_dir_result(raw)
 
 
@@ -347,13 +603,15 @@ async def compare_dir_urls(
         proxy=p,
     )
 
-    raw = await _await_task(fd, lambda: async_compare_dir_urls_result(task))
+    with error_scope():
+        raw = await _await_task(fd, lambda: async_compare_dir_urls_result(task))
     return build_dir_result(raw)
 
 
 __all__ = [
     "compare",
     "compare_dir",
+    "iter_dir",
     "compare_archive",
     "compare_all",
     "compare_many",
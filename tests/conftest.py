@@ -2,8 +2,14 @@
 
 from __future__ import annotations
 
+import base64
+import functools
+import hashlib
+import http.server
 import os
 import tempfile
+import threading
+from collections.abc import Iterator
 from pathlib import Path
 
 import pytest
@@ -36,3 +42,111 @@ def make_files(make_file):
         return {name: make_file(name, content) for name, content in files.items()}
 
     return _make
+
+
+@pytest.fixture
+def make_dir(tmp_path: Path):
+    """Factory fixture: create a directory tree from a dict of {relative_path: content}.
+
+    *name* is relative to tmp_path, or an absolute path.
+    """
+
+    def _make(name: str | Path, files: dict[str, bytes]) -> Path:
+        d = tmp_path / name
+        d.mkdir(parents=True, exist_ok=True)
+        for rel, content in files.items():
+            p = d / rel
+            p.parent.mkdir(parents=True, exist_ok=True)
+            p.write_bytes(content)
+        return d
+
+    return _make
+
+
+@pytest.fixture
+def tree_files() -> dict[str, bytes]:
+    """Files the ``trees`` fixture lays out, under ``a/`` and ``b/``.
+
+    Override it in a module to compare other trees.
+    """
+    return {
+        "a/same.txt": b"same",
+        "b/same.txt": b"same",
+        "a/diff.txt": b"one",
+        "b/diff.txt": b"two",
+        "a/sub/deep.txt": b"deep",
+        "b/sub/deep.txt": b"deep",
+    }
+
+
+@pytest.fixture
+def trees(make_files, tmp_path: Path, tree_files: dict[str, bytes]) -> tuple[Path, Path]:
+    """Two directories ``a`` and ``b`` laid out from ``tree_files``."""
+    make_files(tree_files)
+    for side in ("a", "b"):
+        (tmp_path / side).mkdir(exist_ok=True)
+    return tmp_path / "a", tmp_path / "b"
+
+
+@pytest.fixture
+def make_key_pair(tmp_path: Path):
+    """Factory fixture: write a minisign key pair as ``minisign -G`` lays it out.
+
+    Returns the secret and public key paths. Skips without cryptography.
+    """
+    ed25519 = pytest.importorskip("cryptography.hazmat.primitives.asymmetric.ed25519")
+    serialization = pytest.importorskip("cryptography.hazmat.primitives.serialization")
+
+    def _make(name: str = "key", password: str | None = None) -> tuple[str, str]:
+        private = ed25519.Ed25519PrivateKey.generate()
+        seed = private.private_bytes(serialization.Encoding.Raw,
+                                     serialization.PrivateFormat.Raw,
+                                     serialization.NoEncryption())
+        public = private.public_key().public_bytes(serialization.Encoding.Raw,
+                                                   serialization.PublicFormat.Raw)
+        key_id = os.urandom(8)
+        secret = seed + public
+        checksum = hashlib.blake2b(b"Ed" + key_id + secret, digest_size=32).digest()
+        keynum = key_id + secret + checksum
+        salt = os.urandom(32)
+        # Cheap limits: libsodium picks N=1024, r=8, p=1 for them
+        opslimit, memlimit = 32768, 16 << 20
+        kdf = b"\0\0"
+        if password is not None:
+            kdf = b"Sc"
+            stream = hashlib.scrypt(password.encode(), salt=salt, n=1024, r=8, p=1, dklen=104)
+            keynum = bytes(x ^ y for x, y in zip(keynum, stream))
+        raw = (b"Ed" + kdf + b"B2" + salt + opslimit.to_bytes(8, "little")
+               + memlimit.to_bytes(8, "little") + keynum)
+        secret_path = tmp_path / f"{name}.key"
+        public_path = tmp_path / f"{name}.pub"
+        secret_path.write_text("untrusted comment: test secret key\n"
+                               f"{base64.b64encode(raw).decode()}\n")
+        public_path.write_text("untrusted comment: test public key\n"
+                               f"{base64.b64encode(b'Ed' + key_id + public).decode()}\n")
+        return str(secret_path), str(public_path)
+
+    return _make
+
+
+class _Quiet(http.server.SimpleHTTPRequestHandler):
+    def log_message(self, format: str, *args: object) -> None:
+        pass
+
+
+@pytest.fixture
+def serve_dir() -> Iterator:
+    """Factory fixture: serve a directory over HTTP; returns its base URL."""
+    servers = []
+
+    def _serve(directory: Path) -> str:
+        server = http.server.ThreadingHTTPServer(
+            ("127.0.0.1", 0), functools.partial(_Quiet, directory=str(directory)))
+        threading.Thread(target=server.serve_forever, daemon=True).start()
+        servers.append(server)
+        return f"http://127.0.0.1:{server.server_address[1]}"
+
+    yield _serve
+    for server in servers:
+        server.shutdown()
+        server.server_close()
@@ -0,0 +1,140 @@
+"""Tests for the hashing agent and comparisons against it."""
+
+from __future__ import annotations
+
+import io
+import json
+import threading
+from collections.abc import Iterator
+from pathlib import Path
+
+import pytest
+
+import komparu
+from komparu import DiffReason
+from komparu._agent import AgentServer, parse_agent_url, serve_session
+from komparu._cli import main
+
+
+def _session(requests: list[dict[str, object]], **kwargs: object) -> list[dict[str, object]]:
+    out = io.BytesIO()
+    lines = b"".join(json.dumps(request).encode() + b"\n" for request in requests)
+    serve_session(io.BytesIO(lines), out, **kwargs)  # type: ignore[arg-type]
+    return [json.loads(line) for line in out.getvalue().splitlines()]
+
+
+@pytest.fixture
+def agent(make_dir) -> Iterator[tuple[AgentServer, Path]]:
+    served = make_dir("served", {"a.txt": b"alpha", "sub/b.txt": b"beta",
+                                 "sub/c.txt": b"gamma"})
+    server = AgentServer(("127.0.0.1", 0), roots=[str(served)], token="s3cret")
+    thread = threading.Thread(target=server.serve_forever, daemon=True)
+    thread.start()
+    yield server, served
+    server.shutdown()
+    server.server_close()
+
+
+def _url(server: AgentServer, path: str = "") -> str:
+    return f"komparu://127.0.0.1:{server.server_address[1]}{path}"
+
+
+class TestSession:
+    def test_hash(self, make_dir):
+        root = make_dir("t", {"x": b"x", "d/y": b"y"})
+        answers = _session([{"op": "hello"},
+                            {"op": "hash", "path": str(root), "algorithm": "md5"}])
+        assert answers[0]["ok"] is True and answers[0]["protocol"] == 1
+        assert answers[1:] == [
+            {"file": "d/y", "digest": "415290769594460e2e485922904f345d"},
+            {"file": "x", "digest": "9dd4e461268c8034f5c8564e155c67a6"},
+            {"ok": True, "files": 2},
+        ]
+
+    def test_token(self, tmp_path: Path):
+        answers = _session([{"op": "hash", "path": str(tmp_path)}], token="t")
+        assert answers == [{"ok": False, "error": "the agent needs a token", "errno": 13}]
+        answers = _session([{"op": "hello", "token": "u"}, {"op": "hash"}], token="t")
+        assert len(answers) == 1 and answers[0]["error"] == "wrong agent token"
+        answers = _session([{"op": "hello", "token": "t"}], token="t")
+        assert answers[0]["ok"] is True
+
+    def test_roots(self, tmp_path: Path, make_dir):
+        root = make_dir("root", {"sub/f": b"f"})
+        (root / "out").symlink_to(tmp_path)
+        answers = _session([{"op": "hash", "path": "sub"}, {"op": "hash", "path": "out"},
+                            {"op": "hash", "path": str(tmp_path)}], roots=[str(root)])
+        assert answers[0]["file"] == "f" and answers[1] == {"ok": True, "files": 1}
+        assert answers[2]["ok"] is False and answers[2]["errno"] == 13
+        assert answers[3]["ok"] is False and answers[3]["errno"] == 13
+
+    def test_bad_requests(self, tmp_path: Path):
+        answers = _session([{"op": "hash", "path": str(tmp_path), "algorithm": "nope"},
+                            {"op": "rm"}])
+        assert answers[0]["errno"] == 22 and "nope" in str(answers[0]["error"])
+        assert answers[1]["error"] == "unknown request 'rm'"
+        out = io.BytesIO()
+        serve_session(io.BytesIO(b"[1]\n{}\n"), out)
+        assert json.loads(out.getvalue())["error"] == "malformed request"
+
+
+class TestCompareDirAgent:
+    def test_equal_and_different(self, agent, monkeypatch, make_dir):
+        server, _ = agent
+        monkeypatch.setenv("KOMPARU_AGENT_TOKEN", "s3cret")
+        local = make_dir("local", {"a.txt": b"alpha", "sub/b.txt": b"beta",
+                                   "sub/c.txt": b"gamma"})
+        assert komparu.compare_dir_agent(str(local), _url(server)).equal is True
+        (local / "sub" / "b.txt").write_bytes(b"BETA")
+        (local / "d.txt").write_bytes(b"")
+        (local / "a.txt").unlink()
+        result = komparu.compare_dir_agent(str(local), _url(server), ignore=["c.txt"])
+        assert result.diff == {"sub/b.txt": DiffReason.CONTENT_MISMATCH}
+        assert result.only_left == {"d.txt"}
+        assert result.only_right == {"a.txt"}
+
+    def test_subdirectory(self, agent, make_dir):
+        server, served = agent
+        local = make_dir("local", {"b.txt": b"beta", "c.txt": b"gamma"})
+        assert komparu.compare_dir_agent(str(local), _url(server, f"{served}/sub"),
+                                         token="s3cret").equal is True
+
+    def test_errors(self, agent, tmp_path: Path):
+        server, served = agent
+        with pytest.raises(komparu.SourcePermissionError, match="token"):
+            komparu.compare_dir_agent(str(tmp_path), _url(server), token="wrong")
+        with pytest.raises(komparu.SourcePermissionError, match="roots"):
+            komparu.compare_dir_agent(str(tmp_path), _url(server, "/etc"), token="s3cret")
+        with pytest.raises(komparu.SourceNotFoundError):
+            komparu.compare_dir_agent(str(tmp_path), _url(server, f"{served}/missing"),
+                                      token="s3cret")
+        with pytest.raises(ValueError, match="komparu://"):
+            komparu.compare_dir_agent(str(tmp_path), "http://host/path")
+
+    def test_unreachable(self, tmp_path: Path):
+        server = AgentServer(("127.0.0.1", 0), roots=[str(tmp_path)])
+        url = _url(server)
+        server.server_close()
+        with pytest.raises(komparu.SourceReadError, match="cannot reach"):
+            komparu.compare_dir_agent(str(tmp_path), url)
+
+    def test_parse_url(self):
+        assert parse_agent_url("komparu://host/srv/www") == ("host", 7457, "/srv/www")
+        assert parse_agent_url("komparu://[::1]:9000") == ("::1", 9000, "")
+
+
+class TestCli:
+    def test_compare(self, agent, monkeypatch, capsys, make_dir):
+        server, _ = agent
+        monkeypatch.setenv("KOMPARU_AGENT_TOKEN", "s3cret")
+        local = make_dir("local", {"a.txt": b"alpha", "sub/b.txt": b"beta"})
+        assert main(["compare", str(local), _url(server)]) == 1
+        assert capsys.readouterr().out.splitlines() == ["only in right: sub/c.txt"]
+        assert main(["compare", _url(server), str(local)]) == 1
+        assert capsys.readouterr().out.splitlines() == ["only in left: sub/c.txt"]
+
+    def test_agent_options(self, tmp_path: Path, capsys):
+        assert main(["agent", "--listen", "127.0.0.1:0"]) == 2
+        assert "--root" in capsys.readouterr().err
+        assert main(["agent", "--listen", "nowhere", "--root", str(tmp_path)]) == 2
+        assert "HOST:PORT" in capsys.readouterr().err
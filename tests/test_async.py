@@ -2,6 +2,7 @@
 
 from __future__ import annotations
 
+import asyncio
 import io
 import os
 import tarfile
@@ -12,7 +13,7 @@ import pytest
 
 import komparu
 import komparu.aio
-from komparu import CompareResult, DiffReason
+from komparu import CompareResult, DiffEntry, DiffReason, EntryKind
 
 
 @pytest.fixture(autouse=True)
@@ -151,18 +152,6 @@ class TestAsyncConcurrent:
 # =========================================================================
 
 
-@pytest.fixture
-def make_dir(tmp_path: Path):
-    def _make(name: str, files: dict[str, bytes]) -> Path:
-        d = tmp_path / name
-        for rel, content in files.items():
-            p = d / rel
-            p.parent.mkdir(parents=True, exist_ok=True)
-            p.write_bytes(content)
-        return d
-    return _make
-
-
 class TestAsyncCompareDir:
     @pytest.mark.asyncio
     async def test_same_dir(self, make_dir):
@@ -226,6 +215,101 @@ class TestAsyncCompareDir:
         assert result.equal is True
 
 
+# =========================================================================
+# iter_dir — streaming async directory comparison
+# =========================================================================
+
+
+async def _collect(it) -> dict[str, komparu.DiffEntry]:
+    return {entry.path: entry async for entry in it}
+
+
+class TestAsyncIterDir:
+    @pytest.mark.asyncio
+    async def test_identical_dirs_yield_nothing(self, make_dir):
+        files = {f"file_{i}.txt": os.urandom(200) for i in range(10)}
+        a = make_dir("a", files)
+        b = make_dir("b", files)
+        entries = await _collect(komparu.aio.iter_dir(str(a), str(b)))
+        assert entries == {}
+
+    @pytest.mark.asyncio
+    async def test_include_equal(self, make_dir):
+        files = {"x.txt": b"same", "sub/y.txt": b"also same"}
+        a = make_dir("a", files)
+        b = make_dir("b", files)
+        entries = await _collect(
+            komparu.aio.iter_dir(str(a), str(b), include_equal=True))
+        assert set(entries) == {"x.txt", "sub/y.txt"}
+        assert all(e.kind is EntryKind.EQUAL for e in entries.values())
+        assert all(e.reason is None for e in entries.values())
+
+    @pytest.mark.asyncio
+    async def test_matches_compare_dir(self, make_dir):
+        a = make_dir("a", {
+            "same.txt": b"identical",
+            "diff.txt": b"version A",
+            "only_a.txt": b"exclusive",
+        })
+        b = make_dir("b", {
+            "same.txt": b"identical",
+            "diff.txt": b"version B",
+            "only_b.txt": b"exclusive",
+        })
+        entries = await _collect(komparu.aio.iter_dir(str(a), str(b)))
+        result = await komparu.aio.compare_dir(str(a), str(b))
+
+        assert entries["diff.txt"].kind is EntryKind.DIFF
+        assert entries["diff.txt"].reason is result.diff["diff.txt"]
+        assert entries["only_a.txt"] == DiffEntry(
+            "only_a.txt", EntryKind.ONLY_LEFT, DiffReason.MISSING)
+        assert entries["only_b.txt"] == DiffEntry(
+            "only_b.txt", EntryKind.ONLY_RIGHT, DiffReason.MISSING)
+        assert "same.txt" not in entries
+
+    @pytest.mark.asyncio
+    async def test_parallel_workers(self, make_dir):
+        a = make_dir("a", {f"f{i}.bin": bytes([i]) * 512 for i in range(50)})
+        b = make_dir("b", {f"f{i}.bin": bytes([i ^ (i % 2)]) * 512 for i in range(50)})
+        entries = await _collect(
+            komparu.aio.iter_dir(str(a), str(b), max_workers=4))
+        assert set(entries) == {f"f{i}.bin" for i in range(1, 50, 2)}
+
+    @pytest.mark.asyncio
+    async def test_ignore(self, make_dir):
+        a = make_dir("a", {"keep.txt": b"A", "skip.log": b"A"})
+        b = make_dir("b", {"keep.txt": b"B", "skip.log": b"B"})
+        entries = await _collect(
+            komparu.aio.iter_dir(str(a), str(b), ignore=["*.log"]))
+        assert set(entries) == {"keep.txt"}
+
+    @pytest.mark.asyncio
+    async def test_early_break(self, make_dir):
+        """Abandoning the iterator must not hang or leak the worker."""
+        a = make_dir("a", {f"f{i}.txt": b"A" for i in range(20)})
+        b = make_dir("b", {f"f{i}.txt": b"B" for i in range(20)})
+        it = komparu.aio.iter_dir(str(a), str(b))
+        async for _ in it:
+            break
+        await it.aclose()
+
+    @pytest.mark.asyncio
+    async def test_bounded_queue(self, make_dir):
+        a = make_dir("a", {f"f{i}.txt": b"A" for i in range(100)})
+        b = make_dir("b", {f"f{i}.txt": b"B" for i in range(100)})
+        seen = set()
+        async for entry in komparu.aio.iter_dir(str(a), str(b), max_workers=4, max_pending=2):
+            await asyncio.sleep(0)
+            seen.add(entry.path)
+        assert len(seen) == 100
+
+    @pytest.mark.asyncio
+    async def test_missing_dir(self, tmp_path: Path):
+        with pytest.raises(IOError):
+            await _collect(komparu.aio.iter_dir(
+                str(tmp_path / "nope"), str(tmp_path)))
+
+
 # =========================================================================
 # compare_archive — async archive comparison
 # =========================================================================
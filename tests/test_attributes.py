@@ -0,0 +1,108 @@
+"""Tests for .komparuattributes: per-path text, compare, ignore and metadata rules."""
+
+from __future__ import annotations
+
+import os
+
+import pytest
+
+from komparu._attributes import PathAttributes
+from komparu._cli import main
+
+RULES = """\
+# binary unless said otherwise
+*               binary
+*.txt           text
+docs/*.md       text
+/top.json       compare=json
+build/          ignore
+etc/**          metadata=mode
+etc/motd        -metadata
+"""
+
+
+class TestLookup:
+    def test_patterns(self):
+        rules = PathAttributes(RULES.splitlines())
+        assert rules.lookup("a/b/notes.txt") == {"text": True}
+        assert rules.lookup("docs/x.md") == {"text": True}
+        assert rules.lookup("sub/docs/x.md") == {"text": False}
+        assert rules.lookup("top.json") == {"text": False, "compare": "json"}
+        assert rules.lookup("sub/top.json") == {"text": False}
+        assert rules.lookup("etc/ssh/sshd_config")["metadata"] == "mode"
+        assert rules.lookup("etc/motd")["metadata"] is False
+
+    def test_later_lines_win(self):
+        rules = PathAttributes(["*.log text", "debug.log -text", "*.log !text"])
+        assert rules.lookup("debug.log") == {}
+        rules.add(["*.log binary"], where="extra")
+        assert rules.lookup("x.log") == {"text": False}
+
+    def test_ignored(self):
+        rules = PathAttributes(RULES.splitlines())
+        assert rules.ignored("build")
+        assert rules.ignored("src/build/out.o")
+        assert not rules.ignored("builder/out.o")
+        assert rules.path_filter() is not None
+        assert PathAttributes(["*.txt text"]).path_filter() is None
+
+    @pytest.mark.parametrize("line, message", [
+        ("*.txt eol=lf", "line:2: unknown attribute 'eol'"),
+        ("*.txt text=auto", "text takes no value"),
+        ("*.json compare", "compare needs a value"),
+        ("etc/* metadata=colour", "colour"),
+        ("!*.txt text", "negative patterns"),
+    ])
+    def test_bad_lines(self, line, message):
+        with pytest.raises(ValueError, match=message):
+            PathAttributes(["# fine", line], where="line")
+
+
+class TestCli:
+    def test_tree_rules(self, capsys, make_dir):
+        a = make_dir("a", {".komparuattributes": RULES.encode(), "notes.txt": b"x\r\n",
+                           "data.bin": b"x\r\n", "build/out.o": b"1"})
+        b = make_dir("b", {".komparuattributes": RULES.encode(), "notes.txt": b"x\n",
+                           "data.bin": b"x\n", "build/out.o": b"2"})
+        assert main(["compare", str(a), str(b)]) == 1
+        assert capsys.readouterr().out.splitlines() == ["differ: data.bin (size_mismatch)"]
+        assert main(["compare", "--no-tree-attributes", str(a), str(b)]) == 1
+        assert len(capsys.readouterr().out.splitlines()) == 3
+
+    def test_flag_overrides_tree(self, make_file, capsys, make_dir):
+        a = make_dir("a", {".komparuattributes": b"*.cfg text\n", "x.cfg": b"x\r\n"})
+        b = make_dir("b", {".komparuattributes": b"*.cfg text\n", "x.cfg": b"x\n"})
+        assert main(["compare", str(a), str(b)]) == 0
+        rules = make_file("rules", b"x.cfg binary\n")
+        assert main(["compare", "--attributes", str(rules), str(a), str(b)]) == 1
+        assert "x.cfg" in capsys.readouterr().out
+
+    def test_falls_back_to_flags(self, make_file, capsys, make_dir):
+        a = make_dir("a", {"conf.json": b'{"a": 1, "b": 2}', "x.txt": b"x\r\n"})
+        b = make_dir("b", {"conf.json": b'{"b": 2, "a": 1}', "x.txt": b"x\n"})
+        rules = make_file("rules", b"*.txt text\n")
+        assert main(["compare", "--json", "--attributes", str(rules), str(a), str(b)]) == 0
+
+    def test_metadata(self, make_file, capsys, make_dir):
+        a = make_dir("a", {"etc/motd": b"hi", "etc/passwd": b"root"})
+        b = make_dir("b", {"etc/motd": b"hi", "etc/passwd": b"root"})
+        for name in ("etc/motd", "etc/passwd"):
+            os.chmod(b / name, 0o600)
+        rules = make_file("rules", RULES.encode())
+        assert main(["compare", "--metadata-only", "--attributes", str(rules),
+                     str(a), str(b)]) == 1
+        out = capsys.readouterr().out
+        assert "etc/passwd" in out and "etc/motd" not in out
+
+    def test_refused(self, make_file, capsys, make_dir):
+        a = make_dir("a", {"x.txt": b"1"})
+        rules = make_file("rules", b"*.txt text\n")
+        assert main(["compare", "--attributes", str(rules), str(a / "x.txt"),
+                     str(a / "x.txt")]) == 2
+        assert "needs two local directories" in capsys.readouterr().err
+        assert main(["compare", "--max-bytes", "10", "--attributes", str(rules),
+                     str(a), str(a)]) == 2
+        assert "--max-bytes" in capsys.readouterr().err
+        bad = make_file("bad", b"*.txt compare=fuzzy\n")
+        assert main(["compare", "--attributes", str(bad), str(a), str(a)]) == 2
+        assert f"{bad}:1: unknown strategy 'fuzzy'" in capsys.readouterr().err
@@ -0,0 +1,112 @@
+"""Tests for the append-only audit log."""
+
+from __future__ import annotations
+
+import hashlib
+import json
+from pathlib import Path
+
+import pytest
+
+import komparu
+from komparu import AuditLog, check_audit_log
+from komparu._cli import main
+
+
+def _records(path: Path) -> list[dict]:
+    return [json.loads(line) for line in path.read_bytes().splitlines()]
+
+
+@pytest.fixture
+def trees(tmp_path: Path) -> tuple[Path, Path]:
+    a, b = tmp_path / "a", tmp_path / "b"
+    for root, files in ((a, {"same": b"1", "changed": b"old", "gone": b"x"}),
+                        (b, {"same": b"1", "changed": b"new"})):
+        root.mkdir()
+        for name, content in files.items():
+            (root / name).write_bytes(content)
+    return a, b
+
+
+class TestAuditLog:
+    def test_directory(self, trees, tmp_path: Path):
+        a, b = trees
+        log = tmp_path / "audit.jsonl"
+        with AuditLog(str(log)) as audit:
+            komparu.compare_dir(str(a), str(b), visitor=audit.visitor(str(a), str(b)))
+        records = {Path(r["left"]).name: r for r in _records(log)}
+        assert {name: r["result"] for name, r in records.items()} == {
+            "same": "equal", "changed": "content_mismatch", "gone": "only_left"}
+        changed = records["changed"]
+        assert changed["left"] == str(a / "changed") and changed["right"] == str(b / "changed")
+        assert (changed["size_left"], changed["size_right"]) == (3, 3)
+        assert changed["algorithm"] == "sha256"
+        assert changed["digest_left"] == hashlib.sha256(b"old").hexdigest()
+        assert changed["digest_right"] == hashlib.sha256(b"new").hexdigest()
+        assert changed["time"].endswith("+00:00")
+        assert (records["gone"]["size_right"], records["gone"]["digest_right"]) == (None, None)
+        assert check_audit_log(str(log)) == 3
+
+    def test_chain_continues(self, trees, tmp_path: Path):
+        a, b = trees
+        log = tmp_path / "audit.jsonl"
+        with AuditLog(str(log)) as audit:
+            assert audit.head is None
+            audit.record(str(a / "same"), str(b / "same"), "equal")
+        first = log.read_bytes()
+        with AuditLog(str(log), algorithm="md5") as audit:
+            assert audit.head == hashlib.sha256(first).hexdigest()
+            audit.record(str(a / "changed"), str(b / "changed"), "content_mismatch")
+        assert log.read_bytes().startswith(first)
+        second = _records(log)[1]
+        assert second["prev"] == hashlib.sha256(first).hexdigest()
+        assert second["digest_left"] == hashlib.md5(b"old").hexdigest()
+        assert check_audit_log(str(log)) == 2
+        assert audit.head == hashlib.sha256(log.read_bytes()[len(first):]).hexdigest()
+
+    def test_tampering(self, trees, tmp_path: Path):
+        a, b = trees
+        log = tmp_path / "audit.jsonl"
+        with AuditLog(str(log)) as audit:
+            for name in ("same", "changed", "same"):
+                audit.record(str(a / name), str(b / name), "equal")
+        lines = log.read_bytes().splitlines(keepends=True)
+        log.write_bytes(lines[0] + lines[1].replace(b'"equal"', b'"other"') + lines[2])
+        with pytest.raises(ValueError, match=":3: chain broken"):
+            check_audit_log(str(log))
+        log.write_bytes(lines[0] + lines[2])
+        with pytest.raises(ValueError, match=":2: chain broken"):
+            check_audit_log(str(log))
+        log.write_bytes(lines[0] + b"not json\n")
+        with pytest.raises(ValueError, match=":2: not an audit record"):
+            check_audit_log(str(log))
+
+    def test_incomplete_last_record(self, tmp_path: Path):
+        log = tmp_path / "audit.jsonl"
+        log.write_bytes(b'{"prev": null}\n{"pre')
+        with pytest.raises(ValueError, match="incomplete"):
+            AuditLog(str(log))
+        with pytest.raises(ValueError, match="unsupported hash algorithm"):
+            AuditLog(str(tmp_path / "other.jsonl"), algorithm="nope")
+
+
+class TestCli:
+    def test_compare(self, trees, tmp_path: Path):
+        a, b = trees
+        log = tmp_path / "audit.jsonl"
+        assert main(["compare", "-q", "--audit-log", str(log), str(a), str(b)]) == 1
+        assert main(["compare", "-q", "--audit-log", str(log), str(a / "same"),
+                     str(b / "same")]) == 0
+        assert main(["compare", "-q", "--audit-log", str(log), str(a / "gone"),
+                     str(b / "changed")]) == 1
+        records = _records(log)
+        assert [r["result"] for r in records[3:]] == ["equal", "size_mismatch"]
+        assert check_audit_log(str(log)) == 5
+
+    def test_needs_local_pairs(self, trees, tmp_path: Path, capsys):
+        a, _ = trees
+        (tmp_path / "a.tar").write_bytes(b"")
+        assert main(["compare", "--audit-log", str(tmp_path / "log"), str(a),
+                     str(tmp_path / "a.tar")]) == 2
+        assert "--audit-log needs two local files" in capsys.readouterr().err
+        assert not (tmp_path / "log").exists()
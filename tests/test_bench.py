@@ -0,0 +1,81 @@
+"""Tests for ``komparu bench`` against the system tools."""
+
+from __future__ import annotations
+
+import json
+import shutil
+from pathlib import Path
+
+import pytest
+
+from komparu._bench import BenchResult, bench
+from komparu._cli import main
+
+needs_tools = pytest.mark.skipif(not (shutil.which("cmp") and shutil.which("diff")),
+                                 reason="needs cmp and diff")
+
+
+def _trees(make_files, tmp_path: Path, right: bytes = b"two") -> tuple[str, str]:
+    make_files({"a/x": b"one", "a/sub/y": b"two", "b/x": b"one", "b/sub/y": right})
+    return str(tmp_path / "a"), str(tmp_path / "b")
+
+
+@needs_tools
+class TestBench:
+    def test_files(self, make_files, tmp_path):
+        make_files({"a": b"x" * 10000, "b": b"x" * 10000})
+        results = bench(str(tmp_path / "a"), str(tmp_path / "b"), tools=["komparu", "cmp"],
+                        repeat=2, warmup=0)
+        assert [(r.tool, r.result, len(r.seconds)) for r in results] == [
+            ("komparu", "equal", 2), ("cmp", "equal", 2)]
+        assert results[0].bytes_read == 20000
+        assert results[1].command.startswith("cmp -s ")
+
+    def test_directories(self, make_files, tmp_path):
+        a, b = _trees(make_files, tmp_path, right=b"2")
+        results = {r.tool: r for r in bench(a, b, repeat=1)}
+        assert results["komparu"].result == results["diff"].result == "different"
+        assert results["cmp"] == BenchResult("cmp", "", "not applicable")
+        assert results["cmp"].median is None
+
+    def test_not_installed(self, make_files, tmp_path, monkeypatch):
+        a, b = _trees(make_files, tmp_path)
+        monkeypatch.setattr(shutil, "which", lambda name: None)
+        [result] = bench(a, b, tools=["rsync"])
+        assert result.result == "not installed"
+        assert result.to_dict()["seconds"] == []
+
+    def test_refused(self, make_files, tmp_path):
+        a, _ = _trees(make_files, tmp_path)
+        with pytest.raises(ValueError, match="two files or two directories"):
+            bench(a, str(tmp_path / "a" / "x"))
+        with pytest.raises(ValueError, match="repeat"):
+            bench(a, a, repeat=0)
+        with pytest.raises(ValueError, match="unknown tool: sed"):
+            bench(a, a, tools=["sed"])
+
+
+@needs_tools
+class TestCli:
+    def test_table(self, make_files, tmp_path, capsys):
+        a, b = _trees(make_files, tmp_path)
+        assert main(["bench", "--tool", "komparu", "--tool", "diff", "--repeat", "1", a, b]) == 0
+        lines = capsys.readouterr().out.splitlines()
+        assert lines[0].split() == ["tool", "result", "median", "min", "read", "from", "disk"]
+        assert [line.split()[:2] for line in lines[1:]] == [["komparu", "equal"],
+                                                            ["diff", "equal"]]
+
+    def test_json(self, make_files, tmp_path, capsys):
+        a, b = _trees(make_files, tmp_path, right=b"2")
+        assert main(["bench", "--json", "--tool", "diff", "--warmup", "0", a, b]) == 0
+        [record] = json.loads(capsys.readouterr().out)
+        assert record["result"] == "different"
+        assert len(record["seconds"]) == 3
+        assert record["median"] == sorted(record["seconds"])[1]
+
+    def test_disagreement(self, make_files, tmp_path, monkeypatch, capsys):
+        a, b = _trees(make_files, tmp_path)
+        monkeypatch.setattr("komparu._bench._argv",
+                            lambda tool, path_a, path_b, is_dir: ["false"])
+        assert main(["bench", "--tool", "komparu", "--tool", "diff", a, b]) == 1
+        assert "the tools disagree: komparu equal, diff different" in capsys.readouterr().err
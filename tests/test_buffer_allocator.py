@@ -0,0 +1,108 @@
+"""Tests for the comparison buffer allocator hook."""
+
+from __future__ import annotations
+
+import ctypes
+import ctypes.util
+import threading
+
+import pytest
+
+import komparu
+
+_libc = ctypes.CDLL(ctypes.util.find_library("c") or None)
+_libc.malloc.restype = ctypes.c_void_p
+_libc.malloc.argtypes = [ctypes.c_size_t]
+_libc.free.argtypes = [ctypes.c_void_p]
+
+ALLOC_FN = ctypes.CFUNCTYPE(ctypes.c_void_p, ctypes.c_void_p, ctypes.c_size_t)
+RELEASE_FN = ctypes.CFUNCTYPE(None, ctypes.c_void_p, ctypes.c_void_p, ctypes.c_size_t)
+
+
+class BufferAllocator(ctypes.Structure):
+    _fields_ = [("alloc", ALLOC_FN), ("release", RELEASE_FN), ("ctx", ctypes.c_void_p)]
+
+
+_PyCapsule_New = ctypes.pythonapi.PyCapsule_New
+_PyCapsule_New.restype = ctypes.py_object
+_PyCapsule_New.argtypes = [ctypes.c_void_p, ctypes.c_char_p, ctypes.c_void_p]
+
+_lock = threading.Lock()
+live: dict[int, int] = {}
+sizes: list[int] = []
+
+
+@ALLOC_FN
+def _alloc(ctx, size):
+    ptr = _libc.malloc(size)
+    with _lock:
+        live[ptr] = size
+        sizes.append(size)
+    return ptr
+
+
+@RELEASE_FN
+def _release(ctx, ptr, size):
+    with _lock:
+        assert live.pop(ptr) == size
+    _libc.free(ptr)
+
+
+# Buffers may outlive a test (parked or held by a thread), so the
+# callbacks and the struct live for the whole process.
+_counting = BufferAllocator(_alloc, _release, None)
+_incomplete = BufferAllocator(_alloc, RELEASE_FN(), None)
+
+
+def capsule(allocator: BufferAllocator) -> object:
+    return _PyCapsule_New(ctypes.addressof(allocator), b"komparu.buffer_allocator", None)
+
+
+def flush(make_file) -> None:
+    """Restore malloc and make this thread drop its counting buffers."""
+    komparu.set_buffer_allocator(None)
+    a = make_file("flush_a.bin", b"f")
+    b = make_file("flush_b.bin", b"f")
+    komparu.compare(str(a), str(b))
+
+
+@pytest.fixture
+def counting(make_file):
+    sizes.clear()
+    komparu.set_buffer_allocator(capsule(_counting))
+    yield
+    flush(make_file)
+
+
+class TestSetBufferAllocator:
+    def test_used_for_comparison(self, counting, make_file):
+        a = make_file("a.bin", b"x" * 100_000)
+        b = make_file("b.bin", b"x" * 100_000)
+        assert komparu.compare(str(a), str(b), chunk_size=8192) is True
+        assert sizes == [8192, 8192]
+
+    def test_used_by_workers(self, counting, make_files, tmp_path):
+        make_files({f"{side}/{n}.txt": b"data" for side in "ab" for n in range(20)})
+        result = komparu.compare_dir(str(tmp_path / "a"), str(tmp_path / "b"),
+                                     max_workers=4)
+        assert result.equal
+        assert sizes
+
+    def test_reset_releases_through_owner(self, make_files, tmp_path, make_file):
+        make_files({f"{side}/{n}.txt": b"data" for side in "ab" for n in range(20)})
+        komparu.set_buffer_allocator(capsule(_counting))
+        komparu.compare_dir(str(tmp_path / "a"), str(tmp_path / "b"), max_workers=4)
+        komparu.compare(str(tmp_path / "a/0.txt"), str(tmp_path / "b/0.txt"))
+        assert live
+        flush(make_file)
+        assert not live
+
+    def test_rejects_other_objects(self):
+        with pytest.raises(TypeError, match="capsule"):
+            komparu.set_buffer_allocator(object())
+        with pytest.raises(TypeError, match="capsule"):
+            komparu.set_buffer_allocator(_PyCapsule_New(1, b"other", None))
+
+    def test_rejects_incomplete(self):
+        with pytest.raises(ValueError, match="release"):
+            komparu.set_buffer_allocator(capsule(_incomplete))
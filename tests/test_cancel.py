@@ -0,0 +1,183 @@
+"""Tests for CancelToken cancellation of in-flight comparisons."""
+
+from __future__ import annotations
+
+import os
+import threading
+from pathlib import Path
+
+import pytest
+
+import komparu
+import komparu.aio
+from komparu import (
+    CancelToken,
+    CompareVisitor,
+    ComparisonCancelledError,
+    KomparuError,
+    QuotaReachedError,
+)
+
+
+@pytest.fixture
+def same_content(tmp_path: Path):
+    """Two distinct files with identical content — forces a full scan."""
+    data = os.urandom(256 * 1024)
+    a = tmp_path / "a.bin"
+    b = tmp_path / "b.bin"
+    a.write_bytes(data)
+    b.write_bytes(data)
+    return str(a), str(b)
+
+
+@pytest.fixture
+def dir_pair(tmp_path: Path):
+    for name in ("a", "b"):
+        d = tmp_path / name
+        d.mkdir()
+        for i in range(20):
+            (d / f"f{i}.bin").write_bytes(bytes([i]) * 4096)
+    return str(tmp_path / "a"), str(tmp_path / "b")
+
+
+@pytest.fixture
+def cancelled() -> CancelToken:
+    token = CancelToken()
+    token.cancel()
+    return token
+
+
+class TestCancelToken:
+    def test_initial_state(self):
+        assert CancelToken().cancelled is False
+
+    def test_cancel_is_sticky_and_idempotent(self):
+        token = CancelToken()
+        token.cancel()
+        token.cancel()
+        assert token.cancelled is True
+
+    def test_cancel_from_other_thread(self):
+        token = CancelToken()
+        t = threading.Thread(target=token.cancel)
+        t.start()
+        t.join()
+        assert token.cancelled is True
+
+    def test_error_hierarchy(self):
+        assert issubclass(ComparisonCancelledError, KomparuError)
+
+    def test_repr(self):
+        assert repr(CancelToken()) == "CancelToken(cancelled=False)"
+
+
+class TestSyncCancel:
+    def test_unset_token_is_noop(self, same_content, dir_pair):
+        token = CancelToken()
+        assert komparu.compare(*same_content, cancel=token) is True
+        assert komparu.compare_dir(*dir_pair, cancel=token).equal is True
+        assert list(komparu.iter_dir(*dir_pair, cancel=token)) == []
+
+    def test_compare(self, same_content, cancelled):
+        with pytest.raises(ComparisonCancelledError):
+            komparu.compare(*same_content, cancel=cancelled)
+
+    def test_compare_dir(self, dir_pair, cancelled):
+        with pytest.raises(ComparisonCancelledError):
+            komparu.compare_dir(*dir_pair, cancel=cancelled)
+
+    def test_compare_dir_with_visitor(self, dir_pair, cancelled):
+        with pytest.raises(ComparisonCancelledError):
+            komparu.compare_dir(*dir_pair, cancel=cancelled,
+                                visitor=komparu.CompareVisitor())
+
+    def test_iter_dir(self, dir_pair, cancelled):
+        with pytest.raises(ComparisonCancelledError):
+            list(komparu.iter_dir(*dir_pair, cancel=cancelled))
+
+    def test_with_options(self, same_content, cancelled):
+        opts = komparu.CompareOptions(chunk_size=4096)
+        with pytest.raises(ComparisonCancelledError):
+            komparu.compare(*same_content, options=opts, cancel=cancelled)
+
+    def test_other_errors_not_masked(self, tmp_path: Path):
+        """With an unset token, ordinary errors surface unchanged."""
+        with pytest.raises(FileNotFoundError):
+            komparu.compare(str(tmp_path / "nope"), str(tmp_path / "nope2"),
+                            cancel=CancelToken())
+
+
+class TestByteQuota:
+    def test_within_quota(self, same_content):
+        token = CancelToken(max_bytes=1 << 20)
+        assert komparu.compare(*same_content, quick_check=False, cancel=token)
+        assert token.bytes_read == 2 * 256 * 1024
+        assert not token.quota_reached and not token.cancelled
+
+    def test_compare(self, same_content):
+        token = CancelToken(max_bytes=100_000)
+        with pytest.raises(QuotaReachedError, match="quota reached"):
+            komparu.compare(*same_content, cancel=token)
+        assert token.quota_reached and token.cancelled
+        assert 100_000 < token.bytes_read < 2 * 256 * 1024
+        assert CancelToken().quota_reached is False
+
+    def test_compare_dir_partial(self, dir_pair):
+        token = CancelToken(max_bytes=5 * 2 * 4096)
+        with pytest.raises(QuotaReachedError) as info:
+            komparu.compare_dir(*dir_pair, max_workers=1, cancel=token,
+                                visitor=CompareVisitor())
+        partial = info.value.partial
+        assert partial is not None and partial.equal
+        assert isinstance(info.value, ComparisonCancelledError)
+        with pytest.raises(QuotaReachedError) as info:
+            komparu.compare_dir(*dir_pair, cancel=token)
+        assert info.value.partial is None
+
+    def test_entries_done(self, dir_pair, tmp_path: Path):
+        (tmp_path / "a" / "only").write_bytes(b"")
+        token = CancelToken()
+        assert not komparu.compare_dir(*dir_pair, cancel=token).equal
+        assert token.entries_done == 21
+        assert not komparu.compare_dir(*dir_pair, cancel=token,
+                                       visitor=CompareVisitor()).equal
+        assert token.entries_done == 42
+        assert CancelToken().entries_done == 0
+
+    def test_child_token(self, dir_pair):
+        from komparu._cancel import child_token
+
+        parent = CancelToken(max_bytes=5 * 2 * 4096)
+        child = child_token(parent)
+        with pytest.raises(QuotaReachedError):
+            komparu.compare_dir(*dir_pair, max_workers=1, cancel=child)
+        assert parent.entries_done == child.entries_done > 0
+        assert komparu.compare_dir(*dir_pair, cancel=child_token(None)).equal
+        parent = CancelToken()
+        child = child_token(parent)
+        parent.cancel()
+        assert child.cancelled
+        with pytest.raises(ComparisonCancelledError):
+            komparu.compare_dir(*dir_pair, cancel=child)
+
+    def test_bad_quota(self):
+        with pytest.raises(ValueError, match="max_bytes"):
+            CancelToken(max_bytes=0)
+
+
+class TestAsyncCancel:
+    @pytest.mark.asyncio
+    async def test_compare(self, same_content, cancelled):
+        with pytest.raises(ComparisonCancelledError):
+            await komparu.aio.compare(*same_content, cancel=cancelled)
+
+    @pytest.mark.asyncio
+    async def test_compare_dir(self, dir_pair, cancelled):
+        with pytest.raises(ComparisonCancelledError):
+            await komparu.aio.compare_dir(*dir_pair, cancel=cancelled)
+
+    @pytest.mark.asyncio
+    async def test_iter_dir(self, dir_pair, cancelled):
+        with pytest.raises(ComparisonCancelledError):
+            async for _ in komparu.aio.iter_dir(*dir_pair, cancel=cancelled):
+                pass
@@ -0,0 +1,1700 @@
+"""Tests for the komparu command-line interface."""
+
+from __future__ import annotations
+
+import bz2
+import gzip
+import hashlib
+import importlib.util
+import io
+import json
+import lzma
+import os
+import random
+import re
+import shlex
+import shutil
+import signal
+import sqlite3
+import struct
+import subprocess
+import sys
+import tarfile
+import time
+import zipfile
+import zlib
+from pathlib import Path
+
+import pytest
+
+import komparu._api
+import komparu._cli as cli
+from komparu import DiffEntry, DiffReason, DirResult, EntryKind
+from komparu._cli import main
+
+
+class TestCompareFiles:
+    """`komparu compare` on two files."""
+
+    def test_equal(self, make_file, capsys):
+        a = make_file("a.bin", b"same")
+        b = make_file("b.bin", b"same")
+        assert main(["compare", str(a), str(b)]) == 0
+        assert capsys.readouterr().out == ""
+
+    def test_different(self, make_file, capsys):
+        a = make_file("a.bin", b"aaaa")
+        b = make_file("b.bin", b"bbbb")
+        assert main(["compare", str(a), str(b)]) == 1
+        assert "differ" in capsys.readouterr().out
+
+    def test_quiet(self, make_file, capsys):
+        a = make_file("a.bin", b"aaaa")
+        b = make_file("b.bin", b"bbbb")
+        assert main(["compare", "-q", str(a), str(b)]) == 1
+        assert capsys.readouterr().out == ""
+
+    def test_missing_file_is_error(self, make_file, tmp_path: Path, capsys):
+        a = make_file("a.bin", b"data")
+        assert main(["compare", str(a), str(tmp_path / "nope")]) == 2
+        assert "komparu:" in capsys.readouterr().err
+
+
+class TestCmpCompat:
+    """`komparu compare --cmp-compat`: GNU cmp's messages and exit codes."""
+
+    def _cmp(self, capsys, *args: str) -> tuple[int, str, str]:
+        code = main(["compare", "--cmp-compat", *args])
+        captured = capsys.readouterr()
+        return code, captured.out, captured.err
+
+    def test_differ(self, make_file, tmp_path, monkeypatch, capsys):
+        monkeypatch.chdir(tmp_path)
+        make_file("a", b"ab\ncd\n")
+        make_file("b", b"ab\ncX\n")
+        assert self._cmp(capsys, "a", "b") == (1, "a b differ: byte 5, line 2\n", "")
+        # Positions carry across chunk boundaries
+        assert self._cmp(capsys, "--chunk-size", "2", "a", "b")[1] == "a b differ: byte 5, line 2\n"
+        assert self._cmp(capsys, "a", "a") == (0, "", "")
+        assert self._cmp(capsys, "-q", "a", "b") == (1, "", "")
+
+    def test_eof(self, make_file, tmp_path, monkeypatch, capsys):
+        monkeypatch.chdir(tmp_path)
+        make_file("a", b"ab\ncd\n")
+        make_file("line", b"ab\n")
+        make_file("partial", b"ab\nc")
+        make_file("empty", b"")
+        for args, err in [
+            (("a", "line"), "cmp: EOF on line after byte 3, line 1\n"),
+            (("partial", "a"), "cmp: EOF on partial after byte 4, in line 2\n"),
+            (("a", "empty"), "cmp: EOF on empty which is empty\n"),
+            (("--chunk-size", "1", "partial", "a"),
+             "cmp: EOF on partial after byte 4, in line 2\n"),
+        ]:
+            assert self._cmp(capsys, *args) == (1, "", err)
+
+    def test_stdin(self, make_file, tmp_path, monkeypatch, capsys):
+        monkeypatch.chdir(tmp_path)
+        make_file("a", b"abc")
+        monkeypatch.setattr(sys, "stdin", io.TextIOWrapper(io.BytesIO(b"abd")))
+        assert self._cmp(capsys, "-", "a") == (1, "- a differ: byte 3, line 1\n", "")
+
+    def test_errors(self, make_file, tmp_path, monkeypatch, capsys):
+        monkeypatch.chdir(tmp_path)
+        make_file("a", b"a")
+        (tmp_path / "d").mkdir()
+        assert self._cmp(capsys, "a", "nope") == (
+            2, "", "cmp: nope: No such file or directory\n")
+        assert self._cmp(capsys, "a", "d") == (2, "", "cmp: d: Is a directory\n")
+        assert self._cmp(capsys, "-q", "a", "nope") == (2, "", "")
+        assert self._cmp(capsys, "--text", "a", "a")[0] == 2
+
+    @pytest.mark.skipif(shutil.which("cmp") is None, reason="needs cmp")
+    def test_matches_cmp(self, make_file, tmp_path, monkeypatch, capsys):
+        monkeypatch.chdir(tmp_path)
+        make_file("a", b"one\ntwo\n")
+        make_file("b", b"one\ntw")
+        make_file("c", b"")
+        for pair in [("a", "b"), ("b", "a"), ("c", "a")]:
+            cmp = subprocess.run(["cmp", *pair], capture_output=True, text=True)
+            assert self._cmp(capsys, *pair) == (cmp.returncode, cmp.stdout, cmp.stderr)
+
+
+class TestDiffCompat:
+    """`komparu compare --diff-compat`: the lines and exit codes of `diff -rq`."""
+
+    def _diff(self, capsys, *args: str) -> tuple[int, list[str], str]:
+        code = main(["compare", "--diff-compat", *args])
+        captured = capsys.readouterr()
+        return code, captured.out.splitlines(), captured.err
+
+    def _trees(self, make_dir) -> None:
+        make_dir("a", {"same": b"1", "sub/f": b"1", "sub/deep/z": b"z", "only/x/q": b"q",
+                       "t": b"t", "s": b"s"})
+        make_dir("b", {"same": b"1", "sub/f": b"2", "r": b"r", "t/in": b"t"})
+
+    def test_directories(self, make_dir, tmp_path, monkeypatch, capsys):
+        monkeypatch.chdir(tmp_path)
+        self._trees(make_dir)
+        (tmp_path / "a" / "empty").mkdir()
+        assert self._diff(capsys, "a", "b") == (1, [
+            "Only in a: empty",
+            "Only in a: only",
+            "Only in b: r",
+            "Only in a: s",
+            "Only in a/sub: deep",
+            "Files a/sub/f and b/sub/f differ",
+            "File a/t is a regular file while file b/t is a directory",
+        ], "")
+        assert self._diff(capsys, "a/", "b/")[1][:2] == ["Only in a/: empty", "Only in a/: only"]
+        assert self._diff(capsys, "--ignore", "sub", "--ignore", "only", "a", "b")[1] == [
+            "Only in a: empty", "Only in b: r", "Only in a: s",
+            "File a/t is a regular file while file b/t is a directory",
+        ]
+        assert self._diff(capsys, "-q", "a", "b") == (1, [], "")
+
+    def test_files(self, make_dir, tmp_path, monkeypatch, capsys):
+        monkeypatch.chdir(tmp_path)
+        self._trees(make_dir)
+        assert self._diff(capsys, "a/sub/f", "b/sub/f") == (
+            1, ["Files a/sub/f and b/sub/f differ"], "")
+        assert self._diff(capsys, "a/same", "b") == (0, [], "")
+        assert self._diff(capsys, "a/sub", "b/sub/f") == (
+            1, ["Files a/sub/f and b/sub/f differ"], "")
+        assert self._diff(capsys, "a", "nope") == (
+            2, [], "diff: nope: No such file or directory\n")
+
+    @pytest.mark.skipif(shutil.which("diff") is None, reason="needs diff")
+    def test_matches_diff(self, make_dir, tmp_path, monkeypatch, capsys):
+        monkeypatch.chdir(tmp_path)
+        self._trees(make_dir)
+        (tmp_path / "b" / "empty").write_bytes(b"")
+        (tmp_path / "a" / "empty").mkdir()
+        for pair in [("a", "b"), ("b", "a"), ("a/sub", "b/sub")]:
+            diff = subprocess.run(["diff", "-rq", *pair], capture_output=True, text=True,
+                                  env={**os.environ, "LC_ALL": "C"})
+            assert self._diff(capsys, *pair) == (
+                diff.returncode, diff.stdout.splitlines(), diff.stderr)
+
+
+class TestCompareDirs:
+    """`komparu compare` on two directories."""
+
+    def test_equal(self, make_dir):
+        a = make_dir("a", {"x.txt": b"1", "sub/y.txt": b"2"})
+        b = make_dir("b", {"x.txt": b"1", "sub/y.txt": b"2"})
+        assert main(["compare", str(a), str(b)]) == 0
+
+    def test_report(self, make_dir, capsys):
+        a = make_dir("a", {"x.txt": b"1", "left.txt": b"l"})
+        b = make_dir("b", {"x.txt": b"2", "right.txt": b"r"})
+        assert main(["compare", str(a), str(b)]) == 1
+        out = capsys.readouterr().out
+        assert "differ: x.txt" in out
+        assert "only in left: left.txt" in out
+        assert "only in right: right.txt" in out
+
+    def test_windows_name_on_strict_console(self, make_dir, monkeypatch):
+        # A Windows name with an unpaired surrogate goes out as WTF-8, not as a crash
+        a, b = make_dir("a", {"x": b"1"}), make_dir("b", {"x": b"1"})
+        monkeypatch.setattr(cli, "PATH_ERRORS", "komparu.wtf8")
+        monkeypatch.setattr(cli, "compare_dir", lambda *args, **kwargs: DirResult(
+            equal=False, diff={"a\ud800.txt": DiffReason.CONTENT_MISMATCH}, only_left=set(),
+            only_right=set()))
+        out = io.BytesIO()
+        monkeypatch.setattr(sys, "stdout", io.TextIOWrapper(out, encoding="utf-8"))
+        assert main(["compare", str(a), str(b)]) == 1
+        sys.stdout.flush()
+        assert out.getvalue() == b"differ: a\xed\xa0\x80.txt (content_mismatch)\n"
+
+    def test_unstable(self, make_dir, capsys, monkeypatch):
+        a = make_dir("a", {"live.log": b"1"})
+        b = make_dir("b", {"live.log": b"1"})
+        monkeypatch.setattr(cli, "compare_dir", lambda *args, **kwargs: DirResult(
+            equal=False, diff={"live.log": DiffReason.UNSTABLE}, only_left=set(),
+            only_right=set()))
+        assert main(["compare", str(a), str(b)]) == 1
+        assert capsys.readouterr().out == "unstable: live.log (changed while it was compared)\n"
+        assert main(["compare", "--severity", "unstable=warn", str(a), str(b)]) == 3
+
+    def test_on_error(self, make_dir, capsys, monkeypatch):
+        a = make_dir("a", {"x": b"1"})
+        b = make_dir("b", {"x": b"1"})
+        monkeypatch.setattr(cli, "compare_dir", lambda *args, **kwargs: DirResult(
+            equal=False, diff={"bad": DiffReason.READ_ERROR, "x": DiffReason.CONTENT_MISMATCH},
+            only_left=set(), only_right=set(), errors={"secret"}))
+        assert main(["compare", str(a), str(b)]) == 1
+        assert capsys.readouterr().out == (
+            "differ: x (content_mismatch)\n"
+            "unverifiable: bad (read_error)\n"
+            "unverifiable: secret (permission denied)\n"
+            "2 unverifiable (could not be read)\n")
+        assert main(["compare", "--on-error", "skip", str(a), str(b)]) == 1
+        assert capsys.readouterr().out == "differ: x (content_mismatch)\n"
+        assert main(["compare", "--on-error", "fail", str(a), str(b)]) == 2
+        assert "cannot read: 'bad'" in capsys.readouterr().err
+
+    def test_on_error_skip_only_unreadable(self, make_dir, monkeypatch):
+        a = make_dir("a", {"x": b"1"})
+        b = make_dir("b", {"x": b"1"})
+        monkeypatch.setattr(cli, "compare_dir", lambda *args, **kwargs: DirResult(
+            equal=False, diff={}, only_left=set(), only_right=set(), errors={"secret"}))
+        assert main(["compare", "--on-error", "skip", str(a), str(b)]) == 0
+        assert main(["compare", "--on-error", "report", str(a), str(b)]) == 1
+
+    def test_on_error_fail_stops_early(self, make_dir, capsys, monkeypatch):
+        a = make_dir("a", {"x": b"1"})
+        b = make_dir("b", {"x": b"1"})
+        seen = []
+
+        def stopped(*args, visitor, cancel, **kwargs):
+            with pytest.raises(PermissionError):
+                visitor.on_error(DiffEntry("secret", EntryKind.ERROR, DiffReason.READ_ERROR))
+            seen.append(cancel.cancelled)
+            raise PermissionError(13, "cannot read", "secret")
+
+        monkeypatch.setattr(cli, "compare_dir", stopped)
+        assert main(["compare", "--on-error", "fail", str(a), str(b)]) == 2
+        assert seen == [True]
+
+    def test_lock(self, make_dir, capsys):
+        fcntl = pytest.importorskip("fcntl")
+        a = make_dir("a", {"busy.db": b"1"})
+        b = make_dir("b", {"busy.db": b"1"})
+        assert main(["compare", "--lock", str(a), str(b)]) == 0
+        with open(b / "busy.db", "rb+") as f:
+            fcntl.flock(f, fcntl.LOCK_EX)
+            assert main(["compare", "--lock-timeout", "0", str(a), str(b)]) == 1
+        assert capsys.readouterr().out == "locked: busy.db (held by a writer, not compared)\n"
+        assert main(["compare", "--lock", str(a / "busy.db"), str(b / "busy.db")]) == 2
+        assert "--lock needs two local directories" in capsys.readouterr().err
+
+    def test_vanished(self, make_dir, monkeypatch, capsys):
+        a = make_dir("a", {"tmp": b"1"})
+        b = make_dir("b", {"tmp": b"1"})
+        monkeypatch.setattr(cli, "compare_dir", lambda *args, **kwargs: DirResult(
+            equal=False, diff={"tmp": DiffReason.VANISHED}, only_left=set(), only_right=set()))
+        assert main(["compare", str(a), str(b)]) == 1
+        assert capsys.readouterr().out == "vanished: tmp (deleted before it was compared)\n"
+        assert main(["compare", "--severity", "vanished=warn", str(a), str(b)]) == 3
+
+    def test_ignore(self, make_dir):
+        a = make_dir("a", {"x.txt": b"1", "skip.log": b"a"})
+        b = make_dir("b", {"x.txt": b"1", "skip.log": b"b"})
+        assert main(["compare", "--ignore", "*.log", str(a), str(b)]) == 0
+
+
+class TestCompareDirArchive:
+    """`komparu compare` on a directory and an archive of it."""
+
+    def test_backup(self, make_dir, tmp_path, capsys):
+        site = make_dir("site", {"index.html": b"<html/>", "css/a.css": b"a", "new.txt": b"n"})
+        backup = tmp_path / "site.tar.gz"
+        with tarfile.open(backup, "w:gz") as t:
+            t.add(site / "index.html", "index.html")
+            t.add(site / "css/a.css", "css/a.css")
+        assert main(["compare", "--ignore", "new.txt", str(backup), str(site)]) == 0
+        assert main(["compare", str(backup), str(site)]) == 1
+        assert "only in right: new.txt" in capsys.readouterr().out
+
+    def test_comparators_rejected(self, make_dir, make_file, capsys):
+        site = make_dir("site", {"x.txt": b"1"})
+        backup = make_file("site.zip", b"")
+        assert main(["compare", "--text", str(site), str(backup)]) == 2
+        assert "--text" in capsys.readouterr().err
+
+
+class TestText:
+    """`komparu compare --text` ignores CRLF vs LF."""
+
+    def test_files(self, make_file):
+        a = make_file("a.txt", b"one\r\ntwo\r\n")
+        b = make_file("b.txt", b"one\ntwo\n")
+        assert main(["compare", str(a), str(b)]) == 1
+        assert main(["compare", "--text", str(a), str(b)]) == 0
+
+    def test_files_still_differ(self, make_file, capsys):
+        a = make_file("a.txt", b"one\r\n")
+        b = make_file("b.txt", b"two\n")
+        assert main(["compare", "--text", str(a), str(b)]) == 1
+        assert "differ" in capsys.readouterr().out
+
+    def test_dirs(self, make_dir, capsys):
+        a = make_dir("a", {"x.txt": b"1\r\n2\r\n", "sub/y.txt": b"same", "z.txt": b"a\r\n"})
+        b = make_dir("b", {"x.txt": b"1\n2\n", "sub/y.txt": b"same", "z.txt": b"b\n"})
+        assert main(["compare", "--text", str(a), str(b)]) == 1
+        out = capsys.readouterr().out
+        assert "x.txt" not in out
+        assert "differ: z.txt" in out
+
+    def test_whitespace_flags(self, make_file):
+        a = make_file("a.cfg", b"key  =  1  \n")
+        b = make_file("b.cfg", b"key = 1\n")
+        assert main(["compare", "--text", str(a), str(b)]) == 1
+        assert main(["compare", "-Z", str(a), str(b)]) == 1
+        assert main(["compare", "-b", str(a), str(b)]) == 0
+        assert main(["compare", "--ignore-space-change", str(a), str(b)]) == 0
+
+    def test_trailing_space_flag(self, make_dir):
+        a = make_dir("a", {"gen.cfg": b"x = 1 \r\n"})
+        b = make_dir("b", {"gen.cfg": b"x = 1\n"})
+        assert main(["compare", "--ignore-trailing-space", str(a), str(b)]) == 0
+
+    def test_decode(self, make_dir):
+        a = make_dir("a", {"export.csv": "id;name\r\n1;caf\u00e9\r\n".encode("utf-16")})
+        b = make_dir("b", {"export.csv": "id;name\n1;caf\u00e9\n".encode("utf-8")})
+        assert main(["compare", "--text", str(a), str(b)]) == 1
+        assert main(["compare", "--decode", str(a), str(b)]) == 0
+
+    def test_ignore_bom(self, make_dir):
+        a = make_dir("a", {"notes.txt": b"\xef\xbb\xbfnotes\r\n"})
+        b = make_dir("b", {"notes.txt": b"notes\n"})
+        assert main(["compare", "--text", str(a), str(b)]) == 1
+        assert main(["compare", "--ignore-bom", str(a), str(b)]) == 0
+
+    def test_ignore_final_newline(self, make_dir):
+        a = make_dir("a", {"gen.cfg": b"key = 1\n"})
+        b = make_dir("b", {"gen.cfg": b"key = 1"})
+        assert main(["compare", "--text", str(a), str(b)]) == 1
+        assert main(["compare", "--ignore-final-newline", str(a), str(b)]) == 0
+
+    def test_archive_rejected(self, make_file, capsys):
+        a = make_file("a.zip", b"")
+        assert main(["compare", "--archive", "--text", str(a), str(a)]) == 2
+        assert "--text" in capsys.readouterr().err
+
+
+class TestJson:
+    """`komparu compare --json` compares *.json files structurally."""
+
+    def test_files(self, make_file):
+        a = make_file("a.json", b'{"b": 1, "a": [1.0]}')
+        b = make_file("b.json", b'{"a": [1], "b": 1}')
+        assert main(["compare", str(a), str(b)]) == 1
+        assert main(["compare", "--json", str(a), str(b)]) == 0
+
+    def test_dirs(self, make_dir, capsys):
+        a = make_dir("a", {"x.json": b'{"k": 1, "j": 2}', "y.json": b'{"k": 1}', "z.txt": b"1 "})
+        b = make_dir("b", {"x.json": b'{"j": 2, "k": 1}', "y.json": b'{"k": 2}', "z.txt": b"1"})
+        assert main(["compare", "--json", str(a), str(b)]) == 1
+        out = capsys.readouterr().out
+        assert "x.json" not in out
+        assert "differ: y.json" in out
+        assert "differ: z.txt" in out
+
+    def test_with_text(self, make_dir):
+        a = make_dir("a", {"x.json": b'{"k": 1, "j": 2}', "z.txt": b"1\r\n"})
+        b = make_dir("b", {"x.json": b'{"j": 2, "k": 1}', "z.txt": b"1\n"})
+        assert main(["compare", "--json", "--text", str(a), str(b)]) == 0
+
+
+class TestYaml:
+    """`komparu compare --yaml` compares *.yaml/*.yml files semantically."""
+
+    @pytest.mark.skipif(importlib.util.find_spec("yaml") is None, reason="needs PyYAML")
+    def test_dirs(self, make_dir, capsys):
+        a = make_dir("a", {"m.yaml": b"a: 1\nb: 2\n", "n.yml": b"x: &v 1\ny: *v\n"})
+        b = make_dir("b", {"m.yaml": b"b: 2\na: 1\n", "n.yml": b"{x: 1, y: 1}\n"})
+        assert main(["compare", str(a), str(b)]) == 1
+        assert main(["compare", "--yaml", str(a), str(b)]) == 0
+
+    def test_missing_pyyaml_is_error(self, make_dir, monkeypatch, capsys):
+        monkeypatch.setitem(sys.modules, "yaml", None)
+        a = make_dir("a", {"m.yaml": b"a: 1\n"})
+        assert main(["compare", "--yaml", str(a), str(a)]) == 2
+        assert "PyYAML" in capsys.readouterr().err
+
+
+class TestXml:
+    """`komparu compare --xml` compares *.xml files in canonical form."""
+
+    def test_files(self, make_file):
+        a = make_file("a.xml", b'<r b="2" a="1"/>')
+        b = make_file("b.xml", b"<r a='1' b='2'></r>")
+        assert main(["compare", str(a), str(b)]) == 1
+        assert main(["compare", "--xml", str(a), str(b)]) == 0
+
+
+def tarball(members: dict[str, int], mode: str = "w") -> bytes:
+    """Tarball of one-byte members, keyed by name, valued by mtime."""
+    buf = io.BytesIO()
+    with tarfile.open(fileobj=buf, mode=mode) as t:
+        for name, mtime in members.items():
+            info = tarfile.TarInfo(name)
+            info.size, info.mtime = 1, mtime
+            t.addfile(info, io.BytesIO(b"x"))
+    return buf.getvalue()
+
+
+class TestTar:
+    """`komparu compare --tar` compares tarballs by members and contents."""
+
+    def test_files(self, make_file):
+        a = make_file("a.tar", tarball({"x": 1, "y": 1}))
+        b = make_file("b.tar", tarball({"x": 1, "y": 1}, mode="w:gz"))
+        assert main(["compare", str(a), str(b)]) == 1
+        assert main(["compare", "--tar", str(a), str(b)]) == 0
+
+    def test_ignore_mtime_and_order(self, make_dir):
+        a = make_dir("a", {"src.tar": tarball({"x": 1, "y": 1})})
+        b = make_dir("b", {"src.tar": tarball({"y": 2, "x": 2})})
+        assert main(["compare", "--tar", str(a), str(b)]) == 1
+        assert main(["compare", "--tar-ignore-mtime", str(a), str(b)]) == 1
+        assert main(["compare", "--tar-ignore-mtime", "--tar-ignore-order", str(a), str(b)]) == 0
+
+    def test_before_decompress(self, make_dir):
+        a = make_dir("a", {"src.tar.gz": tarball({"x": 1}, mode="w:gz")})
+        b = make_dir("b", {"src.tar.gz": tarball({"x": 2}, mode="w:gz")})
+        assert main(["compare", "--decompress", str(a), str(b)]) == 1
+        assert main(["compare", "--decompress", "--tar-ignore-mtime", str(a), str(b)]) == 0
+
+
+def zipped(entries: dict[str, tuple[int, ...]], level: int = 6) -> bytes:
+    """Zip of one-byte entries, keyed by name, valued by timestamp."""
+    buf = io.BytesIO()
+    with zipfile.ZipFile(buf, "w", zipfile.ZIP_DEFLATED, compresslevel=level) as z:
+        for name, date_time in entries.items():
+            z.writestr(zipfile.ZipInfo(name, date_time), b"x")
+    return buf.getvalue()
+
+
+class TestZip:
+    """`komparu compare --zip` compares zip files by entries and contents."""
+
+    def test_files(self, make_file):
+        stamp = (2020, 1, 1, 0, 0, 0)
+        a = make_file("a.whl", zipped({"x": stamp, "y": stamp}, level=1))
+        b = make_file("b.whl", zipped({"y": stamp, "x": stamp}, level=9))
+        assert main(["compare", str(a), str(b)]) == 1
+        assert main(["compare", "--zip", str(a), str(b)]) == 0
+
+    def test_ignore_timestamps(self, make_dir):
+        a = make_dir("a", {"app.jar": zipped({"x": (2020, 1, 1, 0, 0, 0)})})
+        b = make_dir("b", {"app.jar": zipped({"x": (2021, 1, 1, 0, 0, 0)})})
+        assert main(["compare", "--zip", str(a), str(b)]) == 1
+        assert main(["compare", "--zip-ignore-timestamps", str(a), str(b)]) == 0
+
+
+def jpeg(scan: bytes, app1: bytes) -> bytes:
+    """Minimal JPEG segment layout with an EXIF-style APP1 segment."""
+    def segment(code: int, data: bytes) -> bytes:
+        return bytes((0xFF, code)) + struct.pack(">H", len(data) + 2) + data
+    return (b"\xff\xd8" + segment(0xE1, app1) + segment(0xDB, bytes(65))
+            + segment(0xDA, b"\x01\x01\x00\x00\x3f\x00") + scan + b"\xff\xd9")
+
+
+class TestImage:
+    """`komparu compare --image` ignores image metadata."""
+
+    def test_dirs(self, make_dir, capsys):
+        a = make_dir("a", {"x.jpg": jpeg(b"\x01", b"Exif old"), "y.jpeg": jpeg(b"\x02", b"")})
+        b = make_dir("b", {"x.jpg": jpeg(b"\x01", b"Exif new tags"), "y.jpeg": jpeg(b"\x03", b"")})
+        assert main(["compare", "--image", str(a), str(b)]) == 1
+        out = capsys.readouterr().out
+        assert "x.jpg" not in out
+        assert "differ: y.jpeg" in out
+
+    def test_files(self, make_file):
+        a = make_file("a.jpg", jpeg(b"\x01", b"Exif old"))
+        b = make_file("b.jpg", jpeg(b"\x01", b"Exif new tags"))
+        assert main(["compare", str(a), str(b)]) == 1
+        assert main(["compare", "--image", str(a), str(b)]) == 0
+
+
+class TestAudio:
+    """`komparu compare --audio` ignores tags."""
+
+    def test_files(self, make_file):
+        frames = (b"\xff\xfb\x90\x00" + bytes(100)) * 2
+        a = make_file("a.mp3", frames + b"TAG" + b"old".ljust(125, b"\0"))
+        b = make_file("b.mp3", frames + b"TAG" + b"new".ljust(125, b"\0"))
+        assert main(["compare", str(a), str(b)]) == 1
+        assert main(["compare", "--audio", str(a), str(b)]) == 0
+
+
+def pdf(doc_id: bytes, text: bytes = b"report") -> bytes:
+    """Single-stream PDF whose trailer carries *doc_id*."""
+    content = zlib.compress(text)
+    return (b"%%PDF-1.7\n1 0 obj\n<< /Length %d /Filter /FlateDecode >>\nstream\n%s\n"
+            b"endstream\nendobj\ntrailer\n<< /ID [<%s> <%s>] >>\n%%%%EOF\n"
+            % (len(content), content, doc_id, doc_id))
+
+
+class TestPdf:
+    """`komparu compare --pdf` ignores randomized PDF fields."""
+
+    def test_files(self, make_file):
+        a = make_file("a.pdf", pdf(b"0" * 32))
+        b = make_file("b.pdf", pdf(b"1" * 32))
+        c = make_file("c.pdf", pdf(b"1" * 32, b"changed"))
+        assert main(["compare", str(a), str(b)]) == 1
+        assert main(["compare", "--pdf", str(a), str(b)]) == 0
+        assert main(["compare", "--pdf", str(a), str(c)]) == 1
+
+
+class TestReproducible:
+    """`komparu compare --reproducible` ignores build timestamps and member order."""
+
+    def test_dirs(self, make_dir):
+        old, new = (2020, 1, 1, 0, 0, 0), (2024, 1, 1, 0, 0, 0)
+        a = make_dir("a", {"app.jar": zipped({"x": old, "y": old}),
+                           "src.tar.gz": tarball({"x": 1, "y": 1}, mode="w:gz")})
+        b = make_dir("b", {"app.jar": zipped({"y": new, "x": new}),
+                           "src.tar.gz": tarball({"y": 2, "x": 2}, mode="w:gz")})
+        assert main(["compare", "--zip", "--tar", str(a), str(b)]) == 1
+        assert main(["compare", "--reproducible", str(a), str(b)]) == 0
+
+
+class TestSqlite:
+    """`komparu compare --sqlite` compares databases by schema and rows."""
+
+    def test_files(self, tmp_path: Path):
+        for name, page_size, rows in (("a.db", 4096, [(1,), (2,)]), ("b.db", 1024, [(2,), (1,)])):
+            with sqlite3.connect(tmp_path / name) as db:
+                db.execute(f"PRAGMA page_size = {page_size}")
+                db.execute("CREATE TABLE t (id INTEGER PRIMARY KEY)")
+                db.executemany("INSERT INTO t VALUES (?)", rows)
+            db.close()
+        a, b = str(tmp_path / "a.db"), str(tmp_path / "b.db")
+        assert main(["compare", a, b]) == 1
+        assert main(["compare", "--sqlite", a, b]) == 0
+
+
+class TestParquet:
+    """`komparu compare --parquet` compares Parquet and Arrow files by schema and rows."""
+
+    def test_missing_pyarrow_is_error(self, make_dir, monkeypatch, capsys):
+        monkeypatch.setitem(sys.modules, "pyarrow", None)
+        a = make_dir("a", {"t.parquet": b"PAR1"})
+        assert main(["compare", "--parquet", str(a), str(a)]) == 2
+        assert "pyarrow" in capsys.readouterr().err
+
+
+class TestDecompress:
+    """`komparu compare --decompress` compares compressed files by content."""
+
+    def test_files(self, make_file):
+        a = make_file("a.gz", gzip.compress(b"data" * 100, compresslevel=1, mtime=1))
+        b = make_file("b.gz", gzip.compress(b"data" * 100, compresslevel=9, mtime=2))
+        assert main(["compare", str(a), str(b)]) == 1
+        assert main(["compare", "--decompress", str(a), str(b)]) == 0
+
+    def test_dirs(self, make_dir, capsys):
+        a = make_dir("a", {"x.gz": gzip.compress(b"x", mtime=1), "y.gz": gzip.compress(b"y")})
+        b = make_dir("b", {"x.gz": gzip.compress(b"x", mtime=2), "y.gz": gzip.compress(b"z")})
+        assert main(["compare", "--decompress", str(a), str(b)]) == 1
+        out = capsys.readouterr().out
+        assert "x.gz" not in out
+        assert "differ: y.gz" in out
+
+    def test_other_formats(self, make_dir):
+        a = make_dir("a", {"x.bz2": bz2.compress(b"x" * 10, 1), "y.xz": lzma.compress(b"y")})
+        b = make_dir("b", {"x.bz2": bz2.compress(b"x" * 10, 9), "y.xz": lzma.compress(b"y", preset=0)})
+        assert main(["compare", str(a), str(b)]) == 1
+        assert main(["compare", "--decompress", str(a), str(b)]) == 0
+
+
+class TestFilter:
+    """`komparu compare --filter PATTERN=COMMAND` compares normalized output."""
+
+    def test_dirs(self, make_dir):
+        a = make_dir("a", {"names.lst": b"bob\nalice\n", "x.bin": b"1"})
+        b = make_dir("b", {"names.lst": b"alice\nbob\n", "x.bin": b"1"})
+        sort = f"*.lst={shlex.quote(sys.executable)} -c 'import sys; sys.stdout.writelines(sorted(sys.stdin))'"
+        assert main(["compare", str(a), str(b)]) == 1
+        assert main(["compare", "--filter", sort, str(a), str(b)]) == 0
+
+    def test_bad_rule_is_error(self, make_dir, capsys):
+        a = make_dir("a", {"x.txt": b"1"})
+        assert main(["compare", "--filter", "sort", str(a), str(a)]) == 2
+        assert "PATTERN=COMMAND" in capsys.readouterr().err
+
+
+class TestCompareAs:
+    """`komparu compare --compare-as PATTERN=STRATEGY` picks a comparator per pattern."""
+
+    def test_strategies(self, make_dir, capsys):
+        a = make_dir("a", {"conf.json": b'{"a": 1, "b": 2}', "data.bin": b"x\r\n",
+                           "notes.txt": b"x\r\n"})
+        b = make_dir("b", {"conf.json": b'{"b": 2,\n "a": 1}', "data.bin": b"x\n",
+                           "notes.txt": b"x\n"})
+        assert main(["compare", "--compare-as", "*.json=json", "--compare-as", "*.txt = text",
+                     str(a), str(b)]) == 1
+        assert capsys.readouterr().out.splitlines() == ["differ: data.bin (size_mismatch)"]
+        # bytes takes a pattern out of --text, and an earlier rule wins over a later one
+        assert main(["compare", "--text", "--json", "--compare-as", "*.bin=bytes",
+                     "--compare-as", "*.json=bytes", "--compare-as", "*.json=json",
+                     str(a), str(b)]) == 1
+        assert capsys.readouterr().out.splitlines() == [
+            "differ: conf.json (size_mismatch)", "differ: data.bin (size_mismatch)"]
+
+    def test_bad_rule(self, make_dir, capsys):
+        a = make_dir("a", {"x.txt": b"1"})
+        assert main(["compare", "--compare-as", "*.txt=fuzzy", str(a), str(a)]) == 2
+        assert "unknown strategy 'fuzzy'" in capsys.readouterr().err
+        assert main(["compare", "--compare-as", "json", str(a), str(a)]) == 2
+        assert "PATTERN=STRATEGY" in capsys.readouterr().err
+
+
+class TestRules:
+    """`komparu compare --rules SCRIPT` runs Python include/compare hooks."""
+
+    SCRIPT = b"""\
+def include(path, stat_a, stat_b):
+    return not path.startswith("cache/")
+
+def compare(path_a, path_b):
+    if path_a.endswith(".version"):
+        return True
+    return None
+"""
+
+    def test_dirs(self, make_dir, make_file):
+        rules = make_file("rules.py", self.SCRIPT)
+        a = make_dir("a", {"app.version": b"1.0", "cache/x": b"1", "crlf.txt": b"a\r\n"})
+        b = make_dir("b", {"app.version": b"1.1", "cache/x": b"2", "crlf.txt": b"a\n"})
+        assert main(["compare", str(a), str(b)]) == 1
+        assert main(["compare", "--rules", str(rules), str(a), str(b)]) == 1
+        assert main(["compare", "--rules", str(rules), "--text", str(a), str(b)]) == 0
+
+    def test_files(self, make_file):
+        rules = make_file("rules.py", self.SCRIPT)
+        a = make_file("a.version", b"1.0")
+        b = make_file("b.version", b"1.1")
+        assert main(["compare", "--rules", str(rules), str(a), str(b)]) == 0
+
+    def test_no_hooks_is_error(self, make_dir, make_file, capsys):
+        rules = make_file("rules.py", b"x = 1\n")
+        a = make_dir("a", {"x.txt": b"1"})
+        assert main(["compare", "--rules", str(rules), str(a), str(a)]) == 2
+        assert "neither include() nor compare()" in capsys.readouterr().err
+
+
+class TestContentAware:
+    """`komparu compare --content-aware` picks comparators by file content."""
+
+    def test_dirs(self, make_dir, capsys):
+        a = make_dir("a", {"export": pdf(b"1" * 32), "bundle": tarball({"x": 1}, "w:gz"),
+                           "notes.txt": b"a\r\n"})
+        b = make_dir("b", {"export": pdf(b"2" * 32), "bundle": tarball({"x": 1}, "w:xz"),
+                           "notes.txt": b"a\n"})
+        assert main(["compare", "--content-aware", str(a), str(b)]) == 1
+        assert capsys.readouterr().out.splitlines() == [
+            "differ: notes.txt (size_mismatch)",
+            "equal: bundle (compared as tar)",
+            "equal: export (compared as pdf)",
+        ]
+        assert main(["compare", "--content-aware", "--text", str(a), str(b)]) == 0
+
+    def test_files(self, make_file, capsys):
+        a = make_file("a.out", pdf(b"1" * 32))
+        b = make_file("b.out", pdf(b"2" * 32, b"other report"))
+        assert main(["compare", "--content-aware", str(a), str(b)]) == 1
+        assert "differ (compared as pdf)" in capsys.readouterr().out
+
+
+class TestSimilarity:
+    """`komparu compare --similarity` reports how much of each differing file matches."""
+
+    def test_files(self, make_file, capsys):
+        a = make_file("a.bin", b"a" * 4096 * 4)
+        b = make_file("b.bin", b"a" * 4096 * 3 + b"b" * 4096)
+        assert main(["compare", "--similarity", str(a), str(b)]) == 1
+        assert "(75.0% similar)" in capsys.readouterr().out
+
+    def test_dirs_ranked(self, make_dir, capsys):
+        block = b"a" * 4096
+        a = make_dir("a", {"close.bin": block * 4, "far.bin": block * 4, "same.bin": block})
+        b = make_dir("b", {"close.bin": block * 3 + b"b" * 4096, "far.bin": b"b" * 4096,
+                           "same.bin": block})
+        assert main(["compare", "--similarity", str(a), str(b)]) == 1
+        assert capsys.readouterr().out.splitlines() == [
+            "differ: far.bin (size_mismatch, 0.0% similar)",
+            "differ: close.bin (content_mismatch, 75.0% similar)",
+        ]
+
+    def test_archive_rejected(self, make_file, capsys):
+        a = make_file("a.zip", b"")
+        assert main(["compare", "--archive", "--similarity", str(a), str(a)]) == 2
+        assert "--similarity" in capsys.readouterr().err
+
+
+class TestNamesOnly:
+    """`komparu compare --names-only` compares tree structure, not content."""
+
+    def test_same_names_different_content(self, make_dir, capsys):
+        a = make_dir("a", {"x.txt": b"one", "sub/y.txt": b"two"})
+        b = make_dir("b", {"x.txt": b"changed", "sub/y.txt": b"2"})
+        assert main(["compare", "--names-only", str(a), str(b)]) == 0
+        assert capsys.readouterr().out == ""
+
+    def test_structure_differs(self, make_dir, capsys):
+        a = make_dir("a", {"x.txt": b"one", "old.txt": b"", "kind/inner.txt": b""})
+        b = make_dir("b", {"x.txt": b"one", "new.txt": b"", "kind": b"now a file"})
+        assert main(["compare", "--names-only", str(a), str(b)]) == 1
+        assert capsys.readouterr().out.splitlines() == [
+            "only in left: kind/inner.txt",
+            "only in left: old.txt",
+            "only in right: kind",
+            "only in right: new.txt",
+        ]
+
+    def test_ignore(self, make_dir):
+        a = make_dir("a", {"x.txt": b"", "build.log": b""})
+        b = make_dir("b", {"x.txt": b""})
+        assert main(["compare", "--names-only", "--ignore", "*.log", str(a), str(b)]) == 0
+
+    def test_files_rejected(self, make_file, capsys):
+        a = make_file("a.txt", b"")
+        assert main(["compare", "--names-only", str(a), str(a)]) == 2
+        assert "--names-only" in capsys.readouterr().err
+
+
+class TestMetadataOnly:
+    """`komparu compare --metadata-only` audits permissions, not content."""
+
+    def test_mode_reported(self, make_dir, capsys):
+        a = make_dir("a", {"shadow": b"x", "motd": b"hello"})
+        b = make_dir("b", {"shadow": b"y", "motd": b"changed"})
+        os.chmod(a / "shadow", 0o640)
+        os.chmod(b / "shadow", 0o644)
+        assert main(["compare", "--metadata-only", str(a), str(b)]) == 1
+        assert capsys.readouterr().out.splitlines() == [
+            "differ: shadow (metadata_mismatch, mode 0640 -> 0644)",
+        ]
+
+    def test_attributes(self, make_dir, capsys):
+        a = make_dir("a", {"f": b""})
+        b = make_dir("b", {"f": b""})
+        os.chmod(b / "f", 0o600)
+        assert main(["compare", "--metadata", "owner", str(a), str(b)]) == 0
+        assert main(["compare", "--metadata", "mode,bogus", str(a), str(b)]) == 2
+        assert "bogus" in capsys.readouterr().err
+
+    def test_times_described(self, make_dir, capsys):
+        a = make_dir("a", {"f": b""})
+        b = make_dir("b", {"f": b""})
+        os.chmod(b / "f", 0o600)
+        assert main(["compare", "--metadata", "mode,ctime", str(a), str(b)]) == 1
+        line, = capsys.readouterr().out.splitlines()
+        assert line.startswith("differ: f (metadata_mismatch, mode ")
+        assert re.search(r", ctime \d{4}-\d\d-\d\dT[\d:]{8}\.\d{9}Z -> .*Z\)$", line)
+
+    def test_names_only_conflict(self, make_dir, capsys):
+        a = make_dir("a", {})
+        assert main(["compare", "--metadata-only", "--names-only", str(a), str(a)]) == 2
+        assert "cannot be combined" in capsys.readouterr().err
+
+
+class TestHash:
+    """`komparu hash DIR` prints a sha256sum-compatible manifest."""
+
+    def test_manifest(self, make_dir, capsys):
+        d = make_dir("d", {"b.txt": b"two", "a.txt": b"one"})
+        assert main(["hash", str(d)]) == 0
+        assert capsys.readouterr().out.splitlines() == [
+            f"{hashlib.sha256(b'one').hexdigest()}  a.txt",
+            f"{hashlib.sha256(b'two').hexdigest()}  b.txt",
+        ]
+
+    def test_escaped_name(self, make_dir, capsys):
+        d = make_dir("d", {"back\\slash": b""})
+        assert main(["hash", "-a", "md5", str(d)]) == 0
+        assert capsys.readouterr().out == f"\\{hashlib.md5(b'').hexdigest()}  back\\\\slash\n"
+
+    def test_output(self, make_dir, tmp_path, capsys):
+        d = make_dir("d", {"a.txt": b"one"})
+        assert main(["hash", str(d), "-o", str(tmp_path / "out")]) == 0
+        assert capsys.readouterr().out == ""
+        assert (tmp_path / "out").read_text() == f"{hashlib.sha256(b'one').hexdigest()}  a.txt\n"
+
+    def test_update(self, make_dir, tmp_path, capsys):
+        d = make_dir("d", {"a": b"one"})
+        manifest = tmp_path / "d.sha256"
+        assert main(["hash", "--update", str(manifest), str(d)]) == 0
+        (d / "b").write_bytes(b"two")
+        assert main(["hash", "--update", str(manifest), str(d)]) == 0
+        assert capsys.readouterr().out == ""
+        assert main(["verify", str(d), str(manifest)]) == 0
+        assert len(manifest.read_text().splitlines()) == 2
+
+    def test_tag(self, make_dir, capsys):
+        d = make_dir("d", {"a.txt": b"one"})
+        assert main(["hash", "--tag", "-a", "sha512", str(d)]) == 0
+        assert capsys.readouterr().out == f"SHA512 (a.txt) = {hashlib.sha512(b'one').hexdigest()}\n"
+
+    def test_hash_option(self, make_dir, tmp_path, capsys):
+        d = make_dir("d", {"a": b"123456789"})
+        assert main(["hash", "--hash", "crc32c", str(d)]) == 0
+        out = capsys.readouterr().out
+        assert out == "e3069283  a\n"
+        (tmp_path / "d.crc").write_text(out)
+        assert main(["verify", "--hash", "crc32c", str(d), str(tmp_path / "d.crc")]) == 0
+
+    def test_bad_algorithm(self, make_dir, capsys):
+        d = make_dir("d", {})
+        assert main(["hash", "-a", "nope", str(d)]) == 2
+        assert "nope" in capsys.readouterr().err
+
+
+class TestHashTree:
+    """`komparu hash --tree DIR` prints one digest for the whole tree."""
+
+    def test_tree(self, make_dir, capsys):
+        a = make_dir("a", {"f": b"one", "sub/g": b"two"})
+        b = make_dir("b", {"f": b"one", "sub/g": b"two"})
+        assert main(["hash", "--tree", str(a)]) == 0
+        digest = capsys.readouterr().out
+        assert main(["hash", "--tree", str(b)]) == 0
+        assert capsys.readouterr().out == digest
+        assert len(digest.strip()) == 64
+
+    def test_metadata(self, make_dir, capsys):
+        a = make_dir("a", {"f": b""})
+        os.chmod(a / "f", 0o600)
+        assert main(["hash", "--tree", str(a)]) == 0
+        plain = capsys.readouterr().out
+        assert main(["hash", "--tree", "--metadata", "mode", str(a)]) == 0
+        assert capsys.readouterr().out != plain
+
+    def test_metadata_needs_tree(self, make_dir, capsys):
+        a = make_dir("a", {})
+        assert main(["hash", "--metadata", "mode", str(a)]) == 2
+        assert "--metadata needs --tree" in capsys.readouterr().err
+
+
+class TestVerify:
+    """`komparu verify DIR MANIFEST` checks a tree against `komparu hash` output."""
+
+    def test_round_trip(self, make_dir, tmp_path, capsys):
+        d = make_dir("d", {"a.txt": b"one", "sub/b.txt": b"two"})
+        assert main(["hash", str(d)]) == 0
+        manifest = tmp_path / "d.sha256"
+        manifest.write_text(capsys.readouterr().out)
+        assert main(["verify", str(d), str(manifest)]) == 0
+        assert capsys.readouterr().out == ""
+
+    def test_report(self, make_dir, tmp_path, capsys):
+        d = make_dir("d", {"same": b"1", "changed": b"new", "extra": b""})
+        manifest = tmp_path / "d.sha256"
+        manifest.write_text("".join(f"{hashlib.sha256(data).hexdigest()}  {name}\n"
+                                    for name, data in [("same", b"1"), ("changed", b"old"),
+                                                       ("gone", b"")]))
+        assert main(["verify", str(d), str(manifest)]) == 1
+        assert capsys.readouterr().out.splitlines() == [
+            "differ: changed (content_mismatch)",
+            "only in tree: extra",
+            "only in manifest: gone",
+        ]
+
+    def test_bsd_manifest_names_algorithm(self, make_dir, tmp_path, capsys):
+        d = make_dir("d", {"a.txt": b"one"})
+        assert main(["hash", "--tag", "-a", "blake2s", str(d)]) == 0
+        manifest = tmp_path / "d.b2"
+        manifest.write_text(capsys.readouterr().out)
+        # 64 hex digits, but the tag says blake2s rather than sha256
+        assert main(["verify", str(d), str(manifest)]) == 0
+
+    def test_sfv(self, make_dir, tmp_path, capsys, monkeypatch):
+        d = make_dir("d", {"a.rar": b"123456789", "b.rar": b"changed"})
+        sfv = tmp_path / "d.sfv"
+        sfv.write_text("; made by hand\na.rar CBF43926\nb.rar 00000000\n")
+        assert main(["verify", str(d), str(sfv)]) == 1
+        assert capsys.readouterr().out.splitlines() == ["differ: b.rar (content_mismatch)"]
+        monkeypatch.setattr("sys.stdin", io.StringIO("a.rar CBF43926\n"))
+        assert main(["verify", "--sfv", "--ignore", "b.rar", str(d), "-"]) == 0
+
+    def test_malformed_manifest(self, make_dir, tmp_path, capsys):
+        d = make_dir("d", {})
+        manifest = tmp_path / "bad"
+        manifest.write_text("not a manifest\n")
+        assert main(["verify", str(d), str(manifest)]) == 2
+        assert "not a checksum line" in capsys.readouterr().err
+
+
+class TestVerifyZero:
+    def test_report(self, make_file, capsys):
+        blank, used = make_file("blank.img", bytes(8192)), make_file("used.img", b"\0\0x")
+        assert main(["verify-zero", str(blank)]) == 0
+        assert capsys.readouterr().out == f"{blank}: all zeros\n"
+        assert main(["verify-zero", str(blank), str(used)]) == 1
+        assert capsys.readouterr().out.splitlines()[1] == f"{used}: not zeroed"
+        assert main(["verify-zero", "-q", str(used.parent / "missing")]) == 2
+
+
+class TestSignedManifests:
+    """`komparu hash --sign` and `komparu verify --public-key` use minisign keys."""
+
+    def test_sign_and_verify(self, make_dir, make_key_pair, tmp_path, capsys):
+        d = make_dir("d", {"a": b"one"})
+        secret, public = make_key_pair()
+        manifest = tmp_path / "d.sha256"
+        assert main(["hash", str(d), "-o", str(manifest), "--sign", secret]) == 0
+        assert (tmp_path / "d.sha256.minisig").exists()
+        assert main(["verify", str(d), str(manifest), "--public-key", public]) == 0
+        assert capsys.readouterr().out == ""
+
+    def test_tampered_manifest(self, make_dir, make_key_pair, tmp_path, capsys):
+        d = make_dir("d", {"a": b"one"})
+        secret, public = make_key_pair()
+        manifest = tmp_path / "d.sha256"
+        assert main(["hash", str(d), "-o", str(manifest), "--sign", secret]) == 0
+        manifest.write_text(manifest.read_text().replace("  a", "  b"))
+        assert main(["verify", str(d), str(manifest), "--public-key", public]) == 2
+        assert "signature does not verify" in capsys.readouterr().err
+
+    def test_encrypted_key_password_from_environment(self, make_dir, make_key_pair, tmp_path,
+                                                     monkeypatch):
+        d = make_dir("d", {"a": b"one"})
+        secret, public = make_key_pair(password="pw")
+        monkeypatch.setenv("KOMPARU_SIGN_PASSWORD", "pw")
+        manifest = tmp_path / "d.sha256"
+        assert main(["hash", str(d), "-o", str(manifest), "--sign", secret]) == 0
+        assert main(["verify", "-q", str(d), str(manifest), "--public-key", public]) == 0
+
+    def test_manifest_url(self, make_dir, make_key_pair, serve_dir, tmp_path, capsys):
+        d = make_dir("d", {"a": b"one"})
+        secret, public = make_key_pair()
+        assert main(["hash", str(d), "-o", str(tmp_path / "d.sha256"), "--sign", secret]) == 0
+        url = f"{serve_dir(tmp_path)}/d.sha256"
+        assert main(["verify", str(d), url, "--public-key", public]) == 0
+        (d / "a").write_bytes(b"two")
+        assert main(["verify", str(d), url, "--public-key", public]) == 1
+        assert main(["verify", str(d), url + "x"]) == 2
+        assert "HTTP 404" in capsys.readouterr().err
+
+    def test_sign_needs_output(self, make_dir, capsys):
+        d = make_dir("d", {})
+        assert main(["hash", str(d), "--sign", "key"]) == 2
+        assert "--sign needs --output" in capsys.readouterr().err
+
+
+class TestDiffManifests:
+    """`komparu diff-manifests A B` compares two manifests offline."""
+
+    def test_report(self, make_dir, tmp_path, capsys):
+        a = make_dir("a", {"same": b"1", "changed": b"old", "gone": b""})
+        b = make_dir("b", {"same": b"1", "changed": b"new", "added": b""})
+        for d in (a, b):
+            assert main(["hash", str(d)]) == 0
+            (tmp_path / f"{d.name}.sha256").write_text(capsys.readouterr().out)
+        assert main(["diff-manifests", str(tmp_path / "a.sha256"),
+                     str(tmp_path / "b.sha256")]) == 1
+        assert capsys.readouterr().out.splitlines() == [
+            "differ: changed (content_mismatch)",
+            "only in left: gone",
+            "only in right: added",
+        ]
+        assert main(["diff-manifests", "-q", str(tmp_path / "a.sha256"),
+                     str(tmp_path / "a.sha256")]) == 0
+        assert capsys.readouterr().out == ""
+
+    def test_both_stdin(self, capsys):
+        assert main(["diff-manifests", "-", "-"]) == 2
+        assert "standard input" in capsys.readouterr().err
+
+
+class TestDupes:
+    """`komparu dupes DIR...` reports groups of identical files."""
+
+    def test_report(self, make_dir, capsys):
+        a = make_dir("a", {"x": b"shared", "y": b"unique", "empty": b""})
+        b = make_dir("b", {"sub/x": b"shared", "empty": b""})
+        assert main(["dupes", str(a), str(b)]) == 1
+        assert capsys.readouterr().out.splitlines() == [
+            "2 files, 6 bytes each:",
+            f"  {a / 'x'}",
+            f"  {b / 'sub' / 'x'}",
+        ]
+
+    def test_no_duplicates(self, make_dir, capsys):
+        a = make_dir("a", {"x": b"one", "y": b"two"})
+        assert main(["dupes", "-q", str(a)]) == 0
+        assert capsys.readouterr().out == ""
+
+    def test_savings(self, make_dir, capsys):
+        a = make_dir("a", {"big1": b"B" * 100, "big2": b"B" * 100, "big3": b"B" * 100,
+                           "small1": b"s", "small2": b"s"})
+        assert main(["dupes", "--savings", "--top", "1", str(a)]) == 1
+        assert capsys.readouterr().out.splitlines() == [
+            "201 bytes wasted by 3 duplicate files in 2 groups",
+            f"  200 bytes: 3 copies of {a / 'big1'} (100 bytes each)",
+        ]
+
+    def test_compare_savings(self, make_dir, capsys):
+        a = make_dir("a", {"x": b"data", "y": b"left"})
+        b = make_dir("b", {"x": b"data", "y": b"right"})
+        assert main(["compare", "--savings", str(a), str(b)]) == 1
+        assert capsys.readouterr().out.splitlines() == [
+            "differ: y (size_mismatch)",
+            "4 bytes wasted by 1 duplicate file in 1 group",
+            f"  4 bytes: 2 copies of {a / 'x'} (4 bytes each)",
+        ]
+
+    def test_compare_savings_needs_directories(self, make_dir, capsys):
+        a = make_dir("a", {"x": b"data"})
+        assert main(["compare", "--savings", str(a / "x"), str(a / "x")]) == 2
+        assert "two directories" in capsys.readouterr().err
+
+    def test_apply_dry_run(self, make_dir, capsys):
+        a = make_dir("a", {"x": b"shared", "y": b"shared"})
+        assert main(["dupes", "--apply", "hardlink", "--dry-run", str(a)]) == 0
+        assert capsys.readouterr().out.splitlines() == [
+            f"would hardlink: {a / 'y'} -> {a / 'x'}",
+            "1 file(s), 6 bytes would be freed",
+        ]
+        assert not os.path.samefile(a / "x", a / "y")
+
+    def test_apply_hardlink(self, make_dir, capsys):
+        a = make_dir("a", {"x": b"shared", "y": b"shared"})
+        assert main(["dupes", "--apply", "hardlink", str(a)]) == 0
+        assert capsys.readouterr().out.splitlines()[-1] == "1 file(s), 6 bytes freed"
+        assert os.path.samefile(a / "x", a / "y")
+        assert main(["dupes", "-q", str(a)]) == 0
+
+    def test_dry_run_needs_apply(self, make_dir, capsys):
+        a = make_dir("a", {"x": b"shared"})
+        assert main(["dupes", "--dry-run", str(a)]) == 2
+        assert "--apply" in capsys.readouterr().err
+
+
+class TestCopyVerify:
+    """`komparu copy-verify SRC DST` copies and checks the copy."""
+
+    def test_file_into_directory(self, make_dir, tmp_path):
+        src = make_dir("src", {"a.bin": b"payload"})
+        dst = make_dir("dst", {})
+        manifest = tmp_path / "copy.sha256"
+        assert main(["copy-verify", str(src / "a.bin"), str(dst), "--manifest",
+                     str(manifest)]) == 0
+        assert (dst / "a.bin").read_bytes() == b"payload"
+        assert manifest.read_text() == f"{hashlib.sha256(b'payload').hexdigest()}  a.bin\n"
+
+    def test_tree(self, make_dir, tmp_path, capsys):
+        src = make_dir("src", {"a": b"1", "sub/b": b"2"})
+        manifest = tmp_path / "copy.sha256"
+        assert main(["copy-verify", str(src), str(tmp_path / "dst"), "--manifest",
+                     str(manifest)]) == 0
+        assert main(["verify", str(tmp_path / "dst"), str(manifest)]) == 0
+        assert capsys.readouterr().out == ""
+
+
+class TestSnapshots:
+    """`komparu snapshot` and `komparu diff-snapshot` detect drift."""
+
+    def test_drift(self, make_dir, tmp_path, capsys):
+        d = make_dir("d", {"keep": b"1", "change": b"old", "gone": b"x"})
+        snap = tmp_path / "d.kpz"
+        assert main(["snapshot", str(d), "-o", str(snap)]) == 0
+        assert main(["diff-snapshot", str(snap), str(d)]) == 0
+        (d / "change").write_bytes(b"new")
+        (d / "gone").unlink()
+        (d / "added").write_bytes(b"")
+        (d / "keep").chmod(0o600)
+        assert main(["diff-snapshot", str(snap), str(d)]) == 1
+        assert capsys.readouterr().out.splitlines() == [
+            "differ: change (content_mismatch)",
+            f"differ: keep (metadata_mismatch, mode {(d / 'change').stat().st_mode & 0o777:04o}"
+            " -> 0600)",
+            "only in snapshot: gone",
+            "only in tree: added",
+        ]
+        assert main(["diff-snapshot", "--metadata", "owner", "-q", str(snap), str(d)]) == 1
+
+    def test_two_snapshots(self, make_dir, tmp_path, capsys):
+        d = make_dir("d", {"a": b"1"})
+        assert main(["snapshot", str(d), "-o", str(tmp_path / "one.kpz")]) == 0
+        (d / "b").write_bytes(b"2")
+        assert main(["snapshot", str(d), "-o", str(tmp_path / "two.kpz")]) == 0
+        assert main(["diff-snapshot", str(tmp_path / "one.kpz"),
+                     str(tmp_path / "two.kpz")]) == 1
+        assert capsys.readouterr().out.splitlines() == ["only in right: b"]
+
+
+class TestChunks:
+    """`komparu compare --chunks` reports shifted content by content-defined chunks."""
+
+    DATA = random.Random(1).randbytes(200_000)
+
+    def test_files(self, make_file, capsys):
+        a = make_file("a.bin", self.DATA)
+        b = make_file("b.bin", self.DATA[:1000] + b"inserted" + self.DATA[1000:])
+        assert main(["compare", "--chunks", str(a), str(b)]) == 1
+        out = capsys.readouterr().out
+        assert "% of chunks shared, 1 insertion)" in out
+
+    def test_dirs(self, make_dir, capsys):
+        a = make_dir("a", {"shifted.bin": self.DATA, "same.bin": b"x"})
+        b = make_dir("b", {"shifted.bin": self.DATA[:5000] + self.DATA[5100:], "same.bin": b"x"})
+        assert main(["compare", "--chunks", str(a), str(b)]) == 1
+        [line] = capsys.readouterr().out.splitlines()
+        assert line.startswith("differ: shifted.bin (size_mismatch, ")
+        assert line.endswith("% of chunks shared, 1 deletion)")
+
+    def test_archive_rejected(self, make_file, capsys):
+        a = make_file("a.zip", b"")
+        assert main(["compare", "--archive", "--chunks", str(a), str(a)]) == 2
+        assert "--chunks" in capsys.readouterr().err
+
+
+class TestRegions:
+    """`komparu compare --regions` lists where differing files differ."""
+
+    DATA = random.Random(1).randbytes(200_000)
+
+    def test_files(self, make_file, capsys):
+        a = make_file("a.bin", self.DATA)
+        b = make_file("b.bin", self.DATA[:1000] + b"inserted" + self.DATA[1000:-10])
+        assert main(["compare", "--regions", str(a), str(b)]) == 1
+        assert capsys.readouterr().out.splitlines()[1:] == [
+            "  inserted: 8 bytes at 1000",
+            "  deleted: 10 bytes at 199990",
+        ]
+
+    def test_dirs(self, make_dir, capsys):
+        changed = bytearray(self.DATA)
+        changed[5000:5002] = b"\0\0"
+        a = make_dir("a", {"x.bin": self.DATA, "same.bin": b"x"})
+        b = make_dir("b", {"x.bin": bytes(changed), "same.bin": b"x"})
+        assert main(["compare", "--regions", str(a), str(b)]) == 1
+        assert capsys.readouterr().out.splitlines() == [
+            "differ: x.bin (content_mismatch)",
+            "  changed: 2 bytes at 5000 -> 2 bytes at 5000",
+        ]
+
+
+class TestSuggestSync:
+    def test_text(self, make_dir, capsys):
+        a = make_dir("a", {"keep": b"1", "changed": b"new", "new/f": b"f"})
+        b = make_dir("b", {"keep": b"1", "changed": b"old!", "gone": b"g"})
+        assert main(["compare", "--suggest-sync", str(a), str(b)]) == 1
+        assert capsys.readouterr().out.splitlines() == [
+            "delete gone", "copy changed 0644 (3 bytes)", "mkdir new 0755",
+            "copy new/f 0644 (1 byte)",
+        ]
+        assert main(["compare", "--suggest-sync", str(a), str(a)]) == 0
+        assert capsys.readouterr().out == ""
+
+    def test_json(self, make_dir, capsys):
+        a = make_dir("a", {"f": b"1"})
+        b = make_dir("b", {})
+        assert main(["compare", "--suggest-sync-json", str(a), str(b)]) == 1
+        assert json.loads(capsys.readouterr().out) == {
+            "steps": [{"action": "copy", "path": "f", "mode": 0o644, "size": 1}], "errors": []}
+
+    def test_needs_directories(self, make_file, capsys):
+        a = make_file("a", b"1")
+        assert main(["compare", "--suggest-sync", str(a), str(a)]) == 2
+        assert "--suggest-sync needs two local directories" in capsys.readouterr().err
+
+
+class TestPairs:
+    """`komparu compare --pair` / `--pairs-from`: many pairs in one run."""
+
+    def test_pairs(self, make_dir, make_file, capsys):
+        a = make_dir("a", {"f": b"1"})
+        b = make_dir("b", {"f": b"2"})
+        x, y = make_file("x", b"same"), make_file("y", b"same")
+        assert main(["compare", "--pair", str(x), str(y), "--pair", str(a), str(b)]) == 1
+        assert capsys.readouterr().out.splitlines() == [
+            f"== {x} {y}", f"== {a} {b}", "differ: f (content_mismatch)",
+            "2 pairs: 1 equal, 1 different, 0 failed",
+        ]
+
+    def test_pairs_from(self, make_dir, make_file, tmp_path, capsys):
+        make_dir("a", {"f": b"1"})
+        make_dir("b", {"f": b"1"})
+        make_file("x", b"same")
+        listing = tmp_path / "pairs.tsv"
+        listing.write_text(f"# trees\n{tmp_path}/a\t{tmp_path}/b\n\n"
+                           f"{tmp_path}/x\t{tmp_path}/missing\n")
+        assert main(["compare", "-q", "--pairs-from", str(listing)]) == 2
+        captured = capsys.readouterr()
+        assert captured.out == ""
+        assert f"komparu: {tmp_path}/x {tmp_path}/missing: " in captured.err
+        listing.write_text(f"{tmp_path}/a\t{tmp_path}/b\n" * 2)
+        assert main(["compare", "--pairs-from", str(listing)]) == 0
+        assert capsys.readouterr().out.splitlines()[-1] == "2 pairs: 2 equal, 0 different, 0 failed"
+
+    def test_bad_listing(self, tmp_path, capsys):
+        listing = tmp_path / "pairs.tsv"
+        listing.write_text("only-one-path\n")
+        assert main(["compare", "--pairs-from", str(listing)]) == 2
+        assert f"{listing}:1: expected LEFT<TAB>RIGHT" in capsys.readouterr().err
+        listing.write_text("# nothing\n")
+        assert main(["compare", "--pairs-from", str(listing)]) == 2
+        assert "lists no pairs" in capsys.readouterr().err
+        assert main(["compare", "--pair", "a", "b", "c", "d"]) == 2
+        assert "--pair and --pairs-from take no paths" in capsys.readouterr().err
+
+
+class TestForensic:
+    """`komparu compare --forensic`: nothing written, access times kept."""
+
+    @pytest.mark.skipif(not sys.platform.startswith("linux"), reason="O_NOATIME")
+    def test_keeps_access_times(self, make_dir, capsys):
+        a = make_dir("a", {"f": b"1", "sub/g": b"1"})
+        b = make_dir("b", {"f": b"2", "sub/g": b"1"})
+        # Older than the mtime, so even relatime would update it on a read
+        paths = [p for root in (a, b) for p in (root, root / "f", root / "sub", root / "sub/g")]
+        for p in paths:
+            os.utime(p, ns=(1_000_000_000, p.stat().st_mtime_ns))
+        assert main(["compare", "--forensic", "-j", "1", str(a), str(b)]) == 1
+        assert capsys.readouterr().out.splitlines() == [
+            "differ: f (content_mismatch)",
+            "forensic: read-only, nothing written to either side; access times kept"]
+        assert [p.stat().st_atime_ns for p in paths] == [1_000_000_000] * len(paths)
+
+    def test_pairs(self, make_dir, make_file, capsys):
+        a, b = make_dir("a", {"f": b"1"}), make_dir("b", {"f": b"1"})
+        x, y = make_file("x", b"1"), make_file("y", b"1")
+        assert main(["compare", "--forensic", "--pair", str(a), str(b),
+                     "--pair", str(x), str(y)]) == 0
+        lines = capsys.readouterr().out.splitlines()
+        assert lines[-2] == "2 pairs: 2 equal, 0 different, 0 failed"
+        assert lines[-1].startswith("forensic: read-only")
+
+    def test_refuses_writers(self, make_dir, tmp_path, capsys):
+        a, b = make_dir("a", {"f": b"1"}), make_dir("b", {"f": b"2"})
+        for extra in (["--patch", str(a / "p")], ["--tui"], ["--json"], ["--savings"]):
+            assert main(["compare", "--forensic", *extra, str(a), str(b)]) == 2
+            assert "--forensic cannot be combined with" in capsys.readouterr().err
+        assert not (a / "p").exists()
+
+    def test_local_only(self, make_dir, make_file, capsys):
+        a = make_dir("a", {"f": b"1"})
+        assert main(["compare", "--forensic", str(a), "host:/srv"]) == 2
+        assert "local files and directories" in capsys.readouterr().err
+        assert main(["compare", "--forensic", str(a), str(make_file("a.tar", b""))]) == 2
+        assert "two files or two directories" in capsys.readouterr().err
+
+
+class TestMaxBytes:
+    """`komparu compare --max-bytes`: stop after reading that much, report what was done."""
+
+    def test_directories(self, make_dir, capsys):
+        files = {f"f{i}": bytes([i]) * 4096 for i in range(8)}
+        a = make_dir("a", {**files, "f0": b"x" * 4096})
+        b = make_dir("b", files)
+        assert main(["compare", "--max-bytes", "20000", "-j", "1", "--no-quick-check",
+                     str(a), str(b)]) == 4
+        captured = capsys.readouterr()
+        assert captured.out.splitlines() == ["differ: f0 (content_mismatch)"]
+        assert "quota reached" in captured.err and "report covers" in captured.err
+        assert main(["compare", "--max-bytes", "1000000", str(a), str(b)]) == 1
+
+    def test_files_and_pairs(self, make_file, capsys):
+        x, y = make_file("x", b"1" * 100_000), make_file("y", b"1" * 100_000)
+        assert main(["compare", "--max-bytes", "1000", str(x), str(y)]) == 4
+        assert "quota reached" in capsys.readouterr().err
+        assert main(["compare", "--max-bytes", "250000", "--pair", str(x), str(y),
+                     "--pair", str(x), str(y), "--pair", str(x), str(y)]) == 4
+        assert capsys.readouterr().out.splitlines()[-1] == (
+            "2 pairs: 1 equal, 0 different, 0 failed, quota reached with 2 left")
+
+    def test_refused(self, make_dir, capsys):
+        a, b = make_dir("a", {"f": b"1"}), make_dir("b", {"f": b"1"})
+        assert main(["compare", "--max-bytes", "100", "--savings", str(a), str(b)]) == 2
+        assert "--max-bytes cannot be combined with --savings" in capsys.readouterr().err
+        assert main(["compare", "--max-bytes", "0", str(a), str(b)]) == 2
+        assert "must be positive" in capsys.readouterr().err
+
+
+class TestRollup:
+    """`komparu compare --rollup` sums the differences up per directory."""
+
+    def test_directories(self, make_dir, capsys):
+        a = make_dir("a", {"same": b"s", "src/x.c": b"1", "src/lib/y.c": b"aa",
+                           "src/lib/z.c": b"z", "docs/old.md": b"12345", "ok/f": b"f"})
+        b = make_dir("b", {"same": b"s", "src/x.c": b"2", "src/lib/y.c": b"aaa",
+                           "src/lib/z.c": b"z", "docs/new.md": b"123", "ok/f": b"f"})
+        assert main(["compare", "--rollup", str(a), str(b)]) == 1
+        out = capsys.readouterr().out.splitlines()
+        assert out[out.index("by directory:"):] == [
+            "by directory:",
+            "  ./ 2 differ, 1 only in left, 1 only in right, 3 equal; 12 bytes not equal",
+            "    docs/ 1 only in left, 1 only in right; 8 bytes not equal",
+            "    src/ 2 differ, 1 equal; 4 bytes not equal",
+            "      lib/ 1 differ, 1 equal; 3 bytes not equal",
+        ]
+        assert main(["compare", "--rollup", "--rollup-depth", "0", str(a), str(b)]) == 1
+        assert capsys.readouterr().out.splitlines()[-2:] == [
+            "by directory:",
+            "  ./ 2 differ, 1 only in left, 1 only in right, 3 equal; 12 bytes not equal"]
+
+    def test_refused(self, make_dir, make_file, capsys):
+        a = make_dir("a", {"f": b"1"})
+        f = make_file("f", b"1")
+        assert main(["compare", "--rollup", str(f), str(f)]) == 2
+        assert "--rollup needs two local directories" in capsys.readouterr().err
+        assert main(["compare", "--rollup", "--names-only", str(a), str(a)]) == 2
+        assert "--rollup needs two local directories" in capsys.readouterr().err
+        assert main(["compare", "--rollup-depth", "1", str(a), str(a)]) == 2
+        assert "--rollup-depth needs --rollup" in capsys.readouterr().err
+
+
+@pytest.mark.skipif(not hasattr(signal, "SIGUSR1"), reason="no SIGUSR1")
+class TestStatusSignal:
+    """SIGUSR1 prints how far a running comparison got, and the run goes on."""
+
+    @staticmethod
+    def _signal_and_wait(capsys) -> str:
+        os.kill(os.getpid(), signal.SIGUSR1)
+        deadline = time.monotonic() + 5
+        err = ""
+        while "komparu:" not in err and time.monotonic() < deadline:
+            time.sleep(0.01)
+            err += capsys.readouterr().err
+        return err
+
+    def test_directories(self, make_dir, capsys, monkeypatch):
+        a = make_dir("a", {"small": b"1", "big.iso": b"2" * 3000, "left": b""})
+        b = make_dir("b", {"small": b"1", "big.iso": b"2" * 3000, "left": b""})
+        compare_dir, compare_dir_c = cli.compare_dir, komparu._api._compare_dir_c
+        lines, plain = [], []
+
+        def reporting(left, right, *, visitor, cancel, **kwargs):
+            result = compare_dir(left, right, visitor=visitor, cancel=cancel, **kwargs)
+            lines.append(self._signal_and_wait(capsys))
+            return result
+
+        def counting(*args, **kwargs):
+            plain.append(args)
+            return compare_dir_c(*args, **kwargs)
+
+        monkeypatch.setattr(cli, "compare_dir", reporting)
+        monkeypatch.setattr(komparu._api, "_compare_dir_c", counting)
+        assert main(["compare", str(a), str(b)]) == 0
+        assert len(plain) == 1  # no visitor: the engine's own path, not the event stream
+        assert re.fullmatch(r"komparu: 3 entries done, .* read \(.*/s\), "
+                            r"0:00:00 elapsed\n", lines[0])
+        assert signal.getsignal(signal.SIGUSR1) is signal.SIG_DFL
+        assert signal.SIGUSR1 not in signal.pthread_sigmask(signal.SIG_BLOCK, [])
+
+    def test_directories_streamed(self, make_dir, capsys, monkeypatch):
+        a = make_dir("a", {"small": b"1", "big.iso": b"2" * 3000})
+        b = make_dir("b", {"small": b"1", "big.iso": b"2" * 3000})
+        compare_dir = cli.compare_dir
+        lines = []
+
+        def reporting(left, right, *, visitor, cancel, **kwargs):
+            visitor.on_entry_started("big.iso")
+            lines.append(self._signal_and_wait(capsys))
+            return compare_dir(left, right, visitor=visitor, cancel=cancel, **kwargs)
+
+        monkeypatch.setattr(cli, "compare_dir", reporting)
+        assert main(["compare", "--rollup", str(a), str(b)]) == 0
+        assert re.fullmatch(r"komparu: 0 entries done, 0 bytes read \(0 bytes/s\), "
+                            r"0:00:00 elapsed; comparing big.iso\n", lines[0])
+
+    def test_file(self, make_file, capsys, monkeypatch):
+        x, y = make_file("x", b"1" * 100_000), make_file("y", b"1" * 100_000)
+        compare = cli.compare
+        lines = []
+
+        def reporting(left, right, **kwargs):
+            assert compare(left, right, **kwargs)
+            lines.append(self._signal_and_wait(capsys))
+            return True
+
+        monkeypatch.setattr(cli, "compare", reporting)
+        assert main(["compare", str(x), str(y)]) == 0
+        assert "KiB read" in lines[0] and lines[0].endswith(f"; comparing {x}\n")
+
+    def test_line(self):
+        status = cli._RunStatus(None, cli.CancelToken(), ["a", "b", "c", "d", "e"])
+        status.start -= 3725
+        assert status.line() == ("komparu: 0 entries done, 0 bytes read (0 bytes/s), "
+                                 "1:02:05 elapsed; comparing a, b, c and 2 more")
+        assert cli._describe_bytes(5 * 1024 ** 3) == "5.0 GiB"
+
+
+class TestPatternFiles:
+    """`--include-from` / `--exclude-from` with rsync filter files."""
+
+    def test_compare(self, make_dir, tmp_path, capsys):
+        a = make_dir("a", {"src/x.c": b"1", "src/x.o": b"1", "cache/blob": b"1",
+                           "keep.log": b"1", "drop.log": b"1"})
+        b = make_dir("b", {"src/x.c": b"2", "src/x.o": b"2"})
+        (tmp_path / "include").write_text("keep.log\n")
+        (tmp_path / "exclude").write_text("# build output\n*.o\n/cache/\n*.log\n")
+        assert main(["compare", "--include-from", str(tmp_path / "include"),
+                     "--exclude-from", str(tmp_path / "exclude"), str(a), str(b)]) == 1
+        assert capsys.readouterr().out.splitlines() == [
+            "differ: src/x.c (content_mismatch)", "only in left: keep.log"]
+
+    def test_sync(self, make_dir, tmp_path, capsys):
+        a = make_dir("a", {"f": b"1", "skip.tmp": b"1"})
+        b = make_dir("b", {"old.tmp": b"1"})
+        (tmp_path / "exclude").write_text("*.tmp\n")
+        assert main(["sync", "--apply", "--delete", "--exclude-from", str(tmp_path / "exclude"),
+                     str(a), str(b)]) == 0
+        assert sorted(os.listdir(b)) == ["f", "old.tmp"]
+
+    def test_not_for_archives(self, make_file, make_dir, tmp_path, capsys):
+        (tmp_path / "exclude").write_text("*.o\n")
+        a = make_dir("a", {"f": b"1"})
+        archive = make_file("a.tar", b"")
+        assert main(["compare", "--exclude-from", str(tmp_path / "exclude"), str(archive),
+                     str(a)]) == 2
+        assert "--exclude-from needs files or directories, not archives" in capsys.readouterr().err
+
+
+class TestDeltaSize:
+    """`komparu compare --delta-size` estimates how small a delta would be."""
+
+    DATA = random.Random(1).randbytes(200_000)
+
+    def test_files(self, make_file, capsys):
+        a = make_file("a.bin", self.DATA)
+        b = make_file("b.bin", self.DATA[:1000] + b"inserted" + self.DATA[1000:])
+        assert main(["compare", "--delta-size", str(a), str(b)]) == 1
+        out = capsys.readouterr().out
+        assert re.search(r"differ \(delta about \d\d of 200008 bytes\)$", out.strip())
+
+    def test_dirs(self, make_dir, capsys):
+        a = make_dir("a", {"x.bin": self.DATA})
+        b = make_dir("b", {"x.bin": self.DATA[:-10]})
+        assert main(["compare", "--delta-size", str(a), str(b)]) == 1
+        [line] = capsys.readouterr().out.splitlines()
+        assert re.fullmatch(r"differ: x\.bin \(size_mismatch, delta about \d+ of 199990 bytes\)",
+                            line)
+
+
+class TestFuzzy:
+    """`komparu compare --fuzzy` scores differing files and pairs lightly modified copies."""
+
+    TEXT = b"".join(b"line %d of the quarterly report, region %d\n" % (i, i % 7)
+                    for i in range(2000))
+
+    def test_files(self, make_file, capsys):
+        a = make_file("a.txt", self.TEXT)
+        b = make_file("b.txt", self.TEXT.replace(b"line 1000 ", b"line one thousand "))
+        assert main(["compare", "--fuzzy", str(a), str(b)]) == 1
+        assert "(fuzzy score " in capsys.readouterr().out
+
+    def test_dirs_pair_renamed_copies(self, make_dir, capsys):
+        a = make_dir("a", {"report.txt": self.TEXT, "gone.bin": bytes(range(256)) * 64})
+        b = make_dir("b", {"report-final.txt": self.TEXT.replace(b"line 1500 ", b"line MD ")})
+        assert main(["compare", "--fuzzy", str(a), str(b)]) == 1
+        out = capsys.readouterr().out
+        assert "similar: report.txt ~ report-final.txt (fuzzy score " in out
+        assert "similar: gone.bin" not in out
+
+    def test_threshold(self, make_dir, capsys):
+        a = make_dir("a", {"report.txt": self.TEXT})
+        b = make_dir("b", {"report-final.txt": self.TEXT.replace(b"line 1500 ", b"line MD ")})
+        assert main(["compare", "--fuzzy-threshold", "100", str(a), str(b)]) == 1
+        assert "similar:" not in capsys.readouterr().out
+        assert main(["compare", "--fuzzy-threshold", "101", str(a), str(b)]) == 2
+
+
+class TestSeverity:
+    """`--severity` rules decide the exit code."""
+
+    def test_info_only_is_success(self, make_dir, capsys):
+        a = make_dir("a", {"x.txt": b"1", "left.txt": b"l"})
+        b = make_dir("b", {"x.txt": b"1"})
+        assert main(["compare", "--severity", "only_left=info", str(a), str(b)]) == 0
+        assert "only in left: left.txt [info]" in capsys.readouterr().out
+
+    def test_warn(self, make_dir):
+        a = make_dir("a", {"x.txt": b"1", "left.txt": b"l"})
+        b = make_dir("b", {"x.txt": b"2"})
+        args = ["compare", "--severity", "only_left=info",
+                "--severity", "content_mismatch=warn", str(a), str(b)]
+        assert main(args) == 3
+
+    def test_highest_wins(self, make_dir, capsys):
+        a = make_dir("a", {"x.txt": b"1", "left.txt": b"l"})
+        b = make_dir("b", {"x.txt": b"2"})
+        assert main(["compare", "--severity", "only_left=warn", str(a), str(b)]) == 1
+        out = capsys.readouterr().out
+        assert "differ: x.txt (content_mismatch) [error]" in out
+
+    def test_default_severity(self, make_dir):
+        a = make_dir("a", {"x.txt": b"1"})
+        b = make_dir("b", {"x.txt": b"2"})
+        assert main(["compare", "--default-severity", "info", str(a), str(b)]) == 0
+
+    def test_files(self, make_file, capsys):
+        a = make_file("a.bin", b"aaaa")
+        b = make_file("b.bin", b"bbbb")
+        assert main(["compare", "--severity", "content_mismatch=warn", str(a), str(b)]) == 3
+        assert "[warn]" in capsys.readouterr().out
+
+    def test_bad_rule_is_error(self, make_dir, capsys):
+        a = make_dir("a", {"x.txt": b"1"})
+        b = make_dir("b", {"x.txt": b"1"})
+        assert main(["compare", "--severity", "mtime=info", str(a), str(b)]) == 2
+        assert "category" in capsys.readouterr().err
+
+
+@pytest.fixture
+def trees(make_dir, tmp_path, monkeypatch):
+    """Two differing trees, run from an empty directory with its own user configuration."""
+    monkeypatch.setenv("XDG_CONFIG_HOME", str(tmp_path / "xdg"))
+    (tmp_path / "work").mkdir()
+    monkeypatch.chdir(tmp_path / "work")
+    a = make_dir("a", {"x.txt": b"1", "build.log": b"a", "left.txt": b"l"})
+    b = make_dir("b", {"x.txt": b"2", "build.log": b"b"})
+    return str(a), str(b)
+
+
+class TestConfigFile:
+    """komparu.toml and ~/.config/komparu/config.toml supply defaults."""
+
+    def test_project_file(self, trees, tmp_path, capsys):
+        (tmp_path / "work" / "komparu.toml").write_text(
+            'ignore = ["*.log"]\n\n[compare]\nseverity = ["only_left=info"]\n'
+            'default-severity = "info"\nquiet = true\n')
+        assert main(["compare", *trees]) == 0
+        assert capsys.readouterr().out == ""
+        assert main(["compare", "--default-severity", "error", *trees]) == 1
+        assert main(["--no-config", "compare", "-q", *trees]) == 1
+
+    def test_pattern_tables(self, trees, tmp_path, capsys):
+        (tmp_path / "work" / "komparu.toml").write_text(
+            '[compare.compare_as]\n"x.txt" = "bytes"\n"*.txt" = "text"\n\n'
+            '[compare.severity]\nonly_left = "info"\n')
+        assert main(["compare", *trees]) == 1
+        out = capsys.readouterr().out
+        assert "differ: x.txt" in out and "only in left: left.txt [info]" in out
+        (tmp_path / "work" / "komparu.toml").write_text(
+            '[compare.compare_as]\n"*.txt" = "sniff"\n')
+        assert main(["compare", *trees]) == 2
+        assert "unknown strategy 'sniff'" in capsys.readouterr().err
+
+    def test_found_in_parent(self, trees, tmp_path, monkeypatch):
+        (tmp_path / "komparu.toml").write_text('[compare]\ndefault_severity = "info"\n')
+        assert main(["compare", *trees]) == 0
+        monkeypatch.chdir("/")
+        assert main(["compare", "-q", *trees]) == 1
+
+    def test_user_file_under_project_file(self, trees, tmp_path):
+        (tmp_path / "xdg" / "komparu").mkdir(parents=True)
+        (tmp_path / "xdg" / "komparu" / "config.toml").write_text(
+            'quiet = true\n[compare]\ndefault_severity = "info"\n')
+        assert main(["compare", *trees]) == 0
+        (tmp_path / "work" / "komparu.toml").write_text('[compare]\ndefault_severity = "warn"\n')
+        assert main(["compare", *trees]) == 3
+
+    def test_explicit_file(self, trees, tmp_path):
+        (tmp_path / "work" / "komparu.toml").write_text('[compare]\ndefault_severity = "warn"\n')
+        (tmp_path / "ci.toml").write_text('[compare]\ndefault_severity = "info"\nquiet = true\n')
+        assert main(["--config", str(tmp_path / "ci.toml"), "compare", *trees]) == 0
+
+    def test_errors(self, trees, tmp_path, capsys):
+        config = tmp_path / "work" / "komparu.toml"
+        for text, message in [
+            ("colour = true\n", "unknown option 'colour'"),
+            ("[verify]\nnames_only = true\n", "unknown option 'names_only' for komparu verify"),
+            ("[compare]\nquiet = 1\n", "expected true or false"),
+            ("[compare]\nchunk_size = \"big\"\n", "chunk_size"),
+            ("[compare]\ndefault_severity = \"loud\"\n", "default_severity"),
+            ("[compare]\nrules = \"rules.py\"\n", "only the user configuration"),
+            ("[sync]\napply = true\n", "only the user configuration"),
+            ("[compare\n", "komparu.toml"),
+        ]:
+            config.write_text(text)
+            assert main(["compare", *trees]) == 2
+            assert message in capsys.readouterr().err
+        (tmp_path / "rules.py").write_text("def include(path, stat_a, stat_b):\n    return True\n")
+        (tmp_path / "trusted.toml").write_text(f'[compare]\nrules = "{tmp_path / "rules.py"}"\n')
+        assert main(["--config", str(tmp_path / "trusted.toml"), "compare", "-q", *trees]) == 1
+
+    def test_environment(self, trees, tmp_path, monkeypatch, capsys):
+        (tmp_path / "work" / "komparu.toml").write_text('[compare]\ndefault_severity = "warn"\n')
+        monkeypatch.setenv("KOMPARU_DEFAULT_SEVERITY", "info")
+        monkeypatch.setenv("KOMPARU_QUIET", "yes")
+        monkeypatch.setenv("KOMPARU_JOBS", "2")
+        monkeypatch.setenv("KOMPARU_AGENT_TOKEN", "not an option")
+        assert main(["compare", *trees]) == 0
+        assert capsys.readouterr().out == ""
+        assert main(["compare", "--default-severity", "warn", *trees]) == 3
+        monkeypatch.setenv("KOMPARU_SEVERITY", "only_left=error, content_mismatch=warn")
+        assert main(["compare", *trees]) == 1
+        monkeypatch.setenv("KOMPARU_NO_CONFIG", "1")
+        assert main(["compare", *trees]) == 1
+        assert "only in left" in capsys.readouterr().out
+        monkeypatch.delenv("KOMPARU_NO_CONFIG")
+        monkeypatch.setenv("KOMPARU_JOBS", "many")
+        assert main(["compare", *trees]) == 2
+        assert "KOMPARU_JOBS" in capsys.readouterr().err
+
+    def test_config_variable(self, trees, tmp_path, monkeypatch):
+        (tmp_path / "ci.toml").write_text('[compare]\ndefault_severity = "info"\nquiet = true\n')
+        monkeypatch.setenv("KOMPARU_CONFIG", str(tmp_path / "ci.toml"))
+        assert main(["compare", *trees]) == 0
+
+    def test_profiles(self, trees, tmp_path, monkeypatch, capsys):
+        (tmp_path / "work" / "komparu.toml").write_text(
+            '[compare]\ndefault_severity = "warn"\n\n'
+            '[profile.quick]\nquiet = true\nignore = ["*.log"]\n\n'
+            '[profile.quick.compare]\nseverity = ["content_mismatch=info", "only_left=info"]\n')
+        (tmp_path / "xdg" / "komparu").mkdir(parents=True)
+        (tmp_path / "xdg" / "komparu" / "config.toml").write_text(
+            '[profile.lenient.compare]\ndefault_severity = "info"\n')
+        assert main(["compare", "-q", *trees]) == 3
+        assert main(["--profile", "quick", "compare", *trees]) == 0
+        assert capsys.readouterr().out == ""
+        assert main(["--profile", "quick", "compare", "--default-severity", "error", *trees]) == 0
+        # The project file's [compare] table still overrides the user's profile
+        assert main(["--profile", "lenient", "compare", "-q", *trees]) == 3
+        monkeypatch.setenv("KOMPARU_PROFILE", "quick")
+        assert main(["compare", *trees]) == 0
+        for argv, message in [
+            (["--profile", "nightly", "compare", *trees], "unknown profile 'nightly'"),
+            (["--no-config", "--profile", "quick", "compare", *trees], "--no-config"),
+        ]:
+            assert main(argv) == 2
+            assert message in capsys.readouterr().err
+        (tmp_path / "work" / "komparu.toml").write_text('[profile.quick]\ncolour = true\n')
+        assert main(["compare", *trees]) == 2
+        assert "[profile.quick]: unknown option 'colour'" in capsys.readouterr().err
+
+
+def _bash_complete(script: str, *words: str) -> list[str]:
+    """What the bash completion *script* offers for the last of *words*."""
+    command = (f"{script}\nCOMP_WORDS=({' '.join(shlex.quote(w) for w in words)})\n"
+               f"COMP_CWORD={len(words) - 1}\n_komparu\nprintf '%s\\n' \"${{COMPREPLY[@]}}\"")
+    out = subprocess.run(["bash", "-c", command], capture_output=True, text=True, check=True)
+    return out.stdout.split()
+
+
+class TestCompletions:
+    def test_every_shell(self, capsys):
+        for shell in ("bash", "zsh", "fish", "powershell"):
+            assert main(["completions", shell]) == 0
+            script = capsys.readouterr().out
+            for word in ("diff-snapshot", "copy-verify", "max-workers", "no-config"):
+                assert word in script, (shell, word)
+        assert main(["completions", "zsh"]) == 0
+        script = capsys.readouterr().out
+        assert script.startswith("#compdef komparu\n")
+        assert "'--default-severity[" in script and ":(info warn error)'" in script
+        assert main(["completions", "fish"]) == 0
+        assert "-l apply -x -a 'hardlink reflink'" in capsys.readouterr().out
+
+    def test_unknown_shell(self, capsys):
+        with pytest.raises(SystemExit):
+            main(["completions", "tcsh"])
+        assert "invalid choice" in capsys.readouterr().err
+
+    @pytest.mark.skipif(shutil.which("bash") is None, reason="needs bash")
+    def test_bash(self, tmp_path, capsys, monkeypatch):
+        assert main(["completions", "bash"]) == 0
+        script = capsys.readouterr().out
+        subprocess.run(["bash", "-n"], input=script, text=True, check=True)
+        assert "diff-manifests" in _bash_complete(script, "komparu", "diff")
+        assert _bash_complete(script, "komparu", "--config", "x.toml", "ver") == [
+            "verify", "verify-zero"]
+        assert "--no-follow-symlinks" in _bash_complete(script, "komparu", "hash", "--no-")
+        assert _bash_complete(script, "komparu", "compare", "--default-severity", "w") == ["warn"]
+        assert _bash_complete(script, "komparu", "completions", "f") == ["fish"]
+        (tmp_path / "tree").mkdir()
+        (tmp_path / "tree.sha256").write_text("")
+        monkeypatch.chdir(tmp_path)
+        assert _bash_complete(script, "komparu", "verify", "tr") == ["tree", "tree.sha256"]
+        assert _bash_complete(script, "komparu", "copy-verify", "--manifest", "tree.") \
+            == ["tree.sha256"]
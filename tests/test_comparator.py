@@ -0,0 +1,1446 @@
+"""Tests for pluggable ContentComparator in directory comparison."""
+
+from __future__ import annotations
+
+import bz2
+import codecs
+import gzip
+import importlib.util
+import io
+import lzma
+import sqlite3
+import struct
+import sys
+import tarfile
+import zipfile
+import zlib
+from pathlib import Path
+
+import pytest
+
+import komparu
+import komparu.aio
+from komparu import (
+    AudioComparator,
+    ContentComparator,
+    DebComparator,
+    DecompressComparator,
+    DiffEntry,
+    DiffReason,
+    EntryKind,
+    ExecutableComparator,
+    FilterComparator,
+    GzipComparator,
+    ImageComparator,
+    JsonComparator,
+    ParquetComparator,
+    PdfComparator,
+    SniffingComparator,
+    SourceReadError,
+    SqliteComparator,
+    TarComparator,
+    TextComparator,
+    UnsupportedSourceError,
+    XmlComparator,
+    YamlComparator,
+    ZipComparator,
+)
+
+
+class IgnoreFirstLine(ContentComparator):
+    """Equal when everything after the first line matches."""
+
+    def __init__(self) -> None:
+        self.calls: list[tuple[str, str]] = []
+
+    def compare(self, path_a: str, path_b: str) -> bool:
+        self.calls.append((path_a, path_b))
+        with open(path_a, "rb") as fa, open(path_b, "rb") as fb:
+            return fa.read().split(b"\n", 1)[1:] == fb.read().split(b"\n", 1)[1:]
+
+
+@pytest.fixture
+def tree_files() -> dict[str, bytes]:
+    return {
+        "a/same.ctr": b"stamp 1\nbody",
+        "b/same.ctr": b"stamp 1\nbody",
+        "a/stamped.ctr": b"stamp 1\nbody",
+        "b/stamped.ctr": b"stamp 22\nbody",
+        "a/real.ctr": b"stamp 1\nold",
+        "b/real.ctr": b"stamp 1\nnew",
+        "a/sub/other.txt": b"stamp 1\nbody",
+        "b/sub/other.txt": b"stamp 2\nbody",
+    }
+
+
+class TestCompareDir:
+    def test_comparator_overrides_diff(self, trees):
+        a, b = map(str, trees)
+        cmp = IgnoreFirstLine()
+        result = komparu.compare_dir(a, b, comparators={"*.ctr": cmp})
+        assert result.diff == {
+            "real.ctr": DiffReason.CONTENT_MISMATCH,
+            "sub/other.txt": DiffReason.CONTENT_MISMATCH,
+        }
+
+    def test_only_differing_files_consulted(self, trees):
+        a, b = map(str, trees)
+        cmp = IgnoreFirstLine()
+        komparu.compare_dir(a, b, comparators={"*.ctr": cmp})
+        names = sorted(Path(pa).name for pa, _ in cmp.calls)
+        assert names == ["real.ctr", "stamped.ctr"]
+
+    def test_pattern_matches_nested_path(self, trees):
+        a, b = map(str, trees)
+        result = komparu.compare_dir(
+            a, b, comparators={"sub/*": IgnoreFirstLine(), "*.ctr": IgnoreFirstLine()})
+        assert result.diff == {"real.ctr": DiffReason.CONTENT_MISMATCH}
+
+    def test_all_equal(self, make_files, tmp_path: Path):
+        make_files({"a/f.ctr": b"1\nx", "b/f.ctr": b"2\nx"})
+        result = komparu.compare_dir(str(tmp_path / "a"), str(tmp_path / "b"),
+                                     comparators={"*.ctr": IgnoreFirstLine()})
+        assert result.equal is True
+
+    def test_with_visitor(self, trees):
+        class Rec(komparu.CompareVisitor):
+            def __init__(self) -> None:
+                self.finished: dict[str, DiffEntry] = {}
+
+            def on_entry_finished(self, entry: DiffEntry) -> None:
+                self.finished[entry.path] = entry
+
+        a, b = map(str, trees)
+        rec = Rec()
+        result = komparu.compare_dir(a, b, visitor=rec, comparators={"*.ctr": IgnoreFirstLine()})
+        assert rec.finished["stamped.ctr"].kind is EntryKind.EQUAL
+        assert "stamped.ctr" not in result.diff
+
+    def test_rejects_non_comparator(self, trees):
+        a, b = map(str, trees)
+        with pytest.raises(TypeError, match="ContentComparator"):
+            komparu.compare_dir(a, b, comparators={"*.ctr": lambda x, y: True})
+
+    def test_exception_propagates(self, trees):
+        class Boom(ContentComparator):
+            def compare(self, path_a: str, path_b: str) -> bool:
+                raise RuntimeError("bad container")
+
+        a, b = map(str, trees)
+        with pytest.raises(RuntimeError, match="bad container"):
+            komparu.compare_dir(a, b, comparators={"*.ctr": Boom()})
+
+
+class TestIterDir:
+    def test_equal_entries_dropped(self, trees):
+        a, b = map(str, trees)
+        paths = {e.path for e in komparu.iter_dir(a, b, comparators={"*.ctr": IgnoreFirstLine()})}
+        assert paths == {"real.ctr", "sub/other.txt"}
+
+    def test_include_equal(self, trees):
+        a, b = map(str, trees)
+        entries = {e.path: e for e in komparu.iter_dir(
+            a, b, include_equal=True, comparators={"*.ctr": IgnoreFirstLine()})}
+        assert entries["stamped.ctr"] == DiffEntry("stamped.ctr", EntryKind.EQUAL)
+
+
+class TestTextComparator:
+    def test_crlf_equals_lf(self, make_file):
+        a = make_file("a.txt", b"one\r\ntwo\r\n")
+        b = make_file("b.txt", b"one\ntwo\n")
+        assert TextComparator().compare(str(a), str(b)) is True
+
+    def test_real_difference(self, make_file):
+        a = make_file("a.txt", b"one\r\ntwo\r\n")
+        b = make_file("b.txt", b"one\nTWO\n")
+        assert TextComparator().compare(str(a), str(b)) is False
+
+    def test_lone_cr_is_content(self, make_file):
+        a = make_file("a.txt", b"one\rtwo")
+        b = make_file("b.txt", b"one\ntwo")
+        assert TextComparator().compare(str(a), str(b)) is False
+
+    def test_trailing_content(self, make_file):
+        a = make_file("a.txt", b"line\r\n")
+        b = make_file("b.txt", b"line\nmore")
+        assert TextComparator().compare(str(a), str(b)) is False
+        assert TextComparator().compare(str(b), str(a)) is False
+
+    @pytest.mark.parametrize("chunk_size", [1, 2, 3, 7])
+    def test_crlf_split_across_reads(self, make_file, chunk_size):
+        a = make_file("a.txt", b"ab\r\ncd\r\n\r\nef\r")
+        b = make_file("b.txt", b"ab\ncd\n\nef\r")
+        assert TextComparator(chunk_size).compare(str(a), str(b)) is True
+
+    def test_trailing_space(self, make_file):
+        a = make_file("a.cfg", b"key = 1   \r\nother = 2\t\n")
+        b = make_file("b.cfg", b"key = 1\nother = 2\n")
+        assert TextComparator().compare(str(a), str(b)) is False
+        assert TextComparator(ignore_trailing_space=True).compare(str(a), str(b)) is True
+
+    def test_trailing_space_keeps_inner_space(self, make_file):
+        a = make_file("a.cfg", b"key  =  1\n")
+        b = make_file("b.cfg", b"key = 1\n")
+        assert TextComparator(ignore_trailing_space=True).compare(str(a), str(b)) is False
+
+    def test_collapse_whitespace(self, make_file):
+        a = make_file("a.cfg", b"key  =\t1 \r\n  nested =  2\n")
+        b = make_file("b.cfg", b"key = 1\n nested = 2\n")
+        assert TextComparator(collapse_whitespace=True).compare(str(a), str(b)) is True
+
+    def test_collapse_does_not_remove_space(self, make_file):
+        a = make_file("a.cfg", b"key=1\n")
+        b = make_file("b.cfg", b"key = 1\n")
+        assert TextComparator(collapse_whitespace=True).compare(str(a), str(b)) is False
+
+    def test_whitespace_keeps_line_structure(self, make_file):
+        a = make_file("a.cfg", b"one\ntwo\n")
+        b = make_file("b.cfg", b"one two\n")
+        c = make_file("c.cfg", b"one\ntwo")
+        assert TextComparator(collapse_whitespace=True).compare(str(a), str(b)) is False
+        assert TextComparator(ignore_trailing_space=True).compare(str(a), str(c)) is False
+
+    @pytest.mark.parametrize("encoding", ["utf-16", "utf-16-le", "utf-16-be", "utf-8-sig", "latin-1"])
+    def test_decode(self, make_file, encoding):
+        text = "name = caf\u00e9\r\nsize = 3\r\n"
+        a = make_file("a.txt", text.encode(encoding))
+        b = make_file("b.txt", text.replace("\r\n", "\n").encode("utf-8"))
+        assert TextComparator().compare(str(a), str(b)) is False
+        assert TextComparator(decode=True).compare(str(a), str(b)) is True
+
+    def test_decode_both_utf16(self, make_file):
+        a = make_file("a.txt", "same text".encode("utf-16-le"))
+        b = make_file("b.txt", "same text".encode("utf-16"))
+        assert TextComparator(decode=True).compare(str(a), str(b)) is True
+
+    def test_decode_real_difference(self, make_file):
+        a = make_file("a.txt", "caf\u00e9".encode("utf-16"))
+        b = make_file("b.txt", "cafe".encode("utf-8"))
+        assert TextComparator(decode=True).compare(str(a), str(b)) is False
+
+    def test_decode_invalid_is_different(self, make_file):
+        a = make_file("a.txt", codecs.BOM_UTF16_LE + b"a\x00b")
+        b = make_file("b.txt", b"ab")
+        assert TextComparator(decode=True).compare(str(a), str(b)) is False
+
+    def test_decode_with_whitespace(self, make_file):
+        a = make_file("a.txt", "key  =  1  \r\n".encode("utf-16"))
+        b = make_file("b.txt", b"key = 1\n")
+        assert TextComparator(decode=True, collapse_whitespace=True).compare(str(a), str(b)) is True
+
+    def test_ignore_bom(self, make_file):
+        a = make_file("a.txt", codecs.BOM_UTF8 + b"line\r\n")
+        b = make_file("b.txt", b"line\n")
+        assert TextComparator().compare(str(a), str(b)) is False
+        assert TextComparator(ignore_bom=True).compare(str(a), str(b)) is True
+
+    def test_ignore_bom_utf16(self, make_file):
+        a = make_file("a.txt", codecs.BOM_UTF16_LE + "x".encode("utf-16-le"))
+        b = make_file("b.txt", "x".encode("utf-16-le"))
+        assert TextComparator(ignore_bom=True).compare(str(a), str(b)) is True
+
+    def test_ignore_bom_only_leading(self, make_file):
+        a = make_file("a.txt", b"line" + codecs.BOM_UTF8)
+        b = make_file("b.txt", b"line")
+        c = make_file("c.txt", b"\xef\xbbline")
+        assert TextComparator(ignore_bom=True).compare(str(a), str(b)) is False
+        assert TextComparator(ignore_bom=True).compare(str(c), str(b)) is False
+
+    def test_ignore_final_newline(self, make_file):
+        a = make_file("a.txt", b"x\r\ny\r\n")
+        b = make_file("b.txt", b"x\ny")
+        assert TextComparator().compare(str(a), str(b)) is False
+        for comparator in (TextComparator(2, ignore_final_newline=True),
+                           TextComparator(ignore_final_newline=True, ignore_trailing_space=True)):
+            assert comparator.compare(str(a), str(b)) is True
+            assert comparator.compare(str(b), str(a)) is True
+
+    def test_ignore_final_newline_only_one(self, make_file):
+        a = make_file("a.txt", b"x\n\n")
+        b = make_file("b.txt", b"x")
+        c = make_file("c.txt", b"x\ny\n")
+        for comparator in (TextComparator(ignore_final_newline=True),
+                           TextComparator(ignore_final_newline=True, ignore_trailing_space=True)):
+            assert comparator.compare(str(a), str(b)) is False
+            assert comparator.compare(str(c), str(b)) is False
+
+    def test_invalid_chunk_size(self):
+        with pytest.raises(ValueError, match="chunk_size"):
+            TextComparator(0)
+
+    def test_in_compare_dir(self, make_files, tmp_path: Path):
+        make_files({
+            "a/win.txt": b"x\r\ny\r\n",
+            "b/win.txt": b"x\ny\n",
+            "a/real.txt": b"x\r\n",
+            "b/real.txt": b"y\n",
+        })
+        result = komparu.compare_dir(str(tmp_path / "a"), str(tmp_path / "b"),
+                                     comparators={"*.txt": TextComparator()})
+        assert result.diff == {"real.txt": DiffReason.SIZE_MISMATCH}
+
+
+class TestJsonComparator:
+    def test_key_order_and_whitespace(self, make_file):
+        a = make_file("a.json", b'{"b": [1, 2], "a": {"x": null, "y": true}}')
+        b = make_file("b.json", b'{\n  "a": {"y": true, "x": null},\n  "b": [1,2]\n}\n')
+        assert JsonComparator().compare(str(a), str(b)) is True
+
+    def test_number_formatting(self, make_file):
+        a = make_file("a.json", b'[1, 2.50, 1e3, -0.0]')
+        b = make_file("b.json", b'[1.0, 2.5, 1000, 0]')
+        assert JsonComparator().compare(str(a), str(b)) is True
+
+    def test_numbers_exact(self, make_file):
+        a = make_file("a.json", b'[12345678901234567890]')
+        b = make_file("b.json", b'[12345678901234567891]')
+        assert JsonComparator().compare(str(a), str(b)) is False
+
+    def test_bool_is_not_number(self, make_file):
+        a = make_file("a.json", b'{"flag": true}')
+        b = make_file("b.json", b'{"flag": 1}')
+        assert JsonComparator().first_mismatch(str(a), str(b)) == "/flag"
+
+    def test_array_order_matters(self, make_file):
+        a = make_file("a.json", b'[1, 2]')
+        b = make_file("b.json", b'[2, 1]')
+        assert JsonComparator().first_mismatch(str(a), str(b)) == "/0"
+
+    @pytest.mark.parametrize(("doc_a", "doc_b", "pointer"), [
+        (b'{"a": {"b": [1, {"c": 1}]}}', b'{"a": {"b": [1, {"c": 2}]}}', "/a/b/1/c"),
+        (b'{"a": 1}', b'{"a": 1, "b": 2}', "/b"),
+        (b'[1, 2]', b'[1, 2, 3]', "/2"),
+        (b'{"a/b": {"m~n": 1}}', b'{"a/b": {"m~n": 2}}', "/a~1b/m~0n"),
+        (b'1', b'"1"', ""),
+    ])
+    def test_first_mismatch(self, make_file, doc_a, doc_b, pointer):
+        a = make_file("a.json", doc_a)
+        b = make_file("b.json", doc_b)
+        assert JsonComparator().first_mismatch(str(a), str(b)) == pointer
+
+    def test_first_mismatch_equal(self, make_file):
+        a = make_file("a.json", b'{"a": 1}')
+        b = make_file("b.json", b'{ "a" : 1.0 }')
+        assert JsonComparator().first_mismatch(str(a), str(b)) is None
+
+    def test_on_mismatch(self, make_file):
+        a = make_file("a.json", b'{"version": 1, "name": "x"}')
+        b = make_file("b.json", b'{"version": 2, "name": "x"}')
+        reports = []
+        comparator = JsonComparator(on_mismatch=lambda *args: reports.append(args))
+        assert comparator.compare(str(a), str(b)) is False
+        assert reports == [(str(a), str(b), "/version")]
+
+    def test_invalid_json_is_different(self, make_file):
+        a = make_file("a.json", b'{"a": 1')
+        b = make_file("b.json", b'{"a": 1}')
+        assert JsonComparator().compare(str(a), str(b)) is False
+        with pytest.raises(ValueError):
+            JsonComparator().first_mismatch(str(a), str(b))
+
+    def test_utf16_document(self, make_file):
+        a = make_file("a.json", '{"name": "caf\u00e9"}'.encode("utf-16"))
+        b = make_file("b.json", '{"name":"caf\u00e9"}'.encode("utf-8"))
+        assert JsonComparator().compare(str(a), str(b)) is True
+
+    def test_in_compare_dir(self, make_files, tmp_path: Path):
+        make_files({
+            "a/conf.json": b'{"b": 1, "a": 2}',
+            "b/conf.json": b'{"a": 2, "b": 1}',
+            "a/data.json": b'{"a": 1}',
+            "b/data.json": b'{"a": 2}',
+        })
+        result = komparu.compare_dir(str(tmp_path / "a"), str(tmp_path / "b"),
+                                     comparators={"*.json": JsonComparator()})
+        assert result.diff == {"data.json": DiffReason.CONTENT_MISMATCH}
+
+
+needs_yaml = pytest.mark.skipif(importlib.util.find_spec("yaml") is None, reason="needs PyYAML")
+
+DEPLOYMENT = b"""\
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: web
+  labels: &labels {app: web, tier: front}
+spec:
+  replicas: 3
+  selector:
+    matchLabels: *labels
+"""
+
+# Same manifest as another tool renders it: keys sorted, aliases
+# expanded, flow style, quoted strings
+DEPLOYMENT_RENDERED = b"""\
+# rendered
+apiVersion: "apps/v1"
+kind: Deployment
+metadata: {labels: {tier: front, app: web}, name: web}
+spec:
+  selector: {matchLabels: {app: web, tier: front}}
+  replicas: 3
+"""
+
+
+@needs_yaml
+class TestYamlComparator:
+    def test_semantically_equal(self, make_file):
+        a = make_file("a.yaml", DEPLOYMENT)
+        b = make_file("b.yaml", DEPLOYMENT_RENDERED)
+        assert YamlComparator().compare(str(a), str(b)) is True
+
+    def test_multi_document(self, make_file):
+        a = make_file("a.yaml", b"---\nkind: Service\n---\nkind: Deployment\nspec: {replicas: 2}\n")
+        b = make_file("b.yaml", b"kind: Service\n---\nkind: Deployment\nspec: {replicas: 3}\n")
+        assert YamlComparator().first_mismatch(str(a), str(b)) == "/1/spec/replicas"
+
+    def test_document_order_matters(self, make_file):
+        a = make_file("a.yaml", b"kind: A\n---\nkind: B\n")
+        b = make_file("b.yaml", b"kind: B\n---\nkind: A\n")
+        assert YamlComparator().compare(str(a), str(b)) is False
+
+    def test_merge_keys(self, make_file):
+        a = make_file("a.yaml", b"base: &b {x: 1, y: 2}\nitem:\n  <<: *b\n  y: 3\n")
+        b = make_file("b.yaml", b"base: {x: 1, y: 2}\nitem: {x: 1, y: 3}\n")
+        assert YamlComparator().compare(str(a), str(b)) is True
+
+    def test_types_matter(self, make_file):
+        a = make_file("a.yaml", b"port: 80\nenabled: yes\n")
+        b = make_file("b.yaml", b"port: '80'\nenabled: true\n")
+        assert YamlComparator().first_mismatch(str(a), str(b)) == "/0/port"
+
+    def test_numbers_by_value(self, make_file):
+        a = make_file("a.yaml", b"ratio: 1\n")
+        b = make_file("b.yaml", b"ratio: 1.0\n")
+        assert YamlComparator().compare(str(a), str(b)) is True
+
+    def test_non_string_keys(self, make_file):
+        a = make_file("a.yaml", b"1: one\ntwo: 2\n")
+        b = make_file("b.yaml", b"two: 2\n1: uno\n")
+        assert YamlComparator().first_mismatch(str(a), str(b)) == "/0/1"
+
+    def test_on_mismatch(self, make_file):
+        a = make_file("a.yaml", b"spec: {replicas: 1}\n")
+        b = make_file("b.yaml", b"spec: {replicas: 2}\n")
+        reports = []
+        assert YamlComparator(on_mismatch=lambda *args: reports.append(args)).compare(
+            str(a), str(b)) is False
+        assert reports == [(str(a), str(b), "/0/spec/replicas")]
+
+    def test_invalid_yaml_is_different(self, make_file):
+        a = make_file("a.yaml", b"key: [unclosed\n")
+        b = make_file("b.yaml", b"key: []\n")
+        assert YamlComparator().compare(str(a), str(b)) is False
+
+    def test_in_compare_dir(self, make_files, tmp_path: Path):
+        make_files({
+            "a/deploy.yaml": DEPLOYMENT,
+            "b/deploy.yaml": DEPLOYMENT_RENDERED,
+            "a/svc.yml": b"port: 80\n",
+            "b/svc.yml": b"port: 81\n",
+        })
+        yaml_comparator = YamlComparator()
+        result = komparu.compare_dir(str(tmp_path / "a"), str(tmp_path / "b"),
+                                     comparators={"*.yaml": yaml_comparator,
+                                                  "*.yml": yaml_comparator})
+        assert result.diff == {"svc.yml": DiffReason.CONTENT_MISMATCH}
+
+
+class TestYamlComparatorWithoutPyYaml:
+    def test_import_error(self, monkeypatch):
+        monkeypatch.setitem(sys.modules, "yaml", None)
+        with pytest.raises(ImportError, match="komparu\\[yaml\\]"):
+            YamlComparator()
+
+
+def gz(data: bytes, *, level: int = 9, mtime: int = 0, name: str = "") -> bytes:
+    buf = io.BytesIO()
+    with gzip.GzipFile(name, "wb", compresslevel=level, fileobj=buf, mtime=mtime) as f:
+        f.write(data)
+    return buf.getvalue()
+
+
+class TestGzipComparator:
+    def test_same_data_different_bytes(self, make_file):
+        data = b"row,value\n" * 5000
+        a = make_file("a.gz", gz(data, level=1, mtime=1_600_000_000, name="a.csv"))
+        b = make_file("b.gz", gz(data, level=9, mtime=1_700_000_000, name="b.csv"))
+        assert a.read_bytes() != b.read_bytes()
+        assert GzipComparator().compare(str(a), str(b)) is True
+
+    def test_different_data(self, make_file):
+        a = make_file("a.gz", gz(b"one"))
+        b = make_file("b.gz", gz(b"two"))
+        c = make_file("c.gz", gz(b"one more"))
+        assert GzipComparator().compare(str(a), str(b)) is False
+        assert GzipComparator().compare(str(a), str(c)) is False
+
+    def test_multi_member(self, make_file):
+        a = make_file("a.gz", gz(b"first ") + gz(b"second"))
+        b = make_file("b.gz", gz(b"first second"))
+        assert GzipComparator(chunk_size=3).compare(str(a), str(b)) is True
+
+    def test_invalid_is_different(self, make_file):
+        a = make_file("a.gz", b"not gzip at all")
+        b = make_file("b.gz", gz(b"data"))
+        t = make_file("t.gz", gz(b"data")[:-6])
+        assert GzipComparator().compare(str(a), str(b)) is False
+        assert GzipComparator().compare(str(t), str(b)) is False
+
+    def test_invalid_chunk_size(self):
+        with pytest.raises(ValueError, match="chunk_size"):
+            GzipComparator(0)
+
+    def test_in_compare_dir(self, make_files, tmp_path: Path):
+        make_files({
+            "a/log.gz": gz(b"log", mtime=1),
+            "b/log.gz": gz(b"log", mtime=2),
+            "a/dump.gz": gz(b"v1"),
+            "b/dump.gz": gz(b"v2"),
+        })
+        result = komparu.compare_dir(str(tmp_path / "a"), str(tmp_path / "b"),
+                                     comparators={"*.gz": GzipComparator()})
+        assert result.diff == {"dump.gz": DiffReason.CONTENT_MISMATCH}
+
+
+needs_zstd = pytest.mark.skipif("zstd" not in DecompressComparator.available_formats(),
+                                reason="zstd needs Python 3.14 or zstandard")
+
+
+def zst(data: bytes) -> bytes:
+    try:
+        from compression import zstd
+        return zstd.compress(data)
+    except ImportError:
+        import zstandard
+        return zstandard.ZstdCompressor().compress(data)
+
+
+class TestDecompressComparator:
+    def test_formats_by_magic(self, make_file):
+        data = b"GET /index.html 200\n" * 2000
+        raw = make_file("raw.log", data)
+        for name, blob in (("a.gz", gz(data, mtime=5)), ("a.bz2", bz2.compress(data, 1)),
+                           ("a.xz", lzma.compress(data, preset=0))):
+            path = make_file(name, blob)
+            assert DecompressComparator().compare(str(path), str(raw)) is True, name
+
+    def test_across_compressors(self, make_file):
+        a = make_file("a.log.gz", gz(b"line\n" * 100))
+        b = make_file("b.log.gz", lzma.compress(b"line\n" * 100))
+        c = make_file("c.log.gz", bz2.compress(b"line\n" * 99))
+        assert DecompressComparator(chunk_size=7).compare(str(a), str(b)) is True
+        assert DecompressComparator().compare(str(a), str(c)) is False
+
+    @needs_zstd
+    def test_zstd(self, make_file):
+        a = make_file("a.zst", zst(b"zstd data" * 100))
+        b = make_file("b.gz", gz(b"zstd data" * 100))
+        assert DecompressComparator().compare(str(a), str(b)) is True
+
+    def test_corrupt_is_different(self, make_file):
+        good = make_file("good.xz", lzma.compress(b"data" * 50))
+        for name, blob in (("t.xz", lzma.compress(b"data" * 50)[:-8]),
+                           ("t.bz2", bz2.compress(b"data" * 50)[:-8]),
+                           ("bad.bz2", b"BZh9" + b"x" * 40),
+                           ("t.gz", gz(b"data" * 50)[:-6])):
+            path = make_file(name, blob)
+            assert DecompressComparator().compare(str(path), str(good)) is False, name
+
+    def test_unavailable_format(self, make_file):
+        a = make_file("a.bz2", bz2.compress(b"data"))
+        comparator = DecompressComparator()
+        del comparator._decompressors["bzip2"]
+        with pytest.raises(UnsupportedSourceError, match="bzip2"):
+            comparator.compare(str(a), str(a))
+
+    def test_available_formats(self):
+        assert {"gzip", "bzip2", "xz"} <= DecompressComparator.available_formats()
+
+    def test_invalid_chunk_size(self):
+        with pytest.raises(ValueError, match="chunk_size"):
+            DecompressComparator(0)
+
+
+def tar(members: list[tuple[str, bytes]], *, mode: str = "w", mtime: int = 0,
+        fmt: int = tarfile.PAX_FORMAT, uid: int = 0) -> bytes:
+    buf = io.BytesIO()
+    with tarfile.open(fileobj=buf, mode=mode, format=fmt) as t:
+        for name, data in members:
+            info = tarfile.TarInfo(name)
+            info.size, info.mtime, info.uid = len(data), mtime, uid
+            t.addfile(info, io.BytesIO(data))
+    return buf.getvalue()
+
+
+class TestTarComparator:
+    def test_repackaged(self, make_file):
+        members = [("pkg/a.txt", b"a" * 5000), ("pkg/b.bin", b"\0b")]
+        a = make_file("a.tar", tar(members, fmt=tarfile.USTAR_FORMAT, uid=1000))
+        b = make_file("b.tar.gz", tar([("./pkg/a.txt", b"a" * 5000), ("pkg/b.bin", b"\0b")],
+                                      mode="w:gz"))
+        assert a.read_bytes() != b.read_bytes()
+        assert TarComparator(chunk_size=7).compare(str(a), str(b)) is True
+
+    def test_different_content(self, make_file):
+        a = make_file("a.tar", tar([("x", b"one")]))
+        b = make_file("b.tar", tar([("x", b"two")]))
+        c = make_file("c.tar", tar([("x", b"one"), ("y", b"")]))
+        d = make_file("d.tar", tar([("z", b"one")]))
+        for other in (b, c, d):
+            assert TarComparator().compare(str(a), str(other)) is False, other.name
+
+    def test_mtime(self, make_file):
+        a = make_file("a.tar", tar([("x", b"1")], mtime=1))
+        b = make_file("b.tar", tar([("x", b"1")], mtime=2))
+        assert TarComparator().compare(str(a), str(b)) is False
+        assert TarComparator(ignore_mtime=True).compare(str(a), str(b)) is True
+
+    def test_order(self, make_file):
+        a = make_file("a.tar", tar([("x", b"1"), ("y", b"2")]))
+        b = make_file("b.tar.xz", tar([("y", b"2"), ("x", b"1")], mode="w:xz"))
+        assert TarComparator().compare(str(a), str(b)) is False
+        assert TarComparator(ignore_order=True).compare(str(a), str(b)) is True
+
+    def test_link_target_and_mode(self, make_file):
+        def linked(target: str, mode: int = 0o644) -> bytes:
+            buf = io.BytesIO()
+            with tarfile.open(fileobj=buf, mode="w") as t:
+                info = tarfile.TarInfo("f")
+                info.mode = mode
+                t.addfile(info, io.BytesIO(b""))
+                link = tarfile.TarInfo("l")
+                link.type, link.linkname = tarfile.SYMTYPE, target
+                t.addfile(link)
+            return buf.getvalue()
+
+        a = make_file("a.tar", linked("f"))
+        assert TarComparator().compare(str(a), str(make_file("b.tar", linked("f")))) is True
+        assert TarComparator().compare(str(a), str(make_file("c.tar", linked("g")))) is False
+        assert TarComparator().compare(str(a), str(make_file("d.tar", linked("f", 0o755)))) is False
+
+    def test_invalid_is_different(self, make_file):
+        good = tar([("x", b"data" * 1000)], mode="w:gz")
+        a = make_file("a.tar.gz", good)
+        for name, blob in (("junk.tar", b"not a tarball"), ("cut.tar.gz", good[:-20])):
+            path = make_file(name, blob)
+            assert TarComparator().compare(str(a), str(path)) is False, name
+
+    def test_invalid_chunk_size(self):
+        with pytest.raises(ValueError, match="chunk_size"):
+            TarComparator(0)
+
+    def test_in_compare_dir(self, make_files, tmp_path: Path):
+        make_files({
+            "a/src.tgz": tar([("x", b"1")], mode="w:gz", mtime=1),
+            "b/src.tgz": tar([("x", b"1")], mtime=1),
+            "a/old.tar": tar([("x", b"1")]),
+            "b/old.tar": tar([("x", b"2")]),
+        })
+        result = komparu.compare_dir(str(tmp_path / "a"), str(tmp_path / "b"),
+                                     comparators={"*.t*": TarComparator()})
+        assert result.diff == {"old.tar": DiffReason.CONTENT_MISMATCH}
+
+
+def zipped(entries: list[tuple[str, bytes]], *, level: int = 6,
+           date_time: tuple[int, ...] = (2020, 1, 1, 0, 0, 0), attr: int = 0o644 << 16,
+           extra: bytes = b"", comment: bytes = b"") -> bytes:
+    buf = io.BytesIO()
+    with zipfile.ZipFile(buf, "w", zipfile.ZIP_DEFLATED, compresslevel=level) as z:
+        for name, data in entries:
+            info = zipfile.ZipInfo(name, date_time)
+            info.compress_type, info.external_attr, info.extra = zipfile.ZIP_DEFLATED, attr, extra
+            z.writestr(info, data)
+        z.comment = comment
+    return buf.getvalue()
+
+
+def deb(files: list[tuple[str, bytes]], *, mtime: int = 0,
+        order: tuple[str, ...] = ("debian-binary", "control.tar.gz", "data.tar.xz")) -> bytes:
+    members = {
+        "debian-binary": b"2.0\n",
+        "control.tar.gz": tar([("./control", b"Package: demo\n")], mode="w:gz", mtime=mtime),
+        "data.tar.xz": tar(files, mode="w:xz", mtime=mtime),
+    }
+    out = bytearray(b"!<arch>\n")
+    for name in order:
+        blob = members[name]
+        out += b"%-16s%-12d%-6d%-6d%-8o%-10d`\n" % (name.encode(), mtime, 0, 0, 0o100644, len(blob))
+        out += blob + b"\n" * (len(blob) % 2)
+    return bytes(out)
+
+
+class TestDebComparator:
+    FILES = [("./usr/bin/demo", b"\x7fELF" * 100), ("./usr/share/doc/demo", b"docs")]
+
+    def test_rebuilt(self, make_file):
+        a = make_file("a.deb", deb(self.FILES, mtime=1_700_000_000))
+        b = make_file("b.deb", deb(self.FILES, mtime=1_700_000_999))
+        assert DebComparator().compare(str(a), str(b)) is False
+        assert DebComparator(chunk_size=7, ignore_mtime=True).compare(str(a), str(b)) is True
+
+    def test_order(self, make_file):
+        a = make_file("a.deb", deb(self.FILES))
+        b = make_file("b.deb", deb(self.FILES[::-1], order=("debian-binary", "data.tar.xz",
+                                                            "control.tar.gz")))
+        assert DebComparator().compare(str(a), str(b)) is False
+        assert DebComparator(ignore_order=True).compare(str(a), str(b)) is True
+
+    def test_different_content(self, make_file):
+        a = make_file("a.deb", deb(self.FILES))
+        b = make_file("b.deb", deb([("./usr/bin/demo", b"patched")]))
+        assert DebComparator(ignore_mtime=True, ignore_order=True).compare(str(a), str(b)) is False
+
+    def test_invalid_is_different(self, make_file):
+        good = deb(self.FILES)
+        a = make_file("a.deb", good)
+        for name, blob in (("junk.deb", b"not a package"), ("cut.deb", good[:-40])):
+            path = make_file(name, blob)
+            assert DebComparator().compare(str(a), str(path)) is False, name
+
+    @pytest.mark.skipif("zst" in tarfile.TarFile.OPEN_METH, reason="tarfile reads zstd")
+    def test_zstd_member_unsupported(self, make_file):
+        a = make_file("a.deb", b"!<arch>\n%-16s%-12d%-6d%-6d%-8o%-10d`\n\x28\xb5\x2f\xfd"
+                      % (b"data.tar.zst", 0, 0, 0, 0o100644, 4))
+        with pytest.raises(UnsupportedSourceError, match="zstd"):
+            DebComparator().compare(str(a), str(a))
+
+    def test_invalid_chunk_size(self):
+        with pytest.raises(ValueError, match="chunk_size"):
+            DebComparator(0)
+
+
+class TestZipComparator:
+    def test_rebuilt(self, make_file):
+        data = b"class Main {}\n" * 500
+        a = make_file("a.zip", zipped([("app/Main.class", data), ("META-INF/", b"")], level=1))
+        b = make_file("b.zip", zipped([("META-INF/", b""), ("app/Main.class", data)], level=9))
+        assert a.read_bytes() != b.read_bytes()
+        assert ZipComparator(chunk_size=5).compare(str(a), str(b)) is True
+
+    def test_different_content(self, make_file):
+        a = make_file("a.zip", zipped([("x", b"one")]))
+        b = make_file("b.zip", zipped([("x", b"two")]))
+        c = make_file("c.zip", zipped([("x", b"one"), ("y", b"")]))
+        d = make_file("d.zip", zipped([("z", b"one")]))
+        for other in (b, c, d):
+            assert ZipComparator().compare(str(a), str(other)) is False, other.name
+
+    def test_timestamps(self, make_file):
+        a = make_file("a.zip", zipped([("x", b"1")], date_time=(2020, 1, 1, 0, 0, 0)))
+        b = make_file("b.zip", zipped([("x", b"1")], date_time=(2024, 6, 1, 12, 0, 0)))
+        assert ZipComparator().compare(str(a), str(b)) is False
+        assert ZipComparator(ignore_timestamps=True).compare(str(a), str(b)) is True
+
+    def test_metadata(self, make_file):
+        # Extended timestamp extra field (0x5455), as Info-ZIP writes it
+        ut = b"UT\x05\x00\x01\x00\x00\x00\x00"
+        a = make_file("a.zip", zipped([("x", b"1")]))
+        for name, blob in (("mode.zip", zipped([("x", b"1")], attr=0o755 << 16)),
+                           ("extra.zip", zipped([("x", b"1")], extra=ut)),
+                           ("comment.zip", zipped([("x", b"1")], comment=b"build 42"))):
+            path = make_file(name, blob)
+            assert ZipComparator().compare(str(a), str(path)) is False, name
+            assert ZipComparator(ignore_metadata=True).compare(str(a), str(path)) is True, name
+
+    def test_timestamp_extra_fields(self, make_file):
+        def ut(mtime: int) -> bytes:
+            return b"UT\x05\x00\x01" + mtime.to_bytes(4, "little")
+        a = make_file("a.zip", zipped([("x", b"1")], extra=ut(1) + b"\x99\x99\x01\x00\x07"))
+        b = make_file("b.zip", zipped([("x", b"1")], extra=ut(2) + b"\x99\x99\x01\x00\x07",
+                                      date_time=(2024, 6, 1, 12, 0, 0)))
+        c = make_file("c.zip", zipped([("x", b"1")], extra=ut(2) + b"\x99\x99\x01\x00\x08"))
+        assert ZipComparator(ignore_timestamps=True).compare(str(a), str(b)) is True
+        assert ZipComparator(ignore_timestamps=True).compare(str(a), str(c)) is False
+
+    def test_invalid_is_different(self, make_file):
+        good = zipped([("x", b"data" * 1000)])
+        a = make_file("a.zip", good)
+        corrupt = bytearray(good)
+        corrupt[40] ^= 0xFF  # inside the deflate stream
+        for name, blob in (("junk.zip", b"not a zip"), ("cut.zip", good[:-30]),
+                           ("bad.zip", bytes(corrupt))):
+            path = make_file(name, blob)
+            assert ZipComparator().compare(str(a), str(path)) is False, name
+
+    def test_invalid_chunk_size(self):
+        with pytest.raises(ValueError, match="chunk_size"):
+            ZipComparator(0)
+
+    def test_in_compare_dir(self, make_files, tmp_path: Path):
+        make_files({
+            "a/app.jar": zipped([("x", b"1")], level=1),
+            "b/app.jar": zipped([("x", b"1")], level=9),
+            "a/old.jar": zipped([("x", b"1")]),
+            "b/old.jar": zipped([("x", b"2")]),
+        })
+        result = komparu.compare_dir(str(tmp_path / "a"), str(tmp_path / "b"),
+                                     comparators={"*.jar": ZipComparator()})
+        assert result.diff == {"old.jar": DiffReason.CONTENT_MISMATCH}
+
+
+def png_chunk(kind: bytes, data: bytes) -> bytes:
+    return struct.pack(">I", len(data)) + kind + data + struct.pack(">I", zlib.crc32(kind + data))
+
+
+# Two rows of a 4x2 grayscale image, each behind its filter byte
+PIXELS = b"\x00\x01\x02\x03\x04\x00\x05\x06\x07\x08"
+
+
+def png(rows: bytes = PIXELS, *, chunks: tuple[tuple[bytes, bytes], ...] = (),
+        level: int = 6, split: int = 0) -> bytes:
+    idat = zlib.compress(rows, level)
+    parts = [idat[i:i + split] for i in range(0, len(idat), split)] if split else [idat]
+    return (b"\x89PNG\r\n\x1a\n" + png_chunk(b"IHDR", struct.pack(">IIBBBBB", 4, 2, 8, 0, 0, 0, 0))
+            + b"".join(png_chunk(kind, data) for kind, data in chunks)
+            + b"".join(png_chunk(b"IDAT", part) for part in parts) + png_chunk(b"IEND", b""))
+
+
+def jpeg(scan: bytes = b"\x12\x34\xff\x00\x56", *,
+         segments: tuple[tuple[int, bytes], ...] = ()) -> bytes:
+    def segment(code: int, data: bytes) -> bytes:
+        return bytes((0xFF, code)) + struct.pack(">H", len(data) + 2) + data
+    return (b"\xff\xd8" + b"".join(segment(code, data) for code, data in segments)
+            + segment(0xDB, bytes(65)) + segment(0xC0, b"\x08\x00\x02\x00\x04\x01\x01\x11\x00")
+            + segment(0xDA, b"\x01\x01\x00\x00\x3f\x00") + scan + b"\xff\xd9")
+
+
+def tiff(data: bytes = bytes(range(8)), *, tags: tuple[tuple[int, bytes], ...] = (),
+         order: str = "<", gap: int = 0) -> bytes:
+    """One-strip 8-bit grayscale TIFF, with UNDEFINED *tags* stored before the strip."""
+    body = bytearray(b"II*\x00" if order == "<" else b"MM\x00*") + bytes(4 + gap)
+    offsets = {}
+    for tag, blob in tags:
+        offsets[tag] = len(body)
+        body += blob
+    strip = len(body)
+    body += data
+    entries = [(256, 4, 1, len(data)), (257, 3, 1, 1), (258, 3, 1, 8), (259, 3, 1, 1),
+               (262, 3, 1, 1), (273, 4, 1, strip), (277, 3, 1, 1), (278, 3, 1, 1),
+               (279, 4, 1, len(data))]
+    entries += [(tag, 7, len(blob), offsets[tag]) for tag, blob in tags]
+    body[4:8] = struct.pack(order + "I", len(body))
+    body += struct.pack(order + "H", len(entries))
+    for tag, kind, count, value in sorted(entries):
+        if kind == 3:  # a SHORT sits in the first half of the value field
+            field = struct.pack(order + "HH", value, 0)
+        else:
+            field = struct.pack(order + "I", value)
+        body += struct.pack(order + "HHI", tag, kind, count) + field
+    return bytes(body + bytes(4))
+
+
+class TestImageComparator:
+    def test_png_metadata_ignored(self, make_file):
+        a = make_file("a.png", png())
+        b = make_file("b.png", png(level=1, split=3, chunks=(
+            (b"iTXt", b"XML:com.adobe.xmp\x00\x00\x00\x00\x00<x:xmpmeta/>"),
+            (b"eXIf", b"MM\x00*\x00\x00\x00\x08\x00\x00"),
+            (b"iCCP", b"sRGB\x00\x00" + zlib.compress(b"profile")),
+            (b"tIME", b"\x07\xea\x01\x01\x00\x00\x00"),
+            (b"tEXt", b"Software\x00photo tool"))))
+        assert a.read_bytes() != b.read_bytes()
+        assert ImageComparator(chunk_size=3).compare(str(a), str(b)) is True
+
+    def test_png_pixels_differ(self, make_file):
+        a = make_file("a.png", png())
+        b = make_file("b.png", png(PIXELS[:-1] + b"\x09"))
+        c = make_file("c.png", png(chunks=((b"PLTE", b"\x00\x00\x00"),)))
+        for other in (b, c):
+            assert ImageComparator().compare(str(a), str(other)) is False, other.name
+
+    def test_jpeg_metadata_ignored(self, make_file):
+        a = make_file("a.jpg", jpeg(segments=((0xE0, b"JFIF\x00\x01\x01"),
+                                              (0xE1, b"Exif\x00\x00MM\x00*"))))
+        b = make_file("b.jpg", jpeg(segments=((0xE1, b"http://ns.adobe.com/xap/1.0/\x00<x/>"),
+                                              (0xE2, b"ICC_PROFILE\x00\x01\x01"),
+                                              (0xFE, b"edited"))))
+        assert ImageComparator(chunk_size=2).compare(str(a), str(b)) is True
+
+    def test_jpeg_pixels_differ(self, make_file):
+        a = make_file("a.jpg", jpeg())
+        b = make_file("b.jpg", jpeg(b"\x12\x35\xff\x00\x56"))
+        # Adobe APP14 selects the color transform, so it is not metadata
+        c = make_file("c.jpg", jpeg(segments=((0xEE, b"Adobe\x00\x64\x00\x00\x00\x00\x01"),)))
+        for other in (b, c):
+            assert ImageComparator().compare(str(a), str(other)) is False, other.name
+
+    def test_tiff_metadata_ignored(self, make_file):
+        a = make_file("a.tif", tiff())
+        b = make_file("b.tif", tiff(gap=16, tags=((270, b"rewritten by a tool\x00"),
+                                                  (34675, b"ICC profile bytes"))))
+        c = make_file("c.tif", tiff(order=">"))
+        for other in (b, c):
+            assert ImageComparator(chunk_size=3).compare(str(a), str(other)) is True, other.name
+
+    def test_tiff_pixels_differ(self, make_file):
+        a = make_file("a.tif", tiff())
+        b = make_file("b.tif", tiff(bytes(8)))
+        assert ImageComparator().compare(str(a), str(b)) is False
+
+    def test_invalid_is_different(self, make_file):
+        a = make_file("a.png", png())
+        corrupt = bytearray(png())
+        corrupt[-20] ^= 0xFF  # inside the deflate stream
+        for name, blob in (("junk.png", b"not an image"), ("cut.png", png()[:-15]),
+                           ("bad.png", bytes(corrupt)), ("jpeg.png", jpeg())):
+            path = make_file(name, blob)
+            assert ImageComparator().compare(str(a), str(path)) is False, name
+        for blob in (jpeg(), tiff()):
+            cut = make_file("cut", blob[:-10])
+            whole = make_file("whole", blob)
+            assert ImageComparator().compare(str(whole), str(cut)) is False
+
+    def test_invalid_chunk_size(self):
+        with pytest.raises(ValueError, match="chunk_size"):
+            ImageComparator(0)
+
+    def test_in_compare_dir(self, make_files, tmp_path: Path):
+        make_files({
+            "a/tagged.png": png(),
+            "b/tagged.png": png(chunks=((b"tEXt", b"Comment\x00new"),)),
+            "a/edited.png": png(),
+            "b/edited.png": png(PIXELS[:-1] + b"\x09"),
+        })
+        result = komparu.compare_dir(str(tmp_path / "a"), str(tmp_path / "b"),
+                                     comparators={"*.png": ImageComparator()})
+        assert result.diff == {"edited.png": DiffReason.CONTENT_MISMATCH}
+
+
+def id3v2(payload: bytes, *, footer: bool = False) -> bytes:
+    size = len(payload)
+    syncsafe = bytes(size >> shift & 0x7F for shift in (21, 14, 7, 0))
+    header = b"\x04\x00" + bytes((0x10 if footer else 0,)) + syncsafe
+    return b"ID3" + header + payload + (b"3DI" + header if footer else b"")
+
+
+def ape(items: bytes) -> bytes:
+    footer = b"APETAGEX" + struct.pack("<IIII", 2000, len(items) + 32, 1, 0) + bytes(8)
+    return items + footer
+
+
+MP3_FRAMES = (b"\xff\xfb\x90\x00" + bytes(range(200))) * 3
+
+
+def flac(frames: bytes, *, blocks: tuple[tuple[int, bytes], ...] = ()) -> bytes:
+    out = bytearray(b"fLaC")
+    blocks = ((0, bytes(34)),) + blocks
+    for i, (kind, data) in enumerate(blocks):
+        out += bytes((kind | (0x80 if i == len(blocks) - 1 else 0),)) + len(data).to_bytes(3, "big")
+        out += data
+    return bytes(out + frames)
+
+
+def ogg(packets: list[bytes], *, serial: int = 1, per_page: int = 4) -> bytes:
+    """Ogg stream of *packets*, paged *per_page* lacing values at a time."""
+    lacing = []
+    for packet in packets:
+        lacing += [255] * (len(packet) // 255) + [len(packet) % 255]
+    body, pos, out = b"".join(packets), 0, bytearray()
+    for seq, i in enumerate(range(0, len(lacing), per_page)):
+        laces = lacing[i:i + per_page]
+        out += (b"OggS\x00\x00" + struct.pack("<qIII", seq * 960, serial, seq, 0)
+                + bytes((len(laces),)) + bytes(laces) + body[pos:pos + sum(laces)])
+        pos += sum(laces)
+    return bytes(out)
+
+
+class TestAudioComparator:
+    def check(self, make_file, a: bytes, b: bytes, expected: bool) -> None:
+        path_a, path_b = make_file("a.audio", a), make_file("b.audio", b)
+        assert AudioComparator(chunk_size=7).compare(str(path_a), str(path_b)) is expected
+
+    def test_mp3_tags(self, make_file):
+        a = id3v2(b"TIT2 old title") + MP3_FRAMES + b"TAG" + b"old".ljust(125, b"\0")
+        for other in (id3v2(b"TIT2 new" * 100) + id3v2(b"APIC") + MP3_FRAMES + ape(b"item" * 9),
+                      MP3_FRAMES,
+                      MP3_FRAMES + b"x" * 20 + b"000020LYRICS200"
+                      + id3v2(b"TIT2 appended", footer=True)):
+            self.check(make_file, a, other, True)
+        self.check(make_file, a, MP3_FRAMES[:-1] + b"\x01", False)
+
+    def test_flac_tags(self, make_file):
+        frames = b"\xff\xf8\x69\x08" + bytes(range(100))
+        a = flac(frames, blocks=((3, b"seektable"), (4, b"ARTIST=old")))
+        b = flac(frames, blocks=((4, b"ARTIST=new" * 20), (6, b"\x89PNG"), (3, b"seektable"),
+                                 (1, bytes(100))))
+        self.check(make_file, a, b, True)
+        self.check(make_file, a, id3v2(b"stray") + b, True)
+        self.check(make_file, a, flac(frames[:-1] + b"\x01", blocks=((3, b"seektable"),)), False)
+        self.check(make_file, a, flac(frames, blocks=((3, b"seektable2"),)), False)
+
+    def test_ogg_tags(self, make_file):
+        audio = [bytes(range(i, i + 60)) for i in range(50)]
+        a = ogg([b"\x01vorbis id", b"\x03vorbis short", b"\x05vorbis setup", *audio])
+        b = ogg([b"\x01vorbis id", b"\x03vorbis " + b"long" * 300, b"\x05vorbis setup", *audio],
+                serial=99, per_page=3)
+        self.check(make_file, a, b, True)
+        self.check(make_file, a, ogg([b"OpusHead", b"OpusTags", *audio]), False)
+        self.check(make_file, a, ogg([b"\x01vorbis id", b"\x05vorbis setup", *audio[::-1]]), False)
+
+    def test_invalid_is_different(self, make_file):
+        a = flac(b"\xff\xf8" + bytes(50))
+        for other in (b"plain data", MP3_FRAMES, a[:20], ogg([b"x" * 10])[:-3]):
+            self.check(make_file, a, other, False)
+
+    def test_invalid_chunk_size(self):
+        with pytest.raises(ValueError, match="chunk_size"):
+            AudioComparator(0)
+
+
+def elf(text: bytes, build_id: bytes, *, signature: bytes = b"") -> bytes:
+    """ELF64 with .text, a GNU build-id note and, optionally, a module signature."""
+    names = b"\0.text\0.note.gnu.build-id\0.shstrtab\0"
+    note = struct.pack("<III", 4, len(build_id), 3) + b"GNU\0" + build_id
+    body = bytearray(64)
+    offsets = []
+    for blob in (text, note, names):
+        offsets.append(len(body))
+        body += blob
+    shoff = len(body)
+    for name, kind, offset, size in ((0, 0, 0, 0), (1, 1, offsets[0], len(text)),
+                                     (7, 7, offsets[1], len(note)),
+                                     (26, 3, offsets[2], len(names))):
+        body += struct.pack("<IIQQQQIIQQ", name, kind, 0, 0, offset, size, 0, 0, 1, 0)
+    body[:64] = b"\x7fELF\x02\x01\x01" + bytes(9) + struct.pack(
+        "<HHIQQQIHHHHHH", 2, 0x3E, 1, 0, 0, shoff, 0, 64, 0, 0, 64, 4, 3)
+    if signature:
+        body += (signature + struct.pack(">5B3xI", 0, 0, 2, 0, 0, len(signature))
+                 + b"~Module signature appended~\n")
+    return bytes(body)
+
+
+def pe(code: bytes, *, stamp: int, guid: bytes, certificate: bytes = b"") -> bytes:
+    """PE32+ with one section holding a CodeView debug directory and *code*."""
+    rsds = b"RSDS" + guid + struct.pack("<I", 1) + b"app.pdb\0"
+    data = struct.pack("<IIHHIIII", 0, stamp, 0, 0, 2, len(rsds), 0x1000 + 28, 0x200 + 28)
+    data += rsds + code
+    optional = bytearray(240)
+    struct.pack_into("<H", optional, 0, 0x20B)
+    struct.pack_into("<I", optional, 64, stamp ^ 0xFFFF)  # CheckSum
+    struct.pack_into("<I", optional, 108, 16)
+    struct.pack_into("<II", optional, 112 + 6 * 8, 0x1000, 28)
+    if certificate:
+        struct.pack_into("<II", optional, 112 + 4 * 8, 0x200 + len(data), len(certificate))
+    headers = (b"MZ" + bytes(58) + struct.pack("<I", 64) + b"PE\0\0"
+               + struct.pack("<HHIIIHH", 0x8664, 1, stamp, 0, 0, len(optional), 0x22)
+               + optional + b".rdata\0\0"
+               + struct.pack("<IIIIIIHHI", len(data), 0x1000, len(data), 0x200, 0, 0, 0, 0, 0))
+    return headers.ljust(0x200, b"\0") + data + certificate
+
+
+def macho(code: bytes, *, uuid: bytes, stamp: int, signature: bytes) -> bytes:
+    """64-bit dylib with LC_UUID, a dylib timestamp and a code signature at the end."""
+    sig_offset = 0x200 + len(code)
+    commands = (struct.pack("<II16s", 0x1B, 24, uuid)
+                + struct.pack("<IIIIII", 0x0D, 40, 24, stamp, 0x10000, 0x10000)
+                + b"lib.dylib".ljust(16, b"\0")
+                + struct.pack("<II16sQQQQiiII", 0x19, 72, b"__LINKEDIT", 0x4000,
+                              len(signature), sig_offset, len(signature), 1, 1, 0, 0)
+                + struct.pack("<IIII", 0x1D, 16, sig_offset, len(signature)))
+    header = struct.pack("<IiiIIIII", 0xFEEDFACF, 0x01000007, 3, 6, 4, len(commands), 0, 0)
+    return (header + commands).ljust(0x200, b"\0") + code + signature
+
+
+def fat(slice_: bytes) -> bytes:
+    """Universal binary holding one slice at offset 0x1000."""
+    header = b"\xca\xfe\xba\xbe" + struct.pack(">IiiIII", 1, 0x01000007, 3, 0x1000, len(slice_), 12)
+    return header.ljust(0x1000, b"\0") + slice_ + bytes(-len(slice_) % 0x1000)
+
+
+class TestExecutableComparator:
+    TEXT = b"\x55\x48\x89\xe5\xc3" * 20
+
+    def check(self, make_file, a: bytes, b: bytes, expected: bool, **options) -> None:
+        path_a, path_b = make_file("a.bin", a), make_file("b.bin", b)
+        assert ExecutableComparator(**options).compare(str(path_a), str(path_b)) is expected
+
+    def test_elf_build_id(self, make_file):
+        a = elf(self.TEXT, b"\x01" * 20)
+        self.check(make_file, a, elf(self.TEXT, b"\x02" * 20), True)
+        self.check(make_file, a, elf(self.TEXT, b"\x02" * 20), False, ignore_build_id=False)
+        self.check(make_file, a, elf(self.TEXT[::-1], b"\x01" * 20), False)
+
+    def test_elf_module_signature(self, make_file):
+        a = elf(self.TEXT, b"\x01" * 20, signature=b"sig")
+        self.check(make_file, a, elf(self.TEXT, b"\x01" * 20, signature=b"longer sig"), True)
+        self.check(make_file, a, elf(self.TEXT, b"\x01" * 20), True)
+        self.check(make_file, a, elf(self.TEXT, b"\x01" * 20), False, ignore_signature=False)
+
+    def test_pe(self, make_file):
+        a = pe(self.TEXT, stamp=1, guid=b"\x01" * 16)
+        b = pe(self.TEXT, stamp=2, guid=b"\x02" * 16, certificate=b"authenticode")
+        self.check(make_file, a, b, True)
+        self.check(make_file, a, b, False, ignore_timestamps=False)
+        self.check(make_file, a, b, False, ignore_signature=False)
+        self.check(make_file, a, pe(self.TEXT[::-1], stamp=1, guid=b"\x01" * 16), False)
+
+    def test_macho(self, make_file):
+        a = macho(self.TEXT, uuid=b"\x01" * 16, stamp=1, signature=b"blob")
+        b = macho(self.TEXT, uuid=b"\x02" * 16, stamp=2, signature=b"longer blob")
+        self.check(make_file, a, b, True)
+        self.check(make_file, fat(a), fat(b), True)
+        self.check(make_file, a, b, False, ignore_build_id=False)
+        self.check(make_file, a, macho(self.TEXT[::-1], uuid=b"\x01" * 16, stamp=1,
+                                       signature=b"blob"), False)
+
+    def test_invalid_is_different(self, make_file):
+        a = elf(self.TEXT, b"\x01" * 20)
+        for other in (pe(self.TEXT, stamp=1, guid=bytes(16)), b"\x7fELF\x02\x01", b"plain data",
+                      elf(self.TEXT, b"\x01" * 20)[:200]):
+            self.check(make_file, a, other, False)
+
+
+def pdf(content: bytes = b"BT (Q3 revenue) Tj ET", *, doc_id: bytes = b"0" * 32,
+        date: bytes = b"D:20240101120000Z", xmp_date: bytes = b"2024-01-01T12:00:00Z",
+        level: int = 6) -> bytes:
+    """One-page PDF with an Info dictionary, an XMP packet and a valid xref table."""
+    xmp = (b'<x:xmpmeta><rdf:Description xmp:ModifyDate="' + xmp_date + b'">'
+           b"<xmp:CreateDate>" + xmp_date + b"</xmp:CreateDate>"
+           b"<xmpMM:DocumentID>uuid:" + doc_id + b"</xmpMM:DocumentID>"
+           b"</rdf:Description></x:xmpmeta>")
+    page = zlib.compress(content, level)
+    objects = [
+        b"<< /Type /Catalog /Pages 2 0 R /Metadata 4 0 R >>",
+        b"<< /Type /Pages /Kids [] /Count 0 >>",
+        b"<< /Length %d /Filter /FlateDecode >>\nstream\n%s\nendstream" % (len(page), page),
+        b"<< /Type /Metadata /Subtype /XML /Length %d >>\nstream\n%s\nendstream" % (len(xmp), xmp),
+        b"<< /Producer (report tool) /CreationDate (%s) /ModDate (%s) >>" % (date, date),
+    ]
+    out = bytearray(b"%PDF-1.7\n")
+    offsets = []
+    for number, body in enumerate(objects, 1):
+        offsets.append(len(out))
+        out += b"%d 0 obj\n%s\nendobj\n" % (number, body)
+    xref = len(out)
+    out += b"xref\n0 %d\n0000000000 65535 f \n" % (len(objects) + 1)
+    out += b"".join(b"%010d 00000 n \n" % offset for offset in offsets)
+    out += (b"trailer\n<< /Size %d /Root 1 0 R /Info 5 0 R /ID [<%s> <%s>] >>\n"
+            b"startxref\n%d\n%%%%EOF\n" % (len(objects) + 1, doc_id, doc_id, xref))
+    return bytes(out)
+
+
+class TestPdfComparator:
+    def test_regenerated(self, make_file):
+        a = make_file("a.pdf", pdf())
+        # Longer dates shift every offset after them
+        b = make_file("b.pdf", pdf(doc_id=b"f" * 32, date=b"D:20250630093000+02'00'",
+                                   xmp_date=b"2025-06-30T09:30:00+02:00", level=9))
+        assert a.read_bytes() != b.read_bytes()
+        assert PdfComparator().compare(str(a), str(b)) is True
+
+    def test_content_differs(self, make_file):
+        a = make_file("a.pdf", pdf())
+        b = make_file("b.pdf", pdf(b"BT (Q4 revenue) Tj ET"))
+        assert PdfComparator().compare(str(a), str(b)) is False
+
+    def test_not_pdf(self, make_file):
+        a = make_file("a.pdf", pdf())
+        b = make_file("b.pdf", pdf()[9:])
+        assert PdfComparator().compare(str(a), str(b)) is False
+        assert PdfComparator().compare(str(b), str(a)) is False
+
+    def test_in_compare_dir(self, make_files, tmp_path: Path):
+        make_files({
+            "a/report.pdf": pdf(),
+            "b/report.pdf": pdf(doc_id=b"1" * 32),
+            "a/other.pdf": pdf(),
+            "b/other.pdf": pdf(b"BT (Q3 lossss) Tj ET"),
+        })
+        result = komparu.compare_dir(str(tmp_path / "a"), str(tmp_path / "b"),
+                                     comparators={"*.pdf": PdfComparator()})
+        assert set(result.diff) == {"other.pdf"}
+
+
+def database(path: Path, rows: list[tuple[int, object]], *, schema: str = "",
+             pragmas: str = "", user_version: int = 0) -> Path:
+    with sqlite3.connect(path) as db:
+        db.executescript(pragmas)
+        db.execute("CREATE TABLE items (id INTEGER PRIMARY KEY, value)")
+        db.execute("CREATE TABLE log (line)")
+        db.executemany("INSERT INTO items VALUES (?, ?)", rows)
+        db.executemany("INSERT INTO log VALUES (?)", [(str(v),) for _, v in rows])
+        db.executescript(schema)
+        db.execute(f"PRAGMA user_version = {user_version}")
+    db.close()
+    return path
+
+
+class TestSqliteComparator:
+    ROWS = [(i, f"value {i}") for i in range(200)]
+
+    def test_layout_ignored(self, tmp_path: Path):
+        a = database(tmp_path / "a.db", self.ROWS)
+        b = database(tmp_path / "b.db", self.ROWS[::-1] + [(999, "x" * 5000)],
+                     pragmas="PRAGMA page_size = 1024;")
+        with sqlite3.connect(b) as db:
+            db.execute("DELETE FROM items WHERE id = 999")
+            db.execute("DELETE FROM log WHERE line = ?", ("x" * 5000,))
+        db.close()
+        assert a.read_bytes() != b.read_bytes()
+        assert SqliteComparator().compare(str(a), str(b)) is True
+
+    def test_wal(self, tmp_path: Path):
+        a = database(tmp_path / "a.db", self.ROWS)
+        b = database(tmp_path / "b.db", self.ROWS[:-1], pragmas="PRAGMA journal_mode = WAL;")
+        writer = sqlite3.connect(b)
+        writer.execute("PRAGMA wal_autocheckpoint = 0")
+        with writer:
+            writer.execute("INSERT INTO items VALUES (?, ?)", self.ROWS[-1])
+            writer.execute("INSERT INTO log VALUES (?)", (self.ROWS[-1][1],))
+        try:
+            assert SqliteComparator().compare(str(a), str(b)) is True
+        finally:
+            writer.close()
+
+    def test_rows_differ(self, tmp_path: Path):
+        a = database(tmp_path / "a.db", self.ROWS)
+        for name, rows in (("changed", self.ROWS[:-1] + [(199, "other")]),
+                           ("missing", self.ROWS[:-1]),
+                           ("retyped", self.ROWS[:-1] + [(199, 1.0)])):
+            b = database(tmp_path / f"{name}.db", rows)
+            assert SqliteComparator().compare(str(a), str(b)) is False, name
+        a = database(tmp_path / "int.db", [(1, 1)])
+        b = database(tmp_path / "float.db", [(1, 1.0)])
+        assert SqliteComparator().compare(str(a), str(b)) is False
+
+    def test_schema_differs(self, tmp_path: Path):
+        a = database(tmp_path / "a.db", self.ROWS)
+        b = database(tmp_path / "b.db", self.ROWS, schema="CREATE INDEX by_value ON items (value);")
+        c = database(tmp_path / "c.db", self.ROWS, user_version=2)
+        for other in (b, c):
+            assert SqliteComparator().compare(str(a), str(other)) is False, other.name
+
+    def test_not_a_database(self, tmp_path: Path, make_file):
+        a = database(tmp_path / "a.db", self.ROWS)
+        b = make_file("b.db", b"SQLite format 3\0" + bytes(100))
+        c = make_file("c.db", b"not a database")
+        for other in (b, c):
+            assert SqliteComparator().compare(str(a), str(other)) is False, other.name
+
+
+needs_pyarrow = pytest.mark.skipif(importlib.util.find_spec("pyarrow") is None,
+                                   reason="needs pyarrow")
+
+
+def parquet(path: Path, values: list[object], *, row_group_size: int | None = None,
+            compression: str = "snappy", metadata: dict[bytes, bytes] | None = None) -> Path:
+    import pyarrow
+    import pyarrow.parquet
+
+    table = pyarrow.table({"id": list(range(len(values))), "value": values})
+    pyarrow.parquet.write_table(table.replace_schema_metadata(metadata), path,
+                                row_group_size=row_group_size, compression=compression)
+    return path
+
+
+def arrow(path: Path, values: list[object], *, batch_rows: int) -> Path:
+    import pyarrow
+    import pyarrow.ipc
+
+    table = pyarrow.table({"id": list(range(len(values))), "value": values})
+    with pyarrow.ipc.new_file(path, table.schema) as writer:
+        for batch in table.to_batches(max_chunksize=batch_rows):
+            writer.write_batch(batch)
+    return path
+
+
+@needs_pyarrow
+class TestParquetComparator:
+    VALUES = [f"value {i}" for i in range(200)]
+
+    def test_metadata_ignored(self, tmp_path: Path):
+        a = parquet(tmp_path / "a.parquet", self.VALUES)
+        b = parquet(tmp_path / "b.parquet", self.VALUES, metadata={b"run": b"2"})
+        assert a.read_bytes() != b.read_bytes()
+        assert ParquetComparator().compare(str(a), str(b)) is True
+
+    def test_row_groups(self, tmp_path: Path):
+        a = parquet(tmp_path / "a.parquet", self.VALUES, row_group_size=50)
+        b = parquet(tmp_path / "b.parquet", self.VALUES)
+        assert ParquetComparator().compare(str(a), str(b)) is False
+        assert ParquetComparator(ignore_row_groups=True).compare(str(a), str(b)) is True
+        assert ParquetComparator(7, ignore_row_groups=True).compare(str(a), str(b)) is True
+
+    def test_compression(self, tmp_path: Path):
+        a = parquet(tmp_path / "a.parquet", self.VALUES)
+        b = parquet(tmp_path / "b.parquet", self.VALUES, compression="zstd")
+        assert ParquetComparator().compare(str(a), str(b)) is False
+        assert ParquetComparator(ignore_compression=True).compare(str(a), str(b)) is True
+
+    def test_rows_differ(self, tmp_path: Path):
+        a = parquet(tmp_path / "a.parquet", self.VALUES)
+        for name, values in (("changed", self.VALUES[:-1] + ["other"]),
+                             ("missing", self.VALUES[:-1]),
+                             ("retyped", list(range(200)))):
+            b = parquet(tmp_path / f"{name}.parquet", values)
+            comparator = ParquetComparator(ignore_row_groups=True)
+            assert comparator.compare(str(a), str(b)) is False, name
+
+    def test_arrow_ipc(self, tmp_path: Path):
+        a = arrow(tmp_path / "a.arrow", self.VALUES, batch_rows=64)
+        b = arrow(tmp_path / "b.arrow", self.VALUES, batch_rows=200)
+        c = arrow(tmp_path / "c.arrow", self.VALUES[:-1] + ["other"], batch_rows=200)
+        assert ParquetComparator().compare(str(a), str(b)) is False
+        assert ParquetComparator(ignore_row_groups=True).compare(str(a), str(b)) is True
+        assert ParquetComparator(ignore_row_groups=True).compare(str(a), str(c)) is False
+
+    def test_not_parquet(self, tmp_path: Path, make_file):
+        a = parquet(tmp_path / "a.parquet", self.VALUES)
+        for other in (make_file("b.parquet", b"PAR1" + bytes(100)),
+                      make_file("c.parquet", b"id,value\n0,value 0\n")):
+            assert ParquetComparator().compare(str(a), str(other)) is False, other.name
+
+    def test_batch_size_validated(self):
+        with pytest.raises(ValueError, match="batch_size"):
+            ParquetComparator(0)
+
+
+class TestParquetComparatorWithoutPyarrow:
+    def test_import_error(self, monkeypatch):
+        monkeypatch.setitem(sys.modules, "pyarrow", None)
+        with pytest.raises(ImportError, match="komparu\\[parquet\\]"):
+            ParquetComparator()
+
+
+def python(script: str, *args: str) -> list[str]:
+    return [sys.executable, "-c", script, *args]
+
+
+class TestFilterComparator:
+    def test_normalized_equal(self, make_file):
+        a = make_file("a.txt", b"b\na\nc\n")
+        b = make_file("b.txt", b"c\nb\na\n")
+        c = make_file("c.txt", b"c\nb\nd\n")
+        comparator = FilterComparator(python("import sys; sys.stdout.writelines(sorted(sys.stdin))"))
+        assert comparator.compare(str(a), str(b)) is True
+        assert comparator.compare(str(a), str(c)) is False
+
+    def test_path_argument(self, make_file):
+        a = make_file("a.txt", b"x  y\n")
+        b = make_file("b.txt", b"x y")
+        script = "import sys; print(open(sys.argv[1]).read().split())"
+        assert FilterComparator(python(script, "{}")).compare(str(a), str(b)) is True
+
+    def test_stops_at_first_difference(self, make_file):
+        a = make_file("a.txt", b"a")
+        b = make_file("b.txt", b"b")
+        endless = ("import sys; out = sys.stdout.buffer; out.write(sys.stdin.buffer.read())\n"
+                   "while True: out.write(bytes(65536))")
+        assert FilterComparator(python(endless), chunk_size=4096).compare(str(a), str(b)) is False
+
+    def test_failing_filter(self, make_file):
+        a = make_file("a.txt", b"same")
+        with pytest.raises(SourceReadError, match="status 3"):
+            FilterComparator(python("import sys; sys.exit(3)")).compare(str(a), str(a))
+
+    def test_command_validated(self):
+        with pytest.raises(ValueError, match="command"):
+            FilterComparator("sort")
+        with pytest.raises(ValueError, match="command"):
+            FilterComparator([])
+
+
+class TestSniffingComparator:
+    def test_detect(self, make_file, tmp_path: Path):
+        members = [("a.txt", b"a")]
+        files = {
+            "compressed": gz(b"log line\n"),
+            "tar": tar(members, mode="w:gz"),
+            "zip": zipped(members),
+            "deb": deb(members),
+            "image": png(),
+            "audio": flac(b"\xff\xf8frames"),
+            "pdf": pdf(),
+            "executable": elf(b"\x90", b"\x01" * 20),
+            "xml": b"\xef\xbb\xbf<?xml version='1.0'?><a/>",
+            "json": b'  {"a": 1}',
+        }
+        sniffer = SniffingComparator()
+        for kind, data in files.items():
+            assert sniffer.detect(str(make_file(f"{kind}.bin", data))) == kind, kind
+        assert sniffer.detect(str(database(tmp_path / "db.bin", [(1, "x")]))) == "sqlite"
+        assert sniffer.detect(str(make_file("plain.bin", b"just text\n"))) is None
+
+    def test_routes_by_content_not_name(self, make_file):
+        routes = []
+        sniffer = SniffingComparator(on_route=lambda a, b, kind: routes.append(kind))
+        a = make_file("report", pdf(doc_id=b"1" * 32))
+        b = make_file("report.bin", pdf(doc_id=b"2" * 32, date=b"D:20250101000000Z"))
+        assert sniffer.compare(str(a), str(b)) is True
+        assert routes == ["pdf"]
+
+    def test_kinds_must_agree(self, make_file):
+        a = make_file("a", gz(b'{"a": 1}'))
+        b = make_file("b", b'{"a": 1}')
+        assert SniffingComparator().compare(str(a), str(b)) is False
+
+    def test_fallback(self, make_file):
+        a = make_file("a.txt", b"one\r\ntwo\r\n")
+        b = make_file("b.txt", b"one\ntwo\n")
+        assert SniffingComparator().compare(str(a), str(b)) is False
+        assert SniffingComparator(fallback=TextComparator()).compare(str(a), str(b)) is True
+
+
+class TestXmlComparator:
+    def test_canonical_equal(self, make_file):
+        a = make_file("a.xml", b'<?xml version="1.0" encoding="UTF-8"?>\n'
+                               b'<rows><row id="1" name="a&#x20;b"/>\n</rows>')
+        b = make_file("b.xml", b"<rows>\n  <row name='a b' id='1'></row>\n</rows>\n")
+        assert XmlComparator().compare(str(a), str(b)) is True
+
+    def test_namespace_prefixes(self, make_file):
+        a = make_file("a.xml", b'<x:data xmlns:x="urn:ds"><x:v>1</x:v></x:data>')
+        b = make_file("b.xml", b'<ds:data xmlns:ds="urn:ds"><ds:v>1</ds:v></ds:data>')
+        c = make_file("c.xml", b'<ds:data xmlns:ds="urn:other"><ds:v>1</ds:v></ds:data>')
+        assert XmlComparator().compare(str(a), str(b)) is True
+        assert XmlComparator().compare(str(b), str(c)) is False
+        assert XmlComparator(rewrite_prefixes=False).compare(str(a), str(b)) is False
+
+    def test_whitespace(self, make_file):
+        a = make_file("a.xml", b"<r><v>  1 </v></r>")
+        b = make_file("b.xml", b"<r>\n<v>1</v>\n</r>")
+        assert XmlComparator().compare(str(a), str(b)) is True
+        assert XmlComparator(strip_whitespace=False).compare(str(a), str(b)) is False
+
+    def test_comments(self, make_file):
+        a = make_file("a.xml", b"<r><!-- exported 2024 --><v>1</v></r>")
+        b = make_file("b.xml", b"<r><v>1</v></r>")
+        assert XmlComparator().compare(str(a), str(b)) is True
+        assert XmlComparator(with_comments=True).compare(str(a), str(b)) is False
+
+    def test_element_order_matters(self, make_file):
+        a = make_file("a.xml", b"<r><a/><b/></r>")
+        b = make_file("b.xml", b"<r><b/><a/></r>")
+        assert XmlComparator().compare(str(a), str(b)) is False
+
+    def test_malformed_is_different(self, make_file):
+        a = make_file("a.xml", b"<r><v>1</r>")
+        b = make_file("b.xml", b"<r><v>1</v></r>")
+        assert XmlComparator().compare(str(a), str(b)) is False
+
+    def test_in_compare_dir(self, make_files, tmp_path: Path):
+        make_files({
+            "a/set.xml": b'<set><i a="1" b="2"/></set>',
+            "b/set.xml": b'<set>\n  <i b="2" a="1" />\n</set>',
+            "a/other.xml": b"<set><i/></set>",
+            "b/other.xml": b"<set><j/></set>",
+        })
+        result = komparu.compare_dir(str(tmp_path / "a"), str(tmp_path / "b"),
+                                     comparators={"*.xml": XmlComparator()})
+        assert result.diff == {"other.xml": DiffReason.CONTENT_MISMATCH}
+
+
+class TestAsync:
+    @pytest.mark.asyncio
+    async def test_compare_dir(self, trees):
+        a, b = map(str, trees)
+        result = await komparu.aio.compare_dir(a, b, comparators={"*.ctr": IgnoreFirstLine()})
+        assert "stamped.ctr" not in result.diff
+
+    @pytest.mark.asyncio
+    async def test_iter_dir(self, trees):
+        a, b = map(str, trees)
+        paths = [e.path async for e in komparu.aio.iter_dir(
+            a, b, comparators={"*.ctr": IgnoreFirstLine()})]
+        assert "stamped.ctr" not in paths
@@ -257,6 +257,19 @@ class TestArchiveSafety:
         result = komparu.compare_archive(str(a_path), str(b))
         assert result.equal is True
 
+    def test_undecodable_entry_name(self, make_tar, tmp_path: Path):
+        """Names that are not UTF-8 come back as os.fsdecode() gives them."""
+        a_path = tmp_path / "a.tar"
+        with tarfile.open(str(a_path), "w", format=tarfile.GNU_FORMAT,
+                          encoding="latin-1") as tf:
+            info = tarfile.TarInfo(name="caf\xe9.txt")
+            info.size = 5
+            tf.addfile(info, io.BytesIO(b"hello"))
+
+        b = make_tar("b.tar.gz", {"file.txt": b"hello"})
+        result = komparu.compare_archive(str(a_path), str(b))
+        assert result.only_left == {os.fsdecode(b"caf\xe9.txt")}
+
     def test_max_entries_bomb(self, tmp_path: Path):
         """Archive with too many entries raises error."""
         a_path = tmp_path / "bomb.tar.gz"
@@ -686,3 +699,56 @@ class TestHashCompare:
         b = make_tar_bz2("b.tar.bz2", files)
         result = komparu.compare_archive(str(a), str(b), hash_compare=True)
         assert result.equal is True
+
+
+class TestCompareDirArchive:
+    """compare_dir_archive: a directory against an archive of it."""
+
+    SITE = {"index.html": b"<html/>", "css/site.css": b"body {}", "img/logo.png": b"\x89PNG"}
+
+    def test_backup_matches(self, make_tar, make_files, tmp_path: Path):
+        make_files({f"site/{name}": data for name, data in self.SITE.items()})
+        archive = make_tar("site.tar.gz", {f"./{name}": data for name, data in self.SITE.items()})
+        result = komparu.compare_dir_archive(str(archive), str(tmp_path / "site"))
+        assert result.equal is True
+
+    def test_differences_keep_sides(self, make_zip, make_files, tmp_path: Path):
+        make_files({
+            "site/index.html": b"<html/>",
+            "site/css/site.css": b"body { color: red }",
+            "site/new.txt": b"n",
+        })
+        archive = make_zip("site.zip", self.SITE)
+        result = komparu.compare_dir_archive(str(archive), str(tmp_path / "site"))
+        assert result.diff == {"css/site.css": DiffReason.SIZE_MISMATCH}
+        assert result.only_left == {"img/logo.png"}
+        assert result.only_right == {"new.txt"}
+
+        flipped = komparu.compare_dir_archive(str(tmp_path / "site"), str(archive), max_workers=1)
+        assert flipped.only_left == {"new.txt"}
+        assert flipped.only_right == {"img/logo.png"}
+
+    def test_content_mismatch_and_ignore(self, make_tar_plain, make_files, tmp_path: Path):
+        make_files({"site/a.txt": b"one", "site/b.log": b"x"})
+        archive = make_tar_plain("site.tar", {"a.txt": b"two"})
+        result = komparu.compare_dir_archive(str(tmp_path / "site"), str(archive),
+                                             ignore=["*.log"])
+        assert result.diff == {"a.txt": DiffReason.CONTENT_MISMATCH}
+        assert result.only_left == set()
+
+    def test_two_directories_rejected(self, make_files, tmp_path: Path):
+        make_files({"a/x": b"1", "b/x": b"1"})
+        with pytest.raises(ValueError, match="archive"):
+            komparu.compare_dir_archive(str(tmp_path / "a"), str(tmp_path / "b"))
+
+    def test_missing_archive(self, make_files, tmp_path: Path):
+        make_files({"site/x": b"1"})
+        with pytest.raises(komparu.SourceNotFoundError):
+            komparu.compare_dir_archive(str(tmp_path / "nope.tar"), str(tmp_path / "site"))
+
+    def test_bomb_limit(self, make_tar, make_files, tmp_path: Path):
+        make_files({"site/big.txt": b"A" * 10240})
+        archive = make_tar("big.tar.gz", {"big.txt": b"A" * 10240})
+        with pytest.raises(komparu.ArchiveBombError):
+            komparu.compare_dir_archive(str(archive), str(tmp_path / "site"),
+                                        max_decompressed_size=1024)
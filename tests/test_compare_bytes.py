@@ -0,0 +1,106 @@
+"""Tests for in-memory buffer comparison helpers."""
+
+from __future__ import annotations
+
+import mmap
+
+import pytest
+
+import komparu
+
+
+class TestCompareBytes:
+    def test_equal(self):
+        assert komparu.compare_bytes(b"abc", b"abc") is True
+
+    def test_different(self):
+        assert komparu.compare_bytes(b"abc", b"abd") is False
+
+    def test_different_length(self):
+        assert komparu.compare_bytes(b"abc", b"abcd") is False
+
+    def test_buffer_types(self):
+        data = b"x" * 1000
+        assert komparu.compare_bytes(bytearray(data), memoryview(data)) is True
+
+
+class TestFirstDiff:
+    def test_equal(self):
+        assert komparu.first_diff(b"same", b"same") is None
+
+    def test_empty(self):
+        assert komparu.first_diff(b"", b"") is None
+
+    def test_first_byte(self):
+        assert komparu.first_diff(b"xbc", b"abc") == 0
+
+    def test_offset_past_scan_window(self):
+        a = bytearray(100_000)
+        b = bytearray(100_000)
+        b[70_001] = 1
+        assert komparu.first_diff(a, b) == 70_001
+
+    def test_prefix(self):
+        assert komparu.first_diff(b"abc", b"abcdef") == 3
+        assert komparu.first_diff(b"abcdef", b"abc") == 3
+        assert komparu.first_diff(b"", b"a") == 0
+
+    def test_mmap(self, make_file):
+        path = make_file("data.bin", b"hello world")
+        with open(path, "rb") as f, mmap.mmap(f.fileno(), 0, access=mmap.ACCESS_READ) as m:
+            assert komparu.first_diff(m, b"hello there") == 6
+
+
+class TestCountDiffBlocks:
+    def test_equal(self):
+        assert komparu.count_diff_blocks(b"a" * 10_000, b"a" * 10_000) == 0
+
+    def test_counts_blocks(self):
+        a = bytearray(16)
+        b = bytearray(16)
+        b[0] = 1
+        b[1] = 1    # same block as b[0]
+        b[9] = 1
+        assert komparu.count_diff_blocks(a, b, block_size=4) == 2
+
+    def test_length_mismatch(self):
+        # Blocks beyond the shorter buffer count as differing
+        assert komparu.count_diff_blocks(b"abcd", b"abcdefghij", block_size=4) == 2
+
+    def test_partial_last_block(self):
+        assert komparu.count_diff_blocks(b"aaaaab", b"aaaaac", block_size=4) == 1
+
+    def test_invalid_block_size(self):
+        with pytest.raises(ValueError, match="block_size"):
+            komparu.count_diff_blocks(b"a", b"a", block_size=0)
+
+
+class TestSimilarity:
+    def test_equal(self, make_file):
+        a = make_file("a.bin", b"a" * 10_000)
+        assert komparu.similarity(str(a), str(a)) == 1.0
+
+    def test_fraction(self, make_file):
+        a = make_file("a.bin", bytes(16))
+        b = make_file("b.bin", bytes(9) + b"x" + bytes(6))
+        assert komparu.similarity(str(a), str(b), block_size=4) == 0.75
+
+    def test_length_mismatch(self, make_file):
+        # Blocks beyond the shorter file count as differing
+        a = make_file("a.bin", b"abcd")
+        b = make_file("b.bin", b"abcdefghij")
+        assert komparu.similarity(str(a), str(b), block_size=4) == 1 / 3
+
+    def test_streamed(self, make_file):
+        a = make_file("a.bin", bytes(200_000))
+        b = make_file("b.bin", bytes(199_999) + b"x")
+        assert komparu.similarity(str(a), str(b), block_size=1000) == 199 / 200
+
+    def test_empty(self, make_file):
+        a = make_file("a.bin", b"")
+        assert komparu.similarity(str(a), str(a)) == 1.0
+
+    def test_invalid_block_size(self, make_file):
+        a = make_file("a.bin", b"a")
+        with pytest.raises(ValueError, match="block_size"):
+            komparu.similarity(str(a), str(a), block_size=0)
@@ -3,27 +3,16 @@
 from __future__ import annotations
 
 import os
+import subprocess
+import sys
+import threading
+import time
 from pathlib import Path
 
 import pytest
 
 import komparu
-from komparu import DiffReason
-
-
-@pytest.fixture
-def make_dir(tmp_path: Path):
-    """Create a directory tree from a dict of {relative_path: content}."""
-
-    def _make(name: str, files: dict[str, bytes]) -> Path:
-        d = tmp_path / name
-        for rel, content in files.items():
-            p = d / rel
-            p.parent.mkdir(parents=True, exist_ok=True)
-            p.write_bytes(content)
-        return d
-
-    return _make
+from komparu import DiffReason, EntryKind
 
 
 class TestSameDir:
@@ -332,6 +321,16 @@ class TestUnicodePaths:
         result = komparu.compare_dir(str(a), str(b))
         assert result.equal is True
 
+    @pytest.mark.skipif(not sys.platform.startswith("linux"), reason="names must be UTF-8")
+    def test_undecodable_names(self, make_dir):
+        """Names that are not UTF-8 are compared and reported as os.fsdecode() gives them."""
+        name = os.fsdecode(b"caf\xe9/men\xfc.txt")
+        a = make_dir(os.fsdecode(b"\xff"), {name: b"one", "same": b"1"})
+        b = make_dir("b", {name: b"two", "same": b"1"})
+        assert komparu.compare_dir(str(a), str(b)).diff == {name: DiffReason.CONTENT_MISMATCH}
+        assert [e.path for e in komparu.iter_dir(str(a), str(b))] == [name]
+        assert komparu.compare_dir(str(a), str(b), max_workers=1, ignore=["caf*"]).equal
+
 
 # ---- New tests: Special character paths ----
 
@@ -729,3 +728,302 @@ class TestPermissionDeniedErrors:
             assert "readable.txt" not in result.diff
         finally:
             target_dir.chmod(0o755)
+
+
+class TestIterDir:
+    """Lazy iteration over directory comparison entries."""
+
+    def test_identical_yields_nothing(self, make_dir):
+        files = {f"f{i}.txt": b"x" * i for i in range(10)}
+        a = make_dir("a", files)
+        b = make_dir("b", files)
+        assert list(komparu.iter_dir(str(a), str(b))) == []
+
+    def test_entries_match_compare_dir(self, make_dir):
+        a = make_dir("a", {"same": b"1", "diff": b"left", "sub/only_a": b"x"})
+        b = make_dir("b", {"same": b"1", "diff": b"right", "only_b": b"y"})
+        entries = {e.path: e for e in komparu.iter_dir(str(a), str(b))}
+        result = komparu.compare_dir(str(a), str(b))
+
+        assert {p for p, e in entries.items() if e.kind is EntryKind.DIFF} == set(result.diff)
+        assert {p for p, e in entries.items() if e.kind is EntryKind.ONLY_LEFT} == result.only_left
+        assert {p for p, e in entries.items() if e.kind is EntryKind.ONLY_RIGHT} == result.only_right
+        assert entries["diff"].reason is result.diff["diff"]
+
+    def test_include_equal(self, make_dir):
+        a = make_dir("a", {"x": b"1", "y": b"2"})
+        b = make_dir("b", {"x": b"1", "y": b"3"})
+        kinds = {e.path: e.kind for e in komparu.iter_dir(str(a), str(b), include_equal=True)}
+        assert kinds == {"x": EntryKind.EQUAL, "y": EntryKind.DIFF}
+
+    def test_is_lazy(self, make_dir):
+        """Nothing runs (not even validation) until iteration starts."""
+        it = komparu.iter_dir("/nonexistent/a", "/nonexistent/b")
+        with pytest.raises(IOError):
+            next(it)
+
+    def test_stop_early(self, make_dir):
+        a = make_dir("a", {f"f{i}": b"A" for i in range(100)})
+        b = make_dir("b", {f"f{i}": b"B" for i in range(100)})
+        first = next(komparu.iter_dir(str(a), str(b), max_workers=4))
+        assert first.kind is EntryKind.DIFF
+
+    def test_ignore_and_options(self, make_dir):
+        a = make_dir("a", {"keep.txt": b"A", "skip.log": b"A"})
+        b = make_dir("b", {"keep.txt": b"B", "skip.log": b"B"})
+        opts = komparu.CompareOptions(ignore=("*.log",))
+        assert [e.path for e in komparu.iter_dir(str(a), str(b), options=opts)] == ["keep.txt"]
+
+    def test_diff_entries_carry_sizes(self, make_dir):
+        a = make_dir("a", {"short": b"ab", "same_len": b"xy", "only_a": b"z"})
+        b = make_dir("b", {"short": b"abcd", "same_len": b"xz"})
+        entries = {e.path: e for e in komparu.iter_dir(str(a), str(b))}
+        assert (entries["short"].size_a, entries["short"].size_b) == (2, 4)
+        assert (entries["same_len"].size_a, entries["same_len"].size_b) == (2, 2)
+        assert entries["only_a"].size_a is None
+
+    def test_bounded_queue_slow_consumer(self, make_dir):
+        """Workers pause on a full queue; every entry still arrives."""
+        a = make_dir("a", {f"f{i}": b"A" for i in range(200)})
+        b = make_dir("b", {f"f{i}": b"B" for i in range(200)})
+        seen = set()
+        for entry in komparu.iter_dir(str(a), str(b), max_workers=4, max_pending=2):
+            if len(seen) % 50 == 0:
+                time.sleep(0.01)
+            seen.add(entry.path)
+        assert seen == {f"f{i}" for i in range(200)}
+
+    def test_unbounded_queue(self, make_dir):
+        a = make_dir("a", {"x": b"1"})
+        b = make_dir("b", {"x": b"2"})
+        assert [e.path for e in komparu.iter_dir(str(a), str(b), max_pending=0)] == ["x"]
+
+    def test_abandoned_bounded_stream_releases_workers(self, make_dir):
+        a = make_dir("a", {f"f{i}": b"A" for i in range(100)})
+        b = make_dir("b", {f"f{i}": b"B" for i in range(100)})
+        for _ in range(20):
+            it = komparu.iter_dir(str(a), str(b), max_pending=1)
+            next(it)
+            it.close()
+        assert len(komparu.compare_dir(str(a), str(b)).diff) == 100
+
+    def test_negative_max_pending(self, make_dir):
+        a = make_dir("a", {"x": b"1"})
+        with pytest.raises(ValueError, match="max_pending"):
+            next(komparu.iter_dir(str(a), str(a), max_pending=-1))
+
+    def test_last_wakeup_not_lost(self, make_dir):
+        """A worker finishing between two polls still ends the stream."""
+        a = make_dir("a", {"x": b"1", "y": b"2", "z": b"3"})
+        b = make_dir("b", {"x": b"1", "y": b"2", "z": b"4"})
+        runs = []
+
+        def loop():
+            for _ in range(50000):
+                runs.append(len(list(komparu.iter_dir(str(a), str(b), include_equal=True))))
+
+        t = threading.Thread(target=loop, daemon=True)
+        t.start()
+        t.join(timeout=60)
+        assert not t.is_alive(), f"stream hung after {len(runs)} runs"
+        assert set(runs) == {3}
+
+
+class TestUnstableFiles:
+    """Files written while they are compared."""
+
+    def test_touched_during_read(self, make_dir):
+        data = os.urandom(1 << 16) * 512
+        a = make_dir("a", {"live.log": data})
+        b = make_dir("b", {"live.log": data})
+        running, stop = threading.Event(), threading.Event()
+
+        def touch() -> None:
+            # Keep moving the mtime, as a writer would, until the comparisons are done
+            n = 0
+            while not stop.is_set():
+                n += 1
+                os.utime(a / "live.log", ns=(n, n))
+                running.set()
+
+        writer = threading.Thread(target=touch)
+        writer.start()
+        running.wait()
+        try:
+            # With one CPU the writer may not get to run during a given read
+            results = [komparu.compare_dir(str(a), str(b), quick_check=False)
+                       for _ in range(20)]
+        finally:
+            stop.set()
+            writer.join()
+        assert {"live.log": DiffReason.UNSTABLE} in [result.diff for result in results]
+        assert all(result.equal or result.diff == {"live.log": DiffReason.UNSTABLE}
+                   for result in results)
+
+    def test_stable_files_unaffected(self, make_dir):
+        a = make_dir("a", {"same": b"1", "diff": b"left"})
+        b = make_dir("b", {"same": b"1", "diff": b"right"})
+        result = komparu.compare_dir(str(a), str(b))
+        assert result.diff == {"diff": DiffReason.SIZE_MISMATCH}
+
+    def test_deleted_after_listing(self, held):
+        a, b, release = held
+
+        def delete() -> None:
+            # free.txt is listed and waits behind busy.db
+            (b / "free.txt").unlink()
+            release()
+
+        threading.Timer(0.2, delete).start()
+        result = komparu.compare_dir(str(a), str(b), max_workers=1, lock=komparu.LockPolicy())
+        assert result.diff == {"free.txt": DiffReason.VANISHED}
+        assert not result.unverifiable
+
+
+@pytest.fixture
+def held(make_dir):
+    """Two equal trees, with b/busy.db under an exclusive lock, and its release."""
+    fcntl = pytest.importorskip("fcntl")
+    a = make_dir("a", {"busy.db": b"1", "free.txt": b"2"})
+    b = make_dir("b", {"busy.db": b"1", "free.txt": b"2"})
+    with open(b / "busy.db", "rb+") as f:
+        fcntl.flock(f, fcntl.LOCK_EX)
+        yield a, b, lambda: fcntl.flock(f, fcntl.LOCK_UN)
+
+
+class TestLockedFiles:
+    """Reading under shared advisory locks."""
+
+    def test_skips_held_file(self, held):
+        a, b, _ = held
+        result = komparu.compare_dir(str(a), str(b), lock=komparu.LockPolicy(timeout=0))
+        assert result.diff == {"busy.db": DiffReason.LOCKED}
+        entries = list(komparu.iter_dir(str(a), str(b), lock=komparu.LockPolicy(timeout=0)))
+        assert [(e.path, e.reason) for e in entries] == [("busy.db", DiffReason.LOCKED)]
+
+    def test_waits_for_writer(self, held):
+        a, b, release = held
+        threading.Timer(0.2, release).start()
+        start = time.monotonic()
+        result = komparu.compare_dir(str(a), str(b), lock=komparu.LockPolicy())
+        assert result.equal is True
+        assert time.monotonic() - start >= 0.15
+
+    def test_unlocked_by_default(self, held):
+        a, b, _ = held
+        assert komparu.compare_dir(str(a), str(b)).equal is True
+
+    def test_policy_validation(self):
+        with pytest.raises(ValueError, match="non-negative"):
+            komparu.LockPolicy(timeout=-1)
+        with pytest.raises(TypeError, match="LockPolicy"):
+            komparu.CompareOptions(lock=1.0)
+
+
+class TestConfinedTraversal:
+    """Without follow_symlinks, reads never leave the compared trees."""
+
+    def test_directory_swapped_for_symlink(self, make_dir, tmp_path):
+        fcntl = pytest.importorskip("fcntl")
+        a = make_dir("a", {"busy": b"0", "sub/f": b"1"})
+        b = make_dir("b", {"busy": b"0", "sub/f": b"1"})
+        make_dir("outside", {"f": b"2"})
+
+        with open(b / "busy", "rb+") as held:
+            fcntl.flock(held, fcntl.LOCK_EX)
+
+            def swap() -> None:
+                # sub/f is listed and waits behind busy; point sub elsewhere
+                (b / "sub").rename(tmp_path / "sub.old")
+                (b / "sub").symlink_to(tmp_path / "outside")
+                fcntl.flock(held, fcntl.LOCK_UN)
+
+            threading.Timer(0.2, swap).start()
+            result = komparu.compare_dir(str(a), str(b), follow_symlinks=False,
+                                         max_workers=1, lock=komparu.LockPolicy())
+        assert result.diff == {"sub/f": DiffReason.READ_ERROR}
+
+    def test_symlinked_root(self, make_dir, tmp_path):
+        a = make_dir("a", {"sub/f": b"1"})
+        make_dir("b", {"sub/f": b"1"})
+        (tmp_path / "link").symlink_to(tmp_path / "b")
+        assert komparu.compare_dir(str(a), str(tmp_path / "link"), follow_symlinks=False).equal
+
+
+class TestLongPaths:
+    """Trees deeper than the Windows MAX_PATH of 260 characters."""
+
+    def test_deep_tree(self, make_dir):
+        deep = "/".join(["node_modules/package"] * 16) + "/index.js"
+        a = make_dir("a", {deep: b"1", "top": b"1"})
+        b = make_dir("b", {deep: b"2", "top": b"1"})
+        assert len(str(a / deep)) > 260
+        assert komparu.compare_dir(str(a), str(b)).diff == {deep: DiffReason.CONTENT_MISMATCH}
+        assert komparu.compare_dir_metadata(str(a), str(b)).equal
+        assert dict(komparu.hash_tree(str(a)))[deep] == dict(komparu.hash_tree(str(b)))["top"]
+
+    def test_extended_length_form(self, monkeypatch):
+        from komparu._helpers import long_path
+
+        long = "C:\\" + "node_modules\\" * 30 + "index.js"
+        assert long_path(long) == long  # only Windows needs it
+        monkeypatch.setattr("sys.platform", "win32")
+        assert long_path(long) == "\\\\?\\" + long
+        assert long_path("C:/short/path") == "C:/short/path"
+        share = "\\\\server\\share\\" + "dir\\" * 70 + "f"
+        assert long_path(share) == "\\\\?\\UNC\\server\\share\\" + "dir\\" * 70 + "f"
+        assert long_path("\\\\?\\" + long) == "\\\\?\\" + long
+
+
+class TestDeepTrees:
+    """Nesting walked with an explicit stack, not the thread's."""
+
+    def test_deep_nesting_small_stack(self, make_dir):
+        deep = "d/" * 250 + "f"
+        a = make_dir("a", {deep: b"1"})
+        b = make_dir("b", {deep: b"2"})
+        results = []
+        old = threading.stack_size(128 * 1024)
+        try:
+            t = threading.Thread(target=lambda: results.append(
+                komparu.compare_dir(str(a), str(b))))
+            t.start()
+            t.join()
+        finally:
+            threading.stack_size(old)
+        assert results[0].diff == {deep: DiffReason.CONTENT_MISMATCH}
+
+    def test_deeper_than_open_dirs(self, make_dir):
+        deep = "d/" * 500
+        files = {deep + "f": b"1", "d/" * 40 + "g": b"2", "d/" * 499 + "h": b"3",
+                 **{"d/" * 5 + f"s{i}": b"%d" % i for i in range(20)}}
+        a = make_dir("a", files)
+        b = make_dir("b", {**files, deep + "f": b"x"})
+        result = komparu.compare_dir(str(a), str(b))
+        assert result.diff == {deep + "f": DiffReason.CONTENT_MISMATCH}
+        assert not result.errors
+        assert len(komparu.plan_dir(str(a), str(b)).pairs) == len(files)
+
+    def test_deep_tree_few_descriptors(self, make_dir):
+        pytest.importorskip("resource")
+        deep = "d/" * 500 + "f"
+        a = make_dir("a", {deep: b"1"})
+        b = make_dir("b", {deep: b"2"})
+        code = (
+            "import resource, sys, komparu\n"
+            "hard = resource.getrlimit(resource.RLIMIT_NOFILE)[1]\n"
+            "resource.setrlimit(resource.RLIMIT_NOFILE, (64, hard))\n"
+            "r = komparu.compare_dir(sys.argv[1], sys.argv[2], max_workers=1)\n"
+            "print(sorted(r.diff), sorted(r.errors))\n"
+        )
+        env = {**os.environ, "PYTHONPATH": os.pathsep.join(sys.path)}
+        out = subprocess.run([sys.executable, "-c", code, str(a), str(b)], env=env,
+                             capture_output=True, text=True, timeout=60)
+        assert out.stdout == f"{[deep]!r} []\n", out.stderr
+
+    def test_symlink_loop(self, make_dir):
+        a = make_dir("a", {"sub/f": b"1"})
+        b = make_dir("b", {"sub/f": b"1"})
+        for root in (a, b):
+            (root / "sub" / "up").symlink_to(root)
+        assert komparu.compare_dir(str(a), str(b)).equal
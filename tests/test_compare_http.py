@@ -167,3 +167,31 @@ class TestHttpRangeSupport:
             httpserver.url_for("/a"),
             httpserver.url_for("/b"),
         ) is True
+
+    def test_short_ranges_not_a_difference(self, httpserver: HTTPServer):
+        """Servers answering a Range with fewer bytes than asked, each a different number."""
+        content = os.urandom(10000)
+
+        def capped(limit: int):
+            def handler(request):
+                range_header = request.headers.get("Range")
+                if not range_header:
+                    resp = Response(content, status=200)
+                    resp.headers["Accept-Ranges"] = "bytes"
+                    return resp
+                start = int(range_header[6:].split("-")[0])
+                chunk = content[start:start + limit]
+                resp = Response(chunk, status=206)
+                end = start + len(chunk) - 1
+                resp.headers["Content-Range"] = f"bytes {start}-{end}/{len(content)}"
+                return resp
+            return handler
+
+        httpserver.expect_request("/a").respond_with_handler(capped(700))
+        httpserver.expect_request("/b").respond_with_handler(capped(1300))
+
+        assert komparu.compare(
+            httpserver.url_for("/a"),
+            httpserver.url_for("/b"),
+            chunk_size=4096,
+        ) is True
@@ -3,11 +3,14 @@
 from __future__ import annotations
 
 import os
+import sys
 from pathlib import Path
 
 import pytest
 
 import komparu
+from komparu._config import reset_config
+from komparu._core import set_mmap_window
 
 
 class TestCompareIdentical:
@@ -190,6 +193,141 @@ class TestCompareEdgeCases:
             komparu.compare(str(link), str(f))
 
 
+def _sparse(path: Path, size: int, data: dict[int, bytes]) -> Path:
+    """A file of *size* bytes that is a hole except for *data* at its offsets."""
+    with open(path, "wb") as f:
+        f.truncate(size)
+        for offset, chunk in data.items():
+            f.seek(offset)
+            f.write(chunk)
+    return path
+
+
+class TestSparseFiles:
+    """Ranges that are holes on both sides are skipped, not read."""
+
+    MB = 1024 * 1024
+
+    def test_equal(self, tmp_dir):
+        layout = {0: b"head", 5 * self.MB: b"middle", 9 * self.MB: b"tail"}
+        a = _sparse(tmp_dir / "a", 10 * self.MB, layout)
+        b = _sparse(tmp_dir / "b", 10 * self.MB, layout)
+        assert komparu.compare(str(a), str(b), quick_check=False) is True
+
+    def test_difference_after_hole(self, tmp_dir):
+        a = _sparse(tmp_dir / "a", 10 * self.MB, {0: b"head", 9 * self.MB: b"tail"})
+        b = _sparse(tmp_dir / "b", 10 * self.MB, {0: b"head", 9 * self.MB: b"TAIL"})
+        assert komparu.compare(str(a), str(b), quick_check=False) is False
+
+    def test_hole_against_zeros(self, tmp_dir):
+        a = _sparse(tmp_dir / "a", 4 * self.MB, {3 * self.MB: b"x"})
+        b = tmp_dir / "b"
+        b.write_bytes(bytes(3 * self.MB) + b"x" + bytes(self.MB - 1))
+        assert komparu.compare(str(a), str(b), quick_check=False) is True
+        b.write_bytes(bytes(self.MB) + b"y" + bytes(2 * self.MB - 1) + b"x" + bytes(self.MB - 1))
+        assert komparu.compare(str(a), str(b), quick_check=False) is False
+
+    def test_different_layouts(self, tmp_dir):
+        a = _sparse(tmp_dir / "a", 6 * self.MB, {self.MB: b"a", 4 * self.MB: b"b"})
+        b = _sparse(tmp_dir / "b", 6 * self.MB, {self.MB: b"a"})
+        assert komparu.compare(str(a), str(b), quick_check=False) is False
+        with open(b, "r+b") as f:
+            f.seek(4 * self.MB)
+            f.write(b"b")
+        assert komparu.compare(str(a), str(b), chunk_size=4096, quick_check=False) is True
+
+    def test_directories(self, tmp_dir):
+        for side in ("a", "b"):
+            (tmp_dir / side).mkdir()
+            _sparse(tmp_dir / side / "disk.img", 8 * self.MB, {7 * self.MB: side.encode()})
+        result = komparu.compare_dir(str(tmp_dir / "a"), str(tmp_dir / "b"), quick_check=False)
+        assert result.diff == {"disk.img": komparu.DiffReason.CONTENT_MISMATCH}
+
+
+class TestVerifyZero:
+    """verify_zero() compares against a source of zeros of the same size."""
+
+    MB = 1024 * 1024
+
+    def test_sparse(self, tmp_dir):
+        img = _sparse(tmp_dir / "img", 8 * self.MB, {})
+        assert komparu.verify_zero(str(img)) is True
+        _sparse(img, 8 * self.MB, {6 * self.MB: bytes(4096)})
+        assert komparu.verify_zero(str(img), quick_check=False) is True
+        _sparse(img, 8 * self.MB, {6 * self.MB + 7: b"\x01"})
+        assert komparu.verify_zero(str(img), quick_check=False) is False
+
+    def test_dense(self, make_file):
+        assert komparu.verify_zero(str(make_file("empty", b""))) is True
+        assert komparu.verify_zero(str(make_file("z", bytes(100_000))), chunk_size=4096) is True
+        assert komparu.verify_zero(str(make_file("nz", bytes(99_999) + b"x"))) is False
+
+    @pytest.mark.skipif(sys.platform == "win32", reason="no /dev/zero")
+    def test_not_a_block_device(self, tmp_dir):
+        with pytest.raises(OSError, match="block device"):
+            komparu.verify_zero("/dev/zero")
+        with pytest.raises(FileNotFoundError):
+            komparu.verify_zero(str(tmp_dir / "missing"))
+
+
+@pytest.mark.skipif(not os.path.exists("/proc/version"), reason="needs procfs")
+class TestVirtualFiles:
+    """/proc files report size 0 but have content: it is read, not assumed."""
+
+    def test_content_is_read(self, make_file):
+        content = Path("/proc/version").read_bytes()
+        assert komparu.compare("/proc/version", str(make_file("copy", content))) is True
+        assert komparu.compare("/proc/version", str(make_file("empty", b""))) is False
+        assert komparu.compare("/proc/version", "/proc/version", quick_check=False) is True
+
+    def test_directories(self, tmp_dir):
+        for side in ("a", "b"):
+            (tmp_dir / side).mkdir()
+        (tmp_dir / "b" / "version").write_bytes(b"")
+        os.symlink("/proc/version", tmp_dir / "a" / "version")
+        result = komparu.compare_dir(str(tmp_dir / "a"), str(tmp_dir / "b"))
+        assert result.diff == {"version": komparu.DiffReason.CONTENT_MISMATCH}
+
+    def test_verify_zero_refused(self):
+        with pytest.raises(OSError, match="size unknown"):
+            komparu.verify_zero("/proc/version")
+
+
+class TestMmapWindow:
+    """Files larger than the mapping window are read through a sliding one."""
+
+    def setup_method(self):
+        komparu.configure(mmap_window=1)
+
+    def teardown_method(self):
+        reset_config()
+
+    def test_rounded_to_pages(self):
+        assert set_mmap_window(1) == os.sysconf("SC_PAGESIZE")
+        with pytest.raises(ValueError, match="non-negative"):
+            komparu.configure(mmap_window=-1)
+        assert set_mmap_window(0) == 1 << 30 if sys.maxsize > 2**32 else 64 << 20
+
+    @pytest.mark.parametrize("offset", [0, 4095, 4096, 5 * 4096 + 7, 10 * 4096 + 99])
+    def test_difference_found(self, make_file, offset):
+        content = bytes(range(256)) * 161
+        changed = bytearray(content)
+        changed[offset] ^= 0xFF
+        a = make_file("a.bin", content)
+        assert komparu.compare(str(a), str(make_file("b.bin", content)), chunk_size=3000,
+                               quick_check=False) is True
+        assert komparu.compare(str(a), str(make_file("c.bin", bytes(changed))), chunk_size=3000,
+                               quick_check=False) is False
+
+    def test_chunk_larger_than_window(self, make_file):
+        content = os.urandom(100_000)
+        a, b = make_file("a.bin", content), make_file("b.bin", content + b"!")
+        assert komparu.compare(str(a), str(make_file("c.bin", content)),
+                               chunk_size=65536) is True
+        assert komparu.compare(str(b), str(make_file("d.bin", content + b"?")),
+                               chunk_size=65536) is False
+
+
 class TestCompareOptions:
     """Test comparison options."""
 
@@ -282,6 +420,17 @@ class TestUnicodeFilePaths:
         a = make_file("\u0444\u0430\u0439\u043b.txt", b"self compare")
         assert komparu.compare(str(a), str(a)) is True
 
+    @pytest.mark.skipif(not sys.platform.startswith("linux"), reason="names must be UTF-8")
+    def test_undecodable_filename(self, make_file):
+        """Names that are not UTF-8, as os.fsdecode() gives them, reach the file."""
+        a = make_file(os.fsdecode(b"caf\xe9_a.txt"), b"latin-1")
+        b = make_file(os.fsdecode(b"caf\xe9_b.txt"), b"latin-2")
+        assert komparu.compare(str(a), str(a)) is True
+        assert komparu.compare(str(a), str(b)) is False
+        with pytest.raises(FileNotFoundError) as exc:
+            komparu.compare(str(a) + "x", str(b))
+        assert exc.value.filename == str(a) + "x"
+
 
 # ---- New tests: Special character file paths ----
 
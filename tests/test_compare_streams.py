@@ -0,0 +1,136 @@
+"""Tests for comparing binary file-like objects."""
+
+from __future__ import annotations
+
+import io
+
+import pytest
+
+import komparu
+
+
+class TrickleStream(io.RawIOBase):
+    """Non-seekable stream that returns at most `step` bytes per read."""
+
+    def __init__(self, data: bytes, step: int = 7) -> None:
+        self._data = data
+        self._pos = 0
+        self._step = step
+
+    def readable(self) -> bool:
+        return True
+
+    def readinto(self, b) -> int:
+        n = min(len(b), self._step, len(self._data) - self._pos)
+        b[:n] = self._data[self._pos:self._pos + n]
+        self._pos += n
+        return n
+
+
+class StallingStream(TrickleStream):
+    """Non-blocking stream with no data for the first `stalls` reads after each one that has."""
+
+    def __init__(self, data: bytes, stalls: int = 1) -> None:
+        super().__init__(data, step=100)
+        self._stalls = stalls
+        self._left = stalls
+
+    def readinto(self, b) -> int | None:
+        if self._left:
+            self._left -= 1
+            return None
+        self._left = self._stalls
+        return super().readinto(b)
+
+
+class FailingStream(io.RawIOBase):
+    """Stream whose reads always fail."""
+
+    def readable(self) -> bool:
+        return True
+
+    def readinto(self, b) -> int:
+        raise RuntimeError("backend exploded")
+
+
+class TestCompareStreams:
+    """compare_streams over in-memory and custom streams."""
+
+    def test_bytesio_equal(self):
+        data = b"x" * 200_000
+        assert komparu.compare_streams(io.BytesIO(data), io.BytesIO(data)) is True
+
+    def test_bytesio_different(self):
+        a = io.BytesIO(b"a" * 1000 + b"1")
+        b = io.BytesIO(b"a" * 1000 + b"2")
+        assert komparu.compare_streams(a, b) is False
+
+    def test_different_length(self):
+        assert komparu.compare_streams(io.BytesIO(b"abc"), io.BytesIO(b"abcd")) is False
+
+    def test_empty(self):
+        assert komparu.compare_streams(io.BytesIO(), io.BytesIO()) is True
+
+    def test_open_files(self, make_file):
+        a = make_file("a.bin", b"payload" * 1000)
+        b = make_file("b.bin", b"payload" * 1000)
+        with open(a, "rb") as fa, open(b, "rb") as fb:
+            assert komparu.compare_streams(fa, fb) is True
+
+    def test_short_reads_not_a_difference(self):
+        data = bytes(range(256)) * 100
+        a = TrickleStream(data, step=7)
+        b = TrickleStream(data, step=13)
+        assert komparu.compare_streams(a, b, chunk_size=64) is True
+
+    def test_transient_no_data_retried(self):
+        data = bytes(range(256)) * 20
+        assert komparu.compare_streams(StallingStream(data), io.BytesIO(data)) is True
+        assert komparu.compare_streams(StallingStream(data, stalls=3),
+                                       StallingStream(data)) is True
+
+    def test_stream_that_stays_empty(self):
+        with pytest.raises(BlockingIOError):
+            komparu.compare_streams(StallingStream(b"data", stalls=100), io.BytesIO(b"data"))
+
+    def test_stream_vs_bytesio(self):
+        data = b"hello world" * 50
+        assert komparu.compare_streams(TrickleStream(data), io.BytesIO(data)) is True
+
+    def test_starts_at_current_position(self):
+        a = io.BytesIO(b"HEADERpayload")
+        a.seek(6)
+        assert komparu.compare_streams(a, io.BytesIO(b"payload")) is True
+
+    def test_stream_exception_propagates(self):
+        with pytest.raises(RuntimeError, match="backend exploded"):
+            komparu.compare_streams(FailingStream(), io.BytesIO(b"data"))
+
+    def test_not_a_stream(self):
+        with pytest.raises(TypeError):
+            komparu.compare_streams(b"bytes", io.BytesIO(b"bytes"))
+
+    def test_invalid_chunk_size(self):
+        with pytest.raises(ValueError):
+            komparu.compare_streams(io.BytesIO(), io.BytesIO(), chunk_size=0)
+
+    def test_options(self):
+        options = komparu.CompareOptions(chunk_size=4, quick_check=False)
+        assert komparu.compare_streams(io.BytesIO(b"abcdefgh"), io.BytesIO(b"abcdefgh"),
+                                       options=options) is True
+        with pytest.raises(TypeError, match="chunk_size="):
+            komparu.compare_streams(io.BytesIO(), io.BytesIO(), chunk_size=8, options=options)
+
+    def test_cancelled(self):
+        token = komparu.CancelToken()
+        token.cancel()
+        with pytest.raises(komparu.ComparisonCancelledError):
+            komparu.compare_streams(io.BytesIO(b"x" * 1000), io.BytesIO(b"x" * 1000),
+                                    cancel=token)
+
+    def test_quota(self):
+        token = komparu.CancelToken(max_bytes=100)
+        with pytest.raises(komparu.QuotaReachedError):
+            komparu.compare_streams(io.BytesIO(b"x" * 100_000), io.BytesIO(b"x" * 100_000),
+                                    chunk_size=4096, cancel=token)
+        assert token.bytes_read >= 100
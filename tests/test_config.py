@@ -55,3 +55,39 @@ class TestConfigure:
         komparu.configure(chunk_size=999)
         reset_config()
         assert get_config().chunk_size == 65536
+
+
+class TestFips:
+
+    def setup_method(self):
+        reset_config()
+        komparu.configure(fips=True)
+
+    def teardown_method(self):
+        reset_config()
+
+    def test_hashes(self, tmp_path):
+        (tmp_path / "f").write_bytes(b"data")
+        for algorithm in ("md5", "blake3", "xxh3", "crc32"):
+            with pytest.raises(ValueError, match="not FIPS-approved"):
+                list(komparu.hash_tree(str(tmp_path), algorithm=algorithm))
+        assert list(komparu.hash_tree(str(tmp_path), algorithm="sha384"))
+
+    def test_blake2b_formats(self, tmp_path):
+        (tmp_path / "a").write_bytes(b"1")
+        (tmp_path / "b").write_bytes(b"2")
+        with pytest.raises(ValueError, match="komparu patches needs blake2b"):
+            komparu.make_patch(str(tmp_path / "a"), str(tmp_path / "b"), str(tmp_path / "p"))
+        assert not (tmp_path / "p").exists()
+        assert komparu.chunk_diff(str(tmp_path / "a"), str(tmp_path / "b")).shared_chunks == 0
+
+    def test_cli(self, tmp_path, monkeypatch, capsys):
+        from komparu._cli import main
+
+        reset_config()
+        assert main(["--fips", "hash", "-a", "blake3", str(tmp_path)]) == 2
+        assert "not FIPS-approved" in capsys.readouterr().err
+        assert get_config().fips is False
+        monkeypatch.setenv("KOMPARU_FIPS", "1")
+        assert main(["hash", "-a", "md5", str(tmp_path)]) == 2
+        assert main(["hash", str(tmp_path)]) == 0
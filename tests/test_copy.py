@@ -0,0 +1,68 @@
+"""Tests for verified copies."""
+
+from __future__ import annotations
+
+import hashlib
+import os
+from pathlib import Path
+
+import pytest
+
+import komparu
+from komparu import _copy
+
+
+class TestCopyVerified:
+    def test_copy(self, tmp_path: Path):
+        src = tmp_path / "src.bin"
+        src.write_bytes(os.urandom(300_000))
+        src.chmod(0o640)
+        dst = tmp_path / "dst.bin"
+        digest = komparu.copy_verified(str(src), str(dst), chunk_size=4096)
+        assert digest == hashlib.sha256(src.read_bytes()).hexdigest()
+        assert dst.read_bytes() == src.read_bytes()
+        assert dst.stat().st_mode & 0o777 == 0o640
+
+    def test_algorithm(self, tmp_path: Path):
+        src = tmp_path / "src"
+        src.write_bytes(b"data")
+        digest = komparu.copy_verified(str(src), str(tmp_path / "dst"), algorithm="crc32c")
+        assert len(digest) == 8
+
+    def test_mismatch(self, tmp_path: Path, monkeypatch):
+        src = tmp_path / "src"
+        src.write_bytes(b"data")
+        monkeypatch.setattr(_copy, "file_digest", lambda path, algorithm: "00")
+        with pytest.raises(komparu.CopyVerifyError, match="does not match") as e:
+            komparu.copy_verified(str(src), str(tmp_path / "dst"))
+        assert e.value.filename == str(tmp_path / "dst")
+
+    def test_missing_source(self, tmp_path: Path):
+        with pytest.raises(FileNotFoundError):
+            komparu.copy_verified(str(tmp_path / "missing"), str(tmp_path / "dst"))
+
+
+class TestCopyTreeVerified:
+    def test_tree(self, tmp_path: Path):
+        src = tmp_path / "src"
+        (src / "sub" / "empty").mkdir(parents=True)
+        (src / "a").write_bytes(b"one")
+        (src / "sub" / "b").write_bytes(b"two")
+        os.symlink("a", src / "link")
+        dst = tmp_path / "dst"
+        copied = list(komparu.copy_tree_verified(str(src), str(dst), follow_symlinks=False))
+        assert copied == [("a", hashlib.sha256(b"one").hexdigest()),
+                          ("sub/b", hashlib.sha256(b"two").hexdigest())]
+        assert (dst / "sub" / "empty").is_dir()
+        assert os.readlink(dst / "link") == "a"
+        assert komparu.compare_dir(str(src), str(dst)).equal
+
+    def test_ignore(self, tmp_path: Path):
+        src = tmp_path / "src"
+        src.mkdir()
+        (src / "keep").write_bytes(b"1")
+        (src / "skip.log").write_bytes(b"2")
+        dst = tmp_path / "dst"
+        assert [p for p, _ in komparu.copy_tree_verified(str(src), str(dst),
+                                                         ignore=["*.log"])] == ["keep"]
+        assert not (dst / "skip.log").exists()
@@ -0,0 +1,147 @@
+"""Tests for content-defined chunk alignment."""
+
+from __future__ import annotations
+
+import random
+
+import pytest
+
+import komparu
+
+
+def noise(size: int = 300_000, seed: int = 1) -> bytes:
+    return random.Random(seed).randbytes(size)
+
+
+class TestChunkDiff:
+    def test_identical(self, make_file):
+        a = make_file("a.bin", noise())
+        diff = komparu.chunk_diff(str(a), str(a))
+        assert diff.shared_chunks == diff.chunks_a == diff.chunks_b
+        assert diff.shared_bytes == diff.size_a == 300_000
+        assert (diff.insertions, diff.deletions, diff.changes) == (0, 0, 0)
+        assert diff.shared_fraction == 1.0
+
+    def test_insertion(self, make_file):
+        # Block-aligned comparison would find everything after offset 1000 differs
+        data = noise()
+        a = make_file("a.bin", data)
+        b = make_file("b.bin", data[:1000] + b"inserted" + data[1000:])
+        diff = komparu.chunk_diff(str(a), str(b))
+        assert (diff.insertions, diff.deletions, diff.changes) == (1, 0, 0)
+        assert diff.size_b == diff.size_a + 8
+        assert diff.shared_fraction > 0.9
+
+    def test_deletion(self, make_file):
+        data = noise()
+        a = make_file("a.bin", data)
+        b = make_file("b.bin", data[:150_000] + data[150_100:])
+        diff = komparu.chunk_diff(str(a), str(b))
+        assert (diff.insertions, diff.deletions, diff.changes) == (0, 1, 0)
+        assert diff.shared_fraction > 0.9
+
+    def test_change(self, make_file):
+        data = noise()
+        a = make_file("a.bin", data)
+        b = make_file("b.bin", data[:100_000] + b"XXXX" + data[100_004:])
+        diff = komparu.chunk_diff(str(a), str(b))
+        assert (diff.insertions, diff.deletions, diff.changes) == (0, 0, 1)
+        assert diff.size_a == diff.size_b
+
+    def test_several_edits(self, make_file):
+        data = noise()
+        a = make_file("a.bin", data)
+        b = make_file("b.bin", data[:50_000] + b"new" + data[50_000:200_000] + data[200_050:])
+        diff = komparu.chunk_diff(str(a), str(b))
+        assert (diff.insertions, diff.deletions) == (1, 1)
+
+    def test_unrelated(self, make_file):
+        a = make_file("a.bin", noise(seed=1))
+        b = make_file("b.bin", noise(seed=2))
+        diff = komparu.chunk_diff(str(a), str(b))
+        assert diff.shared_chunks == 0
+        assert diff.shared_fraction == 0.0
+
+    def test_regions(self, make_file):
+        data = noise()
+        a = make_file("a.bin", data)
+        b = make_file("b.bin", data[:50_000] + b"new" + data[50_000:100_000] + b"XXXX"
+                      + data[100_004:200_000] + data[200_050:])
+        regions = komparu.chunk_diff(str(a), str(b)).regions
+        assert regions == (
+            komparu.DiffRegion(komparu.RegionKind.INSERTED, 50_000, 0, 50_000, 3),
+            komparu.DiffRegion(komparu.RegionKind.CHANGED, 100_000, 4, 100_003, 4),
+            komparu.DiffRegion(komparu.RegionKind.DELETED, 200_000, 50, 200_003, 0),
+        )
+
+    def test_truncated(self, make_file):
+        data = noise()
+        a = make_file("a.bin", data)
+        b = make_file("b.bin", data[:-1000])
+        [region] = komparu.chunk_diff(str(a), str(b)).regions
+        assert region == komparu.DiffRegion(komparu.RegionKind.DELETED, 299_000, 1000,
+                                            299_000, 0)
+
+    def test_identical_has_no_regions(self, make_file):
+        a = make_file("a.bin", noise())
+        assert komparu.chunk_diff(str(a), str(a)).regions == ()
+
+    def test_chunk_sizes(self, make_file):
+        a = make_file("a.bin", noise())
+        small = komparu.chunk_diff(str(a), str(a), avg_chunk_size=1024)
+        large = komparu.chunk_diff(str(a), str(a), avg_chunk_size=32768)
+        assert small.chunks_a > large.chunks_a > 1
+
+    def test_empty(self, make_file):
+        empty = make_file("e.bin", b"")
+        full = make_file("f.bin", noise(10_000))
+        assert komparu.chunk_diff(str(empty), str(empty)).shared_fraction == 1.0
+        diff = komparu.chunk_diff(str(empty), str(full))
+        assert diff.chunks_a == 0
+        assert diff.insertions == 1
+        assert diff.shared_fraction == 0.0
+
+    def test_invalid_chunk_size(self, make_file):
+        a = make_file("a.bin", b"a")
+        with pytest.raises(ValueError, match="avg_chunk_size"):
+            komparu.chunk_diff(str(a), str(a), avg_chunk_size=16)
+
+    def test_missing_file(self, tmp_path, make_file):
+        a = make_file("a.bin", b"a")
+        with pytest.raises(OSError):
+            komparu.chunk_diff(str(a), str(tmp_path / "nope"))
+
+
+class TestDeltaSize:
+    def test_identical(self, make_file):
+        a = make_file("a.bin", noise())
+        assert komparu.delta_size(str(a), str(a)) == 0
+
+    def test_small_edit(self, make_file):
+        data = noise()
+        a = make_file("a.bin", data)
+        b = make_file("b.bin", data[:1000] + b"inserted" + data[1000:])
+        assert 8 < komparu.delta_size(str(a), str(b)) < 64
+
+    def test_moved_content(self, make_file):
+        # Swapping the halves resends nothing but the bytes around the seam
+        data = noise()
+        a = make_file("a.bin", data)
+        b = make_file("b.bin", data[150_000:] + data[:150_000])
+        assert komparu.delta_size(str(a), str(b)) < 20_000
+
+    def test_unrelated(self, make_file):
+        a = make_file("a.bin", noise(seed=1))
+        b = make_file("b.bin", noise(100_000, seed=2))
+        assert komparu.delta_size(str(a), str(b)) > 100_000
+
+    def test_empty(self, make_file):
+        empty = make_file("e.bin", b"")
+        full = make_file("f.bin", noise(10_000))
+        assert komparu.delta_size(str(empty), str(full)) > 10_000
+        assert komparu.delta_size(str(full), str(empty)) == 0
+
+    def test_invalid_chunk_size(self, make_file):
+        a = make_file("a.bin", b"a")
+        with pytest.raises(ValueError, match="avg_chunk_size"):
+            komparu.delta_size(str(a), str(a), avg_chunk_size=0)
@@ -0,0 +1,155 @@
+"""Tests for the warm DirCache shared across directory comparisons."""
+
+from __future__ import annotations
+
+import os
+import time
+from pathlib import Path
+
+import pytest
+
+import komparu
+import komparu.aio
+from komparu import Comparator, CompareOptions, DiffReason, DirCache
+
+
+def age(*paths: Path, seconds: float = 3600) -> None:
+    """Backdate mtime so the files are old enough to be cached."""
+    past = time.time() - seconds
+    for path in paths:
+        os.utime(path, (past, past))
+
+
+@pytest.fixture
+def tree_files() -> dict[str, bytes]:
+    return {
+        "a/same.txt": b"same",
+        "b/same.txt": b"same",
+        "a/sub/also.txt": b"also",
+        "b/sub/also.txt": b"also",
+        "a/diff.txt": b"one",
+        "b/diff.txt": b"two",
+        "a/left.txt": b"l",
+    }
+
+
+@pytest.fixture
+def trees(trees):
+    age(*(path for root in trees for path in root.rglob("*") if path.is_file()))
+    return trees
+
+
+class TestDirCache:
+    def test_empty(self):
+        cache = DirCache()
+        assert len(cache) == 0
+        assert cache.hits == 0
+
+    def test_max_entries_validated(self):
+        with pytest.raises(ValueError, match="positive"):
+            DirCache(max_entries=0)
+        with pytest.raises(TypeError, match="int"):
+            DirCache(max_entries="10")
+
+    def test_rejects_non_cache(self, trees):
+        a, b = trees
+        with pytest.raises(TypeError, match="DirCache"):
+            komparu.compare_dir(str(a), str(b), cache={})
+
+
+class TestCompareDir:
+    def test_remembers_equal_pairs(self, trees):
+        a, b = trees
+        cache = DirCache()
+        first = komparu.compare_dir(str(a), str(b), cache=cache)
+        assert len(cache) == 2
+        assert cache.hits == 0
+
+        second = komparu.compare_dir(str(a), str(b), cache=cache)
+        assert second == first
+        assert second.diff == {"diff.txt": DiffReason.CONTENT_MISMATCH}
+        assert cache.hits == 2
+
+    def test_change_is_detected(self, trees):
+        a, b = trees
+        cache = DirCache()
+        komparu.compare_dir(str(a), str(b), cache=cache)
+
+        # Same size and restored mtime: only ctime/inode reveal the write
+        target = b / "same.txt"
+        st = target.stat()
+        target.write_bytes(b"SAME")
+        os.utime(target, ns=(st.st_atime_ns, st.st_mtime_ns))
+
+        result = komparu.compare_dir(str(a), str(b), cache=cache)
+        assert result.diff == {"same.txt": DiffReason.CONTENT_MISMATCH,
+                               "diff.txt": DiffReason.CONTENT_MISMATCH}
+        assert len(cache) == 1
+
+    def test_recent_files_not_remembered(self, trees):
+        a, b = trees
+        (a / "same.txt").write_bytes(b"same")
+        cache = DirCache()
+        komparu.compare_dir(str(a), str(b), cache=cache)
+        assert len(cache) == 1  # only sub/also.txt
+
+    def test_clear(self, trees):
+        a, b = trees
+        cache = DirCache()
+        komparu.compare_dir(str(a), str(b), cache=cache)
+        komparu.compare_dir(str(a), str(b), cache=cache)
+        cache.clear()
+        assert len(cache) == 0
+        assert cache.hits == 0
+
+    def test_max_entries(self, trees):
+        a, b = trees
+        cache = DirCache(max_entries=1)
+        komparu.compare_dir(str(a), str(b), cache=cache)
+        assert len(cache) == 1
+
+    def test_via_options_and_stream(self, trees):
+        a, b = trees
+        cache = DirCache()
+        opts = CompareOptions(max_workers=2)
+        komparu.compare_dir(str(a), str(b), options=opts, cache=cache)
+        updates = []
+        komparu.compare_dir(str(a), str(b), options=opts, cache=cache,
+                            progress=updates.append)
+        assert cache.hits == 2
+        assert updates[-1].files_done == 4
+
+    def test_iter_dir(self, trees):
+        a, b = trees
+        cache = DirCache()
+        list(komparu.iter_dir(str(a), str(b), cache=cache))
+        entries = {e.path: e.kind for e in
+                   komparu.iter_dir(str(a), str(b), include_equal=True, cache=cache)}
+        assert entries["same.txt"] == komparu.EntryKind.EQUAL
+        assert cache.hits == 2
+
+
+class TestComparator:
+    def test_bound_cache(self, trees):
+        a, b = trees
+        cache = DirCache()
+        comparator = Comparator(cache=cache)
+        assert comparator.cache is cache
+        comparator.compare_dir(str(a), str(b))
+        comparator.compare_dir(str(a), str(b))
+        assert cache.hits == 2
+
+    def test_rejects_non_cache(self):
+        with pytest.raises(TypeError, match="DirCache"):
+            Comparator(cache=object())
+
+
+class TestAsync:
+    @pytest.mark.asyncio
+    async def test_iter_dir(self, trees):
+        a, b = trees
+        cache = DirCache()
+        for _ in range(2):
+            paths = {e.path async for e in komparu.aio.iter_dir(str(a), str(b), cache=cache)}
+            assert paths == {"diff.txt", "left.txt"}
+        assert cache.hits == 2
@@ -0,0 +1,852 @@
+"""Tests for comparing directories inside disk and disc images."""
+
+from __future__ import annotations
+
+import binascii
+import os
+import shutil
+import struct
+import subprocess
+from collections.abc import Iterator
+from pathlib import Path
+from typing import Any
+
+import pytest
+
+from komparu import (
+    ArchiveError,
+    DiffReason,
+    SourceNotFoundError,
+    UnsupportedSourceError,
+    compare_disk_image_dir,
+    compare_disk_images,
+)
+from komparu._cli import main
+
+BIG = bytes(range(256)) * 5000  # spans many clusters and indirect blocks
+
+TREE = {
+    "hello.txt": b"hello",
+    "README": b"plain 8.3 name",
+    "A Long File Name.data": BIG,
+    "empty": b"",
+    "sub/deep/needle": b"deep",
+    "sub/Ünïcödé name": b"unicode",
+}
+
+needs_mke2fs = pytest.mark.skipif(shutil.which("mke2fs") is None, reason="needs mke2fs")
+
+
+def write_tree(root: Path, files: dict[str, bytes]) -> Path:
+    for rel, content in files.items():
+        path = root / rel
+        path.parent.mkdir(parents=True, exist_ok=True)
+        path.write_bytes(content)
+    return root
+
+
+def nested(files: dict[str, bytes]) -> dict:
+    tree: dict = {}
+    for rel, content in files.items():
+        *dirs, name = rel.split("/")
+        node = tree
+        for d in dirs:
+            node = node.setdefault(d, {})
+        node[name] = content
+    return tree
+
+
+def ext_image(tmp_path: Path, files: dict[str, bytes], kind: str = "ext4",
+              name: str = "fs.img") -> Path:
+    src = write_tree(tmp_path / f"{name}.src", files)
+    (src / "link").symlink_to("hello.txt")
+    image = tmp_path / name
+    subprocess.run(["mke2fs", "-q", "-F", "-t", kind, "-b", "1024", "-d", str(src),
+                    str(image), "8M"], check=True, capture_output=True)
+    return image
+
+
+# ---- FAT12/16/32 ----
+
+def _short_name(name: str, taken: set[bytes]) -> tuple[bytes, bool]:
+    """An 8.3 name for *name*, and whether it needs long-name entries."""
+    base, dot, ext = name.rpartition(".") if "." in name else (name, "", "")
+    if (name == name.upper() and name.isascii() and " " not in name
+            and 0 < len(base) <= 8 and len(ext) <= 3 and base.count(".") == 0):
+        return base.encode().ljust(8) + ext.encode().ljust(3), False
+    clean = "".join(c for c in base.upper() if c.isascii() and c.isalnum())[:6] or "X"
+    for n in range(1, 100):
+        short = f"{clean}~{n}".encode().ljust(8) + ext.upper()[:3].encode().ljust(3)
+        if short not in taken:
+            return short, True
+    raise AssertionError(name)
+
+
+def _checksum(short: bytes) -> int:
+    total = 0
+    for byte in short:
+        total = (((total & 1) << 7) + (total >> 1) + byte) & 0xFF
+    return total
+
+
+def _fat_entries(name: str, short: bytes, lfn: bool, attr: int, cluster: int,
+                 size: int) -> bytes:
+    out = b""
+    if lfn:
+        units = name.encode("utf-16-le") + b"\x00\x00"
+        units += b"\xff" * (-len(units) % 26)
+        parts = [units[i:i + 26] for i in range(0, len(units), 26)]
+        for number in range(len(parts), 0, -1):
+            part = parts[number - 1]
+            order = number | (0x40 if number == len(parts) else 0)
+            out += (bytes([order]) + part[:10] + bytes([0x0F, 0, _checksum(short)])
+                    + part[10:22] + b"\x00\x00" + part[22:26])
+    return out + short + struct.pack("<BBBHHHHHHHI", attr, 0, 0, 0, 0, 0, cluster >> 16,
+                                     0, 0, cluster & 0xFFFF, size)
+
+
+class FatBuilder:
+    def __init__(self, bits: int) -> None:
+        self.bits = bits
+        self.bps, self.spc = 512, 1
+        self.sectors = {12: 2880, 16: 20480, 32: 70000}[bits]
+        self.root_entries = 0 if bits == 32 else 224
+        self.fat_sectors = {12: 9, 16: 80, 32: 545}[bits]
+        self.reserved = 32 if bits == 32 else 1
+        self.image = bytearray(self.sectors * self.bps)
+        root_sectors = self.root_entries * 32 // self.bps
+        self.root_offset = (self.reserved + 2 * self.fat_sectors) * self.bps
+        self.heap = self.root_offset + root_sectors * self.bps
+        self.next_cluster = 2
+
+    def set_fat(self, cluster: int, value: int) -> None:
+        base = self.reserved * self.bps
+        for copy in range(2):
+            fat = base + copy * self.fat_sectors * self.bps
+            if self.bits == 12:
+                at = fat + cluster + cluster // 2
+                old, = struct.unpack_from("<H", self.image, at)
+                if cluster & 1:
+                    new = (old & 0x000F) | (value & 0xFFF) << 4
+                else:
+                    new = (old & 0xF000) | (value & 0xFFF)
+                struct.pack_into("<H", self.image, at, new)
+            elif self.bits == 16:
+                struct.pack_into("<H", self.image, fat + cluster * 2, value & 0xFFFF)
+            else:
+                struct.pack_into("<I", self.image, fat + cluster * 4, value & 0x0FFFFFFF)
+
+    def allocate(self, data: bytes, minimum: int = 0) -> int:
+        count = max(-(-len(data) // self.bps), minimum)
+        if not count:
+            return 0
+        first = self.next_cluster
+        self.next_cluster += count
+        for i in range(count):
+            last = i == count - 1
+            self.set_fat(first + i, 0xFFFFFFF if last else first + i + 1)
+        at = self.heap + (first - 2) * self.bps
+        self.image[at:at + len(data)] = data
+        return first
+
+    def directory(self, tree: dict, cluster: int | None, parent: int) -> bytes:
+        taken: set[bytes] = set()
+        body = b""
+        for name, content in tree.items():
+            short, lfn = _short_name(name, taken)
+            taken.add(short)
+            if isinstance(content, dict):
+                count = 2 + sum(1 + len(n.encode("utf-16-le")) // 26 + 1 for n in content)
+                first = self.next_cluster
+                self.next_cluster += -(-count * 32 // self.bps)
+                data = self.directory(content, first, cluster or 0)
+                for i in range(-(-count * 32 // self.bps)):
+                    self.set_fat(first + i, first + i + 1)
+                self.set_fat(first - 1 + -(-count * 32 // self.bps), 0xFFFFFFF)
+                at = self.heap + (first - 2) * self.bps
+                self.image[at:at + len(data)] = data
+                body += _fat_entries(name, short, lfn, 0x10, first, 0)
+            else:
+                body += _fat_entries(name, short, lfn, 0x20, self.allocate(content), len(content))
+        if cluster is not None:
+            dots = (b".".ljust(11) + struct.pack("<BBBHHHHHHHI", 0x10, 0, 0, 0, 0, 0, 0, 0, 0,
+                                                 cluster, 0)
+                    + b"..".ljust(11) + struct.pack("<BBBHHHHHHHI", 0x10, 0, 0, 0, 0, 0, 0, 0,
+                                                    0, parent, 0))
+            body = dots + body
+        return body
+
+    def build(self, files: dict[str, bytes]) -> bytes:
+        boot = bytearray(512)
+        boot[0:3] = b"\xeb\x3c\x90"
+        struct.pack_into("<HBHBHHBHHHII", boot, 11, self.bps, self.spc, self.reserved, 2,
+                         self.root_entries, self.sectors if self.sectors < 65536 else 0, 0xF8,
+                         0 if self.bits == 32 else self.fat_sectors, 63, 255, 0,
+                         self.sectors if self.sectors >= 65536 else 0)
+        if self.bits == 32:
+            struct.pack_into("<II", boot, 36, self.fat_sectors, 0)
+        boot[510:512] = b"\x55\xaa"
+        self.image[0:512] = boot
+        self.set_fat(0, 0xFFFFFF8)
+        self.set_fat(1, 0xFFFFFFF)
+        tree = nested(files)
+        if self.bits == 32:
+            root = self.next_cluster
+            self.next_cluster += 4
+            for i in range(3):
+                self.set_fat(root + i, root + i + 1)
+            self.set_fat(root + 3, 0xFFFFFFF)
+            body = _fat_entries("VOLUME", b"VOLUME     ", False, 0x08, 0, 0)
+            body += self.directory(tree, None, 0)
+            struct.pack_into("<I", self.image, 44, root)
+            at = self.heap + (root - 2) * self.bps
+        else:
+            body = self.directory(tree, None, 0)
+            at = self.root_offset
+        deleted = bytearray(_fat_entries("GONE", b"GONE       ", False, 0x20, 0, 0))
+        deleted[0] = 0xE5
+        self.image[at:at + len(body) + 32] = deleted + body
+        return bytes(self.image)
+
+
+def fat_image(tmp_path: Path, files: dict[str, bytes], bits: int, name: str = "fat.img") -> Path:
+    path = tmp_path / name
+    path.write_bytes(FatBuilder(bits).build(files))
+    return path
+
+
+# ---- exFAT ----
+
+class ExFatBuilder:
+    CLUSTER = 512
+
+    def __init__(self) -> None:
+        self.count = 4000
+        self.fat_offset, self.fat_length = 24, -(-(self.count + 2) * 4 // 512)
+        self.heap_offset = self.fat_offset + self.fat_length
+        self.image = bytearray((self.heap_offset + self.count) * 512)
+        self.next_cluster = 2
+
+    def set_fat(self, cluster: int, value: int) -> None:
+        struct.pack_into("<I", self.image, self.fat_offset * 512 + cluster * 4, value)
+
+    def write(self, cluster: int, data: bytes) -> None:
+        at = (self.heap_offset + cluster - 2) * 512
+        self.image[at:at + len(data)] = data
+
+    def allocate(self, size: int, chain: bool) -> int:
+        count = -(-size // self.CLUSTER)
+        if not count:
+            return 0
+        first = self.next_cluster
+        self.next_cluster += count
+        if chain:
+            for i in range(count):
+                self.set_fat(first + i, 0xFFFFFFFF if i == count - 1 else first + i + 1)
+        return first
+
+    @staticmethod
+    def entry_set(name: str, attrs: int, first: int, length: int, valid: int,
+                  contiguous: bool) -> bytes:
+        units = name.encode("utf-16-le")
+        names = [units[i:i + 30].ljust(30, b"\x00") for i in range(0, len(units), 30)]
+        primary = struct.pack("<BBHH", 0x85, 1 + len(names), 0, attrs).ljust(32, b"\x00")
+        stream = struct.pack("<BBBBHHQIIQ", 0xC0, 0x01 | (0x02 if contiguous else 0), 0,
+                             len(name), 0, 0, valid, 0, first, length)
+        return primary + stream + b"".join(b"\xc1\x00" + n for n in names)
+
+    def directory(self, tree: dict, index: list[int]) -> bytes:
+        body = b""
+        for name, content in tree.items():
+            index[0] += 1
+            if isinstance(content, dict):
+                data = self.directory(content, index)
+                size = max(len(data), 1)
+                size += -size % self.CLUSTER
+                first = self.allocate(size, True)
+                self.write(first, data)
+                body += self.entry_set(name, 0x10, first, size, size, False)
+            else:
+                contiguous = index[0] % 2 == 0
+                first = self.allocate(len(content), not contiguous)
+                valid = len(content)
+                if content.endswith(b"\x00" * 600):  # leave the zero tail unwritten
+                    valid = len(content.rstrip(b"\x00"))
+                self.write(first, content[:valid])
+                body += self.entry_set(name, 0x20, first, len(content), valid, contiguous)
+        return body
+
+    def build(self, files: dict[str, bytes]) -> bytes:
+        boot = bytearray(512)
+        boot[0:3] = b"\xeb\x76\x90"
+        boot[3:11] = b"EXFAT   "
+        root = self.allocate(4 * self.CLUSTER, True)
+        body = self.directory(nested(files), [0])
+        label = b"\x83\x02" + "VOL".encode("utf-16-le")
+        deleted = bytearray(self.entry_set("gone", 0x20, 0, 0, 0, False))
+        deleted[0] = 0x05
+        self.write(root, label.ljust(32, b"\x00") + deleted + body)
+        struct.pack_into("<QQIIIII", boot, 64, 0, len(self.image) // 512, self.fat_offset,
+                         self.fat_length, self.heap_offset, self.count, root)
+        boot[108], boot[109] = 9, 0
+        boot[510:512] = b"\x55\xaa"
+        self.image[0:512] = boot
+        self.set_fat(0, 0xFFFFFFF8)
+        self.set_fat(1, 0xFFFFFFFF)
+        return bytes(self.image)
+
+
+def exfat_image(tmp_path: Path, files: dict[str, bytes], name: str = "exfat.img") -> Path:
+    path = tmp_path / name
+    path.write_bytes(ExFatBuilder().build(files))
+    return path
+
+
+# ---- ISO 9660 and UDF ----
+
+class Link(str):
+    """A symlink target in a nested tree."""
+
+
+def both(fmt: str, value: int) -> bytes:
+    return struct.pack("<" + fmt, value) + struct.pack(">" + fmt, value)
+
+
+def susp(signature: bytes, data: bytes) -> bytes:
+    return signature + bytes([4 + len(data), 1]) + data
+
+
+def rr_px(mode: int) -> bytes:
+    return susp(b"PX", both("I", mode) + both("I", 1) + both("I", 0) + both("I", 0))
+
+
+def rr_sl(target: str) -> bytes:
+    parts = target.split("/")
+    body = b"\x08\x00" if target.startswith("/") else b""
+    for part in parts[1:] if target.startswith("/") else parts:
+        if part in (".", ".."):
+            body += bytes([0x02 if part == "." else 0x04, 0])
+        else:
+            body += bytes([0, len(part.encode())]) + part.encode()
+    return susp(b"SL", b"\x00" + body)
+
+
+def iso_record(ident: bytes, lba: int, size: int, flags: int, use: bytes = b"") -> bytes:
+    body = (b"\x00" + both("I", lba) + both("I", size) + bytes(7) + bytes([flags, 0, 0])
+            + both("H", 1) + bytes([len(ident)]) + ident + b"\x00" * (1 - len(ident) % 2)
+            + use)
+    body += b"\x00" * (1 - len(body) % 2)
+    return bytes([len(body) + 1]) + body
+
+
+class IsoBuilder:
+    """ISO 9660 with Rock Ridge on the primary tree and an optional Joliet tree."""
+
+    EXTENT = 64 * 2048  # bigger files take several multi-extent records
+
+    def __init__(self, rock: bool = True, joliet: bool = False,
+                 relocate: str | None = None) -> None:
+        self.rock, self.joliet, self.relocate = rock, joliet, relocate
+        self.blobs: dict[int, bytes] = {}
+        self.next = 20
+        self.extents: dict[str, list[tuple[int, int]]] = {}
+        self.dirs: dict[tuple[bool, str], tuple[int, int]] = {}
+        self.final = False
+
+    def put(self, data: bytes) -> int:
+        lba = self.next
+        self.blobs[lba] = data
+        self.next += max(1, -(-len(data) // 2048))
+        return lba
+
+    def files(self, path: str, tree: dict) -> None:
+        for name, content in tree.items():
+            child = f"{path}{name}"
+            if isinstance(content, dict):
+                self.files(child + "/", content)
+            elif not isinstance(content, Link):
+                chunks = [content[i:i + self.EXTENT]
+                          for i in range(0, len(content), self.EXTENT)] or [b""]
+                self.extents[child] = [(self.put(chunk), len(chunk)) for chunk in chunks]
+
+    def rock_use(self, name: str, mode: int, extra: bytes = b"") -> bytes:
+        use = rr_px(mode) + susp(b"NM", b"\x00" + name.encode()) + extra
+        if len(use) <= 200:
+            return use
+        # too long for the record: the rest goes to a continuation area
+        rest = use[36:]
+        lba = self.put(rest) if self.final else 0
+        return use[:36] + susp(b"CE", both("I", lba) + both("I", 0) + both("I", len(rest)))
+
+    def ident(self, name: str, index: int, directory: bool, joliet: bool) -> bytes:
+        version = "" if directory else ";1"
+        if joliet:
+            return (name[:64] + version).encode("utf-16-be")  # as mkisofs cuts them
+        if self.rock:
+            return f"{'D' if directory else 'F'}{index}{'' if directory else '.'}{version}".encode()
+        return (name.upper() + version).encode()
+
+    def directory(self, path: str, tree: dict, joliet: bool) -> bytes:
+        rock = self.rock and not joliet
+        own = self.dirs.get((joliet, path), (0, 0))
+        parent = self.dirs.get((joliet, path.rpartition("/")[0]), own)
+        dot_use = rr_px(0o40755) if rock else b""
+        if rock and not path:
+            dot_use = susp(b"SP", b"\xbe\xef\x00") + dot_use
+        records = [iso_record(b"\x00", *own, 0x02, dot_use),
+                   iso_record(b"\x01", *parent, 0x02, rr_px(0o40755) if rock else b"")]
+        children = list(tree.items())
+        if rock and path == "rr_moved" and self.relocate:
+            children.append((self.relocate.rpartition("/")[2], None))
+        for index, (name, content) in enumerate(children):
+            child = f"{path}/{name}" if path else name
+            if content is None:
+                ident = self.ident(name, index, True, joliet)
+                place = self.dirs.get((joliet, self.relocate), (0, 0))
+                records.append(iso_record(ident, *place, 0x02,
+                                          self.rock_use(name, 0o40755, susp(b"RE", b""))))
+            elif isinstance(content, dict) and rock and child == self.relocate:
+                lba = self.dirs.get((joliet, child), (0, 0))[0]
+                records.append(iso_record(self.ident(name, index, False, joliet), 0, 0, 0,
+                                          self.rock_use(name, 0o40755,
+                                                        susp(b"CL", both("I", lba)))))
+            elif isinstance(content, dict):
+                use = self.rock_use(name, 0o40755) if rock else b""
+                records.append(iso_record(self.ident(name, index, True, joliet),
+                                          *self.dirs.get((joliet, child), (0, 0)), 0x02, use))
+            elif isinstance(content, Link):
+                if not rock:
+                    continue  # only Rock Ridge has symlinks
+                records.append(iso_record(self.ident(name, index, False, joliet), 0, 0, 0,
+                                          self.rock_use(name, 0o120777, rr_sl(content))))
+            else:
+                extents = self.extents[child]
+                use = self.rock_use(name, 0o100644) if rock else b""
+                for number, (lba, size) in enumerate(extents):
+                    flags = 0x80 if number < len(extents) - 1 else 0
+                    records.append(iso_record(self.ident(name, index, False, joliet), lba,
+                                              size, flags, use))
+        data = b""
+        for record in records:
+            if len(data) // 2048 != (len(data) + len(record) - 1) // 2048:
+                data += bytes(-len(data) % 2048)
+            data += record
+        return data + bytes(-len(data) % 2048)
+
+    def walk(self, path: str, tree: dict, joliet: bool) -> Iterator[tuple[str, dict]]:
+        yield path, tree
+        for name, content in tree.items():
+            if isinstance(content, dict):
+                yield from self.walk(f"{path}/{name}" if path else name, content, joliet)
+
+    def build(self, files: dict) -> bytes:
+        tree = nested(files)
+        self.files("", tree)
+        primary = {**tree, "rr_moved": {}} if self.rock and self.relocate else tree
+        layouts = [(False, primary)] + ([(True, tree)] if self.joliet else [])
+        sizes = {(joliet, path): len(self.directory(path, subtree, joliet))
+                 for joliet, top in layouts for path, subtree in self.walk("", top, joliet)}
+        for key, size in sizes.items():
+            self.dirs[key] = (self.put(bytes(size)), size)
+        self.final = True
+        for joliet, top in layouts:
+            for path, subtree in self.walk("", top, joliet):
+                self.blobs[self.dirs[(joliet, path)][0]] = self.directory(path, subtree, joliet)
+        image = bytearray(self.next * 2048)
+        for lba, blob in self.blobs.items():
+            image[lba * 2048:lba * 2048 + len(blob)] = blob
+        descriptors = [(1, False)] + ([(2, True)] if self.joliet else [])
+        for sector, (kind, joliet) in enumerate(descriptors, 16):
+            desc = bytearray(2048)
+            desc[0:7] = bytes([kind]) + b"CD001\x01"
+            desc[80:88] = both("I", self.next)
+            desc[128:132] = both("H", 2048)
+            if joliet:
+                desc[88:91] = b"%/E"
+            desc[156:190] = iso_record(b"\x00", *self.dirs[(joliet, "")], 0x02)
+            image[sector * 2048:(sector + 1) * 2048] = desc
+        end = 16 + len(descriptors)
+        image[end * 2048:end * 2048 + 7] = b"\xffCD001\x01"
+        return bytes(image)
+
+
+def iso_image(tmp_path: Path, files: dict, name: str = "disc.iso", **kwargs: Any) -> Path:
+    path = tmp_path / name
+    path.write_bytes(IsoBuilder(**kwargs).build(files))
+    return path
+
+
+def udf_descriptor(ident: int, location: int, body: bytes) -> bytes:
+    tag = bytearray(struct.pack("<HHBBHHHI", ident, 2, 0, 0, 0, binascii.crc_hqx(body, 0),
+                                len(body), location))
+    tag[4] = (sum(tag[0:4]) + sum(tag[5:16])) & 0xFF
+    return bytes(tag) + body
+
+
+def udf_name(name: str) -> bytes:
+    return b"\x08" + name.encode() if name.isascii() else b"\x10" + name.encode("utf-16-be")
+
+
+class UdfBuilder:
+    """UDF on 2048-byte sectors: plain (file entries, short_ad) or with a metadata
+    partition (extended file entries, long_ad)."""
+
+    START = 260  # the partition's first sector
+    EXTENT = 64 * 2048
+
+    def __init__(self, metadata: bool = False, sparable: bool = False) -> None:
+        self.metadata, self.sparable = metadata, sparable
+        self.physical: dict[int, bytes] = {}
+        self.meta: dict[int, bytes] = {}
+        self.next_physical = 0
+        self.next_meta = 0
+
+    def alloc(self, data: bytes, meta: bool) -> int:
+        count = max(1, -(-len(data) // 2048))
+        if meta and self.metadata:
+            block, self.next_meta = self.next_meta, self.next_meta + count
+            self.meta[block] = data
+        else:
+            block, self.next_physical = self.next_physical, self.next_physical + count
+            self.physical[block] = data
+        return block
+
+    def ad(self, length: int, block: int) -> bytes:
+        if self.metadata:
+            return struct.pack("<IIH6x", length, block, 0)  # the physical partition
+        return struct.pack("<II", length, block)
+
+    def file_entry(self, location: int, file_type: int, size: int, ads: bytes,
+                   ad_type: int) -> bytes:
+        icb = struct.pack("<IHHHxBxxxxxxH", 0, 4, 0, 1, file_type, ad_type)
+        if self.metadata:
+            body = (icb + bytes(20) + struct.pack("<QQQ", size, size, 0) + bytes(128)
+                    + struct.pack("<II", 0, len(ads)) + ads)
+            return udf_descriptor(266, location, body)
+        body = (icb + bytes(20) + struct.pack("<QQ", size, 0) + bytes(96)
+                + struct.pack("<II", 0, len(ads)) + ads)
+        return udf_descriptor(261, location, body)
+
+    def data_ads(self, content: bytes) -> tuple[bytes, int]:
+        if len(content) <= 64:
+            return content, 3
+        recorded = content
+        if content.endswith(bytes(4096)):  # leave the zero tail unrecorded
+            recorded = content.rstrip(b"\x00")
+            recorded += bytes(-len(recorded) % 2048)
+        ads = [self.ad(len(recorded[i:i + self.EXTENT]),
+                       self.alloc(recorded[i:i + self.EXTENT], False))
+               for i in range(0, len(recorded), self.EXTENT)]
+        if len(recorded) < len(content):
+            ads.append(self.ad(1 << 30 | len(content) - len(recorded), 0))
+        if len(ads) > 4 and not self.metadata:  # continue the list in an extent descriptor
+            rest = b"".join(ads[3:])
+            block = self.next_physical
+            self.alloc(udf_descriptor(258, block, struct.pack("<II", 0, len(rest)) + rest),
+                       False)
+            ads = ads[:3] + [self.ad(3 << 30 | 2048, block)]
+        return b"".join(ads), 0 if not self.metadata else 1
+
+    def entry(self, content: Any, parent: int | None) -> int:
+        """Lay out a file entry, and what it holds; its block in the ICB partition."""
+        block = self.alloc(b"", True)
+        if isinstance(content, dict):
+            fids = self.fid("", 0x0A, parent if parent is not None else block)
+            for name, child in content.items():
+                fids += self.fid(name, 0x02 if isinstance(child, dict) else 0,
+                                 self.entry(child, block))
+            ads = struct.pack("<II", len(fids), self.alloc(fids, True))  # next to the entry
+            raw = self.file_entry(block, 4, len(fids), ads, 0)
+        elif isinstance(content, Link):
+            path = b"".join(struct.pack("<BBH", 3, 0, 0) if part == ".." else
+                            struct.pack("<BBH", 5, len(udf_name(part)), 0) + udf_name(part)
+                            for part in content.split("/"))
+            raw = self.file_entry(block, 12, len(path), path, 3)
+        else:
+            ads, ad_type = self.data_ads(content)
+            raw = self.file_entry(block, 5, len(content), ads, ad_type)
+        (self.meta if self.metadata else self.physical)[block] = raw
+        return block
+
+    def fid(self, name: str, characteristics: int, block: int) -> bytes:
+        ident = udf_name(name) if name else b""
+        part = 1 if self.metadata else 0
+        body = (struct.pack("<HBB", 1, characteristics, len(ident))
+                + struct.pack("<IIH6xH", 2048, block, part, 0) + ident)
+        raw = udf_descriptor(257, 0, body)
+        return raw + bytes(-len(raw) % 4)
+
+    def build(self, files: dict) -> bytes:
+        fsd = self.alloc(b"", True)
+        root = self.entry(nested(files), None)
+        part = 1 if self.metadata else 0
+        fsd_body = bytes(384) + struct.pack("<IIH", 2048, root, part)
+        (self.meta if self.metadata else self.physical)[fsd] = udf_descriptor(256, fsd,
+                                                                              fsd_body)
+        maps = struct.pack("<BBHH", 1, 6, 1, 0)
+        if self.metadata:
+            # the metadata partition in two runs, with a gap between them
+            half = self.next_meta // 2
+            first = self.next_physical
+            self.next_physical += half + 1
+            second = self.next_physical
+            self.next_physical += self.next_meta - half
+            for block, data in self.meta.items():
+                at = first + block if block < half else second + block - half
+                self.physical[at] = data
+            ads = (struct.pack("<II", half * 2048, first)
+                   + struct.pack("<II", (self.next_meta - half) * 2048, second))
+            location = self.next_physical
+            self.alloc(self.file_entry(location, 250, self.next_meta * 2048, ads, 0), False)
+            ident = b"*UDF Sparable Partition" if self.sparable else b"*UDF Metadata Partition"
+            maps += (struct.pack("<BBxxB", 2, 64, 0) + ident + bytes(8)
+                     + struct.pack("<HHIII", 1, 0, location, 0, 0)).ljust(64, b"\x00")
+        image = bytearray((self.START + self.next_physical + 1) * 2048)
+        for block, data in self.physical.items():
+            at = (self.START + block) * 2048
+            image[at:at + len(data)] = data
+        for sector, ident in enumerate((b"BEA01", b"NSR02", b"TEA01"), 16):
+            image[sector * 2048:sector * 2048 + 7] = b"\x00" + ident + b"\x01"
+        partition = bytearray(496)
+        struct.pack_into("<IHH", partition, 0, 1, 1, 0)
+        struct.pack_into("<III", partition, 168, 1, self.START, self.next_physical + 1)
+        lvd = bytearray(424)
+        struct.pack_into("<I", lvd, 196, 2048)
+        struct.pack_into("<IIH", lvd, 232, 2048, fsd, part)
+        struct.pack_into("<II", lvd, 248, len(maps), 2 if self.metadata else 1)
+        descriptors = [udf_descriptor(5, 32, bytes(partition)),
+                       udf_descriptor(6, 33, bytes(lvd) + maps), udf_descriptor(8, 34, bytes(496))]
+        for sector, desc in enumerate(descriptors, 32):
+            image[sector * 2048:sector * 2048 + len(desc)] = desc
+        anchor = udf_descriptor(2, 256, struct.pack("<II", 3 * 2048, 32).ljust(496, b"\x00"))
+        image[256 * 2048:256 * 2048 + len(anchor)] = anchor
+        return bytes(image)
+
+
+def udf_image(tmp_path: Path, files: dict, name: str = "disc.udf", **kwargs: Any) -> Path:
+    path = tmp_path / name
+    path.write_bytes(UdfBuilder(**kwargs).build(files))
+    return path
+
+
+@pytest.fixture
+def tree(tmp_path: Path) -> Path:
+    return write_tree(tmp_path / "tree", TREE)
+
+
+class TestExt:
+    @needs_mke2fs
+    @pytest.mark.parametrize("kind", ["ext2", "ext3", "ext4"])
+    def test_against_tree(self, tmp_path: Path, tree: Path, kind: str):
+        image = ext_image(tmp_path, TREE, kind)
+        (tree / "link").symlink_to("hello.txt")
+        assert compare_disk_image_dir(str(image), str(tree)).equal
+        (tree / "hello.txt").write_bytes(b"HELLO")
+        (tree / "sub" / "deep" / "needle").write_bytes(b"longer")
+        (tree / "link").unlink()
+        (tree / "link").symlink_to("README")
+        result = compare_disk_image_dir(str(image), str(tree))
+        assert result.diff == {"hello.txt": DiffReason.CONTENT_MISMATCH,
+                               "sub/deep/needle": DiffReason.SIZE_MISMATCH,
+                               "link": DiffReason.CONTENT_MISMATCH}
+
+    @needs_mke2fs
+    def test_subdirectory_and_two_images(self, tmp_path: Path, tree: Path):
+        ext2 = ext_image(tmp_path, TREE, "ext2", "a.img")
+        ext4 = ext_image(tmp_path, {**TREE, "sub/new": b"x"}, "ext4", "b.img")
+        assert compare_disk_image_dir(f"{ext4}:/sub/deep", str(tree / "sub" / "deep")).equal
+        result = compare_disk_images(str(ext2), str(ext4))
+        assert (result.diff, result.only_right) == ({}, {"sub/new"})
+        assert compare_disk_images(str(ext2), str(ext4), ignore=["new"]).equal
+
+    @needs_mke2fs
+    def test_sparse_file(self, tmp_path: Path):
+        src = tmp_path / "src"
+        src.mkdir()
+        with open(src / "sparse", "wb") as f:
+            f.seek(3_000_000)
+            f.write(b"end")
+        image = tmp_path / "fs.img"
+        subprocess.run(["mke2fs", "-q", "-F", "-t", "ext4", "-d", str(src), str(image), "8M"],
+                       check=True, capture_output=True)
+        assert compare_disk_image_dir(str(image), str(src)).equal
+
+
+class TestFat:
+    @pytest.mark.parametrize("bits", [12, 16, 32])
+    def test_against_tree(self, tmp_path: Path, tree: Path, bits: int):
+        image = fat_image(tmp_path, TREE, bits)
+        assert compare_disk_image_dir(str(image), str(tree)).equal
+        (tree / "A Long File Name.data").write_bytes(BIG[:-1] + b"!")
+        (tree / "extra").write_bytes(b"")
+        result = compare_disk_image_dir(str(image), str(tree))
+        assert result.diff == {"A Long File Name.data": DiffReason.CONTENT_MISMATCH}
+        assert result.only_right == {"extra"}
+
+    def test_fat16_against_fat32(self, tmp_path: Path):
+        a = fat_image(tmp_path, TREE, 16, "a.img")
+        b = fat_image(tmp_path, {**TREE, "sub/deep/needle": b"DEEP"}, 32, "b.img")
+        result = compare_disk_images(f"{a}:/sub", f"{b}:/sub")
+        assert result.diff == {"deep/needle": DiffReason.CONTENT_MISMATCH}
+
+    def test_cluster_loop(self, tmp_path: Path):
+        builder = FatBuilder(16)
+        data = bytearray(builder.build({"f": BIG}))
+        struct.pack_into("<H", data, builder.reserved * 512 + 3 * 2, 2)  # cluster 3 -> 2
+        image = tmp_path / "loop.img"
+        image.write_bytes(data)
+        with pytest.raises(ArchiveError, match="bad cluster chain"):
+            compare_disk_images(str(image), str(image))
+
+
+class TestExFat:
+    def test_against_tree(self, tmp_path: Path, tree: Path):
+        files = {**TREE, "zero tail": b"data" + bytes(1000)}
+        write_tree(tree, files)
+        image = exfat_image(tmp_path, files)
+        assert compare_disk_image_dir(str(image), str(tree)).equal
+        assert compare_disk_image_dir(f"{image}:sub", str(tree / "sub")).equal
+        (tree / "zero tail").write_bytes(b"data" + bytes(999) + b"\x01")
+        result = compare_disk_image_dir(str(image), str(tree))
+        assert result.diff == {"zero tail": DiffReason.CONTENT_MISMATCH}
+
+    def test_against_fat(self, tmp_path: Path):
+        result = compare_disk_images(str(exfat_image(tmp_path, TREE)),
+                                     str(fat_image(tmp_path, {**TREE, "hello.txt": b"bye"}, 32)))
+        assert result.diff == {"hello.txt": DiffReason.SIZE_MISMATCH}
+
+
+LONG = "continued " * 18  # too long for a directory record on its own
+LINKS = {"link": "hello.txt", "sub/up": "../README"}
+
+
+def with_links(root: Path, links: dict[str, str]) -> Path:
+    for rel, target in links.items():
+        (root / rel).symlink_to(target)
+    return root
+
+
+class TestIso:
+    def test_rock_ridge(self, tmp_path: Path, tree: Path):
+        files = {**TREE, LONG: b"long", **{rel: Link(to) for rel, to in LINKS.items()}}
+        image = iso_image(tmp_path, files, joliet=True)
+        with_links(write_tree(tree, {LONG: b"long"}), LINKS)
+        assert compare_disk_image_dir(str(image), str(tree)).equal
+        (tree / "sub" / "up").unlink()
+        (tree / "sub" / "up").symlink_to("/README")
+        (tree / "A Long File Name.data").write_bytes(BIG[:-1] + b"!")
+        result = compare_disk_image_dir(str(image), str(tree))
+        assert result.diff == {"sub/up": DiffReason.CONTENT_MISMATCH,
+                               "A Long File Name.data": DiffReason.CONTENT_MISMATCH}
+
+    def test_joliet(self, tmp_path: Path, tree: Path):
+        image = iso_image(tmp_path, TREE, rock=False, joliet=True)
+        assert compare_disk_image_dir(str(image), str(tree)).equal
+        other = iso_image(tmp_path, {**TREE, "sub/new": b""}, "other.iso")
+        result = compare_disk_images(str(image), str(other))
+        assert (result.diff, result.only_right) == ({}, {"sub/new"})
+        assert main(["compare", "--disk-image", f"{image}:sub", str(tree / "sub")]) == 0
+
+    def test_plain_names(self, tmp_path: Path):
+        files = {"hello.txt": b"hello", "docs/readme": BIG}
+        src = write_tree(tmp_path / "src", files)
+        assert compare_disk_image_dir(str(iso_image(tmp_path, files, rock=False)),
+                                      str(src)).equal
+
+    def test_relocated_directory(self, tmp_path: Path, tree: Path):
+        image = iso_image(tmp_path, TREE, relocate="sub/deep")
+        assert compare_disk_image_dir(str(image), str(tree)).equal
+        assert compare_disk_image_dir(f"{image}:sub/deep", str(tree / "sub" / "deep")).equal
+
+
+class TestUdf:
+    FILES = {**TREE, "zero tail": b"data" + bytes(6000)}
+
+    def test_against_tree(self, tmp_path: Path, tree: Path):
+        links = {rel: Link(to) for rel, to in LINKS.items()}
+        image = udf_image(tmp_path, {**self.FILES, **links})
+        with_links(write_tree(tree, self.FILES), LINKS)
+        assert compare_disk_image_dir(str(image), str(tree)).equal
+        (tree / "zero tail").write_bytes(b"data" + bytes(5999) + b"\x01")
+        (tree / "A Long File Name.data").write_bytes(b"!" + BIG[1:])
+        result = compare_disk_image_dir(str(image), str(tree))
+        assert result.diff == {"zero tail": DiffReason.CONTENT_MISMATCH,
+                               "A Long File Name.data": DiffReason.CONTENT_MISMATCH}
+
+    def test_metadata_partition(self, tmp_path: Path, tree: Path):
+        image = udf_image(tmp_path, self.FILES, metadata=True)
+        write_tree(tree, self.FILES)
+        assert compare_disk_image_dir(str(image), str(tree)).equal
+        assert compare_disk_images(str(image), str(iso_image(tmp_path, self.FILES))).equal
+
+    def test_unsupported_partition(self, tmp_path: Path):
+        image = udf_image(tmp_path, TREE, metadata=True, sparable=True)
+        with pytest.raises(UnsupportedSourceError, match="sparable partitions"):
+            compare_disk_images(str(image), str(image))
+
+
+class TestPartitions:
+    @staticmethod
+    def mbr_disk(tmp_path: Path, volume: bytes) -> Path:
+        mbr = bytearray(512)
+        struct.pack_into("<BxxxII", mbr, 446 + 16, 0x0C, 2048, len(volume) // 512)
+        mbr[510:512] = b"\x55\xaa"
+        disk = tmp_path / "disk.img"
+        disk.write_bytes(bytes(mbr) + bytes(2047 * 512) + volume)
+        return disk
+
+    @staticmethod
+    def gpt_disk(tmp_path: Path, volume: bytes) -> Path:
+        mbr = bytearray(512)
+        struct.pack_into("<BxxxII", mbr, 446, 0xEE, 1, 0xFFFFFFFF)
+        mbr[510:512] = b"\x55\xaa"
+        header = bytearray(512)
+        header[:8] = b"EFI PART"
+        struct.pack_into("<QII", header, 72, 2, 128, 128)
+        entries = bytearray(128 * 128)
+        entry = os.urandom(16) + os.urandom(16) + struct.pack("<QQ", 2048,
+                                                             2047 + len(volume) // 512)
+        entries[128 * 2:128 * 2 + len(entry)] = entry  # the third slot: partition 3
+        disk = tmp_path / "gpt.img"
+        body = bytes(mbr) + bytes(header) + bytes(entries)
+        disk.write_bytes(body + bytes(2048 * 512 - len(body)) + volume)
+        return disk
+
+    def test_mbr(self, tmp_path: Path, tree: Path):
+        disk = self.mbr_disk(tmp_path, FatBuilder(16).build(TREE))
+        assert compare_disk_image_dir(f"{disk}@2", str(tree)).equal
+        assert compare_disk_image_dir(f"{disk}@2:/sub", str(tree / "sub")).equal
+        with pytest.raises(ValueError, match="partitioned"):
+            compare_disk_image_dir(str(disk), str(tree))
+        with pytest.raises(SourceNotFoundError, match="no partition 1"):
+            compare_disk_image_dir(f"{disk}@1", str(tree))
+
+    def test_gpt(self, tmp_path: Path, tree: Path):
+        disk = self.gpt_disk(tmp_path, ExFatBuilder().build(TREE))
+        assert compare_disk_image_dir(f"{disk}@3", str(tree)).equal
+
+    def test_missing(self, tmp_path: Path, tree: Path):
+        image = fat_image(tmp_path, TREE, 12)
+        with pytest.raises(SourceNotFoundError, match="no such directory"):
+            compare_disk_image_dir(f"{image}:/nope", str(tree))
+        with pytest.raises(SourceNotFoundError, match="no such disk image"):
+            compare_disk_images(str(tmp_path / "none.img"), str(image))
+        (tmp_path / "blank.img").write_bytes(bytes(4096))
+        with pytest.raises(OSError, match="no ext2/3/4, FAT, exFAT, ISO 9660 or UDF"):
+            compare_disk_images(str(tmp_path / "blank.img"), str(image))
+
+
+class TestCli:
+    def test_compare(self, tmp_path: Path, tree: Path, capsys):
+        image = fat_image(tmp_path, TREE, 16)
+        assert main(["compare", "--disk-image", str(image), str(tree)]) == 0
+        (tree / "hello.txt").write_bytes(b"other")
+        assert main(["compare", "--disk-image", f"{image}:/", str(tree)]) == 1
+        assert "hello.txt" in capsys.readouterr().out
+        other = fat_image(tmp_path, TREE, 32, "other.img")
+        assert main(["compare", "--disk-image", "-q", str(image), str(other)]) == 0
+
+    def test_refused(self, tmp_path: Path, tree: Path, capsys):
+        image = fat_image(tmp_path, TREE, 16)
+        assert main(["compare", "--disk-image", "--oci", str(image), str(tree)]) == 2
+        assert "--disk-image" in capsys.readouterr().err
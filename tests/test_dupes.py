@@ -0,0 +1,113 @@
+"""Tests for finding duplicate files."""
+
+from __future__ import annotations
+
+import os
+import sys
+from pathlib import Path
+
+import pytest
+
+import komparu
+from komparu import DuplicateGroup
+
+
+@pytest.fixture
+def tree(tmp_path: Path) -> Path:
+    root = tmp_path / "tree"
+    (root / "sub").mkdir(parents=True)
+    (root / "a").write_bytes(b"copy")
+    (root / "sub" / "b").write_bytes(b"copy")
+    (root / "c").write_bytes(b"cop!")
+    (root / "empty1").write_bytes(b"")
+    (root / "empty2").write_bytes(b"")
+    return root
+
+
+class TestFindDuplicates:
+    def test_groups(self, tree: Path):
+        assert komparu.find_duplicates([str(tree)]) == [
+            DuplicateGroup(size=4, paths=[str(tree / "a"), str(tree / "sub" / "b")]),
+        ]
+
+    def test_wasted(self):
+        assert DuplicateGroup(size=10, paths=["a", "b", "c"]).wasted == 20
+
+    def test_min_size(self, tree: Path):
+        groups = komparu.find_duplicates([str(tree)], min_size=0)
+        assert [g.size for g in groups] == [4, 0]
+        assert komparu.find_duplicates([str(tree)], min_size=5) == []
+
+    def test_across_trees(self, tree: Path, tmp_path: Path):
+        other = tmp_path / "other"
+        other.mkdir()
+        (other / "c").write_bytes(b"cop!")
+        groups = komparu.find_duplicates([str(tree), str(other)])
+        assert [g.paths for g in groups] == [
+            [str(tree / "a"), str(tree / "sub" / "b")],
+            [str(tree / "c"), str(other / "c")],
+        ]
+
+    def test_same_directory_twice(self, tree: Path):
+        assert len(komparu.find_duplicates([str(tree), str(tree)], hard_links=True)) == 1
+
+    def test_hard_links(self, tree: Path):
+        os.link(tree / "c", tree / "d")
+        assert len(komparu.find_duplicates([str(tree)])) == 1
+        groups = komparu.find_duplicates([str(tree)], hard_links=True)
+        assert groups[1].paths == [str(tree / "c"), str(tree / "d")]
+
+    def test_ignore(self, tree: Path):
+        assert komparu.find_duplicates([str(tree)], ignore=["sub"]) == []
+
+    def test_no_directories(self):
+        with pytest.raises(ValueError, match="directories"):
+            komparu.find_duplicates([])
+
+
+class TestDeduplicate:
+    def test_hardlink(self, tree: Path):
+        groups = komparu.find_duplicates([str(tree)])
+        result = komparu.deduplicate(groups)
+        assert result.linked == [(str(tree / "sub" / "b"), str(tree / "a"))]
+        assert result.skipped == {}
+        assert result.bytes_saved == 4
+        assert os.path.samefile(tree / "a", tree / "sub" / "b")
+        assert os.listdir(tree / "sub") == ["b"]
+
+    def test_dry_run(self, tree: Path):
+        groups = komparu.find_duplicates([str(tree)])
+        result = komparu.deduplicate(groups, dry_run=True)
+        assert result.linked == [(str(tree / "sub" / "b"), str(tree / "a"))]
+        assert not os.path.samefile(tree / "a", tree / "sub" / "b")
+
+    def test_changed_since_found(self, tree: Path):
+        groups = komparu.find_duplicates([str(tree)])
+        (tree / "sub" / "b").write_bytes(b"edit")
+        result = komparu.deduplicate(groups)
+        assert result.linked == []
+        assert result.skipped == {str(tree / "sub" / "b"): "changed since it was found"}
+        assert (tree / "sub" / "b").read_bytes() == b"edit"
+
+    def test_already_linked(self, tree: Path):
+        os.link(tree / "c", tree / "d")
+        groups = komparu.find_duplicates([str(tree)], hard_links=True)
+        result = komparu.deduplicate(groups)
+        assert result.linked == [(str(tree / "sub" / "b"), str(tree / "a"))]
+
+    @pytest.mark.skipif(not sys.platform.startswith("linux"), reason="reflinks are Linux-only")
+    def test_reflink(self, tree: Path):
+        groups = komparu.find_duplicates([str(tree)])
+        result = komparu.deduplicate(groups, method="reflink")
+        b = str(tree / "sub" / "b")
+        if result.skipped:
+            assert result.skipped == {b: "file system does not support reflinks"}
+        else:
+            assert result.linked == [(b, str(tree / "a"))]
+            assert not os.path.samefile(tree / "a", b)
+        assert (tree / "sub" / "b").read_bytes() == b"copy"
+        assert os.listdir(tree / "sub") == ["b"]
+
+    def test_unknown_method(self):
+        with pytest.raises(ValueError, match="method"):
+            komparu.deduplicate([], method="symlink")
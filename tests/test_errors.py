@@ -0,0 +1,170 @@
+"""Tests for the structured komparu exception hierarchy."""
+
+from __future__ import annotations
+
+import errno
+import io
+import os
+import tarfile
+from pathlib import Path
+
+import pytest
+from pytest_httpserver import HTTPServer
+
+import komparu
+import komparu.aio
+from komparu import (
+    KomparuError,
+    SourceNotFoundError,
+    SourceReadError,
+    SourcePermissionError,
+    SourceVanishedError,
+    UnsupportedSourceError,
+    ArchiveError,
+    ArchiveBombError,
+    ComparisonTimeoutError,
+    ComparisonCancelledError,
+)
+from komparu._errors import translate_error
+
+
+class TestHierarchy:
+    """Komparu errors stay catchable as the builtin exceptions."""
+
+    @pytest.mark.parametrize("cls, builtin", [
+        (SourceReadError, OSError),
+        (SourceNotFoundError, FileNotFoundError),
+        (SourcePermissionError, PermissionError),
+        (ComparisonTimeoutError, TimeoutError),
+        (ArchiveError, OSError),
+    ])
+    def test_builtin_base(self, cls, builtin):
+        assert issubclass(cls, KomparuError)
+        assert issubclass(cls, builtin)
+
+    def test_source_subclasses(self):
+        for cls in (SourceNotFoundError, SourcePermissionError,
+                    SourceVanishedError, UnsupportedSourceError):
+            assert issubclass(cls, SourceReadError)
+
+    def test_bomb_is_archive_error(self):
+        assert issubclass(ArchiveBombError, ArchiveError)
+
+
+class TestTranslate:
+    """errno → exception class mapping."""
+
+    @pytest.mark.parametrize("code, cls", [
+        (errno.ENOENT, SourceNotFoundError),
+        (errno.ENOTDIR, SourceNotFoundError),
+        (errno.EACCES, SourcePermissionError),
+        (errno.EPERM, SourcePermissionError),
+        (errno.ETIMEDOUT, ComparisonTimeoutError),
+        (errno.ENOTSUP, UnsupportedSourceError),
+        (errno.EIO, SourceReadError),
+    ])
+    def test_source_errors(self, code, cls):
+        err = translate_error(OSError(code, "boom", "/x"))
+        assert type(err) is cls
+        assert err.errno == code
+        assert err.strerror == "boom"
+        assert err.filename == "/x"
+
+    def test_cancelled(self):
+        err = translate_error(OSError(errno.ECANCELED, "cancelled"))
+        assert isinstance(err, ComparisonCancelledError)
+
+    def test_archive(self):
+        assert type(translate_error(OSError(errno.EIO, "bad"), archive=True)) is ArchiveError
+        assert type(translate_error(OSError(errno.EFBIG, "bomb"), archive=True)) is ArchiveBombError
+
+    def test_no_errno(self):
+        err = translate_error(OSError("plain message"))
+        assert type(err) is SourceReadError
+        assert str(err) == "plain message"
+
+
+class TestLocalErrors:
+    """Errors raised by real comparisons."""
+
+    def test_missing_file(self, make_file, tmp_path: Path):
+        a = make_file("a.bin", b"data")
+        missing = str(tmp_path / "missing.bin")
+        with pytest.raises(SourceNotFoundError) as exc:
+            komparu.compare(str(a), missing)
+        assert exc.value.errno == errno.ENOENT
+        assert exc.value.filename == missing
+
+    def test_directory_as_file(self, make_file, tmp_path: Path):
+        a = make_file("a.bin", b"data")
+        with pytest.raises(UnsupportedSourceError) as exc:
+            komparu.compare(str(a), str(tmp_path))
+        assert exc.value.filename == str(tmp_path)
+
+    @pytest.mark.skipif(hasattr(os, "geteuid") and os.geteuid() == 0,
+                        reason="root bypasses file permissions")
+    def test_permission_denied(self, make_file):
+        a = make_file("a.bin", b"data")
+        b = make_file("b.bin", b"data")
+        b.chmod(0o000)
+        try:
+            with pytest.raises(SourcePermissionError):
+                komparu.compare(str(a), str(b))
+        finally:
+            b.chmod(0o644)
+
+    def test_missing_directory(self, tmp_path: Path):
+        with pytest.raises(SourceNotFoundError):
+            komparu.compare_dir(str(tmp_path), str(tmp_path / "missing"))
+
+    @pytest.mark.asyncio
+    async def test_async_missing_file(self, make_file, tmp_path: Path):
+        a = make_file("a.bin", b"data")
+        missing = str(tmp_path / "missing.bin")
+        with pytest.raises(SourceNotFoundError) as exc:
+            await komparu.aio.compare(str(a), missing)
+        assert exc.value.filename == missing
+
+
+class TestArchiveErrors:
+    def test_invalid_archive(self, make_file):
+        a = make_file("a.txt", b"this is not an archive")
+        b = make_file("b.txt", b"this is not an archive")
+        with pytest.raises(ArchiveError):
+            komparu.compare_archive(str(a), str(b))
+
+    def test_bomb(self, tmp_path: Path):
+        paths = []
+        for name, count in (("many.tar.gz", 20), ("one.tar.gz", 1)):
+            path = tmp_path / name
+            with tarfile.open(str(path), "w:gz") as tf:
+                for i in range(count):
+                    info = tarfile.TarInfo(name=f"file_{i}.txt")
+                    info.size = 1
+                    tf.addfile(info, io.BytesIO(b"x"))
+            paths.append(str(path))
+        with pytest.raises(ArchiveBombError, match="bomb"):
+            komparu.compare_archive(*paths, max_archive_entries=10)
+
+
+class TestHttpErrors:
+    def test_404(self, httpserver: HTTPServer, make_file):
+        local = make_file("local.bin", b"data")
+        httpserver.expect_request("/missing").respond_with_data(b"", status=404)
+        url = httpserver.url_for("/missing")
+        with pytest.raises(SourceNotFoundError) as exc:
+            komparu.compare(str(local), url)
+        assert exc.value.filename == url
+
+    def test_403(self, httpserver: HTTPServer, make_file):
+        local = make_file("local.bin", b"data")
+        httpserver.expect_request("/secret").respond_with_data(b"", status=403)
+        with pytest.raises(SourcePermissionError):
+            komparu.compare(str(local), httpserver.url_for("/secret"))
+
+    def test_500(self, httpserver: HTTPServer, make_file):
+        local = make_file("local.bin", b"data")
+        httpserver.expect_request("/error").respond_with_data(b"", status=500)
+        with pytest.raises(SourceReadError) as exc:
+            komparu.compare(str(local), httpserver.url_for("/error"))
+        assert type(exc.value) is SourceReadError
@@ -0,0 +1,200 @@
+"""Tests for comparing btrfs and ZFS snapshots through their change lists."""
+
+from __future__ import annotations
+
+import os
+import struct
+from pathlib import Path
+
+import pytest
+
+import komparu._fssnap as fssnap
+import komparu._storage as storage
+from komparu import (
+    DiffReason,
+    SourceNotFoundError,
+    SourceReadError,
+    UnsupportedSourceError,
+    compare_fs_snapshots,
+)
+from komparu._cli import main
+
+MKFILE, RENAME, UNLINK, CHMOD, TRUNCATE, UPDATE_EXTENT = 3, 9, 11, 18, 17, 22
+PATH, PATH_TO, SIZE = 15, 16, 4
+
+
+def command(kind: int, **attributes: bytes) -> bytes:
+    codes = {"path": PATH, "path_to": PATH_TO, "size": SIZE}
+    body = b"".join(struct.pack("<HH", codes[name], len(value)) + value
+                    for name, value in attributes.items())
+    return struct.pack("<IHI", len(body), kind, 0) + body
+
+
+def stream(*commands: bytes, version: int = 1) -> bytes:
+    return b"btrfs-stream\0" + struct.pack("<I", version) + b"".join(commands)
+
+
+class FakeTools:
+    """A mount table and canned ``btrfs`` / ``zfs`` output."""
+
+    def __init__(self, mount: tuple[str, str, str], outputs: dict[str, bytes]) -> None:
+        self.mount = mount
+        self.outputs = outputs
+        self.calls: list[list[str]] = []
+
+    def mount_of(self, path: str) -> tuple[str, str, str] | None:
+        return self.mount if path.startswith(self.mount[0]) else None
+
+    def run(self, args: list[str], where: str) -> bytes:
+        self.calls.append(args)
+        return self.outputs[args[1]]
+
+
+@pytest.fixture
+def btrfs(tmp_path: Path, monkeypatch, make_dir) -> tuple[str, str, FakeTools]:
+    """Two "subvolumes" under tmp_path/snaps and the send stream between them."""
+    a = str(make_dir("snaps/a", {
+        "same.txt": b"x", "edited.txt": b"aaaa", "grown.txt": b"a", "gone.txt": b"-",
+        "old/f": b"1", "unlisted.txt": b"one", "link1": b"zz",
+    }))
+    b = str(make_dir("snaps/b", {
+        "same.txt": b"x", "edited.txt": b"bbbb", "grown.txt": b"abc", "new.txt": b"+",
+        "moved/f": b"1", "unlisted.txt": b"two", "link1": b"yy",
+    }))
+    for root in (a, b):
+        os.link(os.path.join(root, "link1"), os.path.join(root, "link2"))
+    fake = FakeTools((str(tmp_path), "btrfs", "/dev/sdb1"), {"send": stream(
+        command(2, path=b"b"),
+        command(CHMOD, path=b"same.txt"),
+        command(UPDATE_EXTENT, path=b"edited.txt"),
+        command(TRUNCATE, path=b"grown.txt", size=struct.pack("<Q", 3)),
+        command(UNLINK, path=b"gone.txt"),
+        command(MKFILE, path=b"o258-7-0"),
+        command(RENAME, path=b"o258-7-0", path_to=b"new.txt"),
+        command(RENAME, path=b"old", path_to=b"moved"),
+        command(UPDATE_EXTENT, path=b"link1"),
+        command(21),
+    )})
+    monkeypatch.setattr(fssnap, "_mount_of", fake.mount_of)
+    snaps = tmp_path / "snaps"
+    monkeypatch.setattr(fssnap, "_subvolume_root",
+                        lambda path: str(snaps / Path(path).relative_to(snaps).parts[0]))
+    monkeypatch.setattr(fssnap, "_run", fake.run)
+    return a, b, fake
+
+
+@pytest.fixture
+def zfs(tmp_path: Path, monkeypatch, make_dir) -> tuple[str, str, FakeTools]:
+    live = tmp_path / "tank" / "data"
+    snapshots = live / ".zfs" / "snapshot"
+    mon = str(make_dir(snapshots / "mon", {"sub/a b": b"1", "sub/kept": b"k", "outside": b"o",
+                                           "other/x/f": b"x"}))
+    tue = str(make_dir(snapshots / "tue", {"sub/a b": b"2", "sub/kept": b"k", "sub/new": b"n",
+                                           "outside": b"O", "sub/y/f": b"x"}))
+    fake = FakeTools((str(live), "zfs", "tank/data"), {
+        "get": b"20\n10\n",  # mon was taken after tue
+        "list": f"{live}\n".encode(),
+        "diff": (f"M\t{live}/sub/a\\0040b\n+\t{live}/sub/new\n"
+                 f"R\t{live}/other/x\t{live}/sub/y\nM\t{live}/outside\n").encode(),
+    })
+    monkeypatch.setattr(fssnap, "_mount_of", fake.mount_of)
+    monkeypatch.setattr(fssnap, "_run", fake.run)
+    return os.path.join(mon, "sub"), os.path.join(tue, "sub"), fake
+
+
+class TestBtrfs:
+    def test_listed_changes(self, btrfs):
+        a, b, fake = btrfs
+        result = compare_fs_snapshots(a, b)
+        assert result.diff == {
+            "edited.txt": DiffReason.CONTENT_MISMATCH, "grown.txt": DiffReason.SIZE_MISMATCH,
+            "link1": DiffReason.CONTENT_MISMATCH, "link2": DiffReason.CONTENT_MISMATCH,
+        }
+        assert result.only_left == {"gone.txt", "old/f"}
+        assert result.only_right == {"new.txt", "moved/f"}
+        assert "unlisted.txt" not in result.diff  # never read
+        assert fake.calls == [["btrfs", "send", "--no-data", "-q", "-p", a, b]]
+
+    def test_ignore(self, btrfs):
+        a, b, _ = btrfs
+        result = compare_fs_snapshots(a, b, ignore=["*.txt", "old"])
+        assert set(result.diff) == {"link1", "link2"}
+        assert result.only_left == set()
+        assert result.only_right == {"moved/f"}
+
+    def test_same_snapshot(self, btrfs):
+        a, _, fake = btrfs
+        assert compare_fs_snapshots(a, a).equal
+        assert fake.calls == []
+
+    def test_bad_stream(self, btrfs):
+        a, b, fake = btrfs
+        fake.outputs["send"] = fake.outputs["send"][:-3]
+        with pytest.raises(SourceReadError, match="truncated"):
+            compare_fs_snapshots(a, b)
+        fake.outputs["send"] = b"not a stream"
+        with pytest.raises(SourceReadError, match="not a btrfs send stream"):
+            compare_fs_snapshots(a, b)
+
+
+class TestZfs:
+    def test_subdirectory(self, zfs):
+        a, b, fake = zfs
+        result = compare_fs_snapshots(a, b)
+        assert result.diff == {"a b": DiffReason.CONTENT_MISMATCH}
+        assert result.only_left == set()
+        assert result.only_right == {"new", "y/f"}
+        assert fake.calls[-1] == ["zfs", "diff", "-H", "tank/data@tue", "tank/data@mon"]
+
+
+class TestLocate:
+    def test_mount_table(self, tmp_path: Path, monkeypatch):
+        mountinfo = tmp_path / "mountinfo"
+        mountinfo.write_text(
+            "22 1 0:21 / / rw - ext4 /dev/sda1 rw\n"
+            "40 22 0:40 / /tank/my\\040data rw - zfs tank/my\\040data rw\n"
+            "41 40 0:41 / /tank/my\\040data/.zfs/snapshot/mon rw - zfs tank/my\\040data@mon rw\n"
+        )
+        monkeypatch.setattr(storage, "_MOUNTINFO", str(mountinfo))
+        assert fssnap._mount_of("/etc") == ("/", "ext4", "/dev/sda1")
+        assert fssnap._mount_of("/tank/my data/x") == ("/tank/my data", "zfs", "tank/my data")
+        monkeypatch.setattr(fssnap.os.path, "realpath", lambda path: path)
+        assert fssnap._locate("/tank/my data/.zfs/snapshot/mon/x/y") == (
+            "zfs", "tank/my data", "tank/my data@mon", "x/y")
+        assert fssnap._locate("/tank/my data/.zfs/snapshot/tue") == (
+            "zfs", "tank/my data", "tank/my data@tue", "")
+        assert fssnap._locate("/tank/my data/x") is None
+        assert fssnap._locate("/etc") is None
+
+
+class TestErrors:
+    def test_not_snapshots(self, zfs, tmp_path: Path):
+        a, b, _ = zfs
+        with pytest.raises(UnsupportedSourceError, match="not snapshots"):
+            compare_fs_snapshots(a, str(tmp_path))
+        with pytest.raises(UnsupportedSourceError, match="same directory"):
+            compare_fs_snapshots(a, os.path.dirname(b))
+        with pytest.raises(SourceNotFoundError):
+            compare_fs_snapshots(a, str(tmp_path / "missing"))
+
+    def test_tool_failure(self):
+        with pytest.raises(SourceReadError, match="boom"):
+            fssnap._run(["sh", "-c", "echo boom >&2; exit 1"], "x")
+        with pytest.raises(UnsupportedSourceError, match="command not found"):
+            fssnap._run(["komparu-no-such-tool"], "x")
+
+
+class TestCli:
+    def test_compare(self, btrfs, capsys):
+        a, b, _ = btrfs
+        assert main(["compare", "--fs-snapshots", a, b]) == 1
+        out = capsys.readouterr().out
+        assert "edited.txt" in out and "unlisted.txt" not in out
+
+    def test_refused(self, btrfs, capsys):
+        a, b, fake = btrfs
+        assert main(["compare", "--fs-snapshots", "--names-only", a, b]) == 2
+        assert "do not apply" in capsys.readouterr().err
+        assert main(["compare", "--fs-snapshots", "--forensic", a, b]) == 2
+        assert "cannot be combined with --fs-snapshots" in capsys.readouterr().err
+        assert fake.calls == []
@@ -0,0 +1,69 @@
+"""Tests for fuzzy hashing and similarity scores."""
+
+from __future__ import annotations
+
+import random
+
+import pytest
+
+import komparu
+
+
+def prose(seed: int = 1, words: int = 20_000) -> bytes:
+    rng = random.Random(seed)
+    return b" ".join(bytes(rng.choices(b"abcdefghijklmnop", k=rng.randint(2, 9)))
+                     for _ in range(words))
+
+
+class TestFuzzyHash:
+    def test_format(self, make_file):
+        fuzzy = komparu.fuzzy_hash(str(make_file("a.txt", prose())))
+        block_size, digest1, digest2 = fuzzy.split(":")
+        assert int(block_size) % 3 == 0
+        assert 32 <= len(digest1) <= 64
+        assert len(digest2) <= 32
+
+    def test_deterministic(self, make_file):
+        a = make_file("a.txt", prose())
+        b = make_file("b.txt", prose())
+        assert komparu.fuzzy_hash(str(a)) == komparu.fuzzy_hash(str(b))
+
+    def test_empty(self, make_file):
+        assert komparu.fuzzy_hash(str(make_file("e", b""))) == "3::"
+
+    def test_missing_file(self, tmp_path):
+        with pytest.raises(OSError):
+            komparu.fuzzy_hash(str(tmp_path / "nope"))
+
+
+class TestFuzzyScore:
+    def test_identical(self, make_file):
+        fuzzy = komparu.fuzzy_hash(str(make_file("a.txt", prose())))
+        assert komparu.fuzzy_score(fuzzy, fuzzy) == 100
+
+    def test_lightly_modified(self, make_file):
+        data = prose()
+        edited = data[:5000] + b"EDITED" + data[5010:60_000] + b"inserted " * 5 + data[60_000:]
+        a = komparu.fuzzy_hash(str(make_file("a.txt", data)))
+        b = komparu.fuzzy_hash(str(make_file("b.txt", edited)))
+        assert 80 <= komparu.fuzzy_score(a, b) < 100
+
+    def test_unrelated(self, make_file):
+        a = komparu.fuzzy_hash(str(make_file("a.txt", prose(1))))
+        b = komparu.fuzzy_hash(str(make_file("b.txt", prose(2))))
+        assert komparu.fuzzy_score(a, b) == 0
+
+    def test_block_sizes_far_apart(self, make_file):
+        a = komparu.fuzzy_hash(str(make_file("a.txt", prose())))
+        b = komparu.fuzzy_hash(str(make_file("b.txt", prose()[:1000])))
+        assert komparu.fuzzy_score(a, b) == 0
+
+    def test_adjacent_block_sizes(self):
+        # The digest at twice the block size lines up with the other hash's
+        # first one; at small block sizes the score is capped by digest length
+        assert komparu.fuzzy_score("6:abcdefghijkl:mnopqrstu", "12:mnopqrstu:xyz") == 36
+        assert komparu.fuzzy_score("48:abcdefghijkl:mnopqrstu", "96:mnopqrstu:xyz") == 100
+
+    def test_invalid_hash(self):
+        with pytest.raises(ValueError, match="fuzzy hash"):
+            komparu.fuzzy_score("not a hash", "3::")
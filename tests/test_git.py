@@ -0,0 +1,147 @@
+"""Tests for comparing a directory with a git commit."""
+
+from __future__ import annotations
+
+import os
+import shutil
+import subprocess
+from pathlib import Path
+
+import pytest
+
+import komparu
+from komparu import DiffReason
+from komparu._cli import main
+
+pytestmark = pytest.mark.skipif(shutil.which("git") is None, reason="needs git")
+
+
+def _git(repo: Path, *args: str) -> str:
+    return subprocess.run(["git", "-C", str(repo), "-c", "user.name=t", "-c", "user.email=t@t",
+                           *args], check=True, capture_output=True, text=True).stdout.strip()
+
+
+@pytest.fixture
+def repo(tmp_path: Path) -> Path:
+    root = tmp_path / "repo"
+    (root / "src").mkdir(parents=True)
+    (root / "src" / "app.py").write_text("print('v1')\n")
+    (root / "run.sh").write_text("#!/bin/sh\n")
+    (root / "run.sh").chmod(0o755)
+    (root / ".gitignore").write_text("*.pyc\n")
+    os.symlink("src/app.py", root / "link")
+    _git(root, "init", "-q")
+    _git(root, "add", ".")
+    _git(root, "commit", "-q", "-m", "v1")
+    _git(root, "tag", "v1")
+    return root
+
+
+@pytest.fixture
+def history(repo: Path) -> Path:
+    (repo / "src" / "app.py").write_text("print('v2')\n")
+    (repo / "run.sh").chmod(0o644)
+    (repo / "notes.txt").write_text("longer than before\n")
+    (repo / ".gitignore").unlink()
+    (repo / "link").unlink()
+    (repo / "link").write_text("now a file")
+    _git(repo, "add", "-A")
+    _git(repo, "commit", "-q", "-m", "v2")
+    _git(repo, "tag", "v2")
+    return repo
+
+
+class TestCompareDirGit:
+    def test_clean_checkout(self, repo: Path):
+        assert komparu.compare_dir_git(str(repo), "HEAD").equal is True
+
+    def test_changes(self, repo: Path):
+        (repo / "src" / "app.py").write_text("print('v2')\n")
+        (repo / "run.sh").chmod(0o644)
+        (repo / "new.txt").write_text("")
+        (repo / "link").unlink()
+        os.symlink("elsewhere", repo / "link")
+        (repo / ".gitignore").unlink()
+        result = komparu.compare_dir_git(str(repo), "v1")
+        assert result.diff == {"src/app.py": DiffReason.CONTENT_MISMATCH,
+                               "run.sh": DiffReason.METADATA_MISMATCH,
+                               "link": DiffReason.CONTENT_MISMATCH}
+        assert result.only_left == {"new.txt"}
+        assert result.only_right == {".gitignore"}
+
+    def test_size_and_type(self, repo: Path):
+        (repo / "run.sh").write_text("#!/bin/bash\n")
+        (repo / "link").unlink()
+        (repo / "link").write_text("src/app.py")
+        result = komparu.compare_dir_git(str(repo), "HEAD")
+        assert result.diff == {"run.sh": DiffReason.SIZE_MISMATCH,
+                               "link": DiffReason.TYPE_MISMATCH}
+
+    def test_deployment_outside_repo(self, repo: Path, tmp_path: Path):
+        deploy = tmp_path / "deploy"
+        (deploy / "sub").mkdir(parents=True)
+        shutil.copy(repo / "src" / "app.py", deploy / "app.py")
+        (deploy / "cache.pyc").write_bytes(b"")
+        (deploy / ".gitignore").write_text("*.pyc\n")
+        result = komparu.compare_dir_git(str(deploy), "v1:src", repo=str(repo))
+        assert result.diff == {}
+        assert result.only_left == {"cache.pyc", ".gitignore"}
+        result = komparu.compare_dir_git(str(deploy), "v1:src", repo=str(repo), gitignore=True,
+                                         ignore=[".gitignore"])
+        assert result.equal is True
+
+    def test_unknown_revision(self, repo: Path):
+        with pytest.raises(komparu.SourceNotFoundError):
+            komparu.compare_dir_git(str(repo), "no-such-tag")
+
+    def test_not_a_repository(self, tmp_path: Path):
+        with pytest.raises(komparu.SourceReadError):
+            komparu.compare_dir_git(str(tmp_path), "HEAD", repo=str(tmp_path))
+
+
+class TestCli:
+    def test_compare(self, repo: Path, capsys):
+        (repo / "new.txt").write_text("")
+        (repo / "src" / "app.py").unlink()
+        assert main(["compare", "--git", "v1", str(repo)]) == 1
+        assert capsys.readouterr().out.splitlines() == [
+            "only in tree: new.txt",
+            "only in commit: src/app.py",
+        ]
+
+    def test_one_directory(self, repo: Path, capsys):
+        assert main(["compare", "--git", "v1", str(repo), str(repo)]) == 2
+        assert main(["compare", str(repo)]) == 2
+        assert "two paths" in capsys.readouterr().err
+
+
+class TestCompareGitRevs:
+    def test_compare(self, history: Path):
+        result = komparu.compare_git_revs("v1", "v2", repo=str(history))
+        assert result.diff == {"src/app.py": DiffReason.CONTENT_MISMATCH,
+                               "run.sh": DiffReason.METADATA_MISMATCH,
+                               "link": DiffReason.TYPE_MISMATCH}
+        assert result.only_left == {".gitignore"}
+        assert result.only_right == {"notes.txt"}
+
+    def test_same_tree(self, history: Path):
+        assert komparu.compare_git_revs("v2", "HEAD", repo=str(history)).equal is True
+
+    def test_ignore(self, history: Path):
+        result = komparu.compare_git_revs("v1:src", "v2:src", repo=str(history),
+                                          ignore=["*.py"])
+        assert result.equal is True
+
+    def test_unknown_revision(self, history: Path):
+        with pytest.raises(komparu.SourceNotFoundError):
+            komparu.compare_git_revs("v1", "v3", repo=str(history))
+
+    def test_cli_range(self, history: Path, capsys):
+        assert main(["compare", "--git", "v1..v2", "--git-repo", str(history),
+                     "--ignore", "src", "--ignore", "link", "--ignore", "run.sh"]) == 1
+        assert capsys.readouterr().out.splitlines() == [
+            "only in v1: .gitignore",
+            "only in v2: notes.txt",
+        ]
+        assert main(["compare", "-q", "--git", "v2...v1", "--git-repo", str(history)]) == 0
+        assert main(["compare", "--git", "v1..v2", str(history)]) == 2
@@ -0,0 +1,143 @@
+"""Tests for comparing pre-opened file and directory descriptors."""
+
+from __future__ import annotations
+
+import os
+from pathlib import Path
+
+import pytest
+
+import komparu
+import komparu.aio
+from komparu import ContentComparator, DiffReason, PathFilter
+
+
+@pytest.fixture
+def open_fd():
+    """Open paths with os.open and close them after the test."""
+    fds: list[int] = []
+
+    def _open(path: Path, flags: int = os.O_RDONLY) -> int:
+        fd = os.open(path, flags)
+        fds.append(fd)
+        return fd
+
+    yield _open
+    for fd in fds:
+        os.close(fd)
+
+
+@pytest.fixture
+def tree_files() -> dict[str, bytes]:
+    return {
+        "a/same.txt": b"same",
+        "b/same.txt": b"same",
+        "a/sub/diff.txt": b"one",
+        "b/sub/diff.txt": b"two",
+        "a/left.txt": b"l",
+    }
+
+
+class TestFileHandles:
+    def test_equal(self, make_file, open_fd):
+        a = open_fd(make_file("a.bin", b"data" * 1000))
+        b = open_fd(make_file("b.bin", b"data" * 1000))
+        assert komparu.compare(a, b) is True
+
+    def test_different(self, make_file, open_fd):
+        a = open_fd(make_file("a.bin", b"data-a"))
+        b = open_fd(make_file("b.bin", b"data-b"))
+        assert komparu.compare(a, b) is False
+
+    def test_handles_stay_open_and_keep_position(self, make_file, open_fd):
+        a = open_fd(make_file("a.bin", b"0123456789"))
+        b = open_fd(make_file("b.bin", b"0123456789"))
+        os.lseek(a, 4, os.SEEK_SET)
+        assert komparu.compare(a, b, quick_check=False) is True
+        assert os.lseek(a, 0, os.SEEK_CUR) == 4
+        assert os.read(b, 3) == b"012"
+
+    def test_rejects_mix(self, make_file, open_fd):
+        path = make_file("a.bin", b"data")
+        with pytest.raises(TypeError, match="mix"):
+            komparu.compare(open_fd(path), str(path))
+
+    def test_via_options(self, make_file, open_fd):
+        a = open_fd(make_file("a.bin", b"data"))
+        b = open_fd(make_file("b.bin", b"data"))
+        assert komparu.compare(a, b, options=komparu.CompareOptions(chunk_size=2)) is True
+
+
+class TestDirHandles:
+    def test_matches_paths(self, trees, open_fd):
+        a, b = trees
+        by_path = komparu.compare_dir(str(a), str(b))
+        by_handle = komparu.compare_dir(open_fd(a, os.O_RDONLY | os.O_DIRECTORY),
+                                        open_fd(b, os.O_RDONLY | os.O_DIRECTORY))
+        assert by_handle == by_path
+        assert by_handle.diff == {"sub/diff.txt": DiffReason.CONTENT_MISMATCH}
+
+    def test_mixed_with_path(self, trees, open_fd):
+        a, b = trees
+        result = komparu.compare_dir(open_fd(a, os.O_RDONLY | os.O_DIRECTORY), str(b))
+        assert result.only_left == {"left.txt"}
+
+    def test_iter_dir(self, trees, open_fd):
+        a, b = trees
+        entries = komparu.iter_dir(open_fd(a, os.O_RDONLY | os.O_DIRECTORY),
+                                   open_fd(b, os.O_RDONLY | os.O_DIRECTORY))
+        by_path = {e.path: e for e in entries}
+        assert set(by_path) == {"sub/diff.txt", "left.txt"}
+        assert by_path["sub/diff.txt"].size_a == 3
+
+    def test_plan_dir(self, trees, open_fd):
+        a, b = trees
+        plan = komparu.plan_dir(open_fd(a, os.O_RDONLY | os.O_DIRECTORY),
+                                open_fd(b, os.O_RDONLY | os.O_DIRECTORY))
+        assert {p.path for p in plan.pairs} == {"same.txt", "sub/diff.txt"}
+
+    def test_path_filter_gets_metadata(self, trees, open_fd):
+        a, b = trees
+        seen: dict[str, int | None] = {}
+
+        class Record(PathFilter):
+            def include(self, path, stat_a, stat_b) -> bool:
+                seen[path] = stat_a.st_size if stat_a is not None else None
+                return True
+
+        komparu.compare_dir(open_fd(a, os.O_RDONLY | os.O_DIRECTORY),
+                            open_fd(b, os.O_RDONLY | os.O_DIRECTORY), path_filter=Record())
+        assert seen["sub/diff.txt"] == 3
+
+    def test_progress(self, trees, open_fd):
+        a, b = trees
+        updates = []
+        komparu.compare_dir(open_fd(a, os.O_RDONLY | os.O_DIRECTORY),
+                            open_fd(b, os.O_RDONLY | os.O_DIRECTORY), progress=updates.append)
+        assert updates[-1].bytes_done == len(b"same") + len(b"one")
+
+    def test_comparators_need_paths(self, trees, open_fd):
+        a, b = trees
+
+        class Never(ContentComparator):
+            def compare(self, path_a: str, path_b: str) -> bool:
+                return False
+
+        with pytest.raises(TypeError, match="directory paths"):
+            komparu.compare_dir(open_fd(a, os.O_RDONLY | os.O_DIRECTORY), str(b),
+                                comparators={"*.txt": Never()})
+
+    def test_negative_handle(self, trees):
+        _, b = trees
+        with pytest.raises(ValueError, match="non-negative"):
+            komparu.compare_dir(-3, str(b))
+
+
+class TestAsync:
+    @pytest.mark.asyncio
+    async def test_iter_dir(self, trees, open_fd):
+        a, b = trees
+        paths = {e.path async for e in komparu.aio.iter_dir(
+            open_fd(a, os.O_RDONLY | os.O_DIRECTORY),
+            open_fd(b, os.O_RDONLY | os.O_DIRECTORY))}
+        assert paths == {"sub/diff.txt", "left.txt"}
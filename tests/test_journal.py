@@ -0,0 +1,113 @@
+"""Tests for ChangeJournal: repeated verification of the paths that changed."""
+
+from __future__ import annotations
+
+import os
+import shutil
+
+import pytest
+
+import komparu._journal as journal_module
+from komparu import (
+    CancelToken,
+    ChangeJournal,
+    CompareOptions,
+    ComparisonCancelledError,
+    DiffReason,
+)
+
+
+@pytest.fixture(params=["fanotify", "inotify", "poll"])
+def journal(request, trees):
+    options = CompareOptions(follow_symlinks=request.param == "inotify")
+    with ChangeJournal(*map(str, trees), options=options,
+                       poll=request.param == "poll") as journal:
+        if journal.backend != request.param:
+            pytest.skip(f"{request.param} is not available here")
+        yield journal
+
+
+@pytest.fixture
+def tops(monkeypatch) -> list[str]:
+    """Paths each verification re-compares."""
+    seen: list[str] = []
+    entries = journal_module._entries
+
+    def spy(roots, top, *args):
+        seen.append(top)
+        return entries(roots, top, *args)
+
+    monkeypatch.setattr(journal_module, "_entries", spy)
+    return seen
+
+
+class TestVerify:
+    def test_only_touched_paths(self, journal, trees, tops):
+        a, b = trees
+        assert journal.pending is None
+        first = journal.verify()
+        assert first.diff == {"diff.txt": DiffReason.CONTENT_MISMATCH}
+        assert journal.verify() == first
+        assert tops == []
+
+        (b / "diff.txt").write_bytes(b"one")
+        (a / "sub" / "new.txt").write_bytes(b"new")
+        # Polling also sees the directory whose modification time changed
+        assert journal.pending - {"sub"} == {"diff.txt", "sub/new.txt"}
+        result = journal.verify()
+        assert result.diff == {}
+        assert result.only_left == {"sub/new.txt"}
+        assert "same.txt" not in tops and "diff.txt" in tops
+
+    def test_moved_directory(self, journal, trees, tops):
+        a, b = trees
+        journal.verify()
+        os.rename(b / "sub", b / "moved")
+        result = journal.verify()
+        assert result.only_left == {"sub/deep.txt"}
+        assert result.only_right == {"moved/deep.txt"}
+        shutil.rmtree(b / "moved")
+        (b / "diff.txt").unlink()
+        result = journal.verify()
+        assert result.only_left == {"sub/deep.txt", "diff.txt"}
+        assert result.only_right == set()
+        assert "same.txt" not in tops
+
+
+class TestJournal:
+    def test_cancelled(self, trees):
+        a, b = trees
+        with ChangeJournal(str(a), str(b)) as journal:
+            token = CancelToken()
+            token.cancel()
+            with pytest.raises(ComparisonCancelledError):
+                journal.verify(cancel=token)
+            (a / "same.txt").write_bytes(b"changed")
+            assert journal.pending is None
+            assert journal.verify().diff.keys() == {"diff.txt", "same.txt"}
+
+    def test_lost_events(self, trees):
+        with ChangeJournal(*map(str, trees)) as journal:
+            journal.verify()
+            journal._add(None)  # what a queue overflow leaves
+            assert journal.pending is None
+            assert journal.verify().diff == {"diff.txt": DiffReason.CONTENT_MISMATCH}
+
+    def test_closed(self, trees):
+        journal = ChangeJournal(*map(str, trees), poll=True)
+        assert "backend='poll'" in repr(journal)
+        journal.close()
+        journal.close()
+        with pytest.raises(ValueError, match="closed"):
+            journal.verify()
+        with pytest.raises(TypeError, match="CompareOptions"):
+            ChangeJournal(*map(str, trees), options={})  # type: ignore[arg-type]
+
+    def test_ignore(self, trees):
+        a, b = trees
+        options = CompareOptions(ignore=("sub",), follow_symlinks=False)
+        with ChangeJournal(str(a), str(b), options=options) as journal:
+            journal.verify()
+            (a / "sub" / "deep.txt").write_bytes(b"changed")
+            assert journal.pending == set()
+            assert journal.verify().diff == {"diff.txt": DiffReason.CONTENT_MISMATCH}
@@ -0,0 +1,153 @@
+"""Tests for the journald/syslog logging sink."""
+
+from __future__ import annotations
+
+import socket
+from collections.abc import Iterator
+from pathlib import Path
+
+import pytest
+
+import komparu._logsink as logsink
+from komparu import DiffReason, DirResult, LogSink
+from komparu._cli import main
+
+
+def _journal(record: bytes) -> dict[str, str]:
+    """Fields of a journald native-protocol datagram."""
+    fields = {}
+    while record:
+        line, _, rest = record.partition(b"\n")
+        if b"=" in line:
+            name, _, value = line.partition(b"=")
+            record = rest
+        else:
+            size = int.from_bytes(rest[:8], "little")
+            name, value, record = line, rest[8:8 + size], rest[8 + size + 1:]
+        fields[name.decode()] = value.decode()
+    return fields
+
+
+def _received(sock: socket.socket) -> list[bytes]:
+    records = []
+    sock.settimeout(0.2)
+    try:
+        while True:
+            records.append(sock.recv(65536))
+    except TimeoutError:
+        return records
+
+
+@pytest.fixture
+def unix_socket(tmp_path: Path) -> Iterator[tuple[str, socket.socket]]:
+    path = str(tmp_path / "log.sock")
+    sock = socket.socket(socket.AF_UNIX, socket.SOCK_DGRAM)
+    sock.bind(path)
+    yield path, sock
+    sock.close()
+
+
+@pytest.fixture
+def udp_socket() -> Iterator[socket.socket]:
+    sock = socket.socket(socket.AF_INET, socket.SOCK_DGRAM)
+    sock.bind(("127.0.0.1", 0))
+    yield sock
+    sock.close()
+
+
+RESULT = DirResult(equal=False, diff={"a.txt": DiffReason.CONTENT_MISMATCH},
+                   only_left={"old"}, only_right=set())
+
+
+class TestJournald:
+    def test_result_and_entries(self, unix_socket):
+        path, sock = unix_socket
+        with LogSink("journald", address=path) as sink:
+            sink.dir_result(RESULT, "/srv/a", "/srv/b", sides=("/srv/a", "/srv/b"))
+        summary, diff, only = [_journal(r) for r in _received(sock)]
+        assert summary["MESSAGE"] == "/srv/a /srv/b differ: 1 diff, 1 only left"
+        assert summary["PRIORITY"] == "4"
+        assert summary["SYSLOG_IDENTIFIER"] == "komparu"
+        assert summary["KOMPARU_EQUAL"] == "false"
+        assert summary["KOMPARU_DIFF"] == "1"
+        assert diff["KOMPARU_PATH"] == "a.txt"
+        assert diff["KOMPARU_REASON"] == "content_mismatch"
+        assert diff["PRIORITY"] == "5"
+        assert only["MESSAGE"] == "only in /srv/a: old"
+
+    def test_newlines_and_errors(self, unix_socket):
+        path, sock = unix_socket
+        with LogSink("journald", address=path, entries=False) as sink:
+            sink.dir_result(RESULT, "/srv/a", "/srv/b")
+            sink.error("cannot read\n/srv/a", left="/srv/a")
+        summary, error = [_journal(r) for r in _received(sock)]
+        assert summary["KOMPARU_EVENT"] == "result"
+        assert error == {**error, "MESSAGE": "cannot read\n/srv/a", "PRIORITY": "3",
+                         "KOMPARU_EVENT": "error", "KOMPARU_LEFT": "/srv/a"}
+
+    def test_forensic(self, unix_socket):
+        path, sock = unix_socket
+        with LogSink("journald", address=path) as sink:
+            sink.forensic(True, 0)
+            sink.forensic(True, 2)
+        kept, denied = [_journal(r) for r in _received(sock)]
+        assert kept["MESSAGE"].endswith("access times kept")
+        assert kept["KOMPARU_EVENT"] == "forensic"
+        assert denied["KOMPARU_ATIME_DENIED"] == "2"
+        assert denied["MESSAGE"].endswith("access times may have changed")
+
+    def test_auto(self, unix_socket, monkeypatch):
+        path, sock = unix_socket
+        monkeypatch.setattr(logsink, "JOURNAL_SOCKET", path)
+        with LogSink() as sink:
+            sink.file_result(True, "a", "b")
+        assert _journal(_received(sock)[0])["MESSAGE"] == "a b equal"
+
+
+class TestSyslog:
+    def test_local(self, unix_socket):
+        path, sock = unix_socket
+        with LogSink("syslog", address=path, facility="local3") as sink:
+            sink.file_result(False, "a", "b")
+        record = _received(sock)[0]
+        assert record.startswith(b"<156>1 ")  # local3 * 8 + warning
+        assert b'[komparu@32473 event="result" left="a" right="b" equal="false"]' in record
+        assert record.endswith(b"\xef\xbb\xbfa b differ")
+
+    def test_udp(self, udp_socket):
+        port = udp_socket.getsockname()[1]
+        with LogSink(f"syslog://127.0.0.1:{port}") as sink:
+            sink.error('bad "path]"', path='x"]')
+        record = _received(udp_socket)[0]
+        assert record.startswith(b"<11>1 ")
+        assert b' komparu ' in record and b" error [" in record
+        assert b'path="x\\"\\]"' in record
+
+    def test_bad_arguments(self):
+        with pytest.raises(ValueError, match="log target"):
+            LogSink("file")
+        with pytest.raises(ValueError, match="facility"):
+            LogSink("syslog", facility="kern")
+        with pytest.raises(ValueError, match="HOST"):
+            LogSink("syslog://")
+
+
+class TestCli:
+    def test_compare(self, tmp_path: Path, udp_socket):
+        (tmp_path / "a").mkdir()
+        (tmp_path / "b").mkdir()
+        (tmp_path / "a" / "f").write_bytes(b"1")
+        (tmp_path / "b" / "f").write_bytes(b"2")
+        target = f"syslog://127.0.0.1:{udp_socket.getsockname()[1]}"
+        assert main(["compare", "-q", "--log", target,
+                     str(tmp_path / "a"), str(tmp_path / "b")]) == 1
+        assert main(["compare", "--log", target, str(tmp_path / "a"),
+                     str(tmp_path / "missing")]) == 2
+        summary, entry, error = _received(udp_socket)
+        assert b'differ: 1 diff' in summary
+        assert b'path="f" reason="content_mismatch"' in entry
+        assert b'command="compare"' in error and b"<11>" in error
+
+    def test_bad_target(self, tmp_path: Path, capsys):
+        assert main(["compare", "--log", "file", str(tmp_path), str(tmp_path)]) == 2
+        assert "log target" in capsys.readouterr().err
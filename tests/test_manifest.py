@@ -0,0 +1,418 @@
+"""Tests for checksum manifests."""
+
+from __future__ import annotations
+
+import hashlib
+import os
+from pathlib import Path
+
+import pytest
+
+import komparu
+
+
+def sha256(data: bytes) -> str:
+    return hashlib.sha256(data).hexdigest()
+
+
+def crc32c_reference(data: bytes) -> int:
+    crc = 0xFFFFFFFF
+    for byte in data:
+        crc ^= byte
+        for _ in range(8):
+            crc = (crc >> 1) ^ (0x82F63B78 & -(crc & 1))
+    return crc ^ 0xFFFFFFFF
+
+
+class TestHashTree:
+    def test_digests_in_path_order(self, make_files, tmp_path):
+        make_files({"b.txt": b"two", "a.txt": b"one", "sub/c.txt": b"three"})
+        assert list(komparu.hash_tree(str(tmp_path))) == [
+            ("a.txt", sha256(b"one")),
+            ("b.txt", sha256(b"two")),
+            ("sub/c.txt", sha256(b"three")),
+        ]
+
+    def test_parallel_matches_sequential(self, make_files, tmp_path):
+        make_files({f"d{i % 7}/f{i}": os.urandom(i * 100) for i in range(200)})
+        parallel = list(komparu.hash_tree(str(tmp_path), max_workers=4))
+        assert parallel == list(komparu.hash_tree(str(tmp_path), max_workers=1))
+        assert len(parallel) == 200
+
+    def test_algorithm(self, make_files, tmp_path):
+        make_files({"a": b"data"})
+        [(_, digest)] = komparu.hash_tree(str(tmp_path), algorithm="blake2b")
+        assert digest == hashlib.blake2b(b"data").hexdigest()
+
+    def test_crc32c(self, make_files, tmp_path):
+        data = os.urandom(1_000_003)
+        make_files({"check": b"123456789", "big": data})
+        digests = dict(komparu.hash_tree(str(tmp_path), algorithm="crc32c"))
+        assert digests["check"] == "e3069283"
+        assert digests["big"] == f"{crc32c_reference(data):08x}"
+
+    def test_crc32(self, make_files, tmp_path):
+        make_files({"check": b"123456789"})
+        assert dict(komparu.hash_tree(str(tmp_path), algorithm="crc32")) == {"check": "cbf43926"}
+
+    def test_optional_algorithms(self, make_files, tmp_path):
+        make_files({"a": b"data"})
+        xxhash = pytest.importorskip("xxhash")
+        [(_, digest)] = komparu.hash_tree(str(tmp_path), algorithm="xxh3")
+        assert digest == xxhash.xxh3_64(b"data").hexdigest()
+        blake3 = pytest.importorskip("blake3")
+        [(_, digest)] = komparu.hash_tree(str(tmp_path), algorithm="blake3")
+        assert digest == blake3.blake3(b"data").hexdigest()
+
+    def test_ignore(self, make_files, tmp_path):
+        make_files({"keep.txt": b"", "build/out.o": b"", "x.log": b""})
+        paths = [p for p, _ in komparu.hash_tree(str(tmp_path), ignore=["build", "*.log"])]
+        assert paths == ["keep.txt"]
+
+    def test_skips_directories_and_empty_tree(self, tmp_path):
+        (tmp_path / "empty").mkdir()
+        assert list(komparu.hash_tree(str(tmp_path))) == []
+
+    def test_unsupported_algorithm(self, tmp_path):
+        with pytest.raises(ValueError, match="unsupported hash algorithm"):
+            list(komparu.hash_tree(str(tmp_path), algorithm="nope"))
+        with pytest.raises(ValueError, match="no fixed digest size"):
+            list(komparu.hash_tree(str(tmp_path), algorithm="shake_256"))
+
+    def test_missing_directory(self, tmp_path):
+        with pytest.raises(OSError):
+            list(komparu.hash_tree(str(tmp_path / "nope")))
+
+
+class TestParseManifest:
+    def test_formats(self):
+        digest = sha256(b"")
+        assert komparu.parse_manifest([
+            f"{digest}  plain.txt\n",
+            f"{digest.upper()} *binary.bin\n",
+            "\n",
+            f"\\{digest}  back\\\\slash\\nnewline\n",
+            f"{digest}  ./dotted\n",
+        ]).digests == {
+            "plain.txt": digest,
+            "binary.bin": digest,
+            "back\\slash\nnewline": digest,
+            "dotted": digest,
+        }
+
+    def test_bsd_lines(self):
+        digest = sha256(b"")
+        manifest = komparu.parse_manifest([
+            f"SHA256 (plain.txt) = {digest}\n",
+            f"SHA256 (with (parens)) = {digest}\n",
+            f"\\SHA256 (back\\\\slash) = {digest}\n",
+        ])
+        assert manifest.algorithm == "sha256"
+        assert manifest.digests == {"plain.txt": digest, "with (parens)": digest,
+                                    "back\\slash": digest}
+
+    def test_windows_md5_file(self):
+        digest = hashlib.md5(b"").hexdigest()
+        assert komparu.parse_manifest([
+            f"\ufeff; Generated by md5summer\r\n",
+            f"{digest} *Disc 1\\track01.flac\r\n",
+            "# comment\r\n",
+        ]).digests == {"Disc 1/track01.flac": digest}
+
+    def test_gnu_lines_name_no_algorithm(self):
+        assert komparu.parse_manifest([f"{sha256(b'')}  a"]).algorithm is None
+
+    def test_mixed_bsd_algorithms(self):
+        with pytest.raises(ValueError, match="m:2: MD5 line in a sha256 manifest"):
+            komparu.parse_manifest([f"SHA256 (a) = {sha256(b'')}",
+                                    f"MD5 (b) = {hashlib.md5(b'').hexdigest()}"], "m")
+
+    def test_unknown_bsd_algorithm(self):
+        with pytest.raises(ValueError, match="unknown hash algorithm FOO"):
+            komparu.parse_manifest(["FOO (a) = 00"])
+
+    @pytest.mark.parametrize("line", ["nothex  a", "abc", "abc0  ", "abc0 -a"])
+    def test_malformed(self, line):
+        with pytest.raises(ValueError, match="m.sha256:1: not a checksum line"):
+            komparu.parse_manifest([line], "m.sha256")
+
+
+    def test_windows_names(self, tmp_path, monkeypatch):
+        # Unpaired surrogates of Windows names are read back from their WTF-8 bytes
+        digest = sha256(b"")
+        monkeypatch.setattr("komparu._manifest.PATH_ERRORS", "komparu.wtf8")
+        (tmp_path / "m").write_bytes(f"{digest}  a".encode() + b"\xed\xa0\x80\xff\n")
+        assert komparu.read_manifest(str(tmp_path / "m")).digests == {"a\ud800\udcff": digest}
+        assert "a\ud800".encode("utf-8", "komparu.wtf8") == b"a\xed\xa0\x80"
+
+
+class TestParseSfv:
+    def test_lines(self):
+        manifest = komparu.parse_sfv([
+            "\ufeff; Generated by WIN-SFV32 v1.1a\r\n",
+            "; comment\n",
+            "release.r00 CBF43926\n",
+            "sub\\name with spaces.nfo    00000000\n",
+            "\n",
+        ])
+        assert manifest.algorithm == "crc32"
+        assert manifest.digests == {"release.r00": "cbf43926",
+                                    "sub/name with spaces.nfo": "00000000"}
+
+    def test_malformed(self):
+        with pytest.raises(ValueError, match="r.sfv:2: not an SFV line"):
+            komparu.parse_sfv(["a 00000000", "a 123"], "r.sfv")
+
+    def test_read_manifest_by_name(self, make_files, tmp_path):
+        make_files({"tree/Release/a.rar": b"123456789",
+                    "release.SFV": b"; x\r\nRelease\\a.rar cbf43926\r\n"})
+        manifest = komparu.read_manifest(str(tmp_path / "release.SFV"))
+        assert manifest.digests == {"Release/a.rar": "cbf43926"}
+        assert komparu.verify_manifest(str(tmp_path / "tree"), str(tmp_path / "release.SFV")).equal
+
+
+class TestFormatManifestLine:
+    def test_gnu(self):
+        assert komparu.format_manifest_line("ab", "a b") == "ab  a b"
+        assert komparu.format_manifest_line("ab", "a\nb") == "\\ab  a\\nb"
+
+    def test_bsd(self):
+        assert komparu.format_manifest_line("ab", "f", algorithm="sha256") == "SHA256 (f) = ab"
+        assert komparu.format_manifest_line("ab", "f", algorithm="blake2b") == "BLAKE2b (f) = ab"
+        assert komparu.format_manifest_line("ab", "f", algorithm="sha3_256") == "SHA3-256 (f) = ab"
+        assert komparu.format_manifest_line("ab", "a\\b", algorithm="md5") == "\\MD5 (a\\\\b) = ab"
+
+    def test_round_trip(self):
+        names = ["plain", "sp ace", "(paren)", "back\\slash", "new\nline"]
+        for algorithm in (None, "sha256"):
+            lines = [komparu.format_manifest_line(sha256(n.encode()), n, algorithm=algorithm)
+                     for n in names]
+            manifest = komparu.parse_manifest(lines)
+            assert manifest.digests == {n: sha256(n.encode()) for n in names}
+            assert manifest.algorithm == algorithm
+
+
+class TestVerifyManifest:
+    def test_matches(self, make_files, tmp_path):
+        make_files({"tree/a": b"one", "tree/sub/b": b"two"})
+        manifest = dict(komparu.hash_tree(str(tmp_path / "tree")))
+        assert komparu.verify_manifest(str(tmp_path / "tree"), manifest).equal
+
+    def test_missing_extra_and_changed(self, make_files, tmp_path):
+        make_files({"tree/same": b"1", "tree/changed": b"new", "tree/extra": b""})
+        manifest = {"same": sha256(b"1"), "changed": sha256(b"old"), "gone": sha256(b"")}
+        result = komparu.verify_manifest(str(tmp_path / "tree"), manifest)
+        assert result.diff == {"changed": komparu.DiffReason.CONTENT_MISMATCH}
+        assert result.only_left == {"extra"}
+        assert result.only_right == {"gone"}
+        assert not result.equal
+
+    def test_manifest_file_and_algorithm_guess(self, make_files, tmp_path):
+        make_files({"tree/a": b"data",
+                    "list.md5": f"{hashlib.md5(b'data').hexdigest()}  a\n".encode()})
+        assert komparu.verify_manifest(str(tmp_path / "tree"), str(tmp_path / "list.md5")).equal
+
+    def test_explicit_algorithm(self, make_files, tmp_path):
+        make_files({"tree/a": b"data"})
+        manifest = {"a": hashlib.blake2s(b"data").hexdigest()}
+        # 64 hex digits read as sha256 unless told otherwise
+        assert not komparu.verify_manifest(str(tmp_path / "tree"), manifest).equal
+        assert komparu.verify_manifest(str(tmp_path / "tree"), manifest,
+                                       algorithm="blake2s").equal
+
+    def test_crc32c_manifest(self, make_files, tmp_path):
+        make_files({"tree/a": b"123456789", "list": b"CRC32C (a) = e3069283\n"})
+        assert komparu.verify_manifest(str(tmp_path / "tree"), str(tmp_path / "list")).equal
+
+    def test_tagged_manifest_file(self, make_files, tmp_path):
+        make_files({"tree/a": b"data",
+                    "list": f"BLAKE2s (a) = {hashlib.blake2s(b'data').hexdigest()}\n".encode()})
+        assert komparu.verify_manifest(str(tmp_path / "tree"), str(tmp_path / "list")).equal
+
+    def test_mixed_lengths(self, make_files, tmp_path):
+        make_files({"tree/a": b""})
+        with pytest.raises(ValueError, match="hash algorithm"):
+            komparu.verify_manifest(str(tmp_path / "tree"),
+                                    {"a": sha256(b""), "b": hashlib.md5(b"").hexdigest()})
+
+    def test_ignore(self, make_files, tmp_path):
+        make_files({"tree/a": b"", "tree/x.log": b""})
+        manifest = {"a": sha256(b""), "old.log": sha256(b"")}
+        assert komparu.verify_manifest(str(tmp_path / "tree"), manifest, ignore=["*.log"]).equal
+
+
+class TestTreeDigest:
+    def test_same_content_same_digest(self, make_files, tmp_path):
+        files = {"a.txt": b"one", "sub/b.txt": b"two", "sub/deep/c": b""}
+        make_files({f"x/{k}": v for k, v in files.items()})
+        make_files({f"y/{k}": v for k, v in reversed(files.items())})
+        digest = komparu.tree_digest(str(tmp_path / "x"))
+        assert digest == komparu.tree_digest(str(tmp_path / "y"), max_workers=1)
+        assert len(digest) == 64
+
+    def test_documented_canonical_form(self, make_files, tmp_path):
+        make_files({"t/a": b"one", "t/d/b": b"two"})
+
+        def h(data: bytes) -> bytes:
+            return hashlib.sha256(data).digest()
+
+        a = h(b"F" + h(b"one"))
+        d = h(b"D" + b"b\0" + h(b"F" + h(b"two")))
+        root = h(b"D" + b"a\0" + a + b"d\0" + d)
+        assert komparu.tree_digest(str(tmp_path / "t")) == root.hex()
+
+    def test_changes_are_detected(self, make_files, tmp_path):
+        make_files({"t/a": b"one", "t/sub/b": b"two"})
+        tree = tmp_path / "t"
+        digests = {komparu.tree_digest(str(tree))}
+        (tree / "sub" / "b").write_bytes(b"2")
+        digests.add(komparu.tree_digest(str(tree)))
+        (tree / "sub" / "b").rename(tree / "sub" / "c")
+        digests.add(komparu.tree_digest(str(tree)))
+        (tree / "empty").mkdir()
+        digests.add(komparu.tree_digest(str(tree)))
+        assert len(digests) == 4
+
+    def test_file_and_directory_differ(self, tmp_path):
+        (tmp_path / "x").mkdir()
+        (tmp_path / "x" / "e").write_bytes(b"")
+        (tmp_path / "y" / "e").mkdir(parents=True)
+        assert komparu.tree_digest(str(tmp_path / "x")) != komparu.tree_digest(str(tmp_path / "y"))
+
+    def test_metadata(self, make_files, tmp_path):
+        make_files({"x/f": b"", "y/f": b""})
+        os.chmod(tmp_path / "x" / "f", 0o600)
+        os.chmod(tmp_path / "y" / "f", 0o644)
+        x, y = str(tmp_path / "x"), str(tmp_path / "y")
+        assert komparu.tree_digest(x) == komparu.tree_digest(y)
+        assert (komparu.tree_digest(x, metadata=["mode"])
+                != komparu.tree_digest(y, metadata=["mode"]))
+
+    def test_symlinks(self, make_files, tmp_path):
+        make_files({"t/target": b"data"})
+        os.symlink("target", tmp_path / "t" / "link")
+        followed = komparu.tree_digest(str(tmp_path / "t"))
+        assert followed != komparu.tree_digest(str(tmp_path / "t"), follow_symlinks=False)
+
+    def test_ignore(self, make_files, tmp_path):
+        make_files({"x/a": b"1", "y/a": b"1", "y/cache/junk": b""})
+        assert (komparu.tree_digest(str(tmp_path / "x"))
+                == komparu.tree_digest(str(tmp_path / "y"), ignore=["cache"]))
+
+    def test_errors(self, tmp_path):
+        with pytest.raises(ValueError, match="unknown metadata attribute"):
+            komparu.tree_digest(str(tmp_path), metadata=["mtime"])
+        with pytest.raises(ValueError, match="unsupported hash algorithm"):
+            komparu.tree_digest(str(tmp_path), algorithm="nope")
+
+
+class TestCompareManifests:
+    def test_equal(self, make_files, tmp_path):
+        make_files({"a/f": b"1", "a/sub/g": b"2"})
+        left = dict(komparu.hash_tree(str(tmp_path / "a")))
+        assert komparu.compare_manifests(left, dict(left)).equal
+
+    def test_report(self, tmp_path):
+        left = tmp_path / "left.sha256"
+        left.write_text(f"{sha256(b'1')}  same\n{sha256(b'old')}  changed\n{sha256(b'')}  gone\n")
+        right = komparu.Manifest({"same": sha256(b"1"), "changed": sha256(b"new"),
+                                  "new": sha256(b"")}, "sha256")
+        result = komparu.compare_manifests(str(left), right)
+        assert result.diff == {"changed": komparu.DiffReason.CONTENT_MISMATCH}
+        assert result.only_left == {"gone"}
+        assert result.only_right == {"new"}
+        assert not result.equal
+
+    def test_ignore(self):
+        left = {"a": sha256(b""), "x.log": sha256(b"1")}
+        right = {"a": sha256(b""), "x.log": sha256(b"2"), "build/o": sha256(b"")}
+        assert komparu.compare_manifests(left, right, ignore=["*.log", "build"]).equal
+
+    def test_different_algorithms(self):
+        with pytest.raises(ValueError, match="different hash algorithms: sha256 and md5"):
+            komparu.compare_manifests(komparu.Manifest({}, "sha256"), komparu.Manifest({}, "md5"))
+        with pytest.raises(ValueError, match="digest lengths"):
+            komparu.compare_manifests({"a": sha256(b"")}, {"a": hashlib.md5(b"").hexdigest()})
+
+
+class TestUpdateManifest:
+    def test_creates_manifest(self, make_files, tmp_path):
+        make_files({"tree/a": b"one", "tree/sub/b": b"two"})
+        manifest = tmp_path / "tree.sha256"
+        result = komparu.update_manifest(str(tmp_path / "tree"), str(manifest))
+        assert result.only_left == {"a", "sub/b"}
+        assert manifest.read_text() == f"{sha256(b'one')}  a\n{sha256(b'two')}  sub/b\n"
+        assert (tmp_path / "tree.sha256.stat").exists()
+
+    def test_unchanged_files_are_not_read(self, make_files, tmp_path):
+        make_files({"tree/a": b"one", "tree/b": b"two"})
+        tree, manifest = tmp_path / "tree", str(tmp_path / "m")
+        komparu.update_manifest(str(tree), manifest)
+        # Same size and mtime: the old digest is kept, though content changed
+        st = os.stat(tree / "a")
+        (tree / "a").write_bytes(b"ONE")
+        os.utime(tree / "a", ns=(st.st_atime_ns, st.st_mtime_ns))
+        assert komparu.update_manifest(str(tree), manifest).equal
+        assert komparu.read_manifest(manifest).digests["a"] == sha256(b"one")
+
+    def test_changes(self, make_files, tmp_path):
+        make_files({"tree/same": b"1", "tree/changed": b"old", "tree/gone": b""})
+        tree, manifest = tmp_path / "tree", str(tmp_path / "m")
+        komparu.update_manifest(str(tree), manifest)
+        (tree / "changed").write_bytes(b"newer")
+        (tree / "gone").unlink()
+        (tree / "added").write_bytes(b"")
+        result = komparu.update_manifest(str(tree), manifest)
+        assert result.diff == {"changed": komparu.DiffReason.CONTENT_MISMATCH}
+        assert result.only_left == {"added"}
+        assert result.only_right == {"gone"}
+        assert komparu.verify_manifest(str(tree), manifest).equal
+
+    def test_keeps_format_and_algorithm(self, make_files, tmp_path):
+        make_files({"tree/a": b"one", "m": f"SHA512 (a) = {'0' * 128}\n".encode()})
+        komparu.update_manifest(str(tmp_path / "tree"), str(tmp_path / "m"))
+        assert (tmp_path / "m").read_text() == (
+            f"SHA512 (a) = {hashlib.sha512(b'one').hexdigest()}\n")
+
+    def test_algorithm_change_rehashes(self, make_files, tmp_path):
+        make_files({"tree/a": b"one"})
+        tree, manifest = str(tmp_path / "tree"), str(tmp_path / "m")
+        komparu.update_manifest(tree, manifest)
+        result = komparu.update_manifest(tree, manifest, algorithm="md5")
+        assert result.equal
+        assert komparu.read_manifest(manifest).digests == {"a": hashlib.md5(b"one").hexdigest()}
+
+    def test_escaped_names(self, make_files, tmp_path):
+        make_files({"tree/back\\slash": b"x"})
+        tree, manifest = str(tmp_path / "tree"), str(tmp_path / "m")
+        komparu.update_manifest(tree, manifest)
+        assert komparu.update_manifest(tree, manifest).equal
+        assert komparu.verify_manifest(tree, manifest).equal
+
+    def _stale(self, make_files, tmp_path: Path) -> tuple[Path, str]:
+        """A tree whose file "a" changed behind the stat cache's back."""
+        make_files({"tree/a": b"one"})
+        tree, manifest = tmp_path / "tree", str(tmp_path / "m")
+        komparu.update_manifest(str(tree), manifest)
+        st = os.stat(tree / "a")
+        (tree / "a").write_bytes(b"ONE")
+        os.utime(tree / "a", ns=(st.st_atime_ns, st.st_mtime_ns))
+        return tree, manifest
+
+    def test_damaged_cache_rehashes(self, make_files, tmp_path):
+        tree, manifest = self._stale(make_files, tmp_path)
+        cache, listed = tmp_path / "m.stat", Path(manifest).read_bytes()
+        data = cache.read_bytes()
+        for broken in (data[:data.rindex(b"#")], data[:-10], data + b"3\t0\tb\n", b""):
+            Path(manifest).write_bytes(listed)
+            cache.write_bytes(broken)
+            assert komparu.update_manifest(str(tree), manifest).diff
+            assert komparu.read_manifest(manifest).digests["a"] == sha256(b"ONE")
+        assert not [name for name in os.listdir(tmp_path) if name.endswith(".komparu-tmp")]
+
+    def test_cache_of_other_manifest_rehashes(self, make_files, tmp_path):
+        # The manifest replaced, the cache left from before: its stats vouch for nothing
+        tree, manifest = self._stale(make_files, tmp_path)
+        Path(manifest).write_text(f"{sha256(b'one')}  a\n\n")
+        assert komparu.update_manifest(str(tree), manifest).diff
+        assert komparu.read_manifest(manifest).digests["a"] == sha256(b"ONE")
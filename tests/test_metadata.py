@@ -0,0 +1,184 @@
+"""Tests for metadata-only comparison."""
+
+from __future__ import annotations
+
+import os
+import stat
+import struct
+import sys
+from pathlib import Path
+
+import pytest
+
+import komparu
+from komparu import DiffReason
+
+
+def _xattrs_supported(path: Path) -> bool:
+    try:
+        os.setxattr(path, "user.komparu-test", b"")
+        os.removexattr(path, "user.komparu-test")
+    except (AttributeError, OSError):
+        return False
+    return True
+
+
+def _set_nodump(path: Path) -> bool:
+    """Set the chattr ``d`` flag (or chflags ``nodump``); False if the file system has none."""
+    if hasattr(os, "chflags"):
+        try:
+            os.chflags(path, stat.UF_NODUMP)
+        except OSError:
+            return False
+        return True
+    if not sys.platform.startswith("linux"):
+        return False
+    import fcntl
+    fs_ioc_setflags, fs_nodump_fl = 0x40086602, 0x40
+    fd = os.open(path, os.O_RDONLY)
+    try:
+        fcntl.ioctl(fd, fs_ioc_setflags, struct.pack("i", fs_nodump_fl))
+    except OSError:
+        return False
+    finally:
+        os.close(fd)
+    return True
+
+
+class TestMetadataDiff:
+    def test_equal(self, make_dir):
+        a = make_dir("a", {"f": b"one"})
+        b = make_dir("b", {"f": b"different content"})
+        os.chmod(a / "f", 0o640)
+        os.chmod(b / "f", 0o640)
+        assert komparu.metadata_diff(str(a / "f"), str(b / "f")) == {}
+
+    def test_mode(self, make_dir):
+        a = make_dir("a", {"f": b""})
+        b = make_dir("b", {"f": b""})
+        os.chmod(a / "f", 0o4755)
+        os.chmod(b / "f", 0o755)
+        assert komparu.metadata_diff(str(a / "f"), str(b / "f")) == {"mode": (0o4755, 0o755)}
+
+    @pytest.mark.skipif(not hasattr(os, "geteuid") or os.geteuid() != 0, reason="needs root")
+    def test_owner(self, make_dir):
+        a = make_dir("a", {"f": b""})
+        b = make_dir("b", {"f": b""})
+        os.chown(b / "f", 1234, 5678)
+        changes = komparu.metadata_diff(str(a / "f"), str(b / "f"))
+        assert changes["owner"] == ((0, 0), (1234, 5678))
+
+    def test_xattrs(self, make_dir):
+        a = make_dir("a", {"f": b""})
+        b = make_dir("b", {"f": b""})
+        if not _xattrs_supported(a / "f"):
+            pytest.skip("no extended attributes here")
+        os.setxattr(a / "f", "user.label", b"golden")
+        changes = komparu.metadata_diff(str(a / "f"), str(b / "f"))
+        assert changes == {"xattrs": ({"user.label": b"golden"}, {})}
+        assert komparu.metadata_diff(str(a / "f"), str(b / "f"), attributes=["mode"]) == {}
+
+    def test_flags(self, make_dir):
+        a = make_dir("a", {"f": b""})
+        b = make_dir("b", {"f": b""})
+        if not _set_nodump(a / "f"):
+            pytest.skip("no file flags here")
+        changes = komparu.metadata_diff(str(a / "f"), str(b / "f"), attributes=["flags"])
+        flags_a, flags_b = changes["flags"]
+        assert flags_a & ~flags_b
+        assert komparu.metadata_diff(str(a / "f"), str(b / "f")) == {}
+
+    def test_birthtime(self, make_dir):
+        a = make_dir("a", {"f": b""})
+        b = make_dir("b", {"f": b""})
+        (b / "f").unlink()
+        (b / "f").write_bytes(b"")
+        os.utime(b / "f", ns=(os.stat(a / "f").st_atime_ns, os.stat(a / "f").st_mtime_ns))
+        changes = komparu.metadata_diff(str(a / "f"), str(b / "f"), attributes=["birthtime"])
+        birth_a, birth_b = changes.get("birthtime", (None, None))
+        if birth_a is None:
+            pytest.skip("no birth time here")
+        assert birth_a < birth_b
+
+    def test_ctime(self, make_dir):
+        a = make_dir("a", {"f": b""})
+        b = make_dir("b", {"f": b""})
+        os.chmod(b / "f", 0o600)
+        os.chmod(b / "f", os.stat(a / "f").st_mode)
+        changes = komparu.metadata_diff(str(a / "f"), str(b / "f"), attributes=["ctime"])
+        assert changes["ctime"][0] < changes["ctime"][1]
+
+    def test_unknown_attribute(self, make_dir):
+        a = make_dir("a", {"f": b""})
+        with pytest.raises(ValueError, match="unknown metadata attribute: mtime"):
+            komparu.metadata_diff(str(a / "f"), str(a / "f"), attributes=["mtime"])
+
+
+class TestCompareDirMetadata:
+    def test_content_not_compared(self, make_dir):
+        a = make_dir("a", {"x.txt": b"one", "sub/y.txt": b"two"})
+        b = make_dir("b", {"x.txt": b"changed", "sub/y.txt": b"2"})
+        assert komparu.compare_dir_metadata(str(a), str(b)).equal
+
+    def test_files_and_directories(self, make_dir):
+        a = make_dir("a", {"bin/tool": b"", "etc/conf": b""})
+        b = make_dir("b", {"bin/tool": b"", "etc/conf": b""})
+        os.chmod(b / "bin" / "tool", 0o777)
+        os.chmod(b / "etc", 0o700)
+        result = komparu.compare_dir_metadata(str(a), str(b))
+        assert result.diff == {"bin/tool": DiffReason.METADATA_MISMATCH,
+                               "etc": DiffReason.METADATA_MISMATCH}
+        assert not result.equal
+
+    def test_one_sided_directory_reported_once(self, make_dir):
+        a = make_dir("a", {"keep": b"", "gone/deep/file": b""})
+        b = make_dir("b", {"keep": b""})
+        result = komparu.compare_dir_metadata(str(a), str(b))
+        assert result.only_left == {"gone"}
+        assert result.only_right == set()
+
+    def test_type_mismatch(self, make_dir):
+        a = make_dir("a", {"kind/inner": b""})
+        b = make_dir("b", {"kind": b""})
+        result = komparu.compare_dir_metadata(str(a), str(b))
+        assert result.diff == {"kind": DiffReason.TYPE_MISMATCH}
+        assert result.only_left == set()
+
+    def test_symlink_loop(self, make_dir):
+        a = make_dir("a", {"f": b""})
+        b = make_dir("b", {"f": b""})
+        os.symlink(".", a / "loop")
+        os.symlink(".", b / "loop")
+        assert komparu.compare_dir_metadata(str(a), str(b)).equal
+
+    def test_ignore(self, make_dir):
+        a = make_dir("a", {"f": b"", "cache/x": b""})
+        b = make_dir("b", {"f": b""})
+        os.chmod(b / "f", 0o600)
+        result = komparu.compare_dir_metadata(str(a), str(b), attributes=["owner"],
+                                              ignore=["cache"])
+        assert result.equal
+
+    def test_ctime_informational(self, make_dir):
+        a = make_dir("a", {"f": b""})
+        b = make_dir("b", {"f": b""})
+        os.chmod(b / "f", 0o600)
+        os.chmod(b / "f", os.stat(a / "f").st_mode)
+        assert komparu.compare_dir_metadata(str(a), str(b), attributes=["mode", "ctime"]).equal
+
+    def test_attributes_per_path(self, make_dir):
+        a = make_dir("a", {"f": b"", "g": b""})
+        b = make_dir("b", {"f": b"", "g": b""})
+        for name in ("f", "g"):
+            os.chmod(b / name, 0o600)
+        result = komparu.compare_dir_metadata(
+            str(a), str(b), attributes=lambda path: ("mode",) if path == "f" else ())
+        assert result.diff == {"f": komparu.DiffReason.METADATA_MISMATCH}
+        with pytest.raises(ValueError, match="colour"):
+            komparu.compare_dir_metadata(str(a), str(b), attributes=lambda path: ("colour",))
+
+    def test_options(self, make_dir):
+        a = make_dir("a", {"f": b"", "tmp/x": b""})
+        b = make_dir("b", {"f": b""})
+        options = komparu.CompareOptions(ignore=("tmp",))
+        assert komparu.compare_dir_metadata(str(a), str(b), options=options).equal
@@ -0,0 +1,158 @@
+"""Tests for the MetricsSink instrumentation hook."""
+
+from __future__ import annotations
+
+from collections import Counter
+from pathlib import Path
+
+import pytest
+
+import komparu
+import komparu.aio
+from komparu import CompareVisitor, MetricsSink
+
+
+class Recorder(MetricsSink):
+    def __init__(self) -> None:
+        self.counters: Counter[str] = Counter()
+        self.timings: dict[str, float] = {}
+
+    def count(self, name: str, value: int = 1) -> None:
+        self.counters[name] += value
+
+    def timing(self, name: str, seconds: float) -> None:
+        self.timings[name] = seconds
+
+
+@pytest.fixture
+def tree_files() -> dict[str, bytes]:
+    return {
+        "a/same.txt": b"hello",
+        "b/same.txt": b"hello",
+        "a/diff.txt": b"one",
+        "b/diff.txt": b"two",
+        "a/left.txt": b"l",
+        "b/right.txt": b"r",
+    }
+
+
+class TestCompareDir:
+    def test_counters(self, trees):
+        a, b = map(str, trees)
+        rec = Recorder()
+        komparu.compare_dir(a, b, metrics=rec)
+        assert rec.counters["files_compared"] == 2
+        assert rec.counters["files_different"] == 1
+        assert rec.counters["only_left"] == 1
+        assert rec.counters["only_right"] == 1
+        assert rec.counters["errors"] == 0
+        assert rec.counters["bytes_compared"] == len(b"hello") + len(b"one")
+
+    def test_timings(self, trees):
+        a, b = map(str, trees)
+        rec = Recorder()
+        komparu.compare_dir(a, b, metrics=rec)
+        assert set(rec.timings) == {"walk", "compare", "total"}
+        assert rec.timings["total"] >= rec.timings["compare"] >= 0
+
+    def test_result_unchanged(self, trees):
+        a, b = map(str, trees)
+        rec = Recorder()
+        assert komparu.compare_dir(a, b, metrics=rec) == komparu.compare_dir(a, b)
+
+    def test_ignored_entries_not_counted(self, trees):
+        a, b = map(str, trees)
+        rec = Recorder()
+        komparu.compare_dir(a, b, metrics=rec, ignore=["diff.txt"])
+        assert rec.counters["files_compared"] == 1
+        assert rec.counters["files_different"] == 0
+
+    def test_with_visitor(self, trees):
+        a, b = map(str, trees)
+        finished: list[str] = []
+
+        class Collect(CompareVisitor):
+            def on_entry_finished(self, entry):
+                finished.append(entry.path)
+
+        rec = Recorder()
+        komparu.compare_dir(a, b, metrics=rec, visitor=Collect())
+        assert len(finished) == 4
+        assert rec.counters["files_compared"] == 2
+
+    def test_via_options(self, trees):
+        a, b = map(str, trees)
+        rec = Recorder()
+        komparu.compare_dir(a, b, metrics=rec,
+                            options=komparu.CompareOptions(max_workers=1))
+        assert rec.counters["files_compared"] == 2
+
+    def test_empty_dirs_record_walk_only(self, tmp_path: Path):
+        (tmp_path / "a").mkdir()
+        (tmp_path / "b").mkdir()
+        rec = Recorder()
+        komparu.compare_dir(str(tmp_path / "a"), str(tmp_path / "b"), metrics=rec)
+        assert set(rec.timings) == {"walk", "total"}
+        assert not +rec.counters
+
+    def test_rejects_non_sink(self, trees):
+        a, b = map(str, trees)
+        with pytest.raises(TypeError, match="MetricsSink"):
+            komparu.compare_dir(a, b, metrics=object())
+
+    def test_default_sink_is_noop(self, trees):
+        a, b = map(str, trees)
+        result = komparu.compare_dir(a, b, metrics=MetricsSink())
+        assert result.equal is False
+
+
+class TestAsync:
+    @pytest.mark.asyncio
+    async def test_counters(self, trees):
+        a, b = map(str, trees)
+        rec = Recorder()
+        await komparu.aio.compare_dir(a, b, metrics=rec)
+        assert rec.counters["files_compared"] == 2
+        assert rec.counters["only_left"] == 1
+        assert "total" in rec.timings
+
+
+def _samples(text: str) -> dict[str, float]:
+    return {line.rsplit(" ", 1)[0]: float(line.rsplit(" ", 1)[1])
+            for line in text.splitlines() if not line.startswith("#")}
+
+
+class TestPrometheus:
+    def test_totals_across_runs(self, trees):
+        a, b = map(str, trees)
+        metrics = komparu.PrometheusMetrics()
+        komparu.compare_dir(a, b, metrics=metrics)
+        komparu.compare_dir(a, b, metrics=metrics)
+        samples = _samples(metrics.render())
+        assert samples["komparu_files_compared_total"] == 4
+        assert samples["komparu_files_different_total"] == 2
+        assert samples["komparu_bytes_compared_total"] == 2 * len(b"helloone")
+        assert samples["komparu_retries_exhausted_total"] == 0
+        assert 'komparu_last_phase_seconds{phase="total"}' in samples
+        assert "komparu_last_run_age_seconds" not in samples
+
+    def test_comparisons(self):
+        metrics = komparu.PrometheusMetrics()
+        metrics.started()
+        metrics.started()
+        assert _samples(metrics.render())["komparu_comparisons_running"] == 2
+        metrics.finished("different", 1.5)
+        samples = _samples(metrics.render())
+        assert samples["komparu_comparisons_running"] == 1
+        assert samples['komparu_comparisons_total{outcome="different"}'] == 1
+        assert samples['komparu_comparisons_total{outcome="equal"}'] == 0
+        assert samples["komparu_last_run_duration_seconds"] == 1.5
+        assert 0 <= samples["komparu_last_run_age_seconds"] < 60
+        with pytest.raises(ValueError, match="outcome"):
+            metrics.finished("maybe", 0)
+
+    def test_format(self):
+        text = komparu.PrometheusMetrics().render()
+        assert text.endswith("\n")
+        assert "# TYPE komparu_files_compared_total counter" in text
+        assert "# TYPE komparu_comparisons_running gauge" in text
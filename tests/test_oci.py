@@ -0,0 +1,240 @@
+"""Tests for comparing container images."""
+
+from __future__ import annotations
+
+import gzip
+import hashlib
+import io
+import json
+import lzma
+import tarfile
+from pathlib import Path
+
+import pytest
+
+import komparu
+from komparu._cli import main
+from komparu._comparator import DecompressComparator
+from komparu._oci import flatten_image
+
+DiffReason = komparu.DiffReason
+
+
+def _layer(entries: dict[str, bytes | str | None], mode: int = 0o644) -> bytes:
+    """A gzipped layer tarball: bytes are files, str symlink targets, None directories."""
+    buf = io.BytesIO()
+    with tarfile.open(fileobj=buf, mode="w") as tar:
+        for name, value in entries.items():
+            info = tarfile.TarInfo(name)
+            if value is None:
+                info.type, info.mode = tarfile.DIRTYPE, 0o755
+                tar.addfile(info)
+            elif isinstance(value, str):
+                info.type, info.linkname = tarfile.SYMTYPE, value
+                tar.addfile(info)
+            else:
+                info.size, info.mode = len(value), mode
+                tar.addfile(info, io.BytesIO(value))
+    return gzip.compress(buf.getvalue())
+
+
+def _blob(root: Path, data: bytes) -> dict[str, object]:
+    digest = hashlib.sha256(data).hexdigest()
+    (root / "blobs" / "sha256").mkdir(parents=True, exist_ok=True)
+    (root / "blobs" / "sha256" / digest).write_bytes(data)
+    return {"digest": f"sha256:{digest}", "size": len(data)}
+
+
+def _manifest(root: Path, layers: list[bytes]) -> dict[str, object]:
+    manifest = {
+        "schemaVersion": 2,
+        "mediaType": "application/vnd.oci.image.manifest.v1+json",
+        "config": _blob(root, b"{}"),
+        "layers": [{"mediaType": "application/vnd.oci.image.layer.v1.tar+gzip",
+                    **_blob(root, layer)} for layer in layers],
+    }
+    return {"mediaType": manifest["mediaType"],
+            **_blob(root, json.dumps(manifest).encode())}
+
+
+def _layout(root: Path, images: dict[str, list[bytes]]) -> Path:
+    """An OCI image layout with one image per tag."""
+    root.mkdir()
+    (root / "oci-layout").write_text('{"imageLayoutVersion": "1.0.0"}')
+    manifests = [{**_manifest(root, layers),
+                  "annotations": {"org.opencontainers.image.ref.name": tag}}
+                 for tag, layers in images.items()]
+    (root / "index.json").write_text(json.dumps({"schemaVersion": 2, "manifests": manifests}))
+    return root
+
+
+def _docker_save(path: Path, tags: dict[str, list[bytes]]) -> Path:
+    """A ``docker save`` tarball of the classic format."""
+    with tarfile.open(path, "w") as tar:
+        def add(name: str, data: bytes) -> None:
+            info = tarfile.TarInfo(name)
+            info.size = len(data)
+            tar.addfile(info, io.BytesIO(data))
+
+        images = []
+        for number, (tag, layers) in enumerate(tags.items()):
+            names = []
+            for index, layer in enumerate(layers):
+                names.append(f"{number}-{index}/layer.tar")
+                add(names[-1], gzip.decompress(layer))
+            images.append({"Config": "config.json", "RepoTags": [tag], "Layers": names})
+        add("manifest.json", json.dumps(images).encode())
+    return path
+
+
+BASE = _layer({"etc": None, "etc/os-release": b"v1\n", "bin": None, "bin/sh": b"shell",
+               "lib": None, "lib/libc.so": b"libc", "lib/old.so": b"old",
+               "var": None, "var/cache": None, "var/cache/a": b"a"})
+
+
+class TestFlatten:
+    def test_whiteouts(self, tmp_path: Path):
+        upper = _layer({"lib/.wh.old.so": b"", "var/cache/.wh..wh..opq": b"",
+                        "var/cache/b": b"b"})
+        layout = _layout(tmp_path / "img", {"latest": [BASE, upper]})
+        tree = flatten_image(str(layout))
+        assert "lib/old.so" not in tree
+        assert "var/cache/a" not in tree
+        assert tree["var/cache/b"].digest == hashlib.sha256(b"b").hexdigest()
+        assert not any(".wh." in path for path in tree)
+
+    def test_file_replaces_directory(self, tmp_path: Path):
+        upper = _layer({"var": b"now a file"})
+        tree = flatten_image(str(_layout(tmp_path / "img", {"latest": [BASE, upper]})))
+        assert tree["var"].kind == "file"
+        assert not any(path.startswith("var/") for path in tree)
+
+    def test_whiteout_of_directory(self, tmp_path: Path):
+        upper = _layer({".wh.var": b""})
+        tree = flatten_image(str(_layout(tmp_path / "img", {"latest": [BASE, upper]})))
+        assert not any(path == "var" or path.startswith("var/") for path in tree)
+
+    def test_other_compressors(self, tmp_path: Path):
+        plain = gzip.decompress(_layer({"a": b"a"}))
+        layout = _layout(tmp_path / "img", {"latest": [lzma.compress(plain), plain]})
+        assert flatten_image(str(layout))["a"].size == 1
+
+    @pytest.mark.skipif("zstd" not in DecompressComparator.available_formats(),
+                        reason="zstd needs Python 3.14 or zstandard")
+    def test_zstd(self, tmp_path: Path):
+        plain = gzip.decompress(_layer({"a": b"a"}))
+        try:
+            from compression import zstd
+            layer = zstd.compress(plain)
+        except ImportError:
+            import zstandard
+            layer = zstandard.ZstdCompressor().compress(plain)
+        assert "a" in flatten_image(str(_layout(tmp_path / "img", {"latest": [layer]})))
+
+
+class TestCompareImages:
+    def test_two_tags_of_one_layout(self, tmp_path: Path):
+        upper = _layer({"etc/os-release": b"v2\n", "lib/.wh.old.so": b"",
+                        "bin/sh": "busybox", "new": b"new"})
+        layout = _layout(tmp_path / "img", {"v1": [BASE], "v2": [BASE, upper]})
+        result = komparu.compare_images(f"{layout}:v1", f"{layout}:v2")
+        assert result.equal is False
+        assert result.diff == {"etc/os-release": DiffReason.CONTENT_MISMATCH,
+                               "bin/sh": DiffReason.TYPE_MISMATCH}
+        assert result.only_left == {"lib/old.so"}
+        assert result.only_right == {"new"}
+
+    def test_same_content_in_other_layers(self, tmp_path: Path):
+        split = [_layer({"etc": None, "etc/os-release": b"v1\n", "bin": None,
+                         "bin/sh": b"shell", "lib": None, "lib/libc.so": b"libc"}),
+                 _layer({"lib/old.so": b"old", "var": None, "var/cache": None,
+                         "var/cache/a": b"a"})]
+        a = _layout(tmp_path / "a", {"latest": [BASE]})
+        b = _layout(tmp_path / "b", {"latest": split})
+        assert komparu.compare_images(str(a), str(b)).equal is True
+
+    def test_metadata(self, tmp_path: Path):
+        a = _layout(tmp_path / "a", {"latest": [_layer({"run": b"#!"})]})
+        b = _layout(tmp_path / "b", {"latest": [_layer({"run": b"#!"}, mode=0o755)]})
+        assert komparu.compare_images(str(a), str(b)).diff == {
+            "run": DiffReason.METADATA_MISMATCH}
+        assert komparu.compare_images(str(a), str(b), metadata=False).equal is True
+
+    def test_docker_save(self, tmp_path: Path):
+        saved = _docker_save(tmp_path / "images.tar", {"app:1.4": [BASE],
+                                                       "app:1.5": [BASE, _layer({"x": b"x"})]})
+        result = komparu.compare_images(f"{saved}:app:1.4", f"{saved}:1.5")
+        assert result.only_right == {"x"}
+        layout = _layout(tmp_path / "img", {"latest": [BASE]})
+        assert komparu.compare_images(f"{saved}:app:1.4", str(layout)).equal is True
+
+    def test_ignore(self, tmp_path: Path):
+        a = _layout(tmp_path / "a", {"latest": [BASE]})
+        b = _layout(tmp_path / "b", {"latest": [BASE, _layer({"var/cache/a": b"z"})]})
+        assert komparu.compare_images(str(a), str(b), ignore=["cache"]).equal is True
+
+    def test_multi_platform(self, tmp_path: Path):
+        root = tmp_path / "img"
+        root.mkdir()
+        (root / "oci-layout").write_text('{"imageLayoutVersion": "1.0.0"}')
+        amd64 = {**_manifest(root, [BASE]),
+                 "platform": {"os": "linux", "architecture": "amd64"}}
+        arm64 = {**_manifest(root, [BASE, _layer({"arm": b""})]),
+                 "platform": {"os": "linux", "architecture": "arm64", "variant": "v8"}}
+        index = json.dumps({"schemaVersion": 2, "manifests": [amd64, arm64]}).encode()
+        (root / "index.json").write_text(json.dumps({"schemaVersion": 2, "manifests": [{
+            "mediaType": "application/vnd.oci.image.index.v1+json", **_blob(root, index)}]}))
+        with pytest.raises(ValueError, match="platform"):
+            komparu.compare_images(str(root), str(root))
+        result = komparu.compare_images(str(root), str(root), platform="linux/arm64")
+        assert result.equal is True
+        assert "arm" in flatten_image(str(root), platform="linux/arm64")
+
+    def test_errors(self, tmp_path: Path):
+        layout = _layout(tmp_path / "img", {"v1": [BASE], "v2": [BASE]})
+        with pytest.raises(ValueError, match="PATH:TAG"):
+            komparu.compare_images(str(layout), f"{layout}:v1")
+        with pytest.raises(komparu.SourceNotFoundError):
+            komparu.compare_images(f"{layout}:v3", f"{layout}:v1")
+        with pytest.raises(komparu.SourceNotFoundError):
+            komparu.compare_images(str(tmp_path / "missing"), f"{layout}:v1")
+        (tmp_path / "junk").mkdir()
+        with pytest.raises(komparu.ArchiveError):
+            komparu.compare_images(str(tmp_path / "junk"), f"{layout}:v1")
+
+
+class TestCompareImageDir:
+    def test_against_rootfs(self, tmp_path: Path):
+        layout = _layout(tmp_path / "img", {"latest": [BASE, _layer({"lib/.wh.old.so": b"",
+                                                                     "bin/sh": "busybox"})]})
+        rootfs = tmp_path / "rootfs"
+        for path in ("etc", "bin", "lib", "var/cache"):
+            (rootfs / path).mkdir(parents=True)
+        (rootfs / "etc" / "os-release").write_bytes(b"v2\n")
+        (rootfs / "bin" / "sh").symlink_to("busybox")
+        (rootfs / "lib" / "libc.so").write_bytes(b"libc")
+        (rootfs / "extra").write_bytes(b"")
+        result = komparu.compare_image_dir(str(layout), str(rootfs))
+        assert result.diff == {"etc/os-release": DiffReason.CONTENT_MISMATCH}
+        assert result.only_left == {"var/cache/a"}
+        assert result.only_right == {"extra"}
+
+
+class TestCli:
+    def test_compare(self, tmp_path: Path, capsys):
+        layout = _layout(tmp_path / "img", {"v1": [BASE], "v2": [BASE, _layer({"x": b"x"})]})
+        assert main(["compare", "--oci", f"{layout}:v1", f"{layout}:v2"]) == 1
+        assert capsys.readouterr().out.splitlines() == ["only in right: x"]
+        assert main(["compare", "--oci", f"{layout}:v1", f"{layout}:v1"]) == 0
+
+    def test_against_directory(self, tmp_path: Path, capsys):
+        layout = _layout(tmp_path / "img", {"latest": [_layer({"a": b"a"})]})
+        (tmp_path / "dir").mkdir()
+        assert main(["compare", "--oci", str(layout), str(tmp_path / "dir")]) == 1
+        assert capsys.readouterr().out.splitlines() == ["only in image: a"]
+
+    def test_options(self, tmp_path: Path, capsys):
+        assert main(["compare", "--platform", "linux/amd64", "a", "b"]) == 2
+        assert "--platform needs --oci" in capsys.readouterr().err
+        assert main(["compare", "--oci", "--similarity", "a", "b"]) == 2
+        assert "do not apply" in capsys.readouterr().err
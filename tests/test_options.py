@@ -0,0 +1,190 @@
+"""Tests for the CompareOptions bundle."""
+
+from __future__ import annotations
+
+from pathlib import Path
+
+import pytest
+
+import komparu
+import komparu.aio
+from komparu import CompareOptions, DiffReason, DirResult, Severity, SeverityPolicy
+
+
+class TestCompareOptions:
+    """Construction, validation and derivation."""
+
+    def test_defaults_match_function_defaults(self):
+        opts = CompareOptions()
+        assert opts.chunk_size == 65536
+        assert opts.size_precheck is True
+        assert opts.quick_check is True
+        assert opts.max_workers == 0
+        assert opts.ignore == ()
+
+    def test_invalid_chunk_size(self):
+        with pytest.raises(ValueError, match="chunk_size"):
+            CompareOptions(chunk_size=0)
+
+    def test_invalid_max_workers(self):
+        with pytest.raises(ValueError, match="max_workers"):
+            CompareOptions(max_workers=-1)
+
+    def test_ignore_string_rejected(self):
+        with pytest.raises(TypeError):
+            CompareOptions(ignore="*.log")
+
+    def test_ignore_normalized_to_tuple(self):
+        assert CompareOptions(ignore=["*.log"]).ignore == ("*.log",)
+
+    def test_replace_revalidates(self):
+        opts = CompareOptions()
+        assert opts.replace(chunk_size=4096).chunk_size == 4096
+        with pytest.raises(ValueError):
+            opts.replace(chunk_size=-1)
+
+
+class TestOptionsInApi:
+    """Passing options= to the public functions."""
+
+    def test_compare(self, make_file):
+        a = make_file("a.bin", b"x" * 1000)
+        b = make_file("b.bin", b"x" * 1000)
+        opts = CompareOptions(chunk_size=16, quick_check=False)
+        assert komparu.compare(str(a), str(b), options=opts) is True
+
+    def test_compare_dir_ignore(self, tmp_path: Path):
+        for side, content in (("a", b"1"), ("b", b"2")):
+            (tmp_path / side).mkdir()
+            (tmp_path / side / "keep.txt").write_bytes(b"same")
+            (tmp_path / side / "skip.log").write_bytes(content)
+        opts = CompareOptions(ignore=("*.log",))
+        result = komparu.compare_dir(str(tmp_path / "a"), str(tmp_path / "b"), options=opts)
+        assert result.equal is True
+
+    def test_keyword_with_options_rejected(self, tmp_path: Path):
+        (tmp_path / "a").mkdir()
+        (tmp_path / "b").mkdir()
+        a, b = str(tmp_path / "a"), str(tmp_path / "b")
+        with pytest.raises(TypeError, match="chunk_size=, ignore="):
+            komparu.compare_dir(a, b, chunk_size=4096, ignore=["*.log"],
+                                options=CompareOptions())
+        with pytest.raises(TypeError, match="quick_check="):
+            komparu.compare(a, b, quick_check=False, options=CompareOptions())
+
+    def test_default_keyword_with_options_allowed(self, tmp_path: Path):
+        (tmp_path / "a").mkdir()
+        (tmp_path / "b").mkdir()
+        result = komparu.compare_dir(str(tmp_path / "a"), str(tmp_path / "b"), ignore=[],
+                                     options=CompareOptions())
+        assert result.equal is True
+
+    def test_metadata(self, tmp_path: Path):
+        for side, mode in (("a", 0o600), ("b", 0o644)):
+            (tmp_path / side).mkdir()
+            (tmp_path / side / "f").write_bytes(b"")
+            (tmp_path / side / "f").chmod(mode)
+        a, b = str(tmp_path / "a"), str(tmp_path / "b")
+        opts = CompareOptions(metadata=["mode"])
+        assert opts.metadata == ("mode",)
+        assert komparu.compare_dir_metadata(a, b, options=opts).diff
+        assert komparu.compare_dir_metadata(a, b, options=CompareOptions(
+            metadata=("owner",))).equal
+        with pytest.raises(TypeError, match="attributes="):
+            komparu.compare_dir_metadata(a, b, attributes=("owner",), options=opts)
+
+    def test_path_filter(self, tmp_path: Path):
+        class NoLogs(komparu.PathFilter):
+            def include(self, path, stat_a, stat_b):
+                return not path.endswith(".log")
+
+        for side, content in (("a", b"1"), ("b", b"2")):
+            (tmp_path / side).mkdir()
+            (tmp_path / side / "skip.log").write_bytes(content)
+        a, b = str(tmp_path / "a"), str(tmp_path / "b")
+        opts = CompareOptions(path_filter=NoLogs())
+        assert komparu.compare_dir(a, b, options=opts).equal
+        assert [e.path for e in komparu.iter_dir(a, b, options=opts)] == []
+        with pytest.raises(TypeError, match="path_filter="):
+            komparu.compare_dir(a, b, path_filter=NoLogs(), options=opts)
+        with pytest.raises(TypeError, match="PathFilter"):
+            CompareOptions(path_filter=lambda path: True)
+
+    def test_fail_fast(self, tmp_path: Path):
+        for side in ("a", "b"):
+            (tmp_path / side).mkdir()
+            for i in range(2000):
+                (tmp_path / side / f"f{i:04}").write_bytes(side.encode() * 64)
+        a, b = str(tmp_path / "a"), str(tmp_path / "b")
+        token = komparu.CancelToken()
+        result = komparu.compare_dir(a, b, options=CompareOptions(fail_fast=True, max_workers=1),
+                                     cancel=token)
+        assert not result.equal and result.diff
+        assert token.entries_done < 2000
+        assert not token.cancelled
+        with pytest.raises(TypeError, match="fail_fast="):
+            komparu.compare_dir(a, b, fail_fast=True, options=CompareOptions())
+
+    def test_fail_fast_equal_trees(self, tmp_path: Path):
+        for side in ("a", "b"):
+            (tmp_path / side).mkdir()
+            (tmp_path / side / "f").write_bytes(b"same")
+        a, b = str(tmp_path / "a"), str(tmp_path / "b")
+        assert komparu.compare_dir(a, b, fail_fast=True).equal
+
+    @pytest.mark.asyncio
+    async def test_fail_fast_async(self, tmp_path: Path):
+        for side in ("a", "b"):
+            (tmp_path / side).mkdir()
+            for i in range(2000):
+                (tmp_path / side / f"f{i:04}").write_bytes(side.encode() * 64)
+        token = komparu.CancelToken()
+        result = await komparu.aio.compare_dir(str(tmp_path / "a"), str(tmp_path / "b"),
+                                               fail_fast=True, max_workers=1, cancel=token)
+        assert not result.equal
+        assert token.entries_done < 2000
+
+    def test_metadata_validated(self):
+        with pytest.raises(ValueError, match="colour"):
+            CompareOptions(metadata=["colour"])
+        with pytest.raises(TypeError):
+            CompareOptions(metadata="mode")
+
+
+class TestSeverityPolicy:
+    RESULT = DirResult(
+        equal=False,
+        diff={"a.txt": DiffReason.CONTENT_MISMATCH, "b.txt": DiffReason.SIZE_MISMATCH},
+        only_left={"left.txt"},
+        only_right=set(),
+    )
+
+    def test_default_is_error(self):
+        assert SeverityPolicy().highest(self.RESULT) is Severity.ERROR
+
+    def test_classify(self):
+        policy = SeverityPolicy({"content_mismatch": "warn", "only_left": Severity.INFO})
+        assert policy.classify(self.RESULT) == {
+            "a.txt": Severity.WARN,
+            "b.txt": Severity.ERROR,
+            "left.txt": Severity.INFO,
+        }
+
+    def test_highest(self):
+        policy = SeverityPolicy({DiffReason.SIZE_MISMATCH: "info"}, default=Severity.WARN)
+        assert policy.highest(self.RESULT) is Severity.WARN
+
+    def test_highest_equal(self):
+        result = DirResult(equal=True, diff={}, only_left=set(), only_right=set())
+        assert SeverityPolicy().highest(result) is None
+
+    def test_unknown_category(self):
+        with pytest.raises(ValueError, match="category"):
+            SeverityPolicy({"mtime": "info"})
+
+    def test_unknown_level(self):
+        with pytest.raises(ValueError):
+            SeverityPolicy({"only_left": "fatal"})
+
+    def test_order(self):
+        assert Severity.INFO.rank < Severity.WARN.rank < Severity.ERROR.rank
@@ -3,6 +3,9 @@
 from __future__ import annotations
 
 import os
+import subprocess
+import sys
+import time
 from pathlib import Path
 
 import pytest
@@ -19,21 +22,6 @@ def allow_localhost():
     komparu.reset_config()
 
 
-@pytest.fixture
-def make_dir(tmp_path: Path):
-    """Create a directory tree from a dict of {relative_path: content}."""
-
-    def _make(name: str, files: dict[str, bytes]) -> Path:
-        d = tmp_path / name
-        for rel, content in files.items():
-            p = d / rel
-            p.parent.mkdir(parents=True, exist_ok=True)
-            p.write_bytes(content)
-        return d
-
-    return _make
-
-
 # =========================================================================
 # Thread pool — via parallel directory comparison
 # =========================================================================
@@ -90,6 +78,35 @@ class TestParallelDirCompare:
         assert result.equal is True
 
 
+    def test_more_workers_than_descriptors(self, make_dir):
+        """Workers are throttled to the open-file limit instead of hitting EMFILE."""
+        fcntl = pytest.importorskip("fcntl")
+        files = {f"f{i}.bin": b"%d" % i for i in range(100)}
+        a = make_dir("a", files)
+        b = make_dir("b", files)
+        code = (
+            "import resource, sys, komparu\n"
+            "hard = resource.getrlimit(resource.RLIMIT_NOFILE)[1]\n"
+            "resource.setrlimit(resource.RLIMIT_NOFILE, (64, hard))\n"
+            "r = komparu.compare_dir(sys.argv[1], sys.argv[2], max_workers=64,\n"
+            "                        lock=komparu.LockPolicy())\n"
+            "print(r.equal, len(r.diff))\n"
+        )
+        env = {**os.environ, "PYTHONPATH": os.pathsep.join(sys.path)}
+        # Writers hold every file, so each worker keeps its pair open while it waits
+        held = [open(b / name, "rb") for name in files]
+        try:
+            for f in held:
+                fcntl.flock(f, fcntl.LOCK_EX)
+            child = subprocess.Popen([sys.executable, "-c", code, str(a), str(b)], env=env,
+                                     stdout=subprocess.PIPE, text=True)
+            time.sleep(0.5)
+        finally:
+            for f in held:
+                f.close()
+        assert child.communicate(timeout=60)[0] == "True 0\n"
+
+
 # =========================================================================
 # compare_all
 # =========================================================================
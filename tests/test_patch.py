@@ -0,0 +1,110 @@
+"""Tests for make_patch, apply_patch and komparu patch."""
+
+from __future__ import annotations
+
+import os
+from pathlib import Path
+
+import pytest
+
+from komparu import PatchError, apply_patch, make_patch
+from komparu._cli import main
+
+
+def _pair(tmp_path: Path) -> tuple[Path, Path]:
+    old = bytes(range(256)) * 64
+    new = old[:5000] + b"inserted" + old[5000:12000] + old[12500:] + b"tail"
+    (tmp_path / "old").write_bytes(old)
+    (tmp_path / "new").write_bytes(new)
+    return tmp_path / "old", tmp_path / "new"
+
+
+class TestPatch:
+    def test_round_trip(self, tmp_path):
+        old, new = _pair(tmp_path)
+        size = make_patch(old, new, tmp_path / "p")
+        assert size == os.path.getsize(tmp_path / "p") < os.path.getsize(new) // 4
+        os.chmod(old, 0o640)
+        assert apply_patch(old, tmp_path / "p")
+        assert old.read_bytes() == new.read_bytes()
+        assert os.stat(old).st_mode & 0o777 == 0o640
+        assert not [name for name in os.listdir(tmp_path) if name.startswith(".")]
+
+    def test_output(self, tmp_path):
+        old, new = _pair(tmp_path)
+        make_patch(old, new, tmp_path / "p", block_size=100)
+        before = old.read_bytes()
+        assert apply_patch(old, tmp_path / "p", output=tmp_path / "out")
+        assert (tmp_path / "out").read_bytes() == new.read_bytes()
+        assert old.read_bytes() == before
+
+    def test_empty_files(self, tmp_path):
+        (tmp_path / "empty").write_bytes(b"")
+        (tmp_path / "full").write_bytes(b"content")
+        make_patch(tmp_path / "empty", tmp_path / "full", tmp_path / "p")
+        assert apply_patch(tmp_path / "empty", tmp_path / "p")
+        assert (tmp_path / "empty").read_bytes() == b"content"
+
+    def test_already_patched(self, tmp_path):
+        old, new = _pair(tmp_path)
+        make_patch(old, new, tmp_path / "p")
+        assert not apply_patch(new, tmp_path / "p")
+        assert not apply_patch(new, tmp_path / "p", output=tmp_path / "out")
+        assert (tmp_path / "out").read_bytes() == new.read_bytes()
+
+    def test_other_version(self, tmp_path):
+        old, new = _pair(tmp_path)
+        make_patch(old, new, tmp_path / "p")
+        old.write_bytes(old.read_bytes()[:-1] + b"!")
+        with pytest.raises(PatchError, match="another version"):
+            apply_patch(old, tmp_path / "p")
+
+    def test_corrupt(self, tmp_path):
+        old, new = _pair(tmp_path)
+        make_patch(old, new, tmp_path / "p")
+        data = (tmp_path / "p").read_bytes()
+        for broken in (b"nope", data[:-3], data[:70]):
+            (tmp_path / "p").write_bytes(broken)
+            with pytest.raises(PatchError):
+                apply_patch(old, tmp_path / "p")
+        assert not [name for name in os.listdir(tmp_path) if name.startswith(".")]
+        assert isinstance(PatchError("x"), ValueError)
+
+    def test_bad_block_size(self, tmp_path):
+        old, new = _pair(tmp_path)
+        with pytest.raises(ValueError):
+            make_patch(old, new, tmp_path / "p", block_size=-1)
+
+
+class TestCli:
+    def test_files(self, tmp_path, capsys):
+        old, new = _pair(tmp_path)
+        assert main(["compare", "--patch", str(tmp_path / "p"), str(old), str(new)]) == 1
+        assert main(["patch", str(old), str(tmp_path / "p")]) == 0
+        assert capsys.readouterr().out.splitlines()[-1] == f"patched: {old}"
+        assert old.read_bytes() == new.read_bytes()
+        assert main(["patch", str(old), str(tmp_path / "p")]) == 0
+        assert capsys.readouterr().out == f"up to date: {old}\n"
+
+    def test_directories(self, make_files, tmp_path, capsys):
+        make_files({"a/same": b"same", "b/same": b"same", "a/sub/f": b"one" * 100,
+                    "b/sub/f": b"one" * 99 + b"two", "a/gone": b"x", "b/new": b"y"})
+        a, b, patches = tmp_path / "a", tmp_path / "b", tmp_path / "patches"
+        assert main(["compare", "-q", "--patch", str(patches), str(a), str(b)]) == 1
+        assert sorted(os.listdir(patches)) == ["sub"]
+        assert os.listdir(patches / "sub") == ["f.kpatch"]
+        assert main(["patch", str(a), str(patches)]) == 0
+        assert capsys.readouterr().out == "patched: sub/f\n"
+        assert (a / "sub" / "f").read_bytes() == (b / "sub" / "f").read_bytes()
+
+    def test_errors(self, make_files, tmp_path, capsys):
+        old, new = _pair(tmp_path)
+        make_patch(old, new, tmp_path / "p")
+        (tmp_path / "other").write_bytes(b"other")
+        assert main(["patch", str(tmp_path / "other"), str(tmp_path / "p")]) == 2
+        assert "another version" in capsys.readouterr().err
+        assert main(["patch", str(tmp_path), str(tmp_path / "p")]) == 2
+        assert "applies to a file" in capsys.readouterr().err
+        make_files({"d/f": b"1"})
+        assert main(["compare", "--patch", str(tmp_path / "q"), str(old), str(tmp_path / "d")]) == 2
+        assert "--patch needs two local files" in capsys.readouterr().err
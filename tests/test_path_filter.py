@@ -0,0 +1,158 @@
+"""Tests for pluggable PathFilter in directory comparison."""
+
+from __future__ import annotations
+
+import os
+
+import pytest
+
+import komparu
+import komparu.aio
+from komparu import PathFilter, RsyncFilter
+
+
+class MaxSize(PathFilter):
+    """Report only entries no larger than *limit* on either side."""
+
+    def __init__(self, limit: int) -> None:
+        self.limit = limit
+        self.seen: dict[str, tuple[bool, bool]] = {}
+
+    def include(self, path, stat_a, stat_b) -> bool:
+        self.seen[path] = (stat_a is not None, stat_b is not None)
+        sizes = [s.st_size for s in (stat_a, stat_b) if s is not None]
+        return max(sizes) <= self.limit
+
+
+@pytest.fixture
+def tree_files() -> dict[str, bytes]:
+    return {
+        "a/small.txt": b"1",
+        "b/small.txt": b"2",
+        "a/big.bin": b"x" * 100,
+        "b/big.bin": b"y" * 100,
+        "a/left_big.bin": b"z" * 100,
+        "b/right.txt": b"r",
+    }
+
+
+class TestCompareDir:
+    def test_filters_by_metadata(self, trees):
+        a, b = map(str, trees)
+        result = komparu.compare_dir(a, b, path_filter=MaxSize(10))
+        assert set(result.diff) == {"small.txt"}
+        assert result.only_left == set()
+        assert result.only_right == {"right.txt"}
+
+    def test_stat_sides(self, trees):
+        a, b = map(str, trees)
+        f = MaxSize(1000)
+        komparu.compare_dir(a, b, path_filter=f)
+        assert f.seen["small.txt"] == (True, True)
+        assert f.seen["left_big.bin"] == (True, False)
+        assert f.seen["right.txt"] == (False, True)
+
+    def test_equal_when_everything_filtered(self, trees):
+        a, b = map(str, trees)
+        result = komparu.compare_dir(a, b, path_filter=MaxSize(-1))
+        assert result.equal is True
+
+    def test_combined_with_ignore(self, trees):
+        a, b = map(str, trees)
+        result = komparu.compare_dir(a, b, path_filter=MaxSize(10), ignore=["small.*"])
+        assert result.diff == {}
+        assert result.only_right == {"right.txt"}
+
+    def test_default_includes_everything(self, trees):
+        a, b = map(str, trees)
+        assert komparu.compare_dir(a, b, path_filter=PathFilter()) == komparu.compare_dir(a, b)
+
+    def test_with_visitor(self, trees):
+        class Rec(komparu.CompareVisitor):
+            def __init__(self) -> None:
+                self.paths: list[str] = []
+
+            def on_entry_finished(self, entry) -> None:
+                self.paths.append(entry.path)
+
+        a, b = map(str, trees)
+        rec = Rec()
+        komparu.compare_dir(a, b, visitor=rec, path_filter=MaxSize(10))
+        assert sorted(rec.paths) == ["right.txt", "small.txt"]
+
+    def test_rejects_non_filter(self, trees):
+        a, b = map(str, trees)
+        with pytest.raises(TypeError, match="PathFilter"):
+            komparu.compare_dir(a, b, path_filter=lambda p: True)
+
+
+class TestIterDir:
+    def test_filtered(self, trees):
+        a, b = map(str, trees)
+        paths = {e.path for e in komparu.iter_dir(a, b, path_filter=MaxSize(10))}
+        assert paths == {"small.txt", "right.txt"}
+
+    @pytest.mark.asyncio
+    async def test_async(self, trees):
+        a, b = map(str, trees)
+        paths = {e.path async for e in komparu.aio.iter_dir(a, b, path_filter=MaxSize(10))}
+        assert paths == {"small.txt", "right.txt"}
+
+
+def _kept(rules: RsyncFilter, paths: list[str]) -> list[str]:
+    """*paths* the rules include, a trailing slash marking a directory."""
+    directory = os.stat(".")
+    return [path for path in paths
+            if rules.include(path.rstrip("/"), directory if path.endswith("/") else None, None)]
+
+
+class TestRsyncFilter:
+    def test_patterns(self):
+        rules = RsyncFilter(["*.o", "/build/", "cache/***", "docs/**/draft-*", "tmp?"])
+        assert _kept(rules, ["a.o", "src/a.o", "build/", "build/x", "src/build/", "cache/",
+                             "src/cache/x/y", "docs/a/b/draft-1", "docs/draft-1", "tmp1",
+                             "tmp12", "src/main.c"]) == [
+            "src/build/", "docs/draft-1", "tmp12", "src/main.c"]
+
+    def test_directory_only(self):
+        rules = RsyncFilter(["- logs/"])
+        assert _kept(rules, ["logs/", "logs", "x/logs/", "logs/a"]) == ["logs"]
+
+    def test_first_match_wins(self):
+        # The usual "only these files" policy
+        rules = RsyncFilter(["+ */", "+ *.txt", "- *"])
+        assert _kept(rules, ["a.txt", "a.bin", "sub/", "sub/b.txt", "sub/b.bin"]) == [
+            "a.txt", "sub/", "sub/b.txt"]
+        rules = RsyncFilter(["+ keep.log", "- *.log"])
+        assert _kept(rules, ["keep.log", "drop.log"]) == ["keep.log"]
+
+    def test_excluded_directory_hides_contents(self):
+        rules = RsyncFilter(["+ secret/public.txt", "- secret/"])
+        assert _kept(rules, ["secret/public.txt", "other.txt"]) == ["other.txt"]
+
+    def test_file_syntax(self, tmp_path):
+        (tmp_path / "include").write_text("# policy\n*.conf\n\n- *.bak\n")
+        (tmp_path / "exclude").write_text("; old\n*.o\r\n+ keep.o\n")
+        rules = RsyncFilter.from_files([str(tmp_path / "include")], [str(tmp_path / "exclude")])
+        assert _kept(rules, ["a.conf", "a.bak", "a.o", "keep.o", "a.c"]) == ["a.conf", "a.c"]
+        # "!" clears every rule before it, the other files' too
+        (tmp_path / "exclude").write_text("*.o\n!\n*.c\n")
+        rules = RsyncFilter.from_files([str(tmp_path / "include")], [str(tmp_path / "exclude")])
+        assert _kept(rules, ["a.bak", "a.o", "a.c"]) == ["a.bak", "a.o"]
+
+    def test_character_classes(self):
+        rules = RsyncFilter(["file[0-9]", "[!a-z]*.log"])
+        assert _kept(rules, ["file1", "filex", "Big.log", "small.log"]) == ["filex", "small.log"]
+
+    def test_bad_rules(self):
+        with pytest.raises(ValueError):
+            RsyncFilter(["- "])
+        with pytest.raises(ValueError):
+            RsyncFilter([], default="x")
+
+    def test_compare_dir(self, make_files, tmp_path):
+        make_files({"a/src/x.c": b"1", "b/src/x.c": b"2", "a/build/x.o": b"1",
+                    "b/build/x.o": b"2", "a/notes.txt": b"1"})
+        result = komparu.compare_dir(str(tmp_path / "a"), str(tmp_path / "b"),
+                                     path_filter=RsyncFilter(["/build/", "*.txt"]))
+        assert set(result.diff) == {"src/x.c"} and not result.only_left
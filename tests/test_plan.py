@@ -0,0 +1,90 @@
+"""Tests for dry-run directory planning."""
+
+from __future__ import annotations
+
+import os
+from pathlib import Path
+
+import pytest
+
+import komparu
+from komparu import DirPlan, PlannedPair, SourceNotFoundError
+
+
+@pytest.fixture
+def tree_files() -> dict[str, bytes]:
+    return {
+        "a/same.txt": b"hello",
+        "b/same.txt": b"hello",
+        "a/sub/changed.bin": b"1234",
+        "b/sub/changed.bin": b"abcd",
+        "a/grown.log": b"x",
+        "b/grown.log": b"xyz",
+        "a/left.txt": b"l",
+        "b/right.txt": b"r",
+    }
+
+
+class TestPlanDir:
+    def test_plan(self, trees):
+        a, b = map(str, trees)
+        plan = komparu.plan_dir(a, b)
+        assert isinstance(plan, DirPlan)
+        assert sorted(plan.pairs, key=lambda p: p.path) == [
+            PlannedPair("same.txt", 5, 5),
+            PlannedPair("sub/changed.bin", 4, 4),
+        ]
+        assert plan.size_mismatch == {"grown.log"}
+        assert plan.only_left == {"left.txt"}
+        assert plan.only_right == {"right.txt"}
+        assert plan.bytes_to_read == 18
+
+    def test_no_size_precheck(self, trees):
+        a, b = map(str, trees)
+        plan = komparu.plan_dir(a, b, size_precheck=False)
+        assert plan.size_mismatch == set()
+        assert PlannedPair("grown.log", 1, 3) in plan.pairs
+
+    def test_reads_nothing(self, trees, tmp_path: Path):
+        a, b = map(str, trees)
+        # Unreadable content does not matter: only metadata is used
+        target = tmp_path / "a" / "same.txt"
+        target.chmod(0o000)
+        try:
+            plan = komparu.plan_dir(a, b)
+        finally:
+            target.chmod(0o644)
+        assert "same.txt" in {p.path for p in plan.pairs}
+
+    @pytest.mark.skipif(not hasattr(os, "link"), reason="no hard links")
+    def test_same_file(self, make_files, tmp_path: Path):
+        make_files({"a/f.bin": b"data", "b/.keep": b""})
+        os.link(tmp_path / "a" / "f.bin", tmp_path / "b" / "f.bin")
+        plan = komparu.plan_dir(str(tmp_path / "a"), str(tmp_path / "b"))
+        assert plan.same_file == {"f.bin"}
+        assert plan.pairs == []
+
+    def test_ignore(self, trees):
+        a, b = map(str, trees)
+        plan = komparu.plan_dir(a, b, ignore=["sub", "*.log"])
+        assert [p.path for p in plan.pairs] == ["same.txt"]
+        assert plan.size_mismatch == set()
+
+    def test_options(self, trees):
+        a, b = map(str, trees)
+        opts = komparu.CompareOptions(size_precheck=False, ignore=("left.txt",))
+        plan = komparu.plan_dir(a, b, options=opts)
+        assert plan.size_mismatch == set()
+        assert plan.only_left == set()
+
+    def test_matches_compare_dir(self, trees):
+        a, b = map(str, trees)
+        plan = komparu.plan_dir(a, b)
+        result = komparu.compare_dir(a, b)
+        assert plan.only_left == result.only_left
+        assert plan.only_right == result.only_right
+        assert plan.size_mismatch <= set(result.diff)
+
+    def test_missing_directory(self, tmp_path: Path):
+        with pytest.raises(SourceNotFoundError):
+            komparu.plan_dir(str(tmp_path), str(tmp_path / "missing"))
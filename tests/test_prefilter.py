@@ -0,0 +1,105 @@
+"""Tests for staged grouping of identical files."""
+
+from __future__ import annotations
+
+import os
+from pathlib import Path
+
+import pytest
+
+import komparu
+from komparu import _prefilter
+from komparu._prefilter import group_identical
+
+
+def _write(tmp_path: Path, name: str, content: bytes) -> str:
+    p = tmp_path / name
+    p.write_bytes(content)
+    return str(p)
+
+
+class TestGroupIdentical:
+    def test_groups_in_input_order(self, tmp_path: Path):
+        a = _write(tmp_path, "a", b"one")
+        b = _write(tmp_path, "b", b"two")
+        c = _write(tmp_path, "c", b"one")
+        assert group_identical([a, b, c]) == [[a, c], [b]]
+
+    def test_unique_sizes_are_not_read(self, tmp_path: Path, monkeypatch):
+        paths = [_write(tmp_path, f"f{i}", b"x" * i) for i in range(1, 5)]
+        read = []
+        monkeypatch.setattr(_prefilter, "_edge_digest",
+                            lambda path, *args: read.append(path) or b"")
+        assert group_identical(paths) == [[p] for p in paths]
+        assert read == []
+
+    def test_edges_decide_small_files(self, tmp_path: Path, monkeypatch):
+        a = _write(tmp_path, "a", b"a" * 100)
+        b = _write(tmp_path, "b", b"a" * 100)
+        monkeypatch.setattr(_prefilter, "_content_digest", None)
+        monkeypatch.setattr(_prefilter, "_compare_c", None)
+        assert group_identical([a, b]) == [[a, b]]
+
+    def test_differing_tail(self, tmp_path: Path):
+        content = os.urandom(20000)
+        a = _write(tmp_path, "a", content)
+        b = _write(tmp_path, "b", content[:-1] + bytes([content[-1] ^ 1]))
+        assert group_identical([a, b], edge_size=1024) == [[a], [b]]
+
+    @pytest.mark.parametrize("count", [2, 3])
+    def test_differing_middle(self, tmp_path: Path, count: int):
+        content = bytearray(os.urandom(20000))
+        same = _write(tmp_path, "same", bytes(content))
+        paths = [same] + [_write(tmp_path, f"copy{i}", bytes(content)) for i in range(count - 2)]
+        content[10000] ^= 1
+        other = _write(tmp_path, "other", bytes(content))
+        assert group_identical(paths + [other], edge_size=1024) == [paths, [other]]
+
+    def test_large_bucket_split_by_content(self, tmp_path: Path):
+        base = os.urandom(20000)
+        paths = []
+        for i in range(6):
+            content = bytearray(base)
+            content[10000] = i % 2
+            paths.append(_write(tmp_path, f"f{i}", bytes(content)))
+        assert group_identical(paths, edge_size=1024, max_workers=4) == [
+            paths[0::2], paths[1::2],
+        ]
+
+    def test_hard_links_are_not_read(self, tmp_path: Path, monkeypatch):
+        a = _write(tmp_path, "a", b"linked")
+        b = str(tmp_path / "b")
+        os.link(a, b)
+        monkeypatch.setattr(_prefilter, "_edge_digest", None)
+        assert group_identical([a, b, a]) == [[a, b, a]]
+
+    def test_empty_files(self, tmp_path: Path):
+        a = _write(tmp_path, "a", b"")
+        b = _write(tmp_path, "b", b"")
+        assert group_identical([a, b]) == [[a, b]]
+
+    def test_missing_file(self, tmp_path: Path):
+        with pytest.raises(komparu.SourceNotFoundError):
+            group_identical([str(tmp_path / "missing")])
+
+    def test_bad_edge_size(self, tmp_path: Path):
+        with pytest.raises(ValueError, match="edge_size"):
+            group_identical([], edge_size=0)
+
+
+class TestCompareManyPrefilter:
+    def test_diff_follows_groups(self, tmp_path: Path):
+        content = os.urandom(20000)
+        a = _write(tmp_path, "a", content)
+        b = _write(tmp_path, "b", content)
+        c = _write(tmp_path, "c", content[::-1])
+        result = komparu.compare_many([a, b, c])
+        assert result.all_equal is False
+        assert result.groups == [{a, b}, {c}]
+        assert result.diff == {(a, b): True, (a, c): False, (b, c): False}
+
+    def test_no_size_precheck_compares_pairs(self, tmp_path: Path, monkeypatch):
+        a = _write(tmp_path, "a", b"same")
+        b = _write(tmp_path, "b", b"same")
+        monkeypatch.setattr("komparu._api.group_identical", None)
+        assert komparu.compare_many([a, b], size_precheck=False).all_equal is True
@@ -0,0 +1,169 @@
+"""Tests for comparing directories over SSH."""
+
+from __future__ import annotations
+
+import os
+import shlex
+import sys
+from pathlib import Path
+
+import pytest
+
+import komparu
+from komparu._cli import main
+from komparu._remote import parse_ssh_target
+
+REMOTE_KOMPARU = f"{shlex.quote(sys.executable)} -m komparu"
+
+
+@pytest.fixture
+def fake_ssh(tmp_path: Path) -> str:
+    """An ssh stand-in running the command on this machine, logging the host."""
+    script = tmp_path / "fake-ssh"
+    script.write_text(f'#!/bin/sh\necho "$1" >> {tmp_path / "hosts"}\nshift\nexec sh -c "$*"\n')
+    script.chmod(0o755)
+    return str(script)
+
+
+@pytest.fixture
+def trees(tmp_path: Path) -> tuple[Path, Path]:
+    local, remote = tmp_path / "local", tmp_path / "remote dir"
+    for root in (local, remote):
+        (root / "sub").mkdir(parents=True)
+        (root / "same").write_bytes(b"same")
+    (local / "sub" / "changed").write_bytes(b"old")
+    (remote / "sub" / "changed").write_bytes(b"new")
+    (local / "local-only").write_bytes(b"")
+    (remote / "remote-only").write_bytes(b"")
+    return local, remote
+
+
+class TestParseSshTarget:
+    def test_targets(self):
+        assert parse_ssh_target("host:/srv") == ("host", "/srv")
+        assert parse_ssh_target("user@host:data") == ("user@host", "data")
+        assert parse_ssh_target("[::1]:/srv") == ("[::1]", "/srv")
+        assert parse_ssh_target("host:") == ("host", ".")
+
+    def test_not_targets(self):
+        assert parse_ssh_target("/srv/data") is None
+        assert parse_ssh_target("dir/host:x") is None
+        assert parse_ssh_target("https://example.com/x") is None
+        assert parse_ssh_target("C:/data") is None
+
+
+class TestCompareDirSsh:
+    def test_compare(self, trees, fake_ssh, tmp_path: Path):
+        local, remote = trees
+        result = komparu.compare_dir_ssh(str(local), f"me@box:{remote}", ssh_command=fake_ssh,
+                                         remote_command=REMOTE_KOMPARU)
+        assert result.equal is False
+        assert result.diff == {"sub/changed": komparu.DiffReason.CONTENT_MISMATCH}
+        assert result.only_left == {"local-only"}
+        assert result.only_right == {"remote-only"}
+        assert (tmp_path / "hosts").read_text() == "me@box\n"
+
+    def test_ignore(self, trees, fake_ssh):
+        local, remote = trees
+        result = komparu.compare_dir_ssh(str(local), f"box:{remote}", ssh_command=fake_ssh,
+                                         remote_command=REMOTE_KOMPARU,
+                                         ignore=["sub", "*-only"])
+        assert result.equal is True
+
+    def test_remote_failure(self, trees, fake_ssh, tmp_path: Path):
+        local, _ = trees
+        with pytest.raises(komparu.SourceReadError):
+            komparu.compare_dir_ssh(str(local), f"box:{tmp_path / 'missing'}",
+                                    ssh_command=fake_ssh, remote_command=REMOTE_KOMPARU)
+
+    def test_not_a_target(self, trees):
+        local, _ = trees
+        with pytest.raises(ValueError, match="host:path"):
+            komparu.compare_dir_ssh(str(local), "/srv/data")
+
+
+class TestChunkDiffSsh:
+    def test_regions(self, tmp_path: Path, fake_ssh):
+        old = os.urandom(50000)
+        (tmp_path / "local").write_bytes(old)
+        (tmp_path / "remote").write_bytes(old[:20000] + b"new" + old[20010:])
+        chunks = komparu.chunk_diff_ssh(str(tmp_path / "local"), f"box:{tmp_path / 'remote'}",
+                                        ssh_command=fake_ssh, remote_command=REMOTE_KOMPARU)
+        assert (chunks.size_a, chunks.size_b) == (50000, 49993)
+        assert chunks.regions == (
+            komparu.DiffRegion(komparu.RegionKind.CHANGED, 20000, 10, 20000, 3),
+        )
+        # The changed block, and the partial last one that has no signature
+        assert chunks.shared_chunks == chunks.chunks_a - 2
+
+    def test_equal(self, tmp_path: Path, fake_ssh):
+        data = os.urandom(10000)
+        for name in ("local", "remote"):
+            (tmp_path / name).write_bytes(data)
+        chunks = komparu.chunk_diff_ssh(str(tmp_path / "local"), f"box:{tmp_path / 'remote'}",
+                                        block_size=1000, ssh_command=fake_ssh,
+                                        remote_command=REMOTE_KOMPARU)
+        assert chunks.regions == ()
+        assert chunks.shared_chunks == chunks.chunks_a == 10
+
+    def test_insertion_and_empty(self, tmp_path: Path, fake_ssh):
+        (tmp_path / "empty").write_bytes(b"")
+        (tmp_path / "remote").write_bytes(b"hello")
+        chunks = komparu.chunk_diff_ssh(str(tmp_path / "empty"), f"box:{tmp_path / 'remote'}",
+                                        ssh_command=fake_ssh, remote_command=REMOTE_KOMPARU)
+        assert chunks.regions == (komparu.DiffRegion(komparu.RegionKind.INSERTED, 0, 0, 0, 5),)
+
+    def test_remote_failure(self, tmp_path: Path, fake_ssh):
+        (tmp_path / "local").write_bytes(os.urandom(100000))
+        with pytest.raises(komparu.SourceReadError, match="No such file"):
+            komparu.chunk_diff_ssh(str(tmp_path / "local"), f"box:{tmp_path / 'missing'}",
+                                   ssh_command=fake_ssh, remote_command=REMOTE_KOMPARU)
+
+
+class TestCli:
+    def test_remote_right(self, trees, fake_ssh, capsys):
+        local, remote = trees
+        assert main(["compare", "--ssh", fake_ssh, "--remote-komparu", REMOTE_KOMPARU,
+                     str(local), f"box:{remote}"]) == 1
+        assert capsys.readouterr().out.splitlines() == [
+            "differ: sub/changed (content_mismatch)",
+            "only in left: local-only",
+            "only in right: remote-only",
+        ]
+
+    def test_remote_left(self, trees, fake_ssh, capsys):
+        local, remote = trees
+        assert main(["compare", "--ssh", fake_ssh, "--remote-komparu", REMOTE_KOMPARU,
+                     f"box:{remote}", str(local)]) == 1
+        out = capsys.readouterr().out.splitlines()
+        assert "only in left: remote-only" in out
+        assert "only in right: local-only" in out
+
+    def test_needs_local_directory(self, trees, capsys):
+        _, remote = trees
+        assert main(["compare", "a:/x", f"box:{remote}"]) == 2
+        assert "local one" in capsys.readouterr().err
+
+    def test_remote_file(self, tmp_path: Path, fake_ssh, capsys):
+        data = os.urandom(10000)
+        (tmp_path / "local").write_bytes(data)
+        (tmp_path / "remote").write_bytes(data[:5000] + b"c" + data[5000:])
+        remote = f"box:{tmp_path / 'remote'}"
+        args = ["compare", "--ssh", fake_ssh, "--remote-komparu", REMOTE_KOMPARU]
+        assert main([*args, "--regions", str(tmp_path / "local"), remote]) == 1
+        assert capsys.readouterr().out.splitlines() == [
+            f"{tmp_path / 'local'} {remote} differ",
+            "  inserted: 1 bytes at 5000",
+        ]
+        assert main([*args, str(tmp_path / "local"), f"box:{tmp_path / 'local'}"]) == 0
+
+    def test_regions_in_directories(self, trees, fake_ssh, capsys):
+        local, remote = trees
+        assert main(["compare", "--ssh", fake_ssh, "--remote-komparu", REMOTE_KOMPARU,
+                     "--chunks", str(local), f"box:{remote}"]) == 1
+        assert "differ: sub/changed (content_mismatch" in capsys.readouterr().out
+
+    def test_regions_need_remote_right(self, trees, capsys):
+        local, remote = trees
+        assert main(["compare", "--regions", f"box:{remote}", str(local)]) == 2
+        assert "on the right" in capsys.readouterr().err
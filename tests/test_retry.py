@@ -0,0 +1,89 @@
+"""Tests for RetryPolicy on directory comparison."""
+
+from __future__ import annotations
+
+import pytest
+
+import komparu
+import komparu.aio
+from komparu import CompareOptions, DiffReason, RetryPolicy
+
+
+@pytest.fixture
+def tree_files() -> dict[str, bytes]:
+    return {
+        "a/same.txt": b"hello",
+        "b/same.txt": b"hello",
+        "a/diff.txt": b"one",
+        "b/diff.txt": b"two",
+        "a/left.txt": b"l",
+    }
+
+
+class TestRetryPolicy:
+    def test_defaults(self):
+        policy = RetryPolicy()
+        assert policy.retries == 3
+        assert policy.delay == 0.1
+        assert policy.max_delay == 2.0
+
+    def test_negative_retries(self):
+        with pytest.raises(ValueError, match="retries"):
+            RetryPolicy(retries=-1)
+
+    def test_negative_delay(self):
+        with pytest.raises(ValueError, match="delay"):
+            RetryPolicy(delay=-0.5)
+
+    def test_non_integer_retries(self):
+        with pytest.raises(TypeError):
+            RetryPolicy(retries=1.5)
+
+    def test_reason_value(self):
+        assert DiffReason("retries_exhausted") is DiffReason.RETRIES_EXHAUSTED
+
+
+class TestCompareDir:
+    def test_result_unchanged_without_errors(self, trees):
+        a, b = map(str, trees)
+        assert komparu.compare_dir(a, b, retry=RetryPolicy()) == komparu.compare_dir(a, b)
+
+    def test_zero_retries(self, trees):
+        a, b = map(str, trees)
+        result = komparu.compare_dir(a, b, retry=RetryPolicy(retries=0))
+        assert set(result.diff) == {"diff.txt"}
+
+    def test_rejects_non_policy(self, trees):
+        a, b = map(str, trees)
+        with pytest.raises(TypeError, match="RetryPolicy"):
+            komparu.compare_dir(a, b, retry=3)
+
+    def test_via_options(self, trees):
+        a, b = map(str, trees)
+        opts = CompareOptions(retry=RetryPolicy(retries=1, delay=0.0))
+        result = komparu.compare_dir(a, b, options=opts)
+        assert result.diff == {"diff.txt": DiffReason.CONTENT_MISMATCH}
+        assert result.only_left == {"left.txt"}
+
+    def test_options_reject_non_policy(self):
+        with pytest.raises(TypeError, match="RetryPolicy"):
+            CompareOptions(retry={"retries": 3})
+
+    def test_iter_dir(self, trees):
+        a, b = map(str, trees)
+        paths = {e.path for e in komparu.iter_dir(a, b, retry=RetryPolicy())}
+        assert paths == {"diff.txt", "left.txt"}
+
+
+class TestAsync:
+    @pytest.mark.asyncio
+    async def test_compare_dir(self, trees):
+        a, b = map(str, trees)
+        result = await komparu.aio.compare_dir(a, b, retry=RetryPolicy())
+        assert set(result.diff) == {"diff.txt"}
+
+    @pytest.mark.asyncio
+    async def test_iter_dir(self, trees):
+        a, b = map(str, trees)
+        paths = {e.path async for e in komparu.aio.iter_dir(a, b, retry=RetryPolicy())}
+        assert paths == {"diff.txt", "left.txt"}
@@ -0,0 +1,65 @@
+"""Tests for per-directory rollups of a comparison."""
+
+from __future__ import annotations
+
+from pathlib import Path
+
+from komparu import (
+    CompareVisitor,
+    DiffEntry,
+    DiffReason,
+    DirSummary,
+    EntryKind,
+    SummaryVisitor,
+    compare_dir,
+    iter_dir,
+    summarize_dirs,
+)
+
+
+def _trees(make_files, tmp_path: Path) -> tuple[str, str]:
+    make_files({
+        "a/same.txt": b"same", "b/same.txt": b"same",
+        "a/src/main.c": b"one", "b/src/main.c": b"three",
+        "a/src/lib/old.c": b"12345",
+        "b/docs/new.md": b"xyz",
+    })
+    return str(tmp_path / "a"), str(tmp_path / "b")
+
+
+class TestSummarize:
+    def test_counts_and_bytes(self, make_files, tmp_path):
+        a, b = _trees(make_files, tmp_path)
+        summaries = summarize_dirs(iter_dir(a, b, include_equal=True), (a, b))
+        assert list(summaries) == ["", "docs", "src", "src/lib"]
+        assert summaries[""] == DirSummary("", equal=1, diff=1, only_left=1, only_right=1,
+                                           bytes=5 + 5 + 3)
+        assert summaries["src"] == DirSummary("src", diff=1, only_left=1, bytes=10)
+        assert summaries["src/lib"].differing == 1
+        assert summaries["docs"].to_dict() == {
+            "path": "docs", "equal": 0, "diff": 0, "only_left": 0, "only_right": 1,
+            "errors": 0, "bytes": 3}
+
+    def test_without_roots(self):
+        entries = [DiffEntry("x/f", EntryKind.ONLY_LEFT, DiffReason.MISSING),
+                   DiffEntry("x/g", EntryKind.DIFF, DiffReason.SIZE_MISMATCH, size_a=1, size_b=4),
+                   DiffEntry("x/h", EntryKind.ERROR, DiffReason.READ_ERROR)]
+        summaries = summarize_dirs(entries)
+        assert summaries["x"] == DirSummary("x", diff=1, only_left=1, errors=1, bytes=4)
+        assert summarize_dirs([]) == {}
+
+
+class TestSummaryVisitor:
+    def test_compare_dir(self, make_files, tmp_path):
+        a, b = _trees(make_files, tmp_path)
+        finished = []
+
+        class Inner(CompareVisitor):
+            def on_entry_finished(self, entry):
+                finished.append(entry.path)
+
+        visitor = SummaryVisitor(a, b, Inner())
+        result = compare_dir(a, b, visitor=visitor)
+        assert visitor.summaries() == summarize_dirs(iter_dir(a, b, include_equal=True), (a, b))
+        assert sorted(finished) == sorted(entry.path for entry in visitor.entries)
+        assert visitor.summaries()[""].differing == len(result.diff) + 2
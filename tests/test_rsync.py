@@ -0,0 +1,102 @@
+"""Tests for rsync-style block signatures and deltas."""
+
+from __future__ import annotations
+
+import io
+import os
+import random
+from array import array
+
+import pytest
+
+from komparu._core import rsum_blocks, rsum_scan
+from komparu._rsync import (
+    OP_COPY,
+    OP_LITERAL,
+    delta_ops,
+    encode_signature,
+    make_signature,
+    read_delta,
+    read_signature,
+    write_delta,
+)
+
+
+def _rebuild(old: bytes, new: bytes, block_size: int) -> bytes:
+    signature = read_signature(io.BytesIO(encode_signature(make_signature(old, block_size))))
+    out = io.BytesIO()
+    write_delta(new, signature, out)
+    out.seek(0)
+    size, ops = read_delta(out)
+    data = b"".join(old[value * block_size:(value + 1) * block_size] if op == OP_COPY
+                    else value for op, value in ops)
+    assert len(data) == size
+    return data
+
+
+class TestRollingChecksum:
+    def test_scan_finds_blocks_at_any_offset(self):
+        data = os.urandom(4096)
+        table = array("I", sorted(array("I", rsum_blocks(data, 512)))).tobytes()
+        assert rsum_scan(b"xyz" + data, 0, 512, table) == [3 + i * 512 for i in range(8)]
+
+    def test_scan_from_start(self):
+        data = os.urandom(2048)
+        table = array("I", sorted(array("I", rsum_blocks(data, 512)))).tobytes()
+        assert rsum_scan(data, 1024, 512, table) == [1024, 1536]
+
+    def test_partial_block_left_out(self):
+        assert len(rsum_blocks(b"x" * 1000, 512)) == 4
+        assert rsum_scan(b"x" * 100, 0, 512, array("I", [1]).tobytes()) == []
+
+    def test_bad_arguments(self):
+        with pytest.raises(ValueError):
+            rsum_blocks(b"x", 0)
+        with pytest.raises(ValueError):
+            rsum_scan(b"x", 0, 512, b"abc")
+
+
+class TestDelta:
+    def test_identical_is_all_copies(self):
+        data = os.urandom(8192)
+        ops = list(delta_ops(data, make_signature(data, 1024)))
+        assert ops == [(OP_COPY, i, 0) for i in range(8)]
+
+    def test_insertion_sends_only_new_bytes(self):
+        old = os.urandom(8192)
+        new = old[:3000] + b"inserted" + old[3000:]
+        ops = list(delta_ops(new, make_signature(old, 1024)))
+        literals = [(a, b) for op, a, b in ops if op == OP_LITERAL]
+        assert sum(b - a for a, b in literals) < 2 * 1024
+        assert sum(op == OP_COPY for op, _, _ in ops) == 7
+
+    def test_round_trip(self):
+        rng = random.Random(7)
+        old = bytes(rng.randrange(256) for _ in range(20000))
+        new = old[5000:12000] + b"moved" + old[:5000] + old[12001:] + b"tail"
+        assert _rebuild(old, new, 700) == new
+        assert _rebuild(b"", new, 512) == new
+        assert _rebuild(old, b"", 512) == b""
+
+    def test_repeated_blocks(self):
+        block = os.urandom(512)
+        assert _rebuild(block * 2, block * 5, 512) == block * 5
+        # Equal blocks are copied in order, not all from the first
+        ops = list(delta_ops(block * 3, make_signature(block * 3, 512)))
+        assert ops == [(OP_COPY, 0, 0), (OP_COPY, 1, 0), (OP_COPY, 2, 0)]
+
+
+class TestStreams:
+    def test_not_a_signature(self):
+        with pytest.raises(ValueError, match="signature"):
+            read_signature(io.BytesIO(b"nope"))
+
+    def test_truncated_signature(self):
+        data = encode_signature(make_signature(os.urandom(2048), 512))
+        with pytest.raises(ValueError, match="truncated"):
+            read_signature(io.BytesIO(data[:-3]))
+
+    def test_unknown_instruction(self):
+        _, ops = read_delta(io.BytesIO(b"KPD1\x00\x07"))
+        with pytest.raises(ValueError, match="instruction"):
+            list(ops)
@@ -0,0 +1,169 @@
+"""Tests for comparing a local tree with an S3 prefix, against an in-memory client."""
+
+from __future__ import annotations
+
+import base64
+import hashlib
+import importlib.util
+import io
+from pathlib import Path
+from typing import Any
+
+import pytest
+
+import komparu
+from komparu import DiffReason
+from komparu import _s3
+from komparu._cli import main
+
+MiB = 1024 * 1024
+
+
+class FakeS3:
+    """The list_objects_v2, head_object and get_object calls compare_dir_s3 makes."""
+
+    def __init__(self) -> None:
+        self.objects: dict[str, dict[str, Any]] = {}
+        self.ranges: list[str] = []
+
+    def put(self, key: str, data: bytes, *, part_size: int | None = None,
+            kms: bool = False, sha256: bool = False) -> None:
+        if part_size is None:
+            etag = hashlib.md5(data).hexdigest()
+        else:
+            parts = [data[i:i + part_size] for i in range(0, len(data), part_size)]
+            etag = hashlib.md5(b"".join(hashlib.md5(p).digest() for p in parts)).hexdigest()
+            etag += f"-{len(parts)}"
+        if kms:
+            etag = "0" * 32
+        self.objects[key] = {"data": data, "etag": etag, "part_size": part_size,
+                             "kms": kms, "sha256": sha256}
+
+    def get_paginator(self, name: str) -> Any:
+        assert name == "list_objects_v2"
+        return self
+
+    def paginate(self, *, Bucket: str, Prefix: str) -> Any:
+        if Bucket != "bucket":
+            raise _s3._ClientError({"Error": {"Code": "NoSuchBucket",
+                                              "Message": "no such bucket"}}, "ListObjectsV2")
+        contents = []
+        for key, obj in sorted(self.objects.items()):
+            if key.startswith(Prefix):
+                entry = {"Key": key, "Size": len(obj["data"]), "ETag": f'"{obj["etag"]}"'}
+                if obj["sha256"]:
+                    entry["ChecksumAlgorithm"] = ["SHA256"]
+                contents.append(entry)
+        # Two pages, to exercise pagination
+        yield {"Contents": contents[:1]}
+        yield {"Contents": contents[1:]}
+
+    def head_object(self, *, Bucket: str, Key: str, PartNumber: int | None = None,
+                    ChecksumMode: str | None = None) -> dict[str, Any]:
+        obj = self.objects[Key]
+        head: dict[str, Any] = {"ContentLength": len(obj["data"])}
+        if PartNumber is not None:
+            head["ContentLength"] = obj["part_size"]
+        if obj["kms"]:
+            head["ServerSideEncryption"] = "aws:kms"
+        if ChecksumMode == "ENABLED" and obj["sha256"]:
+            head["ChecksumSHA256"] = base64.b64encode(
+                hashlib.sha256(obj["data"]).digest()).decode()
+            head["ChecksumType"] = "FULL_OBJECT"
+        return head
+
+    def get_object(self, *, Bucket: str, Key: str, Range: str) -> dict[str, Any]:
+        self.ranges.append(Range)
+        start, end = map(int, Range.removeprefix("bytes=").split("-"))
+        return {"Body": io.BytesIO(self.objects[Key]["data"][start:end + 1])}
+
+
+@pytest.fixture
+def s3() -> FakeS3:
+    return FakeS3()
+
+
+class TestCompareDirS3:
+    def test_etags(self, s3: FakeS3, make_dir):
+        local = str(make_dir("local", {"same": b"same", "sub/changed": b"old", "resized": b"x",
+                                       "local-only": b""}))
+        s3.put("backup/same", b"same")
+        s3.put("backup/sub/changed", b"new")
+        s3.put("backup/resized", b"xx")
+        s3.put("backup/remote-only", b"")
+        s3.put("backup/sub/", b"")
+        s3.put("backupother/same", b"same")
+        result = komparu.compare_dir_s3(local, "s3://bucket/backup", client=s3)
+        assert result.diff == {"sub/changed": DiffReason.CONTENT_MISMATCH,
+                               "resized": DiffReason.SIZE_MISMATCH}
+        assert result.only_left == {"local-only"}
+        assert result.only_right == {"remote-only"}
+        assert s3.ranges == []
+
+    def test_equal(self, s3: FakeS3, make_dir):
+        local = str(make_dir("local", {"a": b"1", "b": b"2"}))
+        s3.put("a", b"1")
+        s3.put("b", b"2")
+        assert komparu.compare_dir_s3(local, "s3://bucket", client=s3).equal is True
+
+    def test_multipart_etag(self, s3: FakeS3, make_dir):
+        data = bytes(range(256)) * 4096 * 5
+        local = str(make_dir("local", {"big": data, "changed": data}))
+        s3.put("big", data, part_size=MiB)
+        s3.put("changed", data[:-1] + b"!", part_size=MiB)
+        result = komparu.compare_dir_s3(local, "s3://bucket", client=s3, chunk_size=65536)
+        assert result.diff == {"changed": DiffReason.CONTENT_MISMATCH}
+        assert s3.ranges == []
+
+    def test_stored_checksum(self, s3: FakeS3, make_dir):
+        local = str(make_dir("local", {"a": b"data", "b": b"data"}))
+        s3.put("a", b"data", sha256=True)
+        s3.put("b", b"dat!", sha256=True)
+        for obj in s3.objects.values():
+            obj["etag"] = "f" * 32  # ignored in favour of the checksum
+        result = komparu.compare_dir_s3(local, "s3://bucket", client=s3)
+        assert result.diff == {"b": DiffReason.CONTENT_MISMATCH}
+
+    def test_kms_reads_ranges(self, s3: FakeS3, make_dir):
+        local = str(make_dir("local", {"same": b"abcdefghij", "changed": b"abcdefghij"}))
+        s3.put("same", b"abcdefghij", kms=True)
+        s3.put("changed", b"abXdefghij", kms=True)
+        result = komparu.compare_dir_s3(local, "s3://bucket", client=s3, chunk_size=4,
+                                        max_workers=1)
+        assert result.diff == {"changed": DiffReason.CONTENT_MISMATCH}
+        # The differing object is read only up to its first differing range
+        assert s3.ranges == ["bytes=0-3", "bytes=0-3", "bytes=4-7", "bytes=8-9"]
+
+    def test_ignore(self, s3: FakeS3, make_dir):
+        local = str(make_dir("local", {"a": b"1", "x.log": b"local"}))
+        s3.put("a", b"1")
+        s3.put("y.log", b"remote")
+        assert komparu.compare_dir_s3(local, "s3://bucket", client=s3,
+                                      ignore=["*.log"]).equal is True
+
+    def test_missing_bucket(self, tmp_path: Path, s3: FakeS3):
+        with pytest.raises(komparu.SourceNotFoundError, match="no such bucket"):
+            komparu.compare_dir_s3(str(tmp_path), "s3://other", client=s3)
+
+    def test_bad_url(self, tmp_path: Path, s3: FakeS3):
+        with pytest.raises(ValueError, match="s3://"):
+            komparu.compare_dir_s3(str(tmp_path), "bucket/prefix", client=s3)
+        with pytest.raises(ValueError, match="bucket"):
+            komparu.compare_dir_s3(str(tmp_path), "s3:///prefix", client=s3)
+
+    @pytest.mark.skipif(importlib.util.find_spec("boto3") is not None,
+                        reason="boto3 is installed")
+    def test_needs_boto3(self, tmp_path: Path):
+        with pytest.raises(ImportError, match=r"komparu\[s3\]"):
+            komparu.compare_dir_s3(str(tmp_path), "s3://bucket")
+
+
+class TestCli:
+    def test_compare(self, s3: FakeS3, monkeypatch, capsys, make_dir):
+        local = str(make_dir("local", {"a": b"1", "b": b"2"}))
+        s3.put("site/a", b"1")
+        s3.put("site/b", b"3")
+        monkeypatch.setattr(_s3, "_client", lambda: s3)
+        assert main(["compare", local, "s3://bucket/site"]) == 1
+        assert capsys.readouterr().out.splitlines() == ["differ: b (content_mismatch)"]
+        assert main(["compare", "s3://bucket/site/", local, "-q"]) == 1
@@ -0,0 +1,108 @@
+"""Tests for comparing files on flaky media."""
+
+from __future__ import annotations
+
+import errno
+from pathlib import Path
+
+import pytest
+
+import komparu._salvage as salvage
+from komparu import (
+    CancelToken,
+    ComparisonCancelledError,
+    SalvageResult,
+    UnreadableRange,
+    compare_salvage,
+)
+from komparu._cli import main
+
+DATA = bytes(range(256)) * 64  # 16 KiB
+
+
+@pytest.fixture
+def files(tmp_path: Path) -> tuple[str, str]:
+    a, b = tmp_path / "a.iso", tmp_path / "b.iso"
+    a.write_bytes(DATA)
+    b.write_bytes(DATA)
+    return str(a), str(b)
+
+
+def bad_sectors(monkeypatch, path: str, *ranges: tuple[int, int], heal_after: int = 0) -> list:
+    """Make reads of *path* overlapping *ranges* fail (until *heal_after* failures)."""
+    read_at, failures = salvage._read_at, []
+
+    def flaky(f, offset, size):
+        if f.name == path and any(offset < end and start < offset + size
+                                  for start, end in ranges):
+            if not heal_after or len(failures) < heal_after:
+                failures.append((offset, size))
+                raise OSError(errno.EIO, "Input/output error")
+        return read_at(f, offset, size)
+
+    monkeypatch.setattr(salvage, "_read_at", flaky)
+    return failures
+
+
+class TestCompareSalvage:
+    def test_equal(self, files):
+        result = compare_salvage(*files, chunk_size=4096)
+        assert result.equal and result.verified and result.unreadable == ()
+
+    def test_unreadable_sectors(self, files, monkeypatch):
+        a, b = files
+        bad_sectors(monkeypatch, a, (1000, 1100), (8192, 9216))
+        bad_sectors(monkeypatch, b, (8704, 9216))
+        result = compare_salvage(a, b, chunk_size=4096, min_read=512)
+        assert result.equal and not result.verified
+        assert result.unreadable == (UnreadableRange(512, 1536, "left"),
+                                     UnreadableRange(8192, 8704, "left"),
+                                     UnreadableRange(8704, 9216, "both"))
+        assert result.unreadable_bytes == 2048
+
+    def test_retries(self, files, monkeypatch):
+        a, b = files
+        failures = bad_sectors(monkeypatch, a, (0, 1), heal_after=3)
+        assert compare_salvage(a, b, chunk_size=1024, min_read=512, retries=2).verified
+        assert failures == [(0, 1024), (0, 512), (0, 512)]
+
+    def test_difference_past_bad_sector(self, files, monkeypatch):
+        a, b = files
+        Path(b).write_bytes(DATA[:12000] + b"x" + DATA[12001:])
+        bad_sectors(monkeypatch, a, (0, 512))
+        result = compare_salvage(a, b, chunk_size=4096)
+        assert not result.equal
+        assert result.unreadable == (UnreadableRange(0, 512, "left"),)
+        Path(b).write_bytes(DATA[:-1])
+        assert compare_salvage(a, b) == SalvageResult(equal=False)
+
+    def test_arguments(self, files):
+        with pytest.raises(ValueError, match="min_read"):
+            compare_salvage(*files, chunk_size=512, min_read=1024)
+        with pytest.raises(ValueError, match="retries"):
+            compare_salvage(*files, retries=-1)
+        token = CancelToken()
+        token.cancel()
+        with pytest.raises(ComparisonCancelledError):
+            compare_salvage(*files, cancel=token)
+
+
+class TestCli:
+    def test_report(self, files, monkeypatch, capsys):
+        a, b = files
+        assert main(["compare", "--salvage", a, b]) == 0
+        bad_sectors(monkeypatch, b, (2048, 2049))
+        assert main(["compare", "--salvage", "--min-read", "2048", "--chunk-size", "8192",
+                     a, b]) == 2
+        captured = capsys.readouterr()
+        assert captured.out == "unreadable in right: bytes 2048-4095 (2048 bytes)\n"
+        assert "2048 bytes in 1 ranges could not be verified" in captured.err
+
+    def test_refused(self, files, tmp_path: Path, capsys):
+        a, b = files
+        assert main(["compare", "--salvage", "--split", a, b]) == 2
+        assert "--salvage cannot be combined with --split" in capsys.readouterr().err
+        assert main(["compare", "--salvage", str(tmp_path), str(tmp_path)]) == 2
+        assert "two local files" in capsys.readouterr().err
+        assert main(["compare", "--min-read", "2048", a, b]) == 2
+        assert "need --salvage" in capsys.readouterr().err
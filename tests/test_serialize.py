@@ -0,0 +1,123 @@
+"""Tests for to_dict/from_dict on result types."""
+
+from __future__ import annotations
+
+import json
+
+import pytest
+
+import komparu
+from komparu import (
+    CompareResult,
+    DiffEntry,
+    DiffReason,
+    DirResult,
+    EntryKind,
+    SyncAction,
+    SyncPlan,
+    SyncStep,
+)
+
+
+class TestDirResult:
+    def test_round_trip(self):
+        result = DirResult(
+            equal=False,
+            diff={"b.txt": DiffReason.SIZE_MISMATCH, "a.txt": DiffReason.CONTENT_MISMATCH},
+            only_left={"z", "y"},
+            only_right={"x"},
+            errors={"locked"},
+        )
+        data = json.loads(json.dumps(result.to_dict()))
+        assert DirResult.from_dict(data) == result
+
+    def test_shape(self):
+        result = DirResult(equal=False, diff={"a": DiffReason.CONTENT_MISMATCH},
+                           only_left={"z", "y"}, only_right=set())
+        assert result.to_dict() == {
+            "equal": False,
+            "diff": {"a": "content_mismatch"},
+            "only_left": ["y", "z"],
+            "only_right": [],
+            "errors": [],
+        }
+
+    def test_byte_order(self):
+        # "\udc80" is the undecodable byte 0x80, "\ue000" is EE 80 80 in UTF-8
+        names = {"\udcff", "\ue000", "\u00e9", "\udc80", "a0", "a-b"}
+        result = DirResult(equal=False, diff={}, only_left=names, only_right=set())
+        assert result.to_dict()["only_left"] == ["a-b", "a0", "\udc80", "\u00e9", "\ue000",
+                                                 "\udcff"]
+        assert sorted(names, key=komparu.path_key) == result.to_dict()["only_left"]
+        assert sorted(["a0", "a-b", "a/b", "a"], key=komparu.tree_key) == ["a", "a/b", "a-b", "a0"]
+
+    def test_errors_optional(self):
+        result = DirResult.from_dict(
+            {"equal": True, "diff": {}, "only_left": [], "only_right": []}
+        )
+        assert result.errors == set()
+
+    def test_real_comparison(self, make_files, tmp_path):
+        make_files({"a/same.txt": b"x", "a/diff.txt": b"1", "a/left.txt": b"l",
+                    "b/same.txt": b"x", "b/diff.txt": b"2"})
+        result = komparu.compare_dir(str(tmp_path / "a"), str(tmp_path / "b"))
+        assert DirResult.from_dict(json.loads(json.dumps(result.to_dict()))) == result
+
+
+class TestDiffEntry:
+    def test_round_trip(self):
+        for entry in (
+            DiffEntry("a", EntryKind.DIFF, DiffReason.CONTENT_MISMATCH),
+            DiffEntry("b", EntryKind.EQUAL),
+        ):
+            assert DiffEntry.from_dict(json.loads(json.dumps(entry.to_dict()))) == entry
+
+    def test_shape(self):
+        entry = DiffEntry("x", EntryKind.ONLY_LEFT, DiffReason.MISSING)
+        assert entry.to_dict() == {"path": "x", "kind": "only_left", "reason": "missing"}
+
+    def test_payload_round_trip(self):
+        entry = DiffEntry("x", EntryKind.DIFF, DiffReason.SIZE_MISMATCH, size_a=1, size_b=5)
+        data = json.loads(json.dumps(entry.to_dict()))
+        assert data["size_a"] == 1 and data["size_b"] == 5
+        restored = DiffEntry.from_dict(data)
+        assert (restored.size_a, restored.size_b) == (1, 5)
+
+    def test_payload_not_part_of_equality(self):
+        plain = DiffEntry("x", EntryKind.DIFF, DiffReason.CONTENT_MISMATCH)
+        sized = DiffEntry("x", EntryKind.DIFF, DiffReason.CONTENT_MISMATCH, size_a=3, size_b=3)
+        assert plain == sized
+        assert hash(plain) == hash(sized)
+
+    def test_payload_is_keyword_only(self):
+        with pytest.raises(TypeError):
+            DiffEntry("x", EntryKind.DIFF, DiffReason.CONTENT_MISMATCH, 3)
+
+    def test_unknown_keys_ignored(self):
+        data = {"path": "x", "kind": "diff", "reason": "content_mismatch", "added_later": 1}
+        expected = DiffEntry("x", EntryKind.DIFF, DiffReason.CONTENT_MISMATCH)
+        assert DiffEntry.from_dict(data) == expected
+
+
+class TestCompareResult:
+    def test_round_trip(self):
+        result = CompareResult(
+            all_equal=False,
+            groups=[{"a", "b"}, {"c"}],
+            diff={("a", "b"): True, ("a", "c"): False, ("b", "c"): False},
+        )
+        data = json.loads(json.dumps(result.to_dict()))
+        assert data["diff"][0] == {"a": "a", "b": "b", "equal": True}
+        assert CompareResult.from_dict(data) == result
+
+
+class TestSyncPlan:
+    def test_round_trip(self):
+        plan = SyncPlan([SyncStep(SyncAction.DELETE, "old"),
+                         SyncStep(SyncAction.MKDIR, "new", 0o755),
+                         SyncStep(SyncAction.COPY, "new/f", 0o644, 12),
+                         SyncStep(SyncAction.COPY, "link")], errors={"locked"})
+        data = json.loads(json.dumps(plan.to_dict()))
+        assert data["steps"][:2] == [{"action": "delete", "path": "old"},
+                                     {"action": "mkdir", "path": "new", "mode": 0o755}]
+        assert SyncPlan.from_dict(data) == plan
@@ -0,0 +1,311 @@
+"""Tests for the HTTP/JSON comparison service."""
+
+from __future__ import annotations
+
+import hashlib
+import hmac
+import http.client
+import http.server
+import json
+import socket
+import socketserver
+import threading
+from collections.abc import Iterator
+from pathlib import Path
+from typing import Any, TypeVar
+
+import pytest
+
+import komparu._server
+from komparu import ComparisonServer, CompareOptions, LogSink, PrometheusMetrics
+from komparu._cli import main
+
+S = TypeVar("S", bound=socketserver.BaseServer)
+
+
+class Client:
+    def __init__(self, server: ComparisonServer, token: str | None = None) -> None:
+        self.port = server.server_address[1]
+        self.token = token
+
+    def request(self, method: str, path: str, body: Any = None
+                ) -> tuple[int, Any, http.client.HTTPResponse]:
+        conn = http.client.HTTPConnection("127.0.0.1", self.port, timeout=10)
+        headers = {"Authorization": f"Bearer {self.token}"} if self.token else {}
+        data = json.dumps(body).encode() if body is not None else None
+        conn.request(method, path, body=data, headers=headers)
+        response = conn.getresponse()
+        raw = response.read()
+        conn.close()
+        return response.status, json.loads(raw) if raw else None, response
+
+    def events(self, id: str) -> list[dict[str, Any]]:
+        conn = http.client.HTTPConnection("127.0.0.1", self.port, timeout=10)
+        conn.request("GET", f"/v1/comparisons/{id}/events")
+        lines = [json.loads(line) for line in conn.getresponse().read().splitlines()]
+        conn.close()
+        return lines
+
+    def wait(self, id: str) -> dict[str, Any]:
+        return self.events(id)[-1]
+
+
+def _serve(server: S) -> Iterator[S]:
+    thread = threading.Thread(target=server.serve_forever, daemon=True)
+    thread.start()
+    yield server
+    server.shutdown()
+    server.server_close()
+
+
+@pytest.fixture
+def server() -> Iterator[ComparisonServer]:
+    yield from _serve(ComparisonServer(("127.0.0.1", 0)))
+
+
+@pytest.fixture
+def trees(make_dir) -> tuple[Path, Path]:
+    files = {f"d{i}/f{j}.bin": bytes([i, j]) * 100 for i in range(5) for j in range(10)}
+    a = make_dir("a", files)
+    b = make_dir("b", {**files, "d1/f1.bin": b"changed", "extra": b""})
+    return a, b
+
+
+class TestComparisons:
+    def test_directories(self, server, trees):
+        a, b = trees
+        client = Client(server)
+        status, job, response = client.request("POST", "/v1/comparisons",
+                                               {"left": str(a), "right": str(b)})
+        assert status == 202
+        assert response.getheader("Location") == f"/v1/comparisons/{job['id']}"
+        events = client.events(job["id"])
+        assert all(event["status"] == "running" for event in events[:-1])
+        final = events[-1]
+        assert final["status"] == "done"
+        assert final["progress"]["files_done"] >= 50
+        assert final["result"]["diff"] == {"d1/f1.bin": "size_mismatch"}
+        assert final["result"]["only_right"] == ["extra"]
+        assert client.request("GET", f"/v1/comparisons/{job['id']}/report")[1] == final["result"]
+        assert client.request("GET", f"/v1/comparisons/{job['id']}")[1] == final
+
+    def test_files_and_options(self, server, trees):
+        a, b = trees
+        client = Client(server)
+        _, job, _ = client.request("POST", "/v1/comparisons", {
+            "left": str(a / "d0" / "f0.bin"), "right": str(b / "d0" / "f0.bin")})
+        assert client.wait(job["id"])["result"] == {"equal": True}
+        _, job, _ = client.request("POST", "/v1/comparisons", {
+            "left": str(a), "right": str(b),
+            "options": {"ignore": ["d1", "extra"], "max_workers": 1}})
+        assert client.wait(job["id"])["result"]["equal"] is True
+
+    def test_failure(self, server, tmp_path: Path):
+        client = Client(server)
+        _, job, _ = client.request("POST", "/v1/comparisons", {
+            "left": str(tmp_path / "missing"), "right": str(tmp_path)})
+        final = client.wait(job["id"])
+        assert final["status"] == "failed" and "missing" in final["error"]
+        status, body, _ = client.request("GET", f"/v1/comparisons/{job['id']}/report")
+        assert status == 409 and body == {"error": "comparison is failed"}
+
+    def test_list_and_delete(self, server, trees):
+        a, b = trees
+        client = Client(server)
+        _, job, _ = client.request("POST", "/v1/comparisons", {"left": str(a), "right": str(b)})
+        client.wait(job["id"])
+        assert [j["id"] for j in client.request("GET", "/v1/comparisons")[1]] == [job["id"]]
+        assert client.request("DELETE", f"/v1/comparisons/{job['id']}")[0] == 200
+        assert client.request("GET", f"/v1/comparisons/{job['id']}")[0] == 404
+        assert client.request("GET", "/v1/comparisons")[1] == []
+
+    def test_bad_requests(self, server):
+        client = Client(server)
+        assert client.request("POST", "/v1/comparisons", {"left": "a"})[0] == 400
+        status, body, _ = client.request("POST", "/v1/comparisons", {
+            "left": "a", "right": "b", "options": {"proxy": "http://x"}})
+        assert status == 400 and "may only set" in body["error"]
+        assert client.request("POST", "/v1/comparisons", {
+            "left": "a", "right": "b", "options": {"chunk_size": 0}})[0] == 400
+        assert client.request("GET", "/v2/comparisons")[0] == 404
+        assert client.request("DELETE", "/v1/comparisons")[0] == 404
+        assert client.request("GET", "/v1/health")[1]["ok"] is True
+
+    def test_max_jobs(self, tmp_path: Path, trees):
+        a, b = trees
+        for server in _serve(ComparisonServer(("127.0.0.1", 0), max_jobs=2)):
+            client = Client(server)
+            ids = []
+            for _ in range(4):
+                _, job, _ = client.request("POST", "/v1/comparisons",
+                                           {"left": str(a), "right": str(b)})
+                client.wait(job["id"])
+                ids.append(job["id"])
+            assert [j["id"] for j in client.request("GET", "/v1/comparisons")[1]] == ids[1:]
+
+    def test_max_running(self, trees, monkeypatch):
+        a, b = trees
+        release = threading.Event()
+        run = komparu._server._run
+
+        def held(job, server):
+            release.wait(10)
+            run(job, server)
+
+        monkeypatch.setattr(komparu._server, "_run", held)
+        for server in _serve(ComparisonServer(("127.0.0.1", 0), max_running=1)):
+            client = Client(server)
+            request = {"left": str(a), "right": str(b)}
+            _, job, _ = client.request("POST", "/v1/comparisons", request)
+            status, body, _ = client.request("POST", "/v1/comparisons", request)
+            assert status == 503 and body == {"error": "already running 1 comparisons"}
+            release.set()
+            client.wait(job["id"])
+            assert client.request("POST", "/v1/comparisons", request)[0] == 202
+
+    def test_max_workers_capped(self, trees):
+        a, b = trees
+        options = CompareOptions(max_workers=2)
+        for server in _serve(ComparisonServer(("127.0.0.1", 0), options=options)):
+            client = Client(server)
+            _, job, _ = client.request("POST", "/v1/comparisons", {
+                "left": str(a), "right": str(b), "options": {"max_workers": 64}})
+            assert server.job(job["id"]).options.max_workers == 2
+            client.wait(job["id"])
+
+
+    def test_metrics(self, trees):
+        a, b = trees
+        for server in _serve(ComparisonServer(("127.0.0.1", 0), metrics=PrometheusMetrics())):
+            client = Client(server)
+            for right in (a, b, a / "missing"):
+                _, job, _ = client.request("POST", "/v1/comparisons",
+                                           {"left": str(a), "right": str(right)})
+                client.wait(job["id"])
+            conn = http.client.HTTPConnection("127.0.0.1", client.port, timeout=10)
+            conn.request("GET", "/metrics")
+            response = conn.getresponse()
+            text = response.read().decode()
+            conn.close()
+            assert response.getheader("Content-Type").startswith("text/plain")
+            assert 'komparu_comparisons_total{outcome="equal"} 1' in text
+            assert 'komparu_comparisons_total{outcome="different"} 1' in text
+            assert 'komparu_comparisons_total{outcome="failed"} 1' in text
+            assert "komparu_files_different_total 1" in text
+            assert "komparu_comparisons_running 0" in text
+
+    def test_no_metrics(self, server):
+        assert Client(server).request("GET", "/metrics")[0] == 404
+
+    def test_log_sink(self, tmp_path: Path, trees):
+        a, b = trees
+        receiver = socket.socket(socket.AF_UNIX, socket.SOCK_DGRAM)
+        receiver.bind(str(tmp_path / "log.sock"))
+        receiver.settimeout(5)
+        sink = LogSink("syslog", address=str(tmp_path / "log.sock"), entries=False)
+        for server in _serve(ComparisonServer(("127.0.0.1", 0), log_sink=sink)):
+            client = Client(server)
+            for right in (b, a / "missing"):
+                _, job, _ = client.request("POST", "/v1/comparisons",
+                                           {"left": str(a), "right": str(right)})
+                client.wait(job["id"])
+        records = [receiver.recv(65536), receiver.recv(65536)]
+        sink.close()
+        receiver.close()
+        assert b"differ: 1 diff, 1 only right" in records[0]
+        assert records[1].startswith(b"<11>") and b"missing" in records[1]
+
+
+class TestAccess:
+    def test_token(self, tmp_path: Path):
+        for server in _serve(ComparisonServer(("127.0.0.1", 0), token="s3cret")):
+            assert Client(server).request("GET", "/v1/health")[0] == 401
+            assert Client(server, "wrong").request("GET", "/v1/health")[0] == 401
+            assert Client(server, "s3cret").request("GET", "/v1/health")[0] == 200
+
+    def test_roots(self, tmp_path: Path, trees):
+        a, b = trees
+        for server in _serve(ComparisonServer(("127.0.0.1", 0), roots=[str(tmp_path)])):
+            client = Client(server)
+            _, job, _ = client.request("POST", "/v1/comparisons", {"left": "a", "right": "b"})
+            assert client.wait(job["id"])["status"] == "done"
+            assert job["left"] == str(a.resolve())
+            status, body, _ = client.request("POST", "/v1/comparisons",
+                                             {"left": "a", "right": "/etc"})
+            assert status == 403 and "outside" in body["error"]
+            assert client.request("POST", "/v1/comparisons", {
+                "left": "a", "right": "http://example.com/b"})[0] == 403
+
+
+class _Hook(http.server.BaseHTTPRequestHandler):
+    server: Receiver
+
+    def do_POST(self) -> None:
+        body = self.rfile.read(int(self.headers["Content-Length"]))
+        self.server.posts.append((json.loads(body), self.headers["X-Komparu-Signature"]))
+        self.server.bodies.append(body)
+        self.send_response(self.server.status)
+        self.send_header("Content-Length", "0")
+        self.end_headers()
+
+    def log_message(self, format: str, *args: Any) -> None:
+        pass
+
+
+class Receiver(http.server.ThreadingHTTPServer):
+    """Webhook endpoint keeping what it is sent."""
+
+    def __init__(self, status: int = 204) -> None:
+        self.status = status
+        self.posts: list[tuple[dict[str, Any], str | None]] = []
+        self.bodies: list[bytes] = []
+        super().__init__(("127.0.0.1", 0), _Hook)
+
+    @property
+    def url(self) -> str:
+        return f"http://127.0.0.1:{self.server_address[1]}/hook"
+
+
+class TestWebhook:
+    def test_differences_and_failures(self, tmp_path: Path, trees):
+        a, b = trees
+        for receiver in _serve(Receiver()):
+            for server in _serve(ComparisonServer(("127.0.0.1", 0), webhook=receiver.url,
+                                                  webhook_secret="k")):
+                client = Client(server)
+                for left, right in ((a, a), (a, b), (tmp_path / "missing", a)):
+                    _, job, _ = client.request("POST", "/v1/comparisons",
+                                               {"left": str(left), "right": str(right)})
+                    final = client.wait(job["id"])
+                    assert ("webhook" in final) == ((left, right) != (a, a))
+            assert [post["event"] for post, _ in receiver.posts] == ["differences", "failed"]
+            different = receiver.posts[0][0]["job"]
+            assert different["status"] == "done"
+            assert different["result"]["only_right"] == ["extra"]
+            assert final["webhook"] == {"status": 204}
+            expected = hmac.new(b"k", receiver.bodies[0], hashlib.sha256).hexdigest()
+            assert receiver.posts[0][1] == f"sha256={expected}"
+
+    def test_webhook_errors(self, trees):
+        a, b = trees
+        for receiver in _serve(Receiver(status=500)):
+            for server in _serve(ComparisonServer(("127.0.0.1", 0), webhook=receiver.url)):
+                client = Client(server)
+                _, job, _ = client.request("POST", "/v1/comparisons",
+                                           {"left": str(a), "right": str(b)})
+                final = client.wait(job["id"])
+        assert final["status"] == "done"
+        assert "500" in final["webhook"]["error"]
+        assert receiver.posts[0][1] is None
+        with pytest.raises(ValueError, match="http"):
+            ComparisonServer(("127.0.0.1", 0), webhook="ftp://x")
+
+
+class TestCli:
+    def test_options(self, capsys):
+        assert main(["serve", "--listen", "nowhere"]) == 2
+        assert "HOST:PORT" in capsys.readouterr().err
+        assert main(["serve", "--listen", "127.0.0.1:0", "--max-jobs", "0"]) == 2
+        assert "max_jobs" in capsys.readouterr().err
+        assert main(["serve", "--listen", "127.0.0.1:0", "--max-running", "0"]) == 2
+        assert "max_running" in capsys.readouterr().err
@@ -0,0 +1,126 @@
+"""Tests for the reusable Comparator."""
+
+from __future__ import annotations
+
+from concurrent.futures import ThreadPoolExecutor
+
+import pytest
+
+import komparu
+from komparu import (
+    CompareOptions,
+    Comparator,
+    ContentComparator,
+    DiffReason,
+    EntryKind,
+    MetricsSink,
+    PathFilter,
+)
+
+
+class AlwaysEqual(ContentComparator):
+    def compare(self, path_a: str, path_b: str) -> bool:
+        return True
+
+
+class NoLogs(PathFilter):
+    def include(self, path, stat_a, stat_b) -> bool:
+        return not path.endswith(".log")
+
+
+@pytest.fixture
+def tree_files() -> dict[str, bytes]:
+    return {
+        "a/same.txt": b"same",
+        "b/same.txt": b"same",
+        "a/diff.txt": b"one",
+        "b/diff.txt": b"two",
+        "a/doc.cfg": b"x=1",
+        "b/doc.cfg": b"x=2",
+        "a/run.log": b"1",
+        "b/run.log": b"2",
+    }
+
+
+class TestConstruction:
+    def test_defaults(self):
+        assert Comparator().options == CompareOptions()
+
+    def test_rejects_non_options(self):
+        with pytest.raises(TypeError, match="CompareOptions"):
+            Comparator({"chunk_size": 4096})
+
+    def test_validates_plugins_eagerly(self):
+        with pytest.raises(TypeError, match="PathFilter"):
+            Comparator(path_filter=lambda p: True)
+        with pytest.raises(TypeError, match="ContentComparator"):
+            Comparator(comparators={"*.cfg": object()})
+        with pytest.raises(TypeError, match="MetricsSink"):
+            Comparator(metrics=object())
+
+    def test_immutable(self):
+        comparator = Comparator()
+        with pytest.raises(AttributeError):
+            comparator.options = CompareOptions(chunk_size=4096)
+
+    def test_comparators_copied(self, trees):
+        a, b = map(str, trees)
+        comparators = {"*.cfg": AlwaysEqual()}
+        comparator = Comparator(comparators=comparators)
+        comparators.clear()
+        assert "doc.cfg" not in comparator.compare_dir(a, b).diff
+
+
+class TestCalls:
+    def test_compare(self, make_file):
+        a = make_file("x.bin", b"data")
+        b = make_file("y.bin", b"data")
+        assert Comparator(CompareOptions(quick_check=False)).compare(str(a), str(b)) is True
+
+    def test_compare_dir_applies_bound_settings(self, trees):
+        a, b = map(str, trees)
+        comparator = Comparator(
+            CompareOptions(max_workers=2),
+            path_filter=NoLogs(),
+            comparators={"*.cfg": AlwaysEqual()},
+        )
+        result = comparator.compare_dir(a, b)
+        assert result.diff == {"diff.txt": DiffReason.CONTENT_MISMATCH}
+
+    def test_iter_dir(self, trees):
+        a, b = map(str, trees)
+        comparator = Comparator(CompareOptions(ignore=("*.log",)))
+        kinds = {e.path: e.kind for e in comparator.iter_dir(a, b, include_equal=True)}
+        assert kinds == {"same.txt": EntryKind.EQUAL, "diff.txt": EntryKind.DIFF,
+                         "doc.cfg": EntryKind.DIFF}
+
+    def test_plan_dir(self, trees):
+        a, b = map(str, trees)
+        plan = Comparator(path_filter=NoLogs()).plan_dir(a, b)
+        assert {p.path for p in plan.pairs} == {"same.txt", "diff.txt", "doc.cfg"}
+
+    def test_metrics_bound(self, trees):
+        a, b = map(str, trees)
+        counted: list[str] = []
+
+        class Sink(MetricsSink):
+            def count(self, name, value=1):
+                counted.append(name)
+
+        Comparator(metrics=Sink()).compare_dir(a, b)
+        assert "files_compared" in counted
+
+    def test_matches_functions(self, trees):
+        a, b = map(str, trees)
+        opts = CompareOptions(ignore=("*.log",))
+        assert Comparator(opts).compare_dir(a, b) == komparu.compare_dir(a, b, options=opts)
+
+
+class TestConcurrency:
+    def test_shared_across_threads(self, trees):
+        a, b = map(str, trees)
+        comparator = Comparator(CompareOptions(ignore=("*.log",)),
+                                comparators={"*.cfg": AlwaysEqual()})
+        with ThreadPoolExecutor(max_workers=8) as pool:
+            results = list(pool.map(lambda _: comparator.compare_dir(a, b), range(32)))
+        assert all(r.diff == {"diff.txt": DiffReason.CONTENT_MISMATCH} for r in results)
@@ -0,0 +1,121 @@
+"""Tests for minisign manifest signatures."""
+
+from __future__ import annotations
+
+import hashlib
+from pathlib import Path
+
+import pytest
+
+import komparu
+
+
+@pytest.fixture
+def manifest(tmp_path):
+    (tmp_path / "tree").mkdir()
+    (tmp_path / "tree" / "a").write_bytes(b"data")
+    path = tmp_path / "tree.sha256"
+    path.write_text(f"{hashlib.sha256(b'data').hexdigest()}  a\n")
+    return path
+
+
+class TestSignatures:
+    def test_round_trip(self, manifest, make_key_pair):
+        secret, public = make_key_pair()
+        signature = komparu.sign_manifest(str(manifest), secret, trusted_comment="release 1.2")
+        assert signature == f"{manifest}.minisig"
+        assert komparu.verify_signature(manifest.read_bytes(), signature, public) == "release 1.2"
+
+    def test_default_trusted_comment(self, manifest, make_key_pair):
+        secret, public = make_key_pair()
+        signature = komparu.sign_manifest(str(manifest), secret)
+        comment = komparu.verify_signature(manifest.read_bytes(), signature, public)
+        assert comment.startswith("timestamp:")
+        assert "\tfile:tree.sha256" in comment
+
+    def test_tampered_manifest(self, manifest, make_key_pair):
+        secret, public = make_key_pair()
+        signature = komparu.sign_manifest(str(manifest), secret)
+        with pytest.raises(komparu.SignatureError, match="does not verify"):
+            komparu.verify_signature(manifest.read_bytes() + b"\n", signature, public)
+
+    def test_tampered_trusted_comment(self, manifest, make_key_pair):
+        secret, public = make_key_pair()
+        signature = Path(komparu.sign_manifest(str(manifest), secret, trusted_comment="v1"))
+        signature.write_text(signature.read_text().replace("trusted comment: v1",
+                                                           "trusted comment: v2"))
+        with pytest.raises(komparu.SignatureError, match="does not verify"):
+            komparu.verify_signature(manifest.read_bytes(), str(signature), public)
+
+    def test_other_key(self, manifest, make_key_pair):
+        secret, _ = make_key_pair("one")
+        _, other = make_key_pair("two")
+        signature = komparu.sign_manifest(str(manifest), secret)
+        with pytest.raises(komparu.SignatureError, match="made with key"):
+            komparu.verify_signature(manifest.read_bytes(), signature, other)
+
+    def test_encrypted_key(self, manifest, make_key_pair):
+        secret, public = make_key_pair(password="hunter2")
+        with pytest.raises(ValueError, match="encrypted"):
+            komparu.sign_manifest(str(manifest), secret)
+        with pytest.raises(ValueError, match="wrong password"):
+            komparu.sign_manifest(str(manifest), secret, password="nope")
+        signature = komparu.sign_manifest(str(manifest), secret, password="hunter2")
+        komparu.verify_signature(manifest.read_bytes(), signature, public)
+
+    def test_malformed_key(self, manifest, tmp_path):
+        pytest.importorskip("cryptography")
+        (tmp_path / "bad.pub").write_text("untrusted comment: x\nnot base64\n")
+        with pytest.raises(ValueError, match="not a minisign public key"):
+            komparu.verify_signature(b"", str(manifest), str(tmp_path / "bad.pub"))
+
+
+class TestSignedVerify:
+    def test_verify_manifest(self, manifest, make_key_pair, tmp_path):
+        secret, public = make_key_pair()
+        komparu.sign_manifest(str(manifest), secret)
+        assert komparu.verify_manifest(str(tmp_path / "tree"), str(manifest),
+                                       public_key=public).equal
+
+    def test_tampered_manifest_is_not_trusted(self, manifest, make_key_pair, tmp_path):
+        secret, public = make_key_pair()
+        komparu.sign_manifest(str(manifest), secret)
+        (tmp_path / "tree" / "a").write_bytes(b"evil")
+        manifest.write_text(f"{hashlib.sha256(b'evil').hexdigest()}  a\n")
+        with pytest.raises(komparu.SignatureError):
+            komparu.verify_manifest(str(tmp_path / "tree"), str(manifest), public_key=public)
+
+    def test_missing_signature(self, manifest, make_key_pair):
+        _, public = make_key_pair()
+        with pytest.raises(FileNotFoundError):
+            komparu.read_manifest(str(manifest), public_key=public)
+
+    def test_public_key_needs_a_path(self, make_key_pair, tmp_path):
+        _, public = make_key_pair()
+        with pytest.raises(ValueError, match="file path"):
+            komparu.verify_manifest(str(tmp_path), {}, public_key=public)
+
+
+class TestRemoteManifest:
+    def test_unsigned(self, manifest, serve_dir, tmp_path):
+        url = f"{serve_dir(tmp_path)}/tree.sha256"
+        assert komparu.read_manifest(url).digests == {"a": hashlib.sha256(b"data").hexdigest()}
+        assert komparu.verify_manifest(str(tmp_path / "tree"), url).equal
+
+    def test_signed(self, manifest, make_key_pair, serve_dir, tmp_path):
+        secret, public = make_key_pair()
+        komparu.sign_manifest(str(manifest), secret)
+        url = f"{serve_dir(tmp_path)}/tree.sha256"
+        assert komparu.verify_manifest(str(tmp_path / "tree"), url, public_key=public).equal
+        manifest.write_text(manifest.read_text() + "\n")
+        with pytest.raises(komparu.SignatureError, match="does not verify"):
+            komparu.read_manifest(url, public_key=public)
+
+    def test_missing(self, manifest, make_key_pair, serve_dir, tmp_path):
+        _, public = make_key_pair()
+        base = serve_dir(tmp_path)
+        with pytest.raises(komparu.SourceNotFoundError, match="404"):
+            komparu.read_manifest(f"{base}/gone.sha256")
+        with pytest.raises(komparu.SourceNotFoundError) as info:
+            komparu.read_manifest(f"{base}/tree.sha256", public_key=public)
+        assert info.value.filename == f"{base}/tree.sha256.minisig"
@@ -0,0 +1,137 @@
+"""Tests for tree snapshots."""
+
+from __future__ import annotations
+
+import hashlib
+import io
+import os
+from pathlib import Path
+
+import pytest
+
+import komparu
+from komparu import DiffReason, Snapshot, SnapshotEntry
+
+
+@pytest.fixture
+def tree(tmp_path: Path) -> Path:
+    root = tmp_path / "tree"
+    (root / "sub" / "deep").mkdir(parents=True)
+    (root / "a.txt").write_bytes(b"alpha")
+    (root / "sub" / "b.txt").write_bytes(b"beta")
+    (root / "sub" / "deep" / "c.txt").write_bytes(b"gamma")
+    os.symlink("a.txt", root / "link")
+    return root
+
+
+def _round_trip(snapshot: Snapshot) -> Snapshot:
+    buf = io.BytesIO()
+    komparu.write_snapshot(snapshot, buf)
+    buf.seek(0)
+    return komparu.read_snapshot(buf)
+
+
+class TestSnapshotTree:
+    def test_entries(self, tree: Path):
+        snapshot = komparu.snapshot_tree(str(tree), follow_symlinks=False)
+        assert snapshot.algorithm == "sha256"
+        assert sorted(snapshot.entries) == ["a.txt", "link", "sub", "sub/b.txt", "sub/deep",
+                                            "sub/deep/c.txt"]
+        a = snapshot.entries["a.txt"]
+        assert a.kind == "file"
+        assert a.size == 5
+        assert a.digest == hashlib.sha256(b"alpha").hexdigest()
+        assert a.mtime_ns == (tree / "a.txt").stat().st_mtime_ns
+        assert snapshot.entries["link"].target == "a.txt"
+        assert snapshot.entries["sub"].kind == "dir"
+
+    def test_round_trip(self, tree: Path):
+        snapshot = komparu.snapshot_tree(str(tree), follow_symlinks=False)
+        assert _round_trip(snapshot) == snapshot
+
+    def test_round_trip_odd_values(self):
+        snapshot = Snapshot(algorithm="crc32c", entries={
+            "old": SnapshotEntry(kind="file", mode=0o4755, uid=70000, gid=0, size=1 << 40,
+                                 mtime_ns=-1_000_000_007, digest="e3069283",
+                                 xattrs={"user.tag": b"\0\xff"}),
+            "old dir": SnapshotEntry(kind="dir", mode=0o1777, uid=0, gid=0, size=0,
+                                     mtime_ns=0),
+            "ünïcode\nname": SnapshotEntry(kind="symlink", mode=0o777, uid=1, gid=2, size=3,
+                                           mtime_ns=5, target="../x"),
+        })
+        assert _round_trip(snapshot) == snapshot
+
+    def test_file(self, tree: Path, tmp_path: Path):
+        snapshot = komparu.snapshot_tree(str(tree))
+        komparu.write_snapshot(snapshot, str(tmp_path / "snap.kpz"))
+        assert komparu.read_snapshot(str(tmp_path / "snap.kpz")) == snapshot
+        assert (tmp_path / "snap.kpz").read_bytes().startswith(b"KPZ1")
+
+    def test_failed_write_keeps_old_file(self, tree: Path, tmp_path: Path, monkeypatch):
+        komparu.write_snapshot(komparu.snapshot_tree(str(tree)), str(tmp_path / "snap.kpz"))
+        before = (tmp_path / "snap.kpz").read_bytes()
+        (tree / "new").write_bytes(b"new")
+
+        def fail(fd: int) -> None:
+            raise OSError(28, "No space left on device")
+
+        monkeypatch.setattr(os, "fsync", fail)
+        with pytest.raises(OSError, match="No space"):
+            komparu.write_snapshot(komparu.snapshot_tree(str(tree)), str(tmp_path / "snap.kpz"))
+        assert (tmp_path / "snap.kpz").read_bytes() == before
+        assert sorted(os.listdir(tmp_path)) == ["snap.kpz", "tree"]
+
+    def test_not_a_snapshot(self, tmp_path: Path):
+        (tmp_path / "x").write_bytes(b"hello")
+        with pytest.raises(ValueError, match="not a komparu snapshot"):
+            komparu.read_snapshot(str(tmp_path / "x"))
+
+    def test_damaged(self, tree: Path):
+        buf = io.BytesIO()
+        komparu.write_snapshot(komparu.snapshot_tree(str(tree)), buf)
+        with pytest.raises(ValueError, match="snapshot"):
+            komparu.read_snapshot(io.BytesIO(buf.getvalue()[:-3]))
+
+
+class TestCompareSnapshot:
+    def test_unchanged(self, tree: Path):
+        snapshot = komparu.snapshot_tree(str(tree))
+        assert komparu.compare_snapshot(snapshot, str(tree)).equal
+
+    def test_drift(self, tree: Path):
+        snapshot = komparu.snapshot_tree(str(tree), follow_symlinks=False)
+        (tree / "a.txt").write_bytes(b"ALPHA")
+        (tree / "sub" / "b.txt").write_bytes(b"beta, longer")
+        (tree / "sub" / "b.txt").chmod(0o600)
+        (tree / "sub" / "deep" / "c.txt").unlink()
+        (tree / "sub" / "deep").rmdir()
+        (tree / "new.txt").write_bytes(b"new")
+        (tree / "link").unlink()
+        os.symlink("sub", tree / "link")
+        result = komparu.compare_snapshot(snapshot, str(tree), follow_symlinks=False)
+        assert result.diff == {"a.txt": DiffReason.CONTENT_MISMATCH,
+                               "sub/b.txt": DiffReason.SIZE_MISMATCH,
+                               "link": DiffReason.CONTENT_MISMATCH}
+        assert result.only_left == {"sub/deep"}
+        assert result.only_right == {"new.txt"}
+
+    def test_metadata(self, tree: Path):
+        snapshot = komparu.snapshot_tree(str(tree), follow_symlinks=False)
+        (tree / "a.txt").chmod(0o600)
+        result = komparu.compare_snapshot(snapshot, str(tree), follow_symlinks=False)
+        assert result.diff == {"a.txt": DiffReason.METADATA_MISMATCH}
+        assert komparu.compare_snapshot(snapshot, str(tree), attributes=("owner",),
+                                        follow_symlinks=False).equal
+
+    def test_two_snapshots(self, tree: Path):
+        before = komparu.snapshot_tree(str(tree))
+        (tree / "a.txt").unlink()
+        after = komparu.snapshot_tree(str(tree))
+        result = komparu.compare_snapshot(before, after, ignore=["link"])
+        assert result.only_left == {"a.txt"}
+        assert not result.diff
+
+    def test_different_algorithms(self, tree: Path):
+        with pytest.raises(ValueError, match="different hash algorithms"):
+            komparu.compare_snapshot(komparu.snapshot_tree(str(tree)),
+                                     komparu.snapshot_tree(str(tree), algorithm="md5"))
@@ -0,0 +1,108 @@
+"""Tests for comparing split volumes as one file."""
+
+from __future__ import annotations
+
+import os
+from pathlib import Path
+
+import pytest
+
+import komparu
+from komparu._cli import main
+
+DATA = bytes(range(256)) * 1000
+
+
+def _split(path: Path, data: bytes, size: int, width: int = 3) -> list[str]:
+    parts = []
+    for n, start in enumerate(range(0, len(data), size), 1):
+        part = Path(f"{path}.{n:0{width}d}")
+        part.write_bytes(data[start:start + size])
+        parts.append(str(part))
+    return parts
+
+
+@pytest.fixture
+def original(tmp_path: Path) -> Path:
+    path = tmp_path / "backup.tar"
+    path.write_bytes(DATA)
+    return path
+
+
+class TestSplitParts:
+    def test_numbered(self, tmp_path: Path):
+        parts = _split(tmp_path / "backup.tar", DATA, 100_000)
+        (tmp_path / "backup.tar.005").write_bytes(b"stray")
+        assert komparu.split_parts(parts[0]) == parts
+        assert komparu.split_parts(parts[1]) == parts[1:]
+        with pytest.raises(FileNotFoundError):
+            komparu.split_parts(str(tmp_path / "other.001"))
+
+    def test_list(self, tmp_path: Path):
+        parts = _split(tmp_path / "x", DATA, 64_000)
+        listing = tmp_path / "x.parts"
+        listing.write_text("# volumes\n" + "".join(f"{os.path.basename(p)}\n\n" for p in parts))
+        assert komparu.split_parts(str(listing)) == parts
+        listing.write_text("# nothing\n")
+        with pytest.raises(ValueError, match="lists no split parts"):
+            komparu.split_parts(str(listing))
+
+
+class TestCompareParts:
+    def test_equal(self, original, tmp_path: Path):
+        parts = _split(tmp_path / "a", DATA, 100_000)
+        assert komparu.compare(parts, str(original)) is True
+        assert komparu.compare(str(original), parts, chunk_size=4096) is True
+        # Two splits of the same file at different boundaries
+        assert komparu.compare(parts, _split(tmp_path / "b", DATA, 7_777)) is True
+
+    def test_different(self, original, tmp_path: Path):
+        changed = bytearray(DATA)
+        changed[200_123] ^= 1
+        parts = _split(tmp_path / "a", bytes(changed), 100_000)
+        assert komparu.compare(parts, str(original)) is False
+        assert komparu.compare(parts, str(original), quick_check=False) is False
+        assert komparu.compare(parts[:-1], str(original)) is False
+        changed[-1] ^= 1
+        changed[200_123] ^= 1
+        tail = _split(tmp_path / "b", bytes(changed), 100_000)
+        assert komparu.compare(tail, str(original), size_precheck=False) is False
+
+    def test_empty_and_sparse_parts(self, original, tmp_path: Path):
+        empty = tmp_path / "empty"
+        empty.write_bytes(b"")
+        parts = _split(tmp_path / "a", DATA, 100_000)
+        assert komparu.compare([parts[0], str(empty), *parts[1:]], str(original)) is True
+        hole = tmp_path / "hole"
+        with open(hole, "wb") as f:
+            f.truncate(1024 * 1024)
+        zeros = tmp_path / "zeros"
+        zeros.write_bytes(b"x" + bytes(2 * 1024 * 1024) + b"y")
+        x, y = tmp_path / "x", tmp_path / "y"
+        x.write_bytes(b"x")
+        y.write_bytes(b"y")
+        assert komparu.compare([str(x), str(hole), str(hole), str(y)], str(zeros),
+                               quick_check=False) is True
+
+    def test_errors(self, original, tmp_path: Path):
+        with pytest.raises(FileNotFoundError):
+            komparu.compare([str(original), str(tmp_path / "missing")], str(original))
+        with pytest.raises(ValueError, match="local files"):
+            komparu.compare([str(original)], "https://example.com/backup.tar")
+        with pytest.raises(ValueError, match="no parts"):
+            komparu.compare([], str(original))
+
+
+class TestCli:
+    def test_compare(self, original, tmp_path: Path, capsys):
+        parts = _split(tmp_path / "a", DATA, 100_000)
+        assert main(["compare", "--split", parts[0], str(original)]) == 0
+        Path(parts[-1]).write_bytes(b"truncated")
+        assert main(["compare", "--split", parts[0], str(original)]) == 1
+        assert capsys.readouterr().out == f"{parts[0]} {original} differ\n"
+
+    def test_refused(self, original, tmp_path: Path, capsys):
+        parts = _split(tmp_path / "a", DATA, 100_000)
+        assert main(["compare", "--split", "--patch", str(tmp_path / "p"), parts[0],
+                     str(original)]) == 2
+        assert "--split cannot be combined with --patch" in capsys.readouterr().err
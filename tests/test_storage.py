@@ -0,0 +1,114 @@
+"""Tests for detect_storage and tune_for_storage."""
+
+from __future__ import annotations
+
+import os
+import sys
+from pathlib import Path
+from types import SimpleNamespace
+
+import pytest
+
+import komparu._cli as cli
+import komparu._storage as storage
+from komparu import CompareOptions, StorageKind, detect_storage, tune_for_storage
+from komparu._cli import main
+
+linux = pytest.mark.skipif(not sys.platform.startswith("linux"), reason="sysfs")
+
+
+@pytest.fixture
+def fake_system(tmp_path: Path, monkeypatch):
+    """A sysfs with a disk, its partition, an SSD, an LVM volume on the disk and the mounts."""
+    devices = tmp_path / "devices"
+    for disk, rotational in (("sda", "1"), ("nvme0n1", "0"), ("dm-0", "0"), ("dm-1", "0")):
+        (devices / disk / "queue").mkdir(parents=True)
+        (devices / disk / "queue" / "rotational").write_text(rotational + "\n")
+    (devices / "sda" / "sda1").mkdir()
+    (devices / "dm-0" / "slaves").mkdir()
+    (devices / "dm-0" / "slaves" / "sda1").symlink_to(devices / "sda" / "sda1")
+    (devices / "dm-1" / "slaves").mkdir()
+    (devices / "dm-1" / "slaves" / "nvme0n1").symlink_to(devices / "nvme0n1")
+    block = tmp_path / "block"
+    block.mkdir()
+    for number, target in (("8:0", "sda"), ("8:1", "sda/sda1"), ("259:0", "nvme0n1"),
+                           ("253:0", "dm-0"), ("253:1", "dm-1")):
+        (block / number).symlink_to(devices / target)
+    mountinfo = tmp_path / "mountinfo"
+    mountinfo.write_text(
+        "28 1 8:1 / / rw,relatime - ext4 /dev/sda1 rw\n"
+        "29 28 259:0 / /fast rw - xfs /dev/nvme0n1 rw\n"
+        "30 28 0:51 / /mnt/share rw - nfs4 server:/export rw\n"
+        "31 28 0:26 / /run rw - tmpfs tmpfs rw\n"
+        "32 28 0:60 / /data rw - btrfs /dev/nvme0n1 rw\n"
+        "33 28 0:61 / /merged rw - overlay overlay rw\n"
+    )
+    monkeypatch.setattr(storage, "_SYSFS_BLOCK", str(block))
+    monkeypatch.setattr(storage, "_MOUNTINFO", str(mountinfo))
+    real_stat = os.stat
+    monkeypatch.setattr(os, "stat", lambda path, *args, **kwargs: (
+        SimpleNamespace(st_rdev=os.makedev(259, 0)) if path == "/dev/nvme0n1"
+        else real_stat(path, *args, **kwargs)))
+    storage._linux_kind.cache_clear()
+    yield
+    storage._linux_kind.cache_clear()
+
+
+@linux
+class TestLinux:
+    def test_block_devices(self, fake_system):
+        assert storage._linux_kind(os.makedev(8, 1)) is StorageKind.HDD
+        assert storage._linux_kind(os.makedev(259, 0)) is StorageKind.SSD
+        assert storage._linux_kind(os.makedev(8, 9)) is StorageKind.UNKNOWN
+
+    def test_stacked_devices(self, fake_system):
+        # dm devices claim not to spin; the disks under them tell
+        assert storage._linux_kind(os.makedev(253, 0)) is StorageKind.HDD
+        assert storage._linux_kind(os.makedev(253, 1)) is StorageKind.SSD
+
+    def test_mounts(self, fake_system):
+        assert storage._linux_kind(os.makedev(0, 51)) is StorageKind.NETWORK
+        assert storage._linux_kind(os.makedev(0, 26)) is StorageKind.SSD
+        assert storage._linux_kind(os.makedev(0, 60)) is StorageKind.SSD
+        assert storage._linux_kind(os.makedev(0, 61)) is StorageKind.UNKNOWN
+
+    def test_real_path(self, tmp_path):
+        assert detect_storage(str(tmp_path)) in StorageKind
+        assert detect_storage(tmp_path / "missing") is StorageKind.UNKNOWN
+        assert detect_storage("https://example.com/file") is StorageKind.UNKNOWN
+
+
+@pytest.fixture
+def kinds(monkeypatch):
+    """Paths named after the storage they are on."""
+    kinds = {"hdd": StorageKind.HDD, "nfs": StorageKind.NETWORK, "ssd": StorageKind.SSD}
+    monkeypatch.setattr(storage, "detect_storage",
+                        lambda path: kinds.get(path, StorageKind.UNKNOWN))
+
+
+class TestTune:
+    def test_picks_settings(self, kinds):
+        options = CompareOptions(ignore=("*.log",))
+        assert tune_for_storage(options, "ssd", "ssd") == options
+        assert tune_for_storage(options, "ssd", "elsewhere") == options
+        assert tune_for_storage(options, "ssd", "hdd") == options.replace(
+            max_workers=1, chunk_size=1 << 20)
+        assert tune_for_storage(options, "nfs", "ssd") == options.replace(
+            max_workers=16, chunk_size=1 << 20)
+        assert tune_for_storage(options, "nfs", "hdd").max_workers == 1
+
+    def test_keeps_explicit_settings(self, kinds):
+        assert tune_for_storage(CompareOptions(max_workers=4), "hdd").max_workers == 4
+        tuned = tune_for_storage(CompareOptions(chunk_size=4096), "hdd")
+        assert (tuned.max_workers, tuned.chunk_size) == (1, 4096)
+
+
+class TestCli:
+    def test_compare_tunes_auto_workers(self, make_files, tmp_path, monkeypatch):
+        make_files({"a/f": b"1", "b/f": b"1"})
+        seen = []
+        monkeypatch.setattr(cli, "tune_for_storage",
+                            lambda options, *paths: seen.append((options, paths)) or options)
+        a, b = str(tmp_path / "a"), str(tmp_path / "b")
+        assert main(["compare", a, b]) == 0
+        assert seen[0][1] == (a, b) and seen[0][0].max_workers == 0
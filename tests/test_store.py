@@ -0,0 +1,110 @@
+"""Tests for comparing a local tree with an object store, against an in-memory file system."""
+
+from __future__ import annotations
+
+import base64
+import hashlib
+import sys
+from typing import Any
+
+import pytest
+
+import komparu
+from komparu import DiffReason
+from komparu import _store
+from komparu._cli import main
+
+
+class FakeStore:
+    """The find and cat_file calls compare_dir_store makes, answered as fsspec does."""
+
+    def __init__(self, md5: str | None = None) -> None:
+        self.objects: dict[str, bytes] = {}
+        self.md5 = md5  # "gcs" or "azure": where the listing carries an MD5
+        self.reads: list[tuple[str, int, int]] = []
+        self.error: OSError | None = None
+
+    def find(self, path: str, withdirs: bool = False, detail: bool = False
+             ) -> dict[str, dict[str, Any]]:
+        assert detail and not withdirs
+        if self.error is not None:
+            raise self.error
+        found = {}
+        for key, data in self.objects.items():
+            if key.startswith(path + "/"):
+                info: dict[str, Any] = {"name": key, "size": len(data), "type": "file"}
+                digest = hashlib.md5(data).digest()
+                if self.md5 == "gcs":
+                    info["md5Hash"] = base64.b64encode(digest).decode()
+                elif self.md5 == "azure":
+                    info["content_settings"] = {"content_md5": bytearray(digest)}
+                found[key] = info
+        return found
+
+    def cat_file(self, path: str, start: int, end: int) -> bytes:
+        self.reads.append((path, start, end))
+        return self.objects[path][start:end]
+
+
+class TestCompareDirStore:
+    def test_ranged_reads(self, make_dir):
+        store = FakeStore()
+        local = str(make_dir("local", {"same": b"x" * 10, "changed": b"old",
+                                       "sub/local-only": b""}))
+        store.objects = {"bucket/site/same": b"x" * 10, "bucket/site/changed": b"new",
+                         "bucket/site/remote-only": b"", "bucket/other/same": b"y"}
+        result = komparu.compare_dir_store(local, "gs://bucket/site/", fs=store, chunk_size=4)
+        assert result.diff == {"changed": DiffReason.CONTENT_MISMATCH}
+        assert result.only_left == {"sub/local-only"}
+        assert result.only_right == {"remote-only"}
+        assert ("bucket/site/same", 8, 10) in store.reads
+
+    def test_listed_md5(self, make_dir):
+        for kind in ("gcs", "azure"):
+            store = FakeStore(md5=kind)
+            local = str(make_dir(f"{kind}/local", {"a": b"1", "b": b"2"}))
+            store.objects = {"c/a": b"1", "c/b": b"3"}
+            result = komparu.compare_dir_store(local, "az://c", fs=store)
+            assert result.diff == {"b": DiffReason.CONTENT_MISMATCH}, kind
+            assert store.reads == [], kind
+
+    def test_size_settles(self, make_dir):
+        store = FakeStore()
+        store.objects = {"c/a": b"12"}
+        local = str(make_dir("local", {"a": b"1"}))
+        result = komparu.compare_dir_store(local, "gs://c", fs=store)
+        assert result.diff == {"a": DiffReason.SIZE_MISMATCH}
+        assert store.reads == []
+
+    def test_ignore(self, make_dir):
+        store = FakeStore()
+        store.objects = {"c/cache/x": b"", "c/a": b"1"}
+        local = str(make_dir("local", {"a": b"1", "cache/y": b""}))
+        assert komparu.compare_dir_store(local, "gs://c", fs=store, ignore=["cache"]).equal
+
+    def test_errors(self, make_dir):
+        local = str(make_dir("local", {"a": b"1"}))
+        store = FakeStore()
+        for error, expected in ((FileNotFoundError("no bucket"), komparu.SourceNotFoundError),
+                                (PermissionError("denied"), komparu.SourcePermissionError),
+                                (OSError("throttled"), komparu.SourceReadError)):
+            store.error = error
+            with pytest.raises(expected):
+                komparu.compare_dir_store(local, "gs://c", fs=store)
+
+    def test_needs_fsspec(self, monkeypatch, make_dir):
+        monkeypatch.setitem(sys.modules, "fsspec", None)
+        with pytest.raises(ImportError, match="komparu\\[cloud\\]"):
+            komparu.compare_dir_store(str(make_dir("local", {})), "gs://c")
+
+
+class TestCli:
+    def test_compare(self, monkeypatch, capsys, make_dir):
+        store = FakeStore()
+        store.objects = {"bucket/site/a": b"1", "bucket/site/b": b"3"}
+        monkeypatch.setattr(_store, "_filesystem",
+                            lambda url, options: (store, url.partition("://")[2]))
+        local = str(make_dir("local", {"a": b"1", "b": b"2"}))
+        assert main(["compare", local, "gs://bucket/site"]) == 1
+        assert capsys.readouterr().out.splitlines() == ["differ: b (content_mismatch)"]
+        assert main(["compare", "az://bucket/site", local, "-q"]) == 1
@@ -0,0 +1,190 @@
+"""Tests for sync_plan, apply_sync_plan, sync_dir and komparu sync."""
+
+from __future__ import annotations
+
+import os
+import shutil
+import sys
+from pathlib import Path
+
+import pytest
+
+from komparu import (
+    CompareOptions,
+    SyncAction,
+    SyncPlan,
+    SyncStep,
+    TextComparator,
+    apply_sync_plan,
+    sync_dir,
+    sync_plan,
+)
+from komparu._cli import main
+
+
+@pytest.fixture
+def tree_files() -> dict[str, bytes]:
+    return {
+        "a/same.txt": b"same",
+        "b/same.txt": b"same",
+        "a/changed.txt": b"new",
+        "b/changed.txt": b"old!",
+        "a/new/deep/f.txt": b"f",
+        "b/gone/x.txt": b"x",
+        "a/swap": b"now a file",
+        "b/swap/inner.txt": b"was a directory",
+        "a/run.sh": b"#!/bin/sh\n",
+        "b/run.sh": b"#!/bin/sh\n",
+    }
+
+
+@pytest.fixture
+def trees(trees) -> tuple[Path, Path]:
+    a, b = trees
+    os.chmod(a / "run.sh", 0o755)
+    os.chmod(b / "run.sh", 0o644)
+    os.chmod(a / "changed.txt", 0o600)
+    return trees
+
+
+def _apply(plan: SyncPlan, a: Path, b: Path) -> None:
+    for step in plan.steps:
+        source, target = a / step.path, b / step.path
+        if step.action is SyncAction.DELETE:
+            if target.is_dir() and not target.is_symlink():
+                shutil.rmtree(target)
+            else:
+                target.unlink()
+        elif step.action is SyncAction.MKDIR:
+            target.mkdir(mode=step.mode)
+        elif step.action is SyncAction.COPY:
+            shutil.copy2(source, target, follow_symlinks=False)
+        else:
+            os.chmod(target, step.mode)
+
+
+class TestSyncPlan:
+    def test_steps(self, trees):
+        plan = sync_plan(*trees)
+        assert plan.steps == [
+            SyncStep(SyncAction.DELETE, "gone"),
+            SyncStep(SyncAction.DELETE, "swap"),
+            SyncStep(SyncAction.COPY, "changed.txt", 0o600, 3),
+            SyncStep(SyncAction.MKDIR, "new", 0o755),
+            SyncStep(SyncAction.MKDIR, "new/deep", 0o755),
+            SyncStep(SyncAction.COPY, "new/deep/f.txt", 0o644, 1),
+            SyncStep(SyncAction.COPY, "swap", 0o644, 10),
+            SyncStep(SyncAction.CHMOD, "run.sh", 0o755),
+        ]
+        assert plan.bytes_to_copy == 14
+        assert plan.errors == set()
+
+    def test_applying_it_makes_the_trees_match(self, trees):
+        _apply(sync_plan(*trees), *trees)
+        assert sync_plan(*trees) == SyncPlan([])
+
+    def test_ignore(self, trees):
+        plan = sync_plan(*trees, ignore=["new", "gone"])
+        assert {step.path for step in plan.steps} == {"swap", "changed.txt", "run.sh"}
+        plan = sync_plan(*trees, options=CompareOptions(ignore=("swap",)))
+        assert "swap" not in {step.path for step in plan.steps}
+
+    def test_comparators(self, trees):
+        a, b = trees
+        (a / "text.txt").write_bytes(b"line\r\n")
+        (b / "text.txt").write_bytes(b"line\n")
+        plan = sync_plan(a, b, comparators={"*.txt": TextComparator()})
+        assert "text.txt" not in {step.path for step in plan.steps}
+
+    @pytest.mark.skipif(sys.platform == "win32", reason="symlinks")
+    def test_symlinks(self, trees):
+        a, b = trees
+        (a / "link").symlink_to("same.txt")
+        followed = sync_plan(a, b)
+        assert SyncStep(SyncAction.COPY, "link", 0o644, 4) in followed.steps
+        copied = sync_plan(a, b, follow_symlinks=False)
+        assert SyncStep(SyncAction.COPY, "link") in copied.steps
+        _apply(copied, a, b)
+        assert os.readlink(b / "link") == "same.txt"
+
+    @pytest.mark.skipif(sys.platform == "win32" or os.geteuid() == 0,
+                        reason="needs permissions to deny")
+    def test_unreadable(self, trees):
+        a, b = trees
+        (b / "locked").mkdir()
+        (a / "locked").mkdir()
+        (a / "locked" / "f").write_bytes(b"f")
+        os.chmod(b / "locked", 0)
+        try:
+            plan = sync_plan(a, b)
+        finally:
+            os.chmod(b / "locked", 0o755)
+        assert "locked" in plan.errors
+        assert not any(step.path.startswith("locked") for step in plan.steps)
+
+
+class TestApply:
+    def test_apply_plan(self, trees):
+        a, b = trees
+        apply_sync_plan(SyncPlan.from_dict(sync_plan(a, b).to_dict()), str(a), str(b))
+        assert sync_plan(a, b) == SyncPlan([])
+        assert (b / "run.sh").stat().st_mode & 0o777 == 0o755
+        assert (b / "changed.txt").stat().st_mtime_ns == (a / "changed.txt").stat().st_mtime_ns
+        assert not list(b.rglob("*.komparu-sync"))
+
+    def test_read_only_directory(self, trees):
+        a, b = trees
+        os.chmod(a / "new" / "deep", 0o555)
+        try:
+            sync_dir(a, b)
+            assert (b / "new" / "deep" / "f.txt").read_bytes() == b"f"
+            assert (b / "new" / "deep").stat().st_mode & 0o777 == 0o555
+        finally:
+            os.chmod(a / "new" / "deep", 0o755)
+            os.chmod(b / "new" / "deep", 0o755)
+
+    def test_keeps_extras_without_delete(self, trees):
+        a, b = trees
+        plan = sync_dir(a, b)
+        # A directory where the left has a file is replaced either way
+        assert SyncStep(SyncAction.DELETE, "swap") in plan.steps
+        assert SyncStep(SyncAction.DELETE, "gone") not in plan.steps
+        assert (b / "gone" / "x.txt").exists() and (b / "swap").read_bytes() == b"now a file"
+        assert sync_dir(a, b, delete=True).steps == [SyncStep(SyncAction.DELETE, "gone")]
+        assert sync_plan(a, b) == SyncPlan([])
+
+    def test_update(self, trees):
+        a, b = trees
+        os.utime(b / "changed.txt", ns=(0, (a / "changed.txt").stat().st_mtime_ns + 10**9))
+        plan = sync_dir(a, b, update=True)
+        assert "changed.txt" not in {step.path for step in plan.steps}
+        assert (b / "changed.txt").read_bytes() == b"old!"
+
+    def test_dry_run(self, trees):
+        a, b = trees
+        assert sync_dir(a, b, dry_run=True, delete=True) == sync_plan(a, b)
+        assert (b / "gone").exists() and not (b / "new").exists()
+
+
+class TestCli:
+    def test_dry_run_by_default(self, trees, capsys):
+        a, b = trees
+        assert main(["sync", "--delete", str(a), str(b)]) == 1
+        captured = capsys.readouterr()
+        assert captured.out.splitlines()[:2] == ["would delete gone", "would delete swap"]
+        assert "8 step(s), 14 bytes to copy; nothing changed without --apply" in captured.err
+        assert (b / "gone").exists()
+
+    def test_apply(self, trees, capsys):
+        a, b = trees
+        assert main(["sync", "--apply", "--ignore", "new", str(a), str(b)]) == 0
+        assert capsys.readouterr().out.splitlines() == [
+            "delete swap", "copy changed.txt 0600 (3 bytes)", "copy swap 0644 (10 bytes)",
+            "chmod run.sh 0755",
+        ]
+        assert main(["sync", "--ignore", "new", "--ignore", "gone", str(a), str(b)]) == 0
+
+    def test_needs_directories(self, trees, capsys):
+        a, b = trees
+        assert main(["sync", str(a / "swap"), str(b)]) == 2
+        assert "sync needs two directories" in capsys.readouterr().err
@@ -0,0 +1,128 @@
+"""Tests for the compare --tui browser model."""
+
+from __future__ import annotations
+
+import json
+from pathlib import Path
+
+import pytest
+
+import komparu._cli as cli
+from komparu import DiffReason, DirResult, Severity, compare_dir
+from komparu._cli import main
+from komparu._tui import DiffBrowser
+
+
+@pytest.fixture
+def result() -> DirResult:
+    return DirResult(
+        equal=False,
+        diff={"src/a.py": DiffReason.CONTENT_MISMATCH, "src/lib/b.py": DiffReason.SIZE_MISMATCH,
+              "top.txt": DiffReason.TYPE_MISMATCH},
+        only_left={"src/old.py"},
+        only_right={"docs/new.md"},
+        errors={"locked"},
+    )
+
+
+def _tree(browser: DiffBrowser) -> list[str]:
+    return [f"{row.path}/ {row.count}" if row.is_dir else f"{row.path} {row.status}"
+            for row in browser.rows()]
+
+
+class TestDiffBrowser:
+    def test_rows(self, result):
+        assert _tree(DiffBrowser(result)) == [
+            "docs/ 1", "docs/new.md only_right", "locked error",
+            "src/ 3", "src/a.py diff", "src/lib/ 1", "src/lib/b.py diff", "src/old.py only_left",
+            "top.txt diff",
+        ]
+
+    def test_fold(self, result):
+        browser = DiffBrowser(result)
+        browser.fold("src")
+        assert _tree(browser) == ["docs/ 1", "docs/new.md only_right", "locked error",
+                                  "src/ 3", "top.txt diff"]
+        browser.fold("src")
+        assert len(browser.rows()) == 9
+
+    def test_filters(self, result):
+        browser = DiffBrowser(result)
+        browser.toggle_status("diff")
+        browser.toggle_status("error")
+        assert _tree(browser) == ["docs/ 1", "docs/new.md only_right",
+                                  "src/ 1", "src/old.py only_left"]
+        browser.toggle_status("only_left")
+        browser.toggle_status("only_right")
+        assert len(browser.rows()) == 9  # hiding everything shows everything
+        browser.pattern = "LIB"
+        assert _tree(browser) == ["src/ 1", "src/lib/ 1", "src/lib/b.py diff"]
+
+    def test_mark_directory(self, result):
+        browser = DiffBrowser(result)
+        src = next(row for row in browser.rows() if row.path == "src")
+        browser.mark(src)
+        assert browser.marked == {"src/a.py", "src/lib/b.py", "src/old.py"}
+        browser.mark(src)
+        assert browser.marked == set()
+
+    def test_export(self, result, tmp_path):
+        browser = DiffBrowser(result, sides=("tree", "manifest"),
+                              severities={"src/a.py": Severity.WARN})
+        paths = ["src/old.py", "src/a.py", "docs/new.md", "locked"]
+        assert browser.export(str(tmp_path / "out.txt"), paths) == 4
+        assert (tmp_path / "out.txt").read_text().splitlines() == [
+            "only in manifest: docs/new.md", "error: locked",
+            "differ: src/a.py (content_mismatch) [warn]", "only in tree: src/old.py",
+        ]
+        browser.export(str(tmp_path / "out.json"), ["src/a.py", "src/old.py"])
+        assert json.loads((tmp_path / "out.json").read_text()) == [
+            {"path": "src/a.py", "status": "diff", "reason": "content_mismatch"},
+            {"path": "src/old.py", "status": "only_left", "reason": None},
+        ]
+
+    def test_details(self, make_files, tmp_path):
+        make_files({"a/f.bin": bytes(40) + b"left", "b/f.bin": bytes(40) + b"RIGHT",
+                    "a/gone": b"x"})
+        a, b = str(tmp_path / "a"), str(tmp_path / "b")
+        browser = DiffBrowser(compare_dir(a, b), (a, b))
+        lines = browser.details("f.bin")
+        assert lines[0] == "differ: f.bin (size_mismatch)"
+        assert lines[2].startswith("left: 44 bytes, mode -rw")
+        assert "first difference at byte 40 (differing bytes in upper case)" in lines
+        assert any(line.startswith("00000020  00 00 00 00 00 00 00 00 6C 65 66 74")
+                   for line in lines)
+        assert browser.details("gone")[3].startswith("right: ")
+        assert DiffBrowser(compare_dir(a, b)).details("f.bin") == [
+            "differ: f.bin (size_mismatch)", ""]
+
+
+class TestCli:
+    def test_needs_terminal(self, make_files, tmp_path, capsys):
+        make_files({"a/f": b"1", "b/f": b"2"})
+        assert main(["compare", "--tui", str(tmp_path / "a"), str(tmp_path / "b")]) == 2
+        assert "--tui needs a terminal" in capsys.readouterr().err
+
+    def test_runs_browser(self, make_files, tmp_path, monkeypatch, capsys):
+        make_files({"a/f": b"1", "b/f": b"2"})
+        seen = []
+        monkeypatch.setattr(cli, "run_tui", lambda browser, title: seen.append((browser, title)))
+        a, b = str(tmp_path / "a"), str(tmp_path / "b")
+        assert main(["compare", "--tui", a, b]) == 1
+        assert capsys.readouterr().out == ""
+        browser, title = seen[0]
+        assert browser.roots == (a, b) and title == f"komparu: {a} vs {b}"
+        assert browser.entries == {"f": ("diff", DiffReason.CONTENT_MISMATCH)}
+
+    def test_rollups(self, make_files, tmp_path, monkeypatch):
+        make_files({"a/d/f": b"1", "b/d/f": b"22", "a/d/same": b"s", "b/d/same": b"s"})
+        seen = []
+        monkeypatch.setattr(cli, "run_tui", lambda browser, title: seen.append(browser))
+        assert main(["compare", "--tui", str(tmp_path / "a"), str(tmp_path / "b")]) == 1
+        assert seen[0].rollup("d") == "1 differ, 1 equal; 2 bytes not equal"
+        assert seen[0].rollup("f") == ""
+
+    def test_files(self, make_files, tmp_path, capsys):
+        make_files({"a": b"1", "b": b"2"})
+        assert main(["compare", "--tui", str(tmp_path / "a"), str(tmp_path / "b")]) == 2
+        assert "--tui needs directories or archives" in capsys.readouterr().err
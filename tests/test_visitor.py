@@ -0,0 +1,208 @@
+"""Tests for CompareVisitor callbacks during directory comparison."""
+
+from __future__ import annotations
+
+import pytest
+
+import komparu
+import komparu.aio
+from komparu import CompareVisitor, DiffEntry, DiffReason, EntryKind, Progress
+
+
+class Recorder(CompareVisitor):
+    def __init__(self) -> None:
+        self.events: list[tuple[str, str]] = []
+        self.finished: dict[str, DiffEntry] = {}
+
+    def on_dir_entered(self, path: str) -> None:
+        self.events.append(("enter", path))
+
+    def on_dir_left(self, path: str) -> None:
+        self.events.append(("leave", path))
+
+    def on_entry_started(self, path: str) -> None:
+        self.events.append(("start", path))
+
+    def on_entry_finished(self, entry: DiffEntry) -> None:
+        self.events.append(("finish", entry.path))
+        self.finished[entry.path] = entry
+
+    def on_error(self, entry: DiffEntry) -> None:
+        self.events.append(("error", entry.path))
+
+
+@pytest.fixture
+def tree_files() -> dict[str, bytes]:
+    return {
+        "a/same.txt": b"identical",
+        "a/sub/diff.txt": b"version A",
+        "a/sub/deep/only_a.txt": b"exclusive",
+        "b/same.txt": b"identical",
+        "b/sub/diff.txt": b"version B",
+        "b/only_b.txt": b"exclusive",
+    }
+
+
+class TestVisitorCallbacks:
+    """The engine drives the visitor for every entry."""
+
+    def test_result_matches_plain_compare_dir(self, trees):
+        a, b = trees
+        visited = komparu.compare_dir(str(a), str(b), visitor=Recorder())
+        plain = komparu.compare_dir(str(a), str(b))
+        assert visited == plain
+
+    def test_every_entry_finished(self, trees):
+        a, b = trees
+        rec = Recorder()
+        komparu.compare_dir(str(a), str(b), visitor=rec)
+        assert rec.finished == {
+            "same.txt": DiffEntry("same.txt", EntryKind.EQUAL, None),
+            "sub/diff.txt": DiffEntry(
+                "sub/diff.txt", EntryKind.DIFF, DiffReason.CONTENT_MISMATCH),
+            "sub/deep/only_a.txt": DiffEntry(
+                "sub/deep/only_a.txt", EntryKind.ONLY_LEFT, DiffReason.MISSING),
+            "only_b.txt": DiffEntry(
+                "only_b.txt", EntryKind.ONLY_RIGHT, DiffReason.MISSING),
+        }
+
+    def test_started_precedes_finished(self, trees):
+        a, b = trees
+        rec = Recorder()
+        komparu.compare_dir(str(a), str(b), visitor=rec, max_workers=1)
+        for path in ("same.txt", "sub/diff.txt"):
+            assert rec.events.index(("start", path)) < rec.events.index(("finish", path))
+        # One-sided entries are never compared, so never "started"
+        assert ("start", "only_b.txt") not in rec.events
+
+    def test_dir_events_balanced_and_nested(self, trees):
+        a, b = trees
+        rec = Recorder()
+        komparu.compare_dir(str(a), str(b), visitor=rec)
+        dirs = [e for e in rec.events if e[0] in ("enter", "leave")]
+        assert dirs == [
+            ("enter", "sub"),
+            ("enter", "sub/deep"),
+            ("leave", "sub/deep"),
+            ("leave", "sub"),
+        ]
+        enter = rec.events.index(("enter", "sub/deep"))
+        leave = rec.events.index(("leave", "sub/deep"))
+        assert enter < rec.events.index(("finish", "sub/deep/only_a.txt")) < leave
+
+    def test_ignore_suppresses_callbacks(self, trees):
+        a, b = trees
+        rec = Recorder()
+        result = komparu.compare_dir(str(a), str(b), visitor=rec, ignore=["sub"])
+        assert all("sub" not in path.split("/") for _, path in rec.events)
+        assert result.diff == {}
+        assert result.only_right == {"only_b.txt"}
+
+    def test_default_methods_are_noops(self, trees):
+        a, b = trees
+        result = komparu.compare_dir(str(a), str(b), visitor=CompareVisitor())
+        assert result.equal is False
+
+    def test_visitor_exception_propagates(self, trees):
+        class Boom(CompareVisitor):
+            def on_entry_finished(self, entry: DiffEntry) -> None:
+                raise RuntimeError("stop")
+
+        a, b = trees
+        with pytest.raises(RuntimeError, match="stop"):
+            komparu.compare_dir(str(a), str(b), visitor=Boom())
+
+    def test_with_options(self, trees):
+        a, b = trees
+        rec = Recorder()
+        opts = komparu.CompareOptions(ignore=("*.txt",))
+        result = komparu.compare_dir(str(a), str(b), options=opts, visitor=rec)
+        assert result.equal is True
+        assert rec.finished == {}
+
+
+class TestProgress:
+    """progress= receives typed updates without a visitor subclass."""
+
+    def test_counts(self, trees):
+        a, b = trees
+        updates: list[Progress] = []
+        komparu.compare_dir(str(a), str(b), progress=updates.append)
+        last = updates[-1]
+        assert last.files_done == 4
+        # same.txt + sub/diff.txt, left side
+        assert last.bytes_done == len(b"identical") + len(b"version A")
+
+    def test_monotonic(self, trees):
+        a, b = trees
+        updates: list[Progress] = []
+        komparu.compare_dir(str(a), str(b), progress=updates.append, max_workers=1)
+        done = [u.files_done for u in updates]
+        assert done == sorted(done)
+        assert {u.path for u in updates} >= {"same.txt", "only_b.txt"}
+
+    def test_size_mismatch_reads_nothing(self, make_dir):
+        a = make_dir("a", {"f.bin": b"short"})
+        b = make_dir("b", {"f.bin": b"much longer"})
+        updates: list[Progress] = []
+        komparu.compare_dir(str(a), str(b), progress=updates.append)
+        assert updates[-1] == Progress(files_done=1, bytes_done=0, path="f.bin")
+
+    def test_with_visitor(self, trees):
+        a, b = trees
+        rec = Recorder()
+        updates: list[Progress] = []
+        komparu.compare_dir(str(a), str(b), visitor=rec, progress=updates.append)
+        assert len(rec.finished) == 4
+        assert updates[-1].files_done == 4
+
+    @pytest.mark.asyncio
+    async def test_async(self, trees):
+        a, b = trees
+        updates: list[Progress] = []
+        await komparu.aio.compare_dir(str(a), str(b), progress=updates.append)
+        assert updates[-1].files_done == 4
+
+    def test_prescan_totals(self, trees):
+        a, b = trees
+        updates: list[Progress] = []
+        komparu.compare_dir(str(a), str(b), progress=updates.append, prescan=True)
+        last = updates[-1]
+        assert {(u.files_total, u.bytes_total) for u in updates} == {
+            (last.files_done, last.bytes_done)}
+        assert last.eta == 0.0
+        assert all(u.elapsed >= 0 for u in updates)
+        updates.clear()
+        komparu.compare_dir(str(a), str(b), progress=updates.append)
+        assert updates[-1].files_total is None and updates[-1].eta is None
+
+    def test_eta(self):
+        assert Progress(1, 25, "x", 4, 100, elapsed=2.0).eta == 6.0
+        assert Progress(1, 0, "x", 4, 0, elapsed=2.0).eta == 6.0
+        assert Progress(0, 0, "x", 4, 100, elapsed=2.0).eta is None
+
+    def test_prescan_needs_progress(self, trees):
+        a, b = trees
+        with pytest.raises(ValueError, match="prescan"):
+            komparu.compare_dir(str(a), str(b), prescan=True)
+
+    @pytest.mark.asyncio
+    async def test_async_prescan(self, trees):
+        a, b = trees
+        updates: list[Progress] = []
+        await komparu.aio.compare_dir(str(a), str(b), progress=updates.append, prescan=True)
+        assert updates[0].files_total == updates[-1].files_done == 4
+
+
+class TestAsyncVisitor:
+    """komparu.aio.compare_dir drives the visitor on the event loop."""
+
+    @pytest.mark.asyncio
+    async def test_async_visitor(self, trees):
+        a, b = trees
+        rec = Recorder()
+        result = await komparu.aio.compare_dir(str(a), str(b), visitor=rec)
+        assert result.diff == {"sub/diff.txt": DiffReason.CONTENT_MISMATCH}
+        assert set(rec.finished) == {
+            "same.txt", "sub/diff.txt", "sub/deep/only_a.txt", "only_b.txt",
+        }
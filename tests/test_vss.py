@@ -0,0 +1,121 @@
+"""Tests for comparing Volume Shadow Copies instead of live paths."""
+
+from __future__ import annotations
+
+import re
+import shutil
+from pathlib import Path
+from types import SimpleNamespace
+
+import pytest
+
+import komparu._vss as vss
+from komparu import (
+    SourcePermissionError,
+    SourceReadError,
+    UnsupportedSourceError,
+    shadow_copy,
+)
+from komparu._cli import main
+
+
+class FakeVss:
+    """Shadow copies as copies of volume directories under *root*."""
+
+    def __init__(self, root: Path) -> None:
+        self.root = root
+        self.created: list[str] = []
+        self.deleted: list[str] = []
+        self.create_code = self.delete_code = 0
+        self.freeze = lambda shadow: None  # what changes between the copy and the live files
+
+    def volume_root(self, path: str) -> str:
+        rel = Path(path).relative_to(self.root)
+        return f"{self.root / rel.parts[0]}/"
+
+    def powershell(self, script: str) -> tuple[int, str]:
+        if script.startswith("$r"):
+            if self.create_code:
+                return self.create_code, ""
+            volume = re.search(r"Volume='(.*?)'", script).group(1)
+            self.created.append(volume)
+            shadow = self.root / f"shadow{len(self.created)}"
+            shutil.copytree(volume, shadow)
+            self.freeze(shadow)
+            return 0, f"{{ID{len(self.created)}}}\n{shadow}\n"
+        self.deleted.append(re.search(r"ID='(.*?)'", script).group(1))
+        return self.delete_code, ""
+
+
+@pytest.fixture
+def fake(tmp_path: Path, monkeypatch) -> FakeVss:
+    for volume in ("c", "d"):
+        (tmp_path / volume / "data").mkdir(parents=True)
+        (tmp_path / volume / "data" / "f").write_bytes(b"live")
+    fake = FakeVss(tmp_path)
+    monkeypatch.setattr(vss, "sys", SimpleNamespace(platform="win32"))
+    monkeypatch.setattr(vss, "_volume_root", fake.volume_root)
+    monkeypatch.setattr(vss, "_powershell", fake.powershell)
+    return fake
+
+
+class TestShadowCopy:
+    def test_one_copy_per_volume(self, fake: FakeVss, tmp_path: Path):
+        c, d = tmp_path / "c", tmp_path / "d"
+        with shadow_copy(str(c / "data"), str(c / "data" / "f"), str(d)) as paths:
+            (c / "data" / "f").write_bytes(b"changed")
+            assert paths == (str(tmp_path / "shadow1" / "data"),
+                             str(tmp_path / "shadow1" / "data" / "f"),
+                             str(tmp_path / "shadow2") + "/")
+            assert Path(paths[1]).read_bytes() == b"live"
+            assert fake.deleted == []
+        assert fake.created == [f"{c}/", f"{d}/"]
+        assert fake.deleted == ["{ID1}", "{ID2}"]
+
+    def test_deleted_after_an_error(self, fake: FakeVss, tmp_path: Path):
+        with pytest.raises(KeyError), shadow_copy(str(tmp_path / "c")):
+            raise KeyError
+        assert fake.deleted == ["{ID1}"]
+        fake.delete_code = 1
+        with pytest.raises(SourceReadError, match="vssadmin delete shadows"), \
+                shadow_copy(str(tmp_path / "c")):
+            pass
+
+    def test_errors(self, fake: FakeVss, tmp_path: Path, monkeypatch):
+        fake.create_code = 1
+        with pytest.raises(SourcePermissionError, match="elevated"):
+            with shadow_copy(str(tmp_path / "c")):
+                pass
+        fake.create_code = 6
+        with pytest.raises(SourceReadError, match="not enough shadow storage"):
+            with shadow_copy(str(tmp_path / "c")):
+                pass
+        with pytest.raises(ValueError, match="no paths"):
+            with shadow_copy():
+                pass
+        monkeypatch.setattr(vss, "sys", SimpleNamespace(platform="linux"))
+        with pytest.raises(UnsupportedSourceError, match="need Windows"):
+            with shadow_copy(str(tmp_path / "c")):
+                pass
+
+
+class TestCli:
+    def test_compares_the_copies(self, fake: FakeVss, tmp_path: Path, capsys):
+        shutil.copytree(tmp_path / "c" / "data", tmp_path / "c" / "copy")
+        left, right = str(tmp_path / "c" / "data"), str(tmp_path / "c" / "copy")
+        assert main(["compare", "--vss", left, right]) == 0
+        fake.freeze = lambda shadow: (shadow / "copy" / "f").write_bytes(b"then")
+        assert main(["compare", "--vss", left, right]) == 1
+        assert "f" in capsys.readouterr().out
+        assert main(["compare", left, right]) == 0
+        assert fake.deleted == ["{ID1}", "{ID2}"]
+
+    def test_refused(self, fake: FakeVss, tmp_path: Path, capsys):
+        c = str(tmp_path / "c")
+        assert main(["compare", "--vss", "--pair", c, c]) == 2
+        assert "--vss cannot be combined with --pair" in capsys.readouterr().err
+        assert main(["compare", "--vss", c, "https://example.com/f"]) == 2
+        assert "local files and directories" in capsys.readouterr().err
+        assert main(["compare", "--vss", "--forensic", c, c]) == 2
+        assert "--vss cannot be combined with --forensic" in capsys.readouterr().err
+        assert fake.created == []
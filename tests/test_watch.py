@@ -0,0 +1,134 @@
+"""Tests for watch_dir and komparu watch: live drift between two trees."""
+
+from __future__ import annotations
+
+import contextlib
+import shutil
+import sys
+import threading
+from collections.abc import Iterator
+from pathlib import Path
+
+import pytest
+
+import komparu._cli as cli
+from komparu import (
+    CancelToken,
+    DiffEntry,
+    DiffReason,
+    DriftEvent,
+    DriftKind,
+    EntryKind,
+    SourceNotFoundError,
+    watch_dir,
+)
+from komparu._cli import main
+
+
+@pytest.fixture
+def cancel() -> Iterator[CancelToken]:
+    """Ends a watch that hangs waiting for an event that never comes."""
+    token = CancelToken()
+    timer = threading.Timer(10, token.cancel)
+    timer.start()
+    yield token
+    timer.cancel()
+
+
+def _summary(event: DriftEvent) -> tuple[str, str, str]:
+    return event.kind.value, event.entry.path, event.entry.kind.value
+
+
+def _check_drift(events: Iterator[DriftEvent], a: Path, b: Path) -> None:
+    assert _summary(next(events)) == ("appeared", "diff.txt", "diff")
+    (b / "diff.txt").write_bytes(b"one")
+    assert _summary(next(events)) == ("resolved", "diff.txt", "diff")
+    (a / "new" / "deeper").mkdir(parents=True)
+    (a / "new" / "deeper" / "f.txt").write_bytes(b"f")
+    assert _summary(next(events)) == ("appeared", "new/deeper/f.txt", "only_left")
+    (b / "sub" / "deep.txt").write_bytes(b"DEEP")
+    assert _summary(next(events)) == ("appeared", "sub/deep.txt", "diff")
+    shutil.rmtree(a / "new")
+    assert _summary(next(events)) == ("resolved", "new/deeper/f.txt", "only_left")
+
+
+class TestWatchDir:
+    @pytest.mark.skipif(sys.platform != "linux", reason="inotify")
+    def test_notifications(self, trees, cancel):
+        with contextlib.closing(watch_dir(*trees, settle=0.05, cancel=cancel)) as events:
+            _check_drift(events, *trees)
+
+    def test_poll(self, trees, cancel):
+        with contextlib.closing(watch_dir(*trees, settle=0.05, poll=0.05,
+                                           cancel=cancel)) as events:
+            _check_drift(events, *trees)
+
+    def test_reason_changes(self, trees, cancel):
+        a, b = trees
+        events = watch_dir(a, b, settle=0.05, cancel=cancel)
+        assert next(events).entry.reason is DiffReason.CONTENT_MISMATCH
+        (b / "diff.txt").write_bytes(b"longer")
+        assert next(events) == DriftEvent(
+            DriftKind.APPEARED, DiffEntry("diff.txt", EntryKind.DIFF, DiffReason.SIZE_MISMATCH))
+        events.close()
+
+    def test_ignore(self, trees, cancel):
+        a, b = trees
+        events = watch_dir(a, b, ignore=["*.log", "cache"], settle=0.05, cancel=cancel)
+        next(events)
+        (a / "build.log").write_bytes(b"log")
+        (a / "cache").mkdir()
+        (a / "cache" / "blob").write_bytes(b"blob")
+        (a / "kept.txt").write_bytes(b"kept")
+        assert _summary(next(events)) == ("appeared", "kept.txt", "only_left")
+        events.close()
+
+    def test_cancel(self, trees):
+        token = CancelToken()
+        events = watch_dir(*trees, cancel=token)
+        next(events)
+        token.cancel()
+        assert list(events) == []
+
+    def test_bad_arguments(self, trees, tmp_path):
+        for kwargs, error in [
+            ({"settle": -1}, ValueError),
+            ({"poll": 0}, ValueError),
+        ]:
+            with pytest.raises(error):
+                next(watch_dir(*trees, **kwargs))
+        with pytest.raises(SourceNotFoundError):
+            next(watch_dir(trees[0], tmp_path / "missing"))
+
+
+class TestCli:
+    def test_prints_drift(self, tmp_path, monkeypatch, capsys):
+        def fake_watch(left, right, **kwargs):
+            assert kwargs["ignore"] == ["*.tmp"] and kwargs["poll"] == 2.0
+            yield DriftEvent(DriftKind.APPEARED,
+                             DiffEntry("x", EntryKind.DIFF, DiffReason.CONTENT_MISMATCH))
+            yield DriftEvent(DriftKind.APPEARED,
+                             DiffEntry("y", EntryKind.ONLY_RIGHT, DiffReason.MISSING))
+            yield DriftEvent(DriftKind.RESOLVED,
+                             DiffEntry("x", EntryKind.DIFF, DiffReason.CONTENT_MISMATCH))
+            raise KeyboardInterrupt
+
+        monkeypatch.setattr(cli, "watch_dir", fake_watch)
+        assert main(["watch", "--ignore", "*.tmp", "--poll", "2", "a", "b"]) == 1
+        lines = [line.split(" ", 1)[1] for line in capsys.readouterr().out.splitlines()]
+        assert lines == ["+ differ: x (content_mismatch)", "+ only in right: y",
+                         "- differ: x (content_mismatch)"]
+
+    def test_resolved_at_exit(self, monkeypatch):
+        def fake_watch(left, right, **kwargs):
+            entry = DiffEntry("x", EntryKind.ONLY_LEFT, DiffReason.MISSING)
+            yield DriftEvent(DriftKind.APPEARED, entry)
+            yield DriftEvent(DriftKind.RESOLVED, entry)
+            raise KeyboardInterrupt
+
+        monkeypatch.setattr(cli, "watch_dir", fake_watch)
+        assert main(["watch", "a", "b"]) == 0
+
+    def test_missing_directory(self, trees, tmp_path, capsys):
+        assert main(["watch", str(trees[0]), str(tmp_path / "missing")]) == 2
+        assert "No such file" in capsys.readouterr().err